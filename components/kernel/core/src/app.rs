@@ -16,6 +16,9 @@
 //! ## Modules
 //!
 //! - [`cursor`]: Cursor management and display utilities for applications
+//! - [`executor`]: Async executor running futures as kernel tasks
+//! - [`log_viewer`]: Kernel log viewer with scrollback and follow mode
+//! - [`shell`]: Debug shell running commands and boot scripts
 //!
 //! ## Usage
 //!
@@ -35,3 +38,21 @@
 /// This module provides functionality for managing application cursors,
 /// including position tracking, visibility control, and cursor rendering.
 pub mod cursor;
+
+/// Async executor
+///
+/// This module polls futures as kernel tasks, woken from interrupt handlers
+/// and timer ticks, so drivers can expose async APIs.
+pub mod executor;
+
+/// Kernel log viewer
+///
+/// This module pages through kernel log records with scrollback, level based
+/// styling and a follow mode tracking the newest record.
+pub mod log_viewer;
+
+/// Debug shell
+///
+/// This module runs command lines on the shell commands of kernel subsystems
+/// and runs the `autoexec.osh` boot script.
+pub mod shell;
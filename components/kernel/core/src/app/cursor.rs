@@ -1,7 +1,9 @@
 use crate::base::graphic::FRAME_BUFFER;
-use crate::base::graphic::position::Coord;
 use crate::base::graphic::position::Coordinal;
 use oso_error::Rslt;
+use oso_no_std_shared::geometry::Point;
+use oso_no_std_shared::geometry::Rect;
+use oso_no_std_shared::geometry::Size;
 
 // TODO: modularize project structure to remove pub keyword
 const MOUSE_CURSOR_WIDTH: usize = 15;
@@ -40,18 +42,32 @@ pub trait MouseCursorDraw {
 
 /// belong to `gui` struct
 pub struct CursorBuf {
-	pos:    Coord,
+	pos:    Point,
 	width:  usize,
 	height: usize,
 }
 
 impl CursorBuf {
 	pub fn new() -> Self {
-		let mut pos = FRAME_BUFFER.right_bottom();
-		*pos.x_mut() = pos.x() / 2;
-		*pos.y_mut() = pos.y() / 2;
+		let size = FRAME_BUFFER.resolution();
+		let pos = Point::new(size.width / 2, size.height / 2,);
 		Self { pos, width: MOUSE_CURSOR_WIDTH, height: MOUSE_CURSOR_HEIGHT, }
 	}
+
+	/// Pixels covered by the cursor, to be redrawn when it moves
+	pub fn area(&self,) -> Rect {
+		Rect::new(self.pos, Size::new(self.width, self.height,),)
+	}
+
+	/// Moves the cursor by `(dx, dy)`, keeping its tip on the screen
+	///
+	/// Returns the area to redraw: the union of the old and the new area.
+	pub fn move_by(&mut self, dx: isize, dy: isize,) -> Rect {
+		let old = self.area();
+		let screen = FRAME_BUFFER.resolution();
+		self.pos = self.pos.offset(dx, dy,).clamp_to(screen,);
+		old.union(&self.area(),)
+	}
 }
 
 impl Default for CursorBuf {
@@ -62,7 +78,7 @@ impl Default for CursorBuf {
 
 impl MouseCursorDraw for CursorBuf {
 	fn draw_mouse_cursor(&mut self,) -> Rslt<(),> {
-		let mut coord = self.pos.clone();
+		let mut coord = self.pos;
 		(0..self.height).for_each(|y| {
 			for x in 0..self.width {
 				match MOUSE_CURSOR[y][x] {
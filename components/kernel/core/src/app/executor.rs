@@ -0,0 +1,522 @@
+//! # Async Executor
+//!
+//! Runs [`Future`]s as kernel tasks, so drivers can offer `async` reads and
+//! writes and protocol code can await events instead of keeping hand-written
+//! state machines.
+//!
+//! ## Tasks
+//!
+//! [`Executor::spawn`] takes a pinned future borrowed for the lifetime of
+//! the executor, so tasks need no allocator and may live on the stack of
+//! the caller. A task is polled when its waker was woken, and dropped when
+//! it completes.
+//!
+//! ## Wakers
+//!
+//! A waker only holds the index of its task and sets the bit of the task in
+//! a global ready mask, so waking is lock free and safe from interrupt
+//! handlers. Hence only one [`Executor`] exists at a time.
+//!
+//! - [`Signal`]: Event set by an interrupt handler and awaited by a task
+//! - [`sleep`]: Completes after a number of timer ticks, counted by [`tick`]
+//!
+//! ## Current Status
+//!
+//! The kernel has no interrupt controller driver, timer tick or timer wheel
+//! yet. The owner of the executor, such as a scheduler task, calls
+//! [`Executor::run`], which idles while no task is ready. Deadlines of
+//! [`sleep`] are kept in a table of [`MAX_TIMERS`] entries which [`tick`]
+//! scans.
+//!
+//! ```rust,ignore
+//! static RX: Signal = Signal::new();
+//!
+//! let mut rx = pin!(async {
+//! 	loop {
+//! 		RX.wait().await;
+//! 		uart.drain();
+//! 	}
+//! });
+//! let mut blink = pin!(async {
+//! 	loop {
+//! 		led.toggle();
+//! 		sleep(500,).await;
+//! 	}
+//! });
+//! // tasks outlive the executor
+//! let mut executor = Executor::new()?;
+//! executor.spawn(rx.as_mut(),)?;
+//! executor.spawn(blink.as_mut(),)?;
+//! executor.run();
+//! ```
+
+use crate::base::perf::idle;
+use crate::base::perf::record;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+use core::task::Context;
+use core::task::Poll;
+use core::task::RawWaker;
+use core::task::RawWakerVTable;
+use core::task::Waker;
+use oso_error::Rslt;
+use oso_error::kernel::ExecutorError;
+use oso_error::oso_err;
+
+/// Tasks an [`Executor`] has room for, one per bit of the ready mask
+pub const MAX_TASKS: usize = 64;
+/// Pending [`sleep`]s. Further sleeps poll until an entry is free
+pub const MAX_TIMERS: usize = 32;
+
+/// bit `i` is set when task `i` was woken
+static READY: AtomicU64 = AtomicU64::new(0,);
+static EXISTS: AtomicBool = AtomicBool::new(false,);
+static TICKS: AtomicU64 = AtomicU64::new(0,);
+static TIMERS: [Timer; MAX_TIMERS] = [const { Timer::new() }; MAX_TIMERS];
+
+static VTABLE: RawWakerVTable =
+	RawWakerVTable::new(clone_waker, wake_task, wake_task, drop_waker,);
+
+/// Runs futures until they complete
+pub struct Executor<'a,> {
+	tasks: [Option<Pin<&'a mut dyn Future<Output = (),>,>,>; MAX_TASKS],
+}
+
+impl<'a,> Executor<'a,> {
+	/// # Errors
+	///
+	/// [`ExecutorError::AlreadyExists`] if another executor has not been
+	/// dropped
+	pub fn new() -> Rslt<Self, ExecutorError,> {
+		if EXISTS.swap(true, Ordering::AcqRel,) {
+			return Err(oso_err!(ExecutorError::AlreadyExists),);
+		}
+		READY.store(0, Ordering::Release,);
+		Ok(Self { tasks: [const { None }; MAX_TASKS], },)
+	}
+
+	/// Adds `task`, which is polled on the next run. Returns its index
+	///
+	/// # Errors
+	///
+	/// [`ExecutorError::TooManyTasks`] if [`MAX_TASKS`] tasks are running
+	pub fn spawn(
+		&mut self,
+		task: Pin<&'a mut dyn Future<Output = (),>,>,
+	) -> Rslt<usize, ExecutorError,> {
+		let Some(index,) = self.tasks.iter().position(Option::is_none,) else {
+			return Err(oso_err!(ExecutorError::TooManyTasks {
+				capacity: MAX_TASKS,
+			}),);
+		};
+		self.tasks[index] = Some(task,);
+		wake(index,);
+		Ok(index,)
+	}
+
+	/// Number of tasks which have not completed
+	pub fn len(&self,) -> usize {
+		self.tasks.iter().flatten().count()
+	}
+
+	pub fn is_empty(&self,) -> bool {
+		self.len() == 0
+	}
+
+	/// Polls woken tasks until none is ready. Returns the number of tasks
+	/// which have not completed
+	pub fn run_until_idle(&mut self,) -> usize {
+		loop {
+			let mut ready = READY.swap(0, Ordering::AcqRel,);
+			if ready == 0 {
+				return self.len();
+			}
+			while ready != 0 {
+				let index = ready.trailing_zeros() as usize;
+				ready &= ready - 1;
+				self.poll(index,);
+			}
+		}
+	}
+
+	/// Runs until every task has completed, waiting for an interrupt while
+	/// no task is ready
+	pub fn run(&mut self,) {
+		while self.run_until_idle() != 0 {
+			idle::wait(|| READY.load(Ordering::Acquire,) != 0,);
+		}
+	}
+
+	fn poll(&mut self, index: usize,) {
+		let Some(task,) = &mut self.tasks[index] else {
+			// woken after it completed
+			return;
+		};
+		let waker = unsafe { Waker::new(index as *const (), &VTABLE,) };
+		let mut cx = Context::from_waker(&waker,);
+		if task.as_mut().poll(&mut cx,).is_ready() {
+			self.tasks[index] = None;
+		}
+	}
+}
+
+impl Drop for Executor<'_,> {
+	fn drop(&mut self,) {
+		// drop tasks while wakers still refer to this executor
+		self.tasks = [const { None }; MAX_TASKS];
+		EXISTS.store(false, Ordering::Release,);
+	}
+}
+
+/// Event signaled from an interrupt handler and awaited by one task
+///
+/// Signals before a task waits are kept, so the task does not miss an
+/// interrupt which arrived while it was busy. Several signals before a wait
+/// count as one.
+pub struct Signal {
+	fired:  AtomicBool,
+	/// index of the waiting task plus one, `0` if none
+	waiter: AtomicUsize,
+}
+
+impl Signal {
+	pub const fn new() -> Self {
+		Self { fired: AtomicBool::new(false,), waiter: AtomicUsize::new(0,), }
+	}
+
+	/// Sets the event and wakes the waiting task. Safe in interrupt handlers
+	pub fn signal(&self,) {
+		self.fired.store(true, Ordering::Release,);
+		match self.waiter.swap(0, Ordering::AcqRel,) {
+			0 => {},
+			waiter => wake(waiter - 1,),
+		}
+	}
+
+	/// Completes once the event is set, and clears it
+	pub fn wait(&self,) -> Wait<'_,> {
+		Wait { signal: self, }
+	}
+}
+
+impl Default for Signal {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Future returned by [`Signal::wait`]
+pub struct Wait<'a,> {
+	signal: &'a Signal,
+}
+
+impl Future for Wait<'_,> {
+	type Output = ();
+
+	fn poll(
+		self: Pin<&mut Self,>,
+		cx: &mut Context<'_,>,
+	) -> Poll<Self::Output,> {
+		let signal = self.signal;
+		if signal.fired.swap(false, Ordering::AcqRel,) {
+			return Poll::Ready((),);
+		}
+		match task_index(cx.waker(),) {
+			Some(index,) => signal.waiter.store(index + 1, Ordering::Release,),
+			// a waker of another executor can not be stored, so poll again
+			None => cx.waker().wake_by_ref(),
+		}
+		// the signal may have fired before the waiter was stored
+		if signal.fired.swap(false, Ordering::AcqRel,) {
+			signal.waiter.store(0, Ordering::Release,);
+			return Poll::Ready((),);
+		}
+		Poll::Pending
+	}
+}
+
+/// Completes `ticks` timer ticks from now
+pub fn sleep(ticks: u64,) -> Sleep {
+	Sleep { deadline: now().saturating_add(ticks,), timer: None, }
+}
+
+/// Future returned by [`sleep`]
+pub struct Sleep {
+	deadline: u64,
+	/// entry of `TIMERS` holding the deadline
+	timer:    Option<usize,>,
+}
+
+impl Future for Sleep {
+	type Output = ();
+
+	fn poll(
+		mut self: Pin<&mut Self,>,
+		cx: &mut Context<'_,>,
+	) -> Poll<Self::Output,> {
+		if now() >= self.deadline {
+			self.release();
+			return Poll::Ready((),);
+		}
+		if self.timer.is_some() {
+			return Poll::Pending;
+		}
+		let timer = task_index(cx.waker(),)
+			.and_then(|task| Timer::claim(self.deadline, task,),);
+		match timer {
+			Some(timer,) => self.timer = Some(timer,),
+			// no free entry, or a waker of another executor
+			None => cx.waker().wake_by_ref(),
+		}
+		Poll::Pending
+	}
+}
+
+impl Sleep {
+	fn release(&mut self,) {
+		if let Some(timer,) = self.timer.take() {
+			let deadline = &TIMERS[timer].deadline;
+			deadline.store(Timer::FREE, Ordering::Release,);
+		}
+	}
+}
+
+impl Drop for Sleep {
+	fn drop(&mut self,) {
+		self.release();
+	}
+}
+
+/// Ticks counted by [`tick`]
+pub fn now() -> u64 {
+	TICKS.load(Ordering::Acquire,)
+}
+
+/// Advances the clock of [`sleep`] and wakes tasks whose deadline passed.
+/// Called from the timer interrupt. The tick is recorded by [`record`]
+pub fn tick() {
+	record::tick();
+	let now = TICKS.fetch_add(1, Ordering::AcqRel,) + 1;
+	for timer in &TIMERS {
+		let deadline = timer.deadline.load(Ordering::Acquire,);
+		if deadline <= now {
+			// the entry stays claimed until its `Sleep` completes
+			timer.deadline.store(Timer::CLAIMED, Ordering::Release,);
+			wake(timer.task.load(Ordering::Acquire,),);
+		}
+	}
+}
+
+struct Timer {
+	/// tick to wake `task` at, or one of the markers
+	deadline: AtomicU64,
+	task:     AtomicUsize,
+}
+
+impl Timer {
+	/// in use without a deadline, while it is claimed or after it expired
+	const CLAIMED: u64 = u64::MAX - 1;
+	const FREE: u64 = u64::MAX;
+
+	const fn new() -> Self {
+		Self {
+			deadline: AtomicU64::new(Self::FREE,),
+			task:     AtomicUsize::new(0,),
+		}
+	}
+
+	/// Index of a free entry now waking `task` at `deadline`
+	fn claim(deadline: u64, task: usize,) -> Option<usize,> {
+		// markers are never reached as deadlines
+		let deadline = deadline.min(Self::CLAIMED - 1,);
+		TIMERS.iter().position(|timer| {
+			let claimed = timer.deadline.compare_exchange(
+				Self::FREE,
+				Self::CLAIMED,
+				Ordering::AcqRel,
+				Ordering::Acquire,
+			);
+			if claimed.is_err() {
+				return false;
+			}
+			timer.task.store(task, Ordering::Release,);
+			timer.deadline.store(deadline, Ordering::Release,);
+			true
+		},)
+	}
+}
+
+/// Index of the task of `waker` if it belongs to the [`Executor`]
+fn task_index(waker: &Waker,) -> Option<usize,> {
+	(waker.vtable() == &VTABLE).then(|| waker.data() as usize,)
+}
+
+fn wake(index: usize,) {
+	READY.fetch_or(1 << index, Ordering::AcqRel,);
+}
+
+unsafe fn clone_waker(data: *const (),) -> RawWaker {
+	RawWaker::new(data, &VTABLE,)
+}
+
+unsafe fn wake_task(data: *const (),) {
+	wake(data as usize,);
+}
+
+unsafe fn drop_waker(_data: *const (),) {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	extern crate std;
+	use core::cell::Cell;
+	use core::future::Pending;
+	use core::pin::pin;
+	use std::sync::Mutex;
+	use std::sync::MutexGuard;
+
+	/// wakers, ticks and timers are global, so tests run one at a time
+	static LOCK: Mutex<(),> = Mutex::new((),);
+
+	fn lock() -> MutexGuard<'static, (),> {
+		LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner(),)
+	}
+
+	fn free_timers() -> usize {
+		let free = |timer: &&Timer| {
+			timer.deadline.load(Ordering::Acquire,) == Timer::FREE
+		};
+		TIMERS.iter().filter(free,).count()
+	}
+
+	#[test]
+	fn test_one_executor_at_a_time() {
+		let _lock = lock();
+		let executor = Executor::new().unwrap();
+		let error = Executor::new().err().and_then(|e| e.desc,);
+		assert_eq!(error, Some(ExecutorError::AlreadyExists));
+		drop(executor,);
+		assert!(Executor::new().is_ok());
+	}
+
+	#[test]
+	fn test_spawn_and_complete() {
+		let _lock = lock();
+		let polls = Cell::new(0,);
+		let mut done = pin!(async { polls.set(polls.get() + 1,) });
+		let mut pending = pin!(async {
+			polls.set(polls.get() + 1,);
+			core::future::pending::<(),>().await
+		});
+		let mut again = pin!(async {});
+		let mut executor = Executor::new().unwrap();
+		assert_eq!(executor.spawn(done.as_mut(),).unwrap(), 0);
+		assert_eq!(executor.spawn(pending.as_mut(),).unwrap(), 1);
+		assert_eq!(executor.len(), 2);
+		assert_eq!(executor.run_until_idle(), 1);
+		assert_eq!(polls.get(), 2);
+		// nothing is ready until a waker is woken
+		assert_eq!(executor.run_until_idle(), 1);
+		assert_eq!(polls.get(), 2);
+		// a completed task frees its slot
+		assert_eq!(executor.spawn(again.as_mut(),).unwrap(), 0);
+		assert_eq!(executor.run_until_idle(), 1);
+	}
+
+	#[test]
+	fn test_too_many_tasks() {
+		let _lock = lock();
+		let mut tasks: [Pending<(),>; MAX_TASKS + 1] =
+			core::array::from_fn(|_| core::future::pending(),);
+		let mut executor = Executor::new().unwrap();
+		let (last, tasks,) = tasks.split_last_mut().unwrap();
+		for task in tasks {
+			executor.spawn(Pin::<&mut Pending<(),>,>::new(task,),).unwrap();
+		}
+		let last = Pin::<&mut Pending<(),>,>::new(last,);
+		let error = executor.spawn(last,).err();
+		let error = error.and_then(|e| e.desc,);
+		let too_many = ExecutorError::TooManyTasks { capacity: MAX_TASKS, };
+		assert_eq!(error, Some(too_many));
+		assert_eq!(executor.run_until_idle(), MAX_TASKS);
+	}
+
+	#[test]
+	fn test_signal() {
+		let _lock = lock();
+		let signal = Signal::new();
+		let received = Cell::new(0,);
+		let mut task = pin!(async {
+			loop {
+				signal.wait().await;
+				received.set(received.get() + 1,);
+			}
+		});
+		let mut executor = Executor::new().unwrap();
+		executor.spawn(task.as_mut(),).unwrap();
+		executor.run_until_idle();
+		assert_eq!(received.get(), 0);
+
+		signal.signal();
+		executor.run_until_idle();
+		assert_eq!(received.get(), 1);
+		// signals while the task is not waiting count as one
+		signal.signal();
+		signal.signal();
+		executor.run_until_idle();
+		executor.run_until_idle();
+		assert_eq!(received.get(), 2);
+	}
+
+	#[test]
+	fn test_signal_with_a_foreign_waker() {
+		let _lock = lock();
+		let signal = Signal::new();
+		let mut wait = pin!(signal.wait());
+		let mut cx = Context::from_waker(Waker::noop(),);
+		assert!(wait.as_mut().poll(&mut cx,).is_pending());
+		assert_eq!(signal.waiter.load(Ordering::Acquire,), 0);
+		signal.signal();
+		assert!(wait.as_mut().poll(&mut cx,).is_ready());
+	}
+
+	#[test]
+	fn test_sleep() {
+		let _lock = lock();
+		let woken = Cell::new(None,);
+		let mut task = pin!(async {
+			sleep(3,).await;
+			woken.set(Some(now(),),);
+		});
+		let start = now();
+		let mut executor = Executor::new().unwrap();
+		executor.spawn(task.as_mut(),).unwrap();
+		executor.run_until_idle();
+		assert_eq!(free_timers(), MAX_TIMERS - 1);
+		for _ in 0..2 {
+			tick();
+			assert_eq!(executor.run_until_idle(), 1);
+		}
+		tick();
+		assert_eq!(executor.run_until_idle(), 0);
+		assert_eq!(woken.get(), Some(start + 3));
+		assert_eq!(free_timers(), MAX_TIMERS);
+	}
+
+	#[test]
+	fn test_dropped_sleep_frees_its_timer() {
+		let _lock = lock();
+		{
+			let mut sleeping = pin!(sleep(10,));
+			let mut executor = Executor::new().unwrap();
+			executor.spawn(sleeping.as_mut(),).unwrap();
+			executor.run_until_idle();
+			assert_eq!(free_timers(), MAX_TIMERS - 1);
+		}
+		assert_eq!(free_timers(), MAX_TIMERS);
+	}
+}
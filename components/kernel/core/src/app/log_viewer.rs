@@ -0,0 +1,209 @@
+//! # Log Viewer
+//!
+//! Shows kernel log records page by page with scrollback and a follow mode
+//! which keeps the newest record on screen.
+//!
+//! ## Keys
+//!
+//! - `PageUp` / `PageDown`: Scroll by a page. Scrolling up leaves follow mode
+//! - `Up` / `Down`: Scroll by a line
+//! - `End`: Jump to the newest record and enter follow mode
+//!
+//! ## Current Status
+//!
+//! The kernel has no IPC channel nor input subsystem yet, so the viewer is a
+//! plain state machine. Its owner feeds keys to [`LogViewer::handle_key`],
+//! reports new records with [`LogViewer::on_append`] and redraws with
+//! [`LogViewer::draw`]. Records are read through [`LogSource`] so the viewer
+//! works with whatever log ring the kernel ends up with.
+//!
+//! ```rust,ignore
+//! let mut viewer = LogViewer::new(rows,);
+//! viewer.on_append(ring.len(),);
+//! if viewer.handle_key(ViewerKey::PageUp, ring.len(),) {
+//! 	viewer.draw(&ring, &mut console,)?;
+//! }
+//! ```
+
+use core::fmt;
+use oso_no_std_shared::color::Color;
+use oso_no_std_shared::color::Style;
+use oso_no_std_shared::parser::markdown::StyledWrite;
+
+/// Severity of a log record
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord,)]
+pub enum LogLevel {
+	Error,
+	Warn,
+	Info,
+	Debug,
+	Trace,
+}
+
+impl LogLevel {
+	/// Every level, from the most severe
+	pub const ALL: [Self; 5] =
+		[Self::Error, Self::Warn, Self::Info, Self::Debug, Self::Trace,];
+
+	/// Level named `name` in lower case, as on the kernel command line and
+	/// in the `log_level` setting
+	pub fn parse(name: &str,) -> Option<Self,> {
+		Self::ALL.into_iter().find(|level| level.name() == name,)
+	}
+
+	/// Lower case name of the level
+	pub const fn name(&self,) -> &'static str {
+		match self {
+			Self::Error => "error",
+			Self::Warn => "warn",
+			Self::Info => "info",
+			Self::Debug => "debug",
+			Self::Trace => "trace",
+		}
+	}
+
+	/// Label written in front of the message
+	pub const fn label(&self,) -> &'static str {
+		match self {
+			Self::Error => "ERROR",
+			Self::Warn => "WARN ",
+			Self::Info => "INFO ",
+			Self::Debug => "DEBUG",
+			Self::Trace => "TRACE",
+		}
+	}
+
+	/// Style of the label. Errors and warnings stand out in bold red and
+	/// yellow, debug output is dimmed
+	pub const fn style(&self,) -> Style {
+		match self {
+			Self::Error => Style::new(Color::RED, Color::BLACK,).bold(),
+			Self::Warn => Style::new(Color::BROWN, Color::BLACK,).bold(),
+			Self::Info => Style::PLAIN,
+			Self::Debug | Self::Trace => {
+				Style::new(Color::DARK_GRAY, Color::BLACK,)
+			},
+		}
+	}
+}
+
+/// Single log record
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct LogRecord<'a,> {
+	pub level:   LogLevel,
+	pub message: &'a str,
+}
+
+/// Records shown by [`LogViewer`], oldest first
+pub trait LogSource {
+	fn len(&self,) -> usize;
+	fn get(&self, index: usize,) -> Option<LogRecord<'_,>,>;
+	fn is_empty(&self,) -> bool {
+		self.len() == 0
+	}
+}
+
+impl LogSource for [LogRecord<'_,>] {
+	fn len(&self,) -> usize {
+		<[LogRecord]>::len(self,)
+	}
+
+	fn get(&self, index: usize,) -> Option<LogRecord<'_,>,> {
+		<[LogRecord]>::get(self, index,).copied()
+	}
+}
+
+/// Keys handled by [`LogViewer`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum ViewerKey {
+	PageUp,
+	PageDown,
+	Up,
+	Down,
+	End,
+}
+
+/// Scroll state of the log viewer
+///
+/// # Fields
+///
+/// * `top` - Index of the first record on screen
+/// * `rows` - Number of records on a page
+/// * `follow` - Whether the view sticks to the newest record
+#[derive(Debug, Clone, PartialEq, Eq,)]
+pub struct LogViewer {
+	top:    usize,
+	rows:   usize,
+	follow: bool,
+}
+
+impl LogViewer {
+	/// Viewer showing `rows` records per page, in follow mode
+	pub const fn new(rows: usize,) -> Self {
+		Self { top: 0, rows, follow: true, }
+	}
+
+	pub fn top(&self,) -> usize {
+		self.top
+	}
+
+	pub fn is_following(&self,) -> bool {
+		self.follow
+	}
+
+	/// Updates the view after the source grew to `len` records. Returns
+	/// whether the view needs to be redrawn
+	pub fn on_append(&mut self, len: usize,) -> bool {
+		if !self.follow {
+			return false;
+		}
+		let top = self.last_top(len,);
+		let moved = top != self.top;
+		self.top = top;
+		moved || len <= self.rows
+	}
+
+	/// Applies `key` to a source of `len` records. Returns whether the view
+	/// needs to be redrawn
+	pub fn handle_key(&mut self, key: ViewerKey, len: usize,) -> bool {
+		let last = self.last_top(len,);
+		let (top, follow,) = match key {
+			ViewerKey::PageUp => (self.top.saturating_sub(self.rows,), false,),
+			ViewerKey::Up => (self.top.saturating_sub(1,), false,),
+			ViewerKey::PageDown => {
+				let top = (self.top + self.rows).min(last,);
+				(top, top == last,)
+			},
+			ViewerKey::Down => {
+				let top = (self.top + 1).min(last,);
+				(top, top == last,)
+			},
+			ViewerKey::End => (last, true,),
+		};
+		let changed = top != self.top || follow != self.follow;
+		self.top = top;
+		self.follow = follow;
+		changed
+	}
+
+	/// Writes the current page of `source` to `out`, one record per line
+	pub fn draw<S: LogSource + ?Sized,>(
+		&self,
+		source: &S,
+		out: &mut impl StyledWrite,
+	) -> fmt::Result {
+		let end = (self.top + self.rows).min(source.len(),);
+		for record in (self.top..end).filter_map(|i| source.get(i,),) {
+			out.set_style(record.level.style(),)?;
+			out.write_str(record.level.label(),)?;
+			out.set_style(Style::PLAIN,)?;
+			writeln!(out, " {}", record.message)?;
+		}
+		Ok((),)
+	}
+
+	/// Top of the last page
+	fn last_top(&self, len: usize,) -> usize {
+		len.saturating_sub(self.rows,)
+	}
+}
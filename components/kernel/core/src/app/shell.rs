@@ -0,0 +1,369 @@
+//! # Debug Shell
+//!
+//! Runs command lines on the shell commands of kernel subsystems, one at a
+//! time with [`execute`] or from a script with [`run_script`].
+//!
+//! ## Commands
+//!
+//! - `blitbench`: [`blit::run_command`]
+//! - `bootinfo`: [`handoff::run_command`]
+//! - `bootprof`: [`boot::run_command`]
+//! - `cpuinfo`: [`cpu::run_command`]
+//! - `dt`: [`dt::run_command`]
+//! - `env`: [`env::run_command`]
+//! - `get`, `set`: [`settings::run_command`]
+//! - `idle`: [`idle::run_command`]
+//! - `irq`: [`irq::run_command`]
+//! - `mmiofault`: [`fault::run_command`]
+//! - `record`: [`record::run_command`]
+//! - `screenshot`: [`capture::run_command`]
+//! - `spinbench`: [`spin::run_command`]
+//! - `tasks`: [`sched::run_command`]
+//! - `trace`: [`trace::run_command`]
+//! - `vt`: [`vt::run_command`]
+//! - `watchdog`: [`watchdog::run_command`]
+//! - `help`: Lists the commands
+//!
+//! ## Boot Script
+//!
+//! [`autoexec`] runs `autoexec.osh` at boot, so a scenario on hardware is
+//! reproduced without typing and boot tests drive the kernel the same way
+//! on every run. The script is the first of [`AUTOEXEC_PATHS`] which exists:
+//! the ESP mounted at `/boot`, then the initial ramdisk the loader reads
+//! from the `[kernel] initrd` path of `loader.cfg`. `autoexec=<path>` on the
+//! kernel command line runs another script, `autoexec=off` none.
+//!
+//! `xtask boot-test --autoexec` and `xtask test` pack their script into the
+//! initial ramdisk. With `test=exit`, a boot without a script or whose
+//! script fails exits QEMU with a failure, see [`test_passed`].
+//!
+//! Scripts are written in the syntax of
+//! [`oso_no_std_shared::shell::script`], with conditions on the entries of
+//! the boot environment:
+//!
+//! ```text
+//! # reproduce the scheduler stall on the board
+//! idle poll on
+//! if board.model == "Raspberry Pi 4 Model B"
+//! 	tasks
+//! end
+//! ```
+//!
+//! Each command is echoed as `osh:<line>> <command>` before it runs, so
+//! serial logs show which command printed what and boot test milestones can
+//! wait for a command. The script stops at the first command which is
+//! unknown or fails.
+//!
+//! ## Current Status
+//!
+//! No interactive shell reads the console yet. [`vfs::init`] mounts the
+//! initial ramdisk at boot, but nothing mounts the ESP, so [`autoexec`] only
+//! finds `/autoexec.osh`. No watchdog is installed at boot, so the `watchdog`
+//! command fails until a caller runs [`watchdog::install`].
+//!
+//! ```rust,ignore
+//! let mut out = EarlyConsole::new();
+//! shell::execute("idle stats", &mut out,)?;
+//! let ran = shell::autoexec(&mut out,)?.unwrap_or(0,);
+//! ```
+
+use crate::base::cpu;
+use crate::base::dt;
+use crate::base::graphic::blit;
+use crate::base::graphic::capture;
+use crate::base::env;
+use crate::base::handoff;
+use crate::base::perf::boot;
+use crate::base::perf::idle;
+use crate::base::perf::irq;
+use crate::base::perf::record;
+use crate::base::perf::trace;
+use crate::base::sched;
+use crate::base::settings;
+use crate::base::spin;
+use crate::base::vt;
+use crate::driver::mmio::fault;
+use crate::driver::watchdog;
+use crate::vfs;
+use crate::vfs::Read;
+use core::fmt;
+use oso_error::Rslt;
+use oso_error::kernel::ShellError;
+use oso_error::kernel::VfsError;
+use oso_error::oso_err;
+use oso_no_std_shared::shell::script;
+use oso_no_std_shared::text::utf8;
+
+/// Scripts [`autoexec`] looks for, in order
+pub const AUTOEXEC_PATHS: [&str; 2] =
+	["/boot/autoexec.osh", "/autoexec.osh",];
+/// Longest script [`autoexec`] reads, in bytes
+pub const MAX_SCRIPT: usize = 4096;
+/// Most words of a command line, including the command name
+pub const MAX_ARGS: usize = 16;
+/// Names of every command, e.g. for completion by the line editor
+pub const COMMANDS: [&str; 19] = [
+	blit::COMMAND,
+	handoff::COMMAND,
+	boot::COMMAND,
+	cpu::COMMAND,
+	dt::COMMAND,
+	env::COMMAND,
+	settings::COMMANDS[0],
+	"help",
+	idle::COMMAND,
+	irq::COMMAND,
+	fault::COMMAND,
+	record::COMMAND,
+	capture::COMMAND,
+	settings::COMMANDS[1],
+	spin::COMMAND,
+	sched::COMMAND,
+	trace::COMMAND,
+	vt::COMMAND,
+	watchdog::COMMAND,
+];
+
+/// Runs the command line `line`. An empty line does nothing
+///
+/// # Errors
+///
+/// - [`ShellError::UnknownCommand`] if no command has the first word as name
+/// - [`ShellError::CommandFailed`] if the command reported an error. The
+///   command describes it in `out`
+/// - [`ShellError::TooManyArgs`] if `line` has more than [`MAX_ARGS`] words
+pub fn execute(
+	line: &str,
+	out: &mut impl fmt::Write,
+) -> Rslt<(), ShellError,> {
+	let mut words = [""; MAX_ARGS];
+	let mut len = 0;
+	for word in line.split_whitespace() {
+		let Some(slot,) = words.get_mut(len,) else {
+			let capacity = MAX_ARGS;
+			return Err(oso_err!(ShellError::TooManyArgs { capacity }),);
+		};
+		*slot = word;
+		len += 1;
+	}
+	let Some((&name, args,),) = words[..len].split_first() else {
+		return Ok((),);
+	};
+
+	let ok = match name {
+		blit::COMMAND => blit::run_command(args, out,).is_ok(),
+		cpu::COMMAND => cpu::run_command(args, out,).is_ok(),
+		handoff::COMMAND => handoff::run_command(args, out,).is_ok(),
+		boot::COMMAND => boot::run_command(args, out,).is_ok(),
+		dt::COMMAND => dt::run_command(args, out,).is_ok(),
+		env::COMMAND => env::run_command(args, out,).is_ok(),
+		idle::COMMAND => idle::run_command(args, out,).is_ok(),
+		irq::COMMAND => irq::run_command(args, out,).is_ok(),
+		fault::COMMAND => fault::run_command(args, out,).is_ok(),
+		record::COMMAND => record::run_command(args, out,).is_ok(),
+		capture::COMMAND => capture::run_command(args, out,).is_ok(),
+		sched::COMMAND => sched::run_command(args, out,).is_ok(),
+		spin::COMMAND => spin::run_command(args, out,).is_ok(),
+		trace::COMMAND => trace::run_command(args, out,).is_ok(),
+		vt::COMMAND => vt::run_command(args, out,).is_ok(),
+		watchdog::COMMAND => watchdog::run_command(args, out,).is_ok(),
+		_ if settings::COMMANDS.contains(&name,) => {
+			settings::run_command(name, args, out,).is_ok()
+		},
+		"help" => {
+			for command in COMMANDS {
+				let _ = writeln!(out, "{command}");
+			}
+			true
+		},
+		_ => {
+			let _ = writeln!(out, "{name}: unknown command");
+			return Err(oso_err!(ShellError::UnknownCommand),);
+		},
+	};
+	if !ok {
+		return Err(oso_err!(ShellError::CommandFailed),);
+	}
+	Ok((),)
+}
+
+/// Runs the commands of the taken branches of the script `src`, and returns
+/// how many ran
+///
+/// # Errors
+///
+/// - [`ShellError::Script`] if the script is malformed. Commands before the
+///   malformed line have run
+/// - [`ShellError::Aborted`] with the line of the first command which is
+///   unknown or fails
+pub fn run_script(
+	src: &str,
+	out: &mut impl fmt::Write,
+) -> Rslt<usize, ShellError,> {
+	let mut ran = 0;
+	for command in script::commands(src, env::get,) {
+		let command =
+			command.map_err(|e| oso_err!(ShellError::Script(e)),)?;
+		let _ = writeln!(out, "osh:{}> {}", command.line, command.text);
+		if execute(command.text, out,).is_err() {
+			let line = command.line;
+			return Err(oso_err!(ShellError::Aborted { line }),);
+		}
+		ran += 1;
+	}
+	Ok(ran,)
+}
+
+/// Runs the boot script, and returns how many commands ran
+///
+/// Returns `Ok(None)` without a script or with `autoexec=off`. Called after
+/// [`env::init`] and [`vfs::init`].
+///
+/// # Errors
+///
+/// - [`ShellError::Vfs`] if the script can not be read, or the script given
+///   by `autoexec=<path>` does not exist
+/// - [`ShellError::TooLarge`] if the script is longer than [`MAX_SCRIPT`]
+/// - [`ShellError::NotUtf8`] if the script is not UTF-8
+/// - Errors of [`run_script`]
+pub fn autoexec(
+	out: &mut impl fmt::Write,
+) -> Rslt<Option<usize,>, ShellError,> {
+	let mut buf = [0; MAX_SCRIPT];
+	let len = match env::get("cmdline.autoexec",) {
+		Some("off",) => return Ok(None,),
+		Some(path,) => read(path, &mut buf,)?,
+		None => {
+			let mut found = None;
+			for path in AUTOEXEC_PATHS {
+				match read(path, &mut buf,) {
+					Ok(len,) => {
+						found = Some(len,);
+						break;
+					},
+					Err(e,) if e.desc == Some(NOT_FOUND,) => {},
+					Err(e,) => return Err(e,),
+				}
+			}
+			let Some(len,) = found else {
+				return Ok(None,);
+			};
+			len
+		},
+	};
+	let src = utf8::from_bytes(&buf[..len],)
+		.map_err(|_| oso_err!(ShellError::NotUtf8),)?;
+	run_script(src, out,).map(Some,)
+}
+
+/// Whether a boot test whose boot script ended with `result` passed
+///
+/// The script must exist and every command succeed. A boot test without a
+/// script fails, as it would pass without testing anything.
+pub fn test_passed(result: &Rslt<Option<usize,>, ShellError,>,) -> bool {
+	matches!(result, Ok(Some(_,),))
+}
+
+/// error of [`read`] for a missing file
+const NOT_FOUND: ShellError = ShellError::Vfs(VfsError::NotFound,);
+
+/// reads the whole file at `path` into `buf`
+fn read(path: &str, buf: &mut [u8],) -> Rslt<usize, ShellError,> {
+	let mut file = vfs::open(path,)?;
+	let len = file.read_full(buf,)?;
+	if len == buf.len() && file.read(&mut [0],)? != 0 {
+		let capacity = buf.len();
+		return Err(oso_err!(ShellError::TooLarge { capacity }),);
+	}
+	Ok(len,)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	extern crate std;
+	use crate::driver::block::tests::RamDisk;
+	use crate::vfs::cpio::Cpio;
+	use crate::vfs::fat32::Fat32;
+	use crate::vfs::fat32::tests::Volume;
+	use crate::vfs::fat32::tests::short;
+	use crate::vfs::tests::initrd;
+	use crate::vfs::tests::lock;
+	use core::cell::RefCell;
+	use std::boxed::Box;
+	use std::string::String;
+	use std::vec;
+
+	/// Mounts an initial ramdisk of `files` at `/`
+	fn mount_initrd(files: &[(&str, &[u8],)],) {
+		let cpio = Cpio::new(initrd(files,),).unwrap();
+		vfs::mount("/", Box::leak(Box::new(cpio,),),).unwrap();
+	}
+
+	fn autoexec() -> (Rslt<Option<usize,>, ShellError,>, String,) {
+		let mut out = String::new();
+		let result = super::autoexec(&mut out,);
+		(result, out,)
+	}
+
+	fn error(result: &Rslt<Option<usize,>, ShellError,>,) -> ShellError {
+		result.as_ref().unwrap_err().desc.unwrap()
+	}
+
+	#[test]
+	fn test_script_results() {
+		let _lock = lock();
+		mount_initrd(&[("autoexec.osh", b"# boot\nhelp\n",)],);
+		let (result, out,) = autoexec();
+		assert_eq!(result.as_ref().ok(), Some(&Some(1)));
+		assert!(out.starts_with("osh:2> help\nblitbench\n"));
+		assert!(test_passed(&result));
+
+		vfs::unmount("/",).unwrap();
+		mount_initrd(&[("autoexec.osh", b"help\nnope\nhelp\n",)],);
+		let (result, out,) = autoexec();
+		assert_eq!(error(&result), ShellError::Aborted { line: 2 });
+		assert!(out.ends_with("osh:2> nope\nnope: unknown command\n"));
+		assert!(!test_passed(&result));
+	}
+
+	#[test]
+	fn test_missing_scripts_fail_boot_tests() {
+		let _lock = lock();
+		let (result, out,) = autoexec();
+		assert_eq!(result.as_ref().ok(), Some(&None));
+		assert_eq!(out, "");
+		assert!(!test_passed(&result));
+
+		mount_initrd(&[("etc/autoexec.osh", b"help",)],);
+		let (result, _,) = autoexec();
+		assert_eq!(result.as_ref().ok(), Some(&None));
+		assert!(!test_passed(&result));
+	}
+
+	#[test]
+	fn test_unreadable_scripts_fail_boot_tests() {
+		let _lock = lock();
+		let large = vec![b'#'; MAX_SCRIPT + 1];
+		mount_initrd(&[("autoexec.osh", &large,)],);
+		let (result, _,) = autoexec();
+		let capacity = MAX_SCRIPT;
+		assert_eq!(error(&result), ShellError::TooLarge { capacity });
+		assert!(!test_passed(&result));
+
+		// the script on the ESP is found first, on a block which can not be
+		// read
+		let mut volume = Volume::new(1,);
+		let script = volume.chain(b"help\n", 1,);
+		let root = volume.dir(&[short(b"AUTOEXECOSH", 0x20, script, 5,)],);
+		let mut disk = RamDisk::new(volume.image(root,), 512,);
+		// reserved sectors and FATs come before the first cluster
+		disk.broken = Some(48,);
+		let fat = Fat32::new(Box::leak(Box::new(RefCell::new(disk,),),),);
+		vfs::mount("/boot", Box::leak(Box::new(fat.unwrap(),),),).unwrap();
+		let (result, out,) = autoexec();
+		assert!(matches!(error(&result), ShellError::Vfs(_)));
+		assert_eq!(out, "");
+		assert!(!test_passed(&result));
+	}
+}
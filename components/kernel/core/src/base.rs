@@ -20,9 +20,34 @@
 //!
 //! ## Modules
 //!
+//! - [`bringup`]: Milestones of secondary cores before they are online
+//! - [`cache`]: Data cache maintenance by address range
+//! - [`console_policy`]: Mirroring of the kernel log to several consoles
+//! - [`cpu`]: Features of the processor and the code paths using them
+//! - [`crash`]: Crash dumps written on panic
+//! - [`dt`]: Device tree handed over at boot
+//! - [`early_console`]: Paravirtual console used before real drivers
+//! - [`efi`]: UEFI runtime services callable after boot
+//! - [`env`]: Read-only boot environment
 //! - [`graphic`]: Graphics and display management functionality
+//! - [`handoff`]: Boot information the kernel was entered with
+//! - `hosted`: Stand-ins for privileged instructions, with the `hosted`
+//!   feature
+//! - [`hypervisor`]: Detection of the hypervisor the kernel runs under
+//! - [`integrity`]: Verification of the kernel image against loader checksums
 //! - [`io`]: Input/output operations and device communication
+//! - [`paging`]: Memory attributes of device mappings
+//! - [`panic_policy`]: What the kernel does after a panic is reported
+//! - [`perf`]: Performance counters, profiler, IRQ latency and idle states
+//! - [`power`]: Reboot and power off
+//! - [`sched`]: Preemptive priority scheduling of kernel tasks
+//! - [`settings`]: Configuration kept across boots in UEFI variables
+//! - [`spin`]: Spinlocks taken with the instructions of the processor
+//! - [`supervisor`]: Panic catching and restart policies for tasks
+//! - [`symbols`]: Names of kernel addresses from the embedded symbol map
+//! - [`timer`]: Work of each timer tick
 //! - [`util`]: System utilities and helper functions
+//! - [`vt`]: Virtual terminals switched by hotkey
 //!
 //! ## Usage
 //!
@@ -46,16 +71,158 @@
 //! // util::system_time();
 //! ```
 
+/// Secondary core bring-up log
+///
+/// Stashes the startup milestones of each core without locks and merges them
+/// into the kernel log once the core is online.
+pub mod bringup;
+
+/// Data cache maintenance by address range
+///
+/// Cleans and invalidates cache lines of memory shared with devices.
+pub mod cache;
+
+/// Console policy
+///
+/// Mirrors the kernel log to the serial console and the framebuffer, each
+/// with its own log level, as chosen on the kernel command line.
+pub mod console_policy;
+
+/// CPU feature detection
+///
+/// Reads the ID registers or CPUID once and picks the CRC-32 and copy
+/// routines the processor supports.
+pub mod cpu;
+
+/// Crash dumps written on panic
+///
+/// Serializes the panic message, registers and backtrace for decoding on the
+/// host with `cargo xtask crash decode`.
+pub mod crash;
+
+/// Device tree handed over at boot
+///
+/// Keeps the blob for lookups after boot and prints it with the `dt` shell
+/// command.
+pub mod dt;
+
+/// Paravirtual console used before real drivers
+///
+/// Writes through semihosting or the port `0xe9` debug console from the first
+/// instruction.
+pub mod early_console;
+
+/// UEFI runtime services callable after boot
+///
+/// Wraps the clock, variable and reset services the loader hands over,
+/// checking the capabilities firmware reported.
+pub mod efi;
+
+/// Read-only environment assembled at boot
+///
+/// Exposes command line entries, the board and build information to
+/// applications, and provides the `env` shell command.
+pub mod env;
+
 /// Graphics and display management functionality
 ///
 /// Provides framebuffer operations, pixel manipulation, and display control.
 pub mod graphic;
 
+/// Boot information the kernel was entered with
+///
+/// Keeps it for the `bootinfo` shell command and dumps it for decoding on the
+/// host with `cargo xtask bootinfo`.
+pub mod handoff;
+
+/// Stand-ins for privileged instructions
+///
+/// Replaces the instructions which trap outside of the kernel when it is
+/// built for a process on the host.
+#[cfg(feature = "hosted")]
+pub mod hosted;
+
+/// Detection of the hypervisor the kernel runs under
+///
+/// Uses CPUID on x86_64 and the device tree on AArch64.
+pub mod hypervisor;
+
+/// Verification of the kernel image against loader checksums
+///
+/// Detects segments corrupted between loading and kernel entry.
+pub mod integrity;
+
 /// Input/output operations and device communication
 ///
 /// Handles keyboard input, mouse events, and other I/O device interactions.
 pub mod io;
 
+/// Mappings of device memory
+///
+/// Maps MMIO registers and framebuffers with the memory attributes they
+/// need, and refuses conflicting attributes for the same memory.
+pub mod paging;
+
+/// Panic policy
+///
+/// Halts, reboots or blinks an LED after a panic, as chosen on the kernel
+/// command line.
+pub mod panic_policy;
+
+/// Performance counters and sampling profiler
+///
+/// Reads cycle and instruction counters, records sampled PCs, times
+/// interrupts and waits in idle states.
+pub mod perf;
+
+/// Reboot and power off
+///
+/// Uses PSCI or the reset control register, with UEFI `ResetSystem` as the
+/// fallback.
+pub mod power;
+
+/// Scheduler
+///
+/// Switches between kernel tasks by priority and time slice, and accounts
+/// the time each task runs.
+pub mod sched;
+
+/// Persistent settings
+///
+/// Stores small configuration values in non-volatile UEFI variables with
+/// checksums and a write budget.
+pub mod settings;
+
+/// Spinlocks
+///
+/// Takes locks with LL/SC or LSE compare-and-swap, chosen at boot by the
+/// features of the processor, and times them with the `spinbench` command.
+pub mod spin;
+
+/// Task-local panic handling and restart policies
+///
+/// Catches the panic of a task so a supervisor can restart it, ignore it or
+/// escalate it to a kernel panic.
+pub mod supervisor;
+
+/// Kernel symbol map
+///
+/// Resolves addresses to symbol names with the table written into the image
+/// after linking, for backtraces and profiles without the ELF symbol table.
+pub mod symbols;
+
+/// Timer tick
+///
+/// Advances the clocks of the executor and the scheduler, and preempts the
+/// running task once its slice is used up.
+pub mod timer;
+
+/// Virtual terminals
+///
+/// Keeps the kernel log, the debug shell and applications on separate
+/// consoles, switched by hotkey or the `vt` shell command.
+pub mod vt;
+
 /// System utilities and helper functions
 ///
 /// Contains various utility functions and data structures used throughout the
@@ -0,0 +1,228 @@
+//! # Secondary Core Bring-up Log
+//!
+//! Secondary cores run their startup code before their per-core structures,
+//! the heap and the console exist. Startup code instead marks milestones
+//! into a stash of its core: a fixed array indexed by affinity level 0 of
+//! `MPIDR_EL1`, written with atomics only, so it works without a stack
+//! frame beyond the call and without locks shared with other cores.
+//!
+//! Once a core is online, [`merge`] copies its milestones into the kernel
+//! log. The milestones of a core which never comes online stay in its stash,
+//! where [`report`] shows how far it got.
+//!
+//! Each stash keeps the first [`STASH_LEN`] milestones of its core. Later
+//! ones are counted but dropped, as the first ones tell where bring-up went
+//! wrong.
+//!
+//! ## Current Status
+//!
+//! Only the boot core runs yet, so nothing marks milestones until secondary
+//! cores are started with PSCI `CPU_ON`.
+//!
+//! ```rust,ignore
+//! use oso_kernel::base::bringup;
+//! use oso_kernel::base::bringup::Milestone;
+//!
+//! // on the secondary core
+//! bringup::mark(Milestone::Entered,);
+//! bringup::mark_with(Milestone::Stack, stack_top as u32,);
+//! bringup::mark(Milestone::Online,);
+//!
+//! // on any core, once the secondary core is online
+//! bringup::merge(core,);
+//! ```
+
+use super::perf::core_id;
+use super::perf::idle::MAX_CORES;
+use super::perf::trace::timestamp;
+use crate::println;
+use core::fmt;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+
+/// Milestones kept per core
+pub const STASH_LEN: usize = 16;
+
+static STASHES: [Stash; MAX_CORES] = [const { Stash::new() }; MAX_CORES];
+
+/// Step of the startup of a secondary core
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum Milestone {
+	/// Entered the kernel from firmware
+	Entered   = 1,
+	/// Switched to its own stack. The argument is the top of the stack
+	Stack     = 2,
+	/// Enabled its MMU
+	Mmu       = 3,
+	/// Installed the exception vectors
+	Vectors   = 4,
+	/// Enabled its interface of the interrupt controller
+	Interrupt = 5,
+	/// Armed its timer
+	Timer     = 6,
+	/// Set up its per-core structures
+	PerCpu    = 7,
+	/// Ready to run tasks
+	Online    = 8,
+	/// Step defined by the caller. The argument tells which
+	Custom    = 0xff,
+}
+
+impl Milestone {
+	pub const fn name(&self,) -> &'static str {
+		match self {
+			Self::Entered => "entered",
+			Self::Stack => "stack",
+			Self::Mmu => "mmu",
+			Self::Vectors => "vectors",
+			Self::Interrupt => "interrupt",
+			Self::Timer => "timer",
+			Self::PerCpu => "per-cpu",
+			Self::Online => "online",
+			Self::Custom => "custom",
+		}
+	}
+
+	const fn from_u8(code: u8,) -> Option<Self,> {
+		Some(match code {
+			1 => Self::Entered,
+			2 => Self::Stack,
+			3 => Self::Mmu,
+			4 => Self::Vectors,
+			5 => Self::Interrupt,
+			6 => Self::Timer,
+			7 => Self::PerCpu,
+			8 => Self::Online,
+			0xff => Self::Custom,
+			_ => return None,
+		},)
+	}
+}
+
+impl fmt::Display for Milestone {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		f.write_str(self.name(),)
+	}
+}
+
+/// Milestone as marked, with the timestamp of
+/// [`trace::timestamp`](super::perf::trace::timestamp)
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct Mark {
+	pub milestone: Milestone,
+	pub arg:       u32,
+	pub timestamp: u64,
+}
+
+impl fmt::Display for Mark {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		write!(f, "{} {:#x} @{}", self.milestone, self.arg, self.timestamp)
+	}
+}
+
+/// Milestones of a core. Only the core itself writes them
+struct Stash {
+	/// `milestone | arg << 8` of each mark
+	marks:      [AtomicU64; STASH_LEN],
+	timestamps: [AtomicU64; STASH_LEN],
+	/// marks made, including dropped ones
+	len:        AtomicUsize,
+	merged:     AtomicBool,
+}
+
+impl Stash {
+	const fn new() -> Self {
+		Self {
+			marks:      [const { AtomicU64::new(0,) }; STASH_LEN],
+			timestamps: [const { AtomicU64::new(0,) }; STASH_LEN],
+			len:        AtomicUsize::new(0,),
+			merged:     AtomicBool::new(false,),
+		}
+	}
+
+	fn push(&self, milestone: Milestone, arg: u32,) {
+		// single writer, so no other core races for the slot
+		let i = self.len.load(Ordering::Relaxed,);
+		if let Some(slot,) = self.marks.get(i,) {
+			let mark = milestone as u64 | (arg as u64) << 8;
+			self.timestamps[i].store(timestamp(), Ordering::Relaxed,);
+			slot.store(mark, Ordering::Relaxed,);
+		}
+		// publishes the slot to readers of `len`
+		self.len.store(i + 1, Ordering::Release,);
+	}
+
+	fn marks(&self,) -> impl Iterator<Item = Mark,> + '_ {
+		let len = self.len.load(Ordering::Acquire,).min(STASH_LEN,);
+		(0..len).filter_map(|i| {
+			let mark = self.marks[i].load(Ordering::Relaxed,);
+			Some(Mark {
+				milestone: Milestone::from_u8(mark as u8,)?,
+				arg:       (mark >> 8) as u32,
+				timestamp: self.timestamps[i].load(Ordering::Relaxed,),
+			},)
+		},)
+	}
+}
+
+/// Marks `milestone` on the calling core
+pub fn mark(milestone: Milestone,) {
+	mark_with(milestone, 0,);
+}
+
+/// Marks `milestone` with `arg` on the calling core. Cores beyond
+/// [`MAX_CORES`] are not recorded
+pub fn mark_with(milestone: Milestone, arg: u32,) {
+	if let Some(stash,) = STASHES.get(core_id(),) {
+		stash.push(milestone, arg,);
+	}
+}
+
+/// Milestones of `core` in the order they were marked
+pub fn marks(core: usize,) -> impl Iterator<Item = Mark,> {
+	STASHES.get(core,).into_iter().flat_map(Stash::marks,)
+}
+
+/// Milestones `core` marked, including those dropped from its full stash
+pub fn marked(core: usize,) -> usize {
+	STASHES.get(core,).map_or(0, |stash| stash.len.load(Ordering::Acquire,),)
+}
+
+/// Copies the milestones of `core` into the kernel log, once
+///
+/// Called when the core is online. Returns `false` if they were merged
+/// already.
+pub fn merge(core: usize,) -> bool {
+	let Some(stash,) = STASHES.get(core,) else {
+		return false;
+	};
+	if stash.merged.swap(true, Ordering::AcqRel,) {
+		return false;
+	}
+	for mark in stash.marks() {
+		println!("cpu{core}: {mark}");
+	}
+	let dropped = marked(core,).saturating_sub(STASH_LEN,);
+	if dropped != 0 {
+		println!("cpu{core}: {dropped} milestones dropped");
+	}
+	true
+}
+
+/// Writes the last milestone of each core which marked some but was not
+/// merged, e.g. as it stopped during bring-up
+pub fn report(out: &mut impl fmt::Write,) -> fmt::Result {
+	for (core, stash,) in STASHES.iter().enumerate() {
+		if stash.merged.load(Ordering::Acquire,) {
+			continue;
+		}
+		if let Some(last,) = stash.marks().last() {
+			let count = marked(core,);
+			writeln!(out, "cpu{core}: stopped after {last} ({count} marked)")?;
+		}
+	}
+	Ok((),)
+}
@@ -0,0 +1,95 @@
+//! # Cache Maintenance
+//!
+//! Data cache operations by address range, needed whenever memory is shared
+//! with a device which does not snoop the CPU caches.
+//!
+//! - [`clean`]: Writes dirty lines back to memory, before a device reads
+//! - [`invalidate`]: Drops lines, before the CPU reads what a device wrote
+//! - [`clean_invalidate`]: Both, for buffers a device reads and writes
+//!
+//! On AArch64 these are `dc cvac`, `dc ivac` and `dc civac` over every line
+//! of the range followed by `dsb sy`. x86_64 DMA is cache coherent, so only a
+//! memory fence is issued there.
+
+use core::sync::atomic::Ordering;
+use core::sync::atomic::fence;
+
+/// Smallest data cache line size in bytes
+#[cfg(target_arch = "aarch64")]
+pub fn line_size() -> usize {
+	let ctr: u64;
+	unsafe {
+		core::arch::asm!("mrs {}, ctr_el0", out(reg) ctr);
+	}
+	// CTR_EL0.DminLine is log2 of the number of 4 byte words
+	4 << ((ctr >> 16) & 0xf)
+}
+
+/// Smallest data cache line size in bytes
+#[cfg(not(target_arch = "aarch64"))]
+pub fn line_size() -> usize {
+	64
+}
+
+/// Writes lines covering `addr..addr + len` back to memory
+///
+/// # Safety
+///
+/// The range must be mapped
+pub unsafe fn clean(addr: *const u8, len: usize,) {
+	unsafe { maintain(addr, len, Op::Clean,) }
+}
+
+/// Discards lines covering `addr..addr + len`
+///
+/// # Safety
+///
+/// The range must be mapped. Dirty data sharing a line with the range is
+/// lost, so the range should be aligned to [`line_size`]
+pub unsafe fn invalidate(addr: *const u8, len: usize,) {
+	unsafe { maintain(addr, len, Op::Invalidate,) }
+}
+
+/// Writes back and discards lines covering `addr..addr + len`
+///
+/// # Safety
+///
+/// The range must be mapped
+pub unsafe fn clean_invalidate(addr: *const u8, len: usize,) {
+	unsafe { maintain(addr, len, Op::CleanInvalidate,) }
+}
+
+#[derive(Clone, Copy,)]
+enum Op {
+	Clean,
+	Invalidate,
+	CleanInvalidate,
+}
+
+/// Applies `op` to every line of the range, then orders it before later
+/// memory accesses and device access
+unsafe fn maintain(addr: *const u8, len: usize, op: Op,) {
+	#[cfg(target_arch = "aarch64")]
+	{
+		use core::arch::asm;
+
+		let line = line_size();
+		let end = addr as usize + len;
+		let mut at = addr as usize & !(line - 1);
+		while at < end {
+			unsafe {
+				match op {
+					Op::Clean => asm!("dc cvac, {}", in(reg) at),
+					Op::Invalidate => asm!("dc ivac, {}", in(reg) at),
+					Op::CleanInvalidate => asm!("dc civac, {}", in(reg) at),
+				}
+			}
+			at += line;
+		}
+		unsafe { asm!("dsb sy") };
+	}
+	#[cfg(not(target_arch = "aarch64"))]
+	let _ = (addr, len, op,);
+
+	fence(Ordering::SeqCst,);
+}
@@ -0,0 +1,233 @@
+//! # Console Policy
+//!
+//! Mirrors the kernel log to several consoles at once, each showing records
+//! down to its own [`LogLevel`], as chosen on the kernel command line:
+//!
+//! - `console=uart0,fb`: Every record to the serial console and the
+//!   framebuffer
+//! - `console=uart0,fb:info`: Every record to the serial console, and `info`
+//!   and more severe records to the framebuffer
+//!
+//! A sink without a level shows every record. Unknown sinks and levels are
+//! ignored. Without `console`, or if no sink is known, the kernel log goes to
+//! the framebuffer only.
+//!
+//! Text written with [`print!`](crate::print) is an `info` record, and
+//! [`log!`](crate::log) writes records of other levels. Colors and the
+//! cursor are applied to every sink, whatever the level of the next record.
+//!
+//! ## Current Status
+//!
+//! There is no UART driver yet, so `uart0` is the [`EarlyConsole`], which is
+//! only written with the `early_console` feature under a hypervisor.
+//!
+//! ```rust,ignore
+//! console_policy::set(Sink::Framebuffer, Some(LogLevel::Warn,),);
+//! log!(Debug, "mapped {pages} pages");
+//! ```
+
+use crate::app::log_viewer::LogLevel;
+use crate::base::early_console::EarlyConsole;
+use crate::base::env;
+use crate::base::vt::Vt;
+use crate::base::vt::VtConsole;
+use core::fmt;
+use core::sync::atomic::AtomicU8;
+use core::sync::atomic::Ordering;
+use oso_no_std_shared::color::Style;
+use oso_no_std_shared::text::console;
+use oso_no_std_shared::text::console::Console;
+use oso_no_std_shared::text::console::ConsoleColor;
+
+/// Raw route of a sink which shows nothing
+const OFF: u8 = u8::MAX;
+
+/// Least severe level shown by each sink, by [`Sink::index`]
+static ROUTES: [AtomicU8; Sink::ALL.len()] = [
+	AtomicU8::new(OFF,),
+	AtomicU8::new(LogLevel::Trace as u8,),
+];
+/// Level of the record being written
+static LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8,);
+
+/// Writes a record of `level` to the kernel log with a newline
+///
+/// ```rust,ignore
+/// log!(Warn, "no device tree, guessing {board}");
+/// ```
+#[macro_export]
+macro_rules! log {
+	($level:ident, $($arg:tt)*) => {
+		$crate::base::console_policy::log(
+			$crate::app::log_viewer::LogLevel::$level,
+			format_args!("{}\n", format_args!($($arg)*)),
+		);
+	};
+}
+
+/// Console the kernel log is mirrored to
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum Sink {
+	/// serial console, named `uart0`
+	Uart0,
+	/// kernel log terminal on the framebuffer, named `fb`
+	Framebuffer,
+}
+
+impl Sink {
+	pub const ALL: [Self; 2] = [Self::Uart0, Self::Framebuffer,];
+
+	/// Sink named `name` on the kernel command line
+	pub fn parse(name: &str,) -> Option<Self,> {
+		Self::ALL.into_iter().find(|sink| sink.name() == name,)
+	}
+
+	pub const fn name(&self,) -> &'static str {
+		match self {
+			Self::Uart0 => "uart0",
+			Self::Framebuffer => "fb",
+		}
+	}
+
+	const fn index(&self,) -> usize {
+		*self as usize
+	}
+}
+
+/// Routes the kernel log as `console` of the kernel command line says, and
+/// makes the mirror the console of [`print!`](crate::print). Called once,
+/// after [`env::init`]
+pub fn init() {
+	/// console handed to [`console::install`]
+	static mut MIRROR: Mirror = Mirror::new();
+
+	if let Some(value,) = env::get("cmdline.console",) {
+		configure(value,);
+	}
+	// SAFETY: `MIRROR` is only referenced here, and `init` is called once
+	console::install(unsafe { &mut *core::ptr::addr_of_mut!(MIRROR) },);
+}
+
+/// Routes the sinks listed in `value`, `sink[:level]` separated by commas,
+/// and turns off the others. Nothing changes if no sink is known
+pub fn configure(value: &str,) {
+	let mut routes = [None; Sink::ALL.len()];
+	for entry in value.split(',',) {
+		let (name, level,) = match entry.split_once(':',) {
+			Some((name, level,),) => (name, LogLevel::parse(level,),),
+			None => (entry, Some(LogLevel::Trace,),),
+		};
+		if let (Some(sink,), Some(level,),) = (Sink::parse(name,), level,) {
+			routes[sink.index()] = Some(level,);
+		}
+	}
+	if routes.iter().all(Option::is_none,) {
+		return;
+	}
+	for (sink, level,) in Sink::ALL.into_iter().zip(routes,) {
+		set(sink, level,);
+	}
+}
+
+/// Shows records of `level` and more severe ones on `sink`, or nothing if
+/// `level` is `None`
+pub fn set(sink: Sink, level: Option<LogLevel,>,) {
+	let raw = level.map_or(OFF, |level| level as u8,);
+	ROUTES[sink.index()].store(raw, Ordering::Relaxed,);
+}
+
+/// Least severe level shown on `sink`, `None` if it is off
+pub fn get(sink: Sink,) -> Option<LogLevel,> {
+	let raw = ROUTES[sink.index()].load(Ordering::Relaxed,);
+	LogLevel::ALL.get(raw as usize,).copied()
+}
+
+/// Writes `args` to the kernel log as a record of `level`. Used by
+/// [`log!`](crate::log)
+pub fn log(level: LogLevel, args: fmt::Arguments,) {
+	console::with(|console| {
+		LEVEL.store(level as u8, Ordering::Relaxed,);
+		let _ = console.write_fmt(args,);
+		LEVEL.store(LogLevel::Info as u8, Ordering::Relaxed,);
+	},);
+}
+
+/// Console writing to every routed sink
+struct Mirror {
+	uart: EarlyConsole,
+	fb:   VtConsole,
+}
+
+impl Mirror {
+	const fn new() -> Self {
+		Self { uart: EarlyConsole::new(), fb: VtConsole::new(Vt::Log,), }
+	}
+
+	fn console(&mut self, sink: Sink,) -> &mut dyn Console {
+		match sink {
+			Sink::Uart0 => &mut self.uart,
+			Sink::Framebuffer => &mut self.fb,
+		}
+	}
+
+	/// Calls `f` with each sink which shows records of `level`, or with
+	/// every routed sink if `level` is `None`. Every sink is called even if
+	/// one fails, and the first error is returned
+	fn each(
+		&mut self,
+		level: Option<LogLevel,>,
+		mut f: impl FnMut(&mut dyn Console,) -> fmt::Result,
+	) -> fmt::Result {
+		let mut result = Ok((),);
+		for sink in Sink::ALL {
+			let shown = match (get(sink,), level,) {
+				(None, _,) => false,
+				(Some(least,), Some(level,),) => level <= least,
+				(Some(_,), None,) => true,
+			};
+			if shown {
+				let r = f(self.console(sink,),);
+				result = result.and(r,);
+			}
+		}
+		result
+	}
+}
+
+impl fmt::Write for Mirror {
+	fn write_str(&mut self, s: &str,) -> fmt::Result {
+		let level = LogLevel::ALL[LEVEL.load(Ordering::Relaxed,) as usize];
+		self.each(Some(level,), |console| console.write_str(s,),)
+	}
+}
+
+impl Console for Mirror {
+	fn set_color(
+		&mut self,
+		foreground: ConsoleColor,
+		background: ConsoleColor,
+	) -> fmt::Result {
+		self.each(None, |console| console.set_color(foreground, background,),)
+	}
+
+	fn clear(&mut self,) -> fmt::Result {
+		self.each(None, |console| console.clear(),)
+	}
+
+	/// cursor of the framebuffer if it is routed, as its rows and columns
+	/// are known
+	fn cursor(&self,) -> (usize, usize,) {
+		match get(Sink::Framebuffer,) {
+			Some(_,) => self.fb.cursor(),
+			None => self.uart.cursor(),
+		}
+	}
+
+	fn set_cursor(&mut self, column: usize, row: usize,) -> fmt::Result {
+		self.each(None, |console| console.set_cursor(column, row,),)
+	}
+
+	fn set_style(&mut self, style: Style,) -> fmt::Result {
+		self.each(None, |console| console.set_style(style,),)
+	}
+}
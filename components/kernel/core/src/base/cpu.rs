@@ -0,0 +1,343 @@
+//! # CPU Features
+//!
+//! Optional features of the processor, read once from its ID registers, so
+//! code paths using them are chosen at run time rather than at build time.
+//!
+//! - **AArch64**: `ID_AA64ISAR0_EL1` for the instructions,
+//!   `ID_AA64PFR0_EL1` for floating point and Advanced SIMD, and
+//!   `ID_AA64MMFR0_EL1` for the physical address range
+//! - **x86_64**: CPUID leaves `1`, `7` and `0x8000_0008`
+//!
+//! Every core of a system is assumed to have the features of the boot core.
+//!
+//! ## Code Paths
+//!
+//! - [`crc32`]: The CRC-32 instructions if there are, otherwise the bitwise
+//!   [`oso_no_std_shared::data::crc32`]. x86_64 only has CRC-32C, so it
+//!   always takes the bitwise one
+//! - [`copy`]: 32 bytes per load and store through the SIMD registers if
+//!   there are and both ends are 16 byte aligned, as `strict-align` forbids
+//!   unaligned accesses
+//!
+//! ## Shell
+//!
+//! [`run_command`] implements the `cpuinfo` shell command, which prints the
+//! identification and the features of the processor.
+//!
+//! ## Current Status
+//!
+//! RISC-V cores describe themselves in `misa`, which is only readable in
+//! machine mode, so no feature is detected on them.
+//!
+//! ```rust,ignore
+//! use oso_kernel::base::cpu;
+//!
+//! println!("cpu: {}", cpu::features());
+//! if cpu::features().has_lse() {
+//! 	lock_with_cas();
+//! }
+//! let crc = !cpu::crc32(!0, bytes,);
+//! ```
+
+use core::fmt;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicU8;
+use core::sync::atomic::AtomicU32;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+use oso_error::Rslt;
+use oso_error::kernel::CpuError;
+use oso_error::oso_err;
+use oso_no_std_shared::data::crc32 as soft_crc32;
+
+/// Name of the shell command handled by [`run_command`]
+pub const COMMAND: &str = "cpuinfo";
+
+static DETECTED: AtomicBool = AtomicBool::new(false,);
+static BITS: AtomicU32 = AtomicU32::new(0,);
+static PA_BITS: AtomicU8 = AtomicU8::new(0,);
+static ID: AtomicU64 = AtomicU64::new(0,);
+
+/// Features of the processor
+///
+/// # Fields
+///
+/// * `bits` - [`Features::CRC32`] and the other feature bits
+/// * `pa_bits` - Bits of physical addresses, `0` if unknown
+/// * `id` - `MIDR_EL1` on AArch64, the signature of CPUID leaf `1` on
+///   x86_64
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq,)]
+pub struct Features {
+	pub bits:    u32,
+	pub pa_bits: u8,
+	pub id:      u64,
+}
+
+impl Features {
+	/// CRC-32 instructions of the IEEE polynomial
+	pub const CRC32: u32 = 1 << 0;
+	pub const SHA1: u32 = 1 << 1;
+	pub const SHA2: u32 = 1 << 2;
+	pub const AES: u32 = 1 << 3;
+	/// Carry-less multiplication
+	pub const PMULL: u32 = 1 << 4;
+	/// Large System Extensions: atomic read-modify-write instructions
+	pub const LSE: u32 = 1 << 5;
+	/// Floating point
+	pub const FP: u32 = 1 << 6;
+	/// Advanced SIMD, or SSE2
+	pub const SIMD: u32 = 1 << 7;
+	/// Hardware random numbers
+	pub const RNG: u32 = 1 << 8;
+	/// Name of each feature bit, in the order they are printed
+	pub const NAMES: [(u32, &str,); 9] = [
+		(Self::CRC32, "crc32",),
+		(Self::SHA1, "sha1",),
+		(Self::SHA2, "sha2",),
+		(Self::AES, "aes",),
+		(Self::PMULL, "pmull",),
+		(Self::LSE, "lse",),
+		(Self::FP, "fp",),
+		(Self::SIMD, "simd",),
+		(Self::RNG, "rng",),
+	];
+
+	/// `true` if every feature of `bits` is present
+	pub const fn has(&self, bits: u32,) -> bool {
+		self.bits & bits == bits
+	}
+
+	pub const fn has_crc32(&self,) -> bool {
+		self.has(Self::CRC32,)
+	}
+
+	pub const fn has_sha2(&self,) -> bool {
+		self.has(Self::SHA2,)
+	}
+
+	pub const fn has_lse(&self,) -> bool {
+		self.has(Self::LSE,)
+	}
+
+	pub const fn has_simd(&self,) -> bool {
+		self.has(Self::SIMD,)
+	}
+
+	/// Names of the features present
+	pub fn names(&self,) -> impl Iterator<Item = &'static str,> + '_ {
+		Self::NAMES
+			.iter()
+			.filter(|(bit, _,)| self.has(*bit,),)
+			.map(|(_, name,)| *name,)
+	}
+}
+
+impl fmt::Display for Features {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		for name in self.names() {
+			write!(f, "{name} ")?;
+		}
+		match self.pa_bits {
+			0 => write!(f, "pa=?"),
+			bits => write!(f, "pa={bits}"),
+		}
+	}
+}
+
+/// Features of the processor, detected on the first call
+pub fn features() -> Features {
+	if !DETECTED.load(Ordering::Acquire,) {
+		let features = detect();
+		BITS.store(features.bits, Ordering::Relaxed,);
+		PA_BITS.store(features.pa_bits, Ordering::Relaxed,);
+		ID.store(features.id, Ordering::Relaxed,);
+		DETECTED.store(true, Ordering::Release,);
+	}
+	Features {
+		bits:    BITS.load(Ordering::Relaxed,),
+		pa_bits: PA_BITS.load(Ordering::Relaxed,),
+		id:      ID.load(Ordering::Relaxed,),
+	}
+}
+
+#[cfg(all(target_arch = "aarch64", not(feature = "hosted")))]
+fn detect() -> Features {
+	use core::arch::asm;
+
+	let (isar0, pfr0, mmfr0, midr,): (u64, u64, u64, u64,);
+	unsafe {
+		asm!("mrs {}, id_aa64isar0_el1", out(reg) isar0);
+		asm!("mrs {}, id_aa64pfr0_el1", out(reg) pfr0);
+		asm!("mrs {}, id_aa64mmfr0_el1", out(reg) mmfr0);
+		asm!("mrs {}, midr_el1", out(reg) midr);
+	}
+	let field = |reg: u64, lsb: u32| (reg >> lsb) & 0xf;
+
+	let mut bits = 0;
+	let mut set = |present: bool, bit: u32| {
+		if present {
+			bits |= bit;
+		}
+	};
+	set(field(isar0, 4,) >= 1, Features::AES,);
+	set(field(isar0, 4,) >= 2, Features::PMULL,);
+	set(field(isar0, 8,) >= 1, Features::SHA1,);
+	set(field(isar0, 12,) >= 1, Features::SHA2,);
+	set(field(isar0, 16,) >= 1, Features::CRC32,);
+	set(field(isar0, 20,) >= 2, Features::LSE,);
+	set(field(isar0, 60,) >= 1, Features::RNG,);
+	// 0xf is not implemented, 0x1 half precision in addition
+	set(field(pfr0, 16,) != 0xf, Features::FP,);
+	set(field(pfr0, 20,) != 0xf, Features::SIMD,);
+
+	let pa_bits = match field(mmfr0, 0,) {
+		0 => 32,
+		1 => 36,
+		2 => 40,
+		3 => 42,
+		4 => 44,
+		5 => 48,
+		6 => 52,
+		_ => 0,
+	};
+	Features { bits, pa_bits, id: midr, }
+}
+
+#[cfg(all(target_arch = "x86_64", not(feature = "hosted")))]
+fn detect() -> Features {
+	use core::arch::x86_64::__cpuid;
+	use core::arch::x86_64::__cpuid_count;
+
+	let leaf1 = __cpuid(1,);
+	let max_leaf = __cpuid(0,).eax;
+	let leaf7_ebx = if max_leaf >= 7 { __cpuid_count(7, 0,).ebx } else { 0 };
+	let max_ext = __cpuid(0x8000_0000,).eax;
+
+	let mut bits = Features::LSE | Features::FP | Features::SIMD;
+	let mut set = |present: bool, bit: u32| {
+		if present {
+			bits |= bit;
+		}
+	};
+	set(leaf1.ecx & 1 << 1 != 0, Features::PMULL,);
+	set(leaf1.ecx & 1 << 25 != 0, Features::AES,);
+	set(leaf1.ecx & 1 << 30 != 0, Features::RNG,);
+	set(leaf7_ebx & 1 << 29 != 0, Features::SHA1 | Features::SHA2,);
+
+	let pa_bits = if max_ext >= 0x8000_0008 {
+		__cpuid(0x8000_0008,).eax as u8
+	} else {
+		0
+	};
+	Features { bits, pa_bits, id: leaf1.eax as u64, }
+}
+
+#[cfg(not(any(
+	target_arch = "aarch64",
+	target_arch = "x86_64",
+	feature = "hosted"
+)))]
+fn detect() -> Features {
+	Features::default()
+}
+
+#[cfg(feature = "hosted")]
+use super::hosted::detect_cpu as detect;
+
+/// Updates a CRC-32 (IEEE) with `bytes`, like
+/// [`crc32::update`](oso_no_std_shared::data::crc32::update). Start with
+/// `!0` and invert the result
+pub fn crc32(crc: u32, bytes: &[u8],) -> u32 {
+	#[cfg(target_arch = "aarch64")]
+	if features().has_crc32() {
+		// SAFETY: the CRC-32 instructions are present
+		return unsafe { crc32_hw(crc, bytes,) };
+	}
+	soft_crc32::update(crc, bytes,)
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "crc")]
+unsafe fn crc32_hw(mut crc: u32, bytes: &[u8],) -> u32 {
+	use core::arch::aarch64::__crc32b;
+	use core::arch::aarch64::__crc32d;
+
+	let (words, rest,) = bytes.as_chunks::<8>();
+	for word in words {
+		crc = __crc32d(crc, u64::from_le_bytes(*word,),);
+	}
+	for byte in rest {
+		crc = __crc32b(crc, *byte,);
+	}
+	crc
+}
+
+/// Copies `len` bytes from `src` to `dst`
+///
+/// # Safety
+///
+/// As [`core::ptr::copy_nonoverlapping`]
+pub unsafe fn copy(dst: *mut u8, src: *const u8, len: usize,) {
+	#[cfg(target_arch = "aarch64")]
+	if features().has_simd() && (dst as usize | src as usize) & 15 == 0 {
+		let bulk = len / 32 * 32;
+		unsafe {
+			copy_simd(dst, src, bulk,);
+			core::ptr::copy_nonoverlapping(
+				src.add(bulk,),
+				dst.add(bulk,),
+				len - bulk,
+			);
+		}
+		return;
+	}
+	unsafe { core::ptr::copy_nonoverlapping(src, dst, len,) };
+}
+
+/// copies `len`, a multiple of 32, bytes between 16 byte aligned buffers
+#[cfg(target_arch = "aarch64")]
+unsafe fn copy_simd(dst: *mut u8, src: *const u8, len: usize,) {
+	for offset in (0..len).step_by(32,) {
+		unsafe {
+			core::arch::asm!(
+				"ldp q0, q1, [{src}]",
+				"stp q0, q1, [{dst}]",
+				src = in(reg) src.add(offset,),
+				dst = in(reg) dst.add(offset,),
+				out("v0") _,
+				out("v1") _,
+				options(nostack, preserves_flags),
+			);
+		}
+	}
+}
+
+/// Runs the `cpuinfo` shell command with the arguments after its name
+pub fn run_command(
+	args: &[&str],
+	out: &mut impl fmt::Write,
+) -> Rslt<(), CpuError,> {
+	if !args.is_empty() {
+		let _ = writeln!(out, "usage: cpuinfo");
+		return Err(oso_err!(CpuError::Usage),);
+	}
+	let features = features();
+	#[cfg(target_arch = "aarch64")]
+	{
+		let midr = features.id;
+		let _ = writeln!(
+			out,
+			"implementer {:#04x} part {:#05x} variant {} revision {}",
+			midr >> 24 & 0xff,
+			midr >> 4 & 0xfff,
+			midr >> 20 & 0xf,
+			midr & 0xf
+		);
+	}
+	#[cfg(not(target_arch = "aarch64"))]
+	{
+		let _ = writeln!(out, "id {:#x}", features.id);
+	}
+	let _ = writeln!(out, "features {features}");
+	Ok((),)
+}
@@ -0,0 +1,470 @@
+//! # Crash Dump
+//!
+//! Serializes the state of the kernel at a panic into a compact framed
+//! format, so a crash can be examined after the machine is gone. Dumps are
+//! decoded on the host by `cargo xtask crash decode <file>`, which resolves
+//! addresses with the symbols of the kernel ELF.
+//!
+//! ## Format
+//!
+//! A dump starts with the magic `OSOD` and a version byte, followed by
+//! records of `tag: u8`, `len: u16` and `len` bytes of payload. Integers are
+//! little endian.
+//!
+//! | tag | record    | payload                                         |
+//! |-----|-----------|-------------------------------------------------|
+//! | 1   | message   | UTF-8 text                                      |
+//! | 2   | registers | `name_len: u8`, name, `value: u64`, repeated    |
+//! | 3   | backtrace | return addresses as `u64`                       |
+//! | 4   | log tail  | UTF-8 text                                      |
+//! | 5   | memory    | same as registers                               |
+//! | 0   | end       | CRC-32 (IEEE) of every byte before this record  |
+//!
+//! ## Sinks
+//!
+//! - [`HexLines`]: Hex text between `BEGIN` and `END` markers, which survives
+//!   serial consoles and log files. The decoder finds it in a whole log
+//! - [`RegionSink`]: Raw bytes into a reserved memory region
+//!
+//! ## Current Status
+//!
+//! The panic handler prints the backtrace with the names of
+//! [`symbols`](crate::base::symbols), and writes a dump to the console when
+//! enabled with [`set_dump_on_panic`]. There is no serial driver, no log ring and no
+//! memory statistics yet, so the log tail and memory records are only
+//! written by callers which have them. The backtrace walks frame records and
+//! needs the kernel built with `-C force-frame-pointers=yes`.
+//!
+//! ```rust,ignore
+//! use oso_kernel::base::crash;
+//!
+//! crash::set_dump_on_panic(true,);
+//! ```
+
+use crate::base::cpu;
+use crate::base::handoff;
+use crate::base::symbols::Symbolized;
+use core::fmt;
+use core::fmt::Write;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::Ordering;
+use oso_no_std_shared::text::fixed::FixedString;
+
+/// First bytes of every dump
+pub const MAGIC: [u8; 4] = *b"OSOD";
+/// Version of the format written by [`DumpWriter`]
+pub const VERSION: u8 = 1;
+/// Line before a hex encoded dump
+pub const BEGIN_MARKER: &str = "-----BEGIN OSO CRASH DUMP-----";
+/// Line after a hex encoded dump
+pub const END_MARKER: &str = "-----END OSO CRASH DUMP-----";
+/// Deepest backtrace written by [`dump_panic`]
+pub const MAX_FRAMES: usize = 32;
+
+/// Record tags
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+#[repr(u8)]
+pub enum Tag {
+	End = 0,
+	Message = 1,
+	Registers = 2,
+	Backtrace = 3,
+	LogTail = 4,
+	Memory = 5,
+}
+
+static DUMP_ON_PANIC: AtomicBool = AtomicBool::new(false,);
+
+/// Enables or disables writing a dump from the panic handler
+pub fn set_dump_on_panic(enable: bool,) {
+	DUMP_ON_PANIC.store(enable, Ordering::Relaxed,);
+}
+
+/// Destination of dump bytes
+///
+/// Panics must not happen here, so errors are dropped by the sink.
+pub trait DumpSink {
+	fn write(&mut self, bytes: &[u8],);
+
+	/// Called once after the last record
+	fn flush(&mut self,) {}
+}
+
+/// Writes records to a [`DumpSink`], keeping the checksum
+pub struct DumpWriter<S: DumpSink,> {
+	sink: S,
+	crc:  u32,
+}
+
+impl<S: DumpSink,> DumpWriter<S,> {
+	/// Writes the header
+	pub fn new(sink: S,) -> Self {
+		let mut writer = Self { sink, crc: !0, };
+		writer.emit(&MAGIC,);
+		writer.emit(&[VERSION,],);
+		writer
+	}
+
+	/// Panic message. Text longer than a record is cut
+	pub fn message(&mut self, text: &str,) {
+		self.text(Tag::Message, text,);
+	}
+
+	/// Register names and values
+	pub fn registers(&mut self, registers: &[(&str, u64,)],) {
+		self.pairs(Tag::Registers, registers,);
+	}
+
+	/// Return addresses, innermost first
+	pub fn backtrace(&mut self, frames: &[u64],) {
+		let frames = &frames[..frames.len().min(u16::MAX as usize / 8,)];
+		self.begin(Tag::Backtrace, frames.len() * 8,);
+		for addr in frames {
+			self.emit(&addr.to_le_bytes(),);
+		}
+	}
+
+	/// Last lines of the kernel log
+	pub fn log_tail(&mut self, text: &str,) {
+		self.text(Tag::LogTail, text,);
+	}
+
+	/// Memory statistics such as free frames or heap usage
+	pub fn memory(&mut self, stats: &[(&str, u64,)],) {
+		self.pairs(Tag::Memory, stats,);
+	}
+
+	/// Writes the end record and returns the sink
+	pub fn finish(mut self,) -> S {
+		let crc = !self.crc;
+		self.begin(Tag::End, 4,);
+		self.sink.write(&crc.to_le_bytes(),);
+		self.sink.flush();
+		self.sink
+	}
+
+	fn text(&mut self, tag: Tag, text: &str,) {
+		let mut len = text.len().min(u16::MAX as usize,);
+		while !text.is_char_boundary(len,) {
+			len -= 1;
+		}
+		self.begin(tag, len,);
+		self.emit(&text.as_bytes()[..len],);
+	}
+
+	fn pairs(&mut self, tag: Tag, pairs: &[(&str, u64,)],) {
+		let mut len = 0;
+		let mut count = 0;
+		for (n, _,) in pairs {
+			let size = 1 + pair_name(n,).len() + 8;
+			if len + size > u16::MAX as usize {
+				break;
+			}
+			len += size;
+			count += 1;
+		}
+
+		self.begin(tag, len,);
+		for (n, value,) in &pairs[..count] {
+			let n = pair_name(n,);
+			self.emit(&[n.len() as u8,],);
+			self.emit(n,);
+			self.emit(&value.to_le_bytes(),);
+		}
+	}
+
+	fn begin(&mut self, tag: Tag, len: usize,) {
+		self.emit(&[tag as u8,],);
+		self.emit(&(len as u16).to_le_bytes(),);
+	}
+
+	fn emit(&mut self, bytes: &[u8],) {
+		self.crc = cpu::crc32(self.crc, bytes,);
+		self.sink.write(bytes,);
+	}
+}
+
+/// Name of a register or statistic, cut to fit its `u8` length
+fn pair_name(name: &str,) -> &[u8] {
+	&name.as_bytes()[..name.len().min(u8::MAX as usize,)]
+}
+
+/// Writes a dump as lines of hex between [`BEGIN_MARKER`] and [`END_MARKER`]
+pub struct HexLines<W: fmt::Write,> {
+	out:  W,
+	line: [u8; 32],
+	len:  usize,
+	end:  &'static str,
+}
+
+impl<W: fmt::Write,> HexLines<W,> {
+	/// Writes [`BEGIN_MARKER`]
+	pub fn new(out: W,) -> Self {
+		Self::with_markers(out, BEGIN_MARKER, END_MARKER,)
+	}
+
+	/// Writes `begin`, and `end` at [`flush`](DumpSink::flush). For framed
+	/// data other than crash dumps
+	pub fn with_markers(
+		mut out: W,
+		begin: &'static str,
+		end: &'static str,
+	) -> Self {
+		let _ = writeln!(out, "\n{begin}");
+		Self { out, line: [0; 32], len: 0, end, }
+	}
+
+	fn write_line(&mut self,) {
+		for byte in &self.line[..self.len] {
+			let _ = write!(self.out, "{byte:02x}");
+		}
+		let _ = writeln!(self.out);
+		self.len = 0;
+	}
+}
+
+impl<W: fmt::Write,> DumpSink for HexLines<W,> {
+	fn write(&mut self, bytes: &[u8],) {
+		for byte in bytes {
+			self.line[self.len] = *byte;
+			self.len += 1;
+			if self.len == self.line.len() {
+				self.write_line();
+			}
+		}
+	}
+
+	/// Writes the last line and the end marker
+	fn flush(&mut self,) {
+		if self.len != 0 {
+			self.write_line();
+		}
+		let _ = writeln!(self.out, "{}", self.end);
+	}
+}
+
+/// Writes a dump into a memory region reserved for it, such as one which
+/// survives a warm reset. Bytes past the end of the region are dropped
+pub struct RegionSink<'a,> {
+	buf:       &'a mut [u8],
+	len:       usize,
+	truncated: bool,
+}
+
+impl<'a,> RegionSink<'a,> {
+	pub fn new(buf: &'a mut [u8],) -> Self {
+		Self { buf, len: 0, truncated: false, }
+	}
+
+	/// Bytes written
+	pub fn len(&self,) -> usize {
+		self.len
+	}
+
+	pub fn is_empty(&self,) -> bool {
+		self.len == 0
+	}
+
+	/// Whether the dump did not fit, in which case it fails its checksum
+	pub fn is_truncated(&self,) -> bool {
+		self.truncated
+	}
+}
+
+impl DumpSink for RegionSink<'_,> {
+	fn write(&mut self, bytes: &[u8],) {
+		let n = bytes.len().min(self.buf.len() - self.len,);
+		self.buf[self.len..self.len + n].copy_from_slice(&bytes[..n],);
+		self.len += n;
+		self.truncated |= n < bytes.len();
+	}
+}
+
+/// Writes a dump of the current state for the panic `info` into `sink`
+pub fn dump_panic<S: DumpSink,>(info: &core::panic::PanicInfo, sink: S,) -> S {
+	let mut message = FixedString::<512,>::new();
+	// a message which does not fit is kept cut
+	let _ = write!(message, "{info}");
+
+	let mut frames = [0; MAX_FRAMES];
+	// SAFETY: best effort on the way down, see `backtrace`
+	let depth = unsafe { backtrace(&mut frames,) };
+
+	let mut writer = DumpWriter::new(sink,);
+	writer.message(&message,);
+	writer.registers(&registers(),);
+	writer.backtrace(&frames[..depth],);
+	writer.finish()
+}
+
+/// Dumps to the console if enabled by [`set_dump_on_panic`], followed by a
+/// dump of the boot information
+///
+/// Called by the panic handler. A panic while dumping does not dump again.
+pub fn dump_on_panic(info: &core::panic::PanicInfo,) {
+	if DUMP_ON_PANIC.swap(false, Ordering::Relaxed,) {
+		dump_panic(info, HexLines::new(Console,),);
+		handoff::dump(&mut Console,);
+	}
+}
+
+/// Writes the backtrace of the caller, a return address and its symbol per
+/// line
+pub fn write_backtrace(out: &mut impl fmt::Write,) -> fmt::Result {
+	let mut frames = [0; MAX_FRAMES];
+	// SAFETY: best effort on the way down, see `backtrace`
+	let depth = unsafe { backtrace(&mut frames,) };
+	for (i, addr,) in frames[..depth].iter().enumerate() {
+		writeln!(out, "  #{i:<2} {}", Symbolized(*addr,))?;
+	}
+	Ok((),)
+}
+
+/// Prints the backtrace of the caller to the console
+///
+/// Called by the panic handler.
+pub fn print_backtrace() {
+	let _ = write_backtrace(&mut Console,);
+}
+
+/// [`fmt::Write`] to the kernel console
+struct Console;
+
+impl fmt::Write for Console {
+	fn write_str(&mut self, s: &str,) -> fmt::Result {
+		crate::base::io::print(format_args!("{s}"),);
+		Ok((),)
+	}
+}
+
+/// Number of registers returned by [`registers`]
+#[cfg(target_arch = "aarch64")]
+pub const REGISTER_COUNT: usize = 8;
+/// Number of registers returned by [`registers`]
+#[cfg(target_arch = "x86_64")]
+pub const REGISTER_COUNT: usize = 4;
+/// Number of registers returned by [`registers`]
+#[cfg(not(any(target_arch = "aarch64", target_arch = "x86_64")))]
+pub const REGISTER_COUNT: usize = 0;
+
+/// Registers of the caller and exception state
+#[cfg(target_arch = "aarch64")]
+pub fn registers() -> [(&'static str, u64,); REGISTER_COUNT] {
+	use core::arch::asm;
+
+	let (fp, lr, sp, elr, esr, far, spsr, el,): (
+		u64,
+		u64,
+		u64,
+		u64,
+		u64,
+		u64,
+		u64,
+		u64,
+	);
+	unsafe {
+		asm!(
+			"mov {fp}, x29",
+			"mov {lr}, x30",
+			"mov {sp}, sp",
+			"mrs {elr}, elr_el1",
+			"mrs {esr}, esr_el1",
+			"mrs {far}, far_el1",
+			"mrs {spsr}, spsr_el1",
+			"mrs {el}, currentel",
+			fp = out(reg) fp,
+			lr = out(reg) lr,
+			sp = out(reg) sp,
+			elr = out(reg) elr,
+			esr = out(reg) esr,
+			far = out(reg) far,
+			spsr = out(reg) spsr,
+			el = out(reg) el,
+		);
+	}
+	[
+		("fp", fp,),
+		("lr", lr,),
+		("sp", sp,),
+		("elr_el1", elr,),
+		("esr_el1", esr,),
+		("far_el1", far,),
+		("spsr_el1", spsr,),
+		("currentel", el,),
+	]
+}
+
+/// Registers of the caller and exception state
+#[cfg(target_arch = "x86_64")]
+pub fn registers() -> [(&'static str, u64,); REGISTER_COUNT] {
+	use core::arch::asm;
+
+	let (rbp, rsp, rflags, cr2,): (u64, u64, u64, u64,);
+	unsafe {
+		asm!(
+			"mov {rbp}, rbp",
+			"mov {rsp}, rsp",
+			"pushfq",
+			"pop {rflags}",
+			"mov {cr2}, cr2",
+			rbp = out(reg) rbp,
+			rsp = out(reg) rsp,
+			rflags = out(reg) rflags,
+			cr2 = out(reg) cr2,
+		);
+	}
+	[("rbp", rbp,), ("rsp", rsp,), ("rflags", rflags,), ("cr2", cr2,),]
+}
+
+/// Registers of the caller and exception state
+#[cfg(not(any(target_arch = "aarch64", target_arch = "x86_64")))]
+pub fn registers() -> [(&'static str, u64,); REGISTER_COUNT] {
+	[]
+}
+
+/// Walks frame records from the caller into `frames` and returns the depth
+///
+/// Both AArch64 and x86_64 frame records are the previous frame pointer
+/// followed by the return address. The walk stops at a null, misaligned or
+/// non increasing frame pointer, as the stack grows down.
+///
+/// # Safety
+///
+/// The kernel must be built with frame pointers, and every frame record on
+/// the chain must be mapped
+pub unsafe fn backtrace(frames: &mut [u64],) -> usize {
+	let mut fp = frame_pointer();
+	let mut depth = 0;
+	while depth < frames.len() && fp != 0 && fp.is_multiple_of(8,) {
+		let record = fp as *const usize;
+		let (next, ret,) = unsafe { (*record, *record.add(1,),) };
+		if ret == 0 {
+			break;
+		}
+		frames[depth] = ret as u64;
+		depth += 1;
+		if next <= fp {
+			break;
+		}
+		fp = next;
+	}
+	depth
+}
+
+#[inline(always)]
+fn frame_pointer() -> usize {
+	let fp: usize;
+	#[cfg(target_arch = "aarch64")]
+	unsafe {
+		core::arch::asm!("mov {}, x29", out(reg) fp);
+	}
+	#[cfg(target_arch = "x86_64")]
+	unsafe {
+		core::arch::asm!("mov {}, rbp", out(reg) fp);
+	}
+	#[cfg(not(any(target_arch = "aarch64", target_arch = "x86_64")))]
+	{
+		fp = 0;
+	}
+	fp
+}
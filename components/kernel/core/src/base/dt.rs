@@ -0,0 +1,321 @@
+//! # Device Tree
+//!
+//! Keeps the device tree the boot loader handed over, with the overlays it
+//! applied, so subsystems and the debug shell look it up after boot.
+//!
+//! ## Shell
+//!
+//! [`run_command`] implements the `dt` shell command:
+//!
+//! - `dt print [path]`: Prints the node at `path`, the root by default, and
+//!   its descendants in the source format of `dtc`
+//! - `dt prop <path> <name>`: Prints one property of the node at `path`
+//! - `dt res <path>`: Prints the registers, interrupts and clock of the node
+//!   at `path` as a driver reads them, with CPU addresses
+//!
+//! The blob does not record types, so values are printed by their look:
+//! string lists as `"a", "b"`, multiples of 4 bytes as cells `<0x1 0x2>` and
+//! anything else as bytes `[01 02]`. Cells of [`PHANDLE_PROPERTIES`] and
+//! `pinctrl-<n>` show the path of the node they refer to, as
+//! `<&{/intc@8000000}>`.
+//!
+//! `cargo xtask dt` prints a `.dtb` file the same way, so the tree the kernel
+//! sees can be diffed against the one built on the host.
+//!
+//! ```rust,ignore
+//! unsafe { dt::init(boot_info.device_tree,) };
+//! let psci = dt::fdt().and_then(|fdt| fdt.find("/psci",),);
+//! ```
+
+use core::fmt;
+use core::ptr::null_mut;
+use core::sync::atomic::AtomicPtr;
+use core::sync::atomic::Ordering;
+use oso_error::OsoError;
+use oso_error::Rslt;
+use oso_error::kernel::DtError;
+use oso_error::oso_err;
+use oso_error::parser::DtResourceError;
+use oso_no_std_shared::bridge::device_tree::DeviceTreeAddress;
+use oso_no_std_shared::bridge::device_tree::Fdt;
+use oso_no_std_shared::bridge::device_tree::Node;
+use oso_no_std_shared::bridge::device_tree::resource::MmioRegion;
+
+/// Name of the shell command handled by [`run_command`]
+pub const COMMAND: &str = "dt";
+/// Properties whose cells are all phandles
+pub const PHANDLE_PROPERTIES: [&str; 6] = [
+	"interrupt-parent",
+	"msi-parent",
+	"next-level-cache",
+	"memory-region",
+	"phy-handle",
+	"cpu",
+];
+
+static DEVICE_TREE: AtomicPtr<u8,> = AtomicPtr::new(null_mut(),);
+
+/// Keeps `device_tree` for [`fdt`]
+///
+/// # Safety
+///
+/// `device_tree` must be null or point to a valid device tree blob which is
+/// never freed
+pub unsafe fn init(device_tree: DeviceTreeAddress,) {
+	DEVICE_TREE.store(device_tree.cast_mut(), Ordering::Release,);
+}
+
+/// Device tree handed over at boot. `None` if there is none
+pub fn fdt() -> Option<Fdt<'static,>,> {
+	// SAFETY: `init` only stores blobs which are never freed
+	unsafe { Fdt::from_addr(DEVICE_TREE.load(Ordering::Acquire,),) }
+}
+
+/// Writes `node` and its descendants in the source format of `dtc`
+pub fn write_node(
+	fdt: &Fdt,
+	node: &Node,
+	out: &mut impl fmt::Write,
+) -> fmt::Result {
+	// nodes whose closing brace is not written yet
+	let mut open = 0;
+	for n in node.subtree() {
+		let level = n.depth - node.depth;
+		close(&mut open, level, out,)?;
+		let name = if n.depth == 0 { "/" } else { n.name };
+		writeln!(out, "{}{name} {{", Indent(level))?;
+		for (name, value,) in n.properties() {
+			write!(out, "{}", Indent(level + 1))?;
+			write_property(fdt, name, value, out,)?;
+			writeln!(out)?;
+		}
+		open = level + 1;
+	}
+	close(&mut open, 0, out,)
+}
+
+/// Writes `name = value;`, or `name;` for an empty value
+pub fn write_property(
+	fdt: &Fdt,
+	name: &str,
+	value: &[u8],
+	out: &mut impl fmt::Write,
+) -> fmt::Result {
+	if value.is_empty() {
+		return write!(out, "{name};");
+	}
+	write!(out, "{name} = ")?;
+	write_value(fdt, name, value, out,)?;
+	out.write_char(';',)
+}
+
+/// Writes `value` of the property `name`, see the [module](self) for the
+/// format
+pub fn write_value(
+	fdt: &Fdt,
+	name: &str,
+	value: &[u8],
+	out: &mut impl fmt::Write,
+) -> fmt::Result {
+	let (cells, rest,) = value.as_chunks::<4>();
+	let cells = cells.iter().map(|cell| u32::from_be_bytes(*cell,),);
+
+	if holds_phandles(name,) && rest.is_empty() {
+		out.write_char('<',)?;
+		for (i, cell,) in cells.enumerate() {
+			if i != 0 {
+				out.write_char(' ',)?;
+			}
+			match fdt.by_phandle(cell,) {
+				Some(node,) => {
+					out.write_str("&{",)?;
+					fdt.write_path(&node, out,)?;
+					out.write_char('}',)?;
+				},
+				None => write!(out, "{cell:#x}")?,
+			}
+		}
+		out.write_char('>',)
+	} else if let Some(strings,) = string_list(value,) {
+		for (i, string,) in strings.split(|b| *b == 0,).enumerate() {
+			if i != 0 {
+				out.write_str(", ",)?;
+			}
+			out.write_char('"',)?;
+			for &b in string {
+				if b == b'"' || b == b'\\' {
+					out.write_char('\\',)?;
+				}
+				out.write_char(b as char,)?;
+			}
+			out.write_char('"',)?;
+		}
+		Ok((),)
+	} else if rest.is_empty() {
+		out.write_char('<',)?;
+		for (i, cell,) in cells.enumerate() {
+			let separator = if i == 0 { "" } else { " " };
+			write!(out, "{separator}{cell:#x}")?;
+		}
+		out.write_char('>',)
+	} else {
+		out.write_char('[',)?;
+		for (i, byte,) in value.iter().enumerate() {
+			let separator = if i == 0 { "" } else { " " };
+			write!(out, "{separator}{byte:02x}")?;
+		}
+		out.write_char(']',)
+	}
+}
+
+/// Runs the `dt` shell command with the arguments after its name
+pub fn run_command(
+	args: &[&str],
+	out: &mut impl fmt::Write,
+) -> Rslt<(), DtError,> {
+	let Some(fdt,) = fdt() else {
+		let _ = writeln!(out, "dt: no device tree");
+		return Err(oso_err!(DtError::NoDeviceTree),);
+	};
+	match args {
+		["print",] | ["print", _,] => {
+			let path = args.get(1,).copied().unwrap_or("/",);
+			let node = find(&fdt, path, out,)?;
+			let _ = write_node(&fdt, &node, out,);
+		},
+		["prop", path, name,] => {
+			let node = find(&fdt, path, out,)?;
+			let Some(value,) = node.property(name,) else {
+				let _ = writeln!(out, "dt: {path}: no property {name}");
+				return Err(oso_err!(DtError::PropertyNotFound),);
+			};
+			let _ = write_property(&fdt, name, value, out,);
+			let _ = writeln!(out);
+		},
+		["res", path,] => {
+			let node = find(&fdt, path, out,)?;
+			if let Err(e,) = write_resources(&node, out,) {
+				let _ = out.write_str("dt: ",);
+				let _ = fdt.write_error(&e.desc.unwrap_or_default(), out,);
+				let _ = writeln!(out);
+				return Err(e.into(),);
+			}
+		},
+		_ => {
+			let _ = writeln!(out, "usage: dt print [path]");
+			let _ = writeln!(out, "       dt prop <path> <name>");
+			let _ = writeln!(out, "       dt res <path>");
+			return Err(oso_err!(DtError::Usage),);
+		},
+	}
+	Ok((),)
+}
+
+fn find<'a,>(
+	fdt: &Fdt<'a,>,
+	path: &str,
+	out: &mut impl fmt::Write,
+) -> Rslt<Node<'a,>, DtError,> {
+	fdt.find(path,).ok_or_else(|| {
+		let _ = writeln!(out, "dt: {path}: no such node");
+		oso_err!(DtError::NodeNotFound)
+	},)
+}
+
+/// Writes each entry of `reg` and `interrupts` of `node`, and its
+/// `clock-frequency`. Missing properties are skipped
+fn write_resources(
+	node: &Node,
+	out: &mut impl fmt::Write,
+) -> Rslt<(), DtResourceError,> {
+	for i in 0.. {
+		match node.reg(i,) {
+			Ok(MmioRegion { base, size, },) => {
+				let _ = writeln!(out, "reg {i}: {base:#x}+{size:#x}");
+			},
+			Err(e,) if is_end(node, "reg", &e,) => break,
+			Err(e,) => return Err(e,),
+		}
+	}
+	for i in 0.. {
+		match node.irq(i,) {
+			Ok(irq,) => {
+				let _ = write!(out, "irq {i}: {:?}", irq.specifier());
+				let _ = write!(out, " of phandle {:#x}", irq.controller);
+				if let Some(intid,) = irq.gic_intid() {
+					let _ = write!(out, ", intid {intid}");
+				}
+				let _ = writeln!(out);
+			},
+			Err(e,) if is_end(node, "interrupts", &e,) => break,
+			Err(e,) => return Err(e,),
+		}
+	}
+	match node.clock_frequency() {
+		Ok(hz,) => {
+			let _ = writeln!(out, "clock: {hz} Hz");
+		},
+		Err(e,) if is_end(node, "clock-frequency", &e,) => {},
+		Err(e,) => return Err(e,),
+	}
+	Ok((),)
+}
+
+/// Whether `error` only says `property` of `node` is missing or has no
+/// more entries
+fn is_end(
+	node: &Node,
+	property: &str,
+	error: &OsoError<DtResourceError,>,
+) -> bool {
+	let Some(desc,) = error.desc else {
+		return false;
+	};
+	let end = matches!(
+		desc,
+		DtResourceError::Missing { .. } | DtResourceError::OutOfRange { .. }
+	);
+	end && desc.source() == Some((node.offset(), property,),)
+}
+
+/// Tabs of nesting `level`
+struct Indent(usize,);
+
+impl fmt::Display for Indent {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		(0..self.0).try_for_each(|_| f.write_str("\t",),)
+	}
+}
+
+/// Writes the closing braces of open nodes down to nesting `level`
+fn close(
+	open: &mut usize,
+	level: usize,
+	out: &mut impl fmt::Write,
+) -> fmt::Result {
+	while *open > level {
+		*open -= 1;
+		writeln!(out, "{}}};", Indent(*open))?;
+	}
+	Ok((),)
+}
+
+fn holds_phandles(name: &str,) -> bool {
+	let pinctrl = name.strip_prefix("pinctrl-",);
+	PHANDLE_PROPERTIES.contains(&name,)
+		|| pinctrl.is_some_and(|n| n.parse::<u32>().is_ok(),)
+}
+
+/// `value` without the final NUL if it looks like a list of printable
+/// strings
+fn string_list(value: &[u8],) -> Option<&[u8],> {
+	let (&0, strings,) = value.split_last()? else {
+		return None;
+	};
+	let printable = |b: &u8| *b == 0 || b.is_ascii_graphic() || *b == b' ';
+	let empty = strings.is_empty()
+		|| strings[0] == 0
+		|| strings.ends_with(&[0,],)
+		|| strings.windows(2,).any(|w| w == [0, 0,],);
+	(!empty && strings.iter().all(printable,)).then_some(strings,)
+}
@@ -0,0 +1,216 @@
+//! # Early Console
+//!
+//! Paravirtual output which works as soon as the hypervisor is known, before
+//! the framebuffer or any UART driver is set up:
+//!
+//! - **AArch64**: Arm semihosting `SYS_WRITE0`. QEMU needs `-semihosting`, and
+//!   on real hardware without a debugger the `hlt` instruction faults
+//! - **x86_64**: the QEMU and Bochs debug console at port `0xe9`. QEMU needs
+//!   `-debugcon stdio`; elsewhere the writes are ignored
+//!
+//! The backend is enabled by the `early_console` feature. Output is
+//! discarded until [`select`] turns the backend on under a hypervisor, as
+//! semihosting faults on real hardware. [`retire`] turns it off when a real
+//! console takes over.
+//!
+//! [`EarlyConsole`] is also a [`Console`], which colors, clears and moves
+//! the cursor with ANSI escape sequences for the terminal on the host.
+
+use super::hypervisor::Hypervisor;
+use core::fmt;
+use core::fmt::Write;
+use core::sync::atomic::AtomicU8;
+use core::sync::atomic::Ordering;
+use oso_no_std_shared::text::console::Console;
+use oso_no_std_shared::text::console::ConsoleColor;
+
+/// Mechanism used by the early console
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum Backend {
+	/// output is discarded
+	None,
+	/// Arm semihosting
+	Semihosting,
+	/// port `0xe9` debug console
+	DebugCon,
+}
+
+impl Backend {
+	/// backend of the architecture, if the `early_console` feature is enabled
+	const DEFAULT: Self = if !cfg!(feature = "early_console") {
+		Self::None
+	} else if cfg!(target_arch = "aarch64") {
+		Self::Semihosting
+	} else if cfg!(target_arch = "x86_64") {
+		Self::DebugCon
+	} else {
+		Self::None
+	};
+
+	const fn from_raw(raw: u8,) -> Self {
+		match raw {
+			1 => Self::Semihosting,
+			2 => Self::DebugCon,
+			_ => Self::None,
+		}
+	}
+}
+
+static BACKEND: AtomicU8 = AtomicU8::new(Backend::None as u8,);
+
+pub fn backend() -> Backend {
+	Backend::from_raw(BACKEND.load(Ordering::Relaxed,),)
+}
+
+/// Turns the backend on only under a hypervisor, where the paravirtual
+/// device exists
+pub fn select(hypervisor: Hypervisor,) {
+	let backend =
+		if hypervisor.is_virtual() { Backend::DEFAULT } else { Backend::None };
+	BACKEND.store(backend as u8, Ordering::Relaxed,);
+}
+
+/// Stops writing to the early console
+pub fn retire() {
+	BACKEND.store(Backend::None as u8, Ordering::Relaxed,);
+}
+
+/// Prints formatted text to the early console with a newline
+///
+/// Does nothing unless the `early_console` feature is enabled and [`select`]
+/// found a hypervisor.
+///
+/// ```rust,ignore
+/// early_println!("entered kernel_main, boot info at {boot_info:p}");
+/// ```
+#[macro_export]
+macro_rules! early_println {
+	($($arg:tt)*) => {
+		$crate::base::early_console::print(
+			format_args!("{}\n", format_args!($($arg)*)),
+		);
+	};
+}
+
+/// Writes `args` to the early console. Used by [`early_println!`]
+pub fn print(args: fmt::Arguments,) {
+	if backend() == Backend::None {
+		return;
+	}
+	let _ = Raw.write_fmt(args,);
+}
+
+/// Early console which keeps track of its cursor
+///
+/// The cursor is counted from the text written through this value, so it
+/// is only accurate if nothing else writes to the early console.
+#[derive(Debug, Default,)]
+pub struct EarlyConsole {
+	column: usize,
+	row:    usize,
+}
+
+impl EarlyConsole {
+	pub const fn new() -> Self {
+		Self { column: 0, row: 0, }
+	}
+}
+
+impl Write for EarlyConsole {
+	fn write_str(&mut self, s: &str,) -> fmt::Result {
+		Raw.write_str(s,)?;
+		for c in s.chars() {
+			match c {
+				'\n' => {
+					self.row += 1;
+					self.column = 0;
+				},
+				'\r' => self.column = 0,
+				_ => self.column += 1,
+			}
+		}
+		Ok((),)
+	}
+}
+
+impl Console for EarlyConsole {
+	fn set_color(
+		&mut self,
+		foreground: ConsoleColor,
+		background: ConsoleColor,
+	) -> fmt::Result {
+		let fg = foreground.ansi_foreground();
+		let bg = background.ansi_background();
+		write!(Raw, "\x1b[{fg};{bg}m")
+	}
+
+	fn clear(&mut self,) -> fmt::Result {
+		Raw.write_str("\x1b[2J\x1b[H",)?;
+		self.column = 0;
+		self.row = 0;
+		Ok((),)
+	}
+
+	fn cursor(&self,) -> (usize, usize,) {
+		(self.column, self.row,)
+	}
+
+	fn set_cursor(&mut self, column: usize, row: usize,) -> fmt::Result {
+		write!(Raw, "\x1b[{};{}H", row + 1, column + 1)?;
+		self.column = column;
+		self.row = row;
+		Ok((),)
+	}
+}
+
+/// writes to the backend without moving a cursor
+struct Raw;
+
+impl Write for Raw {
+	fn write_str(&mut self, s: &str,) -> fmt::Result {
+		match backend() {
+			Backend::None => {},
+			Backend::Semihosting => semihosting_write(s.as_bytes(),),
+			Backend::DebugCon => s.bytes().for_each(debugcon_write,),
+		}
+		Ok((),)
+	}
+}
+
+/// writes through `SYS_WRITE0` in chunks, as it takes a NUL terminated string
+fn semihosting_write(bytes: &[u8],) {
+	#[cfg(target_arch = "aarch64")]
+	{
+		const SYS_WRITE0: u64 = 0x04;
+
+		for chunk in bytes.chunks(63,) {
+			let mut buf = [0u8; 64];
+			buf[..chunk.len()].copy_from_slice(chunk,);
+			unsafe {
+				core::arch::asm!(
+					"hlt #0xf000",
+					inout("x0") SYS_WRITE0 => _,
+					in("x1") buf.as_ptr(),
+					options(nostack, readonly),
+				);
+			}
+		}
+	}
+	#[cfg(not(target_arch = "aarch64"))]
+	let _ = bytes;
+}
+
+fn debugcon_write(byte: u8,) {
+	#[cfg(target_arch = "x86_64")]
+	unsafe {
+		core::arch::asm!(
+			"out dx, al",
+			in("dx") 0xe9u16,
+			in("al") byte,
+			options(nomem, nostack, preserves_flags),
+		);
+	}
+	#[cfg(not(target_arch = "x86_64"))]
+	let _ = byte;
+}
@@ -0,0 +1,418 @@
+//! # UEFI Runtime Services
+//!
+//! Safe wrappers of the runtime services the kernel calls after boot: the
+//! real time clock, variables and system reset.
+//!
+//! ## Capabilities
+//!
+//! The loader hands over the runtime services table with the services
+//! firmware supports after `ExitBootServices` in
+//! [`BootInfo::runtime`](oso_no_std_shared::bridge::boot_info::BootInfo).
+//! Unsupported services fail with [`EfiError::Unsupported`] without calling
+//! into firmware. A service answering `EFI_UNSUPPORTED` anyway is marked
+//! unsupported for the rest of the boot.
+//!
+//! ## Addressing Mode
+//!
+//! If `SetVirtualAddressMap` failed, firmware stays in physical mode and the
+//! table is handed over at its physical address. Both modes are callable as
+//! the kernel keeps runtime regions identity mapped, so callers do not tell
+//! them apart. [`is_virtual`] reports the mode for diagnostics.
+//!
+//! Firmware is not reentrant, so calls are serialized.
+//!
+//! ## Current Status
+//!
+//! Capsules are not passed to firmware yet: [`update_capsule`] is a stub
+//! which always fails with [`EfiError::Unsupported`].
+//!
+//! ```rust,ignore
+//! unsafe { efi::init(boot_info,) };
+//! let now = efi::time()?;
+//! println!("{now}");
+//! efi::reset(ResetKind::Warm,)?;
+//! ```
+
+use core::convert::Infallible;
+use core::fmt;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicPtr;
+use core::sync::atomic::AtomicU32;
+use core::sync::atomic::Ordering;
+use oso_error::Rslt;
+use oso_error::kernel::EfiError;
+use oso_error::oso_err;
+use oso_no_std_shared::bridge::boot_info::BootInfo;
+use oso_no_std_shared::bridge::boot_info::RuntimeCaps;
+
+/// `EFI_VARIABLE_NON_VOLATILE`
+pub const NON_VOLATILE: u32 = 0x01;
+/// `EFI_VARIABLE_BOOTSERVICE_ACCESS`
+pub const BOOTSERVICE_ACCESS: u32 = 0x02;
+/// `EFI_VARIABLE_RUNTIME_ACCESS`
+pub const RUNTIME_ACCESS: u32 = 0x04;
+
+/// `EFI_BUFFER_TOO_SMALL`
+const BUFFER_TOO_SMALL: usize = 1 << (usize::BITS - 1) | 5;
+/// `EFI_UNSUPPORTED`
+const UNSUPPORTED: usize = 1 << (usize::BITS - 1) | 3;
+/// `EFI_NOT_FOUND`
+const NOT_FOUND: usize = 1 << (usize::BITS - 1) | 14;
+/// `EFI_INVALID_PARAMETER`
+const INVALID_PARAMETER: usize = 1 << (usize::BITS - 1) | 2;
+
+static RUNTIME: AtomicPtr<RuntimeServices,> =
+	AtomicPtr::new(core::ptr::null_mut(),);
+static SUPPORTED: AtomicU32 = AtomicU32::new(0,);
+static FLAGS: AtomicU32 = AtomicU32::new(0,);
+static BUSY: AtomicBool = AtomicBool::new(false,);
+
+/// Vendor GUID in the layout of `EFI_GUID`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct Guid {
+	pub data1: u32,
+	pub data2: u16,
+	pub data3: u16,
+	pub data4: [u8; 8],
+}
+
+/// Time of the real time clock in the layout of `EFI_TIME`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq,)]
+pub struct Time {
+	pub year:       u16,
+	pub month:      u8,
+	pub day:        u8,
+	pub hour:       u8,
+	pub minute:     u8,
+	pub second:     u8,
+	_pad1:          u8,
+	pub nanosecond: u32,
+	/// Offset from UTC in minutes, or [`Time::UNSPECIFIED_TIMEZONE`]
+	pub time_zone:  i16,
+	pub daylight:   u8,
+	_pad2:          u8,
+}
+
+impl Time {
+	/// `EFI_UNSPECIFIED_TIMEZONE`: the time is local time
+	pub const UNSPECIFIED_TIMEZONE: i16 = 0x07ff;
+
+	/// Local time without a time zone
+	pub const fn new(
+		year: u16,
+		month: u8,
+		day: u8,
+		hour: u8,
+		minute: u8,
+		second: u8,
+	) -> Self {
+		Self {
+			year,
+			month,
+			day,
+			hour,
+			minute,
+			second,
+			_pad1: 0,
+			nanosecond: 0,
+			time_zone: Self::UNSPECIFIED_TIMEZONE,
+			daylight: 0,
+			_pad2: 0,
+		}
+	}
+
+	/// Whether every field is in the range `EFI_TIME` allows
+	pub fn is_valid(&self,) -> bool {
+		let days = match self.month {
+			2 if self.year.is_multiple_of(4,)
+				&& (!self.year.is_multiple_of(100,)
+					|| self.year.is_multiple_of(400,)) =>
+			{
+				29
+			},
+			2 => 28,
+			4 | 6 | 9 | 11 => 30,
+			_ => 31,
+		};
+		(1900..=9999).contains(&self.year,)
+			&& (1..=12).contains(&self.month,)
+			&& (1..=days).contains(&self.day,)
+			&& self.hour < 24
+			&& self.minute < 60
+			&& self.second < 60
+			&& self.nanosecond < 1_000_000_000
+			&& ((-1440..=1440).contains(&self.time_zone,)
+				|| self.time_zone == Self::UNSPECIFIED_TIMEZONE)
+	}
+}
+
+impl fmt::Display for Time {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		write!(
+			f,
+			"{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+			self.year, self.month, self.day, self.hour, self.minute, self.second
+		)?;
+		if self.time_zone != Self::UNSPECIFIED_TIMEZONE {
+			let zone = self.time_zone.unsigned_abs();
+			let sign = if self.time_zone < 0 { '-' } else { '+' };
+			write!(f, " {sign}{:02}:{:02}", zone / 60, zone % 60)?;
+		}
+		Ok((),)
+	}
+}
+
+/// Kind of reset, as `EFI_RESET_TYPE`
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum ResetKind {
+	/// Resets every circuit of the system
+	Cold     = 0,
+	/// Resets the processors, keeping memory
+	Warm     = 1,
+	/// Powers the system off
+	Shutdown = 2,
+}
+
+/// Records the runtime services handed over by the loader
+///
+/// # Safety
+///
+/// `boot_info.runtime_services` must be `0` or the address of the UEFI
+/// runtime services table, callable at that address
+pub unsafe fn init(boot_info: &BootInfo,) {
+	let rt = boot_info.read_runtime_services() as *mut RuntimeServices;
+	let caps = boot_info.runtime;
+	SUPPORTED.store(caps.supported, Ordering::Release,);
+	FLAGS.store(caps.flags, Ordering::Release,);
+	RUNTIME.store(rt, Ordering::Release,);
+}
+
+/// Services callable now. [`RuntimeCaps::none`] without runtime services
+pub fn capabilities() -> RuntimeCaps {
+	if RUNTIME.load(Ordering::Acquire,).is_null() {
+		return RuntimeCaps::none();
+	}
+	RuntimeCaps {
+		supported: SUPPORTED.load(Ordering::Acquire,),
+		flags:     FLAGS.load(Ordering::Acquire,),
+	}
+}
+
+/// Whether firmware runs in virtual mode. `false` in physical mode or
+/// without runtime services
+pub fn is_virtual() -> bool {
+	capabilities().is_virtual()
+}
+
+/// Current time of the real time clock
+///
+/// # Errors
+///
+/// - [`EfiError::Unavailable`] without runtime services
+/// - [`EfiError::Unsupported`] if firmware has no clock after boot
+/// - [`EfiError::Firmware`] if the clock fails to be read
+pub fn time() -> Rslt<Time, EfiError,> {
+	let mut time = Time::default();
+	call(RuntimeCaps::GET_TIME, |rt| unsafe {
+		(rt.get_time)(&mut time, core::ptr::null_mut(),)
+	},)?;
+	Ok(time,)
+}
+
+/// Sets the real time clock to `time`
+///
+/// # Errors
+///
+/// - [`EfiError::InvalidParameter`] if `time` is not valid
+/// - [`EfiError::Unavailable`] without runtime services
+/// - [`EfiError::Unsupported`] if firmware can not set the clock after boot
+/// - [`EfiError::Firmware`] if the clock fails to be set
+pub fn set_time(time: &Time,) -> Rslt<(), EfiError,> {
+	if !time.is_valid() {
+		return Err(oso_err!(EfiError::InvalidParameter),);
+	}
+	call(RuntimeCaps::SET_TIME, |rt| unsafe { (rt.set_time)(time,) },)
+}
+
+/// Reads the variable `name` of `vendor` into `buf`
+///
+/// `name` is UTF-16 and ends with NUL. Returns the size of the data and the
+/// attributes of the variable.
+///
+/// # Errors
+///
+/// - [`EfiError::NotFound`] if there is no such variable
+/// - [`EfiError::BufferTooSmall`] if `buf` can not hold the data
+/// - [`EfiError::InvalidParameter`] if `name` does not end with NUL
+/// - [`EfiError::Unavailable`] without runtime services
+/// - [`EfiError::Unsupported`] if firmware has no variables after boot
+/// - [`EfiError::Firmware`] if the variable fails to be read
+pub fn variable(
+	name: &[u16],
+	vendor: &Guid,
+	buf: &mut [u8],
+) -> Rslt<(usize, u32,), EfiError,> {
+	if name.last() != Some(&0,) {
+		return Err(oso_err!(EfiError::InvalidParameter),);
+	}
+	let mut size = buf.len();
+	let mut attributes = 0;
+	let read = call(RuntimeCaps::GET_VARIABLE, |rt| unsafe {
+		let (name, data,) = (name.as_ptr(), buf.as_mut_ptr(),);
+		(rt.get_variable)(name, vendor, &mut attributes, &mut size, data,)
+	},);
+	read.map_err(|mut e| {
+		// firmware wrote the size the data needs
+		if let Some(EfiError::BufferTooSmall { required, },) = &mut e.desc {
+			*required = size;
+		}
+		e
+	},)?;
+	Ok((size, attributes,),)
+}
+
+/// Writes `data` as the variable `name` of `vendor` with `attributes`.
+/// Empty `data` deletes the variable
+///
+/// `name` is UTF-16 and ends with NUL. `attributes` must include
+/// [`RUNTIME_ACCESS`] and [`BOOTSERVICE_ACCESS`], as variables without them
+/// are not writable after boot.
+///
+/// # Errors
+///
+/// - [`EfiError::InvalidParameter`] if `name` does not end with NUL or
+///   `attributes` lacks runtime access
+/// - [`EfiError::Unavailable`] without runtime services
+/// - [`EfiError::Unsupported`] if firmware can not write variables after
+///   boot, which is common on boards storing variables on a shared flash
+/// - [`EfiError::Firmware`] if the variable fails to be written
+pub fn set_variable(
+	name: &[u16],
+	vendor: &Guid,
+	attributes: u32,
+	data: &[u8],
+) -> Rslt<(), EfiError,> {
+	let access = RUNTIME_ACCESS | BOOTSERVICE_ACCESS;
+	if name.last() != Some(&0,) || attributes & access != access {
+		return Err(oso_err!(EfiError::InvalidParameter),);
+	}
+	call(RuntimeCaps::SET_VARIABLE, |rt| unsafe {
+		let name = name.as_ptr();
+		(rt.set_variable)(name, vendor, attributes, data.len(), data.as_ptr(),)
+	},)
+}
+
+/// Resets the system through firmware
+///
+/// Returns only if firmware can not reset, in which case the caller falls
+/// back to halting.
+///
+/// # Errors
+///
+/// - [`EfiError::Unavailable`] without runtime services
+/// - [`EfiError::Unsupported`] if firmware can not reset after boot
+pub fn reset(kind: ResetKind,) -> Rslt<Infallible, EfiError,> {
+	let rt = runtime(RuntimeCaps::RESET_SYSTEM,)?;
+	// never returns. `BUSY` is left taken as nothing runs afterwards
+	while BUSY.swap(true, Ordering::Acquire,) {
+		core::hint::spin_loop();
+	}
+	unsafe { (rt.reset_system)(kind as u32, 0, 0, core::ptr::null(),) }
+}
+
+/// Passes capsules to firmware, e.g. a firmware update
+///
+/// Stub: always fails, as the scatter-gather list firmware reads the
+/// capsules through is not built yet.
+///
+/// # Errors
+///
+/// [`EfiError::Unsupported`]
+pub fn update_capsule(capsules: &[&[u8]],) -> Rslt<(), EfiError,> {
+	let _ = capsules;
+	runtime(RuntimeCaps::UPDATE_CAPSULE,)?;
+	Err(oso_err!(EfiError::Unsupported),)
+}
+
+/// Runtime services table, if `service` is supported
+fn runtime(service: u32,) -> Rslt<&'static RuntimeServices, EfiError,> {
+	let rt = RUNTIME.load(Ordering::Acquire,);
+	// SAFETY: `init` only records the runtime services table
+	let rt = unsafe { rt.as_ref() }.ok_or(oso_err!(EfiError::Unavailable),)?;
+	if SUPPORTED.load(Ordering::Acquire,) & service == 0 {
+		return Err(oso_err!(EfiError::Unsupported),);
+	}
+	Ok(rt,)
+}
+
+/// Calls `f` with the table if `service` is supported, one call at a time,
+/// and translates the status it returns
+fn call(
+	service: u32,
+	f: impl FnOnce(&RuntimeServices,) -> usize,
+) -> Rslt<(), EfiError,> {
+	let rt = runtime(service,)?;
+	while BUSY.swap(true, Ordering::Acquire,) {
+		core::hint::spin_loop();
+	}
+	let status = f(rt,);
+	BUSY.store(false, Ordering::Release,);
+
+	match status {
+		0 => Ok((),),
+		UNSUPPORTED => {
+			SUPPORTED.fetch_and(!service, Ordering::AcqRel,);
+			Err(oso_err!(EfiError::Unsupported),)
+		},
+		NOT_FOUND => Err(oso_err!(EfiError::NotFound),),
+		BUFFER_TOO_SMALL => {
+			Err(oso_err!(EfiError::BufferTooSmall { required: 0 }),)
+		},
+		INVALID_PARAMETER => Err(oso_err!(EfiError::InvalidParameter),),
+		status => Err(oso_err!(EfiError::Firmware { status }),),
+	}
+}
+
+/// `EFI_RUNTIME_SERVICES`
+#[repr(C)]
+struct RuntimeServices {
+	_header:                        [u8; 24],
+	get_time:                       unsafe extern "efiapi" fn(
+		time: *mut Time,
+		capabilities: *mut u8,
+	) -> usize,
+	set_time:
+		unsafe extern "efiapi" fn(time: *const Time,) -> usize,
+	_get_wakeup_time:               usize,
+	_set_wakeup_time:               usize,
+	_set_virtual_address_map:       usize,
+	_convert_pointer:               usize,
+	get_variable:                   unsafe extern "efiapi" fn(
+		name: *const u16,
+		vendor: *const Guid,
+		attributes: *mut u32,
+		size: *mut usize,
+		data: *mut u8,
+	) -> usize,
+	_get_next_variable_name:        usize,
+	set_variable:                   unsafe extern "efiapi" fn(
+		name: *const u16,
+		vendor: *const Guid,
+		attributes: u32,
+		size: usize,
+		data: *const u8,
+	) -> usize,
+	_get_next_high_monotonic_count: usize,
+	reset_system:                   unsafe extern "efiapi" fn(
+		kind: u32,
+		status: usize,
+		size: usize,
+		data: *const u8,
+	) -> !,
+	_update_capsule:                usize,
+	_query_capsule_capabilities:    usize,
+	_query_variable_info:           usize,
+}
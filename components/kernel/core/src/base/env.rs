@@ -0,0 +1,252 @@
+//! # Boot Environment
+//!
+//! Read-only `name = value` entries assembled once at boot, so applications
+//! and tests can branch on the boot configuration without parsing the
+//! command line or the device tree again.
+//!
+//! ## Entries
+//!
+//! Names are `namespace.key`:
+//!
+//! - `cmdline.*`: Each `key=value` word of the kernel command line. A word
+//!   without `=` has an empty value
+//! - `board.model`, `board.compatible`: `model` and the first `compatible`
+//!   entry of the device tree root
+//! - `build.version`, `build.arch`, `build.profile`: How the kernel was built
+//! - `boot.hypervisor`: Hypervisor the kernel runs under, or `none`
+//!
+//! Values borrow the command line and the device tree, which the kernel
+//! keeps, so nothing is copied.
+//!
+//! ## Shell
+//!
+//! [`run_command`] implements the `env` shell command:
+//!
+//! - `env`: Prints every entry
+//! - `env <name>`: Prints the entry `name`, or the entries of the namespace
+//!   `name`
+//!
+//! ## Current Status
+//!
+//! The kernel has no system calls yet. [`sys_get`] is the handler a system
+//! call forwards to. The loader has no boot slots, so there is no
+//! `boot.slot` entry.
+//!
+//! ```rust,ignore
+//! unsafe { env::init(boot_info,)? };
+//! if env::get("cmdline.test",).is_some() {
+//! 	run_tests();
+//! }
+//! ```
+
+use super::hypervisor;
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::Ordering;
+use oso_error::Rslt;
+use oso_error::kernel::EnvError;
+use oso_error::oso_err;
+use oso_no_std_shared::bridge::boot_info::BootInfo;
+use oso_no_std_shared::bridge::device_tree::Fdt;
+
+/// Name of the shell command handled by [`run_command`]
+pub const COMMAND: &str = "env";
+/// Entries the environment has room for
+pub const MAX_ENTRIES: usize = 64;
+
+static INITIALIZED: AtomicBool = AtomicBool::new(false,);
+static ENTRIES: Entries = Entries(UnsafeCell::new(Table {
+	entries: [Entry::EMPTY; MAX_ENTRIES],
+	len:     0,
+},),);
+
+/// written once by `init` before `INITIALIZED` is set, then only read
+struct Entries(UnsafeCell<Table,>,);
+
+unsafe impl Sync for Entries {}
+
+struct Table {
+	entries: [Entry; MAX_ENTRIES],
+	len:     usize,
+}
+
+/// Entry of the environment
+///
+/// # Fields
+///
+/// * `namespace` - Part of the name before the first `.`
+/// * `key` - Rest of the name
+/// * `value` - Value, possibly empty
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct Entry {
+	pub namespace: &'static str,
+	pub key:       &'static str,
+	pub value:     &'static str,
+}
+
+impl Entry {
+	const EMPTY: Self = Self { namespace: "", key: "", value: "", };
+
+	/// Whether the name of the entry is `name`
+	pub fn is_named(&self, name: &str,) -> bool {
+		name.split_once('.',) == Some((self.namespace, self.key,),)
+	}
+}
+
+impl fmt::Display for Entry {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		write!(f, "{}.{}={}", self.namespace, self.key, self.value)
+	}
+}
+
+/// Assembles the environment from `boot_info`
+///
+/// Entries which do not fit are dropped, the others stay available.
+///
+/// # Errors
+///
+/// - [`EnvError::AlreadyInitialized`] if called before
+/// - [`EnvError::TooManyEntries`] if more than [`MAX_ENTRIES`] entries were
+///   found
+///
+/// # Safety
+///
+/// The command line and device tree of `boot_info` must be valid and stay
+/// in place while the kernel runs
+pub unsafe fn init(boot_info: &BootInfo,) -> Rslt<(), EnvError,> {
+	if INITIALIZED.load(Ordering::Acquire,) {
+		return Err(oso_err!(EnvError::AlreadyInitialized),);
+	}
+	let table = unsafe { &mut *ENTRIES.0.get() };
+	let mut push = |namespace, key, value| {
+		let fits = table.len < MAX_ENTRIES;
+		if fits {
+			table.entries[table.len] = Entry { namespace, key, value, };
+			table.len += 1;
+		}
+		fits
+	};
+	let mut fits = true;
+
+	fits &= push("build", "version", env!("CARGO_PKG_VERSION"),);
+	fits &= push("build", "arch", ARCH,);
+	fits &= push("build", "profile", PROFILE,);
+	let hypervisor = unsafe { hypervisor::detect(boot_info.device_tree,) };
+	fits &= push("boot", "hypervisor", hypervisor.name(),);
+
+	if let Some(root,) = unsafe { Fdt::from_addr(boot_info.device_tree,) }
+		.and_then(|fdt| fdt.root(),)
+	{
+		let model = root.property("model",).and_then(c_str,);
+		if let Some(model,) = model {
+			fits &= push("board", "model", model,);
+		}
+		if let Some(compatible,) = root.compatible().next() {
+			fits &= push("board", "compatible", compatible,);
+		}
+	}
+
+	let cmdline: &'static str = unsafe { boot_info.cmdline.as_str() };
+	for word in cmdline.split_whitespace() {
+		let (key, value,) = word.split_once('=',).unwrap_or((word, "",),);
+		fits &= push("cmdline", key, value,);
+	}
+
+	INITIALIZED.store(true, Ordering::Release,);
+	if !fits {
+		return Err(oso_err!(EnvError::TooManyEntries {
+			capacity: MAX_ENTRIES
+		}),);
+	}
+	Ok((),)
+}
+
+/// Every entry in the order it was added. Empty before [`init`]
+pub fn entries() -> impl Iterator<Item = Entry,> {
+	let table: &[Entry] = if INITIALIZED.load(Ordering::Acquire,) {
+		let table = unsafe { &*ENTRIES.0.get() };
+		&table.entries[..table.len]
+	} else {
+		&[]
+	};
+	table.iter().copied()
+}
+
+/// Value of the entry `name`
+pub fn get(name: &str,) -> Option<&'static str,> {
+	entries().find(|entry| entry.is_named(name,),).map(|entry| entry.value,)
+}
+
+/// Handler of the system call reading an entry
+///
+/// Copies the value of the entry `name` into `buf` and returns its length.
+///
+/// # Errors
+///
+/// - [`EnvError::NotFound`] if there is no entry `name`, or `name` is not
+///   UTF-8
+/// - [`EnvError::BufferTooSmall`] with the length of the value if it does
+///   not fit `buf`
+pub fn sys_get(name: &[u8], buf: &mut [u8],) -> Rslt<usize, EnvError,> {
+	let value = core::str::from_utf8(name,)
+		.ok()
+		.and_then(get,)
+		.ok_or(oso_err!(EnvError::NotFound),)?;
+	let len = value.len();
+	let Some(dst,) = buf.get_mut(..len,) else {
+		return Err(oso_err!(EnvError::BufferTooSmall { len }),);
+	};
+	dst.copy_from_slice(value.as_bytes(),);
+	Ok(len,)
+}
+
+/// Runs the `env` shell command with the arguments after its name
+pub fn run_command(
+	args: &[&str],
+	out: &mut impl fmt::Write,
+) -> Rslt<(), EnvError,> {
+	match args {
+		[] => {
+			for entry in entries() {
+				let _ = writeln!(out, "{entry}");
+			}
+		},
+		[name,] => {
+			let mut found = false;
+			for entry in entries() {
+				if entry.is_named(name,) || entry.namespace == *name {
+					found = true;
+					let _ = writeln!(out, "{entry}");
+				}
+			}
+			if !found {
+				return Err(oso_err!(EnvError::NotFound),);
+			}
+		},
+		_ => {
+			let _ = writeln!(out, "usage: {COMMAND} [name | namespace]");
+			return Err(oso_err!(EnvError::Usage),);
+		},
+	}
+	Ok((),)
+}
+
+const ARCH: &str = if cfg!(target_arch = "aarch64") {
+	"aarch64"
+} else if cfg!(target_arch = "x86_64") {
+	"x86_64"
+} else if cfg!(target_arch = "riscv64") {
+	"riscv64"
+} else {
+	"unknown"
+};
+
+const PROFILE: &str =
+	if cfg!(debug_assertions) { "debug" } else { "release" };
+
+/// String property up to its terminating NUL
+fn c_str(bytes: &'static [u8],) -> Option<&'static str,> {
+	let bytes = bytes.split(|b| *b == 0,).next()?;
+	core::str::from_utf8(bytes,).ok()
+}
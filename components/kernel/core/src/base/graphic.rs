@@ -41,22 +41,57 @@
 //! let bottom_right = Coord::new(50, 30);
 //! FRAME_BUFFER.fill_rectangle(&top_left, &bottom_right, &color)?;
 //! ```
+//!
+//! ## Boot Framebuffer
+//!
+//! Firmware draws on the framebuffer until the loader exits boot services.
+//! The loader then records the final mode in `BootInfo`, blanks the screen
+//! and reports the buffer as a [`Framebuffer`] region, so no allocator hands
+//! its pages out. [`claim_boot_framebuffer`] checks this
+//! and makes the kernel the single owner of the buffer, mapped
+//! [`WriteCombining`](paging::MemoryAttribute::WriteCombining) so stores to
+//! the screen are merged into bursts.
+//!
+//! [`Framebuffer`]: oso_no_std_shared::bridge::boot_info::MemoryRegionKind::Framebuffer
 
+use crate::base::graphic::blit::Blend;
+use crate::base::graphic::blit::Blitter;
+use crate::base::graphic::blit::Decode;
+use crate::base::graphic::blit::Encode;
 use crate::base::graphic::color::ColorRpr;
 use crate::base::graphic::color::PixelFormat;
 use crate::base::graphic::position::Coord;
 use crate::base::graphic::position::Coordinal;
+use crate::base::paging;
+use crate::base::paging::MemoryAttribute;
 #[cfg(feature = "bgr")] use color::Bgr;
 #[cfg(feature = "bitmask")] use color::Bitmask;
 #[cfg(feature = "bltonly")] use color::BltOnly;
 #[cfg(feature = "rgb")] use color::Rgb;
+use core::ops::Range;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
 use oso_error::Rslt;
 use oso_error::kernel::GraphicError;
 use oso_error::oso_err;
+use oso_no_std_shared::bridge::boot_info::BootInfo;
+use oso_no_std_shared::bridge::boot_info::MemoryRegionKind;
+use oso_no_std_shared::bridge::graphic::FrameBufConf;
+use oso_no_std_shared::geometry::Point;
+use oso_no_std_shared::geometry::Rect;
+use oso_no_std_shared::geometry::Size;
+use oso_no_std_shared::mem;
 // use oso_proc_macro::gen_wrapper_fn;
 
+/// Conversion of images in other formats onto the framebuffer
+pub mod blit;
+/// Screen captures as QOI images
+pub mod capture;
 /// Color representation and pixel format implementations
 pub mod color;
+/// Glyphs of the console font expanded into pixels
+pub use oso_no_std_shared::text::glyph;
 /// Coordinate system and position management
 pub mod position;
 
@@ -95,11 +130,11 @@ pub mod position;
 #[cfg(feature = "rgb")]
 pub static FRAME_BUFFER: FrameBuffer<Rgb,> = FrameBuffer {
 	drawer: Rgb,
-	buf:    0,
-	size:   0,
-	width:  0,
-	height: 0,
-	stride: 0,
+	buf:    AtomicUsize::new(0,),
+	size:   AtomicUsize::new(0,),
+	width:  AtomicUsize::new(0,),
+	height: AtomicUsize::new(0,),
+	stride: AtomicUsize::new(0,),
 };
 
 /// Global framebuffer instance for BGR pixel format
@@ -115,11 +150,11 @@ pub static FRAME_BUFFER: FrameBuffer<Rgb,> = FrameBuffer {
 #[cfg(feature = "bgr")]
 pub static FRAME_BUFFER: FrameBuffer<Bgr,> = FrameBuffer {
 	drawer: Bgr,
-	buf:    0,
-	size:   0,
-	width:  0,
-	height: 0,
-	stride: 0,
+	buf:    AtomicUsize::new(0,),
+	size:   AtomicUsize::new(0,),
+	width:  AtomicUsize::new(0,),
+	height: AtomicUsize::new(0,),
+	stride: AtomicUsize::new(0,),
 };
 
 /// Global framebuffer instance for Bitmask pixel format
@@ -135,11 +170,11 @@ pub static FRAME_BUFFER: FrameBuffer<Bgr,> = FrameBuffer {
 #[cfg(feature = "bitmask")]
 pub static FRAME_BUFFER: FrameBuffer<Bitmask,> = FrameBuffer {
 	drawer: Bitmask,
-	buf:    0,
-	size:   0,
-	width:  0,
-	height: 0,
-	stride: 0,
+	buf:    AtomicUsize::new(0,),
+	size:   AtomicUsize::new(0,),
+	width:  AtomicUsize::new(0,),
+	height: AtomicUsize::new(0,),
+	stride: AtomicUsize::new(0,),
 };
 
 /// Global framebuffer instance for BLT-only pixel format
@@ -155,13 +190,92 @@ pub static FRAME_BUFFER: FrameBuffer<Bitmask,> = FrameBuffer {
 #[cfg(feature = "bltonly")]
 pub static FRAME_BUFFER: FrameBuffer<BltOnly,> = FrameBuffer {
 	drawer: BltOnly,
-	buf:    0,
-	size:   0,
-	width:  0,
-	height: 0,
-	stride: 0,
+	buf:    AtomicUsize::new(0,),
+	size:   AtomicUsize::new(0,),
+	width:  AtomicUsize::new(0,),
+	height: AtomicUsize::new(0,),
+	stride: AtomicUsize::new(0,),
 };
 
+/// whether [`claim_boot_framebuffer`] succeeded
+static BOOT_FRAMEBUFFER_CLAIMED: AtomicBool = AtomicBool::new(false,);
+
+/// Framebuffer the boot loader handed over, owned by the kernel
+///
+/// There is at most one per boot. Dropping it does not return the buffer to
+/// the loader or firmware.
+#[derive(Debug,)]
+pub struct BootFramebuffer {
+	conf: FrameBufConf,
+}
+
+impl BootFramebuffer {
+	/// Mode the loader left the display in, at the mapped address
+	pub fn conf(&self,) -> &FrameBufConf {
+		&self.conf
+	}
+
+	/// Address range of the buffer
+	pub fn range(&self,) -> Range<u64,> {
+		let base = self.conf.base as u64;
+		base..base + self.conf.size as u64
+	}
+}
+
+/// Takes ownership of the framebuffer in `boot_info`
+///
+/// # Errors
+///
+/// - [`GraphicError::NoFramebuffer`] if the boot loader handed over none
+/// - [`GraphicError::AlreadyClaimed`] if called before
+/// - [`GraphicError::NotReserved`] if the memory map reports part of the
+///   buffer as usable memory, which would let an allocator hand it out
+/// - [`GraphicError::Paging`] if the buffer can not be mapped write-combined
+///
+/// # Safety
+///
+/// The framebuffer configuration and the memory map of `boot_info` must be
+/// valid
+pub unsafe fn claim_boot_framebuffer(
+	boot_info: &BootInfo,
+) -> Rslt<BootFramebuffer, GraphicError,> {
+	let Some(conf,) = (unsafe { boot_info.framebuffer() }) else {
+		return Err(oso_err!(GraphicError::NoFramebuffer),);
+	};
+	let base = conf.base as u64;
+	let range = base..base + conf.size as u64;
+	let regions = unsafe { boot_info.memory_map.as_slice() };
+	let handed_out = regions.iter().any(|region| {
+		region.kind.is_usable()
+			&& region.read_phys_start() < range.end
+			&& range.start < region.phys_end()
+	},);
+	if handed_out {
+		return Err(oso_err!(GraphicError::NotReserved),);
+	}
+
+	if BOOT_FRAMEBUFFER_CLAIMED.swap(true, Ordering::AcqRel,) {
+		return Err(oso_err!(GraphicError::AlreadyClaimed),);
+	}
+	let attr = MemoryAttribute::of_region(MemoryRegionKind::Framebuffer,);
+	let mapped = paging::map_device(range.clone(), attr,)?;
+	debug_assert_eq!(
+		paging::attribute_of(range,),
+		Some(MemoryAttribute::WriteCombining,),
+		"framebuffer must be mapped write-combined",
+	);
+
+	let conf = FrameBufConf {
+		pixel_format: conf.pixel_format,
+		base:         mapped,
+		size:         conf.size,
+		width:        conf.width,
+		height:       conf.height,
+		stride:       conf.stride,
+	};
+	Ok(BootFramebuffer { conf, },)
+}
+
 /// Trait for drawing operations on display devices
 ///
 /// This trait defines the core drawing operations that can be performed on a
@@ -213,7 +327,7 @@ pub trait DisplayDraw {
 	///
 	/// ```rust,ignore
 	/// let coord = Coord::new(100, 50);
-	/// let color = Rgb::new(255, 0, 0); // Red pixel
+	/// let color = Color::RED;
 	/// framebuffer.put_pixel(&coord, &color)?;
 	/// ```
 	fn put_pixel(
@@ -224,76 +338,56 @@ pub trait DisplayDraw {
 
 	/// Fills a rectangular area with the specified color
 	///
-	/// This method fills all pixels within the rectangle defined by the
-	/// top-left and bottom-right coordinates (inclusive) with the given color.
+	/// The rectangle is clipped to the display, so parts of it outside the
+	/// display are not drawn.
 	///
 	/// # Arguments
 	///
-	/// * `left_top` - The top-left corner coordinate of the rectangle
-	/// * `right_bottom` - The bottom-right corner coordinate of the rectangle
+	/// * `rect` - The area to fill
 	/// * `color` - The color to fill the rectangle with
 	///
 	/// # Returns
 	///
-	/// * `Ok(())` - If the rectangle was successfully filled
-	/// * `Err(GraphicError::InvalidCoordinate)` - If the coordinates are
-	///   invalid
-	///
-	/// # Coordinate Requirements
-	///
-	/// The coordinates must satisfy the following conditions:
-	/// - `left_top.x < right_bottom.x && left_top.y < right_bottom.y`
-	/// - `right_bottom.x <= framebuffer.width && right_bottom.y <=
-	///   framebuffer.height`
+	/// * `Ok(())` - If the visible part of the rectangle was filled
 	///
 	/// # Examples
 	///
 	/// ```rust,ignore
-	/// let top_left = Coord::new(10, 10);
-	/// let bottom_right = Coord::new(50, 30);
-	/// let color = Rgb::new(0, 255, 0); // Green rectangle
-	/// framebuffer.fill_rectangle(&top_left, &bottom_right, &color)?;
+	/// let rect = Rect::new(Point::new(10, 10,), Size::new(40, 20,),);
+	/// let color = color!("#00ff00");
+	/// framebuffer.fill_rectangle(&rect, &color)?;
 	/// ```
 	fn fill_rectangle(
 		&self,
-		left_top: &impl Coordinal,
-		right_bottom: &impl Coordinal,
+		rect: &Rect,
 		color: &impl ColorRpr,
 	) -> Self::Output;
 
 	/// Draws the outline of a rectangle with the specified color
 	///
 	/// This method draws only the border of the rectangle, leaving the interior
-	/// unchanged. The outline is drawn as a single-pixel-wide border.
+	/// unchanged. The outline is drawn as a single-pixel-wide border on the
+	/// outermost pixels of `rect`, clipped to the display.
 	///
 	/// # Arguments
 	///
-	/// * `left_top` - The top-left corner coordinate of the rectangle
-	/// * `right_bottom` - The bottom-right corner coordinate of the rectangle
+	/// * `rect` - The area whose border is drawn
 	/// * `color` - The color for the rectangle outline
 	///
 	/// # Returns
 	///
-	/// * `Ok(())` - If the outline was successfully drawn
-	/// * `Err(GraphicError::InvalidCoordinate)` - If the coordinates are
-	///   invalid
-	///
-	/// # Coordinate Requirements
-	///
-	/// Same requirements as `fill_rectangle()`.
+	/// * `Ok(())` - If the visible part of the outline was drawn
 	///
 	/// # Examples
 	///
 	/// ```rust,ignore
-	/// let top_left = Coord::new(20, 20);
-	/// let bottom_right = Coord::new(80, 60);
-	/// let color = Rgb::new(0, 0, 255); // Blue outline
-	/// framebuffer.outline_rectangle(&top_left, &bottom_right, &color)?;
+	/// let rect = Rect::from_inclusive(Point::new(20, 20,), Point::new(80, 60,),);
+	/// let color = color!("blue");
+	/// framebuffer.outline_rectangle(&rect, &color)?;
 	/// ```
 	fn outline_rectangle(
 		&self,
-		left_top: &impl Coordinal,
-		right_bottom: &impl Coordinal,
+		rect: &Rect,
 		color: &impl ColorRpr,
 	) -> Self::Output;
 }
@@ -350,15 +444,15 @@ pub struct FrameBuffer<P: PixelFormat,> {
 	/// The pixel format handler for color operations
 	pub drawer: P,
 	/// Base address of the framebuffer memory (as usize for arithmetic)
-	pub buf:    usize,
+	buf:        AtomicUsize,
 	/// Total size of the framebuffer in bytes
-	pub size:   usize,
+	size:       AtomicUsize,
 	/// Display width in pixels
-	pub width:  usize,
+	width:      AtomicUsize,
 	/// Display height in pixels
-	pub height: usize,
+	height:     AtomicUsize,
 	/// Number of bytes per scanline (including any padding)
-	pub stride: usize,
+	stride:     AtomicUsize,
 }
 
 impl<P: PixelFormat,> FrameBuffer<P,> {
@@ -402,20 +496,19 @@ impl<P: PixelFormat,> FrameBuffer<P,> {
 
 		let conf = A { base: 0, width: 0, height: 0, stride: 0, size: 0, };
 
-		let buf = conf.base;
-		let width = conf.width;
-		let height = conf.height;
-		let stride = conf.stride;
-		let size = conf.size;
+		let buf = AtomicUsize::new(conf.base,);
+		let width = AtomicUsize::new(conf.width,);
+		let height = AtomicUsize::new(conf.height,);
+		let stride = AtomicUsize::new(conf.stride,);
+		let size = AtomicUsize::new(conf.size,);
 
 		Self { drawer: pxl_fmt, buf, width, height, stride, size, }
 	}
 
 	/// Initializes a framebuffer instance with hardware-specific parameters
 	///
-	/// This method provides interior mutability for static framebuffer
-	/// instances by allowing modification of the framebuffer parameters after
-	/// creation. It's typically called during kernel initialization when
+	/// The parameters are atomic, so static framebuffer instances, which may
+	/// be placed in read-only memory otherwise, are modified after creation. It's typically called during kernel initialization when
 	/// hardware parameters become available.
 	///
 	/// # Arguments
@@ -430,9 +523,9 @@ impl<P: PixelFormat,> FrameBuffer<P,> {
 	/// # Safety
 	///
 	/// This method is unsafe because:
-	/// - It performs raw pointer manipulation to achieve interior mutability
 	/// - It assumes the provided pointer is valid and properly aligned
-	/// - It doesn't provide synchronization for concurrent access
+	/// - It doesn't keep drawing on other cores from seeing a mix of old and
+	///   new parameters
 	/// - The caller must ensure the memory parameters are valid
 	///
 	/// # Examples
@@ -465,14 +558,38 @@ impl<P: PixelFormat,> FrameBuffer<P,> {
 		height: usize,
 		stride: usize,
 	) {
-		unsafe {
-			let this = this as *mut Self;
-			(*this).buf = buf;
-			(*this).size = size;
-			(*this).width = width;
-			(*this).height = height;
-			(*this).stride = stride;
-		}
+		// the fields are atomic, so statics are written without a `&mut`
+		let this = unsafe { &*this };
+		this.buf.store(buf, Ordering::Relaxed,);
+		this.size.store(size, Ordering::Relaxed,);
+		this.width.store(width, Ordering::Relaxed,);
+		this.height.store(height, Ordering::Relaxed,);
+		this.stride.store(stride, Ordering::Relaxed,);
+	}
+
+	/// Base address of the framebuffer memory
+	pub fn buf(&self,) -> usize {
+		self.buf.load(Ordering::Relaxed,)
+	}
+
+	/// Size of the framebuffer memory in bytes
+	pub fn size(&self,) -> usize {
+		self.size.load(Ordering::Relaxed,)
+	}
+
+	/// Display width in pixels
+	pub fn width(&self,) -> usize {
+		self.width.load(Ordering::Relaxed,)
+	}
+
+	/// Display height in pixels
+	pub fn height(&self,) -> usize {
+		self.height.load(Ordering::Relaxed,)
+	}
+
+	/// Pixels per scanline, including padding
+	pub fn stride(&self,) -> usize {
+		self.stride.load(Ordering::Relaxed,)
 	}
 
 	/// Calculates the byte offset for a pixel at the given coordinate
@@ -509,7 +626,7 @@ impl<P: PixelFormat,> FrameBuffer<P,> {
 	/// ```
 	fn pos(&self, coord: &impl Coordinal,) -> usize {
 		// Each pixel is 4 bytes (32 bits), so multiply by 4
-		(self.stride * coord.y() + coord.x()) * 4
+		(self.stride() * coord.y() + coord.x()) * 4
 	}
 
 	/// Returns the coordinate of the bottom-right corner of the display
@@ -540,7 +657,14 @@ impl<P: PixelFormat,> FrameBuffer<P,> {
 	/// }
 	/// ```
 	pub fn right_bottom(&self,) -> Coord {
-		Coord { x: self.width - 1, y: self.height - 1, }
+		Coord { x: self.width() - 1, y: self.height() - 1, }
+	}
+
+	/// Size of the display in pixels
+	///
+	/// Drawing primitives clip their rectangles to it.
+	pub fn resolution(&self,) -> Size {
+		Size::new(self.width(), self.height(),)
 	}
 
 	/// Creates a mutable slice to framebuffer memory at the specified position
@@ -584,11 +708,119 @@ impl<P: PixelFormat,> FrameBuffer<P,> {
 	/// ```
 	pub fn slice_mut(&self, pos: usize, len: usize,) -> &mut [u8] {
 		let pos = pos * size_of::<u8,>();
-		assert!(self.size - pos > 0);
+		assert!(self.size() - pos > 0);
 
-		let data_at_pos = self.buf + pos;
+		let data_at_pos = self.buf() + pos;
 		unsafe { core::slice::from_raw_parts_mut(data_at_pos as *mut u8, len,) }
 	}
+
+	/// `len` pixels starting at the byte position `pos`, as
+	/// [`Self::slice_mut`]
+	fn pixels_mut(&self, pos: usize, len: usize,) -> &mut [u32] {
+		let bytes = self.slice_mut(pos, len * 4,).as_mut_ptr();
+		unsafe { core::slice::from_raw_parts_mut(bytes as *mut u32, len,) }
+	}
+
+	/// Value of a pixel of `color` in the pixel format of the framebuffer
+	pub fn pixel(&self, color: &impl ColorRpr,) -> u32 {
+		let [first, second, third,] = self.drawer.color_repr(color,);
+		// the fourth byte of a pixel is reserved
+		u32::from_le_bytes([first, second, third, 0,],)
+	}
+
+	/// Copies the image `pixels`, rows of `width` pixels, to `origin`
+	///
+	/// The image is clipped to the display.
+	///
+	/// # Examples
+	///
+	/// ```rust,ignore
+	/// let pixels = glyphs.get(key, bitmap,);
+	/// FRAME_BUFFER.blit(Point::new(8, 16,), GLYPH_WIDTH, pixels,);
+	/// ```
+	pub fn blit(&self, origin: Point, width: usize, pixels: &[u32],) {
+		if width == 0 {
+			return;
+		}
+		let height = pixels.len() / width;
+		let image = Rect::new(origin, Size::new(width, height,),);
+		for span in image.clamp_to(self.resolution(),).spans() {
+			let x = span.start.x - origin.x;
+			let row = (span.start.y - origin.y) * width + x;
+			let pos = self.pos(&span.start,);
+			self.pixels_mut(pos, span.len,)
+				.copy_from_slice(&pixels[row..row + span.len],);
+		}
+	}
+
+	/// Converts the image `texels`, rows of `width` texels, onto the display
+	/// at `origin` through the stages `decode` and `blend` of a [`Blitter`],
+	/// with the pixel format of the framebuffer as encoder
+	///
+	/// The image is clipped to the display, as by [`Self::blit`].
+	///
+	/// # Examples
+	///
+	/// ```rust,ignore
+	/// // RGBA pixels of a decoded PNG, blended over the screen
+	/// FRAME_BUFFER.blit_with(Rgba8, Over, Point::new(0, 0,), 64, &icon,);
+	/// ```
+	pub fn blit_with<D: Decode, B: Blend,>(
+		&self,
+		decode: D,
+		blend: B,
+		origin: Point,
+		width: usize,
+		texels: &[D::Texel],
+	) where
+		P: Encode + Copy,
+	{
+		if width == 0 {
+			return;
+		}
+		let blitter = Blitter::new(decode, blend, self.drawer,);
+		let height = texels.len() / width;
+		let image = Rect::new(origin, Size::new(width, height,),);
+		for span in image.clamp_to(self.resolution(),).spans() {
+			let x = span.start.x - origin.x;
+			let row = (span.start.y - origin.y) * width + x;
+			let pos = self.pos(&span.start,);
+			let pixels = self.pixels_mut(pos, span.len,);
+			blitter.row(&texels[row..row + span.len], pixels,);
+		}
+	}
+
+	/// Moves the display up by `lines` pixel rows and fills the rows
+	/// uncovered at the bottom with `color`
+	///
+	/// Rows are moved with [`mem::copy_rows`], so only the visible part of
+	/// each scanline is copied.
+	///
+	/// # Examples
+	///
+	/// ```rust,ignore
+	/// // scroll a text console by a line of 16 pixels
+	/// FRAME_BUFFER.scroll_up(16, &Color::BLACK,)?;
+	/// ```
+	pub fn scroll_up(
+		&self,
+		lines: usize,
+		color: &impl ColorRpr,
+	) -> Rslt<(), GraphicError,> {
+		let Size { width, height, } = self.resolution();
+		if height == 0 || lines == 0 {
+			return Ok((),);
+		}
+		let lines = lines.min(height,);
+		let kept = height - lines;
+		if kept != 0 {
+			let stride = self.stride() * 4;
+			let buf = self.slice_mut(0, self.size(),);
+			mem::copy_rows(buf, stride, width * 4, lines, 0, kept,);
+		}
+		let bottom = Size::new(width, lines,);
+		self.fill_rectangle(&Rect::new(Point::new(0, kept,), bottom,), color,)
+	}
 }
 
 impl<P: PixelFormat,> DisplayDraw for FrameBuffer<P,> {
@@ -642,29 +874,18 @@ impl<P: PixelFormat,> DisplayDraw for FrameBuffer<P,> {
 
 	/// Fills a rectangular area with the specified color
 	///
-	/// This implementation validates the coordinates and then iterates through
-	/// all pixels within the rectangle, setting each one to the specified
-	/// color. The color conversion is performed once before the loop for
-	/// efficiency.
+	/// This implementation clips the rectangle to the display and writes it
+	/// span by span with [`mem::fill32`]. The color conversion is performed
+	/// once before the loop for efficiency.
 	///
 	/// # Arguments
 	///
-	/// * `left_top` - The top-left corner of the rectangle
-	/// * `right_bottom` - The bottom-right corner of the rectangle
+	/// * `rect` - The area to fill
 	/// * `color` - The color to fill the rectangle with
 	///
 	/// # Returns
 	///
-	/// * `Ok(())` - If the rectangle was successfully filled
-	/// * `Err(GraphicError::InvalidCoordinate)` - If coordinates are invalid
-	///
-	/// # Coordinate Validation
-	///
-	/// The method validates that:
-	/// - `left_top.x() <= right_bottom.x()`
-	/// - `left_top.y() <= right_bottom.y()`
-	/// - `right_bottom.x() < self.width`
-	/// - `right_bottom.y() < self.height`
+	/// * `Ok(())` - If the visible part of the rectangle was filled
 	///
 	/// # Performance Optimization
 	///
@@ -674,43 +895,21 @@ impl<P: PixelFormat,> DisplayDraw for FrameBuffer<P,> {
 	/// # Examples
 	///
 	/// ```rust,ignore
-	/// let top_left = Coord::new(10, 10);
-	/// let bottom_right = Coord::new(50, 30);
-	/// let blue_color = Rgb::new(0, 0, 255);
-	/// framebuffer.fill_rectangle(&top_left, &bottom_right, &blue_color)?;
+	/// let rect = Rect::new(Point::new(10, 10,), Size::new(40, 20,),);
+	/// framebuffer.fill_rectangle(&rect, &Color::BLUE)?;
 	/// ```
 	fn fill_rectangle(
 		&self,
-		left_top: &impl Coordinal,
-		right_bottom: &impl Coordinal,
+		rect: &Rect,
 		color: &impl ColorRpr,
 	) -> Self::Output {
-		// Validate coordinate bounds
-		if left_top.x() > right_bottom.x()
-			|| left_top.y() > right_bottom.y()
-			|| right_bottom.x() > self.width
-			|| right_bottom.y() > self.height
-		{
-			return Err(oso_err!(GraphicError::InvalidCoordinate),);
-		}
-
 		// Convert color once for performance optimization
 		// This reduces pixel format determination to just once per rectangle
-		let color = self.drawer.color_repr(color,);
-		let mut coord = (left_top.x(), left_top.y(),);
-
-		// Fill rectangle row by row
-		for _ in left_top.y()..=right_bottom.y() {
-			for _ in left_top.x()..=right_bottom.x() {
-				let pos = self.pos(&coord,);
-				let pxl = self.slice_mut(pos, 3,);
-				pxl[0] = color[0];
-				pxl[1] = color[1];
-				pxl[2] = color[2];
-				coord.0 += 1;
-			}
-			coord.1 += 1;
-			coord.0 = left_top.x();
+		let pixel = self.pixel(color,);
+
+		for span in rect.clamp_to(self.resolution(),).spans() {
+			let pos = self.pos(&span.start,);
+			mem::fill32(self.pixels_mut(pos, span.len,), pixel,);
 		}
 
 		Ok((),)
@@ -718,104 +917,42 @@ impl<P: PixelFormat,> DisplayDraw for FrameBuffer<P,> {
 
 	/// Draws the outline of a rectangle with the specified color
 	///
-	/// This implementation draws a single-pixel-wide border around the
-	/// rectangle defined by the coordinates. It draws four lines: top, right,
-	/// bottom, and left.
+	/// This implementation fills four rectangles of one pixel width along the
+	/// edges of `rect`: top, bottom, left and right. Each is clipped to the
+	/// display by [`Self::fill_rectangle`].
 	///
 	/// # Arguments
 	///
-	/// * `left_top` - The top-left corner of the rectangle
-	/// * `right_bottom` - The bottom-right corner of the rectangle
+	/// * `rect` - The area whose border is drawn
 	/// * `color` - The color for the rectangle outline
 	///
 	/// # Returns
 	///
-	/// * `Ok(())` - If the outline was successfully drawn
-	/// * `Err(GraphicError::InvalidCoordinate)` - If coordinates are invalid
-	///
-	/// # Drawing Algorithm
-	///
-	/// The outline is drawn in four phases:
-	/// 1. **Top line**: From left_top to (right_bottom.x, left_top.y)
-	/// 2. **Right line**: From (right_bottom.x, left_top.y) to right_bottom
-	/// 3. **Bottom line**: From right_bottom to (left_top.x, right_bottom.y)
-	/// 4. **Left line**: From (left_top.x, right_bottom.y) to left_top
-	///
-	/// # Performance Optimization
-	///
-	/// Like `fill_rectangle`, the color conversion is performed once before
-	/// drawing to avoid repeated conversions.
+	/// * `Ok(())` - If the visible part of the outline was drawn
 	///
 	/// # Examples
 	///
 	/// ```rust,ignore
-	/// let top_left = Coord::new(20, 20);
-	/// let bottom_right = Coord::new(80, 60);
-	/// let green_color = Rgb::new(0, 255, 0);
-	/// framebuffer.outline_rectangle(&top_left, &bottom_right, &green_color)?;
+	/// let rect = Rect::from_inclusive(Point::new(20, 20,), Point::new(80, 60,),);
+	/// framebuffer.outline_rectangle(&rect, &Color::GREEN)?;
 	/// ```
 	fn outline_rectangle(
 		&self,
-		left_top: &impl Coordinal,
-		right_bottom: &impl Coordinal,
+		rect: &Rect,
 		color: &impl ColorRpr,
 	) -> Self::Output {
-		// Validate coordinate bounds
-		if left_top.x() > right_bottom.x()
-			|| left_top.y() > right_bottom.y()
-			|| right_bottom.x() > self.width
-			|| right_bottom.y() > self.height
-		{
-			return Err(oso_err!(GraphicError::InvalidCoordinate),);
-		}
-
-		let width = right_bottom.x() - left_top.x() - 1;
-		let height = right_bottom.y() - left_top.y() - 1;
-
-		// Convert color once for performance
-		let color = self.drawer.color_repr(color,);
-		let mut coord = (left_top.x(), left_top.y(),);
-
-		// Draw top horizontal line
-		for _ in 0..width {
-			let pos = self.pos(&coord,);
-			let pxl = self.slice_mut(pos, 3,);
-			pxl[0] = color[0];
-			pxl[1] = color[1];
-			pxl[2] = color[2];
-			coord.0 += 1;
-		}
-
-		// Draw right vertical line
-		for _ in 0..height {
-			let pos = self.pos(&coord,);
-			let pxl = self.slice_mut(pos, 3,);
-			pxl[0] = color[0];
-			pxl[1] = color[1];
-			pxl[2] = color[2];
-			coord.1 += 1;
-		}
-
-		// Draw bottom horizontal line
-		for _ in 0..width {
-			let pos = self.pos(&coord,);
-			let pxl = self.slice_mut(pos, 3,);
-			pxl[0] = color[0];
-			pxl[1] = color[1];
-			pxl[2] = color[2];
-			coord.0 -= 1;
-		}
-
-		// Draw left vertical line
-		for _ in 0..height {
-			let pos = self.pos(&coord,);
-			let pxl = self.slice_mut(pos, 3,);
-			pxl[0] = color[0];
-			pxl[1] = color[1];
-			pxl[2] = color[2];
-			coord.1 -= 1;
-		}
-
-		Ok((),)
+		let Some(right_bottom,) = rect.right_bottom() else {
+			return Ok((),);
+		};
+		let Size { width, height, } = rect.size;
+		let horizontal = Size::new(width, 1,);
+		let vertical = Size::new(1, height,);
+
+		self.fill_rectangle(&Rect::new(rect.origin, horizontal,), color,)?;
+		let bottom = Point::new(rect.left(), right_bottom.y,);
+		self.fill_rectangle(&Rect::new(bottom, horizontal,), color,)?;
+		self.fill_rectangle(&Rect::new(rect.origin, vertical,), color,)?;
+		let right = Point::new(right_bottom.x, rect.top(),);
+		self.fill_rectangle(&Rect::new(right, vertical,), color,)
 	}
 }
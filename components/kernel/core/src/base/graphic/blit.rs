@@ -0,0 +1,370 @@
+//! # Blit Pipeline
+//!
+//! Images reach the screen in formats other than the one of the framebuffer:
+//! RGBA pixels decoded from PNG and QOI assets, and coverage masks of font
+//! glyphs. [`Blitter`] converts them in three stages, each a type parameter,
+//! so every combination is compiled into a loop of its own instead of one
+//! loop which picks the conversion at every pixel:
+//!
+//! - [`Decode`]: Source texel to [`Rgba`]. [`Rgba8`] and [`Mask`]
+//! - [`Blend`]: Combines it with the pixel on the screen. [`Replace`], which
+//!   never reads the screen, and [`Over`]
+//! - [`Encode`]: [`Rgba`] to a pixel of the framebuffer. [`Rgb`] and [`Bgr`]
+//!
+//! [`FrameBuffer::blit_with`](super::FrameBuffer::blit_with) runs a blitter
+//! on the framebuffer with the pixel format of the framebuffer as encoder.
+//!
+//! ## Shell
+//!
+//! [`run_command`] implements the `blitbench` shell command:
+//!
+//! - `blitbench [rounds]`: Times drawing an image of [`IMAGE_WIDTH`] by
+//!   [`IMAGE_HEIGHT`] pixels of each source format in each blend mode,
+//!   through a blitter and through the naive per pixel loop it replaces
+//!
+//! ```rust,ignore
+//! // a glyph with anti-aliased edges, coverage from 0 to 255 per pixel
+//! FRAME_BUFFER.blit_with(Mask(Color::WHITE,), Over, origin, 8, &coverage,);
+//!
+//! let blitter = Blitter::new(Rgba8, Replace, Bgr,);
+//! blitter.row(&decoded_png[..width], &mut scanline[..width],);
+//! ```
+
+use super::color::Bgr;
+use super::color::Color;
+use super::color::ColorRpr;
+use super::color::PixelFormat;
+use super::color::Rgb;
+use crate::base::perf;
+use core::fmt;
+use core::hint::black_box;
+use oso_error::Rslt;
+use oso_error::kernel::GraphicError;
+use oso_error::oso_err;
+
+/// Name of the shell command handled by [`run_command`]
+pub const COMMAND: &str = "blitbench";
+/// Rounds timed by `blitbench` without an argument
+pub const DEFAULT_ROUNDS: u32 = 100;
+/// Width of the image drawn by `blitbench`
+pub const IMAGE_WIDTH: usize = 32;
+/// Height of the image drawn by `blitbench`
+pub const IMAGE_HEIGHT: usize = 32;
+
+/// Color with opacity, passed between the stages
+///
+/// `alpha` is 255 for opaque and 0 for transparent.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq,)]
+pub struct Rgba {
+	pub red:   u8,
+	pub green: u8,
+	pub blue:  u8,
+	pub alpha: u8,
+}
+
+impl Rgba {
+	/// Opaque `color`
+	pub const fn opaque(color: Color,) -> Self {
+		let Color { red, green, blue, } = color;
+		Self { red, green, blue, alpha: 255, }
+	}
+}
+
+/// Source format of an image
+pub trait Decode {
+	/// A pixel of the image
+	type Texel: Copy;
+	fn decode(&self, texel: Self::Texel,) -> Rgba;
+}
+
+/// Pixels decoded from PNG and QOI images: red, green, blue and alpha bytes
+#[derive(Debug, Clone, Copy,)]
+pub struct Rgba8;
+impl Decode for Rgba8 {
+	type Texel = [u8; 4];
+
+	fn decode(&self, [red, green, blue, alpha,]: [u8; 4],) -> Rgba {
+		Rgba { red, green, blue, alpha, }
+	}
+}
+
+/// Coverage mask of a glyph, drawn in its color. A texel is the coverage of
+/// a pixel, from 0 for none to 255 for full
+#[derive(Debug, Clone, Copy,)]
+pub struct Mask(pub Color,);
+impl Decode for Mask {
+	type Texel = u8;
+
+	fn decode(&self, coverage: u8,) -> Rgba {
+		Rgba { alpha: coverage, ..Rgba::opaque(self.0,) }
+	}
+}
+
+/// How a decoded pixel is combined with the pixel on the screen
+pub trait Blend {
+	/// Whether [`Self::blend`] uses `dst`. If not, the screen is not read
+	/// and `dst` is transparent black
+	const READS_DST: bool;
+	fn blend(&self, src: Rgba, dst: Rgba,) -> Rgba;
+}
+
+/// Overwrites the screen and ignores the alpha of the source
+#[derive(Debug, Clone, Copy,)]
+pub struct Replace;
+impl Blend for Replace {
+	const READS_DST: bool = false;
+
+	fn blend(&self, src: Rgba, _dst: Rgba,) -> Rgba {
+		src
+	}
+}
+
+/// Draws the source over the screen, weighted by the alpha of the source
+#[derive(Debug, Clone, Copy,)]
+pub struct Over;
+impl Blend for Over {
+	const READS_DST: bool = true;
+
+	fn blend(&self, src: Rgba, dst: Rgba,) -> Rgba {
+		// most pixels of glyphs and icons are fully in or out
+		match src.alpha {
+			255 => return src,
+			0 => return dst,
+			_ => {},
+		}
+		let alpha = src.alpha as u32;
+		let mix =
+			|s: u8, d: u8| div255(s as u32 * alpha + d as u32 * (255 - alpha),);
+		Rgba {
+			red:   mix(src.red, dst.red,),
+			green: mix(src.green, dst.green,),
+			blue:  mix(src.blue, dst.blue,),
+			alpha: 255,
+		}
+	}
+}
+
+/// `x / 255`, rounded, for `x` up to `255 * 255`
+const fn div255(x: u32,) -> u8 {
+	let x = x + 128;
+	((x + (x >> 8)) >> 8) as u8
+}
+
+/// Pixel format of the framebuffer
+///
+/// The fourth byte of a pixel is reserved. It is zero after [`Self::encode`]
+/// and ignored by [`Self::decode`].
+pub trait Encode {
+	fn encode(&self, color: Rgba,) -> u32;
+	fn decode(&self, pixel: u32,) -> Rgba;
+}
+
+impl Encode for Rgb {
+	fn encode(&self, color: Rgba,) -> u32 {
+		u32::from_le_bytes([color.red, color.green, color.blue, 0,],)
+	}
+
+	fn decode(&self, pixel: u32,) -> Rgba {
+		let [red, green, blue, _,] = pixel.to_le_bytes();
+		Rgba { red, green, blue, alpha: 255, }
+	}
+}
+
+impl Encode for Bgr {
+	fn encode(&self, color: Rgba,) -> u32 {
+		u32::from_le_bytes([color.blue, color.green, color.red, 0,],)
+	}
+
+	fn decode(&self, pixel: u32,) -> Rgba {
+		let [blue, green, red, _,] = pixel.to_le_bytes();
+		Rgba { red, green, blue, alpha: 255, }
+	}
+}
+
+/// Decode, blend and encode stages converting images onto the screen
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let blitter = Blitter::new(Mask(Color::WHITE,), Over, Rgb,);
+/// blitter.row(&coverage, &mut pixels,);
+/// ```
+#[derive(Debug, Clone, Copy,)]
+pub struct Blitter<D, B, E,> {
+	pub decode: D,
+	pub blend:  B,
+	pub encode: E,
+}
+
+impl<D: Decode, B: Blend, E: Encode,> Blitter<D, B, E,> {
+	pub const fn new(decode: D, blend: B, encode: E,) -> Self {
+		Self { decode, blend, encode, }
+	}
+
+	/// Converts `src` onto the pixels `dst`, as many as the shorter has
+	#[inline]
+	pub fn row(&self, src: &[D::Texel], dst: &mut [u32],) {
+		for (&texel, pixel,) in src.iter().zip(dst,) {
+			let color = self.decode.decode(texel,);
+			let below = if B::READS_DST {
+				self.encode.decode(*pixel,)
+			} else {
+				Rgba::default()
+			};
+			*pixel = self.encode.encode(self.blend.blend(color, below,),);
+		}
+	}
+}
+
+/// Runs the `blitbench` shell command with the arguments after its name
+pub fn run_command(
+	args: &[&str],
+	out: &mut impl fmt::Write,
+) -> Rslt<(), GraphicError,> {
+	let rounds = match args {
+		[] => DEFAULT_ROUNDS,
+		[rounds,] => match rounds.parse::<u32>() {
+			Ok(rounds,) if rounds > 0 => rounds,
+			_ => return usage(out,),
+		},
+		_ => return usage(out,),
+	};
+
+	let mut rgba = [[0; 4]; IMAGE_PIXELS];
+	let mut coverage = [0; IMAGE_PIXELS];
+	let texels = rgba.iter_mut().zip(&mut coverage,);
+	for (i, (texel, mask,),) in texels.enumerate() {
+		// opaque, transparent and translucent pixels in turn
+		let alpha = [255, 0, 128,][i % 3];
+		*texel = [i as u8, (i >> 2) as u8, 0x80, alpha,];
+		*mask = alpha;
+	}
+	let rgba_bytes = rgba.as_flattened();
+	let white = Color::WHITE;
+
+	let _ = writeln!(
+		out,
+		"{rounds} rounds of {IMAGE_WIDTH}x{IMAGE_HEIGHT} pixels, cycles per \
+		 image"
+	);
+	let _ = writeln!(
+		out,
+		"{:<6} {:<7} {:>10} {:>10}",
+		"source", "blend", "pipeline", "naive"
+	);
+	let mut row = |source: &str, blend: &str, pipeline: u64, naive: u64| {
+		let _ =
+			writeln!(out, "{source:<6} {blend:<7} {pipeline:>10} {naive:>10}");
+	};
+	row(
+		"rgba8",
+		"replace",
+		bench(rounds, &Blitter::new(Rgba8, Replace, Bgr,), &rgba,),
+		bench_naive(rounds, Source::Rgba8, false, rgba_bytes,),
+	);
+	row(
+		"rgba8",
+		"over",
+		bench(rounds, &Blitter::new(Rgba8, Over, Bgr,), &rgba,),
+		bench_naive(rounds, Source::Rgba8, true, rgba_bytes,),
+	);
+	row(
+		"mask",
+		"replace",
+		bench(rounds, &Blitter::new(Mask(white,), Replace, Bgr,), &coverage,),
+		bench_naive(rounds, Source::Mask(white,), false, &coverage,),
+	);
+	row(
+		"mask",
+		"over",
+		bench(rounds, &Blitter::new(Mask(white,), Over, Bgr,), &coverage,),
+		bench_naive(rounds, Source::Mask(white,), true, &coverage,),
+	);
+	Ok((),)
+}
+
+/// pixels of the image drawn by `blitbench`
+const IMAGE_PIXELS: usize = IMAGE_WIDTH * IMAGE_HEIGHT;
+
+/// cycles per image drawn by `blitter`
+fn bench<D: Decode, B: Blend,>(
+	rounds: u32,
+	blitter: &Blitter<D, B, Bgr,>,
+	image: &[D::Texel],
+) -> u64 {
+	let mut screen = [0x0040_2010_u32; IMAGE_PIXELS];
+	let ((), counters,) = perf::measure(|| {
+		for _ in 0..rounds {
+			for (src, dst,) in image
+				.chunks_exact(IMAGE_WIDTH,)
+				.zip(screen.chunks_exact_mut(IMAGE_WIDTH,),)
+			{
+				blitter.row(black_box(src,), black_box(dst,),);
+			}
+		}
+	},);
+	counters.cycles / rounds as u64
+}
+
+/// source format picked at run time, as by the naive loop
+#[derive(Clone, Copy,)]
+enum Source {
+	Rgba8,
+	Mask(Color,),
+}
+
+/// cycles per image drawn by [`naive`]
+fn bench_naive(rounds: u32, source: Source, blend: bool, image: &[u8],) -> u64 {
+	let mut screen = [0x0040_2010_u32; IMAGE_PIXELS];
+	let ((), counters,) = perf::measure(|| {
+		for _ in 0..rounds {
+			let screen = black_box(&mut screen,);
+			naive(&Bgr, source, blend, black_box(image,), screen,);
+		}
+	},);
+	counters.cycles / rounds as u64
+}
+
+/// per pixel conversion the pipeline replaces: the source format and blend
+/// mode are matched, and the color goes through [`PixelFormat`], at every
+/// pixel
+fn naive(
+	format: &impl PixelFormat,
+	source: Source,
+	blend: bool,
+	image: &[u8],
+	screen: &mut [u32],
+) {
+	for (i, pixel,) in screen.iter_mut().enumerate() {
+		let (color, alpha,) = match source {
+			Source::Rgba8 => {
+				let texel = &image[i * 4..i * 4 + 4];
+				(Color::rgb(texel[0], texel[1], texel[2],), texel[3],)
+			},
+			Source::Mask(color,) => (color, image[i],),
+		};
+		let color = if blend {
+			// swapping the channels of the screen back is the same swap
+			let [first, second, third, _,] = pixel.to_le_bytes();
+			let below = format.color_repr(&(first, second, third,),);
+			let alpha = alpha as u32;
+			let mix = |s: u8, d: u8| {
+				((s as u32 * alpha + d as u32 * (255 - alpha)) / 255) as u8
+			};
+			Color::rgb(
+				mix(color.red(), below[0],),
+				mix(color.green(), below[1],),
+				mix(color.blue(), below[2],),
+			)
+		} else {
+			color
+		};
+		let [first, second, third,] = format.color_repr(&color,);
+		*pixel = u32::from_le_bytes([first, second, third, 0,],);
+	}
+}
+
+fn usage(out: &mut impl fmt::Write,) -> Rslt<(), GraphicError,> {
+	let _ = writeln!(out, "usage: blitbench [rounds]");
+	Err(oso_err!(GraphicError::Usage),)
+}
@@ -0,0 +1,185 @@
+//! # Screen Capture
+//!
+//! Encodes the screen as a QOI image, so what the consoles drew can be
+//! compared with golden images by `cargo xtask screenshot` and rendering
+//! regressions are caught without looking at the screen.
+//!
+//! - [`dump`]: Hex lines between [`BEGIN_MARKER`] and [`END_MARKER`], like
+//!   crash dumps, which survive serial consoles and are found in a whole log
+//! - [`save`]: Raw image written to a file of the VFS
+//!
+//! Pixels are read back through [`Encode`], so only framebuffers in the
+//! `rgb` and `bgr` formats are captured.
+//!
+//! ## Shell
+//!
+//! [`run_command`] implements the `screenshot` shell command:
+//!
+//! - `screenshot`: Writes a dump of the screen
+//! - `screenshot <path>`: Writes the image to the file at `path`
+//!
+//! ## Current Status
+//!
+//! No file system mounted by the kernel creates files, so [`save`] only
+//! writes to files which exist, such as block devices under `/dev`, from
+//! their start.
+//!
+//! ```rust,ignore
+//! capture::dump(&mut console,)?;
+//! let written = capture::save("/dev/capture",)?;
+//! ```
+
+#[cfg(any(feature = "rgb", feature = "bgr"))]
+use super::FRAME_BUFFER;
+#[cfg(any(feature = "rgb", feature = "bgr"))] use super::FrameBuffer;
+#[cfg(any(feature = "rgb", feature = "bgr"))] use super::blit::Encode;
+#[cfg(any(feature = "rgb", feature = "bgr"))]
+use super::color::PixelFormat;
+use crate::base::crash::DumpSink;
+use crate::base::crash::HexLines;
+use crate::vfs;
+use crate::vfs::Write;
+use core::fmt;
+use oso_error::Rslt;
+use oso_error::kernel::GraphicError;
+use oso_error::oso_err;
+#[cfg(any(feature = "rgb", feature = "bgr"))]
+use oso_no_std_shared::data::qoi::Encoder;
+#[cfg(any(feature = "rgb", feature = "bgr"))]
+use oso_no_std_shared::data::qoi::Header;
+#[cfg(any(feature = "rgb", feature = "bgr"))]
+use oso_no_std_shared::geometry::Point;
+use oso_no_std_shared::geometry::Size;
+
+/// Name of the shell command handled by [`run_command`]
+pub const COMMAND: &str = "screenshot";
+/// Line before a hex encoded image
+pub const BEGIN_MARKER: &str = "-----BEGIN OSO SCREENSHOT-----";
+/// Line after a hex encoded image
+pub const END_MARKER: &str = "-----END OSO SCREENSHOT-----";
+/// Bytes [`save`] collects before writing them to the file
+const CHUNK: usize = 4096;
+
+/// Size of the screen
+///
+/// # Errors
+///
+/// - [`GraphicError::Unreadable`] if pixels of the framebuffer format can
+///   not be read back
+/// - [`GraphicError::NoFramebuffer`] if the framebuffer is not set up
+pub fn screen_size() -> Rslt<Size, GraphicError,> {
+	#[cfg(not(any(feature = "rgb", feature = "bgr")))]
+	return Err(oso_err!(GraphicError::Unreadable),);
+	#[cfg(any(feature = "rgb", feature = "bgr"))]
+	{
+		let size = FRAME_BUFFER.resolution();
+		if size.width == 0 || size.height == 0 {
+			return Err(oso_err!(GraphicError::NoFramebuffer),);
+		}
+		Ok(size,)
+	}
+}
+
+/// Encodes the screen and passes the bytes of the image to `out` as they
+/// are produced
+///
+/// # Errors
+///
+/// Errors of [`screen_size`], before `out` is called
+pub fn capture(out: impl FnMut(&[u8],),) -> Rslt<(), GraphicError,> {
+	let size = screen_size()?;
+	#[cfg(any(feature = "rgb", feature = "bgr"))]
+	encode(&FRAME_BUFFER, size, out,);
+	#[cfg(not(any(feature = "rgb", feature = "bgr")))]
+	let _ = (size, out,);
+	Ok((),)
+}
+
+/// Writes a hex encoded capture of the screen into `out`
+///
+/// # Errors
+///
+/// Errors of [`screen_size`], before anything is written
+pub fn dump(out: &mut impl fmt::Write,) -> Rslt<(), GraphicError,> {
+	screen_size()?;
+	let mut hex = HexLines::with_markers(out, BEGIN_MARKER, END_MARKER,);
+	capture(|bytes| hex.write(bytes,),)?;
+	hex.flush();
+	Ok((),)
+}
+
+/// Writes a capture of the screen to the start of the file at `path`.
+/// Returns the number of bytes written
+///
+/// # Errors
+///
+/// - Errors of [`screen_size`], before the file is opened
+/// - [`GraphicError::Vfs`] if the file can not be opened or written
+pub fn save(path: &str,) -> Rslt<u64, GraphicError,> {
+	screen_size()?;
+	let mut file = vfs::open(path,)?;
+	let mut chunk = [0; CHUNK];
+	let mut len = 0;
+	let mut written = 0;
+	let mut result = Ok((),);
+	let mut flush = |chunk: &[u8]| {
+		if result.is_ok() && !chunk.is_empty() {
+			result = file.write(chunk,).map(|len| written += len as u64,);
+		}
+	};
+	capture(|mut bytes: &[u8]| {
+		while !bytes.is_empty() {
+			let take = bytes.len().min(CHUNK - len,);
+			chunk[len..len + take].copy_from_slice(&bytes[..take],);
+			len += take;
+			bytes = &bytes[take..];
+			if len == CHUNK {
+				flush(&chunk,);
+				len = 0;
+			}
+		}
+	},)?;
+	flush(&chunk[..len],);
+	result?;
+	Ok(written,)
+}
+
+/// Runs the `screenshot` shell command with the arguments after its name
+pub fn run_command(
+	args: &[&str],
+	out: &mut impl fmt::Write,
+) -> Rslt<(), GraphicError,> {
+	let result = match args {
+		[] => dump(out,),
+		[path,] => save(path,).map(|written| {
+			let _ = writeln!(out, "{written} bytes written to {path}");
+		},),
+		_ => {
+			let _ = writeln!(out, "usage: screenshot [path]");
+			return Err(oso_err!(GraphicError::Usage),);
+		},
+	};
+	if let Err(e,) = &result {
+		let _ = writeln!(out, "screenshot: {:?}", e.desc);
+	}
+	result
+}
+
+/// Encodes the pixels of `frame_buffer`, row by row
+#[cfg(any(feature = "rgb", feature = "bgr"))]
+fn encode<P: PixelFormat + Encode,>(
+	frame_buffer: &FrameBuffer<P,>,
+	size: Size,
+	out: impl FnMut(&[u8],),
+) {
+	let header = Header::rgb(size.width as u32, size.height as u32,);
+	let mut encoder = Encoder::new(header, out,);
+	for y in 0..size.height {
+		let pos = frame_buffer.pos(&Point::new(0, y,),);
+		for &pixel in frame_buffer.pixels_mut(pos, size.width,).iter() {
+			let color = frame_buffer.drawer.decode(pixel,);
+			encoder.push([color.red, color.green, color.blue, 0xff,],);
+		}
+	}
+	encoder.finish();
+}
@@ -1,7 +1,12 @@
+/// Color shared with the consoles and the loader. Literals are validated at
+/// compile time by [`color!`](oso_no_std_shared::color!)
+pub use oso_no_std_shared::color::Color;
+
 pub trait PixelFormat {
 	fn color_repr(&self, color: &impl ColorRpr,) -> [u8; 3];
 }
 
+#[derive(Clone, Copy,)]
 pub struct Rgb;
 impl PixelFormat for Rgb {
 	fn color_repr(&self, color: &impl ColorRpr,) -> [u8; 3] {
@@ -9,6 +14,7 @@ impl PixelFormat for Rgb {
 	}
 }
 
+#[derive(Clone, Copy,)]
 pub struct Bgr;
 impl PixelFormat for Bgr {
 	fn color_repr(&self, color: &impl ColorRpr,) -> [u8; 3] {
@@ -42,16 +48,10 @@ pub trait ColorRpr {
 	fn green_mut(&mut self, val: u8,);
 	fn blue_mut(&mut self, val: u8,);
 	fn to_color(&self,) -> Color {
-		Color { red: self.red(), green: self.green(), blue: self.blue(), }
+		Color::rgb(self.red(), self.green(), self.blue(),)
 	}
 }
 
-pub struct Color {
-	red:   u8,
-	green: u8,
-	blue:  u8,
-}
-
 impl ColorRpr for Color {
 	fn red(&self,) -> u8 {
 		self.red
@@ -103,39 +103,3 @@ impl ColorRpr for (u8, u8, u8,) {
 		self.2 = val;
 	}
 }
-
-/// this impl assumes format such as `#012345`
-impl ColorRpr for &str {
-	fn red(&self,) -> u8 {
-		u8::from_str_radix(&self[1..3], 16,)
-			.expect("incorrect representation of color format",)
-	}
-
-	fn green(&self,) -> u8 {
-		u8::from_str_radix(&self[3..5], 16,)
-			.expect("incorrect representation of color format",)
-	}
-
-	fn blue(&self,) -> u8 {
-		u8::from_str_radix(&self[5..7], 16,)
-			.expect("incorrect representation of color format",)
-	}
-
-	fn red_mut(&mut self, _val: u8,) {
-		todo!()
-	}
-
-	fn green_mut(&mut self, _val: u8,) {
-		todo!()
-	}
-
-	fn blue_mut(&mut self, _val: u8,) {
-		todo!()
-	}
-}
-
-impl From<(u8, u8, u8,),> for Color {
-	fn from(value: (u8, u8, u8,),) -> Self {
-		Color { red: value.0, green: value.1, blue: value.2, }
-	}
-}
@@ -1,3 +1,5 @@
+use oso_no_std_shared::geometry::Point;
+
 /// trait for types which can represent 2 dimentional area
 /// implement this trait ensures to be able to get value of x axis & y axis
 pub trait Coordinal {
@@ -51,3 +53,21 @@ impl Coordinal for (usize, usize,) {
 		&mut self.1
 	}
 }
+
+impl Coordinal for Point {
+	fn x(&self,) -> usize {
+		self.x
+	}
+
+	fn y(&self,) -> usize {
+		self.y
+	}
+
+	fn x_mut(&mut self,) -> &mut usize {
+		&mut self.x
+	}
+
+	fn y_mut(&mut self,) -> &mut usize {
+		&mut self.y
+	}
+}
@@ -0,0 +1,198 @@
+//! # Boot Handoff
+//!
+//! Keeps the [`BootInfo`] the kernel was entered with, so what the boot
+//! loader handed over can be examined after boot and attached to bug reports.
+//!
+//! ## Dumps
+//!
+//! [`dump`] writes [`BootInfo::serialize`] as hex lines between
+//! [`BEGIN_MARKER`] and [`END_MARKER`], like crash dumps. A dump is written
+//! by the `bootinfo dump` shell command, and after the crash dump when the
+//! kernel panics with [`crash::set_dump_on_panic`] enabled. The host decodes
+//! it from a raw file or a whole serial log with `cargo xtask bootinfo`.
+//!
+//! [`crash::set_dump_on_panic`]: super::crash::set_dump_on_panic
+//!
+//! ## Shell
+//!
+//! [`run_command`] implements the `bootinfo` shell command:
+//!
+//! - `bootinfo`: Prints a summary of the boot information
+//! - `bootinfo dump`: Writes a dump of it
+//!
+//! ## Current Status
+//!
+//! The loader hands over no entropy, so none is in a dump. Its boot services
+//! calls are, when it was built with its flight recorder, and so are its
+//! milestones after `ExitBootServices`. Multiboot2 boots halt before the
+//! boot information is kept.
+//!
+//! ```rust,ignore
+//! unsafe { handoff::init(boot_info,) };
+//! handoff::dump(&mut console,);
+//! ```
+
+use super::crash::DumpSink;
+use super::crash::HexLines;
+use core::fmt;
+use core::ptr::null_mut;
+use core::sync::atomic::AtomicPtr;
+use core::sync::atomic::Ordering;
+use oso_error::Rslt;
+use oso_error::kernel::HandoffError;
+use oso_error::oso_err;
+use oso_no_std_shared::bridge::boot_info;
+use oso_no_std_shared::bridge::boot_info::BootInfo;
+use oso_no_std_shared::bridge::boot_info::MemoryRegionKind;
+use oso_no_std_shared::bridge::version::BRIDGE_ABI;
+use oso_no_std_shared::units::ByteSize;
+
+/// Name of the shell command handled by [`run_command`]
+pub const COMMAND: &str = "bootinfo";
+/// Line before a hex encoded dump
+pub const BEGIN_MARKER: &str = "-----BEGIN OSO BOOT INFO-----";
+/// Line after a hex encoded dump
+pub const END_MARKER: &str = "-----END OSO BOOT INFO-----";
+
+static BOOT_INFO: AtomicPtr<BootInfo,> = AtomicPtr::new(null_mut(),);
+
+/// Keeps `boot_info` for [`boot_info`] and [`dump`]
+///
+/// # Safety
+///
+/// `boot_info` must be null or point to boot information which, with the
+/// buffers it points to, is never freed
+pub unsafe fn init(boot_info: *const BootInfo,) {
+	BOOT_INFO.store(boot_info.cast_mut(), Ordering::Release,);
+}
+
+/// Boot information the kernel was entered with. `None` if there is none
+pub fn boot_info() -> Option<&'static BootInfo,> {
+	// SAFETY: `init` only stores boot information which is never freed
+	unsafe { BOOT_INFO.load(Ordering::Acquire,).as_ref() }
+}
+
+/// Writes a hex encoded dump of the boot information into `out`. Returns
+/// `false` without writing if there is none
+pub fn dump(out: &mut impl fmt::Write,) -> bool {
+	let Some(boot_info,) = boot_info() else {
+		return false;
+	};
+	let hex = HexLines::with_markers(out, BEGIN_MARKER, END_MARKER,);
+	let mut sink = Sink(hex,);
+	// SAFETY: `init` only stores boot information whose buffers stay valid
+	unsafe { boot_info.serialize(&mut sink,) };
+	sink.0.flush();
+	true
+}
+
+/// Runs the `bootinfo` shell command with the arguments after its name
+pub fn run_command(
+	args: &[&str],
+	out: &mut impl fmt::Write,
+) -> Rslt<(), HandoffError,> {
+	let Some(boot_info,) = boot_info() else {
+		let _ = writeln!(out, "bootinfo: no boot information");
+		return Err(oso_err!(HandoffError::NoBootInfo),);
+	};
+	match args {
+		[] => {
+			let _ = write_summary(boot_info, out,);
+		},
+		["dump",] => {
+			dump(out,);
+		},
+		_ => {
+			let _ = writeln!(out, "usage: bootinfo [dump]");
+			return Err(oso_err!(HandoffError::Usage),);
+		},
+	}
+	Ok((),)
+}
+
+/// One line per part of the boot information
+fn write_summary(
+	boot_info: &BootInfo,
+	out: &mut impl fmt::Write,
+) -> fmt::Result {
+	// SAFETY: `init` only stores boot information whose buffers stay valid
+	let (cmdline, regions, segments, modules, framebuffer,) = unsafe {
+		(
+			boot_info.cmdline.as_str(),
+			boot_info.memory_map.as_slice(),
+			boot_info.segments.as_slice(),
+			boot_info.modules.as_slice(),
+			boot_info.framebuffer(),
+		)
+	};
+	writeln!(out, "abi          {BRIDGE_ABI}")?;
+	writeln!(out, "device tree  {:#x}", boot_info.device_tree as u64)?;
+	writeln!(out, "cmdline      {cmdline:?}")?;
+
+	let usable: u64 = regions
+		.iter()
+		.filter(|region| region.kind == MemoryRegionKind::Usable,)
+		.map(|region| region.size(),)
+		.sum();
+	let (count, usable,) = (regions.len(), ByteSize::new(usable,),);
+	writeln!(out, "memory map   {count} regions, {usable:.1} usable")?;
+
+	let runtime = &boot_info.runtime;
+	write!(out, "runtime      ")?;
+	match boot_info.read_runtime_services() {
+		0 => writeln!(out, "none")?,
+		table => {
+			let mode =
+				if runtime.is_virtual() { "virtual" } else { "physical" };
+			let supported = runtime.supported;
+			writeln!(out, "{table:#x} {mode}, supported {supported:#06x}")?;
+		},
+	}
+
+	writeln!(out, "segments     {}", segments.len())?;
+	match framebuffer {
+		Some(fb,) => writeln!(
+			out,
+			"framebuffer  {}x{} {:?} at {:#x}",
+			fb.width, fb.height, fb.pixel_format, fb.base as u64
+		)?,
+		None => writeln!(out, "framebuffer  none")?,
+	}
+	writeln!(out, "modules      {}", modules.len())?;
+	for module in modules {
+		let (start, size,) = (module.read_start(), module.read_size(),);
+		// SAFETY: as above
+		let cmdline = unsafe { module.cmdline.as_str() };
+		writeln!(out, "  {start:#x} {size} bytes {cmdline:?}")?;
+	}
+
+	let calls = &boot_info.firmware_calls;
+	match calls.read_count() {
+		0 => writeln!(out, "firmware     no calls recorded")?,
+		count => writeln!(
+			out,
+			"firmware     {count} calls, {} dropped",
+			calls.dropped()
+		)?,
+	}
+
+	let milestones = &boot_info.milestones;
+	let handoff = milestones.micros_since_exit(milestones.read_handoff(),);
+	match (milestones.read_exit_boot_services(), handoff,) {
+		(0, _,) => writeln!(out, "milestones   none")?,
+		(_, Some(us,),) => {
+			writeln!(out, "milestones   handoff {us} us after exit")?
+		},
+		(_, None,) => writeln!(out, "milestones   frequency unknown")?,
+	}
+	Ok((),)
+}
+
+/// Passes serialized bytes to a crash dump sink
+struct Sink<S: DumpSink,>(S,);
+
+impl<S: DumpSink,> boot_info::Write for Sink<S,> {
+	fn write(&mut self, bytes: &[u8],) {
+		self.0.write(bytes,);
+	}
+}
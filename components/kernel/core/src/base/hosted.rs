@@ -0,0 +1,87 @@
+//! # Hosted Architecture Layer
+//!
+//! Stand-ins for the privileged instructions of the kernel, used instead of
+//! them when the `hosted` feature builds the kernel for a process on the
+//! host, e.g. by the simulator of the `sim` feature. Instructions which trap
+//! outside of the kernel, like masking interrupts or reading the ID
+//! registers, would kill the process. Code above them runs unchanged.
+//!
+//! | Function               | Replaces                                    |
+//! | ---------------------- | ------------------------------------------- |
+//! | [`detect_cpu`]         | ID registers and CPUID of [`cpu`]           |
+//! | [`disable_interrupts`] | masking interrupts in [`sched`]             |
+//! | [`restore_interrupts`] | unmasking interrupts in [`sched`]           |
+//! | [`wait_for_interrupt`] | `wfi` and `hlt` of [`idle`]                 |
+//! | [`cycles`]             | cycle counter of [`perf`]                   |
+//! | [`instructions`]       | instruction counter of [`perf`]             |
+//! | [`core_id`]            | `MPIDR_EL1` of [`perf`]                     |
+//! | [`reboot`]             | reset of [`power`]                          |
+//! | [`power_off`]          | power off of [`power`]                      |
+//! | [`qemu_exit`]          | exit devices of [`driver::qemu_exit`]       |
+//!
+//! A process has no interrupts to mask, and reports no processor features,
+//! so every code path picked by [`cpu::features`] is the portable one.
+//! Counters read `0`. Rebooting, powering off or exiting QEMU panics, which
+//! ends the process.
+//!
+//! Unit tests of the kernel run on the same build:
+//!
+//! ```sh
+//! cargo test -p oso_kernel --features hosted --lib --target <host triple>
+//! ```
+//!
+//! [`cpu`]: super::cpu
+//! [`cpu::features`]: super::cpu::features
+//! [`sched`]: super::sched
+//! [`idle`]: super::perf::idle
+//! [`perf`]: super::perf
+//! [`power`]: super::power
+//! [`driver::qemu_exit`]: crate::driver::qemu_exit
+
+use super::cpu::Features;
+
+/// No feature is reported
+pub fn detect_cpu() -> Features {
+	Features::default()
+}
+
+/// Returns `0`, there are no interrupts to mask
+pub fn disable_interrupts() -> usize {
+	0
+}
+
+/// Does nothing, see [`disable_interrupts`]
+pub fn restore_interrupts(_flags: usize,) {}
+
+/// Returns at once. Callers waiting for work poll it
+pub fn wait_for_interrupt() {
+	core::hint::spin_loop();
+}
+
+pub fn cycles() -> u64 {
+	0
+}
+
+pub fn instructions() -> u64 {
+	0
+}
+
+/// Every thread counts as core `0`
+pub fn core_id() -> usize {
+	0
+}
+
+/// Panics, as a process can not reboot the host
+pub fn reboot() -> ! {
+	panic!("reboot requested on a hosted build")
+}
+
+/// Panics, as a process can not power the host off
+pub fn power_off() -> ! {
+	panic!("power off requested on a hosted build")
+}
+
+/// Panics, as the process is not QEMU
+pub fn qemu_exit(code: u32,) -> ! {
+	panic!("qemu exit with {code} requested on a hosted build")
+}
@@ -0,0 +1,120 @@
+//! # Hypervisor Detection
+//!
+//! Tells whether the kernel runs under a hypervisor, so that bring-up code can
+//! use paravirtual devices such as the [early console](super::early_console)
+//! which do not exist on real hardware.
+//!
+//! - **x86_64**: CPUID reports a hypervisor in bit 31 of ECX of leaf 1 and its
+//!   vendor in leaf `0x4000_0000`
+//! - **AArch64**: there is no architectural bit, so the device tree is checked
+//!   for a `/hypervisor` node, and for the QEMU `virt` machine which has none
+
+use oso_no_std_shared::bridge::device_tree::DeviceTreeAddress;
+#[cfg(target_arch = "aarch64")]
+use oso_no_std_shared::bridge::device_tree::Fdt;
+
+/// Hypervisor the kernel runs under
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum Hypervisor {
+	/// no hypervisor was found
+	None,
+	Kvm,
+	/// QEMU with its own TCG accelerator, or with an accelerator which can
+	/// not be told apart on this architecture
+	Qemu,
+	Xen,
+	HyperV,
+	VMware,
+	/// a hypervisor with an unknown vendor
+	Other,
+}
+
+impl Hypervisor {
+	pub const fn name(&self,) -> &'static str {
+		match self {
+			Self::None => "none",
+			Self::Kvm => "KVM",
+			Self::Qemu => "QEMU",
+			Self::Xen => "Xen",
+			Self::HyperV => "Hyper-V",
+			Self::VMware => "VMware",
+			Self::Other => "unknown hypervisor",
+		}
+	}
+
+	pub const fn is_virtual(&self,) -> bool {
+		!matches!(self, Self::None)
+	}
+}
+
+/// Detects the hypervisor
+///
+/// `device_tree` is only read on AArch64 and may be null.
+///
+/// # Safety
+///
+/// `device_tree` must be null or point to a valid device tree blob
+pub unsafe fn detect(device_tree: DeviceTreeAddress,) -> Hypervisor {
+	#[cfg(target_arch = "x86_64")]
+	{
+		let _ = device_tree;
+		cpuid()
+	}
+	#[cfg(target_arch = "aarch64")]
+	{
+		match unsafe { Fdt::from_addr(device_tree,) } {
+			Some(fdt,) => from_device_tree(&fdt,),
+			None => Hypervisor::None,
+		}
+	}
+	#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+	{
+		let _ = device_tree;
+		Hypervisor::None
+	}
+}
+
+#[cfg(target_arch = "x86_64")]
+fn cpuid() -> Hypervisor {
+	use core::arch::x86_64::__cpuid;
+
+	const HYPERVISOR_PRESENT: u32 = 1 << 31;
+
+	if __cpuid(1,).ecx & HYPERVISOR_PRESENT == 0 {
+		return Hypervisor::None;
+	}
+	let leaf = __cpuid(0x4000_0000,);
+	let mut vendor = [0; 12];
+	vendor[..4].copy_from_slice(&leaf.ebx.to_le_bytes(),);
+	vendor[4..8].copy_from_slice(&leaf.ecx.to_le_bytes(),);
+	vendor[8..].copy_from_slice(&leaf.edx.to_le_bytes(),);
+	match &vendor {
+		b"KVMKVMKVM\0\0\0" => Hypervisor::Kvm,
+		b"TCGTCGTCGTCG" => Hypervisor::Qemu,
+		b"XenVMMXenVMM" => Hypervisor::Xen,
+		b"Microsoft Hv" => Hypervisor::HyperV,
+		b"VMwareVMware" => Hypervisor::VMware,
+		_ => Hypervisor::Other,
+	}
+}
+
+#[cfg(target_arch = "aarch64")]
+fn from_device_tree(fdt: &Fdt,) -> Hypervisor {
+	if let Some(node,) = fdt.find("/hypervisor",) {
+		return if node.is_compatible("xen,xen",) {
+			Hypervisor::Xen
+		} else {
+			Hypervisor::Other
+		};
+	}
+
+	let is_qemu_virt =
+		fdt.root().is_some_and(|root| root.is_compatible("linux,dummy-virt",),);
+	let has_fw_cfg =
+		fdt.nodes().any(|node| node.is_compatible("qemu,fw-cfg-mmio",),);
+	if is_qemu_virt || has_fw_cfg {
+		Hypervisor::Qemu
+	} else {
+		Hypervisor::None
+	}
+}
@@ -0,0 +1,51 @@
+//! # Kernel Image Integrity
+//!
+//! The loader records a CRC-32 of every loadable segment right after copying
+//! it into memory. Read-only and executable segments must not change until the
+//! kernel starts, so a mismatch means the image was corrupted in between, for
+//! example by firmware writing to memory it handed to the loader.
+
+use oso_no_std_shared::bridge::boot_info::BootInfo;
+use oso_no_std_shared::bridge::boot_info::SegmentChecksum;
+
+/// Verifies the checksums of all non-writable segments listed in `boot_info`
+///
+/// Writable segments are skipped, as statics may already have been written.
+///
+/// # Panics
+///
+/// Panics on the first segment whose contents differ from what the loader
+/// wrote, reporting its index, range, flags and both checksums
+///
+/// # Safety
+///
+/// `boot_info.segments` must describe memory which is mapped and readable
+pub unsafe fn verify_segments(boot_info: &BootInfo,) {
+	let checksums = unsafe { boot_info.segments.as_slice() };
+	for (i, segment,) in checksums.iter().enumerate() {
+		if !segment.is_verifiable() {
+			continue;
+		}
+		let actual = unsafe { segment.current() };
+		if actual != segment.crc32 {
+			report(i, segment, actual,);
+		}
+	}
+}
+
+#[cold]
+fn report(index: usize, segment: &SegmentChecksum, actual: u32,) -> ! {
+	let exec = if segment.flags & SegmentChecksum::EXECUTABLE != 0 {
+		"r-x"
+	} else {
+		"r--"
+	};
+	panic!(
+		"kernel segment {index} ({:#x}..{:#x} {exec}, flags {:#x}) is \
+		 corrupted: crc32 {actual:#010x}, loader wrote {:#010x}",
+		segment.read_start(),
+		segment.read_start() + segment.read_size(),
+		segment.flags,
+		segment.crc32
+	)
+}
@@ -11,6 +11,8 @@
 //! - **Console Output**: Print macros for kernel debugging and logging
 //! - **Character Display**: Individual character rendering with positioning
 //! - **Text Buffer Management**: Automatic text wrapping and scrolling
+//! - **Glyph Cache**: Expanded glyphs are reused, and only cells which
+//!   changed are drawn
 //! - **Font Integration**: Compile-time font loading and processing
 //!
 //! ## Font System
@@ -46,7 +48,7 @@
 //!
 //! - **Automatic Wrapping**: Text wraps to the next line when reaching screen
 //!   edge
-//! - **Scrolling**: Screen clears and resets when reaching the bottom
+//! - **Scrolling**: The screen moves up a line when text reaches the bottom
 //! - **Positioning**: Tracks current cursor position for continuous text output
 //!
 //! ## Architecture
@@ -75,29 +77,54 @@
 //! text_buf.put_char(b'\n')?;
 //! ```
 //!
+//! ### Help Text
+//!
+//! Help pages are written in Markdown-lite (see
+//! [`oso_no_std_shared::parser::markdown`]) and rendered with
+//! [`print_markdown`]:
+//!
+//! ```rust,ignore
+//! print_markdown("# mem\n\nshow memory map\n- `mem -v` verbose\n");
+//! ```
+//!
 //! ## Performance Considerations
 //!
 //! - Font data is embedded at compile time for fast access
-//! - Character rendering is optimized for bitmap operations
+//! - Glyphs are expanded once per character and colors, see
+//!   [`GlyphCache`]
 //! - Text buffer operations are designed for minimal memory allocation
 //! - Console output is synchronous and may impact performance in tight loops
 //!
 //! ## Future Enhancements
 //!
-//! - Color text support
 //! - Multiple font sizes
 //! - Unicode character support
 //! - Hardware-accelerated text rendering
 //! - Input handling (keyboard, mouse)
 
 use super::graphic::FRAME_BUFFER;
+use crate::base::console_policy;
+use crate::base::env;
+use crate::base::graphic::color::Color;
+use crate::base::graphic::glyph::CacheStats;
+use crate::base::graphic::glyph::GLYPH_WIDTH;
+use crate::base::graphic::glyph::GlyphCache;
+use crate::base::graphic::glyph::GlyphKey;
 use crate::base::graphic::position::Coordinal;
+use core::cell::UnsafeCell;
 use core::fmt::Write;
 use core::ops::Add;
 use core::ops::Div;
 use core::ops::Mul;
 use core::ops::Sub;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::Ordering;
 use oso_error::Rslt;
+use oso_no_std_shared::geometry::Point;
+use oso_no_std_shared::parser::markdown;
+use oso_no_std_shared::text::console;
+use oso_no_std_shared::text::console::Console;
+use oso_no_std_shared::text::console::ConsoleColor;
 use oso_proc_macro::font;
 use oso_proc_macro::impl_int;
 
@@ -113,7 +140,8 @@ use oso_proc_macro::impl_int;
 ///
 /// This constant contains the Sinonome font data, embedded at compile time
 /// using the `font!` procedural macro. The font provides 8x16 pixel characters
-/// for the full 8-bit character set (256 characters).
+/// for the full 8-bit character set, of which only printable ASCII is
+/// embedded as nothing else is rendered.
 ///
 /// # Font Format
 ///
@@ -137,9 +165,52 @@ use oso_proc_macro::impl_int;
 /// The font data is accessed by character code:
 ///
 /// ```rust,ignore
-/// let char_data = SINONOME[b'A' as usize]; // Get bitmap for 'A'
+/// let char_data = SINONOME.glyph(b'A' as u32); // Get bitmap for 'A'
 /// ```
-pub const SINONOME: &[u128; 256] = font!("resource/sinonome_font.dat");
+pub const SINONOME: GlyphSubset = GlyphSubset::new(font!(
+	"resource/sinonome_font.dat",
+	ranges = "0x20..0x7F"
+));
+
+/// Glyphs of a font embedded with `font!(path, ranges = "...")`
+pub struct GlyphSubset {
+	/// first code, end code and index of the first glyph of each range
+	ranges: &'static [(u32, u32, u32,)],
+	glyphs: &'static [u128],
+}
+
+impl GlyphSubset {
+	pub const fn new(
+		(ranges, glyphs,): (&'static [(u32, u32, u32,)], &'static [u128],),
+	) -> Self {
+		Self { ranges, glyphs, }
+	}
+
+	/// Bitmap of character `code`, if embedded
+	pub fn glyph(&self, code: u32,) -> Option<u128,> {
+		let (first, _, index,) = self
+			.ranges
+			.iter()
+			.find(|(first, end, _,)| (*first..*end).contains(&code,),)?;
+		self.glyphs.get((index + code - first) as usize,).copied()
+	}
+}
+
+/// Most columns a [`TextBuf`] keeps. Wider screens wrap at this column
+pub const MAX_COLUMNS: usize = 256;
+/// Most rows a [`TextBuf`] keeps. Taller screens scroll at this row
+pub const MAX_ROWS: usize = 96;
+/// Glyphs the console can cache
+pub const GLYPH_SLOTS: usize = 128;
+/// Bytes of glyphs the console caches unless `glyph_cache=<bytes>` is on
+/// the kernel command line
+pub const GLYPH_BUDGET: usize = 32 * 1024;
+/// words of the dirty bitmap of a [`TextBuf`]
+const DIRTY_WORDS: usize = (MAX_COLUMNS * MAX_ROWS).div_ceil(64,);
+
+/// Glyph cache shared by every [`TextBuf`], so the virtual terminals draw
+/// from the same glyphs
+static GLYPHS: Renderer = Renderer::new();
 
 /// Maximum number of digits that can be represented in a u128
 ///
@@ -148,18 +219,6 @@ pub const SINONOME: &[u128; 256] = font!("resource/sinonome_font.dat");
 /// 340282366920938463463374607431768211455).
 pub const MAX_DIGIT: usize = 39;
 
-/// Global console text buffer for kernel output
-///
-/// This static instance provides the primary console interface for the kernel.
-/// It's initialized with a position of (0, 0) and uses 8x16 pixel characters.
-///
-/// # Safety
-///
-/// This static is accessed through unsafe operations in the `print` function
-/// to provide interior mutability. Proper synchronization should be added
-/// for multi-threaded environments.
-static CONSOLE: TextBuf<(usize, usize,),> = TextBuf::new((0, 0,), 8, 16,);
-
 /// Text buffer for managing character display and positioning
 ///
 /// This struct handles the layout and rendering of text characters on the
@@ -177,6 +236,20 @@ static CONSOLE: TextBuf<(usize, usize,),> = TextBuf::new((0, 0,), 8, 16,);
 /// * `col` - Current column position (in character units)
 /// * `font_width` - Width of each character in pixels
 /// * `font_height` - Height of each character in pixels
+/// * `foreground`, `background` - Colors of text written next
+/// * `cells` - Character and colors of each cell, row by row
+/// * `dirty` - Cells changed since the last [`TextBuf::flush`], a bit each
+/// * `visible` - Whether the cells are drawn on the screen
+///
+/// # Dirty Tracking
+///
+/// Writing a character only updates its cell. [`TextBuf::flush`], called
+/// at the end of every write, draws the cells whose character or colors
+/// changed from the [`GlyphCache`], so rewriting a status line only draws
+/// the characters which differ.
+///
+/// A hidden text buffer, e.g. of a virtual terminal in the background, only
+/// updates its cells. It draws all of them when it is shown again.
 ///
 /// # Examples
 ///
@@ -202,6 +275,27 @@ pub struct TextBuf<C: Coordinal,> {
 	pub font_width:  usize,
 	/// Height of each character in pixels
 	pub font_height: usize,
+	foreground:      ConsoleColor,
+	background:      ConsoleColor,
+	cells:           [Cell; MAX_COLUMNS * MAX_ROWS],
+	dirty:           [u64; DIRTY_WORDS],
+	visible:         bool,
+}
+
+/// Character of the console and its colors
+#[derive(Clone, Copy, PartialEq, Eq,)]
+struct Cell {
+	code:       u8,
+	foreground: ConsoleColor,
+	background: ConsoleColor,
+}
+
+impl Cell {
+	const BLANK: Self = Self::blank(ConsoleColor::Black,);
+
+	const fn blank(background: ConsoleColor,) -> Self {
+		Self { code: b' ', foreground: ConsoleColor::LightGray, background, }
+	}
 }
 
 impl<C: Coordinal,> TextBuf<C,> {
@@ -235,52 +329,134 @@ impl<C: Coordinal,> TextBuf<C,> {
 		font_width: usize,
 		font_height: usize,
 	) -> Self {
-		Self { init_pos, row: 0, col: 0, font_width, font_height, }
+		Self {
+			init_pos,
+			row: 0,
+			col: 0,
+			font_width,
+			font_height,
+			foreground: ConsoleColor::LightGray,
+			background: ConsoleColor::Black,
+			cells: [Cell::BLANK; MAX_COLUMNS * MAX_ROWS],
+			dirty: [0; DIRTY_WORDS],
+			visible: true,
+		}
 	}
 
-	/// Calculates the current row position in pixels
-	///
-	/// This method converts the current row position from character units
-	/// to pixel coordinates by multiplying by the font height and adding
-	/// the initial Y position.
-	///
-	/// # Returns
-	///
-	/// The current row position in pixels from the top of the screen
-	///
-	/// # Formula
-	///
-	/// ```text
-	/// pixel_row = init_pos.y + (font_height * current_row)
-	/// ```
-	fn row_pixel(&self,) -> usize {
-		self.init_pos.y() + self.font_height * self.row
+	/// `self`, hidden until [`Self::set_visible`] shows it
+	pub const fn hidden(mut self,) -> Self {
+		self.visible = false;
+		self
 	}
 
-	/// Calculates the current column position in pixels
-	///
-	/// This method converts the current column position from character units
-	/// to pixel coordinates by multiplying by the font width and adding
-	/// the initial X position.
-	///
-	/// # Returns
-	///
-	/// The current column position in pixels from the left of the screen
+	/// Shows or hides the text buffer. Showing it draws every cell
+	pub fn set_visible(&mut self, visible: bool,) {
+		self.visible = visible;
+		if visible {
+			let columns = self.columns();
+			let cells = self.rows() * MAX_COLUMNS;
+			self.dirty = [0; DIRTY_WORDS];
+			for i in (0..cells).filter(|i| i % MAX_COLUMNS < columns,) {
+				self.dirty[i / 64] |= 1 << (i % 64);
+			}
+			self.flush();
+		}
+	}
+
+	/// Columns which fit on the screen
+	fn columns(&self,) -> usize {
+		let width = FRAME_BUFFER.width().saturating_sub(self.init_pos.x(),);
+		(width / self.font_width).min(MAX_COLUMNS,)
+	}
+
+	/// Rows which fit on the screen
+	fn rows(&self,) -> usize {
+		let height = FRAME_BUFFER.height().saturating_sub(self.init_pos.y(),);
+		(height / self.font_height).min(MAX_ROWS,)
+	}
+
+	/// Sets the cell at `col` of `row`, and marks it dirty if it changed
+	fn set_cell(&mut self, col: usize, row: usize, cell: Cell,) {
+		let i = row * MAX_COLUMNS + col;
+		if self.cells[i] != cell {
+			self.cells[i] = cell;
+			self.dirty[i / 64] |= 1 << (i % 64);
+		}
+	}
+
+	/// Draws the cells which changed since the last flush
 	///
-	/// # Formula
+	/// # Examples
 	///
-	/// ```text
-	/// pixel_col = init_pos.x + (font_width * current_col)
+	/// ```rust,ignore
+	/// text_buf.put_char(b'A')?;
+	/// text_buf.flush(); // draws `A` only
 	/// ```
-	fn col_pixel(&self,) -> usize {
-		self.init_pos.x() + self.font_width * self.col
+	pub fn flush(&mut self,) {
+		if !self.visible {
+			self.dirty = [0; DIRTY_WORDS];
+			return;
+		}
+		// cells stay dirty if the glyph cache is in use, e.g. by a panic in
+		// the middle of a flush
+		GLYPHS.with(|glyphs: &mut Glyphs| {
+			for word in 0..DIRTY_WORDS {
+				let mut bits = core::mem::take(&mut self.dirty[word],);
+				while bits != 0 {
+					let i = word * 64 + bits.trailing_zeros() as usize;
+					bits &= bits - 1;
+					self.draw_cell(i % MAX_COLUMNS, i / MAX_COLUMNS, glyphs,);
+				}
+			}
+		},);
+	}
+
+	/// draws the cell at `col` of `row` from the glyph cache
+	fn draw_cell(
+		&self,
+		col: usize,
+		row: usize,
+		glyphs: &mut Glyphs,
+	) {
+		let cell = self.cells[row * MAX_COLUMNS + col];
+		let key = GlyphKey {
+			code:       cell.code as u32,
+			foreground: FRAME_BUFFER.pixel(&Color::from(cell.foreground,),),
+			background: FRAME_BUFFER.pixel(&Color::from(cell.background,),),
+		};
+		// characters which are not embedded are drawn as `?`
+		let bitmap = SINONOME
+			.glyph(key.code,)
+			.or_else(|| SINONOME.glyph(b'?' as u32,),)
+			.unwrap_or(0,);
+		let origin = Point::new(
+			self.init_pos.x() + col * self.font_width,
+			self.init_pos.y() + row * self.font_height,
+		);
+		FRAME_BUFFER.blit(origin, GLYPH_WIDTH, glyphs.get(key, bitmap,),);
+	}
+
+	/// Moves the cells up by a row, and the screen if visible. Cells are
+	/// flushed first, so the pixels moved match them
+	fn scroll(&mut self,) -> Rslt<(),> {
+		self.flush();
+		if self.visible {
+			let background = Color::from(self.background,);
+			FRAME_BUFFER.scroll_up(self.font_height, &background,)?;
+		}
+		self.cells.copy_within(MAX_COLUMNS.., 0,);
+		// the screen was filled with the background below the last row
+		let last = self.rows().saturating_sub(1,) * MAX_COLUMNS;
+		self.cells[last..].fill(Cell::blank(self.background,),);
+		self.row -= 1;
+		Ok((),)
 	}
 
 	/// Resets the text buffer cursor to the initial position
 	///
 	/// This method clears the current cursor position, moving it back to
 	/// row 0, column 0. This is typically used when the screen needs to
-	/// be cleared or when text has reached the bottom of the display.
+	/// be cleared.
 	///
 	/// # Examples
 	///
@@ -296,7 +472,7 @@ impl<C: Coordinal,> TextBuf<C,> {
 	///
 	/// This method handles the rendering of individual characters, including
 	/// special characters like newline. It performs automatic line wrapping
-	/// and screen clearing when necessary.
+	/// and scrolling when necessary.
 	///
 	/// # Arguments
 	///
@@ -316,8 +492,8 @@ impl<C: Coordinal,> TextBuf<C,> {
 	///
 	/// - **Line Wrapping**: When text reaches the right edge, cursor moves to
 	///   next line
-	/// - **Screen Clearing**: When text reaches the bottom, screen is cleared
-	///   and cursor resets
+	/// - **Scrolling**: When text reaches the bottom, the screen moves up a
+	///   line with [`FrameBuffer::scroll_up`](super::graphic::FrameBuffer::scroll_up)
 	/// - **Cursor Advancement**: After each character, cursor moves to the next
 	///   position
 	///
@@ -329,11 +505,7 @@ impl<C: Coordinal,> TextBuf<C,> {
 	/// text_buf.put_char(b'B')?;     // Render 'B' on next line
 	/// ```
 	///
-	/// # TODO
-	///
-	/// - Re-enable pixel rendering (currently commented out)
-	/// - Add color support for characters
-	/// - Implement proper error handling for rendering failures
+	/// The character is drawn by the next [`Self::flush`].
 	fn put_char(&mut self, char: u8,) -> Rslt<(),> {
 		// Handle newline character
 		if char == b'\n' {
@@ -342,35 +514,25 @@ impl<C: Coordinal,> TextBuf<C,> {
 			return Ok((),);
 		}
 
-		// Check if we've reached the bottom of the screen
-		if self.row * self.font_height >= FRAME_BUFFER.height {
-			self.clear();
+		// Scroll up while the line is below the bottom of the screen
+		while self.row > 0 && self.row >= self.rows() {
+			self.scroll()?;
 		}
 
-		// Get the bitmap data for this character
-		let font_data = SINONOME[char as usize];
-		let col_pos = self.col_pixel();
-		let row_pos = self.row_pixel();
-
-		// Render each pixel of the character
-		for i in 0..self.font_width {
-			for j in 0..self.font_height {
-				let flag = i + j * self.font_width;
-				// Determine whether pixel at position (i, j) should be drawn
-				let bit = font_data & (0b1 << flag);
-				if bit != 0 {
-					let _coord = (col_pos + i, row_pos + j,);
-					// TODO: Re-enable pixel rendering
-					// put_pixel(&coord, &"#000000",)?;
-				}
-			}
+		let cell = Cell {
+			code:       char,
+			foreground: self.foreground,
+			background: self.background,
+		};
+		if self.col < MAX_COLUMNS && self.row < MAX_ROWS {
+			self.set_cell(self.col, self.row, cell,);
 		}
 
 		// Advance cursor position
 		self.col += 1;
 
 		// Check for line wrapping
-		if self.col_pixel() + self.font_width >= FRAME_BUFFER.width {
+		if self.col >= self.columns() {
 			self.col = 0;
 			self.row += 1;
 		}
@@ -413,135 +575,125 @@ impl<C: Coordinal,> Write for TextBuf<C,> {
 		for c in s.as_bytes() {
 			self.put_char(*c,)?;
 		}
+		self.flush();
 		Ok((),)
 	}
 }
 
-/// Prints formatted text to the console with a newline
-///
-/// This macro provides a convenient way to output formatted text to the kernel
-/// console. It automatically appends a newline character after the formatted
-/// output.
-///
-/// # Arguments
-///
-/// * `$($arg:tt)*` - Format string and arguments (same as `std::println!`)
-///
-/// # Examples
-///
-/// ```rust,ignore
-/// println!(); // Print just a newline
-/// println!("Hello, World!"); // Print with newline
-/// println!("Value: {}, Address: 0x{:x}", 42, 0x1000); // Formatted output
-/// ```
-///
-/// # Implementation
-///
-/// The macro expands to a call to the `print!` macro with a newline appended:
-///
-/// ```rust,ignore
-/// println!("Hello") // expands to:
-/// print!("{}\n", format_args!("Hello"))
-/// ```
-#[macro_export]
-macro_rules! println {
-	() => {
-		$crate::print!("\n");
-	};
-	($($arg:tt)*) => {
-		$crate::print!("{}\n", format_args!($($arg)*));
-	};
+impl<C: Coordinal,> Console for TextBuf<C,> {
+	/// Styles of [`markdown::render`] arrive here through
+	/// [`Console::set_style`]
+	fn set_color(
+		&mut self,
+		foreground: ConsoleColor,
+		background: ConsoleColor,
+	) -> core::fmt::Result {
+		self.foreground = foreground;
+		self.background = background;
+		Ok((),)
+	}
+
+	/// Blanks every cell in the background color. Only cells which were not
+	/// blank already are drawn
+	fn clear(&mut self,) -> core::fmt::Result {
+		let blank = Cell::blank(self.background,);
+		for row in 0..self.rows() {
+			for col in 0..self.columns() {
+				self.set_cell(col, row, blank,);
+			}
+		}
+		self.flush();
+		TextBuf::clear(self,);
+		Ok((),)
+	}
+
+	fn cursor(&self,) -> (usize, usize,) {
+		(self.col, self.row,)
+	}
+
+	fn set_cursor(&mut self, column: usize, row: usize,) -> core::fmt::Result {
+		self.col = column;
+		self.row = row;
+		Ok((),)
+	}
 }
 
-/// Prints formatted text to the console
-///
-/// This macro provides the primary interface for outputting text from the
-/// kernel. It supports all of Rust's standard formatting options and directs
-/// output to the global console text buffer.
-///
-/// # Arguments
-///
-/// * `$($arg:tt)*` - Format string and arguments (same as `std::print!`)
-///
-/// # Examples
-///
-/// ```rust,ignore
-/// print!("Hello, World!");
-/// print!("Number: {}", 42);
-/// print!("Hex: 0x{:x}, Binary: 0b{:b}", 255, 255);
-/// ```
-///
-/// # Implementation
-///
-/// The macro expands to a call to the `print` function with formatted
-/// arguments:
-///
-/// ```rust,ignore
-/// print!("Hello") // expands to:
-/// crate::base::io::print(format_args!("Hello"))
-/// ```
-#[macro_export]
-macro_rules! print {
-    ($($arg:tt)*) => {
-        $crate::base::io::print(format_args!($($arg)*));
-    };
+/// Makes the kernel log the console of [`print!`] and [`println!`], mirrored
+/// to the sinks of [`console_policy`]. See [`vt`](crate::base::vt) for the
+/// other terminals
+///
+/// [`print!`]: crate::print
+/// [`println!`]: crate::println
+///
+/// `glyph_cache=<bytes>` on the kernel command line sets the budget of the
+/// glyph cache, [`GLYPH_BUDGET`] by default.
+pub fn init() {
+	let budget = env::get("cmdline.glyph_cache",).and_then(|v| v.parse().ok(),);
+	if let Some(budget,) = budget {
+		set_glyph_budget(budget,);
+	}
+	console_policy::init();
 }
 
-/// Low-level print function for console output
-///
-/// This function provides the underlying implementation for the `print!` and
-/// `println!` macros. It writes formatted arguments to the global console
-/// text buffer using unsafe operations to achieve interior mutability.
-///
-/// # Arguments
-///
-/// * `args` - Formatted arguments created by `format_args!`
-///
-/// # Safety
-///
-/// This function uses unsafe operations to obtain a mutable reference to the
-/// static `CONSOLE` text buffer. This is necessary to provide interior
-/// mutability for the global console state.
-///
-/// # Panics
-///
-/// This function will panic if:
-/// - The console text buffer cannot be accessed
-/// - Writing to the console fails
-///
-/// # Implementation Details
+/// Limits the glyph cache of the console to `budget` bytes. A budget
+/// smaller than [`GLYPH_BYTES`] disables caching
 ///
-/// The function:
-/// 1. Casts the static `CONSOLE` to a mutable pointer
-/// 2. Converts the pointer back to a mutable reference
-/// 3. Uses the `Write` trait to output the formatted arguments
-/// 4. Panics if any step fails
-///
-/// # Examples
-///
-/// This function is typically not called directly, but through the macros:
-///
-/// ```rust,ignore
-/// // These macros call print() internally:
-/// print!("Hello");
-/// println!("World");
-/// ```
-pub fn print(args: core::fmt::Arguments,) {
-	use core::fmt::Write;
-	unsafe {
-		// SAFETY: We're obtaining a mutable reference to the static CONSOLE
-		// This is safe because:
-		// 1. The CONSOLE is a valid static with a stable address
-		// 2. We're not creating multiple mutable references simultaneously
-		// 3. The operation is atomic (single-threaded kernel context)
-		// TODO: Add proper synchronization for multi-threaded environments
-		(&CONSOLE as *const TextBuf<(usize, usize,),>
-			as *mut TextBuf<(usize, usize,),>)
-			.as_mut()
-			.unwrap()
-			.write_fmt(args,)
+/// [`GLYPH_BYTES`]: crate::base::graphic::glyph::GLYPH_BYTES
+pub fn set_glyph_budget(budget: usize,) {
+	GLYPHS.with(|glyphs: &mut Glyphs| glyphs.set_budget(budget,),);
+}
+
+/// Hits and misses of the glyph cache of the console
+pub fn glyph_stats() -> CacheStats {
+	GLYPHS.with(|glyphs: &mut Glyphs| glyphs.stats(),).unwrap_or_default()
+}
+
+/// glyph cache of the console
+type Glyphs = GlyphCache<GLYPH_SLOTS,>;
+
+/// glyph cache behind a try-lock
+struct Renderer {
+	busy:   AtomicBool,
+	glyphs: UnsafeCell<Glyphs,>,
+}
+
+// SAFETY: the glyph cache is only accessed while `busy` is held
+unsafe impl Sync for Renderer {}
+
+impl Renderer {
+	const fn new() -> Self {
+		Self {
+			busy:   AtomicBool::new(false,),
+			glyphs: UnsafeCell::new(GlyphCache::new(GLYPH_BUDGET,),),
+		}
 	}
-	.expect("unable to write to console",)
+
+	/// Calls `f` with the glyph cache. `None` if it is in use
+	fn with<R,>(
+		&self,
+		f: impl FnOnce(&mut Glyphs,) -> R,
+	) -> Option<R,> {
+		if self.busy.swap(true, Ordering::Acquire,) {
+			return None;
+		}
+		// SAFETY: the lock is held
+		let r = f(unsafe { &mut *self.glyphs.get() },);
+		self.busy.store(false, Ordering::Release,);
+		Some(r,)
+	}
+}
+
+/// Writes `args` to the console. Used by [`print!`](crate::print)
+pub fn print(args: core::fmt::Arguments,) {
+	console::print(args,);
+}
+
+/// Renders Markdown-lite `src` to the console
+///
+/// Used for help pages of the debug shell and descriptions in the boot menu.
+/// See [`markdown::render`] for the layout.
+pub fn print_markdown(src: &str,) {
+	console::with(|console| markdown::render(src, console,),);
 }
 
 // TODO: Implement integer to string conversion macro
@@ -0,0 +1,249 @@
+//! # Paging
+//!
+//! Device memory is mapped with [`map_device`], which chooses how the CPU
+//! orders, merges and caches accesses to it by a [`MemoryAttribute`]:
+//!
+//! | Attribute        | AArch64 (MAIR) | x86_64 (PAT) |
+//! | ---------------- | -------------- | ------------ |
+//! | `Device`         | Device-nGnRE   | UC           |
+//! | `NonCacheable`   | Normal-NC      | UC-          |
+//! | `WriteCombining` | Normal-NC      | WC           |
+//! | `Normal`         | Normal WB      | WB           |
+//!
+//! MMIO registers need [`MemoryAttribute::Device`], the default, so every
+//! access reaches the device in program order. Framebuffers are written
+//! much faster as [`MemoryAttribute::WriteCombining`], which lets the CPU
+//! merge stores into bursts. [`MemoryAttribute::of_region`] picks the
+//! attribute for a region of the memory map.
+//!
+//! Mapping the same memory with two attributes is undefined on both
+//! architectures, so [`map_device`] refuses a range which overlaps a mapping
+//! of another attribute. Users which rely on an attribute check it with
+//! [`attribute_of`] in debug builds: the DMA pool requires its frames to be
+//! cacheable, since it maintains caches itself, and the boot framebuffer
+//! requires write combining.
+//!
+//! ## Current Status
+//!
+//! The kernel runs with the MMU disabled and memory identity mapped, so
+//! [`map_device`] returns the physical address and only records the
+//! attribute. Every access is `Device-nGnRnE` on AArch64 until page tables
+//! are set up with [`MAIR`] and the recorded mappings.
+//!
+//! ```rust,ignore
+//! let uart = 0x0900_0000..0x0900_1000;
+//! let regs = paging::map_device(uart, MemoryAttribute::default(),)?;
+//! let fb = paging::map_device(range, MemoryAttribute::WriteCombining,)?;
+//! ```
+
+use core::ops::Range;
+use core::sync::atomic::AtomicU8;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+use oso_error::Rslt;
+use oso_error::kernel::PagingError;
+use oso_error::oso_err;
+use oso_no_std_shared::bridge::boot_info::MemoryRegionKind;
+use oso_no_std_shared::units;
+use oso_no_std_shared::units::PhysAddr;
+
+/// Size of a page in bytes
+pub const PAGE_SIZE: u64 = units::PAGE_SIZE;
+/// Most device mappings [`map_device`] records
+pub const MAX_MAPPINGS: usize = 32;
+/// Value of `MAIR_EL1`. Index `i` holds the attribute whose
+/// [`MemoryAttribute::index`] is `i`
+pub const MAIR: u64 = {
+	let mut mair = 0;
+	let mut i = 0;
+	while i < MemoryAttribute::ALL.len() {
+		let attr = MemoryAttribute::ALL[i];
+		mair |= (attr.mair() as u64) << (attr.index() * 8);
+		i += 1;
+	}
+	mair
+};
+
+/// How the CPU accesses a mapping
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq,)]
+#[repr(u8)]
+pub enum MemoryAttribute {
+	/// Uncached, accesses are neither merged nor reordered. For MMIO
+	/// registers
+	#[default]
+	Device = 1,
+	/// Uncached normal memory. Accesses may be merged and reordered
+	NonCacheable,
+	/// Uncached normal memory whose stores are combined into bursts. For
+	/// framebuffers
+	WriteCombining,
+	/// Write-back cached normal memory
+	Normal,
+}
+
+impl MemoryAttribute {
+	pub const ALL: [Self; 4] =
+		[Self::Device, Self::NonCacheable, Self::WriteCombining, Self::Normal,];
+
+	/// Attribute of a mapping of a region of `kind`
+	pub const fn of_region(kind: MemoryRegionKind,) -> Self {
+		match kind {
+			MemoryRegionKind::Framebuffer => Self::WriteCombining,
+			MemoryRegionKind::Mmio | MemoryRegionKind::Reserved => Self::Device,
+			MemoryRegionKind::Usable
+			| MemoryRegionKind::Reclaimable
+			| MemoryRegionKind::Loader
+			| MemoryRegionKind::RuntimeCode
+			| MemoryRegionKind::RuntimeData
+			| MemoryRegionKind::AcpiReclaim
+			| MemoryRegionKind::AcpiNvs => Self::Normal,
+		}
+	}
+
+	/// Index of the attribute in [`MAIR`]
+	pub const fn index(&self,) -> usize {
+		*self as usize - 1
+	}
+
+	/// Attribute encoding of `MAIR_EL1`
+	pub const fn mair(&self,) -> u8 {
+		match self {
+			Self::Device => 0x04,
+			Self::NonCacheable | Self::WriteCombining => 0x44,
+			Self::Normal => 0xff,
+		}
+	}
+
+	/// Memory type of the page attribute table
+	pub const fn pat(&self,) -> u8 {
+		match self {
+			Self::Device => 0x00,
+			Self::NonCacheable => 0x07,
+			Self::WriteCombining => 0x01,
+			Self::Normal => 0x06,
+		}
+	}
+
+	/// Whether the CPU caches the memory. DMA with such memory needs cache
+	/// maintenance
+	pub const fn is_cacheable(&self,) -> bool {
+		matches!(self, Self::Normal)
+	}
+
+	const fn from_raw(raw: u8,) -> Option<Self,> {
+		match raw {
+			1 => Some(Self::Device,),
+			2 => Some(Self::NonCacheable,),
+			3 => Some(Self::WriteCombining,),
+			4 => Some(Self::Normal,),
+			_ => None,
+		}
+	}
+}
+
+/// Maps the device memory at the physical range `phys` with `attr`, and
+/// returns the address to access it at
+///
+/// The range is extended to whole pages. Mapping a range again with the same
+/// attribute returns the same address.
+///
+/// # Errors
+///
+/// - [`PagingError::Empty`] if `phys` is empty
+/// - [`PagingError::Conflict`] if part of the range is mapped with another
+///   attribute
+/// - [`PagingError::TableFull`] if [`MAX_MAPPINGS`] ranges are mapped
+/// - [`PagingError::OutOfRange`] if the last page ends past the address
+///   space
+pub fn map_device(
+	phys: Range<u64,>,
+	attr: MemoryAttribute,
+) -> Rslt<*mut u8, PagingError,> {
+	if phys.is_empty() {
+		return Err(oso_err!(PagingError::Empty),);
+	}
+	let pages = PhysAddr::page_span(phys.start.into(), phys.end.into(),)
+		.ok_or(oso_err!(PagingError::OutOfRange),)?;
+	let (start, end,) = (pages.start.as_u64(), pages.end.as_u64(),);
+
+	let mut covered = false;
+	for mapping in mappings() {
+		if mapping.start < end && start < mapping.end {
+			if mapping.attr != attr {
+				let existing = mapping.attr as u8;
+				return Err(oso_err!(PagingError::Conflict { existing }),);
+			}
+			covered |= mapping.start <= start && end <= mapping.end;
+		}
+	}
+	if !covered {
+		let i = LEN.fetch_add(1, Ordering::AcqRel,);
+		let Some(slot,) = TABLE.get(i,) else {
+			LEN.fetch_sub(1, Ordering::AcqRel,);
+			return Err(oso_err!(PagingError::TableFull),);
+		};
+		slot.start.store(start, Ordering::Relaxed,);
+		slot.end.store(end, Ordering::Relaxed,);
+		slot.attr.store(attr as u8, Ordering::Release,);
+	}
+	// identity mapped while the MMU is disabled
+	Ok(phys.start as *mut u8,)
+}
+
+/// Attribute of the first mapping which overlaps `phys`, or `None` if the
+/// range is only reached through the identity map of normal memory
+pub fn attribute_of(phys: Range<u64,>,) -> Option<MemoryAttribute,> {
+	mappings()
+		.find(|mapping| mapping.start < phys.end && phys.start < mapping.end,)
+		.map(|mapping| mapping.attr,)
+}
+
+/// Device mapping recorded by [`map_device`]
+///
+/// # Fields
+///
+/// * `start` - Physical address of the first page
+/// * `end` - Physical end address, exclusive
+/// * `attr` - Attribute the range is mapped with
+#[derive(Debug, Clone, PartialEq, Eq,)]
+pub struct Mapping {
+	pub start: u64,
+	pub end:   u64,
+	pub attr:  MemoryAttribute,
+}
+
+/// Device mappings in the order they were made
+pub fn mappings() -> impl Iterator<Item = Mapping,> {
+	let len = LEN.load(Ordering::Acquire,).min(MAX_MAPPINGS,);
+	TABLE[..len].iter().filter_map(|slot| {
+		let attr = slot.attr.load(Ordering::Acquire,);
+		let attr = MemoryAttribute::from_raw(attr,)?;
+		Some(Mapping {
+			start: slot.start.load(Ordering::Relaxed,),
+			end: slot.end.load(Ordering::Relaxed,),
+			attr,
+		},)
+	},)
+}
+
+/// recorded mapping. `attr` is 0 until the range is written
+struct Slot {
+	start: AtomicU64,
+	end:   AtomicU64,
+	attr:  AtomicU8,
+}
+
+impl Slot {
+	const fn new() -> Self {
+		Self {
+			start: AtomicU64::new(0,),
+			end:   AtomicU64::new(0,),
+			attr:  AtomicU8::new(0,),
+		}
+	}
+}
+
+static TABLE: [Slot; MAX_MAPPINGS] = [const { Slot::new() }; MAX_MAPPINGS];
+/// number of slots taken
+static LEN: AtomicUsize = AtomicUsize::new(0,);
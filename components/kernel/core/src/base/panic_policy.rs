@@ -0,0 +1,84 @@
+//! # Panic Policy
+//!
+//! Applies the [`PanicPolicy`] chosen on the kernel command line once the
+//! panic handler has reported a panic:
+//!
+//! - `panic=halt`: Waits for events forever. The default
+//! - `panic=reboot`: Resets the system with [`power::reboot`]
+//! - `panic=blink`: Spins toggling the LED on pin `panic_led=<pin>` through
+//!   [`gpio::set`], e.g. `panic=blink panic_led=42` on the Raspberry Pi 4
+//! - `panic=exit`: Exits QEMU with [`qemu_exit::PANICKED`], for test runs
+//!
+//! Unknown values are ignored. Blinking halts instead if no GPIO controller
+//! is installed or it has no such pin.
+
+use crate::base::env;
+use crate::base::power;
+use crate::driver::gpio;
+use crate::driver::gpio::Level;
+use crate::driver::qemu_exit;
+use core::sync::atomic::AtomicU8;
+use core::sync::atomic::AtomicU32;
+use core::sync::atomic::Ordering;
+use oso_no_std_shared::bridge::panic_policy::PanicPolicy;
+
+/// Busy-loop iterations between toggles of the LED. There is no timer to
+/// rely on during a panic, so the rate depends on the processor
+pub const BLINK_SPINS: u32 = 20_000_000;
+
+/// pin used when blinking without `panic_led`
+const NO_PIN: u32 = u32::MAX;
+
+static POLICY: AtomicU8 = AtomicU8::new(PanicPolicy::Halt as u8,);
+static LED: AtomicU32 = AtomicU32::new(NO_PIN,);
+
+/// Reads `panic` and `panic_led` of the kernel command line. Called after
+/// [`env::init`]
+pub fn init() {
+	let policy = env::get("cmdline.panic",).and_then(PanicPolicy::parse,);
+	if let Some(policy,) = policy {
+		set(policy,);
+	}
+	let pin = env::get("cmdline.panic_led",).and_then(|v| v.parse().ok(),);
+	if let Some(pin,) = pin {
+		set_led(pin,);
+	}
+}
+
+pub fn set(policy: PanicPolicy,) {
+	POLICY.store(policy as u8, Ordering::Relaxed,);
+}
+
+pub fn get() -> PanicPolicy {
+	PanicPolicy::from_u8(POLICY.load(Ordering::Relaxed,),).unwrap_or_default()
+}
+
+/// Pin of the LED blinked by [`PanicPolicy::Blink`]
+pub fn set_led(pin: u32,) {
+	LED.store(pin, Ordering::Relaxed,);
+}
+
+/// Applies the policy. Called by the panic handler after reporting
+pub fn act() -> ! {
+	match get() {
+		PanicPolicy::Halt => oso_no_std_shared::wfe(),
+		PanicPolicy::Reboot => power::reboot(),
+		PanicPolicy::Blink => blink(),
+		PanicPolicy::Exit => qemu_exit::qemu_exit(qemu_exit::PANICKED,),
+	}
+}
+
+/// Toggles the LED forever, or halts if it can not be driven
+fn blink() -> ! {
+	let pin = LED.load(Ordering::Relaxed,);
+	let mut level = Level::High;
+	loop {
+		if pin == NO_PIN || gpio::set(pin, level,).is_err() {
+			oso_no_std_shared::wfe()
+		}
+		level = level.toggled();
+		for _ in 0..BLINK_SPINS {
+			core::hint::spin_loop();
+		}
+	}
+}
@@ -0,0 +1,313 @@
+//! # Performance Counters
+//!
+//! Cycle and instruction counts from the AArch64 performance monitors unit,
+//! for deciding where optimization of paths such as the parser and the
+//! graphics code pays off.
+//!
+//! - [`measure`]: Counts a closure
+//! - [`Sampler`]: Records the interrupted PC on each timer interrupt into a
+//!   ring. [`Sampler::report`] counts the samples of each symbol of
+//!   [`symbols`](crate::base::symbols), and printed samples are resolved on
+//!   the host with `oso_dev_util::elf::symbolize`. [`SAMPLES`] is the ring
+//!   of the kernel
+//! - [`irq`]: Latency of each IRQ against a budget
+//! - [`boot`]: Time of each subsystem initialized at boot against a budget
+//! - [`idle`]: Residency of each core in each idle state
+//! - [`trace`]: Tracepoints recorded into per-core rings, exported as a
+//!   Chrome trace on the host
+//! - [`record`]: Input bytes and timer ticks, replayed by the simulator
+//! - [`heap`]: Allocations recorded into the trace rings, folded into a
+//!   flamegraph on the host
+//!
+//! On x86_64 cycles are read from the time stamp counter and instructions
+//! are not counted.
+//!
+//! ## Current Status
+//!
+//! The kernel has no timer interrupt yet, so nothing feeds [`SAMPLES`] until
+//! the handler calls [`Sampler::record`] with `ELR_EL1`.
+//!
+//! ```rust,ignore
+//! use oso_kernel::base::perf;
+//!
+//! perf::init();
+//! let (tree, cost,) = perf::measure(|| parse(src,),);
+//! println!("parse: {} cycles", cost.cycles);
+//! ```
+
+/// Initialization time of boot stages
+pub mod boot;
+/// Heap allocations recorded as trace events
+pub mod heap;
+/// Idle states and their residency
+pub mod idle;
+/// Interrupt latency statistics
+pub mod irq;
+/// Recording of input and timer ticks for replays
+pub mod record;
+/// Event tracing
+pub mod trace;
+
+use crate::base::symbols;
+use core::cmp::Reverse;
+use core::fmt;
+use core::ops::Sub;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+
+/// Number of PCs kept by [`SAMPLES`]
+pub const SAMPLE_COUNT: usize = 1024;
+
+/// Most symbols listed by [`Sampler::report`]. Samples of further symbols
+/// are counted together
+pub const REPORT_SYMBOLS: usize = 32;
+
+/// Sampling ring of the kernel, fed by the timer interrupt
+pub static SAMPLES: Sampler<SAMPLE_COUNT,> = Sampler::new();
+
+/// Counter values, or their difference
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq,)]
+pub struct Counters {
+	pub cycles:       u64,
+	pub instructions: u64,
+}
+
+impl Counters {
+	/// Current values
+	pub fn read() -> Self {
+		Self { cycles: cycles(), instructions: instructions(), }
+	}
+}
+
+impl Sub for Counters {
+	type Output = Self;
+
+	fn sub(self, rhs: Self,) -> Self::Output {
+		Self {
+			cycles:       self.cycles.wrapping_sub(rhs.cycles,),
+			// the instruction counter is 32 bits wide, see [`instructions`]
+			instructions: (self.instructions as u32)
+				.wrapping_sub(rhs.instructions as u32,)
+				as u64,
+		}
+	}
+}
+
+/// Runs `f` and returns its result with the counts it took
+pub fn measure<R,>(f: impl FnOnce() -> R,) -> (R, Counters,) {
+	let start = Counters::read();
+	let r = f();
+	(r, Counters::read() - start,)
+}
+
+/// Enables the cycle counter and counts retired instructions with event
+/// counter 0
+#[cfg(all(target_arch = "aarch64", not(feature = "hosted")))]
+pub fn init() {
+	use core::arch::asm;
+
+	/// PMCR_EL0: enable, reset event counters and reset the cycle counter
+	const PMCR: u64 = 0b111;
+	/// PMCNTENSET_EL0: cycle counter and event counter 0
+	const COUNTERS: u64 = 1 << 31 | 1;
+	/// architectural event `INST_RETIRED`
+	const INST_RETIRED: u64 = 0x08;
+	unsafe {
+		asm!(
+			"msr pmevtyper0_el0, {event}",
+			"msr pmccfiltr_el0, xzr",
+			"msr pmcntenset_el0, {counters}",
+			"msr pmcr_el0, {pmcr}",
+			"isb",
+			event = in(reg) INST_RETIRED,
+			counters = in(reg) COUNTERS,
+			pmcr = in(reg) PMCR,
+		);
+	}
+}
+
+/// The time stamp counter always runs, and hosted builds count nothing
+#[cfg(any(not(target_arch = "aarch64"), feature = "hosted"))]
+pub fn init() {}
+
+/// Cycles since [`init`]
+#[cfg(all(target_arch = "aarch64", not(feature = "hosted")))]
+pub fn cycles() -> u64 {
+	let cycles;
+	unsafe { core::arch::asm!("mrs {}, pmccntr_el0", out(reg) cycles) };
+	cycles
+}
+
+/// Time stamp counter, which counts at a fixed rate close to cycles
+#[cfg(all(target_arch = "x86_64", not(feature = "hosted")))]
+pub fn cycles() -> u64 {
+	unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+#[cfg(not(any(
+	target_arch = "aarch64",
+	target_arch = "x86_64",
+	feature = "hosted"
+)))]
+pub fn cycles() -> u64 {
+	0
+}
+
+#[cfg(feature = "hosted")]
+pub use super::hosted::cycles;
+
+/// Core the caller runs on, as affinity level 0 of `MPIDR_EL1`
+#[cfg(all(target_arch = "aarch64", not(feature = "hosted")))]
+pub fn core_id() -> usize {
+	let mpidr: u64;
+	unsafe { core::arch::asm!("mrs {}, mpidr_el1", out(reg) mpidr) };
+	(mpidr & 0xff) as usize
+}
+
+/// Every core counts as core `0`
+#[cfg(not(any(target_arch = "aarch64", feature = "hosted")))]
+pub fn core_id() -> usize {
+	0
+}
+
+#[cfg(feature = "hosted")]
+pub use super::hosted::core_id;
+
+/// Instructions retired since [`init`]
+#[cfg(all(target_arch = "aarch64", not(feature = "hosted")))]
+pub fn instructions() -> u64 {
+	let count: u64;
+	unsafe { core::arch::asm!("mrs {}, pmevcntr0_el0", out(reg) count) };
+	// event counters are 32 bits wide without FEAT_PMUv3p5
+	count & u32::MAX as u64
+}
+
+/// Not counted
+#[cfg(not(any(target_arch = "aarch64", feature = "hosted")))]
+pub fn instructions() -> u64 {
+	0
+}
+
+#[cfg(feature = "hosted")]
+pub use super::hosted::instructions;
+
+/// Ring of sampled PCs
+///
+/// [`record`](Self::record) is called from interrupt context and never
+/// blocks. Once the ring is full, the oldest sample is overwritten.
+pub struct Sampler<const N: usize,> {
+	slots:   [AtomicU64; N],
+	/// samples recorded since the last clear
+	total:   AtomicUsize,
+	enabled: AtomicBool,
+}
+
+impl<const N: usize,> Sampler<N,> {
+	pub const fn new() -> Self {
+		Self {
+			slots:   [const { AtomicU64::new(0,) }; N],
+			total:   AtomicUsize::new(0,),
+			enabled: AtomicBool::new(false,),
+		}
+	}
+
+	pub fn start(&self,) {
+		self.enabled.store(true, Ordering::Release,);
+	}
+
+	pub fn stop(&self,) {
+		self.enabled.store(false, Ordering::Release,);
+	}
+
+	pub fn is_running(&self,) -> bool {
+		self.enabled.load(Ordering::Acquire,)
+	}
+
+	/// Records `pc` if running
+	pub fn record(&self, pc: u64,) {
+		if !self.is_running() {
+			return;
+		}
+		let i = self.total.fetch_add(1, Ordering::Relaxed,);
+		self.slots[i % N].store(pc, Ordering::Relaxed,);
+	}
+
+	/// Samples recorded since the last clear, including overwritten ones
+	pub fn total(&self,) -> usize {
+		self.total.load(Ordering::Relaxed,)
+	}
+
+	/// Samples in the ring, oldest first
+	///
+	/// Sampling should be stopped first, or samples may be overwritten while
+	/// iterating.
+	pub fn samples(&self,) -> impl Iterator<Item = u64,> + '_ {
+		let total = self.total();
+		let start = total.saturating_sub(N,);
+		(start..total).map(|i| self.slots[i % N].load(Ordering::Relaxed,),)
+	}
+
+	pub fn clear(&self,) {
+		self.total.store(0, Ordering::Relaxed,);
+	}
+
+	/// Writes the samples of each symbol, most sampled first
+	///
+	/// Symbols past the first [`REPORT_SYMBOLS`] found, and samples outside
+	/// every symbol, are written as one line each. Sampling should be
+	/// stopped first.
+	pub fn report(&self, out: &mut impl fmt::Write,) -> fmt::Result {
+		let mut hot = [("", 0,); REPORT_SYMBOLS];
+		let mut len = 0;
+		let (mut others, mut unknown, mut total,) = (0, 0, 0,);
+		for pc in self.samples() {
+			total += 1;
+			let Some((name, _,),) = symbols::resolve(pc,) else {
+				unknown += 1;
+				continue;
+			};
+			match hot[..len].iter_mut().find(|(symbol, _,)| *symbol == name,) {
+				Some((_, count,),) => *count += 1,
+				None if len < REPORT_SYMBOLS => {
+					hot[len] = (name, 1,);
+					len += 1;
+				},
+				None => others += 1,
+			}
+		}
+		hot[..len].sort_unstable_by_key(|&(_, count,)| Reverse(count,),);
+
+		let lines = hot[..len].iter().copied().chain([
+			("(other symbols)", others,),
+			("(unknown)", unknown,),
+		],);
+		for (name, count,) in lines.filter(|(_, count,)| *count != 0,) {
+			let percent = count * 100 / total;
+			writeln!(out, "{count:>6} {percent:>3}% {name}")?;
+		}
+		Ok((),)
+	}
+}
+
+impl<const N: usize,> Default for Sampler<N,> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_counters_wrap() {
+		let start =
+			Counters { cycles: u64::MAX - 1, instructions: 0xffff_fff0, };
+		let end = Counters { cycles: 3, instructions: 0x10, };
+		let delta = Counters { cycles: 5, instructions: 0x20, };
+		assert_eq!(end - start, delta);
+		assert_eq!(start - start, Counters::default());
+	}
+}
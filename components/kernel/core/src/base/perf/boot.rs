@@ -0,0 +1,225 @@
+//! # Boot Profile
+//!
+//! Runs the subsystems initialized at boot as [`Stage`]s, each with the
+//! time its initialization is expected to take, and times them with
+//! [`timestamp`]. A stage taking longer than its budget is reported as a
+//! warning of the kernel log, so a boot latency regression points at the
+//! subsystem which caused it instead of at the boot as a whole.
+//!
+//! Warnings are written once every stage has run, as the console may be
+//! one of the stages. With `test=exit` on the kernel command line, a stage
+//! over budget fails the boot test by exiting QEMU with
+//! [`qemu_exit::FAILED`].
+//!
+//! ## Shell
+//!
+//! [`run_command`] implements the `bootprof` shell command, which prints the
+//! time and budget of each stage. It also prints the [`Milestones`] the
+//! loader stamped after `ExitBootServices` and when the first stage started,
+//! in microseconds since the loader exited boot services, as the loader and
+//! [`timestamp`] read the same counter.
+//!
+//! ## Current Status
+//!
+//! The frequency of the time stamp counter is unknown on x86_64, so stages
+//! are timed but not checked against their budgets there. Only the stages
+//! of [`init`](crate::init) are profiled yet, as the earlier ones of the
+//! entry points take arguments.
+//!
+//! ```rust,ignore
+//! static STAGES: [Stage; 2] = [
+//! 	Stage::new("spin", 100, spin::init,),
+//! 	Stage::new("io", 5_000, io::init,),
+//! ];
+//!
+//! boot::run(&STAGES,);
+//! ```
+
+use super::trace::frequency;
+use super::trace::timestamp;
+use crate::base::env;
+use crate::base::handoff;
+use crate::driver::qemu_exit;
+use crate::log;
+use core::fmt;
+use core::ptr;
+use core::sync::atomic::AtomicPtr;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+use oso_error::Rslt;
+use oso_error::kernel::BootProfileError;
+use oso_error::oso_err;
+use oso_no_std_shared::bridge::boot_info::Milestones;
+
+/// Name of the shell command handled by [`run_command`]
+pub const COMMAND: &str = "bootprof";
+/// Stages which are profiled. Later ones still run, untimed
+pub const MAX_STAGES: usize = 32;
+
+static SLOTS: [Slot; MAX_STAGES] = [const { Slot::new() }; MAX_STAGES];
+/// [`timestamp`] when the first stage of the last [`run`] started
+static STARTED: AtomicU64 = AtomicU64::new(0,);
+
+/// Subsystem initialized at boot
+///
+/// # Fields
+///
+/// * `budget` - Microseconds `init` is expected to take at most
+#[derive(Debug, Clone, Copy,)]
+pub struct Stage {
+	pub name:   &'static str,
+	pub budget: u64,
+	pub init:   fn(),
+}
+
+impl Stage {
+	pub const fn new(name: &'static str, budget: u64, init: fn(),) -> Self {
+		Self { name, budget, init, }
+	}
+}
+
+/// Time a stage took
+///
+/// # Fields
+///
+/// * `elapsed` - Microseconds, `None` if the frequency of [`timestamp`] is
+///   unknown
+/// * `ticks` - Ticks of [`timestamp`]
+#[derive(Debug, Clone, Copy,)]
+pub struct Profile {
+	pub stage:   &'static Stage,
+	pub ticks:   u64,
+	pub elapsed: Option<u64,>,
+}
+
+impl Profile {
+	/// Whether the stage took longer than its budget. `false` if the time
+	/// is unknown
+	pub fn is_over(&self,) -> bool {
+		self.elapsed.is_some_and(|elapsed| elapsed > self.stage.budget,)
+	}
+}
+
+impl fmt::Display for Profile {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		let Stage { name, budget, .. } = self.stage;
+		write!(f, "{name:<16}")?;
+		match self.elapsed {
+			Some(elapsed,) => write!(f, " {elapsed:>10}")?,
+			None => write!(f, " {:>10}", "?")?,
+		}
+		write!(f, " {budget:>10}")?;
+		if self.is_over() {
+			write!(f, " over")?;
+		}
+		Ok((),)
+	}
+}
+
+/// Runs `stages` in order and records their time, then warns of those over
+/// budget. Returns the number of stages over budget
+///
+/// Stages already profiled by an earlier call are replaced from the first
+/// slot on.
+pub fn run(stages: &'static [Stage],) -> usize {
+	reset();
+	STARTED.store(timestamp(), Ordering::Relaxed,);
+	for (i, stage,) in stages.iter().enumerate() {
+		let start = timestamp();
+		(stage.init)();
+		let ticks = timestamp().wrapping_sub(start,);
+		if let Some(slot,) = SLOTS.get(i,) {
+			slot.ticks.store(ticks, Ordering::Relaxed,);
+			let stage = ptr::from_ref(stage,).cast_mut();
+			slot.stage.store(stage, Ordering::Release,);
+		}
+	}
+
+	let mut over = 0;
+	for profile in profiles().filter(Profile::is_over,) {
+		over += 1;
+		let Stage { name, budget, .. } = profile.stage;
+		let elapsed = profile.elapsed.unwrap_or_default();
+		log!(Warn, "boot: {name} took {elapsed} us, budget {budget} us");
+	}
+	if over != 0 && env::get("cmdline.test",) == Some("exit",) {
+		log!(Error, "boot: {over} stages over budget");
+		qemu_exit::qemu_exit(qemu_exit::FAILED,);
+	}
+	over
+}
+
+/// Time of each stage of the last [`run`], in the order they ran
+pub fn profiles() -> impl Iterator<Item = Profile,> {
+	let frequency = frequency();
+	SLOTS.iter().map_while(move |slot| {
+		// SAFETY: `run` only stores stages of a `'static` slice
+		let stage = unsafe { slot.stage.load(Ordering::Acquire,).as_ref()? };
+		let ticks = slot.ticks.load(Ordering::Relaxed,);
+		let elapsed = (frequency != 0)
+			.then(|| (ticks as u128 * 1_000_000 / frequency as u128) as u64,);
+		Some(Profile { stage, ticks, elapsed, },)
+	},)
+}
+
+/// Forgets the stages of the last [`run`]
+fn reset() {
+	for slot in &SLOTS {
+		slot.stage.store(ptr::null_mut(), Ordering::Release,);
+	}
+}
+
+/// Runs the `bootprof` shell command with the arguments after its name
+pub fn run_command(
+	args: &[&str],
+	out: &mut impl fmt::Write,
+) -> Rslt<(), BootProfileError,> {
+	if !args.is_empty() {
+		let _ = writeln!(out, "usage: {COMMAND}");
+		return Err(oso_err!(BootProfileError::Usage),);
+	}
+	let _ = writeln!(out, "{:<16} {:>10} {:>10}", "stage", "us", "budget");
+	for profile in profiles() {
+		let _ = writeln!(out, "{profile}");
+	}
+	if let Some(boot_info,) = handoff::boot_info() {
+		let _ = write_milestones(&boot_info.milestones, out,);
+	}
+	Ok((),)
+}
+
+/// Writes the milestones of the loader and the start of the first stage,
+/// unless the loader stamped none
+fn write_milestones(
+	milestones: &Milestones,
+	out: &mut impl fmt::Write,
+) -> fmt::Result {
+	if milestones.read_exit_boot_services() == 0 {
+		return Ok((),);
+	}
+	writeln!(out, "\n{:<18} {:>10}", "milestone", "us")?;
+	let started = ("first stage", STARTED.load(Ordering::Relaxed,),);
+	for (name, ticks,) in milestones.named().into_iter().chain([started,],) {
+		match milestones.micros_since_exit(ticks,) {
+			Some(us,) => writeln!(out, "{name:<18} {us:>10}")?,
+			None => writeln!(out, "{name:<18} {:>10}", "?")?,
+		}
+	}
+	Ok((),)
+}
+
+/// profiled stage, written once by [`run`]
+struct Slot {
+	/// null until the stage ran
+	stage: AtomicPtr<Stage,>,
+	ticks: AtomicU64,
+}
+
+impl Slot {
+	const fn new() -> Self {
+		Self {
+			stage: AtomicPtr::new(ptr::null_mut(),),
+			ticks: AtomicU64::new(0,),
+		}
+	}
+}
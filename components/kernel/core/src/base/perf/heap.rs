@@ -0,0 +1,139 @@
+//! # Heap Profiling
+//!
+//! [`Traced`] wraps a [`GlobalAlloc`] and, with the `alloc_trace` feature,
+//! records every allocation into the [`trace`] rings: its size, its
+//! alignment and a hash of its call site. `cargo xtask heap <log>` folds the
+//! allocations of a trace dump into stacks for `inferno-flamegraph` or
+//! `flamegraph.pl`, weighted by bytes, to see which subsystems dominate heap
+//! usage over a boot.
+//!
+//! ## Events
+//!
+//! Events are recorded under the category `heap` while tracing runs:
+//!
+//! - `alloc`: An allocation, with the argument packed by [`pack`]
+//! - `site`: The first allocation of a call site, with its hash. Followed by
+//!   one `frame` event per return address of the call site, innermost first
+//!
+//! The call site is the chain of up to [`MAX_FRAMES`] return addresses
+//! above the allocator, hashed to [`SITE_BITS`] bits. A call site seen
+//! before only records `alloc`, as the host already knows its frames, and
+//! sites past [`MAX_SITES`] are recorded without frames.
+//!
+//! ## Current Status
+//!
+//! The kernel has no heap yet. Once it does, its allocator is wrapped:
+//!
+//! ```rust,ignore
+//! #[global_allocator]
+//! static HEAP: Traced<Heap,> = Traced(Heap::new(),);
+//! ```
+
+use super::trace;
+use crate::base::crash;
+use core::alloc::GlobalAlloc;
+use core::alloc::Layout;
+use core::sync::atomic::AtomicU32;
+use core::sync::atomic::Ordering;
+
+/// Return addresses hashed into a call site
+pub const MAX_FRAMES: usize = 8;
+/// Call sites whose frames are recorded
+pub const MAX_SITES: usize = 512;
+/// Bits of a call site hash
+pub const SITE_BITS: u32 = 24;
+
+/// hashes of the call sites whose frames were recorded, `0` if free
+static SITES: [AtomicU32; MAX_SITES] =
+	[const { AtomicU32::new(0,) }; MAX_SITES];
+
+/// Allocator recording the allocations of the allocator it wraps
+pub struct Traced<A,>(pub A,);
+
+unsafe impl<A: GlobalAlloc,> GlobalAlloc for Traced<A,> {
+	unsafe fn alloc(&self, layout: Layout,) -> *mut u8 {
+		record(layout,);
+		unsafe { self.0.alloc(layout,) }
+	}
+
+	unsafe fn alloc_zeroed(&self, layout: Layout,) -> *mut u8 {
+		record(layout,);
+		unsafe { self.0.alloc_zeroed(layout,) }
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout,) {
+		unsafe { self.0.dealloc(ptr, layout,) }
+	}
+
+	unsafe fn realloc(
+		&self,
+		ptr: *mut u8,
+		layout: Layout,
+		new_size: usize,
+	) -> *mut u8 {
+		// counted as an allocation of the new size, as the block may move
+		if let Ok(new,) = Layout::from_size_align(new_size, layout.align(),) {
+			record(new,);
+		}
+		unsafe { self.0.realloc(ptr, layout, new_size,) }
+	}
+}
+
+/// Argument of an `alloc` event: the size saturated to 32 bits, the log2 of
+/// the alignment in the next 8 bits and the call site in the top
+/// [`SITE_BITS`] bits
+pub const fn pack(size: usize, align: usize, site: u32,) -> u64 {
+	let size = if size > u32::MAX as usize { u32::MAX } else { size as u32 };
+	let align = align.trailing_zeros() as u64;
+	size as u64 | align << 32 | (site as u64) << (64 - SITE_BITS)
+}
+
+/// Records an allocation of `layout` if the `alloc_trace` feature is
+/// enabled and tracing runs. Never allocates
+#[inline(always)]
+fn record(layout: Layout,) {
+	if !cfg!(feature = "alloc_trace") || !trace::is_running() {
+		return;
+	}
+	let mut frames = [0; MAX_FRAMES];
+	// SAFETY: the kernel is built with frame pointers, see `crash::backtrace`
+	let depth = unsafe { crash::backtrace(&mut frames,) };
+	let frames = &frames[..depth];
+	let site = site_hash(frames,);
+	if is_new_site(site,) {
+		trace::event!("heap", "site", site);
+		for frame in frames {
+			trace::event!("heap", "frame", *frame);
+		}
+	}
+	let arg = pack(layout.size(), layout.align(), site,);
+	trace::event!("heap", "alloc", arg);
+}
+
+/// FNV-1a of the return addresses, folded to [`SITE_BITS`] bits. Never `0`,
+/// which marks a free slot of [`SITES`]
+fn site_hash(frames: &[u64],) -> u32 {
+	let hash = frames.iter().fold(0x811c_9dc5_u32, |hash, frame| {
+		frame.to_le_bytes().iter().fold(hash, |hash, b| {
+			(hash ^ *b as u32).wrapping_mul(0x0100_0193,)
+		},)
+	},);
+	let mask = (1 << SITE_BITS) - 1;
+	((hash >> SITE_BITS) ^ hash) & mask | 1
+}
+
+/// Whether `site` is seen for the first time, adding it to [`SITES`].
+/// `false` once the table is full
+fn is_new_site(site: u32,) -> bool {
+	let start = site as usize % MAX_SITES;
+	for i in 0..MAX_SITES {
+		let slot = &SITES[(start + i) % MAX_SITES];
+		let ordering = (Ordering::AcqRel, Ordering::Acquire,);
+		match slot.compare_exchange(0, site, ordering.0, ordering.1,) {
+			Ok(_,) => return true,
+			Err(seen,) if seen == site => return false,
+			Err(_,) => {},
+		}
+	}
+	false
+}
@@ -0,0 +1,264 @@
+//! # Idle States
+//!
+//! Waits for work in an idle state and keeps how often and how long each
+//! core resided in each state.
+//!
+//! ## States
+//!
+//! - [`IdleState::Wfi`]: Waits for an interrupt with `wfi`, or `hlt` on
+//!   x86_64
+//! - [`IdleState::Poll`]: Spins until there is work. Wakes up without the
+//!   exit latency of `wfi`, for latency experiments under QEMU
+//!
+//! Polling is chosen with [`set_polling`] or `idle=poll` on the kernel
+//! command line.
+//!
+//! ## Wakeup
+//!
+//! [`wait`] checks for work with interrupts masked and waits before
+//! unmasking them, so an interrupt which makes work between the check and
+//! the wait is not lost: a pending interrupt ends `wfi` even while masked,
+//! and `sti` only takes effect after the following `hlt`. Interrupts are
+//! taken once they are unmasked.
+//!
+//! ## Shell
+//!
+//! [`run_command`] implements the `idle` shell command:
+//!
+//! - `idle stats`: Prints the residency of each core in each state
+//! - `idle reset`: Clears the statistics
+//! - `idle poll on | off`: Switches polling
+//!
+//! ## Current Status
+//!
+//! Only the boot core runs yet, and on x86_64 every core counts as core `0`.
+//! The kernel has no interrupt controller driver yet, so `wfi` ends only on
+//! interrupts left pending by the firmware. While interrupts are masked on
+//! x86_64, `hlt` could only end by a non-maskable interrupt, so [`wait`]
+//! polls.
+//!
+//! ```rust,ignore
+//! perf::idle::init();
+//! perf::idle::wait(|| queue.has_work(),);
+//! ```
+
+use super::core_id;
+use super::cycles;
+use super::trace;
+use crate::base::env;
+#[cfg(feature = "hosted")]
+use crate::base::hosted::wait_for_interrupt;
+use crate::base::sched::disable_interrupts;
+use crate::base::sched::restore_interrupts;
+use core::fmt;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+use oso_error::Rslt;
+use oso_error::kernel::IdleError;
+use oso_error::oso_err;
+
+/// Name of the shell command handled by [`run_command`]
+pub const COMMAND: &str = "idle";
+/// Cores whose residency is kept
+pub const MAX_CORES: usize = 8;
+
+static POLLING: AtomicBool = AtomicBool::new(false,);
+static CORES: [[Residency; IdleState::COUNT]; MAX_CORES] =
+	[const { [const { Residency::new() }; IdleState::COUNT] }; MAX_CORES];
+
+/// Way of waiting for work
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum IdleState {
+	Wfi,
+	Poll,
+}
+
+impl IdleState {
+	pub const ALL: [Self; 2] = [Self::Wfi, Self::Poll,];
+	const COUNT: usize = Self::ALL.len();
+
+	pub const fn name(&self,) -> &'static str {
+		match self {
+			Self::Wfi => "wfi",
+			Self::Poll => "poll",
+		}
+	}
+}
+
+/// Residency of a core in a state
+///
+/// # Fields
+///
+/// * `entries` - Times the state was entered
+/// * `cycles` - [`cycles`] spent in the state
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq,)]
+pub struct IdleStats {
+	pub entries: u64,
+	pub cycles:  u64,
+}
+
+/// Reads `idle=poll` from the boot environment. Called after
+/// [`env::init`]
+pub fn init() {
+	set_polling(env::get("cmdline.idle",) == Some("poll",),);
+}
+
+/// Whether [`wait`] polls instead of waiting for an interrupt
+pub fn set_polling(polling: bool,) {
+	POLLING.store(polling, Ordering::Relaxed,);
+}
+
+pub fn is_polling() -> bool {
+	POLLING.load(Ordering::Relaxed,)
+}
+
+/// Waits in an idle state until `has_work` returns `true` or an interrupt
+/// arrives, then takes pending interrupts
+///
+/// `has_work` is called with interrupts masked. Returns at once if there is
+/// work.
+pub fn wait(has_work: impl Fn() -> bool,) {
+	let flags = disable_interrupts();
+	if has_work() {
+		restore_interrupts(flags,);
+		return;
+	}
+	let _span = trace::span!("idle", "wait");
+	let start = cycles();
+	let state = if is_polling() || !can_wake(flags,) {
+		restore_interrupts(flags,);
+		while !masked(&has_work,) {
+			core::hint::spin_loop();
+		}
+		IdleState::Poll
+	} else {
+		wait_for_interrupt();
+		restore_interrupts(flags,);
+		IdleState::Wfi
+	};
+	if let Some(core,) = CORES.get(core_id(),) {
+		core[state as usize].record(cycles().wrapping_sub(start,),);
+	}
+}
+
+/// Residency of `core` in `state`, `None` if the core is not kept
+pub fn stats(core: usize, state: IdleState,) -> Option<IdleStats,> {
+	CORES.get(core,).map(|states| states[state as usize].load(),)
+}
+
+/// Clears the statistics of every core
+pub fn reset() {
+	for residency in CORES.iter().flatten() {
+		residency.clear();
+	}
+}
+
+/// Runs the `idle` shell command with the arguments after its name
+pub fn run_command(
+	args: &[&str],
+	out: &mut impl fmt::Write,
+) -> Rslt<(), IdleError,> {
+	match args {
+		["stats",] => {
+			let mode = if is_polling() { "poll" } else { "wfi" };
+			let _ = writeln!(out, "idle by {mode}");
+			let _ = writeln!(
+				out,
+				"{:>4} {:<5} {:>10} {:>16}",
+				"core", "state", "entries", "cycles"
+			);
+			for (core, states,) in CORES.iter().enumerate() {
+				for state in IdleState::ALL {
+					let IdleStats { entries, cycles, } =
+						states[state as usize].load();
+					if entries != 0 {
+						let name = state.name();
+						let _ = writeln!(
+							out,
+							"{core:>4} {name:<5} {entries:>10} {cycles:>16}"
+						);
+					}
+				}
+			}
+		},
+		["reset",] => {
+			reset();
+			let _ = writeln!(out, "statistics cleared");
+		},
+		["poll", switch @ ("on" | "off"),] => {
+			set_polling(*switch == "on",);
+			let _ = writeln!(out, "polling {switch}");
+		},
+		_ => {
+			let usage = "stats | reset | poll on|off";
+			let _ = writeln!(out, "usage: {COMMAND} {usage}");
+			return Err(oso_err!(IdleError::Usage),);
+		},
+	}
+	Ok((),)
+}
+
+/// Calls `f` with interrupts masked
+fn masked(f: &impl Fn() -> bool,) -> bool {
+	let flags = disable_interrupts();
+	let r = f();
+	restore_interrupts(flags,);
+	r
+}
+
+struct Residency {
+	entries: AtomicU64,
+	cycles:  AtomicU64,
+}
+
+impl Residency {
+	const fn new() -> Self {
+		Self { entries: AtomicU64::new(0,), cycles: AtomicU64::new(0,), }
+	}
+
+	fn record(&self, cycles: u64,) {
+		self.entries.fetch_add(1, Ordering::Relaxed,);
+		self.cycles.fetch_add(cycles, Ordering::Relaxed,);
+	}
+
+	fn load(&self,) -> IdleStats {
+		IdleStats {
+			entries: self.entries.load(Ordering::Relaxed,),
+			cycles:  self.cycles.load(Ordering::Relaxed,),
+		}
+	}
+
+	fn clear(&self,) {
+		self.entries.store(0, Ordering::Relaxed,);
+		self.cycles.store(0, Ordering::Relaxed,);
+	}
+}
+
+/// Whether an interrupt can end the wait with `flags` restored after it.
+/// `wfi` ends on pending interrupts even while they are masked
+#[cfg(target_arch = "aarch64")]
+fn can_wake(_flags: usize,) -> bool {
+	true
+}
+
+/// Whether an interrupt can end the wait with `flags` restored after it.
+/// `hlt` only ends on interrupts which are enabled
+#[cfg(target_arch = "x86_64")]
+fn can_wake(flags: usize,) -> bool {
+	/// interrupt enable flag of RFLAGS
+	const IF: usize = 1 << 9;
+	flags & IF != 0
+}
+
+/// Waits with interrupts masked. On x86_64 interrupts are enabled for the
+/// `hlt` only, and the caller restores its mask
+#[cfg(not(feature = "hosted"))]
+fn wait_for_interrupt() {
+	unsafe {
+		#[cfg(target_arch = "aarch64")]
+		core::arch::asm!("wfi");
+		#[cfg(target_arch = "x86_64")]
+		core::arch::asm!("sti", "hlt", "cli");
+	}
+}
@@ -0,0 +1,346 @@
+//! # Interrupt Latency
+//!
+//! Times each interrupt with [`cycles`] at entry, when its handler is
+//! dispatched and at the end of interrupt, and keeps the minimum, average
+//! and maximum of each IRQ. Handlers which exceed the budget are reported,
+//! as they are the ones whose work should be deferred out of interrupt
+//! context.
+//!
+//! - Dispatch latency: Entry to dispatch, spent acknowledging and decoding
+//! - Handler time: Dispatch to end of interrupt, spent in the handler
+//!
+//! A handler exceeding the budget set with [`set_budget`] counts as an
+//! overrun. The first overrun of an IRQ and each one setting a new maximum
+//! is printed, so a slow handler does not flood the console.
+//!
+//! ## Shell
+//!
+//! [`run_command`] implements the `irq` shell command:
+//!
+//! - `irq stats`: Prints the statistics of each IRQ which was taken
+//! - `irq reset`: Clears the statistics
+//! - `irq budget [cycles]`: Prints or sets the budget. `0` disables it
+//!
+//! ## Current Status
+//!
+//! The kernel has no interrupt controller driver or workqueue yet, so only
+//! the timer tick of [`timer::interrupt`] is timed, as IRQ [`timer::IRQ`].
+//! The interrupt vector of the future driver brackets each IRQ with
+//! [`enter`], [`Trace::dispatch`] and [`Trace::eoi`]. Only IRQs below
+//! [`MAX_IRQS`] are timed.
+//!
+//! [`timer::interrupt`]: crate::base::timer::interrupt
+//! [`timer::IRQ`]: crate::base::timer::IRQ
+//!
+//! ```rust,ignore
+//! let irq = gic.acknowledge();
+//! let mut trace = perf::irq::enter(irq,);
+//! let handler = handlers[irq as usize];
+//! trace.dispatch();
+//! handler();
+//! gic.end_of_interrupt(irq,);
+//! trace.eoi();
+//! ```
+
+use super::cycles;
+use crate::println;
+use core::fmt;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+use oso_error::Rslt;
+use oso_error::kernel::IrqStatsError;
+use oso_error::oso_err;
+
+/// Name of the shell command handled by [`run_command`]
+pub const COMMAND: &str = "irq";
+/// IRQs timed. Covers the SGIs, PPIs and the SPIs of common boards
+pub const MAX_IRQS: usize = 256;
+
+/// handler time in cycles above which a handler overruns, `0` if none
+static BUDGET: AtomicU64 = AtomicU64::new(0,);
+static SLOTS: [Slot; MAX_IRQS] = [const { Slot::new() }; MAX_IRQS];
+
+/// Timestamps of an interrupt in progress, returned by [`enter`]
+#[must_use = "the interrupt is only recorded by `eoi`"]
+pub struct Trace {
+	irq:        u32,
+	entered:    u64,
+	dispatched: u64,
+}
+
+impl Trace {
+	/// Marks the dispatch to the handler
+	pub fn dispatch(&mut self,) {
+		self.dispatched = cycles();
+	}
+
+	/// Marks the end of interrupt and records the latencies. Without a
+	/// [`dispatch`](Self::dispatch), the whole interrupt counts as handler
+	/// time
+	pub fn eoi(self,) {
+		let Some(slot,) = SLOTS.get(self.irq as usize,) else {
+			return;
+		};
+		let handler = cycles().wrapping_sub(self.dispatched,);
+		let dispatch = self.dispatched.wrapping_sub(self.entered,);
+		let budget = BUDGET.load(Ordering::Relaxed,);
+		if slot.record(dispatch, handler, budget,) {
+			println!(
+				"WARN irq {}: handler took {handler} cycles, budget {budget}",
+				self.irq
+			);
+		}
+	}
+}
+
+/// Starts timing `irq`. Called at interrupt entry, once the IRQ number is
+/// known
+pub fn enter(irq: u32,) -> Trace {
+	let now = cycles();
+	Trace { irq, entered: now, dispatched: now, }
+}
+
+/// Handler time in cycles above which a handler overruns. `0` disables the
+/// check
+pub fn set_budget(cycles: u64,) {
+	BUDGET.store(cycles, Ordering::Relaxed,);
+}
+
+/// Budget set by [`set_budget`]
+pub fn budget() -> u64 {
+	BUDGET.load(Ordering::Relaxed,)
+}
+
+/// Latencies of one kind, in cycles
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq,)]
+pub struct Latency {
+	pub min:   u64,
+	pub max:   u64,
+	pub total: u64,
+}
+
+impl Latency {
+	/// Average over `count` interrupts
+	pub fn avg(&self, count: u64,) -> u64 {
+		self.total.checked_div(count,).unwrap_or(0,)
+	}
+}
+
+/// Statistics of one IRQ
+///
+/// # Fields
+///
+/// * `count` - Interrupts recorded
+/// * `dispatch` - Entry to dispatch
+/// * `handler` - Dispatch to end of interrupt
+/// * `overruns` - Interrupts whose handler time exceeded the budget
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq,)]
+pub struct IrqStats {
+	pub irq:      u32,
+	pub count:    u64,
+	pub dispatch: Latency,
+	pub handler:  Latency,
+	pub overruns: u64,
+}
+
+impl fmt::Display for IrqStats {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		let Self { irq, count, dispatch, handler, overruns, } = self;
+		write!(f, "{irq:>4} {count:>8}")?;
+		for latency in [dispatch, handler,] {
+			let avg = latency.avg(*count,);
+			write!(f, " {:>8} {avg:>8} {:>8}", latency.min, latency.max)?;
+		}
+		write!(f, " {overruns:>6}")
+	}
+}
+
+/// Statistics of every IRQ which was taken, in IRQ order
+pub fn stats() -> impl Iterator<Item = IrqStats,> {
+	SLOTS
+		.iter()
+		.enumerate()
+		.map(|(irq, slot,)| slot.stats(irq as u32,),)
+		.filter(|stats| stats.count != 0,)
+}
+
+/// Clears the statistics of every IRQ
+pub fn reset() {
+	for slot in &SLOTS {
+		slot.count.store(0, Ordering::Relaxed,);
+		slot.dispatch.clear();
+		slot.handler.clear();
+		slot.overruns.store(0, Ordering::Relaxed,);
+	}
+}
+
+/// Runs the `irq` shell command with the arguments after its name
+pub fn run_command(
+	args: &[&str],
+	out: &mut impl fmt::Write,
+) -> Rslt<(), IrqStatsError,> {
+	match args {
+		["stats",] => {
+			let _ = writeln!(
+				out,
+				"{:>4} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>6}",
+				"irq",
+				"count",
+				"dsp min",
+				"dsp avg",
+				"dsp max",
+				"hnd min",
+				"hnd avg",
+				"hnd max",
+				"over"
+			);
+			for stats in stats() {
+				let _ = writeln!(out, "{stats}");
+			}
+		},
+		["reset",] => {
+			reset();
+			let _ = writeln!(out, "statistics cleared");
+		},
+		["budget",] => {
+			let _ = match budget() {
+				0 => writeln!(out, "no budget"),
+				budget => writeln!(out, "budget {budget} cycles"),
+			};
+		},
+		["budget", cycles,] => {
+			let Ok(cycles,) = cycles.parse() else {
+				return Err(oso_err!(IrqStatsError::Usage),);
+			};
+			set_budget(cycles,);
+			let _ = writeln!(out, "budget {cycles} cycles");
+		},
+		_ => {
+			let usage = "stats | reset | budget [cycles]";
+			let _ = writeln!(out, "usage: {COMMAND} {usage}");
+			return Err(oso_err!(IrqStatsError::Usage),);
+		},
+	}
+	Ok((),)
+}
+
+/// statistics of one IRQ, updated from interrupt context without locks
+struct Slot {
+	count:    AtomicU64,
+	dispatch: Counter,
+	handler:  Counter,
+	overruns: AtomicU64,
+}
+
+impl Slot {
+	const fn new() -> Self {
+		Self {
+			count:    AtomicU64::new(0,),
+			dispatch: Counter::new(),
+			handler:  Counter::new(),
+			overruns: AtomicU64::new(0,),
+		}
+	}
+
+	/// Records an interrupt with latencies `dispatch` and `handler`.
+	/// Returns whether its overrun of `budget` is to be printed
+	fn record(&self, dispatch: u64, handler: u64, budget: u64,) -> bool {
+		self.count.fetch_add(1, Ordering::Relaxed,);
+		self.dispatch.record(dispatch,);
+		let max = self.handler.record(handler,);
+
+		if budget == 0 || handler <= budget {
+			return false;
+		}
+		let overruns = self.overruns.fetch_add(1, Ordering::Relaxed,);
+		overruns == 0 || handler > max
+	}
+
+	fn stats(&self, irq: u32,) -> IrqStats {
+		IrqStats {
+			irq,
+			count: self.count.load(Ordering::Relaxed,),
+			dispatch: self.dispatch.load(),
+			handler: self.handler.load(),
+			overruns: self.overruns.load(Ordering::Relaxed,),
+		}
+	}
+}
+
+struct Counter {
+	/// `u64::MAX` until the first record
+	min:   AtomicU64,
+	max:   AtomicU64,
+	total: AtomicU64,
+}
+
+impl Counter {
+	const fn new() -> Self {
+		Self {
+			min:   AtomicU64::new(u64::MAX,),
+			max:   AtomicU64::new(0,),
+			total: AtomicU64::new(0,),
+		}
+	}
+
+	/// Adds `cycles` and returns the previous maximum
+	fn record(&self, cycles: u64,) -> u64 {
+		self.min.fetch_min(cycles, Ordering::Relaxed,);
+		self.total.fetch_add(cycles, Ordering::Relaxed,);
+		self.max.fetch_max(cycles, Ordering::Relaxed,)
+	}
+
+	fn load(&self,) -> Latency {
+		let min = self.min.load(Ordering::Relaxed,);
+		Latency {
+			min:   if min == u64::MAX { 0 } else { min },
+			max:   self.max.load(Ordering::Relaxed,),
+			total: self.total.load(Ordering::Relaxed,),
+		}
+	}
+
+	fn clear(&self,) {
+		self.min.store(u64::MAX, Ordering::Relaxed,);
+		self.max.store(0, Ordering::Relaxed,);
+		self.total.store(0, Ordering::Relaxed,);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_counter() {
+		let counter = Counter::new();
+		assert_eq!(counter.load(), Latency::default());
+		assert_eq!(counter.record(30,), 0);
+		assert_eq!(counter.record(10,), 30);
+		assert_eq!(counter.record(20,), 30);
+		let latency = counter.load();
+		assert_eq!(latency, Latency { min: 10, max: 30, total: 60, });
+		assert_eq!(latency.avg(3,), 20);
+		assert_eq!(latency.avg(0,), 0);
+		counter.clear();
+		assert_eq!(counter.load(), Latency::default());
+	}
+
+	#[test]
+	fn test_overruns() {
+		let slot = Slot::new();
+		assert!(!slot.record(5, 50, 100,));
+		// the first overrun and each new maximum are printed
+		assert!(slot.record(5, 150, 100,));
+		assert!(!slot.record(5, 120, 100,));
+		assert!(slot.record(5, 200, 100,));
+		assert!(!slot.record(5, 100, 100,));
+		// without a budget
+		assert!(!slot.record(5, 1000, 0,));
+
+		let stats = slot.stats(30,);
+		assert_eq!((stats.irq, stats.count, stats.overruns,), (30, 6, 3,));
+		assert_eq!(stats.dispatch, Latency { min: 5, max: 5, total: 30, });
+		let handler = Latency { min: 50, max: 1000, total: 1620, };
+		assert_eq!(stats.handler, handler);
+	}
+}
@@ -0,0 +1,186 @@
+//! # Input Recording
+//!
+//! Records the bytes the shell reads and the timer ticks in between, in the
+//! order they happen. A recording made under QEMU is replayed by the
+//! simulator with `cargo xtask sim --replay <log>`, one tick per frame, so a
+//! scheduler or console bug which only shows with input arriving at a
+//! certain tick is reproduced on every run and kept as a regression test.
+//!
+//! - [`input`]: Records a byte read by the shell
+//! - [`tick`]: Records a timer tick, called by [`executor::tick`]
+//!
+//! Nothing is recorded until [`start`], or `record=on` on the kernel command
+//! line. Once [`LOG_LEN`] events are recorded, further ones are dropped and
+//! counted instead of overwriting the oldest, as a replay with a gap would
+//! go its own way.
+//!
+//! [`executor::tick`]: crate::app::executor::tick
+//!
+//! ## Shell
+//!
+//! [`run_command`] implements the `record` shell command:
+//!
+//! - `record start` / `record stop`: Switches recording
+//! - `record clear`: Drops the recorded events
+//! - `record status`: Prints the events recorded and dropped
+//! - `record dump`: Writes the recording in the format of
+//!   [`oso_no_std_shared::shell::replay`], as hex lines between its markers.
+//!   Recording pauses meanwhile
+//!
+//! ## Current Status
+//!
+//! The kernel has no timer interrupt and reads no input yet, so recordings
+//! under QEMU stay empty until the timer handler calls
+//! [`executor::tick`] and the receive handler of the UART shell calls
+//! [`input`]. The simulator calls both.
+//!
+//! ```rust,ignore
+//! record::start();
+//! // receive interrupt of the UART
+//! record::input(byte,);
+//! ```
+
+use crate::base::crash::DumpSink;
+use crate::base::crash::HexLines;
+use crate::base::env;
+use core::fmt;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicU16;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+use oso_error::Rslt;
+use oso_error::kernel::RecordError;
+use oso_error::oso_err;
+use oso_no_std_shared::shell::replay;
+use oso_no_std_shared::shell::replay::Encoder;
+use oso_no_std_shared::shell::replay::Event;
+
+/// Name of the shell command handled by [`run_command`]
+pub const COMMAND: &str = "record";
+/// Events kept. Later ones are dropped
+pub const LOG_LEN: usize = 8192;
+
+/// slot value of a tick. Input bytes are stored as they are
+const TICK: u16 = 0x100;
+
+static RUNNING: AtomicBool = AtomicBool::new(false,);
+/// events recorded since the last clear, including dropped ones
+static HEAD: AtomicUsize = AtomicUsize::new(0,);
+static LOG: [AtomicU16; LOG_LEN] = [const { AtomicU16::new(0,) }; LOG_LEN];
+
+/// Starts recording if `record=on` is on the kernel command line. Called
+/// after [`env::init`]
+pub fn init() {
+	if env::get("cmdline.record",) == Some("on",) {
+		start();
+	}
+}
+
+pub fn start() {
+	RUNNING.store(true, Ordering::Release,);
+}
+
+pub fn stop() {
+	RUNNING.store(false, Ordering::Release,);
+}
+
+pub fn is_running() -> bool {
+	RUNNING.load(Ordering::Acquire,)
+}
+
+/// Drops the recorded events
+pub fn clear() {
+	HEAD.store(0, Ordering::Relaxed,);
+}
+
+/// Records a byte read by the shell, if recording runs. Never blocks, so it
+/// is called from interrupt context
+pub fn input(byte: u8,) {
+	push(byte as u16,);
+}
+
+/// Records a timer tick, if recording runs
+pub fn tick() {
+	push(TICK,);
+}
+
+/// Events kept and events dropped since the last clear
+pub fn recorded() -> (usize, usize,) {
+	let head = HEAD.load(Ordering::Relaxed,);
+	(head.min(LOG_LEN,), head.saturating_sub(LOG_LEN,),)
+}
+
+/// Events kept, oldest first
+///
+/// Recording should be stopped first, or events may be added while
+/// iterating.
+pub fn events() -> impl Iterator<Item = Event,> {
+	let (kept, _,) = recorded();
+	LOG[..kept].iter().map(|slot| match slot.load(Ordering::Relaxed,) {
+		TICK => Event::Tick,
+		byte => Event::Input(byte as u8,),
+	},)
+}
+
+/// Writes the recording to `sink`
+pub fn dump<S: DumpSink,>(mut sink: S,) -> S {
+	let mut encoder = Encoder::new(|bytes: &[u8]| sink.write(bytes,),);
+	for event in events() {
+		encoder.push(event,);
+	}
+	encoder.finish();
+	sink.flush();
+	sink
+}
+
+/// Runs the `record` shell command with the arguments after its name
+pub fn run_command(
+	args: &[&str],
+	out: &mut impl fmt::Write,
+) -> Rslt<(), RecordError,> {
+	match args {
+		["start",] => {
+			start();
+			let _ = writeln!(out, "recording");
+		},
+		["stop",] => {
+			stop();
+			let _ = writeln!(out, "recording stopped");
+		},
+		["clear",] => {
+			clear();
+			let _ = writeln!(out, "events cleared");
+		},
+		["status",] => {
+			let state = if is_running() { "running" } else { "stopped" };
+			let (kept, dropped,) = recorded();
+			let _ = writeln!(out, "recording {state}");
+			let _ = writeln!(out, "{kept} events, {dropped} dropped");
+		},
+		["dump",] => {
+			let running = is_running();
+			stop();
+			let markers = (replay::BEGIN_MARKER, replay::END_MARKER,);
+			dump(HexLines::with_markers(&mut *out, markers.0, markers.1,),);
+			if running {
+				start();
+			}
+		},
+		_ => {
+			let usage = "start | stop | clear | status | dump";
+			let _ = writeln!(out, "usage: {COMMAND} {usage}");
+			return Err(oso_err!(RecordError::Usage),);
+		},
+	}
+	Ok((),)
+}
+
+fn push(value: u16,) {
+	if !is_running() {
+		return;
+	}
+	let i = HEAD.fetch_add(1, Ordering::Relaxed,);
+	if let Some(slot,) = LOG.get(i,) {
+		slot.store(value, Ordering::Relaxed,);
+	}
+}
@@ -0,0 +1,544 @@
+//! # Event Tracing
+//!
+//! Tracepoints record fixed-size events into a ring per core, cheap enough
+//! to leave in boot and scheduling paths. The rings are dumped over serial
+//! and converted on the host by `cargo xtask trace <log>` into the JSON of
+//! Chrome's `about://tracing`, for a timeline of what each core did.
+//!
+//! - [`event!`]: Instant event, with an optional `u64` argument
+//! - [`span!`]: Event lasting until the returned guard is dropped
+//!
+//! Nothing is recorded until [`start`], or `trace=on` on the kernel command
+//! line. Once a ring is full, its oldest events are overwritten.
+//!
+//! ## Format
+//!
+//! A dump starts with the magic `OSOT` and a version byte, followed by
+//! records of `tag: u8`, `len: u16` and `len` bytes of payload, like a crash
+//! dump. Integers are little endian.
+//!
+//! | tag | record | payload                                                   |
+//! |-----|--------|-----------------------------------------------------------|
+//! | 1   | clock  | `frequency: u64` of timestamps in Hz, `0` if unknown      |
+//! | 2   | names  | `id: u16`, `len: u8`, category, `len: u8`, name, repeated |
+//! | 3   | events | [`RECORD_SIZE`] bytes each, see [`Event`]                 |
+//! | 0   | end    | CRC-32 (IEEE) of every byte before this record            |
+//!
+//! There are as many names and events records as needed to fit their `len`.
+//! Dumps are written as hex lines between [`BEGIN_MARKER`] and
+//! [`END_MARKER`].
+//!
+//! ## Shell
+//!
+//! [`run_command`] implements the `trace` shell command:
+//!
+//! - `trace start` / `trace stop`: Switches recording
+//! - `trace clear`: Drops the recorded events
+//! - `trace status`: Prints the events recorded by each core
+//! - `trace dump`: Dumps the rings. Recording pauses meanwhile
+//!
+//! ## Current Status
+//!
+//! Only the boot core runs yet. Timestamps are the physical count of the
+//! generic timer on AArch64, which the loader stamps its milestones with as
+//! well, and the time stamp counter on x86_64, whose frequency is unknown.
+//!
+//! ```rust,ignore
+//! use oso_kernel::base::perf::trace;
+//!
+//! trace::start();
+//! trace::event!("sched", "wake", task.id() as u64);
+//! let _span = trace::span!("boot", "parse device tree");
+//! ```
+
+use super::core_id;
+use super::idle::MAX_CORES;
+use crate::base::cpu;
+use crate::base::crash::DumpSink;
+use crate::base::crash::HexLines;
+use crate::base::env;
+use core::fmt;
+use core::ptr;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicPtr;
+use core::sync::atomic::AtomicU16;
+use core::sync::atomic::AtomicU32;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+use oso_error::Rslt;
+use oso_error::kernel::TraceError;
+use oso_error::oso_err;
+
+/// Name of the shell command handled by [`run_command`]
+pub const COMMAND: &str = "trace";
+/// First bytes of every dump
+pub const MAGIC: [u8; 4] = *b"OSOT";
+/// Version of the format written by [`dump`]
+pub const VERSION: u8 = 1;
+/// Line before a hex encoded dump
+pub const BEGIN_MARKER: &str = "-----BEGIN OSO TRACE-----";
+/// Line after a hex encoded dump
+pub const END_MARKER: &str = "-----END OSO TRACE-----";
+/// Events kept per core
+pub const RING_LEN: usize = 512;
+/// Tracepoints which can be told apart. Events of later ones are dropped
+pub const MAX_TRACEPOINTS: usize = 256;
+/// Bytes of an event in a dump
+pub const RECORD_SIZE: usize = 24;
+
+/// Records an instant event of the category and name literals, with an
+/// optional argument cast to `u64`
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __trace_event {
+	($category:literal, $name:literal $(,)?) => {
+		$crate::__trace_event!(@record $category, $name, 0)
+	};
+	($category:literal, $name:literal, $arg:expr $(,)?) => {
+		$crate::__trace_event!(@record $category, $name, $arg as u64)
+	};
+	(@record $category:literal, $name:literal, $arg:expr) => {{
+		use $crate::base::perf::trace::Phase;
+		use $crate::base::perf::trace::Tracepoint;
+		static POINT: Tracepoint = Tracepoint::new($category, $name,);
+		POINT.record(Phase::Instant, $arg,);
+	}};
+}
+
+/// Records the beginning of an event of the category and name literals, and
+/// returns a guard recording its end when dropped
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __trace_span {
+	($category:literal, $name:literal $(,)?) => {
+		$crate::__trace_span!(@begin $category, $name, 0)
+	};
+	($category:literal, $name:literal, $arg:expr $(,)?) => {
+		$crate::__trace_span!(@begin $category, $name, $arg as u64)
+	};
+	(@begin $category:literal, $name:literal, $arg:expr) => {{
+		use $crate::base::perf::trace::Tracepoint;
+		static POINT: Tracepoint = Tracepoint::new($category, $name,);
+		POINT.span($arg,)
+	}};
+}
+
+pub use crate::__trace_event as event;
+pub use crate::__trace_span as span;
+
+static RUNNING: AtomicBool = AtomicBool::new(false,);
+static RINGS: [Ring; MAX_CORES] = [const { Ring::new() }; MAX_CORES];
+/// tracepoints by id - 1
+static POINTS: [AtomicPtr<Tracepoint,>; MAX_TRACEPOINTS] =
+	[const { AtomicPtr::new(ptr::null_mut(),) }; MAX_TRACEPOINTS];
+/// id of the next tracepoint hit for the first time
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1,);
+
+/// What an event marks
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+#[repr(u8)]
+pub enum Phase {
+	Instant = 0,
+	Begin = 1,
+	End = 2,
+}
+
+/// Event as read from a ring
+///
+/// # Fields
+///
+/// * `timestamp` - Ticks of the clock described in the module docs
+/// * `arg` - Argument given at the tracepoint
+/// * `id` - Tracepoint, numbered from `1` in the order they were first hit
+/// * `cpu` - Core which recorded the event
+///
+/// In a dump, the fields are followed by the phase and three zero bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct Event {
+	pub timestamp: u64,
+	pub arg:       u64,
+	pub id:        u16,
+	pub cpu:       u16,
+	pub phase:     Phase,
+}
+
+impl Event {
+	/// Bytes of the event in a dump
+	pub fn to_bytes(&self,) -> [u8; RECORD_SIZE] {
+		let mut bytes = [0; RECORD_SIZE];
+		bytes[..8].copy_from_slice(&self.timestamp.to_le_bytes(),);
+		bytes[8..16].copy_from_slice(&self.arg.to_le_bytes(),);
+		bytes[16..18].copy_from_slice(&self.id.to_le_bytes(),);
+		bytes[18..20].copy_from_slice(&self.cpu.to_le_bytes(),);
+		bytes[20] = self.phase as u8;
+		bytes
+	}
+}
+
+/// Source location of events, placed by [`event!`] and [`span!`]
+///
+/// Gets an id when first hit while tracing runs, so the rings store two
+/// bytes instead of the names.
+pub struct Tracepoint {
+	category: &'static str,
+	name:     &'static str,
+	/// `0` until assigned
+	id:       AtomicU16,
+}
+
+impl Tracepoint {
+	pub const fn new(category: &'static str, name: &'static str,) -> Self {
+		Self { category, name, id: AtomicU16::new(0,), }
+	}
+
+	pub fn category(&self,) -> &'static str {
+		self.category
+	}
+
+	pub fn name(&self,) -> &'static str {
+		self.name
+	}
+
+	/// Records an event if tracing runs
+	pub fn record(&'static self, phase: Phase, arg: u64,) {
+		if !is_running() {
+			return;
+		}
+		let ring = RINGS.get(core_id(),);
+		let (Some(id,), Some(ring,),) = (self.id(), ring,) else {
+			return;
+		};
+		ring.push(timestamp(), arg, id, phase,);
+	}
+
+	/// Records the beginning of an event, and its end when the returned
+	/// guard is dropped
+	pub fn span(&'static self, arg: u64,) -> Span {
+		self.record(Phase::Begin, arg,);
+		Span { point: self, arg, }
+	}
+
+	/// id of `self`, assigned on the first call. `None` if there are more
+	/// than [`MAX_TRACEPOINTS`]
+	fn id(&'static self,) -> Option<u16,> {
+		let id = self.id.load(Ordering::Acquire,);
+		if id != 0 {
+			return Some(id,);
+		}
+		let new = NEXT_ID.fetch_add(1, Ordering::Relaxed,);
+		let slot = POINTS.get(new - 1,)?;
+		slot.store(ptr::from_ref(self,).cast_mut(), Ordering::Release,);
+		// another core may have assigned an id meanwhile. the slot of the
+		// loser is a duplicate name, which the host ignores
+		let new = new as u16;
+		let ordering = (Ordering::AcqRel, Ordering::Acquire,);
+		match self.id.compare_exchange(0, new, ordering.0, ordering.1,) {
+			Ok(_,) => Some(new,),
+			Err(id,) => Some(id,),
+		}
+	}
+}
+
+/// Guard returned by [`span!`], recording the end of the event on drop
+#[must_use = "the span ends when the guard is dropped"]
+pub struct Span {
+	point: &'static Tracepoint,
+	arg:   u64,
+}
+
+impl Drop for Span {
+	fn drop(&mut self,) {
+		self.point.record(Phase::End, self.arg,);
+	}
+}
+
+/// Starts recording if `trace=on` is on the kernel command line. Called
+/// after [`env::init`]
+pub fn init() {
+	if env::get("cmdline.trace",) == Some("on",) {
+		start();
+	}
+}
+
+pub fn start() {
+	RUNNING.store(true, Ordering::Release,);
+}
+
+pub fn stop() {
+	RUNNING.store(false, Ordering::Release,);
+}
+
+pub fn is_running() -> bool {
+	RUNNING.load(Ordering::Acquire,)
+}
+
+/// Drops the events of every ring. Tracepoints keep their ids
+pub fn clear() {
+	for ring in &RINGS {
+		ring.head.store(0, Ordering::Relaxed,);
+	}
+}
+
+/// Events recorded by `core` since the last clear, including overwritten
+/// ones. `None` if the core has no ring
+pub fn recorded(core: usize,) -> Option<usize,> {
+	RINGS.get(core,).map(|ring| ring.head.load(Ordering::Relaxed,),)
+}
+
+/// Events in the rings, oldest first within each core
+///
+/// Tracing should be stopped first, or events may be overwritten while
+/// iterating.
+pub fn events() -> impl Iterator<Item = Event,> + Clone {
+	let rings = RINGS.iter().enumerate();
+	rings.flat_map(|(cpu, ring,)| ring.events(cpu as u16,),)
+}
+
+/// Tracepoints by id, starting at id `1`
+pub fn tracepoints()
+-> impl Iterator<Item = (u16, &'static Tracepoint,),> + Clone {
+	POINTS.iter().enumerate().filter_map(|(i, slot,)| {
+		let point = unsafe { slot.load(Ordering::Acquire,).as_ref()? };
+		Some((i as u16 + 1, point,),)
+	},)
+}
+
+/// Frequency of the timestamps in Hz, `0` if unknown
+#[cfg(target_arch = "aarch64")]
+pub fn frequency() -> u64 {
+	let frequency: u64;
+	unsafe { core::arch::asm!("mrs {}, cntfrq_el0", out(reg) frequency) };
+	frequency
+}
+
+/// Frequency of the timestamps in Hz, `0` if unknown
+#[cfg(not(target_arch = "aarch64"))]
+pub fn frequency() -> u64 {
+	0
+}
+
+/// Writes the rings and the names of the tracepoints to `sink`
+pub fn dump<S: DumpSink,>(sink: S,) -> S {
+	let mut writer = Writer { sink, crc: !0, };
+	writer.emit(&MAGIC,);
+	writer.emit(&[VERSION,],);
+
+	writer.begin(Tag::Clock, 8,);
+	writer.emit(&frequency().to_le_bytes(),);
+
+	let mut names = tracepoints().peekable();
+	while names.peek().is_some() {
+		let mut len = 0;
+		let mut count = 0;
+		for (_, point,) in names.clone() {
+			let size = name_size(point,);
+			if len + size > u16::MAX as usize {
+				break;
+			}
+			len += size;
+			count += 1;
+		}
+		writer.begin(Tag::Names, len,);
+		for (id, point,) in names.by_ref().take(count,) {
+			writer.emit(&id.to_le_bytes(),);
+			for text in [point.category, point.name,] {
+				let text = cut(text,);
+				writer.emit(&[text.len() as u8,],);
+				writer.emit(text,);
+			}
+		}
+	}
+
+	const PER_RECORD: usize = u16::MAX as usize / RECORD_SIZE;
+	let mut events = events().peekable();
+	while events.peek().is_some() {
+		let count = events.clone().take(PER_RECORD,).count();
+		writer.begin(Tag::Events, count * RECORD_SIZE,);
+		for event in events.by_ref().take(count,) {
+			writer.emit(&event.to_bytes(),);
+		}
+	}
+
+	let crc = !writer.crc;
+	writer.begin(Tag::End, 4,);
+	writer.sink.write(&crc.to_le_bytes(),);
+	writer.sink.flush();
+	writer.sink
+}
+
+/// Runs the `trace` shell command with the arguments after its name
+pub fn run_command(
+	args: &[&str],
+	out: &mut impl fmt::Write,
+) -> Rslt<(), TraceError,> {
+	match args {
+		["start",] => {
+			start();
+			let _ = writeln!(out, "tracing");
+		},
+		["stop",] => {
+			stop();
+			let _ = writeln!(out, "tracing stopped");
+		},
+		["clear",] => {
+			clear();
+			let _ = writeln!(out, "events cleared");
+		},
+		["status",] => {
+			let state = if is_running() { "running" } else { "stopped" };
+			let _ = writeln!(out, "tracing {state}");
+			let _ = writeln!(
+				out,
+				"{:>4} {:>10} {:>10}",
+				"core", "recorded", "kept"
+			);
+			for core in 0..MAX_CORES {
+				let recorded = recorded(core,).unwrap_or(0,);
+				if recorded != 0 {
+					let kept = recorded.min(RING_LEN,);
+					let _ =
+						writeln!(out, "{core:>4} {recorded:>10} {kept:>10}");
+				}
+			}
+		},
+		["dump",] => {
+			let running = is_running();
+			stop();
+			let markers = (BEGIN_MARKER, END_MARKER,);
+			dump(HexLines::with_markers(&mut *out, markers.0, markers.1,),);
+			if running {
+				start();
+			}
+		},
+		_ => {
+			let usage = "start | stop | clear | status | dump";
+			let _ = writeln!(out, "usage: {COMMAND} {usage}");
+			return Err(oso_err!(TraceError::Usage),);
+		},
+	}
+	Ok((),)
+}
+
+/// record tags
+#[derive(Clone, Copy,)]
+#[repr(u8)]
+enum Tag {
+	End = 0,
+	Clock = 1,
+	Names = 2,
+	Events = 3,
+}
+
+/// writes records to a sink, keeping the checksum
+struct Writer<S: DumpSink,> {
+	sink: S,
+	crc:  u32,
+}
+
+impl<S: DumpSink,> Writer<S,> {
+	fn begin(&mut self, tag: Tag, len: usize,) {
+		self.emit(&[tag as u8,],);
+		self.emit(&(len as u16).to_le_bytes(),);
+	}
+
+	fn emit(&mut self, bytes: &[u8],) {
+		self.crc = cpu::crc32(self.crc, bytes,);
+		self.sink.write(bytes,);
+	}
+}
+
+/// `text` cut to fit its `u8` length
+fn cut(text: &str,) -> &[u8] {
+	&text.as_bytes()[..text.len().min(u8::MAX as usize,)]
+}
+
+/// bytes of the entry of `point` in a names record
+fn name_size(point: &Tracepoint,) -> usize {
+	2 + 1 + cut(point.category,).len() + 1 + cut(point.name,).len()
+}
+
+/// ring of one core
+///
+/// [`push`](Self::push) is called from interrupt context and never blocks.
+/// An interrupt on the same core claims the next slot, so nested events are
+/// not lost.
+struct Ring {
+	/// events recorded since the last clear
+	head:  AtomicUsize,
+	slots: [Slot; RING_LEN],
+}
+
+impl Ring {
+	const fn new() -> Self {
+		Self {
+			head:  AtomicUsize::new(0,),
+			slots: [const { Slot::new() }; RING_LEN],
+		}
+	}
+
+	fn push(&self, timestamp: u64, arg: u64, id: u16, phase: Phase,) {
+		let i = self.head.fetch_add(1, Ordering::Relaxed,);
+		let slot = &self.slots[i % RING_LEN];
+		slot.timestamp.store(timestamp, Ordering::Relaxed,);
+		slot.arg.store(arg, Ordering::Relaxed,);
+		let meta = id as u32 | (phase as u32) << 16;
+		slot.meta.store(meta, Ordering::Relaxed,);
+	}
+
+	fn events(
+		&self,
+		cpu: u16,
+	) -> impl Iterator<Item = Event,> + Clone + '_ {
+		let head = self.head.load(Ordering::Relaxed,);
+		let start = head.saturating_sub(RING_LEN,);
+		(start..head).map(move |i| self.slots[i % RING_LEN].load(cpu,),)
+	}
+}
+
+struct Slot {
+	timestamp: AtomicU64,
+	arg:       AtomicU64,
+	/// id in the low half, phase above
+	meta:      AtomicU32,
+}
+
+impl Slot {
+	const fn new() -> Self {
+		Self {
+			timestamp: AtomicU64::new(0,),
+			arg:       AtomicU64::new(0,),
+			meta:      AtomicU32::new(0,),
+		}
+	}
+
+	fn load(&self, cpu: u16,) -> Event {
+		let meta = self.meta.load(Ordering::Relaxed,);
+		let phase = match meta >> 16 {
+			1 => Phase::Begin,
+			2 => Phase::End,
+			_ => Phase::Instant,
+		};
+		Event {
+			timestamp: self.timestamp.load(Ordering::Relaxed,),
+			arg: self.arg.load(Ordering::Relaxed,),
+			id: meta as u16,
+			cpu,
+			phase,
+		}
+	}
+}
+
+/// Physical count of the generic timer, which runs at [`frequency`]
+#[cfg(target_arch = "aarch64")]
+pub fn timestamp() -> u64 {
+	let count: u64;
+	unsafe { core::arch::asm!("mrs {}, cntpct_el0", out(reg) count) };
+	count
+}
+
+/// Time stamp counter
+#[cfg(not(target_arch = "aarch64"))]
+pub fn timestamp() -> u64 {
+	super::cycles()
+}
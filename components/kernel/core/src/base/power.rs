@@ -0,0 +1,121 @@
+//! # Power Control
+//!
+//! Reboot and power off. Each first asks the platform, then falls back to
+//! `ResetSystem` of the UEFI runtime services, and halts if both fail.
+//!
+//! ## Platform Methods
+//!
+//! - **AArch64**: PSCI `SYSTEM_RESET` and `SYSTEM_OFF`, called through the
+//!   conduit the `method` property of the `/psci` device tree node names
+//! - **x86_64**: Reboot through the reset control register at port `0xcf9`.
+//!   Power off needs ACPI, so only firmware can do it
+//! - **Hosted builds**: Both panic, see `hosted`
+//!
+//! ```rust,ignore
+//! unsafe { power::init(boot_info.device_tree,) };
+//! power::reboot();
+//! ```
+
+#[cfg(not(feature = "hosted"))]
+use super::efi;
+#[cfg(not(feature = "hosted"))]
+use super::efi::ResetKind;
+use core::sync::atomic::AtomicU8;
+use core::sync::atomic::Ordering;
+use oso_no_std_shared::bridge::device_tree::DeviceTreeAddress;
+#[cfg(target_arch = "aarch64")]
+use oso_no_std_shared::bridge::device_tree::Fdt;
+
+/// PSCI `SYSTEM_OFF`
+#[cfg(all(target_arch = "aarch64", not(feature = "hosted")))]
+const PSCI_SYSTEM_OFF: u64 = 0x8400_0008;
+/// PSCI `SYSTEM_RESET`
+#[cfg(all(target_arch = "aarch64", not(feature = "hosted")))]
+const PSCI_SYSTEM_RESET: u64 = 0x8400_0009;
+
+static CONDUIT: AtomicU8 = AtomicU8::new(Conduit::None as u8,);
+
+/// Instruction PSCI calls are made with
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum Conduit {
+	/// No PSCI firmware
+	None,
+	/// Hypervisor call, to firmware at EL2
+	Hvc,
+	/// Secure monitor call, to firmware at EL3
+	Smc,
+}
+
+/// Finds the PSCI conduit in the device tree
+///
+/// # Safety
+///
+/// `device_tree` must be null or point to a valid device tree blob
+pub unsafe fn init(device_tree: DeviceTreeAddress,) {
+	#[cfg(target_arch = "aarch64")]
+	{
+		let method = unsafe { Fdt::from_addr(device_tree,) }
+			.and_then(|fdt| fdt.find("/psci",),)
+			.and_then(|node| node.property("method",),);
+		let conduit = match method {
+			Some(b"hvc\0",) => Conduit::Hvc,
+			Some(b"smc\0",) => Conduit::Smc,
+			_ => Conduit::None,
+		};
+		CONDUIT.store(conduit as u8, Ordering::Release,);
+	}
+	#[cfg(not(target_arch = "aarch64"))]
+	let _ = device_tree;
+}
+
+/// PSCI conduit found by [`init`]
+pub fn conduit() -> Conduit {
+	match CONDUIT.load(Ordering::Acquire,) {
+		1 => Conduit::Hvc,
+		2 => Conduit::Smc,
+		_ => Conduit::None,
+	}
+}
+
+/// Reboots the system
+#[cfg(not(feature = "hosted"))]
+pub fn reboot() -> ! {
+	#[cfg(target_arch = "aarch64")]
+	psci(PSCI_SYSTEM_RESET,);
+	#[cfg(target_arch = "x86_64")]
+	unsafe {
+		// full reset after asserting the reset signal
+		core::arch::asm!("out dx, al", in("dx") 0xcf9_u16, in("al") 0x06_u8);
+	}
+	let _ = efi::reset(ResetKind::Cold,);
+	oso_no_std_shared::wfi()
+}
+
+/// Powers the system off
+#[cfg(not(feature = "hosted"))]
+pub fn power_off() -> ! {
+	#[cfg(target_arch = "aarch64")]
+	psci(PSCI_SYSTEM_OFF,);
+	let _ = efi::reset(ResetKind::Shutdown,);
+	oso_no_std_shared::wfi()
+}
+
+#[cfg(feature = "hosted")]
+pub use super::hosted::power_off;
+#[cfg(feature = "hosted")]
+pub use super::hosted::reboot;
+
+/// Calls the PSCI function `function`, which returns only on failure
+#[cfg(all(target_arch = "aarch64", not(feature = "hosted")))]
+fn psci(function: u64,) {
+	use core::arch::asm;
+
+	unsafe {
+		match conduit() {
+			Conduit::Hvc => asm!("hvc #0", inout("x0") function => _),
+			Conduit::Smc => asm!("smc #0", inout("x0") function => _),
+			Conduit::None => {},
+		}
+	}
+}
@@ -0,0 +1,881 @@
+//! # Scheduler
+//!
+//! Preemptive round-robin scheduling of kernel tasks with static priorities,
+//! so long parsing or rendering work can not keep the shell from running.
+//!
+//! ## Priorities
+//!
+//! There are [`PRIORITIES`] levels, `0` being the highest. The ready task of
+//! the highest level runs, and tasks of the same level take turns. A ready
+//! task gains a level for every [`AGING_TICKS`] ticks it waits, so low
+//! priority tasks are not starved. The gain is dropped once it runs.
+//!
+//! ## Preemption
+//!
+//! [`tick`] charges the running task and asks for a switch when its slice of
+//! [`SLICE_TICKS`] ticks is used up or a task of a higher level became
+//! ready. [`preempt`] switches at the end of the interrupt. Tasks may also
+//! give up the processor with [`yield_now`].
+//!
+//! A switch saves the callee-saved registers on the stack of the task and
+//! loads the stack of the next one. Caller-saved registers are saved by the
+//! interrupt vector or the call to the scheduler.
+//!
+//! ## Panics
+//!
+//! Each task runs under [`supervisor::supervise`] with the [`Policy`] it was
+//! spawned with. A task which panicked and is not restarted exits, so its
+//! slot is free again and its stack is no longer used.
+//!
+//! ## Accounting
+//!
+//! Each task accumulates the ticks it was charged and the [`cycles`] it ran,
+//! shown by the `tasks` shell command which [`run_command`] implements.
+//!
+//! ## Current Status
+//!
+//! [`timer::interrupt`] calls [`tick`] and [`preempt`] on every tick, but the
+//! kernel has no timer driver yet, so only the simulator ticks. On hardware,
+//! tasks switch when they yield or exit. There is no allocator, so callers
+//! provide the stack of a task. The scheduler assumes a single core.
+//!
+//! ```rust,ignore
+//! static mut STACK: [u8; 16 * 1024] = [0; 16 * 1024];
+//!
+//! let stack = unsafe { &mut *&raw mut STACK };
+//! sched::spawn("render", 6, stack, render, Policy::Restart { limit: 3, },)?;
+//! sched::run();
+//! ```
+//!
+//! [`timer::interrupt`]: super::timer::interrupt
+
+use super::perf::cycles;
+use super::perf::idle;
+use super::perf::trace;
+use super::supervisor;
+use super::supervisor::Policy;
+use core::cell::UnsafeCell;
+use core::fmt;
+use oso_error::Rslt;
+use oso_error::kernel::SchedError;
+use oso_error::oso_err;
+
+/// Name of the shell command handled by [`run_command`]
+pub const COMMAND: &str = "tasks";
+/// Tasks the scheduler has room for
+pub const MAX_TASKS: usize = 32;
+/// Priority levels. `0` is the highest
+pub const PRIORITIES: u8 = 8;
+/// Level of tasks which do not need another
+pub const DEFAULT_PRIORITY: u8 = 4;
+/// Ticks a task runs before others of its level get a turn
+pub const SLICE_TICKS: u32 = 4;
+/// Ticks a ready task waits to gain a level
+pub const AGING_TICKS: u32 = 8;
+/// Smallest stack [`spawn`] accepts
+pub const MIN_STACK: usize = 4096;
+
+static SCHED: Sched = Sched(UnsafeCell::new(Scheduler {
+	tasks:        [const { None }; MAX_TASKS],
+	current:      None,
+	idle_sp:      0,
+	idle_catch:   0,
+	need_resched: false,
+	switched_at:  0,
+	idle_cycles:  0,
+},),);
+/// interrupt mask new tasks start with, the one `run` was called with
+static START_FLAGS: Sched<usize,> = Sched(UnsafeCell::new(0,),);
+
+/// only accessed with interrupts disabled on a single core
+struct Sched<T = Scheduler,>(UnsafeCell<T,>,);
+
+unsafe impl<T,> Sync for Sched<T,> {}
+
+/// ID of a task, valid until the task exits
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct TaskId(usize,);
+
+impl TaskId {
+	pub const fn index(&self,) -> usize {
+		self.0
+	}
+}
+
+impl fmt::Display for TaskId {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		self.0.fmt(f,)
+	}
+}
+
+/// State of a task
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum State {
+	Ready,
+	Running,
+}
+
+impl State {
+	pub const fn name(&self,) -> &'static str {
+		match self {
+			Self::Ready => "ready",
+			Self::Running => "running",
+		}
+	}
+}
+
+/// Snapshot of a task
+///
+/// # Fields
+///
+/// * `ticks` - Ticks charged to the task
+/// * `cycles` - Cycles the task ran, up to its last switch
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct TaskInfo {
+	pub id:       TaskId,
+	pub name:     &'static str,
+	pub priority: u8,
+	pub state:    State,
+	pub ticks:    u64,
+	pub cycles:   u64,
+}
+
+impl fmt::Display for TaskInfo {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		let Self { id, name, priority, state, ticks, cycles, } = self;
+		write!(
+			f,
+			"{id:>3} {name:<16} {priority:>4} {:<8} {ticks:>10} {cycles:>16}",
+			state.name()
+		)
+	}
+}
+
+struct Task {
+	name:     &'static str,
+	priority: u8,
+	state:    State,
+	/// what happens when the task panics
+	policy:   Policy,
+	/// stack pointer while switched out
+	sp:       usize,
+	/// innermost `supervisor::catch` while switched out
+	catch:    usize,
+	/// ticks left of the slice
+	slice:    u32,
+	/// ticks waited while ready
+	waited:   u32,
+	ticks:    u64,
+	cycles:   u64,
+}
+
+impl Task {
+	/// Level the task competes at, raised by waiting
+	fn effective_priority(&self,) -> u8 {
+		let gain = (self.waited / AGING_TICKS).min(PRIORITIES as u32,) as u8;
+		self.priority.saturating_sub(gain,)
+	}
+}
+
+struct Scheduler {
+	tasks:        [Option<Task,>; MAX_TASKS],
+	/// running task, `None` while idle
+	current:      Option<usize,>,
+	/// stack pointer of the idle context while a task runs
+	idle_sp:      usize,
+	idle_catch:   usize,
+	need_resched: bool,
+	/// cycle count when the current context was switched in
+	switched_at:  u64,
+	idle_cycles:  u64,
+}
+
+impl Scheduler {
+	/// Ready task to run next, the current one included
+	///
+	/// Tasks after the current one come first, so tasks of the same level
+	/// take turns.
+	fn pick(&self,) -> Option<usize,> {
+		let start = self.current.map_or(0, |i| i + 1,);
+		(0..MAX_TASKS)
+			.map(|n| (start + n) % MAX_TASKS,)
+			.filter_map(|i| self.tasks[i].as_ref().map(|task| (i, task,),),)
+			.min_by_key(|(_, task,)| task.effective_priority(),)
+			.map(|(i, _,)| i,)
+	}
+}
+
+/// Adds a task running `entry` on `stack` at `priority`
+///
+/// The task exits when `entry` returns, and when it panics unless `policy`
+/// restarts it.
+///
+/// # Errors
+///
+/// - [`SchedError::InvalidPriority`] if `priority` is not below
+///   [`PRIORITIES`]
+/// - [`SchedError::StackTooSmall`] if `stack` is smaller than [`MIN_STACK`]
+/// - [`SchedError::TooManyTasks`] if [`MAX_TASKS`] tasks exist
+pub fn spawn(
+	name: &'static str,
+	priority: u8,
+	stack: &'static mut [u8],
+	entry: fn(),
+	policy: Policy,
+) -> Rslt<TaskId, SchedError,> {
+	if priority >= PRIORITIES {
+		return Err(oso_err!(SchedError::InvalidPriority { priority }),);
+	}
+	if stack.len() < MIN_STACK {
+		return Err(oso_err!(SchedError::StackTooSmall { min: MIN_STACK }),);
+	}
+	critical(|sched| {
+		let Some(index,) = sched.tasks.iter().position(Option::is_none,) else {
+			return Err(oso_err!(SchedError::TooManyTasks {
+				capacity: MAX_TASKS,
+			}),);
+		};
+		let sp = unsafe { initial_context(stack, entry,) };
+		sched.tasks[index] = Some(Task {
+			name,
+			priority,
+			state: State::Ready,
+			policy,
+			sp,
+			catch: 0,
+			slice: SLICE_TICKS,
+			waited: 0,
+			ticks: 0,
+			cycles: 0,
+		},);
+		Ok(TaskId(index,),)
+	},)
+}
+
+/// Changes the level of task `id`. Takes effect at the next switch
+///
+/// # Errors
+///
+/// - [`SchedError::InvalidPriority`] if `priority` is not below
+///   [`PRIORITIES`]
+/// - [`SchedError::NotFound`] if there is no task `id`
+pub fn set_priority(id: TaskId, priority: u8,) -> Rslt<(), SchedError,> {
+	if priority >= PRIORITIES {
+		return Err(oso_err!(SchedError::InvalidPriority { priority }),);
+	}
+	critical(|sched| {
+		let task = sched.tasks.get_mut(id.0,).and_then(Option::as_mut,);
+		let task = task.ok_or(oso_err!(SchedError::NotFound),)?;
+		task.priority = priority;
+		sched.need_resched = true;
+		Ok((),)
+	},)
+}
+
+/// Running task, `None` outside of tasks
+pub fn current() -> Option<TaskId,> {
+	critical(|sched| sched.current.map(TaskId,),)
+}
+
+/// Snapshots of every task
+pub fn tasks() -> impl Iterator<Item = TaskInfo,> {
+	(0..MAX_TASKS).filter_map(|i| {
+		critical(|sched| {
+			let task = sched.tasks[i].as_ref()?;
+			Some(TaskInfo {
+				id:       TaskId(i,),
+				name:     task.name,
+				priority: task.priority,
+				state:    task.state,
+				ticks:    task.ticks,
+				cycles:   task.cycles,
+			},)
+		},)
+	},)
+}
+
+/// Cycles spent idle, up to the last switch
+pub fn idle_cycles() -> u64 {
+	critical(|sched| sched.idle_cycles,)
+}
+
+/// Lets other ready tasks of the same or a higher level run
+pub fn yield_now() {
+	schedule();
+}
+
+/// Ends the running task
+///
+/// # Panics
+///
+/// Panics outside of tasks
+pub fn exit() -> ! {
+	critical(|sched| {
+		let current = sched.current.expect("exit outside of a task",);
+		sched.tasks[current] = None;
+	},);
+	schedule();
+	unreachable!("exited task was resumed")
+}
+
+/// Charges the running task a tick and ages the waiting ones. Called from
+/// the timer interrupt, before [`preempt`]
+pub fn tick() {
+	critical(|sched| {
+		let mut best_waiting = PRIORITIES;
+		for task in sched.tasks.iter_mut().flatten() {
+			if task.state == State::Ready {
+				task.waited = task.waited.saturating_add(1,);
+				best_waiting = best_waiting.min(task.effective_priority(),);
+			}
+		}
+		let current = sched.current.and_then(|i| sched.tasks[i].as_mut(),);
+		let Some(current,) = current else {
+			sched.need_resched |= best_waiting < PRIORITIES;
+			return;
+		};
+		current.ticks += 1;
+		current.slice = current.slice.saturating_sub(1,);
+		sched.need_resched |=
+			current.slice == 0 || best_waiting < current.priority;
+	},)
+}
+
+/// Switches tasks if [`tick`] or [`set_priority`] asked for it. Called at
+/// the end of an interrupt, after the interrupt was completed
+pub fn preempt() {
+	if critical(|sched| sched.need_resched,) {
+		schedule();
+	}
+}
+
+/// Runs tasks until none is left, waiting for interrupts in between. The
+/// calling context becomes the idle context
+///
+/// Tasks start with the interrupt mask of the caller.
+pub fn run() {
+	let flags = disable_interrupts();
+	unsafe { *START_FLAGS.0.get() = flags };
+	restore_interrupts(flags,);
+	loop {
+		schedule();
+		let has_tasks = || {
+			critical(|sched| sched.tasks.iter().any(Option::is_some,),)
+		};
+		if !has_tasks() {
+			return;
+		}
+		idle::wait(has_tasks,);
+	}
+}
+
+/// Runs `args` of the `tasks` shell command
+pub fn run_command(
+	args: &[&str],
+	out: &mut impl fmt::Write,
+) -> Rslt<(), SchedError,> {
+	if !args.is_empty() {
+		let _ = writeln!(out, "usage: {COMMAND}");
+		return Err(oso_err!(SchedError::Usage),);
+	}
+	let _ = writeln!(
+		out,
+		"{:>3} {:<16} {:>4} {:<8} {:>10} {:>16}",
+		"id", "name", "prio", "state", "ticks", "cycles"
+	);
+	for task in tasks() {
+		let _ = writeln!(out, "{task}");
+	}
+	let _ = writeln!(out, "idle {} cycles", idle_cycles());
+	Ok((),)
+}
+
+/// Switches to the task [`Scheduler::pick`] chooses, or to the idle context
+/// once no task is left
+fn schedule() {
+	let flags = disable_interrupts();
+	let sched = unsafe { &mut *SCHED.0.get() };
+	sched.need_resched = false;
+	let prev = sched.current;
+	let next = sched.pick();
+	if next == prev {
+		restore_interrupts(flags,);
+		return;
+	}
+
+	let now = cycles();
+	let ran = now.wrapping_sub(sched.switched_at,);
+	sched.switched_at = now;
+	let (to, catch,) = match next.and_then(|i| sched.tasks[i].as_mut(),) {
+		Some(task,) => {
+			task.state = State::Running;
+			task.slice = SLICE_TICKS;
+			task.waited = 0;
+			(task.sp, task.catch,)
+		},
+		None => (sched.idle_sp, sched.idle_catch,),
+	};
+	let catch = supervisor::swap_catch(catch,);
+	// an exited task has no slot, so its context is dropped
+	let mut exited_sp = 0;
+	let from = match prev.map(|i| sched.tasks[i].as_mut(),) {
+		Some(Some(task,),) => {
+			task.cycles += ran;
+			task.state = State::Ready;
+			task.catch = catch;
+			&raw mut task.sp
+		},
+		Some(None,) => &raw mut exited_sp,
+		None => {
+			sched.idle_cycles += ran;
+			sched.idle_catch = catch;
+			&raw mut sched.idle_sp
+		},
+	};
+	sched.current = next;
+
+	trace::event!("sched", "switch", next.map_or(u64::MAX, |i| i as u64));
+	unsafe { switch(from, to,) };
+	restore_interrupts(flags,);
+}
+
+/// Runs `f` with interrupts disabled
+fn critical<R,>(f: impl FnOnce(&mut Scheduler,) -> R,) -> R {
+	let flags = disable_interrupts();
+	let r = f(unsafe { &mut *SCHED.0.get() },);
+	restore_interrupts(flags,);
+	r
+}
+
+/// First code of a task, entered from [`switch`] with `entry` in a
+/// callee-saved register
+extern "C" fn task_start(entry: usize,) -> ! {
+	restore_interrupts(unsafe { *START_FLAGS.0.get() },);
+	let entry: fn() = unsafe { core::mem::transmute(entry,) };
+	let (name, policy,) = critical(|sched| {
+		let task = sched.current.and_then(|i| sched.tasks[i].as_ref(),);
+		let task = task.expect("task started outside of the scheduler",);
+		(task.name, task.policy,)
+	},);
+	// a failed task has been reported, and exits like a finished one
+	supervisor::supervise(&mut Entry { name, entry, }, policy,);
+	exit()
+}
+
+/// Entry of a task, run by [`task_start`] under its policy
+struct Entry {
+	name:  &'static str,
+	entry: fn(),
+}
+
+impl supervisor::Task for Entry {
+	fn name(&self,) -> &'static str {
+		self.name
+	}
+
+	fn run(&mut self,) {
+		(self.entry)()
+	}
+}
+
+/// Lays out a context on `stack` which [`switch`] resumes into
+/// [`task_start`] with `entry`, and returns its stack pointer
+///
+/// # Safety
+///
+/// `stack` must be at least [`MIN_STACK`] bytes
+#[cfg(target_arch = "aarch64")]
+unsafe fn initial_context(stack: &'static mut [u8], entry: fn(),) -> usize {
+	let top = (stack.as_mut_ptr() as usize + stack.len()) & !0xf;
+	let sp = top - SWITCH_FRAME;
+	let frame = sp as *mut usize;
+	unsafe {
+		frame.write_bytes(0, SWITCH_FRAME / 8,);
+		// x19 and x30
+		frame.write(entry as usize,);
+		frame.add(11,).write(task_trampoline as *const () as usize,);
+	}
+	sp
+}
+
+/// Lays out a context on `stack` which [`switch`] resumes into
+/// [`task_start`] with `entry`, and returns its stack pointer
+///
+/// # Safety
+///
+/// `stack` must be at least [`MIN_STACK`] bytes
+#[cfg(target_arch = "x86_64")]
+unsafe fn initial_context(stack: &'static mut [u8], entry: fn(),) -> usize {
+	// aligned as after a call once the frame is popped
+	let top = ((stack.as_mut_ptr() as usize + stack.len()) & !0xf) - 16;
+	let sp = top - SWITCH_FRAME;
+	let frame = sp as *mut usize;
+	unsafe {
+		frame.write_bytes(0, SWITCH_FRAME / 8,);
+		// rbx and the return address
+		frame.add(4,).write(entry as usize,);
+		frame.add(6,).write(task_trampoline as *const () as usize,);
+	}
+	sp
+}
+
+/// x19-x30 and d8-d15
+#[cfg(target_arch = "aarch64")]
+const SWITCH_FRAME: usize = 160;
+/// r15, r14, r13, r12, rbx, rbp and the return address
+#[cfg(target_arch = "x86_64")]
+const SWITCH_FRAME: usize = 56;
+
+/// Saves the callee-saved registers on the stack, stores the stack pointer
+/// to `from` and resumes the context saved at `to`
+#[cfg(target_arch = "aarch64")]
+#[unsafe(naked)]
+unsafe extern "C" fn switch(from: *mut usize, to: usize,) {
+	core::arch::naked_asm!(
+		"sub sp, sp, #160",
+		"stp x19, x20, [sp, #0]",
+		"stp x21, x22, [sp, #16]",
+		"stp x23, x24, [sp, #32]",
+		"stp x25, x26, [sp, #48]",
+		"stp x27, x28, [sp, #64]",
+		"stp x29, x30, [sp, #80]",
+		"stp d8, d9, [sp, #96]",
+		"stp d10, d11, [sp, #112]",
+		"stp d12, d13, [sp, #128]",
+		"stp d14, d15, [sp, #144]",
+		"mov x9, sp",
+		"str x9, [x0]",
+		"mov sp, x1",
+		"ldp x19, x20, [sp, #0]",
+		"ldp x21, x22, [sp, #16]",
+		"ldp x23, x24, [sp, #32]",
+		"ldp x25, x26, [sp, #48]",
+		"ldp x27, x28, [sp, #64]",
+		"ldp x29, x30, [sp, #80]",
+		"ldp d8, d9, [sp, #96]",
+		"ldp d10, d11, [sp, #112]",
+		"ldp d12, d13, [sp, #128]",
+		"ldp d14, d15, [sp, #144]",
+		"add sp, sp, #160",
+		"ret",
+	)
+}
+
+/// Calls [`task_start`] with the entry left in x19
+#[cfg(target_arch = "aarch64")]
+#[unsafe(naked)]
+unsafe extern "C" fn task_trampoline() -> ! {
+	core::arch::naked_asm!(
+		"mov x0, x19",
+		"bl {start}",
+		start = sym task_start,
+	)
+}
+
+/// Saves the callee-saved registers on the stack, stores the stack pointer
+/// to `from` and resumes the context saved at `to`
+#[cfg(target_arch = "x86_64")]
+#[unsafe(naked)]
+unsafe extern "C" fn switch(from: *mut usize, to: usize,) {
+	core::arch::naked_asm!(
+		"push rbp",
+		"push rbx",
+		"push r12",
+		"push r13",
+		"push r14",
+		"push r15",
+		"mov [rdi], rsp",
+		"mov rsp, rsi",
+		"pop r15",
+		"pop r14",
+		"pop r13",
+		"pop r12",
+		"pop rbx",
+		"pop rbp",
+		"ret",
+	)
+}
+
+/// Calls [`task_start`] with the entry left in rbx
+#[cfg(target_arch = "x86_64")]
+#[unsafe(naked)]
+unsafe extern "C" fn task_trampoline() -> ! {
+	core::arch::naked_asm!(
+		"mov rdi, rbx",
+		"call {start}",
+		start = sym task_start,
+	)
+}
+
+/// Masks interrupts and returns the previous mask
+#[cfg(not(feature = "hosted"))]
+pub(crate) fn disable_interrupts() -> usize {
+	let flags;
+	unsafe {
+		#[cfg(target_arch = "aarch64")]
+		core::arch::asm!("mrs {}, daif", "msr daifset, #2", out(reg) flags);
+		#[cfg(target_arch = "x86_64")]
+		core::arch::asm!("pushfq", "pop {}", "cli", out(reg) flags);
+	}
+	flags
+}
+
+/// Restores the mask [`disable_interrupts`] returned
+#[cfg(not(feature = "hosted"))]
+pub(crate) fn restore_interrupts(flags: usize,) {
+	unsafe {
+		#[cfg(target_arch = "aarch64")]
+		core::arch::asm!("msr daif, {}", in(reg) flags);
+		/// interrupt enable flag of RFLAGS
+		#[cfg(target_arch = "x86_64")]
+		const IF: usize = 1 << 9;
+		#[cfg(target_arch = "x86_64")]
+		if flags & IF != 0 {
+			core::arch::asm!("sti");
+		}
+	}
+}
+
+#[cfg(feature = "hosted")]
+pub(crate) use super::hosted::disable_interrupts;
+#[cfg(feature = "hosted")]
+pub(crate) use super::hosted::restore_interrupts;
+
+#[cfg(test)]
+mod tests {
+	extern crate std;
+
+	use super::*;
+	use core::sync::atomic::AtomicU32;
+	use core::sync::atomic::Ordering;
+	use std::string::String;
+	use std::sync::Mutex;
+	use std::sync::MutexGuard;
+	use std::vec;
+	use std::vec::Vec;
+
+	/// what the tasks of [`test_run_switches_tasks`] did, in order
+	static LOG: Mutex<Vec<&str,>,> = Mutex::new(Vec::new(),);
+
+	/// Takes the scheduler and removes every task. Tasks run under
+	/// `supervisor::catch`, so this takes its lock too
+	fn lock() -> MutexGuard<'static, (),> {
+		let guard = supervisor::tests::lock();
+		critical(|sched| {
+			sched.tasks = [const { None }; MAX_TASKS];
+			sched.current = None;
+			sched.need_resched = false;
+		},);
+		guard
+	}
+
+	fn stack() -> &'static mut [u8] {
+		vec![0; MIN_STACK * 4].leak()
+	}
+
+	fn spawn_at(name: &'static str, priority: u8,) -> TaskId {
+		spawn(name, priority, stack(), || {}, Policy::Ignore,).unwrap()
+	}
+
+	/// Pretends task `id` was switched in
+	fn run_as(id: TaskId,) {
+		critical(|sched| {
+			sched.current = Some(id.0,);
+			let task = sched.tasks[id.0].as_mut().unwrap();
+			task.state = State::Running;
+			task.slice = SLICE_TICKS;
+			task.waited = 0;
+		},);
+	}
+
+	fn need_resched() -> bool {
+		critical(|sched| core::mem::take(&mut sched.need_resched,),)
+	}
+
+	fn pick() -> Option<TaskId,> {
+		critical(|sched| sched.pick(),).map(TaskId,)
+	}
+
+	fn log(event: &'static str,) {
+		LOG.lock().unwrap().push(event,);
+	}
+
+	#[test]
+	fn test_spawn_errors() {
+		let _lock = lock();
+		let ignore = Policy::Ignore;
+		let priority = spawn("bad", PRIORITIES, stack(), || {}, ignore,);
+		let invalid = SchedError::InvalidPriority { priority: PRIORITIES, };
+		assert_eq!(priority.unwrap_err().desc, Some(invalid));
+		let small = vec![0; MIN_STACK - 1].leak();
+		let small = spawn("bad", 0, small, || {}, ignore,);
+		let min = SchedError::StackTooSmall { min: MIN_STACK, };
+		assert_eq!(small.unwrap_err().desc, Some(min));
+
+		let ids: Vec<_,> = (0..MAX_TASKS).map(|_| spawn_at("t", 0,),).collect();
+		let full = spawn("full", 0, stack(), || {}, ignore,);
+		let capacity = SchedError::TooManyTasks { capacity: MAX_TASKS, };
+		assert_eq!(full.unwrap_err().desc, Some(capacity));
+		assert_eq!(tasks().count(), MAX_TASKS);
+		assert_eq!(ids[MAX_TASKS - 1].index(), MAX_TASKS - 1);
+
+		let priority = set_priority(ids[0], PRIORITIES,);
+		assert_eq!(priority.unwrap_err().desc, Some(invalid));
+		critical(|sched| sched.tasks[3] = None,);
+		let gone = set_priority(ids[3], 1,);
+		assert_eq!(gone.unwrap_err().desc, Some(SchedError::NotFound));
+		assert!(set_priority(ids[4], 1).is_ok());
+		assert!(need_resched());
+	}
+
+	#[test]
+	fn test_tasks_of_a_level_take_turns() {
+		let _lock = lock();
+		let a = spawn_at("a", DEFAULT_PRIORITY,);
+		let b = spawn_at("b", DEFAULT_PRIORITY,);
+		let low = spawn_at("low", DEFAULT_PRIORITY + 2,);
+		assert_eq!(pick(), Some(a));
+		run_as(a,);
+		assert_eq!(pick(), Some(b));
+		run_as(b,);
+		assert_eq!(pick(), Some(a));
+
+		set_priority(low, 0,).unwrap();
+		assert_eq!(pick(), Some(low));
+		let info = tasks().find(|task| task.id == low,).unwrap();
+		let info = (info.name, info.priority, info.state,);
+		assert_eq!(info, ("low", 0, State::Ready));
+	}
+
+	#[test]
+	fn test_slices() {
+		let _lock = lock();
+		let a = spawn_at("a", DEFAULT_PRIORITY,);
+		spawn_at("b", DEFAULT_PRIORITY,);
+		run_as(a,);
+		for _ in 1..SLICE_TICKS {
+			tick();
+			assert!(!need_resched());
+		}
+		tick();
+		assert!(need_resched());
+		let info = tasks().find(|task| task.id == a,).unwrap();
+		assert_eq!(info.ticks, SLICE_TICKS as u64);
+		assert_eq!(info.state, State::Running);
+
+		// a task of a higher level preempts at the next tick
+		run_as(a,);
+		spawn_at("urgent", DEFAULT_PRIORITY - 1,);
+		tick();
+		assert!(need_resched());
+	}
+
+	#[test]
+	fn test_waiting_tasks_gain_levels() {
+		let _lock = lock();
+		let a = spawn_at("a", DEFAULT_PRIORITY,);
+		let low = spawn_at("low", DEFAULT_PRIORITY + 2,);
+		run_as(a,);
+		// reaching the level of `a` takes turns with it, but does not preempt
+		for _ in 0..2 * AGING_TICKS {
+			tick();
+			critical(|sched| sched.tasks[a.0].as_mut().unwrap().slice = 2,);
+			assert!(!need_resched());
+		}
+		assert_eq!(pick(), Some(low));
+		for _ in 0..AGING_TICKS {
+			tick();
+			critical(|sched| sched.tasks[a.0].as_mut().unwrap().slice = 2,);
+		}
+		assert!(need_resched());
+		assert_eq!(pick(), Some(low));
+
+		// the gain is dropped once the task runs
+		run_as(low,);
+		run_as(a,);
+		assert_eq!(pick(), Some(a));
+	}
+
+	#[test]
+	fn test_run_switches_tasks() {
+		fn first() {
+			log("first 1",);
+			yield_now();
+			log("first 2",);
+		}
+		fn second() {
+			log("second 1",);
+			yield_now();
+			log("second 2",);
+		}
+		fn last() {
+			log("last",);
+			assert_eq!(current(), Some(TaskId(0)));
+		}
+
+		let _lock = lock();
+		LOG.lock().unwrap().clear();
+		let ignore = Policy::Ignore;
+		spawn("last", DEFAULT_PRIORITY + 1, stack(), last, ignore,).unwrap();
+		spawn("first", DEFAULT_PRIORITY, stack(), first, ignore,).unwrap();
+		spawn("second", DEFAULT_PRIORITY, stack(), second, ignore,).unwrap();
+		run();
+		let log = LOG.lock().unwrap().clone();
+		let order = ["first 1", "second 1", "first 2", "second 2", "last",];
+		assert_eq!(log, order);
+		assert_eq!(current(), None);
+		assert_eq!(tasks().count(), 0);
+	}
+
+	#[test]
+	fn test_panicking_tasks_exit() {
+		static RUNS: AtomicU32 = AtomicU32::new(0,);
+		fn flaky() {
+			let run = RUNS.fetch_add(1, Ordering::Relaxed,) + 1;
+			log("flaky",);
+			if run < 3 {
+				supervisor::raise(format_args!("run {run}"), None,);
+			}
+		}
+		fn broken() {
+			log("broken",);
+			supervisor::raise(format_args!("broken"), None,);
+			log("unreachable",);
+		}
+		fn after() {
+			log("after",);
+		}
+
+		let _lock = lock();
+		LOG.lock().unwrap().clear();
+		RUNS.store(0, Ordering::Relaxed,);
+		let restart = Policy::Restart { limit: 2, };
+		spawn("broken", DEFAULT_PRIORITY, stack(), broken, Policy::Ignore,)
+			.unwrap();
+		spawn("flaky", DEFAULT_PRIORITY, stack(), flaky, restart,).unwrap();
+		spawn("after", DEFAULT_PRIORITY + 1, stack(), after, restart,).unwrap();
+		run();
+		let log = LOG.lock().unwrap().clone();
+		assert_eq!(log, ["broken", "flaky", "flaky", "flaky", "after"]);
+		assert_eq!(RUNS.load(Ordering::Relaxed), 3);
+		assert_eq!(tasks().count(), 0);
+	}
+
+	#[test]
+	fn test_run_command() {
+		let _lock = lock();
+		spawn_at("render", 6,);
+		let mut out = String::new();
+		assert!(run_command(&[], &mut out).is_ok());
+		let lines: Vec<_,> = out.lines().collect();
+		assert_eq!(lines.len(), 3);
+		assert!(lines[0].starts_with(" id name"));
+		assert!(lines[1].starts_with("  0 render              6 ready"));
+		assert!(lines[2].starts_with("idle "));
+
+		let mut out = String::new();
+		let usage = run_command(&["all"], &mut out,);
+		assert_eq!(usage.unwrap_err().desc, Some(SchedError::Usage));
+		assert_eq!(out, "usage: tasks\n");
+	}
+}
@@ -0,0 +1,482 @@
+//! # Persistent Settings
+//!
+//! Small configuration values kept across boots in non-volatile UEFI
+//! variables, written by the kernel through the runtime services of
+//! [`efi`](super::efi).
+//!
+//! ## Settings
+//!
+//! - `log_level`: One of `error`, `warn`, `info`, `debug` and `trace`
+//! - `default_app`: Name of the application started after boot
+//! - `last_good_slot`: Boot slot which last booted successfully, `0`-`255`
+//!
+//! Each setting is the variable `oso.<name>` of [`VENDOR`]. Its data is the
+//! UTF-8 value followed by the CRC-32 of the value, little endian, so a
+//! value torn by a power loss reads as [`SettingsError::Corrupt`] instead of
+//! a wrong value.
+//!
+//! ## Writes
+//!
+//! Variable storage is flash which endures a limited number of erases, so
+//! a value equal to the stored one is not written again and at most
+//! [`MAX_WRITES`] writes are made per boot. The kernel has no clock yet, so
+//! the limit is per boot rather than per time.
+//!
+//! ## Shell
+//!
+//! [`run_command`] implements the `get` and `set` shell commands:
+//!
+//! - `get`: Prints every stored setting
+//! - `get <name>`: Prints the setting `name`
+//! - `set <name> <value>`: Stores `value` as the setting `name`
+//!
+//! ## Current Status
+//!
+//! No interactive shell reads the console yet, so `get` and `set` run from
+//! the boot script of [`shell`](crate::app::shell). The loader has no boot
+//! slots, so `last_good_slot` is stored but not read.
+//!
+//! ```rust,ignore
+//! unsafe { efi::init(boot_info,) };
+//! settings::set(Setting::LogLevel, "debug",)?;
+//! let level = settings::get(Setting::LogLevel,)?;
+//! ```
+
+use super::efi;
+use super::efi::Guid;
+use core::fmt;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+use oso_error::Rslt;
+use oso_error::kernel::EfiError;
+use oso_error::kernel::SettingsError;
+use oso_error::oso_err;
+use oso_no_std_shared::bridge::boot_info::RuntimeCaps;
+use oso_no_std_shared::data::crc32;
+use oso_no_std_shared::text::fixed::FixedString;
+use oso_no_std_shared::text::utf8;
+
+/// Names of the shell commands handled by [`run_command`]
+pub const COMMANDS: [&str; 2] = ["get", "set",];
+/// Longest value a setting keeps, in bytes
+pub const MAX_VALUE: usize = 64;
+/// Writes made per boot
+pub const MAX_WRITES: usize = 16;
+/// Vendor GUID of the variables, `4f534f00-7365-7474-696e-677300000000`
+pub const VENDOR: Guid = Guid {
+	data1: 0x4f53_4f00,
+	data2: 0x7365,
+	data3: 0x7474,
+	data4: [0x69, 0x6e, 0x67, 0x73, 0, 0, 0, 0,],
+};
+
+const ATTRIBUTES: u32 =
+	efi::NON_VOLATILE | efi::BOOTSERVICE_ACCESS | efi::RUNTIME_ACCESS;
+/// longest variable name, `oso.` and the longest setting name with its NUL
+const NAME_LEN: usize = 32;
+
+/// Setting kept across boots
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum Setting {
+	LogLevel,
+	DefaultApp,
+	LastGoodSlot,
+}
+
+impl Setting {
+	pub const ALL: [Self; 3] =
+		[Self::LogLevel, Self::DefaultApp, Self::LastGoodSlot,];
+
+	pub const fn name(&self,) -> &'static str {
+		match self {
+			Self::LogLevel => "log_level",
+			Self::DefaultApp => "default_app",
+			Self::LastGoodSlot => "last_good_slot",
+		}
+	}
+
+	/// Setting named `name`
+	pub fn from_name(name: &str,) -> Option<Self,> {
+		Self::ALL.into_iter().find(|setting| setting.name() == name,)
+	}
+
+	/// Whether the setting accepts `value`
+	pub fn accepts(&self, value: &str,) -> bool {
+		match self {
+			Self::LogLevel => {
+				["error", "warn", "info", "debug", "trace",].contains(&value,)
+			},
+			Self::DefaultApp => {
+				!value.is_empty()
+					&& value.bytes().all(|b| b.is_ascii_graphic() && b != b'/',)
+			},
+			Self::LastGoodSlot => value.parse::<u8>().is_ok(),
+		}
+	}
+
+	/// NUL-terminated UTF-16 name of the variable
+	fn variable_name(&self,) -> [u16; NAME_LEN] {
+		let mut name = [0; NAME_LEN];
+		let chars = "oso.".bytes().chain(self.name().bytes(),);
+		for (dst, c,) in name.iter_mut().zip(chars,) {
+			*dst = c as u16;
+		}
+		name
+	}
+}
+
+impl fmt::Display for Setting {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		f.write_str(self.name(),)
+	}
+}
+
+/// Stored value of `setting`
+///
+/// # Errors
+///
+/// - [`SettingsError::Unavailable`] without runtime services
+/// - [`SettingsError::NotFound`] if the setting was never stored
+/// - [`SettingsError::Corrupt`] if the stored value fails its checksum
+/// - [`SettingsError::Firmware`] if the firmware fails to read it
+pub fn get(
+	setting: Setting,
+) -> Rslt<FixedString<MAX_VALUE,>, SettingsError,> {
+	SETTINGS.get(setting,)
+}
+
+/// Stores `value` as `setting`
+///
+/// # Errors
+///
+/// - [`SettingsError::InvalidValue`] if `setting` does not accept `value`
+/// - [`SettingsError::TooLong`] if `value` is longer than [`MAX_VALUE`]
+/// - [`SettingsError::RateLimited`] after [`MAX_WRITES`] writes this boot
+/// - [`SettingsError::Unavailable`] without runtime services
+/// - [`SettingsError::Firmware`] if the firmware fails to write it
+pub fn set(setting: Setting, value: &str,) -> Rslt<(), SettingsError,> {
+	SETTINGS.set(setting, value,)
+}
+
+/// Writes left this boot
+pub fn writes_left() -> usize {
+	SETTINGS.writes_left()
+}
+
+static SETTINGS: Settings<Firmware,> = Settings::new(Firmware,);
+
+/// Storage of UEFI variables
+trait Variables {
+	/// See [`efi::variable`]
+	fn variable(
+		&self,
+		name: &[u16],
+		vendor: &Guid,
+		buf: &mut [u8],
+	) -> Rslt<(usize, u32,), EfiError,>;
+
+	/// See [`efi::set_variable`]
+	fn set_variable(
+		&self,
+		name: &[u16],
+		vendor: &Guid,
+		attributes: u32,
+		data: &[u8],
+	) -> Rslt<(), EfiError,>;
+
+	/// Whether [`set_variable`](Self::set_variable) can be called
+	fn writable(&self,) -> bool;
+}
+
+/// Variables of the firmware, through the runtime services
+struct Firmware;
+
+impl Variables for Firmware {
+	fn variable(
+		&self,
+		name: &[u16],
+		vendor: &Guid,
+		buf: &mut [u8],
+	) -> Rslt<(usize, u32,), EfiError,> {
+		efi::variable(name, vendor, buf,)
+	}
+
+	fn set_variable(
+		&self,
+		name: &[u16],
+		vendor: &Guid,
+		attributes: u32,
+		data: &[u8],
+	) -> Rslt<(), EfiError,> {
+		efi::set_variable(name, vendor, attributes, data,)
+	}
+
+	fn writable(&self,) -> bool {
+		efi::capabilities().supports(RuntimeCaps::SET_VARIABLE,)
+	}
+}
+
+/// Settings kept in `V`, with the writes made this boot
+struct Settings<V: Variables,> {
+	variables: V,
+	writes:    AtomicUsize,
+}
+
+impl<V: Variables,> Settings<V,> {
+	const fn new(variables: V,) -> Self {
+		Self { variables, writes: AtomicUsize::new(0,), }
+	}
+
+	/// See [`get`]
+	fn get(
+		&self,
+		setting: Setting,
+	) -> Rslt<FixedString<MAX_VALUE,>, SettingsError,> {
+		let name = setting.variable_name();
+		let mut data = [0; MAX_VALUE + 4];
+		let (size, _,) = self.variables.variable(&name, &VENDOR, &mut data,)?;
+
+		let Some((value, crc,),) = data[..size].split_last_chunk::<4>() else {
+			return Err(oso_err!(SettingsError::Corrupt),);
+		};
+		if crc32::checksum(value,) != u32::from_le_bytes(*crc,) {
+			return Err(oso_err!(SettingsError::Corrupt),);
+		}
+		let value = utf8::from_bytes(value,)
+			.map_err(|_| oso_err!(SettingsError::Corrupt),)?;
+		let mut out = FixedString::new();
+		// fits, as the buffer only holds `MAX_VALUE` bytes of value
+		let _ = fmt::Write::write_str(&mut out, value,);
+		Ok(out,)
+	}
+
+	/// See [`set`]
+	fn set(&self, setting: Setting, value: &str,) -> Rslt<(), SettingsError,> {
+		if value.len() > MAX_VALUE {
+			let capacity = MAX_VALUE;
+			return Err(oso_err!(SettingsError::TooLong { capacity }),);
+		}
+		if !setting.accepts(value,) {
+			return Err(oso_err!(SettingsError::InvalidValue),);
+		}
+		if !self.variables.writable() {
+			return Err(oso_err!(SettingsError::Unavailable),);
+		}
+		if self.get(setting,).is_ok_and(|stored| stored.as_str() == value,) {
+			return Ok((),);
+		}
+		let take = |writes| (writes < MAX_WRITES).then_some(writes + 1,);
+		let taken = self
+			.writes
+			.fetch_update(Ordering::AcqRel, Ordering::Acquire, take,);
+		if taken.is_err() {
+			return Err(oso_err!(SettingsError::RateLimited),);
+		}
+
+		let name = setting.variable_name();
+		let mut data = [0; MAX_VALUE + 4];
+		let len = value.len();
+		data[..len].copy_from_slice(value.as_bytes(),);
+		let crc = crc32::checksum(value.as_bytes(),);
+		data[len..len + 4].copy_from_slice(&crc.to_le_bytes(),);
+		let data = &data[..len + 4];
+		self.variables.set_variable(&name, &VENDOR, ATTRIBUTES, data,)?;
+		Ok((),)
+	}
+
+	/// See [`writes_left`]
+	fn writes_left(&self,) -> usize {
+		MAX_WRITES.saturating_sub(self.writes.load(Ordering::Acquire,),)
+	}
+}
+
+/// Runs the `get` or `set` shell command with the arguments after its name
+pub fn run_command(
+	command: &str,
+	args: &[&str],
+	out: &mut impl fmt::Write,
+) -> Rslt<(), SettingsError,> {
+	let setting = |name| {
+		Setting::from_name(name,).ok_or(oso_err!(SettingsError::NotFound),)
+	};
+	match (command, args,) {
+		("get", [],) => {
+			for setting in Setting::ALL {
+				match get(setting,) {
+					Ok(value,) => {
+						let _ = writeln!(out, "{setting}={value}");
+					},
+					Err(e,) if e.desc == Some(SettingsError::NotFound,) => {},
+					Err(e,) => return Err(e,),
+				}
+			}
+		},
+		("get", [name,],) => {
+			let setting = setting(name,)?;
+			let value = get(setting,)?;
+			let _ = writeln!(out, "{setting}={value}");
+		},
+		("set", [name, value,],) => {
+			let setting = setting(name,)?;
+			set(setting, value,)?;
+			let _ = writeln!(out, "{setting}={value}");
+		},
+		_ => {
+			let _ = writeln!(out, "usage: get [name] | set <name> <value>");
+			return Err(oso_err!(SettingsError::Usage),);
+		},
+	}
+	Ok((),)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	extern crate std;
+	use core::cell::Cell;
+	use core::cell::RefCell;
+	use std::collections::HashMap;
+	use std::vec::Vec;
+
+	/// variables in memory, counting the writes which reach them
+	#[derive(Default,)]
+	struct Memory {
+		stored:   RefCell<HashMap<Vec<u16,>, Vec<u8,>,>,>,
+		writes:   Cell<usize,>,
+		readonly: bool,
+	}
+
+	impl Memory {
+		fn stored(&self, setting: Setting,) -> Option<Vec<u8,>,> {
+			let name = setting.variable_name().to_vec();
+			self.stored.borrow().get(&name,).cloned()
+		}
+
+		fn store(&self, setting: Setting, data: &[u8],) {
+			let name = setting.variable_name().to_vec();
+			self.stored.borrow_mut().insert(name, data.to_vec(),);
+		}
+	}
+
+	impl Variables for Memory {
+		fn variable(
+			&self,
+			name: &[u16],
+			vendor: &Guid,
+			buf: &mut [u8],
+		) -> Rslt<(usize, u32,), EfiError,> {
+			assert_eq!(*vendor, VENDOR);
+			let stored = self.stored.borrow();
+			let data = stored.get(name,).ok_or(oso_err!(EfiError::NotFound),)?;
+			buf[..data.len()].copy_from_slice(data,);
+			Ok((data.len(), ATTRIBUTES,),)
+		}
+
+		fn set_variable(
+			&self,
+			name: &[u16],
+			vendor: &Guid,
+			attributes: u32,
+			data: &[u8],
+		) -> Rslt<(), EfiError,> {
+			assert_eq!((*vendor, attributes,), (VENDOR, ATTRIBUTES,));
+			self.stored.borrow_mut().insert(name.to_vec(), data.to_vec(),);
+			self.writes.set(self.writes.get() + 1,);
+			Ok((),)
+		}
+
+		fn writable(&self,) -> bool {
+			!self.readonly
+		}
+	}
+
+	fn error<T,>(result: Rslt<T, SettingsError,>,) -> Option<SettingsError,> {
+		result.err().and_then(|e| e.desc,)
+	}
+
+	#[test]
+	fn test_crc_framing() {
+		let settings = Settings::new(Memory::default(),);
+		settings.set(Setting::LogLevel, "debug",).unwrap();
+		let crc = crc32::checksum(b"debug",).to_le_bytes();
+		let framed = [&b"debug"[..], &crc,].concat();
+		assert_eq!(settings.variables.stored(Setting::LogLevel,), Some(framed));
+		let value = settings.get(Setting::LogLevel,).unwrap();
+		assert_eq!(value.as_str(), "debug");
+
+		let not_found = Some(SettingsError::NotFound,);
+		assert_eq!(error(settings.get(Setting::DefaultApp,)), not_found);
+	}
+
+	#[test]
+	fn test_torn_values() {
+		let settings = Settings::new(Memory::default(),);
+		settings.set(Setting::DefaultApp, "clock",).unwrap();
+		let framed = settings.variables.stored(Setting::DefaultApp,).unwrap();
+		let corrupt = Some(SettingsError::Corrupt,);
+
+		// write cut short by a power loss
+		let torn = &framed[..framed.len() - 1];
+		settings.variables.store(Setting::DefaultApp, torn,);
+		assert_eq!(error(settings.get(Setting::DefaultApp,)), corrupt);
+		settings.variables.store(Setting::DefaultApp, &framed[..3],);
+		assert_eq!(error(settings.get(Setting::DefaultApp,)), corrupt);
+		// value of one write with the checksum of another
+		let mut mixed = framed.clone();
+		mixed[0] = b'b';
+		settings.variables.store(Setting::DefaultApp, &mixed,);
+		assert_eq!(error(settings.get(Setting::DefaultApp,)), corrupt);
+		// checksum matches, but the value is not UTF-8
+		let invalid = [0xc0, 0x80,];
+		let crc = crc32::checksum(&invalid,).to_le_bytes();
+		let framed = [&invalid[..], &crc,].concat();
+		settings.variables.store(Setting::DefaultApp, &framed,);
+		assert_eq!(error(settings.get(Setting::DefaultApp,)), corrupt);
+	}
+
+	#[test]
+	fn test_unchanged_value_not_written() {
+		let settings = Settings::new(Memory::default(),);
+		settings.set(Setting::LastGoodSlot, "3",).unwrap();
+		settings.set(Setting::LastGoodSlot, "3",).unwrap();
+		assert_eq!(settings.variables.writes.get(), 1);
+		assert_eq!(settings.writes_left(), MAX_WRITES - 1);
+		settings.set(Setting::LastGoodSlot, "4",).unwrap();
+		assert_eq!(settings.variables.writes.get(), 2);
+	}
+
+	#[test]
+	fn test_write_limit() {
+		let settings = Settings::new(Memory::default(),);
+		for slot in 0..MAX_WRITES {
+			let slot = std::format!("{slot}");
+			settings.set(Setting::LastGoodSlot, &slot,).unwrap();
+		}
+		assert_eq!(settings.writes_left(), 0);
+		let limited = Some(SettingsError::RateLimited,);
+		assert_eq!(error(settings.set(Setting::LastGoodSlot, "255",)), limited);
+		// an unchanged value needs no write
+		let last = std::format!("{}", MAX_WRITES - 1);
+		settings.set(Setting::LastGoodSlot, &last,).unwrap();
+		assert_eq!(settings.variables.writes.get(), MAX_WRITES);
+	}
+
+	#[test]
+	fn test_rejected_values() {
+		let settings = Settings::new(Memory::default(),);
+		let long = [b'a'; MAX_VALUE + 1];
+		let long = core::str::from_utf8(&long,).unwrap();
+		let too_long = Some(SettingsError::TooLong { capacity: MAX_VALUE, },);
+		assert_eq!(error(settings.set(Setting::DefaultApp, long,)), too_long);
+		let invalid = Some(SettingsError::InvalidValue,);
+		assert_eq!(error(settings.set(Setting::LogLevel, "loud",)), invalid);
+		assert_eq!(error(settings.set(Setting::LastGoodSlot, "256",)), invalid);
+		assert_eq!(settings.writes_left(), MAX_WRITES);
+
+		let readonly = Memory { readonly: true, ..Default::default() };
+		let settings = Settings::new(readonly,);
+		let unavailable = Some(SettingsError::Unavailable,);
+		let result = settings.set(Setting::LogLevel, "info",);
+		assert_eq!(error(result), unavailable);
+	}
+}
@@ -0,0 +1,344 @@
+//! # Spinlocks
+//!
+//! [`SpinLock`] takes its lock through the [`Strategy`] chosen at boot by
+//! [`init`], so the fastest instructions of the processor are used without
+//! building a kernel per processor:
+//!
+//! - [`LL_SC`]: Load-acquire exclusive and store exclusive, on every AArch64
+//!   processor
+//! - [`LSE`]: Compare-and-swap with acquire semantics, on processors with the
+//!   Large System Extensions. One instruction instead of a retried pair,
+//!   which scales better when many cores contend
+//! - [`PORTABLE`]: `compare_exchange` of `core`, on other architectures
+//!
+//! The strategy is called through a function pointer. Waiters spin on plain
+//! loads until the lock looks free, so they do not take the cache line
+//! exclusively while it is held. A lock is released with a store-release on
+//! every strategy.
+//!
+//! ## Shell
+//!
+//! [`run_command`] implements the `spinbench` shell command:
+//!
+//! - `spinbench [rounds]`: Times each strategy available for an uncontended
+//!   lock and unlock, and for a failed attempt on a held lock, which is what
+//!   a waiter repeats under contention. Each is timed through the function
+//!   pointer and called directly, so the cost of the selection is visible
+//!
+//! ## Current Status
+//!
+//! Only the boot core runs yet, so contention is measured as failed
+//! attempts of one core rather than as cores competing for a line.
+//!
+//! ```rust,ignore
+//! use oso_kernel::base::spin::SpinLock;
+//!
+//! static COUNTER: SpinLock<u64,> = SpinLock::new(0,);
+//!
+//! spin::init();
+//! *COUNTER.lock() += 1;
+//! ```
+
+#[cfg(target_arch = "aarch64")]
+use super::cpu;
+use super::perf;
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::ops::Deref;
+use core::ops::DerefMut;
+use core::sync::atomic::AtomicPtr;
+use core::sync::atomic::AtomicU32;
+use core::sync::atomic::Ordering;
+use oso_error::Rslt;
+use oso_error::kernel::SpinError;
+use oso_error::oso_err;
+
+/// Name of the shell command handled by [`run_command`]
+pub const COMMAND: &str = "spinbench";
+/// Rounds timed by `spinbench` without an argument
+pub const DEFAULT_ROUNDS: u32 = 10_000;
+
+/// Load-exclusive and store-exclusive
+#[cfg(target_arch = "aarch64")]
+pub static LL_SC: Strategy =
+	Strategy { name: "ll/sc", try_acquire: ll_sc::try_acquire, };
+/// Compare-and-swap of the Large System Extensions
+#[cfg(target_arch = "aarch64")]
+pub static LSE: Strategy =
+	Strategy { name: "lse", try_acquire: lse::try_acquire, };
+/// `compare_exchange` of `core`
+pub static PORTABLE: Strategy =
+	Strategy { name: "portable", try_acquire: portable_try_acquire, };
+
+/// Strategy every core takes locks with
+#[cfg(target_arch = "aarch64")]
+static STRATEGY: AtomicPtr<Strategy,> =
+	AtomicPtr::new(&raw const LL_SC as *mut Strategy,);
+#[cfg(not(target_arch = "aarch64"))]
+static STRATEGY: AtomicPtr<Strategy,> =
+	AtomicPtr::new(&raw const PORTABLE as *mut Strategy,);
+
+/// Instructions a lock is taken with
+///
+/// # Fields
+///
+/// * `name` - Name printed by `spinbench`
+/// * `try_acquire` - Changes the lock word from `0` to `1` with acquire
+///   semantics. Returns `false` without waiting if it was not `0`
+#[derive(Debug,)]
+pub struct Strategy {
+	pub name:        &'static str,
+	pub try_acquire: fn(&AtomicU32,) -> bool,
+}
+
+impl Strategy {
+	/// Takes `lock`, spinning until it is free
+	pub fn acquire(&self, lock: &AtomicU32,) {
+		while !(self.try_acquire)(lock,) {
+			while lock.load(Ordering::Relaxed,) != 0 {
+				core::hint::spin_loop();
+			}
+		}
+	}
+}
+
+/// Chooses the strategy by the features of the processor
+///
+/// Called on the boot core before other cores start. Locks taken before use
+/// the default strategy, which every processor runs. A lock may be taken
+/// with one strategy and released under another, as each changes the lock
+/// word atomically.
+pub fn init() {
+	#[cfg(target_arch = "aarch64")]
+	if cpu::features().has_lse() {
+		select(&LSE,);
+	}
+}
+
+/// Makes every later lock use `strategy`
+///
+/// # Panics
+///
+/// If the processor can not run `strategy`, see [`available`]
+pub fn select(strategy: &'static Strategy,) {
+	assert!(
+		available().any(|s| core::ptr::eq(s, strategy,),),
+		"{} locks are not supported by the processor",
+		strategy.name
+	);
+	STRATEGY.store(strategy as *const _ as *mut _, Ordering::Release,);
+}
+
+/// Strategy locks are taken with
+pub fn strategy() -> &'static Strategy {
+	// SAFETY: only references to statics are stored
+	unsafe { &*STRATEGY.load(Ordering::Acquire,) }
+}
+
+/// Strategies the processor can run
+pub fn available() -> impl Iterator<Item = &'static Strategy,> {
+	#[cfg(target_arch = "aarch64")]
+	let native = [Some(&LL_SC,), cpu::features().has_lse().then_some(&LSE,),];
+	#[cfg(not(target_arch = "aarch64"))]
+	let native = [None, None,];
+	native.into_iter().flatten().chain([&PORTABLE,],)
+}
+
+/// Lock spun on by waiters, for short critical sections
+pub struct SpinLock<T,> {
+	state: AtomicU32,
+	value: UnsafeCell<T,>,
+}
+
+// SAFETY: the value is only reached through the guard of the held lock
+unsafe impl<T: Send,> Sync for SpinLock<T,> {}
+
+impl<T,> SpinLock<T,> {
+	pub const fn new(value: T,) -> Self {
+		Self { state: AtomicU32::new(0,), value: UnsafeCell::new(value,), }
+	}
+
+	/// Takes the lock, spinning until it is free
+	pub fn lock(&self,) -> SpinGuard<'_, T,> {
+		strategy().acquire(&self.state,);
+		SpinGuard { lock: self, }
+	}
+
+	/// Takes the lock if it is free
+	pub fn try_lock(&self,) -> Option<SpinGuard<'_, T,>,> {
+		let taken = (strategy().try_acquire)(&self.state,);
+		taken.then_some(SpinGuard { lock: self, },)
+	}
+
+	pub fn is_locked(&self,) -> bool {
+		self.state.load(Ordering::Relaxed,) != 0
+	}
+
+	pub fn into_inner(self,) -> T {
+		self.value.into_inner()
+	}
+}
+
+impl<T: fmt::Debug,> fmt::Debug for SpinLock<T,> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		match self.try_lock() {
+			Some(guard,) => f.debug_tuple("SpinLock",).field(&*guard,).finish(),
+			None => f.write_str("SpinLock(<locked>)",),
+		}
+	}
+}
+
+/// Access to the value of a held [`SpinLock`], which is released on drop
+pub struct SpinGuard<'a, T,> {
+	lock: &'a SpinLock<T,>,
+}
+
+impl<T,> Deref for SpinGuard<'_, T,> {
+	type Target = T;
+
+	fn deref(&self,) -> &Self::Target {
+		// SAFETY: the lock is held
+		unsafe { &*self.lock.value.get() }
+	}
+}
+
+impl<T,> DerefMut for SpinGuard<'_, T,> {
+	fn deref_mut(&mut self,) -> &mut Self::Target {
+		// SAFETY: the lock is held
+		unsafe { &mut *self.lock.value.get() }
+	}
+}
+
+impl<T,> Drop for SpinGuard<'_, T,> {
+	fn drop(&mut self,) {
+		self.lock.state.store(0, Ordering::Release,);
+	}
+}
+
+fn portable_try_acquire(lock: &AtomicU32,) -> bool {
+	lock.compare_exchange(0, 1, Ordering::Acquire, Ordering::Relaxed,).is_ok()
+}
+
+#[cfg(target_arch = "aarch64")]
+mod ll_sc {
+	use core::arch::asm;
+	use core::sync::atomic::AtomicU32;
+
+	pub fn try_acquire(lock: &AtomicU32,) -> bool {
+		let old: u32;
+		unsafe {
+			asm!(
+				"2: ldaxr {old:w}, [{lock}]",
+				"cbnz {old:w}, 3f",
+				"stxr {failed:w}, {one:w}, [{lock}]",
+				"cbnz {failed:w}, 2b",
+				"b 4f",
+				// leaves the exclusive monitor of the held lock
+				"3: clrex",
+				"4:",
+				lock = in(reg) lock.as_ptr(),
+				one = in(reg) 1_u32,
+				old = out(reg) old,
+				failed = out(reg) _,
+				options(nostack),
+			);
+		}
+		old == 0
+	}
+}
+
+#[cfg(target_arch = "aarch64")]
+mod lse {
+	use core::arch::asm;
+	use core::sync::atomic::AtomicU32;
+
+	pub fn try_acquire(lock: &AtomicU32,) -> bool {
+		// SAFETY: only selected if the processor has LSE
+		unsafe { cas(lock,) }
+	}
+
+	#[target_feature(enable = "lse")]
+	unsafe fn cas(lock: &AtomicU32,) -> bool {
+		let mut old = 0_u32;
+		unsafe {
+			asm!(
+				"casa {old:w}, {one:w}, [{lock}]",
+				lock = in(reg) lock.as_ptr(),
+				one = in(reg) 1_u32,
+				old = inout(reg) old,
+				options(nostack),
+			);
+		}
+		old == 0
+	}
+}
+
+/// Runs the `spinbench` shell command with the arguments after its name
+pub fn run_command(
+	args: &[&str],
+	out: &mut impl fmt::Write,
+) -> Rslt<(), SpinError,> {
+	let rounds = match args {
+		[] => DEFAULT_ROUNDS,
+		[rounds,] => match rounds.parse::<u32>() {
+			Ok(rounds,) if rounds > 0 => rounds,
+			_ => return usage(out,),
+		},
+		_ => return usage(out,),
+	};
+
+	let selected = strategy().name;
+	let _ = writeln!(out, "{rounds} rounds, selected {selected}");
+	let _ = writeln!(
+		out,
+		"{:<9} {:<7} {:>11} {:>11}",
+		"strategy", "call", "uncontended", "contended"
+	);
+	let mut row = |name: &str, call: &str, (free, held,): (u64, u64,)| {
+		let _ = writeln!(out, "{name:<9} {call:<7} {free:>11} {held:>11}");
+	};
+	for strategy in available() {
+		// opaque, so the call is not turned into a direct one
+		let try_acquire = core::hint::black_box(strategy.try_acquire,);
+		row(strategy.name, "pointer", bench(rounds, try_acquire,),);
+	}
+	#[cfg(target_arch = "aarch64")]
+	{
+		row(LL_SC.name, "direct", bench(rounds, ll_sc::try_acquire,),);
+		if cpu::features().has_lse() {
+			row(LSE.name, "direct", bench(rounds, lse::try_acquire,),);
+		}
+	}
+	row(PORTABLE.name, "direct", bench(rounds, portable_try_acquire,),);
+	Ok((),)
+}
+
+/// cycles per round of a lock and unlock of a free lock, and of a failed
+/// attempt on a held lock
+fn bench(
+	rounds: u32,
+	try_acquire: impl Fn(&AtomicU32,) -> bool,
+) -> (u64, u64,) {
+	let lock = AtomicU32::new(0,);
+	let ((), free,) = perf::measure(|| {
+		for _ in 0..rounds {
+			let taken = try_acquire(core::hint::black_box(&lock,),);
+			debug_assert!(taken);
+			lock.store(0, Ordering::Release,);
+		}
+	},);
+	lock.store(1, Ordering::Relaxed,);
+	let ((), held,) = perf::measure(|| {
+		for _ in 0..rounds {
+			let taken = try_acquire(core::hint::black_box(&lock,),);
+			debug_assert!(!taken);
+		}
+	},);
+	let per_round = |cycles: u64| cycles / rounds as u64;
+	(per_round(free.cycles,), per_round(held.cycles,),)
+}
+
+fn usage(out: &mut impl fmt::Write,) -> Rslt<(), SpinError,> {
+	let _ = writeln!(out, "usage: spinbench [rounds]");
+	Err(oso_err!(SpinError::Usage),)
+}
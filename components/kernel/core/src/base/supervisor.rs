@@ -0,0 +1,427 @@
+//! # Task Supervisor
+//!
+//! Keeps a panicking task from taking the whole kernel down. [`catch`] runs
+//! a closure and returns the panic instead of halting, and [`supervise`]
+//! applies a [`Policy`] to a [`Task`] which panicked.
+//!
+//! ## Catching
+//!
+//! The kernel aborts on panic, so nothing unwinds. Instead [`catch`] saves
+//! the callee-saved registers and the stack pointer before it calls the
+//! closure, like `setjmp`. The panic handler asks [`catch_panic`] first,
+//! which records the panic and restores these registers, so [`catch`]
+//! returns as if the closure had returned.
+//!
+//! Nothing on the abandoned part of the stack is dropped. Locks held by the
+//! task stay locked and what it owned is leaked, unless [`Task::reclaim`]
+//! releases it.
+//!
+//! ## Policies
+//!
+//! - [`Policy::Restart`]: Reclaims and runs the task again, up to a limit,
+//!   then escalates
+//! - [`Policy::Ignore`]: Reclaims and reports the task as failed
+//! - [`Policy::Escalate`]: Panics with the report. Inside another
+//!   supervised task, its supervisor handles the panic, otherwise the
+//!   kernel halts
+//!
+//! ## Current Status
+//!
+//! The scheduler runs every task through [`supervise`] under the policy it
+//! was spawned with, and keeps the innermost [`catch`] of each task across
+//! switches. Other callers may supervise work themselves. Catching assumes
+//! a single core: the innermost [`catch`] is global, not per core.
+//!
+//! Hosted builds panic through std, which unwinds instead of calling
+//! [`catch_panic`], so their tests raise panics with [`raise`].
+//!
+//! ```rust,ignore
+//! let outcome = supervisor::supervise(&mut driver_task, Policy::Restart {
+//! 	limit: 3,
+//! },);
+//! ```
+
+use crate::println;
+use core::fmt;
+use core::fmt::Write;
+use core::panic::Location;
+use core::panic::PanicInfo;
+use core::ptr::null_mut;
+use core::sync::atomic::AtomicPtr;
+use core::sync::atomic::Ordering;
+use oso_no_std_shared::text::fixed::FixedString;
+
+/// Longest report a [`Panic`] keeps, in bytes
+pub const MESSAGE_LEN: usize = 96;
+
+/// innermost active `catch`
+static CURRENT: AtomicPtr<Frame,> = AtomicPtr::new(null_mut(),);
+
+/// Panic returned by [`catch`]
+///
+/// # Fields
+///
+/// * `message` - Panic message followed by `at file:line:column`, truncated
+///   to [`MESSAGE_LEN`] bytes
+#[derive(Clone, Copy, PartialEq, Eq,)]
+pub struct Panic {
+	pub message: FixedString<MESSAGE_LEN,>,
+}
+
+impl fmt::Display for Panic {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		f.write_str(&self.message,)
+	}
+}
+
+impl fmt::Debug for Panic {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		fmt::Display::fmt(self, f,)
+	}
+}
+
+/// What [`supervise`] does when a task panics
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum Policy {
+	/// run the task again up to `limit` times, then escalate
+	Restart {
+		limit: u32,
+	},
+	/// report the task as failed
+	Ignore,
+	/// panic with the report
+	Escalate,
+}
+
+/// Unit of work run under a [`Policy`]
+pub trait Task {
+	/// Name used in reports
+	fn name(&self,) -> &'static str;
+
+	/// Runs the task until it finishes
+	fn run(&mut self,);
+
+	/// Releases what a run left behind when it panicked, before the task
+	/// is restarted or given up
+	fn reclaim(&mut self,) {}
+}
+
+/// Result of [`supervise`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum Outcome {
+	/// the task returned after `restarts` restarts
+	Finished {
+		restarts: u32,
+	},
+	/// the task panicked under [`Policy::Ignore`]
+	Failed(Panic,),
+}
+
+/// Runs `task` and applies `policy` when it panics
+///
+/// Every panic is reported on the console.
+///
+/// # Panics
+///
+/// Panics if `task` panics under [`Policy::Escalate`], or more often than
+/// the limit of [`Policy::Restart`]
+pub fn supervise(task: &mut impl Task, policy: Policy,) -> Outcome {
+	let mut restarts = 0;
+	loop {
+		let Err(panic,) = catch(|| task.run(),) else {
+			return Outcome::Finished { restarts, };
+		};
+		println!("task {} panicked: {}", task.name(), panic);
+		task.reclaim();
+
+		match policy {
+			Policy::Restart { limit, } if restarts < limit => {
+				restarts += 1;
+				let name = task.name();
+				println!("restarting task {name} ({restarts}/{limit})");
+			},
+			Policy::Ignore => return Outcome::Failed(panic,),
+			Policy::Restart { .. } | Policy::Escalate => {
+				panic!("supervised task {} failed: {}", task.name(), panic)
+			},
+		}
+	}
+}
+
+/// Calls `f` and returns its panic instead of halting the kernel
+///
+/// See the [module documentation](self) for what is left behind.
+pub fn catch<F: FnOnce() -> R, R,>(f: F,) -> Result<R, Panic,> {
+	struct Call<F, R,> {
+		f:      Option<F,>,
+		result: Option<R,>,
+	}
+
+	unsafe extern "C" fn trampoline<F: FnOnce() -> R, R,>(data: *mut u8,) {
+		let call = unsafe { &mut *(data as *mut Call<F, R,>) };
+		if let Some(f,) = call.f.take() {
+			call.result = Some(f(),);
+		}
+	}
+
+	let mut call = Call { f: Some(f,), result: None, };
+	let mut frame = Frame {
+		registers: [0; REGISTER_COUNT],
+		panic:     None,
+		outer:     CURRENT.load(Ordering::Acquire,),
+	};
+	CURRENT.store(&mut frame, Ordering::Release,);
+	let data = &mut call as *mut Call<F, R,> as *mut u8;
+	let f = trampoline::<F, R,>;
+	let panicked = unsafe { call_with_frame(&mut frame.registers, f, data,) };
+	CURRENT.store(frame.outer, Ordering::Release,);
+
+	match (panicked, frame.panic.take(), call.result.take(),) {
+		(0, _, Some(result,),) => Ok(result,),
+		(_, Some(panic,), _,) => Err(panic,),
+		_ => unreachable!("catch returned without a result or a panic"),
+	}
+}
+
+/// Hands a panic to the innermost [`catch`]
+///
+/// Called first by the panic handler. Returns only if no [`catch`] is
+/// active.
+pub fn catch_panic(info: &PanicInfo,) {
+	raise(format_args!("{}", info.message()), info.location(),);
+}
+
+/// Returns a panic with `message` at `location` from the innermost
+/// [`catch`]
+///
+/// Returns only if no [`catch`] is active.
+pub(crate) fn raise(
+	message: fmt::Arguments<'_,>,
+	location: Option<&Location<'_,>,>,
+) {
+	let frame = CURRENT.load(Ordering::Acquire,);
+	// SAFETY: frames are removed before their `catch` returns
+	let Some(frame,) = (unsafe { frame.as_mut() }) else {
+		return;
+	};
+	// a panic while catching this one goes to the outer `catch`
+	CURRENT.store(frame.outer, Ordering::Release,);
+
+	let mut panic = Panic { message: FixedString::new(), };
+	// truncation is reported by the string itself
+	let _ = panic.message.write_fmt(message,);
+	if let Some(location,) = location {
+		let _ = write!(panic.message, " at {location}");
+	}
+	frame.panic = Some(panic,);
+	unsafe { resume(&frame.registers,) }
+}
+
+/// Replaces the innermost [`catch`] with `frame`, an address returned
+/// before, and returns the replaced one. Used by the scheduler to keep the
+/// catches of each task apart
+pub(crate) fn swap_catch(frame: usize,) -> usize {
+	CURRENT.swap(frame as *mut Frame, Ordering::AcqRel,) as usize
+}
+
+/// State of an active [`catch`]
+struct Frame {
+	registers: [u64; REGISTER_COUNT],
+	panic:     Option<Panic,>,
+	outer:     *mut Frame,
+}
+
+/// x19-x30, sp and d8-d15
+#[cfg(target_arch = "aarch64")]
+const REGISTER_COUNT: usize = 21;
+/// rbx, rbp, r12-r15, rsp and the return address
+#[cfg(target_arch = "x86_64")]
+const REGISTER_COUNT: usize = 8;
+
+/// Saves the callee-saved registers into `registers` and calls `f(data)`.
+/// Returns `0` when `f` returns and `1` when [`resume`] is called
+#[cfg(target_arch = "aarch64")]
+#[unsafe(naked)]
+unsafe extern "C" fn call_with_frame(
+	registers: &mut [u64; REGISTER_COUNT],
+	f: unsafe extern "C" fn(*mut u8,),
+	data: *mut u8,
+) -> usize {
+	core::arch::naked_asm!(
+		"stp x19, x20, [x0, #0]",
+		"stp x21, x22, [x0, #16]",
+		"stp x23, x24, [x0, #32]",
+		"stp x25, x26, [x0, #48]",
+		"stp x27, x28, [x0, #64]",
+		"stp x29, x30, [x0, #80]",
+		"mov x9, sp",
+		"str x9, [x0, #96]",
+		"stp d8, d9, [x0, #104]",
+		"stp d10, d11, [x0, #120]",
+		"stp d12, d13, [x0, #136]",
+		"stp d14, d15, [x0, #152]",
+		"stp x29, x30, [sp, #-16]!",
+		"mov x29, sp",
+		"mov x0, x2",
+		"blr x1",
+		"ldp x29, x30, [sp], #16",
+		"mov x0, #0",
+		"ret",
+	)
+}
+
+/// Returns `1` from the [`call_with_frame`] which saved `registers`
+#[cfg(target_arch = "aarch64")]
+#[unsafe(naked)]
+unsafe extern "C" fn resume(registers: &[u64; REGISTER_COUNT],) -> ! {
+	core::arch::naked_asm!(
+		"ldp x19, x20, [x0, #0]",
+		"ldp x21, x22, [x0, #16]",
+		"ldp x23, x24, [x0, #32]",
+		"ldp x25, x26, [x0, #48]",
+		"ldp x27, x28, [x0, #64]",
+		"ldp x29, x30, [x0, #80]",
+		"ldr x9, [x0, #96]",
+		"mov sp, x9",
+		"ldp d8, d9, [x0, #104]",
+		"ldp d10, d11, [x0, #120]",
+		"ldp d12, d13, [x0, #136]",
+		"ldp d14, d15, [x0, #152]",
+		"mov x0, #1",
+		"ret",
+	)
+}
+
+/// Saves the callee-saved registers into `registers` and calls `f(data)`.
+/// Returns `0` when `f` returns and `1` when [`resume`] is called
+#[cfg(target_arch = "x86_64")]
+#[unsafe(naked)]
+unsafe extern "C" fn call_with_frame(
+	registers: &mut [u64; REGISTER_COUNT],
+	f: unsafe extern "C" fn(*mut u8,),
+	data: *mut u8,
+) -> usize {
+	core::arch::naked_asm!(
+		"mov [rdi], rbx",
+		"mov [rdi + 8], rbp",
+		"mov [rdi + 16], r12",
+		"mov [rdi + 24], r13",
+		"mov [rdi + 32], r14",
+		"mov [rdi + 40], r15",
+		// stack pointer after returning, and the return address
+		"lea rax, [rsp + 8]",
+		"mov [rdi + 48], rax",
+		"mov rax, [rsp]",
+		"mov [rdi + 56], rax",
+		"sub rsp, 8",
+		"mov rdi, rdx",
+		"call rsi",
+		"add rsp, 8",
+		"xor eax, eax",
+		"ret",
+	)
+}
+
+/// Returns `1` from the [`call_with_frame`] which saved `registers`
+#[cfg(target_arch = "x86_64")]
+#[unsafe(naked)]
+unsafe extern "C" fn resume(registers: &[u64; REGISTER_COUNT],) -> ! {
+	core::arch::naked_asm!(
+		"mov rbx, [rdi]",
+		"mov rbp, [rdi + 8]",
+		"mov r12, [rdi + 16]",
+		"mov r13, [rdi + 24]",
+		"mov r14, [rdi + 32]",
+		"mov r15, [rdi + 40]",
+		"mov rsp, [rdi + 48]",
+		"mov eax, 1",
+		"jmp qword ptr [rdi + 56]",
+	)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	extern crate std;
+
+	use super::*;
+	use core::cell::Cell;
+	use std::sync::Mutex;
+	use std::sync::MutexGuard;
+
+	/// the innermost catch is global, so tests which catch run one at a time
+	static LOCK: Mutex<(),> = Mutex::new((),);
+
+	/// Takes the innermost catch, which no catch holds
+	pub(crate) fn lock() -> MutexGuard<'static, (),> {
+		let guard = LOCK.lock();
+		let guard = guard.unwrap_or_else(|poisoned| poisoned.into_inner(),);
+		CURRENT.store(null_mut(), Ordering::Release,);
+		guard
+	}
+
+	struct Flaky {
+		runs:      u32,
+		fail:      u32,
+		reclaimed: u32,
+	}
+
+	impl Task for Flaky {
+		fn name(&self,) -> &'static str {
+			"flaky"
+		}
+
+		fn run(&mut self,) {
+			self.runs += 1;
+			if self.runs <= self.fail {
+				raise(format_args!("run {}", self.runs), None,);
+			}
+		}
+
+		fn reclaim(&mut self,) {
+			self.reclaimed += 1;
+		}
+	}
+
+	#[test]
+	fn test_catch_round_trip() {
+		let _lock = lock();
+		assert_eq!(catch(|| 42,), Ok(42));
+
+		let reached = Cell::new(false,);
+		let location = Location::caller();
+		let panic = catch(|| {
+			raise(format_args!("task {}", 7), Some(location,),);
+			reached.set(true,);
+		},)
+		.unwrap_err();
+		assert!(!reached.get());
+		assert_eq!(&*panic.message, std::format!("task 7 at {location}"));
+		assert!(CURRENT.load(Ordering::Acquire).is_null());
+
+		// an inner catch returns to its caller, still inside the outer one
+		let outer = catch(|| {
+			let inner = catch(|| raise(format_args!("inner"), None,),);
+			assert_eq!(&*inner.unwrap_err().message, "inner");
+			raise(format_args!("outer"), None,);
+		},);
+		assert_eq!(&*outer.unwrap_err().message, "outer");
+		assert!(CURRENT.load(Ordering::Acquire).is_null());
+	}
+
+	#[test]
+	fn test_supervise_policies() {
+		let _lock = lock();
+		let mut task = Flaky { runs: 0, fail: 2, reclaimed: 0, };
+		let outcome = supervise(&mut task, Policy::Restart { limit: 2, },);
+		assert_eq!(outcome, Outcome::Finished { restarts: 2 });
+		assert_eq!((task.runs, task.reclaimed,), (3, 2));
+
+		let mut task = Flaky { runs: 0, fail: 1, reclaimed: 0, };
+		let Outcome::Failed(panic,) = supervise(&mut task, Policy::Ignore,)
+		else {
+			panic!("task did not fail")
+		};
+		assert_eq!(&*panic.message, "run 1");
+		assert_eq!((task.runs, task.reclaimed,), (1, 1));
+	}
+}
@@ -0,0 +1,145 @@
+//! # Kernel Symbols
+//!
+//! Resolves addresses of the kernel to the names of its functions and
+//! statics, so backtraces and profiles are readable on the machine itself.
+//!
+//! The names come from a table sorted by address in the section
+//! `.oso_symbols`. The section is reserved by
+//! [`symbol_map!`](oso_proc_macro::symbol_map) and filled in place by
+//! `cargo xtask` after linking, before release images are stripped. Its
+//! layout is described in `oso_dev_util::symbol_map`, which writes it.
+//!
+//! ```text
+//!   0  header    magic `OSYM`, version, count, capacity
+//!  16  addrs     start of each symbol, then the end of the last one
+//!      names     offset of each name in text, then the length of text
+//!      text      demangled names
+//! ```
+//!
+//! ## Current Status
+//!
+//! The loader does not pass the ELF symbol table, so the embedded table is
+//! the only source of names. A kernel linked without `cargo xtask` keeps an
+//! empty table and resolves nothing. The kernel runs at its link address, so
+//! addresses are looked up as they are.
+//!
+//! ```rust,ignore
+//! use oso_kernel::base::symbols;
+//!
+//! if let Some((name, offset,),) = symbols::resolve(pc,) {
+//! 	println!("{name}+{offset:#x}");
+//! }
+//! println!("{}", symbols::Symbolized(pc,));
+//! ```
+
+use core::fmt;
+
+/// First bytes of the section
+pub const MAGIC: [u8; 4] = *b"OSYM";
+/// Version of the layout understood by [`SymbolMap::parse`]
+pub const VERSION: u8 = 1;
+/// Bytes of the header
+pub const HEADER_SIZE: usize = 16;
+
+oso_proc_macro::symbol_map!(capacity = 0x40000);
+
+/// Symbol containing `addr` in the embedded table, and the offset of `addr`
+/// into it
+pub fn resolve(addr: u64,) -> Option<(&'static str, u64,),> {
+	SymbolMap::parse(symbol_map_bytes(),)?.resolve(addr,)
+}
+
+/// Number of entries in the embedded table. 0 if it was not written
+pub fn len() -> usize {
+	SymbolMap::parse(symbol_map_bytes(),).map_or(0, |map| map.len(),)
+}
+
+/// Formats an address with the symbol containing it, as
+/// `0x0000000040001234 oso_kernel::init+0x14`
+///
+/// Addresses outside every symbol are formatted alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct Symbolized(pub u64,);
+
+impl fmt::Display for Symbolized {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		write!(f, "{:#018x}", self.0)?;
+		match resolve(self.0,) {
+			Some((name, offset,),) => write!(f, " {name}+{offset:#x}"),
+			None => Ok((),),
+		}
+	}
+}
+
+/// Table of symbols in the layout written by `cargo xtask`
+#[derive(Debug, Clone, Copy,)]
+pub struct SymbolMap<'a,> {
+	count: usize,
+	addrs: &'a [u8],
+	names: &'a [u8],
+	text:  &'a [u8],
+}
+
+impl<'a,> SymbolMap<'a,> {
+	/// Table in `bytes`. `None` if the header is not valid or the table
+	/// exceeds `bytes`
+	pub fn parse(bytes: &'a [u8],) -> Option<Self,> {
+		if bytes.get(..4,)? != MAGIC || *bytes.get(4,)? != VERSION {
+			return None;
+		}
+		let count = read_u32(bytes, 8,)? as usize;
+		let (addrs, rest,) =
+			bytes.get(HEADER_SIZE..,)?.split_at_checked((count + 1) * 8,)?;
+		let (names, rest,) = rest.split_at_checked((count + 1) * 4,)?;
+		let mut map = Self { count, addrs, names, text: &[], };
+		map.text = rest.get(..map.name_offset(count,)?,)?;
+		Some(map,)
+	}
+
+	/// Number of entries, including those of gaps between symbols
+	pub fn len(&self,) -> usize {
+		self.count
+	}
+
+	pub fn is_empty(&self,) -> bool {
+		self.count == 0
+	}
+
+	/// Name of the symbol containing `addr`, and the offset of `addr` into
+	/// it
+	pub fn resolve(&self, addr: u64,) -> Option<(&'a str, u64,),> {
+		if self.is_empty()
+			|| addr < self.addr(0,)?
+			|| addr >= self.addr(self.count,)?
+		{
+			return None;
+		}
+		// first entry past `addr`, found among the entries before the end
+		let (mut low, mut high,) = (0, self.count,);
+		while low < high {
+			let mid = (low + high) / 2;
+			if self.addr(mid,)? <= addr { low = mid + 1 } else { high = mid }
+		}
+		let i = low - 1;
+		let range = self.name_offset(i,)?..self.name_offset(i + 1,)?;
+		let name = core::str::from_utf8(self.text.get(range,)?,).ok()?;
+		if name.is_empty() {
+			return None;
+		}
+		Some((name, addr - self.addr(i,)?,),)
+	}
+
+	fn addr(&self, i: usize,) -> Option<u64,> {
+		let bytes = self.addrs.get(i * 8..i * 8 + 8,)?;
+		Some(u64::from_le_bytes(bytes.try_into().ok()?,),)
+	}
+
+	fn name_offset(&self, i: usize,) -> Option<usize,> {
+		read_u32(self.names, i * 4,).map(|offset| offset as usize,)
+	}
+}
+
+fn read_u32(bytes: &[u8], at: usize,) -> Option<u32,> {
+	let bytes = bytes.get(at..at + 4,)?;
+	Some(u32::from_le_bytes(bytes.try_into().ok()?,),)
+}
@@ -0,0 +1,48 @@
+//! # Timer Tick
+//!
+//! [`interrupt`] is the work of one timer tick, in the order it must happen:
+//!
+//! 1. [`executor::tick`] advances the clock of `sleep` and wakes tasks whose
+//!    deadline passed
+//! 2. [`sched::tick`] charges the running task and ages the waiting ones
+//! 3. [`watchdog::pat`] restarts the countdown of the installed watchdog
+//! 4. [`gpio::heartbeat`] blinks the heartbeat LED
+//! 5. [`sched::preempt`] switches tasks if the slice ran out or a task of a
+//!    higher level became ready
+//!
+//! The switch comes last, as it resumes another task before the interrupt
+//! returns. The work before it is timed by [`perf::irq`] as [`IRQ`].
+//!
+//! ## Current Status
+//!
+//! The kernel has no interrupt controller or timer driver yet. The
+//! simulator of the `sim` feature calls [`interrupt`] once per frame, and the
+//! interrupt vector of the future driver calls it for the timer IRQ, after
+//! the end of interrupt.
+//!
+//! [`executor::tick`]: crate::app::executor::tick
+//! [`watchdog::pat`]: crate::driver::watchdog::pat
+//! [`gpio::heartbeat`]: crate::driver::gpio::heartbeat
+
+use super::perf;
+use super::sched;
+use crate::app::executor;
+use crate::driver::gpio;
+use crate::driver::watchdog;
+
+/// IRQ of the timer, the non-secure physical timer PPI of the generic timer
+pub const IRQ: u32 = 30;
+
+/// Runs one timer tick. Called from the timer interrupt with interrupts
+/// masked
+pub fn interrupt() {
+	let mut trace = perf::irq::enter(IRQ,);
+	trace.dispatch();
+	executor::tick();
+	sched::tick();
+	watchdog::pat();
+	gpio::heartbeat();
+	// before the switch, which may not return until a later tick
+	trace.eoi();
+	sched::preempt();
+}
@@ -0,0 +1,248 @@
+//! # Virtual Terminals
+//!
+//! The screen shows one of [`VT_COUNT`] consoles at a time, each with its
+//! own text, cursor and colors. As with the virtual terminals of Linux,
+//! `Alt+F1` to `Alt+F3` switch between them, and so does the `vt` shell
+//! command.
+//!
+//! | Terminal | Hotkey   | Written by                    |
+//! | -------- | -------- | ----------------------------- |
+//! | `log`    | `Alt+F1` | [`print!`](crate::print)      |
+//! | `shell`  | `Alt+F2` | The debug shell               |
+//! | `app`    | `Alt+F3` | Applications                  |
+//!
+//! Every terminal is a [`TextBuf`] drawn through the glyph cache of
+//! [`io`](crate::base::io). Terminals in the background only update their
+//! cells, and switching to a terminal draws all of its cells.
+//!
+//! ## Shell Command
+//!
+//! - `vt`: Lists the terminals and marks the one on screen
+//! - `vt <name|number>`: Switches to a terminal, e.g. `vt shell` or `vt 2`
+//!
+//! ## Current Status
+//!
+//! The kernel has no keyboard driver yet, so hotkeys only switch terminals
+//! once the owner of key input passes function keys to [`handle_hotkey`].
+//! The boot script echoes to the early console rather than the `shell`
+//! terminal.
+//!
+//! ```rust,ignore
+//! let mut out = VtConsole::new(Vt::Shell,);
+//! shell::execute("idle stats", &mut out,)?;
+//! vt::switch(Vt::Shell,)?;
+//! ```
+
+use crate::base::io::TextBuf;
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+use oso_error::Rslt;
+use oso_error::kernel::VtError;
+use oso_error::oso_err;
+use oso_no_std_shared::text::console::Console;
+use oso_no_std_shared::text::console::ConsoleColor;
+
+/// Name of the shell command
+pub const COMMAND: &str = "vt";
+/// Number of terminals
+pub const VT_COUNT: usize = 3;
+
+static TERMINALS: Terminals = Terminals::new();
+/// index of the terminal on screen
+static ACTIVE: AtomicUsize = AtomicUsize::new(0,);
+
+/// Virtual terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum Vt {
+	/// Kernel log, written by [`print!`](crate::print)
+	Log,
+	/// Output of the debug shell
+	Shell,
+	/// Output of applications
+	App,
+}
+
+impl Vt {
+	/// Every terminal, in the order of their hotkeys
+	pub const ALL: [Self; VT_COUNT] = [Self::Log, Self::Shell, Self::App,];
+
+	pub const fn name(&self,) -> &'static str {
+		match self {
+			Self::Log => "log",
+			Self::Shell => "shell",
+			Self::App => "app",
+		}
+	}
+
+	/// Terminal of the name or the number, counted from 1, `name`
+	pub fn from_name(name: &str,) -> Option<Self,> {
+		if let Ok(number,) = name.parse::<usize>() {
+			return Self::ALL.get(number.checked_sub(1,)?,).copied();
+		}
+		Self::ALL.into_iter().find(|vt| vt.name() == name,)
+	}
+}
+
+/// Terminal on screen
+pub fn active() -> Vt {
+	Vt::ALL[ACTIVE.load(Ordering::Relaxed,)]
+}
+
+/// Shows `vt`, and draws all of its cells
+///
+/// # Errors
+///
+/// - [`VtError::Busy`] if a terminal is being written, e.g. by the
+///   interrupted code
+pub fn switch(vt: Vt,) -> Rslt<(), VtError,> {
+	TERMINALS
+		.with(|terminals: &mut Bufs| {
+			let previous = ACTIVE.swap(vt as usize, Ordering::Relaxed,);
+			if previous != vt as usize {
+				terminals[previous].set_visible(false,);
+				terminals[vt as usize].set_visible(true,);
+			}
+		},)
+		.ok_or(oso_err!(VtError::Busy),)
+}
+
+/// Switches terminals for `Alt+F<function>`. `true` if the key was a
+/// hotkey
+///
+/// Called by the owner of key input for function keys, which then does not
+/// pass hotkeys on.
+pub fn handle_hotkey(alt: bool, function: u8,) -> bool {
+	let index = (function as usize).checked_sub(1,);
+	let Some(&vt,) = index.and_then(|i| Vt::ALL.get(i,),).filter(|_| alt,)
+	else {
+		return false;
+	};
+	let _ = switch(vt,);
+	true
+}
+
+/// Calls `f` with the console of `vt`. `None` if a terminal is being
+/// written
+pub fn with<R,>(
+	vt: Vt,
+	f: impl FnOnce(&mut dyn Console,) -> R,
+) -> Option<R,> {
+	TERMINALS.with(|terminals: &mut Bufs| f(&mut terminals[vt as usize],),)
+}
+
+/// Console writing to a virtual terminal
+///
+/// Output is dropped while another console writes a terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct VtConsole {
+	vt: Vt,
+}
+
+impl VtConsole {
+	pub const fn new(vt: Vt,) -> Self {
+		Self { vt, }
+	}
+}
+
+impl fmt::Write for VtConsole {
+	fn write_str(&mut self, s: &str,) -> fmt::Result {
+		with(self.vt, |console| console.write_str(s,),).unwrap_or(Ok((),),)
+	}
+}
+
+impl Console for VtConsole {
+	fn set_color(
+		&mut self,
+		foreground: ConsoleColor,
+		background: ConsoleColor,
+	) -> fmt::Result {
+		with(self.vt, |console| console.set_color(foreground, background,),)
+			.unwrap_or(Err(fmt::Error,),)
+	}
+
+	fn clear(&mut self,) -> fmt::Result {
+		with(self.vt, |console| console.clear(),).unwrap_or(Err(fmt::Error,),)
+	}
+
+	fn cursor(&self,) -> (usize, usize,) {
+		with(self.vt, |console| console.cursor(),).unwrap_or_default()
+	}
+
+	fn set_cursor(&mut self, column: usize, row: usize,) -> fmt::Result {
+		with(self.vt, |console| console.set_cursor(column, row,),)
+			.unwrap_or(Err(fmt::Error,),)
+	}
+}
+
+/// Runs the `vt` shell command with the arguments after its name
+pub fn run_command(
+	args: &[&str],
+	out: &mut impl fmt::Write,
+) -> Rslt<(), VtError,> {
+	match args {
+		[] => {
+			let active = active();
+			for (i, vt,) in Vt::ALL.into_iter().enumerate() {
+				let mark = if vt == active { '*' } else { ' ' };
+				let _ = writeln!(out, "{mark} {} {}", i + 1, vt.name());
+			}
+		},
+		[name,] => {
+			let Some(vt,) = Vt::from_name(name,) else {
+				let _ = writeln!(out, "{COMMAND}: no terminal {name}");
+				return Err(oso_err!(VtError::UnknownTerminal),);
+			};
+			if let Err(e,) = switch(vt,) {
+				let _ = writeln!(out, "{COMMAND}: terminals are busy");
+				return Err(e,);
+			}
+		},
+		_ => {
+			let _ = writeln!(out, "usage: {COMMAND} [name|number]");
+			return Err(oso_err!(VtError::Usage),);
+		},
+	}
+	Ok((),)
+}
+
+/// text buffers of the terminals
+type Bufs = [TextBuf<(usize, usize,),>; VT_COUNT];
+
+/// text buffers of the terminals behind a try-lock
+struct Terminals {
+	busy: AtomicBool,
+	bufs: UnsafeCell<Bufs,>,
+}
+
+// SAFETY: the text buffers are only accessed while `busy` is held
+unsafe impl Sync for Terminals {}
+
+impl Terminals {
+	const fn new() -> Self {
+		Self {
+			busy: AtomicBool::new(false,),
+			bufs: UnsafeCell::new([
+				TextBuf::new((0, 0,), 8, 16,),
+				TextBuf::new((0, 0,), 8, 16,).hidden(),
+				TextBuf::new((0, 0,), 8, 16,).hidden(),
+			],),
+		}
+	}
+
+	/// Calls `f` with the text buffers. `None` if they are in use
+	fn with<R,>(
+		&self,
+		f: impl FnOnce(&mut Bufs,) -> R,
+	) -> Option<R,> {
+		if self.busy.swap(true, Ordering::Acquire,) {
+			return None;
+		}
+		// SAFETY: the lock is held
+		let r = f(unsafe { &mut *self.bufs.get() },);
+		self.busy.store(false, Ordering::Release,);
+		Some(r,)
+	}
+}
@@ -0,0 +1,77 @@
+//! # OSO Simulator
+//!
+//! Runs the kernel graphics, consoles and debug shell in a window on the
+//! host, see `oso_kernel::sim`. Built with the `sim` feature.
+//!
+//! ```text
+//! oso_sim [--replay <log>] [<width>x<height>]
+//! ```
+//!
+//! `--replay` replays the last recording of `record dump` in the serial log.
+
+use oso_kernel::sim;
+use oso_no_std_shared::shell::replay;
+use oso_no_std_shared::shell::replay::Event;
+use oso_no_std_shared::shell::replay::Recording;
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: oso_sim [--replay <log>] [<width>x<height>]";
+
+fn main() -> ExitCode {
+	let mut size = sim::DEFAULT_SIZE;
+	let mut events = vec![];
+	let mut args = std::env::args().skip(1,);
+	while let Some(arg,) = args.next() {
+		if arg == "--replay"
+			&& let Some(log,) = args.next()
+		{
+			let Some(replay,) = load(&log,) else {
+				return ExitCode::FAILURE;
+			};
+			events = replay;
+		} else if let Some(parsed,) = parse_size(&arg,) {
+			size = parsed;
+		} else {
+			eprintln!("{USAGE}");
+			return ExitCode::FAILURE;
+		}
+	}
+
+	let (width, height,) = size;
+	match sim::run(width, height, &events,) {
+		Ok((),) => ExitCode::SUCCESS,
+		Err(e,) => {
+			eprintln!("oso_sim: {e}");
+			ExitCode::FAILURE
+		},
+	}
+}
+
+/// `800x600` as `(800, 600)`. `None` unless both are above zero
+fn parse_size(arg: &str,) -> Option<(usize, usize,),> {
+	let (width, height,) = arg.split_once('x',)?;
+	let (width, height,) = (width.parse().ok()?, height.parse().ok()?,);
+	(width > 0 && height > 0).then_some((width, height,),)
+}
+
+/// Events of the last recording in the serial log at `path`. Reports why
+/// there are none
+fn load(path: &str,) -> Option<Vec<Event,>,> {
+	let log = match std::fs::read_to_string(path,) {
+		Ok(log,) => log,
+		Err(e,) => {
+			eprintln!("oso_sim: {path}: {e}");
+			return None;
+		},
+	};
+	let mut buf = vec![0; log.len() / 2];
+	let recording =
+		replay::find_hex(&log, &mut buf,).and_then(Recording::parse,);
+	match recording {
+		Ok(recording,) => Some(recording.events().collect(),),
+		Err(e,) => {
+			eprintln!("oso_sim: {path}: {:?}", e.desc);
+			None
+		},
+	}
+}
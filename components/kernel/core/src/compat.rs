@@ -0,0 +1,288 @@
+//! # Boot Protocol Compatibility
+//!
+//! oso_kernel is normally started by oso_loader, which passes a [`BootInfo`].
+//! This module lets other boot loaders start it instead by translating their
+//! boot information into the same structure:
+//!
+//! - [`multiboot2`]: GRUB and other Multiboot2 loaders, feature `multiboot2`
+//! - [`limine`]: the Limine boot protocol, feature `limine`
+//!
+//! Each protocol has its own entry point in `main.rs`, which builds
+//! [`BootInfo`] and continues like `kernel_main`.
+//!
+//! ## Storage
+//!
+//! The kernel has no allocator this early, so the translated boot information
+//! lives in statics with room for [`MAX_REGIONS`] memory regions and
+//! [`MAX_MODULES`] modules. Strings and module contents are not copied and
+//! stay where the boot loader put them.
+
+use core::cell::UnsafeCell;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::Ordering;
+use oso_error::Rslt;
+use oso_error::kernel::BootProtocolError;
+use oso_error::oso_err;
+use oso_no_std_shared::bridge::boot_info::BootInfo;
+use oso_no_std_shared::bridge::boot_info::CommandLine;
+use oso_no_std_shared::bridge::boot_info::MemoryRegion;
+use oso_no_std_shared::bridge::boot_info::MemoryRegionKind;
+use oso_no_std_shared::bridge::boot_info::MemoryRegions;
+use oso_no_std_shared::bridge::boot_info::Module;
+use oso_no_std_shared::bridge::boot_info::Modules;
+use oso_no_std_shared::bridge::device_tree::DeviceTreeAddress;
+use oso_no_std_shared::bridge::graphic::FrameBufConf;
+use oso_no_std_shared::bridge::graphic::PixelFormatConf;
+use oso_no_std_shared::units::PhysAddr;
+
+#[cfg(feature = "limine")]
+pub mod limine;
+#[cfg(feature = "multiboot2")]
+pub mod multiboot2;
+
+/// Memory regions [`Builder`] can hold
+pub const MAX_REGIONS: usize = 256;
+/// Modules [`Builder`] can hold
+pub const MAX_MODULES: usize = 16;
+
+static BUILT: AtomicBool = AtomicBool::new(false,);
+static STORAGE: Storage = Storage(UnsafeCell::new(EMPTY_CONTENTS,),);
+
+const EMPTY_CONTENTS: Contents = Contents {
+	info:        BootInfo::new(core::ptr::null(),),
+	regions:     [EMPTY_REGION; MAX_REGIONS],
+	modules:     [EMPTY_MODULE; MAX_MODULES],
+	framebuffer: None,
+};
+const EMPTY_REGION: MemoryRegion =
+	MemoryRegion::new(0, 0, 0, MemoryRegionKind::Reserved, 0,);
+const EMPTY_MODULE: Module = Module::new(0, 0, CommandLine::empty(),);
+
+/// only accessed through the single [`Builder`], see [`BUILT`]
+struct Storage(UnsafeCell<Contents,>,);
+
+unsafe impl Sync for Storage {}
+
+struct Contents {
+	info:        BootInfo,
+	regions:     [MemoryRegion; MAX_REGIONS],
+	modules:     [Module; MAX_MODULES],
+	framebuffer: Option<FrameBufConf,>,
+}
+
+/// Fills the static [`BootInfo`] while a boot protocol is parsed
+pub struct Builder {
+	contents:     &'static mut Contents,
+	region_count: usize,
+	module_count: usize,
+}
+
+impl Builder {
+	/// Takes the static storage
+	///
+	/// # Errors
+	///
+	/// [`BootProtocolError::AlreadyBuilt`] on the second call
+	pub fn new() -> Rslt<Self, BootProtocolError,> {
+		if BUILT.swap(true, Ordering::AcqRel,) {
+			return Err(oso_err!(BootProtocolError::AlreadyBuilt),);
+		}
+		let contents = unsafe { &mut *STORAGE.0.get() };
+		Ok(Self { contents, region_count: 0, module_count: 0, },)
+	}
+
+	pub fn device_tree(&mut self, device_tree: DeviceTreeAddress,) {
+		self.contents.info.device_tree = device_tree;
+	}
+
+	/// Sets the command line, which must stay valid after boot
+	pub fn cmdline(&mut self, cmdline: CommandLine,) {
+		self.contents.info.cmdline = cmdline;
+	}
+
+	/// Adds `len` bytes at `start` as a region of `kind`
+	///
+	/// Boot protocols report byte ranges while [`MemoryRegion`] counts pages,
+	/// so usable ranges shrink to whole pages and all others grow to them.
+	///
+	/// # Errors
+	///
+	/// [`BootProtocolError::TooManyEntries`] if [`MAX_REGIONS`] are already
+	/// added
+	pub fn region(
+		&mut self,
+		start: u64,
+		len: u64,
+		kind: MemoryRegionKind,
+	) -> Rslt<(), BootProtocolError,> {
+		let page = MemoryRegion::PAGE_SIZE;
+		let end = PhysAddr::new(start.saturating_add(len,),);
+		let start = PhysAddr::new(start,);
+		let pages = if kind.is_usable() {
+			start.align_up(page,).map(|start| start..end.align_down(page,),)
+		} else {
+			PhysAddr::page_span(start, end,)
+		};
+		// nothing of the range is a whole page, or its last page ends past
+		// the address space
+		let Some(pages,) = pages.filter(|pages| pages.start < pages.end,)
+		else {
+			return Ok((),);
+		};
+		let (start, end,) = (pages.start.as_u64(), pages.end.as_u64(),);
+
+		let Some(slot,) = self.contents.regions.get_mut(self.region_count,)
+		else {
+			return Err(oso_err!(BootProtocolError::TooManyEntries {
+				what:     "memory regions",
+				capacity: MAX_REGIONS,
+			}),);
+		};
+		*slot = MemoryRegion::new(start, start, (end - start) / page, kind, 0,);
+		self.region_count += 1;
+		Ok((),)
+	}
+
+	/// Adds a module of `size` bytes at `start`
+	///
+	/// # Errors
+	///
+	/// [`BootProtocolError::TooManyEntries`] if [`MAX_MODULES`] are already
+	/// added
+	pub fn module(
+		&mut self,
+		start: u64,
+		size: u64,
+		cmdline: CommandLine,
+	) -> Rslt<(), BootProtocolError,> {
+		let Some(slot,) = self.contents.modules.get_mut(self.module_count,)
+		else {
+			return Err(oso_err!(BootProtocolError::TooManyEntries {
+				what:     "modules",
+				capacity: MAX_MODULES,
+			}),);
+		};
+		*slot = Module::new(start, size, cmdline,);
+		self.module_count += 1;
+		Ok((),)
+	}
+
+	pub fn framebuffer(&mut self, framebuffer: FrameBufConf,) {
+		self.contents.framebuffer = Some(framebuffer,);
+	}
+
+	/// Links the collected entries into the static [`BootInfo`]
+	pub fn finish(self,) -> &'static BootInfo {
+		let contents = self.contents;
+		contents.info.memory_map = MemoryRegions {
+			ptr: contents.regions.as_ptr(),
+			len: self.region_count,
+		};
+		contents.info.modules = Modules {
+			ptr: contents.modules.as_ptr(),
+			len: self.module_count,
+		};
+		if let Some(framebuffer,) = &contents.framebuffer {
+			contents.info.framebuffer = framebuffer;
+		}
+		&contents.info
+	}
+}
+
+/// Pixel format of a direct color framebuffer with 8 bits per channel
+///
+/// Both protocols describe channels by bit offset within a pixel.
+pub fn pixel_format(
+	bits_per_pixel: u16,
+	red_shift: u8,
+	green_shift: u8,
+	blue_shift: u8,
+) -> PixelFormatConf {
+	match (bits_per_pixel, red_shift, green_shift, blue_shift,) {
+		(32, 0, 8, 16,) => PixelFormatConf::Rgb,
+		(32, 16, 8, 0,) => PixelFormatConf::Bgr,
+		_ => PixelFormatConf::Bitmask,
+	}
+}
+
+/// Length of the NUL terminated string at `ptr`
+///
+/// # Safety
+///
+/// `ptr` must be null or point to a NUL terminated string
+pub unsafe fn c_str_len(ptr: *const u8,) -> usize {
+	if ptr.is_null() {
+		return 0;
+	}
+	let mut len = 0;
+	while unsafe { *ptr.add(len,) } != 0 {
+		len += 1;
+	}
+	len
+}
+
+/// Command line of a NUL terminated string, excluding the NUL
+///
+/// # Safety
+///
+/// Same as [`c_str_len`]. The string must stay valid after boot
+pub unsafe fn c_str(ptr: *const u8,) -> CommandLine {
+	CommandLine { ptr, len: unsafe { c_str_len(ptr,) }, }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	extern crate std;
+
+	use super::*;
+	use std::sync::Mutex;
+	use std::sync::MutexGuard;
+
+	/// the storage is global, so tests which parse run one at a time
+	static LOCK: Mutex<(),> = Mutex::new((),);
+
+	/// Takes the storage, empties it and lets the next [`Builder::new`]
+	/// succeed
+	pub(crate) fn lock() -> MutexGuard<'static, (),> {
+		let guard = LOCK.lock();
+		let guard = guard.unwrap_or_else(|poisoned| poisoned.into_inner(),);
+		unsafe { *STORAGE.0.get() = EMPTY_CONTENTS };
+		BUILT.store(false, Ordering::Release,);
+		guard
+	}
+
+	#[test]
+	fn test_builder() {
+		let _lock = lock();
+		let mut builder = Builder::new().unwrap();
+		let built = Builder::new().err().and_then(|error| error.desc,);
+		assert_eq!(built, Some(BootProtocolError::AlreadyBuilt));
+
+		let usable = MemoryRegionKind::Usable;
+		let mmio = MemoryRegionKind::Mmio;
+		// usable memory shrinks to whole pages, the rest grows to them
+		builder.region(0x1800, 0x3000, usable,).unwrap();
+		builder.region(0x1800, 0x800, usable,).unwrap();
+		builder.region(0x1800, 0x10, mmio,).unwrap();
+		builder.region(u64::MAX - 0x800, 0x800, mmio,).unwrap();
+		for _ in 0..MAX_MODULES {
+			builder.module(0x8000, 16, CommandLine::empty(),).unwrap();
+		}
+		let full = builder.module(0x8000, 16, CommandLine::empty(),);
+		let too_many = BootProtocolError::TooManyEntries {
+			what:     "modules",
+			capacity: MAX_MODULES,
+		};
+		assert_eq!(full.unwrap_err().desc, Some(too_many));
+
+		let info = builder.finish();
+		let regions = unsafe { info.memory_map.as_slice() };
+		let expected = [
+			MemoryRegion::new(0x2000, 0x2000, 2, usable, 0,),
+			MemoryRegion::new(0x1000, 0x1000, 1, mmio, 0,),
+		];
+		assert_eq!(regions, expected);
+		assert_eq!(unsafe { info.modules.as_slice() }.len(), MAX_MODULES);
+		assert!(info.framebuffer.is_null());
+	}
+}
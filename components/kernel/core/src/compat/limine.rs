@@ -0,0 +1,472 @@
+//! # Limine Boot Protocol
+//!
+//! The kernel places requests in its image, the boot loader finds them by
+//! their IDs and links a response to each one it answers. Requests live in
+//! the `.limine_requests` section, and [`ENTRY_POINT`] makes Limine start the
+//! kernel at `kernel_main_limine` rather than the ELF entry point used by
+//! oso_loader.
+//!
+//! Limine enters the kernel with paging enabled. Physical memory is mapped at
+//! the offset of the higher half direct map, and the addresses of files and
+//! the framebuffer in [`BootInfo`] are addresses in that map. The memory map
+//! stays physical.
+
+use super::Builder;
+use super::c_str;
+use super::pixel_format;
+use core::cell::UnsafeCell;
+use oso_error::Rslt;
+use oso_error::kernel::BootProtocolError;
+use oso_error::oso_err;
+use oso_no_std_shared::bridge::boot_info::BootInfo;
+use oso_no_std_shared::bridge::boot_info::MemoryRegionKind;
+use oso_no_std_shared::bridge::graphic::FrameBufConf;
+
+const COMMON_MAGIC: [u64; 2] = [0xc7b1_dd30_df4c_8b88, 0x0a82_e883_a194_f07b];
+/// base revision the kernel is written against
+const REVISION: u64 = 2;
+
+#[used]
+#[unsafe(link_section = ".limine_requests")]
+static BASE_REVISION: BaseRevision = BaseRevision(UnsafeCell::new([
+	0xf956_2b2d_5c95_a6c8,
+	0x6a7b_3849_4453_6bdc,
+	REVISION,
+],),);
+
+#[used]
+#[unsafe(link_section = ".limine_requests")]
+pub static ENTRY_POINT: EntryPointRequest = EntryPointRequest {
+	request: Request::new([0x13d8_6c03_5a1c_d3e1, 0x2b0c_aa89_d8f3_026a,],),
+	entry:   kernel_main_limine,
+};
+
+#[used]
+#[unsafe(link_section = ".limine_requests")]
+static MEMMAP: Request<MemmapResponse,> =
+	Request::new([0x67cf_3d9d_378a_806f, 0xe304_acdf_c50c_3c62,],);
+
+#[used]
+#[unsafe(link_section = ".limine_requests")]
+static FRAMEBUFFER: Request<FramebufferResponse,> =
+	Request::new([0x9d58_27dc_d881_dd75, 0xa314_8604_f6fa_b11b,],);
+
+#[used]
+#[unsafe(link_section = ".limine_requests")]
+static MODULES: Request<ModuleResponse,> =
+	Request::new([0x3e7e_2797_02be_32af, 0xca1c_4f3b_d128_0cee,],);
+
+#[used]
+#[unsafe(link_section = ".limine_requests")]
+static EXECUTABLE_FILE: Request<ExecutableFileResponse,> =
+	Request::new([0xad97_e90e_83f1_ed67, 0x31eb_5d1c_5ff2_3b69,],);
+
+#[used]
+#[unsafe(link_section = ".limine_requests")]
+static DEVICE_TREE: Request<DeviceTreeResponse,> =
+	Request::new([0xb40d_db48_fb54_bac7, 0x5450_8149_3f81_ffb7,],);
+
+unsafe extern "C" {
+	/// defined by the kernel binary
+	fn kernel_main_limine() -> !;
+}
+
+/// the boot loader clears the last word if it supports [`REVISION`]
+#[repr(C)]
+struct BaseRevision(UnsafeCell<[u64; 3],>,);
+
+unsafe impl Sync for BaseRevision {}
+
+/// Request of the Limine protocol, answered by a response of type `R`
+#[repr(C)]
+pub struct Request<R,> {
+	id:       [u64; 4],
+	revision: u64,
+	/// written by the boot loader
+	response: UnsafeCell<*const R,>,
+}
+
+unsafe impl<R,> Sync for Request<R,> {}
+
+impl<R,> Request<R,> {
+	const fn new(id: [u64; 2],) -> Self {
+		Self {
+			id:       [COMMON_MAGIC[0], COMMON_MAGIC[1], id[0], id[1],],
+			revision: 0,
+			response: UnsafeCell::new(core::ptr::null(),),
+		}
+	}
+
+	/// Response linked by the boot loader, if it answered
+	fn response(&self,) -> Option<&'static R,> {
+		// the compiler can not know that the boot loader wrote it
+		unsafe { self.response.get().read_volatile().as_ref() }
+	}
+}
+
+/// Request to enter the kernel at `entry`
+#[repr(C)]
+pub struct EntryPointRequest {
+	request: Request<ResponseHeader,>,
+	entry:   unsafe extern "C" fn() -> !,
+}
+
+#[repr(C)]
+struct ResponseHeader {
+	revision: u64,
+}
+
+#[repr(C)]
+struct MemmapResponse {
+	revision:    u64,
+	entry_count: u64,
+	entries:     *const *const MemmapEntry,
+}
+
+#[repr(C)]
+struct MemmapEntry {
+	base:   u64,
+	length: u64,
+	ty:     u64,
+}
+
+#[repr(C)]
+struct FramebufferResponse {
+	revision:          u64,
+	framebuffer_count: u64,
+	framebuffers:      *const *const Framebuffer,
+}
+
+/// leading fields of a framebuffer, which are the same in every revision
+#[repr(C)]
+struct Framebuffer {
+	address:          *mut u8,
+	width:            u64,
+	height:           u64,
+	pitch:            u64,
+	bpp:              u16,
+	memory_model:     u8,
+	red_mask_size:    u8,
+	red_mask_shift:   u8,
+	green_mask_size:  u8,
+	green_mask_shift: u8,
+	blue_mask_size:   u8,
+	blue_mask_shift:  u8,
+}
+
+#[repr(C)]
+struct ModuleResponse {
+	revision:     u64,
+	module_count: u64,
+	modules:      *const *const File,
+}
+
+#[repr(C)]
+struct ExecutableFileResponse {
+	revision: u64,
+	file:     *const File,
+}
+
+/// leading fields of a file, which are the same in every revision
+#[repr(C)]
+struct File {
+	revision: u64,
+	address:  *mut u8,
+	size:     u64,
+	path:     *const u8,
+	cmdline:  *const u8,
+}
+
+#[repr(C)]
+struct DeviceTreeResponse {
+	revision: u64,
+	dtb:      *const u8,
+}
+
+/// Translates the responses of the boot loader into [`BootInfo`]
+///
+/// Framebuffer, modules, command line and device tree are optional.
+///
+/// # Errors
+///
+/// - [`BootProtocolError::UnsupportedRevision`] if the boot loader does not
+///   support the base revision the kernel asked for
+/// - [`BootProtocolError::MissingResponse`] if there is no memory map
+/// - Errors of [`Builder`]
+///
+/// # Safety
+///
+/// Must be called by the entry point before anything overwrites memory of
+/// the boot loader
+pub unsafe fn parse() -> Rslt<&'static BootInfo, BootProtocolError,> {
+	let revision = unsafe { (*BASE_REVISION.0.get())[2] };
+	if revision != 0 {
+		return Err(oso_err!(BootProtocolError::UnsupportedRevision {
+			revision: REVISION
+		}),);
+	}
+
+	let mut builder = Builder::new()?;
+	let Some(memmap,) = MEMMAP.response() else {
+		return Err(oso_err!(BootProtocolError::MissingResponse("memory map")),);
+	};
+	for entry in unsafe { entries(memmap.entries, memmap.entry_count,) } {
+		builder.region(entry.base, entry.length, region_kind(entry.ty,),)?;
+	}
+
+	if let Some(response,) = FRAMEBUFFER.response()
+		&& let Some(framebuffer,) = unsafe {
+			entries(response.framebuffers, response.framebuffer_count,)
+		}
+		.first()
+	{
+		builder.framebuffer(framebuffer_conf(framebuffer,),);
+	}
+
+	if let Some(response,) = MODULES.response() {
+		for file in unsafe { entries(response.modules, response.module_count,) }
+		{
+			let cmdline = unsafe { c_str(file.cmdline,) };
+			builder.module(file.address as u64, file.size, cmdline,)?;
+		}
+	}
+
+	if let Some(response,) = EXECUTABLE_FILE.response()
+		&& let Some(file,) = unsafe { response.file.as_ref() }
+	{
+		builder.cmdline(unsafe { c_str(file.cmdline,) },);
+	}
+
+	if let Some(response,) = DEVICE_TREE.response() {
+		builder.device_tree(response.dtb,);
+	}
+
+	Ok(builder.finish(),)
+}
+
+/// # Safety
+///
+/// `ptr` must point to `count` valid pointers
+unsafe fn entries<'a, T,>(ptr: *const *const T, count: u64,) -> &'a [&'a T] {
+	if ptr.is_null() {
+		return &[];
+	}
+	// `*const T` and `&T` share their layout, and the boot loader does not
+	// link null entries
+	unsafe { core::slice::from_raw_parts(ptr.cast(), count as usize,) }
+}
+
+fn region_kind(ty: u64,) -> MemoryRegionKind {
+	match ty {
+		0 => MemoryRegionKind::Usable,
+		2 => MemoryRegionKind::AcpiReclaim,
+		3 => MemoryRegionKind::AcpiNvs,
+		// responses live in bootloader reclaimable memory
+		5 | 6 => MemoryRegionKind::Loader,
+		7 => MemoryRegionKind::Framebuffer,
+		_ => MemoryRegionKind::Reserved,
+	}
+}
+
+fn framebuffer_conf(fb: &Framebuffer,) -> FrameBufConf {
+	let format = pixel_format(
+		fb.bpp,
+		fb.red_mask_shift,
+		fb.green_mask_shift,
+		fb.blue_mask_shift,
+	);
+	FrameBufConf {
+		pixel_format: format,
+		base:         fb.address,
+		size:         (fb.pitch * fb.height) as usize,
+		width:        fb.width as usize,
+		height:       fb.height as usize,
+		stride:       fb.pitch as usize,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	extern crate std;
+
+	use super::*;
+	use crate::compat::tests::lock;
+	use oso_no_std_shared::bridge::boot_info::MemoryRegion;
+	use oso_no_std_shared::bridge::graphic::PixelFormatConf;
+	use std::boxed::Box;
+	use std::vec;
+	use std::vec::Vec;
+
+	/// stands in for the kernel binary, which defines the entry point
+	#[unsafe(no_mangle)]
+	extern "C" fn kernel_main_limine() -> ! {
+		unreachable!("tests are not entered by limine")
+	}
+
+	/// Links `response` to `request` like the boot loader, or unlinks it
+	fn answer<R,>(request: &Request<R,>, response: Option<R,>,) {
+		let response = response.map_or(core::ptr::null(), |response| {
+			Box::leak(Box::new(response,),) as *const R
+		},);
+		unsafe { request.response.get().write(response,) };
+	}
+
+	/// Array of pointers to `items`, as responses link them
+	fn pointers<T,>(items: Vec<T,>,) -> *const *const T {
+		let items: Vec<_,> = items
+			.into_iter()
+			.map(|item| Box::leak(Box::new(item,),) as *const T,)
+			.collect();
+		items.leak().as_ptr()
+	}
+
+	/// Takes the storage and unlinks every response
+	fn reset(revision: u64,) -> std::sync::MutexGuard<'static, (),> {
+		let guard = lock();
+		unsafe { (*BASE_REVISION.0.get())[2] = revision };
+		answer(&MEMMAP, None,);
+		answer(&FRAMEBUFFER, None,);
+		answer(&MODULES, None,);
+		answer(&EXECUTABLE_FILE, None,);
+		answer(&DEVICE_TREE, None,);
+		guard
+	}
+
+	fn file(address: usize, size: u64, cmdline: &'static [u8],) -> File {
+		File {
+			revision: 0,
+			address: address as *mut u8,
+			size,
+			path: c"/boot/file".as_ptr().cast(),
+			cmdline: cmdline.as_ptr(),
+		}
+	}
+
+	#[test]
+	fn test_parse() {
+		let _lock = reset(0,);
+		let entries = pointers(vec![
+			MemmapEntry { base: 0x10_0000, length: 0x10_0000, ty: 0, },
+			MemmapEntry { base: 0x30_0000, length: 0x1800, ty: 5, },
+			MemmapEntry { base: 0x8000_0000, length: 0x30_0000, ty: 7, },
+		],);
+		let memmap = MemmapResponse { revision: 0, entry_count: 3, entries, };
+		answer(&MEMMAP, Some(memmap,),);
+		let framebuffers = pointers(vec![Framebuffer {
+			address:          0xffff_8000_8000_0000_usize as *mut u8,
+			width:            1024,
+			height:           768,
+			pitch:            4096,
+			bpp:              32,
+			memory_model:     1,
+			red_mask_size:    8,
+			red_mask_shift:   0,
+			green_mask_size:  8,
+			green_mask_shift: 8,
+			blue_mask_size:   8,
+			blue_mask_shift:  16,
+		}],);
+		answer(&FRAMEBUFFER, Some(FramebufferResponse {
+			revision: 0,
+			framebuffer_count: 1,
+			framebuffers,
+		},),);
+		let modules = pointers(vec![file(0x40_0000, 0x2345, b"initrd\0",)],);
+		answer(&MODULES, Some(ModuleResponse {
+			revision: 0,
+			module_count: 1,
+			modules,
+		},),);
+		let kernel = Box::leak(Box::new(file(0x1000, 0x100, b"quiet\0",),),);
+		answer(&EXECUTABLE_FILE, Some(ExecutableFileResponse {
+			revision: 0,
+			file:     kernel,
+		},),);
+		let dtb = 0x4000_0000 as *const u8;
+		answer(&DEVICE_TREE, Some(DeviceTreeResponse { revision: 0, dtb, },),);
+
+		let info = unsafe { parse() }.unwrap();
+		let regions = unsafe { info.memory_map.as_slice() };
+		let expected = [
+			MemoryRegion::new(
+				0x10_0000,
+				0x10_0000,
+				256,
+				MemoryRegionKind::Usable,
+				0,
+			),
+			MemoryRegion::new(
+				0x30_0000,
+				0x30_0000,
+				2,
+				MemoryRegionKind::Loader,
+				0,
+			),
+			MemoryRegion::new(
+				0x8000_0000,
+				0x8000_0000,
+				0x300,
+				MemoryRegionKind::Framebuffer,
+				0,
+			),
+		];
+		assert_eq!(regions, expected);
+		let framebuffer = unsafe { &*info.framebuffer };
+		assert_eq!(framebuffer.pixel_format, PixelFormatConf::Rgb);
+		assert_eq!(framebuffer.size, 4096 * 768);
+		assert_eq!((framebuffer.width, framebuffer.stride,), (1024, 4096));
+		let modules = unsafe { info.modules.as_slice() };
+		assert_eq!((modules[0].start, modules[0].size,), (0x40_0000, 0x2345));
+		assert_eq!(unsafe { modules[0].cmdline.as_str() }, "initrd");
+		assert_eq!(unsafe { info.cmdline.as_str() }, "quiet");
+		assert_eq!(info.device_tree, dtb);
+	}
+
+	#[test]
+	fn test_unsupported_revision() {
+		let _lock = reset(REVISION,);
+		let revision = BootProtocolError::UnsupportedRevision {
+			revision: REVISION,
+		};
+		assert_eq!(unsafe { parse() }.unwrap_err().desc, Some(revision));
+	}
+
+	#[test]
+	fn test_missing_memory_map() {
+		let _lock = reset(0,);
+		let missing = BootProtocolError::MissingResponse("memory map",);
+		assert_eq!(unsafe { parse() }.unwrap_err().desc, Some(missing));
+	}
+
+	#[test]
+	fn test_empty_responses() {
+		let _lock = reset(0,);
+		// responses without entries, and without the kernel file
+		let empty = MemmapResponse {
+			revision:    0,
+			entry_count: 4,
+			entries:     core::ptr::null(),
+		};
+		answer(&MEMMAP, Some(empty,),);
+		answer(&FRAMEBUFFER, Some(FramebufferResponse {
+			revision: 0,
+			framebuffer_count: 0,
+			framebuffers: pointers::<Framebuffer,>(Vec::new(),),
+		},),);
+		answer(&MODULES, Some(ModuleResponse {
+			revision: 0,
+			module_count: 2,
+			modules: core::ptr::null(),
+		},),);
+		answer(&EXECUTABLE_FILE, Some(ExecutableFileResponse {
+			revision: 0,
+			file:     core::ptr::null(),
+		},),);
+		let info = unsafe { parse() }.unwrap();
+		assert!(unsafe { info.memory_map.as_slice() }.is_empty());
+		assert!(unsafe { info.modules.as_slice() }.is_empty());
+		assert!(info.framebuffer.is_null());
+		assert!(info.cmdline.ptr.is_null());
+		assert!(info.device_tree.is_null());
+	}
+}
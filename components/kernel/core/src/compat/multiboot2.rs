@@ -0,0 +1,469 @@
+//! # Multiboot2
+//!
+//! Header and boot information parser of the Multiboot2 specification, as
+//! implemented by GRUB.
+//!
+//! Multiboot2 only defines machine state for i386 and EFI amd64. oso_kernel is
+//! a 64-bit kernel, so the header asks for the EFI amd64 entry: the boot loader
+//! jumps to [`entry`] in long mode with `eax` holding [`BOOTLOADER_MAGIC`] and
+//! `rbx` the boot information, which calls `kernel_main_multiboot2`. Boot
+//! services are still running then. The kernel does not call them and runs
+//! with interrupts disabled, so the firmware stays idle.
+//!
+//! GRUB searches the first 32 KiB of the image for the header. It is placed
+//! in its own `.multiboot2` section of type note, which lld puts right after
+//! the program headers, before any other loadable section. `cargo xtask
+//! build` checks that the linked kernel has it there.
+
+use super::Builder;
+use super::pixel_format;
+use oso_error::Rslt;
+use oso_error::kernel::BootProtocolError;
+use oso_error::oso_err;
+use oso_no_std_shared::bridge::boot_info::BootInfo;
+use oso_no_std_shared::bridge::boot_info::CommandLine;
+use oso_no_std_shared::bridge::boot_info::MemoryRegion;
+use oso_no_std_shared::bridge::boot_info::MemoryRegionKind;
+use oso_no_std_shared::bridge::graphic::FrameBufConf;
+use oso_no_std_shared::units::ByteSize;
+
+/// value of `eax` at entry when started by a Multiboot2 boot loader
+pub const BOOTLOADER_MAGIC: u32 = 0x36d7_6289;
+#[cfg(all(target_arch = "x86_64", not(feature = "hosted")))]
+const HEADER_MAGIC: u32 = 0xe852_50d6;
+/// protected mode i386, the only architecture GRUB accepts on x86
+#[cfg(all(target_arch = "x86_64", not(feature = "hosted")))]
+const ARCHITECTURE_I386: u32 = 0;
+/// fixed part, framebuffer, EFI boot services, EFI amd64 entry and end tags,
+/// each padded to 8 bytes
+#[cfg(all(target_arch = "x86_64", not(feature = "hosted")))]
+const HEADER_LENGTH: u32 = 16 + 24 + 8 + 16 + 8;
+#[cfg(all(target_arch = "x86_64", not(feature = "hosted")))]
+const HEADER_CHECKSUM: u32 =
+	0u32.wrapping_sub(HEADER_MAGIC + ARCHITECTURE_I386 + HEADER_LENGTH,);
+
+mod tag {
+	pub const END: u32 = 0;
+	pub const CMDLINE: u32 = 1;
+	pub const MODULE: u32 = 3;
+	pub const MMAP: u32 = 6;
+	pub const FRAMEBUFFER: u32 = 8;
+	pub const EFI_MMAP: u32 = 17;
+}
+
+// Tags are `type: u16, flags: u16, size: u32` followed by their fields. Flag
+// `1` marks the framebuffer request optional. Without a linker script, lld
+// orders sections by kind, and notes come first. Notes are also kept by
+// `--gc-sections`. Hosted builds are not booted, and link position
+// independent
+#[cfg(all(target_arch = "x86_64", not(feature = "hosted")))]
+core::arch::global_asm!(
+	".pushsection .multiboot2, \"a\", @note",
+	".balign 8",
+	".long {magic}, {arch}, {length}, {checksum}",
+	// framebuffer: any resolution, 32 bits per pixel
+	".short 5, 1",
+	".long 20, 0, 0, 32",
+	".balign 8",
+	// keep boot services, required by the EFI amd64 entry
+	".short 7, 0",
+	".long 8",
+	// EFI amd64 entry address
+	".short 9, 0",
+	".long 12, {entry}",
+	".balign 8",
+	".short 0, 0",
+	".long 8",
+	".popsection",
+	magic = const HEADER_MAGIC,
+	arch = const ARCHITECTURE_I386,
+	length = const HEADER_LENGTH,
+	checksum = const HEADER_CHECKSUM,
+	entry = sym entry,
+);
+
+#[cfg(target_arch = "x86_64")]
+unsafe extern "sysv64" {
+	/// defined by the kernel binary
+	fn kernel_main_multiboot2(magic: u32, info: *const u8,) -> !;
+}
+
+/// Moves the registers set by the boot loader into arguments of
+/// `kernel_main_multiboot2`
+///
+/// # Safety
+///
+/// Only the boot loader jumps here
+#[cfg(target_arch = "x86_64")]
+#[unsafe(naked)]
+pub unsafe extern "sysv64" fn entry() -> ! {
+	core::arch::naked_asm!(
+		"mov edi, eax",
+		"mov rsi, rbx",
+		"and rsp, -16",
+		"call {main}",
+		"ud2",
+		main = sym kernel_main_multiboot2,
+	)
+}
+
+/// Translates the Multiboot2 boot information at `info` into [`BootInfo`]
+///
+/// Memory regions come from the EFI memory map if present, as it tells
+/// runtime services and loaded files apart, and from the Multiboot2 memory map
+/// otherwise. The latter reports the kernel and modules as available memory.
+///
+/// # Errors
+///
+/// - [`BootProtocolError::BadMagic`] if `magic` is not [`BOOTLOADER_MAGIC`]
+/// - [`BootProtocolError::Malformed`] if a tag overruns the information or
+///   is too short for its fields
+/// - Errors of [`Builder`]
+///
+/// # Safety
+///
+/// `info` must point to boot information of `total_size` bytes which is not
+/// reused while the kernel reads [`BootInfo`]
+pub unsafe fn parse(
+	magic: u32,
+	info: *const u8,
+) -> Rslt<&'static BootInfo, BootProtocolError,> {
+	if magic != BOOTLOADER_MAGIC {
+		return Err(oso_err!(BootProtocolError::BadMagic {
+			found: magic as u64
+		}),);
+	}
+	let total_size = unsafe { read_u32(info, 0,) } as usize;
+	let info = unsafe { core::slice::from_raw_parts(info, total_size,) };
+	let has_efi_mmap = tags(info,).any(|tag| {
+		tag.map(|(ty, _,)| ty == tag::EFI_MMAP,).unwrap_or(false,)
+	},);
+
+	let mut builder = Builder::new()?;
+	for tag in tags(info,) {
+		let (ty, body,) = tag?;
+		match ty {
+			tag::CMDLINE => builder.cmdline(string(body,)?,),
+			tag::MODULE => {
+				let start = u32_at(body, 0,)? as u64;
+				let end = u32_at(body, 4,)? as u64;
+				let cmdline = string(entries(body,)?,)?;
+				builder.module(start, end.saturating_sub(start,), cmdline,)?;
+			},
+			tag::MMAP if !has_efi_mmap => mmap(&mut builder, body,)?,
+			tag::EFI_MMAP => efi_mmap(&mut builder, body,)?,
+			tag::FRAMEBUFFER => {
+				if let Some(framebuffer,) = framebuffer(body,)? {
+					builder.framebuffer(framebuffer,);
+				}
+			},
+			_ => {},
+		}
+	}
+	Ok(builder.finish(),)
+}
+
+/// `(type, body)` of each tag up to the end tag. Ends after the first error
+fn tags(
+	info: &[u8],
+) -> impl Iterator<Item = Rslt<(u32, &[u8],), BootProtocolError,>,> {
+	let mut offset = Some(8,);
+	core::iter::from_fn(move || {
+		let current = offset.take()?;
+		let tag = (|| {
+			let ty = u32_at(info, current,)?;
+			let size = u32_at(info, current + 4,)? as usize;
+			if ty == tag::END {
+				return Ok(None,);
+			}
+			let body = if size < 8 {
+				None
+			} else {
+				info.get(current + 8..current + size,)
+			};
+			let Some(body,) = body else {
+				return Err(oso_err!(BootProtocolError::Malformed),);
+			};
+			offset = Some(current + size.next_multiple_of(8,),);
+			Ok(Some((ty, body,),),)
+		})();
+		tag.transpose()
+	},)
+}
+
+fn mmap(builder: &mut Builder, body: &[u8],) -> Rslt<(), BootProtocolError,> {
+	const AVAILABLE: u32 = 1;
+	const ACPI_RECLAIMABLE: u32 = 3;
+	const ACPI_NVS: u32 = 4;
+
+	let entry_size = u32_at(body, 0,)? as usize;
+	if entry_size < 24 {
+		return Err(oso_err!(BootProtocolError::Malformed),);
+	}
+	for entry in entries(body,)?.chunks_exact(entry_size,) {
+		let kind = match u32_at(entry, 16,)? {
+			AVAILABLE => MemoryRegionKind::Usable,
+			ACPI_RECLAIMABLE => MemoryRegionKind::AcpiReclaim,
+			ACPI_NVS => MemoryRegionKind::AcpiNvs,
+			_ => MemoryRegionKind::Reserved,
+		};
+		builder.region(u64_at(entry, 0,)?, u64_at(entry, 8,)?, kind,)?;
+	}
+	Ok((),)
+}
+
+/// EFI memory map as returned by `GetMemoryMap`. Boot services are still
+/// running, so their memory is only reclaimable
+fn efi_mmap(
+	builder: &mut Builder,
+	body: &[u8],
+) -> Rslt<(), BootProtocolError,> {
+	let desc_size = u32_at(body, 0,)? as usize;
+	if desc_size < 32 {
+		return Err(oso_err!(BootProtocolError::Malformed),);
+	}
+	for desc in entries(body,)?.chunks_exact(desc_size,) {
+		let kind = match u32_at(desc, 0,)? {
+			// the kernel and modules are loader data
+			1 | 2 => MemoryRegionKind::Loader,
+			3 | 4 => MemoryRegionKind::Reclaimable,
+			5 => MemoryRegionKind::RuntimeCode,
+			6 => MemoryRegionKind::RuntimeData,
+			7 => MemoryRegionKind::Usable,
+			9 => MemoryRegionKind::AcpiReclaim,
+			10 => MemoryRegionKind::AcpiNvs,
+			11 | 12 => MemoryRegionKind::Mmio,
+			_ => MemoryRegionKind::Reserved,
+		};
+		let pages = u64_at(desc, 24,)?;
+		// a size past the address space is cut by `region`
+		let size = ByteSize::from_pages(pages, MemoryRegion::PAGE_SIZE,);
+		let size = size.map_or(u64::MAX, |size| size.bytes(),);
+		builder.region(u64_at(desc, 8,)?, size, kind,)?;
+	}
+	Ok((),)
+}
+
+/// direct color framebuffers only. indexed and text modes are ignored
+fn framebuffer(
+	body: &[u8],
+) -> Rslt<Option<FrameBufConf,>, BootProtocolError,> {
+	const DIRECT_RGB: u8 = 1;
+
+	let addr = u64_at(body, 0,)?;
+	let pitch = u32_at(body, 8,)? as usize;
+	let width = u32_at(body, 12,)? as usize;
+	let height = u32_at(body, 16,)? as usize;
+	let bpp = *body.get(20,).ok_or(oso_err!(BootProtocolError::Malformed),)?;
+	let ty = *body.get(21,).ok_or(oso_err!(BootProtocolError::Malformed),)?;
+	if ty != DIRECT_RGB {
+		return Ok(None,);
+	}
+	// red position, red size, green position, ...
+	let Some(&[red, _, green, _, blue, _,],) = body.get(24..30,) else {
+		return Err(oso_err!(BootProtocolError::Malformed),);
+	};
+
+	Ok(Some(FrameBufConf {
+		pixel_format: pixel_format(bpp as u16, red, green, blue,),
+		base: addr as *mut u8,
+		size: pitch * height,
+		width,
+		height,
+		stride: pitch,
+	},),)
+}
+
+/// NUL terminated string at the start of `bytes`, excluding the NUL
+fn string(bytes: &[u8],) -> Rslt<CommandLine, BootProtocolError,> {
+	let len = bytes.iter().position(|&b| b == 0,);
+	let len = len.ok_or(oso_err!(BootProtocolError::Malformed),)?;
+	Ok(CommandLine { ptr: bytes.as_ptr(), len, },)
+}
+
+/// Body after the two leading words of module and memory map tags
+fn entries(body: &[u8],) -> Rslt<&[u8], BootProtocolError,> {
+	body.get(8..,).ok_or(oso_err!(BootProtocolError::Malformed),)
+}
+
+fn u32_at(bytes: &[u8], offset: usize,) -> Rslt<u32, BootProtocolError,> {
+	match bytes.get(offset..offset + 4,) {
+		Some(b,) => Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3],],),),
+		None => Err(oso_err!(BootProtocolError::Malformed),),
+	}
+}
+
+fn u64_at(bytes: &[u8], offset: usize,) -> Rslt<u64, BootProtocolError,> {
+	let low = u32_at(bytes, offset,)? as u64;
+	let high = u32_at(bytes, offset + 4,)? as u64;
+	Ok(low | high << 32,)
+}
+
+unsafe fn read_u32(ptr: *const u8, offset: usize,) -> u32 {
+	unsafe { ptr.add(offset,).cast::<u32>().read_unaligned() }
+}
+
+#[cfg(test)]
+mod tests {
+	extern crate std;
+
+	use super::*;
+	use crate::compat::tests::lock;
+	use oso_no_std_shared::bridge::graphic::PixelFormatConf;
+	use std::vec;
+	use std::vec::Vec;
+
+	/// Boot information of `tags` followed by the end tag
+	fn info(tags: &[(u32, Vec<u8,>,)],) -> &'static [u8] {
+		let mut info = vec![0; 8];
+		for (ty, body,) in tags {
+			info.extend_from_slice(&ty.to_le_bytes(),);
+			info.extend_from_slice(&(8 + body.len() as u32).to_le_bytes(),);
+			info.extend_from_slice(body,);
+			info.resize(info.len().next_multiple_of(8,), 0,);
+		}
+		info.extend_from_slice(&[0, 0, 0, 0, 8, 0, 0, 0,],);
+		let total_size = info.len() as u32;
+		info[..4].copy_from_slice(&total_size.to_le_bytes(),);
+		info.leak()
+	}
+
+	fn words(words: &[u32],) -> Vec<u8,> {
+		words.iter().flat_map(|word| word.to_le_bytes(),).collect()
+	}
+
+	fn parse(info: &[u8],) -> Rslt<&'static BootInfo, BootProtocolError,> {
+		unsafe { super::parse(BOOTLOADER_MAGIC, info.as_ptr(),) }
+	}
+
+	/// Error of parsing `info` with `magic`, in an empty storage
+	fn error(magic: u32, info: &[u8],) -> Option<BootProtocolError,> {
+		let _lock = lock();
+		unsafe { super::parse(magic, info.as_ptr(),) }.err()?.desc
+	}
+
+	fn module() -> Vec<u8,> {
+		let mut module = words(&[0x20_0000, 0x20_1800,],);
+		module.extend_from_slice(b"initrd\0",);
+		module
+	}
+
+	/// Memory map of `(address, size, type)` entries
+	fn mmap(entries: &[(u32, u32, u32,)],) -> Vec<u8,> {
+		let mut mmap = words(&[24, 0,],);
+		for &(address, size, ty,) in entries {
+			mmap.extend(words(&[address, 0, size, 0, ty, 0,],),);
+		}
+		mmap
+	}
+
+	/// EFI memory map of `(type, address, pages)` descriptors, padded to 48
+	/// bytes like those of most firmware
+	fn efi_mmap(descs: &[(u32, u32, u32,)],) -> Vec<u8,> {
+		let mut mmap = words(&[48, 1,],);
+		for &(ty, address, pages,) in descs {
+			let desc = [ty, 0, address, 0, 0, 0, pages, 0, 0, 0, 0, 0,];
+			mmap.extend(words(&desc,),);
+		}
+		mmap
+	}
+
+	#[test]
+	fn test_parse() {
+		let _lock = lock();
+		// available 1 MiB at 1 MiB, then reserved 8 KiB
+		let mmap =
+			mmap(&[(0x10_0000, 0x10_0000, 1,), (0xfe00_0000, 0x2000, 2,),],);
+		let mut framebuffer = words(&[0x8000_0000, 0, 4096, 1024, 768,],);
+		// 32 bits per pixel, direct color, red at 16, green at 8, blue at 0
+		framebuffer.extend_from_slice(&[32, 1, 0, 0, 16, 8, 8, 8, 0, 8,],);
+		let info = info(&[
+			(tag::CMDLINE, b"console=serial\0".to_vec(),),
+			(tag::MODULE, module(),),
+			(tag::MMAP, mmap,),
+			(tag::FRAMEBUFFER, framebuffer,),
+			// boot loader name, which is skipped
+			(2, b"GRUB 2.12\0".to_vec(),),
+		],);
+
+		let boot_info = parse(info,).unwrap();
+		assert_eq!(unsafe { boot_info.cmdline.as_str() }, "console=serial");
+		let modules = unsafe { boot_info.modules.as_slice() };
+		assert_eq!(modules.len(), 1);
+		assert_eq!((modules[0].start, modules[0].size,), (0x20_0000, 0x1800));
+		assert_eq!(unsafe { modules[0].cmdline.as_str() }, "initrd");
+		let regions = unsafe { boot_info.memory_map.as_slice() };
+		let usable = MemoryRegionKind::Usable;
+		let reserved = MemoryRegionKind::Reserved;
+		let expected = [
+			MemoryRegion::new(0x10_0000, 0x10_0000, 256, usable, 0,),
+			MemoryRegion::new(0xfe00_0000, 0xfe00_0000, 2, reserved, 0,),
+		];
+		assert_eq!(regions, expected);
+		let framebuffer = unsafe { &*boot_info.framebuffer };
+		assert_eq!(framebuffer.pixel_format, PixelFormatConf::Bgr);
+		assert_eq!(framebuffer.base as usize, 0x8000_0000);
+		let size = (framebuffer.width, framebuffer.height, framebuffer.stride,);
+		assert_eq!(size, (1024, 768, 4096));
+		assert_eq!(framebuffer.size, 4096 * 768);
+	}
+
+	#[test]
+	fn test_efi_mmap_replaces_mmap() {
+		let _lock = lock();
+		let mmap = mmap(&[(0, 0x10_0000, 1,),],);
+		// loader data of 2 pages, then conventional memory of 16
+		let efi_mmap = efi_mmap(&[(2, 0x10_0000, 2,), (7, 0x20_0000, 16,),],);
+		let info = info(&[(tag::MMAP, mmap,), (tag::EFI_MMAP, efi_mmap,),],);
+
+		let boot_info = parse(info,).unwrap();
+		let regions = unsafe { boot_info.memory_map.as_slice() };
+		let loader = MemoryRegionKind::Loader;
+		let usable = MemoryRegionKind::Usable;
+		let expected = [
+			MemoryRegion::new(0x10_0000, 0x10_0000, 2, loader, 0,),
+			MemoryRegion::new(0x20_0000, 0x20_0000, 16, usable, 0,),
+		];
+		assert_eq!(regions, expected);
+		assert!(boot_info.cmdline.ptr.is_null());
+	}
+
+	#[test]
+	fn test_malformed_information() {
+		let bad_magic = BootProtocolError::BadMagic { found: 0x2bad_b002, };
+		assert_eq!(error(0x2bad_b002, info(&[]),), Some(bad_magic));
+
+		let malformed = Some(BootProtocolError::Malformed,);
+		let mut truncated = module();
+		truncated.truncate(8,);
+		let unterminated = words(&[0x20_0000, 0x20_1800, 0x6472_7469,],);
+		let mut short_mmap = efi_mmap(&[(7, 0x20_0000, 16,),],);
+		short_mmap.truncate(6,);
+		// tags cut short of their fields, and strings without their NUL
+		for (ty, body,) in [
+			(tag::MODULE, truncated,),
+			(tag::MODULE, unterminated,),
+			(tag::MMAP, words(&[24,],),),
+			(tag::MMAP, words(&[16, 0,],),),
+			(tag::EFI_MMAP, short_mmap,),
+			(tag::EFI_MMAP, words(&[32,],),),
+			(tag::CMDLINE, b"console".to_vec(),),
+			(tag::FRAMEBUFFER, words(&[0x8000_0000, 0, 4096,],),),
+		] {
+			let info = info(&[(ty, body,),],);
+			assert_eq!(error(BOOTLOADER_MAGIC, info,), malformed);
+		}
+
+		// tags which overrun the information or are smaller than their header
+		let cmdline = (tag::CMDLINE, b"console\0".to_vec(),);
+		let mut overrun = info(&[cmdline],).to_vec();
+		overrun[12..16].copy_from_slice(&64u32.to_le_bytes(),);
+		assert_eq!(error(BOOTLOADER_MAGIC, &overrun,), malformed);
+		overrun[12..16].copy_from_slice(&4u32.to_le_bytes(),);
+		assert_eq!(error(BOOTLOADER_MAGIC, &overrun,), malformed);
+		// the end tag is cut after its type
+		let mut cut = info(&[],).to_vec();
+		cut[..4].copy_from_slice(&12u32.to_le_bytes(),);
+		assert_eq!(error(BOOTLOADER_MAGIC, &cut,), malformed);
+	}
+}
@@ -26,6 +26,22 @@
 //! - PCI configuration space access
 //! - PCI device initialization and management
 //!
+//! ### Storage
+//! - SD host controllers, such as EMMC2 of the Raspberry Pi 4
+//!
+//! ### GPIO Controllers
+//! - Arm PL061 on QEMU `virt`
+//! - BCM2711 GPIO on the Raspberry Pi 4
+//!
+//! ### Watchdog Timers
+//! - Arm SP805 and SBSA generic watchdog
+//! - Intel 6300ESB emulated by QEMU on x86_64
+//!
+//! ### QEMU Exit Devices
+//! - Arm semihosting on AArch64
+//! - `isa-debug-exit` on x86_64
+//! - `sifive_test` on RISC-V
+//!
 //! ### USB Devices
 //! - USB host controller drivers
 //! - USB device enumeration and management
@@ -33,8 +49,14 @@
 //!
 //! ## Modules
 //!
+//! - [`block`]: Interface of block devices
+//! - [`dma`]: DMA buffer pool shared by device drivers
+//! - [`gpio`]: GPIO pins and the heartbeat LED
 //! - [`pci`]: PCI bus and device driver implementation
+//! - [`qemu_exit`]: Exit devices of QEMU which end a test run
+//! - [`sdhci`]: SD card behind an SD host controller
 //! - [`usb`]: USB host controller and device drivers
+//! - [`watchdog`]: Watchdog timers which reset the machine on a hang
 //!
 //! ## Usage
 //!
@@ -63,6 +85,30 @@
 //! 3. **Safety**: All hardware access is memory-safe and validated
 //! 4. **Performance**: Minimal overhead for critical operations
 
+/// Block device interface
+///
+/// This module defines the trait storage drivers implement to be read and
+/// written block by block.
+pub mod block;
+
+/// DMA buffer pool shared by device drivers
+///
+/// This module provides physically contiguous buffers with ownership tracking
+/// and the cache maintenance needed to hand them to devices.
+pub mod dma;
+
+/// GPIO drivers
+///
+/// This module drives GPIO pins of PL061 and BCM2711 controllers and blinks a
+/// heartbeat LED from the timer tick.
+pub mod gpio;
+
+/// Memory-mapped I/O
+///
+/// This module accesses device registers for the drivers, and injects
+/// faults into chosen accesses to test their error paths.
+pub mod mmio;
+
 /// PCI bus and device driver implementation
 ///
 /// This module provides PCI (Peripheral Component Interconnect) bus support,
@@ -70,8 +116,26 @@
 /// management.
 pub mod pci;
 
+/// QEMU exit devices
+///
+/// This module ends QEMU with an exit status, so test runs report their
+/// result through the status of the QEMU process.
+pub mod qemu_exit;
+
+/// SD host controller driver
+///
+/// This module initializes the SD card of the Raspberry Pi 4 and reads and
+/// writes it as a block device.
+pub mod sdhci;
+
 /// USB host controller and device drivers
 ///
 /// This module implements USB (Universal Serial Bus) support, including host
 /// controller drivers, device enumeration, and USB protocol handling.
 pub mod usb;
+
+/// Watchdog timer drivers
+///
+/// This module resets the machine when the kernel stops patting the
+/// watchdog, and provides the `watchdog` shell command.
+pub mod watchdog;
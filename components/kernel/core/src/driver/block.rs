@@ -0,0 +1,151 @@
+//! # Block Devices
+//!
+//! [`BlockDevice`] is the interface between storage drivers and anything
+//! which reads or writes whole blocks, such as a file system.
+//!
+//! ## Devices
+//!
+//! - [`Sdhci`](super::sdhci::Sdhci): SD card behind an SD host controller
+//!
+//! ## Caching
+//!
+//! [`cache::BlockCache`] wraps any device in an LRU cache with read-ahead.
+//!
+//! ## File Systems
+//!
+//! [`crate::vfs::fat32`] reads FAT32 volumes from a device, and
+//! [`crate::vfs::devfs`] exposes devices as files under `/dev`.
+//!
+//! ## Current Status
+//!
+//! Devices are addressed by logical block address:
+//!
+//! ```rust,ignore
+//! let mut card = unsafe { Sdhci::new(Sdhci::PI4_BASE, Sdhci::PI4_CLOCK_HZ,)? };
+//! let mut mbr = [0u8; 512];
+//! card.read_blocks(0, &mut mbr,)?;
+//! ```
+
+use oso_error::Rslt;
+use oso_error::kernel::BlockError;
+use oso_error::oso_err;
+
+/// LRU cache of blocks with read-ahead
+pub mod cache;
+
+/// Common interface of block devices
+pub trait BlockDevice {
+	/// Size of a block in bytes
+	fn block_size(&self,) -> usize;
+
+	/// Number of blocks of the device
+	fn block_count(&self,) -> u64;
+
+	/// Reads `buf.len() / block_size()` blocks starting at `lba` into `buf`
+	fn read_blocks(&mut self, lba: u64, buf: &mut [u8],)
+	-> Rslt<(), BlockError,>;
+
+	/// Writes `buf.len() / block_size()` blocks starting at `lba`
+	fn write_blocks(&mut self, lba: u64, buf: &[u8],) -> Rslt<(), BlockError,>;
+
+	/// Makes writes reach the medium. Devices without a write cache have
+	/// nothing to do
+	fn flush(&mut self,) -> Rslt<(), BlockError,> {
+		Ok((),)
+	}
+
+	/// Number of blocks of a request of `len` bytes at `lba`
+	///
+	/// # Errors
+	///
+	/// - [`BlockError::Misaligned`] if `len` is not a multiple of
+	///   [`block_size`](Self::block_size)
+	/// - [`BlockError::OutOfRange`] if the request reaches past the last block
+	fn check_request(&self, lba: u64, len: usize,) -> Rslt<u64, BlockError,> {
+		if !len.is_multiple_of(self.block_size(),) {
+			return Err(oso_err!(BlockError::Misaligned { len }),);
+		}
+		let count = (len / self.block_size()) as u64;
+		if lba.checked_add(count,).is_none_or(|end| end > self.block_count(),) {
+			return Err(oso_err!(BlockError::OutOfRange { lba, count }),);
+		}
+		Ok(count,)
+	}
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	extern crate std;
+	use std::vec;
+	use std::vec::Vec;
+
+	/// disk in memory, for tests of the users of block devices
+	pub(crate) struct RamDisk {
+		pub(crate) data:       Vec<u8,>,
+		pub(crate) block_size: usize,
+		/// `read_blocks` calls as first block and count
+		pub(crate) reads:      Vec<(u64, u64,),>,
+		/// block whose reads fail
+		pub(crate) broken:     Option<u64,>,
+	}
+
+	impl RamDisk {
+		pub(crate) fn new(data: Vec<u8,>, block_size: usize,) -> Self {
+			Self { data, block_size, reads: vec![], broken: None, }
+		}
+	}
+
+	impl BlockDevice for RamDisk {
+		fn block_size(&self,) -> usize {
+			self.block_size
+		}
+
+		fn block_count(&self,) -> u64 {
+			(self.data.len() / self.block_size) as u64
+		}
+
+		fn read_blocks(
+			&mut self,
+			lba: u64,
+			buf: &mut [u8],
+		) -> Rslt<(), BlockError,> {
+			let count = self.check_request(lba, buf.len(),)?;
+			let blocks = lba..lba + count;
+			if self.broken.is_some_and(|broken| blocks.contains(&broken,),) {
+				return Err(oso_err!(BlockError::Timeout),);
+			}
+			self.reads.push((lba, count,),);
+			let start = lba as usize * self.block_size;
+			buf.copy_from_slice(&self.data[start..start + buf.len()],);
+			Ok((),)
+		}
+
+		fn write_blocks(
+			&mut self,
+			lba: u64,
+			buf: &[u8],
+		) -> Rslt<(), BlockError,> {
+			self.check_request(lba, buf.len(),)?;
+			let start = lba as usize * self.block_size;
+			self.data[start..start + buf.len()].copy_from_slice(buf,);
+			Ok((),)
+		}
+	}
+
+	#[test]
+	fn test_check_request() {
+		let disk = RamDisk::new(vec![0; 4 * 512], 512,);
+		assert_eq!(disk.check_request(0, 0,).unwrap(), 0);
+		assert_eq!(disk.check_request(1, 3 * 512,).unwrap(), 3);
+		assert_eq!(disk.check_request(3, 512,).unwrap(), 1);
+		assert_eq!(disk.check_request(4, 0,).unwrap(), 0);
+		let error = |lba, len| disk.check_request(lba, len,).unwrap_err().desc;
+		assert_eq!(error(0, 511), Some(BlockError::Misaligned { len: 511 }));
+		let out_of_range =
+			|lba, count| Some(BlockError::OutOfRange { lba, count },);
+		assert_eq!(error(2, 3 * 512), out_of_range(2, 3));
+		assert_eq!(error(4, 512), out_of_range(4, 1));
+		assert_eq!(error(u64::MAX, 512), out_of_range(u64::MAX, 1));
+	}
+}
@@ -0,0 +1,490 @@
+//! # Block Cache
+//!
+//! [`BlockCache`] sits between a [`BlockDevice`] and its users and is a
+//! [`BlockDevice`] itself, so a file system does not care whether it reads
+//! through the cache.
+//!
+//! ## Lines
+//!
+//! The cache holds `N` lines of one frame each, taken from a [`FrameSource`]
+//! when the cache is created. A line holds the consecutive blocks starting at
+//! a multiple of its capacity. When every line is in use, the least recently
+//! used one is replaced.
+//!
+//! ## Read-ahead
+//!
+//! A read which starts where the previous one ended is sequential, as when a
+//! file or a directory is scanned. If it misses, the following lines are
+//! loaded as well, so the next reads hit.
+//!
+//! ## Writes
+//!
+//! Writes go through to the device before they return, and update lines which
+//! hold the written blocks. [`BlockDevice::flush`] is passed on to the device.
+//!
+//! ## Current Status
+//!
+//! The kernel has no frame allocator yet, so the cache takes any
+//! [`FrameSource`]. Memory is identity mapped, so frames are accessed at their
+//! physical address.
+//!
+//! ```rust,ignore
+//! let mut disk = BlockCache::<_, _, 32,>::new(card, frames, 4,)?;
+//! disk.read_blocks(lba, &mut sector,)?;
+//! let stats = disk.stats();
+//! ```
+
+use super::BlockDevice;
+use crate::driver::dma::FRAME_SIZE;
+use crate::driver::dma::FrameSource;
+use oso_error::Rslt;
+use oso_error::kernel::BlockError;
+use oso_error::oso_err;
+
+/// Counts of lookups in a [`BlockCache`]
+///
+/// # Fields
+///
+/// * `hits` - Reads served from a line without the device
+/// * `misses` - Reads which loaded their line from the device
+/// * `prefetched` - Lines loaded by read-ahead
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default,)]
+pub struct CacheStats {
+	pub hits:       usize,
+	pub misses:     usize,
+	pub prefetched: usize,
+}
+
+/// LRU cache of `N` frame sized lines in front of a [`BlockDevice`]
+pub struct BlockCache<D: BlockDevice, F: FrameSource, const N: usize,> {
+	device:     D,
+	source:     F,
+	lines:      [Line; N],
+	/// lines loaded after a sequential miss
+	read_ahead: usize,
+	/// block after the last read
+	next_lba:   Option<u64,>,
+	/// incremented on every access, orders lines by their last use
+	clock:      u64,
+	stats:      CacheStats,
+}
+
+#[derive(Clone, Copy,)]
+struct Line {
+	/// address of the frame
+	addr:  usize,
+	first: u64,
+	/// number of valid blocks. `0` marks an empty line
+	len:   usize,
+	used:  u64,
+}
+
+impl Line {
+	const EMPTY: Self = Self { addr: 0, first: 0, len: 0, used: 0, };
+
+	fn holds(&self, first: u64,) -> bool {
+		self.len != 0 && self.first == first
+	}
+}
+
+impl<D: BlockDevice, F: FrameSource, const N: usize,> BlockCache<D, F, N,> {
+	/// Takes `N` frames from `source` for lines in front of `device`
+	///
+	/// `read_ahead` is limited to `N - 1` lines, so read-ahead never replaces
+	/// the line it started from.
+	///
+	/// # Errors
+	///
+	/// - [`BlockError::Unsupported`] if `N` is `0` or the block size of
+	///   `device` does not divide [`FRAME_SIZE`]
+	/// - [`BlockError::OutOfFrames`] if `source` has fewer than `N` frames
+	pub fn new(
+		device: D,
+		mut source: F,
+		read_ahead: usize,
+	) -> Rslt<Self, BlockError,> {
+		let block_size = device.block_size();
+		if N == 0 || block_size == 0 || !FRAME_SIZE.is_multiple_of(block_size,)
+		{
+			return Err(oso_err!(BlockError::Unsupported),);
+		}
+
+		let mut lines = [Line::EMPTY; N];
+		for i in 0..N {
+			let Some(addr,) = source.alloc_frames(1, FRAME_SIZE,) else {
+				for line in &lines[..i] {
+					source.free_frames(line.addr, 1,);
+				}
+				return Err(oso_err!(BlockError::OutOfFrames),);
+			};
+			lines[i].addr = addr;
+		}
+		Ok(Self {
+			device,
+			source,
+			lines,
+			read_ahead: read_ahead.min(N.saturating_sub(1,),),
+			next_lba: None,
+			clock: 0,
+			stats: CacheStats::default(),
+		},)
+	}
+
+	pub fn stats(&self,) -> CacheStats {
+		self.stats
+	}
+
+	pub fn device(&self,) -> &D {
+		&self.device
+	}
+
+	/// Empties every line, e.g. after the medium was changed behind the
+	/// cache
+	pub fn invalidate(&mut self,) {
+		for line in &mut self.lines {
+			line.len = 0;
+		}
+		self.next_lba = None;
+	}
+
+	/// Blocks a line holds
+	fn line_blocks(&self,) -> u64 {
+		(FRAME_SIZE / self.device.block_size()) as u64
+	}
+
+	/// Index of the line holding the blocks from `first`, loading them on a
+	/// miss. The flag tells if it missed
+	fn line(&mut self, first: u64,) -> Rslt<(usize, bool,), BlockError,> {
+		self.clock += 1;
+		if let Some(index,) = self.lines.iter().position(|l| l.holds(first,),) {
+			self.lines[index].used = self.clock;
+			return Ok((index, false,),);
+		}
+
+		let index = self
+			.lines
+			.iter()
+			.enumerate()
+			.min_by_key(|(_, line,)| if line.len == 0 { 0 } else { line.used },)
+			.map(|(index, _,)| index,)
+			.unwrap_or_default();
+		let len = self.line_blocks().min(self.device.block_count() - first,);
+		let bytes = len as usize * self.device.block_size();
+		let line = &mut self.lines[index];
+		// the content is undefined until the read succeeds
+		line.len = 0;
+		let data = unsafe { frame(line.addr,) };
+		self.device.read_blocks(first, &mut data[..bytes],)?;
+		line.first = first;
+		line.len = len as usize;
+		line.used = self.clock;
+		Ok((index, true,),)
+	}
+
+	/// Loads the lines following the one from `first`
+	fn prefetch(&mut self, first: u64,) -> Rslt<(), BlockError,> {
+		let line_blocks = self.line_blocks();
+		for i in 1..=self.read_ahead as u64 {
+			let first = first + i * line_blocks;
+			if first >= self.device.block_count() {
+				break;
+			}
+			if self.line(first,)?.1 {
+				self.stats.prefetched += 1;
+			}
+		}
+		Ok((),)
+	}
+}
+
+impl<D: BlockDevice, F: FrameSource, const N: usize,> BlockDevice
+	for BlockCache<D, F, N,>
+{
+	fn block_size(&self,) -> usize {
+		self.device.block_size()
+	}
+
+	fn block_count(&self,) -> u64 {
+		self.device.block_count()
+	}
+
+	fn read_blocks(
+		&mut self,
+		lba: u64,
+		buf: &mut [u8],
+	) -> Rslt<(), BlockError,> {
+		let count = self.check_request(lba, buf.len(),)?;
+		let sequential = self.next_lba == Some(lba,);
+		self.next_lba = None;
+		let block_size = self.block_size();
+		let line_blocks = self.line_blocks();
+
+		let mut done = 0;
+		while done < count {
+			let current = lba + done;
+			let first = current - current % line_blocks;
+			let (index, missed,) = self.line(first,)?;
+			let line = self.lines[index];
+			let offset = (current - first) as usize;
+			let blocks = (line.len - offset).min((count - done) as usize,);
+
+			let data = unsafe { frame(line.addr,) };
+			let src = offset * block_size..(offset + blocks) * block_size;
+			let src = &data[src];
+			let start = done as usize * block_size;
+			buf[start..start + src.len()].copy_from_slice(src,);
+			done += blocks as u64;
+
+			if missed {
+				self.stats.misses += 1;
+				// the read itself succeeded, so a failed read-ahead is
+				// left for the read which needs the line
+				if sequential {
+					let _ = self.prefetch(first,);
+				}
+			} else {
+				self.stats.hits += 1;
+			}
+		}
+		self.next_lba = Some(lba + count,);
+		Ok((),)
+	}
+
+	fn write_blocks(&mut self, lba: u64, buf: &[u8],) -> Rslt<(), BlockError,> {
+		let count = self.check_request(lba, buf.len(),)?;
+		self.device.write_blocks(lba, buf,)?;
+
+		let block_size = self.block_size();
+		let end = lba + count;
+		for line in self.lines.iter().filter(|line| line.len != 0,) {
+			let start = line.first.max(lba,);
+			let stop = (line.first + line.len as u64).min(end,);
+			if start >= stop {
+				continue;
+			}
+			let data = unsafe { frame(line.addr,) };
+			let dst = (start - line.first) as usize * block_size;
+			let src = (start - lba) as usize * block_size;
+			let len = (stop - start) as usize * block_size;
+			data[dst..dst + len].copy_from_slice(&buf[src..src + len],);
+		}
+		Ok((),)
+	}
+
+	fn flush(&mut self,) -> Rslt<(), BlockError,> {
+		self.device.flush()
+	}
+}
+
+impl<D: BlockDevice, F: FrameSource, const N: usize,> Drop
+	for BlockCache<D, F, N,>
+{
+	fn drop(&mut self,) {
+		for line in &self.lines {
+			self.source.free_frames(line.addr, 1,);
+		}
+	}
+}
+
+/// # Safety
+///
+/// `addr` must be a frame owned by the cache
+unsafe fn frame<'a,>(addr: usize,) -> &'a mut [u8] {
+	unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, FRAME_SIZE,) }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	extern crate std;
+	use crate::driver::block::tests::RamDisk;
+	use std::alloc::Layout;
+	use std::vec;
+	use std::vec::Vec;
+
+	const BLOCK: usize = 512;
+	/// blocks of a line
+	const LINE: u64 = (FRAME_SIZE / BLOCK) as u64;
+
+	fn layout() -> Layout {
+		Layout::from_size_align(FRAME_SIZE, FRAME_SIZE,).unwrap()
+	}
+
+	/// frames from the heap of the process
+	struct HeapFrames {
+		/// frames left to hand out
+		left:      usize,
+		allocated: Vec<usize,>,
+	}
+
+	impl FrameSource for &mut HeapFrames {
+		fn alloc_frames(
+			&mut self,
+			count: usize,
+			align: usize,
+		) -> Option<usize,> {
+			assert_eq!((count, align), (1, FRAME_SIZE));
+			self.left = self.left.checked_sub(1,)?;
+			let addr = unsafe { std::alloc::alloc_zeroed(layout(),) } as usize;
+			self.allocated.push(addr,);
+			Some(addr,)
+		}
+
+		fn free_frames(&mut self, addr: usize, count: usize,) {
+			assert_eq!(count, 1);
+			let i = self.allocated.iter().position(|a| *a == addr,).unwrap();
+			self.allocated.swap_remove(i,);
+			unsafe { std::alloc::dealloc(addr as *mut u8, layout(),) };
+			self.left += 1;
+		}
+	}
+
+	type Cache<'a, const N: usize,> =
+		BlockCache<RamDisk, &'a mut HeapFrames, N,>;
+
+	fn frames(left: usize,) -> HeapFrames {
+		HeapFrames { left, allocated: vec![], }
+	}
+
+	/// cache in front of a disk of `blocks` blocks
+	fn cache<const N: usize,>(
+		frames: &mut HeapFrames,
+		blocks: u64,
+		read_ahead: usize,
+	) -> Rslt<Cache<'_, N,>, BlockError,> {
+		let data = (0..blocks).flat_map(|i| [i as u8; BLOCK],).collect();
+		let disk = RamDisk::new(data, BLOCK,);
+		BlockCache::new(disk, frames, read_ahead,)
+	}
+
+	/// reads `count` blocks at `lba` and checks their content
+	fn read<const N: usize,>(cache: &mut Cache<'_, N,>, lba: u64, count: u64,) {
+		let mut buf = vec![0xff; count as usize * BLOCK];
+		cache.read_blocks(lba, &mut buf,).unwrap();
+		for (i, block,) in buf.chunks(BLOCK,).enumerate() {
+			let lba = lba + i as u64;
+			assert!(block.iter().all(|b| *b == lba as u8), "{lba}");
+		}
+	}
+
+	fn error<T,>(result: Rslt<T, BlockError,>,) -> Option<BlockError,> {
+		result.err().and_then(|e| e.desc,)
+	}
+
+	#[test]
+	fn test_new() {
+		let mut frames = frames(4,);
+		let unsupported = Some(BlockError::Unsupported,);
+		assert_eq!(error(cache::<0,>(&mut frames, 8, 0,)), unsupported);
+		let out_of_frames = Some(BlockError::OutOfFrames,);
+		assert_eq!(error(cache::<8,>(&mut frames, 8, 0,)), out_of_frames);
+		// frames taken before running out are returned
+		assert_eq!((frames.left, frames.allocated.len()), (4, 0));
+
+		let cache = cache::<4,>(&mut frames, 8, 9,).unwrap();
+		assert_eq!(cache.read_ahead, 3);
+	}
+
+	#[test]
+	fn test_hits_and_least_recently_used_eviction() {
+		let mut frames = frames(2,);
+		let mut cache = cache::<2,>(&mut frames, 8 * LINE, 0,).unwrap();
+		read(&mut cache, 2 * LINE, 1,);
+		read(&mut cache, 0, 1,);
+		read(&mut cache, 2 * LINE + 3, 2,);
+		// the line of block 0 is the least recently used one
+		read(&mut cache, 4 * LINE, 1,);
+		read(&mut cache, 2 * LINE + 7, 1,);
+		read(&mut cache, 0, 1,);
+
+		let stats = CacheStats { hits: 2, misses: 4, prefetched: 0, };
+		assert_eq!(cache.stats(), stats);
+		let lines = [2 * LINE, 0, 4 * LINE, 0,].map(|lba| (lba, LINE,),);
+		assert_eq!(cache.device().reads, lines);
+	}
+
+	#[test]
+	fn test_reads_across_lines_and_the_last_short_line() {
+		let mut frames = frames(4,);
+		let mut cache = cache::<4,>(&mut frames, 2 * LINE + 3, 0,).unwrap();
+		read(&mut cache, LINE - 2, LINE + 5,);
+		read(&mut cache, 0, 2 * LINE + 3,);
+		let stats = cache.stats();
+		assert_eq!((stats.hits, stats.misses), (3, 3));
+		assert_eq!(cache.device().reads.last(), Some(&(2 * LINE, 3)));
+
+		let mut buf = [0; BLOCK];
+		let end = 2 * LINE + 3;
+		let out_of_range = BlockError::OutOfRange { lba: end, count: 1, };
+		let read = cache.read_blocks(end, &mut buf,);
+		assert_eq!(error(read), Some(out_of_range));
+		let misaligned = BlockError::Misaligned { len: BLOCK - 1, };
+		let read = cache.read_blocks(0, &mut buf[1..],);
+		assert_eq!(error(read), Some(misaligned));
+	}
+
+	#[test]
+	fn test_read_ahead() {
+		let mut frames = frames(4,);
+		let mut cache = cache::<4,>(&mut frames, 4 * LINE, 2,).unwrap();
+		// the first read is not sequential
+		read(&mut cache, 0, LINE,);
+		assert_eq!(cache.stats().prefetched, 0);
+		read(&mut cache, LINE, 1,);
+		assert_eq!(cache.stats().prefetched, 2);
+		read(&mut cache, LINE + 1, 3 * LINE - 1,);
+		let stats = CacheStats { hits: 3, misses: 2, prefetched: 2, };
+		assert_eq!(cache.stats(), stats);
+
+		// read-ahead stops at the end of the device
+		cache.invalidate();
+		read(&mut cache, 2 * LINE, LINE,);
+		read(&mut cache, 3 * LINE, 1,);
+		assert_eq!(cache.stats().prefetched, 2);
+		assert_eq!(cache.device().reads.last(), Some(&(3 * LINE, LINE)));
+	}
+
+	#[test]
+	fn test_failed_reads_leave_no_line() {
+		let mut frames = frames(2,);
+		let mut cache = cache::<2,>(&mut frames, 3 * LINE, 1,).unwrap();
+		cache.device.broken = Some(2 * LINE + 1,);
+		read(&mut cache, 0, LINE,);
+		// a failed read-ahead is not an error of the read
+		read(&mut cache, LINE, 1,);
+		assert_eq!(cache.stats().prefetched, 0);
+		let mut buf = [0; BLOCK];
+		let timeout = Some(BlockError::Timeout,);
+		assert_eq!(error(cache.read_blocks(2 * LINE, &mut buf,)), timeout);
+
+		cache.device.broken = None;
+		read(&mut cache, 2 * LINE, 1,);
+		assert_eq!(cache.stats().misses, 3);
+	}
+
+	#[test]
+	fn test_writes_go_through_and_update_lines() {
+		let mut frames = frames(2,);
+		let mut cache = cache::<2,>(&mut frames, 2 * LINE, 0,).unwrap();
+		read(&mut cache, 0, 1,);
+
+		let blocks = LINE - 1..LINE + 1;
+		let data: Vec<_,> = blocks.flat_map(|i| [!i as u8; BLOCK],).collect();
+		cache.write_blocks(LINE - 1, &data,).unwrap();
+		let start = (LINE - 1) as usize * BLOCK;
+		assert_eq!(cache.device().data[start..start + data.len()], data);
+
+		let mut buf = vec![0; data.len()];
+		cache.read_blocks(LINE - 1, &mut buf,).unwrap();
+		assert_eq!(buf, data);
+		let stats = cache.stats();
+		assert_eq!((stats.hits, stats.misses), (1, 2));
+	}
+
+	#[test]
+	fn test_drop_returns_frames() {
+		let mut frames = frames(3,);
+		drop(cache::<3,>(&mut frames, LINE, 0,),);
+		assert_eq!((frames.left, frames.allocated.len()), (3, 0));
+	}
+}
@@ -0,0 +1,617 @@
+//! # DMA Buffer Pool
+//!
+//! This module hands out buffers which devices such as virtio and XHCI
+//! controllers can access by DMA. Every buffer is physically contiguous,
+//! starts on a frame boundary and occupies whole frames, so it never shares
+//! a cache line with other data.
+//!
+//! ## Ownership
+//!
+//! A [`DmaBuf`] is owned either by the CPU or by the device. The CPU may only
+//! touch its content while it owns the buffer. Hand-over performs the cache
+//! maintenance the transfer needs:
+//!
+//! - [`DmaBuf::give_to_device`]: Cleans the cache so the device reads what
+//!   the CPU wrote
+//! - [`DmaBuf::take_from_device`]: Invalidates the cache so the CPU reads
+//!   what the device wrote
+//!
+//! ## Backpressure
+//!
+//! A [`Pool`] has a budget of frames. Allocations beyond it fail with
+//! [`DmaError::Exhausted`] instead of draining the frame allocator, and
+//! [`Pool::is_congested`] tells drivers to stop queueing new requests until
+//! buffers in flight are returned.
+//!
+//! ## Address Limits
+//!
+//! Some devices, such as legacy virtio, only address low memory.
+//! [`Pool::alloc_within`] places a buffer below such a limit. When the frame
+//! allocator has no frames there, the buffer is placed anywhere and bounced:
+//! hand-over to the device copies it into low frames taken only for the
+//! transfer, and taking it back copies the content out again. Both cases are
+//! counted in [`BounceStats`].
+//!
+//! ## Current Status
+//!
+//! The kernel has no frame allocator yet, so the pool takes any
+//! [`FrameSource`]. Memory is identity mapped, so the CPU address of a buffer
+//! is also its device address.
+//!
+//! Frames must be normal cacheable memory, as the pool maintains caches
+//! itself. Frames inside a device mapping of [`paging`] fail a debug
+//! assertion.
+//!
+//! ```rust,ignore
+//! let pool = Pool::new(frames, 64,);
+//! let mut ring = pool.alloc(TransferRing::default(),)?;
+//! ring.enqueue(trb,);
+//! let addr = ring.give_to_device()?;
+//! xhci.set_ring(addr,);
+//! // after completion
+//! ring.take_from_device();
+//! ```
+
+use crate::base::cache;
+use crate::base::cpu;
+use crate::base::paging;
+use core::cell::Cell;
+use core::cell::RefCell;
+use core::mem;
+use core::ops::Deref;
+use core::ops::DerefMut;
+use core::ptr::NonNull;
+use oso_error::Rslt;
+use oso_error::kernel::DmaError;
+use oso_error::oso_err;
+use oso_no_std_shared::units::ByteSize;
+use oso_no_std_shared::units::PhysAddr;
+
+/// Size of a frame in bytes
+pub const FRAME_SIZE: usize = 4096;
+
+/// Allocator of physically contiguous frames
+pub trait FrameSource {
+	/// Allocates `count` contiguous frames whose start is aligned to `align`
+	/// bytes. Returns the physical address of the first frame
+	fn alloc_frames(&mut self, count: usize, align: usize,) -> Option<usize,>;
+	/// Returns frames allocated by [`alloc_frames`](Self::alloc_frames)
+	fn free_frames(&mut self, addr: usize, count: usize,);
+
+	/// Like [`alloc_frames`](Self::alloc_frames), but the frames end at or
+	/// below `limit`
+	///
+	/// The default retries nothing and fails if the frames it got are above
+	/// `limit`. Allocators which track memory by address should override it
+	fn alloc_frames_below(
+		&mut self,
+		count: usize,
+		align: usize,
+		limit: u64,
+	) -> Option<usize,> {
+		let addr = self.alloc_frames(count, align,)?;
+		if frames_end(addr, count,).is_some_and(|end| end.as_u64() <= limit,) {
+			Some(addr,)
+		} else {
+			self.free_frames(addr, count,);
+			None
+		}
+	}
+}
+
+/// End of `count` frames at `addr`. `None` if they end past the address
+/// space
+fn frames_end(addr: usize, count: usize,) -> Option<PhysAddr,> {
+	let size = ByteSize::from_pages(count as u64, FRAME_SIZE as u64,)?;
+	PhysAddr::new(addr as u64,).checked_add(size.bytes(),)
+}
+
+/// Counts of device hand-overs of buffers with an address limit
+///
+/// # Fields
+///
+/// * `direct` - Hand-overs of buffers placed below their limit
+/// * `bounced` - Hand-overs which copied through a bounce buffer
+/// * `bytes` - Bytes copied in and out of bounce buffers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default,)]
+pub struct BounceStats {
+	pub direct:  usize,
+	pub bounced: usize,
+	pub bytes:   usize,
+}
+
+/// Side which may access a [`DmaBuf`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum Owner {
+	Cpu,
+	Device,
+}
+
+/// Pool of DMA buffers with a budget of frames
+///
+/// # Fields
+///
+/// * `source` - Allocator frames are carved from
+/// * `budget` - Maximum number of frames lent out at once
+/// * `in_use` - Number of frames currently lent out, bounce buffers included
+/// * `stats` - Counts of hand-overs of buffers with an address limit
+pub struct Pool<F: FrameSource,> {
+	source: RefCell<F,>,
+	budget: usize,
+	in_use: Cell<usize,>,
+	stats:  Cell<BounceStats,>,
+}
+
+impl<F: FrameSource,> Pool<F,> {
+	/// Pool lending at most `budget` frames of `source` at once
+	pub const fn new(source: F, budget: usize,) -> Self {
+		Self {
+			source: RefCell::new(source,),
+			budget,
+			in_use: Cell::new(0,),
+			stats: Cell::new(BounceStats {
+				direct:  0,
+				bounced: 0,
+				bytes:   0,
+			},),
+		}
+	}
+
+	/// Moves `value` into a new CPU owned buffer
+	///
+	/// # Errors
+	///
+	/// [`DmaError::Exhausted`] if the buffer does not fit in the remaining
+	/// budget, [`DmaError::OutOfFrames`] if the frame allocator fails
+	pub fn alloc<T,>(&self, value: T,) -> Rslt<DmaBuf<'_, T, F,>, DmaError,> {
+		self.alloc_inner(value, None,)
+	}
+
+	/// Moves `value` into a new CPU owned buffer which the device accesses
+	/// below address `limit`
+	///
+	/// If no frames are free below `limit`, the buffer is bounced through
+	/// low frames on every hand-over. See the
+	/// [module documentation](self#address-limits).
+	///
+	/// # Errors
+	///
+	/// Same as [`alloc`](Self::alloc)
+	pub fn alloc_within<T,>(
+		&self,
+		value: T,
+		limit: u64,
+	) -> Rslt<DmaBuf<'_, T, F,>, DmaError,> {
+		self.alloc_inner(value, Some(limit,),)
+	}
+
+	/// Number of frames which can still be lent out
+	pub fn available(&self,) -> usize {
+		self.budget - self.in_use.get()
+	}
+
+	/// Whether three quarters of the budget is lent out. Drivers should
+	/// hold back new requests while this is true
+	pub fn is_congested(&self,) -> bool {
+		self.in_use.get() * 4 >= self.budget * 3
+	}
+
+	pub fn bounce_stats(&self,) -> BounceStats {
+		self.stats.get()
+	}
+
+	fn alloc_inner<T,>(
+		&self,
+		value: T,
+		limit: Option<u64,>,
+	) -> Rslt<DmaBuf<'_, T, F,>, DmaError,> {
+		let frames = mem::size_of::<T,>().div_ceil(FRAME_SIZE,).max(1,);
+		let align = mem::align_of::<T,>().max(FRAME_SIZE,);
+		let (ptr, bounce,) = match limit {
+			Some(limit,) => match self.take(frames, align, Some(limit,),) {
+				Ok(ptr,) => (ptr, None,),
+				Err(_,) => (self.take(frames, align, None,)?, Some(limit,),),
+			},
+			None => (self.take(frames, align, None,)?, None,),
+		};
+
+		let ptr = ptr.cast::<T,>();
+		unsafe { ptr.write(value,) };
+		Ok(DmaBuf {
+			pool: self,
+			ptr,
+			frames,
+			owner: Owner::Cpu,
+			limit,
+			bounce: bounce.map(|limit| Bounce { limit, addr: None, },),
+		},)
+	}
+
+	/// Takes `frames` frames from the source within the budget
+	///
+	/// Frames at address 0 cannot be referenced and are returned to the
+	/// source
+	fn take(
+		&self,
+		frames: usize,
+		align: usize,
+		limit: Option<u64,>,
+	) -> Rslt<NonNull<u8,>, DmaError,> {
+		let available = self.available();
+		if frames > available {
+			return Err(oso_err!(DmaError::Exhausted {
+				requested: frames,
+				available
+			}),);
+		}
+
+		let mut source = self.source.borrow_mut();
+		let addr = match limit {
+			Some(limit,) => source
+				.alloc_frames_below(frames, align, limit,)
+				.ok_or(oso_err!(DmaError::OutOfLowFrames { limit }),)?,
+			None => source
+				.alloc_frames(frames, align,)
+				.ok_or(oso_err!(DmaError::OutOfFrames),)?,
+		};
+		let Some(ptr,) = NonNull::new(addr as *mut u8,) else {
+			source.free_frames(addr, frames,);
+			return Err(oso_err!(DmaError::OutOfFrames),);
+		};
+		self.in_use.set(self.in_use.get() + frames,);
+		let end = frames_end(addr, frames,).map_or(u64::MAX, u64::from,);
+		let range = addr as u64..end;
+		debug_assert!(
+			paging::attribute_of(range,).is_none_or(|attr| attr.is_cacheable()),
+			"dma frames must be cacheable memory",
+		);
+		Ok(ptr,)
+	}
+
+	fn release(&self, addr: usize, frames: usize,) {
+		self.source.borrow_mut().free_frames(addr, frames,);
+		self.in_use.set(self.in_use.get() - frames,);
+	}
+
+	fn count(&self, f: impl FnOnce(&mut BounceStats,),) {
+		let mut stats = self.stats.get();
+		f(&mut stats,);
+		self.stats.set(stats,);
+	}
+}
+
+/// Buffer of a [`Pool`] holding a `T`
+///
+/// Dereferencing panics while the device owns the buffer.
+pub struct DmaBuf<'p, T, F: FrameSource,> {
+	pool:   &'p Pool<F,>,
+	ptr:    NonNull<T,>,
+	frames: usize,
+	owner:  Owner,
+	/// Address limit of the device, if any
+	limit:  Option<u64,>,
+	/// Set if the buffer lies above `limit`
+	bounce: Option<Bounce,>,
+}
+
+/// Bounce state of a [`DmaBuf`] placed above its limit
+struct Bounce {
+	limit: u64,
+	/// Low frames holding the copy while the device owns the buffer
+	addr:  Option<usize,>,
+}
+
+impl<T, F: FrameSource,> DmaBuf<'_, T, F,> {
+	/// Address the device uses to access the buffer. For a bounced buffer
+	/// this is the bounce buffer while the device owns it
+	pub fn device_addr(&self,) -> u64 {
+		match self.bounce.as_ref().and_then(|b| b.addr,) {
+			Some(addr,) => addr as u64,
+			None => self.ptr.as_ptr() as u64,
+		}
+	}
+
+	pub fn owner(&self,) -> Owner {
+		self.owner
+	}
+
+	/// Whether hand-overs copy through a bounce buffer
+	pub fn is_bounced(&self,) -> bool {
+		self.bounce.is_some()
+	}
+
+	/// Hands the buffer to the device after writing the CPU's changes back
+	/// to memory. Returns the device address
+	///
+	/// A bounced buffer is copied into low frames first.
+	///
+	/// # Errors
+	///
+	/// Errors of [`Pool::alloc`] if no frames for the bounce buffer are
+	/// available. The buffer stays owned by the CPU
+	pub fn give_to_device(&mut self,) -> Rslt<u64, DmaError,> {
+		if self.owner == Owner::Device {
+			return Ok(self.device_addr(),);
+		}
+
+		let len = self.frames * FRAME_SIZE;
+		if let Some(bounce,) = &mut self.bounce {
+			let addr = self
+				.pool
+				.take(self.frames, FRAME_SIZE, Some(bounce.limit,),)?
+				.as_ptr() as usize;
+			unsafe {
+				cpu::copy(
+					addr as *mut u8,
+					self.ptr.as_ptr() as *const u8,
+					mem::size_of::<T,>(),
+				);
+			}
+			bounce.addr = Some(addr,);
+			self.pool.count(|s| {
+				s.bounced += 1;
+				s.bytes += mem::size_of::<T,>();
+			},);
+		} else if self.limit.is_some() {
+			self.pool.count(|s| s.direct += 1,);
+		}
+
+		let addr = self.device_addr();
+		unsafe { cache::clean(addr as *const u8, len,) };
+		self.owner = Owner::Device;
+		Ok(addr,)
+	}
+
+	/// Takes the buffer back from the device, dropping cached lines so the
+	/// CPU sees what the device wrote
+	///
+	/// A bounced buffer is copied out of its bounce buffer, which is
+	/// returned to the pool. Call this only after the device has finished
+	/// with the buffer
+	pub fn take_from_device(&mut self,) {
+		if self.owner == Owner::Cpu {
+			return;
+		}
+
+		let len = self.frames * FRAME_SIZE;
+		unsafe { cache::invalidate(self.device_addr() as *const u8, len,) };
+		if let Some(addr,) = self.bounce.as_mut().and_then(|b| b.addr.take(),)
+		{
+			unsafe {
+				cpu::copy(
+					self.ptr.as_ptr() as *mut u8,
+					addr as *const u8,
+					mem::size_of::<T,>(),
+				);
+			}
+			self.pool.release(addr, self.frames,);
+			self.pool.count(|s| s.bytes += mem::size_of::<T,>(),);
+		}
+		self.owner = Owner::Cpu;
+	}
+
+	fn assert_cpu_owned(&self,) {
+		assert_eq!(self.owner, Owner::Cpu, "dma buffer is owned by the device");
+	}
+}
+
+impl<T, F: FrameSource,> Deref for DmaBuf<'_, T, F,> {
+	type Target = T;
+
+	fn deref(&self,) -> &Self::Target {
+		self.assert_cpu_owned();
+		unsafe { self.ptr.as_ref() }
+	}
+}
+
+impl<T, F: FrameSource,> DerefMut for DmaBuf<'_, T, F,> {
+	fn deref_mut(&mut self,) -> &mut Self::Target {
+		self.assert_cpu_owned();
+		unsafe { self.ptr.as_mut() }
+	}
+}
+
+impl<T, F: FrameSource,> Drop for DmaBuf<'_, T, F,> {
+	/// Drops the value and returns the frames to the pool
+	///
+	/// The device must not access the buffer anymore. A buffer dropped while
+	/// the device owns it is returned as well, as the pool cannot tell
+	/// whether the transfer was aborted
+	fn drop(&mut self,) {
+		if let Some(addr,) = self.bounce.as_mut().and_then(|b| b.addr.take(),)
+		{
+			self.pool.release(addr, self.frames,);
+		}
+		unsafe { self.ptr.drop_in_place() };
+		self.pool.release(self.ptr.as_ptr() as usize, self.frames,);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	extern crate std;
+	use std::alloc::Layout;
+	use std::vec;
+	use std::vec::Vec;
+
+	const LIMIT: u64 = 0x1_0000_0000;
+
+	fn layout(count: usize, align: usize,) -> Layout {
+		Layout::from_size_align(count * FRAME_SIZE, align,).unwrap()
+	}
+
+	/// frames from the heap of the process. Heap addresses are not low, so
+	/// frames below a limit are only counted
+	struct HeapFrames {
+		/// frames left to hand out below any limit
+		low:       usize,
+		/// hands out address 0 once
+		null:      bool,
+		/// address, count, alignment and whether the frames count as low
+		allocated: Vec<(usize, usize, usize, bool,),>,
+	}
+
+	impl HeapFrames {
+		fn alloc(&mut self, count: usize, align: usize, low: bool,) -> usize {
+			let addr = if self.null {
+				self.null = false;
+				0
+			} else {
+				let layout = layout(count, align,);
+				unsafe { std::alloc::alloc_zeroed(layout,) as usize }
+			};
+			self.allocated.push((addr, count, align, low,),);
+			addr
+		}
+	}
+
+	impl FrameSource for &mut HeapFrames {
+		fn alloc_frames(
+			&mut self,
+			count: usize,
+			align: usize,
+		) -> Option<usize,> {
+			Some(self.alloc(count, align, false,),)
+		}
+
+		fn free_frames(&mut self, addr: usize, count: usize,) {
+			let i = self.allocated.iter().position(|a| a.0 == addr,).unwrap();
+			let (_, allocated, align, low,) = self.allocated.swap_remove(i,);
+			assert_eq!(count, allocated);
+			if low {
+				self.low += count;
+			}
+			if addr != 0 {
+				let layout = layout(count, align,);
+				unsafe { std::alloc::dealloc(addr as *mut u8, layout,) };
+			}
+		}
+
+		fn alloc_frames_below(
+			&mut self,
+			count: usize,
+			align: usize,
+			_limit: u64,
+		) -> Option<usize,> {
+			self.low = self.low.checked_sub(count,)?;
+			Some(self.alloc(count, align, true,),)
+		}
+	}
+
+	fn frames(low: usize,) -> HeapFrames {
+		HeapFrames { low, null: false, allocated: vec![], }
+	}
+
+	fn error<T,>(result: Rslt<T, DmaError,>,) -> Option<DmaError,> {
+		result.err().and_then(|e| e.desc,)
+	}
+
+	/// counts how often it is dropped
+	struct Counted<'a,>(&'a Cell<usize,>,);
+
+	impl Drop for Counted<'_,> {
+		fn drop(&mut self,) {
+			self.0.set(self.0.get() + 1,);
+		}
+	}
+
+	#[test]
+	fn test_alloc() {
+		let mut frames = frames(0,);
+		let pool = Pool::new(&mut frames, 4,);
+		let buf = pool.alloc([7u8; FRAME_SIZE + 1],).unwrap();
+		assert_eq!(buf.owner(), Owner::Cpu);
+		assert!(buf.iter().all(|b| *b == 7));
+		assert_eq!(pool.available(), 2);
+		assert!(!pool.is_congested());
+
+		let exhausted = DmaError::Exhausted { requested: 4, available: 2, };
+		assert_eq!(error(pool.alloc([0u8; 4 * FRAME_SIZE],)), Some(exhausted));
+		let small = pool.alloc(0u64,).unwrap();
+		assert!(pool.is_congested());
+
+		drop(buf,);
+		drop(small,);
+		assert_eq!(pool.available(), 4);
+		assert!(frames.allocated.is_empty());
+	}
+
+	#[test]
+	fn test_null_frame_is_returned() {
+		let mut frames = HeapFrames { null: true, ..frames(0,) };
+		let pool = Pool::new(&mut frames, 4,);
+		assert_eq!(error(pool.alloc(0u64,)), Some(DmaError::OutOfFrames));
+		assert_eq!(pool.available(), 4);
+		assert_eq!(*pool.alloc(1u64,).unwrap(), 1);
+		assert!(frames.allocated.is_empty());
+	}
+
+	#[test]
+	fn test_limit_falls_back_to_bounce() {
+		let mut frames = frames(1,);
+		let pool = Pool::new(&mut frames, 8,);
+		let mut low = pool.alloc_within(1u64, LIMIT,).unwrap();
+		assert!(!low.is_bounced());
+		let mut high = pool.alloc_within(2u64, LIMIT,).unwrap();
+		assert!(high.is_bounced());
+
+		assert_eq!(low.give_to_device().unwrap(), low.ptr.as_ptr() as u64);
+		let out_of_low = DmaError::OutOfLowFrames { limit: LIMIT, };
+		assert_eq!(error(high.give_to_device()), Some(out_of_low));
+		assert_eq!(high.owner(), Owner::Cpu);
+
+		low.take_from_device();
+		drop(low,);
+		let addr = high.give_to_device().unwrap();
+		assert_eq!(high.owner(), Owner::Device);
+		assert_ne!(addr, high.ptr.as_ptr() as u64);
+		let stats = BounceStats { direct: 1, bounced: 1, bytes: 8, };
+		assert_eq!(pool.bounce_stats(), stats);
+	}
+
+	#[test]
+	fn test_bounce_sync() {
+		let mut frames = frames(1,);
+		let pool = Pool::new(&mut frames, 8,);
+		let filler = pool.alloc_within(0u8, LIMIT,).unwrap();
+		let mut buf = pool.alloc_within([1u8; 64], LIMIT,).unwrap();
+		drop(filler,);
+
+		let addr = buf.give_to_device().unwrap();
+		assert_eq!(pool.available(), 6);
+		let device =
+			unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, 64,) };
+		assert_eq!(device, [1; 64]);
+		device.fill(2,);
+
+		buf.take_from_device();
+		assert_eq!(*buf, [2; 64]);
+		assert_eq!(buf.device_addr(), buf.ptr.as_ptr() as u64);
+		assert_eq!(pool.available(), 7);
+		let stats = BounceStats { direct: 0, bounced: 1, bytes: 128, };
+		assert_eq!(pool.bounce_stats(), stats);
+	}
+
+	#[test]
+	fn test_frames_returned_on_drop() {
+		let dropped = Cell::new(0,);
+		let mut frames = frames(1,);
+		let pool = Pool::new(&mut frames, 8,);
+		let filler = pool.alloc_within(0u8, LIMIT,).unwrap();
+		let mut bounced =
+			pool.alloc_within(Counted(&dropped,), LIMIT,).unwrap();
+		drop(filler,);
+		bounced.give_to_device().unwrap();
+		let mut direct = pool.alloc(Counted(&dropped,),).unwrap();
+		direct.give_to_device().unwrap();
+		assert_eq!(pool.available(), 5);
+
+		drop(bounced,);
+		drop(direct,);
+		assert_eq!(dropped.get(), 2);
+		assert_eq!(pool.available(), 8);
+		assert!(frames.allocated.is_empty());
+		assert_eq!(frames.low, 1);
+	}
+}
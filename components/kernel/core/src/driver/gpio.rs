@@ -0,0 +1,448 @@
+//! # GPIO Drivers
+//!
+//! General purpose I/O pins, mainly to drive an LED which shows the kernel is
+//! alive on boards without a serial cable.
+//!
+//! ## Devices
+//!
+//! - [`Pl061`]: Arm PrimeCell GPIO with 8 pins, found on QEMU `virt`
+//! - [`Bcm2711Gpio`]: GPIO block of the Raspberry Pi 4 with 58 pins
+//!
+//! Drivers implement [`Gpio`]. One controller can be installed with
+//! [`install`], after which [`set`] and [`get`] reach it from anywhere.
+//!
+//! ## Heartbeat
+//!
+//! [`Heartbeat`] blinks an LED twice per second from the timer tick. A hang
+//! freezes the LED, and a blinking LED with a frozen screen points at the
+//! display path instead. [`start_heartbeat`] blinks a pin of the installed
+//! controller, which [`timer::interrupt`] advances through [`heartbeat`].
+//!
+//! ## Current Status
+//!
+//! The kernel has no timer driver yet, so the heartbeat only blinks where
+//! [`timer::interrupt`] is called, such as in the simulator.
+//!
+//! ```rust,ignore
+//! unsafe { gpio::install(Controller::Bcm2711(Bcm2711Gpio::PI4_BASE,),) };
+//! gpio::set(Bcm2711Gpio::PI4_ACT_LED, Level::High,)?;
+//!
+//! gpio::start_heartbeat(Bcm2711Gpio::PI4_ACT_LED, 100,)?;
+//! ```
+//!
+//! [`timer::interrupt`]: crate::base::timer::interrupt
+
+use super::mmio;
+use crate::base::sched::disable_interrupts;
+use crate::base::sched::restore_interrupts;
+use core::cell::UnsafeCell;
+use core::sync::atomic::AtomicU8;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+use oso_error::Rslt;
+use oso_error::kernel::GpioError;
+use oso_error::oso_err;
+
+/// Logic level of a pin
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum Level {
+	Low,
+	High,
+}
+
+impl Level {
+	pub const fn toggled(self,) -> Self {
+		match self {
+			Self::Low => Self::High,
+			Self::High => Self::Low,
+		}
+	}
+}
+
+impl From<bool,> for Level {
+	fn from(high: bool,) -> Self {
+		if high { Self::High } else { Self::Low }
+	}
+}
+
+/// Common interface of GPIO controllers
+pub trait Gpio {
+	/// Number of pins. Pins are numbered from `0`
+	fn pin_count(&self,) -> u32;
+
+	/// Makes `pin` an output driven to `level`
+	fn set(&mut self, pin: u32, level: Level,) -> Rslt<(), GpioError,>;
+
+	/// Makes `pin` an input and reads it
+	fn get(&mut self, pin: u32,) -> Rslt<Level, GpioError,>;
+
+	/// Fails if the controller has no `pin`
+	fn check_pin(&self, pin: u32,) -> Rslt<(), GpioError,> {
+		let count = self.pin_count();
+		if pin >= count {
+			return Err(oso_err!(GpioError::PinOutOfRange { pin, count }),);
+		}
+		Ok((),)
+	}
+}
+
+/// Arm PrimeCell PL061 GPIO
+///
+/// Address bits `[9:2]` of a `GPIODATA` access mask the pins it affects, so
+/// a pin is written without reading the others.
+pub struct Pl061 {
+	base: usize,
+}
+
+impl Pl061 {
+	/// Register frame on QEMU `virt`
+	pub const QEMU_VIRT_BASE: usize = 0x0903_0000;
+
+	const DATA: usize = 0x000;
+	const DIR: usize = 0x400;
+
+	/// # Safety
+	///
+	/// `base` must be the mapped register frame of a PL061
+	pub const unsafe fn new(base: usize,) -> Self {
+		Self { base, }
+	}
+
+	/// `GPIODATA` aliased to the pins in `mask`
+	fn data(mask: u32,) -> usize {
+		Self::DATA + ((mask as usize) << 2)
+	}
+
+	fn set_direction(&mut self, pin: u32, output: bool,) {
+		let mask = 1 << pin;
+		unsafe {
+			let dir = read32(self.base, Self::DIR,);
+			let dir = if output { dir | mask } else { dir & !mask };
+			write32(self.base, Self::DIR, dir,);
+		}
+	}
+}
+
+impl Gpio for Pl061 {
+	fn pin_count(&self,) -> u32 {
+		8
+	}
+
+	fn set(&mut self, pin: u32, level: Level,) -> Rslt<(), GpioError,> {
+		self.check_pin(pin,)?;
+		let mask = 1 << pin;
+		let value = if level == Level::High { mask } else { 0 };
+		unsafe { write32(self.base, Self::data(mask,), value,) };
+		self.set_direction(pin, true,);
+		Ok((),)
+	}
+
+	fn get(&mut self, pin: u32,) -> Rslt<Level, GpioError,> {
+		self.check_pin(pin,)?;
+		self.set_direction(pin, false,);
+		let mask = 1 << pin;
+		let data = unsafe { read32(self.base, Self::data(mask,),) };
+		Ok(Level::from(data & mask != 0,),)
+	}
+}
+
+/// GPIO block of the BCM2711, the SoC of the Raspberry Pi 4
+///
+/// Each pin has a 3 bit function in `GPFSELn` and is driven through the
+/// write-one `GPSETn` and `GPCLRn` registers.
+pub struct Bcm2711Gpio {
+	base: usize,
+}
+
+impl Bcm2711Gpio {
+	/// Register frame in the low peripheral mode the firmware boots in
+	pub const PI4_BASE: usize = 0xfe20_0000;
+	/// Green activity LED of the Raspberry Pi 4, active high
+	pub const PI4_ACT_LED: u32 = 42;
+
+	const GPFSEL0: usize = 0x00;
+	const GPSET0: usize = 0x1c;
+	const GPCLR0: usize = 0x28;
+	const GPLEV0: usize = 0x34;
+
+	const FUNCTION_INPUT: u32 = 0b000;
+	const FUNCTION_OUTPUT: u32 = 0b001;
+
+	/// # Safety
+	///
+	/// `base` must be the mapped register frame of a BCM2711 GPIO block
+	pub const unsafe fn new(base: usize,) -> Self {
+		Self { base, }
+	}
+
+	fn set_function(&mut self, pin: u32, function: u32,) {
+		let offset = Self::GPFSEL0 + (pin / 10) as usize * 4;
+		let shift = (pin % 10) * 3;
+		unsafe {
+			let fsel = read32(self.base, offset,) & !(0b111 << shift);
+			write32(self.base, offset, fsel | function << shift,);
+		}
+	}
+
+	/// offset of the register of `pin` among `GPxx0`, `GPxx1` and its bit
+	fn bank(pin: u32,) -> (usize, u32,) {
+		((pin / 32) as usize * 4, 1 << (pin % 32),)
+	}
+}
+
+impl Gpio for Bcm2711Gpio {
+	fn pin_count(&self,) -> u32 {
+		58
+	}
+
+	fn set(&mut self, pin: u32, level: Level,) -> Rslt<(), GpioError,> {
+		self.check_pin(pin,)?;
+		let (bank, bit,) = Self::bank(pin,);
+		let register = match level {
+			Level::High => Self::GPSET0,
+			Level::Low => Self::GPCLR0,
+		};
+		unsafe { write32(self.base, register + bank, bit,) };
+		self.set_function(pin, Self::FUNCTION_OUTPUT,);
+		Ok((),)
+	}
+
+	fn get(&mut self, pin: u32,) -> Rslt<Level, GpioError,> {
+		self.check_pin(pin,)?;
+		self.set_function(pin, Self::FUNCTION_INPUT,);
+		let (bank, bit,) = Self::bank(pin,);
+		let level = unsafe { read32(self.base, Self::GPLEV0 + bank,) };
+		Ok(Level::from(level & bit != 0,),)
+	}
+}
+
+/// Controller reached by [`set`] and [`get`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum Controller {
+	/// PL061 at the base address
+	Pl061(usize,),
+	/// BCM2711 GPIO at the base address
+	Bcm2711(usize,),
+}
+
+const NONE: u8 = 0;
+const PL061: u8 = 1;
+const BCM2711: u8 = 2;
+
+static KIND: AtomicU8 = AtomicU8::new(NONE,);
+static BASE: AtomicUsize = AtomicUsize::new(0,);
+
+/// Makes `controller` the one reached by [`set`] and [`get`]
+///
+/// # Safety
+///
+/// The base address must be the mapped register frame of the controller,
+/// which is not used through any other driver
+pub unsafe fn install(controller: Controller,) {
+	let (kind, base,) = match controller {
+		Controller::Pl061(base,) => (PL061, base,),
+		Controller::Bcm2711(base,) => (BCM2711, base,),
+	};
+	BASE.store(base, Ordering::Relaxed,);
+	KIND.store(kind, Ordering::Release,);
+}
+
+/// Installed controller
+pub fn controller() -> Option<Controller,> {
+	let base = || BASE.load(Ordering::Relaxed,);
+	match KIND.load(Ordering::Acquire,) {
+		PL061 => Some(Controller::Pl061(base(),),),
+		BCM2711 => Some(Controller::Bcm2711(base(),),),
+		_ => None,
+	}
+}
+
+fn with_controller<T,>(
+	f: impl FnOnce(&mut dyn Gpio,) -> Rslt<T, GpioError,>,
+) -> Rslt<T, GpioError,> {
+	match controller() {
+		Some(Controller::Pl061(base,),) => {
+			f(&mut unsafe { Pl061::new(base,) },)
+		},
+		Some(Controller::Bcm2711(base,),) => {
+			f(&mut unsafe { Bcm2711Gpio::new(base,) },)
+		},
+		None => Err(oso_err!(GpioError::NoController),),
+	}
+}
+
+/// Drives `pin` of the installed controller to `level`
+pub fn set(pin: u32, level: Level,) -> Rslt<(), GpioError,> {
+	with_controller(|gpio| gpio.set(pin, level,),)
+}
+
+/// Reads `pin` of the installed controller
+pub fn get(pin: u32,) -> Rslt<Level, GpioError,> {
+	with_controller(|gpio| gpio.get(pin,),)
+}
+
+/// Starts blinking `pin` of the installed controller from the timer tick,
+/// which runs `tick_hz` times per second. Replaces a running heartbeat
+///
+/// # Errors
+///
+/// Fails if no controller is installed or it has no `pin`
+pub fn start_heartbeat(pin: u32, tick_hz: u32,) -> Rslt<(), GpioError,> {
+	let heartbeat =
+		with_controller(|gpio| Heartbeat::new(gpio, pin, tick_hz,),)?;
+	beating(|beating| *beating = Some(heartbeat,),);
+	Ok((),)
+}
+
+/// Advances the heartbeat of [`start_heartbeat`], if any. Called from the
+/// timer tick
+pub fn heartbeat() {
+	beating(|beating| {
+		if let Some(heartbeat,) = beating {
+			let _ = with_controller(|gpio| {
+				heartbeat.tick(gpio,);
+				Ok((),)
+			},);
+		}
+	},);
+}
+
+static BEATING: Beating = Beating(UnsafeCell::new(None,),);
+
+/// only accessed with interrupts disabled on a single core
+struct Beating(UnsafeCell<Option<Heartbeat,>,>,);
+
+unsafe impl Sync for Beating {}
+
+/// Runs `f` on the running heartbeat with interrupts disabled
+fn beating<R,>(f: impl FnOnce(&mut Option<Heartbeat,>,) -> R,) -> R {
+	let flags = disable_interrupts();
+	let r = f(unsafe { &mut *BEATING.0.get() },);
+	restore_interrupts(flags,);
+	r
+}
+
+/// Blinks an LED twice per second
+///
+/// The LED is on for [`Heartbeat::PULSE_MS`] at the start of each second and
+/// again [`Heartbeat::GAP_MS`] later.
+pub struct Heartbeat {
+	pin:     u32,
+	tick_hz: u32,
+	ticks:   u32,
+	level:   Level,
+}
+
+impl Heartbeat {
+	pub const PULSE_MS: u32 = 70;
+	pub const GAP_MS: u32 = 250;
+
+	/// Heartbeat on `pin` of `gpio` for a timer ticking `tick_hz` times per
+	/// second. The LED is turned off
+	///
+	/// # Errors
+	///
+	/// Fails if `gpio` has no `pin`
+	pub fn new(
+		gpio: &mut dyn Gpio,
+		pin: u32,
+		tick_hz: u32,
+	) -> Rslt<Self, GpioError,> {
+		gpio.set(pin, Level::Low,)?;
+		let tick_hz = tick_hz.max(1,);
+		Ok(Self { pin, tick_hz, ticks: 0, level: Level::Low, },)
+	}
+
+	/// Advances by one tick, driving the pin of `gpio` given to
+	/// [`new`](Self::new)
+	pub fn tick(&mut self, gpio: &mut dyn Gpio,) {
+		self.ticks = (self.ticks + 1) % self.tick_hz;
+		let ms = self.ticks as u64 * 1000 / self.tick_hz as u64;
+		let second_pulse = Self::PULSE_MS + Self::GAP_MS;
+		let on = ms < Self::PULSE_MS as u64
+			|| (second_pulse as u64..(second_pulse + Self::PULSE_MS) as u64)
+				.contains(&ms,);
+		let level = Level::from(on,);
+		if level != self.level {
+			// the pin was checked by `new`
+			let _ = gpio.set(self.pin, level,);
+			self.level = level;
+		}
+	}
+}
+
+unsafe fn read32(base: usize, offset: usize,) -> u32 {
+	unsafe { mmio::read32(base + offset,) }
+}
+
+unsafe fn write32(base: usize, offset: usize, value: u32,) {
+	unsafe { mmio::write32(base + offset, value,) }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	extern crate std;
+	use std::vec;
+	use std::vec::Vec;
+
+	/// four output pins which record every level they are driven to
+	#[derive(Default,)]
+	struct FakePins {
+		driven: Vec<(u32, Level,),>,
+	}
+
+	impl Gpio for FakePins {
+		fn pin_count(&self,) -> u32 {
+			4
+		}
+
+		fn set(&mut self, pin: u32, level: Level,) -> Rslt<(), GpioError,> {
+			self.check_pin(pin,)?;
+			self.driven.push((pin, level,),);
+			Ok((),)
+		}
+
+		fn get(&mut self, pin: u32,) -> Rslt<Level, GpioError,> {
+			self.check_pin(pin,)?;
+			Ok(Level::Low,)
+		}
+	}
+
+	#[test]
+	fn test_heartbeat_blinks_twice_per_second() {
+		let mut pins = FakePins::default();
+		let mut heartbeat = Heartbeat::new(&mut pins, 2, 100,).unwrap();
+		assert_eq!(pins.driven, [(2, Level::Low,)]);
+
+		// tick at which the level changed, at 10 ms per tick
+		let mut changes = vec![];
+		for tick in 1..200 {
+			let driven = pins.driven.len();
+			heartbeat.tick(&mut pins,);
+			if let Some((2, level,),) = pins.driven.get(driven,) {
+				changes.push((tick, *level,),);
+			}
+		}
+		// the first second starts with the LED off
+		let expected = [
+			(1, Level::High,),
+			(7, Level::Low,),
+			(32, Level::High,),
+			(39, Level::Low,),
+			(100, Level::High,),
+			(107, Level::Low,),
+			(132, Level::High,),
+			(139, Level::Low,),
+		];
+		assert_eq!(changes, expected);
+	}
+
+	#[test]
+	fn test_heartbeat_pin_out_of_range() {
+		let mut pins = FakePins::default();
+		let error = Heartbeat::new(&mut pins, 4, 100,).err().unwrap().desc;
+		assert_eq!(error, Some(GpioError::PinOutOfRange { pin: 4, count: 4 }));
+		assert!(pins.driven.is_empty());
+	}
+}
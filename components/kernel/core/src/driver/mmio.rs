@@ -0,0 +1,118 @@
+//! # Memory-Mapped I/O
+//!
+//! Volatile accesses to device registers, shared by the drivers so every
+//! register access passes one place. With the `fault_injection` feature,
+//! each access is first checked against the rules of [`fault`], which make
+//! chosen accesses read corrupted values, get stuck or fault, so the error
+//! paths of drivers run on QEMU instead of only on broken hardware.
+//!
+//! ```rust,ignore
+//! let status = unsafe { mmio::read32(base + INT_STATUS,) };
+//! unsafe { mmio::write32(base + INT_STATUS, status,) };
+//! ```
+
+/// Scripted faults of register accesses
+pub mod fault;
+
+use core::ptr::read_volatile;
+use core::ptr::write_volatile;
+#[cfg(feature = "fault_injection")] use fault::Fault;
+
+/// Reads the 32 bit register at `addr`
+///
+/// # Safety
+///
+/// `addr` is a mapped, aligned device register which may be read
+pub unsafe fn read32(addr: usize,) -> u32 {
+	#[cfg(feature = "fault_injection")]
+	match fault::hit(addr,) {
+		None => {},
+		Some(Fault::Corrupt(mask,),) => {
+			return unsafe { read_volatile(addr as *const u32,) } ^ mask;
+		},
+		Some(Fault::Stuck(value,),) => return value,
+		Some(Fault::Abort,) => panic!("mmio: injected fault reading {addr:#x}"),
+	}
+	unsafe { read_volatile(addr as *const u32,) }
+}
+
+/// Writes the 32 bit register at `addr`
+///
+/// # Safety
+///
+/// `addr` is a mapped, aligned device register, and writing `value` to it
+/// does not break memory safety, e.g. by starting DMA into used memory
+pub unsafe fn write32(addr: usize, value: u32,) {
+	#[cfg(feature = "fault_injection")]
+	let value = match fault::hit(addr,) {
+		None => value,
+		Some(Fault::Corrupt(mask,),) => value ^ mask,
+		Some(Fault::Stuck(_,),) => return,
+		Some(Fault::Abort,) => panic!("mmio: injected fault writing {addr:#x}"),
+	};
+	unsafe { write_volatile(addr as *mut u32, value,) }
+}
+
+/// Writes the 16 bit register at `addr`
+///
+/// # Safety
+///
+/// Same as [`write32`]
+pub unsafe fn write16(addr: usize, value: u16,) {
+	#[cfg(feature = "fault_injection")]
+	let value = match fault::hit(addr,) {
+		None => value,
+		Some(Fault::Corrupt(mask,),) => value ^ mask as u16,
+		Some(Fault::Stuck(_,),) => return,
+		Some(Fault::Abort,) => panic!("mmio: injected fault writing {addr:#x}"),
+	};
+	unsafe { write_volatile(addr as *mut u16, value,) }
+}
+
+#[cfg(all(test, feature = "fault_injection"))]
+mod tests {
+	extern crate std;
+
+	use super::*;
+	use fault::Rule;
+	use std::boxed::Box;
+	use std::format;
+	use std::panic::catch_unwind;
+	use std::string::String;
+
+	/// rule of `fault` on the 4 bytes at `addr` from the first access
+	fn rule(addr: usize, once: bool, fault: Fault,) -> Rule {
+		Rule { base: addr, len: 4, nth: 1, once, fault, }
+	}
+
+	#[test]
+	fn test_injected_faults() {
+		let _lock = fault::tests::lock();
+		let registers = Box::leak(Box::new([0x1234u32, 0, 0,],),);
+		let a = registers.as_mut_ptr() as usize;
+		let (b, c,) = (a + 4, a + 8,);
+
+		fault::add(rule(a, true, Fault::Corrupt(0xff,),),).unwrap();
+		assert_eq!(unsafe { read32(a,) }, 0x12cb);
+		assert_eq!(unsafe { read32(a,) }, 0x1234, "one-shot rules fire once");
+
+		fault::add(rule(b, false, Fault::Stuck(7,),),).unwrap();
+		unsafe { write32(b, 1,) };
+		assert_eq!(unsafe { read32(b,) }, 7);
+
+		fault::add(rule(c, false, Fault::Corrupt(0x101,),),).unwrap();
+		unsafe { write16(c, 0x1,) };
+		fault::clear();
+		assert_eq!(unsafe { read32(b,) }, 0, "stuck writes are dropped");
+		assert_eq!(unsafe { read32(c,) }, 0x100);
+
+		fault::add(rule(a, false, Fault::Abort,),).unwrap();
+		let read = catch_unwind(|| unsafe { read32(a,) },);
+		let message = read.unwrap_err().downcast::<String,>().unwrap();
+		let expected = format!("mmio: injected fault reading {a:#x}");
+		assert_eq!(*message, expected);
+		let write = catch_unwind(|| unsafe { write32(a, 0,) },);
+		let message = write.unwrap_err().downcast::<String,>().unwrap();
+		assert_eq!(*message, format!("mmio: injected fault writing {a:#x}"));
+	}
+}
@@ -0,0 +1,454 @@
+//! # MMIO Fault Injection
+//!
+//! Rules which make accesses of [`mmio`](super) to a register range
+//! misbehave from the `nth` access on, so drivers meet the failures their
+//! error paths are written for:
+//!
+//! - [`Fault::Corrupt`]: Values read and written have the bits of a mask
+//!   flipped
+//! - [`Fault::Stuck`]: Reads return a fixed value and writes are dropped,
+//!   so polls of a status register time out
+//! - [`Fault::Abort`]: The access panics, like a synchronous external
+//!   abort, for the supervisor of the task to catch
+//!
+//! A rule counts every access to its range, and fires on the `nth` one and
+//! each later one, or only on the `nth` one if it is a one-shot rule. Rules
+//! are only checked with the `fault_injection` feature. Without it, adding
+//! one fails with [`MmioFaultError::Disabled`].
+//!
+//! ## Shell
+//!
+//! [`run_command`] implements the `mmiofault` shell command, so a boot
+//! script sets up a scenario before the drivers start:
+//!
+//! - `mmiofault add <base> <len> <nth> corrupt <mask> [once]`
+//! - `mmiofault add <base> <len> <nth> stuck <value> [once]`
+//! - `mmiofault add <base> <len> <nth> abort [once]`
+//! - `mmiofault list`: Prints the rules with their accesses and hits
+//! - `mmiofault clear`: Removes every rule
+//!
+//! Numbers are decimal, or hexadecimal with `0x`.
+//!
+//! ## Current Status
+//!
+//! The SD host controller, GPIO and watchdog drivers access their registers
+//! through [`mmio`](super). There is no virtio, UART or interrupt controller
+//! driver yet.
+//!
+//! ```text
+//! # the third read of the SD present state register times out
+//! mmiofault add 0xfe340024 4 3 stuck 0x1 once
+//! ```
+
+use core::fmt;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicU8;
+use core::sync::atomic::AtomicU32;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+use oso_error::Rslt;
+use oso_error::kernel::MmioFaultError;
+use oso_error::oso_err;
+
+/// Name of the shell command handled by [`run_command`]
+pub const COMMAND: &str = "mmiofault";
+/// Rules which can be added at once
+pub const MAX_RULES: usize = 8;
+
+/// whether any rule is added, so accesses skip the rules otherwise
+static ARMED: AtomicBool = AtomicBool::new(false,);
+static RULES: [Slot; MAX_RULES] = [const { Slot::new() }; MAX_RULES];
+
+/// What an access hit by a rule does
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum Fault {
+	/// flips the bits of the mask in the value read or written
+	Corrupt(u32,),
+	/// reads the value, and drops writes
+	Stuck(u32,),
+	/// panics
+	Abort,
+}
+
+impl fmt::Display for Fault {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		match self {
+			Self::Corrupt(mask,) => write!(f, "corrupt {mask:#x}"),
+			Self::Stuck(value,) => write!(f, "stuck {value:#x}"),
+			Self::Abort => write!(f, "abort"),
+		}
+	}
+}
+
+/// Fault of the accesses to `len` bytes from `base`
+///
+/// # Fields
+///
+/// * `nth` - Access which fires the rule first, counted from `1`
+/// * `once` - Whether only the `nth` access is hit
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct Rule {
+	pub base:  usize,
+	pub len:   usize,
+	pub nth:   u32,
+	pub once:  bool,
+	pub fault: Fault,
+}
+
+impl Rule {
+	fn contains(&self, addr: usize,) -> bool {
+		addr.wrapping_sub(self.base,) < self.len
+	}
+
+	fn fires(&self, access: u32,) -> bool {
+		if self.once { access == self.nth } else { access >= self.nth }
+	}
+}
+
+impl fmt::Display for Rule {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		let Self { base, len, nth, once, fault, } = self;
+		write!(f, "{base:#x}+{len:#x} from access {nth}: {fault}")?;
+		if *once {
+			write!(f, " once")?;
+		}
+		Ok((),)
+	}
+}
+
+/// Adds `rule`, whose accesses are counted from now
+///
+/// # Errors
+///
+/// - [`MmioFaultError::Disabled`] without the `fault_injection` feature
+/// - [`MmioFaultError::TooManyRules`] if [`MAX_RULES`] rules are added
+pub fn add(rule: Rule,) -> Rslt<(), MmioFaultError,> {
+	if !cfg!(feature = "fault_injection") {
+		return Err(oso_err!(MmioFaultError::Disabled),);
+	}
+	let free = RULES.iter().find(|slot| {
+		let (success, failure,) = (Ordering::Acquire, Ordering::Relaxed,);
+		slot.state.compare_exchange(FREE, WRITING, success, failure,).is_ok()
+	},);
+	let Some(slot,) = free else {
+		return Err(oso_err!(MmioFaultError::TooManyRules),);
+	};
+	slot.store(rule,);
+	slot.state.store(ARMED_SLOT, Ordering::Release,);
+	ARMED.store(true, Ordering::Release,);
+	Ok((),)
+}
+
+/// Removes every rule
+pub fn clear() {
+	ARMED.store(false, Ordering::Release,);
+	for slot in &RULES {
+		slot.state.store(FREE, Ordering::Release,);
+	}
+}
+
+/// Rules with the accesses they counted and how many they hit
+pub fn rules() -> impl Iterator<Item = (Rule, u32, u32,),> {
+	RULES.iter().filter_map(|slot| {
+		let rule = slot.load()?;
+		let accesses = slot.accesses.load(Ordering::Relaxed,);
+		Some((rule, accesses, slot.hits.load(Ordering::Relaxed,),),)
+	},)
+}
+
+/// Counts an access to `addr` in every rule containing it, and returns the
+/// fault of the first rule it fires. Called by the accessors of
+/// [`mmio`](super) with the `fault_injection` feature
+pub fn hit(addr: usize,) -> Option<Fault,> {
+	if !ARMED.load(Ordering::Relaxed,) {
+		return None;
+	}
+	let mut fault = None;
+	for slot in &RULES {
+		let rule = slot.load().filter(|rule| rule.contains(addr,),);
+		let Some(rule,) = rule else {
+			continue;
+		};
+		let access = slot.accesses.fetch_add(1, Ordering::Relaxed,) + 1;
+		if fault.is_none() && rule.fires(access,) {
+			slot.hits.fetch_add(1, Ordering::Relaxed,);
+			fault = Some(rule.fault,);
+		}
+	}
+	fault
+}
+
+/// Runs the `mmiofault` shell command with the arguments after its name
+pub fn run_command(
+	args: &[&str],
+	out: &mut impl fmt::Write,
+) -> Rslt<(), MmioFaultError,> {
+	match args {
+		["list",] => {
+			for (rule, accesses, hits,) in rules() {
+				let counts = format_args!("{accesses} accesses, {hits} hits");
+				let _ = writeln!(out, "{rule}, {counts}");
+			}
+		},
+		["clear",] => {
+			clear();
+			let _ = writeln!(out, "rules cleared");
+		},
+		["add", base, len, nth, fault @ ..] => {
+			let Some(rule,) = parse_rule(base, len, nth, fault,) else {
+				return usage(out,);
+			};
+			if let Err(e,) = add(rule,) {
+				let _ = writeln!(out, "{COMMAND}: {:?}", e.desc);
+				return Err(e,);
+			}
+			let _ = writeln!(out, "added {rule}");
+		},
+		_ => return usage(out,),
+	}
+	Ok((),)
+}
+
+fn usage(out: &mut impl fmt::Write,) -> Rslt<(), MmioFaultError,> {
+	let fault = "corrupt <mask> | stuck <value> | abort";
+	let rule = "<base> <len> <nth>";
+	let _ = writeln!(out, "usage: {COMMAND} add {rule} {fault} [once]");
+	let _ = writeln!(out, "       {COMMAND} list | clear");
+	Err(oso_err!(MmioFaultError::Usage),)
+}
+
+/// rule of the arguments of `mmiofault add`
+fn parse_rule(
+	base: &str,
+	len: &str,
+	nth: &str,
+	fault: &[&str],
+) -> Option<Rule,> {
+	let (fault, once,) = match fault {
+		[fault @ .., "once",] => (fault, true,),
+		fault => (fault, false,),
+	};
+	let fault = match fault {
+		["corrupt", mask,] => Fault::Corrupt(number(mask,)? as u32,),
+		["stuck", value,] => Fault::Stuck(number(value,)? as u32,),
+		["abort",] => Fault::Abort,
+		_ => return None,
+	};
+	let nth = number(nth,)?;
+	Some(Rule {
+		base: number(base,)? as usize,
+		len: number(len,)? as usize,
+		nth: u32::try_from(nth,).ok().filter(|nth| *nth != 0,)?,
+		once,
+		fault,
+	},)
+}
+
+/// decimal number, or hexadecimal with `0x`
+fn number(s: &str,) -> Option<u64,> {
+	match s.strip_prefix("0x",) {
+		Some(hex,) => u64::from_str_radix(hex, 16,).ok(),
+		None => s.parse().ok(),
+	}
+}
+
+/// [`Slot::state`] of a slot without a rule
+const FREE: u8 = 0;
+/// [`Slot::state`] of a slot whose rule is being written
+const WRITING: u8 = 1;
+/// [`Slot::state`] of a slot whose rule is checked
+const ARMED_SLOT: u8 = 2;
+
+/// rule stored without locks, as it is read on every access
+struct Slot {
+	state:    AtomicU8,
+	base:     AtomicUsize,
+	len:      AtomicUsize,
+	nth:      AtomicU32,
+	/// kind of the fault in the low byte, `once` above
+	kind:     AtomicU8,
+	arg:      AtomicU32,
+	accesses: AtomicU32,
+	hits:     AtomicU32,
+}
+
+impl Slot {
+	const fn new() -> Self {
+		Self {
+			state:    AtomicU8::new(FREE,),
+			base:     AtomicUsize::new(0,),
+			len:      AtomicUsize::new(0,),
+			nth:      AtomicU32::new(0,),
+			kind:     AtomicU8::new(0,),
+			arg:      AtomicU32::new(0,),
+			accesses: AtomicU32::new(0,),
+			hits:     AtomicU32::new(0,),
+		}
+	}
+
+	/// Writes `rule`. Called while the slot is [`WRITING`]
+	fn store(&self, rule: Rule,) {
+		let (kind, arg,) = match rule.fault {
+			Fault::Corrupt(mask,) => (0, mask,),
+			Fault::Stuck(value,) => (1, value,),
+			Fault::Abort => (2, 0,),
+		};
+		self.base.store(rule.base, Ordering::Relaxed,);
+		self.len.store(rule.len, Ordering::Relaxed,);
+		self.nth.store(rule.nth, Ordering::Relaxed,);
+		self.kind.store(kind | (rule.once as u8) << 4, Ordering::Relaxed,);
+		self.arg.store(arg, Ordering::Relaxed,);
+		self.accesses.store(0, Ordering::Relaxed,);
+		self.hits.store(0, Ordering::Relaxed,);
+	}
+
+	/// Rule of the slot, `None` unless it is armed
+	fn load(&self,) -> Option<Rule,> {
+		if self.state.load(Ordering::Acquire,) != ARMED_SLOT {
+			return None;
+		}
+		let kind = self.kind.load(Ordering::Relaxed,);
+		let arg = self.arg.load(Ordering::Relaxed,);
+		let fault = match kind & 0xf {
+			0 => Fault::Corrupt(arg,),
+			1 => Fault::Stuck(arg,),
+			_ => Fault::Abort,
+		};
+		Some(Rule {
+			base: self.base.load(Ordering::Relaxed,),
+			len: self.len.load(Ordering::Relaxed,),
+			nth: self.nth.load(Ordering::Relaxed,),
+			once: kind >> 4 != 0,
+			fault,
+		},)
+	}
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	extern crate std;
+
+	use super::*;
+	use std::string::String;
+	use std::sync::Mutex;
+	use std::sync::MutexGuard;
+
+	/// the rules are global, so tests using them run one at a time
+	static LOCK: Mutex<(),> = Mutex::new((),);
+
+	/// Takes the rules and removes them
+	pub(crate) fn lock() -> MutexGuard<'static, (),> {
+		let guard = LOCK.lock();
+		let guard = guard.unwrap_or_else(|poisoned| poisoned.into_inner(),);
+		clear();
+		guard
+	}
+
+	fn execute(args: &[&str],) -> (Rslt<(), MmioFaultError,>, String,) {
+		let mut out = String::new();
+		let result = run_command(args, &mut out,);
+		(result, out,)
+	}
+
+	fn error(args: &[&str],) -> Option<MmioFaultError,> {
+		execute(args,).0.unwrap_err().desc
+	}
+
+	#[test]
+	fn test_parse_rules() {
+		let rule = parse_rule("0x1000", "8", "3", &["stuck", "0x1", "once",],);
+		let stuck = Rule {
+			base:  0x1000,
+			len:   8,
+			nth:   3,
+			once:  true,
+			fault: Fault::Stuck(1,),
+		};
+		assert_eq!(rule, Some(stuck));
+		let rule = parse_rule("16", "4", "1", &["corrupt", "0xff00",],);
+		let fault = rule.map(|rule| (rule.fault, rule.once,),);
+		assert_eq!(fault, Some((Fault::Corrupt(0xff00), false)));
+		let rule = parse_rule("0", "4", "1", &["abort",],);
+		assert_eq!(rule.map(|rule| rule.fault), Some(Fault::Abort));
+
+		for (nth, fault,) in [
+			("0", &["abort",][..],),
+			("1", &["stuck",],),
+			("1", &["corrupt", "0xg",],),
+			("1", &["abort", "0x1",],),
+			("1", &["once",],),
+			("0x1_0000_0000", &["abort",],),
+		] {
+			assert_eq!(parse_rule("0", "4", nth, fault), None, "{fault:?}");
+		}
+	}
+
+	#[test]
+	fn test_usage() {
+		let _lock = lock();
+		let (result, out,) = execute(&["add", "0", "4",],);
+		assert_eq!(result.unwrap_err().desc, Some(MmioFaultError::Usage));
+		assert!(out.starts_with("usage: mmiofault add <base> <len> <nth>"));
+		let usage = Some(MmioFaultError::Usage,);
+		assert_eq!(error(&["add", "0", "4", "1", "flip",]), usage);
+		assert_eq!(error(&["add", "0", "4", "1", "abort", "twice",]), usage);
+		assert_eq!(error(&[]), usage);
+	}
+
+	#[cfg(not(feature = "fault_injection"))]
+	#[test]
+	fn test_rules_need_the_feature() {
+		let _lock = lock();
+		let (result, out,) = execute(&["add", "0", "4", "1", "abort",],);
+		assert_eq!(result.unwrap_err().desc, Some(MmioFaultError::Disabled));
+		assert_eq!(out, "mmiofault: Some(Disabled)\n");
+		assert_eq!(rules().count(), 0);
+		assert_eq!(hit(0), None);
+	}
+
+	#[cfg(feature = "fault_injection")]
+	#[test]
+	fn test_rules_fire_from_nth_access() {
+		let _lock = lock();
+		let (result, out,) = execute(&["add", "0x100", "8", "2", "abort",],);
+		assert!(result.is_ok());
+		assert_eq!(out, "added 0x100+0x8 from access 2: abort\n");
+		let args = ["add", "0x104", "4", "1", "stuck", "7", "once",];
+		assert!(execute(&args).0.is_ok());
+
+		assert_eq!(hit(0xff), None);
+		assert_eq!(hit(0x108), None);
+		// both rules count the access, the first one firing wins
+		assert_eq!(hit(0x104), Some(Fault::Stuck(7)));
+		assert_eq!(hit(0x104), Some(Fault::Abort));
+		assert_eq!(hit(0x100), Some(Fault::Abort));
+
+		let (result, out,) = execute(&["list",],);
+		assert!(result.is_ok());
+		let mut lines = out.lines();
+		let abort = "0x100+0x8 from access 2: abort, 3 accesses, 2 hits";
+		assert_eq!(lines.next(), Some(abort));
+		let stuck = "0x104+0x4 from access 1: stuck 0x7 once";
+		let stuck = std::format!("{stuck}, 2 accesses, 1 hits");
+		assert_eq!(lines.next(), Some(&*stuck));
+		assert_eq!(lines.next(), None);
+
+		assert_eq!(execute(&["clear",]).1, "rules cleared\n");
+		assert_eq!(hit(0x100), None);
+		assert_eq!(execute(&["list",]).1, "");
+	}
+
+	#[cfg(feature = "fault_injection")]
+	#[test]
+	fn test_too_many_rules() {
+		let _lock = lock();
+		for _ in 0..MAX_RULES {
+			assert!(execute(&["add", "0", "4", "1", "abort",]).0.is_ok());
+		}
+		let (result, out,) = execute(&["add", "0", "4", "1", "abort",],);
+		let full = Some(MmioFaultError::TooManyRules,);
+		assert_eq!(result.unwrap_err().desc, full);
+		assert_eq!(out, "mmiofault: Some(TooManyRules)\n");
+		assert_eq!(rules().count(), MAX_RULES);
+	}
+}
@@ -0,0 +1,96 @@
+//! # QEMU Exit Devices
+//!
+//! Ends QEMU with an exit status chosen by the kernel, so a test run reports
+//! its result through the status of the QEMU process instead of a marker in
+//! the serial output. [`qemu_exit`] uses the device of the architecture:
+//!
+//! - **AArch64**: Arm semihosting `SYS_EXIT` with the extended exit code.
+//!   QEMU needs `-semihosting` and exits with `code`
+//! - **x86_64**: `isa-debug-exit` at port [`DEBUG_EXIT_PORT`]. QEMU needs
+//!   `-device isa-debug-exit,iobase=0xf4,iosize=0x04` and exits with
+//!   `code << 1 | 1`
+//! - **RISC-V**: `sifive_test` at [`SIFIVE_TEST_BASE`] of the `virt` machine.
+//!   QEMU exits with `code`
+//!
+//! Without the device, the call has no effect and the system is powered off
+//! instead. On real hardware the semihosting call of AArch64 faults, so only
+//! call [`qemu_exit`] when the kernel command line asks for it.
+//!
+//! ## Exit Codes
+//!
+//! With `test=exit` on the kernel command line, the kernel exits after the
+//! boot script with [`PASSED`] if every command succeeded and [`FAILED`]
+//! otherwise. `panic=exit` exits with [`PANICKED`] after a panic. `cargo
+//! xtask test` sets both and turns the status into its own.
+
+#[cfg(not(feature = "hosted"))]
+use crate::base::power;
+
+/// Every command of the boot script succeeded
+pub const PASSED: u32 = 0;
+/// A command of the boot script failed, or the script could not be read
+pub const FAILED: u32 = 1;
+/// The kernel panicked
+pub const PANICKED: u32 = 2;
+
+/// I/O port of `isa-debug-exit`
+pub const DEBUG_EXIT_PORT: u16 = 0xf4;
+/// Address of `sifive_test` on the QEMU `virt` machine
+pub const SIFIVE_TEST_BASE: usize = 0x10_0000;
+
+/// Exits QEMU with `code`, or powers off if the device is missing
+#[cfg(not(feature = "hosted"))]
+pub fn qemu_exit(code: u32,) -> ! {
+	#[cfg(target_arch = "aarch64")]
+	semihosting_exit(code,);
+	#[cfg(target_arch = "x86_64")]
+	debug_exit(code,);
+	#[cfg(target_arch = "riscv64")]
+	sifive_test_exit(code,);
+	power::power_off()
+}
+
+#[cfg(feature = "hosted")]
+pub use crate::base::hosted::qemu_exit;
+
+/// `SYS_EXIT` with a parameter block, which carries a code besides the
+/// reason on 64-bit targets
+#[cfg(all(target_arch = "aarch64", not(feature = "hosted")))]
+fn semihosting_exit(code: u32,) {
+	const SYS_EXIT: u64 = 0x18;
+	const ADP_STOPPED_APPLICATION_EXIT: u64 = 0x2_0026;
+
+	let block = [ADP_STOPPED_APPLICATION_EXIT, code as u64,];
+	unsafe {
+		core::arch::asm!(
+			"hlt #0xf000",
+			inout("x0") SYS_EXIT => _,
+			in("x1") block.as_ptr(),
+			options(nostack, readonly),
+		);
+	}
+}
+
+#[cfg(all(target_arch = "x86_64", not(feature = "hosted")))]
+fn debug_exit(code: u32,) {
+	unsafe {
+		core::arch::asm!(
+			"out dx, eax",
+			in("dx") DEBUG_EXIT_PORT,
+			in("eax") code,
+			options(nomem, nostack, preserves_flags),
+		);
+	}
+}
+
+/// Writes `PASS`, or `FAIL` with the code in the upper half
+#[cfg(all(target_arch = "riscv64", not(feature = "hosted")))]
+fn sifive_test_exit(code: u32,) {
+	const FAIL: u32 = 0x3333;
+	const PASS: u32 = 0x5555;
+
+	let value = if code == 0 { PASS } else { FAIL | (code << 16) };
+	unsafe {
+		core::ptr::write_volatile(SIFIVE_TEST_BASE as *mut u32, value,);
+	}
+}
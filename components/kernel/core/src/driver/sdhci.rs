@@ -0,0 +1,576 @@
+//! # SD Host Controller Driver
+//!
+//! Driver of controllers following the SD Host Controller Simplified
+//! Specification, with the card behind it exposed as a [`BlockDevice`].
+//!
+//! ## Devices
+//!
+//! - EMMC2 of the BCM2711, which holds the boot SD card of the Raspberry Pi 4
+//!   at [`Sdhci::PI4_BASE`]
+//!
+//! QEMU does not emulate the Pi 4, and `virt` has virtio-blk instead, so this
+//! driver is for hardware.
+//!
+//! ## Initialization
+//!
+//! The firmware leaves the controller in an unknown state, so [`Sdhci::new`]
+//! resets it and initializes the card again: `CMD0`, `CMD8`, `ACMD41` until
+//! the card is ready, `CMD2`, `CMD3`, `CMD9` for the capacity and `CMD7`.
+//! The card then runs at 25 MHz on a 4 bit bus.
+//!
+//! ## Current Status
+//!
+//! - Data moves through the buffer data port. DMA is not used
+//! - There is no timer, so timeouts are counted in polls of the controller
+//! - SDSC, SDHC and SDXC cards are supported. MMC and SDIO are not
+//!
+//! ```rust,ignore
+//! let mut card = unsafe { Sdhci::new(Sdhci::PI4_BASE, Sdhci::PI4_CLOCK_HZ,)? };
+//! let mut mbr = [0u8; 512];
+//! card.read_blocks(0, &mut mbr,)?;
+//! ```
+
+use super::block::BlockDevice;
+use super::mmio;
+use oso_error::OsoError;
+use oso_error::Rslt;
+use oso_error::kernel::BlockError;
+use oso_error::oso_err;
+
+/// polls of the controller before a command times out
+const POLL_LIMIT: u32 = 1_000_000;
+/// `ACMD41` attempts before the card is given up on
+const OP_COND_ATTEMPTS: u32 = 1_000;
+/// polls between `ACMD41` attempts
+const OP_COND_DELAY: u32 = 10_000;
+
+/// SD card behind an SD host controller
+pub struct Sdhci {
+	base:        usize,
+	clock_hz:    u32,
+	/// relative card address assigned by `CMD3`
+	rca:         u32,
+	/// SDHC and SDXC cards are addressed by block, SDSC cards by byte
+	block_addr:  bool,
+	block_count: u64,
+}
+
+impl Sdhci {
+	/// EMMC2 of the Raspberry Pi 4 in the low peripheral mode
+	pub const PI4_BASE: usize = 0xfe34_0000;
+	/// clock the firmware feeds EMMC2 with
+	pub const PI4_CLOCK_HZ: u32 = 100_000_000;
+	pub const BLOCK_SIZE: usize = 512;
+
+	const BLOCK_SIZE_COUNT: usize = 0x04;
+	const ARGUMENT: usize = 0x08;
+	/// transfer mode in the low half, command in the high half
+	const COMMAND: usize = 0x0c;
+	const RESPONSE: usize = 0x10;
+	const BUFFER: usize = 0x20;
+	const PRESENT_STATE: usize = 0x24;
+	/// host control 1, power control, block gap and wakeup control
+	const HOST_CONTROL: usize = 0x28;
+	/// clock control, timeout control and software reset
+	const CLOCK_CONTROL: usize = 0x2c;
+	/// normal interrupt status in the low half, errors in the high half
+	const INT_STATUS: usize = 0x30;
+	const INT_STATUS_ENABLE: usize = 0x34;
+	const INT_SIGNAL_ENABLE: usize = 0x38;
+
+	const CMD_INHIBIT: u32 = 1 << 0;
+	const DAT_INHIBIT: u32 = 1 << 1;
+
+	const DATA_WIDTH_4: u32 = 1 << 1;
+	/// bus power on at 3.3 V
+	const POWER_3V3: u32 = 0x0f << 8;
+
+	const INTERNAL_CLOCK_ENABLE: u32 = 1 << 0;
+	const INTERNAL_CLOCK_STABLE: u32 = 1 << 1;
+	const SD_CLOCK_ENABLE: u32 = 1 << 2;
+	/// longest data timeout
+	const DATA_TIMEOUT: u32 = 0xe << 16;
+	const RESET_ALL: u32 = 1 << 24;
+	const RESET_CMD: u32 = 1 << 25;
+	const RESET_DAT: u32 = 1 << 26;
+
+	const CMD_COMPLETE: u32 = 1 << 0;
+	const XFER_COMPLETE: u32 = 1 << 1;
+	const BUF_WR_READY: u32 = 1 << 4;
+	const BUF_RD_READY: u32 = 1 << 5;
+	const ERROR: u32 = 1 << 15;
+	const CMD_TIMEOUT: u32 = 1 << 16;
+	const DATA_TIMEOUT_ERROR: u32 = 1 << 20;
+	const ERRORS: u32 = 0xffff << 16;
+
+	const IDENTIFICATION_HZ: u32 = 400_000;
+	const DEFAULT_SPEED_HZ: u32 = 25_000_000;
+
+	/// `CMD8` argument: 2.7-3.6 V and a check pattern echoed by the card
+	const IF_COND: u32 = 0x1aa;
+	/// `ACMD41` argument: 3.2-3.4 V
+	const OCR_VOLTAGE: u32 = 0x0030_0000;
+	const OCR_HCS: u32 = 1 << 30;
+	const OCR_BUSY: u32 = 1 << 31;
+
+	/// Resets the controller and initializes the card in the slot
+	///
+	/// # Errors
+	///
+	/// - [`BlockError::NoMedia`] if no card answers
+	/// - [`BlockError::Unsupported`] if the card does not accept 3.3 V or has
+	///   an unknown CSD structure
+	/// - Errors of commands
+	///
+	/// # Safety
+	///
+	/// `base` must be the mapped registers of an SD host controller, not used
+	/// by anything else. `clock_hz` is the frequency of its base clock
+	pub unsafe fn new(base: usize, clock_hz: u32,) -> Rslt<Self, BlockError,> {
+		let mut card = Self {
+			base,
+			clock_hz,
+			rca: 0,
+			block_addr: false,
+			block_count: 0,
+		};
+		card.reset()?;
+		card.identify()?;
+		Ok(card,)
+	}
+
+	fn reset(&mut self,) -> Rslt<(), BlockError,> {
+		self.write(Self::CLOCK_CONTROL, Self::RESET_ALL,);
+		self.poll(|card| {
+			card.read(Self::CLOCK_CONTROL,) & Self::RESET_ALL == 0
+		},)?;
+		self.write(Self::HOST_CONTROL, Self::POWER_3V3,);
+		self.write(
+			Self::INT_STATUS_ENABLE,
+			Self::ERRORS
+				| Self::CMD_COMPLETE
+				| Self::XFER_COMPLETE
+				| Self::BUF_WR_READY
+				| Self::BUF_RD_READY,
+		);
+		// status is polled, nothing raises an interrupt
+		self.write(Self::INT_SIGNAL_ENABLE, 0,);
+		self.write(Self::INT_STATUS, u32::MAX,);
+		self.set_clock(Self::IDENTIFICATION_HZ,)
+	}
+
+	/// Runs the card at the fastest frequency up to `hz`
+	fn set_clock(&mut self, hz: u32,) -> Rslt<(), BlockError,> {
+		self.poll(|card| {
+			card.read(Self::PRESENT_STATE,)
+				& (Self::CMD_INHIBIT | Self::DAT_INHIBIT)
+				== 0
+		},)?;
+		self.write(Self::CLOCK_CONTROL, 0,);
+
+		// 10 bit divided clock mode: base / (2 * divisor), or base at 0
+		let divisor = self.clock_hz.div_ceil(2 * hz,).min(0x3ff,);
+		let divisor = (divisor & 0xff) << 8 | (divisor >> 8) << 6;
+		let control =
+			Self::DATA_TIMEOUT | divisor | Self::INTERNAL_CLOCK_ENABLE;
+		self.write(Self::CLOCK_CONTROL, control,);
+		self.poll(|card| {
+			card.read(Self::CLOCK_CONTROL,) & Self::INTERNAL_CLOCK_STABLE != 0
+		},)?;
+		self.write(Self::CLOCK_CONTROL, control | Self::SD_CLOCK_ENABLE,);
+		Ok((),)
+	}
+
+	fn identify(&mut self,) -> Rslt<(), BlockError,> {
+		self.command(Command::GO_IDLE_STATE, 0,)
+			.map_err(|_| oso_err!(BlockError::NoMedia),)?;
+
+		// version 1 cards do not know `CMD8`
+		let v2 = match self.command(Command::SEND_IF_COND, Self::IF_COND,) {
+			Ok(echo,) if echo & 0xfff == Self::IF_COND => true,
+			Ok(_,) => return Err(oso_err!(BlockError::Unsupported),),
+			Err(_,) => false,
+		};
+
+		let hcs = if v2 { Self::OCR_HCS } else { 0 };
+		let mut ocr = 0;
+		for _ in 0..OP_COND_ATTEMPTS {
+			ocr = self
+				.app_command(Command::SD_SEND_OP_COND, hcs | Self::OCR_VOLTAGE,)
+				.map_err(|_| oso_err!(BlockError::NoMedia),)?;
+			if ocr & Self::OCR_BUSY != 0 {
+				break;
+			}
+			spin(OP_COND_DELAY,);
+		}
+		if ocr & Self::OCR_BUSY == 0 {
+			return Err(oso_err!(BlockError::NoMedia),);
+		}
+		if ocr & Self::OCR_VOLTAGE == 0 {
+			return Err(oso_err!(BlockError::Unsupported),);
+		}
+		self.block_addr = ocr & Self::OCR_HCS != 0;
+
+		self.command(Command::ALL_SEND_CID, 0,)?;
+		self.rca = self.command(Command::SEND_RELATIVE_ADDR, 0,)? >> 16;
+		self.command(Command::SEND_CSD, self.rca << 16,)?;
+		self.block_count = csd_block_count(self.response128(),)?;
+		self.command(Command::SELECT_CARD, self.rca << 16,)?;
+
+		self.app_command(Command::SET_BUS_WIDTH, 2,)?;
+		let control = self.read(Self::HOST_CONTROL,);
+		self.write(Self::HOST_CONTROL, control | Self::DATA_WIDTH_4,);
+		self.set_clock(Self::DEFAULT_SPEED_HZ,)?;
+		if !self.block_addr {
+			self.command(Command::SET_BLOCKLEN, Self::BLOCK_SIZE as u32,)?;
+		}
+		Ok((),)
+	}
+
+	/// Sends a command without data and returns the first response word
+	fn command(
+		&mut self,
+		command: Command,
+		arg: u32,
+	) -> Rslt<u32, BlockError,> {
+		self.send(command, arg, 0,)?;
+		if command.is_busy() {
+			self.wait(Self::XFER_COMPLETE,)?;
+		}
+		Ok(self.read(Self::RESPONSE,),)
+	}
+
+	fn app_command(
+		&mut self,
+		command: Command,
+		arg: u32,
+	) -> Rslt<u32, BlockError,> {
+		self.command(Command::APP_CMD, self.rca << 16,)?;
+		self.command(command, arg,)
+	}
+
+	/// Issues `command` and waits until it completes. `mode` is the transfer
+	/// mode of data commands
+	fn send(
+		&mut self,
+		command: Command,
+		arg: u32,
+		mode: u32,
+	) -> Rslt<(), BlockError,> {
+		let inhibit = if command.uses_data_line() {
+			Self::CMD_INHIBIT | Self::DAT_INHIBIT
+		} else {
+			Self::CMD_INHIBIT
+		};
+		self.poll(|card| card.read(Self::PRESENT_STATE,) & inhibit == 0,)?;
+		self.write(Self::INT_STATUS, u32::MAX,);
+		self.write(Self::ARGUMENT, arg,);
+		self.write(Self::COMMAND, command.0 << 16 | mode,);
+		self.wait(Self::CMD_COMPLETE,)?;
+		Ok((),)
+	}
+
+	/// Waits for any of `events` and acknowledges them
+	fn wait(&mut self, events: u32,) -> Rslt<(), BlockError,> {
+		for _ in 0..POLL_LIMIT {
+			let status = self.read(Self::INT_STATUS,);
+			if status & Self::ERROR != 0 {
+				return Err(self.recover(status & Self::ERRORS,),);
+			}
+			if status & events != 0 {
+				self.write(Self::INT_STATUS, status & events,);
+				return Ok((),);
+			}
+			core::hint::spin_loop();
+		}
+		let _ = self.recover(0,);
+		Err(oso_err!(BlockError::Timeout),)
+	}
+
+	/// Resets the command and data lines after an error
+	fn recover(&mut self, errors: u32,) -> OsoError<BlockError,> {
+		self.write(Self::INT_STATUS, u32::MAX,);
+		let control = self.read(Self::CLOCK_CONTROL,);
+		let reset = Self::RESET_CMD | Self::RESET_DAT;
+		self.write(Self::CLOCK_CONTROL, control | reset,);
+		let _ = self.poll(|card| card.read(Self::CLOCK_CONTROL,) & reset == 0,);
+
+		if errors & (Self::CMD_TIMEOUT | Self::DATA_TIMEOUT_ERROR) != 0 {
+			oso_err!(BlockError::Timeout)
+		} else {
+			oso_err!(BlockError::Device { status: errors >> 16 })
+		}
+	}
+
+	fn poll(&self, done: impl Fn(&Self,) -> bool,) -> Rslt<(), BlockError,> {
+		for _ in 0..POLL_LIMIT {
+			if done(self,) {
+				return Ok((),);
+			}
+			core::hint::spin_loop();
+		}
+		Err(oso_err!(BlockError::Timeout),)
+	}
+
+	/// 136 bit response without the CRC, which the controller drops
+	fn response128(&self,) -> u128 {
+		(0..4).fold(0, |response, i| {
+			response | (self.read(Self::RESPONSE + i * 4,) as u128) << (i * 32)
+		},)
+	}
+
+	/// Moves `count` blocks between the card and `data`
+	fn transfer(
+		&mut self,
+		lba: u64,
+		count: u64,
+		mut data: Data,
+	) -> Rslt<(), BlockError,> {
+		// the block count register is 16 bits wide
+		let mut done = 0;
+		while done < count {
+			let blocks = (count - done).min(u16::MAX as u64,);
+			let lba = lba + done;
+			let addr = if self.block_addr {
+				lba
+			} else {
+				lba * Self::BLOCK_SIZE as u64
+			};
+			let multiple = blocks > 1;
+			let (command, mode,) = match (&data, multiple,) {
+				(Data::Read(_,), false,) => {
+					(Command::READ_SINGLE_BLOCK, TransferMode::READ,)
+				},
+				(Data::Read(_,), true,) => {
+					(Command::READ_MULTIPLE_BLOCK, TransferMode::READ_MULTIPLE,)
+				},
+				(Data::Write(_,), false,) => {
+					(Command::WRITE_BLOCK, TransferMode::WRITE,)
+				},
+				(Data::Write(_,), true,) => (
+					Command::WRITE_MULTIPLE_BLOCK,
+					TransferMode::WRITE_MULTIPLE,
+				),
+			};
+
+			let size_count = (blocks as u32) << 16 | Self::BLOCK_SIZE as u32;
+			self.write(Self::BLOCK_SIZE_COUNT, size_count,);
+			self.send(command, addr as u32, mode,)?;
+			for block in done..done + blocks {
+				let start = block as usize * Self::BLOCK_SIZE;
+				let range = start..start + Self::BLOCK_SIZE;
+				match &mut data {
+					Data::Read(buf,) => {
+						self.wait(Self::BUF_RD_READY,)?;
+						for word in buf[range].chunks_exact_mut(4,) {
+							let value = self.read(Self::BUFFER,);
+							word.copy_from_slice(&value.to_le_bytes(),);
+						}
+					},
+					Data::Write(buf,) => {
+						self.wait(Self::BUF_WR_READY,)?;
+						for word in buf[range].chunks_exact(4,) {
+							let value = u32::from_le_bytes([
+								word[0], word[1], word[2], word[3],
+							],);
+							self.write(Self::BUFFER, value,);
+						}
+					},
+				}
+			}
+			self.wait(Self::XFER_COMPLETE,)?;
+			done += blocks;
+		}
+		Ok((),)
+	}
+
+	fn read(&self, offset: usize,) -> u32 {
+		unsafe { read32(self.base, offset,) }
+	}
+
+	fn write(&self, offset: usize, value: u32,) {
+		unsafe { write32(self.base, offset, value,) }
+	}
+}
+
+impl BlockDevice for Sdhci {
+	fn block_size(&self,) -> usize {
+		Self::BLOCK_SIZE
+	}
+
+	fn block_count(&self,) -> u64 {
+		self.block_count
+	}
+
+	fn read_blocks(
+		&mut self,
+		lba: u64,
+		buf: &mut [u8],
+	) -> Rslt<(), BlockError,> {
+		let count = self.check_request(lba, buf.len(),)?;
+		self.transfer(lba, count, Data::Read(buf,),)
+	}
+
+	fn write_blocks(&mut self, lba: u64, buf: &[u8],) -> Rslt<(), BlockError,> {
+		let count = self.check_request(lba, buf.len(),)?;
+		self.transfer(lba, count, Data::Write(buf,),)
+	}
+}
+
+enum Data<'a,> {
+	Read(&'a mut [u8],),
+	Write(&'a [u8],),
+}
+
+/// Value of the command register: index and response type
+#[derive(Clone, Copy,)]
+struct Command(u32,);
+
+impl Command {
+	const NO_RESPONSE: u32 = 0b00;
+	const RESPONSE_136: u32 = 0b01;
+	const RESPONSE_48: u32 = 0b10;
+	const RESPONSE_48_BUSY: u32 = 0b11;
+	const CRC_CHECK: u32 = 1 << 3;
+	const INDEX_CHECK: u32 = 1 << 4;
+	const DATA_PRESENT: u32 = 1 << 5;
+
+	const R1: u32 = Self::RESPONSE_48 | Self::CRC_CHECK | Self::INDEX_CHECK;
+	const R1B: u32 =
+		Self::RESPONSE_48_BUSY | Self::CRC_CHECK | Self::INDEX_CHECK;
+	const R2: u32 = Self::RESPONSE_136 | Self::CRC_CHECK;
+	const R3: u32 = Self::RESPONSE_48;
+
+	const GO_IDLE_STATE: Self = Self::new(0, Self::NO_RESPONSE,);
+	const ALL_SEND_CID: Self = Self::new(2, Self::R2,);
+	/// response R6
+	const SEND_RELATIVE_ADDR: Self = Self::new(3, Self::R1,);
+	const SET_BUS_WIDTH: Self = Self::new(6, Self::R1,);
+	const SELECT_CARD: Self = Self::new(7, Self::R1B,);
+	/// response R7
+	const SEND_IF_COND: Self = Self::new(8, Self::R1,);
+	const SEND_CSD: Self = Self::new(9, Self::R2,);
+	const SET_BLOCKLEN: Self = Self::new(16, Self::R1,);
+	const READ_SINGLE_BLOCK: Self =
+		Self::new(17, Self::R1 | Self::DATA_PRESENT,);
+	const READ_MULTIPLE_BLOCK: Self =
+		Self::new(18, Self::R1 | Self::DATA_PRESENT,);
+	const WRITE_BLOCK: Self = Self::new(24, Self::R1 | Self::DATA_PRESENT,);
+	const WRITE_MULTIPLE_BLOCK: Self =
+		Self::new(25, Self::R1 | Self::DATA_PRESENT,);
+	const SD_SEND_OP_COND: Self = Self::new(41, Self::R3,);
+	const APP_CMD: Self = Self::new(55, Self::R1,);
+
+	const fn new(index: u32, flags: u32,) -> Self {
+		Self(index << 8 | flags,)
+	}
+
+	const fn is_busy(self,) -> bool {
+		self.0 & 0b11 == Self::RESPONSE_48_BUSY
+	}
+
+	const fn uses_data_line(self,) -> bool {
+		self.is_busy() || self.0 & Self::DATA_PRESENT != 0
+	}
+}
+
+/// Values of the transfer mode register
+struct TransferMode;
+
+impl TransferMode {
+	const BLOCK_COUNT_ENABLE: u32 = 1 << 1;
+	/// stop multiple block transfers with `CMD12` automatically
+	const AUTO_CMD12: u32 = 1 << 2;
+	const DIRECTION_READ: u32 = 1 << 4;
+	const MULTIPLE_BLOCK: u32 = 1 << 5;
+
+	const WRITE: u32 = Self::BLOCK_COUNT_ENABLE;
+	const WRITE_MULTIPLE: u32 =
+		Self::WRITE | Self::AUTO_CMD12 | Self::MULTIPLE_BLOCK;
+	const READ: u32 = Self::WRITE | Self::DIRECTION_READ;
+	const READ_MULTIPLE: u32 = Self::WRITE_MULTIPLE | Self::DIRECTION_READ;
+}
+
+/// Capacity in 512 byte blocks from the CSD register
+///
+/// `csd` is the response of `CMD9`, which lacks the lowest 8 bits of the
+/// register, so bit `n` of the CSD is bit `n - 8` of `csd`.
+fn csd_block_count(csd: u128,) -> Rslt<u64, BlockError,> {
+	let field = |high: u32, low: u32| {
+		(csd >> (low - 8)) as u64 & ((1 << (high - low + 1)) - 1)
+	};
+	match field(127, 126,) {
+		// SDSC: (C_SIZE + 1) * 2^(C_SIZE_MULT + 2) blocks of 2^READ_BL_LEN
+		0 => {
+			let c_size = field(73, 62,);
+			let mult = field(49, 47,);
+			let read_bl_len = field(83, 80,);
+			let bytes = (c_size + 1) << (mult + 2 + read_bl_len);
+			Ok(bytes / Sdhci::BLOCK_SIZE as u64,)
+		},
+		// SDHC and SDXC: (C_SIZE + 1) * 512 KiB
+		1 => Ok((field(69, 48,) + 1) * 1024,),
+		_ => Err(oso_err!(BlockError::Unsupported),),
+	}
+}
+
+fn spin(polls: u32,) {
+	for _ in 0..polls {
+		core::hint::spin_loop();
+	}
+}
+
+unsafe fn read32(base: usize, offset: usize,) -> u32 {
+	unsafe { mmio::read32(base + offset,) }
+}
+
+unsafe fn write32(base: usize, offset: usize, value: u32,) {
+	unsafe { mmio::write32(base + offset, value,) }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// `CMD9` response with CSD `fields` of high bit, low bit and value, and
+	/// a transfer speed of 25 MHz
+	fn csd(fields: &[(u32, u32, u128,)],) -> u128 {
+		let tran_speed = 0x32 << (96 - 8);
+		fields.iter().fold(tran_speed, |csd, &(high, low, value,)| {
+			assert!(value < 1 << (high - low + 1));
+			csd | value << (low - 8)
+		},)
+	}
+
+	#[test]
+	fn test_csd_v1() {
+		// CSD_STRUCTURE 0, 1 GiB in blocks of 512 bytes
+		let v1 = csd(&[(83, 80, 9,), (73, 62, 4095,), (49, 47, 7,),],);
+		assert_eq!(csd_block_count(v1,).unwrap(), 2 * 1024 * 1024);
+		// 2 GiB, blocks of 1024 bytes
+		let v1 = csd(&[(83, 80, 10,), (73, 62, 4095,), (49, 47, 7,),],);
+		assert_eq!(csd_block_count(v1,).unwrap(), 4 * 1024 * 1024);
+		// smallest
+		let v1 = csd(&[(83, 80, 9,),],);
+		assert_eq!(csd_block_count(v1,).unwrap(), 4);
+	}
+
+	#[test]
+	fn test_csd_v2() {
+		// 8 GB card
+		let v2 = csd(&[(127, 126, 1,), (69, 48, 15159,),],);
+		assert_eq!(csd_block_count(v2,).unwrap(), 15160 * 1024);
+		// largest C_SIZE, 2 TiB
+		let v2 = csd(&[(127, 126, 1,), (69, 48, 0x3f_ffff,),],);
+		assert_eq!(csd_block_count(v2,).unwrap(), 1 << 32);
+	}
+
+	#[test]
+	fn test_csd_unsupported_structure() {
+		for structure in [2, 3,] {
+			let csd = csd(&[(127, 126, structure,), (69, 48, 15159,),],);
+			let error = csd_block_count(csd,).unwrap_err().desc;
+			assert_eq!(error, Some(BlockError::Unsupported));
+		}
+	}
+}
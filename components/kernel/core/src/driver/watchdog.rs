@@ -0,0 +1,581 @@
+//! # Watchdog Timer Drivers
+//!
+//! A watchdog resets the machine unless it is patted before its timeout. The
+//! kernel pats the watchdog given to [`install`] from the timer tick, so a
+//! wedged scheduler or interrupt path stops the pats and the reset brings the
+//! machine back, where the boot logic can fall back to another image.
+//!
+//! ## Devices
+//!
+//! - [`Sp805`]: Arm watchdog module found on Arm boards
+//! - [`GenericWatchdog`]: SBSA generic watchdog of Arm servers and QEMU
+//!   `sbsa-ref`. QEMU `virt` has no watchdog
+//! - [`I6300esb`]: Intel 6300ESB, the watchdog QEMU emulates on x86_64
+//!
+//! Every device counts in two stages. The first stage raises an interrupt,
+//! the second resets. Drivers split the timeout evenly between the stages,
+//! so the watchdog interrupt handler has half the timeout to call [`check`],
+//! which panics and leaves a crash dump before the reset.
+//!
+//! ## Shell
+//!
+//! [`run_command`] implements the `watchdog` shell command on the installed
+//! watchdog, [`run_command_on`] on any:
+//!
+//! - `watchdog`: Prints whether the watchdog is armed and its timeout
+//! - `watchdog arm <ms>`: Arms the watchdog, or changes the timeout of an
+//!   armed one
+//! - `watchdog disarm`: Stops the watchdog
+//!
+//! ## Current Status
+//!
+//! No driver finds the watchdog at boot, so nothing is installed unless a
+//! caller does. The kernel has no interrupt controller driver yet, so
+//! [`check`] is not called, and the boot loader has no fallback image.
+//!
+//! ```rust,ignore
+//! static mut WDT: Option<Sp805> = None;
+//!
+//! let wdt = unsafe { Sp805::new(0x1c0f_0000, 24_000_000,) };
+//! watchdog::install(unsafe { (*&raw mut WDT).insert(wdt,) },);
+//! // `watchdog arm 10000` in the shell, then every timer tick
+//! watchdog::pat();
+//! ```
+
+use super::mmio;
+use crate::base::sched::disable_interrupts;
+use crate::base::sched::restore_interrupts;
+use core::cell::UnsafeCell;
+use core::fmt;
+use oso_error::Rslt;
+use oso_error::kernel::WatchdogError;
+use oso_error::oso_err;
+
+/// Name of the shell command handled by [`run_command`]
+pub const COMMAND: &str = "watchdog";
+
+static INSTALLED: Installed = Installed(UnsafeCell::new(None,),);
+
+/// only accessed with interrupts disabled on a single core
+struct Installed(UnsafeCell<Option<&'static mut dyn Watchdog,>,>,);
+
+unsafe impl Sync for Installed {}
+
+/// Common interface of watchdog devices
+pub trait Watchdog {
+	/// Shortest and longest timeout in milliseconds the device can count
+	fn timeout_range(&self,) -> (u32, u32,);
+
+	/// Starts the watchdog, or restarts an armed one with a new timeout
+	fn arm(&mut self, timeout_ms: u32,) -> Rslt<(), WatchdogError,>;
+
+	fn disarm(&mut self,);
+
+	/// Restarts the countdown. Called from the timer tick
+	fn pat(&mut self,);
+
+	/// Timeout in milliseconds if armed
+	fn timeout(&self,) -> Option<u32,>;
+
+	/// Whether the first stage has expired, so the next expiry resets
+	fn is_expired(&self,) -> bool;
+
+	/// Fails if the device can not count `timeout_ms`
+	fn check_timeout(&self, timeout_ms: u32,) -> Rslt<(), WatchdogError,> {
+		let (min_ms, max_ms,) = self.timeout_range();
+		if timeout_ms < min_ms || timeout_ms > max_ms {
+			return Err(oso_err!(WatchdogError::TimeoutOutOfRange {
+				min_ms,
+				max_ms
+			}),);
+		}
+		Ok((),)
+	}
+}
+
+/// Panics if the first stage of `watchdog` has expired
+///
+/// Called from the watchdog interrupt handler, so a hang leaves a crash dump
+/// instead of only a reset.
+pub fn check(watchdog: &(impl Watchdog + ?Sized),) {
+	if watchdog.is_expired() {
+		let timeout = watchdog.timeout().unwrap_or(0,);
+		panic!("watchdog expired: no pat within {}ms", timeout / 2);
+	}
+}
+
+/// Makes `watchdog` the one [`pat`] and [`run_command`] use, replacing the
+/// installed one
+pub fn install(watchdog: &'static mut dyn Watchdog,) {
+	installed(|installed| *installed = Some(watchdog,),);
+}
+
+/// Pats the installed watchdog if it is armed. Called from the timer tick
+pub fn pat() {
+	installed(|installed| {
+		if let Some(watchdog,) = installed
+			&& watchdog.timeout().is_some()
+		{
+			watchdog.pat();
+		}
+	},);
+}
+
+/// Runs the `watchdog` shell command on the installed watchdog
+///
+/// # Errors
+///
+/// [`WatchdogError::NoDevice`] if none is installed, and the errors of
+/// [`run_command_on`]
+pub fn run_command(
+	args: &[&str],
+	out: &mut impl fmt::Write,
+) -> Rslt<(), WatchdogError,> {
+	installed(|installed| match installed {
+		Some(watchdog,) => run_command_on(*watchdog, args, out,),
+		None => {
+			let _ = writeln!(out, "{COMMAND}: no watchdog installed");
+			Err(oso_err!(WatchdogError::NoDevice),)
+		},
+	},)
+}
+
+/// Runs the `watchdog` shell command with the arguments after its name
+pub fn run_command_on(
+	watchdog: &mut (impl Watchdog + ?Sized),
+	args: &[&str],
+	out: &mut impl fmt::Write,
+) -> Rslt<(), WatchdogError,> {
+	match args {
+		[] => {
+			let (min_ms, max_ms,) = watchdog.timeout_range();
+			let _ = match watchdog.timeout() {
+				Some(ms,) => writeln!(out, "armed, timeout {ms}ms"),
+				None => writeln!(out, "disarmed"),
+			};
+			let _ = writeln!(out, "timeout range {min_ms}ms..={max_ms}ms");
+		},
+		["arm", ms,] => {
+			let Ok(ms,) = ms.parse() else {
+				return Err(oso_err!(WatchdogError::Usage),);
+			};
+			watchdog.arm(ms,)?;
+			let _ = writeln!(out, "armed, timeout {ms}ms");
+		},
+		["disarm",] => {
+			watchdog.disarm();
+			let _ = writeln!(out, "disarmed");
+		},
+		_ => {
+			let _ = writeln!(out, "usage: {COMMAND} [arm <ms> | disarm]");
+			return Err(oso_err!(WatchdogError::Usage),);
+		},
+	}
+	Ok((),)
+}
+
+/// Arm SP805 watchdog module
+///
+/// The counter reloads from `WdogLoad` at each expiry. The first expiry
+/// raises the interrupt, the second resets if the interrupt was not cleared.
+/// Clearing the interrupt is the pat.
+pub struct Sp805 {
+	base:     usize,
+	clock_hz: u32,
+	timeout:  Option<u32,>,
+}
+
+impl Sp805 {
+	const LOAD: usize = 0x000;
+	const CONTROL: usize = 0x008;
+	const INT_CLR: usize = 0x00c;
+	const RIS: usize = 0x010;
+	const LOCK: usize = 0xc00;
+
+	const INTEN: u32 = 1 << 0;
+	const RESEN: u32 = 1 << 1;
+	const UNLOCK: u32 = 0x1acc_e551;
+
+	/// # Safety
+	///
+	/// `base` must be the mapped register frame of an SP805 clocked at
+	/// `clock_hz`, not used by anything else
+	pub unsafe fn new(base: usize, clock_hz: u32,) -> Self {
+		Self { base, clock_hz, timeout: None, }
+	}
+
+	fn unlocked(&mut self, f: impl FnOnce(&mut Self,),) {
+		unsafe { write32(self.base, Self::LOCK, Self::UNLOCK,) };
+		f(self,);
+		unsafe { write32(self.base, Self::LOCK, 0,) };
+	}
+}
+
+impl Watchdog for Sp805 {
+	fn timeout_range(&self,) -> (u32, u32,) {
+		stage_range(self.clock_hz as u64, u32::MAX as u64,)
+	}
+
+	fn arm(&mut self, timeout_ms: u32,) -> Rslt<(), WatchdogError,> {
+		self.check_timeout(timeout_ms,)?;
+		let load = stage_ticks(self.clock_hz as u64, timeout_ms,) as u32;
+		self.unlocked(|wdt| unsafe {
+			write32(wdt.base, Self::LOAD, load,);
+			write32(wdt.base, Self::INT_CLR, 0,);
+			write32(wdt.base, Self::CONTROL, Self::INTEN | Self::RESEN,);
+		},);
+		self.timeout = Some(timeout_ms,);
+		Ok((),)
+	}
+
+	fn disarm(&mut self,) {
+		self.unlocked(|wdt| unsafe { write32(wdt.base, Self::CONTROL, 0,) },);
+		self.timeout = None;
+	}
+
+	fn pat(&mut self,) {
+		// any write clears the interrupt and reloads the counter
+		self.unlocked(|wdt| unsafe { write32(wdt.base, Self::INT_CLR, 0,) },);
+	}
+
+	fn timeout(&self,) -> Option<u32,> {
+		self.timeout
+	}
+
+	fn is_expired(&self,) -> bool {
+		unsafe { read32(self.base, Self::RIS,) & 1 != 0 }
+	}
+}
+
+/// SBSA generic watchdog
+///
+/// The control frame sets the offset `WOR` counted by the system counter.
+/// Signal `WS0` is raised when it elapses, and `WS1` resets after it elapses
+/// once more. Writing the refresh frame is the pat.
+pub struct GenericWatchdog {
+	control:  usize,
+	refresh:  usize,
+	clock_hz: u64,
+	timeout:  Option<u32,>,
+}
+
+impl GenericWatchdog {
+	const WCS: usize = 0x000;
+	const WOR: usize = 0x008;
+	const WRR: usize = 0x000;
+
+	const EN: u32 = 1 << 0;
+	const WS0: u32 = 1 << 1;
+
+	/// # Safety
+	///
+	/// `control` and `refresh` must be the mapped frames of a generic
+	/// watchdog, not used by anything else. `clock_hz` is the frequency of
+	/// the system counter, `CNTFRQ_EL0`
+	pub unsafe fn new(control: usize, refresh: usize, clock_hz: u64,) -> Self {
+		Self { control, refresh, clock_hz, timeout: None, }
+	}
+}
+
+impl Watchdog for GenericWatchdog {
+	fn timeout_range(&self,) -> (u32, u32,) {
+		stage_range(self.clock_hz, u32::MAX as u64,)
+	}
+
+	fn arm(&mut self, timeout_ms: u32,) -> Rslt<(), WatchdogError,> {
+		self.check_timeout(timeout_ms,)?;
+		let offset = stage_ticks(self.clock_hz, timeout_ms,) as u32;
+		unsafe {
+			write32(self.control, Self::WOR, offset,);
+			write32(self.control, Self::WCS, Self::EN,);
+		}
+		self.pat();
+		self.timeout = Some(timeout_ms,);
+		Ok((),)
+	}
+
+	fn disarm(&mut self,) {
+		unsafe { write32(self.control, Self::WCS, 0,) };
+		self.timeout = None;
+	}
+
+	fn pat(&mut self,) {
+		unsafe { write32(self.refresh, Self::WRR, 0,) };
+	}
+
+	fn timeout(&self,) -> Option<u32,> {
+		self.timeout
+	}
+
+	fn is_expired(&self,) -> bool {
+		unsafe { read32(self.control, Self::WCS,) & Self::WS0 != 0 }
+	}
+}
+
+/// PCI configuration space of a single function
+pub trait ConfigSpace {
+	fn write_u8(&mut self, offset: u16, value: u8,);
+	fn write_u16(&mut self, offset: u16, value: u16,);
+}
+
+/// Intel 6300ESB watchdog, a PCI function with a memory BAR
+///
+/// Timer 1 raises the interrupt, timer 2 then resets. Both count a clock of
+/// about 1 kHz, so a stage of `secs` seconds is preloaded with `secs << 10`
+/// and each stage of a timeout with half that, `secs << 9` as Linux does.
+/// Registers only take writes right after an unlock sequence written to the
+/// reload register.
+pub struct I6300esb<C: ConfigSpace,> {
+	base:    usize,
+	config:  C,
+	timeout: Option<u32,>,
+}
+
+impl<C: ConfigSpace,> I6300esb<C,> {
+	const TIMER1: usize = 0x00;
+	const TIMER2: usize = 0x04;
+	const GINTSR: usize = 0x08;
+	const RELOAD: usize = 0x0c;
+
+	const CONFIG_REG: u16 = 0x60;
+	const LOCK_REG: u16 = 0x68;
+
+	/// interrupts of timer 1 disabled, as in QEMU they are not delivered
+	const INTTYPE_NONE: u16 = 0x03;
+	const ENABLE: u8 = 0x02;
+	const RELOAD_KEY: u16 = 0x100;
+	const UNLOCK: [u16; 2] = [0x80, 0x86,];
+	const MAX_TICKS: u64 = 0xf_ffff;
+	const TICKS_PER_SEC: u64 = 1024;
+
+	/// # Safety
+	///
+	/// `base` must be the mapped memory BAR of the function whose
+	/// configuration space is `config`, not used by anything else
+	pub unsafe fn new(base: usize, mut config: C,) -> Self {
+		config.write_u16(Self::CONFIG_REG, Self::INTTYPE_NONE,);
+		Self { base, config, timeout: None, }
+	}
+
+	fn unlock(&self,) {
+		for key in Self::UNLOCK {
+			unsafe { write16(self.base, Self::RELOAD, key,) };
+		}
+	}
+}
+
+impl<C: ConfigSpace,> Watchdog for I6300esb<C,> {
+	fn timeout_range(&self,) -> (u32, u32,) {
+		stage_range(Self::TICKS_PER_SEC, Self::MAX_TICKS,)
+	}
+
+	fn arm(&mut self, timeout_ms: u32,) -> Rslt<(), WatchdogError,> {
+		self.check_timeout(timeout_ms,)?;
+		let ticks = stage_ticks(Self::TICKS_PER_SEC, timeout_ms,);
+		for timer in [Self::TIMER1, Self::TIMER2,] {
+			self.unlock();
+			unsafe { write32(self.base, timer, ticks as u32,) };
+		}
+		self.pat();
+		self.config.write_u8(Self::LOCK_REG, Self::ENABLE,);
+		self.timeout = Some(timeout_ms,);
+		Ok((),)
+	}
+
+	fn disarm(&mut self,) {
+		self.config.write_u8(Self::LOCK_REG, 0,);
+		self.timeout = None;
+	}
+
+	fn pat(&mut self,) {
+		self.unlock();
+		unsafe { write16(self.base, Self::RELOAD, Self::RELOAD_KEY,) };
+	}
+
+	fn timeout(&self,) -> Option<u32,> {
+		self.timeout
+	}
+
+	fn is_expired(&self,) -> bool {
+		unsafe { read32(self.base, Self::GINTSR,) & 1 != 0 }
+	}
+}
+
+/// Runs `f` on the installed watchdog with interrupts disabled
+fn installed<R,>(
+	f: impl FnOnce(&mut Option<&'static mut dyn Watchdog,>,) -> R,
+) -> R {
+	let flags = disable_interrupts();
+	let r = f(unsafe { &mut *INSTALLED.0.get() },);
+	restore_interrupts(flags,);
+	r
+}
+
+/// Ticks of a clock at `clock_hz` in one stage, half of `timeout_ms`
+fn stage_ticks(clock_hz: u64, timeout_ms: u32,) -> u64 {
+	clock_hz * timeout_ms as u64 / 1000 / 2
+}
+
+/// Timeout range of two stages which count up to `max_ticks` each
+fn stage_range(clock_hz: u64, max_ticks: u64,) -> (u32, u32,) {
+	// at least one tick per stage
+	let min = 2000u64.div_ceil(clock_hz.max(1,),);
+	let max = max_ticks * 2000 / clock_hz.max(1,);
+	(min as u32, max.min(u32::MAX as u64,) as u32,)
+}
+
+unsafe fn read32(base: usize, offset: usize,) -> u32 {
+	unsafe { mmio::read32(base + offset,) }
+}
+
+unsafe fn write32(base: usize, offset: usize, value: u32,) {
+	unsafe { mmio::write32(base + offset, value,) }
+}
+
+unsafe fn write16(base: usize, offset: usize, value: u16,) {
+	unsafe { mmio::write16(base + offset, value,) }
+}
+
+#[cfg(test)]
+mod tests {
+	extern crate std;
+
+	use super::*;
+	use crate::app::shell;
+	use core::sync::atomic::AtomicU32;
+	use core::sync::atomic::Ordering;
+	use std::boxed::Box;
+	use std::string::String;
+	use std::vec::Vec;
+
+	/// pats of every [`Counter`]
+	static PATS: AtomicU32 = AtomicU32::new(0,);
+
+	/// watchdog counting its pats in [`PATS`]
+	struct Counter {
+		timeout: Option<u32,>,
+	}
+
+	impl Watchdog for Counter {
+		fn timeout_range(&self,) -> (u32, u32,) {
+			(10, 1000,)
+		}
+
+		fn arm(&mut self, timeout_ms: u32,) -> Rslt<(), WatchdogError,> {
+			self.check_timeout(timeout_ms,)?;
+			self.timeout = Some(timeout_ms,);
+			Ok((),)
+		}
+
+		fn disarm(&mut self,) {
+			self.timeout = None;
+		}
+
+		fn pat(&mut self,) {
+			PATS.fetch_add(1, Ordering::Relaxed,);
+		}
+
+		fn timeout(&self,) -> Option<u32,> {
+			self.timeout
+		}
+
+		fn is_expired(&self,) -> bool {
+			false
+		}
+	}
+
+	fn execute(line: &str,) -> (bool, String,) {
+		let mut out = String::new();
+		let ok = shell::execute(line, &mut out,).is_ok();
+		(ok, out,)
+	}
+
+	#[test]
+	fn test_installed_watchdog() {
+		let mut out = String::new();
+		let none = run_command(&[], &mut out,).unwrap_err().desc;
+		assert_eq!(none, Some(WatchdogError::NoDevice));
+		assert_eq!(out, "watchdog: no watchdog installed\n");
+
+		install(Box::leak(Box::new(Counter { timeout: None, },),),);
+		let pats = || PATS.load(Ordering::Relaxed,);
+		pat();
+		assert_eq!(pats(), 0, "disarmed watchdogs are not patted");
+
+		assert_eq!(execute("watchdog arm 5"), (false, String::new()));
+		let armed = String::from("armed, timeout 100ms\n",);
+		assert_eq!(execute("watchdog arm 100"), (true, armed.clone()));
+		let status = armed + "timeout range 10ms..=1000ms\n";
+		assert_eq!(execute("watchdog"), (true, status));
+		pat();
+		pat();
+		assert_eq!(pats(), 2);
+
+		let disarmed = String::from("disarmed\n",);
+		assert_eq!(execute("watchdog disarm"), (true, disarmed));
+		pat();
+		assert_eq!(pats(), 2);
+		let usage = String::from("usage: watchdog [arm <ms> | disarm]\n",);
+		assert_eq!(execute("watchdog pat"), (false, usage));
+	}
+	/// configuration space recording its writes as offset and value
+	#[derive(Default,)]
+	struct Config(Vec<(u16, u16,),>,);
+
+	impl ConfigSpace for Config {
+		fn write_u8(&mut self, offset: u16, value: u8,) {
+			self.0.push((offset, value as u16,),);
+		}
+
+		fn write_u16(&mut self, offset: u16, value: u16,) {
+			self.0.push((offset, value,),);
+		}
+	}
+
+	#[test]
+	fn test_i6300esb_stages_split_the_timeout() {
+		let frame = Box::leak(Box::new([0u32; 4],),);
+		let base = frame.as_mut_ptr() as usize;
+		let mut wdt = unsafe { I6300esb::new(base, Config::default(),) };
+		assert_eq!(wdt.timeout_range(), (2, 2_047_998));
+
+		wdt.arm(10_000,).unwrap();
+		// 5 seconds of the 1 kHz clock in each stage, `10 << 9` as in Linux
+		assert_eq!(frame[..2], [10 << 9, 10 << 9]);
+		let lock = (I6300esb::<Config,>::LOCK_REG, 2,);
+		assert_eq!(wdt.config.0.last(), Some(&lock));
+		assert!(wdt.arm(1,).is_err());
+	}
+
+	#[cfg(feature = "fault_injection")]
+	#[test]
+	fn test_injected_faults_reach_sp805() {
+		use crate::driver::mmio::fault;
+		use std::format;
+		use std::panic::AssertUnwindSafe;
+		use std::panic::catch_unwind;
+
+		let _lock = fault::tests::lock();
+		let frame = Box::leak(Box::new([0u32; 0x400],),);
+		let base = frame.as_mut_ptr() as usize;
+		let mut wdt = unsafe { Sp805::new(base, 1000,) };
+		wdt.arm(100,).unwrap();
+		check(&wdt,);
+
+		// the raw interrupt status reads raised as if no pat came in time
+		let ris = base + Sp805::RIS;
+		let (ok, _,) = execute(&format!("mmiofault add {ris:#x} 4 1 stuck 1"),);
+		assert!(ok);
+		let expired = catch_unwind(|| check(&wdt,),).unwrap_err();
+		let message = expired.downcast::<String,>().unwrap();
+		assert_eq!(*message, "watchdog expired: no pat within 50ms");
+
+		let lock = base + Sp805::LOCK;
+		fault::clear();
+		let (ok, _,) = execute(&format!("mmiofault add {lock:#x} 4 1 abort"),);
+		assert!(ok);
+		let pat = catch_unwind(AssertUnwindSafe(|| wdt.pat(),),).unwrap_err();
+		let message = pat.downcast::<String,>().unwrap();
+		assert_eq!(*message, format!("mmio: injected fault writing {lock:#x}"));
+	}
+}
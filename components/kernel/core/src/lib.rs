@@ -19,11 +19,27 @@
 //!
 //! ## Architecture
 //!
-//! The kernel is organized into three main modules:
+//! The kernel is organized into four main modules:
 //!
 //! - [`app`]: Application execution and management subsystem
 //! - [`base`]: Core kernel functionality and basic data structures
 //! - [`driver`]: Hardware device drivers and low-level hardware abstraction
+//! - [`vfs`]: File systems behind one path API
+//!
+//! ## Boot Protocols
+//!
+//! Besides oso_loader, the kernel can be started by other boot loaders. Their
+//! entry points are enabled by feature flags, see `compat`:
+//!
+//! - `multiboot2`: GRUB and other Multiboot2 loaders on x86_64
+//! - `limine`: the Limine boot protocol
+//!
+//! ## Host Simulator
+//!
+//! The `hosted` feature builds the kernel for a process on the host, with
+//! the privileged instructions replaced, see `base::hosted`. The `sim`
+//! feature adds a window running the graphics, the virtual terminals and the
+//! debug shell on it, see `sim`. It is started with `cargo xtask sim`.
 //!
 //! ## Graphics Support
 //!
@@ -50,7 +66,7 @@
 //!
 //! The kernel implements a custom panic handler that prints debug information
 //! and enters a low-power wait-for-event state rather than terminating the
-//! system.
+//! system. It can also write a crash dump, see [`base::crash`].
 //!
 //! ## Dependencies
 //!
@@ -88,7 +104,8 @@
 #![feature(new_range_api)]
 #![feature(generic_const_exprs)]
 
-use oso_no_std_shared::wfe;
+pub use oso_no_std_shared::print;
+pub use oso_no_std_shared::println;
 
 /// Application execution and management subsystem
 ///
@@ -108,11 +125,30 @@ pub mod base;
 /// abstractions for hardware-specific operations.
 pub mod driver;
 
+/// Virtual file system
+///
+/// This module provides one path API over the initial ramdisk, FAT32 volumes
+/// and the devices of the kernel.
+pub mod vfs;
+
+/// Boot information of other boot protocols
+///
+/// Translates Multiboot2 and Limine boot information into `BootInfo`.
+#[cfg(any(feature = "multiboot2", feature = "limine"))]
+pub mod compat;
+
+/// Host simulator
+///
+/// Runs the graphics, consoles and debug shell of the kernel in a window on
+/// the host.
+#[cfg(feature = "sim")]
+pub mod sim;
+
 /// Custom panic handler for the kernel environment
 ///
 /// This panic handler is called when the kernel encounters an unrecoverable
-/// error. It prints diagnostic information and enters a low-power
-/// wait-for-event state to preserve system stability.
+/// error. It prints diagnostic information and then applies the panic policy
+/// of the kernel command line.
 ///
 /// # Arguments
 ///
@@ -120,9 +156,12 @@ pub mod driver;
 ///
 /// # Behavior
 ///
-/// 1. Prints the panic information to the console
-/// 2. Enters an infinite wait-for-event loop to conserve power
-/// 3. Never returns, maintaining system in a stable state
+/// 1. Returns to the innermost [`base::supervisor::catch`] if a supervised
+///    task panicked
+/// 2. Prints the panic information to the console
+/// 3. Writes a crash dump if enabled by [`base::crash::set_dump_on_panic`]
+/// 4. Halts, reboots or blinks an LED as chosen by
+///    [`base::panic_policy`]. Never returns
 ///
 /// # Examples
 ///
@@ -132,10 +171,14 @@ pub mod driver;
 /// // This will trigger the panic handler
 /// panic!("Critical kernel error occurred");
 /// ```
+#[cfg(not(feature = "hosted"))]
 #[panic_handler]
 fn panic(info: &core::panic::PanicInfo,) -> ! {
+	base::supervisor::catch_panic(info,);
 	println!("{}", info);
-	wfe()
+	base::crash::print_backtrace();
+	base::crash::dump_on_panic(info,);
+	base::panic_policy::act()
 }
 
 /// Initializes the kernel and all its subsystems
@@ -149,12 +192,13 @@ fn panic(info: &core::panic::PanicInfo,) -> ! {
 ///
 /// The initialization process includes:
 ///
-/// 1. **Hardware Initialization**: Set up CPU, memory management unit, and
+/// 1. **Console**: Install the framebuffer console of `println!`
+/// 2. **Hardware Initialization**: Set up CPU, memory management unit, and
 ///    interrupt controllers
-/// 2. **Kernel Setup**: Initialize core kernel data structures and subsystems
-/// 3. **Utility Setup**: Configure system utilities and services
-/// 4. **Driver Initialization**: Load and initialize device drivers
-/// 5. **Application Framework**: Prepare the application execution environment
+/// 3. **Kernel Setup**: Initialize core kernel data structures and subsystems
+/// 4. **Utility Setup**: Configure system utilities and services
+/// 5. **Driver Initialization**: Load and initialize device drivers
+/// 6. **Application Framework**: Prepare the application execution environment
 ///
 /// # Safety
 ///
@@ -188,6 +232,17 @@ fn panic(info: &core::panic::PanicInfo,) -> ! {
 /// - Configure system services
 /// - Set up application execution environment
 pub fn init() {
+	use base::perf::boot;
+	use base::perf::boot::Stage;
+
+	/// subsystems in the order they are initialized, with their budgets in
+	/// microseconds
+	static STAGES: [Stage; 2] = [
+		Stage::new("spin", 100, base::spin::init,),
+		Stage::new("io", 5_000, base::io::init,),
+	];
+
+	boot::run(&STAGES,);
 	// TODO: Implement hardware initialization
 	// TODO: Set up memory management
 	// TODO: Initialize interrupt controllers
@@ -11,11 +11,19 @@
 //!
 //! ## Boot Process
 //!
-//! 1. Bootloader transfers control to `kernel_main`
-//! 2. Interrupts are disabled for initialization safety
-//! 3. Kernel subsystems are initialized via `init()`
-//! 4. Main application is launched
-//! 5. System enters low-power wait state
+//! 1. Bootloader transfers control to `kernel_main`, after checking the
+//!    version note of the kernel against its own version
+//! 2. Interrupts are disabled for initialization safety, the features of
+//!    the processor are printed, and the early console is kept only if the
+//!    kernel runs under a hypervisor
+//! 3. Read-only kernel segments are verified against loader checksums
+//! 4. The boot environment is assembled from the command line and device
+//!    tree, and runtime services are handed to `efi` and `power`
+//! 5. Kernel subsystems are initialized via `init()`
+//! 6. The initial ramdisk is mounted at `/` and devfs at `/dev`
+//! 7. The `autoexec.osh` boot script runs in the debug shell
+//! 8. Main application is launched
+//! 9. System enters low-power wait state
 //!
 //! ## Safety Considerations
 //!
@@ -31,8 +39,14 @@
 
 use core::arch::asm;
 use oso_error::Rslt;
-#[cfg(target_arch = "aarch64")]
+#[cfg(any(target_arch = "aarch64", feature = "limine"))]
+use oso_no_std_shared::bridge::boot_info::BootInfo;
 use oso_no_std_shared::bridge::device_tree::DeviceTreeAddress;
+use oso_no_std_shared::bridge::version::BRIDGE_ABI;
+use oso_no_std_shared::bridge::version::KernelVersion;
+use oso_no_std_shared::bridge::version::Version;
+use oso_no_std_shared::bridge::version::VersionNote;
+use oso_no_std_shared::bridge::version::VersionRange;
 use oso_no_std_shared::wfi;
 
 // TODO: Re-enable graphics functionality when implemented
@@ -48,7 +62,60 @@ use oso_no_std_shared::wfi;
 // use oso_kernel::base::graphic::fill_rectangle;
 // use oso_kernel::base::graphic::outline_rectangle;
 
+#[cfg(target_arch = "aarch64")]
+use oso_kernel::app::shell;
+use oso_kernel::base::cpu;
+use oso_kernel::base::early_console;
+#[cfg(target_arch = "aarch64")]
+use oso_kernel::base::early_console::EarlyConsole;
+#[cfg(target_arch = "aarch64")]
+use oso_kernel::base::efi;
+#[cfg(any(target_arch = "aarch64", feature = "limine"))]
+use oso_kernel::base::dt;
+#[cfg(any(target_arch = "aarch64", feature = "limine"))]
+use oso_kernel::base::env;
+#[cfg(target_arch = "aarch64")]
+use oso_kernel::base::graphic;
+use oso_kernel::base::graphic::BootFramebuffer;
+#[cfg(any(target_arch = "aarch64", feature = "limine"))]
+use oso_kernel::base::handoff;
+use oso_kernel::base::hypervisor;
+#[cfg(target_arch = "aarch64")]
+use oso_kernel::base::integrity::verify_segments;
+#[cfg(any(target_arch = "aarch64", feature = "limine"))]
+use oso_kernel::base::panic_policy;
+#[cfg(any(target_arch = "aarch64", feature = "limine"))]
+use oso_kernel::base::perf::idle;
+#[cfg(any(target_arch = "aarch64", feature = "limine"))]
+use oso_kernel::base::perf::record;
+#[cfg(any(target_arch = "aarch64", feature = "limine"))]
+use oso_kernel::base::perf::trace;
+#[cfg(target_arch = "aarch64")]
+use oso_kernel::base::power;
+#[cfg(feature = "limine")]
+use oso_kernel::compat::limine;
+#[cfg(all(feature = "multiboot2", target_arch = "x86_64"))]
+use oso_kernel::compat::multiboot2;
+#[cfg(target_arch = "aarch64")]
+use oso_kernel::driver::qemu_exit;
+use oso_kernel::early_println;
 use oso_kernel::init;
+#[cfg(target_arch = "aarch64")]
+use oso_kernel::vfs;
+
+/// Version of the kernel and the loader versions it accepts
+///
+/// oso_loader reads this note before jumping to the kernel, and refuses to
+/// boot a loader outside the range. Widen the range when a loader release
+/// keeps working with this kernel, and bump [`BRIDGE_ABI`] instead when the
+/// boot information changes.
+#[used]
+#[unsafe(link_section = ".note.oso.version")]
+static VERSION: VersionNote = VersionNote::new(KernelVersion {
+	version: Version::from_pkg(env!("CARGO_PKG_VERSION"),),
+	abi:     BRIDGE_ABI,
+	loader:  VersionRange::caret(Version::new(0, 1, 0,),),
+},);
 
 /// Main entry point for the OSO kernel on AArch64 architecture
 ///
@@ -58,9 +125,10 @@ use oso_kernel::init;
 ///
 /// # Arguments
 ///
-/// * `_device_tree_ptr` - Pointer to the device tree blob (DTB) passed by the
-///   bootloader. Currently unused but reserved for future hardware discovery
-///   implementation.
+/// * `boot_info` - Boot information passed by the bootloader: device tree
+///   blob (DTB), memory map, virtual address of UEFI runtime services,
+///   checksums of the kernel segments and boot modules, such as the initial
+///   ramdisk
 ///
 /// # Safety
 ///
@@ -73,9 +141,11 @@ use oso_kernel::init;
 ///
 /// 1. **Interrupt Disable**: Disables IRQ (Interrupt Request) to prevent
 ///    interruptions during critical initialization phases
-/// 2. **Kernel Initialization**: Calls `init()` to set up all kernel subsystems
-/// 3. **Application Launch**: Starts the main kernel application
-/// 4. **Power Management**: Enters wait-for-interrupt state to conserve power
+/// 2. **Integrity Check**: Verifies read-only segments against the checksums
+///    the loader recorded, panicking on corruption
+/// 3. **Kernel Initialization**: Calls `init()` to set up all kernel subsystems
+/// 4. **Application Launch**: Starts the main kernel application
+/// 5. **Power Management**: Enters wait-for-interrupt state to conserve power
 ///
 /// # Assembly Instructions
 ///
@@ -99,7 +169,7 @@ use oso_kernel::init;
 /// - Add error handling for initialization failures
 #[unsafe(no_mangle)]
 #[cfg(target_arch = "aarch64")]
-pub extern "C" fn kernel_main(_device_tree_ptr: DeviceTreeAddress,) {
+pub extern "C" fn kernel_main(boot_info: *const BootInfo,) {
 	// Disable IRQ (interrupt request) to prevent interruptions during
 	// initialization This is critical for system stability during the boot
 	// process
@@ -108,12 +178,32 @@ pub extern "C" fn kernel_main(_device_tree_ptr: DeviceTreeAddress,) {
 		// DAIF: Debug, SError, IRQ, FIQ exception mask register
 		asm!("msr daifset, #2");
 	}
+	let boot_info = unsafe { boot_info.as_ref() };
+	detect_hypervisor(
+		boot_info.map_or(core::ptr::null(), |info| info.device_tree,),
+	);
+	early_println!("oso_kernel: entered kernel_main");
+	report_cpu();
+
+	// Fail before running code which may have been corrupted after loading
+	let mut framebuffer = None;
+	if let Some(boot_info,) = boot_info {
+		unsafe { verify_segments(boot_info,) };
+		init_env(boot_info,);
+		unsafe { efi::init(boot_info,) };
+		unsafe { power::init(boot_info.device_tree,) };
+		framebuffer = claim_framebuffer(boot_info,);
+		mount_file_systems(boot_info,);
+	}
 
 	// Initialize all kernel subsystems
 	init();
 
+	// Replay the boot script, e.g. of a test scenario
+	autoexec();
+
 	// Launch the main kernel application
-	let _ = app();
+	let _ = app(framebuffer,);
 
 	// Enter wait-for-interrupt state for power efficiency
 	// This stops the CPU until an interrupt occurs, conserving power
@@ -121,6 +211,172 @@ pub extern "C" fn kernel_main(_device_tree_ptr: DeviceTreeAddress,) {
 	wfi();
 }
 
+/// Entry point when started by a Limine boot loader
+///
+/// Limine jumps here instead of the ELF entry point because of the entry point
+/// request in `oso_kernel::compat::limine`. Boot information is translated
+/// into `BootInfo`, then the kernel continues like `kernel_main`. There are
+/// no segment checksums to verify, as they are recorded by oso_loader.
+///
+/// # Panics
+///
+/// Panics if the boot loader does not support the protocol revision or did
+/// not provide a memory map
+#[unsafe(no_mangle)]
+#[cfg(feature = "limine")]
+pub extern "C" fn kernel_main_limine() -> ! {
+	disable_interrupts();
+	let boot_info = unsafe { limine::parse() };
+	let boot_info = boot_info.expect("limine boot information is unusable",);
+	detect_hypervisor(boot_info.device_tree,);
+	early_println!("oso_kernel: entered kernel_main_limine");
+	report_cpu();
+	init_env(boot_info,);
+
+	#[cfg(target_arch = "aarch64")]
+	{
+		let framebuffer = claim_framebuffer(boot_info,);
+		mount_file_systems(boot_info,);
+		init();
+		autoexec();
+		let _ = app(framebuffer,);
+	}
+	wfi()
+}
+
+/// Entry point when started by GRUB or another Multiboot2 boot loader
+///
+/// Called by `oso_kernel::compat::multiboot2::entry` with the registers the
+/// boot loader set. Like `kernel_main` on x86_64, it halts after translating
+/// the boot information into `BootInfo`.
+///
+/// # Arguments
+///
+/// * `magic` - Must be `BOOTLOADER_MAGIC`, otherwise the kernel was not
+///   started by a Multiboot2 boot loader
+/// * `info` - Physical address of the Multiboot2 boot information
+///
+/// # Panics
+///
+/// Panics if `magic` is wrong or the boot information is malformed
+///
+/// # Safety
+///
+/// Only called by the entry stub, with the registers of the boot loader
+#[unsafe(no_mangle)]
+#[cfg(all(feature = "multiboot2", target_arch = "x86_64"))]
+pub unsafe extern "sysv64" fn kernel_main_multiboot2(
+	magic: u32,
+	info: *const u8,
+) -> ! {
+	disable_interrupts();
+	let boot_info = unsafe { multiboot2::parse(magic, info,) };
+	let boot_info =
+		boot_info.expect("multiboot2 boot information is unusable",);
+	detect_hypervisor(boot_info.device_tree,);
+	early_println!("oso_kernel: entered kernel_main_multiboot2");
+	report_cpu();
+	wfi()
+}
+
+/// Prints the features of the processor the kernel chose its code paths by
+fn report_cpu() {
+	early_println!("oso_kernel: cpu: {}", cpu::features());
+}
+
+/// Turns the early console on only under a hypervisor and reports which one
+///
+/// Nothing reaches the early console before, since semihosting faults on
+/// real hardware
+fn detect_hypervisor(device_tree: DeviceTreeAddress,) {
+	let hypervisor = unsafe { hypervisor::detect(device_tree,) };
+	early_console::select(hypervisor,);
+	early_println!("oso_kernel: hypervisor: {}", hypervisor.name());
+}
+
+/// Keeps the boot information and the device tree, assembles the boot
+/// environment and applies the options read from it. A partial environment
+/// is still usable, so errors are only reported
+#[cfg(any(target_arch = "aarch64", feature = "limine"))]
+fn init_env(boot_info: &BootInfo,) {
+	unsafe { handoff::init(boot_info,) };
+	unsafe { dt::init(boot_info.device_tree,) };
+	if let Err(e,) = unsafe { env::init(boot_info,) } {
+		early_println!("oso_kernel: boot environment: {e:?}");
+	}
+	idle::init();
+	trace::init();
+	record::init();
+	panic_policy::init();
+}
+
+/// Takes over the framebuffer the boot loader handed over. The kernel boots
+/// without a display if it can not
+#[cfg(target_arch = "aarch64")]
+fn claim_framebuffer(boot_info: &BootInfo,) -> Option<BootFramebuffer,> {
+	match unsafe { graphic::claim_boot_framebuffer(boot_info,) } {
+		Ok(framebuffer,) => {
+			let conf = framebuffer.conf();
+			early_println!(
+				"oso_kernel: framebuffer {}x{} at {:#x}",
+				conf.width,
+				conf.height,
+				conf.base as u64
+			);
+			Some(framebuffer,)
+		},
+		Err(e,) => {
+			early_println!("oso_kernel: framebuffer: {e:?}");
+			None
+		},
+	}
+}
+
+/// Mounts the initial ramdisk and devfs for the boot script. The kernel
+/// boots without them if they can not be mounted
+#[cfg(target_arch = "aarch64")]
+fn mount_file_systems(boot_info: &BootInfo,) {
+	match unsafe { vfs::init(boot_info,) } {
+		Ok(Some(module,),) => {
+			early_println!("oso_kernel: initrd: module {module} mounted at /");
+		},
+		Ok(None,) => early_println!("oso_kernel: initrd: none"),
+		Err(e,) => early_println!("oso_kernel: mount: {e:?}"),
+	}
+}
+
+/// Runs the boot script of the debug shell, echoing to the early console
+/// where boot tests read it. A failed script does not stop the boot, unless
+/// `test=exit` asks to exit QEMU with the result of the script. A missing
+/// script then fails too, see [`shell::test_passed`]
+#[cfg(target_arch = "aarch64")]
+fn autoexec() {
+	let result = shell::autoexec(&mut EarlyConsole::new(),);
+	match &result {
+		Ok(Some(ran,),) => {
+			early_println!("oso_kernel: autoexec: {ran} commands");
+		},
+		Ok(None,) => early_println!("oso_kernel: autoexec: no script"),
+		Err(e,) => early_println!("oso_kernel: autoexec: {e:?}"),
+	}
+	if env::get("cmdline.test",) == Some("exit",) {
+		let passed = shell::test_passed(&result,);
+		let code = if passed { qemu_exit::PASSED } else { qemu_exit::FAILED };
+		qemu_exit::qemu_exit(code,);
+	}
+}
+
+/// Masks interrupts of the boot loader's environment
+#[cfg(any(feature = "limine", feature = "multiboot2"))]
+fn disable_interrupts() {
+	unsafe {
+		#[cfg(target_arch = "aarch64")]
+		asm!("msr daifset, #2");
+		#[cfg(target_arch = "x86_64")]
+		asm!("cli");
+	}
+}
+
 /// Main entry point for the OSO kernel on x86_64 architecture
 ///
 /// This function provides basic x86_64 support for development and testing
@@ -164,6 +420,10 @@ pub extern "C" fn kernel_main(_device_tree_ptr: DeviceTreeAddress,) {
 #[unsafe(no_mangle)]
 #[cfg(target_arch = "x86_64")]
 pub extern "sysv64" fn kernel_main() {
+	detect_hypervisor(core::ptr::null(),);
+	early_println!("oso_kernel: entered kernel_main");
+	report_cpu();
+
 	// Current implementation: halt immediately for debugging
 	// This prevents further execution and allows for system inspection
 	loop {
@@ -201,6 +461,11 @@ pub extern "sysv64" fn kernel_main() {
 /// initialization. Currently, it serves as a placeholder for future application
 /// functionality and contains commented-out graphics demonstration code.
 ///
+/// # Arguments
+///
+/// * `_framebuffer` - Framebuffer claimed from the boot loader, to be drawn
+///   on once graphics operations are implemented
+///
 /// # Returns
 ///
 /// * `Rslt<()>` - Result indicating success or failure of application execution
@@ -216,7 +481,7 @@ pub extern "sysv64" fn kernel_main() {
 /// The commented code demonstrates the intended graphics capabilities:
 ///
 /// - **Rectangle Drawing**: Fill and outline rectangle operations
-/// - **Color Support**: Various color formats and hex color parsing
+/// - **Color Support**: Colors validated at compile time by `color!`
 /// - **Frame Buffer Operations**: Direct frame buffer manipulation
 /// - **Cursor Support**: Mouse cursor rendering and management
 /// - **Debug Output**: Frame buffer information logging
@@ -238,38 +503,38 @@ pub extern "sysv64" fn kernel_main() {
 ///
 /// ```rust,ignore
 /// // Fill background
-/// fill_rectangle(&(0, 0), &frame_buffer.right_bottom(), &"#ffffff")?;
+/// let screen = Rect::of_size(frame_buffer.resolution(),);
+/// fill_rectangle(&screen, &color!("#ffffff"))?;
 ///
 /// // Draw colored rectangles
-/// fill_rectangle(&(100, 100), &(200, 200), &"#fedcba")?;
+/// let rect = Rect::from_inclusive(Point::new(100, 100,), Point::new(200, 200,),);
+/// fill_rectangle(&rect, &color!("#fedcba"))?;
 ///
 /// // Draw outlines
-/// outline_rectangle(&(100, 100), &(300, 300), &"#fedcba")?;
+/// outline_rectangle(&rect, &color!("#fedcba"))?;
 /// ```
 ///
 /// # TODO
 ///
 /// - Implement graphics subsystem initialization
 /// - Enable frame buffer operations
-/// - Add color parsing and management
 /// - Implement cursor rendering system
 /// - Add user interface elements
 /// - Implement application lifecycle management
-fn app() -> Rslt<(),> {
+fn app(_framebuffer: Option<BootFramebuffer,>,) -> Rslt<(),> {
 	// TODO: Implement graphics operations
 	// The following code represents planned graphics functionality:
 
 	// Background and rectangle filling operations
-	// fill_rectangle(&(100, 100,), &(700, 500,), &"#abcdef",)?;
-	// fill_rectangle(&(0, 0,), &FRAME_BUFFER.right_bottom(), &"#012345",)?;
-	// fill_rectangle(&(100, 100,), &(200, 200,), &"#fedcba",)?;
-	// fill_rectangle(&(0, 0,), &FRAME_BUFFER.right_bottom(), &"#ffffff",)?;
-	// fill_rectangle(&(0, 0,), &FRAME_BUFFER.right_bottom(), &"#abcdef",)?;
+	// let screen = Rect::of_size(FRAME_BUFFER.resolution(),);
+	// let rect = Rect::new(Point::new(100, 100,), Size::new(600, 400,),);
+	// fill_rectangle(&rect, &color!("#abcdef"),)?;
+	// fill_rectangle(&screen, &color!("#012345"),)?;
+	// fill_rectangle(&screen, &color!("#ffffff"),)?;
 
 	// Outline rectangle operations
-	// outline_rectangle(&(100, 100,), &(300, 300,), &"#fedcba",)?;
-	// outline_rectangle(&(101, 101,), &(299, 299,), &"#fedcba",)?;
-	// outline_rectangle(&(102, 102,), &(298, 298,), &"#fedcba",)?;
+	// let rect = Rect::new(Point::new(100, 100,), Size::new(200, 200,),);
+	// outline_rectangle(&rect, &color!("#fedcba"),)?;
 
 	// Debug information output
 	// println!("width: {} height: {}", FRAME_BUFFER.width,
@@ -0,0 +1,310 @@
+//! # Host Simulator
+//!
+//! Runs the graphics, the virtual terminals and the debug shell of the kernel
+//! in a window on the host, so work on what the screen shows is tried in
+//! seconds instead of a QEMU boot. The `sim` feature builds this module and
+//! the `oso_sim` binary running it. `cargo xtask sim` builds and runs it.
+//!
+//! The kernel code is the same as on hardware: [`FRAME_BUFFER`] points to
+//! the pixels of the window and [`init`](crate::init) installs the consoles.
+//! The architecture layer below is mocked by the `hosted` feature, which
+//! `sim` enables. Pixels are copied to the window 60 times a second, and
+//! each frame stands in for a timer interrupt calling [`timer::interrupt`].
+//!
+//! ## Input
+//!
+//! Bytes read from stdin, and keys typed into the window as the bytes a
+//! terminal sends, go through [`KeyDecoder`] to a [`LineEditor`] on the
+//! `shell` terminal, and submitted lines run with [`shell::execute`]. A
+//! scenario is piped in as a script:
+//!
+//! ```text
+//! printf 'vt\nidle stats\n' | cargo xtask sim
+//! ```
+//!
+//! Each byte is passed to [`record::input`], so `record start` records a
+//! session like under QEMU. `Alt+F1` to `Alt+F3` of the window switch
+//! terminals with [`vt::handle_hotkey`].
+//!
+//! ## Replay
+//!
+//! `cargo xtask sim --replay <log>` replays the last recording of a serial
+//! log, written by `record dump`. Every frame feeds the input bytes up to
+//! the next tick of the recording and ticks, so input arrives at the same
+//! tick as when it was recorded. Input from stdin and typed keys is dropped
+//! until the recording ends.
+//!
+//! ## Current Status
+//!
+//! Only the `bgr` pixel format is shown correctly, as the window takes
+//! `0RGB` pixels. Commands reading boot information report that there is
+//! none.
+
+extern crate std;
+
+use crate::app::shell;
+use crate::base::graphic::FRAME_BUFFER;
+use crate::base::graphic::FrameBuffer;
+use crate::base::perf::record;
+use crate::base::timer;
+use crate::base::vt;
+use crate::base::vt::Vt;
+use crate::base::vt::VtConsole;
+use crate::println;
+use core::fmt;
+use core::fmt::Write;
+use minifb::InputCallback;
+use minifb::Key as WindowKey;
+use minifb::KeyRepeat;
+use minifb::Window;
+use minifb::WindowOptions;
+use oso_no_std_shared::shell::line_editor::Action;
+use oso_no_std_shared::shell::line_editor::Key;
+use oso_no_std_shared::shell::line_editor::KeyDecoder;
+use oso_no_std_shared::shell::line_editor::LineEditor;
+use oso_no_std_shared::shell::replay::Event;
+use oso_no_std_shared::text::console::Console;
+use std::boxed::Box;
+use std::io::Read;
+use std::sync::mpsc;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::vec;
+
+#[cfg(not(feature = "bgr"))]
+compile_error!(
+	"the simulator shows `bgr` pixels, build it with `--no-default-features \
+	 --features sim,bgr`"
+);
+
+/// Title of the window
+pub const TITLE: &str = "oso sim";
+/// Size of the window in pixels, unless given
+pub const DEFAULT_SIZE: (usize, usize,) = (800, 600,);
+/// Prompt of the shell
+pub const PROMPT: &str = "osh> ";
+/// Longest command line in bytes
+pub const LINE_CAPACITY: usize = 128;
+/// Command lines kept in the history
+pub const HISTORY: usize = 16;
+
+/// Opens a window of `width` x `height` pixels and runs the kernel in it
+/// until the window is closed, replaying the events of `replay` first
+///
+/// # Errors
+///
+/// Returns the error of the window system if the window can not be opened
+/// or drawn
+pub fn run(
+	width: usize,
+	height: usize,
+	replay: &[Event],
+) -> minifb::Result<(),> {
+	// the kernel draws through the raw address, so no reference is kept
+	let pixels = Box::into_raw(vec![0_u32; width * height].into_boxed_slice(),);
+	// SAFETY: the pixels are never freed, and the window only reads them
+	// between frames, while the kernel does not draw
+	unsafe {
+		FrameBuffer::init(
+			&FRAME_BUFFER,
+			pixels as *mut u32 as usize,
+			width * height * 4,
+			width,
+			height,
+			width,
+		);
+	}
+	crate::init();
+	println!("oso_kernel: simulator {width}x{height}");
+
+	let options = WindowOptions::default();
+	let mut window = Window::new(TITLE, width, height, options,)?;
+	window.set_target_fps(60,);
+	let (bytes, input,) = mpsc::channel();
+	window.set_input_callback(Box::new(Chars(bytes.clone(),),),);
+	read_stdin(bytes,);
+
+	let _ = vt::switch(Vt::Shell,);
+	let mut terminal = Terminal::new();
+	let _ = terminal.prompt();
+	let mut replay = replay.iter();
+	let mut replaying = replay.len() != 0;
+	while window.is_open() {
+		let alt = window.is_key_down(WindowKey::LeftAlt,)
+			|| window.is_key_down(WindowKey::RightAlt,);
+		let mut typed = vec![];
+		for key in window.get_keys_pressed(KeyRepeat::Yes,) {
+			if let Some(function,) = function_key(key,) {
+				vt::handle_hotkey(alt, function,);
+			} else if let Some(bytes,) = escape(key,) {
+				typed.extend_from_slice(bytes,);
+			}
+		}
+		typed.extend(input.try_iter(),);
+
+		if replaying {
+			// live input would make the replay diverge
+			for event in replay.by_ref() {
+				match *event {
+					Event::Tick => break,
+					Event::Input(byte,) => terminal.read(byte,),
+				}
+			}
+			if replay.len() == 0 {
+				replaying = false;
+				println!("oso_sim: replay done");
+			}
+		} else {
+			for byte in typed {
+				terminal.read(byte,);
+			}
+		}
+		timer::interrupt();
+		// SAFETY: see above
+		window.update_with_buffer(unsafe { &*pixels }, width, height,)?;
+	}
+	Ok((),)
+}
+
+/// Line editor on the `shell` terminal
+struct Terminal {
+	decoder: KeyDecoder,
+	editor:  LineEditor<'static, LINE_CAPACITY, HISTORY,>,
+	out:     VtConsole,
+	/// length of the line on screen, whose rest is blanked by a redraw
+	shown:   usize,
+}
+
+impl Terminal {
+	fn new() -> Self {
+		Self {
+			decoder: KeyDecoder::new(),
+			editor:  LineEditor::new(&shell::COMMANDS,),
+			out:     VtConsole::new(Vt::Shell,),
+			shown:   0,
+		}
+	}
+
+	/// Records and decodes a byte of input
+	fn read(&mut self, byte: u8,) {
+		record::input(byte,);
+		if let Some(key,) = self.decoder.feed(byte,) {
+			self.feed(key,);
+		}
+	}
+
+	fn feed(&mut self, key: Key,) {
+		let _ = match self.editor.feed(key,) {
+			Action::None => Ok((),),
+			Action::Redraw => self.redraw(),
+			Action::Candidates => self.list_candidates(),
+			Action::Submit => self.submit(),
+		};
+	}
+
+	/// Writes the prompt on a new line
+	fn prompt(&mut self,) -> fmt::Result {
+		self.shown = 0;
+		self.out.write_str(PROMPT,)
+	}
+
+	/// Rewrites the line after the prompt and moves the cursor in it
+	fn redraw(&mut self,) -> fmt::Result {
+		let (_, row,) = self.out.cursor();
+		let line = self.editor.line();
+		self.out.set_cursor(PROMPT.len(), row,)?;
+		self.out.write_str(line,)?;
+		for _ in line.len()..self.shown {
+			self.out.write_char(' ',)?;
+		}
+		self.shown = line.len();
+		self.out.set_cursor(PROMPT.len() + self.editor.cursor(), row,)
+	}
+
+	fn list_candidates(&mut self,) -> fmt::Result {
+		writeln!(self.out)?;
+		for name in self.editor.completions() {
+			write!(self.out, "{name}  ")?;
+		}
+		writeln!(self.out)?;
+		self.prompt()?;
+		self.redraw()
+	}
+
+	/// Runs the line. Failed commands describe the error themselves
+	fn submit(&mut self,) -> fmt::Result {
+		let end = PROMPT.len() + self.editor.line().len();
+		let (_, row,) = self.out.cursor();
+		self.out.set_cursor(end, row,)?;
+		writeln!(self.out)?;
+		let _ = shell::execute(self.editor.line(), &mut self.out,);
+		self.prompt()
+	}
+}
+
+/// Passes printable characters typed into the window on as bytes
+struct Chars(Sender<u8,>,);
+
+impl InputCallback for Chars {
+	fn add_char(&mut self, uni_char: u32,) {
+		if let Ok(c,) = u8::try_from(uni_char,)
+			&& (b' '..=b'~').contains(&c,)
+		{
+			let _ = self.0.send(c,);
+		}
+	}
+}
+
+/// Passes stdin on from a thread of its own, until stdin or the window is
+/// closed
+fn read_stdin(bytes: Sender<u8,>,) {
+	thread::spawn(move || {
+		for byte in std::io::stdin().lock().bytes() {
+			let Ok(byte,) = byte else {
+				break;
+			};
+			if bytes.send(byte,).is_err() {
+				break;
+			}
+		}
+	},);
+}
+
+/// Number of a function key, e.g. `1` of `F1`
+fn function_key(key: WindowKey,) -> Option<u8,> {
+	const KEYS: [WindowKey; 12] = [
+		WindowKey::F1,
+		WindowKey::F2,
+		WindowKey::F3,
+		WindowKey::F4,
+		WindowKey::F5,
+		WindowKey::F6,
+		WindowKey::F7,
+		WindowKey::F8,
+		WindowKey::F9,
+		WindowKey::F10,
+		WindowKey::F11,
+		WindowKey::F12,
+	];
+	let index = KEYS.iter().position(|k| *k == key,)?;
+	Some(index as u8 + 1,)
+}
+
+/// Bytes a terminal sends for an editing key of the window. Characters
+/// come through [`Chars`]
+fn escape(key: WindowKey,) -> Option<&'static [u8],> {
+	let bytes: &[u8] = match key {
+		WindowKey::Backspace => b"\x7f",
+		WindowKey::Delete => b"\x1b[3~",
+		WindowKey::Left => b"\x1b[D",
+		WindowKey::Right => b"\x1b[C",
+		WindowKey::Home => b"\x1b[H",
+		WindowKey::End => b"\x1b[F",
+		WindowKey::Up => b"\x1b[A",
+		WindowKey::Down => b"\x1b[B",
+		WindowKey::Tab => b"\t",
+		WindowKey::Enter | WindowKey::NumPadEnter => b"\r",
+		_ => return None,
+	};
+	Some(bytes,)
+}
@@ -0,0 +1,669 @@
+//! # Virtual File System
+//!
+//! One path API over every file system of the kernel. File systems implement
+//! [`FileSystem`] and are mounted at an absolute path, after which
+//! [`open`], [`stat`] and [`read_dir`] reach them without knowing which file
+//! system holds a path.
+//!
+//! ## File Systems
+//!
+//! - [`cpio`]: Initial ramdisk in the `newc` cpio format
+//! - [`fat32`]: FAT32 volume on a block device, such as the boot SD card
+//! - [`devfs`]: Synthetic `/dev` with the console and block devices
+//!
+//! ## Paths
+//!
+//! Paths are absolute and normalized with [`PathN::normalize`], so `/` and `\`
+//! both separate components and `..` is resolved before lookup. A path
+//! belongs to the mount with the longest matching path, and the rest of it is
+//! looked up in that file system.
+//!
+//! ## Boot
+//!
+//! [`init`] mounts the first boot module which is a cpio archive at `/` and
+//! [`devfs`] at `/dev`, before the boot script runs.
+//!
+//! ## Current Status
+//!
+//! - The kernel has no allocator, so the mount table has room for
+//!   [`MAX_MOUNTS`] file systems and names are copied into [`DirEntry`]
+//! - FAT32 and the initial ramdisk are read-only
+//! - No driver of the boot disk runs at boot, so nothing mounts the ESP
+//!
+//! ```rust,ignore
+//! vfs::mount("/", &INITRD,)?;
+//! vfs::mount("/boot", &BOOT,)?;
+//! vfs::mount("/dev", &DevFs,)?;
+//!
+//! let mut cfg = vfs::open("/boot/cfg",)?;
+//! let len = cfg.read(&mut buf,)?;
+//! ```
+
+use core::cell::UnsafeCell;
+use cpio::Cpio;
+use devfs::DevFs;
+use oso_error::Rslt;
+use oso_error::kernel::VfsError;
+use oso_error::oso_err;
+use oso_no_std_shared::bridge::boot_info::BootInfo;
+use oso_no_std_shared::path::Component;
+use oso_no_std_shared::path::PathBufN;
+use oso_no_std_shared::path::PathN;
+
+/// Initial ramdisk in the `newc` cpio format
+pub mod cpio;
+
+/// Synthetic file system of devices
+pub mod devfs;
+
+/// FAT32 file system on a block device
+pub mod fat32;
+
+/// File systems the mount table can hold
+pub const MAX_MOUNTS: usize = 8;
+/// Longest name a [`DirEntry`] holds, in bytes
+pub const NAME_MAX: usize = 255;
+/// Longest normalized path, in bytes
+pub const PATH_MAX: usize = 1024;
+
+static MOUNTS: Mounts = Mounts(UnsafeCell::new([None; MAX_MOUNTS],),);
+static INITRD: Initrd = Initrd(UnsafeCell::new(None,),);
+
+/// the kernel runs on one core and mounts before it opens files
+struct Mounts(UnsafeCell<[Option<Mount,>; MAX_MOUNTS],>,);
+
+unsafe impl Sync for Mounts {}
+
+/// set once by [`init`]
+struct Initrd(UnsafeCell<Option<Cpio,>,>,);
+
+unsafe impl Sync for Initrd {}
+
+#[derive(Clone, Copy,)]
+struct Mount {
+	path: &'static str,
+	fs:   &'static dyn FileSystem,
+}
+
+/// Kind of a [`Node`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum FileKind {
+	File,
+	Directory,
+	Device,
+}
+
+/// Result of [`Stat::stat`]
+///
+/// # Fields
+///
+/// * `kind` - Kind of the node
+/// * `size` - Size in bytes. `0` for directories
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct Metadata {
+	pub kind: FileKind,
+	pub size: u64,
+}
+
+/// File or directory of a file system
+///
+/// # Fields
+///
+/// * `id` - Identifies the node within its file system. The file system
+///   defines the meaning
+/// * `kind` - Kind of the node
+/// * `size` - Size in bytes. `0` for directories
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct Node {
+	pub id:   u64,
+	pub kind: FileKind,
+	pub size: u64,
+}
+
+impl Node {
+	pub const fn metadata(&self,) -> Metadata {
+		Metadata { kind: self.kind, size: self.size, }
+	}
+
+	pub const fn is_dir(&self,) -> bool {
+		matches!(self.kind, FileKind::Directory)
+	}
+}
+
+/// Entry of a directory
+pub struct DirEntry {
+	name: [u8; NAME_MAX],
+	len:  usize,
+	node: Node,
+}
+
+impl DirEntry {
+	/// Entry named `name`, truncated to [`NAME_MAX`] bytes at a character
+	/// boundary
+	pub fn new(name: &str, node: Node,) -> Self {
+		let mut len = name.len().min(NAME_MAX,);
+		while !name.is_char_boundary(len,) {
+			len -= 1;
+		}
+		let mut entry = Self { name: [0; NAME_MAX], len, node, };
+		entry.name[..len].copy_from_slice(&name.as_bytes()[..len],);
+		entry
+	}
+
+	pub fn node(&self,) -> Node {
+		self.node
+	}
+
+	pub fn name(&self,) -> &str {
+		// built from a `str` at a character boundary
+		unsafe { core::str::from_utf8_unchecked(&self.name[..self.len],) }
+	}
+}
+
+/// Interface of file systems
+///
+/// Methods take `&self` as mounted file systems are shared. File systems
+/// which modify state keep it behind interior mutability.
+pub trait FileSystem {
+	/// Root directory
+	fn root(&self,) -> Node;
+
+	/// Next entry of `dir` from `cursor`, which starts at `0` and is
+	/// advanced past the returned entry. `None` after the last entry
+	fn read_dir(
+		&self,
+		dir: &Node,
+		cursor: &mut u64,
+	) -> Rslt<Option<DirEntry,>, VfsError,>;
+
+	/// Reads from `offset` of `node` into `buf`. Returns the number of bytes
+	/// read, which is `0` at the end of the file
+	fn read(
+		&self,
+		node: &Node,
+		offset: u64,
+		buf: &mut [u8],
+	) -> Rslt<usize, VfsError,>;
+
+	/// Writes `buf` at `offset` of `node`. Returns the number of bytes
+	/// written
+	fn write(
+		&self,
+		_node: &Node,
+		_offset: u64,
+		_buf: &[u8],
+	) -> Rslt<usize, VfsError,> {
+		Err(oso_err!(VfsError::ReadOnly),)
+	}
+
+	/// Entry `name` of `dir`
+	fn find(&self, dir: &Node, name: &str,) -> Rslt<Node, VfsError,> {
+		if !dir.is_dir() {
+			return Err(oso_err!(VfsError::NotADirectory),);
+		}
+		let mut cursor = 0;
+		while let Some(entry,) = self.read_dir(dir, &mut cursor,)? {
+			if entry.name() == name {
+				return Ok(entry.node(),);
+			}
+		}
+		Err(oso_err!(VfsError::NotFound),)
+	}
+}
+
+/// Sequential reads
+pub trait Read {
+	/// Reads into `buf` and returns the number of bytes read, which is `0` at
+	/// the end of the file
+	fn read(&mut self, buf: &mut [u8],) -> Rslt<usize, VfsError,>;
+
+	/// Reads until `buf` is full or the file ends
+	fn read_full(&mut self, buf: &mut [u8],) -> Rslt<usize, VfsError,> {
+		let mut done = 0;
+		while done < buf.len() {
+			match self.read(&mut buf[done..],)? {
+				0 => break,
+				len => done += len,
+			}
+		}
+		Ok(done,)
+	}
+}
+
+/// Sequential writes
+pub trait Write {
+	/// Writes `buf` and returns the number of bytes written
+	fn write(&mut self, buf: &[u8],) -> Rslt<usize, VfsError,>;
+}
+
+/// Position to [`Seek::seek`] to
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum SeekFrom {
+	Start(u64,),
+	End(i64,),
+	Current(i64,),
+}
+
+/// Moves the position of reads and writes
+pub trait Seek {
+	/// Moves to `pos` and returns the new position from the start
+	fn seek(&mut self, pos: SeekFrom,) -> Rslt<u64, VfsError,>;
+}
+
+/// Metadata of an open node
+pub trait Stat {
+	fn stat(&self,) -> Rslt<Metadata, VfsError,>;
+}
+
+/// Open file of a mounted file system
+pub struct File {
+	fs:   &'static dyn FileSystem,
+	node: Node,
+	pos:  u64,
+}
+
+impl File {
+	pub fn node(&self,) -> &Node {
+		&self.node
+	}
+}
+
+impl Read for File {
+	fn read(&mut self, buf: &mut [u8],) -> Rslt<usize, VfsError,> {
+		let len = self.fs.read(&self.node, self.pos, buf,)?;
+		self.pos += len as u64;
+		Ok(len,)
+	}
+}
+
+impl Write for File {
+	fn write(&mut self, buf: &[u8],) -> Rslt<usize, VfsError,> {
+		let len = self.fs.write(&self.node, self.pos, buf,)?;
+		self.pos += len as u64;
+		Ok(len,)
+	}
+}
+
+impl Seek for File {
+	fn seek(&mut self, pos: SeekFrom,) -> Rslt<u64, VfsError,> {
+		let pos = match pos {
+			SeekFrom::Start(pos,) => Some(pos,),
+			SeekFrom::End(delta,) => self.node.size.checked_add_signed(delta,),
+			SeekFrom::Current(delta,) => self.pos.checked_add_signed(delta,),
+		};
+		let Some(pos,) = pos else {
+			return Err(oso_err!(VfsError::InvalidSeek),);
+		};
+		self.pos = pos;
+		Ok(pos,)
+	}
+}
+
+impl Stat for File {
+	fn stat(&self,) -> Rslt<Metadata, VfsError,> {
+		Ok(self.node.metadata(),)
+	}
+}
+
+/// Iterator over the entries of a directory, returned by [`read_dir`]
+pub struct ReadDir {
+	fs:     &'static dyn FileSystem,
+	dir:    Node,
+	cursor: u64,
+}
+
+impl Iterator for ReadDir {
+	type Item = Rslt<DirEntry, VfsError,>;
+
+	fn next(&mut self,) -> Option<Self::Item,> {
+		self.fs.read_dir(&self.dir, &mut self.cursor,).transpose()
+	}
+}
+
+/// Mounts the file systems of the boot: the first boot module which is a
+/// cpio archive at `/`, and [`devfs`] at `/dev`. Returns the index of the
+/// module mounted at `/`, `None` if no module is an archive
+///
+/// # Safety
+///
+/// Called once. The modules of `boot_info` must stay mapped while the kernel
+/// runs
+///
+/// # Errors
+///
+/// Errors of [`mount`]
+pub unsafe fn init(boot_info: &BootInfo,) -> Rslt<Option<usize,>, VfsError,> {
+	let modules = unsafe { boot_info.modules.as_slice() };
+	let initrd = modules.iter().enumerate().find_map(|(i, module,)| {
+		let cpio = Cpio::new(unsafe { module.contents() },).ok()?;
+		Some((i, cpio,),)
+	},);
+	let index = match initrd {
+		Some((i, cpio,),) => {
+			let cpio = unsafe { &mut *INITRD.0.get() }.insert(cpio,);
+			mount("/", cpio,)?;
+			Some(i,)
+		},
+		None => None,
+	};
+	mount("/dev", &DevFs,)?;
+	Ok(index,)
+}
+
+/// Mounts `fs` at `path`
+///
+/// # Errors
+///
+/// - [`VfsError::InvalidPath`] if `path` is not absolute or longer than
+///   [`PATH_MAX`]
+/// - [`VfsError::AlreadyMounted`] if a file system is mounted at `path`
+/// - [`VfsError::TooManyEntries`] if [`MAX_MOUNTS`] are mounted
+pub fn mount(
+	path: &'static str,
+	fs: &'static dyn FileSystem,
+) -> Rslt<(), VfsError,> {
+	normalize(path,)?;
+	let mounts = unsafe { &mut *MOUNTS.0.get() };
+	if mounts.iter().flatten().any(|mount| same_path(mount.path, path,),) {
+		return Err(oso_err!(VfsError::AlreadyMounted),);
+	}
+	let Some(slot,) = mounts.iter_mut().find(|slot| slot.is_none(),) else {
+		return Err(oso_err!(VfsError::TooManyEntries {
+			what:     "mounts",
+			capacity: MAX_MOUNTS,
+		}),);
+	};
+	*slot = Some(Mount { path, fs, },);
+	Ok((),)
+}
+
+/// Removes the file system mounted at `path`. Open files stay usable
+///
+/// # Errors
+///
+/// [`VfsError::NotFound`] if nothing is mounted at `path`
+pub fn unmount(path: &str,) -> Rslt<(), VfsError,> {
+	let mounts = unsafe { &mut *MOUNTS.0.get() };
+	let Some(slot,) = mounts
+		.iter_mut()
+		.find(|slot| slot.is_some_and(|mount| same_path(mount.path, path,),),)
+	else {
+		return Err(oso_err!(VfsError::NotFound),);
+	};
+	*slot = None;
+	Ok((),)
+}
+
+/// Opens the file or device at `path`
+///
+/// # Errors
+///
+/// - [`VfsError::IsADirectory`] if `path` is a directory
+/// - Errors of [`stat`]
+pub fn open(path: &str,) -> Rslt<File, VfsError,> {
+	let (fs, node,) = resolve(path,)?;
+	if node.is_dir() {
+		return Err(oso_err!(VfsError::IsADirectory),);
+	}
+	Ok(File { fs, node, pos: 0, },)
+}
+
+/// Metadata of the node at `path`
+///
+/// # Errors
+///
+/// - [`VfsError::InvalidPath`] if `path` is not absolute or longer than
+///   [`PATH_MAX`]
+/// - [`VfsError::NotFound`] if no file system is mounted at `path` or it has
+///   no node there
+/// - Errors of the file system
+pub fn stat(path: &str,) -> Rslt<Metadata, VfsError,> {
+	Ok(resolve(path,)?.1.metadata(),)
+}
+
+/// Entries of the directory at `path`
+///
+/// # Errors
+///
+/// - [`VfsError::NotADirectory`] if `path` is not a directory
+/// - Errors of [`stat`]
+pub fn read_dir(path: &str,) -> Rslt<ReadDir, VfsError,> {
+	let (fs, dir,) = resolve(path,)?;
+	if !dir.is_dir() {
+		return Err(oso_err!(VfsError::NotADirectory),);
+	}
+	Ok(ReadDir { fs, dir, cursor: 0, },)
+}
+
+/// File system holding `path` and the node of `path` in it
+fn resolve(path: &str,) -> Rslt<(&'static dyn FileSystem, Node,), VfsError,> {
+	let path = normalize(path,)?;
+	let mut best: Option<(Mount, usize,),> = None;
+	let mounts = unsafe { &*MOUNTS.0.get() };
+	for mount in mounts.iter().flatten() {
+		let Some(depth,) = mount_depth(mount.path, &path,)? else {
+			continue;
+		};
+		if best.is_none_or(|(_, best,)| depth >= best,) {
+			best = Some((*mount, depth,),);
+		}
+	}
+	let Some((mount, depth,),) = best else {
+		return Err(oso_err!(VfsError::NotFound),);
+	};
+
+	let mut node = mount.fs.root();
+	for name in names(&path,).skip(depth,) {
+		node = mount.fs.find(&node, name,)?;
+	}
+	Ok((mount.fs, node,),)
+}
+
+/// Number of components of `mount` if `path` is at or below it
+fn mount_depth(mount: &str, path: &PathN,) -> Rslt<Option<usize,>, VfsError,> {
+	let mut path = names(path,);
+	let mut depth = 0;
+	for name in names(&normalize(mount,)?,) {
+		if path.next() != Some(name,) {
+			return Ok(None,);
+		}
+		depth += 1;
+	}
+	Ok(Some(depth,),)
+}
+
+fn same_path(a: &str, b: &str,) -> bool {
+	match (normalize(a,), normalize(b,),) {
+		(Ok(a,), Ok(b,),) => a == b,
+		_ => false,
+	}
+}
+
+/// # Errors
+///
+/// [`VfsError::InvalidPath`] if `path` is relative or longer than [`PATH_MAX`]
+fn normalize(path: &str,) -> Rslt<PathBufN<PATH_MAX,>, VfsError,> {
+	let path = PathN::new(path,);
+	if !path.is_absolute() {
+		return Err(oso_err!(VfsError::InvalidPath),);
+	}
+	path.normalize().map_err(|_| oso_err!(VfsError::InvalidPath),)
+}
+
+/// Names of a normalized absolute path
+fn names(path: &PathN,) -> impl Iterator<Item = &str,> {
+	path.components().filter_map(|component| match component {
+		Component::Normal(name,) => Some(name,),
+		_ => None,
+	},)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	extern crate std;
+	use cpio::tests::DIRECTORY;
+	use cpio::tests::FILE;
+	use cpio::tests::archive;
+	use oso_no_std_shared::bridge::boot_info::CommandLine;
+	use oso_no_std_shared::bridge::boot_info::Module;
+	use oso_no_std_shared::bridge::boot_info::Modules;
+	use std::boxed::Box;
+	use std::string::String;
+	use std::sync::Mutex;
+	use std::sync::MutexGuard;
+	use std::vec::Vec;
+
+	/// the mount table is global, so tests using it run one at a time
+	static LOCK: Mutex<(),> = Mutex::new((),);
+
+	/// Takes the mount table and empties it
+	pub(crate) fn lock() -> MutexGuard<'static, (),> {
+		let guard = LOCK.lock();
+		let guard = guard.unwrap_or_else(|poisoned| poisoned.into_inner(),);
+		unmount_all();
+		guard
+	}
+
+	fn unmount_all() {
+		unsafe { *MOUNTS.0.get() = [None; MAX_MOUNTS] };
+	}
+
+	/// archive with the directory `etc` and `files` as path and contents
+	pub(crate) fn initrd(files: &[(&str, &[u8],)],) -> &'static [u8] {
+		let files = files.iter().map(|(path, data,)| (*path, FILE, *data,),);
+		let mut entries = std::vec![("etc", DIRECTORY, &b""[..],)];
+		entries.extend(files,);
+		archive(&entries,)
+	}
+
+	fn leak(fs: impl FileSystem + 'static,) -> &'static dyn FileSystem {
+		Box::leak(Box::new(fs,),)
+	}
+
+	fn error<T,>(result: Rslt<T, VfsError,>,) -> Option<VfsError,> {
+		result.err().and_then(|e| e.desc,)
+	}
+
+	fn read_to_string(path: &str,) -> String {
+		let mut buf = [0; 64];
+		let len = open(path,).unwrap().read_full(&mut buf,).unwrap();
+		String::from_utf8(buf[..len].to_vec(),).unwrap()
+	}
+
+	#[test]
+	fn test_paths_resolve_to_the_longest_mount() {
+		let _lock = lock();
+		let files: [(_, &[u8],); 2] =
+			[("etc/motd", b"root",), ("boot/cfg", b"hidden",),];
+		let root = initrd(&files,);
+		mount("/", leak(Cpio::new(root,).unwrap(),),).unwrap();
+		let boot = initrd(&[("cfg", b"boot",),],);
+		mount("/boot/", leak(Cpio::new(boot,).unwrap(),),).unwrap();
+
+		assert_eq!(read_to_string("/etc/motd"), "root");
+		assert_eq!(read_to_string("/boot/cfg"), "boot");
+		assert_eq!(read_to_string("\\boot\\..\\etc//./motd"), "root");
+		assert_eq!(read_to_string("/boot/../boot/cfg"), "boot");
+		let dir = Metadata { kind: FileKind::Directory, size: 0, };
+		assert_eq!(stat("/boot",).unwrap(), dir);
+
+		unmount("/boot",).unwrap();
+		assert_eq!(read_to_string("/boot/cfg"), "hidden");
+		assert_eq!(error(unmount("/boot")), Some(VfsError::NotFound));
+	}
+
+	#[test]
+	fn test_mount_errors() {
+		let _lock = lock();
+		let fs = leak(Cpio::new(initrd(&[],),).unwrap(),);
+		assert_eq!(error(open("/etc")), Some(VfsError::NotFound));
+		assert_eq!(error(mount("boot", fs,)), Some(VfsError::InvalidPath));
+		mount("/mnt/0", fs,).unwrap();
+		let again = mount("/mnt/./0/", fs,);
+		assert_eq!(error(again), Some(VfsError::AlreadyMounted));
+
+		const PATHS: [&str; MAX_MOUNTS - 1] =
+			["/a", "/b", "/c", "/d", "/e", "/f", "/g",];
+		for path in PATHS {
+			mount(path, fs,).unwrap();
+		}
+		let full =
+			VfsError::TooManyEntries { what: "mounts", capacity: MAX_MOUNTS, };
+		assert_eq!(error(mount("/", fs,)), Some(full));
+		assert_eq!(error(open("relative")), Some(VfsError::InvalidPath));
+	}
+
+	#[test]
+	fn test_files_and_directories() {
+		let _lock = lock();
+		let root = initrd(&[("etc/motd", b"0123456789",),],);
+		mount("/", leak(Cpio::new(root,).unwrap(),),).unwrap();
+
+		assert_eq!(error(open("/etc")), Some(VfsError::IsADirectory));
+		assert_eq!(error(read_dir("/etc/motd")), Some(VfsError::NotADirectory));
+		let names: Vec<String,> = read_dir("/etc",)
+			.unwrap()
+			.map(|entry| entry.unwrap().name().into(),)
+			.collect();
+		assert_eq!(names, ["motd"]);
+
+		let mut file = open("/etc/motd",).unwrap();
+		let file_stat = Metadata { kind: FileKind::File, size: 10, };
+		assert_eq!(file.stat().unwrap(), file_stat);
+		let mut buf = [0; 4];
+		assert_eq!(file.seek(SeekFrom::End(-3),).unwrap(), 7);
+		assert_eq!(file.read(&mut buf,).unwrap(), 3);
+		assert_eq!(&buf[..3], b"789");
+		assert_eq!(file.seek(SeekFrom::Current(-6),).unwrap(), 4);
+		assert_eq!(file.read_full(&mut buf,).unwrap(), 4);
+		assert_eq!(&buf, b"4567");
+		let before_start = file.seek(SeekFrom::Current(-9),);
+		assert_eq!(error(before_start), Some(VfsError::InvalidSeek));
+		assert_eq!(error(file.write(b"x")), Some(VfsError::ReadOnly));
+	}
+
+	#[test]
+	fn test_fat32_volume() {
+		let _lock = lock();
+		let boot = fat32::tests::mount(fat32::tests::sample(),);
+		mount("/boot", leak(boot,),).unwrap();
+		assert_eq!(read_to_string("/boot/efi/boot/BOOTAA64.EFI"), "efi");
+		assert_eq!(stat("/boot/oso_kernel.elf").unwrap().size, 3000);
+	}
+
+	#[test]
+	fn test_dir_entry_names_are_truncated_at_characters() {
+		let node = Node { id: 0, kind: FileKind::File, size: 0, };
+		let name: String = "あ".repeat(NAME_MAX / 3 + 1,);
+		let entry = DirEntry::new(&name, node,);
+		assert_eq!(entry.name().len(), NAME_MAX);
+		let name = String::from("a",) + &name;
+		assert_eq!(DirEntry::new(&name, node,).name().len(), NAME_MAX - 2);
+	}
+
+	#[test]
+	fn test_init_mounts_the_first_archive() {
+		let _lock = lock();
+		let module = |contents: &'static [u8]| {
+			let start = contents.as_ptr() as u64;
+			Module::new(start, contents.len() as u64, CommandLine::empty(),)
+		};
+		let modules: &'static [Module] = Box::leak(Box::new([
+			module(b"not an archive",),
+			module(initrd(&[("autoexec.osh", b"help",),],),),
+			module(initrd(&[("autoexec.osh", b"second",),],),),
+		],),);
+		let mut boot_info = BootInfo::new(core::ptr::null(),);
+		let (ptr, len,) = (modules.as_ptr(), modules.len(),);
+		boot_info.modules = Modules { ptr, len, };
+		assert_eq!(unsafe { init(&boot_info,) }.unwrap(), Some(1));
+		assert_eq!(read_to_string("/autoexec.osh"), "help");
+		assert_eq!(stat("/dev/console").unwrap().kind, FileKind::Device);
+
+		unmount_all();
+		boot_info.modules = Modules::empty();
+		assert_eq!(unsafe { init(&boot_info,) }.unwrap(), None);
+		assert_eq!(error(stat("/autoexec.osh")), Some(VfsError::NotFound));
+	}
+}
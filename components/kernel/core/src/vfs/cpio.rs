@@ -0,0 +1,356 @@
+//! # cpio Initial Ramdisk
+//!
+//! Read-only file system over an archive in the `newc` format, as written by
+//! `cpio -H newc`. The archive is usually a boot module.
+//!
+//! ## Layout
+//!
+//! Each entry is a 110 byte header of ASCII hex fields, the path and the
+//! contents, the latter two padded to 4 bytes. The entry named `TRAILER!!!`
+//! ends the archive.
+//!
+//! Archives do not need entries for directories: a directory also exists if
+//! any path lies below it.
+//!
+//! ## Node IDs
+//!
+//! The low 32 bits are the offset of an entry, the high 32 bits the length of
+//! the prefix of its path which names the node. The root is `0`, the empty
+//! prefix of the first entry.
+
+use super::DirEntry;
+use super::FileKind;
+use super::FileSystem;
+use super::Node;
+use oso_error::Rslt;
+use oso_error::kernel::VfsError;
+use oso_error::oso_err;
+
+const MAGIC: &[u8] = b"070701";
+const HEADER_SIZE: usize = 110;
+const TRAILER: &str = "TRAILER!!!";
+
+const MODE_TYPE: u32 = 0o170_000;
+const MODE_DIRECTORY: u32 = 0o040_000;
+
+/// Initial ramdisk in the `newc` cpio format
+pub struct Cpio {
+	archive: &'static [u8],
+}
+
+/// Parsed header of an entry
+#[derive(Clone, Copy,)]
+struct Entry {
+	offset: usize,
+	/// path without a leading `./` or `/`
+	path:   &'static str,
+	mode:   u32,
+	data:   &'static [u8],
+	/// offset of the next entry
+	next:   usize,
+}
+
+impl Entry {
+	fn kind(&self,) -> FileKind {
+		if self.mode & MODE_TYPE == MODE_DIRECTORY {
+			FileKind::Directory
+		} else {
+			FileKind::File
+		}
+	}
+}
+
+impl Cpio {
+	/// # Errors
+	///
+	/// [`VfsError::Corrupt`] if `archive` does not start with a `newc` header
+	pub fn new(archive: &'static [u8],) -> Rslt<Self, VfsError,> {
+		if !archive.starts_with(MAGIC,) {
+			return Err(oso_err!(VfsError::Corrupt),);
+		}
+		Ok(Self { archive },)
+	}
+
+	/// Entry at `offset`, `None` at the trailer
+	fn entry(&self, offset: usize,) -> Rslt<Option<Entry,>, VfsError,> {
+		let corrupt = || oso_err!(VfsError::Corrupt);
+		let header = self
+			.archive
+			.get(offset..offset + HEADER_SIZE,)
+			.ok_or_else(corrupt,)?;
+		if !header.starts_with(MAGIC,) {
+			return Err(corrupt(),);
+		}
+		// magic, ino, mode, uid, gid, nlink, mtime, filesize, ...
+		let mode = hex(&header[14..22],)?;
+		let file_size = hex(&header[54..62],)? as usize;
+		let name_size = hex(&header[94..102],)? as usize;
+
+		let name_start = offset + HEADER_SIZE;
+		// the name size counts the terminating NUL
+		let name = self
+			.archive
+			.get(name_start..name_start + name_size.saturating_sub(1,),)
+			.ok_or_else(corrupt,)?;
+		let path = core::str::from_utf8(name,).map_err(|_| corrupt(),)?;
+		if path == TRAILER {
+			return Ok(None,);
+		}
+		let data_start = (name_start + name_size).next_multiple_of(4,);
+		let data = self
+			.archive
+			.get(data_start..data_start + file_size,)
+			.ok_or_else(corrupt,)?;
+		let path = path.trim_start_matches("./",).trim_start_matches('/',);
+		Ok(Some(Entry {
+			offset,
+			path,
+			mode,
+			data,
+			next: (data_start + file_size).next_multiple_of(4,),
+		},),)
+	}
+
+	/// Entry of `node` and the path it names
+	fn node_path(
+		&self,
+		node: &Node,
+	) -> Rslt<(Entry, &'static str,), VfsError,> {
+		let offset = node.id as u32 as usize;
+		let len = (node.id >> 32) as usize;
+		let entry = self.entry(offset,)?.ok_or(oso_err!(VfsError::NotFound),)?;
+		let path = entry.path.get(..len,).ok_or(oso_err!(VfsError::Corrupt),)?;
+		Ok((entry, path,),)
+	}
+
+	/// Whether an entry before `before` has a path at or below `path`
+	fn is_listed(&self, path: &str, before: usize,) -> Rslt<bool, VfsError,> {
+		let mut offset = 0;
+		while offset < before {
+			let Some(entry,) = self.entry(offset,)? else {
+				break;
+			};
+			if is_below(entry.path, path,) || entry.path == path {
+				return Ok(true,);
+			}
+			offset = entry.next;
+		}
+		Ok(false,)
+	}
+}
+
+impl FileSystem for Cpio {
+	fn root(&self,) -> Node {
+		Node { id: 0, kind: FileKind::Directory, size: 0, }
+	}
+
+	/// `cursor` is the offset of the next entry to look at
+	fn read_dir(
+		&self,
+		dir: &Node,
+		cursor: &mut u64,
+	) -> Rslt<Option<DirEntry,>, VfsError,> {
+		let dir_path = if dir.id == 0 { "" } else { self.node_path(dir,)?.1 };
+		while let Some(entry,) = self.entry(*cursor as usize,)? {
+			*cursor = entry.next as u64;
+			let rest = if dir_path.is_empty() {
+				entry.path
+			} else if is_below(entry.path, dir_path,) {
+				&entry.path[dir_path.len() + 1..]
+			} else {
+				continue;
+			};
+			if rest.is_empty() || rest == "." {
+				continue;
+			}
+
+			let (name, node,) = match rest.split_once('/',) {
+				// only a path below the child names it
+				Some((name, _,),) => {
+					let len = entry.path.len() - rest.len() + name.len();
+					let node = Node {
+						id:   entry.offset as u64 | (len as u64) << 32,
+						kind: FileKind::Directory,
+						size: 0,
+					};
+					(name, node,)
+				},
+				None => {
+					let len = entry.path.len() as u64;
+					let node = Node {
+						id:   entry.offset as u64 | len << 32,
+						kind: entry.kind(),
+						size: entry.data.len() as u64,
+					};
+					(rest, node,)
+				},
+			};
+			let child = &entry.path[..node.id as usize >> 32];
+			if !self.is_listed(child, entry.offset,)? {
+				return Ok(Some(DirEntry::new(name, node,),),);
+			}
+		}
+		Ok(None,)
+	}
+
+	fn read(
+		&self,
+		node: &Node,
+		offset: u64,
+		buf: &mut [u8],
+	) -> Rslt<usize, VfsError,> {
+		if node.is_dir() {
+			return Err(oso_err!(VfsError::IsADirectory),);
+		}
+		let (entry, _,) = self.node_path(node,)?;
+		let data = entry.data.get(offset as usize..,).unwrap_or_default();
+		let len = data.len().min(buf.len(),);
+		buf[..len].copy_from_slice(&data[..len],);
+		Ok(len,)
+	}
+}
+
+/// Whether `path` lies below the directory `dir`
+fn is_below(path: &str, dir: &str,) -> bool {
+	path.len() > dir.len()
+		&& path.starts_with(dir,)
+		&& path.as_bytes()[dir.len()] == b'/'
+}
+
+fn hex(field: &[u8],) -> Rslt<u32, VfsError,> {
+	core::str::from_utf8(field,)
+		.ok()
+		.and_then(|field| u32::from_str_radix(field, 16,).ok(),)
+		.ok_or(oso_err!(VfsError::Corrupt),)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	extern crate std;
+	use std::format;
+	use std::string::String;
+	use std::vec;
+	use std::vec::Vec;
+
+	pub(crate) const FILE: u32 = 0o100_644;
+	pub(crate) const DIRECTORY: u32 = 0o040_755;
+
+	/// `newc` archive of `entries` as path, mode and contents
+	pub(crate) fn archive(entries: &[(&str, u32, &[u8],)],) -> &'static [u8] {
+		let mut out = vec![];
+		let trailer = (TRAILER, 0, &[][..],);
+		for (path, mode, data,) in entries.iter().chain([&trailer,],) {
+			let fields = [
+				0,
+				*mode,
+				0,
+				0,
+				1,
+				0,
+				data.len() as u32,
+				0,
+				0,
+				0,
+				0,
+				path.len() as u32 + 1,
+				0,
+			];
+			out.extend_from_slice(MAGIC,);
+			for field in fields {
+				out.extend_from_slice(format!("{field:08X}").as_bytes(),);
+			}
+			out.extend_from_slice(path.as_bytes(),);
+			out.push(0,);
+			out.resize(out.len().next_multiple_of(4,), 0,);
+			out.extend_from_slice(data,);
+			out.resize(out.len().next_multiple_of(4,), 0,);
+		}
+		out.leak()
+	}
+
+	fn sample() -> Cpio {
+		Cpio::new(archive(&[
+			(".", DIRECTORY, b"",),
+			("./etc", DIRECTORY, b"",),
+			("./etc/motd", FILE, b"hello\n",),
+			("bin/sh", FILE, b"#!",),
+			("/init", FILE, b"initial",),
+			("bin/ls", FILE, b"",),
+		],),)
+		.unwrap()
+	}
+
+	/// names and kinds of the entries of `dir`
+	fn list(cpio: &Cpio, dir: &Node,) -> Vec<(String, FileKind,),> {
+		let mut cursor = 0;
+		let mut entries = vec![];
+		while let Some(entry,) = cpio.read_dir(dir, &mut cursor,).unwrap() {
+			entries.push((entry.name().into(), entry.node().kind,),);
+		}
+		entries
+	}
+
+	fn error<T,>(result: Rslt<T, VfsError,>,) -> Option<VfsError,> {
+		result.err().and_then(|e| e.desc,)
+	}
+
+	#[test]
+	fn test_directories_list_each_child_once() {
+		let cpio = sample();
+		let root = cpio.root();
+		let dir = |name: &str| (name.into(), FileKind::Directory,);
+		let file = |name: &str| (name.into(), FileKind::File,);
+		assert_eq!(list(&cpio, &root), [dir("etc"), dir("bin"), file("init")]);
+
+		let bin = cpio.find(&root, "bin",).unwrap();
+		assert_eq!(list(&cpio, &bin), [file("sh"), file("ls")]);
+		let etc = cpio.find(&root, "etc",).unwrap();
+		assert_eq!(list(&cpio, &etc), [file("motd")]);
+	}
+
+	#[test]
+	fn test_find_and_read() {
+		let cpio = sample();
+		let root = cpio.root();
+		let etc = cpio.find(&root, "etc",).unwrap();
+		let motd = cpio.find(&etc, "motd",).unwrap();
+		assert_eq!((motd.kind, motd.size), (FileKind::File, 6));
+
+		let mut buf = [0; 4];
+		assert_eq!(cpio.read(&motd, 0, &mut buf,).unwrap(), 4);
+		assert_eq!(&buf, b"hell");
+		assert_eq!(cpio.read(&motd, 4, &mut buf,).unwrap(), 2);
+		assert_eq!(&buf[..2], b"o\n");
+		assert_eq!(cpio.read(&motd, 9, &mut buf,).unwrap(), 0);
+
+		let missing = cpio.find(&root, "motd",);
+		assert_eq!(error(missing), Some(VfsError::NotFound));
+		let below_file = cpio.find(&motd, "x",);
+		assert_eq!(error(below_file), Some(VfsError::NotADirectory));
+		let dir = cpio.read(&etc, 0, &mut buf,);
+		assert_eq!(error(dir), Some(VfsError::IsADirectory));
+	}
+
+	#[test]
+	fn test_corrupt_archives() {
+		let corrupt = Some(VfsError::Corrupt,);
+		assert_eq!(error(Cpio::new(b"070707")), corrupt);
+
+		let valid = archive(&[("a", FILE, b"data",),],);
+		// the file ends before its contents
+		let cpio = Cpio::new(&valid[..HEADER_SIZE + 4],).unwrap();
+		assert_eq!(error(cpio.read_dir(&cpio.root(), &mut 0,)), corrupt);
+		// no trailer
+		let end = valid.len() - HEADER_SIZE - 12;
+		let cpio = Cpio::new(&valid[..end],).unwrap();
+		let mut cursor = 0;
+		assert!(cpio.read_dir(&cpio.root(), &mut cursor,).unwrap().is_some());
+		assert_eq!(error(cpio.read_dir(&cpio.root(), &mut cursor,)), corrupt);
+
+		let mut bad_hex = valid.to_vec();
+		bad_hex[54] = b'g';
+		let cpio = Cpio::new(bad_hex.leak(),).unwrap();
+		assert_eq!(error(cpio.read_dir(&cpio.root(), &mut 0,)), corrupt);
+	}
+}
@@ -0,0 +1,231 @@
+//! # devfs
+//!
+//! Synthetic file system of devices, usually mounted at `/dev`.
+//!
+//! ## Devices
+//!
+//! - `console`: Writes go to the kernel console. Reads return nothing, as the
+//!   console has no input
+//! - Block devices registered with [`register_block`], under the name they
+//!   were registered with. Reads and writes take any byte offset and length
+//!
+//! ## Node IDs
+//!
+//! `0` is the root, `1` the console and `2 + i` the block device `i`.
+
+use super::DirEntry;
+use super::FileKind;
+use super::FileSystem;
+use super::Node;
+use crate::base::io::print;
+use crate::driver::block::BlockDevice;
+use core::cell::RefCell;
+use core::cell::UnsafeCell;
+use oso_error::Rslt;
+use oso_error::kernel::VfsError;
+use oso_error::oso_err;
+
+/// Block devices devfs has room for
+pub const MAX_DEVICES: usize = 8;
+/// Size of the buffer partial blocks go through
+const BOUNCE_SIZE: usize = 4096;
+
+const ROOT: u64 = 0;
+const CONSOLE: u64 = 1;
+const FIRST_BLOCK: u64 = 2;
+
+static DEVICES: Devices = Devices(UnsafeCell::new([None; MAX_DEVICES],),);
+
+/// devices are registered during boot, before devfs is used
+struct Devices(UnsafeCell<[Option<BlockEntry,>; MAX_DEVICES],>,);
+
+unsafe impl Sync for Devices {}
+
+#[derive(Clone, Copy,)]
+struct BlockEntry {
+	name:   &'static str,
+	device: &'static RefCell<dyn BlockDevice,>,
+}
+
+/// File system of devices
+pub struct DevFs;
+
+/// Makes `device` appear as `name` in devfs
+///
+/// # Errors
+///
+/// - [`VfsError::InvalidPath`] if `name` is empty, has a `/` or is `console`
+/// - [`VfsError::AlreadyMounted`] if a device is registered as `name`
+/// - [`VfsError::TooManyEntries`] if [`MAX_DEVICES`] are registered
+pub fn register_block(
+	name: &'static str,
+	device: &'static RefCell<dyn BlockDevice,>,
+) -> Rslt<(), VfsError,> {
+	if name.is_empty() || name.contains('/',) || name == "console" {
+		return Err(oso_err!(VfsError::InvalidPath),);
+	}
+	let devices = unsafe { &mut *DEVICES.0.get() };
+	if devices.iter().flatten().any(|entry| entry.name == name,) {
+		return Err(oso_err!(VfsError::AlreadyMounted),);
+	}
+	let Some(slot,) = devices.iter_mut().find(|slot| slot.is_none(),) else {
+		return Err(oso_err!(VfsError::TooManyEntries {
+			what:     "devices",
+			capacity: MAX_DEVICES,
+		}),);
+	};
+	*slot = Some(BlockEntry { name, device, },);
+	Ok((),)
+}
+
+impl DevFs {
+	fn block(id: u64,) -> Rslt<BlockEntry, VfsError,> {
+		let devices = unsafe { &*DEVICES.0.get() };
+		id.checked_sub(FIRST_BLOCK,)
+			.and_then(|i| devices.get(i as usize,).copied().flatten(),)
+			.ok_or(oso_err!(VfsError::NotFound),)
+	}
+
+	fn node(id: u64,) -> Option<Node,> {
+		match id {
+			ROOT => Some(Node { id, kind: FileKind::Directory, size: 0, },),
+			CONSOLE => Some(Node { id, kind: FileKind::Device, size: 0, },),
+			_ => {
+				let device = Self::block(id,).ok()?.device.borrow();
+				let size = device.block_count() * device.block_size() as u64;
+				Some(Node { id, kind: FileKind::Device, size, },)
+			},
+		}
+	}
+}
+
+impl FileSystem for DevFs {
+	fn root(&self,) -> Node {
+		Node { id: ROOT, kind: FileKind::Directory, size: 0, }
+	}
+
+	/// `cursor` is the ID of the next node to look at
+	fn read_dir(
+		&self,
+		dir: &Node,
+		cursor: &mut u64,
+	) -> Rslt<Option<DirEntry,>, VfsError,> {
+		if dir.id != ROOT {
+			return Err(oso_err!(VfsError::NotADirectory),);
+		}
+		*cursor = (*cursor).max(CONSOLE,);
+		while *cursor < FIRST_BLOCK + MAX_DEVICES as u64 {
+			let id = *cursor;
+			*cursor += 1;
+			let Some(node,) = Self::node(id,) else {
+				continue;
+			};
+			let name = match id {
+				CONSOLE => "console",
+				_ => Self::block(node.id,)?.name,
+			};
+			return Ok(Some(DirEntry::new(name, node,),),);
+		}
+		Ok(None,)
+	}
+
+	fn read(
+		&self,
+		node: &Node,
+		offset: u64,
+		buf: &mut [u8],
+	) -> Rslt<usize, VfsError,> {
+		match node.id {
+			ROOT => Err(oso_err!(VfsError::IsADirectory),),
+			CONSOLE => Ok(0,),
+			_ => {
+				let mut device = Self::block(node.id,)?.device.borrow_mut();
+				let mut buf = buf;
+				let len = buf.len();
+				block_io(&mut *device, offset, len, |device, lba, at, bounce| {
+					device.read_blocks(lba, bounce,)?;
+					let len = (bounce.len() - at).min(buf.len(),);
+					let (head, rest,) =
+						core::mem::take(&mut buf,).split_at_mut(len,);
+					head.copy_from_slice(&bounce[at..at + len],);
+					buf = rest;
+					Ok(len,)
+				},)
+			},
+		}
+	}
+
+	fn write(
+		&self,
+		node: &Node,
+		offset: u64,
+		buf: &[u8],
+	) -> Rslt<usize, VfsError,> {
+		match node.id {
+			ROOT => Err(oso_err!(VfsError::IsADirectory),),
+			CONSOLE => {
+				for chunk in buf.utf8_chunks() {
+					print(format_args!("{}", chunk.valid()),);
+					if !chunk.invalid().is_empty() {
+						print(format_args!("{}", char::REPLACEMENT_CHARACTER),);
+					}
+				}
+				Ok(buf.len(),)
+			},
+			_ => {
+				let mut device = Self::block(node.id,)?.device.borrow_mut();
+				let mut buf = buf;
+				let len = buf.len();
+				block_io(&mut *device, offset, len, |device, lba, at, bounce| {
+					let len = (bounce.len() - at).min(buf.len(),);
+					// blocks written in part keep the rest of their content
+					if at != 0 || len != bounce.len() {
+						device.read_blocks(lba, bounce,)?;
+					}
+					bounce[at..at + len].copy_from_slice(&buf[..len],);
+					device.write_blocks(lba, bounce,)?;
+					buf = &buf[len..];
+					Ok(len,)
+				},)
+			},
+		}
+	}
+}
+
+/// Splits `len` bytes from `offset` of `device` into runs of blocks which fit
+/// the bounce buffer, and calls `run` with the first block of each, the
+/// offset into it and a buffer of its size. `run` returns the bytes it
+/// consumed. Stops at the end of the device
+fn block_io(
+	device: &mut dyn BlockDevice,
+	offset: u64,
+	len: usize,
+	mut run: impl FnMut(
+		&mut dyn BlockDevice,
+		u64,
+		usize,
+		&mut [u8],
+	) -> Rslt<usize, VfsError,>,
+) -> Rslt<usize, VfsError,> {
+	let block_size = device.block_size();
+	let size = device.block_count() * block_size as u64;
+	if block_size == 0 || block_size > BOUNCE_SIZE {
+		return Err(oso_err!(VfsError::Unsupported),);
+	}
+	let len = len.min(size.saturating_sub(offset,) as usize,);
+	let mut bounce = [0; BOUNCE_SIZE];
+	let per_run = BOUNCE_SIZE / block_size;
+
+	let mut done = 0;
+	while done < len {
+		let pos = offset + done as u64;
+		let lba = pos / block_size as u64;
+		let at = (pos % block_size as u64) as usize;
+		let blocks = (at + len - done)
+			.div_ceil(block_size,)
+			.min(per_run,)
+			.min((device.block_count() - lba) as usize,);
+		done += run(device, lba, at, &mut bounce[..blocks * block_size],)?;
+	}
+	Ok(done,)
+}
@@ -0,0 +1,904 @@
+//! # FAT32
+//!
+//! Read-only FAT32 over a [`BlockDevice`], such as the boot SD card or the
+//! EFI system partition.
+//!
+//! ## Volume
+//!
+//! [`Fat32::new`] takes the volume at the start of the device, or the first
+//! FAT32 partition of its MBR or GPT partition table.
+//!
+//! ## Names
+//!
+//! Long names are used where present, short names otherwise. Lookup ignores
+//! ASCII case, like FAT does.
+//!
+//! ## Node IDs
+//!
+//! The ID of a node is its first cluster.
+
+use super::DirEntry;
+use super::FileKind;
+use super::FileSystem;
+use super::NAME_MAX;
+use super::Node;
+use crate::driver::block::BlockDevice;
+use core::cell::RefCell;
+use oso_error::Rslt;
+use oso_error::kernel::VfsError;
+use oso_error::oso_err;
+
+const SECTOR_SIZE: usize = 512;
+const DIR_ENTRY_SIZE: usize = 32;
+/// FAT entries at or above this end a cluster chain
+const END_OF_CHAIN: u32 = 0x0fff_fff8;
+const CLUSTER_MASK: u32 = 0x0fff_ffff;
+
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_LONG_NAME: u8 = 0x0f;
+const DELETED: u8 = 0xe5;
+/// UTF-16 code units of a long name
+const LONG_NAME_MAX: usize = 255;
+
+/// MBR partition types of FAT32
+const MBR_FAT32: [u8; 2] = [0x0b, 0x0c];
+const MBR_PROTECTIVE: u8 = 0xee;
+const GPT_SIGNATURE: &[u8] = b"EFI PART";
+
+type Sector = [u8; SECTOR_SIZE];
+
+/// FAT32 volume on a block device
+pub struct Fat32 {
+	device:              &'static RefCell<dyn BlockDevice,>,
+	sectors_per_cluster: u64,
+	/// first block of the first FAT
+	fat_start:           u64,
+	/// first block of cluster `2`
+	data_start:          u64,
+	root_cluster:        u32,
+	cluster_count:       u32,
+}
+
+impl Fat32 {
+	/// Finds a FAT32 volume on `device`
+	///
+	/// # Errors
+	///
+	/// - [`VfsError::NotFound`] if the device holds no FAT32 volume
+	/// - [`VfsError::Unsupported`] if its sectors are not the size of the
+	///   blocks of the device
+	/// - [`VfsError::Device`] if reading the device fails
+	pub fn new(
+		device: &'static RefCell<dyn BlockDevice,>,
+	) -> Rslt<Self, VfsError,> {
+		if device.borrow().block_size() != SECTOR_SIZE {
+			return Err(oso_err!(VfsError::Unsupported),);
+		}
+		let start = find_volume(device,)?;
+		let mut bpb = [0; SECTOR_SIZE];
+		device.borrow_mut().read_blocks(start, &mut bpb,)?;
+
+		let sectors_per_cluster = bpb[13] as u64;
+		let reserved = u16_at(&bpb, 14,) as u64;
+		let fat_count = bpb[16] as u64;
+		let total = match u16_at(&bpb, 19,) {
+			0 => u32_at(&bpb, 32,) as u64,
+			total => total as u64,
+		};
+		let fat_size = u32_at(&bpb, 36,) as u64;
+		let root_cluster = u32_at(&bpb, 44,);
+
+		let fat_start = start + reserved;
+		let data_start = fat_start + fat_count * fat_size;
+		let data_sectors = (start + total).saturating_sub(data_start,);
+		let cluster_count = (data_sectors / sectors_per_cluster) as u32;
+		let volume = Self {
+			device,
+			sectors_per_cluster,
+			fat_start,
+			data_start,
+			root_cluster,
+			cluster_count,
+		};
+		if !volume.is_cluster(root_cluster,) {
+			return Err(oso_err!(VfsError::Corrupt),);
+		}
+		Ok(volume,)
+	}
+
+	fn cluster_size(&self,) -> u64 {
+		self.sectors_per_cluster * SECTOR_SIZE as u64
+	}
+
+	fn is_cluster(&self, cluster: u32,) -> bool {
+		(2..self.cluster_count.saturating_add(2,)).contains(&cluster,)
+	}
+
+	fn cluster_lba(&self, cluster: u32,) -> u64 {
+		self.data_start + (cluster - 2) as u64 * self.sectors_per_cluster
+	}
+
+	fn read_sectors(&self, lba: u64, buf: &mut [u8],) -> Rslt<(), VfsError,> {
+		self.device.borrow_mut().read_blocks(lba, buf,)?;
+		Ok((),)
+	}
+
+	/// Cluster after `cluster` in its chain, `None` at the end
+	fn next_cluster(&self, cluster: u32,) -> Rslt<Option<u32,>, VfsError,> {
+		let offset = cluster as u64 * 4;
+		let mut sector = [0; SECTOR_SIZE];
+		let lba = self.fat_start + offset / SECTOR_SIZE as u64;
+		self.read_sectors(lba, &mut sector,)?;
+		let next =
+			u32_at(&sector, offset as usize % SECTOR_SIZE,) & CLUSTER_MASK;
+		if next >= END_OF_CHAIN {
+			Ok(None,)
+		} else if self.is_cluster(next,) {
+			Ok(Some(next,),)
+		} else {
+			Err(oso_err!(VfsError::Corrupt),)
+		}
+	}
+
+	/// Cluster `index` of the chain from `first`, `None` past its end
+	fn nth_cluster(
+		&self,
+		first: u32,
+		index: u64,
+	) -> Rslt<Option<u32,>, VfsError,> {
+		if !self.is_cluster(first,) {
+			return Err(oso_err!(VfsError::Corrupt),);
+		}
+		let mut cluster = first;
+		for _ in 0..index {
+			match self.next_cluster(cluster,)? {
+				Some(next,) => cluster = next,
+				None => return Ok(None,),
+			}
+		}
+		Ok(Some(cluster,),)
+	}
+}
+
+impl FileSystem for Fat32 {
+	fn root(&self,) -> Node {
+		Node {
+			id:   self.root_cluster as u64,
+			kind: FileKind::Directory,
+			size: 0,
+		}
+	}
+
+	/// `cursor` is the index of the next 32 byte directory entry
+	fn read_dir(
+		&self,
+		dir: &Node,
+		cursor: &mut u64,
+	) -> Rslt<Option<DirEntry,>, VfsError,> {
+		if !dir.is_dir() {
+			return Err(oso_err!(VfsError::NotADirectory),);
+		}
+		let per_cluster = self.cluster_size() / DIR_ENTRY_SIZE as u64;
+		let Some(mut cluster,) =
+			self.nth_cluster(dir.id as u32, *cursor / per_cluster,)?
+		else {
+			return Ok(None,);
+		};
+		let mut long_name = LongName::new();
+		let mut sector = [0; SECTOR_SIZE];
+		let mut loaded = None;
+		let mut first = true;
+
+		loop {
+			let index = *cursor % per_cluster;
+			if index == 0 && !first {
+				match self.next_cluster(cluster,)? {
+					Some(next,) => cluster = next,
+					None => return Ok(None,),
+				}
+			}
+			first = false;
+			let byte = index as usize * DIR_ENTRY_SIZE;
+			let lba = self.cluster_lba(cluster,) + (byte / SECTOR_SIZE) as u64;
+			if loaded != Some(lba,) {
+				self.read_sectors(lba, &mut sector,)?;
+				loaded = Some(lba,);
+			}
+			let at = byte % SECTOR_SIZE;
+			let entry: &[u8; DIR_ENTRY_SIZE] =
+				sector[at..at + DIR_ENTRY_SIZE].try_into().unwrap();
+
+			match entry[0] {
+				// end of the directory. the cursor stays here
+				0 => return Ok(None,),
+				DELETED => {
+					long_name.clear();
+					*cursor += 1;
+					continue;
+				},
+				_ => *cursor += 1,
+			}
+			let attr = entry[11];
+			if attr & ATTR_LONG_NAME == ATTR_LONG_NAME {
+				long_name.push(entry,);
+				continue;
+			}
+			if attr & ATTR_VOLUME_ID != 0 {
+				long_name.clear();
+				continue;
+			}
+
+			let short = &entry[..11];
+			if short == b".          " || short == b"..         " {
+				long_name.clear();
+				continue;
+			}
+			let cluster =
+				(u16_at(entry, 20,) as u32) << 16 | u16_at(entry, 26,) as u32;
+			let node = Node {
+				id:   cluster as u64,
+				kind: if attr & ATTR_DIRECTORY != 0 {
+					FileKind::Directory
+				} else {
+					FileKind::File
+				},
+				size: u32_at(entry, 28,) as u64,
+			};
+			let mut buf = [0; NAME_MAX];
+			let name = match long_name.decode(short, &mut buf,) {
+				Some(len,) => &buf[..len],
+				None => {
+					let len = short_name(entry, &mut buf,);
+					&buf[..len]
+				},
+			};
+			// both decoders write whole UTF-8 characters
+			let name = core::str::from_utf8(name,).unwrap_or_default();
+			return Ok(Some(DirEntry::new(name, node,),),);
+		}
+	}
+
+	fn read(
+		&self,
+		node: &Node,
+		offset: u64,
+		buf: &mut [u8],
+	) -> Rslt<usize, VfsError,> {
+		if node.is_dir() {
+			return Err(oso_err!(VfsError::IsADirectory),);
+		}
+		if offset >= node.size {
+			return Ok(0,);
+		}
+		let len = buf.len().min((node.size - offset) as usize,);
+		let cluster_size = self.cluster_size();
+		let Some(mut cluster,) =
+			self.nth_cluster(node.id as u32, offset / cluster_size,)?
+		else {
+			return Err(oso_err!(VfsError::Corrupt),);
+		};
+
+		let mut done = 0;
+		let mut pos = offset;
+		while done < len {
+			if done != 0 && pos.is_multiple_of(cluster_size,) {
+				cluster = self
+					.next_cluster(cluster,)?
+					.ok_or(oso_err!(VfsError::Corrupt),)?;
+			}
+			let in_cluster = (pos % cluster_size) as usize;
+			let lba =
+				self.cluster_lba(cluster,) + (in_cluster / SECTOR_SIZE) as u64;
+			let in_sector = in_cluster % SECTOR_SIZE;
+			let remaining = len - done;
+
+			// whole sectors go straight into `buf`
+			let sectors = ((cluster_size as usize - in_cluster) / SECTOR_SIZE)
+				.min(remaining / SECTOR_SIZE,);
+			let read = if in_sector == 0 && sectors != 0 {
+				let bytes = sectors * SECTOR_SIZE;
+				self.read_sectors(lba, &mut buf[done..done + bytes],)?;
+				bytes
+			} else {
+				let mut sector = [0; SECTOR_SIZE];
+				self.read_sectors(lba, &mut sector,)?;
+				let bytes = (SECTOR_SIZE - in_sector).min(remaining,);
+				buf[done..done + bytes]
+					.copy_from_slice(&sector[in_sector..in_sector + bytes],);
+				bytes
+			};
+			done += read;
+			pos += read as u64;
+		}
+		Ok(len,)
+	}
+
+	fn find(&self, dir: &Node, name: &str,) -> Rslt<Node, VfsError,> {
+		if !dir.is_dir() {
+			return Err(oso_err!(VfsError::NotADirectory),);
+		}
+		let mut cursor = 0;
+		while let Some(entry,) = self.read_dir(dir, &mut cursor,)? {
+			if entry.name().eq_ignore_ascii_case(name,) {
+				return Ok(entry.node(),);
+			}
+		}
+		Err(oso_err!(VfsError::NotFound),)
+	}
+}
+
+/// Long name collected from the entries in front of a short entry, which
+/// store it backwards in pieces of 13 UTF-16 code units
+struct LongName {
+	units:    [u16; LONG_NAME_MAX],
+	checksum: u8,
+	/// sequence number of the next expected piece. `0` when no name is
+	/// collected or the name is complete
+	expect:   u8,
+	valid:    bool,
+}
+
+impl LongName {
+	const LAST: u8 = 0x40;
+	/// offsets of the code units in an entry
+	const UNITS: [usize; 13] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30,];
+
+	fn new() -> Self {
+		Self {
+			units:    [0xffff; LONG_NAME_MAX],
+			checksum: 0,
+			expect:   0,
+			valid:    false,
+		}
+	}
+
+	fn clear(&mut self,) {
+		self.valid = false;
+		self.expect = 0;
+	}
+
+	fn push(&mut self, entry: &[u8; DIR_ENTRY_SIZE],) {
+		let order = entry[0];
+		let sequence = order & 0x1f;
+		if order & Self::LAST != 0 {
+			self.units = [0xffff; LONG_NAME_MAX];
+			self.checksum = entry[13];
+			self.valid = true;
+		} else if !self.valid
+			|| sequence != self.expect
+			|| entry[13] != self.checksum
+		{
+			self.valid = false;
+			return;
+		}
+		if sequence == 0 {
+			self.valid = false;
+			return;
+		}
+		self.expect = sequence - 1;
+
+		let base = (sequence as usize - 1) * Self::UNITS.len();
+		for (i, offset,) in Self::UNITS.iter().enumerate() {
+			if let Some(unit,) = self.units.get_mut(base + i,) {
+				*unit = u16_at(entry, *offset,);
+			}
+		}
+	}
+
+	/// Writes the name as UTF-8 into `out` if it belongs to `short`. Returns
+	/// the length
+	fn decode(
+		&mut self,
+		short: &[u8],
+		out: &mut [u8; NAME_MAX],
+	) -> Option<usize,> {
+		let complete = self.valid && self.expect == 0;
+		self.clear();
+		if !complete || checksum(short,) != self.checksum {
+			return None;
+		}
+		let units = self
+			.units
+			.iter()
+			.copied()
+			.take_while(|unit| *unit != 0 && *unit != 0xffff,);
+		let mut len = 0;
+		for c in char::decode_utf16(units,) {
+			let c = c.unwrap_or(char::REPLACEMENT_CHARACTER,);
+			if len + c.len_utf8() > NAME_MAX {
+				break;
+			}
+			len += c.encode_utf8(&mut out[len..],).len();
+		}
+		Some(len,)
+	}
+}
+
+/// Checksum of a short name stored in its long name entries
+fn checksum(short: &[u8],) -> u8 {
+	short
+		.iter()
+		.fold(0u8, |sum, byte| sum.rotate_right(1,).wrapping_add(*byte,),)
+}
+
+/// Writes the `NAME.EXT` form of a short entry into `out`. Returns the length
+fn short_name(
+	entry: &[u8; DIR_ENTRY_SIZE],
+	out: &mut [u8; NAME_MAX],
+) -> usize {
+	const LOWER_BASE: u8 = 0x08;
+	const LOWER_EXT: u8 = 0x10;
+
+	let case = entry[12];
+	let mut len = 0;
+	let mut put = |bytes: &[u8], lower: bool| {
+		for byte in bytes.iter().take_while(|b| **b != b' ',) {
+			// other bytes are in an OEM code page
+			let byte = match *byte {
+				0x05 => b'_',
+				b if !b.is_ascii() => b'_',
+				b if lower => b.to_ascii_lowercase(),
+				b => b,
+			};
+			out[len] = byte;
+			len += 1;
+		}
+	};
+	put(&entry[..8], case & LOWER_BASE != 0,);
+	if entry[8] != b' ' {
+		put(b".", false,);
+		put(&entry[8..11], case & LOWER_EXT != 0,);
+	}
+	len
+}
+
+/// First block of a FAT32 volume on `device`
+fn find_volume(device: &RefCell<dyn BlockDevice,>,) -> Rslt<u64, VfsError,> {
+	let mut sector = [0; SECTOR_SIZE];
+	device.borrow_mut().read_blocks(0, &mut sector,)?;
+	if is_fat32(&sector,) {
+		return Ok(0,);
+	}
+	if sector[510..] != [0x55, 0xaa] {
+		return Err(oso_err!(VfsError::NotFound),);
+	}
+
+	let mbr = sector;
+	for entry in mbr[446..510].chunks_exact(16,) {
+		let ty = entry[4];
+		let start = u32_at(entry, 8,) as u64;
+		if ty == MBR_PROTECTIVE {
+			return find_gpt_volume(device,);
+		}
+		if MBR_FAT32.contains(&ty,) && is_fat32_at(device, start,)? {
+			return Ok(start,);
+		}
+	}
+	Err(oso_err!(VfsError::NotFound),)
+}
+
+/// First partition of a GPT which holds a FAT32 volume
+fn find_gpt_volume(
+	device: &RefCell<dyn BlockDevice,>,
+) -> Rslt<u64, VfsError,> {
+	let mut header = [0; SECTOR_SIZE];
+	device.borrow_mut().read_blocks(1, &mut header,)?;
+	if !header.starts_with(GPT_SIGNATURE,) {
+		return Err(oso_err!(VfsError::Corrupt),);
+	}
+	let entries = u64_at(&header, 72,);
+	let count = u32_at(&header, 80,) as u64;
+	let size = u32_at(&header, 84,) as u64;
+	if size < 128 || !(SECTOR_SIZE as u64).is_multiple_of(size,) {
+		return Err(oso_err!(VfsError::Unsupported),);
+	}
+
+	let per_sector = SECTOR_SIZE as u64 / size;
+	let mut sector = [0; SECTOR_SIZE];
+	for i in 0..count {
+		if i % per_sector == 0 {
+			let lba = entries + i / per_sector;
+			device.borrow_mut().read_blocks(lba, &mut sector,)?;
+		}
+		let at = ((i % per_sector) * size) as usize;
+		let entry = &sector[at..at + size as usize];
+		// unused entries have a zero type GUID
+		if entry[..16].iter().all(|b| *b == 0,) {
+			continue;
+		}
+		let start = u64_at(entry, 32,);
+		if is_fat32_at(device, start,)? {
+			return Ok(start,);
+		}
+	}
+	Err(oso_err!(VfsError::NotFound),)
+}
+
+fn is_fat32_at(
+	device: &RefCell<dyn BlockDevice,>,
+	lba: u64,
+) -> Rslt<bool, VfsError,> {
+	if lba >= device.borrow().block_count() {
+		return Ok(false,);
+	}
+	let mut sector = [0; SECTOR_SIZE];
+	device.borrow_mut().read_blocks(lba, &mut sector,)?;
+	Ok(is_fat32(&sector,),)
+}
+
+/// Whether `sector` is the boot sector of a FAT32 volume with 512 byte sectors
+fn is_fat32(sector: &Sector,) -> bool {
+	let sectors_per_cluster = sector[13];
+	sector[510..] == [0x55, 0xaa]
+		&& u16_at(sector, 11,) as usize == SECTOR_SIZE
+		&& sectors_per_cluster.is_power_of_two()
+		&& sector[16] != 0
+		// FAT12 and FAT16 have a 16 bit FAT size
+		&& u16_at(sector, 22,) == 0
+		&& u32_at(sector, 36,) != 0
+}
+
+fn u16_at(bytes: &[u8], offset: usize,) -> u16 {
+	u16::from_le_bytes([bytes[offset], bytes[offset + 1],],)
+}
+
+fn u32_at(bytes: &[u8], offset: usize,) -> u32 {
+	u16_at(bytes, offset,) as u32 | (u16_at(bytes, offset + 2,) as u32) << 16
+}
+
+fn u64_at(bytes: &[u8], offset: usize,) -> u64 {
+	u32_at(bytes, offset,) as u64 | (u32_at(bytes, offset + 4,) as u64) << 32
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	extern crate std;
+	use crate::driver::block::tests::RamDisk;
+	use std::boxed::Box;
+	use std::string::String;
+	use std::vec;
+	use std::vec::Vec;
+
+	type RawEntry = [u8; DIR_ENTRY_SIZE];
+
+	const RESERVED: usize = 32;
+	const FATS: usize = 2;
+	/// sectors of a FAT, for 1024 clusters
+	const FAT_SECTORS: usize = 8;
+
+	/// FAT32 volume built in memory
+	pub(crate) struct Volume {
+		sectors_per_cluster: usize,
+		data:                Vec<u8,>,
+		fat:                 Vec<u32,>,
+		next:                u32,
+	}
+
+	impl Volume {
+		pub(crate) fn new(sectors_per_cluster: usize,) -> Self {
+			let mut fat = vec![0; FAT_SECTORS * SECTOR_SIZE / 4];
+			fat[0] = 0x0fff_fff8;
+			fat[1] = 0x0fff_ffff;
+			Self { sectors_per_cluster, data: vec![], fat, next: 2, }
+		}
+
+		fn cluster_size(&self,) -> usize {
+			self.sectors_per_cluster * SECTOR_SIZE
+		}
+
+		/// Writes `data` into a new chain whose clusters are `stride` apart.
+		/// Returns the first cluster
+		pub(crate) fn chain(&mut self, data: &[u8], stride: u32,) -> u32 {
+			let count = data.len().div_ceil(self.cluster_size(),).max(1,);
+			let first = self.next;
+			let size = self.cluster_size();
+			for (i, chunk,) in data.chunks(size,).enumerate() {
+				let at = (first + i as u32 * stride - 2) as usize * size;
+				self.data.resize(self.data.len().max(at + size,), 0,);
+				self.data[at..at + chunk.len()].copy_from_slice(chunk,);
+			}
+			for i in 0..count as u32 {
+				let cluster = first + i * stride;
+				let last = i + 1 == count as u32;
+				self.fat[cluster as usize] =
+					if last { 0x0fff_ffff } else { cluster + stride };
+			}
+			self.next = first + count as u32 * stride;
+			first
+		}
+
+		/// Directory of `entries`. Returns its first cluster
+		pub(crate) fn dir(&mut self, entries: &[RawEntry],) -> u32 {
+			self.chain(entries.as_flattened(), 1,)
+		}
+
+		/// Image of the volume with the root directory at `root`
+		pub(crate) fn image(&self, root: u32,) -> Vec<u8,> {
+			let clusters = self.fat.len() - 2;
+			let data_start = RESERVED + FATS * FAT_SECTORS;
+			let total = data_start + clusters * self.sectors_per_cluster;
+			let mut image = vec![0; total * SECTOR_SIZE];
+			let bpb = &mut image[..SECTOR_SIZE];
+			bpb[..3].copy_from_slice(&[0xeb, 0x58, 0x90,],);
+			bpb[11..13].copy_from_slice(&(SECTOR_SIZE as u16).to_le_bytes(),);
+			bpb[13] = self.sectors_per_cluster as u8;
+			bpb[14..16].copy_from_slice(&(RESERVED as u16).to_le_bytes(),);
+			bpb[16] = FATS as u8;
+			bpb[21] = 0xf8;
+			bpb[32..36].copy_from_slice(&(total as u32).to_le_bytes(),);
+			bpb[36..40].copy_from_slice(&(FAT_SECTORS as u32).to_le_bytes(),);
+			bpb[44..48].copy_from_slice(&root.to_le_bytes(),);
+			bpb[510..].copy_from_slice(&[0x55, 0xaa,],);
+
+			let fat: Vec<_,> =
+				self.fat.iter().flat_map(|e| e.to_le_bytes(),).collect();
+			for i in 0..FATS {
+				let at = (RESERVED + i * FAT_SECTORS) * SECTOR_SIZE;
+				image[at..at + fat.len()].copy_from_slice(&fat,);
+			}
+			let at = data_start * SECTOR_SIZE;
+			image[at..at + self.data.len()].copy_from_slice(&self.data,);
+			image
+		}
+	}
+
+	/// short entry named `short`, in the on-disk form without the dot
+	pub(crate) fn short(
+		short: &[u8; 11],
+		attr: u8,
+		cluster: u32,
+		size: u32,
+	) -> RawEntry {
+		let mut entry = [0; DIR_ENTRY_SIZE];
+		entry[..11].copy_from_slice(short,);
+		entry[11] = attr;
+		let high = (cluster >> 16) as u16;
+		entry[20..22].copy_from_slice(&high.to_le_bytes(),);
+		entry[26..28].copy_from_slice(&(cluster as u16).to_le_bytes(),);
+		entry[28..32].copy_from_slice(&size.to_le_bytes(),);
+		entry
+	}
+
+	/// long name entries of `name` in front of the short entry `entry`
+	pub(crate) fn named(name: &str, entry: RawEntry,) -> Vec<RawEntry,> {
+		let mut units: Vec<u16,> = name.encode_utf16().collect();
+		units.push(0,);
+		units.resize(units.len().next_multiple_of(13,), 0xffff,);
+		let sum = checksum(&entry[..11],);
+		let pieces = units.len() / 13;
+		let mut entries: Vec<_,> = units
+			.chunks(13,)
+			.enumerate()
+			.map(|(i, piece,)| {
+				let mut long = [0; DIR_ENTRY_SIZE];
+				long[0] = i as u8 + 1;
+				if i + 1 == pieces {
+					long[0] |= LongName::LAST;
+				}
+				long[11] = ATTR_LONG_NAME;
+				long[13] = sum;
+				for (unit, at,) in piece.iter().zip(LongName::UNITS,) {
+					long[at..at + 2].copy_from_slice(&unit.to_le_bytes(),);
+				}
+				long
+			},)
+			.collect();
+		entries.reverse();
+		entries.push(entry,);
+		entries
+	}
+
+	/// content of the file `kernel` of [`sample`]
+	fn kernel_data() -> Vec<u8,> {
+		(0..3000u32).map(|i| (i * 7) as u8,).collect()
+	}
+
+	/// volume with two sectors per cluster and the tree
+	///
+	/// ```text
+	/// oso_kernel.elf  3000 bytes in every other cluster
+	/// MOTD            700 bytes
+	/// notes.txt       stored lower case in the short entry
+	/// README.TXT      with a long name of another entry
+	/// EFI/BOOT/BOOTAA64.EFI
+	/// MANY/F00 .. F39 empty files, across two clusters
+	/// ```
+	pub(crate) fn sample() -> Vec<u8,> {
+		let mut volume = Volume::new(2,);
+		let kernel = volume.chain(&kernel_data(), 2,);
+		let motd = volume.chain(&[b'm'; 700], 1,);
+		let efi_boot_file = volume.chain(b"efi", 1,);
+		let boot = volume.next;
+		let boot_entries = [
+			short(b".          ", ATTR_DIRECTORY, boot, 0,),
+			short(b"..         ", ATTR_DIRECTORY, 0, 0,),
+			short(b"BOOTAA64EFI", 0x20, efi_boot_file, 3,),
+		];
+		assert_eq!(volume.dir(&boot_entries,), boot);
+		let boot = short(b"BOOT       ", ATTR_DIRECTORY, boot, 0,);
+		let efi = volume.dir(&[boot,],);
+		let many: Vec<_,> = (0..40)
+			.map(|i| {
+				let name = std::format!("F{i:02}        ");
+				short(name.as_bytes().try_into().unwrap(), 0x20, 0, 0,)
+			},)
+			.collect();
+		let many = volume.dir(&many,);
+
+		let mut root = vec![short(b"OSO        ", ATTR_VOLUME_ID, 0, 0,)];
+		let kernel_entry = short(b"OSO_KE~1ELF", 0x20, kernel, 3000,);
+		root.extend(named("oso_kernel.elf", kernel_entry,),);
+		let mut deleted = short(b"OLD     TXT", 0x20, 0, 0,);
+		deleted[0] = DELETED;
+		root.push(deleted,);
+		root.push(short(b"MOTD       ", 0x20, motd, 700,),);
+		let mut notes = short(b"NOTES   TXT", 0x20, 0, 0,);
+		notes[12] = 0x18;
+		root.push(notes,);
+		// a long name left over from another short entry
+		let other = short(b"OTHER      ", 0x20, 0, 0,);
+		root.extend(named("other name", other,).split_last().unwrap().1,);
+		root.push(short(b"README  TXT", 0x20, 0, 0,),);
+		root.push(short(b"EFI        ", ATTR_DIRECTORY, efi, 0,),);
+		root.push(short(b"MANY       ", ATTR_DIRECTORY, many, 0,),);
+		let root = volume.dir(&root,);
+		volume.image(root,)
+	}
+
+	pub(crate) fn device(
+		image: Vec<u8,>,
+		block_size: usize,
+	) -> &'static RefCell<dyn BlockDevice,> {
+		Box::leak(Box::new(RefCell::new(RamDisk::new(image, block_size,),),),)
+	}
+
+	pub(crate) fn mount(image: Vec<u8,>,) -> Fat32 {
+		Fat32::new(device(image, SECTOR_SIZE,),).unwrap()
+	}
+
+	fn list(fat: &Fat32, dir: &Node,) -> Vec<(String, FileKind, u64,),> {
+		let mut cursor = 0;
+		let mut entries = vec![];
+		while let Some(entry,) = fat.read_dir(dir, &mut cursor,).unwrap() {
+			let node = entry.node();
+			entries.push((entry.name().into(), node.kind, node.size,),);
+		}
+		entries
+	}
+
+	fn error<T,>(result: Rslt<T, VfsError,>,) -> Option<VfsError,> {
+		result.err().and_then(|e| e.desc,)
+	}
+
+	#[test]
+	fn test_volume_locations() {
+		let volume = sample();
+		let start = 8;
+		let offset = start * SECTOR_SIZE;
+		let mut disk = vec![0; offset + volume.len()];
+		disk[offset..].copy_from_slice(&volume,);
+
+		// MBR with a partition of another type first
+		let mut mbr = disk.clone();
+		mbr[446 + 4] = 0x07;
+		mbr[446 + 8..446 + 12].copy_from_slice(&1u32.to_le_bytes(),);
+		mbr[462 + 4] = 0x0c;
+		let lba = (start as u32).to_le_bytes();
+		mbr[462 + 8..462 + 12].copy_from_slice(&lba,);
+		mbr[510..512].copy_from_slice(&[0x55, 0xaa,],);
+		assert_eq!(find_volume(device(mbr, SECTOR_SIZE,),).unwrap(), 8);
+
+		// GPT whose first entry is unused
+		let mut gpt = disk.clone();
+		gpt[446 + 4] = MBR_PROTECTIVE;
+		gpt[510..512].copy_from_slice(&[0x55, 0xaa,],);
+		let header = &mut gpt[SECTOR_SIZE..2 * SECTOR_SIZE];
+		header[..8].copy_from_slice(GPT_SIGNATURE,);
+		header[72..80].copy_from_slice(&2u64.to_le_bytes(),);
+		header[80..84].copy_from_slice(&4u32.to_le_bytes(),);
+		header[84..88].copy_from_slice(&128u32.to_le_bytes(),);
+		let entry = &mut gpt[2 * SECTOR_SIZE + 128..2 * SECTOR_SIZE + 256];
+		entry[..16].fill(0xaa,);
+		entry[32..40].copy_from_slice(&(start as u64).to_le_bytes(),);
+		assert_eq!(find_volume(device(gpt, SECTOR_SIZE,),).unwrap(), 8);
+
+		let not_found = Some(VfsError::NotFound,);
+		let blank = find_volume(device(vec![0; 4096], 512,),);
+		assert_eq!(error(blank), not_found);
+		// partitions past the end of the device are skipped
+		let mut outside = disk.clone();
+		outside[446 + 4] = 0x0c;
+		outside[446 + 8..446 + 12].copy_from_slice(&u32::MAX.to_le_bytes(),);
+		outside[510..512].copy_from_slice(&[0x55, 0xaa,],);
+		let outside = find_volume(device(outside, SECTOR_SIZE,),);
+		assert_eq!(error(outside), not_found);
+
+		let unsupported = Fat32::new(device(volume, 4096,),);
+		assert_eq!(error(unsupported), Some(VfsError::Unsupported));
+	}
+
+	#[test]
+	fn test_read_dir() {
+		let fat = mount(sample(),);
+		let root = fat.root();
+		let dir = |name: &str| (name.into(), FileKind::Directory, 0,);
+		let file = |name: &str, size| (name.into(), FileKind::File, size,);
+		let entries = [
+			file("oso_kernel.elf", 3000,),
+			file("MOTD", 700,),
+			file("notes.txt", 0,),
+			file("README.TXT", 0,),
+			dir("EFI",),
+			dir("MANY",),
+		];
+		assert_eq!(list(&fat, &root), entries);
+
+		let many = fat.find(&root, "many",).unwrap();
+		let names: Vec<_,> =
+			list(&fat, &many,).into_iter().map(|entry| entry.0,).collect();
+		let expected: Vec<_,> =
+			(0..40).map(|i| std::format!("F{i:02}"),).collect();
+		assert_eq!(names, expected);
+		let motd = fat.find(&root, "motd",).unwrap();
+		let not_a_directory = fat.read_dir(&motd, &mut 0,);
+		assert_eq!(error(not_a_directory), Some(VfsError::NotADirectory));
+	}
+
+	#[test]
+	fn test_find_ignores_ascii_case() {
+		let fat = mount(sample(),);
+		let mut node = fat.root();
+		for name in ["efi", "Boot", "bootaa64.EFI",] {
+			node = fat.find(&node, name,).unwrap();
+		}
+		let mut buf = [0; 8];
+		assert_eq!(fat.read(&node, 0, &mut buf,).unwrap(), 3);
+		assert_eq!(&buf[..3], b"efi");
+
+		let missing = fat.find(&fat.root(), "OSO_KE~1.ELF",);
+		assert_eq!(error(missing), Some(VfsError::NotFound));
+	}
+
+	#[test]
+	fn test_read() {
+		let fat = mount(sample(),);
+		let kernel = fat.find(&fat.root(), "OSO_KERNEL.ELF",).unwrap();
+		let data = kernel_data();
+		let mut buf = vec![0; 4096];
+		assert_eq!(fat.read(&kernel, 0, &mut buf,).unwrap(), 3000);
+		assert_eq!(buf[..3000], data);
+		// unaligned starts and ends across fragmented clusters
+		for (offset, len,) in [(1, 1), (511, 2), (1000, 1100), (2999, 10),] {
+			let read = fat.read(&kernel, offset as u64, &mut buf[..len],);
+			let read = read.unwrap();
+			let end = (offset + len).min(3000,);
+			assert_eq!(read, end - offset);
+			assert_eq!(buf[..read], data[offset..end], "{offset}");
+		}
+		assert_eq!(fat.read(&kernel, 3000, &mut buf,).unwrap(), 0);
+
+		let efi = fat.find(&fat.root(), "EFI",).unwrap();
+		let directory = fat.read(&efi, 0, &mut buf,);
+		assert_eq!(error(directory), Some(VfsError::IsADirectory));
+	}
+
+	#[test]
+	fn test_corrupt_volumes() {
+		let corrupt = Some(VfsError::Corrupt,);
+		let image = Volume::new(1,).image(2000,);
+		assert_eq!(error(Fat32::new(device(image, SECTOR_SIZE,),)), corrupt);
+
+		// the chain of the file leaves the volume
+		let mut volume = Volume::new(1,);
+		let file = volume.chain(&[1; 1024], 1,);
+		volume.fat[file as usize] = 5000;
+		let entry = short(b"FILE       ", 0x20, file, 1024,);
+		let root = volume.dir(&[entry,],);
+		let fat = mount(volume.image(root,),);
+		let node = fat.find(&fat.root(), "file",).unwrap();
+		let mut buf = [0; 1024];
+		assert_eq!(error(fat.read(&node, 0, &mut buf,)), corrupt);
+	}
+}
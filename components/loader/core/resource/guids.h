@@ -0,0 +1,250 @@
+// GUIDs of the UEFI specification, as printed by the specification.
+// Read by `oso_proc_macro::guids!`. Paste new `#define` blocks as they are.
+
+// Boot Services
+#define EFI_LOADED_IMAGE_PROTOCOL_GUID\
+ {0x5B1B31A1,0x9562,0x11d2,\
+ {0x8E,0x3F,0x00,0xA0,0xC9,0x69,0x72,0x3B}}
+
+#define EFI_LOADED_IMAGE_DEVICE_PATH_PROTOCOL_GUID\
+ {0xbc62157e,0x3e33,0x4fec,\
+ {0x99,0x20,0x2d,0x3b,0x36,0xd7,0x50,0xdf}}
+
+#define EFI_DRIVER_BINDING_PROTOCOL_GUID\
+ {0x18A031AB,0xB443,0x4D1A,\
+ {0xA5,0xC0,0x0C,0x09,0x26,0x1E,0x9F,0x71}}
+
+#define EFI_COMPONENT_NAME2_PROTOCOL_GUID\
+ {0x6A7A5CFF,0xE8D9,0x4F70,\
+ {0xBA,0xDA,0x75,0xAB,0x30,0x25,0xCE,0x14}}
+
+#define EFI_DECOMPRESS_PROTOCOL_GUID\
+ {0xd8117cfe,0x94a6,0x11d4,\
+ {0x9a,0x3a,0x00,0x90,0x27,0x3f,0xc1,0x4d}}
+
+#define EFI_TIMESTAMP_PROTOCOL_GUID\
+ {0xafbfde41,0x2e6e,0x4262,\
+ {0xba,0x65,0x62,0xb9,0x23,0x6e,0x54,0x95}}
+
+#define EFI_RNG_PROTOCOL_GUID\
+ {0x3152bca5,0xeade,0x433d,\
+ {0x86,0x2e,0xc0,0x1c,0xdc,0x29,0x1f,0x44}}
+
+#define EFI_MEMORY_ATTRIBUTE_PROTOCOL_GUID\
+ {0xf4560cf6,0x40ec,0x4b4a,\
+ {0xa1,0x92,0xbf,0x1d,0x57,0xd0,0xb1,0x89}}
+
+// Device Path
+#define EFI_DEVICE_PATH_PROTOCOL_GUID\
+ {0x09576e91,0x6d3f,0x11d2,\
+ {0x8e,0x39,0x00,0xa0,0xc9,0x69,0x72,0x3b}}
+
+#define EFI_DEVICE_PATH_UTILITIES_PROTOCOL_GUID\
+ {0x0379be4e,0xd706,0x437d,\
+ {0xb0,0x37,0xed,0xb8,0x2f,0xb7,0x72,0xa4}}
+
+#define EFI_DEVICE_PATH_TO_TEXT_PROTOCOL_GUID\
+ {0x8b843e20,0x8132,0x4852,\
+ {0x90,0xcc,0x55,0x1a,0x4e,0x4a,0x7f,0x1c}}
+
+#define EFI_DEVICE_PATH_FROM_TEXT_PROTOCOL_GUID\
+ {0x05c99a21,0xc70f,0x4ad2,\
+ {0x8a,0x5f,0x35,0xdf,0x33,0x43,0xf5,0x1e}}
+
+// Console
+#define EFI_SIMPLE_TEXT_INPUT_PROTOCOL_GUID\
+ {0x387477c1,0x69c7,0x11d2,\
+ {0x8e,0x39,0x00,0xa0,0xc9,0x69,0x72,0x3b}}
+
+#define EFI_SIMPLE_TEXT_INPUT_EX_PROTOCOL_GUID\
+ {0xdd9e7534,0x7762,0x4698,\
+ {0x8c,0x14,0xf5,0x85,0x17,0xa6,0x25,0xaa}}
+
+#define EFI_SIMPLE_TEXT_OUTPUT_PROTOCOL_GUID\
+ {0x387477c2,0x69c7,0x11d2,\
+ {0x8e,0x39,0x00,0xa0,0xc9,0x69,0x72,0x3b}}
+
+#define EFI_SIMPLE_POINTER_PROTOCOL_GUID\
+ {0x31878c87,0x0b75,0x11d5,\
+ {0x9a,0x4f,0x00,0x90,0x27,0x3f,0xc1,0x4d}}
+
+#define EFI_ABSOLUTE_POINTER_PROTOCOL_GUID\
+ {0x8D59D32B,0xC655,0x4AE9,\
+ {0x9B,0x15,0xF2,0x59,0x04,0x99,0x2A,0x43}}
+
+#define EFI_SERIAL_IO_PROTOCOL_GUID\
+ {0xBB25CF6F,0xF1D4,0x11D2,\
+ {0x9A,0x0C,0x00,0x90,0x27,0x3F,0xC1,0xFD}}
+
+#define EFI_GRAPHICS_OUTPUT_PROTOCOL_GUID\
+ {0x9042a9de,0x23dc,0x4a38,\
+ {0x96,0xfb,0x7a,0xde,0xd0,0x80,0x51,0x6a}}
+
+#define EFI_EDID_DISCOVERED_PROTOCOL_GUID\
+ {0x1C0C34F6,0xD380,0x41FA,\
+ {0xA0,0x49,0x8A,0xD0,0x6C,0x1A,0x66,0xAA}}
+
+#define EFI_EDID_ACTIVE_PROTOCOL_GUID\
+ {0xBD8C1056,0x9F36,0x44EC,\
+ {0x92,0xA8,0xA6,0x33,0x7F,0x81,0x79,0x86}}
+
+// Media Access
+#define EFI_LOAD_FILE_PROTOCOL_GUID\
+ {0x56EC3091,0x954C,0x11d2,\
+ {0x8E,0x3F,0x00,0xA0,0xC9,0x69,0x72,0x3B}}
+
+#define EFI_LOAD_FILE2_PROTOCOL_GUID\
+ {0x4006c0c1,0xfcb3,0x403e,\
+ {0x99,0x6d,0x4a,0x6c,0x87,0x24,0xe0,0x6d}}
+
+#define EFI_SIMPLE_FILE_SYSTEM_PROTOCOL_GUID\
+ {0x964e5b22,0x6459,0x11d2,\
+ {0x8e,0x39,0x00,0xa0,0xc9,0x69,0x72,0x3b}}
+
+#define EFI_FILE_INFO_GUID\
+ {0x09576e92,0x6d3f,0x11d2,\
+ {0x8e,0x39,0x00,0xa0,0xc9,0x69,0x72,0x3b}}
+
+#define EFI_FILE_SYSTEM_INFO_GUID\
+ {0x09576e93,0x6d3f,0x11d2,\
+ {0x8e,0x39,0x00,0xa0,0xc9,0x69,0x72,0x3b}}
+
+#define EFI_FILE_SYSTEM_VOLUME_LABEL_GUID\
+ {0xdb47d7d3,0xfe81,0x11d3,\
+ {0x9a,0x35,0x00,0x90,0x27,0x3f,0xc1,0x4d}}
+
+#define EFI_DISK_IO_PROTOCOL_GUID\
+ {0xCE345171,0xBA0B,0x11d2,\
+ {0x8e,0x4F,0x00,0xa0,0xc9,0x69,0x72,0x3b}}
+
+#define EFI_DISK_IO2_PROTOCOL_GUID\
+ {0x151c8eae,0x7f2c,0x472c,\
+ {0x9e,0x54,0x98,0x28,0x19,0x4f,0x6a,0x88}}
+
+#define EFI_BLOCK_IO_PROTOCOL_GUID\
+ {0x964e5b21,0x6459,0x11d2,\
+ {0x8e,0x39,0x00,0xa0,0xc9,0x69,0x72,0x3b}}
+
+#define EFI_BLOCK_IO2_PROTOCOL_GUID\
+ {0xa77b2472,0xe282,0x4e9f,\
+ {0xa2,0x45,0xc2,0xc0,0xe2,0x7b,0xbc,0xc1}}
+
+#define EFI_PARTITION_INFO_PROTOCOL_GUID\
+ {0x8cf2f62c,0xbc9b,0x4821,\
+ {0x80,0x8d,0xec,0x9e,0xc4,0x21,0xa1,0xa0}}
+
+#define EFI_UNICODE_COLLATION2_PROTOCOL_GUID\
+ {0xa4c751fc,0x23ae,0x4c3e,\
+ {0x92,0xe9,0x49,0x64,0xcf,0x63,0xf3,0x49}}
+
+// Buses
+#define EFI_PCI_ROOT_BRIDGE_IO_PROTOCOL_GUID\
+ {0x2F707EBB,0x4A1A,0x11d4,\
+ {0x9A,0x38,0x00,0x90,0x27,0x3F,0xC1,0x4D}}
+
+#define EFI_PCI_IO_PROTOCOL_GUID\
+ {0x4cf5b200,0x68b8,0x4ca5,\
+ {0x9e,0xec,0xb2,0x3e,0x3f,0x50,0x02,0x9a}}
+
+#define EFI_SCSI_IO_PROTOCOL_GUID\
+ {0x932f47e6,0x2362,0x4002,\
+ {0x80,0x3e,0x3c,0xd5,0x4b,0x13,0x8f,0x85}}
+
+#define EFI_EXT_SCSI_PASS_THRU_PROTOCOL_GUID\
+ {0x143b7632,0xb81b,0x4cb7,\
+ {0xab,0xd3,0xb6,0x25,0xa5,0xb9,0xbf,0xfe}}
+
+#define EFI_ATA_PASS_THRU_PROTOCOL_GUID\
+ {0x1d3de7f0,0x0807,0x424f,\
+ {0xaa,0x69,0x11,0xa5,0x4e,0x19,0xa4,0x6f}}
+
+#define EFI_NVM_EXPRESS_PASS_THRU_PROTOCOL_GUID\
+ {0x52c78312,0x8edc,0x4233,\
+ {0x98,0xf2,0x1a,0x1a,0xa5,0xe3,0x88,0xa5}}
+
+#define EFI_SD_MMC_PASS_THRU_PROTOCOL_GUID\
+ {0x716ef0d9,0xff83,0x4f69,\
+ {0x81,0xe9,0x51,0x8b,0xd3,0x9a,0x8e,0x70}}
+
+#define EFI_USB2_HC_PROTOCOL_GUID\
+ {0x3e745226,0x9818,0x45b6,\
+ {0xa2,0xac,0xd7,0xcd,0x0e,0x8b,0xa2,0xbc}}
+
+#define EFI_USB_IO_PROTOCOL_GUID\
+ {0x2B2F68D6,0x0CD2,0x44cf,\
+ {0x8E,0x8B,0xBB,0xA2,0x0B,0x1B,0x5B,0x75}}
+
+// Network
+#define EFI_SIMPLE_NETWORK_PROTOCOL_GUID\
+ {0xA19832B9,0xAC25,0x11D3,\
+ {0x9A,0x2D,0x00,0x90,0x27,0x3F,0xC1,0x4D}}
+
+#define EFI_PXE_BASE_CODE_PROTOCOL_GUID\
+ {0x03C4E603,0xAC28,0x11d3,\
+ {0x9A,0x2D,0x00,0x90,0x27,0x3F,0xC1,0x4D}}
+
+// HII
+#define EFI_HII_DATABASE_PROTOCOL_GUID\
+ {0xef9fc172,0xa1b2,0x4693,\
+ {0xb3,0x27,0x6d,0x32,0xfc,0x41,0x60,0x42}}
+
+#define EFI_HII_STRING_PROTOCOL_GUID\
+ {0x0fd96974,0x23aa,0x4cdc,\
+ {0xb9,0xcb,0x98,0xd1,0x77,0x50,0x32,0x2a}}
+
+#define EFI_HII_FONT_PROTOCOL_GUID\
+ {0xe9ca4775,0x8657,0x47fc,\
+ {0x97,0xe7,0x7e,0xd6,0x5a,0x08,0x43,0x24}}
+
+#define EFI_HII_CONFIG_ROUTING_PROTOCOL_GUID\
+ {0x587e72d7,0xcc50,0x4f79,\
+ {0x82,0x09,0xca,0x29,0x1f,0xc1,0xa1,0x0f}}
+
+#define EFI_HII_PACKAGE_LIST_PROTOCOL_GUID\
+ {0x6a1ee763,0xd47a,0x43b4,\
+ {0xaa,0xbe,0xef,0x1d,0xe2,0xab,0x56,0xfc}}
+
+// Configuration Tables and Variables
+#define EFI_ACPI_20_TABLE_GUID\
+ {0x8868e871,0xe4f1,0x11d3,\
+ {0xbc,0x22,0x00,0x80,0xc7,0x3c,0x88,0x81}}
+
+#define ACPI_TABLE_GUID\
+ {0xeb9d2d30,0x2d88,0x11d3,\
+ {0x9a,0x16,0x00,0x90,0x27,0x3f,0xc1,0x4d}}
+
+#define SMBIOS_TABLE_GUID\
+ {0xeb9d2d31,0x2d88,0x11d3,\
+ {0x9a,0x16,0x00,0x90,0x27,0x3f,0xc1,0x4d}}
+
+#define SMBIOS3_TABLE_GUID\
+ {0xf2fd1544,0x9794,0x4a2c,\
+ {0x99,0x2e,0xe5,0xbb,0xcf,0x20,0xe3,0x94}}
+
+#define EFI_DTB_TABLE_GUID\
+ {0xb1b621d5,0xf19c,0x41a5,\
+ {0x83,0x0b,0xd9,0x15,0x2c,0x69,0xaa,0xe0}}
+
+#define EFI_MEMORY_ATTRIBUTES_TABLE_GUID\
+ {0xdcfa911d,0x26eb,0x469f,\
+ {0xa2,0x20,0x38,0xb7,0xdc,0x46,0x12,0x20}}
+
+#define EFI_RT_PROPERTIES_TABLE_GUID\
+ {0xeb66918a,0x7eef,0x402a,\
+ {0x84,0x2e,0x93,0x1d,0x21,0xc3,0x8a,0xe9}}
+
+#define EFI_SYSTEM_RESOURCE_TABLE_GUID\
+ {0xb122a263,0x3661,0x4f68,\
+ {0x99,0x29,0x78,0xf8,0xb0,0xd6,0x21,0x80}}
+
+#define EFI_DEBUG_IMAGE_INFO_TABLE_GUID\
+ {0x49152e77,0x1ada,0x4764,\
+ {0xb7,0xa2,0x7a,0xfe,0xfe,0xd9,0x5e,0x8b}}
+
+#define EFI_GLOBAL_VARIABLE_GUID\
+ {0x8BE4DF61,0x93CA,0x11d2,\
+ {0xAA,0x0D,0x00,0xE0,0x98,0x03,0x2B,0x8C}}
+
+#define EFI_IMAGE_SECURITY_DATABASE_GUID\
+ {0xd719b2cb,0x3d3a,0x4596,\
+ {0xa3,0xbc,0xda,0xd0,0x0e,0x67,0x65,0x6f}}
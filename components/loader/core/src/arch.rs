@@ -0,0 +1,60 @@
+//! # Architecture Counters
+//!
+//! Reads the free running counter of the CPU directly, so the loader keeps
+//! a time source after `ExitBootServices`, when the time services of
+//! firmware are gone. The counter is the one the kernel times its boot
+//! stages with, so the [`Milestones`] the loader stamps with it line up with
+//! the boot profile of the kernel.
+//!
+//! | arch    | counter                          | frequency        |
+//! |---------|----------------------------------|------------------|
+//! | aarch64 | `CNTPCT_EL0`, physical count     | `CNTFRQ_EL0`     |
+//! | riscv64 | `time` CSR read by `rdtime`      | unknown          |
+//! | x86_64  | time stamp counter               | unknown          |
+//!
+//! The frequency of `time` is `timebase-frequency` of `/cpus` in the device
+//! tree, which the kernel reads itself.
+//!
+//! ```rust,ignore
+//! let start = arch::counter();
+//! let memory_map = exit_boot_services();
+//! let ticks = arch::counter().wrapping_sub(start,);
+//! ```
+//!
+//! [`Milestones`]: oso_no_std_shared::bridge::boot_info::Milestones
+
+/// Physical count of the generic timer
+#[cfg(target_arch = "aarch64")]
+pub fn counter() -> u64 {
+	let count: u64;
+	unsafe { core::arch::asm!("isb", "mrs {}, cntpct_el0", out(reg) count) };
+	count
+}
+
+/// Real time counter
+#[cfg(target_arch = "riscv64")]
+pub fn counter() -> u64 {
+	let count: u64;
+	unsafe { core::arch::asm!("rdtime {}", out(reg) count) };
+	count
+}
+
+/// Time stamp counter
+#[cfg(target_arch = "x86_64")]
+pub fn counter() -> u64 {
+	unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+/// Frequency of [`counter`] in Hz
+#[cfg(target_arch = "aarch64")]
+pub fn counter_frequency() -> u64 {
+	let frequency: u64;
+	unsafe { core::arch::asm!("mrs {}, cntfrq_el0", out(reg) frequency) };
+	frequency
+}
+
+/// Frequency of [`counter`] in Hz, `0` as it is unknown
+#[cfg(not(target_arch = "aarch64"))]
+pub fn counter_frequency() -> u64 {
+	0
+}
@@ -16,13 +16,21 @@
 //!
 //! ## Modules
 //!
+//! - `allocator`: Heap allocators backing `alloc`
 //! - `console`: Text input/output operations
 //! - `controller`: Device controller management
+//! - `flight`: Flight recorder of boot services calls
 //! - `fs`: File system operations
 //! - `guid`: UEFI GUID definitions and utilities
+//! - `image`: Loaded image information
+//! - `known_guids`: Names of the GUIDs defined by the specification
 //! - `memory`: Memory allocation and management
+//! - `mock`: Mock boot services for unit tests on the host
 //! - `protocol`: Protocol interface definitions
+//! - `runtime`: Virtual address layout for runtime services
+//! - `serial`: Buffered log output to the serial port
 //! - `service`: Boot and runtime service wrappers
+//! - `string`: UCS-2 strings passed to and from firmware
 //! - `table`: System table access and management
 //!
 //! ## Design Philosophy
@@ -34,30 +42,61 @@
 use super::raw::types::Status;
 use crate::raw::service::BootServices;
 use crate::raw::service::RuntimeServices;
+use crate::chibi_uefi::string::Ucs2Str;
+use crate::raw::types::Event;
+use crate::raw::types::Guid;
+use crate::raw::types::Tpl;
 use crate::raw::types::UnsafeHandle;
+use crate::raw::types::event::EventType;
 use crate::raw::types::memory::MemoryMapBackingMemory;
+use crate::raw::types::memory::MemoryMapInfo;
+use crate::raw::types::memory::MemoryMapOwned;
 use crate::raw::types::memory::MemoryType;
 use crate::raw::types::memory::PAGE_SIZE;
 use crate::raw::types::misc::ResetType;
+use crate::raw::types::time::Time;
+use crate::raw::types::time::TimerDelay;
+use crate::raw::types::variable::VariableAttributes;
 use core::ffi::c_void;
 use core::ptr::NonNull;
 use core::sync::atomic::AtomicPtr;
 use core::sync::atomic::Ordering;
+use oso_error::Rslt;
+use oso_error::loader::UefiError;
+use oso_no_std_shared::bridge::boot_info::FirmwareService;
+use oso_no_std_shared::units::ByteSize;
 
+/// Heap allocators and their statistics
+pub mod allocator;
 /// Console input/output operations
 pub mod console;
 /// Device controller management and connection
 pub mod controller;
+/// Recording of boot services calls handed to the kernel
+pub mod flight;
 /// File system access and operations
 pub mod fs;
 /// UEFI GUID definitions and utilities
 pub mod guid;
+/// Information about the loaded loader image
+pub mod image;
+/// Names of GUIDs for diagnostics
+pub mod known_guids;
 /// Memory allocation and management utilities
 pub mod memory;
+/// Mock boot services for unit tests on the host
+#[cfg(test)]
+pub mod mock;
 /// UEFI protocol interface definitions
 pub mod protocol;
+/// Runtime services virtual mapping and memory attributes table
+pub mod runtime;
+/// Buffered log output to the serial port
+pub mod serial;
 /// Boot and runtime service wrappers
 pub mod service;
+/// UCS-2 strings passed to and from firmware
+pub mod string;
 /// System table access and management
 pub mod table;
 
@@ -163,55 +202,271 @@ impl BootServices {
 	/// for kernel execution. After calling this function, only runtime services
 	/// remain available.
 	///
+	/// # Returns
+	///
+	/// The memory map which was passed to `ExitBootServices`. It is the final
+	/// memory map of the boot process and is required by
+	/// `SetVirtualAddressMap`. The backing buffer is allocated as loader data
+	/// so that it stays valid after boot services are gone.
+	///
 	/// # Important
 	///
 	/// This is a one-way transition - once boot services are exited, they
 	/// cannot be re-entered. This should only be called when ready to
-	/// transfer control to the kernel.
-	pub fn exit_boot_services(&self,) {
-		let mem_ty = MemoryType::BOOT_SERVICES_DATA;
+	/// transfer control to the kernel. Later calls of
+	/// [`boot_services`](table::boot_services) panic, and the console is
+	/// uninstalled, so [`println!`](crate::println) prints nothing.
+	pub fn exit_boot_services(&self,) -> MemoryMapOwned {
+		let mem_ty = MemoryType::LOADER_DATA;
 
 		let mut buf = MemoryMapBackingMemory::new(mem_ty,)
 			.expect("failed to allocate memory",);
-		let status =
+		let (status, info,) =
 			unsafe { self.try_exit_boot_services(buf.as_mut_slice(),) };
 
 		if !status.is_success() {
 			todo!("failed to exit boot service. reset the machine");
 		}
+		table::set_boot_phase(table::BootPhase::Runtime,);
+		// the console output protocol is gone with boot services
+		oso_no_std_shared::text::console::uninstall();
+
+		MemoryMapOwned::from_initialized_memory(buf, info,)
+	}
+
+	/// Blocks until one of `events` is signaled
+	///
+	/// # Returns
+	///
+	/// Index of the signaled event in `events`
+	pub fn wait_for_event(
+		&self,
+		events: &mut [Event],
+	) -> Rslt<usize, UefiError,> {
+		let mut index = 0;
+		let len = events.len();
+		unsafe { (self.wait_for_event)(len, events.as_mut_ptr(), &mut index,) }
+			.record(FirmwareService::WaitForEvent, [len as u64, index as u64,],)
+			.ok_or_with(|_| index,)
 	}
 
-	unsafe fn try_exit_boot_services(&self, buf: &mut [u8],) -> Status {
+	/// Creates an event calling `notify` at `tpl` when signaled
+	///
+	/// # Safety
+	///
+	/// `context` must stay valid as long as the event can be signaled
+	pub unsafe fn create_event(
+		&self,
+		ty: EventType,
+		tpl: Tpl,
+		notify: Option<unsafe extern "efiapi" fn(Event, *mut c_void,),>,
+		context: *mut c_void,
+	) -> Rslt<Event, UefiError,> {
+		let mut event = core::ptr::null_mut();
+		let args = [ty.0 as u64, tpl.0 as u64,];
+		unsafe { (self.create_event)(ty, tpl, notify, context, &mut event,) }
+			.record(FirmwareService::CreateEvent, args,)
+			.ok_or_with(|_| event,)
+	}
+
+	/// Arms the timer of `event`
+	///
+	/// # Params
+	///
+	/// `trigger_time` is in units of 100 nanoseconds
+	///
+	/// # Safety
+	///
+	/// `event` must be an open timer event
+	pub unsafe fn set_timer(
+		&self,
+		event: Event,
+		delay: TimerDelay,
+		trigger_time: u64,
+	) -> Rslt<Status, UefiError,> {
+		let args = [delay.0 as u64, trigger_time,];
+		unsafe { (self.set_timer)(event, delay, trigger_time,) }
+			.record(FirmwareService::SetTimer, args,)
+			.ok_or()
+	}
+
+	/// # Safety
+	///
+	/// `event` must be open. it is invalid afterwards
+	pub unsafe fn close_event(
+		&self,
+		event: Event,
+	) -> Rslt<Status, UefiError,> {
+		unsafe { (self.close_event)(event,) }
+			.record(FirmwareService::CloseEvent, [event.addr() as u64, 0,],)
+			.ok_or()
+	}
+
+	/// Runs `f` at `tpl`, so that notify functions of lower levels can not
+	/// interrupt it
+	///
+	/// `tpl` must not be lower than the current level
+	pub fn with_tpl<T,>(&self, tpl: Tpl, f: impl FnOnce() -> T,) -> T {
+		let old = unsafe { (self.raise_tpl)(tpl,) };
+		let t = f();
+		unsafe { (self.restore_tpl)(old,) };
+		t
+	}
+
+	/// Busy waits at least `micro_seconds`
+	pub fn stall(&self, micro_seconds: usize,) -> Rslt<Status, UefiError,> {
+		unsafe { (self.stall)(micro_seconds,) }
+			.record(FirmwareService::Stall, [micro_seconds as u64, 0,],)
+			.ok_or()
+	}
+
+	unsafe fn try_exit_boot_services(
+		&self,
+		buf: &mut [u8],
+	) -> (Status, MemoryMapInfo,) {
 		let mem_map = self.get_memory_map(buf,).expect("failed to get memmap",);
 		// core::mem::forget(mem_map,);
-		unsafe {
-			(self.exit_boot_services)(image_handle().as_ptr(), mem_map.map_key,)
-		}
+		let image = image_handle().as_ptr();
+		let status =
+			unsafe { (self.exit_boot_services)(image, mem_map.map_key,) }
+				.record(FirmwareService::ExitBootServices, [
+					image.addr() as u64,
+					mem_map.map_key as u64,
+				],);
+		(status, mem_map,)
 	}
 }
 
 impl RuntimeServices {
+	/// Current time of the real time clock
+	pub fn time(&self,) -> Rslt<Time, UefiError,> {
+		let mut time = Time::default();
+		unsafe { (self.get_time)(&mut time, core::ptr::null_mut(),) }
+			.ok_or()?;
+		Ok(time,)
+	}
+
+	/// Sets the real time clock to `time`
+	pub fn set_time(&self, time: &Time,) -> Rslt<(), UefiError,> {
+		unsafe { (self.set_time)(time,) }.ok_or()?;
+		Ok((),)
+	}
+
+	/// Reads the variable `name` of `vendor` into `buf`
+	///
+	/// # Returns
+	///
+	/// The size of the data and the attributes of the variable
+	///
+	/// # Errors
+	///
+	/// `EFI_BUFFER_TOO_SMALL` if `buf` can not hold the data, and
+	/// `EFI_NOT_FOUND` if there is no such variable
+	pub fn variable(
+		&self,
+		name: &Ucs2Str,
+		vendor: &Guid,
+		buf: &mut [u8],
+	) -> Rslt<(usize, VariableAttributes,), UefiError,> {
+		let mut attributes = VariableAttributes(0,);
+		let mut size = buf.len();
+		unsafe {
+			(self.get_variable)(
+				name.as_ptr(),
+				vendor,
+				&mut attributes,
+				&mut size,
+				buf.as_mut_ptr(),
+			)
+		}
+		.ok_or()?;
+		Ok((size, attributes,),)
+	}
+
+	/// Writes `data` as the variable `name` of `vendor`. Empty `data`
+	/// deletes the variable
+	///
+	/// Variables written after `ExitBootServices` need
+	/// [`VariableAttributes::RUNTIME_ACCESS`].
+	pub fn set_variable(
+		&self,
+		name: &Ucs2Str,
+		vendor: &Guid,
+		attributes: VariableAttributes,
+		data: &[u8],
+	) -> Rslt<(), UefiError,> {
+		unsafe {
+			(self.set_variable)(
+				name.as_ptr(),
+				vendor,
+				attributes,
+				data.len(),
+				data.as_ptr(),
+			)
+		}
+		.ok_or()?;
+		Ok((),)
+	}
+
 	/// Resets the system
 	///
-	/// This method would reset the entire system with the specified reset type.
-	/// Currently not implemented (marked as todo!()).
+	/// This method resets the entire system with the specified reset type.
 	///
 	/// # Arguments
 	///
-	/// * `_reset_type` - Type of reset to perform
-	/// * `_status` - Status code to report
-	/// * `_data` - Optional data to pass with the reset
+	/// * `reset_type` - Type of reset to perform
+	/// * `status` - Status code to report
+	/// * `data` - Optional data to pass with the reset. For non success
+	///   status, firmware expects a null terminated UTF-16 string describing
+	///   the reason, optionally followed by binary data
 	///
 	/// # Note
 	///
 	/// This function never returns as it resets the system.
 	pub fn reset(
 		&self,
-		_reset_type: ResetType,
-		_status: Status,
-		_data: Option<&[u8],>,
+		reset_type: ResetType,
+		status: Status,
+		data: Option<&[u8],>,
 	) -> ! {
-		todo!()
+		let (size, data,) = match data {
+			Some(data,) => (data.len(), data.as_ptr(),),
+			None => (0, core::ptr::null(),),
+		};
+		unsafe { (self.reset_system)(reset_type, status, size, data,) }
+	}
+
+	/// Switches runtime services into virtual addressing mode
+	///
+	/// Every runtime region of `memory_map` must already have its
+	/// `virtual_start` assigned (see [`runtime::VirtualLayout`]). Firmware
+	/// converts its internal pointers, including the runtime services table
+	/// itself, according to the map.
+	///
+	/// # Arguments
+	///
+	/// * `memory_map` - The memory map returned by
+	///   [`BootServices::exit_boot_services`]
+	///
+	/// # Note
+	///
+	/// Must be called after exiting boot services, and at most once.
+	pub fn set_virtual_address_map(
+		&self,
+		memory_map: &mut MemoryMapOwned,
+	) -> Rslt<(), UefiError,> {
+		let map_size = memory_map.len * memory_map.info.desc_size;
+		let virtual_map = memory_map.buf.as_mut_slice().as_mut_ptr().cast();
+		unsafe {
+			(self.set_virtual_address_map)(
+				map_size,
+				memory_map.info.desc_size,
+				memory_map.info.desc_ver,
+				virtual_map,
+			)
+		}
+		.ok_or()?;
+		Ok((),)
 	}
 }
 
@@ -284,11 +539,79 @@ pub(crate) fn set_image_handle_panicking(image_handle: UnsafeHandle,) {
 /// # Returns
 ///
 /// Number of pages required (rounded up)
-///
-/// # Note
-///
-/// This function adds 1 to ensure sufficient space, which may result in
-/// slight over-allocation but guarantees adequate memory.
 pub fn required_pages(size: usize,) -> usize {
-	size / PAGE_SIZE + 1
+	ByteSize::from(size,).pages(PAGE_SIZE as u64,) as usize
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::chibi_uefi::mock::Call;
+	use crate::chibi_uefi::mock::Firmware;
+	use crate::chibi_uefi::mock::IMAGE;
+	use crate::chibi_uefi::mock::Service;
+	use crate::raw::types::memory::MemoryAttribute;
+	use crate::raw::types::memory::MemoryDescriptor;
+	use table::BootPhase;
+
+	const DESCRIPTOR: MemoryDescriptor = MemoryDescriptor {
+		memory_type:    MemoryType::CONVENTIONAL,
+		physical_start: 0x10_0000,
+		virtual_start:  0,
+		page_count:     0x100,
+		attribute:      MemoryAttribute(0,),
+	};
+
+	#[test]
+	fn test_exit_boot_services() {
+		let fw = Firmware::install();
+		let descriptor = DESCRIPTOR;
+		fw.set_memory_map(&[descriptor; 2],);
+		assert_eq!(table::boot_phase(), BootPhase::Boot);
+
+		let map = table::boot_services().exit_boot_services();
+		assert_eq!(map.len, 2);
+		assert_eq!(map.entry(1,), Some(&descriptor));
+		let services: Vec<_,> = fw.calls().iter().map(|c| c.service,).collect();
+		assert_eq!(services, [
+			Service::GetMemoryMap,
+			Service::AllocatePool,
+			Service::GetMemoryMap,
+			Service::ExitBootServices,
+		]);
+		assert_eq!(fw.calls().last(), Some(&Call {
+			service: Service::ExitBootServices,
+			handle:  IMAGE,
+			guid:    None,
+		}));
+		assert_eq!(table::boot_phase(), BootPhase::Runtime);
+		assert!(table::try_boot_services().is_none());
+	}
+
+	#[test]
+	#[should_panic = "boot services are used after exit_boot_services"]
+	fn test_boot_services_after_exit() {
+		let fw = Firmware::install();
+		fw.set_memory_map(&[DESCRIPTOR],);
+		table::boot_services().exit_boot_services();
+
+		let _ = table::boot_services().stall(10,);
+	}
+
+	#[test]
+	fn test_failed_exit_keeps_boot_services() {
+		let fw = Firmware::install();
+		fw.set_memory_map(&[DESCRIPTOR],);
+		fw.fail(Service::ExitBootServices, Status::EFI_INVALID_PARAMETER,);
+
+		let bs = table::boot_services();
+		let mut buf = [0u64; 16];
+		let bytes = unsafe {
+			core::slice::from_raw_parts_mut(buf.as_mut_ptr().cast::<u8>(), 128,)
+		};
+		let (status, _,) = unsafe { bs.try_exit_boot_services(bytes,) };
+		assert!(!status.is_success());
+		assert_eq!(table::boot_phase(), BootPhase::Boot);
+		assert!(table::try_boot_services().is_some());
+	}
 }
@@ -1,27 +1,90 @@
+use super::serial;
+use super::table::boot_services;
 use super::table::system_table;
+use crate::print;
+use crate::println;
 use crate::raw::protocol::text::TextOutputProtocol;
+use crate::raw::types::text::InputKey;
+use crate::raw::types::text::TextAttribute;
+use core::sync::atomic::AtomicU8;
+use core::sync::atomic::Ordering;
+use oso_no_std_shared::text::console;
+use oso_no_std_shared::text::console::Console;
+use oso_no_std_shared::text::console::ConsoleColor;
+use oso_no_std_shared::units::ByteSize;
 
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Info as u8,);
+
+/// logs a line if verbosity of the console or the serial port is
+/// [`Verbosity::Info`] or higher
 #[macro_export]
-macro_rules! print {
+macro_rules! info {
 	($($args:tt)*) => {
-		$crate::chibi_uefi::console::print(core::format_args!($($args)*),);
+		$crate::chibi_uefi::console::log(
+			$crate::chibi_uefi::console::Verbosity::Info,
+			core::format_args!($($args)*),
+		);
 	};
 }
 
+/// logs a line if verbosity of the console or the serial port is
+/// [`Verbosity::Debug`]
 #[macro_export]
-macro_rules! println {
-	() => {
-		$crate::print!("\n");
+macro_rules! debug {
+	($($args:tt)*) => {
+		$crate::chibi_uefi::console::log(
+			$crate::chibi_uefi::console::Verbosity::Debug,
+			core::format_args!($($args)*),
+		);
 	};
-	($($args:tt)*)=>{
-		print!("{}{}", core::format_args!($($args)*), "\n");
+}
+
+/// amount of diagnostic output printed by [`info!`] and [`debug!`]
+///
+/// errors are always shown regardless of verbosity
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord,)]
+pub enum Verbosity {
+	Quiet,
+	#[default]
+	Info,
+	Debug,
+}
+
+impl Verbosity {
+	pub(crate) fn from_raw(raw: u8,) -> Self {
+		match raw {
+			0 => Self::Quiet,
+			1 => Self::Info,
+			_ => Self::Debug,
+		}
 	}
 }
 
-pub fn print(args: core::fmt::Arguments,) {
-	use core::fmt::Write;
+pub fn set_verbosity(verbosity: Verbosity,) {
+	VERBOSITY.store(verbosity as u8, Ordering::Relaxed,);
+}
+
+pub fn verbosity() -> Verbosity {
+	Verbosity::from_raw(VERBOSITY.load(Ordering::Relaxed,),)
+}
+
+/// prints `args` and a newline to the console and queues them for the serial
+/// port, where `level` is enabled
+///
+/// the console is written synchronously. see [`serial`] for the serial port
+pub fn log(level: Verbosity, args: core::fmt::Arguments,) {
+	if verbosity() >= level {
+		println!("{args}");
+	}
+	serial::log(level, args,);
+}
+
+/// Makes the console output of the system table the global console of
+/// [`print!`] and [`println!`]
+pub fn install() {
 	let st = unsafe { system_table().as_ref() };
-	unsafe { st.stdout.as_mut() }.unwrap().write_fmt(args,).unwrap();
+	console::install(unsafe { st.stdout.as_mut() }.unwrap(),);
 }
 
 impl core::fmt::Write for TextOutputProtocol {
@@ -30,3 +93,111 @@ impl core::fmt::Write for TextOutputProtocol {
 		Ok((),)
 	}
 }
+
+impl Console for TextOutputProtocol {
+	/// `background` is shown as its dark variant, as UEFI only has 8
+	/// background colors
+	fn set_color(
+		&mut self,
+		foreground: ConsoleColor,
+		background: ConsoleColor,
+	) -> core::fmt::Result {
+		let attr =
+			TextAttribute::new(foreground as usize, background as usize,);
+		self.set_attribute(attr,)?;
+		Ok((),)
+	}
+
+	fn clear(&mut self,) -> core::fmt::Result {
+		TextOutputProtocol::clear(self,)?;
+		Ok((),)
+	}
+
+	fn cursor(&self,) -> (usize, usize,) {
+		self.cursor_position()
+	}
+
+	fn set_cursor(&mut self, column: usize, row: usize,) -> core::fmt::Result {
+		TextOutputProtocol::set_cursor(self, column, row,)?;
+		Ok((),)
+	}
+}
+
+/// Progress of a long operation, shown on one line of the console
+///
+/// The line is redrawn when the percentage changes, at [`Verbosity::Info`] or
+/// higher.
+pub struct Progress {
+	label:   &'static str,
+	total:   usize,
+	percent: Option<usize,>,
+}
+
+impl Progress {
+	pub fn new(label: &'static str, total: usize,) -> Self {
+		Self { label, total, percent: None, }
+	}
+
+	/// shows that `done` of `total` bytes are done
+	pub fn update(&mut self, done: usize,) {
+		let percent = (done * 100).checked_div(self.total,).unwrap_or(100,);
+		if verbosity() < Verbosity::Info || self.percent == Some(percent,) {
+			return;
+		}
+		self.percent = Some(percent,);
+		let done = ByteSize::from(done,);
+		let total = ByteSize::from(self.total,);
+		// trailing spaces clear what is left of a longer line, as sizes get
+		// shorter when they reach the next unit
+		let label = self.label;
+		print!("\r{label}: {percent:3}% ({done:.1} / {total:.1})   ");
+	}
+
+	/// ends the line of the progress
+	pub fn finish(self,) {
+		if self.percent.is_some() {
+			println!();
+		}
+	}
+}
+
+/// `true` if `Esc` has been pressed. other keys are discarded
+pub fn escape_pressed() -> bool {
+	let st = unsafe { system_table().as_ref() };
+	let stdin = unsafe { st.stdin.as_mut() }.unwrap();
+	core::iter::from_fn(|| stdin.read_key_stroke(),)
+		.any(|key| key.scan_code == InputKey::SCAN_ESC,)
+}
+
+/// waits at most `millis` milliseconds for a key press
+pub fn read_key_timeout(millis: u64,) -> Option<InputKey,> {
+	const POLL_INTERVAL: u64 = 10;
+
+	let st = unsafe { system_table().as_ref() };
+	let stdin = unsafe { st.stdin.as_mut() }.unwrap();
+	let mut waited = 0;
+	loop {
+		if let Some(key,) = stdin.read_key_stroke() {
+			return Some(key,);
+		}
+		if waited >= millis {
+			return None;
+		}
+		let _ = boot_services().stall(POLL_INTERVAL as usize * 1000,);
+		waited += POLL_INTERVAL;
+	}
+}
+
+/// blocks until a key is pressed and returns it
+pub fn read_key() -> InputKey {
+	let st = unsafe { system_table().as_ref() };
+	let stdin = unsafe { st.stdin.as_mut() }.unwrap();
+	loop {
+		if let Some(key,) = stdin.read_key_stroke() {
+			return key;
+		}
+		let mut events = [stdin.wait_for_key(),];
+		// on failure, fall back to polling
+		let _ = boot_services().wait_for_event(&mut events,);
+	}
+}
@@ -5,6 +5,7 @@ use crate::raw::service::BootServices;
 use crate::raw::types::Boolean;
 use crate::raw::types::Status;
 use crate::raw::types::UnsafeHandle;
+use oso_no_std_shared::bridge::boot_info::FirmwareService;
 
 use super::Handle;
 
@@ -35,5 +36,9 @@ impl BootServices {
 				recursive,
 			)
 		}
+		.record(FirmwareService::ConnectController, [
+			controller_handle.addr() as u64,
+			recursive.0 as u64,
+		],)
 	}
 }
@@ -0,0 +1,179 @@
+//! # Flight Recorder
+//!
+//! Records the boot services calls of the loader, so the sequence of firmware
+//! interactions which led to a kernel missing devices or finding odd memory
+//! can be reconstructed after the fact.
+//!
+//! The wrappers of [`BootServices`](crate::raw::service::BootServices) pass
+//! the status of each call through [`Status::record`], which appends a
+//! [`FirmwareCall`] with the service, two key arguments and a timestamp to a
+//! ring of [`CAPACITY`] calls. Once full, the oldest calls are overwritten.
+//! `Handoff::finish` hands the ring to the kernel in
+//! `BootInfo::firmware_calls`, where it is part of the boot information
+//! dump decoded by `cargo xtask bootinfo`.
+//!
+//! Nothing is recorded until [`start`] allocates the ring as loader data,
+//! which the kernel keeps until it has read the boot information. `init`
+//! starts it when the loader is built with the `flight_recorder` feature.
+//!
+//! ```rust,ignore
+//! flight::start();
+//! boot_services().stall(10,)?;
+//! let calls = flight::calls();
+//! assert_eq!(calls.read_count(), 1);
+//! ```
+
+use crate::arch::counter;
+use crate::raw::types::Status;
+use alloc::vec;
+use core::ptr::null_mut;
+use core::sync::atomic::AtomicPtr;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+use oso_no_std_shared::bridge::boot_info::FirmwareCall;
+use oso_no_std_shared::bridge::boot_info::FirmwareCalls;
+use oso_no_std_shared::bridge::boot_info::FirmwareService;
+
+/// Number of calls the ring holds
+pub const CAPACITY: usize = 512;
+
+/// Ring and the number of calls recorded into it
+struct Recorder {
+	ring:  AtomicPtr<FirmwareCall,>,
+	count: AtomicU64,
+}
+
+impl Recorder {
+	const fn new() -> Self {
+		Self { ring: AtomicPtr::new(null_mut(),), count: AtomicU64::new(0,), }
+	}
+}
+
+#[cfg(not(test))]
+static RECORDER: Recorder = Recorder::new();
+
+// tests of the mock firmware run in parallel, each thread with its own ring
+#[cfg(test)]
+std::thread_local! {
+	static RECORDER: Recorder = const { Recorder::new() };
+}
+
+#[cfg(not(test))]
+fn with_recorder<R,>(f: impl FnOnce(&Recorder,) -> R,) -> R {
+	f(&RECORDER,)
+}
+
+#[cfg(test)]
+fn with_recorder<R,>(f: impl FnOnce(&Recorder,) -> R,) -> R {
+	RECORDER.with(f,)
+}
+
+/// Allocates the ring and starts recording. Calls made before are not
+/// recorded, including the allocation of the ring
+pub fn start() {
+	let empty = FirmwareCall::new(FirmwareService::Stall, [0, 0,], 0, 0,);
+	let ring = vec![empty; CAPACITY].leak();
+	with_recorder(|recorder| {
+		recorder.count.store(0, Ordering::Relaxed,);
+		recorder.ring.store(ring.as_mut_ptr(), Ordering::Release,);
+	},);
+}
+
+/// Ring of the calls recorded so far, empty if recording never started
+pub fn calls() -> FirmwareCalls {
+	with_recorder(|recorder| {
+		let ptr = recorder.ring.load(Ordering::Acquire,);
+		if ptr.is_null() {
+			return FirmwareCalls::empty();
+		}
+		let mut calls = FirmwareCalls { ptr, capacity: CAPACITY, count: 0, };
+		calls.write_count(recorder.count.load(Ordering::Relaxed,),);
+		calls
+	},)
+}
+
+impl Status {
+	/// Records the call of `service` which returned `self`, and passes
+	/// `self` on
+	///
+	/// `args` are what [`FirmwareService`] describes for `service`.
+	pub(crate) fn record(
+		self,
+		service: FirmwareService,
+		args: [u64; 2],
+	) -> Self {
+		with_recorder(|recorder| {
+			let ring = recorder.ring.load(Ordering::Acquire,);
+			if ring.is_null() {
+				return;
+			}
+			let i = recorder.count.fetch_add(1, Ordering::Relaxed,);
+			let call =
+				FirmwareCall::new(service, args, self.0 as u64, counter(),);
+			// SAFETY: `start` allocated `CAPACITY` calls, never freed
+			unsafe { ring.add(i as usize % CAPACITY,).write(call,) };
+		},);
+		self
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::chibi_uefi::mock::Firmware;
+	use crate::chibi_uefi::mock::Service;
+	use crate::chibi_uefi::table::boot_services;
+	use crate::raw::types::memory::MemoryType;
+	use std::vec::Vec;
+
+	/// services and arguments of the calls kept in the ring
+	fn recorded() -> Vec<(FirmwareService, u64, [u64; 2],),> {
+		let calls = calls();
+		// SAFETY: the ring of this thread is leaked
+		let calls = unsafe { calls.iter() };
+		calls
+			.map(|c| {
+				let args = [c.read_arg0(), c.read_arg1(),];
+				(c.service, c.read_status(), args,)
+			},)
+			.collect()
+	}
+
+	#[test]
+	fn test_record_boot_services_calls() {
+		let fw = Firmware::install();
+		let bs = boot_services();
+		let _ = bs.stall(1,);
+		assert_eq!(calls().read_count(), 0);
+
+		start();
+		let _ = bs.stall(10,);
+		fw.fail(Service::AllocatePool, Status::EFI_OUT_OF_RESOURCES,);
+		assert!(bs.allocate_pool(MemoryType::LOADER_DATA, 64,).is_err());
+
+		let unsupported = Status::EFI_UNSUPPORTED.0 as u64;
+		let out_of_resources = Status::EFI_OUT_OF_RESOURCES.0 as u64;
+		assert_eq!(recorded(), [
+			(FirmwareService::Stall, unsupported, [10, 0,],),
+			(FirmwareService::AllocatePool, out_of_resources, [2, 64,],),
+		]);
+	}
+
+	#[test]
+	fn test_ring_keeps_latest_calls() {
+		let _fw = Firmware::install();
+		start();
+		let bs = boot_services();
+		for us in 0..CAPACITY + 3 {
+			let _ = bs.stall(us,);
+		}
+
+		let calls = calls();
+		assert_eq!(calls.read_count(), CAPACITY as u64 + 3);
+		assert_eq!(calls.dropped(), 3);
+		let recorded = recorded();
+		assert_eq!(recorded.len(), CAPACITY);
+		assert_eq!(recorded[0].2, [3, 0,]);
+		assert_eq!(recorded[CAPACITY - 1].2, [CAPACITY as u64 + 2, 0,]);
+	}
+}
@@ -1,5 +1,5 @@
 use crate::Rslt;
-use crate::into_null_terminated_utf16;
+use crate::chibi_uefi::string::Ucs2String;
 use crate::raw::protocol::file::FileProtocolV1;
 use crate::raw::protocol::file::SimpleFileSystemProtocol;
 use crate::raw::types::Status;
@@ -9,6 +9,7 @@ use crate::raw::types::file::FileInformation;
 use crate::raw::types::file::OpenMode;
 use alloc::vec;
 use alloc::vec::Vec;
+use core::ops::ControlFlow;
 use core::ptr;
 use core::ptr::NonNull;
 use oso_error::loader::UefiError;
@@ -31,7 +32,9 @@ impl FileProtocolV1 {
 		mode: OpenMode,
 		attrs: FileAttributes,
 	) -> Rslt<&mut FileProtocolV1, UefiError,> {
-		let path = into_null_terminated_utf16(path,);
+		let path = Ucs2String::new(path.as_ref(),).map_err(|_| {
+			oso_err!(UefiError::Custom("path is not representable in ucs-2"))
+		},)?;
 		let path = path.as_ptr();
 
 		let mut file = ptr::null_mut();
@@ -59,10 +62,39 @@ impl FileProtocolV1 {
 		let file_info = self.get_file_info()?;
 		let mut buf = vec![0; file_info.file_size as usize];
 		let read_len = unsafe { self.read(buf.as_mut_slice(),) }?;
-		assert_eq!(read_len, file_info.file_size as usize);
+		if read_len != buf.len() {
+			let e = UefiError::Custom("file is shorter than its size",);
+			return Err(oso_err!(e).into(),);
+		}
 		Ok(buf,)
 	}
 
+	/// fills `buf` by reads of at most `chunk` bytes, calling `each` with the
+	/// number of bytes read so far after each of them
+	///
+	/// # Returns
+	///
+	/// number of bytes read. it is less than the length of `buf` if the file
+	/// ended early or `each` returned [`ControlFlow::Break`]
+	pub fn read_chunks(
+		&mut self,
+		buf: &mut [u8],
+		chunk: usize,
+		mut each: impl FnMut(usize,) -> ControlFlow<(),>,
+	) -> Rslt<usize, UefiError,> {
+		let mut read = 0;
+		while read < buf.len() {
+			let end = (read + chunk).min(buf.len(),);
+			let len = unsafe { self.read(&mut buf[read..end],) }?;
+			read += len;
+			// zero length read means end of file
+			if len == 0 || each(read,).is_break() {
+				break;
+			}
+		}
+		Ok(read,)
+	}
+
 	pub fn get_info<F: FileInformation,>(
 		&mut self,
 		buf: &mut [u8],
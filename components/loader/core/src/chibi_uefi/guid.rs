@@ -1,5 +1,7 @@
+use super::known_guids;
 use crate::Rslt;
 use crate::raw::types::Guid;
+use core::fmt::Display;
 use oso_error::oso_err;
 
 #[macro_export]
@@ -62,6 +64,27 @@ impl Guid {
 			node,
 		)
 	}
+
+	/// name given by the specification, if it is in the known GUIDs table
+	pub fn name(&self,) -> Option<&'static str,> {
+		known_guids::name_of(self,)
+	}
+}
+
+/// registry format, such as `5b1b31a1-9562-11d2-8e3f-00a0c969723b`
+impl Display for Guid {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+		write!(
+			f,
+			"{:08x}-{:04x}-{:04x}-{:02x}{:02x}-",
+			self.time_low,
+			u16::from_le_bytes(self.time_mid,),
+			u16::from_le_bytes(self.time_high_and_version,),
+			self.clock_seq_high_and_reserved,
+			self.clock_seq_low
+		)?;
+		self.node.iter().try_for_each(|b| write!(f, "{b:02x}"),)
+	}
 }
 
 pub const fn read_to_hex<const N: usize,>(s: &str, buf: &mut [Hex; N],) {
@@ -0,0 +1,157 @@
+use super::Handle;
+use super::image_handle;
+use super::known_guids::Named;
+use super::protocol::OpenProtoAttr;
+use super::protocol::OpenProtoNecessity;
+use super::table::boot_services;
+use crate::Rslt;
+use crate::raw::protocol::device_path::DevicePathProtocol;
+use crate::raw::protocol::image::LoadedImageProtocol;
+use crate::raw::types::Guid;
+use crate::raw::types::memory::MemoryType;
+use crate::raw::types::protocol::DeviceSubType;
+use crate::raw::types::protocol::DeviceType;
+use alloc::format;
+use alloc::string::String;
+use core::ops::Range;
+use oso_error::loader::UefiError;
+
+/// information about the running loader image, copied out of
+/// `EFI_LOADED_IMAGE_PROTOCOL`
+#[derive(Debug, Clone,)]
+pub struct LoadedImage {
+	pub base:        u64,
+	pub size:        u64,
+	pub code_type:   MemoryType,
+	pub data_type:   MemoryType,
+	/// text representation of the device the image was loaded from
+	pub device_path: String,
+	/// path of the image file on `device_path`
+	pub file_path:   String,
+}
+
+impl LoadedImage {
+	/// physical address range the image occupies
+	pub fn range(&self,) -> Range<u64,> {
+		self.base..self.base + self.size
+	}
+}
+
+impl core::fmt::Display for LoadedImage {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+		write!(
+			f,
+			"{:#x}..{:#x} ({:#x} bytes) {}{}",
+			self.base,
+			self.base + self.size,
+			self.size,
+			self.device_path,
+			self.file_path
+		)
+	}
+}
+
+/// queries `EFI_LOADED_IMAGE_PROTOCOL` of the loader itself
+pub fn loaded_image() -> Rslt<LoadedImage, UefiError,> {
+	let bs = boot_services();
+	let necessity = OpenProtoNecessity::for_app(image_handle(),);
+	let interface = unsafe {
+		bs.open_protocol::<LoadedImageProtocol>(
+			necessity,
+			OpenProtoAttr::GET_PROTOCOL,
+		)
+	}?;
+	let image = unsafe { interface.interface().as_ref() };
+
+	let file_path = unsafe { image.file_path.as_ref() }
+		.map(|path| unsafe { device_path_text(path,) },)
+		.unwrap_or_default();
+	let device_path = unsafe { Handle::from_ptr(image.device_handle,) }
+		.and_then(device_path_of,)
+		.unwrap_or_default();
+
+	Ok(LoadedImage {
+		base: image.image_base as u64,
+		size: image.image_size,
+		code_type: image.image_code_type,
+		data_type: image.image_data_type,
+		device_path,
+		file_path,
+	},)
+}
+
+/// text representation of the device path installed on `handle`
+pub fn device_path_of(handle: Handle,) -> Option<String,> {
+	let necessity = OpenProtoNecessity::for_app(handle,);
+	let interface = unsafe {
+		boot_services().open_protocol::<DevicePathProtocol>(
+			necessity,
+			OpenProtoAttr::GET_PROTOCOL,
+		)
+	}
+	.ok()?;
+	let path = unsafe { interface.interface().as_ref() };
+	Some(unsafe { device_path_text(path,) },)
+}
+
+/// renders a device path as text
+///
+/// file path nodes are decoded as they are. vendor nodes are shown with the
+/// name of their GUID. other nodes are shown as `TYPE(subtype)` since the
+/// loader has no use for their payload
+///
+/// # Safety
+///
+/// `path` must be a well formed device path terminated by an end node
+pub unsafe fn device_path_text(path: &DevicePathProtocol,) -> String {
+	let mut text = String::new();
+	let mut node = Some(path,);
+	while let Some(n,) = node {
+		if n.is_end() {
+			break;
+		}
+
+		if n.major_type == DeviceType::MEDIA
+			&& n.subtype == DeviceSubType::MEDIA_FILE_PATH
+		{
+			let data = unsafe { n.data() };
+			let utf16 = data
+				.chunks_exact(2,)
+				.map(|c| u16::from_le_bytes([c[0], c[1],],),)
+				.take_while(|c| *c != 0,);
+			text.extend(char::decode_utf16(utf16,).map(|c| {
+				c.unwrap_or(char::REPLACEMENT_CHARACTER,)
+			},),);
+		} else if let Some((node_text, guid,),) = unsafe { vendor_guid(n,) } {
+			text.push_str(&format!("{node_text}({})/", Named(&guid,)),);
+		} else {
+			text.push_str(&format!("{:?}({})/", n.major_type, n.subtype.0),);
+		}
+
+		node = unsafe { n.next() };
+	}
+	text
+}
+
+/// text name of a vendor defined node and the GUID identifying its payload
+///
+/// # Safety
+///
+/// `node` must be a part of a well formed device path
+unsafe fn vendor_guid(
+	node: &DevicePathProtocol,
+) -> Option<(&'static str, Guid,),> {
+	let (vendor, node_text,) = match node.major_type {
+		DeviceType::HARDWARE => (DeviceSubType::HARDWARE_VENDOR, "VenHw",),
+		DeviceType::MESSAGING => (DeviceSubType::MESSAGING_VENDOR, "VenMsg",),
+		DeviceType::MEDIA => (DeviceSubType::MEDIA_VENDOR, "VenMedia",),
+		_ => return None,
+	};
+	let data = unsafe { node.data() };
+	if node.subtype != vendor || data.len() < size_of::<Guid,>() {
+		return None;
+	}
+	// payload is not aligned for `Guid`
+	let guid = unsafe { data.as_ptr().cast::<Guid>().read_unaligned() };
+	Some((node_text, guid,),)
+}
@@ -0,0 +1,22 @@
+//! # Known GUIDs
+//!
+//! Names of the GUIDs defined by the UEFI specification, generated from
+//! `resource/guids.h`. Used to print readable protocol and table names in
+//! diagnostics.
+
+use crate::raw::types::Guid;
+use core::fmt::Display;
+
+oso_proc_macro::guids!("resource/guids.h");
+
+/// displays the name of a GUID if it is known, the GUID itself otherwise
+pub struct Named<'a,>(pub &'a Guid,);
+
+impl Display for Named<'_,> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_,>,) -> core::fmt::Result {
+		match name_of(self.0,) {
+			Some(name,) => f.write_str(name,),
+			None => write!(f, "{}", self.0),
+		}
+	}
+}
@@ -1,6 +1,5 @@
 use oso_error::loader::UefiError;
 
-use super::table::boot_services;
 use crate::Rslt;
 use crate::raw::service::BootServices;
 use crate::raw::types::PhysicalAddress;
@@ -9,44 +8,13 @@ use crate::raw::types::memory::AllocateType;
 use crate::raw::types::memory::MemoryDescriptor;
 use crate::raw::types::memory::MemoryMapInfo;
 use crate::raw::types::memory::MemoryType;
-use core::alloc::GlobalAlloc;
-use core::alloc::Layout;
+use alloc::vec;
+use oso_no_std_shared::bridge::boot_info::FirmwareService;
+use alloc::vec::Vec;
 use core::ptr::NonNull;
 
 type RsltU<T,> = Rslt<T, UefiError,>;
 
-#[global_allocator]
-static LOADER_ALLOCATOR: LoaderAllocator = LoaderAllocator;
-
-pub struct LoaderAllocator;
-
-unsafe impl GlobalAlloc for LoaderAllocator {
-	unsafe fn alloc(&self, layout: core::alloc::Layout,) -> *mut u8 {
-		if layout.align() > 8 {
-			panic!()
-		}
-		let mem_ty = MemoryType::LOADER_DATA;
-		let bs = boot_services();
-		bs.allocate_pool(mem_ty, layout.size(),)
-			.expect("allocation failed",)
-			.as_ptr()
-	}
-
-	unsafe fn dealloc(&self, ptr: *mut u8, layout: core::alloc::Layout,) {
-		if layout.align() > 8 {
-			panic!()
-		}
-		let bs = boot_services();
-		bs.free_pool(unsafe { ptr.as_mut_unchecked() },)
-			.expect("deallocation failed",);
-	}
-}
-
-#[alloc_error_handler]
-fn alloc_error(layout: Layout,) -> ! {
-	panic!("system run out of memory: {layout:#?}")
-}
-
 impl BootServices {
 	pub fn allocate_pool(
 		&self,
@@ -54,7 +22,10 @@ impl BootServices {
 		size: usize,
 	) -> RsltU<NonNull<u8,>,> {
 		let mut buf = core::ptr::null_mut();
-		unsafe { (self.allocate_pool)(mem_ty, size, &mut buf,) }.ok_or()?;
+		let args = [mem_ty.0 as u64, size as u64,];
+		unsafe { (self.allocate_pool)(mem_ty, size, &mut buf,) }
+			.record(FirmwareService::AllocatePool, args,)
+			.ok_or()?;
 		Ok(unsafe {
 			// "allocate_pool must not return a null pointer if successful
 			NonNull::new_unchecked(buf,)
@@ -62,7 +33,10 @@ impl BootServices {
 	}
 
 	pub fn free_pool(&self, ptr: &mut u8,) -> RsltU<Status,> {
-		unsafe { (self.free_pool)(ptr,).ok_or() }
+		let addr = core::ptr::from_mut(ptr,).addr() as u64;
+		unsafe { (self.free_pool)(ptr,) }
+			.record(FirmwareService::FreePool, [addr, 0,],)
+			.ok_or()
 	}
 
 	pub fn allocate_pages(
@@ -80,9 +54,23 @@ impl BootServices {
 				&mut alloc_head,
 			)
 		}
+		.record(FirmwareService::AllocatePages, [
+			page_count as u64,
+			alloc_head,
+		],)
 		.ok_or_with(|_| alloc_head,)
 	}
 
+	pub fn free_pages(
+		&self,
+		memory: PhysicalAddress,
+		page_count: usize,
+	) -> RsltU<Status,> {
+		unsafe { (self.free_pages)(memory, page_count,) }
+			.record(FirmwareService::FreePages, [memory, page_count as u64,],)
+			.ok_or()
+	}
+
 	pub fn memory_map_size(&self,) -> (usize, usize,) {
 		let mut map_size = 0;
 		let mut map_key = 0;
@@ -97,7 +85,8 @@ impl BootServices {
 				&mut descriptor_size,
 				&mut desc_version,
 			)
-		};
+		}
+		.record(FirmwareService::GetMemoryMap, [0, map_size as u64,],);
 		assert_eq!(status, Status::EFI_BUFFER_TOO_SMALL);
 
 		assert_eq!(
@@ -119,6 +108,7 @@ impl BootServices {
 	}
 
 	pub fn get_memory_map(&self, buf: &mut [u8],) -> RsltU<MemoryMapInfo,> {
+		let buf_size = buf.len() as u64;
 		let mut map_size = buf.len();
 		let map_buf = buf.as_mut_ptr().cast::<MemoryDescriptor>();
 		let mut map_key = 0;
@@ -140,6 +130,7 @@ impl BootServices {
 				&mut desc_ver,
 			)
 		}
+		.record(FirmwareService::GetMemoryMap, [buf_size, map_size as u64,],)
 		.ok_or_with(|_| MemoryMapInfo {
 			map_size,
 			desc_size,
@@ -147,4 +138,115 @@ impl BootServices {
 			desc_ver,
 		},)
 	}
+
+	/// Copy of the current memory map, for inspection before exiting boot
+	/// services
+	pub fn memory_map_snapshot(&self,) -> RsltU<Vec<MemoryDescriptor,>,> {
+		// allocating the buffer may add a few entries
+		const EXTRA_ENTRIES: usize = 8;
+
+		let (map_size, desc_size,) = self.memory_map_size();
+		let len = map_size + desc_size * EXTRA_ENTRIES;
+		// u64 elements align the buffer like a descriptor
+		let mut buf = vec![0u64; len.div_ceil(size_of::<u64,>(),)];
+		let bytes = unsafe {
+			core::slice::from_raw_parts_mut(buf.as_mut_ptr().cast::<u8>(), len,)
+		};
+		let info = self.get_memory_map(bytes,)?;
+
+		let descriptors = (0..info.entry_count())
+			.map(|i| {
+				let desc = bytes[i * info.desc_size..].as_ptr();
+				unsafe { desc.cast::<MemoryDescriptor>().read_unaligned() }
+			},)
+			.collect();
+		Ok(descriptors,)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::chibi_uefi::mock::Firmware;
+	use crate::chibi_uefi::mock::Service;
+	use crate::chibi_uefi::table::boot_services;
+	use crate::raw::types::memory::MemoryAttribute;
+
+	fn descriptor(
+		memory_type: MemoryType,
+		physical_start: u64,
+		page_count: u64,
+	) -> MemoryDescriptor {
+		MemoryDescriptor {
+			memory_type,
+			physical_start,
+			virtual_start: 0,
+			page_count,
+			attribute: MemoryAttribute(0,),
+		}
+	}
+
+	fn sample_map() -> [MemoryDescriptor; 3] {
+		[
+			descriptor(MemoryType::CONVENTIONAL, 0, 0x9f,),
+			descriptor(MemoryType::LOADER_DATA, 0x10_0000, 0x100,),
+			descriptor(MemoryType::CONVENTIONAL, 0x20_0000, 0x1000,),
+		]
+	}
+
+	#[test]
+	fn test_memory_map_size() {
+		let fw = Firmware::install();
+		fw.set_memory_map(&sample_map(),);
+
+		let (map_size, desc_size,) = boot_services().memory_map_size();
+		assert_eq!(desc_size, size_of::<MemoryDescriptor,>());
+		assert_eq!(map_size, desc_size * 3);
+	}
+
+	#[test]
+	fn test_get_memory_map_too_small() {
+		let fw = Firmware::install();
+		fw.set_memory_map(&sample_map(),);
+
+		let mut buf = [0u64; 4];
+		let bytes = unsafe {
+			core::slice::from_raw_parts_mut(buf.as_mut_ptr().cast::<u8>(), 32,)
+		};
+		assert!(boot_services().get_memory_map(bytes,).is_err());
+	}
+
+	#[test]
+	fn test_memory_map_snapshot() {
+		let fw = Firmware::install();
+		fw.set_memory_map(&sample_map(),);
+
+		let snapshot = boot_services().memory_map_snapshot().unwrap();
+		assert_eq!(snapshot, sample_map());
+		assert_eq!(fw.calls_to(Service::GetMemoryMap,), 2);
+	}
+
+	#[test]
+	fn test_allocate_pool_error_status() {
+		let fw = Firmware::install();
+		fw.fail(Service::AllocatePool, Status::EFI_OUT_OF_RESOURCES,);
+
+		let pool = boot_services().allocate_pool(MemoryType::LOADER_DATA, 64,);
+		assert!(pool.is_err());
+		assert_eq!(fw.live_pools(), 0);
+	}
+
+	#[test]
+	fn test_free_pool() {
+		let fw = Firmware::install();
+		let bs = boot_services();
+
+		let mut pool = bs.allocate_pool(MemoryType::LOADER_DATA, 64,).unwrap();
+		assert_eq!(fw.live_pools(), 1);
+		bs.free_pool(unsafe { pool.as_mut() },).unwrap();
+		assert_eq!(fw.live_pools(), 0);
+
+		let mut unknown = 0u8;
+		assert!(bs.free_pool(&mut unknown,).is_err());
+	}
 }
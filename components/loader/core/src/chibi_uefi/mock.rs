@@ -0,0 +1,691 @@
+//! # Mock Firmware
+//!
+//! Boot services for unit tests of the loader on the host, where there is no
+//! firmware. [`Firmware::install`] publishes a system table whose boot
+//! services are plain functions of this module, so code under test calls
+//! them through [`boot_services`](super::table::boot_services) as it calls
+//! the firmware.
+//!
+//! The services answer from a script set up by the test: protocols installed
+//! on handles, a memory map, and statuses returned in place of the next call
+//! of a service. Every call is recorded, so a test checks what was asked of
+//! the firmware as well as what the loader made of the answer. Services
+//! without a mock return `EFI_UNSUPPORTED` and are recorded as
+//! [`Service::Other`].
+//!
+//! The tables are shared by the whole process and installed once, while
+//! scripts and records belong to the calling thread, so tests running in
+//! parallel do not see each other's calls.
+//!
+//! The loader targets UEFI by default, so its tests are run for the host:
+//!
+//! ```sh
+//! cargo test --lib --target x86_64-unknown-linux-gnu
+//! ```
+//!
+//! ```rust,ignore
+//! let fw = Firmware::install();
+//! fw.install_protocol(handle(1,), LoadedImageProtocol::GUID, ptr,);
+//! fw.fail(Service::OpenProtocol, Status::EFI_ACCESS_DENIED,);
+//!
+//! let bs = boot_services();
+//! assert!(bs.open_protocol_exclusive::<LoadedImageProtocol>(h,).is_err());
+//! assert_eq!(fw.calls_to(Service::OpenProtocol,), 1);
+//! ```
+
+use super::set_image_handle_panicking;
+use super::table::BootPhase;
+use super::table::set_boot_phase;
+use super::table::set_system_table_panicking;
+use crate::raw::protocol::OpenProtocolInformationEntry;
+use crate::raw::protocol::device_path::DevicePathProtocol;
+use crate::raw::service::BootServices;
+use crate::raw::table::SystemTable;
+use crate::raw::types::Boolean;
+use crate::raw::types::Char16;
+use crate::raw::types::Event;
+use crate::raw::types::Guid;
+use crate::raw::types::PhysicalAddress;
+use crate::raw::types::Status;
+use crate::raw::types::Tpl;
+use crate::raw::types::UnsafeHandle;
+use crate::raw::types::event::EventType;
+use crate::raw::types::memory::AllocateType;
+use crate::raw::types::memory::MemoryDescriptor;
+use crate::raw::types::memory::MemoryType;
+use crate::raw::types::memory::PAGE_SIZE;
+use crate::raw::types::protocol::InterfaceType;
+use crate::raw::types::time::TimerDelay;
+use core::ffi::c_void;
+use core::ptr;
+use std::alloc::Layout;
+use std::boxed::Box;
+use std::cell::RefCell;
+use std::sync::Once;
+use std::vec::Vec;
+
+/// Handle of the loader image
+pub const IMAGE: UnsafeHandle = ptr::without_provenance_mut(0x1000,);
+/// Version of the descriptors of the mock memory map
+pub const DESCRIPTOR_VERSION: u32 = 1;
+
+static INSTALL: Once = Once::new();
+
+std::thread_local! {
+	static STATE: RefCell<State,> = RefCell::default();
+}
+
+/// Boot service called by the code under test
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum Service {
+	AllocatePages,
+	FreePages,
+	AllocatePool,
+	FreePool,
+	GetMemoryMap,
+	LocateHandleBuffer,
+	OpenProtocol,
+	CloseProtocol,
+	HandleProtocol,
+	ProtocolsPerHandle,
+	ConnectController,
+	ExitBootServices,
+	/// Service without a mock, by the name of its field
+	Other(&'static str,),
+}
+
+/// Recorded call of a boot service
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct Call {
+	pub service: Service,
+	/// Handle the call is about, null if none
+	pub handle:  UnsafeHandle,
+	/// Protocol the call is about
+	pub guid:    Option<Guid,>,
+}
+
+/// Script and records of the calling thread
+///
+/// Dropping it keeps the tables installed, as other threads may still use
+/// them.
+#[derive(Debug,)]
+pub struct Firmware(());
+
+impl Firmware {
+	/// Installs the mock tables if not yet installed, and clears the script
+	/// and the records of the calling thread, which is back in
+	/// [`BootPhase::Boot`]
+	pub fn install() -> Self {
+		INSTALL.call_once(|| {
+			let boot_services = Box::leak(Box::new(boot_services(),),);
+			// SAFETY: the table only holds integers and pointers
+			let mut table: SystemTable = unsafe { core::mem::zeroed() };
+			table.boot_services = boot_services;
+			set_system_table_panicking(Box::leak(Box::new(table,),),);
+			set_image_handle_panicking(IMAGE,);
+		},);
+		STATE.with_borrow_mut(|state| *state = State::default(),);
+		set_boot_phase(BootPhase::Boot,);
+		Self((),)
+	}
+
+	/// Installs the protocol `guid` with `interface` on `handle`
+	pub fn install_protocol(
+		&self,
+		handle: UnsafeHandle,
+		guid: Guid,
+		interface: *mut c_void,
+	) {
+		STATE.with_borrow_mut(|state| {
+			state.protocols.push(Installed {
+				handle,
+				guid: Box::new(guid,),
+				interface,
+			},)
+		},);
+	}
+
+	/// Replaces the memory map, which changes its key
+	pub fn set_memory_map(&self, descriptors: &[MemoryDescriptor],) {
+		STATE.with_borrow_mut(|state| {
+			state.memory_map = descriptors.to_vec();
+			state.map_key += 1;
+		},);
+	}
+
+	/// Returns `status` from the next call of `service` instead of running
+	/// it. Several failures of a service are returned in order
+	pub fn fail(&self, service: Service, status: Status,) {
+		STATE.with_borrow_mut(|state| {
+			state.failures.push((service, status,),);
+		},);
+	}
+
+	/// Calls so far, oldest first
+	pub fn calls(&self,) -> Vec<Call,> {
+		STATE.with_borrow(|state| state.calls.clone(),)
+	}
+
+	/// Number of calls of `service` so far
+	pub fn calls_to(&self, service: Service,) -> usize {
+		STATE.with_borrow(|state| {
+			state.calls.iter().filter(|call| call.service == service,).count()
+		},)
+	}
+
+	/// Protocols opened and not closed yet, as handle and GUID
+	pub fn open_protocols(&self,) -> Vec<(UnsafeHandle, Guid,),> {
+		STATE.with_borrow(|state| state.open.clone(),)
+	}
+
+	/// Number of pool allocations not freed yet
+	pub fn live_pools(&self,) -> usize {
+		STATE.with_borrow(|state| state.pools.len(),)
+	}
+
+	/// Number of pages allocated and not freed yet
+	pub fn live_pages(&self,) -> usize {
+		STATE.with_borrow(|state| {
+			let bytes: usize =
+				state.pages.iter().map(|(_, layout,)| layout.size(),).sum();
+			bytes / PAGE_SIZE
+		},)
+	}
+}
+
+/// Opaque handle numbered `n`, which must not be 0
+pub fn handle(n: usize,) -> UnsafeHandle {
+	assert_ne!(n, 0, "handles are not null");
+	ptr::without_provenance_mut(n,)
+}
+
+#[derive(Debug, Default,)]
+struct State {
+	calls:      Vec<Call,>,
+	failures:   Vec<(Service, Status,),>,
+	protocols:  Vec<Installed,>,
+	memory_map: Vec<MemoryDescriptor,>,
+	map_key:    usize,
+	/// address and layout of each live pool allocation
+	pools:      Vec<(usize, Layout,),>,
+	/// address and layout of each live page allocation
+	pages:      Vec<(usize, Layout,),>,
+	open:       Vec<(UnsafeHandle, Guid,),>,
+}
+
+#[derive(Debug,)]
+struct Installed {
+	handle:    UnsafeHandle,
+	/// boxed, as `protocols_per_handle` hands out its address
+	guid:      Box<Guid,>,
+	interface: *mut c_void,
+}
+
+/// records the call, and takes the scripted status of `service` if any
+fn enter(
+	service: Service,
+	handle: UnsafeHandle,
+	guid: *const Guid,
+) -> Option<Status,> {
+	// SAFETY: callers pass null or a valid GUID
+	let guid = unsafe { guid.as_ref() }.copied();
+	STATE.with_borrow_mut(|state| {
+		state.calls.push(Call { service, handle, guid, },);
+		let i = state.failures.iter().position(|(s, _,)| *s == service,)?;
+		Some(state.failures.remove(i,).1,)
+	},)
+}
+
+/// allocation returned by `allocate_pool`
+fn pool(size: usize,) -> *mut u8 {
+	let layout = Layout::from_size_align(size.max(1,), 8,).unwrap();
+	// SAFETY: the size is not zero
+	let ptr = unsafe { std::alloc::alloc_zeroed(layout,) };
+	STATE.with_borrow_mut(|state| state.pools.push((ptr as usize, layout,),),);
+	ptr
+}
+
+unsafe extern "efiapi" fn raise_tpl(_new_tpl: Tpl,) -> Tpl {
+	Tpl::APPLICATION
+}
+
+unsafe extern "efiapi" fn restore_tpl(_old_tpl: Tpl,) {}
+
+unsafe extern "efiapi" fn allocate_pages(
+	allocation_type: AllocateType,
+	_memory_type: MemoryType,
+	pages: usize,
+	memory: *mut PhysicalAddress,
+) -> Status {
+	if let Some(status,) =
+		enter(Service::AllocatePages, ptr::null_mut(), ptr::null(),)
+	{
+		return status;
+	}
+	// the mock can not place pages at a given address
+	if allocation_type != AllocateType::ALLOCATE_ANY_PAGES || pages == 0 {
+		return Status::EFI_UNSUPPORTED;
+	}
+	let size = pages * PAGE_SIZE;
+	let layout = Layout::from_size_align(size, PAGE_SIZE,).unwrap();
+	// SAFETY: the size is not zero
+	let ptr = unsafe { std::alloc::alloc_zeroed(layout,) }.expose_provenance();
+	STATE.with_borrow_mut(|state| state.pages.push((ptr, layout,),),);
+	unsafe { *memory = ptr as PhysicalAddress };
+	Status::EFI_SUCCESS
+}
+
+unsafe extern "efiapi" fn free_pages(
+	memory: PhysicalAddress,
+	pages: usize,
+) -> Status {
+	if let Some(status,) =
+		enter(Service::FreePages, ptr::null_mut(), ptr::null(),)
+	{
+		return status;
+	}
+	let layout = STATE.with_borrow_mut(|state| {
+		let i = state.pages.iter().position(|&(p, layout,)| {
+			p as PhysicalAddress == memory && layout.size() == pages * PAGE_SIZE
+		},)?;
+		Some(state.pages.remove(i,).1,)
+	},);
+	let Some(layout,) = layout else {
+		return Status::EFI_NOT_FOUND;
+	};
+	let ptr = ptr::with_exposed_provenance_mut(memory as usize,);
+	unsafe { std::alloc::dealloc(ptr, layout,) };
+	Status::EFI_SUCCESS
+}
+
+unsafe extern "efiapi" fn allocate_pool(
+	_pool_type: MemoryType,
+	size: usize,
+	buffer: *mut *mut u8,
+) -> Status {
+	if let Some(status,) =
+		enter(Service::AllocatePool, ptr::null_mut(), ptr::null(),)
+	{
+		return status;
+	}
+	unsafe { *buffer = pool(size,) };
+	Status::EFI_SUCCESS
+}
+
+unsafe extern "efiapi" fn free_pool(buffer: *mut u8,) -> Status {
+	if let Some(status,) =
+		enter(Service::FreePool, ptr::null_mut(), ptr::null(),)
+	{
+		return status;
+	}
+	let layout = STATE.with_borrow_mut(|state| {
+		let i = state.pools.iter().position(|(p, _,)| *p == buffer as usize,)?;
+		Some(state.pools.remove(i,).1,)
+	},);
+	let Some(layout,) = layout else {
+		return Status::EFI_INVALID_PARAMETER;
+	};
+	unsafe { std::alloc::dealloc(buffer, layout,) };
+	Status::EFI_SUCCESS
+}
+
+unsafe extern "efiapi" fn get_memory_map(
+	memory_map_size: *mut usize,
+	memory_map: *mut MemoryDescriptor,
+	map_key: *mut usize,
+	descriptor_size: *mut usize,
+	descriptor_version: *mut u32,
+) -> Status {
+	if let Some(status,) =
+		enter(Service::GetMemoryMap, ptr::null_mut(), ptr::null(),)
+	{
+		return status;
+	}
+	let (descriptors, key,) =
+		STATE.with_borrow(|state| (state.memory_map.clone(), state.map_key,),);
+	let required = size_of_val(descriptors.as_slice(),);
+	unsafe {
+		*descriptor_size = size_of::<MemoryDescriptor,>();
+		*descriptor_version = DESCRIPTOR_VERSION;
+		let available = *memory_map_size;
+		*memory_map_size = required;
+		if available < required || memory_map.is_null() {
+			return Status::EFI_BUFFER_TOO_SMALL;
+		}
+		let len = descriptors.len();
+		ptr::copy_nonoverlapping(descriptors.as_ptr(), memory_map, len,);
+		*map_key = key;
+	}
+	Status::EFI_SUCCESS
+}
+
+unsafe extern "efiapi" fn locate_handle_buffer(
+	search_type: i32,
+	protocol: *const Guid,
+	_search_key: *const c_void,
+	handles_count: *mut usize,
+	buffer: *mut *mut UnsafeHandle,
+) -> Status {
+	if let Some(status,) =
+		enter(Service::LocateHandleBuffer, ptr::null_mut(), protocol,)
+	{
+		return status;
+	}
+	// SAFETY: null or a valid GUID
+	let guid = unsafe { protocol.as_ref() };
+	let mut handles: Vec<UnsafeHandle,> = STATE.with_borrow(|state| {
+		state
+			.protocols
+			.iter()
+			.filter(|p| search_type == 0 || Some(&*p.guid) == guid,)
+			.map(|p| p.handle,)
+			.collect()
+	},);
+	if search_type != 0 && search_type != 2 {
+		return Status::EFI_UNSUPPORTED;
+	}
+	handles.dedup();
+	if handles.is_empty() {
+		return Status::EFI_NOT_FOUND;
+	}
+
+	let out = pool(size_of_val(handles.as_slice(),),).cast::<UnsafeHandle>();
+	unsafe {
+		ptr::copy_nonoverlapping(handles.as_ptr(), out, handles.len(),);
+		*handles_count = handles.len();
+		*buffer = out;
+	}
+	Status::EFI_SUCCESS
+}
+
+/// interface of `guid` on `handle`
+fn installed(handle: UnsafeHandle, guid: *const Guid,) -> Option<*mut c_void,> {
+	// SAFETY: callers pass a valid GUID
+	let guid = unsafe { *guid };
+	STATE.with_borrow(|state| {
+		state
+			.protocols
+			.iter()
+			.find(|p| p.handle == handle && *p.guid == guid,)
+			.map(|p| p.interface,)
+	},)
+}
+
+unsafe extern "efiapi" fn open_protocol(
+	handle: UnsafeHandle,
+	protocol: *const Guid,
+	interface: *mut *mut c_void,
+	_agent_handle: UnsafeHandle,
+	_controller_handle: UnsafeHandle,
+	_attributes: u32,
+) -> Status {
+	if let Some(status,) = enter(Service::OpenProtocol, handle, protocol,) {
+		return status;
+	}
+	let Some(found,) = installed(handle, protocol,) else {
+		return Status::EFI_UNSUPPORTED;
+	};
+	// SAFETY: `installed` found the GUID
+	let guid = unsafe { *protocol };
+	STATE.with_borrow_mut(|state| state.open.push((handle, guid,),),);
+	if !interface.is_null() {
+		unsafe { *interface = found };
+	}
+	Status::EFI_SUCCESS
+}
+
+unsafe extern "efiapi" fn close_protocol(
+	handle: UnsafeHandle,
+	protocol: *const Guid,
+	_agent_handle: UnsafeHandle,
+	_controller_handle: UnsafeHandle,
+) -> Status {
+	if let Some(status,) = enter(Service::CloseProtocol, handle, protocol,) {
+		return status;
+	}
+	let key = (handle, unsafe { *protocol },);
+	let closed = STATE.with_borrow_mut(|state| {
+		let i = state.open.iter().position(|open| *open == key,)?;
+		Some(state.open.remove(i,),)
+	},);
+	match closed {
+		Some(_,) => Status::EFI_SUCCESS,
+		None => Status::EFI_NOT_FOUND,
+	}
+}
+
+unsafe extern "efiapi" fn handle_protocol(
+	handle: UnsafeHandle,
+	protocol: *const Guid,
+	interface: *mut *mut c_void,
+) -> Status {
+	if let Some(status,) = enter(Service::HandleProtocol, handle, protocol,) {
+		return status;
+	}
+	match installed(handle, protocol,) {
+		Some(found,) => {
+			unsafe { *interface = found };
+			Status::EFI_SUCCESS
+		},
+		None => Status::EFI_UNSUPPORTED,
+	}
+}
+
+unsafe extern "efiapi" fn protocols_per_handle(
+	handle: UnsafeHandle,
+	protocol_buffer: *mut *mut *const Guid,
+	protocol_buffer_count: *mut usize,
+) -> Status {
+	if let Some(status,) =
+		enter(Service::ProtocolsPerHandle, handle, ptr::null(),)
+	{
+		return status;
+	}
+	let guids: Vec<*const Guid,> = STATE.with_borrow(|state| {
+		state
+			.protocols
+			.iter()
+			.filter(|p| p.handle == handle,)
+			.map(|p| &*p.guid as *const Guid,)
+			.collect()
+	},);
+	if guids.is_empty() {
+		return Status::EFI_INVALID_PARAMETER;
+	}
+
+	let out = pool(size_of_val(guids.as_slice(),),).cast::<*const Guid>();
+	unsafe {
+		ptr::copy_nonoverlapping(guids.as_ptr(), out, guids.len(),);
+		*protocol_buffer_count = guids.len();
+		*protocol_buffer = out;
+	}
+	Status::EFI_SUCCESS
+}
+
+unsafe extern "efiapi" fn connect_controller(
+	controller_handle: UnsafeHandle,
+	_driver_image_handle: UnsafeHandle,
+	_remaining_device_path: *const DevicePathProtocol,
+	_recursive: Boolean,
+) -> Status {
+	enter(Service::ConnectController, controller_handle, ptr::null(),)
+		.unwrap_or(Status::EFI_SUCCESS,)
+}
+
+unsafe extern "efiapi" fn exit_boot_services(
+	image_handle: UnsafeHandle,
+	map_key: usize,
+) -> Status {
+	if let Some(status,) =
+		enter(Service::ExitBootServices, image_handle, ptr::null(),)
+	{
+		return status;
+	}
+	if STATE.with_borrow(|state| state.map_key,) == map_key {
+		Status::EFI_SUCCESS
+	} else {
+		Status::EFI_INVALID_PARAMETER
+	}
+}
+
+unsafe extern "efiapi" fn copy_mem(
+	dest: *mut u8,
+	source: *const u8,
+	len: usize,
+) {
+	unsafe { ptr::copy(source, dest, len,) };
+}
+
+unsafe extern "efiapi" fn set_mem(buf: *mut u8, size: usize, value: u8,) {
+	unsafe { ptr::write_bytes(buf, value, size,) };
+}
+
+/// defines services which are recorded as [`Service::Other`] and return
+/// `EFI_UNSUPPORTED`
+macro_rules! unsupported {
+	($($name:ident($($arg:ty),* $(,)?);)*) => {$(
+		unsafe extern "efiapi" fn $name($(_: $arg),*) -> Status {
+			let other = Service::Other(stringify!($name),);
+			enter(other, ptr::null_mut(), ptr::null(),)
+				.unwrap_or(Status::EFI_UNSUPPORTED,)
+		}
+	)*};
+}
+
+unsupported! {
+	create_event(
+		EventType,
+		Tpl,
+		Option<unsafe extern "efiapi" fn(Event, *mut c_void,),>,
+		*mut c_void,
+		*mut Event,
+	);
+	set_timer(Event, TimerDelay, u64);
+	wait_for_event(usize, *mut Event, *mut usize);
+	signal_event(Event);
+	close_event(Event);
+	check_event(Event);
+	install_protocol_interface(
+		*mut UnsafeHandle,
+		*const Guid,
+		InterfaceType,
+		*const c_void,
+	);
+	reinstall_protocol_interface(
+		*mut UnsafeHandle,
+		*const Guid,
+		*const c_void,
+		*const c_void,
+	);
+	uninstall_protocol_interface(UnsafeHandle, *const Guid, *const c_void);
+	register_protocol_notify(*const Guid, Event, *mut *const c_void);
+	locate_handle(
+		i32,
+		*const Guid,
+		*const c_void,
+		*mut usize,
+		*mut UnsafeHandle,
+	);
+	locate_device_path(
+		*const Guid,
+		*mut *const DevicePathProtocol,
+		*mut *mut c_void,
+	);
+	install_configuration_table(*const Guid, *const c_void);
+	load_image(
+		Boolean,
+		UnsafeHandle,
+		*const DevicePathProtocol,
+		*const u8,
+		usize,
+		*mut UnsafeHandle,
+	);
+	start_image(UnsafeHandle, *mut usize, *mut *mut Char16);
+	exit(UnsafeHandle, Status, usize, *mut Char16);
+	unload_image(UnsafeHandle);
+	get_next_monotonic_count(*mut u64);
+	stall(usize);
+	set_watchdog_timer(usize, u64, usize, *const u16);
+	disconnect_controller(UnsafeHandle, UnsafeHandle, UnsafeHandle);
+	open_protocol_information(
+		UnsafeHandle,
+		*const Guid,
+		*mut *const OpenProtocolInformationEntry,
+		*mut usize,
+	);
+	locate_protocol(*const Guid, *mut c_void, *mut *mut c_void);
+	calculate_crc32(*const c_void, usize, *mut u32);
+	create_event_ex(
+		EventType,
+		Tpl,
+		Option<unsafe extern "efiapi" fn(Event, *mut c_void,),>,
+		*mut c_void,
+		*mut Guid,
+		*mut Event,
+	);
+}
+
+unsafe extern "C" fn install_multiple_protocol_interfaces(
+	_: *mut UnsafeHandle,
+	_: ...
+) -> Status {
+	Status::EFI_UNSUPPORTED
+}
+
+unsafe extern "C" fn uninstall_multiple_protocol_interfaces(
+	_: UnsafeHandle,
+	_: ...
+) -> Status {
+	Status::EFI_UNSUPPORTED
+}
+
+fn boot_services() -> BootServices {
+	BootServices {
+		// SAFETY: the header only holds integers
+		header: unsafe { core::mem::zeroed() },
+		raise_tpl,
+		restore_tpl,
+		allocate_pages,
+		free_pages,
+		get_memory_map,
+		allocate_pool,
+		free_pool,
+		create_event,
+		set_timer,
+		wait_for_event,
+		signal_event,
+		close_event,
+		check_event,
+		install_protocol_interface,
+		reinstall_protocol_interface,
+		uninstall_protocol_interface,
+		handle_protocol,
+		reserved: ptr::null_mut(),
+		register_protocol_notify,
+		locate_handle,
+		locate_device_path,
+		install_configuration_table,
+		load_image,
+		start_image,
+		exit,
+		unload_image,
+		exit_boot_services,
+		get_next_monotonic_count,
+		stall,
+		set_watchdog_timer,
+		connect_controller,
+		disconnect_controller,
+		open_protocol,
+		close_protocol,
+		open_protocol_information,
+		protocols_per_handle,
+		locate_handle_buffer,
+		locate_protocol,
+		install_multiple_protocol_interfaces,
+		uninstall_multiple_protocol_interfaces,
+		calculate_crc32,
+		copy_mem,
+		set_mem,
+		create_event_ex,
+	}
+}
@@ -1,10 +1,14 @@
 use super::Handle;
+use super::image::device_path_of;
 use super::image_handle;
+use super::known_guids::Named;
 use super::table::boot_services;
 use crate::guid;
 use crate::raw::protocol::device_path::DevicePathProtocol;
 use crate::raw::protocol::file::SimpleFileSystemProtocol;
 use crate::raw::protocol::graphic::GraphicsOutputProtocol;
+use crate::raw::protocol::image::LoadedImageProtocol;
+use crate::raw::protocol::serial::SerialIoProtocol;
 use crate::raw::protocol::text::TextOutputProtocol;
 use crate::raw::service::BootServices;
 use crate::raw::types::Guid;
@@ -12,12 +16,16 @@ use crate::raw::types::UnsafeHandle;
 use crate::raw::types::file::FileInfo;
 use crate::raw::types::file::FileSystemInfo;
 use crate::raw::types::file::FileSystemVolumeLabel;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
 use core::ffi::c_void;
 use core::ptr;
 use core::ptr::NonNull;
 use oso_error::Rslt;
 use oso_error::loader::UefiError;
 use oso_error::oso_err;
+use oso_no_std_shared::bridge::boot_info::FirmwareService;
 
 type RsltU<T,> = Rslt<T, UefiError,>;
 
@@ -53,6 +61,14 @@ impl Protocol for GraphicsOutputProtocol {
 	const GUID: Guid = guid!("9042a9de-23dc-4a38-96fb-7aded080516a");
 }
 
+impl Protocol for LoadedImageProtocol {
+	const GUID: Guid = guid!("5b1b31a1-9562-11d2-8e3f-00a0c969723b");
+}
+
+impl Protocol for SerialIoProtocol {
+	const GUID: Guid = guid!("bb25cf6f-f1d4-11d2-9a0c-0090273fc1fd");
+}
+
 impl BootServices {
 	/// # Safety
 	/// TODO: fill doc comment
@@ -81,6 +97,10 @@ impl BootServices {
 				&mut buffer,
 			)
 		}
+		.record(FirmwareService::LocateHandleBuffer, [
+			ty as u64,
+			num_handles as u64,
+		],)
 		.ok_or()?;
 
 		let handler_range =
@@ -151,6 +171,10 @@ impl BootServices {
 				Handle::opt_to_ptr(necessity.controller.clone(),),
 				attr.0,
 			)
+			.record(FirmwareService::OpenProtocol, [
+				necessity.handle.as_ptr().addr() as u64,
+				P::GUID.time_low as u64,
+			],)
 			.ok_or_with(|_| ProtocolInterface {
 				interface: if interface.is_null() {
 					None
@@ -181,22 +205,80 @@ impl BootServices {
 		unsafe { bs.open_protocol(necessity, attr,) }
 	}
 
+	/// Interface of the protocol `P` on `handle`, which is not closed
 	pub fn handle_protocol<P: Protocol,>(
 		&self,
 		handle: Handle,
-	) -> RsltU<NonNull<ProtocolInterface<P,>,>,> {
-		let interface = ptr::null_mut();
+	) -> RsltU<NonNull<P,>,> {
+		let mut interface = ptr::null_mut();
 		unsafe {
-			(self.handle_protocol)(handle.as_ptr(), &P::GUID, interface,)
-				.ok_or_with(|_| {
-					let interface =
-						(*interface).cast::<ProtocolInterface<P,>>();
-					NonNull::new(interface,).expect("interface is null",)
-				},)
+			(self.handle_protocol)(handle.as_ptr(), &P::GUID, &mut interface,)
 		}
+		.record(FirmwareService::HandleProtocol, [
+			handle.as_ptr().addr() as u64,
+			P::GUID.time_low as u64,
+		],)
+		.ok_or()?;
+		NonNull::new(interface.cast(),)
+			.ok_or(oso_err!(UefiError::Custom("interface is null")),)
 	}
 }
 
+impl BootServices {
+	/// GUIDs of the protocols installed on `handle`
+	pub fn protocols_per_handle(&self, handle: &Handle,) -> RsltU<Vec<Guid,>,> {
+		let mut buffer = ptr::null_mut();
+		let mut count = 0;
+		unsafe {
+			(self.protocols_per_handle)(
+				handle.as_ptr(),
+				&mut buffer,
+				&mut count,
+			)
+		}
+		.record(FirmwareService::ProtocolsPerHandle, [
+			handle.as_ptr().addr() as u64,
+			count as u64,
+		],)
+		.ok_or()?;
+		if buffer.is_null() {
+			return Ok(Vec::new(),);
+		}
+
+		let guids = unsafe { core::slice::from_raw_parts(buffer, count,) }
+			.iter()
+			.filter_map(|guid| unsafe { guid.as_ref() }.copied(),)
+			.collect();
+		self.free_pool(unsafe { buffer.cast::<u8>().as_mut_unchecked() },)?;
+		Ok(guids,)
+	}
+}
+
+/// one line per handle of the system: its device path, if any, followed by
+/// the names of its protocols
+pub fn dump_handles() -> RsltU<Vec<String,>,> {
+	let bs = boot_services();
+	let handles =
+		unsafe { bs.locate_handle_buffer(HandleSearchType::AllHandles,) }?;
+
+	let mut lines = Vec::with_capacity(handles.len(),);
+	for raw in handles.iter() {
+		let Some(handle,) = (unsafe { Handle::from_ptr(*raw,) }) else {
+			continue;
+		};
+		// a handle whose protocols can not be listed is still worth a line
+		let protocols = bs.protocols_per_handle(&handle,).unwrap_or_default();
+		let protocols: Vec<String,> =
+			protocols.iter().map(|guid| format!("{}", Named(guid,)),).collect();
+		let path = device_path_of(handle,).unwrap_or_default();
+		lines.push(format!("{raw:p} {path} [{}]", protocols.join(", ")),);
+	}
+
+	let buffer = handles.as_mut_ptr().cast::<u8>();
+	bs.free_pool(unsafe { buffer.as_mut_unchecked() },)?;
+	Ok(lines,)
+}
+
 #[derive(Debug,)]
 pub enum HandleSearchType<'g,> {
 	/// return all handles present on the system
@@ -289,3 +371,161 @@ impl OpenProtoNecessity {
 		Handle::opt_to_ptr(self.controller.clone(),)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::chibi_uefi::mock::Call;
+	use crate::chibi_uefi::mock::Firmware;
+	use crate::chibi_uefi::mock::Service;
+	use crate::chibi_uefi::mock::handle;
+	use crate::raw::types::Status;
+
+	const IMAGE: Guid = LoadedImageProtocol::GUID;
+	const SERIAL: Guid = SerialIoProtocol::GUID;
+
+	/// interface pointer handed out by the mock. never dereferenced
+	fn interface(n: usize,) -> *mut c_void {
+		ptr::without_provenance_mut(n,)
+	}
+
+	fn handle_of(n: usize,) -> Handle {
+		unsafe { Handle::from_ptr(handle(n,),) }.unwrap()
+	}
+
+	#[test]
+	fn test_handle_for_protocol_takes_first_handle() {
+		let fw = Firmware::install();
+		let fs = SimpleFileSystemProtocol::GUID;
+		fw.install_protocol(handle(1,), IMAGE, interface(1,),);
+		fw.install_protocol(handle(2,), fs, interface(2,),);
+		fw.install_protocol(handle(3,), fs, interface(3,),);
+
+		let bs = boot_services();
+		let found =
+			unsafe { bs.handle_for_protocol::<SimpleFileSystemProtocol>() };
+		assert_eq!(found.unwrap().as_ptr(), handle(2,));
+		assert_eq!(fw.calls(), [Call {
+			service: Service::LocateHandleBuffer,
+			handle:  ptr::null_mut(),
+			guid:    Some(fs,),
+		}]);
+	}
+
+	#[test]
+	fn test_handle_for_protocol_without_handles() {
+		let fw = Firmware::install();
+		fw.install_protocol(handle(1,), IMAGE, interface(1,),);
+
+		let bs = boot_services();
+		let found = unsafe { bs.handle_for_protocol::<SerialIoProtocol>() };
+		assert!(found.is_err());
+	}
+
+	#[test]
+	fn test_locate_handle_buffer_error_status() {
+		let fw = Firmware::install();
+		fw.install_protocol(handle(1,), IMAGE, interface(1,),);
+		fw.fail(Service::LocateHandleBuffer, Status::EFI_OUT_OF_RESOURCES,);
+
+		let bs = boot_services();
+		let all = || unsafe {
+			bs.locate_handle_buffer(HandleSearchType::AllHandles,)
+		};
+		assert!(all().is_err());
+		// the failure is used up
+		assert_eq!(all().unwrap(), [handle(1,)]);
+	}
+
+	#[test]
+	fn test_open_protocol_closes_on_drop() {
+		let fw = Firmware::install();
+		fw.install_protocol(handle(4,), IMAGE, interface(0x40,),);
+
+		let bs = boot_services();
+		let opened = bs.open_protocol_exclusive::<LoadedImageProtocol>(
+			handle_of(4,),
+		);
+		let opened = opened.unwrap();
+		assert_eq!(opened.interface().as_ptr().cast(), interface(0x40,));
+		assert_eq!(fw.open_protocols(), [(handle(4,), IMAGE,)]);
+
+		drop(opened,);
+		assert!(fw.open_protocols().is_empty());
+		assert_eq!(fw.calls()[1], Call {
+			service: Service::CloseProtocol,
+			handle:  handle(4,),
+			guid:    Some(IMAGE,),
+		});
+	}
+
+	#[test]
+	fn test_open_protocol_error_is_not_closed() {
+		let fw = Firmware::install();
+		fw.install_protocol(handle(4,), IMAGE, interface(1,),);
+		fw.fail(Service::OpenProtocol, Status::EFI_ACCESS_DENIED,);
+
+		let bs = boot_services();
+		let opened = bs.open_protocol_exclusive::<LoadedImageProtocol>(
+			handle_of(4,),
+		);
+		assert!(opened.is_err());
+		assert!(fw.open_protocols().is_empty());
+		assert_eq!(fw.calls_to(Service::CloseProtocol,), 0);
+	}
+
+	#[test]
+	fn test_open_protocol_not_installed() {
+		let fw = Firmware::install();
+		fw.install_protocol(handle(4,), IMAGE, interface(1,),);
+
+		let bs = boot_services();
+		let opened =
+			bs.open_protocol_exclusive::<SerialIoProtocol>(handle_of(4,),);
+		assert!(opened.is_err());
+		assert_eq!(fw.calls_to(Service::OpenProtocol,), 1);
+	}
+
+	#[test]
+	fn test_handle_protocol() {
+		let fw = Firmware::install();
+		fw.install_protocol(handle(5,), SERIAL, interface(0x50,),);
+
+		let bs = boot_services();
+		let serial = bs.handle_protocol::<SerialIoProtocol>(handle_of(5,),);
+		assert_eq!(serial.unwrap().as_ptr().cast(), interface(0x50,));
+		let gop = bs.handle_protocol::<GraphicsOutputProtocol>(handle_of(5,),);
+		assert!(gop.is_err());
+	}
+
+	#[test]
+	fn test_protocols_per_handle_frees_buffer() {
+		let fw = Firmware::install();
+		let path = DevicePathProtocol::GUID;
+		fw.install_protocol(handle(6,), IMAGE, interface(1,),);
+		fw.install_protocol(handle(6,), path, interface(2,),);
+
+		let bs = boot_services();
+		let guids = bs.protocols_per_handle(&handle_of(6,),).unwrap();
+		assert_eq!(guids, [IMAGE, path]);
+		assert_eq!(fw.live_pools(), 0);
+	}
+
+	#[test]
+	fn test_dump_handles() {
+		let fw = Firmware::install();
+		fw.install_protocol(handle(7,), IMAGE, interface(1,),);
+		fw.install_protocol(handle(8,), SERIAL, interface(2,),);
+		let text = TextOutputProtocol::GUID;
+		fw.install_protocol(handle(8,), text, interface(3,),);
+
+		let lines = dump_handles().unwrap();
+		assert_eq!(lines.len(), 2);
+		assert!(lines[0].ends_with("[EFI_LOADED_IMAGE_PROTOCOL]"), "{lines:?}");
+		assert!(lines[1].contains("EFI_SERIAL_IO_PROTOCOL"), "{lines:?}");
+		assert_eq!(fw.live_pools(), 0);
+		// neither handle has a device path to keep open
+		assert_eq!(fw.calls_to(Service::OpenProtocol,), 2);
+		assert!(fw.open_protocols().is_empty());
+	}
+}
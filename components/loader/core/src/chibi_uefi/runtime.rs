@@ -0,0 +1,312 @@
+//! # Runtime Services Virtual Mapping
+//!
+//! UEFI runtime services keep working after `ExitBootServices` only if the
+//! loader tells firmware where runtime regions will live in the kernel's
+//! address space via `SetVirtualAddressMap`. This module computes that layout
+//! and translates the final memory map into [`MemoryRegion`]s for
+//! [`BootInfo`](oso_no_std_shared::bridge::boot_info::BootInfo).
+//!
+//! Nothing here allocates after boot services are exited. Buffers are reserved
+//! in advance and only filled afterwards.
+//!
+//! Which services stay callable after `ExitBootServices` is read by
+//! [`capabilities`] beforehand and handed to the kernel as [`RuntimeCaps`].
+
+use super::table::runtime_services;
+use super::table::system_table;
+use crate::Rslt;
+use crate::raw::types::memory::MemoryAttribute;
+use crate::raw::types::memory::MemoryDescriptor;
+use crate::raw::types::memory::MemoryMapOwned;
+use crate::raw::types::memory::MemoryType;
+use crate::raw::types::memory::PAGE_SIZE;
+use alloc::vec::Vec;
+use core::ops::Range;
+use oso_error::loader::UefiError;
+use oso_no_std_shared::bridge::boot_info::MemoryRegion;
+use oso_no_std_shared::bridge::boot_info::MemoryRegionKind;
+use oso_no_std_shared::bridge::boot_info::RuntimeCaps;
+use oso_no_std_shared::units::PhysAddr;
+
+/// Strategy to assign virtual addresses to runtime regions
+#[derive(Clone, Copy, Debug, PartialEq, Eq,)]
+pub enum VirtualLayout {
+	/// Virtual address equals physical address
+	///
+	/// The kernel currently runs with the MMU disabled, so this is the only
+	/// layout usable right after handoff.
+	Identity,
+	/// Runtime regions are packed contiguously from `base` in ascending order
+	/// of their physical addresses
+	Packed { base: u64, },
+}
+
+impl VirtualLayout {
+	/// Writes `virtual_start` of every runtime region in `memory_map`
+	///
+	/// Non runtime regions are left untouched as firmware ignores them.
+	pub fn assign(&self, memory_map: &mut MemoryMapOwned,) {
+		let base = match self {
+			Self::Identity => {
+				for i in 0..memory_map.len {
+					let desc = memory_map.entry_mut(i,).unwrap();
+					if is_runtime(desc,) {
+						desc.virtual_start = desc.physical_start;
+					}
+				}
+				return;
+			},
+			Self::Packed { base, } => *base,
+		};
+
+		// selection in ascending physical order without allocating: memory maps
+		// have at most a few hundred entries
+		let mut next_virt = base;
+		let mut last_phys = None;
+		loop {
+			let next = (0..memory_map.len)
+				.filter_map(|i| Some((i, memory_map.entry(i,)?,),),)
+				.filter(|(_, desc,)| is_runtime(desc,),)
+				.filter(|(_, desc,)| {
+					last_phys.is_none_or(|last| desc.physical_start > last,)
+				},)
+				.min_by_key(|(_, desc,)| desc.physical_start,)
+				.map(|(i, _,)| i,);
+			let Some(i,) = next else {
+				break;
+			};
+
+			let desc = memory_map.entry_mut(i,).unwrap();
+			desc.virtual_start = next_virt;
+			next_virt += desc.page_count * PAGE_SIZE as u64;
+			last_phys = Some(desc.physical_start,);
+		}
+	}
+}
+
+/// Runtime services firmware supports after `ExitBootServices`
+///
+/// Read from `EFI_RT_PROPERTIES_TABLE`. Firmware without the table supports
+/// every service. `GetTime` is also tried once, as firmware without a real
+/// time clock may list it and fail every call. The result has no
+/// [`RuntimeCaps::VIRTUAL_MODE`], which is only known after
+/// `SetVirtualAddressMap`.
+///
+/// Must be called before exiting boot services, as the table may live in
+/// boot services memory.
+pub fn capabilities() -> Rslt<RuntimeCaps, UefiError,> {
+	let table = unsafe { system_table().as_ref() }.rt_properties_table()?;
+	let mut supported = match table {
+		Some(table,) => unsafe { table.as_ref() }.runtime_services_supported,
+		None => RuntimeCaps::ALL,
+	};
+	if supported & RuntimeCaps::GET_TIME != 0
+		&& runtime_services().time().is_err()
+	{
+		supported &= !RuntimeCaps::GET_TIME;
+	}
+	Ok(RuntimeCaps { supported, flags: 0, },)
+}
+
+fn is_runtime(desc: &MemoryDescriptor,) -> bool {
+	desc.attribute.0 & MemoryAttribute::EFI_MEMORY_RUNTIME != 0
+}
+
+/// Copies entries of `EFI_MEMORY_ATTRIBUTES_TABLE`
+///
+/// The table may live in boot services memory, so it has to be copied before
+/// exiting boot services. Returns an empty vector if firmware does not publish
+/// the table.
+pub fn memory_attributes() -> Rslt<Vec<MemoryDescriptor,>, UefiError,> {
+	let Some(table,) =
+		unsafe { system_table().as_ref() }.memory_attributes_table()?
+	else {
+		return Ok(Vec::new(),);
+	};
+	let table = unsafe { table.as_ref() };
+
+	let entries = (0..table.number_of_entries as usize)
+		.filter_map(|i| unsafe { table.entry(i,) }.copied(),)
+		.collect();
+	Ok(entries,)
+}
+
+/// Upper bound of regions [`describe_memory`] produces for a memory map with
+/// `map_entries` entries
+pub fn region_capacity(
+	map_entries: usize,
+	attributes: &[MemoryDescriptor],
+) -> usize {
+	// splitting out the loader image and the framebuffer adds at most two
+	// regions each, and the framebuffer one if it is not in the map
+	map_entries + attributes.len() + 5
+}
+
+/// Translates `memory_map` into [`MemoryRegion`]s
+///
+/// Runtime regions covered by the memory attributes table are split into the
+/// table's entries so the kernel can map runtime code read-only and runtime
+/// data non-executable. Virtual addresses of split entries keep their offset
+/// inside the original region.
+///
+/// The part of loader regions occupied by `loader_image` is reported as
+/// [`MemoryRegionKind::Reclaimable`], since nothing in the loader image is
+/// referenced after handoff. The pages of `framebuffer` are reported as
+/// [`MemoryRegionKind::Framebuffer`], with a region of their own if firmware
+/// does not list them.
+///
+/// Regions are pushed into `regions` without growing it. Entries which do not
+/// fit into the reserved capacity are dropped.
+///
+/// Gaps between descriptors are not reported. Firmware leaves out of the
+/// memory map what the kernel may not use as memory: unpopulated address
+/// space, and device memory which firmware does not access itself. The
+/// kernel finds devices through the device tree instead, and treats any
+/// address outside of every region as absent, so a region for a gap would
+/// tell it nothing.
+pub fn describe_memory(
+	memory_map: &MemoryMapOwned,
+	attributes: &[MemoryDescriptor],
+	loader_image: Range<u64,>,
+	framebuffer: Option<Range<u64,>,>,
+	regions: &mut Vec<MemoryRegion,>,
+) {
+	let mut push = |region: MemoryRegion| {
+		if regions.len() < regions.capacity() {
+			regions.push(region,);
+		}
+	};
+
+	let framebuffer_pages = framebuffer.clone().unwrap_or(0..0,);
+	for desc in memory_map.entries() {
+		let region = region_of(desc,);
+		if !is_runtime(desc,) {
+			let image_parts = if region.kind == MemoryRegionKind::Loader {
+				let reclaimable = MemoryRegionKind::Reclaimable;
+				split_out(region, &loader_image, reclaimable,)
+			} else {
+				[Some(region,), None, None,]
+			};
+			image_parts
+				.into_iter()
+				.flatten()
+				.flat_map(|part| {
+					let kind = MemoryRegionKind::Framebuffer;
+					split_out(part, &framebuffer_pages, kind,)
+				},)
+				.flatten()
+				.for_each(&mut push,);
+			continue;
+		}
+
+		let mut split = attributes
+			.iter()
+			.filter(|attr| {
+				region.contains(attr.physical_start,)
+					&& attr.physical_start + attr.page_count * PAGE_SIZE as u64
+						<= region.phys_end()
+			},)
+			.peekable();
+		if split.peek().is_none() {
+			push(region,);
+			continue;
+		}
+
+		for attr in split {
+			let offset = attr.physical_start - region.read_phys_start();
+			push(MemoryRegion::new(
+				attr.physical_start,
+				region.read_virt_start() + offset,
+				attr.page_count,
+				region_kind(attr.memory_type,),
+				region.read_attribute() | attr.attribute.0,
+			),);
+		}
+	}
+
+	let listed = |region: &MemoryRegion| {
+		region.kind == MemoryRegionKind::Framebuffer
+	};
+	if let Some(framebuffer,) = framebuffer
+		&& !regions.iter().any(listed,)
+		&& regions.len() < regions.capacity()
+		&& let Some(span,) = page_span(&framebuffer,)
+	{
+		let start = span.start.as_u64();
+		let pages = (span.end - span.start) / PAGE_SIZE as u64;
+		let kind = MemoryRegionKind::Framebuffer;
+		regions.push(MemoryRegion::new(start, start, pages, kind, 0,),);
+	}
+}
+
+/// splits `region` into the parts before, inside and after the pages of
+/// `range`. the inside part becomes `kind`
+fn split_out(
+	region: MemoryRegion,
+	range: &Range<u64,>,
+	kind: MemoryRegionKind,
+) -> [Option<MemoryRegion,>; 3] {
+	let Some(span,) = page_span(range,) else {
+		return [Some(region,), None, None,];
+	};
+	let page = PAGE_SIZE as u64;
+	let phys_start = region.read_phys_start();
+	let start = phys_start.max(span.start.as_u64(),);
+	let end = region.phys_end().min(span.end.as_u64(),);
+	if start >= end {
+		return [Some(region,), None, None,];
+	}
+
+	let part = |from: u64, to: u64, kind: MemoryRegionKind| {
+		let virt_start = region.read_virt_start() + (from - phys_start);
+		let pages = (to - from) / page;
+		let attribute = region.read_attribute();
+		let part = MemoryRegion::new(from, virt_start, pages, kind, attribute,);
+		(from < to).then_some(part,)
+	};
+	[
+		part(phys_start, start, region.kind,),
+		part(start, end, kind,),
+		part(end, region.phys_end(), region.kind,),
+	]
+}
+
+/// whole pages covering `range`
+fn page_span(range: &Range<u64,>,) -> Option<Range<PhysAddr,>,> {
+	PhysAddr::page_span(range.start.into(), range.end.into(),)
+}
+
+fn region_of(desc: &MemoryDescriptor,) -> MemoryRegion {
+	let virt_start = if is_runtime(desc,) && desc.virtual_start != 0 {
+		desc.virtual_start
+	} else {
+		desc.physical_start
+	};
+	MemoryRegion::new(
+		desc.physical_start,
+		virt_start,
+		desc.page_count,
+		region_kind(desc.memory_type,),
+		desc.attribute.0,
+	)
+}
+
+fn region_kind(memory_type: MemoryType,) -> MemoryRegionKind {
+	match memory_type {
+		MemoryType::CONVENTIONAL => MemoryRegionKind::Usable,
+		MemoryType::BOOT_SERVICES_CODE | MemoryType::BOOT_SERVICES_DATA => {
+			MemoryRegionKind::Reclaimable
+		},
+		MemoryType::LOADER_CODE | MemoryType::LOADER_DATA => {
+			MemoryRegionKind::Loader
+		},
+		MemoryType::RUNTIME_SERVICES_CODE => MemoryRegionKind::RuntimeCode,
+		MemoryType::RUNTIME_SERVICES_DATA => MemoryRegionKind::RuntimeData,
+		MemoryType::ACPI_RECLAIM => MemoryRegionKind::AcpiReclaim,
+		MemoryType::ACPI_NON_VOLATILE => MemoryRegionKind::AcpiNvs,
+		MemoryType::MMIO | MemoryType::MMIO_PORT_SPACE => {
+			MemoryRegionKind::Mmio
+		},
+		_ => MemoryRegionKind::Reserved,
+	}
+}
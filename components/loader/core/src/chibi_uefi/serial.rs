@@ -0,0 +1,255 @@
+//! # Buffered Serial Logging
+//!
+//! Output of [`info!`](crate::info) and [`debug!`](crate::debug) is copied to
+//! a buffer of [`TX_BUFFER_SIZE`] bytes and written to the serial port by a
+//! periodic timer event, so verbose logging does not wait for the UART. While
+//! the device uses hardware flow control and the receiver clears CTS, the
+//! bytes stay in the buffer.
+//!
+//! The timer stops with boot services, so [`finish`] writes the rest
+//! synchronously before exiting them. Without a timer, every line is written
+//! synchronously.
+
+use super::console::Verbosity;
+use super::table::boot_services;
+use crate::raw::protocol::serial::SerialIoProtocol;
+use crate::raw::types::Event;
+use crate::raw::types::Tpl;
+use crate::raw::types::event::EventType;
+use crate::raw::types::serial::ControlBits;
+use crate::raw::types::time::TimerDelay;
+use core::cell::UnsafeCell;
+use core::ffi::c_void;
+use core::fmt::Write;
+use core::ptr;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicPtr;
+use core::sync::atomic::AtomicU8;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+use oso_error::Rslt;
+use oso_error::loader::UefiError;
+
+/// size of the buffer of bytes waiting for the device
+pub const TX_BUFFER_SIZE: usize = 16 * 1024;
+/// interval of the timer flushing the buffer, in units of 100 nanoseconds
+const TICK: u64 = 10 * 10_000;
+/// how long [`finish`] and a full buffer wait for the receiver in total
+const BLOCKING_TIMEOUT_US: usize = 1_000_000;
+const RETRY_INTERVAL_US: usize = 1_000;
+
+static SERIAL: AtomicPtr<SerialIoProtocol,> = AtomicPtr::new(ptr::null_mut(),);
+static TIMER: AtomicPtr<c_void,> = AtomicPtr::new(ptr::null_mut(),);
+static SERIAL_VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Quiet as u8,);
+/// bytes discarded because the receiver did not accept them in time
+static DROPPED: AtomicUsize = AtomicUsize::new(0,);
+/// set when the receiver did not accept anything for
+/// [`BLOCKING_TIMEOUT_US`], so that later writes do not wait again until it
+/// accepts bytes
+static STALLED: AtomicBool = AtomicBool::new(false,);
+static TX: Tx = Tx(UnsafeCell::new(Ring::new(),),);
+
+/// bytes waiting for the device
+///
+/// only accessed at [`Tpl::CALLBACK`], which the timer can not interrupt
+struct Tx(UnsafeCell<Ring,>,);
+
+unsafe impl Sync for Tx {}
+
+struct Ring {
+	buf:  [u8; TX_BUFFER_SIZE],
+	head: usize,
+	len:  usize,
+}
+
+impl Ring {
+	const fn new() -> Self {
+		Self { buf: [0; TX_BUFFER_SIZE], head: 0, len: 0, }
+	}
+
+	fn is_full(&self,) -> bool {
+		self.len == TX_BUFFER_SIZE
+	}
+
+	fn push(&mut self, byte: u8,) {
+		let tail = (self.head + self.len) % TX_BUFFER_SIZE;
+		self.buf[tail] = byte;
+		self.len += 1;
+	}
+
+	/// oldest bytes which are contiguous in the buffer
+	fn front(&self,) -> &[u8] {
+		let end = (self.head + self.len).min(TX_BUFFER_SIZE,);
+		&self.buf[self.head..end]
+	}
+
+	fn consume(&mut self, n: usize,) {
+		self.head = (self.head + n) % TX_BUFFER_SIZE;
+		self.len -= n;
+	}
+}
+
+/// how [`drain`] treats a receiver which is not ready
+#[derive(Clone, Copy, PartialEq, Eq,)]
+enum Wait {
+	/// leave the rest for the next tick
+	No,
+	/// retry for at most [`BLOCKING_TIMEOUT_US`]
+	Bounded,
+}
+
+/// Starts logging to the first serial port at `verbosity`
+///
+/// Nothing is logged to the serial port at [`Verbosity::Quiet`].
+///
+/// # Errors
+///
+/// Returns an error if there is no serial port. If only the timer can not be
+/// created, lines are written synchronously instead.
+pub fn start(verbosity: Verbosity,) -> Rslt<(), UefiError,> {
+	SERIAL_VERBOSITY.store(verbosity as u8, Ordering::Relaxed,);
+	if verbosity == Verbosity::Quiet {
+		return Ok((),);
+	}
+
+	let bs = boot_services();
+	let serial = bs.open_protocol_with::<SerialIoProtocol>()?;
+	SERIAL.store(serial.interface().as_ptr(), Ordering::Release,);
+	// the port is used until boot services are exited
+	core::mem::forget(serial,);
+
+	let ty = EventType(EventType::TIMER | EventType::NOTIFY_SIGNAL,);
+	let Ok(timer,) = (unsafe {
+		bs.create_event(ty, Tpl::CALLBACK, Some(on_tick,), ptr::null_mut(),)
+	}) else {
+		return Ok((),);
+	};
+	if unsafe { bs.set_timer(timer, TimerDelay::PERIODIC, TICK,) }.is_err() {
+		let _ = unsafe { bs.close_event(timer,) };
+		return Ok((),);
+	}
+	TIMER.store(timer, Ordering::Release,);
+	Ok((),)
+}
+
+pub fn verbosity() -> Verbosity {
+	Verbosity::from_raw(SERIAL_VERBOSITY.load(Ordering::Relaxed,),)
+}
+
+/// Queues `args` and a newline if `level` is enabled for the serial port
+pub fn log(level: Verbosity, args: core::fmt::Arguments,) {
+	if verbosity() < level || SERIAL.load(Ordering::Acquire,).is_null() {
+		return;
+	}
+
+	boot_services().with_tpl(Tpl::CALLBACK, || {
+		let tx = unsafe { &mut *TX.0.get() };
+		let _ = Queue(tx,).write_fmt(format_args!("{args}\n"),);
+		if TIMER.load(Ordering::Acquire,).is_null() {
+			drain(tx, Wait::Bounded,);
+		}
+	},);
+}
+
+/// Stops the timer and writes the queued bytes synchronously
+///
+/// Called before exiting boot services. Later lines are not written to the
+/// serial port.
+pub fn finish() {
+	let bs = boot_services();
+	let timer = TIMER.swap(ptr::null_mut(), Ordering::AcqRel,);
+	if !timer.is_null() {
+		let _ = unsafe { bs.set_timer(timer, TimerDelay::CANCEL, 0,) };
+		let _ = unsafe { bs.close_event(timer,) };
+	}
+	if SERIAL.load(Ordering::Acquire,).is_null() {
+		return;
+	}
+
+	bs.with_tpl(Tpl::CALLBACK, || {
+		let tx = unsafe { &mut *TX.0.get() };
+		let dropped = DROPPED.swap(0, Ordering::Relaxed,);
+		if dropped != 0 {
+			let _ = writeln!(Queue(tx,), "[{dropped} bytes of log dropped]");
+		}
+		drain(tx, Wait::Bounded,);
+	},);
+	SERIAL.store(ptr::null_mut(), Ordering::Release,);
+}
+
+/// notify function of the timer. runs at [`Tpl::CALLBACK`]
+unsafe extern "efiapi" fn on_tick(_event: Event, _context: *mut c_void,) {
+	let tx = unsafe { &mut *TX.0.get() };
+	drain(tx, Wait::No,);
+}
+
+/// writes queued bytes while the receiver accepts them
+fn drain(tx: &mut Ring, wait: Wait,) {
+	let Some(serial,) = (unsafe { SERIAL.load(Ordering::Acquire,).as_mut() })
+	else {
+		return;
+	};
+
+	let mut waited = 0;
+	while tx.len != 0 {
+		let written = if clear_to_send(serial,) {
+			serial.write(tx.front(),).unwrap_or(0,)
+		} else {
+			0
+		};
+		tx.consume(written,);
+		if written != 0 {
+			STALLED.store(false, Ordering::Relaxed,);
+			continue;
+		}
+
+		if wait == Wait::No || STALLED.load(Ordering::Relaxed,) {
+			return;
+		}
+		if waited >= BLOCKING_TIMEOUT_US {
+			STALLED.store(true, Ordering::Relaxed,);
+			return;
+		}
+		let _ = boot_services().stall(RETRY_INTERVAL_US,);
+		waited += RETRY_INTERVAL_US;
+	}
+}
+
+/// `false` while hardware flow control holds the transmitter
+fn clear_to_send(serial: &mut SerialIoProtocol,) -> bool {
+	let Ok(control,) = serial.control() else {
+		return true;
+	};
+	!control.contains(ControlBits::HARDWARE_FLOW_CONTROL_ENABLE,)
+		|| control.contains(ControlBits::CLEAR_TO_SEND,)
+}
+
+/// formats into the buffer, translating `\n` to `\r\n` for terminals
+struct Queue<'a,>(&'a mut Ring,);
+
+impl Write for Queue<'_,> {
+	fn write_str(&mut self, s: &str,) -> core::fmt::Result {
+		for &byte in s.as_bytes() {
+			if byte == b'\n' {
+				self.push(b'\r',);
+			}
+			self.push(byte,);
+		}
+		Ok((),)
+	}
+}
+
+impl Queue<'_,> {
+	/// makes room by writing synchronously if the buffer is full. the byte is
+	/// dropped if the receiver does not accept any in time
+	fn push(&mut self, byte: u8,) {
+		if self.0.is_full() {
+			drain(self.0, Wait::Bounded,);
+		}
+		if self.0.is_full() {
+			DROPPED.fetch_add(1, Ordering::Relaxed,);
+			return;
+		}
+		self.0.push(byte,);
+	}
+}
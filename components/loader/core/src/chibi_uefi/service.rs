@@ -1,5 +1,13 @@
+use super::allocator;
+use super::serial;
 use super::table::boot_services;
+use crate::debug;
+use crate::raw::types::memory::MemoryMapOwned;
 
-pub fn exit_boot_services() {
-	boot_services().exit_boot_services();
+/// logs the work of the heap and writes the rest of the serial log, then
+/// exits boot services
+pub fn exit_boot_services() -> MemoryMapOwned {
+	debug!("heap: {}", allocator::stats());
+	serial::finish();
+	boot_services().exit_boot_services()
 }
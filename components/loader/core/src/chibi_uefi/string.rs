@@ -0,0 +1,221 @@
+//! UCS-2 strings used by UEFI
+//!
+//! Firmware takes and returns null terminated UCS-2 strings. [`Ucs2Str`]
+//! borrows one, whether it lives in firmware owned memory or in a
+//! [`Ucs2String`] built from a `&str`.
+//!
+//! Both directions have a strict and a lossy mode. Strict conversion fails on
+//! anything UCS-2 cannot hold: nulls inside the string, surrogates and
+//! characters outside the basic multilingual plane. Lossy conversion replaces
+//! them with `U+FFFD`.
+//!
+//! ```rust,ignore
+//! let path = Ucs2String::new("\\EFI\\oso\\loader.cfg",)?;
+//! root.open(&path, OpenMode::READ, FileAttributes::empty(),)?;
+//!
+//! let vendor = unsafe { Ucs2Str::from_ptr(system_table.firmware_vendor,) };
+//! println!("firmware: {vendor}");
+//! ```
+
+use crate::raw::types::Char16;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Deref;
+use oso_error::Rslt;
+use oso_error::loader::Ucs2Error;
+use oso_error::oso_err;
+
+/// Borrowed null terminated UCS-2 string, like `CStr16` of other UEFI crates
+#[derive(PartialEq, Eq,)]
+#[repr(transparent)]
+pub struct Ucs2Str([Char16],);
+
+impl Ucs2Str {
+	/// Borrows the string at `ptr` up to its null
+	///
+	/// The content is not validated. Firmware strings may hold surrogates,
+	/// which [`chars`](Self::chars) replaces and
+	/// [`to_string_strict`](Self::to_string_strict) rejects.
+	///
+	/// # Safety
+	///
+	/// `ptr` must point to a null terminated string which stays valid and
+	/// unchanged for `'a`
+	pub unsafe fn from_ptr<'a,>(ptr: *const Char16,) -> &'a Self {
+		let mut len = 0;
+		while unsafe { *ptr.add(len,) } != 0 {
+			len += 1;
+		}
+		let units = unsafe { core::slice::from_raw_parts(ptr, len + 1,) };
+		unsafe { Self::from_units_with_nul_unchecked(units,) }
+	}
+
+	/// Borrows `units`, which must end with its only null and must not have
+	/// surrogates
+	pub fn from_units_with_nul(units: &[Char16],) -> Rslt<&Self, Ucs2Error,> {
+		let Some((0, content,),) = units.split_last() else {
+			return Err(oso_err!(Ucs2Error::MissingNul),);
+		};
+		for (i, unit,) in content.iter().enumerate() {
+			match unit {
+				0 => return Err(oso_err!(Ucs2Error::InteriorNul(i)),),
+				0xd800..=0xdfff => {
+					return Err(oso_err!(Ucs2Error::Surrogate(i)),);
+				},
+				_ => (),
+			}
+		}
+		Ok(unsafe { Self::from_units_with_nul_unchecked(units,) },)
+	}
+
+	/// # Safety
+	///
+	/// `units` must end with its only null
+	pub unsafe fn from_units_with_nul_unchecked(units: &[Char16],) -> &Self {
+		// SAFETY: `Ucs2Str` is a transparent wrapper of `[Char16]`
+		unsafe { &*(units as *const [Char16] as *const Self) }
+	}
+
+	/// Pointer to pass to firmware
+	pub fn as_ptr(&self,) -> *const Char16 {
+		self.0.as_ptr()
+	}
+
+	/// Code units without the null
+	pub fn units(&self,) -> &[Char16] {
+		&self.0[..self.0.len() - 1]
+	}
+
+	/// Code units with the null
+	pub fn units_with_nul(&self,) -> &[Char16] {
+		&self.0
+	}
+
+	pub fn len(&self,) -> usize {
+		self.0.len() - 1
+	}
+
+	pub fn is_empty(&self,) -> bool {
+		self.len() == 0
+	}
+
+	/// Characters of the string. Surrogates are replaced with `U+FFFD`
+	pub fn chars(&self,) -> impl Iterator<Item = char,> + '_ {
+		self.units().iter().map(|unit| {
+			char::from_u32(*unit as u32,)
+				.unwrap_or(char::REPLACEMENT_CHARACTER,)
+		},)
+	}
+
+	/// Converts to `String`, failing on surrogates
+	pub fn to_string_strict(&self,) -> Rslt<String, Ucs2Error,> {
+		let mut s = String::with_capacity(self.len(),);
+		for (i, unit,) in self.units().iter().enumerate() {
+			let Some(c,) = char::from_u32(*unit as u32,) else {
+				return Err(oso_err!(Ucs2Error::Surrogate(i)),);
+			};
+			s.push(c,);
+		}
+		Ok(s,)
+	}
+
+	/// Converts to `String`, replacing surrogates with `U+FFFD`
+	pub fn to_string_lossy(&self,) -> String {
+		self.chars().collect()
+	}
+}
+
+impl fmt::Display for Ucs2Str {
+	/// Writes the string lossily as [`chars`](Self::chars)
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		self.chars().try_for_each(|c| fmt::Write::write_char(f, c,),)
+	}
+}
+
+impl fmt::Debug for Ucs2Str {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		write!(f, "u\"{}\"", self.to_string_lossy().escape_debug())
+	}
+}
+
+impl AsRef<Ucs2Str,> for Ucs2Str {
+	fn as_ref(&self,) -> &Ucs2Str {
+		self
+	}
+}
+
+/// Owned null terminated UCS-2 string
+#[derive(Clone, PartialEq, Eq,)]
+pub struct Ucs2String(Vec<Char16,>,);
+
+impl Ucs2String {
+	/// Encodes `s`, failing on nulls and characters outside of the basic
+	/// multilingual plane. Offsets in errors are byte offsets of `s`
+	pub fn new(s: &str,) -> Rslt<Self, Ucs2Error,> {
+		let mut units = Vec::with_capacity(s.len() + 1,);
+		for (i, c,) in s.char_indices() {
+			match Self::unit_of(c,) {
+				Some(0,) => return Err(oso_err!(Ucs2Error::InteriorNul(i)),),
+				Some(unit,) => units.push(unit,),
+				None => return Err(oso_err!(Ucs2Error::Unrepresentable(i)),),
+			}
+		}
+		units.push(0,);
+		Ok(Self(units,),)
+	}
+
+	/// Encodes `s`, replacing nulls and characters outside of the basic
+	/// multilingual plane with `U+FFFD`
+	pub fn from_str_lossy(s: &str,) -> Self {
+		let replacement = char::REPLACEMENT_CHARACTER as Char16;
+		let mut units: Vec<Char16,> = s
+			.chars()
+			.map(|c| match Self::unit_of(c,) {
+				Some(0,) | None => replacement,
+				Some(unit,) => unit,
+			},)
+			.collect();
+		units.push(0,);
+		Self(units,)
+	}
+
+	fn unit_of(c: char,) -> Option<Char16,> {
+		Char16::try_from(c as u32,).ok()
+	}
+}
+
+impl Deref for Ucs2String {
+	type Target = Ucs2Str;
+
+	fn deref(&self,) -> &Self::Target {
+		// SAFETY: constructors push the only null at the end
+		unsafe { Ucs2Str::from_units_with_nul_unchecked(&self.0,) }
+	}
+}
+
+impl AsRef<Ucs2Str,> for Ucs2String {
+	fn as_ref(&self,) -> &Ucs2Str {
+		self
+	}
+}
+
+impl TryFrom<&str,> for Ucs2String {
+	type Error = oso_error::OsoError<Ucs2Error,>;
+
+	fn try_from(s: &str,) -> Result<Self, Self::Error,> {
+		Self::new(s,)
+	}
+}
+
+impl fmt::Display for Ucs2String {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		fmt::Display::fmt(&**self, f,)
+	}
+}
+
+impl fmt::Debug for Ucs2String {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		fmt::Debug::fmt(&**self, f,)
+	}
+}
@@ -1,13 +1,50 @@
+//! # System Table
+//!
+//! Access to the system table passed to the loader, and to the services it
+//! points to.
+//!
+//! The boot services are gone once `ExitBootServices` succeeds: firmware may
+//! reuse their memory, so a later call is undefined behavior rather than an
+//! error. [`boot_phase`] tracks the transition, and [`boot_services`] panics
+//! at the late caller instead of calling into firmware.
+//!
+//! ```rust,ignore
+//! let map = boot_services().exit_boot_services();
+//! assert_eq!(boot_phase(), BootPhase::Runtime);
+//! assert!(try_boot_services().is_none());
+//! runtime_services().set_virtual_address_map(&mut map,)?;
+//! ```
+
 use crate::raw::service::BootServices;
 use crate::raw::service::RuntimeServices;
 use crate::raw::table::SystemTable;
 use core::ptr::NonNull;
 use core::sync::atomic::AtomicPtr;
+use core::sync::atomic::AtomicU8;
 use core::sync::atomic::Ordering;
 
 static SYSTEM_TABLE: AtomicPtr<SystemTable,> =
 	AtomicPtr::new(core::ptr::null_mut(),);
 
+#[cfg(not(test))]
+static PHASE: AtomicU8 = AtomicU8::new(BootPhase::Boot as u8,);
+
+// tests of the mock firmware run in parallel, each thread with its own phase
+#[cfg(test)]
+std::thread_local! {
+	static PHASE: AtomicU8 = const { AtomicU8::new(BootPhase::Boot as u8,) };
+}
+
+/// Phase of the boot process, which decides the services available
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+#[repr(u8)]
+pub enum BootPhase {
+	/// Boot and runtime services are available
+	Boot,
+	/// `ExitBootServices` succeeded. Only runtime services are available
+	Runtime,
+}
+
 unsafe fn set_system_table(ptr: *const SystemTable,) {
 	SYSTEM_TABLE.store(ptr.cast_mut(), Ordering::Release,);
 }
@@ -26,10 +63,51 @@ pub fn system_table() -> NonNull<SystemTable,> {
 	NonNull::new(p,).expect("set_system_table has not been called",)
 }
 
+/// Current phase of the boot process
+pub fn boot_phase() -> BootPhase {
+	let raw = with_phase(|phase| phase.load(Ordering::Acquire,),);
+	if raw == BootPhase::Runtime as u8 {
+		BootPhase::Runtime
+	} else {
+		BootPhase::Boot
+	}
+}
+
+/// Moves to `phase`. Called with [`BootPhase::Runtime`] once
+/// `ExitBootServices` succeeds
+pub(crate) fn set_boot_phase(phase: BootPhase,) {
+	with_phase(|p| p.store(phase as u8, Ordering::Release,),);
+}
+
+#[cfg(not(test))]
+fn with_phase<R,>(f: impl FnOnce(&AtomicU8,) -> R,) -> R {
+	f(&PHASE,)
+}
+
+#[cfg(test)]
+fn with_phase<R,>(f: impl FnOnce(&AtomicU8,) -> R,) -> R {
+	PHASE.with(f,)
+}
+
+/// Boot services, or `None` after exiting them
+pub fn try_boot_services<'a,>() -> Option<&'a BootServices,> {
+	if boot_phase() == BootPhase::Runtime {
+		return None;
+	}
+	let syst = system_table();
+	unsafe { syst.as_ref().boot_services.as_ref() }
+}
+
 /// # Panics
 ///
-/// if boot_services is null, then panics
+/// - if boot services were exited. The panic points to the caller, which
+///   would otherwise call into memory firmware may have reused
+/// - if boot_services is null
+#[track_caller]
 pub fn boot_services<'a,>() -> &'a BootServices {
+	if boot_phase() == BootPhase::Runtime {
+		panic!("boot services are used after exit_boot_services");
+	}
 	let syst = system_table();
 	unsafe { syst.as_ref().boot_services.as_ref() }.unwrap()
 }
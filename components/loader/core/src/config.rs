@@ -0,0 +1,243 @@
+//! # Loader Configuration Module
+//!
+//! This module reads [`CONFIG_PATH`] from the boot volume. The file is a
+//! subset of TOML parsed by [`oso_no_std_shared::parser::config`], so host
+//! tools can read and write it with a std TOML crate.
+//!
+//! ## Format
+//!
+//! ```toml
+//! # seconds to wait before booting. 0 boots immediately
+//! timeout = 3
+//!
+//! [kernel]
+//! path = '\oso_kernel.elf'
+//! cmdline = "console=ttyAMA0"
+//! # boot even if the kernel does not accept this loader version
+//! check_version = true
+//! # cpio archive handed to the kernel, which mounts it at /
+//! initrd = '\initrd.cpio'
+//!
+//! [graphics]
+//! width = 1280
+//! height = 720
+//!
+//! [log]
+//! # quiet, info or debug
+//! level = "info"
+//! # level of the buffered log on the serial port. quiet disables it
+//! serial = "quiet"
+//!
+//! [panic]
+//! # halt or reboot after a panic. blink and exit halt in the loader
+//! policy = "reboot"
+//!
+//! # device tree overlays, applied in this order. keys only name them
+//! [overlays]
+//! uart1 = '\EFI\oso\overlays\uart1.dtbo'
+//! ```
+//!
+//! Every key is optional. A missing file is treated as an empty one, unknown
+//! keys are ignored. Paths may be separated by `/` or `\`.
+
+use crate::chibi_uefi::console::Verbosity;
+use crate::error_screen::AtStage;
+use crate::load::KERNEL_PATH;
+use crate::load::open_file;
+use alloc::string::String;
+use alloc::vec::Vec;
+use oso_error::Rslt;
+use oso_error::loader::BootError;
+use oso_error::loader::BootStage;
+use oso_error::loader::UefiError;
+use oso_error::oso_err;
+use oso_error::parser::ConfigError;
+use oso_no_std_shared::bridge::panic_policy::PanicPolicy;
+use oso_no_std_shared::parser::config::Config;
+use oso_no_std_shared::parser::config::Entry;
+use oso_no_std_shared::path::PathN;
+use oso_no_std_shared::text::utf8;
+
+/// Path of the loader configuration on the boot volume
+pub const CONFIG_PATH: &str = "\\EFI\\oso\\loader.cfg";
+
+/// Settings read from [`CONFIG_PATH`]
+///
+/// # Fields
+///
+/// * `kernel_path` - Path of the kernel on the boot volume
+/// * `cmdline` - Command line passed to the kernel
+/// * `check_version` - Whether to refuse a kernel whose version note does
+///   not match the loader
+/// * `initrd` - Path of the initial ramdisk on the boot volume, handed to
+///   the kernel as a boot module
+/// * `graphics_mode` - Resolution `(width, height)` to switch to. `None`
+///   keeps the mode chosen by firmware
+/// * `verbosity` - Amount of diagnostic output of the loader
+/// * `serial_verbosity` - Amount of diagnostic output logged to the serial
+///   port
+/// * `timeout` - Seconds to wait for a key press before booting
+/// * `panic_policy` - What the panic handler does after printing the panic
+/// * `overlays` - Paths of device tree overlays on the boot volume, in the
+///   order they are applied
+#[derive(Debug, Clone, PartialEq, Eq,)]
+pub struct LoaderConfig {
+	pub kernel_path:      String,
+	pub cmdline:          String,
+	pub check_version:    bool,
+	pub initrd:           Option<String,>,
+	pub graphics_mode:    Option<(usize, usize,),>,
+	pub verbosity:        Verbosity,
+	pub serial_verbosity: Verbosity,
+	pub timeout:          u64,
+	pub panic_policy:     PanicPolicy,
+	pub overlays:         Vec<String,>,
+}
+
+impl Default for LoaderConfig {
+	fn default() -> Self {
+		Self {
+			kernel_path:      KERNEL_PATH.into(),
+			cmdline:          String::new(),
+			check_version:    true,
+			initrd:           None,
+			graphics_mode:    None,
+			verbosity:        Verbosity::default(),
+			serial_verbosity: Verbosity::Quiet,
+			timeout:          0,
+			panic_policy:     PanicPolicy::Halt,
+			overlays:         Vec::new(),
+		}
+	}
+}
+
+impl LoaderConfig {
+	/// Reads and parses [`CONFIG_PATH`]
+	///
+	/// Returns the default configuration if the file does not exist.
+	pub fn load() -> Rslt<Self, BootError,> {
+		let mut file = match open_file(CONFIG_PATH,) {
+			Ok(file,) => file,
+			Err(e,) if is_not_found(&e.desc,) => return Ok(Self::default(),),
+			Err(e,) => return Err(e,).at(BootStage::Config,),
+		};
+		let bytes = unsafe { file.as_mut() }
+			.read_as_bytes()
+			.at(BootStage::Config,)?;
+		let src = utf8::from_bytes(&bytes,).map_err(|_| {
+			oso_err!(BootError {
+				stage: BootStage::Config,
+				cause: Some("configuration is not valid utf-8"),
+				..Default::default()
+			})
+		},)?;
+
+		Self::parse(src,).at(BootStage::Config,)
+	}
+
+	/// Parses configuration source
+	///
+	/// # Errors
+	///
+	/// Besides syntax errors, returns `ConfigError::TypeMismatch` if a known
+	/// key has a value of wrong type and `ConfigError::InvalidValue` if the
+	/// value is out of range.
+	pub fn parse(src: &str,) -> Rslt<Self, ConfigError,> {
+		let config = Config::parse(src,)?;
+		Self::from_config(&config,).map_err(|e| oso_err!(e),)
+	}
+
+	fn from_config(config: &Config,) -> Result<Self, ConfigError,> {
+		let mut loader_config = Self::default();
+
+		if let Some(entry,) = config.entry("", "timeout",) {
+			loader_config.timeout = unsigned(entry,)?;
+		}
+		if let Some(entry,) = config.entry("kernel", "path",) {
+			loader_config.kernel_path = file_path(entry,)?.as_str().into();
+		}
+		if let Some(entry,) = config.entry("kernel", "cmdline",) {
+			loader_config.cmdline = string(entry,)?.into();
+		}
+		if let Some(entry,) = config.entry("kernel", "check_version",) {
+			loader_config.check_version = boolean(entry,)?;
+		}
+		if let Some(entry,) = config.entry("kernel", "initrd",) {
+			loader_config.initrd = Some(file_path(entry,)?.as_str().into(),);
+		}
+
+		let width = config.entry("graphics", "width",);
+		let height = config.entry("graphics", "height",);
+		loader_config.graphics_mode = match (width, height,) {
+			(Some(width,), Some(height,),) => Some((
+				unsigned(width,)? as usize,
+				unsigned(height,)? as usize,
+			),),
+			// resolution is meaningless without both sides
+			(Some(entry,), None,) | (None, Some(entry,),) => {
+				return Err(ConfigError::InvalidValue(entry.line,),);
+			},
+			(None, None,) => None,
+		};
+
+		if let Some(entry,) = config.entry("log", "level",) {
+			loader_config.verbosity = verbosity(entry,)?;
+		}
+		if let Some(entry,) = config.entry("log", "serial",) {
+			loader_config.serial_verbosity = verbosity(entry,)?;
+		}
+		if let Some(entry,) = config.entry("panic", "policy",) {
+			loader_config.panic_policy = panic_policy(entry,)?;
+		}
+		for entry in config.section("overlays",) {
+			loader_config.overlays.push(file_path(entry,)?.as_str().into(),);
+		}
+
+		Ok(loader_config,)
+	}
+}
+
+fn string<'a,>(entry: Entry<'a,>,) -> Result<&'a str, ConfigError,> {
+	entry.value.as_str().ok_or(ConfigError::TypeMismatch(entry.line,),)
+}
+
+fn boolean(entry: Entry,) -> Result<bool, ConfigError,> {
+	entry.value.as_bool().ok_or(ConfigError::TypeMismatch(entry.line,),)
+}
+
+/// Path of a file, which must end in a file name
+fn file_path<'a,>(entry: Entry<'a,>,) -> Result<&'a PathN, ConfigError,> {
+	let path =
+		entry.value.as_path().ok_or(ConfigError::TypeMismatch(entry.line,),)?;
+	if path.file_name().is_none() {
+		return Err(ConfigError::InvalidValue(entry.line,),);
+	}
+	Ok(path,)
+}
+
+fn unsigned(entry: Entry,) -> Result<u64, ConfigError,> {
+	let value =
+		entry.value.as_int().ok_or(ConfigError::TypeMismatch(entry.line,),)?;
+	u64::try_from(value,).map_err(|_| ConfigError::InvalidValue(entry.line,),)
+}
+
+fn verbosity(entry: Entry,) -> Result<Verbosity, ConfigError,> {
+	match string(entry,)? {
+		"quiet" => Ok(Verbosity::Quiet,),
+		"info" => Ok(Verbosity::Info,),
+		"debug" => Ok(Verbosity::Debug,),
+		_ => Err(ConfigError::InvalidValue(entry.line,),),
+	}
+}
+
+fn panic_policy(entry: Entry,) -> Result<PanicPolicy, ConfigError,> {
+	PanicPolicy::parse(string(entry,)?,)
+		.ok_or(ConfigError::InvalidValue(entry.line,),)
+}
+
+fn is_not_found(desc: &Option<UefiError,>,) -> bool {
+	matches!(
+		desc,
+		Some(UefiError::ErrorStatus(s)) if s.starts_with("EFI_NOT_FOUND")
+	)
+}
@@ -57,6 +57,8 @@ use oso_error::OsoError;
 use oso_error::loader::EfiParseError;
 use oso_error::loader::EfiParseStage;
 use oso_error::oso_err;
+use oso_no_std_shared::text::utf8::TryFromBytes;
+use oso_no_std_shared::text::utf8::split_once_byte;
 use program_header::ProgramHeaderType;
 use section_header::SHT_GNU_VERDEF;
 use section_header::SHT_GNU_VERNEED;
@@ -70,6 +72,8 @@ use section_header::get_string_table;
 pub mod hash;
 /// Program header parsing and types
 pub mod program_header;
+/// Dynamic relocations applied to the loaded kernel
+pub mod relocation;
 /// Section header parsing and types
 pub mod section_header;
 
@@ -135,6 +139,7 @@ const ELF_ABI_VERSION_INDEX: usize = 8;
 ///
 /// ## Flags
 /// - `is_position_independent_executable`: Whether this is a PIE binary
+#[derive(Default,)]
 pub struct Elf {
 	pub header:                             ElfHeader,
 	pub program_headers:                    Vec<ProgramHeader,>,
@@ -1131,7 +1136,7 @@ impl StringTable {
 			Self::from_slice(&binary[offset..offset + len], delimiter,);
 		let mut i = 0;
 		while i < rslt.bytes.len() {
-			let s = rslt.delimitor.read_bytes(&binary[i..],)?;
+			let s = rslt.delimitor.read_bytes(&rslt.bytes[i..],)?;
 			let len = s.len();
 			rslt.strings.push((i, s,),);
 			i += len + 1;
@@ -1176,26 +1181,20 @@ impl StringContext {
 	fn read_bytes(&self, bytes: &[u8],) -> Rslt<String, EfiParseError,> {
 		let bytes = match self {
 			StringContext::Delimiter(delimiter,) => {
-				let mut i = 0;
-				while let a = &bytes[i..=i]
-					&& a != [*delimiter,]
-				{
-					i += 1;
-					if i >= bytes.len() {
-						return Err(oso_err!(
-							EfiParseError::DelimiterNotFound(*delimiter)
-						),);
-					}
-				}
-
-				&bytes[..i]
+				split_once_byte(bytes, *delimiter,)
+					.ok_or(oso_err!(EfiParseError::DelimiterNotFound(
+						*delimiter
+					)),)?
+					.0
 			},
 			StringContext::DelimiterUntil(..,) => todo!(),
 			StringContext::Length(l,) => &bytes[..*l],
 		};
 
-		let rslt = String::from_utf8_lossy(bytes,);
-		Ok(rslt.to_string(),)
+		let s = <&str>::try_from_bytes(bytes,).map_err(|e| {
+			oso_err!(EfiParseError::InvalidUtf8 { offset: e.offset })
+		},)?;
+		Ok(s.to_string(),)
 	}
 }
 
@@ -1786,7 +1785,13 @@ impl IntoIterator for &RelocationSection {
 	type Item = <RelocationIterator as Iterator>::Item;
 
 	fn into_iter(self,) -> Self::IntoIter {
-		todo!()
+		RelocationIterator {
+			bytes:   self.bytes.clone(),
+			offset:  0,
+			index:   0,
+			count:   self.count,
+			context: self.context.clone(),
+		}
 	}
 }
 
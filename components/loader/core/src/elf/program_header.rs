@@ -2,9 +2,7 @@ use crate::Rslt;
 use crate::elf::read_le_bytes;
 use alloc::format;
 use alloc::vec::Vec;
-use oso_error::OsoError;
 use oso_error::loader::EfiParseError;
-use oso_error::oso_err;
 
 #[derive(PartialEq, Eq,)]
 pub struct ProgramHeader {
@@ -41,7 +39,7 @@ impl ProgramHeader {
 			let memory_size = read_le_bytes(offset, binary,).unwrap();
 			let align = read_le_bytes(offset, binary,).unwrap();
 
-			let ty = ProgramHeaderType::try_from(ty,)?;
+			let ty = ProgramHeaderType::from(ty,);
 
 			let program_header = Self {
 				ty,
@@ -78,82 +76,102 @@ impl core::fmt::Debug for ProgramHeader {
 	}
 }
 
-#[repr(u32)]
-#[derive(PartialEq, Eq, Debug,)]
+/// type of a segment
+///
+/// linkers add segments such as `GNU_STACK` or `NOTE` which the loader has no
+/// use for. types without a variant are kept as [`Self::Other`] instead of
+/// failing the parse, since only `LOAD` segments are loaded
+#[derive(PartialEq, Eq, Debug, Clone, Copy,)]
 pub enum ProgramHeaderType {
 	/// ARM unwind segment
-	ArmExidx    = 0x7000_0001,
+	ArmExidx,
 	/// Dynamic linking information
-	Dynamic     = 2,
+	Dynamic,
 	/// GCC .eh_frame_hdr segment
-	GnuEhFrame  = 0x6474_e550,
+	GnuEhFrame,
 	/// GNU property notes for linker and run-time loaders
-	GnuProperty = 0x6474_e553,
+	GnuProperty,
 	/// Read-only after relocation
-	GnuRelro    = 0x6474_e552,
+	GnuRelro,
+	/// SFrame stack trace information
+	GnuSframe,
 	/// Indicates stack executability
-	GnuStack    = 0x6474_e551,
+	GnuStack,
 	/// End of OS-specific
-	Hios        = 0x6fff_ffff,
+	Hios,
 	/// End of processor-specific
-	Hiproc      = 0x7fff_ffff,
+	Hiproc,
 	/// Program interpreter
-	Interp      = 3,
+	Interp,
 	/// Loadable program segment
-	Load        = 1,
+	Load,
 	/// Start of OS-specific
-	Loos        = 0x6000_0000,
+	Loos,
 	/// Start of processor-specific
-	Loproc      = 0x7000_0000,
+	Loproc,
 	/// Sun Specific segment
-	Losunw      = 0x6fff_fffa,
+	Losunw,
 	/// Auxiliary information
-	Note        = 4,
+	Note,
 	/// Programg header table entry unused
-	Null        = 0,
+	Null,
 	/// Number of defined types
-	Num         = 8,
+	Num,
 	/// Entry for header table itself
-	Phdr        = 6,
+	Phdr,
 	/// Reserved
-	Shlib       = 5,
+	Shlib,
 	/// Stack segment
-	Sunwstack   = 0x6fff_fffb,
+	Sunwstack,
 	/// Thread-local storage segment
-	Tls         = 7,
+	Tls,
+	/// OS or processor specific type without a variant
+	Other(u32,),
 }
 
-impl TryFrom<u32,> for ProgramHeaderType {
-	type Error = OsoError<EfiParseError,>;
+/// values of the types with a variant
+const PROGRAM_HEADER_TYPES: [(u32, ProgramHeaderType,); 21] = [
+	(0x7000_0001, ProgramHeaderType::ArmExidx,),
+	(2, ProgramHeaderType::Dynamic,),
+	(0x6474_e550, ProgramHeaderType::GnuEhFrame,),
+	(0x6474_e553, ProgramHeaderType::GnuProperty,),
+	(0x6474_e552, ProgramHeaderType::GnuRelro,),
+	(0x6474_e554, ProgramHeaderType::GnuSframe,),
+	(0x6474_e551, ProgramHeaderType::GnuStack,),
+	(0x6fff_ffff, ProgramHeaderType::Hios,),
+	(0x7fff_ffff, ProgramHeaderType::Hiproc,),
+	(3, ProgramHeaderType::Interp,),
+	(1, ProgramHeaderType::Load,),
+	(0x6000_0000, ProgramHeaderType::Loos,),
+	(0x7000_0000, ProgramHeaderType::Loproc,),
+	(0x6fff_fffa, ProgramHeaderType::Losunw,),
+	(4, ProgramHeaderType::Note,),
+	(0, ProgramHeaderType::Null,),
+	(8, ProgramHeaderType::Num,),
+	(6, ProgramHeaderType::Phdr,),
+	(5, ProgramHeaderType::Shlib,),
+	(0x6fff_fffb, ProgramHeaderType::Sunwstack,),
+	(7, ProgramHeaderType::Tls,),
+];
 
-	fn try_from(value: u32,) -> Result<Self, Self::Error,> {
-		let ty = match value {
-			0x7000_0001 => Self::ArmExidx,
-			2 => Self::Dynamic,
-			0x6474_e550 => Self::GnuEhFrame,
-			0x6474_e553 => Self::GnuProperty,
-			0x6474_e552 => Self::GnuRelro,
-			0x6474_e551 => Self::GnuStack,
-			0x6fff_ffff => Self::Hios,
-			0x7fff_ffff => Self::Hiproc,
-			3 => Self::Interp,
-			1 => Self::Load,
-			0x6000_0000 => Self::Loos,
-			0x7000_0000 => Self::Loproc,
-			0x6fff_fffa => Self::Losunw,
-			4 => Self::Note,
-			0 => Self::Null,
-			8 => Self::Num,
-			6 => Self::Phdr,
-			5 => Self::Shlib,
-			0x6fff_fffb => Self::Sunwstack,
-			7 => Self::Tls,
-			_ => {
-				return Err(oso_err!(EfiParseError::InvalidProgramHeaderType(
-					value
-				)),);
-			},
-		};
-		Ok(ty,)
+impl From<u32,> for ProgramHeaderType {
+	fn from(value: u32,) -> Self {
+		PROGRAM_HEADER_TYPES
+			.iter()
+			.find(|(v, _,)| *v == value,)
+			.map_or(Self::Other(value,), |(_, ty,)| *ty,)
+	}
+}
+
+impl From<ProgramHeaderType,> for u32 {
+	fn from(ty: ProgramHeaderType,) -> Self {
+		if let ProgramHeaderType::Other(value,) = ty {
+			return value;
+		}
+		PROGRAM_HEADER_TYPES
+			.iter()
+			.find(|(_, t,)| *t == ty,)
+			.map(|(v, _,)| *v,)
+			.expect("every variant but Other has a value",)
 	}
 }
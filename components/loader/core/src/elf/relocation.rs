@@ -0,0 +1,295 @@
+//! # Relocation
+//!
+//! Applies the dynamic relocations of the kernel once its segments are
+//! copied, so a position independent kernel runs at an address other than
+//! the one it was linked at.
+//!
+//! [`Relocator`] walks `dynamic_relocation_with_addend`,
+//! `dynamic_relocation` and `procedure_linkage_table_relocation` of an
+//! [`Elf`] and patches the 64 bit words they point at in the loaded image:
+//!
+//! | relocation            | value   |
+//! |-----------------------|---------|
+//! | `R_AARCH64_RELATIVE`  | `B + A` |
+//! | `R_AARCH64_ABS64`     | `S + A` |
+//! | `R_AARCH64_GLOB_DAT`  | `S + A` |
+//! | `R_AARCH64_JUMP_SLOT` | `S + A` |
+//!
+//! `B` is the distance from the link address to the load address, `S` the
+//! loaded address of the symbol and `A` the addend, which REL relocations
+//! keep in the word they patch. The kernel is linked on its own, so symbols
+//! it does not define are errors, except weak ones which resolve to `0`.
+//! Relocations of other machines are errors too.
+//!
+//! The kernel targets use the `static` relocation model, so the kernel
+//! built today has no dynamic relocations and is loaded at its link address.
+//! A position independent kernel is placed and relocated by the tests of
+//! [`load`](crate::load).
+//!
+//! ```rust,ignore
+//! let relocator = Relocator::new(&elf, link_head, load_head,);
+//! let applied = relocator.apply(image,)?;
+//! ```
+
+use super::Elf;
+use super::ElfHeader;
+use super::Relocation;
+use super::RelocationSection;
+use super::SymbolTable;
+use crate::Rslt;
+use oso_error::loader::EfiParseError;
+use oso_error::oso_err;
+
+/// Relocation without effect
+pub const R_AARCH64_NONE: u32 = 0;
+/// Address of a symbol
+pub const R_AARCH64_ABS64: u32 = 257;
+/// Global offset table entry of a symbol
+pub const R_AARCH64_GLOB_DAT: u32 = 1025;
+/// Procedure linkage table entry of a symbol
+pub const R_AARCH64_JUMP_SLOT: u32 = 1026;
+/// Address relative to the load address
+pub const R_AARCH64_RELATIVE: u32 = 1027;
+
+/// Size of a symbol of a 64 bit symbol table
+const SYMBOL_SIZE: usize = 24;
+/// Section index of undefined symbols
+const SHN_UNDEF: u16 = 0;
+/// Section index of absolute symbols, which do not move with the image
+const SHN_ABS: u16 = 0xfff1;
+/// Binding of weak symbols
+const STB_WEAK: u8 = 2;
+
+/// Applies the dynamic relocations of a loaded [`Elf`]
+pub struct Relocator<'a,> {
+	machine:  u16,
+	symbols:  &'a SymbolTable,
+	sections: [&'a RelocationSection; 3],
+	/// link address of the first byte of the image
+	link:     u64,
+	/// load address minus link address, wrapping
+	bias:     u64,
+}
+
+impl<'a,> Relocator<'a,> {
+	/// Relocator of `elf`, whose loaded segments span from the link address
+	/// `link` on and were placed at `load`
+	pub fn new(elf: &'a Elf, link: u64, load: u64,) -> Self {
+		Self {
+			machine: elf.header.machine,
+			symbols: &elf.dynamic_symbol_table,
+			sections: [
+				&elf.dynamic_relocation_with_addend,
+				&elf.dynamic_relocation,
+				&elf.procedure_linkage_table_relocation,
+			],
+			link,
+			bias: load.wrapping_sub(link,),
+		}
+	}
+
+	/// Distance from the link address to the load address, wrapping
+	pub fn bias(&self,) -> u64 {
+		self.bias
+	}
+
+	/// Patches `image`, the loaded segments from the link address of
+	/// [`Self::new`] on, and returns the number of relocations applied
+	///
+	/// # Errors
+	///
+	/// - [`EfiParseError::UnsupportedRelocation`] for relocations of other
+	///   types or machines
+	/// - [`EfiParseError::RelocationOutOfImage`] if a relocation patches a
+	///   word outside of `image`
+	/// - [`EfiParseError::UndefinedSymbol`] if a relocation refers to a
+	///   symbol the kernel does not define
+	pub fn apply(&self, image: &mut [u8],) -> Rslt<usize, EfiParseError,> {
+		let mut applied = 0;
+		for relocation in self.sections.into_iter().flatten() {
+			if relocation.ty != R_AARCH64_NONE {
+				self.apply_one(&relocation, image,)?;
+				applied += 1;
+			}
+		}
+		Ok(applied,)
+	}
+
+	fn apply_one(
+		&self,
+		relocation: &Relocation,
+		image: &mut [u8],
+	) -> Rslt<(), EfiParseError,> {
+		let Relocation { offset, addend, symbol_index, ty, } = *relocation;
+		let out_of_image = || oso_err!(EfiParseError::RelocationOutOfImage {
+			offset
+		});
+		let start = offset.checked_sub(self.link,).ok_or_else(out_of_image,)?;
+		let word = usize::try_from(start,)
+			.ok()
+			.and_then(|start| image.get_mut(start..)?.first_chunk_mut::<8>(),)
+			.ok_or_else(out_of_image,)?;
+		let addend = match addend {
+			Some(addend,) => addend as u64,
+			None => u64::from_le_bytes(*word,),
+		};
+
+		let machine = self.machine;
+		let value = match (machine, ty,) {
+			(ElfHeader::EM_AARCH64, R_AARCH64_RELATIVE,) => {
+				self.bias.wrapping_add(addend,)
+			},
+			(
+				ElfHeader::EM_AARCH64,
+				R_AARCH64_ABS64 | R_AARCH64_GLOB_DAT | R_AARCH64_JUMP_SLOT,
+			) => self.symbol(symbol_index,)?.wrapping_add(addend,),
+			_ => {
+				let error = EfiParseError::UnsupportedRelocation {
+					machine,
+					ty,
+				};
+				return Err(oso_err!(error),);
+			},
+		};
+		*word = value.to_le_bytes();
+		Ok((),)
+	}
+
+	/// Loaded address of the dynamic symbol `index`
+	fn symbol(&self, index: usize,) -> Rslt<u64, EfiParseError,> {
+		let undefined = || oso_err!(EfiParseError::UndefinedSymbol(index));
+		let start = index.checked_mul(SYMBOL_SIZE,).ok_or_else(undefined,)?;
+		let symbol = self
+			.symbols
+			.bytes
+			.get(start..)
+			.and_then(|bytes| bytes.first_chunk::<SYMBOL_SIZE>(),)
+			.ok_or_else(undefined,)?;
+
+		let binding = symbol[4] >> 4;
+		let section = u16::from_le_bytes([symbol[6], symbol[7],],);
+		let value = u64::from_le_bytes(symbol[8..16].try_into().unwrap(),);
+		match section {
+			SHN_UNDEF if binding == STB_WEAK => Ok(0,),
+			SHN_UNDEF => Err(undefined(),),
+			SHN_ABS => Ok(value,),
+			_ => Ok(value.wrapping_add(self.bias,),),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::elf::Context;
+
+	const LINK: u64 = 0x4000_0000;
+	const LOAD: u64 = 0x8000_0000;
+
+	/// RELA section of `(offset, symbol, type, addend)` entries
+	fn rela(entries: &[(u64, u64, u32, i64,)],) -> RelocationSection {
+		let mut bytes = vec![];
+		for &(offset, symbol, ty, addend,) in entries {
+			bytes.extend_from_slice(&offset.to_le_bytes(),);
+			bytes.extend_from_slice(&(symbol << 32 | ty as u64).to_le_bytes(),);
+			bytes.extend_from_slice(&addend.to_le_bytes(),);
+		}
+		let end = bytes.len();
+		let context = (true, Context::default(),);
+		let count = entries.len();
+		RelocationSection { bytes, count, context, start: 0, end, }
+	}
+
+	/// symbol table of `(section, binding, value)` entries after the null
+	/// symbol
+	fn symbols(entries: &[(u16, u8, u64,)],) -> SymbolTable {
+		let mut bytes = vec![0; SYMBOL_SIZE];
+		for &(section, binding, value,) in entries {
+			bytes.extend_from_slice(&[0, 0, 0, 0, binding << 4, 0,],);
+			bytes.extend_from_slice(&section.to_le_bytes(),);
+			bytes.extend_from_slice(&value.to_le_bytes(),);
+			bytes.extend_from_slice(&0u64.to_le_bytes(),);
+		}
+		let end = bytes.len();
+		let count = entries.len() + 1;
+		SymbolTable { bytes, count, ctx: Context::default(), start: 0, end, }
+	}
+
+	fn relocator<'a,>(
+		symbols: &'a SymbolTable,
+		rela: &'a RelocationSection,
+		empty: &'a RelocationSection,
+	) -> Relocator<'a,> {
+		Relocator {
+			machine: ElfHeader::EM_AARCH64,
+			symbols,
+			sections: [rela, empty, empty,],
+			link: LINK,
+			bias: LOAD.wrapping_sub(LINK,),
+		}
+	}
+
+	fn word(image: &[u8], offset: usize,) -> u64 {
+		u64::from_le_bytes(image[offset..offset + 8].try_into().unwrap(),)
+	}
+
+	#[test]
+	fn test_relocations_move_with_the_image() {
+		let symbols = symbols(&[(1, 1, LINK + 0x100,), (SHN_ABS, 1, 0x42,)],);
+		let rela = rela(&[
+			(LINK, 0, R_AARCH64_RELATIVE, LINK as i64 + 0x200,),
+			(LINK + 8, 1, R_AARCH64_ABS64, 4,),
+			(LINK + 16, 1, R_AARCH64_GLOB_DAT, 0,),
+			(LINK + 24, 2, R_AARCH64_JUMP_SLOT, 0,),
+			(LINK + 32, 0, R_AARCH64_NONE, 0,),
+		],);
+		let empty = RelocationSection::default();
+		let mut image = [0; 40];
+
+		let relocator = relocator(&symbols, &rela, &empty,);
+		assert_eq!(relocator.apply(&mut image,).unwrap(), 4);
+		assert_eq!(word(&image, 0,), LOAD + 0x200);
+		assert_eq!(word(&image, 8,), LOAD + 0x104);
+		assert_eq!(word(&image, 16,), LOAD + 0x100);
+		assert_eq!(word(&image, 24,), 0x42);
+		assert_eq!(word(&image, 32,), 0);
+	}
+
+	#[test]
+	fn test_relocation_errors() {
+		let symbols = symbols(&[(SHN_UNDEF, 2, 0,), (SHN_UNDEF, 1, 0,)],);
+		let empty = RelocationSection::default();
+		let mut image = [0; 16];
+		let apply = |rela: &RelocationSection, image: &mut [u8]| {
+			relocator(&symbols, rela, &empty,).apply(image,).unwrap_err().desc
+		};
+
+		let weak = rela(&[(LINK, 1, R_AARCH64_ABS64, 8,)],);
+		let relocator = relocator(&symbols, &weak, &empty,);
+		assert_eq!(relocator.apply(&mut image,).unwrap(), 1);
+		assert_eq!(word(&image, 0,), 8);
+
+		let undefined = rela(&[(LINK, 2, R_AARCH64_GLOB_DAT, 0,)],);
+		let error = apply(&undefined, &mut image,);
+		assert!(matches!(error, Some(EfiParseError::UndefinedSymbol(2,),)));
+
+		let outside = rela(&[(LINK + 12, 0, R_AARCH64_RELATIVE, 0,)],);
+		let error = apply(&outside, &mut image,);
+		assert!(matches!(
+			error,
+			Some(EfiParseError::RelocationOutOfImage { offset },)
+				if offset == LINK + 12
+		));
+
+		// R_AARCH64_COPY
+		let copy = rela(&[(LINK, 1, 1024, 0,)],);
+		let error = apply(&copy, &mut image,);
+		assert!(matches!(
+			error,
+			Some(EfiParseError::UnsupportedRelocation {
+				machine: ElfHeader::EM_AARCH64,
+				ty:      1024,
+			},)
+		));
+	}
+}
@@ -0,0 +1,398 @@
+//! # Loader Error Screen
+//!
+//! Instead of dumping a panic message, the loader reports failures on a
+//! bordered text box which explains what went wrong and how to fix it. The
+//! user then chooses to retry, return to the firmware boot menu or reboot.
+//!
+//! Errors reach this module as [`BootError`], which records the
+//! [`BootStage`] that failed. Use [`AtStage::at`] to annotate results of each
+//! boot step.
+
+use crate::chibi_uefi::console::read_key;
+use crate::chibi_uefi::table::system_table;
+use crate::config::CONFIG_PATH;
+use crate::raw::protocol::text::TextOutputProtocol;
+use crate::raw::types::text::InputKey;
+use crate::raw::types::text::TextAttribute;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::fmt::Write;
+use oso_error::OsoError;
+use oso_error::Rslt;
+use oso_error::loader::BootError;
+use oso_error::loader::BootStage;
+use oso_error::loader::EfiParseError;
+use oso_error::loader::OverlayError;
+use oso_error::loader::ShortRead;
+use oso_error::loader::UefiError;
+use oso_error::parser::ConfigError;
+
+/// Widest box drawn regardless of console width
+const MAX_WIDTH: usize = 76;
+
+/// Error descriptors which can explain their cause in a short string
+pub trait Cause {
+	fn cause(&self,) -> Option<&'static str,>;
+}
+
+impl Cause for () {
+	fn cause(&self,) -> Option<&'static str,> {
+		None
+	}
+}
+
+impl Cause for UefiError {
+	fn cause(&self,) -> Option<&'static str,> {
+		match self {
+			UefiError::CustomStatus => Some("unknown uefi status",),
+			UefiError::ErrorStatus(s,) | UefiError::Custom(s,) => Some(s,),
+		}
+	}
+}
+
+impl Cause for EfiParseError {
+	fn cause(&self,) -> Option<&'static str,> {
+		let cause = match self {
+			EfiParseError::EndOfBinary { .. } => "unexpected end of binary",
+			EfiParseError::SizeOverflow { .. } => "size overflow",
+			EfiParseError::UnknownEfiType(_,) => "unknown elf type",
+			EfiParseError::InvalidIdentLen(_,) => "invalid ident length",
+			EfiParseError::BadMagicNumber(..,) => "bad magic number",
+			EfiParseError::InvalidFileClass(_,) => "invalid file class",
+			EfiParseError::OsAbiOutOfSupport(_,) => "unsupported os abi",
+			EfiParseError::DelimiterNotFound(_,) => "delimiter not found",
+			EfiParseError::InvalidUtf8 { .. } => "string is not valid utf-8",
+			EfiParseError::TooManySymbolsOffset { .. } => {
+				"too many symbols offset"
+			},
+			EfiParseError::InvalidEndianFlag(_,) => "invalid endian flag",
+			EfiParseError::InvalidGnuHash { .. } => "invalid gnu hash",
+			EfiParseError::Unknown => "unknown parse error",
+			EfiParseError::UnsupportedRelocation { .. } => {
+				"unsupported relocation"
+			},
+			EfiParseError::RelocationOutOfImage { .. } => {
+				"relocation outside of the kernel"
+			},
+			EfiParseError::UndefinedSymbol(_,) => "undefined symbol",
+		};
+		Some(cause,)
+	}
+}
+
+impl Cause for OverlayError {
+	fn cause(&self,) -> Option<&'static str,> {
+		let cause = match self {
+			OverlayError::Malformed => "not a valid device tree blob",
+			OverlayError::MissingTarget => "fragment has no target",
+			OverlayError::TargetNotFound => "target node not found",
+			OverlayError::UnresolvedSymbol => "label not found in __symbols__",
+			OverlayError::InvalidFixup => "invalid fixup",
+		};
+		Some(cause,)
+	}
+}
+
+impl Cause for ConfigError {
+	fn cause(&self,) -> Option<&'static str,> {
+		let cause = match self {
+			ConfigError::InvalidSection(_,) => "invalid section header",
+			ConfigError::InvalidKey(_,) => "invalid key",
+			ConfigError::MissingEquals(_,) => "expected `key = value`",
+			ConfigError::InvalidValue(_,) => "invalid value",
+			ConfigError::UnterminatedString(_,) => "unterminated string",
+			ConfigError::UnsupportedEscape(_,) => {
+				"escape sequence in \"...\" string. use '...' instead"
+			},
+			ConfigError::TrailingCharacters(_,) => "unexpected characters",
+			ConfigError::DuplicateKey(_,) => "duplicate key",
+			ConfigError::DuplicateSection(_,) => "duplicate section",
+			ConfigError::TypeMismatch(_,) => "value has wrong type",
+			ConfigError::Unknown => return None,
+		};
+		Some(cause,)
+	}
+}
+
+/// Annotates an error with the boot stage it happened in
+pub trait AtStage<T,> {
+	fn at(self, stage: BootStage,) -> Rslt<T, BootError,>;
+}
+
+impl<T, V: Cause + Debug,> AtStage<T,> for Rslt<T, V,> {
+	fn at(self, stage: BootStage,) -> Rslt<T, BootError,> {
+		self.map_err(|e| OsoError {
+			from: e.from,
+			desc: Some(BootError {
+				stage,
+				cause: e.desc.as_ref().and_then(Cause::cause,),
+				..Default::default()
+			},),
+		},)
+	}
+}
+
+/// What the user wants to do after reading the error screen
+#[derive(Clone, Copy, Debug, PartialEq, Eq,)]
+pub enum Choice {
+	/// Run the boot sequence again
+	Retry,
+	/// Return to the firmware boot manager
+	BootMenu,
+	/// Cold reset the machine
+	Reboot,
+}
+
+impl Choice {
+	fn from_key(key: InputKey,) -> Option<Self,> {
+		if key.scan_code == InputKey::SCAN_ESC {
+			return Some(Self::Reboot,);
+		}
+		match key.char()? {
+			'r' | 'R' => Some(Self::Retry,),
+			'm' | 'M' => Some(Self::BootMenu,),
+			_ => None,
+		}
+	}
+}
+
+/// User facing description of a boot failure
+///
+/// # Fields
+///
+/// * `title` - One line summary
+/// * `message` - What went wrong
+/// * `hint` - Suggested fix
+/// * `cause` - Description of the underlying error, if known
+/// * `origin` - Module where the error was raised
+#[derive(Debug,)]
+pub struct ErrorReport {
+	pub title:   &'static str,
+	pub message: String,
+	pub hint:    &'static str,
+	pub cause:   Option<&'static str,>,
+	pub origin:  &'static str,
+}
+
+impl ErrorReport {
+	/// # Arguments
+	///
+	/// * `error` - The error to describe
+	/// * `kernel_path` - Path of the kernel the loader tried to boot
+	pub fn new(error: &OsoError<BootError,>, kernel_path: &str,) -> Self {
+		let (stage, cause, short_read,) = error
+			.desc
+			.as_ref()
+			.map(|desc| (desc.stage, desc.cause, desc.short_read,),)
+			.unwrap_or_default();
+
+		let not_found = cause.is_some_and(|c| c.starts_with("EFI_NOT_FOUND",),);
+		let (title, message, hint,): (_, String, _,) = match stage {
+			BootStage::Init => (
+				"initialization failed",
+				"failed to initialize uefi services".into(),
+				"firmware may lack a required protocol. update the firmware",
+			),
+			BootStage::Config => (
+				"invalid configuration",
+				format!("failed to read {CONFIG_PATH}"),
+				"fix the reported error, or delete the file to use defaults",
+			),
+			BootStage::LoaderImage => (
+				"loader image unavailable",
+				"failed to locate the image of the loader itself".into(),
+				"reinstall the loader into \\EFI\\BOOT on the boot volume",
+			),
+			BootStage::KernelOpen if not_found => (
+				"kernel not found",
+				format!("kernel not found at {kernel_path}"),
+				"copy the kernel to this path, or set `path` in [kernel] \
+				 section of the loader configuration",
+			),
+			BootStage::KernelOpen => (
+				"cannot open kernel",
+				format!("failed to open {kernel_path}"),
+				"make sure the boot volume is a FAT formatted EFI system \
+				 partition",
+			),
+			BootStage::KernelRead
+				if let Some(ShortRead { read, expected, },) = short_read =>
+			{
+				(
+					"kernel is truncated",
+					format!(
+						"read {read} of {expected} bytes of {kernel_path}"
+					),
+					"the copy of the kernel is incomplete. copy it again",
+				)
+			},
+			BootStage::KernelRead => (
+				"cannot read kernel",
+				format!("failed to read {kernel_path}"),
+				"the file may be corrupted. rebuild and copy it again",
+			),
+			BootStage::KernelParse => (
+				"invalid kernel",
+				format!("{kernel_path} is not a valid elf executable"),
+				"rebuild the kernel for the architecture of this machine",
+			),
+			BootStage::KernelVersion => (
+				"incompatible kernel",
+				format!("{kernel_path} does not work with this loader"),
+				"install the loader and the kernel of the same build, or set \
+				 `check_version = false` in [kernel] section to boot anyway",
+			),
+			BootStage::KernelLoad => (
+				"cannot load kernel",
+				"failed to place kernel segments at their link address".into(),
+				"the link address may overlap firmware memory. change it in \
+				 the kernel linker script or give the machine more memory",
+			),
+			BootStage::DeviceTree => (
+				"device tree missing",
+				"firmware does not provide a device tree".into(),
+				"boot on a platform which publishes a device tree, e.g. qemu \
+				 -machine virt",
+			),
+			BootStage::Overlay if not_found => (
+				"device tree overlay not found",
+				"an overlay listed in [overlays] section does not exist".into(),
+				"copy the overlay to the boot volume, or remove it from the \
+				 loader configuration",
+			),
+			BootStage::Overlay => (
+				"cannot apply device tree overlay",
+				"failed to apply an overlay of [overlays] section".into(),
+				"build the overlay with `dtc -@` for the device tree of this \
+				 machine, or remove it from the loader configuration",
+			),
+			BootStage::Initrd if not_found => (
+				"initial ramdisk not found",
+				"the `initrd` of [kernel] section does not exist".into(),
+				"copy the archive to the boot volume, or remove `initrd` from \
+				 the loader configuration",
+			),
+			BootStage::Initrd => (
+				"cannot read initial ramdisk",
+				"failed to read the `initrd` of [kernel] section".into(),
+				"the file may be corrupted. copy it again",
+			),
+			BootStage::Handoff => (
+				"cannot prepare boot information",
+				"failed to collect memory map for the kernel".into(),
+				"give the machine more memory and retry",
+			),
+			BootStage::MemoryMap => (
+				"inconsistent memory layout",
+				"memory map does not match what the kernel is given".into(),
+				"this is a bug of the loader or the firmware. set `level = \
+				 \"debug\"` in [log] section to print the memory map",
+			),
+		};
+
+		Self { title, message, hint, cause, origin: error.from, }
+	}
+
+	/// Lines displayed inside the box, each at most `width` characters
+	fn lines(&self, width: usize,) -> Vec<String,> {
+		let mut lines = Vec::new();
+		lines.push(self.title.into(),);
+		lines.push(String::new(),);
+		wrap(&self.message, width, &mut lines,);
+		lines.push(String::new(),);
+		wrap(&format!("hint: {}", self.hint), width, &mut lines,);
+		if let Some(cause,) = self.cause {
+			wrap(&format!("cause: {cause}"), width, &mut lines,);
+		}
+		wrap(&format!("at: {}", self.origin), width, &mut lines,);
+		lines.push(String::new(),);
+		wrap("[r] retry  [m] boot menu  [esc] reboot", width, &mut lines,);
+		lines
+	}
+}
+
+/// Greedy word wrap. Words longer than `width` are split
+fn wrap(text: &str, width: usize, lines: &mut Vec<String,>,) {
+	let mut line = String::new();
+	let mut len = 0;
+	for word in text.split_whitespace() {
+		let word_len = word.chars().count();
+		if len != 0 && len + 1 + word_len > width {
+			lines.push(core::mem::take(&mut line,),);
+			len = 0;
+		}
+		if len != 0 {
+			line.push(' ',);
+			len += 1;
+		}
+
+		for c in word.chars() {
+			if len == width {
+				lines.push(core::mem::take(&mut line,),);
+				len = 0;
+			}
+			line.push(c,);
+			len += 1;
+		}
+	}
+	lines.push(line,);
+}
+
+/// Draws `report` and blocks until the user makes a choice
+///
+/// Console errors are ignored: there is no better place to report them.
+pub fn show(report: &ErrorReport,) -> Choice {
+	let stdout = unsafe { system_table().as_ref().stdout.as_mut() }.unwrap();
+	let _ = stdout.set_attribute(TextAttribute::new(
+		TextAttribute::WHITE,
+		TextAttribute::RED,
+	),);
+	let _ = stdout.clear();
+	let _ = stdout.enable_cursor(false,);
+
+	let (columns, rows,) = stdout.size().unwrap_or((80, 25,),);
+	// 2 columns for borders and 2 for padding. `columns - 1` avoids the last
+	// column as writing there scrolls some consoles
+	let inner = columns.saturating_sub(1,).min(MAX_WIDTH,).saturating_sub(4,);
+	let lines = report.lines(inner,);
+	let left = columns.saturating_sub(inner + 4,) / 2;
+	let top = rows.saturating_sub(lines.len() + 2,) / 2;
+
+	draw_box(stdout, &lines, inner, left, top,);
+
+	let choice = loop {
+		if let Some(choice,) = Choice::from_key(read_key(),) {
+			break choice;
+		}
+	};
+
+	let _ = stdout.set_attribute(TextAttribute::new(
+		TextAttribute::LIGHTGRAY,
+		TextAttribute::BLACK,
+	),);
+	let _ = stdout.clear();
+	let _ = stdout.enable_cursor(true,);
+	choice
+}
+
+fn draw_box(
+	stdout: &mut TextOutputProtocol,
+	lines: &[String],
+	inner: usize,
+	left: usize,
+	top: usize,
+) {
+	let horizontal: String = core::iter::repeat_n('─', inner + 2,).collect();
+
+	let _ = stdout.set_cursor(left, top,);
+	let _ = write!(stdout, "┌{horizontal}┐");
+	for (i, line,) in lines.iter().enumerate() {
+		let _ = stdout.set_cursor(left, top + 1 + i,);
+		let pad = inner.saturating_sub(line.chars().count(),);
+		let _ = write!(stdout, "│ {line}{:pad$} │", "");
+	}
+	let _ = stdout.set_cursor(left, top + 1 + lines.len(),);
+	let _ = write!(stdout, "└{horizontal}┘");
+}
@@ -0,0 +1,235 @@
+//! # Kernel Handoff Module
+//!
+//! This module prepares [`BootInfo`] for the kernel. Preparation is split
+//! around `ExitBootServices`:
+//!
+//! 1. [`Handoff::new`] runs while boot services are available. It allocates
+//!    `BootInfo`, the kernel command line and the framebuffer configuration,
+//!    takes over the initial ramdisk as the boot module, copies the memory
+//!    attributes table and reserves space for the final
+//!    memory map. Dropping the [`Handoff`] frees all of it, so a boot attempt
+//!    which fails afterwards can be retried
+//! 2. [`Handoff::finish`] runs after boot services are exited. It blanks the
+//!    framebuffer, assigns virtual addresses to runtime regions, calls
+//!    `SetVirtualAddressMap` and fills the reserved buffers without
+//!    allocating
+//!
+//! ## Runtime Services
+//!
+//! The runtime services firmware supports are detected by [`Handoff::new`].
+//! [`Handoff::finish`] hands them over in [`BootInfo::runtime`] whether or not
+//! `SetVirtualAddressMap` succeeds: firmware which failed to switch stays in
+//! physical mode, and the kernel calls it at the physical address of the
+//! table.
+//!
+//! ## Firmware Calls
+//!
+//! [`Handoff::finish`] hands the ring of the flight recorder over in
+//! [`BootInfo::firmware_calls`], after `ExitBootServices` was recorded as
+//! its last call. It is empty unless the loader was built with the
+//! `flight_recorder` feature, see [`flight`](crate::chibi_uefi::flight).
+//!
+//! ## Milestones
+//!
+//! Firmware has no time services after `ExitBootServices`, so
+//! [`Handoff::finish`] stamps [`BootInfo::milestones`] with the counter of
+//! [`arch`](crate::arch), the one the kernel profiles its boot with: when
+//! boot services were exited, when `SetVirtualAddressMap` returned and when
+//! the boot information was complete.
+//!
+//! ## Framebuffer Ownership
+//!
+//! Firmware draws on the GOP framebuffer until its drivers are torn down by
+//! `ExitBootServices`. From then on the framebuffer belongs to the kernel:
+//! the loader records the final mode in [`BootInfo::framebuffer`], clears
+//! what firmware left on screen and reports the buffer as a framebuffer
+//! region of the memory map, which the kernel claims with
+//! `graphic::claim_boot_framebuffer`.
+
+use crate::Rslt;
+use crate::arch;
+use crate::chibi_uefi::flight;
+use crate::chibi_uefi::runtime::VirtualLayout;
+use crate::chibi_uefi::runtime::capabilities;
+use crate::chibi_uefi::runtime::describe_memory;
+use crate::chibi_uefi::runtime::memory_attributes;
+use crate::chibi_uefi::runtime::region_capacity;
+use crate::chibi_uefi::table::boot_services;
+use crate::chibi_uefi::table::runtime_services;
+use crate::chibi_uefi::table::system_table;
+use crate::raw::types::memory::MemoryDescriptor;
+use crate::raw::types::memory::MemoryMapOwned;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::Range;
+use oso_error::loader::UefiError;
+use oso_no_std_shared::bridge::boot_info::BootInfo;
+use oso_no_std_shared::bridge::boot_info::CommandLine;
+use oso_no_std_shared::bridge::boot_info::MemoryRegion;
+use oso_no_std_shared::bridge::boot_info::MemoryRegions;
+use oso_no_std_shared::bridge::boot_info::Module;
+use oso_no_std_shared::bridge::boot_info::Modules;
+use oso_no_std_shared::bridge::boot_info::RuntimeCaps;
+use oso_no_std_shared::bridge::boot_info::SegmentChecksum;
+use oso_no_std_shared::bridge::boot_info::SegmentChecksums;
+use oso_no_std_shared::bridge::device_tree::DeviceTreeAddress;
+use oso_no_std_shared::bridge::graphic::FrameBufConf;
+
+/// Extra regions reserved on top of the current memory map size
+///
+/// Allocations made after [`Handoff::new`] (including the memory map buffer
+/// itself) may split existing entries.
+const EXTRA_REGIONS: usize = 16;
+
+/// Boot information under construction
+///
+/// Owns what `boot_info` points at until [`Handoff::finish`] hands it to the
+/// kernel
+pub struct Handoff {
+	boot_info:   Box<BootInfo,>,
+	cmdline:     String,
+	checksums:   Vec<SegmentChecksum,>,
+	/// path and contents of the initial ramdisk
+	initrd:      Option<(String, Vec<u8,>,),>,
+	modules:     Vec<Module,>,
+	regions:     Vec<MemoryRegion,>,
+	attributes:  Vec<MemoryDescriptor,>,
+	layout:      VirtualLayout,
+	/// physical range of the loader image, reported as reclaimable
+	image:       Range<u64,>,
+	/// framebuffer handed to the kernel
+	framebuffer: Option<Box<FrameBufConf,>,>,
+	/// runtime services supported after boot
+	runtime:     RuntimeCaps,
+}
+
+impl Handoff {
+	/// Allocates everything needed to build `BootInfo` after exiting boot
+	/// services
+	///
+	/// # Arguments
+	///
+	/// * `device_tree` - Address of the device tree blob passed to the kernel
+	/// * `layout` - Virtual address layout of runtime services
+	/// * `image` - Physical range of the loader image
+	/// * `cmdline` - Kernel command line
+	/// * `checksums` - Checksums of the kernel segments
+	/// * `initrd` - Path and contents of the initial ramdisk
+	/// * `framebuffer` - Framebuffer in its final mode, if the kernel may draw
+	///   on it
+	pub fn new(
+		device_tree: DeviceTreeAddress,
+		layout: VirtualLayout,
+		image: Range<u64,>,
+		cmdline: &str,
+		checksums: &[SegmentChecksum],
+		initrd: Option<(String, Vec<u8,>,),>,
+		framebuffer: Option<FrameBufConf,>,
+	) -> Rslt<Self, UefiError,> {
+		// heap contents do not move with their owners
+		let mut boot_info = Box::new(BootInfo::new(device_tree,),);
+		let cmdline = String::from(cmdline,);
+		boot_info.cmdline =
+			CommandLine { ptr: cmdline.as_ptr(), len: cmdline.len(), };
+		let checksums = checksums.to_vec();
+		boot_info.segments =
+			SegmentChecksums { ptr: checksums.as_ptr(), len: checksums.len(), };
+		let modules: Vec<_,> = initrd
+			.iter()
+			.map(|(path, contents,)| {
+				let (ptr, len,) = (path.as_ptr(), path.len(),);
+				let start = contents.as_ptr() as u64;
+				let size = contents.len() as u64;
+				Module::new(start, size, CommandLine { ptr, len, },)
+			},)
+			.collect();
+		boot_info.modules =
+			Modules { ptr: modules.as_ptr(), len: modules.len(), };
+		let framebuffer = framebuffer.map(Box::new,);
+		if let Some(fb,) = &framebuffer {
+			boot_info.framebuffer = &**fb;
+		}
+		let attributes = memory_attributes()?;
+		let runtime = capabilities()?;
+
+		let (map_size, desc_size,) = boot_services().memory_map_size();
+		let capacity =
+			region_capacity(map_size / desc_size + EXTRA_REGIONS, &attributes,);
+		let regions = Vec::with_capacity(capacity,);
+
+		Ok(Self {
+			boot_info,
+			cmdline,
+			checksums,
+			initrd,
+			modules,
+			regions,
+			attributes,
+			layout,
+			image,
+			framebuffer,
+			runtime,
+		},)
+	}
+
+	/// Completes `BootInfo` with the final memory map
+	///
+	/// Failure of `SetVirtualAddressMap` is not fatal: the kernel is handed
+	/// the physical address of the runtime services table, and
+	/// [`BootInfo::runtime`] lacks [`RuntimeCaps::VIRTUAL_MODE`].
+	///
+	/// # Arguments
+	///
+	/// * `memory_map` - The memory map returned by `exit_boot_services`
+	pub fn finish(
+		mut self,
+		mut memory_map: MemoryMapOwned,
+	) -> &'static BootInfo {
+		let milestones = &mut self.boot_info.milestones;
+		milestones.write_exit_boot_services(arch::counter(),);
+		milestones.write_frequency(arch::counter_frequency(),);
+
+		// firmware stopped drawing with its drivers torn down
+		if let Some(fb,) = &self.framebuffer {
+			unsafe { fb.base.write_bytes(0, fb.size,) };
+		}
+
+		self.layout.assign(&mut memory_map,);
+		let virtual_mode =
+			runtime_services().set_virtual_address_map(&mut memory_map,);
+		self.boot_info.milestones.write_virtual_mode(arch::counter(),);
+
+		// converted into the virtual address space if firmware switched
+		let rt = unsafe { system_table().as_ref() }.runtime_services;
+		self.boot_info.write_runtime_services(rt as u64,);
+		if virtual_mode.is_ok() {
+			self.runtime.flags |= RuntimeCaps::VIRTUAL_MODE;
+		}
+		self.boot_info.runtime = self.runtime;
+		self.boot_info.firmware_calls = flight::calls();
+
+		let framebuffer = self.framebuffer.as_ref().map(|fb| {
+			fb.base as u64..fb.base as u64 + fb.size as u64
+		},);
+		describe_memory(
+			&memory_map,
+			&self.attributes,
+			self.image,
+			framebuffer,
+			&mut self.regions,
+		);
+		let regions = self.regions.leak();
+		self.boot_info.memory_map =
+			MemoryRegions { ptr: regions.as_ptr(), len: regions.len(), };
+
+		self.boot_info.milestones.write_handoff(arch::counter(),);
+		// the kernel reads them through `boot_info` from here
+		core::mem::forget(self.cmdline,);
+		core::mem::forget(self.checksums,);
+		core::mem::forget(self.initrd,);
+		core::mem::forget(self.modules,);
+		core::mem::forget(self.framebuffer,);
+		Box::leak(self.boot_info,)
+	}
+}
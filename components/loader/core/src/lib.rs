@@ -29,7 +29,7 @@
 //! point is `efi_main` which initializes the system, loads the kernel, and
 //! transfers control.
 
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 #![allow(incomplete_features)]
 #![feature(alloc_error_handler)]
 #![feature(ptr_as_ref_unchecked)]
@@ -38,20 +38,22 @@
 #![feature(generic_const_exprs)]
 #![feature(associated_type_defaults)]
 #![feature(assert_matches)]
+#![cfg_attr(test, feature(c_variadic))]
 // #![feature(nonzero_internals)]
 //#![feature(stdarch_arm_hints)]
 
 extern crate alloc;
 
-use alloc::vec::Vec;
 use chibi_uefi::protocol::HandleSearchType;
 use chibi_uefi::table::boot_services;
 use core::ptr::NonNull;
+use core::sync::atomic::AtomicU8;
+use core::sync::atomic::Ordering;
 use oso_error::Rslt;
 use oso_error::loader::UefiError;
 use oso_error::oso_err;
-use oso_no_std_shared::bridge::device_tree::DeviceTreeAddress;
-use oso_no_std_shared::wfe;
+use oso_no_std_shared::bridge::boot_info::BootInfo;
+use oso_no_std_shared::bridge::panic_policy::PanicPolicy;
 use oso_no_std_shared::wfi;
 use raw::table::SystemTable;
 use raw::types::Status;
@@ -60,24 +62,62 @@ use raw::types::UnsafeHandle;
 use crate::chibi_uefi::table::system_table;
 use crate::raw::table::ConfigTable;
 
+pub use oso_no_std_shared::print;
+pub use oso_no_std_shared::println;
+
+/// Counter of the CPU, usable without boot services
+pub mod arch;
 /// UEFI interface wrapper providing simplified access to UEFI services
 pub mod chibi_uefi;
+/// Loader configuration file
+pub mod config;
 /// ELF file parsing and loading functionality
 pub mod elf;
+/// User facing error screen shown when booting fails
+pub mod error_screen;
+/// Boot information preparation for kernel handoff
+pub mod handoff;
 /// Kernel and graphics loading utilities
 pub mod load;
+/// Sanity checks of the memory map before handoff
+pub mod memmap;
+/// Device tree overlays applied before handoff
+pub mod overlay;
 /// Raw UEFI types and protocol definitions
 pub mod raw;
 
+/// Policy applied by the panic handler, set by [`set_panic_policy`]
+static PANIC_POLICY: AtomicU8 = AtomicU8::new(PanicPolicy::Halt as u8,);
+
+/// Chooses what the panic handler does after printing the panic
+///
+/// Panics before the configuration is read halt. The loader has no LED
+/// driver nor QEMU exit device, so [`PanicPolicy::Blink`] and
+/// [`PanicPolicy::Exit`] halt as well.
+pub fn set_panic_policy(policy: PanicPolicy,) {
+	PANIC_POLICY.store(policy as u8, Ordering::Relaxed,);
+}
+
 /// Custom panic handler for the UEFI environment
 ///
-/// This panic handler prints debug information and enters a wait-for-event loop
-/// instead of terminating the program, which is appropriate for a UEFI
-/// application.
+/// This panic handler prints debug information and then applies the policy
+/// of [`set_panic_policy`]: a wait-for-event loop which keeps the message on
+/// screen, or a cold reset through UEFI runtime services.
+#[cfg(not(test))]
 #[panic_handler]
 fn panic(panic: &core::panic::PanicInfo,) -> ! {
 	println!("{panic:#?}");
-	wfe()
+	let policy = PanicPolicy::from_u8(PANIC_POLICY.load(Ordering::Relaxed,),);
+	match policy.unwrap_or_default() {
+		PanicPolicy::Reboot => chibi_uefi::table::runtime_services().reset(
+			raw::types::misc::ResetType::COLD,
+			Status::EFI_SUCCESS,
+			None,
+		),
+		PanicPolicy::Halt | PanicPolicy::Blink | PanicPolicy::Exit => {
+			oso_no_std_shared::wfe()
+		},
+	}
 }
 
 /// Macro for handling errors that cannot be processed with the `?` operator
@@ -112,6 +152,9 @@ macro_rules! on_error {
 /// This function performs essential initialization tasks:
 /// - Clears the console output
 /// - Sets up the system table and image handle
+/// - Starts the flight recorder of boot services calls with the
+///   `flight_recorder` feature
+/// - Installs the console output as the console of [`println!`]
 /// - Connects all available UEFI devices
 ///
 /// Device connection is performed by iterating through all handles and calling
@@ -136,6 +179,9 @@ pub fn init(image_handle: UnsafeHandle, syst: *const SystemTable,) {
 	// Initialize UEFI table access
 	chibi_uefi::table::set_system_table_panicking(syst,);
 	chibi_uefi::set_image_handle_panicking(image_handle,);
+	#[cfg(feature = "flight_recorder")]
+	chibi_uefi::flight::start();
+	chibi_uefi::console::install();
 
 	// Connect all available devices
 	let bs = boot_services();
@@ -165,24 +211,6 @@ fn clear_console(syst: *const SystemTable,) {
 	unsafe { syst.as_ref().unwrap().stdout.as_mut().unwrap().clear().unwrap() };
 }
 
-/// Converts a string to null-terminated UTF-16 format
-///
-/// This utility function is used for UEFI string operations which require
-/// null-terminated UTF-16 strings.
-///
-/// # Arguments
-///
-/// * `s` - String-like input to convert
-///
-/// # Returns
-///
-/// A vector containing the UTF-16 representation with null terminator
-fn into_null_terminated_utf16(s: impl AsRef<str,>,) -> Vec<u16,> {
-	let mut utf16_repr: Vec<u16,> = s.as_ref().encode_utf16().collect();
-	utf16_repr.push(0,);
-	utf16_repr
-}
-
 /// Retrieves the device tree configuration table from UEFI
 ///
 /// The device tree is essential for kernel initialization on ARM and RISC-V
@@ -209,13 +237,13 @@ pub fn get_device_tree() -> Rslt<NonNull<ConfigTable,>, UefiError,> {
 /// This function performs the final handoff to the kernel:
 /// 1. Disables MMU (on aarch64)
 /// 2. Clears caches (on aarch64)
-/// 3. Calls the kernel entry point with the device tree address
+/// 3. Calls the kernel entry point with the boot information
 /// 4. Falls back to wait-for-interrupt if kernel returns
 ///
 /// # Arguments
 ///
 /// * `kernel_entry` - Physical address of the kernel entry point
-/// * `device_tree_ptr` - Address of the device tree for kernel initialization
+/// * `boot_info` - Boot information completed by [`handoff::Handoff::finish`]
 ///
 /// # Architecture-specific Behavior
 ///
@@ -239,17 +267,17 @@ pub fn get_device_tree() -> Rslt<NonNull<ConfigTable,>, UefiError,> {
 /// - Direct kernel execution
 ///
 /// The function never returns under normal circumstances.
-pub fn exec_kernel(kernel_entry: u64, device_tree_ptr: DeviceTreeAddress,) {
+pub fn exec_kernel(kernel_entry: u64, boot_info: &'static BootInfo,) {
 	// Convert entry point to function pointer
 	let kernel_entry = kernel_entry as *const ();
 
 	// Define kernel entry point signature based on architecture
 	#[cfg(target_arch = "riscv64")]
-	type KernelEntry = extern "C" fn(DeviceTreeAddress,);
+	type KernelEntry = extern "C" fn(*const BootInfo,);
 	#[cfg(target_arch = "aarch64")]
-	type KernelEntry = extern "C" fn(DeviceTreeAddress,);
+	type KernelEntry = extern "C" fn(*const BootInfo,);
 	#[cfg(target_arch = "x86_64")]
-	type KernelEntry = extern "sysv64" fn(DeviceTreeAddress,);
+	type KernelEntry = extern "sysv64" fn(*const BootInfo,);
 
 	let entry_point = unsafe {
 		core::mem::transmute::<*const (), KernelEntry,>(kernel_entry,)
@@ -281,7 +309,7 @@ pub fn exec_kernel(kernel_entry: u64, device_tree_ptr: DeviceTreeAddress,) {
 	}
 
 	// Jump to kernel with MMU disabled
-	entry_point(device_tree_ptr,);
+	entry_point(boot_info,);
 
 	// If we reach here, kernel execution failed
 	wfi();
@@ -4,12 +4,18 @@
 //! filesystem and configuring graphics output for the kernel environment.
 
 use crate::Rslt;
+use crate::chibi_uefi::console::Progress;
+use crate::chibi_uefi::console::escape_pressed;
 use crate::chibi_uefi::required_pages;
 use crate::chibi_uefi::table::boot_services;
 use crate::elf::Elf;
+use crate::elf::ElfHeader;
+use crate::elf::ElfType;
 use crate::elf::program_header::ProgramHeaderType;
-use crate::print;
-use crate::println;
+use crate::elf::relocation::Relocator;
+use crate::error_screen::AtStage;
+use crate::debug;
+use crate::info;
 use crate::raw::protocol::file::FileProtocolV1;
 use crate::raw::protocol::file::SimpleFileSystemProtocol;
 use crate::raw::protocol::graphic::GraphicsOutputProtocol;
@@ -17,23 +23,111 @@ use crate::raw::types::PhysicalAddress;
 use crate::raw::types::file::FileAttributes;
 use crate::raw::types::file::OpenMode;
 use crate::raw::types::memory::AllocateType;
+use crate::raw::types::memory::MemoryType;
+use crate::raw::types::memory::PAGE_SIZE;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::ControlFlow;
+use core::ops::Range;
 use core::ptr::NonNull;
+use oso_error::OsoError;
+use oso_error::loader::BootError;
+use oso_error::loader::BootStage;
+use oso_error::loader::ShortRead;
+use oso_error::loader::UefiError;
+use oso_error::oso_err;
+use oso_no_std_shared::bridge::boot_info::SegmentChecksum;
 use oso_no_std_shared::bridge::graphic::FrameBufConf;
+use oso_no_std_shared::bridge::version;
+use oso_no_std_shared::bridge::version::BRIDGE_ABI;
+use oso_no_std_shared::bridge::version::KernelVersion;
+use oso_no_std_shared::bridge::version::Version;
+use oso_no_std_shared::bridge::version::VersionRange;
+use oso_no_std_shared::path::PathBufN;
+use oso_no_std_shared::path::PathN;
+use oso_no_std_shared::path::Separator;
+
+/// Default path of the kernel on the boot volume
+pub const KERNEL_PATH: &str = "\\oso_kernel.elf";
+/// Size of each read of the kernel file. Between reads, progress is shown
+/// and `Esc` is checked
+pub const READ_CHUNK_SIZE: usize = 1024 * 1024;
+/// Longest path [`open_file`] accepts after normalization
+pub const PATH_MAX: usize = 512;
+/// Version of the loader, checked by the kernel
+pub const LOADER_VERSION: Version =
+	Version::from_pkg(env!("CARGO_PKG_VERSION"),);
+/// Kernel versions the loader boots
+pub const KERNEL_VERSIONS: VersionRange =
+	VersionRange::caret(Version::new(0, 1, 0,),);
+/// Size of the header of a 64 bit ELF file
+const ELF_HEADER_SIZE: usize = 64;
+
+/// Kernel placed in memory
+///
+/// # Fields
+///
+/// * `entry` - Physical address of the entry point
+/// * `segments` - Physical ranges of the loaded segments
+/// * `checksums` - CRC-32 of each loaded segment, verified by the kernel
+/// * `version` - Version note of the kernel, if it has one
+/// * `pages` - Pages the kernel is placed in
+pub struct LoadedKernel {
+	pub entry:     PhysicalAddress,
+	pub segments:  Vec<Range<u64,>,>,
+	pub checksums: Vec<SegmentChecksum,>,
+	pub version:   Option<KernelVersion,>,
+	pub pages:     KernelPages,
+}
+
+/// Pages allocated for the kernel
+///
+/// They are freed when dropped, so a boot attempt which fails after loading
+/// the kernel leaves its link address free for a retry.
+/// [`KernelPages::keep`] leaves them allocated for the kernel to run in
+pub struct KernelPages {
+	start: PhysicalAddress,
+	count: usize,
+}
+
+impl KernelPages {
+	/// Leaves the pages allocated. Called once nothing can fail before the
+	/// kernel runs
+	pub fn keep(self,) {
+		core::mem::forget(self,);
+	}
+}
+
+impl Drop for KernelPages {
+	fn drop(&mut self,) {
+		// Failing to free only leaves the pages unused until handoff
+		let _ = boot_services().free_pages(self.start, self.count,);
+	}
+}
 
 /// Loads the kernel ELF file and prepares it for execution
 ///
 /// This function performs the complete kernel loading process:
-/// 1. Opens the kernel ELF file from the filesystem
+/// 1. Opens the kernel ELF file at `path` from the boot volume
 /// 2. Reads and parses the ELF content
 /// 3. Calculates memory requirements for all loadable segments
-/// 4. Allocates memory at the required virtual addresses
-/// 5. Copies loadable segments to their target locations
-/// 6. Returns the kernel entry point address
+/// 4. Allocates memory at the required virtual addresses. A position
+///    independent kernel is placed elsewhere if they are taken
+/// 5. Copies loadable segments to their target locations, applies the
+///    dynamic relocations with [`Relocator`] and checksums the segments
+/// 6. Returns the kernel entry point address, the loaded segments and the
+///    version note
+///
+/// # Arguments
+///
+/// * `path` - Absolute path of the kernel on the boot volume, e.g.
+///   [`KERNEL_PATH`]
 ///
 /// # Returns
 ///
-/// * `Ok(PhysicalAddress)` - The physical address of the kernel entry point
-/// * `Err(_)` - If any step of the loading process fails
+/// * `Ok(LoadedKernel)` - The kernel entry point and the loaded segments
+/// * `Err(BootError)` - If any step of the loading process fails. The error
+///   records which [`BootStage`] failed
 ///
 /// # Errors
 ///
@@ -41,68 +135,307 @@ use oso_no_std_shared::bridge::graphic::FrameBufConf;
 /// - The kernel file cannot be opened or read
 /// - ELF parsing fails (invalid format, unsupported architecture, etc.)
 /// - Memory allocation fails for kernel segments
-/// - File I/O operations fail
-///
-/// # Panics
-///
-/// Panics if ELF parsing fails with an unrecoverable error, as this indicates
-/// a fundamental problem with the kernel file that cannot be resolved.
-pub fn kernel() -> Rslt<PhysicalAddress,> {
+/// - Memory is allocated at an address other than the link address of a
+///   kernel which is not position independent
+/// - A dynamic relocation can not be applied
+/// - File I/O operations fail, or the file is shorter than its size or its
+///   headers
+///
+/// Pressing `Esc` while the file is read aborts loading. The error then has
+/// `aborted` set
+pub fn kernel(path: &str,) -> Rslt<LoadedKernel, BootError,> {
 	// Open and read the kernel ELF file
-	let mut kernel_file = open_kernel_file()?;
-	let contents = unsafe { kernel_file.as_mut() }.read_as_bytes()?;
+	let mut kernel_file = open_file(path,).at(BootStage::KernelOpen,)?;
+	let contents = read_kernel(unsafe { kernel_file.as_mut() },)?;
+
+	// Headers may describe more than the file holds if it was truncated,
+	// which the parser does not expect
+	check_extent(&contents,)?;
 
 	// Parse the ELF file structure
-	let elf = match Elf::parse(&contents,) {
-		Ok(elf,) => elf,
-		Err(e,) => panic!("unrecoverable error: {e:?}"),
-	};
+	let elf = Elf::parse(&contents,).at(BootStage::KernelParse,)?;
+	place(&elf, &contents,)
+}
 
+/// Places the segments of the kernel `elf`, read from `contents`, at its
+/// link address, or anywhere if it is position independent and the link
+/// address is taken, then applies its dynamic relocations
+///
+/// # Errors
+///
+/// Steps 3 to 5 of [`kernel`]
+fn place(elf: &Elf, contents: &[u8],) -> Rslt<LoadedKernel, BootError,> {
 	// Calculate memory requirements for all loadable segments
-	let (head, tail,) = elf_address_range(&elf,);
+	let (head, tail,) = elf_address_range(elf,);
 	let kernel_size = tail - head;
 
-	// Allocate memory for the kernel at the required address
-	let page_count = required_pages(kernel_size,);
+	// Allocate memory for the kernel at the required address. The pages are
+	// freed again if anything below fails
+	let count = required_pages(kernel_size,);
 	let alloc_head = boot_services().allocate_pages(
 		AllocateType::ALLOCATE_ADDRESS,
-		crate::raw::types::memory::MemoryType::LOADER_DATA,
-		page_count,
+		MemoryType::LOADER_DATA,
+		count,
 		head as u64,
-	)?;
+	);
+	// A position independent kernel runs wherever it is placed
+	let (pages, alloc_head,) = match alloc_head {
+		Ok(start,) => (KernelPages { start, count, }, start,),
+		Err(_,) if elf.header.ty == ElfType::SharedObject => {
+			allocate_anywhere(elf, kernel_size,)?
+		},
+		Err(e,) => return Err(e,).at(BootStage::KernelLoad,),
+	};
 
-	println!("----------------------------");
+	debug!("----------------------------");
 
 	// Verify allocation was at the requested address
-	assert_eq!(alloc_head as usize, head);
+	if alloc_head as usize != head && elf.header.ty != ElfType::SharedObject {
+		return Err(oso_err!(BootError {
+			stage: BootStage::KernelLoad,
+			cause: Some("kernel allocated at unexpected address"),
+			..Default::default()
+		}),);
+	}
+
+	// Copy all loadable segments to their target locations, and point
+	// addresses in them at where they were placed
+	let relocator = Relocator::new(elf, head as u64, alloc_head,);
+	let bias = relocator.bias();
+	copy_load_segment(elf, contents, bias,);
+	// SAFETY: the pages were allocated above and the segments copied
+	let image = unsafe {
+		core::slice::from_raw_parts_mut(alloc_head as *mut u8, kernel_size,)
+	};
+	let applied = relocator.apply(image,).at(BootStage::KernelLoad,)?;
+	let checksums = checksum_segments(elf, bias,);
+
+	debug!("head: {head:#x}, tail: {tail:#x}");
+	if applied != 0 {
+		info!("kernel placed at {alloc_head:#x}, {applied} relocations");
+	}
+
+	let segments = elf
+		.program_headers
+		.iter()
+		.filter(|ph| ph.ty == ProgramHeaderType::Load,)
+		.map(|ph| {
+			let start = ph.virtual_address.wrapping_add(bias,);
+			start..start + ph.memory_size
+		},)
+		.collect();
+	let version = elf
+		.program_headers
+		.iter()
+		.filter(|ph| ph.ty == ProgramHeaderType::Note,)
+		.find_map(|ph| {
+			let start = ph.offset as usize;
+			version::find(contents.get(start..start + ph.file_size as usize,)?,)
+		},);
+	Ok(LoadedKernel {
+		entry: (elf.entry_point_address() as u64).wrapping_add(bias,),
+		segments,
+		checksums,
+		version,
+		pages,
+	},)
+}
+
+/// Decides whether the kernel with the version note `kernel` may boot, and
+/// logs the decision
+///
+/// A kernel without a note predates the handshake and is refused, as its
+/// boot information layout is unknown. With `enforce` unset, an incompatible
+/// kernel boots after a warning, e.g. to bisect across a version bump.
+///
+/// # Errors
+///
+/// Fails at [`BootStage::KernelVersion`] if the loader and the kernel are
+/// not compatible
+pub fn check_version(
+	kernel: Option<&KernelVersion,>,
+	enforce: bool,
+) -> Rslt<(), BootError,> {
+	let cause = match kernel {
+		Some(kernel,) => {
+			info!(
+				"loader {LOADER_VERSION} (bridge abi {BRIDGE_ABI}), kernel {} \
+				 (bridge abi {})",
+				kernel.version, kernel.abi
+			);
+			debug!(
+				"loader accepts kernel {KERNEL_VERSIONS}, kernel accepts \
+				 loader {}",
+				kernel.loader
+			);
+			let compatibility =
+				kernel.check(LOADER_VERSION, KERNEL_VERSIONS,);
+			info!("version check: {}", compatibility.as_str());
+			if compatibility.is_compatible() {
+				return Ok((),);
+			}
+			compatibility.as_str()
+		},
+		None => {
+			info!("version check: kernel has no version note");
+			"kernel has no version note"
+		},
+	};
+	if !enforce {
+		info!("version check is disabled. booting anyway");
+		return Ok((),);
+	}
+	Err(oso_err!(BootError {
+		stage: BootStage::KernelVersion,
+		cause: Some(cause),
+		..Default::default()
+	}),)
+}
+
+/// Reads the initial ramdisk at `path`, which is handed to the kernel as a
+/// boot module
+///
+/// # Errors
+///
+/// Fails at [`BootStage::Initrd`] if the file can not be opened or read
+pub fn initrd(path: &str,) -> Rslt<Vec<u8,>, BootError,> {
+	let mut file = open_file(path,).at(BootStage::Initrd,)?;
+	let contents =
+		unsafe { file.as_mut() }.read_as_bytes().at(BootStage::Initrd,)?;
+	info!("initial ramdisk {path}: {} bytes", contents.len());
+	Ok(contents,)
+}
 
-	// Copy all loadable segments to their target locations
-	copy_load_segment(&elf, &contents,);
+/// Reads the kernel file in chunks of [`READ_CHUNK_SIZE`] bytes
+///
+/// # Errors
+///
+/// Besides errors of the file, fails with `short_read` if fewer bytes than
+/// the size in the file information could be read, and with `aborted` if the
+/// user pressed `Esc`
+fn read_kernel(file: &mut FileProtocolV1,) -> Rslt<Vec<u8,>, BootError,> {
+	let size = file.get_file_info().at(BootStage::KernelRead,)?.file_size;
+	let size = size as usize;
+	let mut contents = vec![0; size];
 
-	println!("head: {head:#x}, tail: {tail:#x}");
+	let mut progress = Progress::new("reading kernel", size,);
+	let mut aborted = false;
+	let read = file
+		.read_chunks(&mut contents, READ_CHUNK_SIZE, |read| {
+			progress.update(read,);
+			aborted = escape_pressed();
+			if aborted {
+				return ControlFlow::Break((),);
+			}
+			ControlFlow::Continue((),)
+		},)
+		.at(BootStage::KernelRead,)?;
+	progress.finish();
 
-	Ok(elf.entry_point_address() as u64,)
+	if aborted {
+		return Err(oso_err!(BootError {
+			stage: BootStage::KernelRead,
+			cause: Some("aborted by the user"),
+			aborted: true,
+			..Default::default()
+		}),);
+	}
+	if read != size {
+		return Err(short_read(read, size,),);
+	}
+	debug!("read {read} bytes of kernel");
+	Ok(contents,)
+}
+
+/// Error of a kernel file with `read` bytes where `expected` were needed
+fn short_read(read: usize, expected: usize,) -> OsoError<BootError,> {
+	oso_err!(BootError {
+		stage: BootStage::KernelRead,
+		cause: Some("kernel file ended early"),
+		short_read: Some(ShortRead { read, expected, },),
+		..Default::default()
+	})
 }
 
-/// Opens the kernel ELF file from the filesystem
+/// Fails with [`short_read`] unless `contents` holds the ELF header, the
+/// program header table, the section header table and the contents of the
+/// segments. Called before [`Elf::parse`], which expects all of them
+///
+/// # Errors
+///
+/// - A short read if `contents` ends before any of them
+/// - [`BootStage::KernelParse`] if the ELF header is invalid
+fn check_extent(contents: &[u8],) -> Rslt<(), BootError,> {
+	let check = |expected: u64| {
+		let expected = usize::try_from(expected,).unwrap_or(usize::MAX,);
+		if contents.len() < expected {
+			return Err(short_read(contents.len(), expected,),);
+		}
+		Ok((),)
+	};
+	check(ELF_HEADER_SIZE as u64,)?;
+	let header = ElfHeader::parse(&contents[..ELF_HEADER_SIZE],)
+		.at(BootStage::KernelParse,)?;
+
+	let table = |offset: u64, entry_size: u16, count: u16| {
+		offset.saturating_add(entry_size as u64 * count as u64,)
+	};
+	let program_headers = table(
+		header.program_header_offset,
+		header.program_header_entry_size,
+		header.program_header_count,
+	);
+	check(program_headers,)?;
+	check(table(
+		header.section_header_offset,
+		header.section_header_entry_size,
+		header.section_header_count,
+	),)?;
+
+	// `p_offset` and `p_filesz` of each program header
+	let field = |at: u64| {
+		let at = usize::try_from(at,).ok()?;
+		let bytes = contents.get(at..)?.first_chunk::<8>()?;
+		Some(u64::from_le_bytes(*bytes,),)
+	};
+	let entry_size = header.program_header_entry_size as u64;
+	for i in 0..header.program_header_count as u64 {
+		let ph = header.program_header_offset + i * entry_size;
+		let fields = (field(ph + 8,), field(ph + 32,),);
+		let (Some(offset,), Some(file_size,),) = fields else {
+			return Err(short_read(contents.len(), program_headers as usize,),);
+		};
+		check(offset.saturating_add(file_size,),)?;
+	}
+	Ok((),)
+}
+
+/// Opens a file from the filesystem
 ///
 /// This function locates the simple file system protocol and opens the
-/// kernel file named "oso_kernel.elf" from the root directory.
+/// file at `path` relative to the root directory. `path` may use `/` or `\`
+/// and is normalized before it is passed to firmware.
 ///
 /// # Returns
 ///
-/// * `Ok(NonNull<FileProtocolV1>)` - Handle to the opened kernel file
-/// * `Err(_)` - If file system access or file opening fails
+/// * `Ok(NonNull<FileProtocolV1>)` - Handle to the opened file
+/// * `Err(UefiError)` - If file system access or file opening fails
 ///
 /// # Errors
 ///
 /// This function can fail if:
+/// - The normalized path is longer than [`PATH_MAX`]
 /// - No simple file system protocol is available
 /// - The volume cannot be opened
-/// - The kernel file does not exist or cannot be opened
-fn open_kernel_file() -> Rslt<NonNull<FileProtocolV1,>,> {
+/// - The file does not exist or cannot be opened
+pub fn open_file(path: &str,) -> Rslt<NonNull<FileProtocolV1,>, UefiError,> {
 	let open_mode = OpenMode::READ;
 	let attrs = FileAttributes(0,);
+	let mut path: PathBufN<PATH_MAX,> =
+		PathN::new(path,).normalize().map_err(|_| {
+			oso_err!(UefiError::Custom("path is too long or contains nul"))
+		},)?;
+	path.set_separator(Separator::Backslash,);
 
 	let bs = boot_services();
 
@@ -118,11 +451,10 @@ fn open_kernel_file() -> Rslt<NonNull<FileProtocolV1,>,> {
 	}
 	.open_volume()?;
 
-	// Open the kernel file
-	let kernel_file = volume.open("oso_kernel.elf", open_mode, attrs,)?;
-	let non_null_kernel_file =
-		NonNull::new(kernel_file,).expect("reference can't be null",);
-	Ok(non_null_kernel_file,)
+	// Open the file
+	let file = volume.open(path.as_str(), open_mode, attrs,)?;
+	let non_null_file = NonNull::new(file,).expect("reference can't be null",);
+	Ok(non_null_file,)
 }
 
 /// Calculates the memory address range required for all loadable ELF segments
@@ -164,16 +496,51 @@ fn elf_address_range(elf: &Elf,) -> (usize, usize,) {
 	pair
 }
 
+/// Allocates pages for a position independent kernel of `size` bytes
+/// wherever they are free, keeping the alignment of its segments
+///
+/// # Returns
+///
+/// The pages allocated, and the load address of the lowest link address of
+/// the kernel in them
+fn allocate_anywhere(
+	elf: &Elf,
+	size: usize,
+) -> Rslt<(KernelPages, u64,), BootError,> {
+	let align = elf
+		.program_headers
+		.iter()
+		.filter(|ph| ph.ty == ProgramHeaderType::Load,)
+		.map(|ph| ph.align.max(1,),)
+		.max()
+		.unwrap_or(1,)
+		.next_power_of_two();
+	// room to move the start up to the alignment
+	let slack = (align as usize).saturating_sub(PAGE_SIZE,);
+	let count = required_pages(size + slack,);
+	let start = boot_services()
+		.allocate_pages(
+			AllocateType::ALLOCATE_ANY_PAGES,
+			MemoryType::LOADER_DATA,
+			count,
+			0,
+		)
+		.at(BootStage::KernelLoad,)?;
+	Ok((KernelPages { start, count, }, start.next_multiple_of(align,),),)
+}
+
 /// Copies all loadable ELF segments to their target memory locations
 ///
 /// This function processes each LOAD-type program header and:
-/// 1. Copies the segment data from the ELF file to the target virtual address
+/// 1. Copies the segment data from the ELF file to the target virtual
+///    address moved by `bias`
 /// 2. Zero-fills any remaining memory (typically for .bss sections)
 ///
 /// # Arguments
 ///
 /// * `elf` - Reference to the parsed ELF file containing program headers
 /// * `src` - The raw ELF file content as bytes
+/// * `bias` - Distance from the link address to the load address, wrapping
 ///
 /// # Memory Layout
 ///
@@ -187,7 +554,7 @@ fn elf_address_range(elf: &Elf,) -> (usize, usize,) {
 /// This function uses unsafe operations to write directly to virtual memory
 /// addresses specified in the ELF program headers. The caller must ensure
 /// that the target memory has been properly allocated.
-fn copy_load_segment(elf: &Elf, src: &[u8],) {
+fn copy_load_segment(elf: &Elf, src: &[u8], bias: u64,) {
 	for ph in &elf.program_headers {
 		if ph.ty != ProgramHeaderType::Load {
 			continue;
@@ -197,7 +564,7 @@ fn copy_load_segment(elf: &Elf, src: &[u8],) {
 		let mem_size = ph.memory_size as usize;
 		let dest = unsafe {
 			core::slice::from_raw_parts_mut(
-				ph.virtual_address as *mut u8,
+				ph.virtual_address.wrapping_add(bias,) as *mut u8,
 				mem_size,
 			)
 		};
@@ -212,6 +579,39 @@ fn copy_load_segment(elf: &Elf, src: &[u8],) {
 	}
 }
 
+/// CRC-32 of each loadable segment as the kernel will see it, read back
+/// from where it was placed `bias` away from its link address. Computed
+/// after relocations are applied
+///
+/// # Returns
+///
+/// Checksums of the segments in program header order
+fn checksum_segments(elf: &Elf, bias: u64,) -> Vec<SegmentChecksum,> {
+	let mut checksums = Vec::new();
+	for ph in &elf.program_headers {
+		if ph.ty != ProgramHeaderType::Load {
+			continue;
+		}
+
+		// SAFETY: `copy_load_segment` wrote the segment there
+		let checksum = unsafe {
+			SegmentChecksum::compute(
+				ph.virtual_address.wrapping_add(bias,),
+				ph.memory_size,
+				ph.flags,
+			)
+		};
+		debug!(
+			"segment {:#x}..{:#x}: crc32 {:#010x}",
+			checksum.read_start(),
+			checksum.read_start() + checksum.read_size(),
+			checksum.crc32
+		);
+		checksums.push(checksum,);
+	}
+	checksums
+}
+
 /// Configures graphics output for the kernel
 ///
 /// This function queries the UEFI Graphics Output Protocol to obtain
@@ -260,3 +660,208 @@ pub fn graphic_config() -> Rslt<FrameBufConf,> {
 
 	Ok(fbc,)
 }
+
+/// Switches graphics output to the mode with the given resolution
+///
+/// # Errors
+///
+/// Returns `UefiError::Custom` if no mode has the requested resolution, or the
+/// error of the failing protocol call.
+pub fn set_graphics_mode(width: usize, height: usize,) -> Rslt<(), UefiError,> {
+	let bs = boot_services();
+	let mut gout =
+		bs.open_protocol_with::<GraphicsOutputProtocol>()?.interface();
+	let gout = unsafe { gout.as_mut() };
+
+	let max_mode = gout.mode().max_mode;
+	let mode = (0..max_mode)
+		.find(|i| {
+			gout.query_mode(*i,)
+				.is_ok_and(|info| info.resolution() == (width, height,),)
+		},)
+		.ok_or(oso_err!(UefiError::Custom("no graphics mode matches")),)?;
+
+	gout.set_mode(mode,)?;
+	Ok((),)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::chibi_uefi::mock::Firmware;
+	use crate::chibi_uefi::mock::Service;
+	use crate::elf::Context;
+	use crate::elf::RelocationSection;
+	use crate::elf::program_header::ProgramHeader;
+	use crate::elf::relocation::R_AARCH64_RELATIVE;
+
+	/// link address of the kernels built by [`pie`]
+	const LINK: u64 = 0x4008_0000;
+
+	/// Position independent kernel of one segment linked at [`LINK`], with
+	/// `image` followed by `bss` zeroed bytes, aligned to `align`. Each of
+	/// `relocations` points the word at its offset at its target, both
+	/// relative to [`LINK`]
+	fn pie(
+		image: &[u8],
+		bss: u64,
+		align: u64,
+		relocations: &[(u64, u64,)],
+	) -> Elf {
+		let info = R_AARCH64_RELATIVE as u64;
+		let mut bytes = vec![];
+		for &(offset, target,) in relocations {
+			bytes.extend_from_slice(&(LINK + offset).to_le_bytes(),);
+			bytes.extend_from_slice(&info.to_le_bytes(),);
+			bytes.extend_from_slice(&(LINK + target).to_le_bytes(),);
+		}
+		let end = bytes.len();
+		let context = (true, Context::default(),);
+		let count = relocations.len();
+		let rela = RelocationSection { bytes, count, context, start: 0, end, };
+		let segment = ProgramHeader {
+			ty: ProgramHeaderType::Load,
+			flags: 0b111,
+			offset: 0,
+			virtual_address: LINK,
+			physical_address: LINK,
+			file_size: image.len() as u64,
+			memory_size: image.len() as u64 + bss,
+			align,
+		};
+		Elf {
+			header: ElfHeader {
+				ty: ElfType::SharedObject,
+				machine: ElfHeader::EM_AARCH64,
+				entry: LINK + 0x10,
+				..Default::default()
+			},
+			program_headers: vec![segment],
+			dynamic_relocation_with_addend: rela,
+			..Default::default()
+		}
+	}
+
+	fn pages(count: usize,) -> KernelPages {
+		let start = boot_services()
+			.allocate_pages(
+				AllocateType::ALLOCATE_ANY_PAGES,
+				MemoryType::LOADER_DATA,
+				count,
+				0,
+			)
+			.unwrap();
+		KernelPages { start, count, }
+	}
+
+	#[test]
+	fn test_kernel_pages_are_freed_unless_kept() {
+		let fw = Firmware::install();
+		drop(pages(4,),);
+		assert_eq!(fw.live_pages(), 0);
+		assert_eq!(fw.calls_to(Service::FreePages,), 1);
+
+		pages(2,).keep();
+		assert_eq!(fw.live_pages(), 2);
+		assert_eq!(fw.calls_to(Service::FreePages,), 1);
+	}
+
+	#[test]
+	fn test_allocate_anywhere_keeps_the_alignment() {
+		let fw = Firmware::install();
+		let size = 0x2800;
+		for align in [0, 0x1000, 0x3000, 0x1_0000, 0x20_0000,] {
+			let elf = pie(&[], size as u64, align, &[],);
+			let (pages, load,) = allocate_anywhere(&elf, size,).unwrap();
+			let align = align.max(1,).next_power_of_two();
+			// pages are page aligned, so only the rest of the alignment is
+			// needed to move the start
+			let slack = (align as usize).saturating_sub(PAGE_SIZE,);
+			let count = required_pages(size + slack,);
+			assert_eq!(pages.count, count, "{align:#x}");
+			assert_eq!(load, pages.start.next_multiple_of(align,));
+			let end = pages.start as usize + pages.count * PAGE_SIZE;
+			assert!(load as usize + size <= end, "{align:#x}");
+		}
+		assert_eq!(fw.live_pages(), 0);
+	}
+
+	#[test]
+	fn test_position_independent_kernels_are_placed_anywhere() {
+		let fw = Firmware::install();
+		let mut image = [0xaa; 0x40];
+		image[0x20..0x28].fill(0,);
+		let elf = pie(&image, 0x100, 0x1_0000, &[(0x20, 0x30,)],);
+
+		// the mock can not allocate at the link address
+		let kernel = place(&elf, &image,).unwrap();
+		assert_eq!(fw.calls_to(Service::AllocatePages,), 2);
+		let load = kernel.segments[0].start;
+		assert_ne!(load, LINK);
+		assert_eq!(load % 0x1_0000, 0);
+		assert_eq!(kernel.segments.len(), 1);
+		assert_eq!(kernel.segments[0], load..load + 0x140);
+		assert_eq!(kernel.entry, load + 0x10);
+		assert_eq!(kernel.checksums.len(), 1);
+
+		// SAFETY: the segment was placed there
+		let placed = unsafe {
+			core::slice::from_raw_parts(load as *const u8, 0x140,)
+		};
+		assert_eq!(placed[..0x20], image[..0x20]);
+		let word = u64::from_le_bytes(placed[0x20..0x28].try_into().unwrap(),);
+		assert_eq!(word, load + 0x30, "the relocation moves with the kernel");
+		assert!(placed[0x40..].iter().all(|b| *b == 0));
+
+		drop(kernel,);
+		assert_eq!(fw.live_pages(), 0);
+
+		// a kernel which is not position independent needs its link address
+		let mut elf = elf;
+		elf.header.ty = ElfType::Executable;
+		let error = place(&elf, &image,).err().unwrap().desc.unwrap();
+		assert_eq!(error.stage, BootStage::KernelLoad);
+		assert_eq!(fw.live_pages(), 0);
+	}
+
+	/// ELF file of `0x200` bytes with one segment in its second half
+	fn elf_file() -> Vec<u8,> {
+		let mut file = vec![0; 0x200];
+		file[..8].copy_from_slice(b"\x7fELF\x02\x01\x01\0",);
+		let mut put = |at: usize, bytes: &[u8]| {
+			file[at..at + bytes.len()].copy_from_slice(bytes,);
+		};
+		put(16, &2u16.to_le_bytes(),);
+		put(18, &ElfHeader::EM_AARCH64.to_le_bytes(),);
+		put(20, &1u32.to_le_bytes(),);
+		// program headers right after the header, no section headers
+		put(32, &64u64.to_le_bytes(),);
+		put(52, &64u16.to_le_bytes(),);
+		put(54, &56u16.to_le_bytes(),);
+		put(56, &1u16.to_le_bytes(),);
+		put(58, &64u16.to_le_bytes(),);
+		// PT_LOAD of the bytes from `0x100`
+		put(64, &1u32.to_le_bytes(),);
+		put(64 + 8, &0x100u64.to_le_bytes(),);
+		put(64 + 32, &0x100u64.to_le_bytes(),);
+		file
+	}
+
+	#[test]
+	fn test_truncated_kernels_are_short_reads() {
+		let file = elf_file();
+		assert!(check_extent(&file).is_ok());
+		let cuts = [(0, 64,), (10, 64,), (100, 120,), (0x1ff, 0x200,),];
+		for (read, expected,) in cuts {
+			let error = check_extent(&file[..read],).unwrap_err().desc.unwrap();
+			assert_eq!(error.stage, BootStage::KernelRead);
+			let short_read = Some(ShortRead { read, expected, },);
+			assert_eq!(error.short_read, short_read, "{read:#x}");
+		}
+
+		let mut bad = file.clone();
+		bad[0] = 0;
+		let error = check_extent(&bad,).unwrap_err().desc.unwrap();
+		assert_eq!(error.stage, BootStage::KernelParse);
+	}
+}
@@ -10,15 +10,46 @@
 extern crate alloc;
 
 use oso_error::Rslt;
+use oso_error::loader::BootError;
+use oso_error::loader::BootStage;
+use oso_loader::chibi_uefi::console::read_key_timeout;
+use oso_loader::chibi_uefi::console::Verbosity;
+use oso_loader::chibi_uefi::console::set_verbosity;
+use oso_loader::chibi_uefi::console::verbosity;
+use oso_loader::chibi_uefi::image::loaded_image;
+use oso_loader::chibi_uefi::protocol::dump_handles;
+use oso_loader::chibi_uefi::runtime::VirtualLayout;
+use oso_loader::chibi_uefi::serial;
 use oso_loader::chibi_uefi::service::exit_boot_services;
+use oso_loader::chibi_uefi::table::boot_services;
+use oso_loader::chibi_uefi::table::runtime_services;
+use oso_loader::config::LoaderConfig;
+use oso_loader::debug;
+use oso_loader::error_screen;
+use oso_loader::error_screen::AtStage;
+use oso_loader::error_screen::Choice;
+use oso_loader::error_screen::ErrorReport;
 use oso_loader::exec_kernel;
 use oso_loader::get_device_tree;
+use oso_loader::handoff::Handoff;
+use oso_loader::info;
 use oso_loader::init;
+use oso_loader::load::KERNEL_PATH;
+use oso_loader::load::check_version;
+use oso_loader::load::graphic_config;
+use oso_loader::load::initrd;
 use oso_loader::load::kernel;
+use oso_loader::load::set_graphics_mode;
+use oso_loader::memmap;
+use oso_loader::overlay::apply_files as apply_overlays;
+use oso_loader::print;
+use oso_loader::println;
 use oso_loader::raw::table::SystemTable;
 use oso_loader::raw::types::Status;
 use oso_loader::raw::types::UnsafeHandle;
-use oso_no_std_shared::bridge::device_tree::DeviceTreeAddress;
+use oso_loader::raw::types::misc::ResetType;
+use oso_loader::raw::types::text::InputKey;
+use oso_loader::set_panic_policy;
 
 /// UEFI application entry point
 ///
@@ -29,10 +60,16 @@ use oso_no_std_shared::bridge::device_tree::DeviceTreeAddress;
 /// # Boot Sequence
 ///
 /// 1. **Initialization**: Set up UEFI services and connect devices
-/// 2. **Kernel Loading**: Load and parse the ELF kernel from filesystem
-/// 3. **Device Tree**: Retrieve hardware configuration information
-/// 4. **Boot Services Exit**: Transition from boot-time to runtime environment
-/// 5. **Kernel Execution**: Transfer control to the loaded kernel
+/// 2. **Configuration**: Read the loader configuration and wait for the boot
+///    timeout
+/// 3. **Kernel Loading**: Load and parse the ELF kernel from filesystem, and
+///    read the initial ramdisk
+/// 4. **Device Tree**: Retrieve hardware configuration information
+/// 5. **Boot Services Exit**: Transition from boot-time to runtime environment
+/// 6. **Runtime Services**: Blank the framebuffer handed to the kernel,
+///    switch runtime services into virtual mode and complete boot
+///    information
+/// 7. **Kernel Execution**: Transfer control to the loaded kernel
 ///
 /// # Arguments
 ///
@@ -42,11 +79,15 @@ use oso_no_std_shared::bridge::device_tree::DeviceTreeAddress;
 /// # Returns
 ///
 /// * `Status::EFI_SUCCESS` - Boot completed successfully (should not return)
+/// * `Status::EFI_ABORTED` - The user chose to return to the firmware boot
+///   menu on the error screen, during the boot timeout or while the kernel
+///   was read
 ///
-/// # Panics
+/// # Errors
 ///
-/// Panics if any critical boot operation fails, as recovery is not possible
-/// in the bootloader context.
+/// If any boot step before exiting boot services fails, an error screen
+/// describing the failure is shown. The user may retry the boot sequence,
+/// return to the firmware boot menu or reboot.
 ///
 /// # Safety
 ///
@@ -60,50 +101,207 @@ pub extern "efiapi" fn efi_image_entry_point(
 	// Initialize UEFI environment and connect devices
 	init(image_handle, system_table,);
 
+	// Read loader configuration
+	let config = match recover(LoaderConfig::load, KERNEL_PATH,) {
+		Ok(config,) => config,
+		Err(status,) => return status,
+	};
+	set_panic_policy(config.panic_policy,);
+	set_verbosity(config.verbosity,);
+	// The console still shows the log without a serial port
+	if serial::start(config.serial_verbosity,).is_err() {
+		info!("serial port is unavailable");
+	}
+
+	// Give the user a chance to return to the firmware boot menu
+	if !countdown(config.timeout,) {
+		return Status::EFI_ABORTED;
+	}
+
 	// Load kernel and prepare for execution
-	let (kernel_entry, device_tree_ptr,) =
-		app().expect("error arise while executing application",);
+	let (kernel_entry, handoff,) =
+		match recover(|| app(&config,), &config.kernel_path,) {
+			Ok(prepared,) => prepared,
+			Err(status,) => return status,
+		};
 
 	// Exit UEFI boot services - point of no return
-	exit_boot_services();
+	let memory_map = exit_boot_services();
+
+	// Enter virtual mode and complete boot information
+	let boot_info = handoff.finish(memory_map,);
 
 	// Transfer control to kernel
-	exec_kernel(kernel_entry, device_tree_ptr,);
+	exec_kernel(kernel_entry, boot_info,);
 
 	// Should never reach here
 	Status::EFI_SUCCESS
 }
 
+/// Runs `step` until it succeeds, showing the error screen on each failure
+///
+/// An error the user aborted with returns to the boot menu without the error
+/// screen.
+///
+/// # Returns
+///
+/// * `Ok(T)` - Result of the successful run
+/// * `Err(Status)` - Status to return to firmware when the user chose the boot
+///   menu
+fn recover<T,>(
+	mut step: impl FnMut() -> Rslt<T, BootError,>,
+	kernel_path: &str,
+) -> Result<T, Status,> {
+	loop {
+		let e = match step() {
+			Ok(t,) => return Ok(t,),
+			Err(e,) => e,
+		};
+		// The user already chose to leave
+		if e.desc.as_ref().is_some_and(|desc| desc.aborted,) {
+			return Err(Status::EFI_ABORTED,);
+		}
+		match error_screen::show(&ErrorReport::new(&e, kernel_path,),) {
+			Choice::Retry => continue,
+			Choice::BootMenu => return Err(Status::EFI_ABORTED,),
+			Choice::Reboot => runtime_services().reset(
+				ResetType::COLD,
+				Status::EFI_SUCCESS,
+				None,
+			),
+		}
+	}
+}
+
+/// Counts down `seconds` before booting
+///
+/// Any key boots immediately, `Esc` cancels booting.
+///
+/// # Returns
+///
+/// `false` if the user cancelled booting
+fn countdown(seconds: u64,) -> bool {
+	for remaining in (1..=seconds).rev() {
+		print!("\rbooting in {remaining}s. any key: boot now, esc: boot menu ");
+		if let Some(key,) = read_key_timeout(1000,) {
+			println!();
+			return key.scan_code != InputKey::SCAN_ESC;
+		}
+	}
+	if seconds != 0 {
+		println!();
+	}
+	true
+}
+
 /// Main application logic for the bootloader
 ///
 /// This function encapsulates the core bootloader functionality:
-/// - Loading the kernel ELF file from the filesystem
+/// - Applying the graphics mode of the configuration
+/// - Reporting the loader image location
+/// - Loading the kernel ELF file and the initial ramdisk from the filesystem
 /// - Retrieving the device tree configuration
-/// - Preparing parameters for kernel execution
+/// - Preparing boot information for kernel execution
+/// - Checking the memory map against the kernel and the framebuffer
 ///
 /// # Returns
 ///
-/// * `Ok((u64, DeviceTreeAddress))` - Tuple containing:
+/// * `Ok((u64, Handoff))` - Tuple containing:
 ///   - Kernel entry point address
-///   - Device tree pointer for kernel initialization
-/// * `Err(_)` - If kernel loading or device tree retrieval fails
+///   - Boot information to be completed after exiting boot services
+/// * `Err(BootError)` - If kernel loading or device tree retrieval fails. The
+///   error records which [`BootStage`] failed
 ///
 /// # Errors
 ///
 /// This function can fail if:
+/// - The loaded image protocol of the loader cannot be opened
 /// - The kernel file cannot be found or loaded
+/// - The initial ramdisk cannot be read
 /// - The ELF parsing fails
 /// - Memory allocation for kernel loading fails
 /// - Device tree cannot be retrieved from UEFI
-fn app() -> Rslt<(u64, DeviceTreeAddress,),> {
+/// - A device tree overlay cannot be read or applied
+/// - The memory map is inconsistent, see [`memmap::check`]
+fn app(config: &LoaderConfig,) -> Rslt<(u64, Handoff,), BootError,> {
+	// Failing to switch graphics mode is not fatal: firmware's mode still works
+	if let Some((width, height,),) = config.graphics_mode
+		&& set_graphics_mode(width, height,).is_err()
+	{
+		info!("graphics mode {width}x{height} is unavailable");
+	}
+
+	// Report where the loader itself lives
+	let image = loaded_image().at(BootStage::LoaderImage,)?;
+	info!("loader image: {image}");
+	// Listing handles is only a diagnostic, so a failure is not fatal
+	if verbosity() >= Verbosity::Debug
+		&& let Ok(handles,) = dump_handles()
+	{
+		handles.iter().for_each(|line| debug!("{line}"),);
+	}
+
 	// Load kernel ELF file and get entry point
-	let kernel_addr = kernel()?;
+	let kernel = kernel(&config.kernel_path,)?;
+	check_version(kernel.version.as_ref(), config.check_version,)?;
+	let initrd = match &config.initrd {
+		Some(path,) => Some((path.clone(), initrd(path,)?,),),
+		None => None,
+	};
 
 	// Get device tree configuration for kernel
-	let device_tree = get_device_tree()?;
+	let device_tree = get_device_tree().at(BootStage::DeviceTree,)?;
 
 	// Convert device tree pointer for kernel handoff
-	let device_tree_ptr = device_tree.as_ptr().cast_const().cast();
+	let mut device_tree_ptr =
+		unsafe { device_tree.as_ref() }.vendor_table().cast_const().cast();
+	if !config.overlays.is_empty() {
+		// SAFETY: firmware publishes a blob under the device tree guid
+		device_tree_ptr =
+			unsafe { apply_overlays(device_tree_ptr, &config.overlays,) }?;
+	}
+
+	// The mode is final from here, and the kernel owns the framebuffer after
+	// handoff. Without pixel access the kernel can not draw on it
+	let framebuffer = graphic_config()
+		.ok()
+		.filter(|fb| fb.pixel_format.supports_pixel_access() && fb.size != 0,);
+	let framebuffer_range = framebuffer
+		.as_ref()
+		.map(|fb| fb.base as u64..fb.base as u64 + fb.size as u64,);
+	match &framebuffer {
+		Some(fb,) => info!(
+			"framebuffer {}x{} at {:#x} handed to the kernel",
+			fb.width, fb.height, fb.base as u64
+		),
+		None => info!("no framebuffer for the kernel"),
+	}
+
+	// Reserve boot information. Kernel runs with MMU disabled, so runtime
+	// services are identity mapped
+	let handoff = Handoff::new(
+		device_tree_ptr,
+		VirtualLayout::Identity,
+		image.range(),
+		&config.cmdline,
+		&kernel.checksums,
+		initrd,
+		framebuffer,
+	)
+	.at(BootStage::Handoff,)?;
+
+	// Catch layout bugs while the error screen is still available. Nothing
+	// but the final memory map is allocated afterwards
+	let map = boot_services()
+		.memory_map_snapshot()
+		.at(BootStage::MemoryMap,)?;
+	if verbosity() >= Verbosity::Debug {
+		memmap::print_table(&map,);
+	}
+	memmap::check(&map, &kernel.segments, framebuffer_range,)?;
 
-	Ok((kernel_addr, device_tree_ptr,),)
+	// Up to here, an error frees the kernel and the boot information, so a
+	// retry starts over
+	kernel.pages.keep();
+	Ok((kernel.entry, handoff,),)
 }
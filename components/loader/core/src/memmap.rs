@@ -0,0 +1,264 @@
+//! # Memory Map Checks
+//!
+//! Layout bugs of the loader, such as a kernel segment outside of loader
+//! memory, would only surface as obscure faults in the kernel. This module
+//! checks the memory map right before boot services are exited and reports
+//! violations on the error screen instead.
+//!
+//! [`print_table`] logs the map, merging adjacent descriptors of the same
+//! type, at [`Verbosity::Debug`](crate::chibi_uefi::console::Verbosity).
+
+use crate::debug;
+use crate::info;
+use crate::raw::types::memory::MemoryDescriptor;
+use crate::raw::types::memory::MemoryType;
+use crate::raw::types::memory::PAGE_SIZE;
+use alloc::vec::Vec;
+use core::ops::Range;
+use oso_error::Rslt;
+use oso_error::loader::BootError;
+use oso_error::loader::BootStage;
+use oso_error::oso_err;
+use oso_no_std_shared::units::ByteSize;
+use oso_no_std_shared::units::PhysAddr;
+
+/// Inconsistency between the memory map and what is handed to the kernel
+#[derive(Clone, Debug, PartialEq, Eq,)]
+pub enum Violation {
+	/// descriptors at these indices share memory
+	Overlap(usize, usize,),
+	/// part of a kernel segment is not memory the loader allocated
+	KernelOutside(Range<u64,>,),
+	/// part of the framebuffer is memory the kernel may allocate
+	FramebufferInRam(Range<u64,>,),
+}
+
+impl Violation {
+	fn cause(&self,) -> &'static str {
+		match self {
+			Self::Overlap(..,) => "memory map descriptors overlap",
+			Self::KernelOutside(_,) => "kernel segment outside loader memory",
+			Self::FramebufferInRam(_,) => {
+				"framebuffer overlaps memory handed to the kernel"
+			},
+		}
+	}
+}
+
+/// Checks `map` against the kernel `segments` and the `framebuffer`
+///
+/// # Errors
+///
+/// Fails at [`BootStage::MemoryMap`] with the cause of the first violation.
+/// Every violation is logged.
+pub fn check(
+	map: &[MemoryDescriptor],
+	segments: &[Range<u64,>],
+	framebuffer: Option<Range<u64,>,>,
+) -> Rslt<(), BootError,> {
+	let violations = violations(map, segments, framebuffer,);
+	for violation in &violations {
+		info!("memory map: {violation:x?}");
+	}
+	match violations.first() {
+		None => Ok((),),
+		Some(violation,) => Err(oso_err!(BootError {
+			stage: BootStage::MemoryMap,
+			cause: Some(violation.cause()),
+			..Default::default()
+		}),),
+	}
+}
+
+/// Every violation of `map`, see [`check`]
+pub fn violations(
+	map: &[MemoryDescriptor],
+	segments: &[Range<u64,>],
+	framebuffer: Option<Range<u64,>,>,
+) -> Vec<Violation,> {
+	let mut sorted: Vec<(usize, Range<u64,>,),> =
+		map.iter().map(range,).enumerate().collect();
+	sorted.sort_by_key(|(_, range,)| range.start,);
+
+	let mut violations = Vec::new();
+	// descriptor reaching farthest so far, which overlaps any later one
+	// starting before its end
+	let mut farthest: Option<(usize, u64,),> = None;
+	for (i, range,) in &sorted {
+		if let Some((j, end,),) = farthest
+			&& end > range.start
+		{
+			violations.push(Violation::Overlap(j, *i,),);
+		}
+		if farthest.is_none_or(|(_, end,)| range.end > end,) {
+			farthest = Some((*i, range.end,),);
+		}
+	}
+
+	// free memory is handed to the kernel, which would overwrite itself
+	let loadable = |desc: &MemoryDescriptor| {
+		matches!(
+			desc.memory_type,
+			MemoryType::LOADER_CODE | MemoryType::LOADER_DATA
+		)
+	};
+	for segment in segments {
+		let segment = page_align(segment,);
+		let gaps = uncovered(map, &segment, loadable,);
+		violations.extend(gaps.into_iter().map(Violation::KernelOutside,),);
+	}
+
+	if let Some(framebuffer,) = framebuffer {
+		violations.extend(
+			map.iter()
+				.filter(|desc| is_ram(desc.memory_type,),)
+				.filter_map(|desc| intersection(&range(desc,), &framebuffer,),)
+				.map(Violation::FramebufferInRam,),
+		);
+	}
+	violations
+}
+
+/// Logs `map` as a table, merging contiguous descriptors of the same type
+pub fn print_table(map: &[MemoryDescriptor],) {
+	let mut sorted: Vec<&MemoryDescriptor,> = map.iter().collect();
+	sorted.sort_by_key(|desc| desc.physical_start,);
+
+	debug!("{:>18} {:>18} {:>10} type", "start", "end", "pages");
+	let mut rows = sorted.into_iter().peekable();
+	while let Some(first,) = rows.next() {
+		let mut range = range(first,);
+		while let Some(next,) = rows.next_if(|next| {
+			next.memory_type == first.memory_type
+				&& next.physical_start == range.end
+		},) {
+			range.end = self::range(next,).end;
+		}
+		let pages = ByteSize::new(range.end - range.start,);
+		let pages = pages.pages(PAGE_SIZE as u64,);
+		debug!(
+			"{:#018x} {:#018x} {pages:>10} {:?}",
+			range.start, range.end, first.memory_type
+		);
+	}
+}
+
+/// memory the kernel may use as ram after handoff
+fn is_ram(memory_type: MemoryType,) -> bool {
+	matches!(
+		memory_type,
+		MemoryType::CONVENTIONAL
+			| MemoryType::LOADER_CODE
+			| MemoryType::LOADER_DATA
+			| MemoryType::BOOT_SERVICES_CODE
+			| MemoryType::BOOT_SERVICES_DATA
+	)
+}
+
+/// memory of `desc`, cut at the end of the address space for descriptors
+/// which claim more
+fn range(desc: &MemoryDescriptor,) -> Range<u64,> {
+	let start = PhysAddr::new(desc.physical_start,);
+	let end = ByteSize::from_pages(desc.page_count, PAGE_SIZE as u64,)
+		.and_then(|size| start.checked_add(size.bytes(),),)
+		.map_or(u64::MAX, u64::from,);
+	start.as_u64()..end
+}
+
+fn page_align(range: &Range<u64,>,) -> Range<u64,> {
+	let start = PhysAddr::new(range.start,);
+	match PhysAddr::page_span(start, range.end.into(),) {
+		Some(pages,) => pages.start.as_u64()..pages.end.as_u64(),
+		None => start.align_down(PAGE_SIZE as u64,).as_u64()..u64::MAX,
+	}
+}
+
+fn intersection(a: &Range<u64,>, b: &Range<u64,>,) -> Option<Range<u64,>,> {
+	let range = a.start.max(b.start,)..a.end.min(b.end,);
+	(!range.is_empty()).then_some(range,)
+}
+
+/// parts of `target` not covered by descriptors satisfying `accept`
+fn uncovered(
+	map: &[MemoryDescriptor],
+	target: &Range<u64,>,
+	accept: impl Fn(&MemoryDescriptor,) -> bool,
+) -> Vec<Range<u64,>,> {
+	let mut covered: Vec<Range<u64,>,> = map
+		.iter()
+		.filter(|desc| accept(desc,),)
+		.filter_map(|desc| intersection(&range(desc,), target,),)
+		.collect();
+	covered.sort_by_key(|range| range.start,);
+
+	let mut gaps = Vec::new();
+	let mut cursor = target.start;
+	for range in covered {
+		if cursor < range.start {
+			gaps.push(cursor..range.start,);
+		}
+		cursor = cursor.max(range.end,);
+	}
+	if cursor < target.end {
+		gaps.push(cursor..target.end,);
+	}
+	gaps
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::raw::types::memory::MemoryAttribute;
+	use alloc::vec;
+
+	fn descriptor(
+		memory_type: MemoryType,
+		physical_start: u64,
+		page_count: u64,
+	) -> MemoryDescriptor {
+		MemoryDescriptor {
+			memory_type,
+			physical_start,
+			virtual_start: 0,
+			page_count,
+			attribute: MemoryAttribute(0,),
+		}
+	}
+
+	fn sample_map() -> [MemoryDescriptor; 3] {
+		[
+			descriptor(MemoryType::CONVENTIONAL, 0, 0x100,),
+			descriptor(MemoryType::LOADER_DATA, 0x10_0000, 0x100,),
+			descriptor(MemoryType::CONVENTIONAL, 0x20_0000, 0x1000,),
+		]
+	}
+
+	#[test]
+	fn test_kernel_segments() {
+		let map = sample_map();
+		let inside = 0x10_0800..0x18_0000;
+		assert_eq!(violations(&map, &[inside,], None,), vec![]);
+
+		let straddling = 0x1f_f000..0x20_2000;
+		let outside = Violation::KernelOutside(0x20_0000..0x20_2000,);
+		assert_eq!(violations(&map, &[straddling,], None,), vec![outside]);
+
+		let conventional = 0x30_0000..0x30_0100;
+		let outside = Violation::KernelOutside(0x30_0000..0x30_1000,);
+		assert_eq!(violations(&map, &[conventional,], None,), vec![outside]);
+	}
+
+	#[test]
+	fn test_overlap_and_framebuffer() {
+		let mut map = sample_map().to_vec();
+		map.push(descriptor(MemoryType::MMIO, 0x1f_0000, 0x20,),);
+		let framebuffer = Some(0x8000_0000..0x8010_0000,);
+		let overlaps =
+			vec![Violation::Overlap(1, 3,), Violation::Overlap(3, 2,)];
+		assert_eq!(violations(&map, &[], framebuffer,), overlaps);
+
+		let framebuffer = Some(0x11f_f000..0x120_1000,);
+		let ram = Violation::FramebufferInRam(0x11f_f000..0x120_0000,);
+		assert_eq!(violations(&sample_map(), &[], framebuffer,), vec![ram]);
+	}
+}
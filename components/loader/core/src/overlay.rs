@@ -0,0 +1,666 @@
+//! # Device Tree Overlay Module
+//!
+//! This module applies device tree overlays (`.dtbo`) onto the device tree of
+//! firmware before it is handed to the kernel. Peripherals of a board, such
+//! as extra UARTs or GPIO devices, are then described without rebuilding the
+//! firmware or the loader.
+//!
+//! Overlays are listed in `[overlays]` section of the loader configuration
+//! and applied in the order of the file. They are expected as `dtc -@` emits
+//! them for `/plugin/;` sources:
+//!
+//! - `/fragment@N` nodes with a `target` phandle or a `target-path`, and an
+//!   `__overlay__` child whose properties and children are merged into the
+//!   target. Properties of the overlay replace those of the same name
+//! - `__fixups__`, listing where the overlay refers to labels of the base
+//!   tree. Labels are resolved through `__symbols__` of the base tree
+//! - `__local_fixups__`, listing where the overlay refers to its own
+//!   phandles. Those are renumbered above the phandles of the base tree
+//!
+//! Labels of the overlay are added to `__symbols__` of the merged tree, so a
+//! later overlay may refer to nodes an earlier one added.
+//!
+//! ## Current Status
+//!
+//! Overlays are only read from the boot volume. The loader does not load an
+//! initial ramdisk yet, so overlays packed into one are not found.
+//!
+//! ```rust,ignore
+//! let mut tree = Tree::parse(base,)?;
+//! tree.apply(&overlay,)?;
+//! let merged = tree.to_blob();
+//! ```
+
+use crate::Rslt;
+use crate::error_screen::AtStage;
+use crate::info;
+use crate::load::open_file;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use oso_error::loader::BootError;
+use oso_error::loader::BootStage;
+use oso_error::loader::OverlayError;
+use oso_error::oso_err;
+use oso_no_std_shared::bridge::device_tree::DeviceTreeAddress;
+use oso_no_std_shared::bridge::device_tree::Fdt;
+use oso_no_std_shared::bridge::device_tree::be32;
+use oso_no_std_shared::bridge::device_tree::be64;
+use oso_no_std_shared::bridge::device_tree::c_str;
+use oso_no_std_shared::bridge::device_tree::name_matches;
+use oso_no_std_shared::bridge::device_tree::strings;
+use oso_no_std_shared::bridge::device_tree::token;
+
+/// Version of the blobs written by [`Tree::to_blob`]
+const VERSION: u32 = 17;
+/// Oldest version readers of [`VERSION`] blobs must support
+const LAST_COMPATIBLE_VERSION: u32 = 16;
+const HEADER_SIZE: usize = 40;
+
+/// Applies the overlays at `paths` onto the blob at `base`
+///
+/// Returns the address of the merged blob, which is never freed so the
+/// kernel can read it. `base` itself is left untouched.
+///
+/// # Errors
+///
+/// Fails at [`BootStage::Overlay`] if an overlay can not be read or applied,
+/// and at [`BootStage::DeviceTree`] if `base` is not a valid blob.
+///
+/// # Safety
+///
+/// `base` must point to a device tree blob
+pub unsafe fn apply_files(
+	base: DeviceTreeAddress,
+	paths: &[String],
+) -> Rslt<DeviceTreeAddress, BootError,> {
+	if unsafe { Fdt::from_addr(base,) }.is_none() {
+		let error = oso_err!(OverlayError::Malformed);
+		return Err(error,).at(BootStage::DeviceTree,);
+	}
+	// the header was checked, so `totalsize` bytes are part of the blob
+	let size = unsafe { base.add(4,).cast::<u32>().read_unaligned() };
+	let blob = unsafe {
+		core::slice::from_raw_parts(base, u32::from_be(size,) as usize,)
+	};
+	let mut tree = Tree::parse(blob,).at(BootStage::DeviceTree,)?;
+
+	for path in paths {
+		let mut file = open_file(path,).at(BootStage::Overlay,)?;
+		let overlay = unsafe { file.as_mut() }
+			.read_as_bytes()
+			.at(BootStage::Overlay,)?;
+		tree.apply(&overlay,).at(BootStage::Overlay,)?;
+		info!("device tree overlay {path} applied");
+	}
+
+	Ok(tree.to_blob().leak().as_ptr(),)
+}
+
+/// Device tree which can be modified, unlike [`Fdt`]
+#[derive(Debug, Clone, PartialEq, Eq,)]
+pub struct Tree {
+	pub root:         TreeNode,
+	/// `(address, size)` of each memory reservation
+	pub reservations: Vec<(u64, u64,),>,
+	/// physical id of the boot cpu
+	pub boot_cpu:     u32,
+}
+
+/// Node of a [`Tree`]
+#[derive(Debug, Clone, Default, PartialEq, Eq,)]
+pub struct TreeNode {
+	/// name including the unit address, empty for the root
+	pub name:       String,
+	pub properties: Vec<(String, Vec<u8,>,),>,
+	pub children:   Vec<TreeNode,>,
+}
+
+impl Tree {
+	/// Reads a flattened device tree blob
+	pub fn parse(blob: &[u8],) -> Rslt<Self, OverlayError,> {
+		let malformed = || oso_err!(OverlayError::Malformed);
+		let fdt = Fdt::new(blob,).ok_or_else(malformed,)?;
+
+		// ancestors of the current node, the root first
+		let mut stack: Vec<TreeNode,> = Vec::new();
+		for node in fdt.nodes() {
+			if node.depth == 0 && !stack.is_empty() {
+				return Err(malformed(),);
+			}
+			close(&mut stack, node.depth,);
+			stack.push(TreeNode {
+				name:       node.name.into(),
+				properties: node
+					.properties()
+					.map(|(name, value,)| (name.into(), value.into(),),)
+					.collect(),
+				children:   Vec::new(),
+			},);
+		}
+		close(&mut stack, 1,);
+		let root = stack.pop().ok_or_else(malformed,)?;
+
+		let mut reservations = Vec::new();
+		let mut offset = be32(blob, 16,).ok_or_else(malformed,)? as usize;
+		loop {
+			let address = be64(blob, offset,).ok_or_else(malformed,)?;
+			let size = be64(blob, offset + 8,).ok_or_else(malformed,)?;
+			if address == 0 && size == 0 {
+				break;
+			}
+			reservations.push((address, size,),);
+			offset += 16;
+		}
+		let boot_cpu = be32(blob, 28,).ok_or_else(malformed,)?;
+
+		Ok(Self { root, reservations, boot_cpu, },)
+	}
+
+	/// Writes the tree as a flattened device tree blob
+	pub fn to_blob(&self,) -> Vec<u8,> {
+		let mut structure = Vec::new();
+		let mut strings = Vec::new();
+		let mut string_offsets = BTreeMap::new();
+		self.root.write(&mut structure, &mut strings, &mut string_offsets,);
+		push_be32(&mut structure, token::END,);
+
+		let reservations = HEADER_SIZE;
+		// terminated by an empty entry
+		let structure_offset =
+			reservations + (self.reservations.len() + 1) * 16;
+		let strings_offset = structure_offset + structure.len();
+		let total_size = strings_offset + strings.len();
+
+		let mut blob = Vec::with_capacity(total_size,);
+		for field in [
+			Fdt::MAGIC,
+			total_size as u32,
+			structure_offset as u32,
+			strings_offset as u32,
+			reservations as u32,
+			VERSION,
+			LAST_COMPATIBLE_VERSION,
+			self.boot_cpu,
+			strings.len() as u32,
+			structure.len() as u32,
+		] {
+			push_be32(&mut blob, field,);
+		}
+		for (address, size,) in self.reservations.iter().chain([&(0, 0,),],) {
+			blob.extend_from_slice(&address.to_be_bytes(),);
+			blob.extend_from_slice(&size.to_be_bytes(),);
+		}
+		blob.extend_from_slice(&structure,);
+		blob.extend_from_slice(&strings,);
+		blob
+	}
+
+	/// Applies the overlay blob `overlay` onto this tree
+	///
+	/// # Errors
+	///
+	/// The tree is left partially modified on failure.
+	pub fn apply(&mut self, overlay: &[u8],) -> Rslt<(), OverlayError,> {
+		let mut overlay = Self::parse(overlay,)?.root;
+
+		// references to labels of the base tree
+		if let Some(fixups,) = overlay.take_child("__fixups__",) {
+			for (label, value,) in &fixups.properties {
+				let phandle = self.label_phandle(label,)?;
+				for fixup in strings(value,) {
+					write_fixup(&mut overlay, fixup, phandle,)?;
+				}
+			}
+		}
+
+		// phandles of the overlay must not collide with those of the base
+		let delta = self.root.max_phandle();
+		overlay.shift_phandles(delta,)?;
+		if let Some(local_fixups,) = overlay.take_child("__local_fixups__",) {
+			overlay.shift_references(&local_fixups, delta,)?;
+		}
+
+		let symbols = overlay.take_child("__symbols__",);
+		// path of each fragment in the overlay and of its target in the base
+		let mut targets = Vec::new();
+		for mut fragment in overlay.children {
+			let Some(body,) = fragment.take_child("__overlay__",) else {
+				continue;
+			};
+			let path = self.target_path(&fragment,)?;
+			self.root
+				.find_mut(&path,)
+				.ok_or(oso_err!(OverlayError::TargetNotFound),)?
+				.merge(body,);
+			targets.push((format!("/{}/__overlay__", fragment.name), path,),);
+		}
+
+		if let Some(symbols,) = symbols {
+			self.add_symbols(symbols, &targets,);
+		}
+		Ok((),)
+	}
+
+	/// Phandle of the node `label` refers to in `__symbols__`, which is given
+	/// one if it has none
+	fn label_phandle(&mut self, label: &str,) -> Rslt<u32, OverlayError,> {
+		let unresolved = || oso_err!(OverlayError::UnresolvedSymbol);
+		let path: String = self
+			.root
+			.child("__symbols__",)
+			.and_then(|symbols| symbols.property(label,),)
+			.and_then(c_str,)
+			.ok_or_else(unresolved,)?
+			.into();
+		let next = self.root.max_phandle() + 1;
+		let node = self.root.find_mut(&path,).ok_or_else(unresolved,)?;
+		Ok(match node.phandle() {
+			Some(phandle,) => phandle,
+			None => {
+				node.set_property("phandle", next.to_be_bytes().into(),);
+				next
+			},
+		},)
+	}
+
+	/// Path of the node `fragment` applies to
+	fn target_path(&self, fragment: &TreeNode,) -> Rslt<String, OverlayError,> {
+		let not_found = || oso_err!(OverlayError::TargetNotFound);
+		if let Some(target,) = fragment.property("target",) {
+			let phandle = be32(target, 0,).ok_or_else(not_found,)?;
+			return self.root.path_of(phandle,).ok_or_else(not_found,);
+		}
+		let path = fragment
+			.property("target-path",)
+			.ok_or(oso_err!(OverlayError::MissingTarget),)?;
+		c_str(path,).map(String::from,).ok_or_else(not_found,)
+	}
+
+	/// Adds labels of an overlay, pointing into the base tree where the
+	/// fragments were merged
+	fn add_symbols(
+		&mut self,
+		symbols: TreeNode,
+		targets: &[(String, String,)],
+	) {
+		if self.root.child("__symbols__",).is_none() {
+			self.root.children.push(TreeNode {
+				name: "__symbols__".into(),
+				..Default::default()
+			},);
+		}
+		let Some(base,) = self.root.child_mut("__symbols__",) else {
+			return;
+		};
+		for (label, value,) in symbols.properties {
+			let Some(path,) = c_str(&value,) else {
+				continue;
+			};
+			// labels outside of fragments do not exist in the merged tree
+			let Some((rest, target,),) =
+				targets.iter().find_map(|(fragment, target,)| {
+					Some((path.strip_prefix(fragment.as_str(),)?, target,),)
+				},)
+			else {
+				continue;
+			};
+			let mut merged = String::from(target.trim_end_matches('/',),);
+			merged.push_str(rest,);
+			if merged.is_empty() {
+				merged.push('/',);
+			}
+			merged.push('\0',);
+			base.set_property(&label, merged.into_bytes(),);
+		}
+	}
+}
+
+impl TreeNode {
+	pub fn property(&self, name: &str,) -> Option<&[u8],> {
+		self.properties
+			.iter()
+			.find(|(n, _,)| n == name,)
+			.map(|(_, value,)| value.as_slice(),)
+	}
+
+	pub fn property_mut(&mut self, name: &str,) -> Option<&mut Vec<u8,>,> {
+		self.properties
+			.iter_mut()
+			.find(|(n, _,)| n == name,)
+			.map(|(_, value,)| value,)
+	}
+
+	/// Replaces the property `name`, or adds it after the others
+	pub fn set_property(&mut self, name: &str, value: Vec<u8,>,) {
+		match self.property_mut(name,) {
+			Some(old,) => *old = value,
+			None => self.properties.push((name.into(), value,),),
+		}
+	}
+
+	pub fn child(&self, name: &str,) -> Option<&TreeNode,> {
+		self.children.iter().find(|child| child.is_named(name,),)
+	}
+
+	pub fn child_mut(&mut self, name: &str,) -> Option<&mut TreeNode,> {
+		self.children.iter_mut().find(|child| child.is_named(name,),)
+	}
+
+	/// Node at `path` relative to this node, as
+	/// [`Fdt::find`](oso_no_std_shared::bridge::device_tree::Fdt::find)
+	pub fn find(&self, path: &str,) -> Option<&TreeNode,> {
+		path.split('/',)
+			.filter(|c| !c.is_empty(),)
+			.try_fold(self, |node, name| node.child(name,),)
+	}
+
+	pub fn find_mut(&mut self, path: &str,) -> Option<&mut TreeNode,> {
+		path.split('/',)
+			.filter(|c| !c.is_empty(),)
+			.try_fold(self, |node, name| node.child_mut(name,),)
+	}
+
+	/// See [`name_matches`]
+	pub fn is_named(&self, name: &str,) -> bool {
+		name_matches(&self.name, name,)
+	}
+
+	pub fn phandle(&self,) -> Option<u32,> {
+		let value = self
+			.property("phandle",)
+			.or_else(|| self.property("linux,phandle",),)?;
+		be32(value, 0,)
+	}
+
+	/// Largest phandle of this node and its descendants, `0` if none
+	fn max_phandle(&self,) -> u32 {
+		let own = self.phandle().filter(|p| *p != u32::MAX,).unwrap_or(0,);
+		self.children.iter().map(Self::max_phandle,).fold(own, u32::max,)
+	}
+
+	/// Path of the node with `phandle` relative to this node
+	fn path_of(&self, phandle: u32,) -> Option<String,> {
+		if self.phandle() == Some(phandle,) {
+			return Some(String::from("/",),);
+		}
+		self.children.iter().find_map(|child| {
+			let rest = child.path_of(phandle,)?;
+			let rest = rest.trim_end_matches('/',);
+			Some(format!("/{}{rest}", child.name),)
+		},)
+	}
+
+	fn take_child(&mut self, name: &str,) -> Option<TreeNode,> {
+		let index = self.children.iter().position(|c| c.name == name,)?;
+		Some(self.children.remove(index,),)
+	}
+
+	/// Adds `delta` to the phandles of this node and its descendants
+	fn shift_phandles(&mut self, delta: u32,) -> Rslt<(), OverlayError,> {
+		for (name, value,) in &mut self.properties {
+			if name == "phandle" || name == "linux,phandle" {
+				add_be32(value, 0, delta,)?;
+			}
+		}
+		self.children.iter_mut().try_for_each(|c| c.shift_phandles(delta,),)
+	}
+
+	/// Adds `delta` to references at the offsets `fixups` lists, a node of
+	/// `__local_fixups__` mirroring this node
+	fn shift_references(
+		&mut self,
+		fixups: &TreeNode,
+		delta: u32,
+	) -> Rslt<(), OverlayError,> {
+		let invalid = || oso_err!(OverlayError::InvalidFixup);
+		for (name, offsets,) in &fixups.properties {
+			let value = self.property_mut(name,).ok_or_else(invalid,)?;
+			for offset in offsets.chunks(4,) {
+				let offset = be32(offset, 0,).ok_or_else(invalid,)?;
+				add_be32(value, offset as usize, delta,)?;
+			}
+		}
+		for fixups in &fixups.children {
+			self.child_mut(&fixups.name,)
+				.ok_or_else(invalid,)?
+				.shift_references(fixups, delta,)?;
+		}
+		Ok((),)
+	}
+
+	/// Merges properties and children of `other` into this node
+	fn merge(&mut self, other: TreeNode,) {
+		for (name, value,) in other.properties {
+			self.set_property(&name, value,);
+		}
+		for child in other.children {
+			match self.children.iter_mut().find(|c| c.name == child.name,) {
+				Some(own,) => own.merge(child,),
+				None => self.children.push(child,),
+			}
+		}
+	}
+
+	fn write(
+		&self,
+		structure: &mut Vec<u8,>,
+		strings: &mut Vec<u8,>,
+		string_offsets: &mut BTreeMap<String, u32,>,
+	) {
+		push_be32(structure, token::BEGIN_NODE,);
+		structure.extend_from_slice(self.name.as_bytes(),);
+		structure.push(0,);
+		pad4(structure,);
+
+		for (name, value,) in &self.properties {
+			// names are shared between properties
+			let offset =
+				*string_offsets.entry(name.clone(),).or_insert_with(|| {
+					let offset = strings.len() as u32;
+					strings.extend_from_slice(name.as_bytes(),);
+					strings.push(0,);
+					offset
+				},);
+			push_be32(structure, token::PROP,);
+			push_be32(structure, value.len() as u32,);
+			push_be32(structure, offset,);
+			structure.extend_from_slice(value,);
+			pad4(structure,);
+		}
+
+		for child in &self.children {
+			child.write(structure, strings, string_offsets,);
+		}
+		push_be32(structure, token::END_NODE,);
+	}
+}
+
+/// Moves the nodes deeper than `depth` into their parents
+fn close(stack: &mut Vec<TreeNode,>, depth: usize,) {
+	while stack.len() > depth.max(1,) {
+		let Some(child,) = stack.pop() else { break };
+		if let Some(parent,) = stack.last_mut() {
+			parent.children.push(child,);
+		}
+	}
+}
+
+/// Writes `phandle` where a `path:property:offset` entry of `__fixups__`
+/// points
+fn write_fixup(
+	overlay: &mut TreeNode,
+	fixup: &str,
+	phandle: u32,
+) -> Rslt<(), OverlayError,> {
+	let invalid = || oso_err!(OverlayError::InvalidFixup);
+	let mut parts = fixup.rsplitn(3, ':',);
+	let (Some(offset,), Some(property,), Some(path,),) =
+		(parts.next(), parts.next(), parts.next(),)
+	else {
+		return Err(invalid(),);
+	};
+	let offset: usize = offset.parse().map_err(|_| invalid(),)?;
+	let value = overlay
+		.find_mut(path,)
+		.and_then(|node| node.property_mut(property,),)
+		.ok_or_else(invalid,)?;
+	value
+		.get_mut(offset..offset + 4,)
+		.ok_or_else(invalid,)?
+		.copy_from_slice(&phandle.to_be_bytes(),);
+	Ok((),)
+}
+
+fn add_be32(
+	value: &mut [u8],
+	offset: usize,
+	delta: u32,
+) -> Rslt<(), OverlayError,> {
+	let old =
+		be32(value, offset,).ok_or(oso_err!(OverlayError::InvalidFixup),)?;
+	let new =
+		old.checked_add(delta,).ok_or(oso_err!(OverlayError::Malformed),)?;
+	value[offset..offset + 4].copy_from_slice(&new.to_be_bytes(),);
+	Ok((),)
+}
+
+fn push_be32(bytes: &mut Vec<u8,>, value: u32,) {
+	bytes.extend_from_slice(&value.to_be_bytes(),);
+}
+
+fn pad4(bytes: &mut Vec<u8,>,) {
+	bytes.resize(bytes.len().next_multiple_of(4,), 0,);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use alloc::vec;
+
+	const UNRESOLVED: &[u8] = &[0xff, 0xff, 0xff, 0xff,];
+
+	fn node(
+		name: &str,
+		properties: &[(&str, &[u8],)],
+		children: Vec<TreeNode,>,
+	) -> TreeNode {
+		TreeNode {
+			name: name.into(),
+			properties: properties
+				.iter()
+				.map(|(name, value,)| ((*name).into(), value.to_vec(),),)
+				.collect(),
+			children,
+		}
+	}
+
+	fn leaf(name: &str, properties: &[(&str, &[u8],)],) -> TreeNode {
+		node(name, properties, vec![],)
+	}
+
+	fn tree(root: TreeNode,) -> Tree {
+		Tree {
+			root,
+			reservations: vec![(0x4000_0000, 0x1000,)],
+			boot_cpu: 0,
+		}
+	}
+
+	/// overlay with one fragment merging `body` into `target`
+	fn overlay(
+		target: (&str, &[u8],),
+		body: Vec<TreeNode,>,
+		extra: Vec<TreeNode,>,
+	) -> Vec<u8,> {
+		let body = node("__overlay__", &[], body,);
+		let mut children = vec![node("fragment@0", &[target], vec![body],)];
+		children.extend(extra,);
+		tree(node("", &[], children,),).to_blob()
+	}
+
+	/// `/soc/gpio@9030000` labelled `gpio` with phandle 1
+	fn base() -> Tree {
+		let gpio = leaf("gpio@9030000", &[("phandle", &[0, 0, 0, 1,],),],);
+		let soc = node("soc", &[("#address-cells", &[0, 0, 0, 1,],),], vec![
+			gpio,
+		],);
+		let symbols =
+			leaf("__symbols__", &[("gpio", b"/soc/gpio@9030000\0",),],);
+		tree(node("", &[("model", b"virt\0",),], vec![soc, symbols],),)
+	}
+
+	#[test]
+	fn test_blob_round_trips() {
+		let base = base();
+		let blob = base.to_blob();
+		assert!(Fdt::new(&blob,).is_some());
+		assert_eq!(Tree::parse(&blob,).unwrap(), base);
+	}
+
+	#[test]
+	fn test_merges_fragment_by_target_path() {
+		let uart = leaf("serial@9040000", &[("status", b"okay\0",),],);
+		let blob = overlay(("target-path", b"/soc\0",), vec![uart], vec![],);
+
+		let mut base = base();
+		base.apply(&blob,).unwrap();
+		let uart = base.root.find("/soc/serial@9040000",).unwrap();
+		assert_eq!(uart.property("status"), Some(&b"okay\0"[..]));
+		assert!(base.root.find("/fragment@0",).is_none());
+	}
+
+	#[test]
+	fn test_resolves_fixups_and_renumbers_phandles() {
+		// `led` refers to `&gpio` of the base and to `&pins` of the overlay
+		let pins = leaf("pins", &[("phandle", &[0, 0, 0, 1,],),],);
+		let led = leaf("led", &[
+			("gpios", &[0xff, 0xff, 0xff, 0xff, 0, 0, 0, 3,],),
+			("pinctrl-0", &[0, 0, 0, 1,],),
+		],);
+		let fixups = leaf("__fixups__", &[(
+			"gpio",
+			b"/fragment@0:target:0\0/fragment@0/__overlay__/led:gpios:0\0",
+		),],);
+		let led_fixups = leaf("led", &[("pinctrl-0", &[0, 0, 0, 0,],),],);
+		let body_fixups = node("__overlay__", &[], vec![led_fixups],);
+		let fragment_fixups = node("fragment@0", &[], vec![body_fixups],);
+		let local_fixups =
+			node("__local_fixups__", &[], vec![fragment_fixups],);
+		let symbols = leaf("__symbols__", &[(
+			"pins",
+			b"/fragment@0/__overlay__/pins\0",
+		),],);
+		let blob = overlay(("target", UNRESOLVED,), vec![led, pins], vec![
+			fixups,
+			local_fixups,
+			symbols,
+		],);
+
+		let mut base = base();
+		base.apply(&blob,).unwrap();
+		let gpio = base.root.find("/soc/gpio",).unwrap();
+		let led = gpio.child("led",).unwrap();
+		let gpios: &[u8] = &[0, 0, 0, 1, 0, 0, 0, 3,];
+		assert_eq!(led.property("gpios"), Some(gpios));
+		assert_eq!(led.property("pinctrl-0"), Some(&[0, 0, 0, 2,][..]));
+		assert_eq!(gpio.child("pins").and_then(TreeNode::phandle), Some(2));
+		let symbols = base.root.child("__symbols__",).unwrap();
+		let pins: &[u8] = b"/soc/gpio@9030000/pins\0";
+		assert_eq!(symbols.property("pins"), Some(pins));
+	}
+
+	#[test]
+	fn test_rejects_unknown_labels_and_targets() {
+		let fixups =
+			leaf("__fixups__", &[("uart", b"/fragment@0:target:0\0",),],);
+		let blob = overlay(("target", UNRESOLVED,), vec![], vec![fixups],);
+		let error = base().apply(&blob,).unwrap_err();
+		assert_eq!(error.desc, Some(OverlayError::UnresolvedSymbol));
+
+		let blob = overlay(("target-path", b"/pl011\0",), vec![], vec![],);
+		let error = base().apply(&blob,).unwrap_err();
+		assert_eq!(error.desc, Some(OverlayError::TargetNotFound));
+	}
+}
@@ -3,6 +3,8 @@ use super::types::UnsafeHandle;
 pub mod device_path;
 pub mod file;
 pub mod graphic;
+pub mod image;
+pub mod serial;
 pub mod text;
 
 #[derive(Debug,)]
@@ -8,3 +8,41 @@ pub struct DevicePathProtocol {
 	pub subtype:    DeviceSubType,
 	pub length:     [u8; 2],
 }
+
+impl DevicePathProtocol {
+	/// length of this node in bytes including the header
+	pub fn node_len(&self,) -> usize {
+		u16::from_le_bytes(self.length,) as usize
+	}
+
+	pub fn is_end(&self,) -> bool {
+		self.major_type == DeviceType::END
+			&& self.subtype == DeviceSubType::END_ENTIRE
+	}
+
+	/// returns the node following `self`, or `None` if `self` is the end node
+	///
+	/// # Safety
+	///
+	/// `self` must be a part of a well formed device path
+	pub unsafe fn next(&self,) -> Option<&Self,> {
+		if self.is_end() || self.node_len() < size_of::<Self,>() {
+			return None;
+		}
+		let next = unsafe { (self as *const Self).byte_add(self.node_len(),) };
+		unsafe { next.as_ref() }
+	}
+
+	/// payload of this node following the header
+	///
+	/// # Safety
+	///
+	/// `self` must be a part of a well formed device path
+	pub unsafe fn data(&self,) -> &[u8] {
+		let head = unsafe {
+			(self as *const Self).cast::<u8>().add(size_of::<Self,>(),)
+		};
+		let len = self.node_len().saturating_sub(size_of::<Self,>(),);
+		unsafe { core::slice::from_raw_parts(head, len,) }
+	}
+}
@@ -4,6 +4,8 @@ use crate::raw::types::graphic::GraphicsOutputBltOperation;
 use crate::raw::types::graphic::GraphicsOutputBltPixel;
 use crate::raw::types::graphic::GraphicsOutputModeInfo;
 use crate::raw::types::graphic::GraphicsOutputProtocolMode;
+use oso_error::Rslt;
+use oso_error::loader::UefiError;
 
 #[repr(C)]
 pub struct GraphicsOutputProtocol {
@@ -31,14 +33,17 @@ pub struct GraphicsOutputProtocol {
 }
 
 impl GraphicsOutputProtocol {
-	pub fn query_mode(&self, index: u32,) {
+	pub fn query_mode(
+		&self,
+		index: u32,
+	) -> Rslt<GraphicsOutputModeInfo, UefiError,> {
 		let mut info_size = 0;
 		let mut info_heap_ptr = core::ptr::null();
-		let _ = unsafe {
+		unsafe {
 			(self.query_mode)(self, index, &mut info_size, &mut info_heap_ptr,)
 		}
 		.ok_or_with(|_| {
-			let _info = unsafe { *info_heap_ptr };
+			let info = unsafe { *info_heap_ptr };
 			let info_heap_ptr = unsafe {
 				info_heap_ptr.cast::<u8>().cast_mut().as_mut().unwrap()
 			};
@@ -46,7 +51,12 @@ impl GraphicsOutputProtocol {
 			boot_services()
 				.free_pool(info_heap_ptr,)
 				.expect("buffer should be deallocatable",);
-		},);
+			info
+		},)
+	}
+
+	pub fn set_mode(&mut self, index: u32,) -> Rslt<Status, UefiError,> {
+		unsafe { (self.set_mode)(self, index,) }.ok_or()
 	}
 
 	pub fn mode(&self,) -> &GraphicsOutputProtocolMode {
@@ -0,0 +1,30 @@
+use super::device_path::DevicePathProtocol;
+use crate::raw::table::SystemTable;
+use crate::raw::types::Status;
+use crate::raw::types::UnsafeHandle;
+use crate::raw::types::memory::MemoryType;
+use core::ffi::c_void;
+
+/// `EFI_LOADED_IMAGE_PROTOCOL`
+///
+/// installed by firmware on the image handle of every loaded image
+#[repr(C)]
+pub struct LoadedImageProtocol {
+	pub revision:          u32,
+	pub parent_handle:     UnsafeHandle,
+	pub system_table:      *const SystemTable,
+	/// handle of the device the image was loaded from
+	pub device_handle:     UnsafeHandle,
+	/// file path of the image relative to `device_handle`
+	pub file_path:         *const DevicePathProtocol,
+	reserved:              *const c_void,
+	pub load_options_size: u32,
+	pub load_options:      *const c_void,
+	pub image_base:        *const c_void,
+	pub image_size:        u64,
+	pub image_code_type:   MemoryType,
+	pub image_data_type:   MemoryType,
+	pub unload:            Option<
+		unsafe extern "efiapi" fn(image_handle: UnsafeHandle,) -> Status,
+	>,
+}
@@ -0,0 +1,67 @@
+use crate::raw::types::Status;
+use crate::raw::types::serial::ControlBits;
+use crate::raw::types::serial::SerialIoMode;
+use core::ffi::c_void;
+use oso_error::Rslt;
+use oso_error::loader::UefiError;
+
+/// `EFI_SERIAL_IO_PROTOCOL`
+#[repr(C)]
+pub struct SerialIoProtocol {
+	pub revision:       u32,
+	pub reset:          unsafe extern "efiapi" fn(this: *mut Self,) -> Status,
+	pub set_attributes: unsafe extern "efiapi" fn(
+		this: *mut Self,
+		baud_rate: u64,
+		receive_fifo_depth: u32,
+		timeout: u32,
+		parity: u32,
+		data_bits: u8,
+		stop_bits: u32,
+	) -> Status,
+	pub set_control:
+		unsafe extern "efiapi" fn(this: *mut Self, control: u32,) -> Status,
+	pub get_control: unsafe extern "efiapi" fn(
+		this: *mut Self,
+		control: *mut u32,
+	) -> Status,
+	pub write: unsafe extern "efiapi" fn(
+		this: *mut Self,
+		buffer_size: *mut usize,
+		buffer: *const c_void,
+	) -> Status,
+	pub read: unsafe extern "efiapi" fn(
+		this: *mut Self,
+		buffer_size: *mut usize,
+		buffer: *mut c_void,
+	) -> Status,
+	pub mode:           *const SerialIoMode,
+}
+
+impl SerialIoProtocol {
+	/// writes as much of `bytes` as the device accepts before its timeout
+	///
+	/// # Returns
+	///
+	/// number of bytes written. a timeout is not an error, as the rest can be
+	/// written later
+	pub fn write(&mut self, bytes: &[u8],) -> Rslt<usize, UefiError,> {
+		let mut len = bytes.len();
+		let status =
+			unsafe { (self.write)(self, &mut len, bytes.as_ptr().cast(),) };
+		if status == Status::EFI_TIMEOUT {
+			return Ok(len,);
+		}
+		status.ok_or_with(|_| len,)
+	}
+
+	pub fn control(&mut self,) -> Rslt<ControlBits, UefiError,> {
+		let mut bits = 0;
+		unsafe { (self.get_control)(self, &mut bits,) }
+			.ok_or_with(|_| ControlBits(bits,),)
+	}
+
+	pub fn mode(&self,) -> SerialIoMode {
+		unsafe { self.mode.as_ref() }.copied().unwrap_or_default()
+	}
+}
@@ -1,9 +1,10 @@
-use crate::into_null_terminated_utf16;
+use crate::chibi_uefi::string::Ucs2String;
 use crate::raw::types::Boolean;
+use crate::raw::types::Event;
 use crate::raw::types::Status;
 use crate::raw::types::text::InputKey;
+use crate::raw::types::text::TextAttribute;
 use crate::raw::types::text::TextOutputModePtr;
-use core::ffi::c_void;
 use oso_error::Rslt;
 use oso_error::loader::UefiError;
 
@@ -17,7 +18,7 @@ pub struct TextInputProtocol {
 		this: *mut Self,
 		key: *const InputKey,
 	) -> Status,
-	wait_for_key:    *mut c_void,
+	wait_for_key:    Event,
 }
 
 #[repr(C)]
@@ -39,7 +40,7 @@ pub struct TextOutputProtocol {
 		mode_number: usize,
 		columns: *mut usize,
 		rows: *mut usize,
-	),
+	) -> Status,
 	set_mode: unsafe extern "efiapi" fn(
 		this: *mut Self,
 		mode_number: usize,
@@ -57,19 +58,33 @@ pub struct TextOutputProtocol {
 	mode:          TextOutputModePtr,
 }
 
+impl TextInputProtocol {
+	/// returns next keystroke, or `None` if no key is pressed
+	pub fn read_key_stroke(&mut self,) -> Option<InputKey,> {
+		let key = InputKey::default();
+		let status = unsafe { (self.read_key_stroke)(self, &key,) };
+		status.is_success().then_some(key,)
+	}
+
+	/// event signaled when a key is available
+	pub fn wait_for_key(&self,) -> Event {
+		self.wait_for_key
+	}
+}
+
 impl TextOutputProtocol {
 	/// # Params
 	///
-	/// this function expects `s` to be encoded as utf8
+	/// characters UCS-2 cannot hold are written as `U+FFFD`
 	pub fn output(&mut self, s: impl AsRef<str,>,) -> Rslt<Status, UefiError,> {
-		let utf16_repr = into_null_terminated_utf16(s,);
+		let utf16_repr = Ucs2String::from_str_lossy(s.as_ref(),);
 		let utf16_repr = utf16_repr.as_ptr();
 		unsafe { (self.output)(self, utf16_repr,) }.ok_or()
 	}
 
 	/// wrapper function of `(TextOutputProtocol.test)(ptr_of_u16)` call
 	pub fn test(&mut self, s: impl AsRef<str,>,) -> bool {
-		let utf16_repr = into_null_terminated_utf16(s,);
+		let utf16_repr = Ucs2String::from_str_lossy(s.as_ref(),);
 		let utf16_repr = utf16_repr.as_ptr();
 		unsafe { (self.test)(self, utf16_repr,) }.is_success()
 	}
@@ -77,4 +92,42 @@ impl TextOutputProtocol {
 	pub fn clear(&mut self,) -> Rslt<Status, UefiError,> {
 		unsafe { (self.clear)(self,) }.ok_or()
 	}
+
+	pub fn set_cursor(
+		&mut self,
+		column: usize,
+		row: usize,
+	) -> Rslt<Status, UefiError,> {
+		unsafe { (self.set_cursor)(self, column, row,) }.ok_or()
+	}
+
+	/// # Params
+	///
+	/// `attr` is combination of foreground and background color. see
+	/// [`TextAttribute`]
+	pub fn set_attribute(
+		&mut self,
+		attr: TextAttribute,
+	) -> Rslt<Status, UefiError,> {
+		unsafe { (self.set_attr)(self, attr.0,) }.ok_or()
+	}
+
+	pub fn enable_cursor(&mut self, visible: bool,) -> Rslt<Status, UefiError,> {
+		let visible = if visible { Boolean::TRUE } else { Boolean::FALSE };
+		unsafe { (self.enable_cursor)(self, visible,) }.ok_or()
+	}
+
+	/// returns `(column, row)` of the cursor
+	pub fn cursor_position(&self,) -> (usize, usize,) {
+		self.mode.cursor()
+	}
+
+	/// returns `(columns, rows)` of current text mode
+	pub fn size(&mut self,) -> Rslt<(usize, usize,), UefiError,> {
+		let mode = self.mode.current_mode();
+		let mut columns = 0;
+		let mut rows = 0;
+		unsafe { (self.query_mode)(self, mode, &mut columns, &mut rows,) }
+			.ok_or_with(|_| (columns, rows,),)
+	}
 }
@@ -10,6 +10,7 @@ use crate::Rslt;
 use crate::guid;
 use crate::raw::protocol::text::TextInputProtocol;
 use crate::raw::protocol::text::TextOutputProtocol;
+use crate::raw::types::memory::MemoryAttributesTable;
 use core::ffi::c_void;
 use core::ptr::NonNull;
 
@@ -43,6 +44,16 @@ pub struct ConfigTable {
 	vendor_table: *mut c_void,
 }
 
+impl ConfigTable {
+	pub fn vendor_guid(&self,) -> Guid {
+		self.vendor_guid
+	}
+
+	pub fn vendor_table(&self,) -> *mut c_void {
+		self.vendor_table
+	}
+}
+
 pub struct ConfigTableStream {
 	max_index:     usize,
 	config_tables: Option<NonNull<ConfigTable,>,>,
@@ -72,6 +83,21 @@ impl ConfigTableStream {
 
 pub const DEVICE_TREE_TABLE_GUID: Guid =
 	guid!("b1b621d5-f19c-41a5-830b-d9152c69aae0");
+pub const MEMORY_ATTRIBUTES_TABLE_GUID: Guid =
+	guid!("dcfa911d-26eb-469f-a220-38b7dc461220");
+pub const RT_PROPERTIES_TABLE_GUID: Guid =
+	guid!("eb66918a-7eef-402a-842e-931d21c38ae9");
+
+/// `EFI_RT_PROPERTIES_TABLE`
+#[derive(Debug, Clone, Copy,)]
+#[repr(C)]
+pub struct RtPropertiesTable {
+	pub version:                    u16,
+	pub length:                     u16,
+	/// `EFI_RT_SUPPORTED_*` bits of the services callable after
+	/// `ExitBootServices`
+	pub runtime_services_supported: u32,
+}
 
 impl SystemTable {
 	pub fn get_config_tables(&self,) -> Rslt<ConfigTableStream, UefiError,> {
@@ -94,4 +120,31 @@ impl SystemTable {
 	) -> Rslt<Option<NonNull<ConfigTable,>,>, UefiError,> {
 		self.config_table_with(DEVICE_TREE_TABLE_GUID,)
 	}
+
+	/// returns `EFI_MEMORY_ATTRIBUTES_TABLE` if firmware publishes it
+	pub fn memory_attributes_table(
+		&self,
+	) -> Rslt<Option<NonNull<MemoryAttributesTable,>,>, UefiError,> {
+		let Some(table,) =
+			self.config_table_with(MEMORY_ATTRIBUTES_TABLE_GUID,)?
+		else {
+			return Ok(None,);
+		};
+		Ok(NonNull::new(
+			unsafe { table.as_ref() }.vendor_table().cast(),
+		),)
+	}
+
+	/// returns `EFI_RT_PROPERTIES_TABLE` if firmware publishes it
+	pub fn rt_properties_table(
+		&self,
+	) -> Rslt<Option<NonNull<RtPropertiesTable,>,>, UefiError,> {
+		let Some(table,) = self.config_table_with(RT_PROPERTIES_TABLE_GUID,)?
+		else {
+			return Ok(None,);
+		};
+		Ok(NonNull::new(
+			unsafe { table.as_ref() }.vendor_table().cast(),
+		),)
+	}
 }
@@ -12,6 +12,7 @@ pub mod graphic;
 pub mod memory;
 pub mod misc;
 pub mod protocol;
+pub mod serial;
 pub mod text;
 pub mod time;
 pub mod util;
@@ -4,8 +4,9 @@ use crate::chibi_uefi::table::boot_services;
 use alloc::format;
 use core::ops::RangeInclusive;
 use core::ptr::NonNull;
+use oso_no_std_shared::units;
 
-pub const PAGE_SIZE: usize = 4096;
+pub const PAGE_SIZE: usize = units::PAGE_SIZE as usize;
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash,)]
@@ -173,4 +174,68 @@ impl MemoryMapOwned {
 		let len = info.entry_count();
 		Self { buf, info, len, }
 	}
+
+	/// returns `i`th descriptor. descriptors are `desc_size` bytes apart which
+	/// may be larger than `size_of::<MemoryDescriptor>()`
+	pub fn entry(&self, i: usize,) -> Option<&MemoryDescriptor,> {
+		if i >= self.len {
+			return None;
+		}
+		let head = self.buf.0.cast::<u8>().as_ptr();
+		unsafe {
+			head.add(i * self.info.desc_size,)
+				.cast::<MemoryDescriptor>()
+				.as_ref()
+		}
+	}
+
+	pub fn entry_mut(&mut self, i: usize,) -> Option<&mut MemoryDescriptor,> {
+		if i >= self.len {
+			return None;
+		}
+		let head = self.buf.0.cast::<u8>().as_ptr();
+		unsafe {
+			head.add(i * self.info.desc_size,)
+				.cast::<MemoryDescriptor>()
+				.as_mut()
+		}
+	}
+
+	pub fn entries(&self,) -> impl Iterator<Item = &MemoryDescriptor,> {
+		(0..self.len).filter_map(|i| self.entry(i,),)
+	}
+}
+
+/// header of `EFI_MEMORY_ATTRIBUTES_TABLE`
+///
+/// `number_of_entries` descriptors of `descriptor_size` bytes follow the
+/// header. each entry describes a part of a runtime services region with more
+/// precise permission attributes (`EFI_MEMORY_RO`, `EFI_MEMORY_XP`)
+#[repr(C)]
+#[derive(Clone, Copy, Debug,)]
+pub struct MemoryAttributesTable {
+	pub version:           u32,
+	pub number_of_entries: u32,
+	pub descriptor_size:   u32,
+	pub flags:             u32,
+}
+
+impl MemoryAttributesTable {
+	/// returns `i`th entry of the table
+	///
+	/// # Safety
+	///
+	/// `self` must be followed by `number_of_entries` descriptors as the uefi
+	/// spec defines
+	pub unsafe fn entry(&self, i: usize,) -> Option<&MemoryDescriptor,> {
+		if i >= self.number_of_entries as usize {
+			return None;
+		}
+		let head = (self as *const Self).cast::<u8>();
+		unsafe {
+			head.add(size_of::<Self,>() + i * self.descriptor_size as usize,)
+				.cast::<MemoryDescriptor>()
+				.as_ref()
+		}
+	}
 }
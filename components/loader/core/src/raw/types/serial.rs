@@ -0,0 +1,37 @@
+/// current attributes of a serial device
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default,)]
+pub struct SerialIoMode {
+	/// bits of [`ControlBits`] the device supports
+	pub control_mask:       u32,
+	/// microseconds a `Read` or `Write` waits for one character
+	pub timeout:            u32,
+	pub baud_rate:          u64,
+	pub receive_fifo_depth: u32,
+	pub data_bits:          u32,
+	pub parity:             u32,
+	pub stop_bits:          u32,
+}
+
+/// control and status bits of `GetControl` and `SetControl`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash,)]
+#[repr(transparent)]
+pub struct ControlBits(pub u32,);
+
+impl ControlBits {
+	pub const CARRIER_DETECT: u32 = 0x0080;
+	pub const CLEAR_TO_SEND: u32 = 0x0010;
+	pub const DATA_SET_READY: u32 = 0x0020;
+	pub const DATA_TERMINAL_READY: u32 = 0x0001;
+	pub const HARDWARE_FLOW_CONTROL_ENABLE: u32 = 0x4000;
+	pub const HARDWARE_LOOPBACK_ENABLE: u32 = 0x1000;
+	pub const INPUT_BUFFER_EMPTY: u32 = 0x0100;
+	pub const OUTPUT_BUFFER_EMPTY: u32 = 0x0200;
+	pub const REQUEST_TO_SEND: u32 = 0x0002;
+	pub const RING_INDICATE: u32 = 0x0040;
+	pub const SOFTWARE_LOOPBACK_ENABLE: u32 = 0x2000;
+
+	pub fn contains(&self, bits: u32,) -> bool {
+		self.0 & bits == bits
+	}
+}
@@ -1,9 +1,22 @@
 use super::Boolean;
 
 #[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq,)]
 pub struct InputKey {
-	scan_code:    u16,
-	unicode_char: u16,
+	pub scan_code:    u16,
+	pub unicode_char: u16,
+}
+
+impl InputKey {
+	pub const SCAN_ESC: u16 = 0x17;
+
+	/// returns printable character of the key, if any
+	pub fn char(&self,) -> Option<char,> {
+		match self.unicode_char {
+			0 => None,
+			c => char::from_u32(c as u32,),
+		}
+	}
 }
 
 #[repr(C)]
@@ -22,3 +35,40 @@ pub struct TextOutputModePtr {
 }
 
 unsafe impl Sync for TextOutputModePtr {}
+
+impl TextOutputModePtr {
+	pub fn current_mode(&self,) -> usize {
+		unsafe { self.tom.as_ref() }.map(|m| m.mode as usize,).unwrap_or(0,)
+	}
+
+	/// `(column, row)` of the cursor
+	pub fn cursor(&self,) -> (usize, usize,) {
+		unsafe { self.tom.as_ref() }
+			.map(|m| (m.cursor_column as usize, m.cursor_row as usize,),)
+			.unwrap_or((0, 0,),)
+	}
+}
+
+/// foreground and background color passed to `SetAttribute`
+#[derive(Clone, Copy, Debug, PartialEq, Eq,)]
+#[repr(transparent)]
+pub struct TextAttribute(pub usize,);
+
+impl TextAttribute {
+	pub const BLACK: usize = 0x00;
+	pub const BLUE: usize = 0x01;
+	pub const GREEN: usize = 0x02;
+	pub const CYAN: usize = 0x03;
+	pub const RED: usize = 0x04;
+	pub const MAGENTA: usize = 0x05;
+	pub const BROWN: usize = 0x06;
+	pub const LIGHTGRAY: usize = 0x07;
+	pub const DARKGRAY: usize = 0x08;
+	pub const YELLOW: usize = 0x0e;
+	pub const WHITE: usize = 0x0f;
+
+	/// background accepts only the first 8 colors
+	pub const fn new(foreground: usize, background: usize,) -> Self {
+		Self(foreground | ((background & 0x07) << 4),)
+	}
+}
@@ -3,7 +3,7 @@ use crate::c_style_enum;
 use super::Boolean;
 
 #[repr(C)]
-#[derive(Clone, Copy, Debug,)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq,)]
 pub struct Time {
 	year:        u16,
 	month:       u8,
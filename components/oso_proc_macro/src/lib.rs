@@ -13,6 +13,14 @@
 //! - **UEFI Status Codes**: Generate status code enums from UEFI specifications
 //! - **ELF Testing**: Utilities for testing ELF header and program header
 //!   parsing
+//! - **Bridge Layout**: Check the layout of types handed from the loader to
+//!   the kernel
+//! - **Symbol Map**: Reserve the section the kernel resolves its addresses
+//!   from
+//! - **Error Codes**: Number the variants of error enums for the registry
+//!   of the workspace
+//! - **Safety Contracts**: Record why unsafe code is sound, for the unsafe
+//!   audit of the workspace
 //!
 //! ## Usage
 //!
@@ -29,45 +37,69 @@ use crate::pm_logic::fnl;
 use oso_proc_macro_logic as pm_logic;
 use oso_proc_macro_logic::drv;
 use oso_proc_macro_logic::oso_proc_macro_helper::Diag;
+use oso_proc_macro_logic::oso_proc_macro_helper::Level as DiagLevel;
+use oso_proc_macro_logic::oso_proc_macro_helper::Report;
 use proc_macro::Diagnostic;
 use proc_macro::Level;
+use proc_macro2::Span;
 
 trait ErrorDiagnose {
 	type T;
-	fn unwrap_or_emit(self,) -> Self::T;
+	/// output of the macro, after emitting its diagnostics
+	///
+	/// on error, `fallback` builds the output from the `compile_error!`
+	/// invocations reporting it. unlike a panic, this lets other errors in
+	/// the crate surface and keeps the IDE analyzing the rest of the file
+	fn unwrap_or_emit(
+		self,
+		fallback: impl FnOnce(proc_macro2::TokenStream,) -> Self::T,
+	) -> Self::T;
 }
 
-impl<T,> ErrorDiagnose for anyhow::Result<(T, Vec<Diag,>,),> {
+impl<T, D: Into<Report,>,> ErrorDiagnose for anyhow::Result<(T, Vec<D,>,),> {
 	type T = T;
 
-	fn unwrap_or_emit(self,) -> Self::T {
+	fn unwrap_or_emit(
+		self,
+		fallback: impl FnOnce(proc_macro2::TokenStream,) -> Self::T,
+	) -> Self::T {
 		match self {
 			Self::Ok((o, diag,),) => {
-				diag.iter().for_each(|d| match d {
-					Diag::Err(msg,) => {
-						Diagnostic::new(Level::Error, msg,).emit()
-					},
-					Diag::Warn(msg,) => {
-						Diagnostic::new(Level::Warning, msg,).emit()
-					},
-					Diag::Note(msg,) => {
-						Diagnostic::new(Level::Note, msg,).emit()
-					},
-					Diag::Help(msg,) => {
-						Diagnostic::new(Level::Help, msg,).emit()
-					},
-				},);
-
+				diag.into_iter().for_each(|d| emit(&d.into(),),);
 				o
 			},
 			Self::Err(e,) => {
-				Diagnostic::new(Level::Error, format!("{e}"),).emit();
-				panic!("{e}");
+				// spanned errors, possibly combined, point at their inputs
+				let errors = match e.downcast::<syn::Error>() {
+					Ok(errors,) => errors,
+					Err(e,) => {
+						syn::Error::new(Span::call_site(), format!("{e:#}"),)
+					},
+				};
+				fallback(errors.to_compile_error(),)
 			},
 		}
 	}
 }
-fnl!(font => syn::LitStr,
+
+/// emits `diag` at its span, or at the macro call site
+fn emit(diag: &Report,) {
+	let level = match diag.level {
+		DiagLevel::Err => Level::Error,
+		DiagLevel::Warn => Level::Warning,
+		DiagLevel::Note => Level::Note,
+		DiagLevel::Help => Level::Help,
+	};
+	match diag.span {
+		Some(span,) => {
+			Diagnostic::spanned(span.unwrap(), level, diag.message(),)
+		},
+		None => Diagnostic::new(level, diag.message(),),
+	}
+	.emit()
+}
+
+fnl!(font => pm_logic::font::FontArgs, fallback: pm_logic::font::fallback,
 r#"Generates embedded font data from font files at compile time.
 
 This procedural macro takes a relative path to the project root and processes
@@ -78,17 +110,24 @@ The macro converts font data into bitfield representations for efficient storage
 
 * `path` - A string literal containing the relative path from the project root to the directory
   containing font data files
+* `ranges` - Optional character code ranges to embed, separated by commas. Other glyphs are
+  left out and a note reports the bytes saved
 
 # Returns
 
 Returns a token stream representing an array slice of processed font data.
 The generated code will be in the form `&[font_data_1, font_data_2, ...]`.
 
+With `ranges`, the generated code is `(&[(first, end, index), ...], &[font_data, ...])`,
+where the glyph of code `c` in `first..end` is at `index + c - first`.
+
 # Examples
 
 ```rust,ignore
 // Generate font data from files in the "assets/fonts" directory
 let fonts = fonts_data!("assets/fonts");
+// Printable ASCII and box drawing only
+let (index, glyphs) = font!("assets/font.dat", ranges = "0x20..0x7F, 0xB3..=0xDA");
 ```
 
 # Panics
@@ -182,7 +221,8 @@ This macro will cause a compile-time error if:
 - Any trait method has an unsupported signature"#
 );
 
-fnl!(status => syn::Lit,
+fnl!(status => pm_logic::status::StatusArgs,
+fallback: pm_logic::status::fallback,
 r#"Generates UEFI status code definitions from the official UEFI specification.
 
 This procedural macro fetches status code information from the UEFI specification
@@ -190,10 +230,20 @@ website and generates a complete `Status` struct with associated constants and
 error handling methods. The macro downloads and parses the specification page
 at compile time to ensure the status codes are up-to-date and accurate.
 
+The generated code is cached under `target/oso_proc_macro_cache` per version,
+so the page is only downloaded once. Set `OSO_PROC_MACRO_NO_CACHE` to fetch it
+again.
+
+Pinning the page with `pin = "sha256:..."` fails the build when the fetched page
+differs, and records the status codes in `status.lock` next to the manifest.
+When the pinned version is changed, notes list the codes added and removed
+since the recorded one.
+
 # Parameters
 
 * `version` - A floating-point literal specifying the UEFI specification version (e.g., `2.8`,
   `2.9`, `2.10`)
+* `pin` - Optional sha256 of the status codes page, as `sha256:` followed by hex digits
 
 # Returns
 
@@ -238,13 +288,91 @@ status_from_spec!(2.9);
 This macro requires internet access at compile time to fetch the UEFI specification.
 The macro will download from: `https://uefi.org/specs/UEFI/{version}/Apx_D_Status_Codes.html`
 
+A copy vendored under `specs/` by `cargo xtask vendor-specs` is read instead when present.
+With `OSO_OFFLINE=1`, a missing copy is a compile error rather than a download.
+
 # Panics
 
 This macro will cause a compile-time error if:
 - The version parameter is not a floating-point literal
 - The UEFI specification page cannot be accessed
 - The specification page format has changed and cannot be parsed
-- Network connectivity issues prevent downloading the specification"#
+- Network connectivity issues prevent downloading the specification
+- `OSO_OFFLINE=1` is set and the specification is not vendored"#
+);
+
+fnl!(guids => syn::LitStr, fallback: pm_logic::guids::fallback,
+r#"Generates a lookup table of UEFI GUIDs and their names.
+
+The table is read from a file of `#define` blocks copied from the UEFI
+specification, since the specification has no single appendix listing every GUID.
+Lines starting with `//` are ignored and the `_GUID` suffix is left out of the names.
+
+# Parameters
+
+* `path` - A string literal containing the path of the table relative to the manifest
+  directory of the calling crate
+
+# Returns
+
+Returns a token stream containing:
+- `KNOWN_GUIDS`, a `&[(Guid, &str)]` of every definition in file order
+- `name_of(&Guid) -> Option<&'static str>` looking a GUID up in `KNOWN_GUIDS`
+
+`Guid` is resolved in the scope of the caller.
+
+# Examples
+
+```rust,ignore
+use crate::raw::types::Guid;
+
+oso_proc_macro::guids!("resource/guids.h");
+
+assert_eq!(name_of(&LoadedImageProtocol::GUID), Some("EFI_LOADED_IMAGE_PROTOCOL"));
+```
+
+# Panics
+
+This macro will cause a compile-time error if:
+- The table file cannot be read
+- A definition does not have 11 hex numbers or its name does not end with `_GUID`"#
+);
+
+fnl!(symbol_map => pm_logic::symbol_map::SymbolMapArgs,
+fallback: pm_logic::symbol_map::fallback,
+r#"Reserves the section `.oso_symbols` for the symbol map of the kernel.
+
+The section is reserved with a header and no symbols. After the kernel is linked,
+`cargo xtask` writes a table sorted by address of the names of its symbols into
+the section in place, so the kernel resolves addresses such as those of a backtrace
+without the ELF symbol table, which the loader does not pass and release images
+strip.
+
+# Parameters
+
+* `capacity` - An integer literal giving the bytes of the section, including the header
+  of 16 bytes. A multiple of 8 below 4 GiB
+
+# Returns
+
+Returns a token stream containing:
+- `SYMBOL_MAP`, a static placed in `.oso_symbols`
+- `symbol_map_bytes() -> &'static [u8]` returning the contents of the section as
+  written after linking
+
+# Examples
+
+```rust,ignore
+oso_proc_macro::symbol_map!(capacity = 0x40000);
+
+let map = SymbolMap::parse(symbol_map_bytes());
+```
+
+# Panics
+
+This macro will cause a compile-time error if:
+- The argument is not `capacity = N`
+- `N` is not a multiple of 8 between the header size and 4 GiB"#
 );
 
 fnl!(test_elf_header_parse => proc_macro2::TokenStream,
@@ -341,8 +469,11 @@ test_program_headers_parse!(my_program_headers);
 # Program Header Validation
 
 The macro validates all aspects of program headers including:
-- Header type (LOAD, DYNAMIC, INTERP, etc.)
-- Flags (read, write, execute permissions)
+- Header type (LOAD, DYNAMIC, INTERP, etc.). GNU segments such as GNU_STACK, GNU_RELRO
+  and NOTE have their own variants, and types readelf prints as a number, such as
+  `LOOS+0x...`, are compared through `ProgramHeaderType::from`, which maps types without a
+  variant to `ProgramHeaderType::Other`
+- Flags (read, write, execute permissions), including segments without any flag
 - File and memory offsets
 - Virtual and physical addresses
 - File and memory sizes
@@ -370,6 +501,70 @@ r#""#
 
 atr!(features => proc_macro2::TokenStream, syn::ItemEnum, r#""#);
 
+drv!(BridgeLayout, bridge_layout => syn::DeriveInput, attributes: layout,
+r#"Checks the layout of a type shared by raw pointer between the loader and
+the kernel
+
+The type is `#[repr(C)]`, or a fieldless enum with an integer repr. Its size
+is given by `#[layout(size = N)]` on the type and the offset of each field by
+`#[layout(offset = N)]` on the field. A mismatch fails the build.
+
+Integer and float fields wider than 32 bits get `read_<field>` and
+`write_<field>` accessors which store the field as little endian, for targets
+whose byte order or atomic width differs from the other side of the handoff.
+
+```ignore
+#[repr(C)]
+#[derive(BridgeLayout)]
+#[layout(size = 16)]
+struct Range {
+	#[layout(offset = 0)]
+	start: u64,
+	#[layout(offset = 8)]
+	len:   u64,
+}
+```"#
+);
+
+atr!(safety => pm_logic::safety::SafetyArgs, syn::Item,
+r#"Writes down why unsafe code is sound. Changes nothing at run time
+
+`requires = "..."` is the contract callers of an `unsafe fn`, or implementors
+of an `unsafe trait`, uphold. It is added to the documentation as a `# Safety`
+section unless there is one. `reason = "..."` says why the unsafe code inside
+a function, or an `unsafe impl`, is sound. `cargo xtask audit-unsafe` lists
+every unsafe block, function, impl and trait of the workspace with its
+contract, or flags it as missing one.
+
+```ignore
+#[safety(requires = "`virt` is not mapped and `frame` is owned by the caller")]
+pub unsafe fn map(virt: u64, frame: u64) { .. }
+
+#[safety(reason = "the table is only written before other cores start")]
+fn install_table() { unsafe { .. } }
+```"#
+);
+
+drv!(ErrorCode, error_code => syn::DeriveInput, attributes: oso_error_code,
+r#"Gives every variant of an error enum a stable code
+
+Each variant takes its code from `#[oso_error_code(N)]`, a `u16` which no
+other variant of the enum uses. The generated `code(&self) -> u16` returns it.
+`cargo xtask error-codes` collects the codes of the workspace into a registry
+and rejects codes shared by different enums.
+
+```ignore
+#[derive(Debug, ErrorCode)]
+pub enum GpioError {
+	/// controller has no pin with this number
+	#[oso_error_code(0x0e01)]
+	PinOutOfRange { pin: u32, count: u32 },
+	#[oso_error_code(0x0e02)]
+	NoController,
+}
+```"#
+);
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -378,7 +573,7 @@ mod tests {
 	#[test]
 	fn test_error_diagnose_trait_ok() {
 		let result: anyhow::Result<(i32, Vec<Diag,>,),> = Ok((42, vec![],),);
-		let value = result.unwrap_or_emit();
+		let value = result.unwrap_or_emit(|_| unreachable!(),);
 		assert_eq!(value, 42);
 	}
 
@@ -411,11 +606,34 @@ mod tests {
 	}
 
 	#[test]
-	#[should_panic]
 	fn test_error_diagnose_trait_err() {
 		let result: anyhow::Result<(i32, Vec<Diag,>,),> =
 			Err(anyhow!("Test error"),);
-		let _value = result.unwrap_or_emit();
+		let value = result.unwrap_or_emit(|errors| {
+			let errors = errors.to_string();
+			assert!(errors.contains("compile_error"));
+			assert!(errors.contains("Test error"));
+			-1
+		},);
+		assert_eq!(value, -1);
+	}
+
+	#[test]
+	fn test_error_diagnose_fallback_stub() {
+		let span = Span::call_site();
+		let result: anyhow::Result<(proc_macro2::TokenStream, Vec<Diag,>,),> =
+			Err(Diag::error(
+				pm_logic::oso_proc_macro_helper::Code::STATUS_VERSION,
+				span,
+				"bad version",
+			),);
+		let version: syn::Lit = syn::parse_quote!(2.11);
+		let tokens = result.unwrap_or_emit(|errors| {
+			pm_logic::status::fallback(&version.into(), errors,)
+		},);
+		let tokens = tokens.to_string();
+		assert!(tokens.contains("[OSO0003] bad version"));
+		assert!(tokens.contains("struct Status"));
 	}
 
 	#[test]
@@ -472,7 +690,7 @@ mod tests {
 	fn test_error_diagnose_empty_diagnostics() {
 		let result: anyhow::Result<(Vec<i32,>, Vec<Diag,>,),> =
 			Ok((vec![1, 2, 3], vec![],),);
-		let value = result.unwrap_or_emit();
+		let value = result.unwrap_or_emit(|_| unreachable!(),);
 		assert_eq!(value, vec![1, 2, 3]);
 	}
 
@@ -482,15 +700,18 @@ mod tests {
 		// Test that the trait works with different types
 		let string_result: anyhow::Result<(String, Vec<Diag,>,),> =
 			Ok(("test".to_string(), vec![],),);
-		assert_eq!(string_result.unwrap_or_emit(), "test");
+		let value = string_result.unwrap_or_emit(|_| unreachable!(),);
+		assert_eq!(value, "test");
 
 		let vec_result: anyhow::Result<(Vec<u8,>, Vec<Diag,>,),> =
 			Ok((vec![1, 2, 3], vec![],),);
-		assert_eq!(vec_result.unwrap_or_emit(), vec![1, 2, 3]);
+		let value = vec_result.unwrap_or_emit(|_| unreachable!(),);
+		assert_eq!(value, vec![1, 2, 3]);
 
 		let option_result: anyhow::Result<(Option<i32,>, Vec<Diag,>,),> =
 			Ok((Some(42,), vec![],),);
-		assert_eq!(option_result.unwrap_or_emit(), Some(42));
+		let value = option_result.unwrap_or_emit(|_| unreachable!(),);
+		assert_eq!(value, Some(42));
 	}
 
 	#[test]
@@ -536,7 +757,7 @@ mod tests {
 
 		let result: anyhow::Result<(HashMap<String, i32,>, Vec<Diag,>,),> =
 			Ok((map.clone(), vec![],),);
-		let value = result.unwrap_or_emit();
+		let value = result.unwrap_or_emit(|_| unreachable!(),);
 		assert_eq!(value, map);
 	}
 
@@ -551,7 +772,7 @@ mod tests {
 		let test_struct = TestStruct { value: 100, };
 		let result: anyhow::Result<(TestStruct, Vec<Diag,>,),> =
 			Ok((test_struct, vec![],),);
-		let value = result.unwrap_or_emit();
+		let value = result.unwrap_or_emit(|_| unreachable!(),);
 		assert_eq!(value.value, 100);
 	}
 }
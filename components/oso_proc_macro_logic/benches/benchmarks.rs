@@ -104,6 +104,7 @@ fn benchmark_impl_init() {
 
 #[test]
 fn benchmark_status_from_spec_html_parsing() {
+	use oso_proc_macro_logic::html::*;
 	use oso_proc_macro_logic::status::*;
 
 	let test_html = r#"
@@ -0,0 +1,286 @@
+//! # Bridge Layout
+//!
+//! `#[derive(BridgeLayout)]` pins the layout of a type shared by raw pointer
+//! between the loader and the kernel. The expected size and the offset of
+//! every field are written next to the type and checked at compile time, so
+//! a change of layout fails the build of both sides instead of the handoff:
+//!
+//! ```ignore
+//! #[repr(C)]
+//! #[derive(BridgeLayout)]
+//! #[layout(size = 16)]
+//! struct Range {
+//! 	#[layout(offset = 0)]
+//! 	start: u64,
+//! 	#[layout(offset = 8)]
+//! 	len:   u64,
+//! }
+//!
+//! let start = range.read_start();
+//! range.write_len(4096,);
+//! ```
+//!
+//! The type must be `#[repr(C)]`, or a fieldless enum with an integer repr.
+//!
+//! Integer and float fields wider than 32 bits get `read_<field>` and
+//! `write_<field>` accessors which serialize the field as little endian.
+//! 32 bits is the widest access every target makes atomically, and a target
+//! whose byte order differs from the other side of the handoff still reads
+//! the value the other side wrote as long as both use the accessors.
+
+use crate::RsltP;
+use anyhow::Result as Rslt;
+use anyhow::bail;
+use quote::format_ident;
+
+/// types serialized by the accessors
+const WIDE: [&str; 5] = ["u64", "i64", "u128", "i128", "f64",];
+
+pub fn bridge_layout(item: syn::DeriveInput,) -> RsltP {
+	if !item.generics.params.is_empty() {
+		bail!("layout of `{}` depends on its parameters", item.ident);
+	}
+	check_repr(&item,)?;
+
+	let ident = &item.ident;
+	let size = LayoutAttr::of(&item.attrs,)?.size.ok_or_else(|| {
+		syn::Error::new_spanned(ident, "expected `#[layout(size = N)]`",)
+	},)?;
+	let message = format!("size of `{ident}` changed");
+	let mut checks = vec![quote::quote! {
+		assert!(::core::mem::size_of::<#ident>() == #size, #message);
+	}];
+	let mut accessors = vec![];
+
+	if let syn::Data::Struct(syn::DataStruct { fields, .. },) = &item.data {
+		for field in fields {
+			let Some(name,) = &field.ident else {
+				bail!("fields of `{ident}` must be named");
+			};
+			let offset = LayoutAttr::of(&field.attrs,)?.offset.ok_or_else(|| {
+				let message = "expected `#[layout(offset = N)]`";
+				syn::Error::new_spanned(name, message,)
+			},)?;
+			let message = format!("offset of `{ident}::{name}` changed");
+			checks.push(quote::quote! {
+				assert!(
+					::core::mem::offset_of!(#ident, #name) == #offset,
+					#message
+				);
+			},);
+			accessors.extend(accessor(name, &field.ty,),);
+		}
+	}
+
+	let accessors = (!accessors.is_empty()).then(|| {
+		quote::quote! {
+			impl #ident {
+				#(#accessors)*
+			}
+		}
+	},);
+	Ok((
+		quote::quote! {
+			const _: () = {
+				#(#checks)*
+			};
+			#accessors
+		},
+		vec![],
+	),)
+}
+
+/// arguments of `#[layout(size = N)]` and `#[layout(offset = N)]`
+#[derive(Default,)]
+struct LayoutAttr {
+	size:   Option<syn::LitInt,>,
+	offset: Option<syn::LitInt,>,
+}
+
+impl LayoutAttr {
+	/// arguments of every `layout` attribute in `attrs`
+	fn of(attrs: &[syn::Attribute],) -> syn::Result<Self,> {
+		let mut args = Self::default();
+		for attr in attrs.iter().filter(|a| a.path().is_ident("layout",),) {
+			attr.parse_nested_meta(|meta| {
+				if meta.path.is_ident("size",) {
+					args.size = Some(meta.value()?.parse()?,);
+				} else if meta.path.is_ident("offset",) {
+					args.offset = Some(meta.value()?.parse()?,);
+				} else {
+					return Err(meta.error("expected `size` or `offset`",),);
+				}
+				Ok((),)
+			},)?;
+		}
+		Ok(args,)
+	}
+}
+
+/// structs must be `repr(C)`, enums may also have an integer repr
+fn check_repr(item: &syn::DeriveInput,) -> Rslt<(),> {
+	let mut reprs = vec![];
+	for attr in item.attrs.iter().filter(|a| a.path().is_ident("repr",),) {
+		attr.parse_nested_meta(|meta| {
+			if let Some(ident,) = meta.path.get_ident() {
+				reprs.push(ident.to_string(),);
+			}
+			Ok((),)
+		},)?;
+	}
+
+	let is_int = |repr: &String| {
+		let bits = repr.trim_start_matches(['u', 'i',],);
+		bits != repr && ["8", "16", "32", "64",].contains(&bits,)
+	};
+	let stable = match &item.data {
+		syn::Data::Struct(_,) => reprs.iter().any(|r| r == "C",),
+		syn::Data::Enum(e,) => {
+			let fieldless = e.variants.iter().all(|v| v.fields.is_empty(),);
+			fieldless && reprs.iter().any(|r| r == "C" || is_int(r,),)
+		},
+		syn::Data::Union(_,) => false,
+	};
+	if !stable {
+		let ident = &item.ident;
+		let message = "bridge types must be `#[repr(C)]` structs or fieldless \
+		               enums with `#[repr(C)]` or an integer repr";
+		bail!(syn::Error::new_spanned(ident, message));
+	}
+	Ok((),)
+}
+
+/// little endian `read_<name>` and `write_<name>` of wide fields
+fn accessor(
+	name: &syn::Ident,
+	ty: &syn::Type,
+) -> Option<proc_macro2::TokenStream,> {
+	let syn::Type::Path(syn::TypePath { qself: None, path, },) = ty else {
+		return None;
+	};
+	let prim = path.get_ident()?;
+	if !WIDE.contains(&prim.to_string().as_str(),) {
+		return None;
+	}
+
+	let read = format_ident!("read_{name}");
+	let write = format_ident!("write_{name}");
+	let read_doc = format!("`{name}`, stored as little endian");
+	let write_doc = format!("Stores `{name}` as little endian");
+	Some(quote::quote! {
+		#[doc = #read_doc]
+		pub const fn #read(&self) -> #prim {
+			#prim::from_le_bytes(self.#name.to_ne_bytes())
+		}
+
+		#[doc = #write_doc]
+		pub const fn #write(&mut self, value: #prim) {
+			self.#name = #prim::from_ne_bytes(value.to_le_bytes());
+		}
+	},)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use syn::parse_quote;
+
+	#[test]
+	fn test_bridge_layout_checks_every_field() {
+		let item: syn::DeriveInput = parse_quote! {
+			#[repr(C)]
+			#[layout(size = 16)]
+			struct Pair {
+				#[layout(offset = 0)]
+				ptr: *const u8,
+				#[layout(offset = 8)]
+				len: usize,
+			}
+		};
+		let (tokens, diags,) = bridge_layout(item,).unwrap();
+		let tokens = tokens.to_string();
+		assert!(tokens.contains("size_of :: < Pair > () == 16"));
+		assert!(tokens.contains("offset_of ! (Pair , ptr) == 0"));
+		assert!(tokens.contains("offset_of ! (Pair , len) == 8"));
+		assert!(!tokens.contains("fn read_"));
+		assert!(diags.is_empty());
+	}
+
+	#[test]
+	fn test_bridge_layout_accessors_of_wide_fields() {
+		let item: syn::DeriveInput = parse_quote! {
+			#[repr(C)]
+			#[layout(size = 16)]
+			struct Region {
+				#[layout(offset = 0)]
+				start: u64,
+				#[layout(offset = 8)]
+				flags: u32,
+			}
+		};
+		let tokens = bridge_layout(item,).unwrap().0.to_string();
+		assert!(tokens.contains("fn read_start (& self) -> u64"));
+		assert!(tokens.contains("fn write_start (& mut self , value : u64)"));
+		assert!(tokens.contains("from_le_bytes"));
+		assert!(!tokens.contains("read_flags"));
+	}
+
+	#[test]
+	fn test_bridge_layout_enum() {
+		let item: syn::DeriveInput = parse_quote! {
+			#[repr(u32)]
+			#[layout(size = 4)]
+			enum Kind { A, B }
+		};
+		let tokens = bridge_layout(item,).unwrap().0.to_string();
+		assert!(tokens.contains("size_of :: < Kind > () == 4"));
+		assert!(!tokens.contains("impl Kind"));
+	}
+
+	#[test]
+	fn test_bridge_layout_rejects_unstable_layout() {
+		let rust_repr: syn::DeriveInput = parse_quote! {
+			#[layout(size = 8)]
+			struct A { #[layout(offset = 0)] a: u64 }
+		};
+		assert!(bridge_layout(rust_repr,).is_err());
+
+		let data_enum: syn::DeriveInput = parse_quote! {
+			#[repr(u32)]
+			#[layout(size = 8)]
+			enum B { A(u32) }
+		};
+		assert!(bridge_layout(data_enum,).is_err());
+
+		let unsized_repr: syn::DeriveInput = parse_quote! {
+			#[repr(usize)]
+			#[layout(size = 8)]
+			enum C { A }
+		};
+		assert!(bridge_layout(unsized_repr,).is_err());
+	}
+
+	#[test]
+	fn test_bridge_layout_requires_size_and_offsets() {
+		let no_size: syn::DeriveInput = parse_quote! {
+			#[repr(C)]
+			struct A { #[layout(offset = 0)] a: u64 }
+		};
+		assert!(bridge_layout(no_size,).is_err());
+
+		let no_offset: syn::DeriveInput = parse_quote! {
+			#[repr(C)]
+			#[layout(size = 16)]
+			struct B { #[layout(offset = 0)] a: u64, b: u64 }
+		};
+		let e = bridge_layout(no_offset,).unwrap_err();
+		assert!(e.to_string().contains("offset"));
+
+		let unknown: syn::DeriveInput = parse_quote! {
+			#[repr(C)]
+			#[layout(size = 8, align = 8)]
+			struct C { #[layout(offset = 0)] a: u64 }
+		};
+		assert!(bridge_layout(unknown,).is_err());
+	}
+}
@@ -0,0 +1,148 @@
+//! # Generated Code Cache
+//!
+//! Some macros do expensive work on every expansion: `status!` downloads the
+//! UEFI specification and `font!` converts every glyph. Their output only
+//! depends on a few inputs, so it is stored under [`CACHE_DIR`] keyed by a
+//! hash of those inputs, and reused until an input changes.
+//!
+//! Only expansions without diagnostics are stored, so warnings are shown on
+//! every build. Cached output loses its spans, which only matters for
+//! diagnostics the compiler reports inside it.
+//!
+//! Setting [`BYPASS_ENV`] disables the cache for a build.
+//!
+//! ```ignore
+//! let contents = std::fs::read(&path,)?;
+//! cache::cached("font", &[path.as_bytes(), &contents,], || generate(&path,),)
+//! ```
+
+use crate::RsltP;
+use anyhow::Result as Rslt;
+use oso_dev_util_helper::fs::project_root_path;
+use std::path::PathBuf;
+
+/// cache directory, relative to the project root
+pub const CACHE_DIR: &str = "target/oso_proc_macro_cache";
+/// environment variable which disables the cache when set
+pub const BYPASS_ENV: &str = "OSO_PROC_MACRO_NO_CACHE";
+/// part of every key. bump when the output of a generator changes, so that
+/// stale entries are not reused
+const CACHE_VERSION: u32 = 1;
+
+/// output of `generate`, or the one stored for the same `kind` and `key`
+///
+/// errors of the cache itself are ignored, falling back to `generate`
+pub fn cached(
+	kind: &str,
+	key: &[&[u8]],
+	generate: impl FnOnce() -> RsltP,
+) -> RsltP {
+	let Some(cache,) = Cache::open() else {
+		return generate();
+	};
+	if let Some(tokens,) = cache.get(kind, key,) {
+		return Ok((tokens, vec![],),);
+	}
+
+	let (tokens, diags,) = generate()?;
+	if diags.is_empty() {
+		// a failed write only costs the next build
+		let _ = cache.put(kind, key, &tokens,);
+	}
+	Ok((tokens, diags,),)
+}
+
+/// directory of cached expansions
+pub struct Cache {
+	dir: PathBuf,
+}
+
+impl Cache {
+	/// cache of the project, or `None` if bypassed by [`BYPASS_ENV`]
+	pub fn open() -> Option<Self,> {
+		if std::env::var_os(BYPASS_ENV,).is_some() {
+			return None;
+		}
+		let root = project_root_path().ok()?;
+		Some(Self::at(root.join(CACHE_DIR,),),)
+	}
+
+	pub fn at(dir: impl Into<PathBuf,>,) -> Self {
+		Self { dir: dir.into(), }
+	}
+
+	pub fn get(
+		&self,
+		kind: &str,
+		key: &[&[u8]],
+	) -> Option<proc_macro2::TokenStream,> {
+		let code = std::fs::read_to_string(self.path(kind, key,),).ok()?;
+		// a corrupted entry is regenerated
+		code.parse().ok()
+	}
+
+	pub fn put(
+		&self,
+		kind: &str,
+		key: &[&[u8]],
+		tokens: &proc_macro2::TokenStream,
+	) -> Rslt<(),> {
+		std::fs::create_dir_all(&self.dir,)?;
+		let path = self.path(kind, key,);
+		// crates expanding the same macro may run in parallel. renaming
+		// keeps readers from seeing a partial entry
+		let tmp = path.with_extension(format!("{}.tmp", std::process::id()),);
+		std::fs::write(&tmp, tokens.to_string(),)?;
+		std::fs::rename(&tmp, &path,)?;
+		Ok((),)
+	}
+
+	fn path(&self, kind: &str, key: &[&[u8]],) -> PathBuf {
+		self.dir.join(format!("{kind}-{:016x}.rs", hash(key)),)
+	}
+}
+
+/// FNV-1a of `CACHE_VERSION` and the length prefixed parts. unlike the
+/// hasher of std, stable across toolchains
+fn hash(parts: &[&[u8]],) -> u64 {
+	let mut hash = 0xcbf2_9ce4_8422_2325u64;
+	let mut feed = |bytes: &[u8]| {
+		for byte in bytes {
+			hash ^= *byte as u64;
+			hash = hash.wrapping_mul(0x0100_0000_01b3,);
+		}
+	};
+	feed(&CACHE_VERSION.to_le_bytes(),);
+	for part in parts {
+		feed(&(part.len() as u64).to_le_bytes(),);
+		feed(part,);
+	}
+	hash
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tempfile::TempDir;
+
+	#[test]
+	fn test_hash_separates_parts() {
+		assert_eq!(hash(&[b"ab", b"c",]), hash(&[b"ab", b"c",]));
+		assert_ne!(hash(&[b"ab", b"c",]), hash(&[b"a", b"bc",]));
+		assert_ne!(hash(&[b"ab",]), hash(&[b"ab", b"",]));
+	}
+
+	#[test]
+	fn test_cache_round_trip() {
+		let dir = TempDir::new().unwrap();
+		let cache = Cache::at(dir.path(),);
+		let tokens = quote::quote! { pub struct Status(pub usize); };
+
+		assert!(cache.get("status", &[b"2.11",],).is_none());
+		cache.put("status", &[b"2.11",], &tokens,).unwrap();
+		let hit = cache.get("status", &[b"2.11",],).unwrap();
+		assert_eq!(hit.to_string(), tokens.to_string());
+		assert!(cache.get("status", &[b"2.10",],).is_none());
+		assert!(cache.get("font", &[b"2.11",],).is_none());
+	}
+}
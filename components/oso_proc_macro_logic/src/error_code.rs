@@ -0,0 +1,147 @@
+//! # Error Codes
+//!
+//! `#[derive(ErrorCode)]` gives every variant of an error enum a stable
+//! number, written next to the variant and returned by the generated
+//! `code`:
+//!
+//! ```ignore
+//! #[derive(Debug, ErrorCode)]
+//! pub enum GpioError {
+//! 	/// controller has no pin with this number
+//! 	#[oso_error_code(0x0e01)]
+//! 	PinOutOfRange { pin: u32, count: u32 },
+//! 	#[oso_error_code(0x0e02)]
+//! 	NoController,
+//! }
+//!
+//! assert_eq!(GpioError::NoController.code(), 0x0e02);
+//! ```
+//!
+//! Every variant needs a code and the codes of an enum must differ. Codes of
+//! different enums are checked by `cargo xtask error-codes`, which also
+//! writes the registry of all codes in the workspace.
+
+use crate::RsltP;
+use crate::oso_proc_macro_helper::Code;
+use crate::oso_proc_macro_helper::Diag;
+use anyhow::bail;
+use std::collections::HashMap;
+
+/// name of the attribute carrying the code of a variant
+pub const ATTR: &str = "oso_error_code";
+
+pub fn error_code(item: syn::DeriveInput,) -> RsltP {
+	let ident = &item.ident;
+	let syn::Data::Enum(data,) = &item.data else {
+		bail!(syn::Error::new_spanned(ident, "`ErrorCode` expects an enum"));
+	};
+
+	let mut seen: HashMap<u16, &syn::Ident,> = HashMap::new();
+	let mut arms = vec![];
+	for variant in &data.variants {
+		let name = &variant.ident;
+		let code = code_of(variant,)?;
+		let value = code.base10_parse::<u16>().map_err(|_| {
+			let message = "error code must be an integer up to `0xffff`";
+			Diag::error(Code::ERROR_CODE, code.span(), message,)
+		},)?;
+		if let Some(other,) = seen.insert(value, name,) {
+			return Err(Diag::error(
+				Code::ERROR_CODE,
+				code.span(),
+				format!("error code {value:#06x} is used by `{other}` already"),
+			),);
+		}
+		arms.push(quote::quote! { Self::#name { .. } => #value, },);
+	}
+
+	let (impl_generics, ty_generics, where_clause,) =
+		item.generics.split_for_impl();
+	Ok((
+		quote::quote! {
+			impl #impl_generics #ident #ty_generics #where_clause {
+				/// Code of the variant in the error code registry
+				pub const fn code(&self) -> u16 {
+					match self {
+						#(#arms)*
+					}
+				}
+			}
+		},
+		vec![],
+	),)
+}
+
+/// argument of the only `#[oso_error_code(..)]` of `variant`
+fn code_of(variant: &syn::Variant,) -> anyhow::Result<syn::LitInt,> {
+	let mut attrs = variant.attrs.iter().filter(|a| a.path().is_ident(ATTR,),);
+	let name = &variant.ident;
+	let Some(attr,) = attrs.next() else {
+		return Err(Diag::error(
+			Code::ERROR_CODE,
+			name.span(),
+			format!("`{name}` has no `#[{ATTR}(..)]`"),
+		),);
+	};
+	if let Some(extra,) = attrs.next() {
+		return Err(Diag::error(
+			Code::ERROR_CODE,
+			extra.path().get_ident().unwrap().span(),
+			format!("`{name}` has more than one `#[{ATTR}(..)]`"),
+		),);
+	}
+	Ok(attr.parse_args()?,)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use syn::parse_quote;
+
+	#[test]
+	fn test_error_code_of_every_variant() {
+		let item: syn::DeriveInput = parse_quote! {
+			enum GpioError {
+				#[oso_error_code(0x0e01)]
+				PinOutOfRange { pin: u32, count: u32 },
+				#[oso_error_code(0x0e02)]
+				NoController,
+				#[oso_error_code(3)]
+				Busy(u32),
+			}
+		};
+		let (tokens, diags,) = error_code(item,).unwrap();
+		let tokens = tokens.to_string();
+		assert!(tokens.contains("pub const fn code (& self) -> u16"));
+		assert!(tokens.contains("Self :: PinOutOfRange { .. } => 3585u16"));
+		assert!(tokens.contains("Self :: NoController { .. } => 3586u16"));
+		assert!(tokens.contains("Self :: Busy { .. } => 3u16"));
+		assert!(diags.is_empty());
+	}
+
+	#[test]
+	fn test_error_code_rejects_bad_codes() {
+		let message = |item: syn::DeriveInput| {
+			let e = error_code(item,).unwrap_err();
+			e.downcast::<syn::Error>().unwrap().to_string()
+		};
+
+		let missing = message(parse_quote! {
+			enum A { #[oso_error_code(1)] B, C }
+		},);
+		assert_eq!(missing, "[OSO0010] `C` has no `#[oso_error_code(..)]`");
+
+		let duplicate = message(parse_quote! {
+			enum A { #[oso_error_code(1)] B, #[oso_error_code(0x1)] C }
+		},);
+		assert!(duplicate.contains("0x0001 is used by `B` already"));
+
+		let wide = message(parse_quote! {
+			enum A { #[oso_error_code(0x10000)] B }
+		},);
+		assert!(wide.contains("up to `0xffff`"));
+
+		let item: syn::DeriveInput = parse_quote! { struct A; };
+		assert!(error_code(item,).is_err());
+	}
+}
@@ -10,7 +10,8 @@ pub fn features(
 	_attr: proc_macro2::TokenStream,
 	mut item: syn::ItemEnum,
 ) -> RsltP {
-	let mut hs = std::collections::HashSet::new();
+	// sorted so that variant order does not change between builds
+	let mut hs = std::collections::BTreeSet::new();
 	all_crates()?
 		.iter()
 		.filter_map(|e| {
@@ -19,17 +20,22 @@ pub fn features(
 		},)
 		.try_for_each(|toml| -> Rslt<(),> {
 			if let Some(toml::Value::Table(t,),) = toml?.get("features",) {
-				t.into_iter().for_each(|(feature, _,)| {
-					hs.insert(feature.clone(),);
-				},);
+				// `default` is not a feature users can select
+				t.keys().filter(|feature| *feature != "default",).for_each(
+					|feature| {
+						hs.insert(feature.clone(),);
+					},
+				);
 			}
 			Ok((),)
 		},)?;
 
-	hs.iter().for_each(|variant| {
-		let variant: String = variant.to_camel();
+	hs.iter().for_each(|feature| {
+		let variant: String = feature.to_camel();
 		let variant = format_ident!("{variant}");
-		let variant: syn::Variant = syn::parse_quote!(#variant);
+		// keep the name cargo knows, so `as_ref` can be passed to `--features`
+		let variant: syn::Variant =
+			syn::parse_quote!(#[strum(serialize = #feature)] #variant);
 		item.variants.push(variant,);
 	},);
 
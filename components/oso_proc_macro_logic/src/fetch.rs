@@ -0,0 +1,68 @@
+//! # Network Access
+//!
+//! Every page a macro reads from the web goes through [`fetch`], which
+//! prefers the copy vendored by `cargo xtask vendor-specs`. With
+//! `OSO_OFFLINE=1`, a page that is not vendored is an error instead of a
+//! download, see [`oso_dev_util_helper::specs`].
+
+use crate::oso_proc_macro_helper::Code;
+use crate::oso_proc_macro_helper::Diag;
+use anyhow::Result as Rslt;
+use oso_dev_util_helper::fs::project_root_path;
+use oso_dev_util_helper::specs;
+use oso_dev_util_helper::specs::OFFLINE_ENV;
+use proc_macro2::Span;
+use std::path::Path;
+
+/// body of the page at `url`, reporting at `span` why it can not be read
+pub fn fetch(url: &str, span: Span,) -> Rslt<String,> {
+	fetch_from(&project_root_path()?, url, specs::is_offline(), span,)
+}
+
+/// body of the page at `url`, vendored under `root` or downloaded unless
+/// `offline`
+fn fetch_from(
+	root: &Path,
+	url: &str,
+	offline: bool,
+	span: Span,
+) -> Rslt<String,> {
+	let vendored = specs::vendored_path(root, url,);
+	if vendored.exists() {
+		return Ok(std::fs::read_to_string(vendored,)?,);
+	}
+	if offline {
+		return Err(Diag::error(
+			Code::OFFLINE,
+			span,
+			format!(
+				"{OFFLINE_ENV}=1 forbids downloading {url}. run `cargo xtask \
+				 vendor-specs` while online to vendor it"
+			),
+		),);
+	}
+	let mut rsp = ureq::get(url,).call()?;
+	Ok(rsp.body_mut().read_to_string()?,)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_fetch_offline() {
+		let root = tempfile::tempdir().unwrap();
+		let url = specs::status_page_url("2.11",);
+		let span = Span::call_site();
+
+		let err = fetch_from(root.path(), &url, true, span,).unwrap_err();
+		let err = err.downcast::<syn::Error>().unwrap().to_string();
+		assert!(err.starts_with("[OSO0009] OSO_OFFLINE=1 forbids"), "{err}");
+
+		let vendored = specs::vendored_path(root.path(), &url,);
+		std::fs::create_dir_all(vendored.parent().unwrap(),).unwrap();
+		std::fs::write(&vendored, "<html></html>",).unwrap();
+		let page = fetch_from(root.path(), &url, true, span,).unwrap();
+		assert_eq!(page, "<html></html>");
+	}
+}
@@ -3,22 +3,213 @@
 //! This module provides functionality for loading and processing ASCII font
 //! data for use in the OSO operating system. It handles bitmap font conversion
 //! from text-based representations to binary formats suitable for rendering.
+//!
+//! Glyphs are converted in parallel. Glyphs which can not be converted are
+//! embedded blank and summarized in a warning.
 
 use crate::Rslt;
 use crate::RsltP;
+use crate::cache;
+use crate::oso_proc_macro_helper::Code;
+use crate::oso_proc_macro_helper::Diag;
+use crate::oso_proc_macro_helper::Report;
+use rayon::prelude::*;
+use std::ops::Range;
 use syn::LitStr;
+use syn::Token;
+use syn::parse::Parse;
+use syn::parse::ParseStream;
 
 /// Number of ASCII characters supported (0-255)
 const CHARACTER_COUNT: usize = 256;
 
-pub fn font(path: syn::LitStr,) -> RsltP {
-	let fonts = convert_bitfield(&font_data(path,)?,);
-	Ok((
-		quote::quote! {
-			&[#(#fonts),*]
+/// Input of `font!`: a path and optionally the ranges of glyphs to embed,
+/// such as `ranges = "0x20..0x7F, 0xB0..=0xB2"`
+#[derive(Clone,)]
+pub struct FontArgs {
+	pub path:   LitStr,
+	pub ranges: Option<LitStr,>,
+}
+
+impl From<LitStr,> for FontArgs {
+	fn from(path: LitStr,) -> Self {
+		Self { path, ranges: None, }
+	}
+}
+
+impl Parse for FontArgs {
+	fn parse(input: ParseStream,) -> syn::Result<Self,> {
+		let path = input.parse()?;
+		let mut ranges = None;
+		if input.parse::<Option<Token![,],>>()?.is_some() && !input.is_empty() {
+			let key: syn::Ident = input.parse()?;
+			if key != "ranges" {
+				return Err(syn::Error::new(key.span(), "expected `ranges`",),);
+			}
+			input.parse::<Token![=]>()?;
+			ranges = Some(input.parse()?,);
+			input.parse::<Option<Token![,],>>()?;
+		}
+		Ok(Self { path, ranges, },)
+	}
+}
+
+/// Glyph table of a font file
+///
+/// Expands to `&[u128; 256]` indexed by character code. With `ranges`, only
+/// the glyphs in them are embedded and the expansion is
+/// `(&[(u32, u32, u32)], &[u128])`: for each range its first code, end code
+/// and index of its first glyph, followed by the glyphs.
+pub fn font(args: impl Into<FontArgs,>,) -> RsltP {
+	let FontArgs { path, ranges, } = args.into();
+	let (ranges, mut diags,) = match &ranges {
+		Some(lit,) => {
+			let (ranges, diags,) = subset(lit,)?;
+			(Some(ranges,), diags,)
 		},
-		vec![],
-	),)
+		None => (None, vec![],),
+	};
+	let subset_key = format!("{ranges:?}");
+
+	// an unreadable file is reported by `font_data`
+	let contents = std::fs::read(font_path(&path,)?,).unwrap_or_default();
+	let name = path.value();
+	let key: [&[u8]; 3] = [name.as_bytes(), subset_key.as_bytes(), &contents,];
+	let (tokens, glyph_diags,) = cache::cached("font", &key, || {
+		let glyphs = convert_glyphs(&font_data(path.clone(),)?,);
+		let included: Vec<usize,> = match &ranges {
+			Some(ranges,) => ranges.iter().flat_map(|r| r.clone(),).collect(),
+			None => (0..CHARACTER_COUNT).collect(),
+		};
+		let skipped: Vec<String,> = included
+			.iter()
+			.filter(|i| glyphs[**i].is_none(),)
+			.map(|i| format!("{i:#04x}"),)
+			.collect();
+		// a clean font expands silently, so that it stays cached
+		let diags = if skipped.is_empty() {
+			vec![]
+		} else {
+			let summary = format!(
+				"{name}: {} of {} glyphs processed, {} skipped as blank: {}",
+				included.len() - skipped.len(),
+				included.len(),
+				skipped.len(),
+				skipped.join(", ")
+			);
+			vec![
+				Diag::Warn(summary,)
+					.with_span(path.span(),)
+					.with_code(Code::FONT_PIXEL,),
+			]
+		};
+
+		let fonts = included.iter().map(|i| glyphs[*i].unwrap_or(0,),);
+		let Some(ranges,) = &ranges else {
+			return Ok((quote::quote! { &[#(#fonts),*] }, diags,),);
+		};
+		let index = ranges.iter().scan(0u32, |first, r| {
+			let (start, end,) = (r.start as u32, r.end as u32,);
+			let entry = quote::quote! { (#start, #end, #first) };
+			*first += end - start;
+			Some(entry,)
+		},);
+		Ok((quote::quote! { (&[#(#index),*], &[#(#fonts),*]) }, diags,),)
+	},)?;
+
+	diags.extend(glyph_diags,);
+	Ok((tokens, diags,),)
+}
+
+/// Ranges of glyphs in `lit`, ascending and clamped to the font
+///
+/// A note reports the size saved by the subset. Parts of ranges outside
+/// the font are warned about and dropped.
+fn subset(lit: &LitStr,) -> Rslt<(Vec<Range<usize,>,>, Vec<Report,>,),> {
+	let error = |msg: String| Diag::error(Code::FONT_RANGES, lit.span(), msg,);
+	let number = |s: &str| {
+		let s = s.trim();
+		match s.strip_prefix("0x",).or_else(|| s.strip_prefix("0X",),) {
+			Some(hex,) => usize::from_str_radix(hex, 16,),
+			None => s.parse(),
+		}
+		.map_err(|_| error(format!("`{s}` is not a character code"),),)
+	};
+
+	let mut ranges: Vec<Range<usize,>,> = vec![];
+	let mut diags = vec![];
+	for part in lit.value().split(',',).filter(|p| !p.trim().is_empty(),) {
+		let range = match part.split_once("..=",) {
+			Some((start, last,),) => number(start,)?..number(last,)? + 1,
+			None => match part.split_once("..",) {
+				Some((start, end,),) => number(start,)?..number(end,)?,
+				None => {
+					return Err(error(format!(
+						"`{}` is not a range such as `0x20..0x7F`",
+						part.trim()
+					),),);
+				},
+			},
+		};
+		if range.is_empty() {
+			return Err(error(format!("`{}` is empty", part.trim()),),);
+		}
+		if ranges.last().is_some_and(|last| last.end > range.start,) {
+			return Err(error(format!(
+				"`{}` overlaps or precedes the previous range",
+				part.trim()
+			),),);
+		}
+		if range.end > CHARACTER_COUNT {
+			diags.push(
+				Diag::Warn(format!(
+					"`{}` exceeds the {CHARACTER_COUNT} glyphs of the font",
+					part.trim()
+				),)
+				.with_span(lit.span(),)
+				.with_code(Code::FONT_RANGES,),
+			);
+		}
+		let range = range.start.min(CHARACTER_COUNT,)
+			..range.end.min(CHARACTER_COUNT,);
+		if !range.is_empty() {
+			ranges.push(range,);
+		}
+	}
+	if ranges.is_empty() {
+		return Err(error("no glyph is in the ranges".to_string(),),);
+	}
+
+	let count: usize = ranges.iter().map(|r| r.len(),).sum();
+	let glyph = size_of::<u128,>();
+	let index = size_of::<[u32; 3],>() * ranges.len();
+	let saved = (CHARACTER_COUNT - count) * glyph;
+	diags.push(
+		Diag::Note(format!(
+			"embedding {count} of {CHARACTER_COUNT} glyphs saves {} bytes",
+			saved.saturating_sub(index,)
+		),)
+		.with_span(lit.span(),),
+	);
+	Ok((ranges, diags,),)
+}
+
+/// Expansion of `font!` when the font can not be loaded: an empty glyph
+/// table, or an empty subset, after the errors
+pub fn fallback(
+	args: &FontArgs,
+	errors: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+	let table = match args.ranges {
+		Some(_,) => quote::quote! { (&[], &[]) },
+		None => quote::quote! { &[0; #CHARACTER_COUNT] },
+	};
+	quote::quote! {
+		{
+			#errors
+			#table
+		}
+	}
 }
 
 /// Loads and processes ASCII font data from a specified file path
@@ -39,9 +230,9 @@ pub fn font(path: syn::LitStr,) -> RsltP {
 /// one ASCII character. Each string contains 128 characters (16 lines × 8
 /// characters per line).
 ///
-/// # Panics
+/// # Errors
 ///
-/// This function will panic if:
+/// Returns an error pointing at `specified_path` if:
 /// - The font file cannot be read
 /// - Any character bitmap doesn't have exactly 128 characters
 ///
@@ -54,15 +245,16 @@ pub fn font(path: syn::LitStr,) -> RsltP {
 /// assert_eq!(font_data.len(), 256);
 /// ```
 fn font_data(specified_path: LitStr,) -> Rslt<Vec<String,>,> {
-	// Get the project root directory, falling back to compile-time directory if
-	// needed
-	let project_root = std::env::var("CARGO_MANIFEST_DIR",)?;
-
-	// Construct the full path to the font file
-	let path = format!("{project_root}/{}", specified_path.value());
+	let path = font_path(&specified_path,)?;
 
 	// Read the font data file
-	let font_data = std::fs::read_to_string(&path,)?;
+	let font_data = std::fs::read_to_string(&path,).map_err(|e| {
+		Diag::error(
+			Code::FONT_READ,
+			specified_path.span(),
+			format!("failed to read {path}: {e}"),
+		)
+	},)?;
 
 	// Split the file into lines and filter out empty lines and hex values
 	let fonts_data_lines: Vec<&str,> = font_data
@@ -72,6 +264,18 @@ fn font_data(specified_path: LitStr,) -> Rslt<Vec<String,>,> {
 		.filter(|s| !(s.is_empty() || s.contains("0x",)),) // Remove empty lines and hex values
 		.collect();
 
+	if fonts_data_lines.len() < CHARACTER_COUNT * 16 {
+		return Err(Diag::error(
+			Code::FONT_GLYPH,
+			specified_path.span(),
+			format!(
+				"{path} has {} lines, expected {} for {CHARACTER_COUNT} glyphs",
+				fonts_data_lines.len(),
+				CHARACTER_COUNT * 16
+			),
+		),);
+	}
+
 	// Process each character (16 lines per character)
 	let mut fonts = vec!["".to_string(); CHARACTER_COUNT];
 	for idx in 0..CHARACTER_COUNT {
@@ -81,10 +285,29 @@ fn font_data(specified_path: LitStr,) -> Rslt<Vec<String,>,> {
 
 	// Verify that each character has exactly 128 characters (16 lines × 8
 	// chars)
-	fonts.iter().for_each(|s| assert_eq!(s.len(), 128),);
+	if let Some((idx, glyph,),) =
+		fonts.iter().enumerate().find(|(_, s,)| s.len() != 128,)
+	{
+		return Err(Diag::error(
+			Code::FONT_GLYPH,
+			specified_path.span(),
+			format!(
+				"glyph {idx:#04x} of {path} has {} pixels, expected 16 lines \
+				 of 8",
+				glyph.len()
+			),
+		),);
+	}
 	Ok(fonts,)
 }
 
+/// Path of the font file, which is given relative to the manifest directory
+/// of the crate expanding the macro
+fn font_path(specified_path: &LitStr,) -> Rslt<String,> {
+	let project_root = std::env::var("CARGO_MANIFEST_DIR",)?;
+	Ok(format!("{project_root}/{}", specified_path.value()),)
+}
+
 /// Converts text-based font bitmaps to binary bitfield representation
 ///
 /// This function takes the string-based font data (with '.' and '@' characters)
@@ -117,36 +340,41 @@ fn font_data(specified_path: LitStr,) -> Rslt<Vec<String,>,> {
 /// let bitfields = convert_bitfield(&fonts);
 /// assert_eq!(bitfields.len(), 256);
 /// ```
+///
+/// Glyphs skipped by [`convert_glyphs`] are blank, as `font!` embeds them.
+#[cfg(test)]
 fn convert_bitfield(fonts: &[String],) -> Vec<u128,> {
-	let fonts: Vec<u128,> = fonts
-		.iter()
-		.map(|s| {
-			// Split each character's bitmap into 16 lines
-			let lines = s.split("\n",).collect::<Vec<&str,>>();
-
-			// Process each line and combine into a single u128
-			let a: u128 = lines
-				.into_iter()
-				.enumerate()
-				.map(|(i, s,)| {
-					// Convert '.' to '0' and '@' to '1'
-					let s = s.replace(".", "0",).replace("@", "1",);
-
-					// Reverse the bit order for proper display orientation
-					let s: String = s.chars().rev().collect();
-
-					// Parse the binary string to get the line value
-					let line = u128::from_str_radix(&s, 2,).unwrap();
-
-					// Shift the line to its proper position (line i goes to bit
-					// position i*8)
-					line << i
-				},)
-				.sum(); // Combine all lines using bitwise OR (via sum)
-			a
+	convert_glyphs(fonts,).into_iter().map(|g| g.unwrap_or(0,),).collect()
+}
+
+/// Converts each glyph in parallel, keeping their order. A glyph with pixels
+/// other than '.' and '@', or with a line wider than 128 pixels, is `None`
+fn convert_glyphs(fonts: &[String],) -> Vec<Option<u128,>,> {
+	fonts.par_iter().map(|s| glyph_bits(s,),).collect()
+}
+
+fn glyph_bits(glyph: &str,) -> Option<u128,> {
+	// Split each character's bitmap into 16 lines
+	let lines = glyph.split("\n",);
+
+	// Process each line and combine into a single u128
+	lines
+		.enumerate()
+		.map(|(i, s,)| {
+			// Convert '.' to '0' and '@' to '1'
+			let s = s.replace(".", "0",).replace("@", "1",);
+
+			// Reverse the bit order for proper display orientation
+			let s: String = s.chars().rev().collect();
+
+			// Parse the binary string to get the line value
+			let line = u128::from_str_radix(&s, 2,).ok()?;
+
+			// Shift the line to its proper position (line i goes to bit
+			// position i*8)
+			Some(line << i,)
 		},)
-		.collect();
-	fonts
+		.sum() // Combine all lines using bitwise OR (via sum)
 }
 
 #[cfg(test)]
@@ -748,6 +976,90 @@ mod tests {
 		Ok((),)
 	}
 
+	#[test]
+	fn test_subset_ranges() {
+		let lit = |s: &str| LitStr::new(s, proc_macro2::Span::call_site(),);
+
+		let ranges = lit("0x20..0x7F, 0xB3..=0xB5, 250..300",);
+		let (ranges, diags,) = subset(&ranges,).unwrap();
+		assert_eq!(ranges, [0x20..0x7f, 0xb3..0xb6, 250..256]);
+		// the last range is clamped, then the size saved is noted
+		assert_eq!(diags.len(), 2);
+		assert_eq!(diags[0].level, crate::oso_proc_macro_helper::Level::Warn);
+		assert!(diags[1].msg.starts_with("embedding 104 of 256 glyphs"));
+
+		for bad in ["0x20", "0x7F..0x20", "0x20..0x30, 0x28..0x40", "x..y", ""]
+		{
+			let err = subset(&lit(bad,),).unwrap_err();
+			assert!(err.to_string().starts_with("[OSO0006]"), "{bad}: {err}");
+		}
+	}
+
+	#[test]
+	fn test_font_args_parse() {
+		let args: FontArgs = syn::parse_quote!("a.dat", ranges = "0x20..0x7F");
+		assert_eq!(args.path.value(), "a.dat");
+		assert_eq!(args.ranges.unwrap().value(), "0x20..0x7F");
+
+		let args: FontArgs = syn::parse_quote!("a.dat",);
+		assert!(args.ranges.is_none());
+		let typo = syn::parse_str::<FontArgs,>(r#""a.dat", range = "1..2""#);
+		assert!(typo.is_err());
+	}
+
+	#[test]
+	fn test_font_subset_index() -> Rslt<(),> {
+		let project_root = std::env::var("CARGO_MANIFEST_DIR",)?;
+		let file = "test_font_subset_index.txt";
+		fs::write(
+			format!("{project_root}/{file}"),
+			"...@@...\n".repeat(16 * CHARACTER_COUNT,),
+		)?;
+
+		let args = FontArgs {
+			path:   LitStr::new(file, proc_macro2::Span::call_site(),),
+			ranges: Some(LitStr::new(
+				"0x41..0x43, 0x61..=0x61",
+				proc_macro2::Span::call_site(),
+			),),
+		};
+		let (tokens, diags,) = font(args,)?;
+		fs::remove_file(format!("{project_root}/{file}"),)?;
+
+		let code = tokens.to_string();
+		assert!(code.contains("(65u32 , 67u32 , 0u32)"), "{code}");
+		assert!(code.contains("(97u32 , 98u32 , 2u32)"), "{code}");
+		assert_eq!(code.matches("u128",).count(), 3);
+		assert_eq!(diags.len(), 1);
+		assert!(diags[0].msg.contains("3 of 256"));
+		Ok((),)
+	}
+
+	#[test]
+	fn test_font_skips_unconvertible_glyphs() -> Rslt<(),> {
+		let project_root = std::env::var("CARGO_MANIFEST_DIR",)?;
+		let file = "test_font_skipped_glyph.txt";
+		let mut data = String::new();
+		for i in 0..CHARACTER_COUNT {
+			let line = if i == 0x41 { "...xx...\n" } else { "...@@...\n" };
+			data.push_str(&line.repeat(16,),);
+		}
+		fs::write(format!("{project_root}/{file}"), data,)?;
+
+		let lit_str = LitStr::new(file, proc_macro2::Span::call_site(),);
+		let (tokens, diags,) = font(lit_str,)?;
+		fs::remove_file(format!("{project_root}/{file}"),)?;
+
+		assert_eq!(diags.len(), 1);
+		assert_eq!(diags[0].code, Some(Code::FONT_PIXEL));
+		let summary = "255 of 256 glyphs processed, 1 skipped";
+		assert!(diags[0].msg.contains(summary));
+		assert!(diags[0].msg.ends_with("0x41"));
+		assert!(tokens.to_string().contains(" 0u128 ,"));
+		assert_eq!(glyph_bits("...x....",), None);
+		Ok((),)
+	}
+
 	#[test]
 	fn test_font_data_with_wrong_character_length() -> Rslt<(),> {
 		use std::env;
@@ -771,9 +1083,10 @@ mod tests {
 			proc_macro2::Span::call_site(),
 		);
 
-		// This should panic due to the assertion in font_data
-		let result = std::panic::catch_unwind(|| font_data(lit_str,),);
-		assert!(result.is_err());
+		// The malformed glyph is reported at the path literal
+		let err = font_data(lit_str,).unwrap_err();
+		assert!(err.downcast_ref::<syn::Error>().is_some());
+		assert!(err.to_string().starts_with("[OSO0002]"));
 
 		// Cleanup
 		let _ = fs::remove_file(test_file_path,);
@@ -1,11 +1,38 @@
+//! # Workspace Chart
+//!
+//! `#[derive(FromPathBuf)]` generates an enum of every crate in the workspace
+//! from the field marked `#[chart]`, and conversions between the struct, the
+//! enum and paths.
+//!
+//! The enum also knows how crates are nested in the filesystem: the parent of
+//! a crate is the nearest crate containing its directory. Navigation methods
+//! of the struct convert to the types given to the attribute, which default
+//! to the struct itself:
+//!
+//! ```ignore
+//! #[derive(FromPathBuf)]
+//! struct OsoCrate {
+//! 	path: PathBuf,
+//! 	#[chart(parent = OsoCrate, children = [OsoCrate])]
+//! 	i_am: OsoCrateChart,
+//! }
+//!
+//! let kernel = project_root()?.find("oso_kernel",);
+//! ```
+
 use crate::RsltP;
 use anyhow::Result as Rslt;
 use anyhow::anyhow;
 use anyhow::bail;
 use itertools::Itertools;
+use oso_dev_util_helper::chart::DepChart;
+use oso_dev_util_helper::fs::CARGO_MANIFEST;
 use oso_dev_util_helper::fs::all_crates;
+use oso_dev_util_helper::fs::read_toml;
 use oso_dev_util_helper::util::CaseConvert;
 use quote::format_ident;
+use std::path::PathBuf;
+use syn::punctuated::Punctuated;
 
 pub fn from_path_buf(item: syn::DeriveInput,) -> RsltP {
 	match item.data {
@@ -20,17 +47,119 @@ pub fn struct_impl(mut struct_def: syn::DeriveInput,) -> RsltP {
 	let enum_parts = enum_parts(&struct_def,)?;
 	let enum_name = enum_parts.name.clone();
 	let enum_dumped = enum_parts.dump();
+	let navigation = navigation(&struct_def,)?;
 	let struct_dumped = struct_dump(struct_def, enum_name,)?;
 
 	Ok((
 		quote::quote! {
 			#enum_dumped
 			#struct_dumped
+			#navigation
 		},
 		vec![],
 	),)
 }
 
+/// arguments of `#[chart(parent = Ty, children = [Ty])]`
+#[derive(Default,)]
+struct ChartAttr {
+	parent:   Option<syn::Type,>,
+	children: Option<syn::Type,>,
+}
+
+impl ChartAttr {
+	/// arguments of `attr`. bare `#[chart]` has none
+	fn parse(attr: &syn::Attribute,) -> syn::Result<Self,> {
+		let mut args = Self::default();
+		if matches!(attr.meta, syn::Meta::Path(_)) {
+			return Ok(args,);
+		}
+
+		attr.parse_nested_meta(|meta| {
+			if meta.path.is_ident("parent",) {
+				args.parent = Some(meta.value()?.parse()?,);
+			} else if meta.path.is_ident("children",) {
+				let value = meta.value()?;
+				let ty = if value.peek(syn::token::Bracket,) {
+					let content;
+					syn::bracketed!(content in value);
+					type Types = Punctuated<syn::Type, syn::Token![,],>;
+					let tys = Types::parse_terminated(&content,)?;
+					let mut tys = tys.into_iter();
+					match (tys.next(), tys.next(),) {
+						(Some(ty,), None,) => ty,
+						_ => {
+							return Err(meta.error(
+								"children are converted to exactly one type",
+							),);
+						},
+					}
+				} else {
+					value.parse()?
+				};
+				args.children = Some(ty,);
+			} else {
+				return Err(meta.error("expected `parent` or `children`",),);
+			}
+			Ok((),)
+		},)?;
+		Ok(args,)
+	}
+}
+
+/// `parent()`, `children()` and `find()` of the struct, delegating to the
+/// field marked `#[chart]`. nothing without the field
+fn navigation(
+	struct_def: &syn::DeriveInput,
+) -> Rslt<Option<proc_macro2::TokenStream,>,> {
+	let syn::Data::Struct(syn::DataStruct { fields, .. },) = &struct_def.data
+	else {
+		bail!("expected struct, found {struct_def:?}")
+	};
+	let Some((i, field, attr,),) =
+		fields.iter().enumerate().find_map(|(i, f,)| {
+			let attr = f.attrs.iter().find(|a| a.path().is_ident("chart",),)?;
+			Some((i, f, attr,),)
+		},)
+	else {
+		return Ok(None,);
+	};
+
+	let member = match &field.ident {
+		Some(ident,) => quote::quote! { #ident },
+		None => {
+			let index = syn::Index::from(i,);
+			quote::quote! { #index }
+		},
+	};
+	let ChartAttr { parent, children, } = ChartAttr::parse(attr,)?;
+	let parent = parent.unwrap_or_else(|| syn::parse_quote! { Self },);
+	let children = children.unwrap_or_else(|| syn::parse_quote! { Self },);
+
+	let ident = &struct_def.ident;
+	let (impl_generics, ty_generics, where_clause,) =
+		struct_def.generics.split_for_impl();
+	Ok(Some(quote::quote! {
+		impl #impl_generics #ident #ty_generics #where_clause {
+			/// nearest crate containing this one
+			pub fn parent(&self) -> Option<#parent> {
+				self.#member.parent().map(<#parent>::from)
+			}
+
+			/// crates whose parent is this one
+			pub fn children(&self) -> Vec<#children> {
+				let children = self.#member.children().into_iter();
+				children.map(<#children>::from).collect()
+			}
+
+			/// this crate or a crate below it named `name`
+			pub fn find(&self, name: &str) -> Option<#children> {
+				self.#member.find(name).map(<#children>::from)
+			}
+		}
+	},),)
+}
+
 fn trim_name(struct_def: &mut syn::DeriveInput,) {
 	let mut name = struct_def.ident.to_string();
 	name.remove_matches('_',);
@@ -42,6 +171,12 @@ struct EnumParts {
 	variants:      Vec<proc_macro2::TokenStream,>,
 	variants_attr: Vec<Option<proc_macro2::TokenStream,>,>,
 	paths:         Vec<proc_macro2::TokenStream,>,
+	/// package names, or directory names of virtual manifests
+	names:         Vec<String,>,
+	/// index of the parent of each crate
+	parents:       Vec<Option<usize,>,>,
+	/// Dependency chart of the crates, rendered into the enum's docs
+	chart:         Option<String,>,
 }
 
 impl EnumParts {
@@ -50,8 +185,28 @@ impl EnumParts {
 		let variants = &self.variants;
 		let variants_attr = &self.variants_attr;
 		let paths = &self.paths;
+		let names = &self.names;
+		let parents = self.parents.iter().map(|parent| match parent {
+			Some(i,) => {
+				let parent = &variants[*i];
+				quote::quote! { Some(Self::#parent) }
+			},
+			None => quote::quote! { None },
+		},);
+		let children = (0..variants.len()).map(|i| {
+			let children = self
+				.parents
+				.iter()
+				.positions(|parent| *parent == Some(i,),)
+				.map(|c| &variants[c],);
+			quote::quote! { vec![#(Self::#children),*] }
+		},);
+		let doc = self.chart.iter().map(|chart| {
+			format!("Crates of the workspace\n\n```mermaid\n{chart}```")
+		},);
 
 		quote::quote! {
+			#(#[doc = #doc])*
 			#[derive(Default, PartialEq, Eq, Clone, Debug)]
 			pub enum #name {
 				#(
@@ -67,6 +222,35 @@ impl EnumParts {
 						#(Self::#variants => PathBuf::from_str(#paths).unwrap(),)*
 					}
 				}
+
+				/// package name, or directory name of a virtual manifest
+				pub fn name(&self) -> &'static str {
+					match self {
+						#(Self::#variants => #names,)*
+					}
+				}
+
+				/// nearest crate containing this one
+				pub fn parent(&self) -> Option<Self> {
+					match self {
+						#(Self::#variants => #parents,)*
+					}
+				}
+
+				/// crates whose parent is this one
+				pub fn children(&self) -> Vec<Self> {
+					match self {
+						#(Self::#variants => #children,)*
+					}
+				}
+
+				/// this crate or a crate below it named `name`. depth first
+				pub fn find(&self, name: &str) -> Option<Self> {
+					if self.name() == name {
+						return Some(self.clone());
+					}
+					self.children().iter().find_map(|child| child.find(name))
+				}
 			}
 
 			impl From<PathBuf,> for #name {
@@ -122,7 +306,54 @@ fn enum_parts(struct_def: &syn::DeriveInput,) -> Rslt<EnumParts,> {
 		)
 		.try_collect()?;
 
-	Ok(EnumParts { name, variants, variants_attr, paths, },)
+	let names = crate_list.iter().map(|pb| crate_name(pb,),).try_collect()?;
+	let parents = parent_indices(&crate_list,);
+
+	// docs are a nicety. a manifest the chart can't read must not break
+	// the derive
+	let chart = DepChart::new(&crate_list,).ok().map(|c| c.mermaid(),);
+
+	Ok(EnumParts {
+		name,
+		variants,
+		variants_attr,
+		paths,
+		names,
+		parents,
+		chart,
+	},)
+}
+
+/// package name in the manifest of `dir`, or its directory name
+fn crate_name(dir: &std::path::Path,) -> Rslt<String,> {
+	if let Some(toml,) = read_toml(dir.join(CARGO_MANIFEST,),)
+		&& let Some(name,) = toml?
+			.get("package",)
+			.and_then(|pkg| pkg.get("name",),)
+			.and_then(|name| name.as_str(),)
+	{
+		return Ok(name.to_string(),);
+	}
+	let name = dir
+		.file_name()
+		.and_then(|name| name.to_str(),)
+		.ok_or(anyhow!("{} has no directory name", dir.display()),)?;
+	Ok(name.to_string(),)
+}
+
+/// index of the deepest other crate whose directory contains each crate
+fn parent_indices(crates: &[PathBuf],) -> Vec<Option<usize,>,> {
+	crates
+		.iter()
+		.map(|child| {
+			crates
+				.iter()
+				.enumerate()
+				.filter(|(_, dir,)| *dir != child && child.starts_with(dir,),)
+				.max_by_key(|(_, dir,)| dir.components().count(),)
+				.map(|(i, _,)| i,)
+		},)
+		.collect()
 }
 
 fn detect_chart_type(struct_def: &syn::DeriveInput,) -> Option<syn::Type,> {
@@ -130,11 +361,10 @@ fn detect_chart_type(struct_def: &syn::DeriveInput,) -> Option<syn::Type,> {
 	else {
 		panic!("expected struct, found {struct_def:?}")
 	};
-	fields.iter().find(|f| {
-		f.attrs.iter().any(
-			|attr| matches!(attr.meta, syn::Meta::Path(ref p) if p.get_ident() == Some(&format_ident!("chart"))),
-		)
-	},).map(|f| f.ty.clone())
+	fields
+		.iter()
+		.find(|f| f.attrs.iter().any(|attr| attr.path().is_ident("chart",),),)
+		.map(|f| f.ty.clone(),)
 }
 
 fn struct_dump(
@@ -242,9 +472,7 @@ fn field_construct(
 }
 
 fn is_attred(f: &mut syn::Field,) -> bool {
-	f.attrs
-		.iter()
-		.any(|a| matches!(&a.meta, syn::Meta::Path(p) if p.is_ident("chart")),)
+	f.attrs.iter().any(|a| a.path().is_ident("chart",),)
 }
 
 #[cfg(test)]
@@ -400,6 +628,60 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn test_chart_attr_parse() {
+		let field: syn::Field = parse_quote! {
+			#[chart(parent = Workspace, children = [Package])]
+			i_am: Chart
+		};
+		let attr = ChartAttr::parse(&field.attrs[0],).unwrap();
+		assert_eq!(attr.parent, Some(parse_quote! { Workspace }));
+		assert_eq!(attr.children, Some(parse_quote! { Package }));
+
+		let bare: syn::Attribute = parse_quote! { #[chart] };
+		let attr = ChartAttr::parse(&bare,).unwrap();
+		assert!(attr.parent.is_none() && attr.children.is_none());
+
+		let two: syn::Attribute = parse_quote! { #[chart(children = [A, B])] };
+		assert!(ChartAttr::parse(&two,).is_err());
+		let unknown: syn::Attribute = parse_quote! { #[chart(sibling = A)] };
+		assert!(ChartAttr::parse(&unknown,).is_err());
+	}
+
+	#[test]
+	fn test_parent_indices() {
+		let crates = [
+			PathBuf::from("/ws/components/loader",),
+			PathBuf::from("/ws/components/loader/core",),
+			PathBuf::from("/ws/components/kernel/core",),
+			PathBuf::from("/ws/xtask",),
+			PathBuf::from("/ws",),
+		];
+		assert_eq!(parent_indices(&crates,), vec![
+			Some(4),
+			Some(0),
+			Some(4),
+			Some(4),
+			None
+		]);
+	}
+
+	#[test]
+	fn test_navigation_delegates_to_chart() {
+		let tuple: syn::DeriveInput = parse_quote! {
+			struct Crate(PathBuf, #[chart(parent = Workspace)] CrateChart);
+		};
+		let tokens = navigation(&tuple,).unwrap().unwrap().to_string();
+		assert!(tokens.contains("Option < Workspace >"));
+		assert!(tokens.contains("self . 1 . children ()"));
+		assert!(tokens.contains("Vec < Self >"));
+
+		let plain: syn::DeriveInput = parse_quote! {
+			struct Crate { path: PathBuf }
+		};
+		assert!(navigation(&plain,).unwrap().is_none());
+	}
+
 	#[test]
 	fn test_camel_case_conversion_logic() {
 		// Test the camel case conversion logic used in enum_impl
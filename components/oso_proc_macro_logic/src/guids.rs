@@ -0,0 +1,255 @@
+//! # UEFI GUID Table
+//!
+//! Generates a lookup table from GUIDs to their names, so diagnostics print
+//! `EFI_LOADED_IMAGE_PROTOCOL` instead of sixteen bytes.
+//!
+//! GUIDs are scattered over the whole UEFI specification rather than listed
+//! in one appendix, so the table is read from a vendored file of the
+//! `#define` blocks as the specification prints them:
+//!
+//! ```c
+//! // comments are ignored
+//! #define EFI_LOADED_IMAGE_PROTOCOL_GUID\
+//!  {0x5B1B31A1,0x9562,0x11d2,\
+//!  {0x8E,0x3F,0x00,0xA0,0xC9,0x69,0x72,0x3B}}
+//! ```
+//!
+//! New entries are added by pasting the block from the specification.
+
+use crate::RsltP;
+use crate::oso_proc_macro_helper::Code;
+use crate::oso_proc_macro_helper::Diag;
+use crate::oso_proc_macro_helper::Report;
+use anyhow::Result as Rslt;
+use proc_macro2::Span;
+use std::collections::HashSet;
+use syn::LitStr;
+
+/// suffix of the names in the specification, left out of the table
+const GUID_SUFFIX: &str = "_GUID";
+
+/// a `#define` of the table
+#[derive(Debug, Clone, PartialEq, Eq,)]
+pub struct GuidDef {
+	/// name without [`GUID_SUFFIX`]
+	pub name:  String,
+	/// `time_low`, `time_mid`, `time_high_and_version` and the eight bytes
+	/// following them
+	pub data1: u32,
+	pub data2: u16,
+	pub data3: u16,
+	pub data4: [u8; 8],
+}
+
+impl GuidDef {
+	fn to_tokens(&self,) -> proc_macro2::TokenStream {
+		let Self { name, data1, data2, data3, data4, } = self;
+		let time_mid = data2.to_le_bytes();
+		let time_high_and_version = data3.to_le_bytes();
+		let clock_seq_high_and_reserved = data4[0];
+		let clock_seq_low = data4[1];
+		let node = &data4[2..];
+		quote::quote! {
+			(
+				Guid {
+					time_low: #data1,
+					time_mid: [#(#time_mid),*],
+					time_high_and_version: [#(#time_high_and_version),*],
+					clock_seq_high_and_reserved: #clock_seq_high_and_reserved,
+					clock_seq_low: #clock_seq_low,
+					node: [#(#node),*],
+				},
+				#name,
+			)
+		}
+	}
+}
+
+/// Generates `KNOWN_GUIDS` and `name_of` from the table at `path`, relative
+/// to the manifest directory of the expanding crate
+///
+/// The expansion refers to `Guid` of the caller's scope
+pub fn guids(path: LitStr,) -> RsltP {
+	let project_root = std::env::var("CARGO_MANIFEST_DIR",)?;
+	let file = format!("{project_root}/{}", path.value());
+	let table = std::fs::read_to_string(&file,).map_err(|e| {
+		Diag::error(
+			Code::GUID_TABLE,
+			path.span(),
+			format!("failed to read {file}: {e}"),
+		)
+	},)?;
+
+	let defs = parse_table(&table,)
+		.map_err(|e| Diag::error(Code::GUID_TABLE, path.span(), e,),)?;
+	let diags = duplicates(&defs, path.span(),);
+	let entries = defs.iter().map(GuidDef::to_tokens,);
+
+	Ok((
+		quote::quote! {
+			/// GUIDs defined by the UEFI specification and their names
+			pub const KNOWN_GUIDS: &[(Guid, &str,)] = &[#(#entries,)*];
+
+			/// name of `guid` in [`KNOWN_GUIDS`]
+			pub fn name_of(guid: &Guid,) -> Option<&'static str,> {
+				KNOWN_GUIDS.iter().find(|(g, _,)| g == guid,).map(|(_, n,)| *n,)
+			}
+		},
+		diags,
+	),)
+}
+
+/// empty table, so that callers of `name_of` still compile
+pub fn fallback(
+	_path: &LitStr,
+	errors: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+	quote::quote! {
+		#errors
+		pub const KNOWN_GUIDS: &[(Guid, &str,)] = &[];
+		pub fn name_of(_guid: &Guid,) -> Option<&'static str,> {
+			None
+		}
+	}
+}
+
+/// parses every `#define` of `table`
+pub fn parse_table(table: &str,) -> Rslt<Vec<GuidDef,>,> {
+	let table = table
+		.lines()
+		.filter(|l| !l.trim_start().starts_with("//",),)
+		.map(|l| l.trim_end().trim_end_matches('\\',),)
+		.collect::<Vec<_,>>()
+		.join(" ",);
+
+	table
+		.split("#define",)
+		.skip(1,)
+		.map(|def| {
+			let def = def.trim();
+			let end = def
+				.find(|c: char| c == '{' || c.is_whitespace(),)
+				.unwrap_or(def.len(),);
+			let (name, value,) = def.split_at(end,);
+			parse_def(name, value,)
+				.map_err(|e| anyhow::anyhow!("{name}: {e}"),)
+		},)
+		.collect()
+}
+
+fn parse_def(name: &str, value: &str,) -> Rslt<GuidDef,> {
+	let Some(name,) = name.strip_suffix(GUID_SUFFIX,) else {
+		anyhow::bail!("names of GUIDs end with {GUID_SUFFIX}")
+	};
+	let numbers: Vec<u32,> = value
+		.split(|c: char| !c.is_ascii_alphanumeric(),)
+		.filter(|s| !s.is_empty(),)
+		.map(|s| {
+			let hex = s.strip_prefix("0x",).or(s.strip_prefix("0X",),);
+			let Some(hex,) = hex else {
+				anyhow::bail!("`{s}` is not a hex number")
+			};
+			Ok(u32::from_str_radix(hex, 16,)?,)
+		},)
+		.try_collect()?;
+
+	let [data1, data2, data3, ref data4 @ ..] = numbers[..] else {
+		anyhow::bail!("expected 11 numbers, found {}", numbers.len())
+	};
+	if data4.len() != 8 {
+		anyhow::bail!("expected 11 numbers, found {}", numbers.len())
+	}
+	let narrow = |n: u32, max: u32| {
+		if n > max {
+			anyhow::bail!("{n:#x} is larger than {max:#x}")
+		}
+		Ok(n,)
+	};
+	let mut bytes = [0; 8];
+	for (byte, n,) in bytes.iter_mut().zip(data4,) {
+		*byte = narrow(*n, u8::MAX as u32,)? as u8;
+	}
+	Ok(GuidDef {
+		name:  name.to_string(),
+		data1,
+		data2: narrow(data2, u16::MAX as u32,)? as u16,
+		data3: narrow(data3, u16::MAX as u32,)? as u16,
+		data4: bytes,
+	},)
+}
+
+/// warnings about names or GUIDs defined twice. lookups return the first
+fn duplicates(defs: &[GuidDef], span: Span,) -> Vec<Report,> {
+	let mut names = HashSet::new();
+	let mut guids = HashSet::new();
+	let mut diags = vec![];
+	for def in defs {
+		if !names.insert(&def.name,) {
+			diags.push(
+				Diag::Warn(format!("{} is defined twice", def.name),)
+					.with_span(span,)
+					.with_code(Code::GUID_TABLE,),
+			);
+		}
+		if !guids.insert((def.data1, def.data2, def.data3, def.data4,),) {
+			diags.push(
+				Diag::Warn(format!(
+					"GUID of {} is already named differently",
+					def.name
+				),)
+				.with_span(span,)
+				.with_code(Code::GUID_TABLE,),
+			);
+		}
+	}
+	diags
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const TABLE: &str = r"
+// 9.1 EFI Loaded Image Protocol
+#define EFI_LOADED_IMAGE_PROTOCOL_GUID\
+ {0x5B1B31A1,0x9562,0x11d2,\
+ {0x8E,0x3F,0x00,0xA0,0xC9,0x69,0x72,0x3B}}
+
+#define EFI_DEVICE_PATH_PROTOCOL_GUID \
+ {0x09576e91,0x6d3f,0x11d2,\
+ {0x8e,0x39,0x00,0xa0,0xc9,0x69,0x72,0x3b}}
+";
+
+	#[test]
+	fn test_parse_table() {
+		let defs = parse_table(TABLE,).unwrap();
+		assert_eq!(defs.len(), 2);
+		assert_eq!(defs[0], GuidDef {
+			name:  "EFI_LOADED_IMAGE_PROTOCOL".to_string(),
+			data1: 0x5B1B31A1,
+			data2: 0x9562,
+			data3: 0x11d2,
+			data4: [0x8E, 0x3F, 0x00, 0xA0, 0xC9, 0x69, 0x72, 0x3B,],
+		});
+		assert_eq!(defs[1].name, "EFI_DEVICE_PATH_PROTOCOL");
+		assert!(duplicates(&defs, Span::call_site(),).is_empty());
+	}
+
+	#[test]
+	fn test_parse_table_rejects_malformed() {
+		let short = "#define A_GUID {0x1,0x2,0x3,{0x4}}";
+		assert!(parse_table(short,).unwrap_err().to_string().contains("11"));
+		let wide = "#define A_GUID {0x1,0x2,0x3,\\
+		            {0x4,0x5,0x6,0x7,0x8,0x9,0xa,0x100}}";
+		assert!(parse_table(wide,).unwrap_err().to_string().contains("0x100"));
+		let unnamed = "#define A {0x1,0x2,0x3,{0x4,0x5,0x6,0x7,0x8,0x9,0xa}}";
+		assert!(parse_table(unnamed,).is_err());
+	}
+
+	#[test]
+	fn test_duplicates_warn() {
+		let twice = format!("{TABLE}{TABLE}");
+		let defs = parse_table(&twice,).unwrap();
+		assert_eq!(duplicates(&defs, Span::call_site(),).len(), 4);
+	}
+}
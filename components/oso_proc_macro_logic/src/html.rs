@@ -0,0 +1,169 @@
+//! # HTML DOM Helpers
+//!
+//! Small selector functions over [`markup5ever_rcdom`] trees, shared by
+//! macros which scrape specification pages.
+//!
+//! - [`get_element_by_id`], [`get_elements_by_attribute`] and
+//!   [`get_elements_by_name`] find elements
+//! - [`text_content`] collects the text of an element
+//! - [`table`] reads tables by caption or id with header-keyed cells
+
+use html5ever::local_name;
+use markup5ever::LocalNameStaticSet;
+use markup5ever_rcdom::Node;
+use markup5ever_rcdom::NodeData;
+use std::rc::Rc;
+
+pub mod table;
+
+/// Searches for an HTML element with a specific ID in the DOM tree
+///
+/// This function recursively traverses the HTML DOM tree to find an element
+/// with the specified ID attribute. It performs a depth-first search through
+/// all child nodes.
+///
+/// # Arguments
+///
+/// * `node` - The root node to start searching from
+/// * `id` - The ID attribute value to search for
+///
+/// # Returns
+///
+/// An `Option<Rc<Node>>` containing the found element, or `None` if not found
+pub fn get_element_by_id(node: Rc<Node,>, id: &str,) -> Option<Rc<Node,>,> {
+	// Check if current node has the target ID
+	let found = if let NodeData::Element { attrs, .. } = &node.data {
+		let attrs_borrow = attrs.borrow();
+		attrs_borrow.iter().any(|a| {
+			// Create a tendril for the target ID
+			let value = unsafe {
+				tendril::StrTendril::from_byte_slice_without_validating(
+					id.as_bytes(),
+				)
+			};
+			let local_name = local_name!("id");
+
+			// Check if this attribute is an ID with the target value
+			*a.name.local == *local_name && a.value == value
+		},)
+	} else {
+		false
+	};
+
+	if found {
+		Some(node,)
+	} else {
+		// Recursively search child nodes
+		node.children
+			.borrow()
+			.iter()
+			.find_map(|n| get_element_by_id(n.clone(), id,),)
+	}
+}
+
+/// Searches for HTML elements with a specific attribute value
+///
+/// This function recursively searches the DOM tree for elements that have
+/// an attribute with the specified name containing the specified value.
+///
+/// # Arguments
+///
+/// * `node` - The root node to start searching from
+/// * `attr` - The attribute name to search for
+/// * `value` - The value that the attribute should contain
+///
+/// # Returns
+///
+/// A vector of all matching nodes. Returns an empty vector if no matches are
+/// found.
+pub fn get_elements_by_attribute(
+	node: Rc<Node,>,
+	attr: &str,
+	value: &str,
+) -> Vec<Rc<Node,>,> {
+	let mut rslt = vec![];
+
+	// Check if current node matches the attribute criteria
+	let matches = match &node.data {
+		NodeData::Element { attrs, .. } => attrs.borrow().iter().any(|a| {
+			let local_name =
+				string_cache::Atom::<LocalNameStaticSet,>::from(attr,);
+			*a.name.local == *local_name && a.value.contains(value,)
+		},),
+		_ => false,
+	};
+
+	if matches {
+		rslt.push(node.clone(),);
+	}
+
+	// Recursively search child nodes
+	node.children.borrow().iter().for_each(|n| {
+		let mut child_matches =
+			get_elements_by_attribute(n.clone(), attr, value,);
+		rslt.append(&mut child_matches,);
+	},);
+
+	rslt
+}
+
+/// Searches for HTML elements with a specific tag name
+///
+/// This function recursively searches the DOM tree for elements with
+/// the specified tag name (e.g., "div", "table", "tr").
+///
+/// # Arguments
+///
+/// * `node` - The root node to start searching from
+/// * `tag_name` - The HTML tag name to search for
+///
+/// # Returns
+///
+/// A vector of all matching elements
+///
+/// # Caution
+///
+/// clone argument passed to `node` every time
+pub fn get_elements_by_name(
+	node: Rc<Node,>,
+	tag_name: &str,
+) -> Vec<Rc<Node,>,> {
+	let mut rslt = vec![];
+
+	// Check if current node matches the tag name
+	let matches = match &node.data {
+		NodeData::Element { name, .. } => {
+			let element_name =
+				string_cache::Atom::<LocalNameStaticSet,>::from(tag_name,);
+			*name.local == *element_name
+		},
+		_ => false,
+	};
+
+	if matches {
+		rslt.push(node.clone(),);
+	}
+
+	// Recursively search child nodes
+	node.children.borrow().clone().into_iter().for_each(|n| {
+		let mut child_matches = get_elements_by_name(n.clone(), tag_name,);
+		rslt.append(&mut child_matches,);
+	},);
+
+	rslt
+}
+
+/// Text of `node` and its descendants, with runs of whitespace collapsed to
+/// a single space and both ends trimmed
+pub fn text_content(node: &Rc<Node,>,) -> String {
+	fn collect(node: &Rc<Node,>, out: &mut String,) {
+		match &node.data {
+			NodeData::Text { contents, } => out.push_str(&contents.borrow(),),
+			_ => node.children.borrow().iter().for_each(|n| collect(n, out,),),
+		}
+	}
+
+	let mut raw = String::new();
+	collect(node, &mut raw,);
+	raw.split_whitespace().collect::<Vec<_,>>().join(" ",)
+}
@@ -0,0 +1,242 @@
+//! # HTML Table Extraction
+//!
+//! Reads `<table>` elements as rows of cells keyed by header text, so
+//! scrapers don't depend on column order or on markup inside cells.
+//!
+//! ```rust,ignore
+//! let table = TableRef::by_caption(document, "EFI_STATUS Success Codes",)
+//! 	.expect("table exists",);
+//! for row in table.rows() {
+//! 	println!("{} = {}", row["Mnemonic"].text(), row["Value"].text());
+//! }
+//! ```
+//!
+//! The header row is the first row of `<thead>`, or the first row of the
+//! table without one. Header keys match case insensitively.
+//!
+//! Keep the document alive while using a [`TableRef`]. Dropping the document
+//! node empties every node of the tree, including the table.
+
+use crate::html::get_element_by_id;
+use crate::html::get_elements_by_name;
+use crate::html::text_content;
+use markup5ever_rcdom::Node;
+use markup5ever_rcdom::NodeData;
+use std::ops::Index;
+use std::rc::Rc;
+
+/// `<table>` element with its header texts
+#[derive(Debug, Clone,)]
+pub struct TableRef {
+	node:    Rc<Node,>,
+	headers: Vec<String,>,
+	/// Index of the header row among `tr` elements of the table
+	head:    Option<usize,>,
+}
+
+impl TableRef {
+	/// Wraps `node`, which is expected to be a `<table>`
+	pub fn new(node: Rc<Node,>,) -> Self {
+		let rows = get_elements_by_name(node.clone(), "tr",);
+		let in_thead = get_elements_by_name(node.clone(), "thead",)
+			.first()
+			.and_then(|thead| {
+				let first = get_elements_by_name(thead.clone(), "tr",);
+				let first = first.first()?.clone();
+				rows.iter().position(|r| Rc::ptr_eq(r, &first,),)
+			},);
+		let head = in_thead.or((!rows.is_empty()).then_some(0,),);
+		let headers = head
+			.map(|i| cells_of(&rows[i],).iter().map(text_content,).collect())
+			.unwrap_or_default();
+		Self { node, headers, head, }
+	}
+
+	/// First table under `root` whose `<caption>` contains `caption`
+	pub fn by_caption(root: Rc<Node,>, caption: &str,) -> Option<Self,> {
+		get_elements_by_name(root, "table",)
+			.into_iter()
+			.find(|table| {
+				get_elements_by_name(table.clone(), "caption",)
+					.first()
+					.is_some_and(|c| text_content(c,).contains(caption,),)
+			},)
+			.map(Self::new,)
+	}
+
+	/// Table with `id` under `root`. A table inside an element with `id`
+	/// is found as well, as documentation generators often put the id on a
+	/// wrapper
+	pub fn by_id(root: Rc<Node,>, id: &str,) -> Option<Self,> {
+		let found = get_element_by_id(root, id,)?;
+		let table = get_elements_by_name(found, "table",).into_iter().next();
+		table.map(Self::new,)
+	}
+
+	pub fn node(&self,) -> &Rc<Node,> {
+		&self.node
+	}
+
+	/// Header texts in column order
+	pub fn headers(&self,) -> &[String] {
+		&self.headers
+	}
+
+	/// Column index of header `key`
+	pub fn column(&self, key: &str,) -> Option<usize,> {
+		let key = key.trim();
+		self.headers.iter().position(|h| h.eq_ignore_ascii_case(key,),)
+	}
+
+	/// Rows except the header row
+	pub fn rows(&self,) -> impl Iterator<Item = RowRef<'_,>,> {
+		get_elements_by_name(self.node.clone(), "tr",)
+			.into_iter()
+			.enumerate()
+			.filter(|(i, _,)| Some(*i,) != self.head,)
+			.map(|(_, node,)| RowRef::new(self, node,),)
+	}
+}
+
+/// `<tr>` element of a [`TableRef`]
+///
+/// Cells are accessed by position with [`RowRef::cell`] or by header with
+/// [`RowRef::get`] and `row["Header"]`.
+#[derive(Debug, Clone,)]
+pub struct RowRef<'t,> {
+	table: &'t TableRef,
+	node:  Rc<Node,>,
+	cells: Vec<CellRef,>,
+}
+
+impl<'t,> RowRef<'t,> {
+	fn new(table: &'t TableRef, node: Rc<Node,>,) -> Self {
+		let cells = cells_of(&node,).into_iter().map(CellRef,).collect();
+		Self { table, node, cells, }
+	}
+
+	pub fn node(&self,) -> &Rc<Node,> {
+		&self.node
+	}
+
+	pub fn cells(&self,) -> &[CellRef] {
+		&self.cells
+	}
+
+	pub fn cell(&self, column: usize,) -> Option<&CellRef,> {
+		self.cells.get(column,)
+	}
+
+	/// Cell under header `key`
+	pub fn get(&self, key: &str,) -> Option<&CellRef,> {
+		self.cell(self.table.column(key,)?,)
+	}
+}
+
+impl Index<&str,> for RowRef<'_,> {
+	type Output = CellRef;
+
+	/// # Panics
+	///
+	/// Panics if the table has no header `key` or the row has no cell under
+	/// it
+	fn index(&self, key: &str,) -> &Self::Output {
+		self.get(key,).unwrap_or_else(|| {
+			panic!("no cell under `{key}`. headers: {:?}", self.table.headers)
+		},)
+	}
+}
+
+/// `<td>` or `<th>` element
+#[derive(Debug, Clone,)]
+pub struct CellRef(Rc<Node,>,);
+
+impl CellRef {
+	pub fn node(&self,) -> &Rc<Node,> {
+		&self.0
+	}
+
+	/// Text of the cell with whitespace collapsed. See
+	/// [`text_content`]
+	pub fn text(&self,) -> String {
+		text_content(&self.0,)
+	}
+}
+
+/// `td` and `th` children of `row`
+fn cells_of(row: &Rc<Node,>,) -> Vec<Rc<Node,>,> {
+	row.children
+		.borrow()
+		.iter()
+		.filter(|n| {
+			matches!(
+				&n.data,
+				NodeData::Element { name, .. }
+					if &*name.local == "td" || &*name.local == "th"
+			)
+		},)
+		.cloned()
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use html5ever::tendril::TendrilSink;
+	use markup5ever_rcdom::RcDom;
+
+	const STATUS_TABLE: &str = r#"
+<section id="codes">
+<table>
+	<caption><span>Table D.1 EFI_STATUS Success Codes</span></caption>
+	<thead><tr><th><p>Mnemonic</p></th><th><p>Value</p></th></tr></thead>
+	<tbody>
+		<tr><td><p>EFI_SUCCESS</p></td><td><p>0</p></td></tr>
+		<tr><td><p><code>EFI_WARN</code> UNKNOWN</p></td><td>1</td></tr>
+	</tbody>
+</table>
+</section>"#;
+
+	fn parse(html: &str,) -> Rc<Node,> {
+		html5ever::parse_document(RcDom::default(), Default::default(),)
+			.one(html,)
+			.document
+	}
+
+	#[test]
+	fn test_header_keyed_access() {
+		let doc = parse(STATUS_TABLE,);
+		let table = TableRef::by_caption(doc.clone(), "Success",)
+			.expect("table exists",);
+		assert_eq!(table.headers(), ["Mnemonic", "Value"]);
+
+		let rows: Vec<RowRef,> = table.rows().collect();
+		assert_eq!(rows.len(), 2);
+		assert_eq!(rows[0]["Mnemonic"].text(), "EFI_SUCCESS");
+		assert_eq!(rows[0]["value"].text(), "0");
+		assert_eq!(rows[1]["Mnemonic"].text(), "EFI_WARN UNKNOWN");
+		assert!(rows[1].get("Description").is_none());
+	}
+
+	#[test]
+	fn test_by_id_of_wrapper() {
+		let doc = parse(STATUS_TABLE,);
+		let table = TableRef::by_id(doc.clone(), "codes",).unwrap();
+		assert_eq!(table.rows().count(), 2);
+		assert!(TableRef::by_caption(doc, "Error").is_none());
+	}
+
+	#[test]
+	fn test_first_row_is_header_without_thead() {
+		let html = "<table><tr><th>A</th></tr><tr><td>x</td></tr></table>";
+		assert!(TableRef::by_id(parse(html,), "missing",).is_none());
+
+		let doc = parse(html,);
+		let table = get_elements_by_name(doc.clone(), "table",);
+		let table = TableRef::new(table[0].clone(),);
+		assert_eq!(table.headers(), ["A"]);
+		let rows: Vec<RowRef,> = table.rows().collect();
+		assert_eq!(rows.len(), 1);
+		assert_eq!(rows[0]["A"].text(), "x");
+	}
+}
@@ -11,6 +11,8 @@ use syn::parse::Parse;
 use syn::spanned::Spanned;
 
 use crate::RsltP;
+use crate::oso_proc_macro_helper::Code;
+use crate::oso_proc_macro_helper::Diag;
 
 /// A collection of Rust types parsed from a token stream
 ///
@@ -94,13 +96,28 @@ impl Parse for Types {
 }
 
 pub fn impl_int(types: Types,) -> RsltP {
-	let integers = types.iter().map(implement,);
+	let mut diags = vec![];
+	let integers: Vec<_,> = types
+		.iter()
+		.filter(|ty| match unwrap_primitive(ty,) {
+			Ok(_,) => true,
+			Err(e,) => {
+				diags.push(
+					Diag::Err(e.to_string(),)
+						.with_span(e.span(),)
+						.with_code(Code::NOT_PRIMITIVE,),
+				);
+				false
+			},
+		},)
+		.map(implement,)
+		.collect();
 
 	Ok((
 		quote::quote! {
 			#(#integers)*
 		},
-		vec![],
+		diags,
 	),)
 }
 
@@ -7,6 +7,7 @@
 //! - ELF file parsing and analysis
 //! - UEFI status code generation from specifications
 //! - Code generation utilities for wrapper functions and trait implementations
+//! - The section reserved for the symbol map of the kernel
 //!
 //!
 //! ## Features
@@ -35,9 +36,21 @@ pub mod wrapper;
 /// Trait implementation generation for integer types
 pub mod impl_int;
 
+/// HTML DOM helpers for scraping specifications
+pub mod html;
+
+/// Reading of web pages, vendored or downloaded
+pub mod fetch;
+
 /// UEFI status code parsing from HTML specifications
 pub mod status;
 
+/// UEFI GUID lookup table from the definitions of the specification
+pub mod guids;
+
+/// Cache of generated code keyed by the contents of macro inputs
+pub mod cache;
+
 /// ELF header parsing and analysis utilities
 pub mod test_elf_header_parse;
 
@@ -46,15 +59,27 @@ pub mod test_program_headers_parse;
 
 pub mod from_path_buf;
 
+/// Compile time layout checks of types shared by the loader and the kernel
+pub mod bridge_layout;
+
+/// Section reserved for the symbol map of the kernel
+pub mod symbol_map;
+
+/// Stable codes of the variants of error enums
+pub mod error_code;
+
+/// Safety contracts of unsafe code, collected by `cargo xtask audit-unsafe`
+pub mod safety;
+
 pub mod features;
 pub mod oso_proc_macro_helper;
 
 use anyhow::Result as Rslt;
 use oso_dev_util_helper::fs::check_oso_kernel;
 
-use crate::oso_proc_macro_helper::Diag;
+use crate::oso_proc_macro_helper::Report;
 
-type RsltP = Rslt<(proc_macro2::TokenStream, Vec<Diag,>,),>;
+type RsltP = Rslt<(proc_macro2::TokenStream, Vec<Report,>,),>;
 
 #[cfg(test)]
 mod tests {
@@ -394,8 +419,8 @@ mod tests {
 		fn test_function_with_diags() -> RsltP {
 			let tokens = quote::quote! { fn test() {} };
 			let diags = vec![
-				Diag::Warn("Test warning".to_string(),),
-				Diag::Note("Test note".to_string(),),
+				Diag::Warn("Test warning".to_string(),).into(),
+				Diag::Note("Test note".to_string(),).into(),
 			];
 			Ok((tokens, diags,),)
 		}
@@ -508,8 +533,8 @@ mod tests {
 
 		// Create some diagnostics
 		let diags = vec![
-			Diag::Err("Error from module interaction".to_string(),),
-			Diag::Warn("Warning from module interaction".to_string(),),
+			Diag::Err("Error from module interaction".to_string(),).into(),
+			Diag::Warn("Warning from module interaction".to_string(),).into(),
 		];
 
 		// Test that we can create a result with diagnostics
@@ -607,9 +632,9 @@ mod tests {
 			};
 
 			let complex_diags = vec![
-				Diag::Note("Complex structure created".to_string(),),
-				Diag::Warn("This is a test warning".to_string(),),
-				Diag::Help("Consider using simpler types".to_string(),),
+				Diag::Note("Complex structure created".to_string(),).into(),
+				Diag::Warn("This is a test warning".to_string(),).into(),
+				Diag::Help("Consider using simpler types".to_string(),).into(),
 			];
 
 			Ok((complex_tokens, complex_diags,),)
@@ -1,53 +1,147 @@
+use proc_macro2::Span;
+use proc_macro2::TokenStream;
+use quote::ToTokens;
+use std::fmt::Display;
+
+/// Defines a function-like macro
+///
+/// `fallback` builds the expansion from the parsed input and the
+/// `compile_error!` invocations when the macro fails, so that code using its
+/// output still type checks. By default only the errors are expanded.
 #[macro_export]
 macro_rules! fnl {
+	($name:ident => $ty:ty, fallback: $fallback:expr, $doc:literal) => {
+		$crate::def! {
+			#[proc_macro]
+			$doc
+			$name(item => $ty,)
+			fallback: $crate::oso_proc_macro_helper::with_input(
+				&item,
+				$fallback,
+			),
+		}
+	};
 	($name:ident => $ty:ty, $doc:literal) => {
-		#[proc_macro]
-		#[doc = $doc]
-		pub fn $name(
-			item: proc_macro::TokenStream,
-		) -> proc_macro::TokenStream {
-			$crate::def! { $name, item => $ty, }
+		$crate::def! {
+			#[proc_macro]
+			$doc
+			$name(item => $ty,)
+			fallback: |errors| errors,
 		}
 	};
 }
 
+/// Defines an attribute macro. The item is kept unchanged when the macro
+/// fails
 #[macro_export]
 macro_rules! atr {
 	($name:ident => $ty:ty, $ty2:ty, $doc:literal) => {
-		#[proc_macro_attribute]
-		#[doc = $doc]
-		pub fn $name(
-			attr: proc_macro::TokenStream,
-			item: proc_macro::TokenStream,
-		) -> proc_macro::TokenStream {
-			$crate::def! { $name, attr => $ty, item => $ty2, }
+		$crate::def! {
+			#[proc_macro_attribute]
+			$doc
+			$name(attr => $ty, item => $ty2,)
+			fallback: $crate::oso_proc_macro_helper::keep_item(&item,),
 		}
 	};
 }
 
+/// Defines a derive macro. Only the errors are expanded when the macro fails
 #[macro_export]
 macro_rules! drv {
 	($derive:ident, $name:ident => $ty:ty, $(attributes: $($attributes:ident,)+)? $doc:literal) => {
-		#[proc_macro_derive($derive $($(, attributes($attributes))+)?)]
-		#[doc = $doc]
-		pub fn $name(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
-			$crate::def! { $name, item => $ty, }
-
+		$crate::def! {
+			#[proc_macro_derive($derive $($(, attributes($attributes))+)?)]
+			$doc
+			$name(item => $ty,)
+			fallback: |errors| errors,
 		}
 	};
 }
 
+/// Defines the function of a procedural macro, shared by [`fnl`], [`atr`]
+/// and [`drv`]
+///
+/// `kind` registers the function. Each parameter is parsed as its type and
+/// passed to the function of the same name as the macro, in the module of
+/// that name in this crate. The path starts with `$crate`, so callers need
+/// not depend on this crate under a particular name. `fallback` is evaluated
+/// after parsing, so it may borrow the parsed parameters.
+///
+/// `doc` becomes the documentation of the macro, followed by a link to its
+/// implementation. `unwrap_or_emit` is resolved at the call site, which
+/// decides how diagnostics are emitted.
+///
+/// The function is always `pub`, as procedural macros are exported from the
+/// root of their crate. The three wrappers are the only family of macros
+/// built on it.
+///
+/// ```ignore
+/// def! {
+/// 	#[proc_macro]
+/// 	"Generates embedded font data"
+/// 	font(item => pm_logic::font::FontArgs,)
+/// 	fallback: |errors| errors,
+/// }
+/// ```
 #[macro_export]
 macro_rules! def {
-	($name:ident, $($param:ident => $ty:ty,)+)=>{
-		$(
-			let $param = syn::parse_macro_input!($param as $ty);
-		)?
-
-		oso_proc_macro_logic::$name::$name($($param,)+).unwrap_or_emit().into()
+	(
+		#[$kind:meta]
+		$doc:literal
+		$name:ident($($param:ident => $ty:ty,)+)
+		fallback: $fallback:expr $(,)?
+	) => {
+		#[$kind]
+		#[doc = $doc]
+		#[doc = ""]
+		#[doc = concat!(
+			"implemented by `oso_proc_macro_logic::",
+			stringify!($name),
+			"::",
+			stringify!($name),
+			"`"
+		)]
+		pub fn $name(
+			$($param: proc_macro::TokenStream,)+
+		) -> proc_macro::TokenStream {
+			$(
+				let $param = match syn::parse2::<$ty>($param.into(),) {
+					Ok(parsed,) => parsed,
+					Err(e,) => return e.to_compile_error().into(),
+				};
+			)+
+			// evaluated before the inputs are moved
+			let fallback = $fallback;
+
+			$crate::$name::$name($($param,)+)
+				.unwrap_or_emit(fallback,)
+				.into()
+		}
 	};
 }
 
+/// Fallback of [`fnl`], which builds the expansion from a copy of the parsed
+/// `input` and the errors
+pub fn with_input<T: Clone, F: FnOnce(&T, TokenStream,) -> TokenStream,>(
+	input: &T,
+	fallback: F,
+) -> impl FnOnce(TokenStream,) -> TokenStream + use<T, F,> {
+	let input = input.clone();
+	move |errors| fallback(&input, errors,)
+}
+
+/// Fallback of [`atr`], which expands to the errors followed by the
+/// unchanged `item`
+pub fn keep_item<T: ToTokens,>(
+	item: &T,
+) -> impl FnOnce(TokenStream,) -> TokenStream + use<T,> {
+	let item = item.to_token_stream();
+	move |mut errors| {
+		errors.extend(item,);
+		errors
+	}
+}
+
 #[derive(Debug,)]
 pub enum Diag {
 	Err(String,),
@@ -56,10 +150,139 @@ pub enum Diag {
 	Help(String,),
 }
 
+impl Diag {
+	/// points the diagnostic at `span`
+	pub fn with_span(self, span: Span,) -> Report {
+		Report::from(self,).with_span(span,)
+	}
+
+	pub fn with_code(self, code: Code,) -> Report {
+		Report::from(self,).with_code(code,)
+	}
+
+	/// fatal error pointing at `span`
+	///
+	/// the returned error is a [`syn::Error`], which `unwrap_or_emit` reports
+	/// at its span
+	pub fn error(code: Code, span: Span, msg: impl Display,) -> anyhow::Error {
+		syn::Error::new(span, format!("[{code}] {msg}"),).into()
+	}
+}
+
+/// [`Diag`] emitted alongside the output of a macro
+///
+/// Without a span, the diagnostic points at the whole macro invocation.
+/// Fatal errors which abort the expansion are returned as [`Diag::error`]
+/// instead, as spans can not be sent through `anyhow::Error`.
+#[derive(Debug,)]
+pub struct Report {
+	pub level: Level,
+	pub msg:   String,
+	/// offending input
+	pub span:  Option<Span,>,
+	pub code:  Option<Code,>,
+}
+
+impl Report {
+	/// points the diagnostic at `span`
+	pub fn with_span(mut self, span: Span,) -> Self {
+		self.span = Some(span,);
+		self
+	}
+
+	pub fn with_code(mut self, code: Code,) -> Self {
+		self.code = Some(code,);
+		self
+	}
+
+	/// message prefixed with the code, as it is shown to the user
+	pub fn message(&self,) -> String {
+		match self.code {
+			Some(code,) => format!("[{code}] {}", self.msg),
+			None => self.msg.clone(),
+		}
+	}
+}
+
+impl From<Diag,> for Report {
+	fn from(diag: Diag,) -> Self {
+		let (level, msg,) = match diag {
+			Diag::Err(msg,) => (Level::Err, msg,),
+			Diag::Warn(msg,) => (Level::Warn, msg,),
+			Diag::Note(msg,) => (Level::Note, msg,),
+			Diag::Help(msg,) => (Level::Help, msg,),
+		};
+		Self { level, msg, span: None, code: None, }
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum Level {
+	Err,
+	Warn,
+	Note,
+	Help,
+}
+
+/// Identifies the kind of a diagnostic. Shown as `OSO0001`
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct Code(pub u16,);
+
+impl Code {
+	/// font file can not be read
+	pub const FONT_READ: Self = Self(1,);
+	/// glyph of a font file is not 16 lines of 8 pixels
+	pub const FONT_GLYPH: Self = Self(2,);
+	/// `status!` takes the specification version as a float literal
+	pub const STATUS_VERSION: Self = Self(3,);
+	/// `impl_int!` takes primitive integer types only
+	pub const NOT_PRIMITIVE: Self = Self(4,);
+	/// glyph of a font file has pixels other than `.` and `@`
+	pub const FONT_PIXEL: Self = Self(5,);
+	/// `ranges` of `font!` are malformed or outside the font
+	pub const FONT_RANGES: Self = Self(6,);
+	/// `pin` of `status!` is malformed or does not match the specification
+	pub const STATUS_PIN: Self = Self(7,);
+	/// GUID table of `guids!` can not be read or has a malformed definition
+	pub const GUID_TABLE: Self = Self(8,);
+	/// a macro needs a page which is not vendored while `OSO_OFFLINE=1`
+	pub const OFFLINE: Self = Self(9,);
+	/// variant of `ErrorCode` has no code, a malformed one or one used by
+	/// another variant
+	pub const ERROR_CODE: Self = Self(10,);
+	/// `safety` is applied to an unsupported item or lacks an argument the
+	/// item needs
+	pub const SAFETY: Self = Self(11,);
+}
+
+impl Display for Code {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_,>,) -> std::fmt::Result {
+		write!(f, "OSO{:04}", self.0)
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
 
+	#[test]
+	fn test_diag_code_and_span() {
+		let report = Report::from(Diag::Warn("unused glyph".to_string(),),);
+		assert_eq!(report.level, Level::Warn);
+		assert_eq!(report.message(), "unused glyph");
+		assert!(report.span.is_none());
+
+		let span = Span::call_site();
+		let diag = Diag::Warn("unused glyph".to_string(),);
+		let report = diag.with_code(Code::FONT_GLYPH,).with_span(span,);
+		assert_eq!(report.message(), "[OSO0002] unused glyph");
+		assert!(report.span.is_some());
+
+		let err = Diag::error(Code::NOT_PRIMITIVE, span, "not an integer",);
+		let err = err.downcast::<syn::Error>().unwrap();
+		assert_eq!(err.to_string(), "[OSO0004] not an integer");
+	}
+
 	#[test]
 	fn test_diag_enum_variants() {
 		// Test that all Diag variants can be created
@@ -500,4 +723,102 @@ mod tests {
 		// If this compiles, the macro token handling is working
 		assert!(true);
 	}
+
+	/// definitions through [`def`] as [`fnl`] and [`atr`] make them, with
+	/// `proc_macro2` standing in for `proc_macro`, whose token streams only
+	/// exist inside a procedural macro
+	mod expand {
+		use super::*;
+		use crate::safety::SafetyArgs;
+		use crate::symbol_map::SymbolMapArgs;
+		use proc_macro2 as proc_macro;
+
+		/// like the one of `oso_proc_macro`, without emitting diagnostics
+		trait ErrorDiagnose {
+			type T;
+			fn unwrap_or_emit(
+				self,
+				fallback: impl FnOnce(TokenStream,) -> Self::T,
+			) -> Self::T;
+		}
+
+		impl<T,> ErrorDiagnose for anyhow::Result<(T, Vec<Report,>,),> {
+			type T = T;
+
+			fn unwrap_or_emit(
+				self,
+				fallback: impl FnOnce(TokenStream,) -> Self::T,
+			) -> Self::T {
+				match self {
+					Ok((o, _,),) => o,
+					Err(e,) => {
+						let errors = e.downcast::<syn::Error>().unwrap();
+						fallback(errors.to_compile_error(),)
+					},
+				}
+			}
+		}
+
+		fn placeholder(
+			_: &SymbolMapArgs,
+			mut errors: TokenStream,
+		) -> TokenStream {
+			errors.extend(quote::quote!(static SYMBOL_MAP: [u8; 0] = [];),);
+			errors
+		}
+
+		crate::def! {
+			#[inline]
+			"integers"
+			impl_int(item => crate::impl_int::Types,)
+			fallback: |errors| errors,
+		}
+
+		crate::def! {
+			#[inline]
+			"symbol map"
+			symbol_map(item => SymbolMapArgs,)
+			fallback: with_input(&item, placeholder,),
+		}
+
+		crate::def! {
+			#[inline]
+			"contracts"
+			safety(attr => SafetyArgs, item => syn::Item,)
+			fallback: keep_item(&item,),
+		}
+	}
+
+	#[test]
+	fn test_fnl_expansion() {
+		let tokens = expand::impl_int(quote::quote!(u8, u16),).to_string();
+		assert!(tokens.contains("for u8"));
+		assert!(tokens.contains("for u16"));
+		// input which does not parse expands to the error only
+		let tokens = expand::impl_int(quote::quote!(u8, 1),).to_string();
+		assert!(tokens.contains("compile_error"));
+	}
+
+	#[test]
+	fn test_fnl_fallback() {
+		let tokens = expand::symbol_map(quote::quote!(capacity = 64),);
+		assert!(!tokens.to_string().contains("compile_error"));
+		let tokens = expand::symbol_map(quote::quote!(capacity = 3),);
+		let tokens = tokens.to_string();
+		assert!(tokens.contains("compile_error"));
+		assert!(tokens.ends_with("static SYMBOL_MAP : [u8 ; 0] = [] ;"));
+	}
+
+	#[test]
+	fn test_atr_keeps_item() {
+		let args = quote::quote!(requires = "`ptr` is valid");
+		let item = quote::quote!(unsafe fn read(ptr: *const u8) {});
+		let tokens = expand::safety(args.clone(), item,).to_string();
+		assert!(tokens.contains("# Safety"));
+
+		let item = quote::quote!(struct Kept;);
+		let tokens = expand::safety(args, item,).to_string();
+		assert!(tokens.contains("[OSO0011]"));
+		assert!(tokens.ends_with("struct Kept ;"));
+	}
 }
@@ -0,0 +1,189 @@
+//! # Safety Contracts
+//!
+//! `#[safety(..)]` writes down why unsafe code is sound, in a form `cargo
+//! xtask audit-unsafe` can pair with the code. It changes nothing at run
+//! time:
+//!
+//! ```ignore
+//! /// Maps `frame` at `virt`
+//! #[safety(requires = "`virt` is not mapped and `frame` is owned by the \
+//!                      caller")]
+//! pub unsafe fn map(virt: u64, frame: u64,) { .. }
+//!
+//! #[safety(reason = "the table is only written before other cores start")]
+//! fn install_table() { unsafe { .. } }
+//! ```
+//!
+//! - `requires`: The contract callers of an `unsafe fn` or implementors of
+//!   an `unsafe trait` uphold. Added to the documentation as a `# Safety`
+//!   section unless there is one
+//! - `reason`: Why the unsafe code inside a function, or an `unsafe impl`,
+//!   is sound
+//!
+//! Unsafe items need `requires`, other items `reason`.
+
+use crate::RsltP;
+use crate::oso_proc_macro_helper::Code;
+use crate::oso_proc_macro_helper::Diag;
+use quote::ToTokens;
+use syn::LitStr;
+use syn::Token;
+use syn::parse::Parse;
+use syn::parse::ParseStream;
+
+/// Arguments of `#[safety(reason = "..", requires = "..")]`
+#[derive(Default,)]
+pub struct SafetyArgs {
+	pub reason:   Option<LitStr,>,
+	pub requires: Option<LitStr,>,
+}
+
+impl Parse for SafetyArgs {
+	fn parse(input: ParseStream,) -> syn::Result<Self,> {
+		let mut args = Self::default();
+		while !input.is_empty() {
+			let key: syn::Ident = input.parse()?;
+			input.parse::<Token![=]>()?;
+			let value = Some(input.parse()?,);
+			let slot = match key.to_string().as_str() {
+				"reason" => &mut args.reason,
+				"requires" => &mut args.requires,
+				_ => {
+					let message = "expected `reason` or `requires`";
+					return Err(syn::Error::new(key.span(), message,),);
+				},
+			};
+			if slot.is_some() {
+				return Err(syn::Error::new(key.span(), "given twice",),);
+			}
+			*slot = value;
+			if !input.is_empty() {
+				input.parse::<Token![,]>()?;
+			}
+		}
+		Ok(args,)
+	}
+}
+
+pub fn safety(args: SafetyArgs, mut item: syn::Item,) -> RsltP {
+	let (is_contract, span, attrs,) = match &mut item {
+		syn::Item::Fn(f,) => {
+			(f.sig.unsafety.is_some(), f.sig.ident.span(), &mut f.attrs,)
+		},
+		syn::Item::Trait(t,) => {
+			(t.unsafety.is_some(), t.ident.span(), &mut t.attrs,)
+		},
+		// an unsafe impl is sound for a reason, like a safe function
+		syn::Item::Impl(i,) => (false, i.impl_token.span, &mut i.attrs,),
+		_ => {
+			return Err(Diag::error(
+				Code::SAFETY,
+				proc_macro2::Span::call_site(),
+				"`safety` applies to functions, impls and traits",
+			),);
+		},
+	};
+	match (is_contract, &args.requires, &args.reason,) {
+		(true, None, _,) => {
+			let message = "unsafe items need `requires`, the contract their \
+			               users uphold";
+			return Err(Diag::error(Code::SAFETY, span, message,),);
+		},
+		(false, _, None,) => {
+			let message = "`reason` is missing, why the unsafe code is sound";
+			return Err(Diag::error(Code::SAFETY, span, message,),);
+		},
+		_ => {},
+	}
+
+	if let Some(requires,) = args.requires.filter(|_| is_contract,)
+		&& !has_safety_section(attrs,)
+	{
+		let requires = requires.value();
+		attrs.push(syn::parse_quote!(#[doc = ""]),);
+		attrs.push(syn::parse_quote!(#[doc = " # Safety"]),);
+		attrs.push(syn::parse_quote!(#[doc = ""]),);
+		attrs.push(syn::parse_quote!(#[doc = #requires]),);
+	}
+	Ok((item.to_token_stream(), vec![],),)
+}
+
+/// whether the documentation in `attrs` has a `# Safety` heading
+fn has_safety_section(attrs: &[syn::Attribute],) -> bool {
+	attrs.iter().any(|attr| {
+		let syn::Meta::NameValue(meta,) = &attr.meta else {
+			return false;
+		};
+		let syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(doc,), .. },) =
+			&meta.value
+		else {
+			return false;
+		};
+		meta.path.is_ident("doc",) && doc.value().trim() == "# Safety"
+	},)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use syn::parse_quote;
+
+	#[test]
+	fn test_safety_documents_contract() {
+		let args: SafetyArgs = parse_quote!(requires = "`ptr` is valid");
+		let item: syn::Item = parse_quote! {
+			/// Reads `ptr`
+			unsafe fn read(ptr: *const u8) -> u8 { unsafe { *ptr } }
+		};
+		let tokens = safety(args, item,).unwrap().0.to_string();
+		assert!(tokens.contains("# [doc = \" # Safety\"]"));
+		assert!(tokens.contains("# [doc = \"`ptr` is valid\"]"));
+		assert!(tokens.contains("unsafe fn read"));
+
+		let args: SafetyArgs = parse_quote!(requires = "`ptr` is valid");
+		let item: syn::Item = parse_quote! {
+			/// # Safety
+			unsafe fn read(ptr: *const u8) -> u8 { unsafe { *ptr } }
+		};
+		let tokens = safety(args, item,).unwrap().0.to_string();
+		assert_eq!(tokens.matches("Safety",).count(), 1);
+	}
+
+	#[test]
+	fn test_safety_reason_of_safe_code() {
+		let args: SafetyArgs = parse_quote!(reason = "no other core runs");
+		let item: syn::Item = parse_quote! {
+			fn install() { unsafe { TABLE = 1; } }
+		};
+		let tokens = safety(args, item,).unwrap().0.to_string();
+		assert!(!tokens.contains("doc"));
+
+		let args: SafetyArgs = parse_quote!(reason = "only moves a pointer");
+		let item: syn::Item = parse_quote! { unsafe impl Send for Frame {} };
+		assert!(safety(args, item,).is_ok());
+	}
+
+	#[test]
+	fn test_safety_rejects_missing_arguments() {
+		let message = |args: SafetyArgs, item: syn::Item| {
+			let e = safety(args, item,).unwrap_err();
+			e.downcast::<syn::Error>().unwrap().to_string()
+		};
+
+		let args: SafetyArgs = parse_quote!(reason = "sound");
+		let item: syn::Item = parse_quote! { unsafe fn f() {} };
+		assert!(message(args, item).starts_with("[OSO0011] unsafe items"));
+
+		let args: SafetyArgs = parse_quote!(requires = "valid");
+		let item: syn::Item = parse_quote! { fn f() {} };
+		assert!(message(args, item).contains("`reason` is missing"));
+
+		let args: SafetyArgs = parse_quote!(reason = "sound");
+		let item: syn::Item = parse_quote! { struct S; };
+		assert!(message(args, item).contains("functions, impls and traits"));
+
+		let twice = "reason = \"a\", reason = \"b\"";
+		assert!(syn::parse_str::<SafetyArgs,>(twice).is_err());
+		assert!(syn::parse_str::<SafetyArgs,>("because = \"a\"").is_err());
+	}
+}
@@ -5,7 +5,8 @@
 //! and warning codes along with their mnemonics, values, and descriptions.
 //!
 //! The parser works by:
-//! 1. Fetching the UEFI specification page via HTTP
+//! 1. Fetching the UEFI specification page via HTTP, or reading its vendored
+//!    copy, see [`crate::fetch`]
 //! 2. Parsing the HTML content to extract status code tables
 //! 3. Converting the table data into structured Rust types
 //!
@@ -13,18 +14,29 @@
 //! status codes in operating system development.
 
 use crate::RsltP;
+use crate::cache;
+use crate::fetch::fetch;
+use crate::html::get_element_by_id;
+use crate::html::table::RowRef;
+use crate::html::table::TableRef;
+use crate::oso_proc_macro_helper::Code;
 use crate::oso_proc_macro_helper::Diag;
+use crate::oso_proc_macro_helper::Report;
 use anyhow::Result as Rslt;
 use anyhow::anyhow;
-use anyhow::bail;
-use html5ever::local_name;
 use html5ever::tendril::TendrilSink;
-use markup5ever::LocalNameStaticSet;
 use markup5ever_rcdom::Node;
-use markup5ever_rcdom::NodeData;
 use markup5ever_rcdom::RcDom;
+use oso_dev_util_helper::specs;
 use proc_macro2::Span;
+use sha2::Digest;
+use sha2::Sha256;
+use std::path::PathBuf;
 use std::rc::Rc;
+use syn::LitStr;
+use syn::Token;
+use syn::parse::Parse;
+use syn::parse::ParseStream;
 
 /// HTML element ID of the main status codes section in the UEFI specification
 const MAIN_SECTION_ID: &str = "status-codes";
@@ -41,6 +53,10 @@ const ERROR_CODE_TABLE_ID: &str =
 const WARN_CODE_TABLE_ID: &str =
 	"efi-status-warning-codes-high-bit-clear-apx-d-status-codes";
 
+/// Headers of the status code tables, in the order [`status_codes_info`]
+/// expects
+const STATUS_TABLE_HEADERS: [&str; 3] = ["Mnemonic", "Value", "Description",];
+
 /// Trait for converting status code information into token stream parts.
 ///
 /// This trait provides a method to convert status code information into
@@ -137,31 +153,242 @@ impl StatusCodeInfo {
 	pub const ERROR_BIT: usize = 1 << (usize::BITS - 1);
 }
 
-pub fn status(version: syn::Lit,) -> RsltP {
+/// Records the pinned specification, next to the manifest of the crate
+/// expanding `status!`. Commit it, so changes of the status codes show up
+pub const LOCK_FILE: &str = "status.lock";
+
+/// Input of `status!`: the specification version and optionally the sha256
+/// of its status code page, such as `pin = "sha256:0123..."`
+#[derive(Clone,)]
+pub struct StatusArgs {
+	pub version: syn::Lit,
+	pub pin:     Option<LitStr,>,
+}
+
+impl From<syn::Lit,> for StatusArgs {
+	fn from(version: syn::Lit,) -> Self {
+		Self { version, pin: None, }
+	}
+}
+
+impl Parse for StatusArgs {
+	fn parse(input: ParseStream,) -> syn::Result<Self,> {
+		let version = input.parse()?;
+		let mut pin = None;
+		if input.parse::<Option<Token![,],>>()?.is_some() && !input.is_empty() {
+			let key: syn::Ident = input.parse()?;
+			if key != "pin" {
+				return Err(syn::Error::new(key.span(), "expected `pin`",),);
+			}
+			input.parse::<Token![=]>()?;
+			pin = Some(input.parse()?,);
+			input.parse::<Option<Token![,],>>()?;
+		}
+		Ok(Self { version, pin, },)
+	}
+}
+
+/// Status codes of the UEFI specification
+///
+/// With `pin`, the fetched page must match it and [`LOCK_FILE`] is updated.
+/// When the pinned version changes, notes list the codes added and removed
+/// since the previous one.
+pub fn status(args: impl Into<StatusArgs,>,) -> RsltP {
+	let StatusArgs { version, pin, } = args.into();
 	let syn::Lit::Float(version,) = version else {
-		bail!("version is floating point literal. found {version:?}")
+		return Err(Diag::error(
+			Code::STATUS_VERSION,
+			version.span(),
+			"version must be a floating point literal such as `2.10`",
+		),);
 	};
+	if let Some(pin,) = &pin {
+		let value = pin.value();
+		let hex = value.strip_prefix("sha256:",).unwrap_or_default();
+		if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit(),) {
+			return Err(Diag::error(
+				Code::STATUS_PIN,
+				pin.span(),
+				"pin must be `sha256:` followed by 64 hex digits",
+			),);
+		}
+	}
+
+	let span = version.span();
+	// the published specification of a version does not change
+	let version = version.base10_digits();
+	let pinned = pin.as_ref().map(|pin| pin.value(),).unwrap_or_default();
+	cache::cached("status", &[version.as_bytes(), pinned.as_bytes(),], || {
+		// Construct the URL for the UEFI specification page
+		let status_spec_url = specs::status_page_url(version,);
+
+		// Fetch and parse the specification page
+		let page = fetch(&status_spec_url, span,)?;
+		let spec_page = parse_status_page(&page,)?;
+
+		let mut diags = vec![];
+		if let Some(pin,) = &pin {
+			let digest = sha256(page.as_bytes(),);
+			if !digest.eq_ignore_ascii_case(&pin.value(),) {
+				return Err(Diag::error(
+					Code::STATUS_PIN,
+					pin.span(),
+					format!(
+						"status codes page of {version} does not match the \
+						 pin. fetched {digest}"
+					),
+				),);
+			}
+			diags = update_lock(version, &digest, &spec_page, pin.span(),)?;
+		}
 
-	// Construct the URL for the UEFI specification page
-	let status_spec_url = format!(
-		"https://uefi.org/specs/UEFI/{version}/Apx_D_Status_Codes.html"
-	);
+		// Generate the Status struct implementation using the helper
+		let c_enum_impl = impl_status(&spec_page,);
 
-	// Fetch and parse the specification page
-	let spec_page = status_spec_page(&status_spec_url,)?;
-	// Generate the Status struct implementation using the helper
-	let c_enum_impl = impl_status(&spec_page,);
+		// Generate the complete Status struct with all implementations
+		let enum_def = quote::quote! {
+				#[repr(transparent)]
+				#[derive(Eq, PartialEq, Clone, Debug,)]
+				pub struct Status(pub usize);
 
-	// Generate the complete Status struct with all implementations
-	let enum_def = quote::quote! {
-			#[repr(transparent)]
-			#[derive(Eq, PartialEq, Clone, Debug,)]
-			pub struct Status(pub usize);
+				#c_enum_impl
+		};
+
+		Ok((enum_def, diags,),)
+	},)
+}
+
+/// Pinned specification as recorded in [`LOCK_FILE`]
+#[derive(Debug, PartialEq, Eq,)]
+struct Lock {
+	version: String,
+	pin:     String,
+	/// mnemonics of all status codes, sorted
+	codes:   Vec<String,>,
+}
 
-			#c_enum_impl
+impl Lock {
+	fn new(version: &str, pin: &str, spec_page: &StatusCode,) -> Self {
+		let mut codes: Vec<String,> = [
+			&spec_page.success,
+			&spec_page.warn,
+			&spec_page.error,
+		]
+		.into_iter()
+		.flatten()
+		.map(|sci| sci.mnemonic.clone(),)
+		.collect();
+		codes.sort();
+		Self { version: version.to_string(), pin: pin.to_string(), codes, }
+	}
+
+	fn parse(lock: &str,) -> Option<Self,> {
+		let table: toml::Table = lock.parse().ok()?;
+		let text = |key: &str| Some(table.get(key,)?.as_str()?.to_string(),);
+		let codes = table
+			.get("codes",)?
+			.as_array()?
+			.iter()
+			.map(|code| Some(code.as_str()?.to_string(),),)
+			.collect::<Option<Vec<_,>,>>()?;
+		Some(Self { version: text("version",)?, pin: text("pin",)?, codes, },)
+	}
+
+	fn to_toml(&self,) -> String {
+		let mut out = format!(
+			"# recorded by `status!`. do not edit\nversion = \"{}\"\npin = \
+			 \"{}\"\ncodes = [\n",
+			self.version, self.pin
+		);
+		for code in &self.codes {
+			out.push_str(&format!("\t\"{code}\",\n"),);
+		}
+		out.push_str("]\n",);
+		out
+	}
+}
+
+/// Records the pinned specification in [`LOCK_FILE`], noting how its status
+/// codes differ from the previously recorded one
+fn update_lock(
+	version: &str,
+	pin: &str,
+	spec_page: &StatusCode,
+	span: Span,
+) -> Rslt<Vec<Report,>,> {
+	let path =
+		PathBuf::from(std::env::var("CARGO_MANIFEST_DIR",)?,).join(LOCK_FILE,);
+	let lock = Lock::new(version, pin, spec_page,);
+	let previous =
+		std::fs::read_to_string(&path,).ok().and_then(|s| Lock::parse(&s,),);
+	let notes = match previous {
+		Some(previous,) if previous == lock => return Ok(vec![],),
+		Some(previous,) => lock_diff(&previous, &lock,),
+		None => vec![format!(
+			"recorded {} status codes of {version} in {LOCK_FILE}",
+			lock.codes.len()
+		)],
 	};
+	std::fs::write(&path, lock.to_toml(),)?;
+	let notes =
+		notes.into_iter().map(|note| Diag::Note(note,).with_span(span,),);
+	Ok(notes.collect(),)
+}
 
-	Ok((enum_def, vec![],),)
+fn lock_diff(previous: &Lock, lock: &Lock,) -> Vec<String,> {
+	let missing_from = |codes: &[String], other: &[String]| {
+		codes
+			.iter()
+			.filter(|code| !other.contains(code,),)
+			.cloned()
+			.collect::<Vec<_,>>()
+	};
+	let added = missing_from(&lock.codes, &previous.codes,);
+	let removed = missing_from(&previous.codes, &lock.codes,);
+
+	let from = &previous.version;
+	let mut notes = vec![];
+	if !added.is_empty() {
+		notes.push(format!(
+			"status codes added since {from}: {}",
+			added.join(", ")
+		),);
+	}
+	if !removed.is_empty() {
+		notes.push(format!(
+			"status codes removed since {from}: {}",
+			removed.join(", ")
+		),);
+	}
+	if notes.is_empty() {
+		notes.push(format!(
+			"specification {} has the same status codes as {from}",
+			lock.version
+		),);
+	}
+	notes
+}
+
+/// `sha256:` followed by the hex digest of `bytes`
+fn sha256(bytes: &[u8],) -> String {
+	let digest = Sha256::digest(bytes,);
+	let hex: String = digest.iter().map(|b| format!("{b:02x}"),).collect();
+	format!("sha256:{hex}")
+}
+
+/// Expansion of `status!` when the specification can not be fetched: the
+/// `Status` type without constants after the errors
+pub fn fallback(
+	_args: &StatusArgs,
+	errors: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+	quote::quote! {
+		#errors
+
+		#[repr(transparent)]
+		#[derive(Eq, PartialEq, Clone, Debug,)]
+		pub struct Status(pub usize);
+	}
 }
 
 /// Fetches and parses UEFI status codes from the official specification
@@ -198,13 +425,15 @@ pub fn status(version: syn::Lit,) -> RsltP {
 pub fn status_spec_page(
 	status_spec_url: impl Into<String,>,
 ) -> Rslt<StatusCode,> {
-	// Fetch the specification page
-	let mut rsp = ureq::get(status_spec_url.into(),).call()?;
-	let rsp_body = rsp.body_mut().read_to_string()?;
+	let url = status_spec_url.into();
+	parse_status_page(&fetch(&url, Span::call_site(),)?,)
+}
 
+/// Status codes in the HTML of the status codes appendix
+pub fn parse_status_page(page: &str,) -> Rslt<StatusCode,> {
 	// Parse the HTML document
 	let dom = html5ever::parse_document(RcDom::default(), Default::default(),)
-		.one(rsp_body.as_str(),);
+		.one(page,);
 
 	let node = dom.document;
 
@@ -212,34 +441,15 @@ pub fn status_spec_page(
 	let main_section = get_element_by_id(node.clone(), MAIN_SECTION_ID,)
 		.expect("failed to get main section node",);
 
-	// Extract the three status code tables
-	let success_code_table =
-		get_element_by_id(main_section.clone(), SUCCESS_CODE_TABLE_ID,).ok_or(
-			anyhow!("ELEMENT WITH ID NOT FOUND: {SUCCESS_CODE_TABLE_ID}"),
-		)?;
-	let error_code_table =
-		get_element_by_id(main_section.clone(), ERROR_CODE_TABLE_ID,).ok_or(
-			anyhow!("ELEMENT WITH ID NOT FOUND: {ERROR_CODE_TABLE_ID}"),
-		)?;
-	let warn_code_table =
-		get_element_by_id(main_section.clone(), WARN_CODE_TABLE_ID,).ok_or(
-			anyhow!("ELEMENT WITH ID NOT FOUND: {WARN_CODE_TABLE_ID}"),
-		)?;
-
-	// Extract table rows from each table (skip header row)
-	let success_code_table_rows = table_rows(success_code_table.clone(),);
-	let error_code_table_rows = table_rows(error_code_table.clone(),);
-	let warn_code_table_rows = table_rows(warn_code_table.clone(),);
-
-	// Parse table data from each row
-	let success_codes_info: Vec<Vec<String,>,> = success_code_table_rows
-		.iter()
-		.map(|n| table_data(n.clone(),),)
-		.collect();
-	let error_codes_info: Vec<Vec<String,>,> =
-		error_code_table_rows.iter().map(|n| table_data(n.clone(),),).collect();
-	let warn_codes_info: Vec<Vec<String,>,> =
-		warn_code_table_rows.iter().map(|n| table_data(n.clone(),),).collect();
+	// Read mnemonic, value and description of each status code table
+	let table_info = |id: &str| -> Rslt<Vec<Vec<String,>,>,> {
+		let table = TableRef::by_id(main_section.clone(), id,)
+			.ok_or(anyhow!("ELEMENT WITH ID NOT FOUND: {id}"),)?;
+		table.rows().map(|row| status_row(&row,),).collect()
+	};
+	let success_codes_info = table_info(SUCCESS_CODE_TABLE_ID,)?;
+	let error_codes_info = table_info(ERROR_CODE_TABLE_ID,)?;
+	let warn_codes_info = table_info(WARN_CODE_TABLE_ID,)?;
 
 	// Convert raw table data to structured status code info
 	let success_codes = status_codes_info(success_codes_info,);
@@ -401,171 +611,8 @@ fn assoc_const(
 	}
 }
 
-/// Searches for an HTML element with a specific ID in the DOM tree
-///
-/// This function recursively traverses the HTML DOM tree to find an element
-/// with the specified ID attribute. It performs a depth-first search through
-/// all child nodes.
-///
-/// # Arguments
-///
-/// * `node` - The root node to start searching from
-/// * `id` - The ID attribute value to search for
-///
-/// # Returns
-///
-/// An `Option<Rc<Node>>` containing the found element, or `None` if not found
-pub fn get_element_by_id(node: Rc<Node,>, id: &str,) -> Option<Rc<Node,>,> {
-	// Check if current node has the target ID
-	let found = if let NodeData::Element { attrs, .. } = &node.data {
-		let attrs_borrow = attrs.borrow();
-		attrs_borrow.iter().any(|a| {
-			// Create a tendril for the target ID
-			let value = unsafe {
-				tendril::StrTendril::from_byte_slice_without_validating(
-					id.as_bytes(),
-				)
-			};
-			let local_name = local_name!("id");
-
-			// Check if this attribute is an ID with the target value
-			*a.name.local == *local_name && a.value == value
-		},)
-	} else {
-		false
-	};
-
-	if found {
-		Some(node,)
-	} else {
-		// Recursively search child nodes
-		node.children
-			.borrow()
-			.iter()
-			.find_map(|n| get_element_by_id(n.clone(), id,),)
-	}
-}
-
-/// Searches for HTML elements with a specific attribute value
-///
-/// This function recursively searches the DOM tree for elements that have
-/// an attribute with the specified name containing the specified value.
-///
-/// # Arguments
-///
-/// * `node` - The root node to start searching from
-/// * `attr` - The attribute name to search for
-/// * `value` - The value that the attribute should contain
-///
-/// # Returns
-///
-/// A vector of all matching nodes. Returns an empty vector if no matches are
-/// found.
-///
-/// # Note
-///
-/// This function is currently unused but kept for potential future use.
-#[allow(dead_code)]
-fn get_elements_by_attribute(
-	node: Rc<Node,>,
-	attr: &str,
-	value: &str,
-) -> Vec<Rc<Node,>,> {
-	let mut rslt = vec![];
-
-	// Check if current node matches the attribute criteria
-	let matches = match &node.data {
-		NodeData::Element { attrs, .. } => attrs.borrow().iter().any(|a| {
-			let local_name =
-				string_cache::Atom::<LocalNameStaticSet,>::from(attr,);
-			*a.name.local == *local_name && a.value.contains(value,)
-		},),
-		_ => false,
-	};
-
-	if matches {
-		rslt.push(node.clone(),);
-	}
-
-	// Recursively search child nodes
-	node.children.borrow().iter().for_each(|n| {
-		let mut child_matches =
-			get_elements_by_attribute(n.clone(), attr, value,);
-		rslt.append(&mut child_matches,);
-	},);
-
-	rslt
-}
-
-/// Searches for HTML elements with a specific tag name
-///
-/// This function recursively searches the DOM tree for elements with
-/// the specified tag name (e.g., "div", "table", "tr").
-///
-/// # Arguments
-///
-/// * `node` - The root node to start searching from
-/// * `tag_name` - The HTML tag name to search for
-///
-/// # Returns
-///
-/// A vector of all matching elements
-///
-/// # Caution
-///
-/// clone argument passed to `node` every time
-fn get_elements_by_name(node: Rc<Node,>, tag_name: &str,) -> Vec<Rc<Node,>,> {
-	let mut rslt = vec![];
-
-	// Check if current node matches the tag name
-	let matches = match &node.data {
-		NodeData::Element { name, .. } => {
-			let element_name =
-				string_cache::Atom::<LocalNameStaticSet,>::from(tag_name,);
-			*name.local == *element_name
-		},
-		_ => false,
-	};
-
-	if matches {
-		rslt.push(node.clone(),);
-	}
-
-	// Recursively search child nodes
-	node.children.borrow().clone().into_iter().for_each(|n| {
-		let mut child_matches = get_elements_by_name(n.clone(), tag_name,);
-		rslt.append(&mut child_matches,);
-	},);
-
-	rslt
-}
-
-/// Extracts table rows from an HTML table, excluding the header row
-///
-/// This function finds all `<tr>` elements within a table and returns all
-/// rows except the first one (which is assumed to be the header).
-///
-/// # Arguments
-///
-/// * `node` - The table node to extract rows from
-///
-/// # Returns
-///
-/// A vector of table row nodes, excluding the header row
-fn table_rows(node: Rc<Node,>,) -> Vec<Rc<Node,>,> {
-	// Get all <tr> elements and skip the first one (header)
-	get_elements_by_name(node.clone(), "tr",)[1..].to_vec()
-}
-
-/// Extracts text data from table cells in a table row
-///
-/// This function expects a table row with exactly 3 cells containing
-/// paragraph (`<p>`) elements with text content. It extracts the text
-/// from each cell to build the status code information.
-///
-/// # Arguments
-///
-/// * `node` - The table row node to extract data from
+/// Extracts text of the mnemonic, value and description cells of a status
+/// code table row
 ///
 /// # Returns
 ///
@@ -574,40 +621,18 @@ fn table_rows(node: Rc<Node,>,) -> Vec<Rc<Node,>,> {
 /// 2. Status code value (e.g., "0x00000000")
 /// 3. Status code description
 ///
-/// # Panics
+/// # Errors
 ///
-/// Panics if the expected paragraph elements or text nodes are not found
-fn table_data(node: Rc<Node,>,) -> Vec<String,> {
-	let mut rslt = vec![];
-
-	// Find all paragraph elements in the row (should be 3)
-	let row = get_elements_by_name(node.clone(), "p",);
-
-	// Extract text from the first cell (mnemonic)
-	let NodeData::Text { ref contents, } =
-		row[0].clone().children.borrow()[0].clone().data
-	else {
-		panic!("text node expected: {:#?}", row[0].clone())
-	};
-	rslt.push(contents.borrow().as_str().to_string(),);
-
-	// Extract text from the second cell (value)
-	let NodeData::Text { ref contents, } =
-		row[1].clone().children.borrow()[0].clone().data
-	else {
-		panic!("text node expected: {:#?}", row[1].clone())
-	};
-	rslt.push(contents.borrow().as_str().to_string(),);
-
-	// Extract text from the third cell (description)
-	let NodeData::Text { ref contents, } =
-		row[2].clone().children.borrow()[0].clone().data
-	else {
-		panic!("text node expected: {:#?}", row[2].clone())
-	};
-	rslt.push(contents.borrow().as_str().to_string(),);
-
-	rslt
+/// Returns an error if the row has no cell under one of the headers
+fn status_row(row: &RowRef,) -> Rslt<Vec<String,>,> {
+	STATUS_TABLE_HEADERS
+		.iter()
+		.map(|header| {
+			row.get(header,)
+				.map(|cell| cell.text(),)
+				.ok_or(anyhow!("no `{header}` cell in status code table"),)
+		},)
+		.collect()
 }
 
 /// Converts raw table data into structured status code information
@@ -710,7 +735,10 @@ fn inspect_node(node: Rc<Node,>,) -> Diag {
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use crate::html::get_elements_by_attribute;
+	use crate::html::get_elements_by_name;
 	use html5ever::QualName;
+	use html5ever::local_name;
 	use html5ever::ns;
 	use markup5ever::namespace_url;
 
@@ -850,10 +878,10 @@ mod tests {
 		let node = parse_text(table_html,);
 		let table_node =
 			get_elements_by_name(node.clone(), "table",)[0].clone();
-		let rows = table_rows(table_node,);
+		let table = TableRef::new(table_node,);
 
 		// Should return 2 rows (excluding header)
-		assert_eq!(rows.len(), 2);
+		assert_eq!(table.rows().count(), 2);
 	}
 
 	#[test]
@@ -861,6 +889,7 @@ mod tests {
 		// Create a table row with paragraph elements
 		let row_html = r#"
 <table>
+	<tr><th>Mnemonic</th><th>Value</th><th>Description</th></tr>
 	<tr>
 		<td><p>EFI_SUCCESS</p></td>
 		<td><p>0x00000000</p></td>
@@ -869,9 +898,12 @@ mod tests {
 <table/>"#;
 
 		let node = parse_text(row_html,);
-		let row_node = get_elements_by_name(node.clone(), "tr",);
-		assert_eq!(row_node.len(), 1, "{row_node:#?}");
-		let data = table_data(row_node[0].clone(),);
+		let table_node =
+			get_elements_by_name(node.clone(), "table",)[0].clone();
+		let table = TableRef::new(table_node,);
+		let rows: Vec<RowRef,> = table.rows().collect();
+		assert_eq!(rows.len(), 1, "{rows:#?}");
+		let data = status_row(&rows[0],).unwrap();
 
 		assert_eq!(data.len(), 3);
 		assert_eq!(data[0], "EFI_SUCCESS");
@@ -979,6 +1011,53 @@ mod tests {
 		assert!(result.is_some());
 	}
 
+	#[test]
+	fn test_status_args_parse() {
+		let args: StatusArgs = syn::parse_quote!(2.11);
+		assert!(args.pin.is_none());
+		let args: StatusArgs = syn::parse_quote!(2.11, pin = "sha256:00");
+		assert_eq!(args.pin.unwrap().value(), "sha256:00");
+
+		// a malformed pin is rejected before fetching
+		let args: StatusArgs = syn::parse_quote!(2.11, pin = "md5:00");
+		let err = status(args,).unwrap_err();
+		assert!(err.to_string().starts_with("[OSO0007]"));
+	}
+
+	#[test]
+	fn test_sha256() {
+		assert_eq!(
+			sha256(b"abc"),
+			"sha256:ba7816bf8f01cfea414140de5dae2223\
+			 b00361a396177a9cb410ff61f20015ad"
+		);
+	}
+
+	#[test]
+	fn test_lock_round_trip_and_diff() {
+		let info = |mnemonic: &str| StatusCodeInfo {
+			mnemonic: mnemonic.to_string(),
+			value:    0,
+			desc:     String::new(),
+		};
+		let spec = |codes: &[&str]| StatusCode {
+			success: vec![info("EFI_SUCCESS",)],
+			error:   codes.iter().map(|c| info(c,),).collect(),
+			warn:    vec![],
+		};
+
+		let old = Lock::new("2.10", "sha256:aa", &spec(&["EFI_LOAD_ERROR",],),);
+		assert_eq!(Lock::parse(&old.to_toml(),), Some(old));
+
+		let old = Lock::new("2.10", "sha256:aa", &spec(&["EFI_LOAD_ERROR",],),);
+		let new = Lock::new("2.11", "sha256:bb", &spec(&["EFI_HTTP_ERROR",],),);
+		assert_eq!(lock_diff(&old, &new,), [
+			"status codes added since 2.10: EFI_HTTP_ERROR",
+			"status codes removed since 2.10: EFI_LOAD_ERROR",
+		]);
+		assert_eq!(lock_diff(&old, &old,).len(), 1);
+	}
+
 	#[test]
 	fn test_constants_values() {
 		// Test that the HTML element ID constants are correct
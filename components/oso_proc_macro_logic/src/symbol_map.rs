@@ -0,0 +1,181 @@
+//! # Symbol Map
+//!
+//! `symbol_map!` reserves the section `.oso_symbols` in the kernel image, so
+//! the kernel resolves addresses to names without the ELF symbol table.
+//! The kernel does not know its own addresses until it is linked, so the
+//! section is reserved empty and `cargo xtask` writes the table into it after
+//! linking. Patching in place keeps every address of the image as linked.
+//!
+//! The section starts with a header of 16 bytes, little endian:
+//!
+//! - `magic: [u8; 4]`: `OSYM`
+//! - `version: u8`, followed by 3 reserved bytes
+//! - `count: u32`: Number of symbols, 0 until the table is written
+//! - `capacity: u32`: Size of the section including the header
+//!
+//! The layout of the table following it is described in
+//! `oso_dev_util::symbol_map`, which writes it.
+//!
+//! ```ignore
+//! symbol_map!(capacity = 0x40000);
+//!
+//! let bytes: &'static [u8] = symbol_map_bytes();
+//! ```
+
+use crate::RsltP;
+use syn::LitInt;
+use syn::Token;
+use syn::parse::Parse;
+use syn::parse::ParseStream;
+
+/// name of the reserved section
+pub const SECTION: &str = ".oso_symbols";
+/// first bytes of the section
+pub const MAGIC: [u8; 4] = *b"OSYM";
+/// version of the layout of the section
+pub const VERSION: u8 = 1;
+/// bytes of the header
+pub const HEADER_SIZE: usize = 16;
+
+/// arguments of `symbol_map!`
+#[derive(Debug, Clone,)]
+pub struct SymbolMapArgs {
+	/// bytes of the section including the header
+	pub capacity: LitInt,
+}
+
+impl Parse for SymbolMapArgs {
+	fn parse(input: ParseStream,) -> syn::Result<Self,> {
+		let key: syn::Ident = input.parse()?;
+		if key != "capacity" {
+			return Err(syn::Error::new(key.span(), "expected `capacity`",),);
+		}
+		input.parse::<Token![=]>()?;
+		let capacity = input.parse()?;
+		input.parse::<Option<Token![,],>>()?;
+		Ok(Self { capacity, },)
+	}
+}
+
+/// Reserves the section as `static SYMBOL_MAP` and generates
+/// `symbol_map_bytes`, which returns its contents
+///
+/// The contents are read through an opaque pointer, as the compiler would
+/// otherwise fold reads of the empty table written here.
+pub fn symbol_map(args: SymbolMapArgs,) -> RsltP {
+	let capacity: usize = args.capacity.base10_parse()?;
+	if capacity <= HEADER_SIZE || !capacity.is_multiple_of(8,) {
+		return Err(syn::Error::new(
+			args.capacity.span(),
+			format!(
+				"capacity is a multiple of 8 larger than the header of \
+				 {HEADER_SIZE} bytes"
+			),
+		)
+		.into(),);
+	}
+	let Ok(capacity_field,) = u32::try_from(capacity,) else {
+		return Err(syn::Error::new(
+			args.capacity.span(),
+			"capacity does not fit in 32 bits",
+		)
+		.into(),);
+	};
+
+	let header = header(capacity_field,);
+	let body = capacity - HEADER_SIZE;
+	Ok((
+		quote::quote! {
+			/// table of `symbol_map!`, written after linking
+			#[repr(C, align(8))]
+			struct SymbolMapSection {
+				header: [u8; #HEADER_SIZE],
+				body:   [u8; #body],
+			}
+
+			#[used]
+			#[unsafe(link_section = #SECTION)]
+			static SYMBOL_MAP: SymbolMapSection = SymbolMapSection {
+				header: [#(#header),*],
+				body:   [0; #body],
+			};
+
+			/// contents of the section `.oso_symbols`
+			fn symbol_map_bytes() -> &'static [u8] {
+				let map = ::core::hint::black_box(&raw const SYMBOL_MAP,);
+				// SAFETY: `map` points to the static, which is never written
+				// while the kernel runs
+				unsafe {
+					::core::slice::from_raw_parts(map.cast::<u8>(), #capacity,)
+				}
+			}
+		},
+		vec![],
+	),)
+}
+
+/// `symbol_map_bytes` returning an empty map, so that callers still compile
+pub fn fallback(
+	_args: &SymbolMapArgs,
+	errors: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+	quote::quote! {
+		#errors
+		fn symbol_map_bytes() -> &'static [u8] {
+			&[]
+		}
+	}
+}
+
+/// header of a section of `capacity` bytes without symbols
+pub fn header(capacity: u32,) -> [u8; HEADER_SIZE] {
+	let mut header = [0; HEADER_SIZE];
+	header[..4].copy_from_slice(&MAGIC,);
+	header[4] = VERSION;
+	header[12..].copy_from_slice(&capacity.to_le_bytes(),);
+	header
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use syn::parse_quote;
+
+	#[test]
+	fn test_symbol_map_reserves_section() {
+		let args: SymbolMapArgs = parse_quote!(capacity = 0x100);
+		let (tokens, diags,) = symbol_map(args,).unwrap();
+		let tokens = tokens.to_string();
+		assert!(tokens.contains("link_section = \".oso_symbols\""));
+		assert!(tokens.contains("body : [0 ; 240usize]"));
+		assert!(tokens.contains("fn symbol_map_bytes"));
+		assert!(diags.is_empty());
+	}
+
+	#[test]
+	fn test_symbol_map_header() {
+		let header = header(0x100,);
+		assert_eq!(&header[..4], b"OSYM");
+		assert_eq!(header[4], VERSION);
+		assert_eq!(&header[8..12], &[0; 4]);
+		assert_eq!(&header[12..], &0x100_u32.to_le_bytes());
+	}
+
+	#[test]
+	fn test_symbol_map_rejects_bad_capacity() {
+		for args in [
+			parse_quote!(capacity = 16),
+			parse_quote!(capacity = 100),
+			parse_quote!(capacity = 0x1_0000_0000),
+		] {
+			let args: SymbolMapArgs = args;
+			assert!(symbol_map(args,).is_err());
+		}
+	}
+
+	#[test]
+	fn test_symbol_map_args_key() {
+		let args = syn::parse_str::<SymbolMapArgs,>("size = 64",);
+		assert!(args.is_err());
+	}
+}
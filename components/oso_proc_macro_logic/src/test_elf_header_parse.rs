@@ -422,7 +422,10 @@ fn parse_elf_version(header: &ReadElfH,) -> RsltP {
 
 	Ok((
 		elf_version.clone(),
-		vec![Diag::Warn(format!("unrecognized elf version: {elf_version}"),)],
+		vec![
+			Diag::Warn(format!("unrecognized elf version: {elf_version}"),)
+				.into(),
+		],
 	),)
 }
 
@@ -505,7 +508,10 @@ fn parse_abi_version(header: &ReadElfH,) -> RsltP {
 
 	Ok((
 		abi_version.clone(),
-		vec![Diag::Warn(format!("unrecognized abi version: {abi_version}"),)],
+		vec![
+			Diag::Warn(format!("unrecognized abi version: {abi_version}"),)
+				.into(),
+		],
 	),)
 }
 
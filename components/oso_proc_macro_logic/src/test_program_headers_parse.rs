@@ -132,29 +132,32 @@ pub fn program_headers_info() -> RsltP {
 	let program_headers = readelf_l()?;
 
 	// Generate ProgramHeader struct for each program header entry
-	let program_headers = program_headers.iter().map(|rel| {
-		let ty = parse_program_header_type(rel,);
-		let flags = rel.flags;
-		let offset = rel.offset;
-		let virtual_address = rel.virtual_address;
-		let physical_address = rel.physical_address;
-		let file_size = rel.file_size;
-		let memory_size = rel.memory_size;
-		let align = rel.align;
-
-		quote::quote! {
-			ProgramHeader {
-				ty: #ty,
-				flags: #flags,
-				offset: #offset,
-				virtual_address: #virtual_address,
-				physical_address: #physical_address,
-				file_size: #file_size,
-				memory_size: #memory_size,
-				align: #align,
-			}
-		}
-	},);
+	let program_headers: Vec<_,> = program_headers
+		.iter()
+		.map(|rel| -> Rslt<_,> {
+			let ty = parse_program_header_type(rel,)?;
+			let flags = rel.flags;
+			let offset = rel.offset;
+			let virtual_address = rel.virtual_address;
+			let physical_address = rel.physical_address;
+			let file_size = rel.file_size;
+			let memory_size = rel.memory_size;
+			let align = rel.align;
+
+			Ok(quote::quote! {
+				ProgramHeader {
+					ty: #ty,
+					flags: #flags,
+					offset: #offset,
+					virtual_address: #virtual_address,
+					physical_address: #physical_address,
+					file_size: #file_size,
+					memory_size: #memory_size,
+					align: #align,
+				}
+			},)
+		},)
+		.try_collect()?;
 
 	// Generate vector containing all program headers
 	Ok((
@@ -167,11 +170,32 @@ pub fn program_headers_info() -> RsltP {
 	),)
 }
 
+/// readelf names of the segment types which `ProgramHeaderType` has a
+/// variant for, other than the range markers such as `LOOS`
+///
+/// processor specific names depend on the machine of the binary
+const SEGMENT_TYPES: &[(&str, &str,)] = &[
+	("NULL", "Null",),
+	("LOAD", "Load",),
+	("DYNAMIC", "Dynamic",),
+	("INTERP", "Interp",),
+	("NOTE", "Note",),
+	("SHLIB", "Shlib",),
+	("PHDR", "Phdr",),
+	("TLS", "Tls",),
+	("GNU_EH_FRAME", "GnuEhFrame",),
+	("GNU_STACK", "GnuStack",),
+	("GNU_RELRO", "GnuRelro",),
+	("GNU_PROPERTY", "GnuProperty",),
+	("GNU_SFRAME", "GnuSframe",),
+	("SUNWSTACK", "Sunwstack",),
+	("EXIDX", "ArmExidx",),
+];
+
 /// Parses program header type from readelf output.
 ///
-/// Converts the program header type string into the appropriate enum variant.
-/// The function converts underscore-separated uppercase strings into CamelCase
-/// identifiers for the ProgramHeaderType enum.
+/// Converts the program header type string into the matching
+/// `ProgramHeaderType`.
 ///
 /// # Parameters
 ///
@@ -179,38 +203,55 @@ pub fn program_headers_info() -> RsltP {
 ///
 /// # Returns
 ///
-/// Returns a token stream representing the ProgramHeaderType enum variant
+/// Returns a token stream representing the ProgramHeaderType
 ///
 /// # Conversion Logic
 ///
-/// The function:
-/// 1. Splits the type string on underscores
-/// 2. Converts each word to CamelCase (first letter uppercase, rest lowercase)
-/// 3. Concatenates the words to form the enum variant name
+/// - names of [`SEGMENT_TYPES`] become their variant
+/// - types readelf has no name for are printed as an offset from `LOOS` or
+///   `LOPROC`, or as `<unknown>: value`. They are converted from their value,
+///   so the loader decides between a variant and `ProgramHeaderType::Other`
 ///
 /// # Examples
 ///
-/// - "PT_LOAD" -> `ProgramHeaderType::PtLoad`
-/// - "PT_DYNAMIC" -> `ProgramHeaderType::PtDynamic`
-/// - "PT_INTERP" -> `ProgramHeaderType::PtInterp`
+/// - "LOAD" -> `ProgramHeaderType::Load`
+/// - "GNU_STACK" -> `ProgramHeaderType::GnuStack`
+/// - "LOOS+0x474e554" -> `ProgramHeaderType::from(0x6474e554u32)`
+///
+/// # Errors
+///
+/// Returns an error for a name without a value which is not in
+/// [`SEGMENT_TYPES`]
 fn parse_program_header_type(
 	program_header: &ReadElfL,
-) -> proc_macro2::TokenStream {
-	// Convert underscore_separated to CamelCase
-	let camel_cased: String = program_header
-		.ty
-		.split("_",)
-		.flat_map(|word| {
-			word.char_indices().map(|(i, c,)| {
-				if i == 0 { c } else { (c as u8 - b'A' + b'a') as char }
-			},)
-		},)
-		.collect();
-
-	let ident = syn::Ident::new(&camel_cased, Span::call_site(),);
+) -> Rslt<proc_macro2::TokenStream,> {
+	let ty = program_header.ty.as_str();
+	if let Some((_, variant,),) =
+		SEGMENT_TYPES.iter().find(|(name, _,)| *name == ty,)
+	{
+		let ident = syn::Ident::new(variant, Span::call_site(),);
+		return Ok(quote::quote! { ProgramHeaderType::#ident },);
+	}
+
+	let value = segment_type_value(ty,).ok_or_else(|| {
+		anyhow!("unknown segment type `{ty}`. add it to SEGMENT_TYPES")
+	},)?;
+	Ok(quote::quote! { ProgramHeaderType::from(#value) },)
+}
 
-	quote::quote! {
-		ProgramHeaderType::#ident
+/// value of a segment type which readelf printed as a number
+fn segment_type_value(ty: &str,) -> Option<u32,> {
+	const LOOS: u32 = 0x6000_0000;
+	const LOPROC: u32 = 0x7000_0000;
+
+	if let Some(offset,) = ty.strip_prefix("LOOS+",) {
+		LOOS.checked_add(parse_str_hex_repr(offset,).ok()?,)
+	} else if let Some(offset,) = ty.strip_prefix("LOPROC+",) {
+		LOPROC.checked_add(parse_str_hex_repr(offset,).ok()?,)
+	} else if let Some(value,) = ty.strip_prefix("<unknown>:",) {
+		parse_str_hex_repr(value.trim(),).ok()
+	} else {
+		None
 	}
 }
 
@@ -223,32 +264,49 @@ pub fn readelf_l() -> Rslt<Vec<ReadElfL,>,> {
 		program_headers_count(&program_headers_info[0],)?;
 
 	program_headers_fields(&program_headers_info, program_header_count,)
-		.map(|s| {
-			let fields_info: Vec<_,> =
-				s.split(" ",).filter(|s| !s.is_empty(),).collect();
-
-			let ty = fields_info[0].to_string();
-			let offset = parse_str_hex_repr(fields_info[1],)?;
-			let virtual_address = parse_str_hex_repr(fields_info[2],)?;
-			let physical_address = parse_str_hex_repr(fields_info[3],)?;
-			let file_size = parse_str_hex_repr(fields_info[4],)?;
-			let memory_size = parse_str_hex_repr(fields_info[5],)?;
-			let (flags, align,) = parse_flags_and_align(&fields_info,)?;
-
-			Ok(ReadElfL {
-				ty,
-				offset,
-				virtual_address,
-				physical_address,
-				file_size,
-				memory_size,
-				flags,
-				align,
-			},)
-		},)
+		.map(|s| parse_program_header(&s,),)
 		.try_collect()
 }
 
+/// parses the two lines readelf prints for a program header, joined
+fn parse_program_header(line: &str,) -> Rslt<ReadElfL,> {
+	let mut fields_info: Vec<_,> =
+		line.split(" ",).filter(|s| !s.is_empty(),).collect();
+	// `<unknown>: 7000ab` is the only type containing a space
+	let ty = if fields_info.first() == Some(&"<unknown>:",)
+		&& fields_info.len() > 1
+	{
+		let value = fields_info.remove(1,);
+		format!("<unknown>: {value}")
+	} else {
+		fields_info.first().copied().unwrap_or_default().to_string()
+	};
+	if fields_info.len() < 7 {
+		return Err(anyhow!(
+			"program header of {} fields is too short: {line}",
+			fields_info.len()
+		),);
+	}
+
+	let offset = parse_str_hex_repr(fields_info[1],)?;
+	let virtual_address = parse_str_hex_repr(fields_info[2],)?;
+	let physical_address = parse_str_hex_repr(fields_info[3],)?;
+	let file_size = parse_str_hex_repr(fields_info[4],)?;
+	let memory_size = parse_str_hex_repr(fields_info[5],)?;
+	let (flags, align,) = parse_flags_and_align(&fields_info,)?;
+
+	Ok(ReadElfL {
+		ty,
+		offset,
+		virtual_address,
+		physical_address,
+		file_size,
+		memory_size,
+		flags,
+		align,
+	},)
+}
+
 fn readelf_l_out() -> Rslt<Vec<String,>,> {
 	let program_headers_info = Command::new("readelf",)
 		.args(["-l", "target/oso_kernel.elf",],)
@@ -328,33 +386,30 @@ fn parse_str_hex_repr<I: IntField,>(hex: &str,) -> Rslt<I,> {
 	I::parse(hex_repr,)
 }
 
+/// flags are printed as up to three letters padded by spaces, such as `R E`,
+/// and may be missing entirely. so every field between the memory size and
+/// the alignment is a part of the flags
 fn parse_flags_and_align(fields_info: &[&str],) -> Rslt<(u32, u64,),> {
-	let rslt = if fields_info.len() == 8 {
-		let flags_str = fields_info[6];
-		let mut flags = 0;
-		if flags_str.contains("R",) {
-			flags |= 0b100;
-		}
-		if flags_str.contains("W",) {
-			flags |= 0b10;
-		}
-		if flags_str.contains("X",) {
-			flags |= 0b1;
-		};
-
-		let align = parse_str_hex_repr(fields_info[7],)?;
-		(flags, align,)
-	} else if fields_info.len() == 9 {
-		let align = parse_str_hex_repr(fields_info[8],)?;
-		(0b101, align,)
-	} else {
+	let [flags_strs @ .., align,] = fields_info.get(6..,).unwrap_or(&[],) else {
 		return Err(anyhow!(
-			"fields_info length should be 8 or 9, get {}",
+			"fields_info length should be 7 or more, get {}",
 			fields_info.len()
 		),);
 	};
 
-	Ok(rslt,)
+	let mut flags = 0;
+	for flag in flags_strs.iter().flat_map(|s| s.chars(),) {
+		flags |= match flag {
+			'R' => 0b100,
+			'W' => 0b10,
+			// readelf prints the execute flag as `E`
+			'E' | 'X' => 0b1,
+			_ => return Err(anyhow!("unknown segment flag `{flag}`"),),
+		};
+	}
+
+	let align = parse_str_hex_repr(align,)?;
+	Ok((flags, align,),)
 }
 
 #[cfg(test)]
@@ -776,4 +831,53 @@ mod tests {
 
 		Ok((),)
 	}
+
+	#[test]
+	fn test_parse_program_header_gnu_segments() -> Rslt<(),> {
+		let stack = parse_program_header(
+			"  GNU_STACK      0x0000000000000000 0x0000000000000000 \
+			 0x0000000000000000                 0x0000000000000000 \
+			 0x0000000000000000  RW     0x10",
+		)?;
+		assert_eq!(stack.ty, "GNU_STACK");
+		assert_eq!((stack.flags, stack.align,), (0b110, 0x10,));
+
+		let load = parse_program_header(
+			"  LOAD 0x1000 0x401000 0x401000 0x2000 0x2000  R E    0x1000",
+		)?;
+		assert_eq!(load.flags, 0b101);
+
+		// a segment without any flag
+		let note = parse_program_header("  NOTE 0x0 0x0 0x0 0x0 0x0 0x4",)?;
+		assert_eq!((note.flags, note.align,), (0, 4,));
+
+		let unknown =
+			parse_program_header("  <unknown>: 8 0x0 0x0 0x0 0x0 0x0 R 0x8",)?;
+		assert_eq!(unknown.ty, "<unknown>: 8");
+		assert_eq!(unknown.flags, 0b100);
+
+		assert!(parse_program_header("  LOAD 0x0 0x0",).is_err());
+		Ok((),)
+	}
+
+	#[test]
+	fn test_parse_program_header_type() -> Rslt<(),> {
+		let ty = |ty: &str| {
+			let header = ReadElfL { ty: ty.to_string(), ..Default::default() };
+			parse_program_header_type(&header,).map(|t| t.to_string(),)
+		};
+		assert_eq!(ty("GNU_RELRO")?, "ProgramHeaderType :: GnuRelro");
+		assert_eq!(ty("EXIDX")?, "ProgramHeaderType :: ArmExidx");
+		assert_eq!(
+			ty("LOOS+0x474e554")?,
+			"ProgramHeaderType :: from (1685382484u32)"
+		);
+		assert_eq!(
+			ty("LOPROC+0x2")?,
+			"ProgramHeaderType :: from (1879048194u32)"
+		);
+		assert_eq!(ty("<unknown>: 8")?, "ProgramHeaderType :: from (8u32)");
+		assert!(ty("OPENBSD_RANDOMIZE").is_err());
+		Ok((),)
+	}
 }
@@ -110,7 +110,7 @@ fn test_impl_init_integration() {
 
 #[test]
 fn test_status_from_spec_html_parsing_integration() {
-	use status::*;
+	use html::*;
 
 	// Test the HTML parsing functions with a complete example
 	let test_html = r#"
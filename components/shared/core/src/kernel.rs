@@ -1,5 +1,563 @@
-#[derive(Debug, Default,)]
+use crate::OsoError;
+use crate::parser::DtResourceError;
+use crate::parser::ScriptError;
+use oso_proc_macro::ErrorCode;
+
+#[derive(Debug, Default, ErrorCode,)]
 pub enum GraphicError {
 	#[default]
+	#[oso_error_code(0x0a01)]
 	InvalidCoordinate,
+	/// boot information has no framebuffer to claim
+	#[oso_error_code(0x0a02)]
+	NoFramebuffer,
+	/// the boot framebuffer is claimed already
+	#[oso_error_code(0x0a03)]
+	AlreadyClaimed,
+	/// the memory map hands out part of the framebuffer as usable memory
+	#[oso_error_code(0x0a04)]
+	NotReserved,
+	/// the framebuffer could not be mapped
+	#[oso_error_code(0x0a05)]
+	Paging(PagingError,),
+	/// shell command has unknown or missing arguments
+	#[oso_error_code(0x0a06)]
+	Usage,
+	/// pixel format of the framebuffer can not be read back
+	#[oso_error_code(0x0a07)]
+	Unreadable,
+	/// screen capture could not be written to a file
+	#[oso_error_code(0x0a08)]
+	Vfs(VfsError,),
+}
+
+impl From<OsoError<PagingError,>,> for OsoError<GraphicError,> {
+	fn from(value: OsoError<PagingError,>,) -> Self {
+		let desc = Some(GraphicError::Paging(value.desc.unwrap_or_default(),),);
+		OsoError { from: value.from, desc, }
+	}
+}
+
+impl From<OsoError<VfsError,>,> for OsoError<GraphicError,> {
+	fn from(value: OsoError<VfsError,>,) -> Self {
+		let desc = Some(GraphicError::Vfs(value.desc.unwrap_or_default(),),);
+		OsoError { from: value.from, desc, }
+	}
+}
+
+impl From<OsoError<GraphicError,>,> for OsoError<(),> {
+	fn from(value: OsoError<GraphicError,>,) -> Self {
+		OsoError { from: value.from, desc: Some((),), }
+	}
+}
+
+/// error of the dma buffer pool
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum DmaError {
+	/// allocation exceeds the budget of the pool. drivers should wait for
+	/// buffers in flight to complete before retrying
+	#[oso_error_code(0x0b01)]
+	Exhausted {
+		requested: usize,
+		available: usize,
+	},
+	/// frame allocator has no contiguous range left
+	#[default]
+	#[oso_error_code(0x0b02)]
+	OutOfFrames,
+	/// frame allocator has no contiguous range ending at or below `limit`
+	#[oso_error_code(0x0b03)]
+	OutOfLowFrames {
+		limit: u64,
+	},
+}
+
+/// error of watchdog drivers and the `watchdog` shell command
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum WatchdogError {
+	/// timeout can not be counted by the device
+	#[oso_error_code(0x0c01)]
+	TimeoutOutOfRange {
+		min_ms: u32,
+		max_ms: u32,
+	},
+	/// shell command has unknown or missing arguments
+	#[default]
+	#[oso_error_code(0x0c02)]
+	Usage,
+	/// no watchdog was installed
+	#[oso_error_code(0x0c03)]
+	NoDevice,
+}
+
+/// error of adapting the boot information of another boot protocol
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum BootProtocolError {
+	/// boot loader passed an unexpected magic value
+	#[oso_error_code(0x0d01)]
+	BadMagic {
+		found: u64,
+	},
+	/// a structure is truncated or overruns its container
+	#[default]
+	#[oso_error_code(0x0d02)]
+	Malformed,
+	/// boot loader does not support the protocol revision the kernel asked for
+	#[oso_error_code(0x0d03)]
+	UnsupportedRevision {
+		revision: u64,
+	},
+	/// boot loader did not answer a required request
+	#[oso_error_code(0x0d04)]
+	MissingResponse(&'static str,),
+	/// more entries than the kernel reserved room for
+	#[oso_error_code(0x0d05)]
+	TooManyEntries {
+		what:     &'static str,
+		capacity: usize,
+	},
+	/// boot information can only be built once
+	#[oso_error_code(0x0d06)]
+	AlreadyBuilt,
+}
+
+/// error of gpio drivers
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum GpioError {
+	/// controller has no pin with this number
+	#[oso_error_code(0x0e01)]
+	PinOutOfRange {
+		pin:   u32,
+		count: u32,
+	},
+	/// `gpio::set` was called before a controller was installed
+	#[default]
+	#[oso_error_code(0x0e02)]
+	NoController,
+}
+
+/// error of block devices
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum BlockError {
+	/// request reaches past the last block of the device
+	#[oso_error_code(0x0f01)]
+	OutOfRange {
+		lba:   u64,
+		count: u64,
+	},
+	/// buffer length is not a multiple of the block size
+	#[oso_error_code(0x0f02)]
+	Misaligned {
+		len: usize,
+	},
+	/// no card in the slot, or the card did not answer initialization
+	#[default]
+	#[oso_error_code(0x0f03)]
+	NoMedia,
+	/// card needs a feature the driver does not implement
+	#[oso_error_code(0x0f04)]
+	Unsupported,
+	/// device did not finish a command in time
+	#[oso_error_code(0x0f05)]
+	Timeout,
+	/// frame allocator has no frames left for the block cache
+	#[oso_error_code(0x0f06)]
+	OutOfFrames,
+	/// controller reported an error. raw error status of the device
+	#[oso_error_code(0x0f07)]
+	Device {
+		status: u32,
+	},
+}
+
+/// error of the virtual file system and the file systems behind it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum VfsError {
+	/// no file or directory at the path
+	#[default]
+	#[oso_error_code(0x1001)]
+	NotFound,
+	/// path is relative or longer than the kernel reserved room for
+	#[oso_error_code(0x1002)]
+	InvalidPath,
+	/// a component of the path other than the last is not a directory
+	#[oso_error_code(0x1003)]
+	NotADirectory,
+	/// file operation on a directory
+	#[oso_error_code(0x1004)]
+	IsADirectory,
+	/// file system or device does not support writing
+	#[oso_error_code(0x1005)]
+	ReadOnly,
+	/// seek before the start of the file
+	#[oso_error_code(0x1006)]
+	InvalidSeek,
+	/// another file system is mounted at the path
+	#[oso_error_code(0x1007)]
+	AlreadyMounted,
+	/// more mounts or devices than the kernel reserved room for
+	#[oso_error_code(0x1008)]
+	TooManyEntries {
+		what:     &'static str,
+		capacity: usize,
+	},
+	/// on-disk structures are inconsistent
+	#[oso_error_code(0x1009)]
+	Corrupt,
+	/// file system uses a feature the driver does not implement
+	#[oso_error_code(0x100a)]
+	Unsupported,
+	/// block device below the file system failed
+	#[oso_error_code(0x100b)]
+	Device(BlockError,),
+}
+
+impl From<OsoError<BlockError,>,> for OsoError<VfsError,> {
+	fn from(value: OsoError<BlockError,>,) -> Self {
+		let error = value.desc.unwrap_or_default();
+		OsoError { from: value.from, desc: Some(VfsError::Device(error,),), }
+	}
+}
+
+/// error of the boot environment service
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum EnvError {
+	/// no entry has the name
+	#[default]
+	#[oso_error_code(0x1101)]
+	NotFound,
+	/// value does not fit the buffer of the caller
+	#[oso_error_code(0x1102)]
+	BufferTooSmall {
+		len: usize,
+	},
+	/// the environment was already assembled
+	#[oso_error_code(0x1103)]
+	AlreadyInitialized,
+	/// boot information has more entries than the kernel reserved room for
+	#[oso_error_code(0x1104)]
+	TooManyEntries {
+		capacity: usize,
+	},
+	/// shell command was called with wrong arguments
+	#[oso_error_code(0x1105)]
+	Usage,
+}
+
+/// error of the async executor
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum ExecutorError {
+	/// another executor is running. wakers are global, so there is one
+	#[default]
+	#[oso_error_code(0x1201)]
+	AlreadyExists,
+	/// every task slot is in use
+	#[oso_error_code(0x1202)]
+	TooManyTasks {
+		capacity: usize,
+	},
+}
+
+/// error of the interrupt latency statistics
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum IrqStatsError {
+	/// shell command has unknown or missing arguments
+	#[default]
+	#[oso_error_code(0x1301)]
+	Usage,
+}
+
+/// error of the persistent settings
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum SettingsError {
+	/// the loader did not hand over runtime services
+	#[default]
+	#[oso_error_code(0x1401)]
+	Unavailable,
+	/// no setting of the name, or it was never stored
+	#[oso_error_code(0x1402)]
+	NotFound,
+	/// stored value fails its checksum or is not UTF-8
+	#[oso_error_code(0x1403)]
+	Corrupt,
+	/// value is not accepted by the setting
+	#[oso_error_code(0x1404)]
+	InvalidValue,
+	/// value is longer than settings keep
+	#[oso_error_code(0x1405)]
+	TooLong {
+		capacity: usize,
+	},
+	/// write budget of this boot is used up
+	#[oso_error_code(0x1406)]
+	RateLimited,
+	/// firmware returned an error status
+	#[oso_error_code(0x1407)]
+	Firmware {
+		status: usize,
+	},
+	/// shell command has unknown or missing arguments
+	#[oso_error_code(0x1408)]
+	Usage,
+}
+
+impl From<OsoError<EfiError,>,> for OsoError<SettingsError,> {
+	fn from(value: OsoError<EfiError,>,) -> Self {
+		let desc = value.desc.map(|e| match e {
+			EfiError::Unavailable | EfiError::Unsupported => {
+				SettingsError::Unavailable
+			},
+			EfiError::NotFound => SettingsError::NotFound,
+			// not written by the settings
+			EfiError::BufferTooSmall { .. } => SettingsError::Corrupt,
+			EfiError::InvalidParameter => SettingsError::InvalidValue,
+			EfiError::Firmware { status, } => {
+				SettingsError::Firmware { status, }
+			},
+		},);
+		OsoError { from: value.from, desc, }
+	}
+}
+
+/// error of the uefi runtime services
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum EfiError {
+	/// the loader did not hand over runtime services
+	#[default]
+	#[oso_error_code(0x1501)]
+	Unavailable,
+	/// firmware does not support the service after boot
+	#[oso_error_code(0x1502)]
+	Unsupported,
+	/// no variable of the name
+	#[oso_error_code(0x1503)]
+	NotFound,
+	/// buffer can not hold the data of the variable
+	#[oso_error_code(0x1504)]
+	BufferTooSmall {
+		required: usize,
+	},
+	/// argument is out of range, e.g. a time on the 32nd of a month
+	#[oso_error_code(0x1505)]
+	InvalidParameter,
+	/// firmware returned an error status
+	#[oso_error_code(0x1506)]
+	Firmware {
+		status: usize,
+	},
+}
+
+/// error of the scheduler
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum SchedError {
+	/// no task has the ID
+	#[default]
+	#[oso_error_code(0x1601)]
+	NotFound,
+	/// every task slot is in use
+	#[oso_error_code(0x1602)]
+	TooManyTasks {
+		capacity: usize,
+	},
+	/// priority is not below the number of levels
+	#[oso_error_code(0x1603)]
+	InvalidPriority {
+		priority: u8,
+	},
+	/// stack can not hold the initial context of a task
+	#[oso_error_code(0x1604)]
+	StackTooSmall {
+		min: usize,
+	},
+	/// shell command has unknown or missing arguments
+	#[oso_error_code(0x1605)]
+	Usage,
+}
+
+/// error of the idle statistics
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum IdleError {
+	/// shell command has unknown or missing arguments
+	#[default]
+	#[oso_error_code(0x1701)]
+	Usage,
+}
+
+/// error of the `cpuinfo` shell command
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum CpuError {
+	/// shell command has unknown or missing arguments
+	#[default]
+	#[oso_error_code(0x1801)]
+	Usage,
+}
+
+/// error of the `spinbench` shell command
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum SpinError {
+	/// shell command has unknown or missing arguments
+	#[default]
+	#[oso_error_code(0x1901)]
+	Usage,
+}
+
+/// error of the `dt` shell command
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum DtError {
+	/// shell command has unknown or missing arguments
+	#[default]
+	#[oso_error_code(0x1a01)]
+	Usage,
+	/// the boot loader handed over no device tree
+	#[oso_error_code(0x1a02)]
+	NoDeviceTree,
+	/// no node has the path
+	#[oso_error_code(0x1a03)]
+	NodeNotFound,
+	/// the node has no property of the name
+	#[oso_error_code(0x1a04)]
+	PropertyNotFound,
+	/// a resource of the node could not be read
+	#[oso_error_code(0x1a05)]
+	Resource(DtResourceError,),
+}
+
+impl From<OsoError<DtResourceError,>,> for OsoError<DtError,> {
+	fn from(value: OsoError<DtResourceError,>,) -> Self {
+		let desc = Some(DtError::Resource(value.desc.unwrap_or_default(),),);
+		OsoError { from: value.from, desc, }
+	}
+}
+
+/// error of the `bootinfo` shell command
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum HandoffError {
+	/// shell command has unknown or missing arguments
+	#[default]
+	#[oso_error_code(0x1b01)]
+	Usage,
+	/// the kernel was entered without boot information
+	#[oso_error_code(0x1b02)]
+	NoBootInfo,
+}
+
+/// error of device memory mappings
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum PagingError {
+	/// range has no bytes
+	#[default]
+	#[oso_error_code(0x1c01)]
+	Empty,
+	/// part of the range is mapped with the attribute `existing` already.
+	/// mapping memory with two attributes is undefined
+	#[oso_error_code(0x1c02)]
+	Conflict {
+		existing: u8,
+	},
+	/// every slot of the mapping table is taken
+	#[oso_error_code(0x1c03)]
+	TableFull,
+	/// the last page of the range ends past the address space
+	#[oso_error_code(0x1c04)]
+	OutOfRange,
+}
+
+/// error of the event tracer
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum TraceError {
+	/// shell command has unknown or missing arguments
+	#[default]
+	#[oso_error_code(0x1d01)]
+	Usage,
+}
+
+/// error of the input recorder
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum RecordError {
+	/// shell command has unknown or missing arguments
+	#[default]
+	#[oso_error_code(0x1e01)]
+	Usage,
+}
+
+/// error of the virtual terminals
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum VtError {
+	/// shell command has unknown or missing arguments
+	#[default]
+	#[oso_error_code(0x1f01)]
+	Usage,
+	/// no terminal has the name or number
+	#[oso_error_code(0x1f02)]
+	UnknownTerminal,
+	/// a terminal is being written
+	#[oso_error_code(0x1f03)]
+	Busy,
+}
+
+/// error of the debug shell
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum ShellError {
+	/// no command has the name
+	#[default]
+	#[oso_error_code(0x2001)]
+	UnknownCommand,
+	/// command ran and reported an error
+	#[oso_error_code(0x2002)]
+	CommandFailed,
+	/// command line has more words than the shell splits
+	#[oso_error_code(0x2003)]
+	TooManyArgs {
+		capacity: usize,
+	},
+	/// script stopped at the line, whose command is unknown or failed
+	#[oso_error_code(0x2004)]
+	Aborted {
+		line: usize,
+	},
+	/// script is malformed
+	#[oso_error_code(0x2005)]
+	Script(ScriptError,),
+	/// script is longer than the buffer it is read into
+	#[oso_error_code(0x2006)]
+	TooLarge {
+		capacity: usize,
+	},
+	/// script is not UTF-8
+	#[oso_error_code(0x2007)]
+	NotUtf8,
+	/// script could not be read
+	#[oso_error_code(0x2008)]
+	Vfs(VfsError,),
+}
+
+impl From<OsoError<VfsError,>,> for OsoError<ShellError,> {
+	fn from(value: OsoError<VfsError,>,) -> Self {
+		let error = value.desc.unwrap_or_default();
+		OsoError { from: value.from, desc: Some(ShellError::Vfs(error,),), }
+	}
+}
+
+/// error of the boot profile
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum BootProfileError {
+	/// shell command has unknown or missing arguments
+	#[default]
+	#[oso_error_code(0x2101)]
+	Usage,
+}
+
+/// error of the MMIO fault injection
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum MmioFaultError {
+	/// shell command has unknown or missing arguments
+	#[default]
+	#[oso_error_code(0x2201)]
+	Usage,
+	/// every rule slot is in use
+	#[oso_error_code(0x2202)]
+	TooManyRules,
+	/// the kernel is built without the `fault_injection` feature
+	#[oso_error_code(0x2203)]
+	Disabled,
 }
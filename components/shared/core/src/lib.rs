@@ -80,6 +80,24 @@
 //! }
 //! ```
 //!
+//! ## Error Codes
+//!
+//! Every variant of the error enums has a code, given by
+//! `#[oso_error_code(..)]` and returned by its `code` method. The high byte
+//! of a code identifies the enum and the low byte the variant, so a new enum
+//! takes the next free high byte. [`registry`] lists every code of the
+//! workspace and is written by `cargo xtask error-codes`, which also rejects
+//! codes used twice.
+//!
+//! ```rust
+//! use oso_error::kernel::GpioError;
+//! use oso_error::registry;
+//!
+//! let code = GpioError::NoController.code();
+//! let entry = registry::lookup(code,).unwrap();
+//! assert_eq!(entry.name, "oso_error::kernel::GpioError::NoController");
+//! ```
+//!
 //! ## Design Philosophy
 //!
 //! The `oso_error` crate is designed to be minimal yet flexible, providing just
@@ -96,6 +114,7 @@ use core::fmt::Debug;
 pub mod kernel;
 pub mod loader;
 pub mod parser;
+pub mod registry;
 
 /// A type alias for commonly used Result type with OsoError as the error type.
 ///
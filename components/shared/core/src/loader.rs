@@ -1,11 +1,14 @@
 use crate::OsoError;
+use oso_proc_macro::ErrorCode;
 
-#[derive(Debug, Default,)]
+#[derive(Debug, Default, ErrorCode,)]
 pub enum EfiParseError {
+	#[oso_error_code(0x0101)]
 	EndOfBinary {
 		parser_pos: &'static str,
 		stage:      EfiParseStage,
 	},
+	#[oso_error_code(0x0102)]
 	SizeOverflow {
 		stage:    EfiParseStage,
 		name:     u64,
@@ -13,26 +16,54 @@ pub enum EfiParseError {
 		base:     u64,
 		size:     u64,
 	},
+	#[oso_error_code(0x0103)]
 	UnknownEfiType(u16,),
+	#[oso_error_code(0x0104)]
 	InvalidIdentLen(usize,),
+	#[oso_error_code(0x0105)]
 	BadMagicNumber(u8, u8, u8, u8,),
+	#[oso_error_code(0x0106)]
 	InvalidFileClass(u8,),
+	#[oso_error_code(0x0107)]
 	OsAbiOutOfSupport(u8,),
 	/// string context
+	#[oso_error_code(0x0108)]
 	DelimiterNotFound(u8,),
+	/// string at `offset` of a string table is not valid utf-8
+	#[oso_error_code(0x0109)]
+	InvalidUtf8 {
+		offset: usize,
+	},
+	#[oso_error_code(0x010a)]
 	TooManySymbolsOffset {
 		offset: usize,
 		count:  usize,
 	},
+	#[oso_error_code(0x010b)]
 	InvalidEndianFlag(u8,),
-	InvalidProgramHeaderType(u32,),
+	#[oso_error_code(0x010d)]
 	InvalidGnuHash {
 		buckets_count: usize,
 		min_chain:     usize,
 		bloom_size:    usize,
 	},
 	#[default]
+	#[oso_error_code(0x010e)]
 	Unknown,
+	/// relocation type the loader can not apply for the machine
+	#[oso_error_code(0x010f)]
+	UnsupportedRelocation {
+		machine: u16,
+		ty:      u32,
+	},
+	/// relocation patches an address outside of the loaded segments
+	#[oso_error_code(0x0110)]
+	RelocationOutOfImage {
+		offset: u64,
+	},
+	/// relocation refers to a symbol the kernel does not define
+	#[oso_error_code(0x0111)]
+	UndefinedSymbol(usize,),
 }
 
 #[derive(Debug, Default,)]
@@ -44,16 +75,104 @@ pub enum EfiParseStage {
 	StringTable,
 }
 
-#[derive(Debug, Default,)]
+#[derive(Debug, Default, ErrorCode,)]
 pub enum UefiError {
 	#[default]
+	#[oso_error_code(0x0201)]
 	CustomStatus,
+	#[oso_error_code(0x0202)]
 	ErrorStatus(&'static str,),
+	#[oso_error_code(0x0203)]
 	Custom(&'static str,),
 }
 
+/// error of strict ucs-2 conversion
+///
+/// offsets count code units for ucs-2 input and bytes for `str` input
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum Ucs2Error {
+	/// code units do not end with null
+	#[default]
+	#[oso_error_code(0x0301)]
+	MissingNul,
+	/// null found before the end
+	#[oso_error_code(0x0302)]
+	InteriorNul(usize,),
+	/// surrogate code unit, which ucs-2 does not have
+	#[oso_error_code(0x0303)]
+	Surrogate(usize,),
+	/// character outside of the basic multilingual plane
+	#[oso_error_code(0x0304)]
+	Unrepresentable(usize,),
+}
+
+/// error of applying a device tree overlay
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum OverlayError {
+	/// the base tree or the overlay is not a valid blob
+	#[default]
+	#[oso_error_code(0x0401)]
+	Malformed,
+	/// a fragment has neither `target` nor `target-path`
+	#[oso_error_code(0x0402)]
+	MissingTarget,
+	/// the base tree has no node a fragment targets
+	#[oso_error_code(0x0403)]
+	TargetNotFound,
+	/// `__fixups__` names a label missing from `__symbols__` of the base tree
+	#[oso_error_code(0x0404)]
+	UnresolvedSymbol,
+	/// a fixup points outside of the properties of the overlay
+	#[oso_error_code(0x0405)]
+	InvalidFixup,
+}
+
 impl From<OsoError<UefiError,>,> for OsoError<(),> {
 	fn from(value: OsoError<UefiError,>,) -> Self {
 		OsoError { from: value.from, desc: Some((),), }
 	}
 }
+
+/// step of the boot process which failed
+///
+/// the loader uses this to pick a message and a suggested fix for the error
+/// screen
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq,)]
+pub enum BootStage {
+	#[default]
+	Init,
+	Config,
+	LoaderImage,
+	KernelOpen,
+	KernelRead,
+	KernelParse,
+	KernelVersion,
+	KernelLoad,
+	DeviceTree,
+	Overlay,
+	Initrd,
+	Handoff,
+	MemoryMap,
+}
+
+/// error descriptor of the loader application
+///
+/// `cause` keeps the uefi status description (or parse error name) of the
+/// underlying error, which would otherwise be lost when errors of different
+/// descriptor types are merged. `short_read` records how much of a file was
+/// read when it ended early. `aborted` is set when the user cancelled the
+/// stage, which returns to the boot menu instead of reporting an error
+#[derive(Debug, Default,)]
+pub struct BootError {
+	pub stage:      BootStage,
+	pub cause:      Option<&'static str,>,
+	pub short_read: Option<ShortRead,>,
+	pub aborted:    bool,
+}
+
+/// bytes read from a file and bytes it was expected to have
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq,)]
+pub struct ShortRead {
+	pub read:     usize,
+	pub expected: usize,
+}
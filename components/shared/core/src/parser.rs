@@ -1,5 +1,214 @@
-#[derive(Debug, Default,)]
+use oso_proc_macro::ErrorCode;
+
+#[derive(Debug, Default, ErrorCode,)]
 pub enum ParserError {
 	#[default]
+	#[oso_error_code(0x0501)]
 	Dummy,
 }
+
+/// error of the loader configuration parser
+///
+/// every variant except `Unknown` carries 1-based line number where the error
+/// is detected
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum ConfigError {
+	/// section header is not `[name]`
+	#[oso_error_code(0x0601)]
+	InvalidSection(usize,),
+	/// key contains characters other than `A-Za-z0-9_-`
+	#[oso_error_code(0x0602)]
+	InvalidKey(usize,),
+	/// line is neither blank, comment, section nor `key = value`
+	#[oso_error_code(0x0603)]
+	MissingEquals(usize,),
+	/// value is not a string, integer or boolean
+	#[oso_error_code(0x0604)]
+	InvalidValue(usize,),
+	#[oso_error_code(0x0605)]
+	UnterminatedString(usize,),
+	/// basic strings with escape sequences are not supported. use literal
+	/// strings (`'...'`) instead
+	#[oso_error_code(0x0606)]
+	UnsupportedEscape(usize,),
+	/// characters other than a comment follow a value or section header
+	#[oso_error_code(0x0607)]
+	TrailingCharacters(usize,),
+	#[oso_error_code(0x0608)]
+	DuplicateKey(usize,),
+	#[oso_error_code(0x0609)]
+	DuplicateSection(usize,),
+	/// value has a different type than expected by the consumer
+	#[oso_error_code(0x060a)]
+	TypeMismatch(usize,),
+	#[default]
+	#[oso_error_code(0x060b)]
+	Unknown,
+}
+
+impl ConfigError {
+	pub fn line(&self,) -> Option<usize,> {
+		match self {
+			Self::InvalidSection(l,)
+			| Self::InvalidKey(l,)
+			| Self::MissingEquals(l,)
+			| Self::InvalidValue(l,)
+			| Self::UnterminatedString(l,)
+			| Self::UnsupportedEscape(l,)
+			| Self::TrailingCharacters(l,)
+			| Self::DuplicateKey(l,)
+			| Self::DuplicateSection(l,)
+			| Self::TypeMismatch(l,) => Some(*l,),
+			Self::Unknown => None,
+		}
+	}
+}
+
+/// error of strict utf-8 decoding
+///
+/// `offset` is where the first invalid sequence starts. `len` is the length of
+/// the invalid sequence, or `None` if the input ends in the middle of a
+/// sequence
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq,)]
+pub struct Utf8Error {
+	pub offset: usize,
+	pub len:    Option<usize,>,
+}
+
+/// error of path manipulation
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum PathError {
+	/// result does not fit a buffer of `capacity` bytes
+	#[oso_error_code(0x0701)]
+	TooLong {
+		capacity: usize,
+	},
+	/// path contains NUL, which firmware and C strings read as its end
+	#[default]
+	#[oso_error_code(0x0702)]
+	Nul,
+}
+
+/// error of shell scripts
+///
+/// every variant carries the 1-based line number where the error is detected
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum ScriptError {
+	/// `if` is not followed by `name`, `!name`, `name == value` or
+	/// `name != value`
+	#[oso_error_code(0x0801)]
+	InvalidCondition(usize,),
+	/// `else` outside of an `if` block, or a second `else` in one block
+	#[oso_error_code(0x0802)]
+	UnexpectedElse(usize,),
+	/// `end` without an open `if` block
+	#[oso_error_code(0x0803)]
+	UnexpectedEnd(usize,),
+	/// the script ends inside the `if` block opened at the line
+	#[oso_error_code(0x0804)]
+	UnterminatedIf(usize,),
+	/// `if` blocks are nested deeper than the parser keeps track of
+	#[oso_error_code(0x0805)]
+	TooDeep(usize,),
+	#[default]
+	#[oso_error_code(0x0806)]
+	Unknown,
+}
+
+impl ScriptError {
+	pub fn line(&self,) -> Option<usize,> {
+		match self {
+			Self::InvalidCondition(l,)
+			| Self::UnexpectedElse(l,)
+			| Self::UnexpectedEnd(l,)
+			| Self::UnterminatedIf(l,)
+			| Self::TooDeep(l,) => Some(*l,),
+			Self::Unknown => None,
+		}
+	}
+}
+
+/// error of input recordings
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum ReplayError {
+	/// no hex encoded recording is between the markers of the text
+	#[default]
+	#[oso_error_code(0x0901)]
+	NotFound,
+	/// begin marker without an end marker, or the recording ends before its
+	/// checksum
+	#[oso_error_code(0x0902)]
+	Truncated,
+	/// hex digits are odd in number or not hex
+	#[oso_error_code(0x0903)]
+	InvalidHex,
+	/// buffer is too small for the recording of `len` bytes
+	#[oso_error_code(0x0904)]
+	TooLarge {
+		len: usize,
+	},
+	/// recording does not start with the magic
+	#[oso_error_code(0x0905)]
+	BadMagic,
+	#[oso_error_code(0x0906)]
+	UnsupportedVersion(u8,),
+	/// record tag at the byte offset is not known
+	#[oso_error_code(0x0907)]
+	UnknownTag(usize,),
+	#[oso_error_code(0x0908)]
+	BadChecksum,
+}
+
+/// error of a resource read from a device tree node
+///
+/// every variant except `Unknown` carries the offset of the node, which
+/// `Fdt::node_at` finds it by, and the property which is missing or
+/// malformed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ErrorCode,)]
+pub enum DtResourceError {
+	/// the node has no such property
+	#[oso_error_code(0x2301)]
+	Missing {
+		node:     usize,
+		property: &'static str,
+	},
+	/// the length of the property is not a multiple of its entries, a cell
+	/// count is out of range or a phandle refers to no node
+	#[oso_error_code(0x2302)]
+	Malformed {
+		node:     usize,
+		property: &'static str,
+	},
+	/// the property has no entry `index`
+	#[oso_error_code(0x2303)]
+	OutOfRange {
+		node:     usize,
+		property: &'static str,
+		index:    usize,
+	},
+	/// no entry of `ranges` of the bus node holds the address
+	#[oso_error_code(0x2304)]
+	Untranslatable {
+		node:     usize,
+		property: &'static str,
+	},
+	/// the node is deeper than the tree is walked, or not part of the tree
+	#[default]
+	#[oso_error_code(0x2305)]
+	Unknown,
+}
+
+impl DtResourceError {
+	/// `(node, property)` the error points at
+	pub fn source(&self,) -> Option<(usize, &'static str,),> {
+		match self {
+			Self::Missing { node, property, }
+			| Self::Malformed { node, property, }
+			| Self::OutOfRange { node, property, .. }
+			| Self::Untranslatable { node, property, } => {
+				Some((*node, *property,),)
+			},
+			Self::Unknown => None,
+		}
+	}
+}
@@ -0,0 +1,819 @@
+//! # Error Code Registry
+//!
+//! Every error code of the workspace, generated by `cargo xtask error-codes`
+//! from the `#[oso_error_code(..)]` of each variant. Do not edit
+
+/// Variant of an error enum and its code
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct Entry {
+	pub code: u16,
+	/// path of the variant, starting with its package name
+	pub name: &'static str,
+	/// documentation of the variant, joined into one line
+	pub doc:  &'static str,
+}
+
+/// Entry of `code`
+pub fn lookup(code: u16,) -> Option<&'static Entry,> {
+	let i = ENTRIES.binary_search_by_key(&code, |entry| entry.code,).ok()?;
+	Some(&ENTRIES[i],)
+}
+
+/// Every entry, sorted by code
+pub const ENTRIES: &[Entry] = &[
+	Entry {
+		code: 0x0101,
+		name: "oso_error::loader::EfiParseError::EndOfBinary",
+		doc:  "",
+	},
+	Entry {
+		code: 0x0102,
+		name: "oso_error::loader::EfiParseError::SizeOverflow",
+		doc:  "",
+	},
+	Entry {
+		code: 0x0103,
+		name: "oso_error::loader::EfiParseError::UnknownEfiType",
+		doc:  "",
+	},
+	Entry {
+		code: 0x0104,
+		name: "oso_error::loader::EfiParseError::InvalidIdentLen",
+		doc:  "",
+	},
+	Entry {
+		code: 0x0105,
+		name: "oso_error::loader::EfiParseError::BadMagicNumber",
+		doc:  "",
+	},
+	Entry {
+		code: 0x0106,
+		name: "oso_error::loader::EfiParseError::InvalidFileClass",
+		doc:  "",
+	},
+	Entry {
+		code: 0x0107,
+		name: "oso_error::loader::EfiParseError::OsAbiOutOfSupport",
+		doc:  "",
+	},
+	Entry {
+		code: 0x0108,
+		name: "oso_error::loader::EfiParseError::DelimiterNotFound",
+		doc:  "string context",
+	},
+	Entry {
+		code: 0x0109,
+		name: "oso_error::loader::EfiParseError::InvalidUtf8",
+		doc:  "string at `offset` of a string table is not valid utf-8",
+	},
+	Entry {
+		code: 0x010a,
+		name: "oso_error::loader::EfiParseError::TooManySymbolsOffset",
+		doc:  "",
+	},
+	Entry {
+		code: 0x010b,
+		name: "oso_error::loader::EfiParseError::InvalidEndianFlag",
+		doc:  "",
+	},
+	Entry {
+		code: 0x010d,
+		name: "oso_error::loader::EfiParseError::InvalidGnuHash",
+		doc:  "",
+	},
+	Entry {
+		code: 0x010e,
+		name: "oso_error::loader::EfiParseError::Unknown",
+		doc:  "",
+	},
+	Entry {
+		code: 0x010f,
+		name: "oso_error::loader::EfiParseError::UnsupportedRelocation",
+		doc:  "relocation type the loader can not apply for the machine",
+	},
+	Entry {
+		code: 0x0110,
+		name: "oso_error::loader::EfiParseError::RelocationOutOfImage",
+		doc:  "relocation patches an address outside of the loaded segments",
+	},
+	Entry {
+		code: 0x0111,
+		name: "oso_error::loader::EfiParseError::UndefinedSymbol",
+		doc:  "relocation refers to a symbol the kernel does not define",
+	},
+	Entry {
+		code: 0x0201,
+		name: "oso_error::loader::UefiError::CustomStatus",
+		doc:  "",
+	},
+	Entry {
+		code: 0x0202,
+		name: "oso_error::loader::UefiError::ErrorStatus",
+		doc:  "",
+	},
+	Entry {
+		code: 0x0203,
+		name: "oso_error::loader::UefiError::Custom",
+		doc:  "",
+	},
+	Entry {
+		code: 0x0301,
+		name: "oso_error::loader::Ucs2Error::MissingNul",
+		doc:  "code units do not end with null",
+	},
+	Entry {
+		code: 0x0302,
+		name: "oso_error::loader::Ucs2Error::InteriorNul",
+		doc:  "null found before the end",
+	},
+	Entry {
+		code: 0x0303,
+		name: "oso_error::loader::Ucs2Error::Surrogate",
+		doc:  "surrogate code unit, which ucs-2 does not have",
+	},
+	Entry {
+		code: 0x0304,
+		name: "oso_error::loader::Ucs2Error::Unrepresentable",
+		doc:  "character outside of the basic multilingual plane",
+	},
+	Entry {
+		code: 0x0401,
+		name: "oso_error::loader::OverlayError::Malformed",
+		doc:  "the base tree or the overlay is not a valid blob",
+	},
+	Entry {
+		code: 0x0402,
+		name: "oso_error::loader::OverlayError::MissingTarget",
+		doc:  "a fragment has neither `target` nor `target-path`",
+	},
+	Entry {
+		code: 0x0403,
+		name: "oso_error::loader::OverlayError::TargetNotFound",
+		doc:  "the base tree has no node a fragment targets",
+	},
+	Entry {
+		code: 0x0404,
+		name: "oso_error::loader::OverlayError::UnresolvedSymbol",
+		doc:  "`__fixups__` names a label missing from `__symbols__` of the base tree",
+	},
+	Entry {
+		code: 0x0405,
+		name: "oso_error::loader::OverlayError::InvalidFixup",
+		doc:  "a fixup points outside of the properties of the overlay",
+	},
+	Entry {
+		code: 0x0501,
+		name: "oso_error::parser::ParserError::Dummy",
+		doc:  "",
+	},
+	Entry {
+		code: 0x0601,
+		name: "oso_error::parser::ConfigError::InvalidSection",
+		doc:  "section header is not `[name]`",
+	},
+	Entry {
+		code: 0x0602,
+		name: "oso_error::parser::ConfigError::InvalidKey",
+		doc:  "key contains characters other than `A-Za-z0-9_-`",
+	},
+	Entry {
+		code: 0x0603,
+		name: "oso_error::parser::ConfigError::MissingEquals",
+		doc:  "line is neither blank, comment, section nor `key = value`",
+	},
+	Entry {
+		code: 0x0604,
+		name: "oso_error::parser::ConfigError::InvalidValue",
+		doc:  "value is not a string, integer or boolean",
+	},
+	Entry {
+		code: 0x0605,
+		name: "oso_error::parser::ConfigError::UnterminatedString",
+		doc:  "",
+	},
+	Entry {
+		code: 0x0606,
+		name: "oso_error::parser::ConfigError::UnsupportedEscape",
+		doc:  "basic strings with escape sequences are not supported. use literal strings (`'...'`) instead",
+	},
+	Entry {
+		code: 0x0607,
+		name: "oso_error::parser::ConfigError::TrailingCharacters",
+		doc:  "characters other than a comment follow a value or section header",
+	},
+	Entry {
+		code: 0x0608,
+		name: "oso_error::parser::ConfigError::DuplicateKey",
+		doc:  "",
+	},
+	Entry {
+		code: 0x0609,
+		name: "oso_error::parser::ConfigError::DuplicateSection",
+		doc:  "",
+	},
+	Entry {
+		code: 0x060a,
+		name: "oso_error::parser::ConfigError::TypeMismatch",
+		doc:  "value has a different type than expected by the consumer",
+	},
+	Entry {
+		code: 0x060b,
+		name: "oso_error::parser::ConfigError::Unknown",
+		doc:  "",
+	},
+	Entry {
+		code: 0x0701,
+		name: "oso_error::parser::PathError::TooLong",
+		doc:  "result does not fit a buffer of `capacity` bytes",
+	},
+	Entry {
+		code: 0x0702,
+		name: "oso_error::parser::PathError::Nul",
+		doc:  "path contains NUL, which firmware and C strings read as its end",
+	},
+	Entry {
+		code: 0x0801,
+		name: "oso_error::parser::ScriptError::InvalidCondition",
+		doc:  "`if` is not followed by `name`, `!name`, `name == value` or `name != value`",
+	},
+	Entry {
+		code: 0x0802,
+		name: "oso_error::parser::ScriptError::UnexpectedElse",
+		doc:  "`else` outside of an `if` block, or a second `else` in one block",
+	},
+	Entry {
+		code: 0x0803,
+		name: "oso_error::parser::ScriptError::UnexpectedEnd",
+		doc:  "`end` without an open `if` block",
+	},
+	Entry {
+		code: 0x0804,
+		name: "oso_error::parser::ScriptError::UnterminatedIf",
+		doc:  "the script ends inside the `if` block opened at the line",
+	},
+	Entry {
+		code: 0x0805,
+		name: "oso_error::parser::ScriptError::TooDeep",
+		doc:  "`if` blocks are nested deeper than the parser keeps track of",
+	},
+	Entry {
+		code: 0x0806,
+		name: "oso_error::parser::ScriptError::Unknown",
+		doc:  "",
+	},
+	Entry {
+		code: 0x0901,
+		name: "oso_error::parser::ReplayError::NotFound",
+		doc:  "no hex encoded recording is between the markers of the text",
+	},
+	Entry {
+		code: 0x0902,
+		name: "oso_error::parser::ReplayError::Truncated",
+		doc:  "begin marker without an end marker, or the recording ends before its checksum",
+	},
+	Entry {
+		code: 0x0903,
+		name: "oso_error::parser::ReplayError::InvalidHex",
+		doc:  "hex digits are odd in number or not hex",
+	},
+	Entry {
+		code: 0x0904,
+		name: "oso_error::parser::ReplayError::TooLarge",
+		doc:  "buffer is too small for the recording of `len` bytes",
+	},
+	Entry {
+		code: 0x0905,
+		name: "oso_error::parser::ReplayError::BadMagic",
+		doc:  "recording does not start with the magic",
+	},
+	Entry {
+		code: 0x0906,
+		name: "oso_error::parser::ReplayError::UnsupportedVersion",
+		doc:  "",
+	},
+	Entry {
+		code: 0x0907,
+		name: "oso_error::parser::ReplayError::UnknownTag",
+		doc:  "record tag at the byte offset is not known",
+	},
+	Entry {
+		code: 0x0908,
+		name: "oso_error::parser::ReplayError::BadChecksum",
+		doc:  "",
+	},
+	Entry {
+		code: 0x0a01,
+		name: "oso_error::kernel::GraphicError::InvalidCoordinate",
+		doc:  "",
+	},
+	Entry {
+		code: 0x0a02,
+		name: "oso_error::kernel::GraphicError::NoFramebuffer",
+		doc:  "boot information has no framebuffer to claim",
+	},
+	Entry {
+		code: 0x0a03,
+		name: "oso_error::kernel::GraphicError::AlreadyClaimed",
+		doc:  "the boot framebuffer is claimed already",
+	},
+	Entry {
+		code: 0x0a04,
+		name: "oso_error::kernel::GraphicError::NotReserved",
+		doc:  "the memory map hands out part of the framebuffer as usable memory",
+	},
+	Entry {
+		code: 0x0a05,
+		name: "oso_error::kernel::GraphicError::Paging",
+		doc:  "the framebuffer could not be mapped",
+	},
+	Entry {
+		code: 0x0a06,
+		name: "oso_error::kernel::GraphicError::Usage",
+		doc:  "shell command has unknown or missing arguments",
+	},
+	Entry {
+		code: 0x0a07,
+		name: "oso_error::kernel::GraphicError::Unreadable",
+		doc:  "pixel format of the framebuffer can not be read back",
+	},
+	Entry {
+		code: 0x0a08,
+		name: "oso_error::kernel::GraphicError::Vfs",
+		doc:  "screen capture could not be written to a file",
+	},
+	Entry {
+		code: 0x0b01,
+		name: "oso_error::kernel::DmaError::Exhausted",
+		doc:  "allocation exceeds the budget of the pool. drivers should wait for buffers in flight to complete before retrying",
+	},
+	Entry {
+		code: 0x0b02,
+		name: "oso_error::kernel::DmaError::OutOfFrames",
+		doc:  "frame allocator has no contiguous range left",
+	},
+	Entry {
+		code: 0x0b03,
+		name: "oso_error::kernel::DmaError::OutOfLowFrames",
+		doc:  "frame allocator has no contiguous range ending at or below `limit`",
+	},
+	Entry {
+		code: 0x0c01,
+		name: "oso_error::kernel::WatchdogError::TimeoutOutOfRange",
+		doc:  "timeout can not be counted by the device",
+	},
+	Entry {
+		code: 0x0c02,
+		name: "oso_error::kernel::WatchdogError::Usage",
+		doc:  "shell command has unknown or missing arguments",
+	},
+	Entry {
+		code: 0x0c03,
+		name: "oso_error::kernel::WatchdogError::NoDevice",
+		doc:  "no watchdog was installed",
+	},
+	Entry {
+		code: 0x0d01,
+		name: "oso_error::kernel::BootProtocolError::BadMagic",
+		doc:  "boot loader passed an unexpected magic value",
+	},
+	Entry {
+		code: 0x0d02,
+		name: "oso_error::kernel::BootProtocolError::Malformed",
+		doc:  "a structure is truncated or overruns its container",
+	},
+	Entry {
+		code: 0x0d03,
+		name: "oso_error::kernel::BootProtocolError::UnsupportedRevision",
+		doc:  "boot loader does not support the protocol revision the kernel asked for",
+	},
+	Entry {
+		code: 0x0d04,
+		name: "oso_error::kernel::BootProtocolError::MissingResponse",
+		doc:  "boot loader did not answer a required request",
+	},
+	Entry {
+		code: 0x0d05,
+		name: "oso_error::kernel::BootProtocolError::TooManyEntries",
+		doc:  "more entries than the kernel reserved room for",
+	},
+	Entry {
+		code: 0x0d06,
+		name: "oso_error::kernel::BootProtocolError::AlreadyBuilt",
+		doc:  "boot information can only be built once",
+	},
+	Entry {
+		code: 0x0e01,
+		name: "oso_error::kernel::GpioError::PinOutOfRange",
+		doc:  "controller has no pin with this number",
+	},
+	Entry {
+		code: 0x0e02,
+		name: "oso_error::kernel::GpioError::NoController",
+		doc:  "`gpio::set` was called before a controller was installed",
+	},
+	Entry {
+		code: 0x0f01,
+		name: "oso_error::kernel::BlockError::OutOfRange",
+		doc:  "request reaches past the last block of the device",
+	},
+	Entry {
+		code: 0x0f02,
+		name: "oso_error::kernel::BlockError::Misaligned",
+		doc:  "buffer length is not a multiple of the block size",
+	},
+	Entry {
+		code: 0x0f03,
+		name: "oso_error::kernel::BlockError::NoMedia",
+		doc:  "no card in the slot, or the card did not answer initialization",
+	},
+	Entry {
+		code: 0x0f04,
+		name: "oso_error::kernel::BlockError::Unsupported",
+		doc:  "card needs a feature the driver does not implement",
+	},
+	Entry {
+		code: 0x0f05,
+		name: "oso_error::kernel::BlockError::Timeout",
+		doc:  "device did not finish a command in time",
+	},
+	Entry {
+		code: 0x0f06,
+		name: "oso_error::kernel::BlockError::OutOfFrames",
+		doc:  "frame allocator has no frames left for the block cache",
+	},
+	Entry {
+		code: 0x0f07,
+		name: "oso_error::kernel::BlockError::Device",
+		doc:  "controller reported an error. raw error status of the device",
+	},
+	Entry {
+		code: 0x1001,
+		name: "oso_error::kernel::VfsError::NotFound",
+		doc:  "no file or directory at the path",
+	},
+	Entry {
+		code: 0x1002,
+		name: "oso_error::kernel::VfsError::InvalidPath",
+		doc:  "path is relative or longer than the kernel reserved room for",
+	},
+	Entry {
+		code: 0x1003,
+		name: "oso_error::kernel::VfsError::NotADirectory",
+		doc:  "a component of the path other than the last is not a directory",
+	},
+	Entry {
+		code: 0x1004,
+		name: "oso_error::kernel::VfsError::IsADirectory",
+		doc:  "file operation on a directory",
+	},
+	Entry {
+		code: 0x1005,
+		name: "oso_error::kernel::VfsError::ReadOnly",
+		doc:  "file system or device does not support writing",
+	},
+	Entry {
+		code: 0x1006,
+		name: "oso_error::kernel::VfsError::InvalidSeek",
+		doc:  "seek before the start of the file",
+	},
+	Entry {
+		code: 0x1007,
+		name: "oso_error::kernel::VfsError::AlreadyMounted",
+		doc:  "another file system is mounted at the path",
+	},
+	Entry {
+		code: 0x1008,
+		name: "oso_error::kernel::VfsError::TooManyEntries",
+		doc:  "more mounts or devices than the kernel reserved room for",
+	},
+	Entry {
+		code: 0x1009,
+		name: "oso_error::kernel::VfsError::Corrupt",
+		doc:  "on-disk structures are inconsistent",
+	},
+	Entry {
+		code: 0x100a,
+		name: "oso_error::kernel::VfsError::Unsupported",
+		doc:  "file system uses a feature the driver does not implement",
+	},
+	Entry {
+		code: 0x100b,
+		name: "oso_error::kernel::VfsError::Device",
+		doc:  "block device below the file system failed",
+	},
+	Entry {
+		code: 0x1101,
+		name: "oso_error::kernel::EnvError::NotFound",
+		doc:  "no entry has the name",
+	},
+	Entry {
+		code: 0x1102,
+		name: "oso_error::kernel::EnvError::BufferTooSmall",
+		doc:  "value does not fit the buffer of the caller",
+	},
+	Entry {
+		code: 0x1103,
+		name: "oso_error::kernel::EnvError::AlreadyInitialized",
+		doc:  "the environment was already assembled",
+	},
+	Entry {
+		code: 0x1104,
+		name: "oso_error::kernel::EnvError::TooManyEntries",
+		doc:  "boot information has more entries than the kernel reserved room for",
+	},
+	Entry {
+		code: 0x1105,
+		name: "oso_error::kernel::EnvError::Usage",
+		doc:  "shell command was called with wrong arguments",
+	},
+	Entry {
+		code: 0x1201,
+		name: "oso_error::kernel::ExecutorError::AlreadyExists",
+		doc:  "another executor is running. wakers are global, so there is one",
+	},
+	Entry {
+		code: 0x1202,
+		name: "oso_error::kernel::ExecutorError::TooManyTasks",
+		doc:  "every task slot is in use",
+	},
+	Entry {
+		code: 0x1301,
+		name: "oso_error::kernel::IrqStatsError::Usage",
+		doc:  "shell command has unknown or missing arguments",
+	},
+	Entry {
+		code: 0x1401,
+		name: "oso_error::kernel::SettingsError::Unavailable",
+		doc:  "the loader did not hand over runtime services",
+	},
+	Entry {
+		code: 0x1402,
+		name: "oso_error::kernel::SettingsError::NotFound",
+		doc:  "no setting of the name, or it was never stored",
+	},
+	Entry {
+		code: 0x1403,
+		name: "oso_error::kernel::SettingsError::Corrupt",
+		doc:  "stored value fails its checksum or is not UTF-8",
+	},
+	Entry {
+		code: 0x1404,
+		name: "oso_error::kernel::SettingsError::InvalidValue",
+		doc:  "value is not accepted by the setting",
+	},
+	Entry {
+		code: 0x1405,
+		name: "oso_error::kernel::SettingsError::TooLong",
+		doc:  "value is longer than settings keep",
+	},
+	Entry {
+		code: 0x1406,
+		name: "oso_error::kernel::SettingsError::RateLimited",
+		doc:  "write budget of this boot is used up",
+	},
+	Entry {
+		code: 0x1407,
+		name: "oso_error::kernel::SettingsError::Firmware",
+		doc:  "firmware returned an error status",
+	},
+	Entry {
+		code: 0x1408,
+		name: "oso_error::kernel::SettingsError::Usage",
+		doc:  "shell command has unknown or missing arguments",
+	},
+	Entry {
+		code: 0x1501,
+		name: "oso_error::kernel::EfiError::Unavailable",
+		doc:  "the loader did not hand over runtime services",
+	},
+	Entry {
+		code: 0x1502,
+		name: "oso_error::kernel::EfiError::Unsupported",
+		doc:  "firmware does not support the service after boot",
+	},
+	Entry {
+		code: 0x1503,
+		name: "oso_error::kernel::EfiError::NotFound",
+		doc:  "no variable of the name",
+	},
+	Entry {
+		code: 0x1504,
+		name: "oso_error::kernel::EfiError::BufferTooSmall",
+		doc:  "buffer can not hold the data of the variable",
+	},
+	Entry {
+		code: 0x1505,
+		name: "oso_error::kernel::EfiError::InvalidParameter",
+		doc:  "argument is out of range, e.g. a time on the 32nd of a month",
+	},
+	Entry {
+		code: 0x1506,
+		name: "oso_error::kernel::EfiError::Firmware",
+		doc:  "firmware returned an error status",
+	},
+	Entry {
+		code: 0x1601,
+		name: "oso_error::kernel::SchedError::NotFound",
+		doc:  "no task has the ID",
+	},
+	Entry {
+		code: 0x1602,
+		name: "oso_error::kernel::SchedError::TooManyTasks",
+		doc:  "every task slot is in use",
+	},
+	Entry {
+		code: 0x1603,
+		name: "oso_error::kernel::SchedError::InvalidPriority",
+		doc:  "priority is not below the number of levels",
+	},
+	Entry {
+		code: 0x1604,
+		name: "oso_error::kernel::SchedError::StackTooSmall",
+		doc:  "stack can not hold the initial context of a task",
+	},
+	Entry {
+		code: 0x1605,
+		name: "oso_error::kernel::SchedError::Usage",
+		doc:  "shell command has unknown or missing arguments",
+	},
+	Entry {
+		code: 0x1701,
+		name: "oso_error::kernel::IdleError::Usage",
+		doc:  "shell command has unknown or missing arguments",
+	},
+	Entry {
+		code: 0x1801,
+		name: "oso_error::kernel::CpuError::Usage",
+		doc:  "shell command has unknown or missing arguments",
+	},
+	Entry {
+		code: 0x1901,
+		name: "oso_error::kernel::SpinError::Usage",
+		doc:  "shell command has unknown or missing arguments",
+	},
+	Entry {
+		code: 0x1a01,
+		name: "oso_error::kernel::DtError::Usage",
+		doc:  "shell command has unknown or missing arguments",
+	},
+	Entry {
+		code: 0x1a02,
+		name: "oso_error::kernel::DtError::NoDeviceTree",
+		doc:  "the boot loader handed over no device tree",
+	},
+	Entry {
+		code: 0x1a03,
+		name: "oso_error::kernel::DtError::NodeNotFound",
+		doc:  "no node has the path",
+	},
+	Entry {
+		code: 0x1a04,
+		name: "oso_error::kernel::DtError::PropertyNotFound",
+		doc:  "the node has no property of the name",
+	},
+	Entry {
+		code: 0x1a05,
+		name: "oso_error::kernel::DtError::Resource",
+		doc:  "a resource of the node could not be read",
+	},
+	Entry {
+		code: 0x1b01,
+		name: "oso_error::kernel::HandoffError::Usage",
+		doc:  "shell command has unknown or missing arguments",
+	},
+	Entry {
+		code: 0x1b02,
+		name: "oso_error::kernel::HandoffError::NoBootInfo",
+		doc:  "the kernel was entered without boot information",
+	},
+	Entry {
+		code: 0x1c01,
+		name: "oso_error::kernel::PagingError::Empty",
+		doc:  "range has no bytes",
+	},
+	Entry {
+		code: 0x1c02,
+		name: "oso_error::kernel::PagingError::Conflict",
+		doc:  "part of the range is mapped with the attribute `existing` already. mapping memory with two attributes is undefined",
+	},
+	Entry {
+		code: 0x1c03,
+		name: "oso_error::kernel::PagingError::TableFull",
+		doc:  "every slot of the mapping table is taken",
+	},
+	Entry {
+		code: 0x1c04,
+		name: "oso_error::kernel::PagingError::OutOfRange",
+		doc:  "the last page of the range ends past the address space",
+	},
+	Entry {
+		code: 0x1d01,
+		name: "oso_error::kernel::TraceError::Usage",
+		doc:  "shell command has unknown or missing arguments",
+	},
+	Entry {
+		code: 0x1e01,
+		name: "oso_error::kernel::RecordError::Usage",
+		doc:  "shell command has unknown or missing arguments",
+	},
+	Entry {
+		code: 0x1f01,
+		name: "oso_error::kernel::VtError::Usage",
+		doc:  "shell command has unknown or missing arguments",
+	},
+	Entry {
+		code: 0x1f02,
+		name: "oso_error::kernel::VtError::UnknownTerminal",
+		doc:  "no terminal has the name or number",
+	},
+	Entry {
+		code: 0x1f03,
+		name: "oso_error::kernel::VtError::Busy",
+		doc:  "a terminal is being written",
+	},
+	Entry {
+		code: 0x2001,
+		name: "oso_error::kernel::ShellError::UnknownCommand",
+		doc:  "no command has the name",
+	},
+	Entry {
+		code: 0x2002,
+		name: "oso_error::kernel::ShellError::CommandFailed",
+		doc:  "command ran and reported an error",
+	},
+	Entry {
+		code: 0x2003,
+		name: "oso_error::kernel::ShellError::TooManyArgs",
+		doc:  "command line has more words than the shell splits",
+	},
+	Entry {
+		code: 0x2004,
+		name: "oso_error::kernel::ShellError::Aborted",
+		doc:  "script stopped at the line, whose command is unknown or failed",
+	},
+	Entry {
+		code: 0x2005,
+		name: "oso_error::kernel::ShellError::Script",
+		doc:  "script is malformed",
+	},
+	Entry {
+		code: 0x2006,
+		name: "oso_error::kernel::ShellError::TooLarge",
+		doc:  "script is longer than the buffer it is read into",
+	},
+	Entry {
+		code: 0x2007,
+		name: "oso_error::kernel::ShellError::NotUtf8",
+		doc:  "script is not UTF-8",
+	},
+	Entry {
+		code: 0x2008,
+		name: "oso_error::kernel::ShellError::Vfs",
+		doc:  "script could not be read",
+	},
+	Entry {
+		code: 0x2101,
+		name: "oso_error::kernel::BootProfileError::Usage",
+		doc:  "shell command has unknown or missing arguments",
+	},
+	Entry {
+		code: 0x2201,
+		name: "oso_error::kernel::MmioFaultError::Usage",
+		doc:  "shell command has unknown or missing arguments",
+	},
+	Entry {
+		code: 0x2202,
+		name: "oso_error::kernel::MmioFaultError::TooManyRules",
+		doc:  "every rule slot is in use",
+	},
+	Entry {
+		code: 0x2203,
+		name: "oso_error::kernel::MmioFaultError::Disabled",
+		doc:  "the kernel is built without the `fault_injection` feature",
+	},
+	Entry {
+		code: 0x2301,
+		name: "oso_error::parser::DtResourceError::Missing",
+		doc:  "the node has no such property",
+	},
+	Entry {
+		code: 0x2302,
+		name: "oso_error::parser::DtResourceError::Malformed",
+		doc:  "the length of the property is not a multiple of its entries, a cell count is out of range or a phandle refers to no node",
+	},
+	Entry {
+		code: 0x2303,
+		name: "oso_error::parser::DtResourceError::OutOfRange",
+		doc:  "the property has no entry `index`",
+	},
+	Entry {
+		code: 0x2304,
+		name: "oso_error::parser::DtResourceError::Untranslatable",
+		doc:  "no entry of `ranges` of the bus node holds the address",
+	},
+	Entry {
+		code: 0x2305,
+		name: "oso_error::parser::DtResourceError::Unknown",
+		doc:  "the node is deeper than the tree is walked, or not part of the tree",
+	},
+];
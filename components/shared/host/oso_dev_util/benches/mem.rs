@@ -0,0 +1,87 @@
+//! Benchmarks of the framebuffer memory routines of `oso_no_std_shared::mem`
+//! against the per pixel loops they replace
+//!
+//! - `fill/pixels`, `fill/fill32`, `fill/fill64`: Filling a 1920x1080
+//!   screen of 32 bit pixels
+//! - `scroll/rows`, `scroll/copy_rows`: Moving the screen up by a text line
+//!   of 16 pixels, with 64 bytes of padding per scanline
+//!
+//! `scroll/rows` copies with the `memmove` of the host, which is about as
+//! fast as `rep movsb`. The kernel has no such `memmove`, so the gain of
+//! `copy_rows` there is larger than measured here.
+//!
+//! Run through `cargo xtask bench` with the `parsers` suite.
+
+use criterion::Criterion;
+use criterion::Throughput;
+use criterion::criterion_group;
+use criterion::criterion_main;
+use oso_no_std_shared::mem;
+use std::hint::black_box;
+
+const WIDTH: usize = 1920;
+const HEIGHT: usize = 1080;
+/// bytes per scanline
+const STRIDE: usize = WIDTH * 4 + 64;
+/// height of a text line
+const LINE: usize = 16;
+const PIXEL: u32 = 0x00ff_8000;
+
+fn bench_fill(c: &mut Criterion,) {
+	let mut screen = vec![0_u32; WIDTH * HEIGHT];
+	let mut group = c.benchmark_group("fill",);
+	group.throughput(Throughput::Bytes((screen.len() * 4) as u64,),);
+	// the loop of `fill_rectangle` before it used `mem::fill32`
+	group.bench_function("pixels", |b| {
+		b.iter(|| {
+			let bytes = as_bytes(&mut screen,);
+			for pixel in bytes.chunks_exact_mut(4,) {
+				let [red, green, blue, _,] = black_box(PIXEL,).to_le_bytes();
+				pixel[0] = red;
+				pixel[1] = green;
+				pixel[2] = blue;
+			}
+		},)
+	},);
+	group.bench_function("fill32", |b| {
+		b.iter(|| mem::fill32(black_box(&mut screen,), black_box(PIXEL,),),)
+	},);
+	let mut pairs = vec![0_u64; WIDTH * HEIGHT / 2];
+	let pair = (PIXEL as u64) << 32 | PIXEL as u64;
+	group.bench_function("fill64", |b| {
+		b.iter(|| mem::fill64(black_box(&mut pairs,), black_box(pair,),),)
+	},);
+	group.finish();
+}
+
+fn bench_scroll(c: &mut Criterion,) {
+	let mut screen = vec![0x5a_u8; STRIDE * HEIGHT];
+	let rows = HEIGHT - LINE;
+	let mut group = c.benchmark_group("scroll",);
+	group.throughput(Throughput::Bytes((rows * WIDTH * 4) as u64,),);
+	group.bench_function("rows", |b| {
+		b.iter(|| {
+			let buf = black_box(&mut screen,);
+			for row in 0..rows {
+				let from = (row + LINE) * STRIDE;
+				buf.copy_within(from..from + WIDTH * 4, row * STRIDE,);
+			}
+		},)
+	},);
+	group.bench_function("copy_rows", |b| {
+		b.iter(|| {
+			let buf = black_box(&mut screen,);
+			mem::copy_rows(buf, STRIDE, WIDTH * 4, LINE, 0, rows,);
+		},)
+	},);
+	group.finish();
+}
+
+fn as_bytes(pixels: &mut [u32],) -> &mut [u8] {
+	let len = pixels.len() * 4;
+	// SAFETY: `u8` has no alignment requirement and `len` covers `pixels`
+	unsafe { std::slice::from_raw_parts_mut(pixels.as_mut_ptr().cast(), len,) }
+}
+
+criterion_group!(benches, bench_fill, bench_scroll);
+criterion_main!(benches);
@@ -0,0 +1,222 @@
+//! Benchmarks of the parsers used by build tooling
+//!
+//! - `elf/minimal`, `elf/full`: Program headers only, against the whole
+//!   image with sections. The last built kernel is parsed if present,
+//!   otherwise this benchmark binary
+//! - `fat/lookup`, `fat/list`: File lookup and listing in a nested directory
+//! - `font/glyph_table`: Conversion of the console font into glyph bitmaps
+//! - `fdt/parse`, `fdt/walk`, `fdt/find`: The host parser and the kernel's
+//!   walker over a tree shaped like the one of QEMU's `virt` machine
+//! - `glyph/expand`, `glyph/cached_line`: Expansion of one glyph into
+//!   pixels, and a line of text drawn through the kernel's glyph cache
+//!
+//! Framebuffer fills and copies are measured by the `mem` suite.
+//!
+//! Run through `cargo xtask bench`, which stores results under
+//! `target/xtask/bench` and compares them with a saved baseline.
+
+use criterion::Criterion;
+use criterion::criterion_group;
+use criterion::criterion_main;
+use oso_dev_util::decl_manage::crate_::CrateInfo;
+use oso_dev_util::decl_manage::workspace::WorkspaceInfo;
+use oso_dev_util::dtb::DtNode;
+use oso_dev_util::dtb::Dtb;
+use oso_dev_util::elf;
+use oso_dev_util::elf::ElfPatcher;
+use oso_dev_util::fat::FatImage;
+use oso_no_std_shared::bridge::device_tree::Fdt;
+use oso_no_std_shared::text::glyph;
+use oso_no_std_shared::text::glyph::GlyphCache;
+use oso_no_std_shared::text::glyph::GlyphKey;
+use oso_proc_macro_logic::font;
+use std::hint::black_box;
+use std::path::PathBuf;
+
+/// console font, relative to the kernel crate
+const FONT: &str = "resource/sinonome_font.dat";
+
+fn elf_image() -> Option<Vec<u8,>,> {
+	let kernel = oso_dev_util::fs::project_root()
+		.map(|root| root.path().join("target/xtask/oso_kernel.elf",),);
+	let candidates = kernel.into_iter().chain(std::env::current_exe(),);
+	candidates
+		.filter_map(|path| std::fs::read(path,).ok(),)
+		.find(|bytes| elf::program_headers(bytes,).is_ok(),)
+}
+
+fn bench_elf(c: &mut Criterion,) {
+	let Some(image,) = elf_image() else {
+		eprintln!("no 64-bit little-endian elf to parse, skipping elf benches");
+		return;
+	};
+
+	let mut group = c.benchmark_group("elf",);
+	group.bench_function("minimal", |b| {
+		b.iter(|| elf::program_headers(black_box(&image,),).unwrap(),)
+	},);
+	group.bench_function("full", |b| {
+		b.iter(|| ElfPatcher::parse(black_box(image.clone(),),).unwrap(),)
+	},);
+	group.finish();
+}
+
+fn bench_fat(c: &mut Criterion,) {
+	let path: PathBuf = std::env::temp_dir().join("oso_bench_fat.img",);
+	let mut img = FatImage::create(&path, 40 << 20,).unwrap();
+	for i in 0..64 {
+		let data = vec![i as u8; 4096];
+		img.write_file(&format!("EFI/OSO/MODULE{i:02}.BIN"), &data,).unwrap();
+	}
+	img.flush().unwrap();
+
+	let mut group = c.benchmark_group("fat",);
+	group.bench_function("lookup", |b| {
+		b.iter(|| img.read_file(black_box("efi/oso/module63.bin",),).unwrap(),)
+	},);
+	group.bench_function("list", |b| {
+		b.iter(|| img.list(black_box("EFI/OSO",),).unwrap(),)
+	},);
+	group.finish();
+	drop(img,);
+	let _ = std::fs::remove_file(&path,);
+}
+
+fn bench_font(c: &mut Criterion,) {
+	let root = oso_dev_util::fs::project_root().unwrap();
+	let Some(kernel,) = root
+		.members()
+		.into_iter()
+		.find(|member| member.path().join(FONT,).exists(),)
+	else {
+		eprintln!("{FONT} is not in any crate, skipping font benches");
+		return;
+	};
+	// `font` resolves paths against the manifest directory, as when the
+	// `font!` macro expands in the kernel
+	// SAFETY: no other thread reads the environment
+	unsafe { std::env::set_var("CARGO_MANIFEST_DIR", kernel.path(),) };
+
+	let path = syn::LitStr::new(FONT, proc_macro2::Span::call_site(),);
+	c.bench_function("font/glyph_table", |b| {
+		b.iter(|| font::font(black_box(path.clone(),),).unwrap(),)
+	},);
+}
+
+/// node named `name` with `properties`
+fn node(name: &str, properties: &[(&str, &[u8],)],) -> DtNode {
+	let properties = properties
+		.iter()
+		.map(|(name, value,)| (name.to_string(), value.to_vec(),),)
+		.collect();
+	DtNode { name: name.into(), properties, children: vec![] }
+}
+
+/// device tree shaped like the one of QEMU's `virt` machine
+fn virt_dtb() -> Vec<u8,> {
+	let cells = 2u32.to_be_bytes();
+	let mut root = node("", &[
+		("#address-cells", &cells,),
+		("#size-cells", &cells,),
+		("compatible", b"linux,dummy-virt\0",),
+	],);
+
+	let mut cpus = node("cpus", &[("#address-cells", &1u32.to_be_bytes(),),],);
+	for i in 0..4u32 {
+		cpus.children.push(node(&format!("cpu@{i}"), &[
+			("device_type", b"cpu\0",),
+			("compatible", b"arm,cortex-a72\0",),
+			("reg", &i.to_be_bytes(),),
+			("enable-method", b"psci\0",),
+		],),);
+	}
+	root.children.push(cpus,);
+	root.children.push(node("memory@40000000", &[
+		("device_type", b"memory\0",),
+		("reg", &[0, 0, 0, 0x40, 0, 0, 0, 0, 0, 0, 0, 0, 0x8, 0, 0, 0,],),
+	],),);
+	root.children.push(node("intc@8000000", &[
+		("compatible", b"arm,cortex-a15-gic\0",),
+		("interrupt-controller", &[],),
+		("phandle", &1u32.to_be_bytes(),),
+	],),);
+	root.children.push(node("pl011@9000000", &[
+		("compatible", b"arm,pl011\0arm,primecell\0",),
+		("interrupts", &[0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 4,],),
+	],),);
+	for i in 0..32u32 {
+		let address = 0x0a00_0000 + i * 0x200;
+		let irq = 0x10 + i as u8;
+		root.children.push(node(&format!("virtio_mmio@{address:x}"), &[
+			("dma-coherent", &[],),
+			("interrupts", &[0, 0, 0, 0, 0, 0, 0, irq, 0, 0, 0, 1,],),
+			("reg", &address.to_be_bytes(),),
+			("compatible", b"virtio,mmio\0",),
+		],),);
+	}
+	Dtb { root }.to_blob()
+}
+
+fn bench_fdt(c: &mut Criterion,) {
+	let blob = virt_dtb();
+
+	let mut group = c.benchmark_group("fdt",);
+	group.bench_function("parse", |b| {
+		b.iter(|| Dtb::parse(black_box(&blob,),).unwrap(),)
+	},);
+	group.bench_function("walk", |b| {
+		b.iter(|| {
+			let fdt = Fdt::new(black_box(&blob,),).unwrap();
+			let mut len = 0;
+			for node in fdt.nodes() {
+				for (_, value,) in node.properties() {
+					len += value.len();
+				}
+			}
+			len
+		},)
+	},);
+	group.bench_function("find", |b| {
+		let fdt = Fdt::new(&blob,).unwrap();
+		b.iter(|| fdt.find(black_box("/virtio_mmio@a003e00",),).unwrap(),)
+	},);
+	group.finish();
+}
+
+/// line of text as drawn on the console: 80 characters out of 26 glyphs
+fn line() -> Vec<GlyphKey,> {
+	(0..80u32)
+		.map(|i| GlyphKey {
+			code:       'a' as u32 + i % 26,
+			foreground: 0xffff_ffff,
+			background: 0,
+		},)
+		.collect()
+}
+
+/// arbitrary bitmap of `code`
+fn bitmap(code: u32,) -> u128 {
+	(code as u128).wrapping_mul(0x9e37_79b9_7f4a_7c15_f39c_c060_5ced_c835,)
+}
+
+fn bench_glyph(c: &mut Criterion,) {
+	let mut group = c.benchmark_group("glyph",);
+	group.bench_function("expand", |b| {
+		b.iter(|| glyph::expand(black_box(bitmap('a' as u32,),), !0, 0,),)
+	},);
+	let line = line();
+	let mut cache = GlyphCache::<256,>::new(1 << 20,);
+	group.bench_function("cached_line", |b| {
+		b.iter(|| {
+			for key in black_box(&line,) {
+				black_box(cache.get(*key, bitmap(key.code,),),);
+			}
+		},)
+	},);
+	group.finish();
+}
+
+criterion_group!(
+	parsers, bench_elf, bench_fat, bench_font, bench_fdt, bench_glyph
+);
+criterion_main!(parsers);
@@ -0,0 +1,271 @@
+//! # Convention Audit
+//!
+//! Checks conventions of the workspace which neither rustc nor clippy
+//! enforce:
+//!
+//! - Module files and directories are snake case
+//! - Package names are snake case and start with [`CRATE_PREFIX`]
+//! - Crates sit in a group matching their kind, e.g. no host crate under
+//!   `components/shared/no_std`
+//!
+//! Each [`Violation`] comes with a suggested fix where one is obvious.
+//!
+//! ```rust,no_run
+//! use oso_dev_util::audit::audit;
+//! use oso_dev_util_helper::fs::project_root_path;
+//!
+//! for violation in audit(&project_root_path().unwrap(),).unwrap() {
+//!     println!("{violation}");
+//! }
+//! ```
+
+use crate::scaffold::CrateKind;
+use anyhow::Result as Rslt;
+use oso_dev_util_helper::chart::is_no_std;
+use oso_dev_util_helper::chart::is_proc_macro;
+use oso_dev_util_helper::fs::CARGO_MANIFEST;
+use oso_dev_util_helper::fs::all_crates;
+use oso_dev_util_helper::fs::read_toml;
+use oso_dev_util_helper::util::CaseConvert;
+use std::fmt::Display;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Prefix of every package name
+pub const CRATE_PREFIX: &str = "oso_";
+/// Packages exempt from [`CRATE_PREFIX`]
+const PREFIX_EXCEPTIONS: [&str; 1] = ["xtask",];
+/// Directories of a crate holding modules
+const SOURCE_DIRS: [&str; 4] = ["src", "tests", "benches", "examples",];
+/// Groups only `no_std` crates belong to, relative to the project root
+const NO_STD_GROUPS: [&str; 3] =
+	["components/shared/no_std", "components/kernel", "components/loader",];
+
+/// Convention checked by [`audit`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, strum_macros::AsRefStr,)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Rule {
+	/// Module file or directory name is not snake case
+	ModuleName,
+	/// Package name is not snake case or lacks [`CRATE_PREFIX`]
+	CrateName,
+	/// Crate is in a group of another kind
+	Placement,
+}
+
+/// Broken convention
+///
+/// # Fields
+///
+/// * `rule` - Which convention is broken
+/// * `path` - Offending file or crate directory, relative to the root
+/// * `message` - What is wrong
+/// * `suggestion` - How to fix it, if obvious
+#[derive(Clone, Debug, PartialEq, Eq,)]
+pub struct Violation {
+	pub rule:       Rule,
+	pub path:       PathBuf,
+	pub message:    String,
+	pub suggestion: Option<String,>,
+}
+
+impl Display for Violation {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_,>,) -> std::fmt::Result {
+		write!(
+			f,
+			"[{}] {}: {}",
+			self.rule.as_ref(),
+			self.path.display(),
+			self.message
+		)?;
+		if let Some(suggestion,) = &self.suggestion {
+			write!(f, "\n\thelp: {suggestion}")?;
+		}
+		Ok((),)
+	}
+}
+
+/// Audits every crate of the workspace at `root`
+pub fn audit(root: &Path,) -> Rslt<Vec<Violation,>,> {
+	audit_crates(root, &all_crates()?,)
+}
+
+/// Audits `crates` located under `root`
+pub fn audit_crates(
+	root: &Path,
+	crates: &[PathBuf],
+) -> Rslt<Vec<Violation,>,> {
+	let mut violations = vec![];
+	for dir in crates {
+		let rel = dir.strip_prefix(root,).unwrap_or(dir,);
+		for src in SOURCE_DIRS {
+			check_modules(root, &dir.join(src,), &mut violations,)?;
+		}
+
+		let Some(manifest,) = read_toml(dir.join(CARGO_MANIFEST,),) else {
+			continue;
+		};
+		let manifest = manifest?;
+		// virtual manifest of the workspace
+		let Some(name,) = manifest
+			.get("package",)
+			.and_then(|p| p.get("name",),)
+			.and_then(|n| n.as_str(),)
+		else {
+			continue;
+		};
+		violations.extend(check_crate_name(rel, name,),);
+		violations.extend(check_placement(rel, crate_kind(dir, &manifest,),),);
+	}
+	Ok(violations,)
+}
+
+fn check_modules(
+	root: &Path,
+	dir: &Path,
+	violations: &mut Vec<Violation,>,
+) -> Rslt<(),> {
+	let Ok(entries,) = dir.read_dir() else {
+		return Ok((),);
+	};
+	for entry in entries {
+		let path = entry?.path();
+		let is_dir = path.is_dir();
+		if !is_dir && path.extension().is_none_or(|ext| ext != "rs",) {
+			continue;
+		}
+
+		let stem = path
+			.file_stem()
+			.and_then(|s| s.to_str(),)
+			.unwrap_or_default()
+			.to_string();
+		if !stem.is_snake() {
+			let snake: String = stem.to_snake();
+			let renamed = if is_dir { snake } else { format!("{snake}.rs") };
+			violations.push(Violation {
+				rule:       Rule::ModuleName,
+				path:       path.strip_prefix(root,).unwrap_or(&path,).into(),
+				message:    format!("module `{stem}` is not snake case"),
+				suggestion: Some(format!("rename to `{renamed}`"),),
+			},);
+		}
+		if is_dir {
+			check_modules(root, &path, violations,)?;
+		}
+	}
+	Ok((),)
+}
+
+fn check_crate_name(rel: &Path, name: &str,) -> Option<Violation,> {
+	let owned = name.to_string();
+	let snake = owned.is_snake();
+	let prefixed = name.starts_with(CRATE_PREFIX,)
+		|| PREFIX_EXCEPTIONS.contains(&name,);
+	if snake && prefixed {
+		return None;
+	}
+
+	let mut fixed: String = if snake { owned } else { owned.to_snake() };
+	if !prefixed {
+		fixed = format!("{CRATE_PREFIX}{fixed}");
+	}
+	let problem = match (snake, prefixed,) {
+		(false, false,) => {
+			format!("is not snake case nor starts with `{CRATE_PREFIX}`")
+		},
+		(false, true,) => "is not snake case".to_string(),
+		_ => format!("does not start with `{CRATE_PREFIX}`"),
+	};
+	Some(Violation {
+		rule:       Rule::CrateName,
+		path:       rel.to_path_buf(),
+		message:    format!("package `{name}` {problem}"),
+		suggestion: Some(format!("rename package to `{fixed}`"),),
+	},)
+}
+
+fn check_placement(rel: &Path, kind: CrateKind,) -> Option<Violation,> {
+	let in_no_std_group = NO_STD_GROUPS.iter().any(|g| rel.starts_with(g,),);
+	let in_host_group = rel.starts_with(CrateKind::Host.dir(),);
+	let misplaced = match kind {
+		CrateKind::NoStd => in_host_group,
+		CrateKind::Host | CrateKind::ProcMacro => in_no_std_group,
+	};
+	if !misplaced {
+		return None;
+	}
+
+	let name = rel.file_name().unwrap_or_default().display();
+	Some(Violation {
+		rule:       Rule::Placement,
+		path:       rel.to_path_buf(),
+		message:    format!("{kind} crate is in a group of another kind"),
+		suggestion: Some(format!("move to `{}/{name}`", kind.dir()),),
+	},)
+}
+
+/// Kind of the crate at `dir`, judged by its manifest and crate root
+fn crate_kind(dir: &Path, manifest: &toml::Table,) -> CrateKind {
+	if is_proc_macro(manifest,) {
+		CrateKind::ProcMacro
+	} else if is_no_std(dir,) {
+		CrateKind::NoStd
+	} else {
+		CrateKind::Host
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn write(path: &Path, content: &str,) {
+		std::fs::create_dir_all(path.parent().unwrap(),).unwrap();
+		std::fs::write(path, content,).unwrap();
+	}
+
+	fn manifest(name: &str,) -> String {
+		format!("[package]\nname = \"{name}\"\n")
+	}
+
+	#[test]
+	fn test_violations_are_reported() {
+		let root = std::env::temp_dir().join("oso_audit_test",);
+		let _ = std::fs::remove_dir_all(&root,);
+		let good = root.join("components/shared/no_std/oso_good",);
+		write(&good.join("Cargo.toml",), &manifest("oso_good",),);
+		write(&good.join("src/lib.rs",), "#![no_std]\n",);
+		write(&good.join("src/boot_info.rs",), "",);
+		let bad = root.join("components/shared/no_std/bad",);
+		write(&bad.join("Cargo.toml",), &manifest("Bad-Crate",),);
+		write(&bad.join("src/lib.rs",), "",);
+		write(&bad.join("src/FrameBuf.rs",), "",);
+
+		let violations = audit_crates(&root, &[good, bad,],).unwrap();
+		let rules: Vec<Rule,> = violations.iter().map(|v| v.rule,).collect();
+		assert_eq!(rules.len(), 3, "{violations:#?}");
+		assert!(rules.contains(&Rule::ModuleName));
+		assert!(rules.contains(&Rule::CrateName));
+		assert!(rules.contains(&Rule::Placement));
+
+		let module = violations.iter().find(|v| v.rule == Rule::ModuleName,);
+		assert_eq!(
+			module.unwrap().suggestion.as_deref(),
+			Some("rename to `frame_buf.rs`")
+		);
+		let name = violations.iter().find(|v| v.rule == Rule::CrateName,);
+		assert_eq!(
+			name.unwrap().suggestion.as_deref(),
+			Some("rename package to `oso_bad_crate`")
+		);
+	}
+
+	#[test]
+	fn test_prefix_exception() {
+		assert!(check_crate_name(Path::new("."), "xtask").is_none());
+		assert!(check_crate_name(Path::new("."), "oso_kernel").is_none());
+		let v = check_crate_name(Path::new("a",), "kernel",).unwrap();
+		assert_eq!(v.message, "package `kernel` does not start with `oso_`");
+	}
+}
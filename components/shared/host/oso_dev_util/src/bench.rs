@@ -0,0 +1,156 @@
+//! # Benchmark Results
+//!
+//! Compares results of the `parsers` and `mem` benchmark suites between two
+//! runs, so `cargo xtask bench --compare <baseline>` can flag regressions.
+//!
+//! Criterion stores each benchmark under `<home>/<group>/<name>/<run>`,
+//! where `<run>` is the name given by `--save-baseline`. The mean of
+//! `estimates.json` is compared.
+
+use anyhow::Context;
+use anyhow::Result as Rslt;
+use anyhow::bail;
+use std::fmt::Display;
+use std::path::Path;
+
+/// results directory of criterion under the workspace root
+pub const CRITERION_HOME: &str = "target/xtask/bench";
+/// run name of the latest results
+pub const LATEST: &str = "latest";
+
+/// mean time of a benchmark in two runs
+#[derive(Debug, Clone, PartialEq,)]
+pub struct Comparison {
+	/// `<group>/<name>`
+	pub name:        String,
+	pub baseline_ns: f64,
+	pub current_ns:  f64,
+}
+
+impl Comparison {
+	/// change of the mean in percent. positive is slower
+	pub fn change(&self,) -> f64 {
+		(self.current_ns - self.baseline_ns) / self.baseline_ns * 100.0
+	}
+
+	pub fn is_regression(&self, threshold_percent: f64,) -> bool {
+		self.change() > threshold_percent
+	}
+}
+
+impl Display for Comparison {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_,>,) -> std::fmt::Result {
+		write!(
+			f,
+			"{}: {:.1}ns -> {:.1}ns ({:+.1}%)",
+			self.name,
+			self.baseline_ns,
+			self.current_ns,
+			self.change()
+		)
+	}
+}
+
+/// compares every benchmark under `home` which has results of both runs.
+/// sorted by name
+pub fn compare(
+	home: &Path,
+	baseline: &str,
+	current: &str,
+) -> Rslt<Vec<Comparison,>,> {
+	let mut comparisons = vec![];
+	collect(home, home, baseline, current, &mut comparisons,)?;
+	if comparisons.is_empty() {
+		bail!("no benchmark in {} has a `{baseline}` run", home.display());
+	}
+	comparisons.sort_by(|a, b| a.name.cmp(&b.name,),);
+	Ok(comparisons,)
+}
+
+fn collect(
+	home: &Path,
+	dir: &Path,
+	baseline: &str,
+	current: &str,
+	out: &mut Vec<Comparison,>,
+) -> Rslt<(),> {
+	let old = dir.join(baseline,).join("estimates.json",);
+	let new = dir.join(current,).join("estimates.json",);
+	if old.is_file() && new.is_file() {
+		let name = dir.strip_prefix(home,)?.to_string_lossy();
+		let name = name.replace('\\', "/",);
+		out.push(Comparison {
+			name,
+			baseline_ns: mean_ns(&std::fs::read_to_string(&old,)?,)
+				.with_context(|| old.display().to_string(),)?,
+			current_ns: mean_ns(&std::fs::read_to_string(&new,)?,)
+				.with_context(|| new.display().to_string(),)?,
+		},);
+		return Ok((),);
+	}
+
+	for entry in std::fs::read_dir(dir,)? {
+		let path = entry?.path();
+		if path.is_dir() {
+			collect(home, &path, baseline, current, out,)?;
+		}
+	}
+	Ok((),)
+}
+
+/// point estimate of the mean in `estimates.json`
+fn mean_ns(json: &str,) -> Rslt<f64,> {
+	const KEY: &str = "\"point_estimate\":";
+	let Some(mean,) = json.find("\"mean\"",) else {
+		bail!("estimates have no mean")
+	};
+	let Some(at,) = json[mean..].find(KEY,) else {
+		bail!("mean has no point estimate")
+	};
+	let value = json[mean + at + KEY.len()..].trim_start();
+	let end = value.find([',', '}',],).unwrap_or(value.len(),);
+	Ok(value[..end].trim().parse()?,)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn estimates(mean: f64,) -> String {
+		format!(
+			"{{\"mean\":{{\"confidence_interval\":{{\"lower_bound\":1.0}},\
+			 \"point_estimate\":{mean},\"standard_error\":0.1}},\
+			 \"median\":{{\"point_estimate\":7.0}}}}"
+		)
+	}
+
+	#[test]
+	fn test_mean_ns() {
+		assert_eq!(mean_ns(&estimates(123.5,),).unwrap(), 123.5);
+		assert!(mean_ns("{}").is_err());
+	}
+
+	#[test]
+	fn test_compare_flags_regression() {
+		let home = std::env::temp_dir().join("oso_bench_compare",);
+		let _ = std::fs::remove_dir_all(&home,);
+		for (name, base, latest,) in
+			[("elf/full", 100.0, 130.0,), ("fat/lookup", 100.0, 101.0,),]
+		{
+			for (run, mean,) in [("main", base,), (LATEST, latest,),] {
+				let dir = home.join(name,).join(run,);
+				std::fs::create_dir_all(&dir,).unwrap();
+				std::fs::write(dir.join("estimates.json",), estimates(mean,),)
+					.unwrap();
+			}
+		}
+
+		let comparisons = compare(&home, "main", LATEST,).unwrap();
+		assert_eq!(comparisons.len(), 2);
+		assert_eq!(comparisons[0].name, "elf/full");
+		assert!(comparisons[0].is_regression(5.0));
+		assert!(!comparisons[1].is_regression(5.0));
+		assert!(compare(&home, "missing", LATEST,).is_err());
+		std::fs::remove_dir_all(&home,).unwrap();
+	}
+}
@@ -1,6 +1,15 @@
 use anyhow::Context as _;
 use anyhow::Result as Rslt;
+use anyhow::bail;
+use crate::cli::BuildArgs;
+use crate::cli::OutputArgs;
+use crate::cli::TargetArgs;
+use crate::cli::Verbosity;
+use crate::scaffold::CrateKind;
+use clap::CommandFactory;
 use clap::Parser;
+use clap::error::ErrorKind;
+use oso_dev_util_helper::cli::Run;
 use oso_proc_macro::features;
 use ovmf_prebuilt::FileType;
 use ovmf_prebuilt::Prebuilt;
@@ -10,25 +19,113 @@ use std::process::Command;
 use std::str::FromStr;
 use strum_macros::Display;
 
+pub mod parallel;
+pub mod target;
+
 pub trait CompileOpt {
 	fn build_mode(&self,) -> impl Into<String,>;
 	fn feature_flags(&self,) -> Vec<impl Into<String,>,>;
 	fn arch(&self,) -> impl Into<String,>;
 }
 
+/// Cargo features declared by crates of the workspace
+///
+/// Variants are generated from `[features]` tables of every `Cargo.toml`.
+/// `as_ref` and `from_str` use the name cargo knows, e.g. `Feature::Rgb` is
+/// `"rgb"`.
 #[features]
 #[derive(
 	strum_macros::AsRefStr,
 	strum_macros::EnumIs,
 	strum_macros::EnumString,
 	Clone,
+	Copy,
+	PartialEq,
+	Eq,
+	Debug,
 )]
 pub enum Feature {}
 
+/// Features enabled by `default` of the crates. A test checks that this is
+/// the union of `[features] default` of every manifest
+pub const DEFAULT_FEATURES: &[Feature] = &[Feature::Bltonly,];
+
+/// Constraints between features checked by [`Cli::to_opts`]
+pub const FEATURE_RULES: &[FeatureRule] = &[
+	// pixel format of the frame buffer. each one compiles its own drawing code
+	FeatureRule::Exclusive(&[
+		Feature::Rgb,
+		Feature::Bgr,
+		Feature::Bitmask,
+		Feature::Bltonly,
+	],),
+	// heap allocator of the loader
+	FeatureRule::Exclusive(&[Feature::PoolAllocator, Feature::BumpAllocator,],),
+];
+
+/// Constraint between features
+#[derive(Clone, Copy, Debug, PartialEq, Eq,)]
+pub enum FeatureRule {
+	/// At most one of the features can be enabled
+	Exclusive(&'static [Feature],),
+	/// The first feature needs the second one
+	Requires(Feature, Feature,),
+}
+
+impl FeatureRule {
+	/// # Errors
+	///
+	/// Returns an error naming the offending features if `features` breaks
+	/// this rule
+	pub fn check(&self, features: &[Feature],) -> Rslt<(),> {
+		match self {
+			Self::Exclusive(group,) => {
+				let enabled: Vec<_,> = group
+					.iter()
+					.filter(|f| features.contains(f,),)
+					.map(AsRef::as_ref,)
+					.collect();
+				if enabled.len() > 1 {
+					let enabled = enabled.join(", ",);
+					bail!("features {enabled} are mutually exclusive");
+				}
+			},
+			Self::Requires(feature, required,) => {
+				if features.contains(feature,) && !features.contains(required,)
+				{
+					bail!(
+						"feature {} requires feature {}",
+						feature.as_ref(),
+						required.as_ref()
+					);
+				}
+			},
+		}
+		Ok((),)
+	}
+
+	/// `true` if `a` and `b` can not be enabled at the same time
+	fn conflicts(&self, a: Feature, b: Feature,) -> bool {
+		match self {
+			Self::Exclusive(group,) => {
+				a != b && group.contains(&a,) && group.contains(&b,)
+			},
+			Self::Requires(..,) => false,
+		}
+	}
+}
+
 pub struct Opts {
 	pub build_mode:    BuildMode,
 	pub feature_flags: Vec<Feature,>,
 	pub arch:          Arch,
+	pub verbosity:     Verbosity,
+	pub dry_run:       bool,
+	/// Maximum number of crates built at once
+	pub jobs:          usize,
+	/// Keep ANSI escape sequences in serial logs
+	pub keep_ansi:     bool,
+	pub task:          Task,
 }
 
 impl Default for Opts {
@@ -38,8 +135,55 @@ impl Default for Opts {
 }
 
 impl Opts {
+	/// Parses command line. Exits with usage error if the selected features
+	/// break [`FEATURE_RULES`]
 	pub fn new() -> Self {
-		Cli::parse().to_opts()
+		Cli::parse().to_opts().unwrap_or_else(|e| {
+			Cli::command().error(ErrorKind::ArgumentConflict, e,).exit()
+		},)
+	}
+
+	/// Arguments of cargo which select [`Opts::feature_flags`]
+	///
+	/// Default features are kept unless one of the selected features conflicts
+	/// with them, e.g. `-f rgb` replaces default `bltonly`. Empty if no feature
+	/// is selected.
+	pub fn feature_args(&self,) -> Vec<String,> {
+		if self.feature_flags.is_empty() {
+			return vec![];
+		}
+
+		let defaults = DEFAULT_FEATURES.iter().filter(|default| {
+			!self.feature_flags.iter().any(|f| {
+				FEATURE_RULES.iter().any(|rule| rule.conflicts(*f, **default,),)
+			},)
+		},);
+		let mut features: Vec<&str,> = self
+			.feature_flags
+			.iter()
+			.chain(defaults,)
+			.map(AsRef::as_ref,)
+			.collect();
+		features.sort_unstable();
+		features.dedup();
+
+		vec![
+			"--no-default-features".into(),
+			"--features".into(),
+			features.join(",",),
+		]
+	}
+
+	/// Runs `cmd`, or only prints it if `--dry-run` is given
+	pub fn exec(&self, cmd: &mut Command,) -> Rslt<(),> {
+		if !self.dry_run {
+			return cmd.run();
+		}
+
+		let args: Vec<_,> =
+			cmd.get_args().map(|a| a.to_string_lossy(),).collect();
+		println!("{} {}", cmd.get_program().display(), args.join(" "));
+		Ok((),)
 	}
 }
 
@@ -57,23 +201,228 @@ impl CompileOpt for Opts {
 	}
 }
 
+/// Command line of xtask
+///
+/// Composed of the argument groups in [`crate::cli`] shared with other host
+/// tools.
 #[derive(clap::Parser,)]
 pub struct Cli {
-	#[arg(value_enum, short)]
-	pub build_mode:    Option<BuildMode,>,
-	#[arg(short)]
-	pub feature_flags: Option<Vec<Feature,>,>,
-	#[arg(short)]
-	pub arch:          Option<Arch,>,
+	#[command(flatten)]
+	pub target: TargetArgs,
+	#[command(flatten)]
+	pub build:  BuildArgs,
+	#[command(flatten)]
+	pub output: OutputArgs,
+	#[command(subcommand)]
+	pub task:   Option<Task,>,
+}
+
+/// What xtask does after building
+#[derive(clap::Subcommand, Clone, Debug, Default, PartialEq, Eq,)]
+pub enum Task {
+	/// run QEMU interactively. the default
+	#[default]
+	Run,
+	/// boot QEMU and check serial output for milestones
+	BootTest {
+		/// milestone script. built-in milestones are checked if omitted
+		#[arg(long)]
+		script:   Option<PathBuf,>,
+		/// shell script packed into the initial ramdisk as `autoexec.osh`,
+		/// which the kernel runs at boot
+		#[arg(long)]
+		autoexec: Option<PathBuf,>,
+	},
+	/// boot QEMU, run a shell script at boot and exit with its result
+	Test {
+		/// shell script packed into the initial ramdisk as `autoexec.osh`
+		script:  PathBuf,
+		/// seconds the kernel may take to exit QEMU
+		#[arg(long, default_value_t = 60)]
+		timeout: u64,
+	},
+	/// generate a new workspace crate from templates instead of building
+	NewCrate {
+		/// package name in snake case
+		name: String,
+		#[arg(long, value_enum)]
+		kind: CrateKind,
+	},
+	/// check naming conventions and crate placement instead of building
+	Audit,
+	/// print the dependency chart of workspace crates instead of building
+	Graph {
+		#[arg(long, value_enum, default_value_t)]
+		format: GraphFormat,
+		/// write the chart to this file instead of stdout
+		#[arg(long)]
+		output: Option<PathBuf,>,
+	},
+	/// run parser benchmarks instead of building
+	Bench {
+		/// save results as this baseline instead of `latest`
+		#[arg(long)]
+		save:      Option<String,>,
+		/// compare results with this saved baseline and fail on regressions
+		#[arg(long)]
+		compare:   Option<String,>,
+		/// slowdown in percent which counts as a regression
+		#[arg(long, default_value_t = 5)]
+		threshold: u32,
+	},
+	/// inspect kernel crash dumps instead of building
+	Crash {
+		#[command(subcommand)]
+		command: CrashCommand,
+	},
+	/// convert a kernel trace dump into a Chrome trace instead of building
+	Trace {
+		/// raw dump or serial log holding one
+		file:      PathBuf,
+		/// JSON file to write. defaults to `file` with the extension `json`
+		#[arg(long)]
+		output:    Option<PathBuf,>,
+		/// timestamp frequency in Hz, overriding the one in the dump
+		#[arg(long)]
+		frequency: Option<u64,>,
+	},
+	/// fold the heap allocations of a kernel trace dump into stacks for a
+	/// flamegraph instead of building
+	Heap {
+		/// raw dump or serial log holding one
+		file:   PathBuf,
+		/// file to write. defaults to `file` with the extension `folded`
+		#[arg(long)]
+		output: Option<PathBuf,>,
+		/// kernel image whose symbols name the frames. defaults to the last
+		/// built one
+		#[arg(long)]
+		kernel: Option<PathBuf,>,
+	},
+	/// download the specification pages read by macros into `specs/` for
+	/// offline builds instead of building
+	VendorSpecs,
+	/// write the registry of error codes and reject duplicate codes instead
+	/// of building
+	ErrorCodes {
+		/// fail if the registry is out of date instead of writing it
+		#[arg(long)]
+		check: bool,
+	},
+	/// list every unsafe block, function, impl and trait with its safety
+	/// contract instead of building
+	AuditUnsafe {
+		/// markdown file to write the inventory to
+		#[arg(long)]
+		output: Option<PathBuf,>,
+		/// fail if a site has no safety contract
+		#[arg(long)]
+		strict: bool,
+	},
+	/// print a device tree blob like the kernel shell command `dt` instead
+	/// of building
+	Dt {
+		#[command(subcommand)]
+		command: DtCommand,
+	},
+	/// pretty-print the boot information the kernel was handed instead of
+	/// building
+	Bootinfo {
+		/// raw dump or serial log holding one
+		file: PathBuf,
+	},
+	/// extract a screen capture of the kernel and compare it with a golden
+	/// image instead of building
+	Screenshot {
+		/// raw QOI image or serial log holding a capture
+		file:      PathBuf,
+		/// QOI file to write. defaults to `file` with the extension `qoi`
+		#[arg(long)]
+		output:    Option<PathBuf,>,
+		/// golden image the capture must match
+		#[arg(long)]
+		golden:    Option<PathBuf,>,
+		/// largest difference of a color channel which still matches
+		#[arg(long, default_value_t = 0)]
+		tolerance: u8,
+		/// write the capture as the golden image instead of comparing
+		#[arg(long, requires = "golden")]
+		update:    bool,
+	},
+	/// run the kernel console and shell in a window on the host instead of
+	/// QEMU
+	Sim {
+		/// window size as `<width>x<height>`. defaults to `800x600`
+		size:   Option<String,>,
+		/// serial log whose last `record dump` is replayed
+		#[arg(long)]
+		replay: Option<PathBuf,>,
+	},
+}
+
+/// Subcommands of [`Task::Crash`]
+#[derive(clap::Subcommand, Clone, Debug, PartialEq, Eq,)]
+pub enum CrashCommand {
+	/// pretty-print a crash dump, either raw or within a serial log
+	Decode {
+		file:   PathBuf,
+		/// kernel ELF to resolve addresses with. defaults to the last built
+		/// image
+		#[arg(long)]
+		kernel: Option<PathBuf,>,
+	},
+}
+
+/// Subcommands of [`Task::Dt`]
+#[derive(clap::Subcommand, Clone, Debug, PartialEq, Eq,)]
+pub enum DtCommand {
+	/// print a node and its descendants
+	Print {
+		file: PathBuf,
+		/// node to print. defaults to the root
+		path: Option<String,>,
+	},
+	/// print one property of a node
+	Prop {
+		file: PathBuf,
+		path: String,
+		name: String,
+	},
+}
+
+/// Output format of [`Task::Graph`]
+#[derive(
+	clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Display,
+)]
+#[strum(serialize_all = "lowercase")]
+pub enum GraphFormat {
+	/// mermaid flowchart, renderable in markdown and rustdoc
+	#[default]
+	Mermaid,
+	/// graphviz DOT
+	Dot,
 }
 
 impl Cli {
-	pub fn to_opts(self,) -> Opts {
-		Opts {
-			build_mode:    self.build_mode.unwrap_or_default(),
-			feature_flags: self.feature_flags.unwrap_or_default(),
-			arch:          self.arch.unwrap_or_default(),
+	/// # Errors
+	///
+	/// Returns an error if the selected features break [`FEATURE_RULES`]
+	pub fn to_opts(self,) -> Rslt<Opts,> {
+		let feature_flags = self.build.feature_flags();
+		for rule in FEATURE_RULES {
+			rule.check(&feature_flags,)?;
 		}
+
+		Ok(Opts {
+			build_mode: self.build.build_mode(),
+			feature_flags,
+			arch: self.target.arch(),
+			verbosity: self.output.verbosity(),
+			dry_run: self.output.dry_run,
+			jobs: self.build.jobs(),
+			keep_ansi: self.output.keep_ansi,
+			task: self.task.unwrap_or_default(),
+		},)
 	}
 }
 
@@ -91,11 +440,29 @@ impl Cli {
 	Display,
 )]
 pub enum BuildMode {
+	// `relese` is the spelling of older versions
+	#[value(alias = "relese")]
+	#[strum(to_string = "Release", serialize = "Relese")]
 	Release,
 	#[default]
 	Debug,
 }
 
+impl BuildMode {
+	#[deprecated = "misspelled. use `BuildMode::Release`"]
+	#[allow(non_upper_case_globals)]
+	pub const Relese: Self = Self::Release;
+
+	/// Directory cargo writes artifacts of this profile into, e.g.
+	/// `target/<triple>/release`
+	pub fn profile_dir(&self,) -> &'static str {
+		match self {
+			Self::Release => "release",
+			Self::Debug => "debug",
+		}
+	}
+}
+
 pub enum Runtime {
 	Mac,
 	Linux,
@@ -193,6 +560,7 @@ impl From<Arch,> for ovmf_prebuilt::Arch {
 		match value {
 			Arch::Aarch64 => ovmf_prebuilt::Arch::Aarch64,
 			Arch::Riscv64 => ovmf_prebuilt::Arch::Riscv64,
+			Arch::X86_64 => ovmf_prebuilt::Arch::X64,
 		}
 	}
 }
@@ -214,6 +582,8 @@ pub enum Arch {
 	#[default]
 	Aarch64,
 	Riscv64,
+	#[value(name = "x86_64")]
+	X86_64,
 }
 
 impl Arch {
@@ -226,6 +596,7 @@ impl Arch {
 		match self {
 			Self::Aarch64 => "bootaa64.efi",
 			Self::Riscv64 => "bootriscv64.efi",
+			Self::X86_64 => "bootx64.efi",
 		}
 	}
 }
@@ -279,6 +650,19 @@ mod tests {
 		assert!(BuildMode::from_str("Invalid").is_err());
 	}
 
+	#[test]
+	#[allow(deprecated)]
+	fn test_build_mode_old_spelling() {
+		assert_eq!(BuildMode::from_str("Relese").unwrap(), BuildMode::Release);
+		assert_eq!(BuildMode::Relese, BuildMode::Release);
+		assert_eq!(BuildMode::Release.as_ref(), "Release");
+		assert_eq!(BuildMode::Release.to_string(), "Release");
+
+		let cli = Cli::try_parse_from(["xtask", "-b", "relese",],).unwrap();
+		assert_eq!(cli.build.build_mode(), BuildMode::Release);
+		assert_eq!(BuildMode::Release.profile_dir(), "release");
+	}
+
 	#[test]
 	fn test_arch_default() {
 		let default_arch = Arch::default();
@@ -311,12 +695,17 @@ mod tests {
 	#[test]
 	fn test_cli_to_opts_with_values() {
 		let cli = Cli {
-			build_mode:    Some(BuildMode::Release,),
-			feature_flags: Some(vec![],),
-			arch:          Some(Arch::Riscv64,),
+			target: TargetArgs { arch: Some(Arch::Riscv64,), },
+			build:  BuildArgs {
+				build_mode:    Some(BuildMode::Release,),
+				feature_flags: Some(vec![],),
+				jobs:          None,
+			},
+			output: OutputArgs::default(),
+			task:   None,
 		};
 
-		let opts = cli.to_opts();
+		let opts = cli.to_opts().unwrap();
 		assert!(opts.build_mode.is_release());
 		assert!(opts.feature_flags.is_empty());
 	}
@@ -324,12 +713,17 @@ mod tests {
 	#[test]
 	fn test_cli_to_opts_with_defaults() {
 		let cli = Cli {
-			build_mode:    None,
-			feature_flags: None,
-			arch:          None,
+			target: TargetArgs { arch: None, },
+			build:  BuildArgs {
+				build_mode:    None,
+				feature_flags: None,
+				jobs:          None,
+			},
+			output: OutputArgs::default(),
+			task:   None,
 		};
 
-		let opts = cli.to_opts();
+		let opts = cli.to_opts().unwrap();
 		assert!(opts.build_mode.is_debug());
 		assert!(opts.feature_flags.is_empty());
 	}
@@ -340,6 +734,11 @@ mod tests {
 			build_mode:    BuildMode::Release,
 			feature_flags: vec![],
 			arch:          Arch::Riscv64,
+			verbosity:     Verbosity::default(),
+			dry_run:       false,
+			jobs:          1,
+			keep_ansi:     false,
+			task:          Task::Run,
 		};
 
 		let build_mode: String = opts.build_mode().into();
@@ -391,14 +790,192 @@ mod tests {
 	}
 
 	#[test]
-	fn test_feature_enum_exists() {
-		// Test that Feature enum exists and can be used in collections
-		let features: Vec<Feature,> = vec![];
-		assert!(features.is_empty());
+	fn test_feature_uses_cargo_names() {
+		assert_eq!(Feature::Rgb.as_ref(), "rgb");
+		assert_eq!(Feature::Bltonly.as_ref(), "bltonly");
+		assert_eq!(Feature::from_str("bitmask").unwrap(), Feature::Bitmask);
+		// `default` is not selectable
+		assert!(Feature::from_str("default").is_err());
+	}
+
+	#[test]
+	fn test_default_features_match_manifests() {
+		use oso_dev_util_helper::fs::CARGO_MANIFEST;
+		use oso_dev_util_helper::fs::all_crates;
+		use oso_dev_util_helper::fs::read_toml;
+
+		let mut defaults = vec![];
+		for manifest in all_crates()
+			.unwrap()
+			.iter()
+			.filter_map(|dir| read_toml(dir.join(CARGO_MANIFEST,),),)
+		{
+			let manifest = manifest.unwrap();
+			let Some(toml::Value::Array(default,),) =
+				manifest.get("features",).and_then(|f| f.get("default",),)
+			else {
+				continue;
+			};
+			let default = default.iter().map(|f| f.as_str().unwrap(),);
+			defaults.extend(default.map(str::to_string,),);
+		}
+		defaults.sort_unstable();
+		defaults.dedup();
+
+		let mut declared: Vec<_,> =
+			DEFAULT_FEATURES.iter().map(|f| f.as_ref().to_string(),).collect();
+		declared.sort_unstable();
+		assert_eq!(declared, defaults);
+	}
 
-		// Test that Feature implements required traits
-		let _phantom: std::marker::PhantomData<Feature,> =
-			std::marker::PhantomData;
+	#[test]
+	fn test_exclusive_features_are_rejected() {
+		let cli = Cli::try_parse_from(["xtask", "-f", "rgb,bgr",],).unwrap();
+		let e = cli.to_opts().err().unwrap();
+		assert!(e.to_string().contains("rgb, bgr"));
+
+		let cli =
+			Cli::try_parse_from(["xtask", "-f", "rgb", "-f", "rgb",],).unwrap();
+		assert!(cli.to_opts().is_ok());
+	}
+
+	#[test]
+	fn test_task_subcommand() {
+		let opts = Cli::try_parse_from(["xtask",],).unwrap().to_opts().unwrap();
+		assert_eq!(opts.task, Task::Run);
+
+		let args = ["xtask", "-a", "aarch64", "boot-test", "--keep-ansi",];
+		let opts = Cli::try_parse_from(args,).unwrap().to_opts().unwrap();
+		assert_eq!(opts.task, Task::BootTest { script: None, autoexec: None });
+		assert!(opts.keep_ansi);
+
+		let args = ["xtask", "boot-test", "--autoexec", "sched.osh",];
+		let opts = Cli::try_parse_from(args,).unwrap().to_opts().unwrap();
+		assert_eq!(opts.task, Task::BootTest {
+			script:   None,
+			autoexec: Some(PathBuf::from("sched.osh",),),
+		});
+
+		let args = ["xtask", "test", "sched.osh", "--timeout", "10",];
+		let opts = Cli::try_parse_from(args,).unwrap().to_opts().unwrap();
+		assert_eq!(opts.task, Task::Test {
+			script:  PathBuf::from("sched.osh",),
+			timeout: 10,
+		});
+
+		let args = ["xtask", "new-crate", "oso_fs", "--kind", "no_std",];
+		let opts = Cli::try_parse_from(args,).unwrap().to_opts().unwrap();
+		assert_eq!(opts.task, Task::NewCrate {
+			name: "oso_fs".into(),
+			kind: CrateKind::NoStd,
+		});
+
+		let opts = Cli::try_parse_from(["xtask", "audit",],).unwrap();
+		assert_eq!(opts.to_opts().unwrap().task, Task::Audit);
+
+		let args = ["xtask", "graph", "--format", "dot",];
+		let opts = Cli::try_parse_from(args,).unwrap().to_opts().unwrap();
+		assert_eq!(opts.task, Task::Graph {
+			format: GraphFormat::Dot,
+			output: None,
+		});
+
+		let args = ["xtask", "bench", "--compare", "main",];
+		let opts = Cli::try_parse_from(args,).unwrap().to_opts().unwrap();
+		assert_eq!(opts.task, Task::Bench {
+			save:      None,
+			compare:   Some("main".into(),),
+			threshold: 5,
+		});
+
+		let args = ["xtask", "trace", "serial.log", "--frequency", "62500000",];
+		let opts = Cli::try_parse_from(args,).unwrap().to_opts().unwrap();
+		assert_eq!(opts.task, Task::Trace {
+			file:      "serial.log".into(),
+			output:    None,
+			frequency: Some(62_500_000,),
+		});
+
+		let args = ["xtask", "heap", "serial.log", "--output", "boot.folded",];
+		let opts = Cli::try_parse_from(args,).unwrap().to_opts().unwrap();
+		assert_eq!(opts.task, Task::Heap {
+			file:   "serial.log".into(),
+			output: Some("boot.folded".into(),),
+			kernel: None,
+		});
+
+		let args = ["xtask", "error-codes", "--check",];
+		let opts = Cli::try_parse_from(args,).unwrap().to_opts().unwrap();
+		assert_eq!(opts.task, Task::ErrorCodes { check: true });
+
+		let args = ["xtask", "audit-unsafe", "--output", "unsafe.md",];
+		let opts = Cli::try_parse_from(args,).unwrap().to_opts().unwrap();
+		assert_eq!(opts.task, Task::AuditUnsafe {
+			output: Some(PathBuf::from("unsafe.md"),),
+			strict: false,
+		});
+
+		let args = [
+			"xtask",
+			"screenshot",
+			"serial.log",
+			"--golden",
+			"console.qoi",
+			"--tolerance",
+			"2",
+		];
+		let opts = Cli::try_parse_from(args,).unwrap().to_opts().unwrap();
+		assert_eq!(opts.task, Task::Screenshot {
+			file:      "serial.log".into(),
+			output:    None,
+			golden:    Some("console.qoi".into(),),
+			tolerance: 2,
+			update:    false,
+		});
+		let args = ["xtask", "screenshot", "serial.log", "--update",];
+		assert!(Cli::try_parse_from(args,).is_err());
+
+		let args = ["xtask", "vendor-specs",];
+		let opts = Cli::try_parse_from(args,).unwrap().to_opts().unwrap();
+		assert_eq!(opts.task, Task::VendorSpecs);
+
+		let args = ["xtask", "dt", "prop", "virt.dtb", "/psci", "method",];
+		let opts = Cli::try_parse_from(args,).unwrap().to_opts().unwrap();
+		assert_eq!(opts.task, Task::Dt {
+			command: DtCommand::Prop {
+				file: "virt.dtb".into(),
+				path: "/psci".into(),
+				name: "method".into(),
+			},
+		});
+
+		let args = ["xtask", "bootinfo", "serial.log",];
+		let opts = Cli::try_parse_from(args,).unwrap().to_opts().unwrap();
+		assert_eq!(opts.task, Task::Bootinfo { file: "serial.log".into(), });
+
+		let args = ["xtask", "sim", "1024x768", "--replay", "serial.log",];
+		let opts = Cli::try_parse_from(args,).unwrap().to_opts().unwrap();
+		assert_eq!(opts.task, Task::Sim {
+			size:   Some("1024x768".into(),),
+			replay: Some("serial.log".into(),),
+		});
+
+		let args = ["xtask", "crash", "decode", "serial.log",];
+		let opts = Cli::try_parse_from(args,).unwrap().to_opts().unwrap();
+		assert_eq!(opts.task, Task::Crash {
+			command: CrashCommand::Decode {
+				file:   "serial.log".into(),
+				kernel: None,
+			},
+		});
+	}
+
+	#[test]
+	fn test_requires_rule() {
+		let rule = FeatureRule::Requires(Feature::Rgb, Feature::Bitmask,);
+		assert!(rule.check(&[Feature::Rgb],).is_err());
+		assert!(rule.check(&[Feature::Rgb, Feature::Bitmask],).is_ok());
+		assert!(rule.check(&[Feature::Bitmask],).is_ok());
 	}
 
 	// Property-based tests
@@ -423,12 +1000,13 @@ mod tests {
 			arch in prop::option::of(prop::sample::select(vec![Arch::Aarch64, Arch::Riscv64]))
 		) {
 			let cli = Cli {
-				build_mode,
-				feature_flags: Some(vec![]),
-				arch,
+				target: TargetArgs { arch },
+				build: BuildArgs { build_mode, feature_flags: Some(vec![]), jobs: None },
+				output: OutputArgs::default(),
+				task: None,
 			};
 
-			let opts = cli.to_opts();
+			let opts = cli.to_opts().unwrap();
 
 			// Check that values are preserved or defaults are used
 			match build_mode {
@@ -456,9 +1034,10 @@ mod tests {
 
 		// Test Arch variants
 		let arch_variants = Arch::value_variants();
-		assert_eq!(arch_variants.len(), 2);
+		assert_eq!(arch_variants.len(), 3);
 		assert!(arch_variants.contains(&Arch::Aarch64));
 		assert!(arch_variants.contains(&Arch::Riscv64));
+		assert!(arch_variants.contains(&Arch::X86_64));
 	}
 
 	#[test]
@@ -478,6 +1057,11 @@ mod tests {
 			build_mode:    BuildMode::Debug,
 			feature_flags: vec![],
 			arch:          Arch::default(),
+			verbosity:     Verbosity::default(),
+			dry_run:       false,
+			jobs:          1,
+			keep_ansi:     false,
+			task:          Task::Run,
 		};
 
 		let flags = opts.feature_flags();
@@ -488,19 +1072,29 @@ mod tests {
 	fn test_struct_field_access() {
 		// Test that all struct fields are accessible
 		let cli = Cli {
-			build_mode:    Some(BuildMode::Debug,),
-			feature_flags: Some(vec![],),
-			arch:          Some(Arch::Riscv64,),
+			target: TargetArgs { arch: Some(Arch::Riscv64,), },
+			build:  BuildArgs {
+				build_mode:    Some(BuildMode::Debug,),
+				feature_flags: Some(vec![],),
+				jobs:          None,
+			},
+			output: OutputArgs::default(),
+			task:   None,
 		};
 
-		assert!(cli.build_mode.unwrap().is_debug());
-		assert!(cli.feature_flags.unwrap().is_empty());
-		assert!(cli.arch.unwrap().is_riscv_64());
+		assert!(cli.build.build_mode.unwrap().is_debug());
+		assert!(cli.build.feature_flags.unwrap().is_empty());
+		assert!(cli.target.arch.unwrap().is_riscv_64());
 
 		let opts = Opts {
 			build_mode:    BuildMode::Release,
 			feature_flags: vec![],
 			arch:          Arch::Aarch64,
+			verbosity:     Verbosity::default(),
+			dry_run:       false,
+			jobs:          1,
+			keep_ansi:     false,
+			task:          Task::Run,
 		};
 
 		assert!(opts.build_mode.is_release());
@@ -526,6 +1120,9 @@ mod tests {
 			match variant {
 				Arch::Aarch64 => assert!(variant.is_aarch_64()),
 				Arch::Riscv64 => assert!(variant.is_riscv_64()),
+				Arch::X86_64 => {
+					assert!(!variant.is_aarch_64() && !variant.is_riscv_64())
+				},
 			}
 		}
 	}
@@ -602,6 +1199,11 @@ mod tests {
 						build_mode:    *bm,
 						feature_flags: vec![],
 						arch:          *a,
+						verbosity:     Verbosity::default(),
+						dry_run:       false,
+						jobs:          1,
+						keep_ansi:     false,
+						task:          Task::Run,
 					};
 				},)
 			},)
@@ -622,12 +1224,17 @@ mod tests {
 
 		// Test default CLI
 		let cli = Cli {
-			build_mode:    None,
-			feature_flags: None,
-			arch:          None,
+			target: TargetArgs { arch: None, },
+			build:  BuildArgs {
+				build_mode:    None,
+				feature_flags: None,
+				jobs:          None,
+			},
+			output: OutputArgs::default(),
+			task:   None,
 		};
 
-		let opts = cli.to_opts();
+		let opts = cli.to_opts().unwrap();
 		assert!(opts.build_mode.is_debug());
 		assert!(opts.arch.is_aarch_64());
 	}
@@ -654,23 +1261,37 @@ mod tests {
 	}
 
 	#[test]
-	fn test_feature_flags_empty() {
-		// Test that Feature enum is empty as expected
-		// Note: Feature enum doesn't implement ValueEnum since it's empty
-
-		// Test that we can create empty vectors
-		let features: Vec<Feature,> = vec![];
-		assert!(features.is_empty());
-
-		// Test in Opts
-		let opts = Opts {
-			build_mode:    BuildMode::Debug,
-			feature_flags: features,
-			arch:          Arch::default(),
+	fn test_feature_args() {
+		let opts = |feature_flags| Opts {
+			build_mode: BuildMode::Debug,
+			feature_flags,
+			arch: Arch::default(),
+			verbosity: Verbosity::default(),
+			dry_run: false,
+			jobs: 1,
+			keep_ansi: false,
+			task: Task::Run,
 		};
 
-		let returned_features = opts.feature_flags();
-		assert!(returned_features.is_empty());
+		assert!(opts(vec![],).feature_args().is_empty());
+		// conflicting default is replaced
+		assert_eq!(opts(vec![Feature::Rgb],).feature_args(), [
+			"--no-default-features",
+			"--features",
+			"rgb",
+		]);
+		assert_eq!(opts(vec![Feature::Bltonly],).feature_args(), [
+			"--no-default-features",
+			"--features",
+			"bltonly",
+		]);
+
+		let flags: Vec<String,> = opts(vec![Feature::Bgr],)
+			.feature_flags()
+			.into_iter()
+			.map(Into::into,)
+			.collect();
+		assert_eq!(flags, ["bgr"]);
 	}
 
 	#[test]
@@ -0,0 +1,268 @@
+//! # Parallel Builds
+//!
+//! Runs one command per crate of a [`CrateGraph`]. A crate starts once all
+//! crates it depends on have succeeded, and at most `workers` commands run at
+//! the same time. Output of each command is prefixed with its crate name.
+//!
+//! When a command fails, no further command is started and running ones are
+//! killed, then a summary of every crate is printed.
+
+use crate::decl_manage::graph::CrateGraph;
+use anyhow::Context as _;
+use anyhow::Result as Rslt;
+use anyhow::bail;
+use colored::Colorize;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// How often running commands are checked for cancellation
+const POLL_INTERVAL: Duration = Duration::from_millis(50,);
+
+/// Outcome of the command of a crate
+#[derive(Clone, Copy, Debug, PartialEq, Eq, strum_macros::AsRefStr,)]
+#[strum(serialize_all = "lowercase")]
+pub enum JobState {
+	Pending,
+	Running,
+	Succeeded,
+	Failed,
+	/// Killed because another crate failed
+	Cancelled,
+	/// Never started because another crate failed
+	Skipped,
+}
+
+/// Runs `cmds` in dependency order and prints a summary on failure
+///
+/// # Arguments
+///
+/// * `graph` - Dependencies between crates
+/// * `cmds` - Command of each crate, in order of [`CrateGraph::nodes`]
+/// * `workers` - Maximum number of commands running at once
+///
+/// # Errors
+///
+/// Returns an error naming the crates which failed
+pub fn run_parallel(
+	graph: &CrateGraph,
+	cmds: Vec<Command,>,
+	workers: usize,
+) -> Rslt<(),> {
+	let states = run_jobs(graph, cmds, workers,);
+	if states.iter().all(|s| *s == JobState::Succeeded,) {
+		return Ok((),);
+	}
+
+	eprintln!("\n{}", "build summary".bold());
+	for (node, state,) in graph.nodes().iter().zip(&states,) {
+		let state = match state {
+			JobState::Succeeded => state.as_ref().green(),
+			JobState::Failed => state.as_ref().red().bold(),
+			_ => state.as_ref().yellow(),
+		};
+		eprintln!("  {:<24} {state}", node.name);
+	}
+
+	let failed: Vec<&str,> = graph
+		.nodes()
+		.iter()
+		.zip(&states,)
+		.filter(|(_, state,)| **state == JobState::Failed,)
+		.map(|(node, _,)| node.name.as_str(),)
+		.collect();
+	bail!("failed to build {}", failed.join(", "))
+}
+
+/// Runs `cmds` in dependency order and returns the outcome of each
+///
+/// See [`run_parallel`] for arguments.
+pub fn run_jobs(
+	graph: &CrateGraph,
+	cmds: Vec<Command,>,
+	workers: usize,
+) -> Vec<JobState,> {
+	assert_eq!(graph.len(), cmds.len(), "one command per crate is required");
+
+	let mut cmds: Vec<Option<Command,>,> =
+		cmds.into_iter().map(Some,).collect();
+	let mut states = vec![JobState::Pending; graph.len()];
+	let cancel = Arc::new(AtomicBool::new(false,),);
+	let (tx, rx,) = mpsc::channel();
+	let mut running = 0;
+
+	loop {
+		if !cancel.load(Ordering::Relaxed,) {
+			for (i, node,) in graph.nodes().iter().enumerate() {
+				if running >= workers.max(1,) {
+					break;
+				}
+				let ready = states[i] == JobState::Pending
+					&& node
+						.deps
+						.iter()
+						.all(|d| states[*d] == JobState::Succeeded,);
+				if !ready {
+					continue;
+				}
+
+				states[i] = JobState::Running;
+				running += 1;
+				let name = node.name.clone();
+				let mut cmd = cmds[i].take().expect("each job starts once",);
+				let cancel = cancel.clone();
+				let tx = tx.clone();
+				thread::spawn(move || {
+					let state = run_job(&name, &mut cmd, &cancel,)
+						.unwrap_or_else(|e| {
+							eprintln!("{} {e:#}", prefix(&name,));
+							JobState::Failed
+						},);
+					let _ = tx.send((i, state,),);
+				},);
+			}
+		}
+
+		if running == 0 {
+			break;
+		}
+		let (i, state,) = rx.recv().expect("workers always report back",);
+		running -= 1;
+		states[i] = state;
+		if state == JobState::Failed {
+			cancel.store(true, Ordering::Relaxed,);
+		}
+	}
+
+	states
+		.iter_mut()
+		.filter(|s| **s == JobState::Pending,)
+		.for_each(|s| *s = JobState::Skipped,);
+	states
+}
+
+fn run_job(
+	name: &str,
+	cmd: &mut Command,
+	cancel: &AtomicBool,
+) -> Rslt<JobState,> {
+	let mut child = cmd
+		.stdout(Stdio::piped(),)
+		.stderr(Stdio::piped(),)
+		.spawn()
+		.with_context(|| format!("failed to spawn {cmd:?}"),)?;
+	let stdout = child.stdout.take().expect("stdout is piped",);
+	let stderr = child.stderr.take().expect("stderr is piped",);
+	let stdout = forward(name, stdout, false,);
+	let stderr = forward(name, stderr, true,);
+
+	let state = loop {
+		if let Some(status,) = child.try_wait()? {
+			break if status.success() {
+				JobState::Succeeded
+			} else {
+				JobState::Failed
+			};
+		}
+		if cancel.load(Ordering::Relaxed,) {
+			let _ = child.kill();
+			let _ = child.wait();
+			break JobState::Cancelled;
+		}
+		thread::sleep(POLL_INTERVAL,);
+	};
+
+	// grandchildren of a killed command may keep pipes open. don't wait for
+	// them
+	if state != JobState::Cancelled {
+		let _ = stdout.join();
+		let _ = stderr.join();
+	}
+	Ok(state,)
+}
+
+/// Copies `pipe` line by line to stdout or stderr with crate name prefixed
+fn forward(
+	name: &str,
+	pipe: impl Read + Send + 'static,
+	to_stderr: bool,
+) -> JoinHandle<(),> {
+	let prefix = prefix(name,);
+	thread::spawn(move || {
+		for line in BufReader::new(pipe,).lines().map_while(Result::ok,) {
+			if to_stderr {
+				eprintln!("{prefix} {line}");
+			} else {
+				println!("{prefix} {line}");
+			}
+		}
+	},)
+}
+
+fn prefix(name: &str,) -> String {
+	format!("[{name}]").cyan().bold().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn graph(deps: &[(&str, &[&str],)],) -> CrateGraph {
+		CrateGraph::from_deps(deps,).unwrap()
+	}
+
+	fn sh(script: &str,) -> Command {
+		let mut cmd = Command::new("sh",);
+		cmd.args(["-c", script,],);
+		cmd
+	}
+
+	#[test]
+	fn test_dependencies_finish_first() {
+		// other test processes may run this test at the same time
+		let name = format!("oso_parallel_order_{}", std::process::id());
+		let dir = std::env::temp_dir().join(name,);
+		let _ = std::fs::remove_dir_all(&dir,);
+		std::fs::create_dir_all(&dir,).unwrap();
+		let marker = dir.join("a",).display().to_string();
+
+		let g = graph(&[("a", &[],), ("b", &["a",],),],);
+		let cmds = vec![
+			sh(&format!("sleep 0.2 && touch {marker}"),),
+			sh(&format!("test -f {marker}"),),
+		];
+		let states = run_jobs(&g, cmds, 4,);
+		assert_eq!(states, [JobState::Succeeded, JobState::Succeeded]);
+		std::fs::remove_dir_all(&dir,).unwrap();
+	}
+
+	#[test]
+	fn test_failure_stops_other_jobs() {
+		let g = graph(&[("a", &[],), ("b", &["a",],), ("c", &[],),],);
+		let cmds =
+			vec![sh("sleep 0.1 && false",), sh("true",), sh("sleep 10",)];
+		let states = run_jobs(&g, cmds, 2,);
+		assert_eq!(states, [
+			JobState::Failed,
+			JobState::Skipped,
+			JobState::Cancelled
+		]);
+	}
+
+	#[test]
+	fn test_summary_names_failed_crates() {
+		let g = graph(&[("ok", &[],), ("broken", &[],),],);
+		let e = run_parallel(&g, vec![sh("true",), sh("exit 3",)], 1,)
+			.unwrap_err();
+		assert_eq!(e.to_string(), "failed to build broken");
+	}
+}
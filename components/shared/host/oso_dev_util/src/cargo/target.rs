@@ -0,0 +1,332 @@
+//! # Custom Target Specifications
+//!
+//! The kernel runs on bare metal with its own link address and entry point,
+//! which no built-in rustc target provides. Instead of maintaining JSON files
+//! by hand, [`TargetSpec`] generates the specification for each [`Arch`],
+//! writes it under [`TARGETS_DIR`] and checks that rustc accepts it.
+//!
+//! Plain `cargo build` in the kernel crate reads the specifications in
+//! [`CHECKED_IN_SPECS`] next to its manifest. They are the output of
+//! [`TargetSpec::to_json`], and a test fails when the two drift apart.
+//!
+//! ```rust,no_run
+//! use oso_dev_util::cargo::Arch;
+//! use oso_dev_util::cargo::target::TargetSpec;
+//! use oso_dev_util_helper::fs::project_root_path;
+//! use std::process::Command;
+//!
+//! let spec = TargetSpec::kernel(Arch::Aarch64,);
+//! let path = spec.install(&project_root_path().unwrap(),).unwrap();
+//! let mut cargo = Command::new("cargo",);
+//! cargo.arg("build",).args(TargetSpec::cargo_args(&path,),);
+//! ```
+
+use super::Arch;
+use anyhow::Context as _;
+use anyhow::Result as Rslt;
+use anyhow::bail;
+use std::ffi::OsString;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Directory of generated specifications, relative to the project root
+pub const TARGETS_DIR: &str = "target/xtask/targets";
+/// Specifications checked in next to the kernel manifest
+pub const CHECKED_IN_SPECS: [(Arch, &str,); 2] = [
+	(Arch::Aarch64, "aarch64-unknown-none-elf.json",),
+	(Arch::X86_64, "x86_64-unknown-none-elf.json",),
+];
+
+/// Custom target specification of rustc
+///
+/// Only fields which differ from rustc's defaults are kept.
+///
+/// # Fields
+///
+/// * `arch` - Architecture the specification is for
+/// * `llvm_target` - Target triple passed to LLVM
+/// * `data_layout` - Must equal the default layout of LLVM for `llvm_target`
+/// * `features` - Target features, e.g. `+strict-align`
+/// * `code_model` - LLVM code model
+/// * `llvm_abiname` - ABI name, if the architecture has several
+/// * `rustc_abi` - ABI of rustc, e.g. to pass floats in integer registers
+/// * `max_atomic_width` - Widest atomic operation in bits
+/// * `entry` - Symbol of the entry point
+/// * `image_base` - Link address of the image
+#[derive(Clone, Debug, PartialEq, Eq,)]
+pub struct TargetSpec {
+	pub arch:             Arch,
+	pub llvm_target:      &'static str,
+	pub data_layout:      &'static str,
+	pub features:         &'static str,
+	pub code_model:       &'static str,
+	pub llvm_abiname:     Option<&'static str,>,
+	pub rustc_abi:        Option<&'static str,>,
+	pub max_atomic_width: u32,
+	pub entry:            &'static str,
+	pub image_base:       u64,
+}
+
+impl TargetSpec {
+	/// Specification of the kernel image
+	pub fn kernel(arch: Arch,) -> Self {
+		match arch {
+			Arch::Aarch64 => Self {
+				arch,
+				llvm_target: "aarch64-unknown-none",
+				data_layout: "e-m:e-p270:32:32-p271:32:32-p272:64:64-i8:8:32-\
+				              i16:16:32-i64:64-i128:128-n32:64-S128-Fn32",
+				// the kernel starts with MMU off, where unaligned access faults.
+				// the rest is what LLVM enables for aarch64 anyway, spelled
+				// out as rustc's built-in aarch64-unknown-none does
+				features: "+v8a,+strict-align,+neon",
+				code_model: "small",
+				llvm_abiname: None,
+				rustc_abi: None,
+				// `ldxp`/`stxp` are part of ARMv8.0, as in aarch64-unknown-none
+				max_atomic_width: 128,
+				entry: "kernel_main",
+				image_base: 0x4000_0000,
+			},
+			Arch::Riscv64 => Self {
+				arch,
+				llvm_target: "riscv64",
+				data_layout: "e-m:e-p:64:64-i64:64-i128:128-n32:64-S128",
+				// no floating point registers in the kernel
+				features: "+m,+a,+c,+zicsr,+zifencei",
+				code_model: "medium",
+				llvm_abiname: Some("lp64",),
+				rustc_abi: None,
+				max_atomic_width: 64,
+				entry: "kernel_main",
+				// right after OpenSBI
+				image_base: 0x8020_0000,
+			},
+			Arch::X86_64 => Self {
+				arch,
+				llvm_target: "x86_64-unknown-none",
+				data_layout: "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-\
+				              i128:128-f80:128-n8:16:32:64-S128",
+				// interrupt handlers don't save SIMD registers
+				features: "-mmx,-sse,+soft-float",
+				code_model: "kernel",
+				llvm_abiname: None,
+				rustc_abi: Some("x86-softfloat",),
+				max_atomic_width: 64,
+				entry: "kernel_main",
+				image_base: 0x10_0000,
+			},
+		}
+	}
+
+	/// Name of the target. Cargo names the output directory after it
+	pub fn name(&self,) -> String {
+		format!("{}-oso-none-elf", self.rust_arch())
+	}
+
+	/// Value of `target_arch` cfg
+	pub fn rust_arch(&self,) -> &'static str {
+		match self.arch {
+			Arch::Aarch64 => "aarch64",
+			Arch::Riscv64 => "riscv64",
+			Arch::X86_64 => "x86_64",
+		}
+	}
+
+	/// Path of the specification under `root`
+	pub fn path(&self, root: &Path,) -> PathBuf {
+		root.join(TARGETS_DIR,).join(format!("{}.json", self.name()),)
+	}
+
+	pub fn to_json(&self,) -> String {
+		let mut fields = vec![
+			("llvm-target", quote(self.llvm_target,),),
+			("data-layout", quote(self.data_layout,),),
+			("arch", quote(self.rust_arch(),),),
+			("target-endian", quote("little",),),
+			("target-pointer-width", "64".into(),),
+			("max-atomic-width", self.max_atomic_width.to_string(),),
+			("os", quote("none",),),
+			("executables", "true".into(),),
+			// rustc's current name of `ld.lld`
+			("linker-flavor", quote("gnu-lld",),),
+			("linker", quote("rust-lld",),),
+			("panic-strategy", quote("abort",),),
+			// linked at `image_base`. rustc defaults to `pic` otherwise
+			("relocation-model", quote("static",),),
+			("relro-level", quote("off",),),
+			("disable-redzone", "true".into(),),
+			("code-model", quote(self.code_model,),),
+			("features", quote(self.features,),),
+		];
+		if let Some(abi,) = self.llvm_abiname {
+			fields.push(("llvm-abiname", quote(abi,),),);
+		}
+		if let Some(abi,) = self.rustc_abi {
+			fields.push(("rustc-abi", quote(abi,),),);
+		}
+		let link_args = [
+			format!("--entry={}", self.entry),
+			"--static".into(),
+			format!("--image-base={:#x}", self.image_base),
+		]
+		.map(|arg| quote(&arg,),)
+		.join(", ",);
+		fields.push((
+			"post-link-args",
+			format!("{{\n\t\t\"gnu-lld\": [{link_args}]\n\t}}"),
+		),);
+
+		let mut json = String::from("{\n",);
+		for (i, (key, value,),) in fields.iter().enumerate() {
+			let comma = if i + 1 == fields.len() { "" } else { "," };
+			let _ = writeln!(json, "\t\"{key}\": {value}{comma}");
+		}
+		json.push_str("}\n",);
+		json
+	}
+
+	/// Writes the specification under `root` and validates it
+	///
+	/// The file is rewritten only if its content changes, so cargo does not
+	/// rebuild everything on each invocation.
+	///
+	/// # Returns
+	///
+	/// Path of the written specification
+	pub fn install(&self, root: &Path,) -> Rslt<PathBuf,> {
+		let path = self.path(root,);
+		let json = self.to_json();
+		if std::fs::read_to_string(&path,).ok().as_ref() != Some(&json,) {
+			let dir = path.parent().expect("spec path always has parent",);
+			std::fs::create_dir_all(dir,).with_context(|| {
+				format!("failed to create {}", dir.display())
+			},)?;
+			std::fs::write(&path, json,).with_context(|| {
+				format!("failed to write {}", path.display())
+			},)?;
+		}
+		self.validate(&path,)?;
+		Ok(path,)
+	}
+
+	/// Asks rustc to load the specification at `path`
+	///
+	/// # Errors
+	///
+	/// Returns an error if rustc rejects the file, or the file describes a
+	/// different architecture
+	pub fn validate(&self, path: &Path,) -> Rslt<(),> {
+		let out = Command::new("rustc",)
+			.args(["-Zunstable-options", "--print", "cfg", "--target",],)
+			.arg(path,)
+			.output()
+			.context("failed to run rustc",)?;
+		if !out.status.success() {
+			bail!(
+				"rustc rejected {}:\n{}",
+				path.display(),
+				String::from_utf8_lossy(&out.stderr)
+			);
+		}
+
+		let expected = format!("target_arch=\"{}\"", self.rust_arch());
+		let cfg = String::from_utf8_lossy(&out.stdout,);
+		if !cfg.lines().any(|line| line == expected,) {
+			bail!("{} is not a target for {}", path.display(), self.arch);
+		}
+		Ok((),)
+	}
+
+	/// Arguments which make cargo build for the specification at `path`
+	pub fn cargo_args(path: &Path,) -> [OsString; 3] {
+		["-Zjson-target-spec".into(), "--target".into(), path.into(),]
+	}
+}
+
+fn quote(s: &str,) -> String {
+	format!("\"{}\"", s.replace('\\', "\\\\",).replace('"', "\\\"",))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use clap::ValueEnum;
+	use oso_dev_util_helper::fs::all_crates;
+
+	/// `-Zunstable-options` of [`TargetSpec::validate`] needs a nightly rustc
+	fn nightly() -> bool {
+		let out = Command::new("rustc",).arg("-vV",).output().unwrap();
+		let version = String::from_utf8_lossy(&out.stdout,);
+		let nightly =
+			version.contains("-nightly",) || version.contains("-dev",);
+		if !nightly {
+			eprintln!("skipped: rustc is not nightly");
+		}
+		nightly
+	}
+
+	/// empty directory for `test`, not shared with other test processes
+	fn temp_root(test: &str,) -> PathBuf {
+		let name = format!("oso_target_spec_{test}_{}", std::process::id());
+		let root = std::env::temp_dir().join(name,);
+		let _ = std::fs::remove_dir_all(&root,);
+		root
+	}
+
+	#[test]
+	fn test_spec_is_accepted_by_rustc() {
+		if !nightly() {
+			return;
+		}
+		let root = temp_root("accepted",);
+		for arch in Arch::value_variants() {
+			let spec = TargetSpec::kernel(*arch,);
+			let path = spec.install(&root,).unwrap();
+			assert!(path.ends_with(format!("{}.json", spec.name())));
+			assert_eq!(std::fs::read_to_string(&path).unwrap(), spec.to_json());
+		}
+		std::fs::remove_dir_all(&root,).unwrap();
+	}
+
+	#[test]
+	fn test_validate_rejects_other_arch() {
+		if !nightly() {
+			return;
+		}
+		let root = temp_root("mismatch",);
+		let path = TargetSpec::kernel(Arch::Aarch64,).install(&root,).unwrap();
+		assert!(TargetSpec::kernel(Arch::Riscv64).validate(&path).is_err());
+		std::fs::remove_dir_all(&root,).unwrap();
+	}
+
+	#[test]
+	fn test_checked_in_specs_are_generated() {
+		let (_, first,) = CHECKED_IN_SPECS[0];
+		let kernel = all_crates()
+			.unwrap()
+			.into_iter()
+			.find(|dir| dir.join(first,).exists(),)
+			.unwrap();
+		for (arch, name,) in CHECKED_IN_SPECS {
+			let spec = kernel.join(name,);
+			let json = TargetSpec::kernel(arch,).to_json();
+			let checked_in = std::fs::read_to_string(&spec,).unwrap();
+			assert_eq!(
+				checked_in,
+				json,
+				"{} is out of date. write `TargetSpec::to_json` into it",
+				spec.display()
+			);
+		}
+	}
+
+	#[test]
+	fn test_post_link_args() {
+		let json = TargetSpec::kernel(Arch::Aarch64,).to_json();
+		assert!(json.contains("\"gnu-lld\": [\"--entry=kernel_main\""));
+		assert!(json.contains("\"--image-base=0x40000000\"]"));
+	}
+}
@@ -0,0 +1,185 @@
+//! # Shared Command Line Flags
+//!
+//! Composable `clap` argument groups shared by xtask and other host tools.
+//! Each tool flattens the groups it needs into its own parser, so common flags
+//! have the same name, short form and meaning everywhere.
+//!
+//! | group          | flags                                          |
+//! | -------------- | ---------------------------------------------- |
+//! | [`TargetArgs`] | `-a/--arch`                                    |
+//! | [`BuildArgs`]  | `-b/--build-mode`, `-f/--feature-flags`, `-j`  |
+//! | [`OutputArgs`] | `-v`, `-q`, `--dry-run`, `--keep-ansi`         |
+//!
+//! ## Usage
+//!
+//! ```rust,no_run
+//! use clap::Parser;
+//! use oso_dev_util::cli::OutputArgs;
+//! use oso_dev_util::cli::TargetArgs;
+//!
+//! /// a host tool which only cares about architecture
+//! #[derive(Parser,)]
+//! struct ObjdumpCli {
+//!     #[command(flatten)]
+//!     target: TargetArgs,
+//!     #[command(flatten)]
+//!     output: OutputArgs,
+//!     file:   std::path::PathBuf,
+//! }
+//!
+//! let cli = ObjdumpCli::parse();
+//! println!("{:?}", cli.target.arch());
+//! ```
+
+use crate::cargo::Arch;
+use crate::cargo::BuildMode;
+use crate::cargo::Feature;
+
+/// Target selection
+#[derive(clap::Args, Clone, Copy, Debug, Default, PartialEq, Eq,)]
+pub struct TargetArgs {
+	/// target architecture [default: aarch64]
+	#[arg(value_enum, short, long)]
+	pub arch: Option<Arch,>,
+}
+
+impl TargetArgs {
+	pub fn arch(&self,) -> Arch {
+		self.arch.unwrap_or_default()
+	}
+}
+
+/// How to compile
+#[derive(clap::Args, Clone, Default,)]
+pub struct BuildArgs {
+	/// build profile [default: debug]
+	#[arg(value_enum, short, long)]
+	pub build_mode:    Option<BuildMode,>,
+	/// cargo features to enable. comma separated or repeated
+	#[arg(short, long, value_delimiter = ',')]
+	pub feature_flags: Option<Vec<Feature,>,>,
+	/// number of crates built at once [default: number of cpus]
+	#[arg(short, long)]
+	pub jobs:          Option<usize,>,
+}
+
+impl BuildArgs {
+	pub fn build_mode(&self,) -> BuildMode {
+		self.build_mode.unwrap_or_default()
+	}
+
+	pub fn feature_flags(&self,) -> Vec<Feature,> {
+		self.feature_flags.clone().unwrap_or_default()
+	}
+
+	pub fn jobs(&self,) -> usize {
+		self.jobs
+			.or_else(|| {
+				std::thread::available_parallelism().ok().map(usize::from,)
+			},)
+			.unwrap_or(1,)
+			.max(1,)
+	}
+}
+
+/// Amount of output and whether to actually execute commands
+///
+/// Flags of this group are global: they are accepted before and after
+/// subcommands.
+#[derive(clap::Args, Clone, Copy, Debug, Default, PartialEq, Eq,)]
+pub struct OutputArgs {
+	/// print more output. repeat for even more
+	#[arg(short, long, action = clap::ArgAction::Count, global = true)]
+	pub verbose:   u8,
+	/// print errors only
+	#[arg(short, long, global = true, conflicts_with = "verbose")]
+	pub quiet:     bool,
+	/// print commands instead of executing them
+	#[arg(long, global = true)]
+	pub dry_run:   bool,
+	/// keep ANSI escape sequences in serial logs
+	#[arg(long, global = true)]
+	pub keep_ansi: bool,
+}
+
+impl OutputArgs {
+	pub fn verbosity(&self,) -> Verbosity {
+		match (self.quiet, self.verbose,) {
+			(true, _,) => Verbosity::Quiet,
+			(false, 0,) => Verbosity::Normal,
+			(false, 1,) => Verbosity::Verbose,
+			(false, _,) => Verbosity::Trace,
+		}
+	}
+}
+
+/// Resolved output level of [`OutputArgs`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord,)]
+pub enum Verbosity {
+	Quiet,
+	#[default]
+	Normal,
+	Verbose,
+	Trace,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use clap::Parser;
+
+	#[derive(Parser,)]
+	struct TestCli {
+		#[command(flatten)]
+		target: TargetArgs,
+		#[command(flatten)]
+		build:  BuildArgs,
+		#[command(flatten)]
+		output: OutputArgs,
+	}
+
+	fn parse(args: &[&str],) -> TestCli {
+		TestCli::try_parse_from([&["test",], args,].concat(),).unwrap()
+	}
+
+	#[test]
+	fn test_defaults() {
+		let cli = parse(&[],);
+		assert_eq!(cli.target.arch(), Arch::Aarch64);
+		assert_eq!(cli.build.build_mode(), BuildMode::Debug);
+		assert!(cli.build.feature_flags().is_empty());
+		assert!(cli.build.jobs() >= 1);
+		assert_eq!(cli.output.verbosity(), Verbosity::Normal);
+		assert!(!cli.output.dry_run);
+	}
+
+	#[test]
+	fn test_long_and_short_flags() {
+		let short = parse(&["-a", "riscv64", "-b", "release", "-vv",],);
+		let long = parse(&[
+			"--arch",
+			"riscv64",
+			"--build-mode",
+			"release",
+			"--verbose",
+			"--verbose",
+		],);
+		for cli in [short, long,] {
+			assert_eq!(cli.target.arch(), Arch::Riscv64);
+			assert_eq!(cli.build.build_mode(), BuildMode::Release);
+			assert_eq!(cli.output.verbosity(), Verbosity::Trace);
+		}
+		assert_eq!(parse(&["-a", "x86_64"]).target.arch(), Arch::X86_64);
+	}
+
+	#[test]
+	fn test_quiet_conflicts_with_verbose() {
+		assert!(TestCli::try_parse_from(["test", "-q", "-v",]).is_err());
+		assert_eq!(parse(&["-q",]).output.verbosity(), Verbosity::Quiet);
+	}
+
+	#[test]
+	fn test_dry_run() {
+		assert!(parse(&["--dry-run",]).output.dry_run);
+	}
+}
@@ -0,0 +1,94 @@
+//! # Initial Ramdisk Writer
+//!
+//! Writes `newc` cpio archives, the format the kernel mounts as its initial
+//! ramdisk. Every entry is a regular file readable by everyone, with inode
+//! numbers, owners and times left at `0`, so the same files always give the
+//! same archive.
+//!
+//! ```rust
+//! use oso_dev_util::cpio;
+//!
+//! let initrd = cpio::archive(&[("autoexec.osh", b"tasks\n".as_slice(),)],);
+//! assert!(initrd.starts_with(cpio::MAGIC,));
+//! ```
+
+/// Magic of each `newc` header
+pub const MAGIC: &[u8] = b"070701";
+/// Name of the entry ending an archive
+pub const TRAILER: &str = "TRAILER!!!";
+/// Mode of the files of an archive, a regular file with `rw-r--r--`
+pub const FILE_MODE: u32 = 0o100_644;
+
+/// Archive of `files` as path and contents, in the given order
+///
+/// # Panics
+///
+/// Panics if a path or a file does not fit the 32 bit fields of a header
+pub fn archive(files: &[(&str, &[u8],)],) -> Vec<u8,> {
+	let mut out = vec![];
+	for (path, contents,) in files {
+		entry(&mut out, path, FILE_MODE, contents,);
+	}
+	entry(&mut out, TRAILER, 0, &[],);
+	out
+}
+
+/// Appends a header, the name and the contents, each padded to 4 bytes
+fn entry(out: &mut Vec<u8,>, path: &str, mode: u32, contents: &[u8],) {
+	let size = u32::try_from(contents.len(),).expect("file exceeds 4 GiB",);
+	let name_size = u32::try_from(path.len() + 1,).expect("path is too long",);
+	// ino, mode, uid, gid, nlink, mtime, filesize, devmajor, devminor,
+	// rdevmajor, rdevminor, namesize, check
+	let fields = [0, mode, 0, 0, 1, 0, size, 0, 0, 0, 0, name_size, 0,];
+	out.extend_from_slice(MAGIC,);
+	for field in fields {
+		out.extend_from_slice(format!("{field:08X}").as_bytes(),);
+	}
+	out.extend_from_slice(path.as_bytes(),);
+	out.push(0,);
+	out.resize(out.len().next_multiple_of(4,), 0,);
+	out.extend_from_slice(contents,);
+	out.resize(out.len().next_multiple_of(4,), 0,);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// `(name, mode, contents)` of each entry of `archive`
+	fn entries(mut archive: &[u8],) -> Vec<(String, u32, Vec<u8,>,),> {
+		let field = |header: &[u8], i: usize| {
+			let hex = std::str::from_utf8(&header[6 + i * 8..14 + i * 8],);
+			u32::from_str_radix(hex.unwrap(), 16,).unwrap() as usize
+		};
+		let mut entries = vec![];
+		while !archive.is_empty() {
+			assert!(archive.starts_with(MAGIC));
+			let (mode, size, name_size,) =
+				(field(archive, 1,), field(archive, 6,), field(archive, 11,),);
+			let name_end = 110 + name_size;
+			let name = &archive[110..name_end - 1];
+			assert_eq!(archive[name_end - 1], 0);
+			let data = name_end.next_multiple_of(4,);
+			let name = String::from_utf8(name.to_vec(),).unwrap();
+			let contents = archive[data..data + size].to_vec();
+			entries.push((name, mode as u32, contents,),);
+			archive = &archive[(data + size).next_multiple_of(4,)..];
+		}
+		entries
+	}
+
+	#[test]
+	fn test_archive() {
+		let files: [(_, &[u8],); 2] =
+			[("autoexec.osh", b"tasks\n",), ("etc/a", b"",),];
+		let initrd = archive(&files,);
+		assert_eq!(initrd.len() % 4, 0);
+		assert_eq!(entries(&initrd), [
+			("autoexec.osh".to_string(), FILE_MODE, b"tasks\n".to_vec(),),
+			("etc/a".to_string(), FILE_MODE, vec![],),
+			(TRAILER.to_string(), 0, vec![],),
+		]);
+		assert_eq!(entries(&archive(&[],)).len(), 1);
+	}
+}
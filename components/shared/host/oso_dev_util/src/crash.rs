@@ -0,0 +1,296 @@
+//! # Crash Dump Decoder
+//!
+//! Host side counterpart of the kernel's `base::crash` module. Decodes the
+//! framed dump written on panic and renders it with addresses resolved
+//! against the symbols of the kernel ELF.
+//!
+//! A dump is read either as raw bytes starting with [`MAGIC`], as copied out
+//! of a reserved memory region, or as hex lines between [`BEGIN_MARKER`] and
+//! [`END_MARKER`] anywhere in a text such as a serial log. When a log holds
+//! several dumps, the last one is decoded.
+//!
+//! The format is described in the kernel module. Both sides must agree on
+//! [`VERSION`].
+
+use crate::elf::Symbol;
+use crate::elf::symbolize;
+use anyhow::Result as Rslt;
+use anyhow::bail;
+use anyhow::ensure;
+use std::fmt::Write;
+
+/// first bytes of every dump
+pub const MAGIC: &[u8; 4] = b"OSOD";
+/// version of the format understood by [`CrashDump::parse`]
+pub const VERSION: u8 = 1;
+/// line before a hex encoded dump
+pub const BEGIN_MARKER: &str = "-----BEGIN OSO CRASH DUMP-----";
+/// line after a hex encoded dump
+pub const END_MARKER: &str = "-----END OSO CRASH DUMP-----";
+
+const TAG_END: u8 = 0;
+const TAG_MESSAGE: u8 = 1;
+const TAG_REGISTERS: u8 = 2;
+const TAG_BACKTRACE: u8 = 3;
+const TAG_LOG_TAIL: u8 = 4;
+const TAG_MEMORY: u8 = 5;
+
+/// contents of a crash dump
+#[derive(Debug, Clone, PartialEq, Eq, Default,)]
+pub struct CrashDump {
+	pub message:   String,
+	pub registers: Vec<(String, u64,),>,
+	/// return addresses, innermost first
+	pub backtrace: Vec<u64,>,
+	pub log_tail:  Option<String,>,
+	pub memory:    Vec<(String, u64,),>,
+}
+
+impl CrashDump {
+	/// decodes a dump file, which is either a raw dump or a text holding a
+	/// hex encoded one
+	pub fn decode(file: &[u8],) -> Rslt<Self,> {
+		if file.starts_with(MAGIC,) {
+			return Self::parse(file,);
+		}
+		let text = String::from_utf8_lossy(file,);
+		Self::parse(&find_hex(&text, BEGIN_MARKER, END_MARKER,)?,)
+	}
+
+	/// parses a raw dump, checking its checksum
+	pub fn parse(bytes: &[u8],) -> Rslt<Self,> {
+		ensure!(bytes.starts_with(MAGIC,), "not a crash dump: bad magic");
+		ensure!(bytes.len() > MAGIC.len(), "crash dump has no version");
+		let version = bytes[MAGIC.len()];
+		ensure!(
+			version == VERSION,
+			"crash dump version {version} is not supported, expected {VERSION}"
+		);
+
+		let mut dump = Self::default();
+		let mut at = MAGIC.len() + 1;
+		loop {
+			let Some(&[tag, lo, hi,],) = bytes.get(at..at + 3,) else {
+				bail!("crash dump is cut at {at:#x} before its end record")
+			};
+			let len = u16::from_le_bytes([lo, hi,],) as usize;
+			let Some(payload,) = bytes.get(at + 3..at + 3 + len,) else {
+				bail!("record at {at:#x} exceeds the crash dump")
+			};
+
+			match tag {
+				TAG_END => {
+					ensure!(len == 4, "end record has length {len}");
+					let expected = u32::from_le_bytes(payload.try_into()?,);
+					let actual = crc32(&bytes[..at],);
+					ensure!(
+						expected == actual,
+						"crash dump checksum mismatch: {actual:#010x} != \
+						 {expected:#010x}"
+					);
+					return Ok(dump,);
+				},
+				TAG_MESSAGE => dump.message = text(payload,),
+				TAG_REGISTERS => dump.registers = pairs(payload,)?,
+				TAG_BACKTRACE => {
+					ensure!(len.is_multiple_of(8,), "backtrace is cut");
+					dump.backtrace = payload
+						.chunks_exact(8,)
+						.map(|addr| {
+							u64::from_le_bytes(addr.try_into().unwrap(),)
+						},)
+						.collect();
+				},
+				TAG_LOG_TAIL => dump.log_tail = Some(text(payload,),),
+				TAG_MEMORY => dump.memory = pairs(payload,)?,
+				// records of later versions of the kernel
+				_ => (),
+			}
+			at += 3 + len;
+		}
+	}
+
+	/// human readable report. backtrace addresses are resolved with
+	/// `symbols`, which may be empty
+	pub fn render(&self, symbols: &[Symbol],) -> String {
+		let mut out = String::new();
+		writeln!(out, "panic: {}", self.message).unwrap();
+
+		if !self.registers.is_empty() {
+			writeln!(out, "\nregisters:").unwrap();
+			write_pairs(&mut out, &self.registers, true,);
+		}
+
+		writeln!(out, "\nbacktrace:").unwrap();
+		if self.backtrace.is_empty() {
+			writeln!(out, "  (empty)").unwrap();
+		}
+		for (i, addr,) in self.backtrace.iter().enumerate() {
+			write!(out, "  #{i:<2} {addr:#018x}").unwrap();
+			match symbolize(symbols, *addr,) {
+				Some((symbol, offset,),) => {
+					let name = rustc_demangle::demangle(&symbol.name,);
+					writeln!(out, "  {name:#}+{offset:#x}").unwrap();
+				},
+				None => writeln!(out, "  ??").unwrap(),
+			}
+		}
+
+		if !self.memory.is_empty() {
+			writeln!(out, "\nmemory:").unwrap();
+			write_pairs(&mut out, &self.memory, false,);
+		}
+
+		if let Some(log,) = &self.log_tail {
+			writeln!(out, "\nlog tail:").unwrap();
+			for line in log.lines() {
+				writeln!(out, "  {line}").unwrap();
+			}
+		}
+		out
+	}
+}
+
+/// bytes of the last hex encoded dump between `begin_marker` and
+/// `end_marker` in `text`
+///
+/// line noise such as `\r` and surrounding whitespace is ignored, as the
+/// dump usually comes through a serial console
+pub(crate) fn find_hex(
+	text: &str,
+	begin_marker: &str,
+	end_marker: &str,
+) -> Rslt<Vec<u8,>,> {
+	let Some(begin,) = text.rfind(begin_marker,) else {
+		bail!("no dump found: missing `{begin_marker}`")
+	};
+	let body = &text[begin + begin_marker.len()..];
+	let Some(end,) = body.find(end_marker,) else {
+		bail!("dump is cut: missing `{end_marker}`")
+	};
+
+	let hex: String = body[..end].split_whitespace().collect();
+	ensure!(hex.len().is_multiple_of(2,), "dump has odd hex digits");
+	(0..hex.len())
+		.step_by(2,)
+		.map(|i| Ok(u8::from_str_radix(&hex[i..i + 2], 16,)?,),)
+		.collect()
+}
+
+fn text(payload: &[u8],) -> String {
+	String::from_utf8_lossy(payload,).into_owned()
+}
+
+fn pairs(mut payload: &[u8],) -> Rslt<Vec<(String, u64,),>,> {
+	let mut pairs = vec![];
+	while let Some((&len, rest,),) = payload.split_first() {
+		let len = len as usize;
+		ensure!(rest.len() >= len + 8, "register record is cut");
+		let value = u64::from_le_bytes(rest[len..len + 8].try_into()?,);
+		pairs.push((text(&rest[..len],), value,),);
+		payload = &rest[len + 8..];
+	}
+	Ok(pairs,)
+}
+
+/// registers are written in hex, statistics in decimal
+fn write_pairs(out: &mut String, pairs: &[(String, u64,)], hex: bool,) {
+	let width = pairs.iter().map(|(name, _,)| name.len(),).max().unwrap_or(0,);
+	for (name, value,) in pairs {
+		if hex {
+			writeln!(out, "  {name:<width$}  {value:#018x}").unwrap();
+		} else {
+			writeln!(out, "  {name:<width$}  {value}").unwrap();
+		}
+	}
+}
+
+/// CRC-32 (IEEE), as computed by the kernel
+pub(crate) fn crc32(bytes: &[u8],) -> u32 {
+	let mut crc = !0u32;
+	for byte in bytes {
+		crc ^= *byte as u32;
+		for _ in 0..8 {
+			let mask = (crc & 1).wrapping_neg();
+			crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+		}
+	}
+	!crc
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// dump as the kernel writes it, with a message, two registers and a
+	/// backtrace of two frames
+	fn sample_dump() -> Vec<u8,> {
+		let mut out = MAGIC.to_vec();
+		out.push(VERSION,);
+		let mut record = |tag: u8, payload: &[u8]| {
+			out.push(tag,);
+			out.extend_from_slice(&(payload.len() as u16).to_le_bytes(),);
+			out.extend_from_slice(payload,);
+		};
+		record(TAG_MESSAGE, b"panicked at src/lib.rs:1:1:\noops",);
+		let mut regs = vec![];
+		for (name, value,) in [("lr", 0x4000_0010u64,), ("sp", 0x8000,),] {
+			regs.push(name.len() as u8,);
+			regs.extend_from_slice(name.as_bytes(),);
+			regs.extend_from_slice(&value.to_le_bytes(),);
+		}
+		record(TAG_REGISTERS, &regs,);
+		let frames: Vec<u8,> = [0x4000_0006u64, 0x5000_0000,]
+			.iter()
+			.flat_map(|addr| addr.to_le_bytes(),)
+			.collect();
+		record(TAG_BACKTRACE, &frames,);
+
+		let crc = crc32(&out,);
+		out.push(TAG_END,);
+		out.extend_from_slice(&4u16.to_le_bytes(),);
+		out.extend_from_slice(&crc.to_le_bytes(),);
+		out
+	}
+
+	#[test]
+	fn test_crc32_check_value() {
+		assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+	}
+
+	#[test]
+	fn test_decode_hex_from_log() {
+		let hex: String =
+			sample_dump().iter().map(|b| format!("{b:02x}"),).collect();
+		let (head, tail,) = hex.split_at(40,);
+		let log = format!(
+			"boot ok\r\n{BEGIN_MARKER}\r\n{head}\r\n{tail}\r\n{END_MARKER}\r\n"
+		);
+
+		let dump = CrashDump::decode(log.as_bytes(),).unwrap();
+		assert_eq!(dump, CrashDump::decode(&sample_dump(),).unwrap());
+		assert_eq!(dump.message, "panicked at src/lib.rs:1:1:\noops");
+		assert_eq!(dump.registers[1], ("sp".to_string(), 0x8000));
+		assert_eq!(dump.backtrace, [0x4000_0006, 0x5000_0000]);
+
+		let symbols = [Symbol {
+			name: "oso_kernel_init".to_string(),
+			addr: 0x4000_0000,
+			size: 0x10,
+		}];
+		let report = dump.render(&symbols,);
+		assert!(report.contains("0x0000000040000006  oso_kernel_init+0x6"));
+		assert!(report.contains("0x0000000050000000  ??"));
+	}
+
+	#[test]
+	fn test_reject_corrupted() {
+		let mut dump = sample_dump();
+		dump[8] ^= 1;
+		let err = CrashDump::parse(&dump,).unwrap_err();
+		assert!(err.to_string().contains("checksum"));
+
+		let cut = &sample_dump()[..20];
+		assert!(CrashDump::parse(cut,).is_err());
+	}
+}
@@ -1,20 +1,24 @@
 use crate::Rslt;
 use crate::cargo::CompileOpt;
 use crate::cargo::Opts;
+use crate::cargo::target::TargetSpec;
 use crate::decl_manage::crate_::Crate;
 use crate::decl_manage::crate_::CrateInfo;
 use crate::decl_manage::crate_::OsoCrate;
 use crate::decl_manage::package::PackageSurvey;
-use anyhow::bail;
-use oso_dev_util_helper::fs::search_in_with;
+use oso_dev_util_helper::fs::project_root_path;
+use std::ffi::OsString;
 use std::path::PathBuf;
 
 pub mod crate_;
+pub mod graph;
 pub mod package;
 pub mod workspace;
 
 pub trait CargoCrate {
 	fn specified_target(&self,) -> Rslt<impl Into<String,>,>;
+	/// Arguments which make cargo build for [`Self::specified_target`]
+	fn target_args(&self,) -> Rslt<Vec<OsString,>,>;
 	fn build_artifact(&self,) -> Rslt<PathBuf,>;
 	fn as_crate(&self,) -> &impl Crate;
 	fn as_opts(&self,) -> &impl CompileOpt;
@@ -27,23 +31,24 @@ pub struct OsoCargoInterface {
 
 impl CargoCrate for OsoCargoInterface {
 	fn specified_target(&self,) -> Rslt<impl Into<String,>,> {
-		let search_rslt = search_in_with(&self.ws.path(), |entry| {
-			let file_name = entry
-				.as_ref()
-				.expect("file io error",)
-				.file_name()
-				.to_string_lossy()
-				.to_string();
-			let arch = self.opt.arch().into();
-
-			file_name.contains(&arch,) && file_name.ends_with(".json",)
-		},);
+		let target: String = self.ws.default_target()?.into();
+		// crates which run on oso are built for generated specification
+		if target.ends_with(".json",) {
+			Ok(self.target_spec().name(),)
+		} else {
+			Ok(target,)
+		}
+	}
 
-		match search_rslt {
-			Ok(Some(p,),) => Ok(p.to_string_lossy().to_string(),),
-			Ok(None,) => self.ws.default_target().map(|s| s.into(),),
-			Err(e,) => bail!("{e}"),
+	fn target_args(&self,) -> Rslt<Vec<OsString,>,> {
+		let target: String = self.specified_target()?.into();
+		if target != self.target_spec().name() {
+			return Ok(vec!["--target".into(), target.into()],);
 		}
+
+		let root = project_root_path()?;
+		let path = self.target_spec().install(&root,)?;
+		Ok(TargetSpec::cargo_args(&path,).into(),)
 	}
 
 	fn build_artifact(&self,) -> Rslt<PathBuf,> {
@@ -52,7 +57,7 @@ impl CargoCrate for OsoCargoInterface {
 			.path()
 			.join("target",)
 			.join(self.specified_target()?.into(),)
-			.join(self.opt.build_mode().into(),),)
+			.join(self.opt.build_mode.profile_dir(),),)
 	}
 
 	fn as_crate(&self,) -> &impl Crate {
@@ -63,3 +68,9 @@ impl CargoCrate for OsoCargoInterface {
 		&self.opt
 	}
 }
+
+impl OsoCargoInterface {
+	fn target_spec(&self,) -> TargetSpec {
+		TargetSpec::kernel(self.opt.arch,)
+	}
+}
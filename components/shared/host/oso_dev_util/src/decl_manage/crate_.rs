@@ -160,10 +160,12 @@ pub trait CrateInfo: CrateCalled {
 	}
 }
 
+/// crate of the workspace. `parent()`, `children()` and `find(name)` walk
+/// crates nested in the filesystem
 #[derive(FromPathBuf, Default, PartialEq, Eq, Clone,)]
 pub struct OsoCrate {
 	path: PathBuf,
-	#[chart]
+	#[chart(parent = OsoCrate, children = [OsoCrate])]
 	i_am: OsoCrateChart,
 }
 
@@ -411,6 +413,24 @@ mod tests {
 		assert_eq!(crate_from_chart.path(), current_dir);
 	}
 
+	#[test]
+	fn test_chart_navigation_from_project_root() {
+		let root = crate::fs::project_root().unwrap();
+		assert!(root.parent().is_none());
+
+		let dev_util = root.find("oso_dev_util",).unwrap();
+		assert_eq!(dev_util.name(), "oso_dev_util");
+		assert!(dev_util.path().starts_with(root.path(),));
+		assert!(root.find("no_such_crate",).is_none());
+
+		// every child points back to its parent
+		for child in root.children() {
+			assert_eq!(child.parent(), Some(root.clone()));
+		}
+		let parent = dev_util.parent().unwrap();
+		assert!(parent.children().contains(&dev_util));
+	}
+
 	#[test]
 	fn test_debug_implementation() {
 		let current_dir =
@@ -486,10 +506,17 @@ mod tests {
 		use crate::cargo::BuildMode;
 		use crate::cargo::Feature;
 		use crate::cargo::Opts;
+		use crate::cargo::Task;
+		use crate::cli::Verbosity;
 		let _opts = Opts {
 			build_mode:    BuildMode::Debug,
 			feature_flags: Vec::<Feature,>::new(),
 			arch:          Arch::Aarch64,
+			verbosity:     Verbosity::default(),
+			dry_run:       false,
+			jobs:          1,
+			keep_ansi:     false,
+			task:          Task::Run,
 		};
 	}
 
@@ -0,0 +1,188 @@
+//! # Crate Dependency Graph
+//!
+//! Records which crates of a build depend on which, so that independent
+//! crates can be built at the same time. Only dependencies between the given
+//! crates are tracked; crates.io dependencies are cargo's business.
+
+use crate::Rslt;
+use crate::decl_manage::crate_::CrateInfo;
+use anyhow::bail;
+use std::path::PathBuf;
+
+/// Dependency tables of a manifest which order builds
+const DEPENDENCY_TABLES: [&str; 2] = ["dependencies", "build-dependencies",];
+
+/// Crate in [`CrateGraph`]
+///
+/// # Fields
+///
+/// * `name` - Package name
+/// * `path` - Directory of the crate
+/// * `deps` - Indices of crates this crate depends on
+#[derive(Clone, Debug, PartialEq, Eq,)]
+pub struct Node {
+	pub name: String,
+	pub path: PathBuf,
+	pub deps: Vec<usize,>,
+}
+
+/// Dependency graph between crates, free of cycles
+#[derive(Clone, Debug, Default, PartialEq, Eq,)]
+pub struct CrateGraph {
+	nodes: Vec<Node,>,
+}
+
+impl CrateGraph {
+	/// Reads manifests of `crates` and connects crates which depend on each
+	/// other
+	pub fn new(crates: &[impl CrateInfo],) -> Rslt<Self,> {
+		let manifests = crates
+			.iter()
+			.map(|c| -> Rslt<_,> {
+				let toml = c.toml()?;
+				let name = toml
+					.get("package",)
+					.and_then(|pkg| pkg.get("name",),)
+					.and_then(|name| name.as_str(),)
+					.map_or_else(|| c.name(), str::to_string,);
+				let deps: Vec<String,> = DEPENDENCY_TABLES
+					.iter()
+					.filter_map(|table| toml.get(*table,)?.as_table(),)
+					.flat_map(|table| table.keys().cloned(),)
+					.collect();
+				Ok((name, c.path(), deps,),)
+			},)
+			.collect::<Rslt<Vec<_,>,>>()?;
+		Self::from_manifests(manifests,)
+	}
+
+	/// Builds graph from `(name, path, dependency names)` of each crate
+	pub fn from_manifests(
+		manifests: Vec<(String, PathBuf, Vec<String,>,),>,
+	) -> Rslt<Self,> {
+		let names: Vec<String,> =
+			manifests.iter().map(|(name, ..,)| name.clone(),).collect();
+		let nodes = manifests
+			.into_iter()
+			.map(|(name, path, deps,)| {
+				let deps = deps
+					.iter()
+					.filter_map(|dep| names.iter().position(|n| n == dep,),)
+					.collect();
+				Node { name, path, deps, }
+			},)
+			.collect();
+
+		let graph = Self { nodes, };
+		graph.check_acyclic()?;
+		Ok(graph,)
+	}
+
+	pub fn nodes(&self,) -> &[Node] {
+		&self.nodes
+	}
+
+	pub fn len(&self,) -> usize {
+		self.nodes.len()
+	}
+
+	pub fn is_empty(&self,) -> bool {
+		self.nodes.is_empty()
+	}
+
+	/// Indices of crates which transitively depend on `index`
+	pub fn dependents_of(&self, index: usize,) -> Vec<usize,> {
+		let mut found = vec![false; self.len()];
+		let mut stack = vec![index];
+		while let Some(i,) = stack.pop() {
+			for (j, node,) in self.nodes.iter().enumerate() {
+				if !found[j] && node.deps.contains(&i,) {
+					found[j] = true;
+					stack.push(j,);
+				}
+			}
+		}
+		(0..self.len()).filter(|i| found[*i],).collect()
+	}
+
+	fn check_acyclic(&self,) -> Rslt<(),> {
+		// Kahn's algorithm. crates left with dependencies are on a cycle
+		let mut done = vec![false; self.len()];
+		loop {
+			let ready: Vec<usize,> = (0..self.len())
+				.filter(|i| {
+					!done[*i] && self.nodes[*i].deps.iter().all(|d| done[*d],)
+				},)
+				.collect();
+			if ready.is_empty() {
+				break;
+			}
+			ready.into_iter().for_each(|i| done[i] = true,);
+		}
+
+		let cycle: Vec<&str,> = (0..self.len())
+			.filter(|i| !done[*i],)
+			.map(|i| self.nodes[i].name.as_str(),)
+			.collect();
+		if !cycle.is_empty() {
+			bail!("dependency cycle between {}", cycle.join(", "));
+		}
+		Ok((),)
+	}
+}
+
+#[cfg(test)]
+impl CrateGraph {
+	/// Builds graph from `(name, dependency names)` of each crate, with the
+	/// name as path. Fixture of tests which need a graph
+	pub(crate) fn from_deps(deps: &[(&str, &[&str],)],) -> Rslt<Self,> {
+		Self::from_manifests(
+			deps.iter()
+				.map(|(name, deps,)| {
+					let deps = deps.iter().map(|d| d.to_string(),).collect();
+					(name.to_string(), PathBuf::from(name,), deps,)
+				},)
+				.collect(),
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_unknown_dependencies_are_ignored() {
+		let g = CrateGraph::from_deps(&[
+			("loader", &["anyhow", "shared",],),
+			("shared", &[],),
+		],)
+		.unwrap();
+		assert_eq!(g.nodes()[0].deps, [1]);
+		assert!(g.nodes()[1].deps.is_empty());
+	}
+
+	#[test]
+	fn test_dependents_are_transitive() {
+		let g = CrateGraph::from_deps(&[
+			("a", &[],),
+			("b", &["a",],),
+			("c", &["b",],),
+			("d", &[],),
+		],)
+		.unwrap();
+		assert_eq!(g.dependents_of(0), [1, 2]);
+		assert!(g.dependents_of(3).is_empty());
+	}
+
+	#[test]
+	fn test_cycle_is_rejected() {
+		let e = CrateGraph::from_deps(&[
+			("a", &["b",],),
+			("b", &["a",],),
+			("c", &[],),
+		],)
+		.unwrap_err();
+		assert_eq!(e.to_string(), "dependency cycle between a, b");
+	}
+}
@@ -0,0 +1,460 @@
+//! # Device Tree Printer
+//!
+//! Host side counterpart of the kernel's `dt` shell command. Reads a `.dtb`
+//! file and prints nodes and properties exactly as the kernel does, so the
+//! output of `dt print` in a serial log can be diffed against the blob built
+//! on the host.
+//!
+//! The format is described in the kernel module `base::dt`. Both sides must
+//! agree on [`PHANDLE_PROPERTIES`].
+//!
+//! [`Dtb::to_blob`] writes a tree back into a blob, to build fixtures for
+//! tests and benchmarks without `dtc`.
+
+use anyhow::Result as Rslt;
+use anyhow::bail;
+use anyhow::ensure;
+use std::fmt::Write;
+
+/// first word of every blob
+pub const MAGIC: u32 = 0xd00d_feed;
+/// properties whose cells are all phandles
+pub const PHANDLE_PROPERTIES: [&str; 6] = [
+	"interrupt-parent",
+	"msi-parent",
+	"next-level-cache",
+	"memory-region",
+	"phy-handle",
+	"cpu",
+];
+
+const BEGIN_NODE: u32 = 1;
+const END_NODE: u32 = 2;
+const PROP: u32 = 3;
+const NOP: u32 = 4;
+const END: u32 = 9;
+/// size of the header of version 17
+const HEADER_SIZE: usize = 40;
+/// memory reservation block holding only its terminating entry
+const EMPTY_RESERVATIONS: [u8; 16] = [0; 16];
+
+/// device tree read from a blob
+#[derive(Debug, Clone, PartialEq, Eq,)]
+pub struct Dtb {
+	pub root: DtNode,
+}
+
+/// node of a [`Dtb`]
+#[derive(Debug, Clone, Default, PartialEq, Eq,)]
+pub struct DtNode {
+	/// name including the unit address, empty for the root
+	pub name:       String,
+	pub properties: Vec<(String, Vec<u8,>,),>,
+	pub children:   Vec<DtNode,>,
+}
+
+impl Dtb {
+	pub fn parse(blob: &[u8],) -> Rslt<Self,> {
+		ensure!(be32(blob, 0,)? == MAGIC, "not a device tree blob");
+		let structure = be32(blob, 8,)? as usize;
+		let strings = be32(blob, 12,)? as usize;
+		let strings_size = be32(blob, 32,)? as usize;
+		let structure_size = be32(blob, 36,)? as usize;
+		let Some(structure,) = blob.get(structure..structure + structure_size,)
+		else {
+			bail!("structure block is out of the blob");
+		};
+		let Some(strings,) = blob.get(strings..strings + strings_size,) else {
+			bail!("strings block is out of the blob");
+		};
+
+		// ancestors of the current node, the root first
+		let mut stack: Vec<DtNode,> = vec![];
+		let mut root = None;
+		let mut cursor = 0;
+		loop {
+			match be32(structure, cursor,)? {
+				BEGIN_NODE => {
+					let name = c_str(&structure[cursor + 4..],)?;
+					cursor = (cursor + 4 + name.len() + 1).next_multiple_of(4,);
+					ensure!(root.is_none(), "second root node");
+					stack.push(DtNode { name, ..Default::default() },);
+				},
+				END_NODE => {
+					let Some(node,) = stack.pop() else {
+						bail!("unbalanced end of node at {cursor:#x}");
+					};
+					match stack.last_mut() {
+						Some(parent,) => parent.children.push(node,),
+						None => root = Some(node,),
+					}
+					cursor += 4;
+				},
+				PROP => {
+					let len = be32(structure, cursor + 4,)? as usize;
+					let name = be32(structure, cursor + 8,)? as usize;
+					let Some(value,) =
+						structure.get(cursor + 12..cursor + 12 + len,)
+					else {
+						bail!("property at {cursor:#x} is truncated");
+					};
+					let Some(node,) = stack.last_mut() else {
+						bail!("property at {cursor:#x} is outside of nodes");
+					};
+					let Some(name,) = strings.get(name..,) else {
+						bail!("property name at {cursor:#x} is out of range");
+					};
+					node.properties.push((c_str(name,)?, value.to_vec(),),);
+					cursor = (cursor + 12 + len).next_multiple_of(4,);
+				},
+				NOP => cursor += 4,
+				END => break,
+				token => bail!("unknown token {token:#x} at {cursor:#x}"),
+			}
+		}
+		ensure!(stack.is_empty(), "nodes are not closed");
+		let Some(root,) = root else {
+			bail!("no root node");
+		};
+		Ok(Self { root, },)
+	}
+
+	/// Blob of version 17 without memory reservations. [`Self::parse`] reads
+	/// it back into the same tree
+	pub fn to_blob(&self,) -> Vec<u8,> {
+		let mut structure = vec![];
+		let mut strings = vec![];
+		self.root.write(&mut structure, &mut strings,);
+		structure.extend_from_slice(&END.to_be_bytes(),);
+
+		let structure_offset = HEADER_SIZE + EMPTY_RESERVATIONS.len();
+		let strings_offset = structure_offset + structure.len();
+		let header = [
+			MAGIC,
+			(strings_offset + strings.len()) as u32,
+			structure_offset as u32,
+			strings_offset as u32,
+			HEADER_SIZE as u32,
+			17,
+			16,
+			0,
+			strings.len() as u32,
+			structure.len() as u32,
+		];
+		let mut blob: Vec<_,> =
+			header.iter().flat_map(|word| word.to_be_bytes(),).collect();
+		blob.extend_from_slice(&EMPTY_RESERVATIONS,);
+		blob.extend_from_slice(&structure,);
+		blob.extend_from_slice(&strings,);
+		blob
+	}
+
+	/// node at the absolute `path`
+	///
+	/// A path component without a unit address also matches a node with
+	/// one, so `/memory` finds `/memory@40000000`.
+	pub fn find(&self, path: &str,) -> Option<&DtNode,> {
+		path.split('/',)
+			.filter(|c| !c.is_empty(),)
+			.try_fold(&self.root, |node, name| {
+				node.children.iter().find(|c| c.is_named(name,),)
+			},)
+	}
+
+	/// output of `dt print <path>`
+	pub fn print(&self, path: &str,) -> Rslt<String,> {
+		let Some(node,) = self.find(path,) else {
+			bail!("dt: {path}: no such node");
+		};
+		let mut out = String::new();
+		let is_root = std::ptr::eq(node, &self.root,);
+		let name = if is_root { "/" } else { &node.name };
+		self.write_node(node, name, 0, &mut out,);
+		Ok(out,)
+	}
+
+	/// output of `dt prop <path> <name>`
+	pub fn prop(&self, path: &str, name: &str,) -> Rslt<String,> {
+		let Some(node,) = self.find(path,) else {
+			bail!("dt: {path}: no such node");
+		};
+		let Some(value,) = node.property(name,) else {
+			bail!("dt: {path}: no property {name}");
+		};
+		let mut out = String::new();
+		self.write_property(name, value, &mut out,);
+		out.push('\n',);
+		Ok(out,)
+	}
+
+	/// path of the node with `phandle`
+	pub fn path_of(&self, phandle: u32,) -> Option<String,> {
+		fn search(
+			node: &DtNode,
+			phandle: u32,
+			path: &str,
+		) -> Option<String,> {
+			if node.phandle() == Some(phandle,) {
+				let path = if path.is_empty() { "/" } else { path };
+				return Some(path.into(),);
+			}
+			node.children.iter().find_map(|child| {
+				search(child, phandle, &format!("{path}/{}", child.name),)
+			},)
+		}
+		search(&self.root, phandle, "",)
+	}
+
+	fn write_node(
+		&self,
+		node: &DtNode,
+		name: &str,
+		level: usize,
+		out: &mut String,
+	) {
+		let indent = "\t".repeat(level,);
+		let _ = writeln!(out, "{indent}{name} {{");
+		for (name, value,) in &node.properties {
+			let _ = write!(out, "{indent}\t");
+			self.write_property(name, value, out,);
+			out.push('\n',);
+		}
+		for child in &node.children {
+			self.write_node(child, &child.name, level + 1, out,);
+		}
+		let _ = writeln!(out, "{indent}}};");
+	}
+
+	fn write_property(&self, name: &str, value: &[u8], out: &mut String,) {
+		if value.is_empty() {
+			let _ = write!(out, "{name};");
+			return;
+		}
+		let _ = write!(out, "{name} = ");
+		self.write_value(name, value, out,);
+		out.push(';',);
+	}
+
+	/// `value` of the property `name` as `dt` prints it
+	pub fn write_value(&self, name: &str, value: &[u8], out: &mut String,) {
+		let (cells, rest,) = value.as_chunks::<4>();
+		let cells: Vec<_,> =
+			cells.iter().map(|cell| u32::from_be_bytes(*cell,),).collect();
+
+		if holds_phandles(name,) && rest.is_empty() {
+			let cells: Vec<_,> = cells
+				.iter()
+				.map(|cell| match self.path_of(*cell,) {
+					Some(path,) => format!("&{{{path}}}"),
+					None => format!("{cell:#x}"),
+				},)
+				.collect();
+			let _ = write!(out, "<{}>", cells.join(" "));
+		} else if let Some(strings,) = string_list(value,) {
+			let strings: Vec<_,> = strings
+				.split(|b| *b == 0,)
+				.map(|s| {
+					let s = String::from_utf8_lossy(s,)
+						.replace('\\', "\\\\",)
+						.replace('"', "\\\"",);
+					format!("\"{s}\"")
+				},)
+				.collect();
+			out.push_str(&strings.join(", ",),);
+		} else if rest.is_empty() {
+			let cells: Vec<_,> =
+				cells.iter().map(|cell| format!("{cell:#x}"),).collect();
+			let _ = write!(out, "<{}>", cells.join(" "));
+		} else {
+			let bytes: Vec<_,> =
+				value.iter().map(|byte| format!("{byte:02x}"),).collect();
+			let _ = write!(out, "[{}]", bytes.join(" "));
+		}
+	}
+}
+
+impl DtNode {
+	pub fn property(&self, name: &str,) -> Option<&[u8],> {
+		self.properties
+			.iter()
+			.find(|(n, _,)| n == name,)
+			.map(|(_, value,)| value.as_slice(),)
+	}
+
+	/// `true` if the name is `name`, or the name without the unit address is
+	/// `name` and `name` has none
+	pub fn is_named(&self, name: &str,) -> bool {
+		let base_name = self.name.split('@',).next();
+		self.name == name || !name.contains('@',) && base_name == Some(name,)
+	}
+
+	pub fn phandle(&self,) -> Option<u32,> {
+		let value = self
+			.property("phandle",)
+			.or_else(|| self.property("linux,phandle",),)?;
+		be32(value, 0,).ok()
+	}
+
+	/// appends the node to the structure block and the names of its
+	/// properties to `strings`, unless they are there already
+	fn write(&self, structure: &mut Vec<u8,>, strings: &mut Vec<u8,>,) {
+		fn pad(structure: &mut Vec<u8,>,) {
+			structure.resize(structure.len().next_multiple_of(4,), 0,);
+		}
+
+		structure.extend_from_slice(&BEGIN_NODE.to_be_bytes(),);
+		structure.extend_from_slice(self.name.as_bytes(),);
+		structure.push(0,);
+		pad(structure,);
+		for (name, value,) in &self.properties {
+			let offset = string_offset(strings, name,);
+			for word in [PROP, value.len() as u32, offset,] {
+				structure.extend_from_slice(&word.to_be_bytes(),);
+			}
+			structure.extend_from_slice(value,);
+			pad(structure,);
+		}
+		for child in &self.children {
+			child.write(structure, strings,);
+		}
+		structure.extend_from_slice(&END_NODE.to_be_bytes(),);
+	}
+}
+
+/// offset of `name` in the strings block, appended if it is not there yet
+fn string_offset(strings: &mut Vec<u8,>, name: &str,) -> u32 {
+	let mut offset = 0;
+	for s in strings.split(|b| *b == 0,) {
+		if s == name.as_bytes() && offset < strings.len() {
+			return offset as u32;
+		}
+		offset += s.len() + 1;
+	}
+	let offset = strings.len();
+	strings.extend_from_slice(name.as_bytes(),);
+	strings.push(0,);
+	offset as u32
+}
+
+fn holds_phandles(name: &str,) -> bool {
+	let pinctrl = name.strip_prefix("pinctrl-",);
+	PHANDLE_PROPERTIES.contains(&name,)
+		|| pinctrl.is_some_and(|n| n.parse::<u32>().is_ok(),)
+}
+
+/// `value` without the final NUL if it looks like a list of printable
+/// strings
+fn string_list(value: &[u8],) -> Option<&[u8],> {
+	let (&0, strings,) = value.split_last()? else {
+		return None;
+	};
+	let printable = |b: &u8| *b == 0 || b.is_ascii_graphic() || *b == b' ';
+	let empty = strings.is_empty()
+		|| strings[0] == 0
+		|| strings.ends_with(&[0,],)
+		|| strings.windows(2,).any(|w| w == [0, 0,],);
+	(!empty && strings.iter().all(printable,)).then_some(strings,)
+}
+
+fn be32(bytes: &[u8], offset: usize,) -> Rslt<u32,> {
+	let Some(b,) = bytes.get(offset..offset + 4,) else {
+		bail!("blob ends at {offset:#x}");
+	};
+	Ok(u32::from_be_bytes(b.try_into()?,),)
+}
+
+/// string up to the first NUL
+fn c_str(bytes: &[u8],) -> Rslt<String,> {
+	let Some(len,) = bytes.iter().position(|b| *b == 0,) else {
+		bail!("string is not terminated");
+	};
+	Ok(std::str::from_utf8(&bytes[..len],)?.into(),)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn push(blob: &mut Vec<u8,>, words: &[u32],) {
+		words.iter().for_each(|w| blob.extend_from_slice(&w.to_be_bytes(),),);
+	}
+
+	/// `/` with a string list, `/intc` with a phandle and `/uart@9000000`
+	/// with a phandle reference, cells, bytes and an empty property
+	fn sample() -> Vec<u8,> {
+		let strings = b"model\0phandle\0interrupt-parent\0reg\0mac\0flag\0";
+		let mut s = vec![];
+		push(&mut s, &[BEGIN_NODE, 0, PROP, 10, 0,],);
+		s.extend_from_slice(b"qemu\0virt\0\0\0",);
+		push(&mut s, &[BEGIN_NODE,],);
+		s.extend_from_slice(b"intc\0\0\0\0",);
+		push(&mut s, &[PROP, 4, 6, 1, END_NODE, BEGIN_NODE,],);
+		s.extend_from_slice(b"uart@9000000\0\0\0\0",);
+		push(&mut s, &[PROP, 4, 14, 1, PROP, 8, 31, 0x900_0000, 0x1000,],);
+		push(&mut s, &[PROP, 3, 35,],);
+		s.extend_from_slice(&[1, 2, 3, 0,],);
+		push(&mut s, &[PROP, 0, 39, END_NODE, END_NODE, END,],);
+
+		let mut blob = vec![];
+		push(&mut blob, &[
+			MAGIC,
+			(56 + s.len() + strings.len()) as u32,
+			56,
+			(56 + s.len()) as u32,
+			40,
+			17,
+			16,
+			0,
+			strings.len() as u32,
+			s.len() as u32,
+		],);
+		// empty memory reservation block
+		blob.extend_from_slice(&[0; 16],);
+		blob.extend_from_slice(&s,);
+		blob.extend_from_slice(strings,);
+		blob
+	}
+
+	#[test]
+	fn test_print() {
+		let dtb = Dtb::parse(&sample(),).unwrap();
+		let expected = "\
+/ {
+	model = \"qemu\", \"virt\";
+	intc {
+		phandle = <0x1>;
+	};
+	uart@9000000 {
+		interrupt-parent = <&{/intc}>;
+		reg = <0x9000000 0x1000>;
+		mac = [01 02 03];
+		flag;
+	};
+};
+";
+		assert_eq!(dtb.print("/",).unwrap(), expected);
+	}
+
+	#[test]
+	fn test_to_blob_is_inverse_of_parse() {
+		let blob = sample();
+		let dtb = Dtb::parse(&blob,).unwrap();
+		assert_eq!(dtb.to_blob(), blob);
+
+		let mut dtb = dtb;
+		let uart = &mut dtb.root.children[1];
+		uart.properties.push(("model".into(), b"pl011\0".to_vec(),),);
+		let empty = DtNode { name: "empty".into(), ..Default::default() };
+		uart.children.push(empty,);
+		assert_eq!(Dtb::parse(&dtb.to_blob()).unwrap(), dtb);
+	}
+
+	#[test]
+	fn test_prop() {
+		let dtb = Dtb::parse(&sample(),).unwrap();
+		let prop = dtb.prop("/uart", "interrupt-parent",).unwrap();
+		assert_eq!(prop, "interrupt-parent = <&{/intc}>;\n");
+		assert!(dtb.prop("/uart", "clocks",).is_err());
+		assert!(dtb.print("/soc",).is_err());
+	}
+}
@@ -0,0 +1,912 @@
+//! # ELF Patcher
+//!
+//! Host side counterpart of the loader's ELF parser. While the loader only
+//! needs to read kernel images, build tooling needs to rewrite them after
+//! linking: stamping build information into a note section, adjusting segment
+//! permissions and stripping symbols for release images.
+//!
+//! Only 64-bit little-endian images are supported, which covers every target
+//! the kernel is built for.
+//!
+//! ## Layout strategy
+//!
+//! Bytes covered by program headers (and allocated sections) are never moved,
+//! because the loader copies segments straight from their file offsets. Every
+//! non-allocated section is re-laid out after that region and the section
+//! header table is appended at the end of the file.
+
+use anyhow::Result as Rslt;
+use anyhow::bail;
+use anyhow::ensure;
+use std::path::Path;
+
+const ELF_MAGIC: &[u8; 4] = b"\x7fELF";
+const ELF_CLASS_64: u8 = 2;
+const ELF_DATA_LITTLE_ENDIAN: u8 = 1;
+const EHDR_SIZE: usize = 64;
+const PHDR_SIZE: usize = 56;
+const SHDR_SIZE: usize = 64;
+const SYM_SIZE: usize = 24;
+
+/// section type of symbol tables
+pub const SHT_SYMTAB: u32 = 2;
+/// section type of string tables
+pub const SHT_STRTAB: u32 = 3;
+/// section type of relocations with addends
+pub const SHT_RELA: u32 = 4;
+/// section type of note sections
+pub const SHT_NOTE: u32 = 7;
+/// section type of relocations without addends
+pub const SHT_REL: u32 = 9;
+/// symbol type of section symbols
+pub const STT_SECTION: u8 = 3;
+/// symbol type of source file symbols
+pub const STT_FILE: u8 = 4;
+/// section occupies memory during execution
+pub const SHF_ALLOC: u64 = 0x2;
+/// `sh_info` of the section is a section index
+pub const SHF_INFO_LINK: u64 = 0x40;
+
+/// segment is executable
+pub const PF_X: u32 = 0x1;
+/// segment is writable
+pub const PF_W: u32 = 0x2;
+/// segment is readable
+pub const PF_R: u32 = 0x4;
+
+/// name of the note section stamped by [`ElfPatcher::stamp_meta`]
+pub const OSO_META_SECTION: &str = ".oso_meta";
+/// owner name written into `.oso_meta` notes
+pub const OSO_META_OWNER: &str = "OSO";
+/// note type of build information notes
+pub const NT_OSO_BUILD_INFO: u32 = 1;
+
+/// name of the section holding the Multiboot2 header of the kernel
+pub const MULTIBOOT2_SECTION: &str = ".multiboot2";
+/// bytes at the start of the file which Multiboot2 boot loaders search for
+/// the header
+pub const MULTIBOOT2_SEARCH_LEN: u64 = 32 * 1024;
+/// first word of the Multiboot2 header
+const MULTIBOOT2_MAGIC: u32 = 0xe852_50d6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default,)]
+pub struct ProgramHeader {
+	pub ty:     u32,
+	pub flags:  u32,
+	pub offset: u64,
+	pub vaddr:  u64,
+	pub paddr:  u64,
+	pub filesz: u64,
+	pub memsz:  u64,
+	pub align:  u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default,)]
+pub struct SectionHeader {
+	pub name:      u32,
+	pub ty:        u32,
+	pub flags:     u64,
+	pub addr:      u64,
+	pub offset:    u64,
+	pub size:      u64,
+	pub link:      u32,
+	pub info:      u32,
+	pub addralign: u64,
+	pub entsize:   u64,
+}
+
+impl SectionHeader {
+	pub fn is_alloc(&self,) -> bool {
+		self.flags & SHF_ALLOC != 0
+	}
+
+	/// whether `info` is the index of a section, like the section relocation
+	/// sections apply to
+	pub fn info_is_section(&self,) -> bool {
+		matches!(self.ty, SHT_REL | SHT_RELA) || self.flags & SHF_INFO_LINK != 0
+	}
+}
+
+/// a section together with its resolved name and (for non allocated sections)
+/// its owned contents
+#[derive(Debug, Clone,)]
+pub struct Section {
+	pub name:   String,
+	pub header: SectionHeader,
+	pub data:   Vec<u8,>,
+}
+
+/// a named address from the symbol table
+#[derive(Debug, Clone, PartialEq, Eq,)]
+pub struct Symbol {
+	pub name: String,
+	pub addr: u64,
+	pub size: u64,
+}
+
+/// in-memory editable representation of an ELF image
+#[derive(Debug, Clone,)]
+pub struct ElfPatcher {
+	bytes:               Vec<u8,>,
+	pub program_headers: Vec<ProgramHeader,>,
+	pub sections:        Vec<Section,>,
+	shstrndx:            usize,
+}
+
+impl ElfPatcher {
+	pub fn open(path: impl AsRef<Path,>,) -> Rslt<Self,> {
+		Self::parse(std::fs::read(path,)?,)
+	}
+
+	pub fn parse(bytes: Vec<u8,>,) -> Rslt<Self,> {
+		let program_headers = program_headers(&bytes,)?;
+		let shoff = read_u64(&bytes, 0x28,)? as usize;
+		let shnum = read_u16(&bytes, 0x3c,)? as usize;
+		let shstrndx = read_u16(&bytes, 0x3e,)? as usize;
+
+		let headers = (0..shnum)
+			.map(|i| SectionHeader::read(&bytes, shoff + i * SHDR_SIZE,),)
+			.collect::<Rslt<Vec<_,>,>>()?;
+
+		let sections = if headers.is_empty() {
+			vec![]
+		} else {
+			ensure!(shstrndx < headers.len(), "invalid e_shstrndx {shstrndx}");
+			let shstrtab = section_bytes(&bytes, &headers[shstrndx],)?.to_vec();
+			headers
+				.into_iter()
+				.map(|header| {
+					let name = c_str_at(&shstrtab, header.name as usize,)?;
+					let data = section_bytes(&bytes, &header,)?.to_vec();
+					Ok(Section { name, header, data, },)
+				},)
+				.collect::<Rslt<Vec<_,>,>>()?
+		};
+
+		Ok(Self { bytes, program_headers, sections, shstrndx, },)
+	}
+
+	pub fn section(&self, name: &str,) -> Option<&Section,> {
+		self.sections.iter().find(|s| s.name == name,)
+	}
+
+	/// symbols of `.symtab` which have an address, sorted by address
+	///
+	/// section and file symbols are skipped. stripped images have none
+	pub fn symbols(&self,) -> Rslt<Vec<Symbol,>,> {
+		let Some(symtab,) =
+			self.sections.iter().find(|s| s.header.ty == SHT_SYMTAB,)
+		else {
+			return Ok(vec![],);
+		};
+		let link = symtab.header.link as usize;
+		let Some(strtab,) = self.sections.get(link,) else {
+			bail!("symbol table links to missing section {link}")
+		};
+
+		let mut symbols = vec![];
+		for entry in symtab.data.chunks_exact(SYM_SIZE,) {
+			let name = read_u32(entry, 0,)? as usize;
+			let ty = entry[4] & 0xf;
+			let addr = read_u64(entry, 8,)?;
+			let size = read_u64(entry, 16,)?;
+			if name == 0 || addr == 0 || ty == STT_SECTION || ty == STT_FILE {
+				continue;
+			}
+			let name = c_str_at(&strtab.data, name,)?;
+			symbols.push(Symbol { name, addr, size, },);
+		}
+		symbols.sort_by_key(|s| s.addr,);
+		Ok(symbols,)
+	}
+
+	/// checks that Multiboot2 boot loaders find the header in
+	/// [`MULTIBOOT2_SECTION`]: 8 byte aligned, within the first
+	/// [`MULTIBOOT2_SEARCH_LEN`] bytes of the file and with a valid checksum
+	///
+	/// images without the section pass
+	pub fn check_multiboot2_header(&self,) -> Rslt<(),> {
+		let Some(section,) = self.section(MULTIBOOT2_SECTION,) else {
+			return Ok((),);
+		};
+		// magic, architecture, header length and checksum
+		let words = (0..4)
+			.map(|i| read_u32(&section.data, i * 4,),)
+			.collect::<Rslt<Vec<_,>,>>()?;
+		ensure!(
+			words[0] == MULTIBOOT2_MAGIC,
+			"{MULTIBOOT2_SECTION} does not start with the multiboot2 header"
+		);
+		let sum =
+			words.iter().fold(0u32, |sum, word| sum.wrapping_add(*word,),);
+		ensure!(sum == 0, "multiboot2 header has a bad checksum");
+
+		let start = section.header.offset;
+		let end = start + words[2] as u64;
+		ensure!(
+			start % 8 == 0 && end <= MULTIBOOT2_SEARCH_LEN,
+			"multiboot2 header at {start:#x}..{end:#x} of the file is not 8 \
+			 byte aligned within the first {MULTIBOOT2_SEARCH_LEN:#x} bytes, \
+			 where boot loaders search for it"
+		);
+		Ok((),)
+	}
+
+	fn section_index(&self, name: &str,) -> Option<usize,> {
+		self.sections.iter().position(|s| s.name == name,)
+	}
+
+	/// replaces contents of the section called `name`
+	///
+	/// allocated sections are patched in place, so the new contents must fit
+	/// into the original size. the remainder is zero filled
+	pub fn set_section_data(
+		&mut self,
+		name: &str,
+		data: impl Into<Vec<u8,>,>,
+	) -> Rslt<(),> {
+		let Some(index,) = self.section_index(name,) else {
+			bail!("section {name} does not exist")
+		};
+		let mut data = data.into();
+		let section = &mut self.sections[index];
+
+		if section.header.is_alloc() {
+			let size = section.header.size as usize;
+			ensure!(
+				data.len() <= size,
+				"{name} is allocated and can not grow: {} > {size}",
+				data.len()
+			);
+			data.resize(size, 0,);
+			let offset = section.header.offset as usize;
+			self.bytes[offset..offset + size].copy_from_slice(&data,);
+		}
+
+		section.header.size = data.len() as u64;
+		section.data = data;
+		Ok((),)
+	}
+
+	/// adds a non allocated note section, or replaces its contents when a
+	/// section with the same name already exists
+	pub fn add_note(
+		&mut self,
+		section_name: &str,
+		owner: &str,
+		note_type: u32,
+		desc: &[u8],
+	) -> Rslt<(),> {
+		let note = encode_note(owner, note_type, desc,);
+		if self.section_index(section_name,).is_some() {
+			return self.set_section_data(section_name, note,);
+		}
+
+		self.sections.push(Section {
+			name:   section_name.to_string(),
+			header: SectionHeader {
+				ty: SHT_NOTE,
+				addralign: 4,
+				size: note.len() as u64,
+				..Default::default()
+			},
+			data:   note,
+		},);
+		Ok((),)
+	}
+
+	/// stamps `key=value` lines into the `.oso_meta` note
+	pub fn stamp_meta<'a,>(
+		&mut self,
+		entries: impl IntoIterator<Item = (&'a str, &'a str,),>,
+	) -> Rslt<(),> {
+		let desc: String = entries
+			.into_iter()
+			.map(|(k, v,)| format!("{k}={v}\n"),)
+			.collect();
+		self.add_note(
+			OSO_META_SECTION,
+			OSO_META_OWNER,
+			NT_OSO_BUILD_INFO,
+			desc.as_bytes(),
+		)
+	}
+
+	/// overwrites `p_flags` of the program header at `index`
+	pub fn set_segment_flags(&mut self, index: usize, flags: u32,) -> Rslt<(),> {
+		let Some(ph,) = self.program_headers.get_mut(index,) else {
+			bail!("program header {index} does not exist")
+		};
+		ph.flags = flags;
+		Ok((),)
+	}
+
+	/// removes symbol tables, their string tables and debug sections
+	///
+	/// returns names of the removed sections
+	pub fn strip(&mut self,) -> Vec<String,> {
+		let shstrtab_name = self.sections[self.shstrndx].name.clone();
+		let removable: Vec<bool,> = self
+			.sections
+			.iter()
+			.enumerate()
+			.map(|(i, s,)| {
+				i != 0
+					&& !s.header.is_alloc()
+					&& s.name != shstrtab_name
+					&& (s.header.ty == SHT_SYMTAB
+						|| s.header.ty == SHT_STRTAB
+						|| s.name.starts_with(".debug",)
+						|| s.name == ".comment")
+			},)
+			.collect();
+		self.remove_sections(&removable,)
+	}
+
+	fn remove_sections(&mut self, removable: &[bool],) -> Vec<String,> {
+		let mut remap = Vec::with_capacity(self.sections.len(),);
+		let mut next = 0;
+		for remove in removable {
+			remap.push(if *remove { None } else { Some(next,) },);
+			if !remove {
+				next += 1;
+			}
+		}
+
+		let mut removed = vec![];
+		let sections = std::mem::take(&mut self.sections,);
+		for (section, remove,) in sections.into_iter().zip(removable,) {
+			if *remove {
+				removed.push(section.name,);
+			} else {
+				self.sections.push(section,);
+			}
+		}
+
+		// links to removed sections become `SHN_UNDEF`
+		let remapped = |index: u32| {
+			remap.get(index as usize,).copied().flatten().unwrap_or(0,) as u32
+		};
+		for section in &mut self.sections {
+			let header = &mut section.header;
+			header.link = remapped(header.link,);
+			// `sh_info` of symbol tables counts symbols instead
+			if header.info_is_section() {
+				header.info = remapped(header.info,);
+			}
+		}
+		self.shstrndx = remap[self.shstrndx].expect("shstrtab is never removed",);
+
+		removed
+	}
+
+	/// end of the region which must stay at its original file offset
+	fn fixed_region_end(&self,) -> usize {
+		let phdr_end = read_u64(&self.bytes, 0x20,).unwrap_or(0,) as usize
+			+ self.program_headers.len() * PHDR_SIZE;
+		let segment_end = self
+			.program_headers
+			.iter()
+			.map(|ph| (ph.offset + ph.filesz) as usize,)
+			.max()
+			.unwrap_or(0,);
+		let alloc_end = self
+			.sections
+			.iter()
+			.filter(|s| s.header.is_alloc() && s.header.ty != SHT_NOBITS,)
+			.map(|s| (s.header.offset + s.header.size) as usize,)
+			.max()
+			.unwrap_or(0,);
+
+		EHDR_SIZE.max(phdr_end,).max(segment_end,).max(alloc_end,)
+	}
+
+	/// serializes the image, recomputing offsets of every movable section
+	pub fn to_bytes(&self,) -> Vec<u8,> {
+		let mut out = self.bytes[..self.fixed_region_end()].to_vec();
+
+		// rebuild section name string table from current names
+		let mut shstrtab = vec![0u8];
+		let mut name_offsets = Vec::with_capacity(self.sections.len(),);
+		for section in &self.sections {
+			if section.name.is_empty() {
+				name_offsets.push(0,);
+				continue;
+			}
+			name_offsets.push(shstrtab.len() as u32,);
+			shstrtab.extend_from_slice(section.name.as_bytes(),);
+			shstrtab.push(0,);
+		}
+
+		let mut headers = Vec::with_capacity(self.sections.len(),);
+		for (i, section,) in self.sections.iter().enumerate() {
+			let mut header = section.header;
+			header.name = name_offsets[i];
+
+			let movable = i != 0 && !header.is_alloc() && header.ty != SHT_NOBITS;
+			if movable {
+				let data =
+					if i == self.shstrndx { &shstrtab } else { &section.data };
+				let align = header.addralign.max(1,) as usize;
+				out.resize(out.len().next_multiple_of(align,), 0,);
+				header.offset = out.len() as u64;
+				header.size = data.len() as u64;
+				out.extend_from_slice(data,);
+			}
+			headers.push(header,);
+		}
+
+		out.resize(out.len().next_multiple_of(8,), 0,);
+		let shoff = out.len() as u64;
+		for header in &headers {
+			header.write(&mut out,);
+		}
+
+		// patch elf header
+		out[0x28..0x30].copy_from_slice(&shoff.to_le_bytes(),);
+		out[0x3c..0x3e]
+			.copy_from_slice(&(headers.len() as u16).to_le_bytes(),);
+		out[0x3e..0x40].copy_from_slice(&(self.shstrndx as u16).to_le_bytes(),);
+
+		// patch program headers
+		let phoff = read_u64(&out, 0x20,).unwrap_or(0,) as usize;
+		for (i, ph,) in self.program_headers.iter().enumerate() {
+			ph.write(&mut out[phoff + i * PHDR_SIZE..phoff + (i + 1) * PHDR_SIZE],);
+		}
+
+		out
+	}
+
+	pub fn write_to(&self, path: impl AsRef<Path,>,) -> Rslt<(),> {
+		std::fs::write(path, self.to_bytes(),)?;
+		Ok((),)
+	}
+}
+
+/// section type which occupies no file space
+const SHT_NOBITS: u32 = 8;
+
+impl ProgramHeader {
+	fn read(bytes: &[u8], at: usize,) -> Rslt<Self,> {
+		Ok(Self {
+			ty:     read_u32(bytes, at,)?,
+			flags:  read_u32(bytes, at + 0x04,)?,
+			offset: read_u64(bytes, at + 0x08,)?,
+			vaddr:  read_u64(bytes, at + 0x10,)?,
+			paddr:  read_u64(bytes, at + 0x18,)?,
+			filesz: read_u64(bytes, at + 0x20,)?,
+			memsz:  read_u64(bytes, at + 0x28,)?,
+			align:  read_u64(bytes, at + 0x30,)?,
+		},)
+	}
+
+	fn write(&self, out: &mut [u8],) {
+		out[0x00..0x04].copy_from_slice(&self.ty.to_le_bytes(),);
+		out[0x04..0x08].copy_from_slice(&self.flags.to_le_bytes(),);
+		out[0x08..0x10].copy_from_slice(&self.offset.to_le_bytes(),);
+		out[0x10..0x18].copy_from_slice(&self.vaddr.to_le_bytes(),);
+		out[0x18..0x20].copy_from_slice(&self.paddr.to_le_bytes(),);
+		out[0x20..0x28].copy_from_slice(&self.filesz.to_le_bytes(),);
+		out[0x28..0x30].copy_from_slice(&self.memsz.to_le_bytes(),);
+		out[0x30..0x38].copy_from_slice(&self.align.to_le_bytes(),);
+	}
+}
+
+impl SectionHeader {
+	fn read(bytes: &[u8], at: usize,) -> Rslt<Self,> {
+		Ok(Self {
+			name:      read_u32(bytes, at,)?,
+			ty:        read_u32(bytes, at + 0x04,)?,
+			flags:     read_u64(bytes, at + 0x08,)?,
+			addr:      read_u64(bytes, at + 0x10,)?,
+			offset:    read_u64(bytes, at + 0x18,)?,
+			size:      read_u64(bytes, at + 0x20,)?,
+			link:      read_u32(bytes, at + 0x28,)?,
+			info:      read_u32(bytes, at + 0x2c,)?,
+			addralign: read_u64(bytes, at + 0x30,)?,
+			entsize:   read_u64(bytes, at + 0x38,)?,
+		},)
+	}
+
+	fn write(&self, out: &mut Vec<u8,>,) {
+		out.extend_from_slice(&self.name.to_le_bytes(),);
+		out.extend_from_slice(&self.ty.to_le_bytes(),);
+		out.extend_from_slice(&self.flags.to_le_bytes(),);
+		out.extend_from_slice(&self.addr.to_le_bytes(),);
+		out.extend_from_slice(&self.offset.to_le_bytes(),);
+		out.extend_from_slice(&self.size.to_le_bytes(),);
+		out.extend_from_slice(&self.link.to_le_bytes(),);
+		out.extend_from_slice(&self.info.to_le_bytes(),);
+		out.extend_from_slice(&self.addralign.to_le_bytes(),);
+		out.extend_from_slice(&self.entsize.to_le_bytes(),);
+	}
+}
+
+/// encodes a single `Elf64_Nhdr` entry followed by its padded name and desc
+pub fn encode_note(owner: &str, note_type: u32, desc: &[u8],) -> Vec<u8,> {
+	let mut note = vec![];
+	note.extend_from_slice(&(owner.len() as u32 + 1).to_le_bytes(),);
+	note.extend_from_slice(&(desc.len() as u32).to_le_bytes(),);
+	note.extend_from_slice(&note_type.to_le_bytes(),);
+	note.extend_from_slice(owner.as_bytes(),);
+	note.push(0,);
+	note.resize(note.len().next_multiple_of(4,), 0,);
+	note.extend_from_slice(desc,);
+	note.resize(note.len().next_multiple_of(4,), 0,);
+	note
+}
+
+/// reads only the file header and program headers, which is all a loader
+/// needs. [`ElfPatcher::parse`] reads sections as well
+pub fn program_headers(bytes: &[u8],) -> Rslt<Vec<ProgramHeader,>,> {
+	ensure!(bytes.len() >= EHDR_SIZE, "binary is too small to be elf");
+	ensure!(&bytes[..4] == ELF_MAGIC, "bad elf magic number");
+	ensure!(bytes[4] == ELF_CLASS_64, "only 64-bit elf is supported");
+	ensure!(
+		bytes[5] == ELF_DATA_LITTLE_ENDIAN,
+		"only little-endian elf is supported"
+	);
+
+	let phoff = read_u64(bytes, 0x20,)? as usize;
+	let phnum = read_u16(bytes, 0x38,)? as usize;
+	(0..phnum)
+		.map(|i| ProgramHeader::read(bytes, phoff + i * PHDR_SIZE,),)
+		.collect()
+}
+
+/// finds the symbol containing `addr` in `symbols` sorted by address
+///
+/// returns the symbol and the offset of `addr` into it. symbols without a
+/// size are taken to extend to the next symbol
+pub fn symbolize(symbols: &[Symbol], addr: u64,) -> Option<(&Symbol, u64,),> {
+	let i = symbols.partition_point(|s| s.addr <= addr,);
+	let symbol = &symbols[i.checked_sub(1,)?];
+	let offset = addr - symbol.addr;
+	if symbol.size != 0 && offset >= symbol.size {
+		return None;
+	}
+	Some((symbol, offset,),)
+}
+
+fn section_bytes<'a,>(
+	bytes: &'a [u8],
+	header: &SectionHeader,
+) -> Rslt<&'a [u8],> {
+	if header.ty == SHT_NOBITS || header.ty == 0 {
+		return Ok(&[],);
+	}
+	let head = header.offset as usize;
+	let tail = head + header.size as usize;
+	ensure!(tail <= bytes.len(), "section exceeds file size: {head:#x}..{tail:#x}");
+	Ok(&bytes[head..tail],)
+}
+
+fn c_str_at(table: &[u8], offset: usize,) -> Rslt<String,> {
+	let Some(tail,) = table.get(offset..,) else {
+		bail!("string offset {offset:#x} is out of table")
+	};
+	let len = tail.iter().position(|b| *b == 0,).unwrap_or(tail.len(),);
+	Ok(String::from_utf8_lossy(&tail[..len],).into_owned(),)
+}
+
+fn read_u16(bytes: &[u8], at: usize,) -> Rslt<u16,> {
+	Ok(u16::from_le_bytes(read_array(bytes, at,)?,),)
+}
+
+fn read_u32(bytes: &[u8], at: usize,) -> Rslt<u32,> {
+	Ok(u32::from_le_bytes(read_array(bytes, at,)?,),)
+}
+
+fn read_u64(bytes: &[u8], at: usize,) -> Rslt<u64,> {
+	Ok(u64::from_le_bytes(read_array(bytes, at,)?,),)
+}
+
+fn read_array<const N: usize,>(bytes: &[u8], at: usize,) -> Rslt<[u8; N],> {
+	match bytes.get(at..at + N,) {
+		Some(b,) => Ok(b.try_into()?,),
+		None => bail!("unexpected end of binary at {at:#x}"),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// builds a tiny elf: one PT_LOAD segment with `.text`, plus `.symtab`,
+	/// `.strtab` and `.shstrtab`
+	fn sample_elf() -> Vec<u8,> {
+		let text = [0xaa_u8; 16];
+		let strtab = b"\0sym\0".to_vec();
+		let mut symtab = vec![0u8; 24 * 2];
+		// `sym`: a function of 8 bytes at the start of `.text` + 4
+		symtab[24..28].copy_from_slice(&1u32.to_le_bytes(),);
+		symtab[28] = 0x12;
+		symtab[30..32].copy_from_slice(&1u16.to_le_bytes(),);
+		symtab[32..40].copy_from_slice(&0x4000_0004u64.to_le_bytes(),);
+		symtab[40..48].copy_from_slice(&8u64.to_le_bytes(),);
+		let shstrtab = b"\0.text\0.symtab\0.strtab\0.shstrtab\0".to_vec();
+
+		let text_off = EHDR_SIZE + PHDR_SIZE;
+		let symtab_off = text_off + text.len();
+		let strtab_off = symtab_off + symtab.len();
+		let shstrtab_off = strtab_off + strtab.len();
+		let shoff = (shstrtab_off + shstrtab.len()).next_multiple_of(8,);
+
+		let mut out = vec![0u8; EHDR_SIZE];
+		out[..4].copy_from_slice(ELF_MAGIC,);
+		out[4] = ELF_CLASS_64;
+		out[5] = ELF_DATA_LITTLE_ENDIAN;
+		out[6] = 1;
+		out[0x10..0x12].copy_from_slice(&2u16.to_le_bytes(),);
+		out[0x20..0x28].copy_from_slice(&(EHDR_SIZE as u64).to_le_bytes(),);
+		out[0x28..0x30].copy_from_slice(&(shoff as u64).to_le_bytes(),);
+		out[0x34..0x36].copy_from_slice(&(EHDR_SIZE as u16).to_le_bytes(),);
+		out[0x36..0x38].copy_from_slice(&(PHDR_SIZE as u16).to_le_bytes(),);
+		out[0x38..0x3a].copy_from_slice(&1u16.to_le_bytes(),);
+		out[0x3a..0x3c].copy_from_slice(&(SHDR_SIZE as u16).to_le_bytes(),);
+		out[0x3c..0x3e].copy_from_slice(&5u16.to_le_bytes(),);
+		out[0x3e..0x40].copy_from_slice(&4u16.to_le_bytes(),);
+
+		let mut ph = [0u8; PHDR_SIZE];
+		ProgramHeader {
+			ty:     1,
+			flags:  PF_R | PF_W | PF_X,
+			offset: text_off as u64,
+			vaddr:  0x4000_0000,
+			paddr:  0x4000_0000,
+			filesz: text.len() as u64,
+			memsz:  text.len() as u64,
+			align:  0x1000,
+		}
+		.write(&mut ph,);
+		out.extend_from_slice(&ph,);
+		out.extend_from_slice(&text,);
+		out.extend_from_slice(&symtab,);
+		out.extend_from_slice(&strtab,);
+		out.extend_from_slice(&shstrtab,);
+		out.resize(shoff, 0,);
+
+		let headers = [
+			SectionHeader::default(),
+			SectionHeader {
+				name: 1,
+				ty: 1,
+				flags: SHF_ALLOC | 0x4,
+				addr: 0x4000_0000,
+				offset: text_off as u64,
+				size: text.len() as u64,
+				addralign: 4,
+				..Default::default()
+			},
+			SectionHeader {
+				name: 7,
+				ty: SHT_SYMTAB,
+				offset: symtab_off as u64,
+				size: symtab.len() as u64,
+				link: 3,
+				addralign: 8,
+				entsize: 24,
+				..Default::default()
+			},
+			SectionHeader {
+				name: 15,
+				ty: SHT_STRTAB,
+				offset: strtab_off as u64,
+				size: strtab.len() as u64,
+				addralign: 1,
+				..Default::default()
+			},
+			SectionHeader {
+				name: 23,
+				ty: SHT_STRTAB,
+				offset: shstrtab_off as u64,
+				size: shstrtab.len() as u64,
+				addralign: 1,
+				..Default::default()
+			},
+		];
+		for header in headers {
+			header.write(&mut out,);
+		}
+		out
+	}
+
+	#[test]
+	fn test_parse_sample() {
+		let elf = ElfPatcher::parse(sample_elf(),).unwrap();
+		let names: Vec<_,> = elf.sections.iter().map(|s| s.name.as_str(),).collect();
+		assert_eq!(names, ["", ".text", ".symtab", ".strtab", ".shstrtab"]);
+		assert_eq!(elf.program_headers.len(), 1);
+		assert_eq!(elf.section(".text").unwrap().data, [0xaa; 16]);
+		let minimal = program_headers(&sample_elf(),).unwrap();
+		assert_eq!(minimal, elf.program_headers);
+	}
+
+	#[test]
+	fn test_roundtrip_preserves_segments() {
+		let original = sample_elf();
+		let elf = ElfPatcher::parse(original.clone(),).unwrap();
+		let rewritten = elf.to_bytes();
+		let reparsed = ElfPatcher::parse(rewritten.clone(),).unwrap();
+
+		assert_eq!(reparsed.program_headers, elf.program_headers);
+		let text = reparsed.section(".text",).unwrap();
+		assert_eq!(text.header.offset, elf.section(".text").unwrap().header.offset);
+		assert_eq!(text.data, [0xaa; 16]);
+	}
+
+	#[test]
+	fn test_stamp_meta_adds_note() {
+		let mut elf = ElfPatcher::parse(sample_elf(),).unwrap();
+		elf.stamp_meta([("mode", "release",), ("arch", "aarch64",),],)
+			.unwrap();
+		let reparsed = ElfPatcher::parse(elf.to_bytes(),).unwrap();
+
+		let meta = reparsed.section(OSO_META_SECTION,).unwrap();
+		assert_eq!(meta.header.ty, SHT_NOTE);
+		assert_eq!(meta.data.len() % 4, 0);
+		let desc = String::from_utf8_lossy(&meta.data,);
+		assert!(desc.contains("mode=release\n"));
+		assert!(desc.contains("arch=aarch64\n"));
+	}
+
+	#[test]
+	fn test_stamp_meta_twice_replaces() {
+		let mut elf = ElfPatcher::parse(sample_elf(),).unwrap();
+		elf.stamp_meta([("a", "1",),],).unwrap();
+		elf.stamp_meta([("a", "2",),],).unwrap();
+		let count = elf
+			.sections
+			.iter()
+			.filter(|s| s.name == OSO_META_SECTION,)
+			.count();
+		assert_eq!(count, 1);
+	}
+
+	#[test]
+	fn test_set_segment_flags() {
+		let mut elf = ElfPatcher::parse(sample_elf(),).unwrap();
+		elf.set_segment_flags(0, PF_R | PF_X,).unwrap();
+		let reparsed = ElfPatcher::parse(elf.to_bytes(),).unwrap();
+		assert_eq!(reparsed.program_headers[0].flags, PF_R | PF_X);
+		assert!(elf.set_segment_flags(3, PF_R,).is_err());
+	}
+
+	#[test]
+	fn test_strip_removes_symbols() {
+		let mut elf = ElfPatcher::parse(sample_elf(),).unwrap();
+		let removed = elf.strip();
+		assert_eq!(removed, [".symtab", ".strtab"]);
+
+		let reparsed = ElfPatcher::parse(elf.to_bytes(),).unwrap();
+		let names: Vec<_,> =
+			reparsed.sections.iter().map(|s| s.name.as_str(),).collect();
+		assert_eq!(names, ["", ".text", ".shstrtab"]);
+		assert_eq!(reparsed.section(".text").unwrap().data, [0xaa; 16]);
+	}
+
+	#[test]
+	fn test_remove_section_before_symtab_remaps_links() {
+		let mut elf = ElfPatcher::parse(sample_elf(),).unwrap();
+		// `.debug_info` before `.text` and `.symtab`, and relocations of `.text`
+		let debug_info = Section {
+			name:   ".debug_info".to_string(),
+			header: SectionHeader { ty: 1, addralign: 1, ..Default::default() },
+			data:   vec![0x55; 4],
+		};
+		elf.sections.insert(1, debug_info,);
+		elf.sections[3].header.link = 4;
+		elf.sections[3].header.info = 2;
+		elf.shstrndx = 5;
+		elf.sections.push(Section {
+			name:   ".rela.text".to_string(),
+			header: SectionHeader {
+				ty: SHT_RELA,
+				flags: SHF_INFO_LINK,
+				link: 3,
+				info: 2,
+				addralign: 8,
+				entsize: 24,
+				..Default::default()
+			},
+			data:   vec![],
+		},);
+
+		let removable = [false, true, false, false, false, false, false,];
+		assert_eq!(elf.remove_sections(&removable,), [".debug_info"]);
+
+		let reparsed = ElfPatcher::parse(elf.to_bytes(),).unwrap();
+		let name = |index: u32| reparsed.sections[index as usize].name.as_str();
+		let symtab = reparsed.section(".symtab",).unwrap();
+		assert_eq!(name(symtab.header.link,), ".strtab");
+		// the index of the first global symbol is left alone
+		assert_eq!(symtab.header.info, 2);
+		let rela = reparsed.section(".rela.text",).unwrap();
+		assert_eq!(name(rela.header.link,), ".symtab");
+		assert_eq!(name(rela.header.info,), ".text");
+		assert_eq!(reparsed.symbols().unwrap().len(), 1);
+	}
+
+	#[test]
+	fn test_symbols_and_symbolize() {
+		let elf = ElfPatcher::parse(sample_elf(),).unwrap();
+		let symbols = elf.symbols().unwrap();
+		assert_eq!(symbols, [Symbol {
+			name: "sym".to_string(),
+			addr: 0x4000_0004,
+			size: 8,
+		}]);
+		let (symbol, offset,) = symbolize(&symbols, 0x4000_0006,).unwrap();
+		assert_eq!((symbol.name.as_str(), offset,), ("sym", 2,));
+		assert!(symbolize(&symbols, 0x4000_0003,).is_none());
+		assert!(symbolize(&symbols, 0x4000_000c,).is_none());
+	}
+
+	#[test]
+	fn test_alloc_section_can_not_grow() {
+		let mut elf = ElfPatcher::parse(sample_elf(),).unwrap();
+		assert!(elf.set_section_data(".text", vec![0; 32],).is_err());
+		elf.set_section_data(".text", vec![0x11; 4],).unwrap();
+
+		let reparsed = ElfPatcher::parse(elf.to_bytes(),).unwrap();
+		let text = &reparsed.section(".text",).unwrap().data;
+		assert_eq!(&text[..4], &[0x11; 4]);
+		assert_eq!(&text[4..], &[0; 12]);
+	}
+
+	#[test]
+	fn test_check_multiboot2_header() {
+		let mut elf = ElfPatcher::parse(sample_elf(),).unwrap();
+		assert!(elf.check_multiboot2_header().is_ok());
+
+		let length = 24u32;
+		let checksum = 0u32.wrapping_sub(MULTIBOOT2_MAGIC + length,);
+		let mut header = [MULTIBOOT2_MAGIC, 0, length, checksum,]
+			.iter()
+			.flat_map(|word| word.to_le_bytes(),)
+			.collect::<Vec<_,>>();
+		header.extend_from_slice(&[0, 0, 0, 0, 8, 0, 0, 0,],);
+		let section = |offset: u64, data: &[u8]| Section {
+			name:   MULTIBOOT2_SECTION.to_string(),
+			header: SectionHeader {
+				ty: SHT_NOTE,
+				flags: SHF_ALLOC,
+				offset,
+				size: data.len() as u64,
+				addralign: 8,
+				..Default::default()
+			},
+			data:   data.to_vec(),
+		};
+
+		elf.sections.push(section(0x190, &header,),);
+		assert!(elf.check_multiboot2_header().is_ok());
+		// past the search window, straddling its end, and misaligned
+		for offset in [0x9d98, MULTIBOOT2_SEARCH_LEN - 16, 0x194,] {
+			*elf.sections.last_mut().unwrap() = section(offset, &header,);
+			assert!(elf.check_multiboot2_header().is_err());
+		}
+
+		let mut corrupt = header.clone();
+		corrupt[12] ^= 1;
+		*elf.sections.last_mut().unwrap() = section(0x190, &corrupt,);
+		assert!(elf.check_multiboot2_header().is_err());
+		*elf.sections.last_mut().unwrap() = section(0x190, &header[..8],);
+		assert!(elf.check_multiboot2_header().is_err());
+	}
+
+	#[test]
+	fn test_reject_non_elf() {
+		assert!(ElfPatcher::parse(vec![0; 128],).is_err());
+		assert!(ElfPatcher::parse(vec![0; 4],).is_err());
+	}
+
+	#[test]
+	fn test_encode_note_alignment() {
+		let note = encode_note("OSO", 1, b"abcde",);
+		// header(12) + "OSO\0"(4) + desc padded to 8
+		assert_eq!(note.len(), 12 + 4 + 8);
+		assert_eq!(u32::from_le_bytes(note[0..4].try_into().unwrap()), 4);
+		assert_eq!(u32::from_le_bytes(note[4..8].try_into().unwrap()), 5);
+	}
+}
@@ -0,0 +1,338 @@
+//! # Error Code Registry
+//!
+//! Collects the codes which `#[derive(ErrorCode)]` enums give their variants
+//! with `#[oso_error_code(..)]`, across every crate of the workspace. The
+//! derive only sees one enum, so codes shared by different enums are found
+//! here, by [`duplicates`].
+//!
+//! The registry is written twice, from the same [`Entry`] list:
+//!
+//! - [`REGISTRY_MD`]: A table for readers, by [`markdown`]
+//! - [`REGISTRY_RS`]: `oso_error::registry`, by [`rust_table`], so code on
+//!   the target can name the variant behind a code
+//!
+//! ```rust,no_run
+//! use oso_dev_util::error_codes::collect;
+//! use oso_dev_util::error_codes::duplicates;
+//! use oso_dev_util_helper::fs::all_crates;
+//!
+//! let entries = collect(&all_crates().unwrap(),).unwrap();
+//! for (code, names,) in duplicates(&entries,) {
+//!     println!("{code:#06x}: {}", names.join(", "));
+//! }
+//! ```
+
+use anyhow::Result as Rslt;
+use oso_dev_util_helper::fs::CARGO_MANIFEST;
+use oso_dev_util_helper::fs::read_toml;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+use syn::punctuated::Punctuated;
+
+/// Markdown registry, relative to the project root
+pub const REGISTRY_MD: &str = "components/shared/core/ERROR_CODES.md";
+/// Rust registry, relative to the project root
+pub const REGISTRY_RS: &str = "components/shared/core/src/registry.rs";
+/// Attribute carrying the code of a variant
+const ATTR: &str = "oso_error_code";
+/// Derive which reads [`ATTR`]
+const DERIVE: &str = "ErrorCode";
+
+/// Variant of an error enum and its code
+///
+/// # Fields
+///
+/// * `code` - Value of `#[oso_error_code(..)]`
+/// * `name` - Path of the variant, starting with its package name
+/// * `doc` - Documentation of the variant, joined into one line
+/// * `file` - Source file declaring the variant
+#[derive(Clone, Debug, PartialEq, Eq,)]
+pub struct Entry {
+	pub code: u16,
+	pub name: String,
+	pub doc:  String,
+	pub file: PathBuf,
+}
+
+/// Entries of the crates at `crates`, sorted by code
+pub fn collect(crates: &[PathBuf],) -> Rslt<Vec<Entry,>,> {
+	let mut entries = vec![];
+	for dir in crates {
+		let Some(manifest,) = read_toml(dir.join(CARGO_MANIFEST,),) else {
+			continue;
+		};
+		let manifest = manifest?;
+		// virtual manifest of the workspace
+		let Some(package,) = manifest
+			.get("package",)
+			.and_then(|p| p.get("name",),)
+			.and_then(|n| n.as_str(),)
+		else {
+			continue;
+		};
+		let src = dir.join("src",);
+		collect_dir(&src, &src, package, &mut entries,)?;
+	}
+	entries.sort_by(|a, b| (a.code, &a.name,).cmp(&(b.code, &b.name,),),);
+	Ok(entries,)
+}
+
+/// Codes used by more than one variant, with the names of the variants
+pub fn duplicates(entries: &[Entry],) -> Vec<(u16, Vec<String,>,),> {
+	let mut by_code: BTreeMap<u16, Vec<String,>,> = BTreeMap::new();
+	for entry in entries {
+		by_code.entry(entry.code,).or_default().push(entry.name.clone(),);
+	}
+	by_code.into_iter().filter(|(_, names,)| names.len() > 1,).collect()
+}
+
+/// Registry as a markdown table
+pub fn markdown(entries: &[Entry],) -> String {
+	let mut out = "# Error Codes\n\nGenerated by `cargo xtask error-codes` \
+	               from the `#[oso_error_code(..)]` of each variant. Do not \
+	               edit.\n\n| Code | Variant | Description |\n| ---- | \
+	               ------- | ----------- |\n"
+		.to_string();
+	for Entry { code, name, doc, .. } in entries {
+		let doc = doc.replace('|', "\\|",);
+		out.push_str(&format!("| `{code:#06x}` | `{name}` | {doc} |\n"),);
+	}
+	out
+}
+
+/// Registry as the source of `oso_error::registry`
+pub fn rust_table(entries: &[Entry],) -> String {
+	let mut out = RUST_HEADER.to_string();
+	for Entry { code, name, doc, .. } in entries {
+		out.push_str(&format!(
+			"\tEntry {{\n\t\tcode: {code:#06x},\n\t\tname: {name:?},\n\t\t\
+			 doc:  {doc:?},\n\t}},\n"
+		),);
+	}
+	out.push_str("];\n",);
+	out
+}
+
+const RUST_HEADER: &str = "//! # Error Code Registry
+//!
+//! Every error code of the workspace, generated by `cargo xtask error-codes`
+//! from the `#[oso_error_code(..)]` of each variant. Do not edit
+
+/// Variant of an error enum and its code
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct Entry {
+\tpub code: u16,
+\t/// path of the variant, starting with its package name
+\tpub name: &'static str,
+\t/// documentation of the variant, joined into one line
+\tpub doc:  &'static str,
+}
+
+/// Entry of `code`
+pub fn lookup(code: u16,) -> Option<&'static Entry,> {
+\tlet i = ENTRIES.binary_search_by_key(&code, |entry| entry.code,).ok()?;
+\tSome(&ENTRIES[i],)
+}
+
+/// Every entry, sorted by code
+pub const ENTRIES: &[Entry] = &[
+";
+
+fn collect_dir(
+	src: &Path,
+	dir: &Path,
+	package: &str,
+	entries: &mut Vec<Entry,>,
+) -> Rslt<(),> {
+	let Ok(read_dir,) = dir.read_dir() else {
+		return Ok((),);
+	};
+	for entry in read_dir {
+		let path = entry?.path();
+		if path.is_dir() {
+			collect_dir(src, &path, package, entries,)?;
+			continue;
+		}
+		if path.extension().is_none_or(|ext| ext != "rs",) {
+			continue;
+		}
+		let source = std::fs::read_to_string(&path,)?;
+		// most files have no codes, and need not be parsed
+		if !source.contains(ATTR,) {
+			continue;
+		}
+		let file = syn::parse_file(&source,)
+			.map_err(|e| anyhow::anyhow!("{}: {e}", path.display()),)?;
+		let module = module_path(package, path.strip_prefix(src,)?,);
+		collect_items(&file.items, &module, &path, entries,)?;
+	}
+	Ok((),)
+}
+
+/// `oso_error::kernel` for `kernel.rs` of the package `oso_error`
+fn module_path(package: &str, rel: &Path,) -> String {
+	let mut module = package.to_string();
+	for part in rel.with_extension("",).iter() {
+		let part = part.to_string_lossy();
+		if !["lib", "main", "mod",].contains(&part.as_ref(),) {
+			module = format!("{module}::{part}");
+		}
+	}
+	module
+}
+
+fn collect_items(
+	items: &[syn::Item],
+	module: &str,
+	file: &Path,
+	entries: &mut Vec<Entry,>,
+) -> Rslt<(),> {
+	for item in items {
+		match item {
+			syn::Item::Enum(item,) if derives_error_code(&item.attrs,) => {
+				let prefix = format!("{module}::{}", item.ident);
+				for variant in &item.variants {
+					let Some(code,) = code_of(&variant.attrs,)? else {
+						// rejected by the derive
+						continue;
+					};
+					entries.push(Entry {
+						code,
+						name: format!("{prefix}::{}", variant.ident),
+						doc: doc_of(&variant.attrs,),
+						file: file.to_path_buf(),
+					},);
+				}
+			},
+			syn::Item::Mod(item,) => {
+				let Some((_, items,),) = &item.content else {
+					continue;
+				};
+				let module = format!("{module}::{}", item.ident);
+				collect_items(items, &module, file, entries,)?;
+			},
+			_ => {},
+		}
+	}
+	Ok((),)
+}
+
+fn derives_error_code(attrs: &[syn::Attribute],) -> bool {
+	attrs.iter().filter(|a| a.path().is_ident("derive",),).any(|attr| {
+		let paths = attr.parse_args_with(
+			Punctuated::<syn::Path, syn::Token![,],>::parse_terminated,
+		);
+		let is_derive = |path: &syn::Path| {
+			path.segments.last().is_some_and(|s| s.ident == DERIVE,)
+		};
+		paths.is_ok_and(|paths| paths.iter().any(is_derive,),)
+	},)
+}
+
+fn code_of(attrs: &[syn::Attribute],) -> Rslt<Option<u16,>,> {
+	let Some(attr,) = attrs.iter().find(|a| a.path().is_ident(ATTR,),) else {
+		return Ok(None,);
+	};
+	let code: syn::LitInt = attr.parse_args()?;
+	Ok(Some(code.base10_parse()?,),)
+}
+
+/// `///` lines of `attrs`, trimmed and joined with spaces
+fn doc_of(attrs: &[syn::Attribute],) -> String {
+	let lines = attrs.iter().filter_map(|attr| {
+		let syn::Meta::NameValue(meta,) = &attr.meta else {
+			return None;
+		};
+		if !meta.path.is_ident("doc",) {
+			return None;
+		}
+		let syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(doc,), .. },) =
+			&meta.value
+		else {
+			return None;
+		};
+		Some(doc.value().trim().to_string(),)
+	},);
+	lines.collect::<Vec<_,>>().join(" ",)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn write(path: &Path, content: &str,) {
+		std::fs::create_dir_all(path.parent().unwrap(),).unwrap();
+		std::fs::write(path, content,).unwrap();
+	}
+
+	const GPIO: &str = "
+		#[derive(Debug, oso_proc_macro::ErrorCode,)]
+		pub enum GpioError {
+			/// controller has no pin
+			/// with this number
+			#[oso_error_code(0x0e01)]
+			PinOutOfRange { pin: u32 },
+			#[oso_error_code(0x0e02)]
+			NoController,
+		}
+		#[derive(Debug,)]
+		pub enum Plain { #[oso_error_code(0x0e03)] A }
+	";
+	const BLOCK: &str = "
+		pub mod io {
+			#[derive(ErrorCode)]
+			pub enum BlockError {
+				/// a | b
+				#[oso_error_code(0x0e02)]
+				Timeout,
+			}
+		}
+	";
+
+	/// entries of a crate written under the temporary directory `name`
+	fn entries(name: &str,) -> Vec<Entry,> {
+		let root = std::env::temp_dir().join(name,);
+		let _ = std::fs::remove_dir_all(&root,);
+		let dir = root.join("oso_error",);
+		write(&dir.join("Cargo.toml",), "[package]\nname = \"oso_error\"\n",);
+		write(&dir.join("src/lib.rs",), "pub mod kernel;\n",);
+		write(&dir.join("src/kernel.rs",), GPIO,);
+		write(&dir.join("src/driver/mod.rs",), BLOCK,);
+		collect(&[dir,],).unwrap()
+	}
+
+	#[test]
+	fn test_collect_error_codes() {
+		let entries = entries("oso_error_codes_collect",);
+		let names: Vec<_,> = entries.iter().map(|e| e.name.as_str(),).collect();
+		assert_eq!(names, [
+			"oso_error::kernel::GpioError::PinOutOfRange",
+			"oso_error::driver::io::BlockError::Timeout",
+			"oso_error::kernel::GpioError::NoController",
+		]);
+		assert_eq!(entries[0].code, 0x0e01);
+		assert_eq!(entries[0].doc, "controller has no pin with this number");
+		assert_eq!(duplicates(&entries,), vec![(0x0e02, vec![
+			"oso_error::driver::io::BlockError::Timeout".to_string(),
+			"oso_error::kernel::GpioError::NoController".to_string(),
+		])]);
+	}
+
+	#[test]
+	fn test_registry_output() {
+		let entries = entries("oso_error_codes_output",);
+		let md = markdown(&entries,);
+		let row = "| `0x0e02` | `oso_error::driver::io::BlockError::Timeout` | \
+		           a \\| b |\n";
+		assert!(md.contains(row), "{md}");
+
+		let rs = rust_table(&entries,);
+		assert!(rs.starts_with("//! # Error Code Registry\n"));
+		assert!(rs.contains(
+			"\tEntry {\n\t\tcode: 0x0e01,\n\t\tname: \
+			 \"oso_error::kernel::GpioError::PinOutOfRange\",\n"
+		));
+		assert!(rs.ends_with("\t},\n];\n"));
+	}
+}
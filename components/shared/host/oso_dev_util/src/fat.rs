@@ -0,0 +1,790 @@
+//! # FAT32 Image Writer
+//!
+//! Creates and updates FAT32 file systems stored in a plain file, without
+//! mounting or external tools. The image has no partition table; UEFI
+//! firmware reads such a "superfloppy" directly.
+//!
+//! Only what image assembly needs is supported:
+//!
+//! - Formatting a new image
+//! - Creating directories and writing, replacing and reading files
+//! - Long file names, so `oso_kernel.elf` keeps its name
+//!
+//! Files are replaced in place: clusters of the old content are freed and
+//! the directory entry is updated, so other files are left untouched.
+//!
+//! ```rust,no_run
+//! use oso_dev_util::fat::FatImage;
+//!
+//! let mut img = FatImage::create("disk.img", 64 << 20,).unwrap();
+//! img.write_file("EFI/BOOT/BOOTAA64.EFI", b"...",).unwrap();
+//! img.flush().unwrap();
+//! ```
+
+use anyhow::Result as Rslt;
+use anyhow::bail;
+use anyhow::ensure;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+
+const SECTOR_SIZE: u32 = 512;
+const RESERVED_SECTORS: u32 = 32;
+const FAT_COUNT: u32 = 2;
+const ROOT_CLUSTER: u32 = 2;
+/// FAT32 needs at least this many clusters, or readers take it for FAT16
+const MIN_CLUSTERS: u32 = 65525;
+const DIR_ENTRY_SIZE: usize = 32;
+
+const FREE: u32 = 0;
+const END_OF_CHAIN: u32 = 0x0FFF_FFFF;
+/// Entries at or above this value end a chain
+const END_OF_CHAIN_MIN: u32 = 0x0FFF_FFF8;
+const ENTRY_MASK: u32 = 0x0FFF_FFFF;
+
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_ARCHIVE: u8 = 0x20;
+const ATTR_LONG_NAME: u8 = 0x0F;
+const DELETED: u8 = 0xE5;
+const LAST_LONG_ENTRY: u8 = 0x40;
+/// UCS-2 characters stored in one long name entry
+const LONG_NAME_CHARS: usize = 13;
+/// Timestamp of every entry: 2024-01-01 00:00. Fixed so that images are
+/// reproducible
+const DATE: u16 = ((2024 - 1980) << 9) | (1 << 5) | 1;
+
+/// Layout read from the boot sector
+#[derive(Clone, Copy, Debug, PartialEq, Eq,)]
+struct Geometry {
+	sectors_per_cluster: u32,
+	reserved_sectors:    u32,
+	fat_sectors:         u32,
+	total_sectors:       u32,
+}
+
+impl Geometry {
+	fn for_size(size: u64,) -> Rslt<Self,> {
+		let total_sectors = u32::try_from(size / SECTOR_SIZE as u64,)?;
+		let sectors_per_cluster = 1;
+		// the FAT shrinks the data area it describes. iterate until stable
+		let mut fat_sectors = 1;
+		loop {
+			let data = total_sectors
+				.saturating_sub(RESERVED_SECTORS + FAT_COUNT * fat_sectors,);
+			let clusters = data / sectors_per_cluster;
+			let needed = ((clusters + 2) * 4).div_ceil(SECTOR_SIZE,);
+			if needed <= fat_sectors {
+				break;
+			}
+			fat_sectors = needed;
+		}
+
+		let geometry = Self {
+			sectors_per_cluster,
+			reserved_sectors: RESERVED_SECTORS,
+			fat_sectors,
+			total_sectors,
+		};
+		ensure!(
+			geometry.cluster_count() >= MIN_CLUSTERS,
+			"{size} bytes is too small for FAT32"
+		);
+		Ok(geometry,)
+	}
+
+	fn parse(boot: &[u8; SECTOR_SIZE as usize],) -> Rslt<Self,> {
+		let u16_at = |i: usize| u16::from_le_bytes([boot[i], boot[i + 1],],);
+		let u32_at = |i: usize| {
+			u32::from_le_bytes(boot[i..i + 4].try_into().unwrap(),)
+		};
+		ensure!(boot[510..] == [0x55, 0xAA], "missing boot signature");
+		ensure!(&boot[82..90] == b"FAT32   ", "not a FAT32 file system");
+		ensure!(
+			u16_at(11,) as u32 == SECTOR_SIZE,
+			"only {SECTOR_SIZE} byte sectors are supported"
+		);
+		ensure!(
+			u32_at(44,) == ROOT_CLUSTER,
+			"root directory must start at cluster {ROOT_CLUSTER}"
+		);
+		Ok(Self {
+			sectors_per_cluster: boot[13] as u32,
+			reserved_sectors:    u16_at(14,) as u32,
+			fat_sectors:         u32_at(36,),
+			total_sectors:       u32_at(32,),
+		},)
+	}
+
+	fn cluster_size(&self,) -> usize {
+		(self.sectors_per_cluster * SECTOR_SIZE) as usize
+	}
+
+	fn data_start(&self,) -> u64 {
+		(self.reserved_sectors + FAT_COUNT * self.fat_sectors) as u64
+			* SECTOR_SIZE as u64
+	}
+
+	fn cluster_count(&self,) -> u32 {
+		let data = self.total_sectors
+			- self.reserved_sectors
+			- FAT_COUNT * self.fat_sectors;
+		data / self.sectors_per_cluster
+	}
+
+	fn cluster_offset(&self, cluster: u32,) -> u64 {
+		self.data_start() + (cluster - 2) as u64 * self.cluster_size() as u64
+	}
+
+	fn fat_offset(&self, copy: u32,) -> u64 {
+		(self.reserved_sectors + copy * self.fat_sectors) as u64
+			* SECTOR_SIZE as u64
+	}
+
+	fn boot_sector(&self,) -> [u8; SECTOR_SIZE as usize] {
+		let mut b = [0u8; SECTOR_SIZE as usize];
+		b[..3].copy_from_slice(&[0xEB, 0x58, 0x90,],);
+		b[3..11].copy_from_slice(b"OSO     ",);
+		b[11..13].copy_from_slice(&(SECTOR_SIZE as u16).to_le_bytes(),);
+		b[13] = self.sectors_per_cluster as u8;
+		let reserved = self.reserved_sectors as u16;
+		b[14..16].copy_from_slice(&reserved.to_le_bytes(),);
+		b[16] = FAT_COUNT as u8;
+		// media descriptor of fixed disks
+		b[21] = 0xF8;
+		b[24..26].copy_from_slice(&32u16.to_le_bytes(),);
+		b[26..28].copy_from_slice(&64u16.to_le_bytes(),);
+		b[32..36].copy_from_slice(&self.total_sectors.to_le_bytes(),);
+		b[36..40].copy_from_slice(&self.fat_sectors.to_le_bytes(),);
+		b[44..48].copy_from_slice(&ROOT_CLUSTER.to_le_bytes(),);
+		// fs info and backup boot sector
+		b[48..50].copy_from_slice(&1u16.to_le_bytes(),);
+		b[50..52].copy_from_slice(&6u16.to_le_bytes(),);
+		b[64] = 0x80;
+		b[66] = 0x29;
+		b[67..71].copy_from_slice(&0x050_0050u32.to_le_bytes(),);
+		b[71..82].copy_from_slice(b"OSO        ",);
+		b[82..90].copy_from_slice(b"FAT32   ",);
+		b[510..].copy_from_slice(&[0x55, 0xAA,],);
+		b
+	}
+
+	fn fs_info_sector() -> [u8; SECTOR_SIZE as usize] {
+		let mut b = [0u8; SECTOR_SIZE as usize];
+		b[..4].copy_from_slice(&0x4161_5252u32.to_le_bytes(),);
+		b[484..488].copy_from_slice(&0x6141_7272u32.to_le_bytes(),);
+		// free count and next free cluster are unknown
+		b[488..496].fill(0xFF,);
+		b[508..].copy_from_slice(&0xAA55_0000u32.to_le_bytes(),);
+		b
+	}
+}
+
+/// Entry found by [`FatImage`] in a directory
+#[derive(Clone, Debug, PartialEq, Eq,)]
+struct Entry {
+	/// Long name if present, short name otherwise
+	name:    String,
+	short:   [u8; 11],
+	/// Index of the short entry in the directory
+	slot:    usize,
+	cluster: u32,
+	size:    u32,
+	is_dir:  bool,
+}
+
+/// FAT32 file system stored in a file
+pub struct FatImage {
+	file:     File,
+	geometry: Geometry,
+	fat:      Vec<u32,>,
+}
+
+impl FatImage {
+	/// Creates a new image of `size` bytes at `path`, replacing any existing
+	/// file
+	///
+	/// # Errors
+	///
+	/// Returns an error if `size` is too small for FAT32. 33 MiB is enough
+	pub fn create(path: impl AsRef<Path,>, size: u64,) -> Rslt<Self,> {
+		let geometry = Geometry::for_size(size,)?;
+		let mut file = OpenOptions::new()
+			.read(true,)
+			.write(true,)
+			.create(true,)
+			.truncate(true,)
+			.open(path,)?;
+		file.set_len(geometry.total_sectors as u64 * SECTOR_SIZE as u64,)?;
+
+		let boot = geometry.boot_sector();
+		let fs_info = Geometry::fs_info_sector();
+		for base in [0, 6,] {
+			file.seek(SeekFrom::Start(base * SECTOR_SIZE as u64,),)?;
+			file.write_all(&boot,)?;
+			file.write_all(&fs_info,)?;
+		}
+
+		let mut fat = vec![FREE; geometry.cluster_count() as usize + 2];
+		fat[0] = 0x0FFF_FFF8;
+		fat[1] = END_OF_CHAIN;
+		fat[ROOT_CLUSTER as usize] = END_OF_CHAIN;
+		let mut img = Self { file, geometry, fat, };
+		img.write_cluster(ROOT_CLUSTER, &vec![0; geometry.cluster_size()],)?;
+		img.flush()?;
+		Ok(img,)
+	}
+
+	/// Opens an image created by [`FatImage::create`]
+	pub fn open(path: impl AsRef<Path,>,) -> Rslt<Self,> {
+		let mut file = OpenOptions::new().read(true,).write(true,).open(path,)?;
+		let mut boot = [0u8; SECTOR_SIZE as usize];
+		file.read_exact(&mut boot,)?;
+		let geometry = Geometry::parse(&boot,)?;
+
+		let mut raw = vec![0u8; (geometry.cluster_count() as usize + 2) * 4];
+		file.seek(SeekFrom::Start(geometry.fat_offset(0,),),)?;
+		file.read_exact(&mut raw,)?;
+		let fat = raw
+			.chunks_exact(4,)
+			.map(|b| u32::from_le_bytes(b.try_into().unwrap(),) & ENTRY_MASK,)
+			.collect();
+		Ok(Self { file, geometry, fat, },)
+	}
+
+	/// Writes `data` to `path`, creating parent directories. An existing file
+	/// is replaced
+	///
+	/// `path` is separated by `/` and matched case-insensitively.
+	pub fn write_file(&mut self, path: &str, data: &[u8],) -> Rslt<(),> {
+		let (dir, name,) = self.parent_dir(path, true,)?;
+		let dir = dir.expect("parent directories are created",);
+
+		let clusters = data.len().div_ceil(self.geometry.cluster_size(),);
+		let size = u32::try_from(data.len(),)?;
+		let existing = self.find(dir, name,)?;
+		if let Some(entry,) = &existing {
+			ensure!(!entry.is_dir, "{path} is a directory");
+			self.free_chain(entry.cluster,);
+		}
+
+		let chain = self.alloc_chain(clusters,)?;
+		for (cluster, chunk,) in
+			chain.iter().zip(data.chunks(self.geometry.cluster_size(),),)
+		{
+			self.write_cluster(*cluster, chunk,)?;
+		}
+		let first = chain.first().copied().unwrap_or(FREE,);
+
+		match existing {
+			Some(entry,) => {
+				let mut raw = self.read_slot(dir, entry.slot,)?;
+				set_cluster(&mut raw, first,);
+				raw[28..32].copy_from_slice(&size.to_le_bytes(),);
+				self.write_slot(dir, entry.slot, &raw,)?;
+			},
+			None => self.add_entry(dir, name, ATTR_ARCHIVE, first, size,)?,
+		}
+		Ok((),)
+	}
+
+	/// Reads the file at `path`. `None` if it does not exist
+	pub fn read_file(&mut self, path: &str,) -> Rslt<Option<Vec<u8,>,>,> {
+		let (Some(dir,), name,) = self.parent_dir(path, false,)? else {
+			return Ok(None,);
+		};
+		let Some(entry,) = self.find(dir, name,)? else {
+			return Ok(None,);
+		};
+		ensure!(!entry.is_dir, "{path} is a directory");
+
+		let mut data = Vec::with_capacity(entry.size as usize,);
+		for cluster in self.chain(entry.cluster,) {
+			data.extend(self.read_cluster(cluster,)?,);
+		}
+		data.truncate(entry.size as usize,);
+		Ok(Some(data,),)
+	}
+
+	/// Names in the directory at `path`. `""` is the root directory
+	pub fn list(&mut self, path: &str,) -> Rslt<Vec<String,>,> {
+		let mut dir = ROOT_CLUSTER;
+		for name in path.split('/',).filter(|s| !s.is_empty(),) {
+			match self.find(dir, name,)? {
+				Some(entry,) if entry.is_dir => dir = entry.cluster,
+				_ => bail!("{path} is not a directory"),
+			}
+		}
+		Ok(self
+			.entries(dir,)?
+			.into_iter()
+			.map(|e| e.name,)
+			.filter(|name| name != "." && name != "..",)
+			.collect(),)
+	}
+
+	/// Writes both copies of the FAT
+	pub fn flush(&mut self,) -> Rslt<(),> {
+		let raw: Vec<u8,> =
+			self.fat.iter().flat_map(|e| e.to_le_bytes(),).collect();
+		for copy in 0..FAT_COUNT {
+			self.file.seek(SeekFrom::Start(self.geometry.fat_offset(copy,),),)?;
+			self.file.write_all(&raw,)?;
+		}
+		self.file.flush()?;
+		Ok((),)
+	}
+
+	/// Resolves the directory containing `path`. Returns the directory's
+	/// cluster, `None` if it does not exist and `create` is false
+	fn parent_dir<'a,>(
+		&mut self,
+		path: &'a str,
+		create: bool,
+	) -> Rslt<(Option<u32,>, &'a str,),> {
+		let mut parts: Vec<&str,> =
+			path.split('/',).filter(|s| !s.is_empty(),).collect();
+		let Some(name,) = parts.pop() else {
+			bail!("empty path");
+		};
+
+		let mut dir = ROOT_CLUSTER;
+		for part in parts {
+			dir = match self.find(dir, part,)? {
+				Some(entry,) if entry.is_dir => entry.cluster,
+				Some(_,) => bail!("{part} in {path} is not a directory"),
+				None if create => self.make_dir(dir, part,)?,
+				None => return Ok((None, name,),),
+			};
+		}
+		Ok((Some(dir,), name,),)
+	}
+
+	fn make_dir(&mut self, parent: u32, name: &str,) -> Rslt<u32,> {
+		let cluster = self.alloc_chain(1,)?[0];
+		let mut data = vec![0u8; self.geometry.cluster_size()];
+		let dot = short_entry(*b".          ", ATTR_DIRECTORY, cluster, 0,);
+		// `..` of a top level directory points to cluster 0, not the root
+		let parent_cluster = if parent == ROOT_CLUSTER { 0 } else { parent };
+		let dotdot =
+			short_entry(*b"..         ", ATTR_DIRECTORY, parent_cluster, 0,);
+		data[..DIR_ENTRY_SIZE].copy_from_slice(&dot,);
+		data[DIR_ENTRY_SIZE..2 * DIR_ENTRY_SIZE].copy_from_slice(&dotdot,);
+		self.write_cluster(cluster, &data,)?;
+		self.add_entry(parent, name, ATTR_DIRECTORY, cluster, 0,)?;
+		Ok(cluster,)
+	}
+
+	fn add_entry(
+		&mut self,
+		dir: u32,
+		name: &str,
+		attr: u8,
+		cluster: u32,
+		size: u32,
+	) -> Rslt<(),> {
+		let existing = self.entries(dir,)?;
+		let (short, long,) = match short_name(name,) {
+			Some(short,) => (short, None,),
+			None => {
+				let short = unique_short_name(name, &existing,);
+				(short, Some(name,),)
+			},
+		};
+
+		let mut raws = vec![];
+		if let Some(long,) = long {
+			raws = long_entries(long, checksum(&short,),)?;
+		}
+		raws.push(short_entry(short, attr, cluster, size,),);
+
+		let start = self.free_slots(dir, raws.len(),)?;
+		for (i, raw,) in raws.iter().enumerate() {
+			self.write_slot(dir, start + i, raw,)?;
+		}
+		Ok((),)
+	}
+
+	/// Finds `count` consecutive unused slots, growing the directory if needed
+	fn free_slots(&mut self, dir: u32, count: usize,) -> Rslt<usize,> {
+		let raw = self.read_chain(dir,)?;
+		let slots = raw.len() / DIR_ENTRY_SIZE;
+		let mut run = 0;
+		for slot in 0..slots {
+			let first = raw[slot * DIR_ENTRY_SIZE];
+			if first == 0 || first == DELETED {
+				run += 1;
+				if run == count {
+					return Ok(slot + 1 - count,);
+				}
+			} else {
+				run = 0;
+			}
+		}
+
+		// append clusters. the run may continue into them
+		let per_cluster = self.geometry.cluster_size() / DIR_ENTRY_SIZE;
+		let missing = count - run;
+		let last = *self.chain(dir,).last().expect("directory has a cluster",);
+		let added = self.alloc_chain(missing.div_ceil(per_cluster,),)?;
+		self.fat[last as usize] = added[0];
+		for cluster in added {
+			let zero = vec![0; self.geometry.cluster_size()];
+			self.write_cluster(cluster, &zero,)?;
+		}
+		Ok(slots - run,)
+	}
+
+	fn find(&mut self, dir: u32, name: &str,) -> Rslt<Option<Entry,>,> {
+		Ok(self
+			.entries(dir,)?
+			.into_iter()
+			.find(|e| e.name.eq_ignore_ascii_case(name,),),)
+	}
+
+	fn entries(&mut self, dir: u32,) -> Rslt<Vec<Entry,>,> {
+		let raw = self.read_chain(dir,)?;
+		let mut entries = vec![];
+		let mut long: Vec<(usize, [u8; DIR_ENTRY_SIZE],),> = vec![];
+		for (slot, e,) in raw.chunks_exact(DIR_ENTRY_SIZE,).enumerate() {
+			let e: [u8; DIR_ENTRY_SIZE] = e.try_into().unwrap();
+			match e[0] {
+				0 => break,
+				DELETED => {
+					long.clear();
+					continue;
+				},
+				_ => {},
+			}
+			if e[11] == ATTR_LONG_NAME {
+				long.push((slot, e,),);
+				continue;
+			}
+
+			let short: [u8; 11] = e[..11].try_into().unwrap();
+			let long_name = (!long.is_empty()
+				&& long.iter().all(|(_, l,)| l[13] == checksum(&short,),))
+			.then(|| decode_long_name(&long,),);
+			entries.push(Entry {
+				name: long_name.unwrap_or_else(|| display_short_name(&short,),),
+				short,
+				slot,
+				cluster: (u16::from_le_bytes([e[20], e[21],],) as u32) << 16
+					| u16::from_le_bytes([e[26], e[27],],) as u32,
+				size: u32::from_le_bytes(e[28..32].try_into().unwrap(),),
+				is_dir: e[11] & ATTR_DIRECTORY != 0,
+			},);
+			long.clear();
+		}
+		Ok(entries,)
+	}
+
+	fn read_slot(
+		&mut self,
+		dir: u32,
+		slot: usize,
+	) -> Rslt<[u8; DIR_ENTRY_SIZE],> {
+		let offset = self.slot_offset(dir, slot,);
+		let mut raw = [0u8; DIR_ENTRY_SIZE];
+		self.file.seek(SeekFrom::Start(offset,),)?;
+		self.file.read_exact(&mut raw,)?;
+		Ok(raw,)
+	}
+
+	fn write_slot(
+		&mut self,
+		dir: u32,
+		slot: usize,
+		raw: &[u8; DIR_ENTRY_SIZE],
+	) -> Rslt<(),> {
+		let offset = self.slot_offset(dir, slot,);
+		self.file.seek(SeekFrom::Start(offset,),)?;
+		self.file.write_all(raw,)?;
+		Ok((),)
+	}
+
+	fn slot_offset(&self, dir: u32, slot: usize,) -> u64 {
+		let per_cluster = self.geometry.cluster_size() / DIR_ENTRY_SIZE;
+		let cluster = self.chain(dir,)[slot / per_cluster];
+		self.geometry.cluster_offset(cluster,)
+			+ (slot % per_cluster * DIR_ENTRY_SIZE) as u64
+	}
+
+	fn chain(&self, first: u32,) -> Vec<u32,> {
+		let mut chain = vec![];
+		let mut cluster = first;
+		while (2..END_OF_CHAIN_MIN).contains(&cluster,)
+			&& chain.len() < self.fat.len()
+		{
+			chain.push(cluster,);
+			cluster = self.fat[cluster as usize];
+		}
+		chain
+	}
+
+	fn alloc_chain(&mut self, count: usize,) -> Rslt<Vec<u32,>,> {
+		let chain: Vec<u32,> = (2..self.fat.len() as u32)
+			.filter(|c| self.fat[*c as usize] == FREE,)
+			.take(count,)
+			.collect();
+		ensure!(chain.len() == count, "image is full");
+		for pair in chain.windows(2,) {
+			self.fat[pair[0] as usize] = pair[1];
+		}
+		if let Some(last,) = chain.last() {
+			self.fat[*last as usize] = END_OF_CHAIN;
+		}
+		Ok(chain,)
+	}
+
+	fn free_chain(&mut self, first: u32,) {
+		for cluster in self.chain(first,) {
+			self.fat[cluster as usize] = FREE;
+		}
+	}
+
+	fn read_chain(&mut self, first: u32,) -> Rslt<Vec<u8,>,> {
+		let mut data = vec![];
+		for cluster in self.chain(first,) {
+			data.extend(self.read_cluster(cluster,)?,);
+		}
+		Ok(data,)
+	}
+
+	fn read_cluster(&mut self, cluster: u32,) -> Rslt<Vec<u8,>,> {
+		let mut data = vec![0u8; self.geometry.cluster_size()];
+		self.file
+			.seek(SeekFrom::Start(self.geometry.cluster_offset(cluster,),),)?;
+		self.file.read_exact(&mut data,)?;
+		Ok(data,)
+	}
+
+	/// Writes `data` to the head of `cluster`. The rest is zero filled
+	fn write_cluster(&mut self, cluster: u32, data: &[u8],) -> Rslt<(),> {
+		let mut buf = vec![0u8; self.geometry.cluster_size()];
+		buf[..data.len()].copy_from_slice(data,);
+		self.file
+			.seek(SeekFrom::Start(self.geometry.cluster_offset(cluster,),),)?;
+		self.file.write_all(&buf,)?;
+		Ok((),)
+	}
+}
+
+fn short_entry(
+	name: [u8; 11],
+	attr: u8,
+	cluster: u32,
+	size: u32,
+) -> [u8; DIR_ENTRY_SIZE] {
+	let mut e = [0u8; DIR_ENTRY_SIZE];
+	e[..11].copy_from_slice(&name,);
+	e[11] = attr;
+	for offset in [16, 18, 24,] {
+		e[offset..offset + 2].copy_from_slice(&DATE.to_le_bytes(),);
+	}
+	set_cluster(&mut e, cluster,);
+	e[28..32].copy_from_slice(&size.to_le_bytes(),);
+	e
+}
+
+fn set_cluster(e: &mut [u8; DIR_ENTRY_SIZE], cluster: u32,) {
+	e[20..22].copy_from_slice(&((cluster >> 16) as u16).to_le_bytes(),);
+	e[26..28].copy_from_slice(&(cluster as u16).to_le_bytes(),);
+}
+
+fn is_short_char(c: u8,) -> bool {
+	c.is_ascii_uppercase()
+		|| c.is_ascii_digit()
+		|| b"!#$%&'()-@^_`{}~".contains(&c,)
+}
+
+/// `name` in 8.3 form, if it can be stored without a long name
+fn short_name(name: &str,) -> Option<[u8; 11],> {
+	let (base, ext,) = name.split_once('.',).unwrap_or((name, "",),);
+	let valid = !base.is_empty()
+		&& base.len() <= 8
+		&& ext.len() <= 3
+		&& base.bytes().chain(ext.bytes(),).all(is_short_char,);
+	if !valid {
+		return None;
+	}
+
+	let mut short = [b' '; 11];
+	short[..base.len()].copy_from_slice(base.as_bytes(),);
+	short[8..8 + ext.len()].copy_from_slice(ext.as_bytes(),);
+	Some(short,)
+}
+
+/// `BASIS~N.EXT` alias of a long name, unique in the directory
+fn unique_short_name(name: &str, existing: &[Entry],) -> [u8; 11] {
+	let (base, ext,) = match name.rsplit_once('.',) {
+		Some((base, ext,),) if !base.is_empty() => (base, ext,),
+		_ => (name, "",),
+	};
+	let clean = |s: &str, max: usize| -> Vec<u8,> {
+		s.bytes()
+			.map(|c| c.to_ascii_uppercase(),)
+			.filter(|c| *c != b'.' && *c != b' ',)
+			.map(|c| if is_short_char(c,) { c } else { b'_' },)
+			.take(max,)
+			.collect()
+	};
+	let base = clean(base, 8,);
+	let ext = clean(ext, 3,);
+
+	for n in 1.. {
+		let tail = format!("~{n}");
+		let keep = base.len().min(8 - tail.len(),);
+		let mut short = [b' '; 11];
+		short[..keep].copy_from_slice(&base[..keep],);
+		short[keep..keep + tail.len()].copy_from_slice(tail.as_bytes(),);
+		short[8..8 + ext.len()].copy_from_slice(&ext,);
+		if existing.iter().all(|e| e.short != short,) {
+			return short;
+		}
+	}
+	unreachable!()
+}
+
+fn display_short_name(short: &[u8; 11],) -> String {
+	let base = String::from_utf8_lossy(&short[..8],).trim_end().to_string();
+	let ext = String::from_utf8_lossy(&short[8..],).trim_end().to_string();
+	if ext.is_empty() { base } else { format!("{base}.{ext}") }
+}
+
+fn checksum(short: &[u8; 11],) -> u8 {
+	short.iter().fold(0u8, |sum, c| {
+		(sum & 1).wrapping_shl(7,).wrapping_add(sum >> 1,).wrapping_add(*c,)
+	},)
+}
+
+/// Long name entries of `name`, in the order they are stored
+fn long_entries(
+	name: &str,
+	checksum: u8,
+) -> Rslt<Vec<[u8; DIR_ENTRY_SIZE],>,> {
+	let mut chars: Vec<u16,> = name.encode_utf16().collect();
+	ensure!(chars.len() <= 255, "{name} is longer than 255 characters");
+	if !chars.len().is_multiple_of(LONG_NAME_CHARS,) {
+		chars.push(0,);
+	}
+	while !chars.len().is_multiple_of(LONG_NAME_CHARS,) {
+		chars.push(0xFFFF,);
+	}
+
+	let count = chars.len() / LONG_NAME_CHARS;
+	let entries = chars
+		.chunks(LONG_NAME_CHARS,)
+		.enumerate()
+		.map(|(i, part,)| {
+			let mut e = [0u8; DIR_ENTRY_SIZE];
+			e[0] = (i + 1) as u8;
+			if i + 1 == count {
+				e[0] |= LAST_LONG_ENTRY;
+			}
+			e[11] = ATTR_LONG_NAME;
+			e[13] = checksum;
+			for (c, offset,) in part.iter().zip(long_name_offsets(),) {
+				e[offset..offset + 2].copy_from_slice(&c.to_le_bytes(),);
+			}
+			e
+		},)
+		.rev()
+		.collect();
+	Ok(entries,)
+}
+
+/// Offsets of the 13 characters in a long name entry
+fn long_name_offsets() -> impl Iterator<Item = usize,> {
+	(1..11)
+		.step_by(2,)
+		.chain((14..26).step_by(2,),)
+		.chain((28..32).step_by(2,),)
+}
+
+fn decode_long_name(entries: &[(usize, [u8; DIR_ENTRY_SIZE],)],) -> String {
+	let mut chars = vec![];
+	// stored last part first
+	for (_, e,) in entries.iter().rev() {
+		for offset in long_name_offsets() {
+			let c = u16::from_le_bytes([e[offset], e[offset + 1],],);
+			if c == 0 {
+				break;
+			}
+			chars.push(c,);
+		}
+	}
+	String::from_utf16_lossy(&chars,)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn image(name: &str,) -> (std::path::PathBuf, FatImage,) {
+		let path = std::env::temp_dir().join(name,);
+		let img = FatImage::create(&path, 40 << 20,).unwrap();
+		(path, img,)
+	}
+
+	#[test]
+	fn test_write_and_read_back() {
+		let (path, mut img,) = image("oso_fat_rw.img",);
+		let big: Vec<u8,> = (0..5000u32).map(|i| i as u8,).collect();
+		img.write_file("EFI/BOOT/BOOTAA64.EFI", &big,).unwrap();
+		img.write_file("oso_kernel.elf", b"kernel",).unwrap();
+		img.flush().unwrap();
+
+		let mut img = FatImage::open(&path,).unwrap();
+		assert_eq!(img.read_file("efi/boot/bootaa64.efi").unwrap(), Some(big));
+		assert_eq!(
+			img.read_file("OSO_KERNEL.ELF").unwrap(),
+			Some(b"kernel".to_vec())
+		);
+		assert_eq!(img.list("").unwrap(), ["EFI", "oso_kernel.elf"]);
+		assert_eq!(img.read_file("missing/file").unwrap(), None);
+	}
+
+	#[test]
+	fn test_replace_frees_old_clusters() {
+		let (_, mut img,) = image("oso_fat_replace.img",);
+		img.write_file("a.bin", &[1; 4096],).unwrap();
+		let used = img.fat.iter().filter(|e| **e != FREE,).count();
+		img.write_file("a.bin", &[2; 100],).unwrap();
+		assert_eq!(img.fat.iter().filter(|e| **e != FREE).count(), used - 7);
+		assert_eq!(img.read_file("a.bin").unwrap(), Some(vec![2; 100]));
+		assert_eq!(img.list("").unwrap(), ["a.bin"]);
+	}
+
+	#[test]
+	fn test_directory_grows() {
+		let (_, mut img,) = image("oso_fat_grow.img",);
+		for i in 0..40 {
+			img.write_file(&format!("dir/long_file_name_{i}.txt"), &[i],)
+				.unwrap();
+		}
+		for i in 0..40 {
+			let data = img.read_file(&format!("dir/long_file_name_{i}.txt"),);
+			assert_eq!(data.unwrap(), Some(vec![i]));
+		}
+		assert_eq!(img.list("dir").unwrap().len(), 40);
+	}
+
+	#[test]
+	fn test_short_names() {
+		assert_eq!(short_name("BOOTAA64.EFI"), Some(*b"BOOTAA64EFI"));
+		assert_eq!(short_name("EFI"), Some(*b"EFI        "));
+		assert_eq!(short_name("boot.efi"), None);
+		assert_eq!(short_name("OSO_KERNEL.ELF"), None);
+		assert_eq!(&unique_short_name("oso_kernel.elf", &[]), b"OSO_KE~1ELF");
+	}
+
+	#[test]
+	fn test_too_small() {
+		let path = std::env::temp_dir().join("oso_fat_small.img",);
+		assert!(FatImage::create(path, 1 << 20).is_err());
+	}
+}
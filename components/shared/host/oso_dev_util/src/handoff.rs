@@ -0,0 +1,630 @@
+//! # Boot Information Decoder
+//!
+//! Host side counterpart of the kernel's `base::handoff` module. Decodes the
+//! boot information the kernel was handed, as serialized by
+//! `BootInfo::serialize` of `oso_no_std_shared`, and renders it for bug
+//! reports.
+//!
+//! A dump is read either as raw bytes starting with [`MAGIC`], or as hex
+//! lines between [`BEGIN_MARKER`] and [`END_MARKER`] anywhere in a text such
+//! as a serial log. When a log holds several dumps, the last one is decoded.
+//!
+//! The format is described in `oso_no_std_shared::bridge::boot_info`. Both
+//! sides must agree on [`VERSION`].
+
+use crate::crash::crc32;
+use crate::crash::find_hex;
+use anyhow::Result as Rslt;
+use anyhow::bail;
+use anyhow::ensure;
+use std::fmt::Write;
+
+/// first bytes of every dump
+pub const MAGIC: &[u8; 4] = b"OSOB";
+/// version of the format understood by [`BootInfoDump::parse`]
+pub const VERSION: u8 = 1;
+/// line before a hex encoded dump
+pub const BEGIN_MARKER: &str = "-----BEGIN OSO BOOT INFO-----";
+/// line after a hex encoded dump
+pub const END_MARKER: &str = "-----END OSO BOOT INFO-----";
+/// names of `MemoryRegionKind` by discriminant
+pub const REGION_KINDS: [&str; 10] = [
+	"usable",
+	"reclaimable",
+	"loader",
+	"runtime code",
+	"runtime data",
+	"acpi reclaim",
+	"acpi nvs",
+	"mmio",
+	"reserved",
+	"framebuffer",
+];
+/// names of `PixelFormatConf` by discriminant
+pub const PIXEL_FORMATS: [&str; 4] = ["rgb", "bgr", "bitmask", "bltonly",];
+/// names of the `RuntimeCaps` service bits, lowest first
+pub const RUNTIME_SERVICES: [&str; 14] = [
+	"get_time",
+	"set_time",
+	"get_wakeup_time",
+	"set_wakeup_time",
+	"get_variable",
+	"get_next_variable_name",
+	"set_variable",
+	"set_virtual_address_map",
+	"convert_pointer",
+	"get_next_high_monotonic_count",
+	"reset_system",
+	"update_capsule",
+	"query_capsule_capabilities",
+	"query_variable_info",
+];
+/// names of `FirmwareService` by discriminant
+pub const FIRMWARE_SERVICES: [&str; 16] = [
+	"AllocatePages",
+	"GetMemoryMap",
+	"AllocatePool",
+	"FreePool",
+	"CreateEvent",
+	"SetTimer",
+	"WaitForEvent",
+	"CloseEvent",
+	"HandleProtocol",
+	"LocateHandleBuffer",
+	"OpenProtocol",
+	"ProtocolsPerHandle",
+	"ConnectController",
+	"Stall",
+	"ExitBootServices",
+	"FreePages",
+];
+
+const TAG_END: u8 = 0;
+const TAG_ABI: u8 = 1;
+const TAG_DEVICE_TREE: u8 = 2;
+const TAG_COMMAND_LINE: u8 = 3;
+const TAG_MEMORY_REGION: u8 = 4;
+const TAG_RUNTIME: u8 = 5;
+const TAG_SEGMENT: u8 = 6;
+const TAG_FRAMEBUFFER: u8 = 7;
+const TAG_MODULE: u8 = 8;
+const TAG_FIRMWARE_CALL: u8 = 9;
+const TAG_MILESTONES: u8 = 10;
+
+/// entry of the memory map
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct Region {
+	pub phys_start: u64,
+	pub virt_start: u64,
+	pub page_count: u64,
+	/// discriminant of `MemoryRegionKind`, see [`REGION_KINDS`]
+	pub kind:       u32,
+	pub attribute:  u64,
+}
+
+/// UEFI runtime services handed over
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct Runtime {
+	/// address of the table, `0` if there is none
+	pub table:     u64,
+	/// service bits, see [`RUNTIME_SERVICES`]
+	pub supported: u32,
+	pub flags:     u32,
+}
+
+/// checksum of a kernel segment as the loader wrote it
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct Segment {
+	pub start: u64,
+	pub size:  u64,
+	/// `p_flags` of the program header
+	pub flags: u32,
+	pub crc32: u32,
+}
+
+/// framebuffer handed over
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct Framebuffer {
+	/// discriminant of `PixelFormatConf`, see [`PIXEL_FORMATS`]
+	pub format: u32,
+	pub base:   u64,
+	pub size:   u64,
+	pub width:  u64,
+	pub height: u64,
+	pub stride: u64,
+}
+
+/// file loaded next to the kernel
+#[derive(Debug, Clone, PartialEq, Eq,)]
+pub struct Module {
+	pub start:   u64,
+	pub size:    u64,
+	pub cmdline: String,
+}
+
+/// boot services call recorded by the loader
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct FirmwareCall {
+	/// counter of the CPU when the call returned
+	pub timestamp: u64,
+	/// `EFI_STATUS` the call returned
+	pub status:    u64,
+	pub args:      [u64; 2],
+	/// discriminant of `FirmwareService`, see [`FIRMWARE_SERVICES`]
+	pub service:   u32,
+}
+
+/// counter values of the loader after `ExitBootServices`, `0` if not
+/// reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct Milestones {
+	/// frequency of the counter in Hz, `0` if unknown
+	pub frequency:          u64,
+	pub exit_boot_services: u64,
+	pub virtual_mode:       u64,
+	pub handoff:            u64,
+}
+
+/// contents of a boot information dump
+#[derive(Debug, Clone, PartialEq, Eq, Default,)]
+pub struct BootInfoDump {
+	/// `BRIDGE_ABI` of the kernel which wrote the dump
+	pub abi:         Option<u32,>,
+	pub device_tree: u64,
+	pub cmdline:     String,
+	pub memory_map:  Vec<Region,>,
+	pub runtime:     Option<Runtime,>,
+	pub segments:    Vec<Segment,>,
+	pub framebuffer: Option<Framebuffer,>,
+	pub modules:     Vec<Module,>,
+	/// boot services calls of the loader, oldest first
+	pub firmware:    Vec<FirmwareCall,>,
+	pub milestones:  Option<Milestones,>,
+}
+
+impl BootInfoDump {
+	/// decodes a dump file, which is either a raw dump or a text holding a
+	/// hex encoded one
+	pub fn decode(file: &[u8],) -> Rslt<Self,> {
+		if file.starts_with(MAGIC,) {
+			return Self::parse(file,);
+		}
+		let text = String::from_utf8_lossy(file,);
+		Self::parse(&find_hex(&text, BEGIN_MARKER, END_MARKER,)?,)
+	}
+
+	/// parses a raw dump, checking its checksum
+	pub fn parse(bytes: &[u8],) -> Rslt<Self,> {
+		ensure!(bytes.starts_with(MAGIC,), "not a boot info dump: bad magic");
+		ensure!(bytes.len() > MAGIC.len(), "boot info dump has no version");
+		let version = bytes[MAGIC.len()];
+		ensure!(
+			version == VERSION,
+			"boot info dump version {version} is not supported, expected \
+			 {VERSION}"
+		);
+
+		let mut dump = Self::default();
+		let mut at = MAGIC.len() + 1;
+		loop {
+			let Some(&[tag, lo, hi,],) = bytes.get(at..at + 3,) else {
+				bail!("boot info dump is cut at {at:#x} before its end record")
+			};
+			let len = u16::from_le_bytes([lo, hi,],) as usize;
+			let Some(payload,) = bytes.get(at + 3..at + 3 + len,) else {
+				bail!("record at {at:#x} exceeds the boot info dump")
+			};
+			let mut fields = Fields { payload, tag, };
+
+			match tag {
+				TAG_END => {
+					ensure!(len == 4, "end record has length {len}");
+					let expected = u32::from_le_bytes(payload.try_into()?,);
+					let actual = crc32(&bytes[..at],);
+					ensure!(
+						expected == actual,
+						"boot info dump checksum mismatch: {actual:#010x} != \
+						 {expected:#010x}"
+					);
+					return Ok(dump,);
+				},
+				TAG_ABI => dump.abi = Some(fields.u32()?,),
+				TAG_DEVICE_TREE => dump.device_tree = fields.u64()?,
+				TAG_COMMAND_LINE => dump.cmdline = fields.text(),
+				TAG_MEMORY_REGION => dump.memory_map.push(Region {
+					phys_start: fields.u64()?,
+					virt_start: fields.u64()?,
+					page_count: fields.u64()?,
+					kind:       fields.u32()?,
+					attribute:  fields.u64()?,
+				},),
+				TAG_RUNTIME => {
+					dump.runtime = Some(Runtime {
+						table:     fields.u64()?,
+						supported: fields.u32()?,
+						flags:     fields.u32()?,
+					},)
+				},
+				TAG_SEGMENT => dump.segments.push(Segment {
+					start: fields.u64()?,
+					size:  fields.u64()?,
+					flags: fields.u32()?,
+					crc32: fields.u32()?,
+				},),
+				TAG_FRAMEBUFFER => {
+					dump.framebuffer = Some(Framebuffer {
+						format: fields.u32()?,
+						base:   fields.u64()?,
+						size:   fields.u64()?,
+						width:  fields.u64()?,
+						height: fields.u64()?,
+						stride: fields.u64()?,
+					},)
+				},
+				TAG_MODULE => dump.modules.push(Module {
+					start:   fields.u64()?,
+					size:    fields.u64()?,
+					cmdline: fields.text(),
+				},),
+				TAG_FIRMWARE_CALL => dump.firmware.push(FirmwareCall {
+					timestamp: fields.u64()?,
+					status:    fields.u64()?,
+					args:      [fields.u64()?, fields.u64()?,],
+					service:   fields.u32()?,
+				},),
+				TAG_MILESTONES => {
+					dump.milestones = Some(Milestones {
+						frequency:          fields.u64()?,
+						exit_boot_services: fields.u64()?,
+						virtual_mode:       fields.u64()?,
+						handoff:            fields.u64()?,
+					},)
+				},
+				// records of later versions of the kernel
+				_ => (),
+			}
+			at += 3 + len;
+		}
+	}
+
+	/// human readable report
+	pub fn render(&self,) -> String {
+		let mut out = String::new();
+		let abi = self.abi.map_or("?".to_string(), |abi| abi.to_string(),);
+		writeln!(out, "bridge abi:  {abi}").unwrap();
+		writeln!(out, "device tree: {:#x}", self.device_tree).unwrap();
+		writeln!(out, "cmdline:     {:?}", self.cmdline).unwrap();
+
+		writeln!(out, "\nmemory map:").unwrap();
+		if self.memory_map.is_empty() {
+			writeln!(out, "  (empty)").unwrap();
+		}
+		for region in &self.memory_map {
+			let kind = REGION_KINDS.get(region.kind as usize,);
+			let kind = kind.map_or(format!("kind {}", region.kind), |kind| {
+				kind.to_string()
+			},);
+			let end = region.phys_start + region.page_count * 4096;
+			write!(out, "  {:#014x}-{end:#014x}", region.phys_start).unwrap();
+			if region.virt_start != region.phys_start {
+				write!(out, " at {:#x}", region.virt_start).unwrap();
+			}
+			writeln!(out, "  {kind}  attr {:#x}", region.attribute).unwrap();
+		}
+		let usable: u64 = self
+			.memory_map
+			.iter()
+			.filter(|region| region.kind == 0,)
+			.map(|region| region.page_count * 4,)
+			.sum();
+		writeln!(out, "  {usable} KiB usable").unwrap();
+
+		writeln!(out, "\nruntime services:").unwrap();
+		match self.runtime {
+			Some(runtime,) if runtime.table != 0 => {
+				let mode =
+					if runtime.flags & 1 != 0 { "virtual" } else { "physical" };
+				writeln!(out, "  table {:#x} ({mode})", runtime.table)
+					.unwrap();
+				let services: Vec<_,> = RUNTIME_SERVICES
+					.iter()
+					.enumerate()
+					.filter(|(bit, _,)| runtime.supported & 1 << bit != 0,)
+					.map(|(_, name,)| *name,)
+					.collect();
+				writeln!(out, "  supported {}", services.join(" ",)).unwrap();
+			},
+			_ => writeln!(out, "  none").unwrap(),
+		}
+
+		writeln!(out, "\nkernel segments:").unwrap();
+		if self.segments.is_empty() {
+			writeln!(out, "  (none)").unwrap();
+		}
+		for segment in &self.segments {
+			let flag = |bit: u32, c: char| {
+				if segment.flags & bit != 0 { c } else { '-' }
+			};
+			writeln!(
+				out,
+				"  {:#014x} {:>10} bytes  r{}{}  crc32 {:#010x}",
+				segment.start,
+				segment.size,
+				flag(0x2, 'w',),
+				flag(0x1, 'x',),
+				segment.crc32
+			)
+			.unwrap();
+		}
+
+		writeln!(out, "\nframebuffer:").unwrap();
+		match self.framebuffer {
+			Some(fb,) => {
+				let format = PIXEL_FORMATS.get(fb.format as usize,);
+				writeln!(
+					out,
+					"  {}x{} {} at {:#x}, stride {} bytes, {} bytes",
+					fb.width,
+					fb.height,
+					format.unwrap_or(&"?",),
+					fb.base,
+					fb.stride,
+					fb.size
+				)
+				.unwrap();
+			},
+			None => writeln!(out, "  none").unwrap(),
+		}
+
+		writeln!(out, "\nmodules:").unwrap();
+		if self.modules.is_empty() {
+			writeln!(out, "  (none)").unwrap();
+		}
+		for module in &self.modules {
+			let (start, size, cmdline,) =
+				(module.start, module.size, &module.cmdline,);
+			writeln!(out, "  {start:#014x} {size:>10} bytes  {cmdline:?}")
+				.unwrap();
+		}
+
+		writeln!(out, "\nfirmware calls:").unwrap();
+		if self.firmware.is_empty() {
+			writeln!(out, "  (none recorded)").unwrap();
+		}
+		let start = self.firmware.first().map_or(0, |call| call.timestamp,);
+		for call in &self.firmware {
+			let service = match FIRMWARE_SERVICES.get(call.service as usize,) {
+				Some(name,) => name.to_string(),
+				None => format!("service {}", call.service),
+			};
+			let [arg0, arg1,] = call.args;
+			writeln!(
+				out,
+				"  +{:<12} {service:<18} {arg0:#x} {arg1:#x}  {}",
+				call.timestamp.wrapping_sub(start,),
+				status_name(call.status)
+			)
+			.unwrap();
+		}
+
+		writeln!(out, "\nloader milestones:").unwrap();
+		match self.milestones {
+			Some(milestones,) => {
+				let Milestones {
+					frequency,
+					exit_boot_services: exit,
+					virtual_mode,
+					handoff,
+				} = milestones;
+				let named = [
+					("exit_boot_services", exit,),
+					("virtual_mode", virtual_mode,),
+					("handoff", handoff,),
+				];
+				for (name, ticks,) in named {
+					let since = ticks.wrapping_sub(exit,);
+					let time = match (ticks, frequency,) {
+						(0, _,) => "not reached".to_string(),
+						(_, 0,) => format!("+{since} ticks"),
+						_ => format!(
+							"+{} us",
+							since as u128 * 1_000_000 / frequency as u128
+						),
+					};
+					writeln!(out, "  {name:<18} {time}").unwrap();
+				}
+			},
+			None => writeln!(out, "  (none)").unwrap(),
+		}
+		out
+	}
+}
+
+/// `success`, or the error or warning number of `status`
+fn status_name(status: u64,) -> String {
+	const ERROR_BIT: u64 = 1 << 63;
+	match status {
+		0 => "success".to_string(),
+		status if status & ERROR_BIT != 0 => {
+			format!("error {}", status & !ERROR_BIT)
+		},
+		status => format!("warning {status}"),
+	}
+}
+
+/// fields of a record, read in order
+struct Fields<'a,> {
+	payload: &'a [u8],
+	tag:     u8,
+}
+
+impl Fields<'_,> {
+	fn u32(&mut self,) -> Rslt<u32,> {
+		Ok(u32::from_le_bytes(self.take::<4>()?,),)
+	}
+
+	fn u64(&mut self,) -> Rslt<u64,> {
+		Ok(u64::from_le_bytes(self.take::<8>()?,),)
+	}
+
+	/// the rest of the record
+	fn text(&mut self,) -> String {
+		let text = String::from_utf8_lossy(self.payload,).into_owned();
+		self.payload = &[];
+		text
+	}
+
+	fn take<const N: usize,>(&mut self,) -> Rslt<[u8; N],> {
+		let Some((field, rest,),) = self.payload.split_first_chunk::<N>()
+		else {
+			bail!("record of tag {} is cut", self.tag)
+		};
+		self.payload = rest;
+		Ok(*field,)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use oso_no_std_shared::bridge::boot_info;
+	use oso_no_std_shared::bridge::boot_info::BootInfo;
+	use oso_no_std_shared::bridge::boot_info::CommandLine;
+	use oso_no_std_shared::bridge::boot_info::FirmwareCall as BootCall;
+	use oso_no_std_shared::bridge::boot_info::FirmwareCalls;
+	use oso_no_std_shared::bridge::boot_info::FirmwareService;
+	use oso_no_std_shared::bridge::boot_info::MemoryRegion;
+	use oso_no_std_shared::bridge::boot_info::MemoryRegionKind;
+	use oso_no_std_shared::bridge::boot_info::MemoryRegions;
+	use oso_no_std_shared::bridge::boot_info::Milestones as BootMilestones;
+	use oso_no_std_shared::bridge::boot_info::Module as BootModule;
+	use oso_no_std_shared::bridge::boot_info::Modules;
+	use oso_no_std_shared::bridge::boot_info::RuntimeCaps;
+
+	struct Bytes(Vec<u8,>,);
+
+	impl boot_info::Write for Bytes {
+		fn write(&mut self, bytes: &[u8],) {
+			self.0.extend_from_slice(bytes,);
+		}
+	}
+
+	/// dump as the kernel writes it, with two memory regions, runtime
+	/// services in virtual mode, an initial ramdisk and a ring of firmware
+	/// calls which dropped its oldest call, and milestones of the loader up
+	/// to `SetVirtualAddressMap`
+	fn sample_dump() -> Vec<u8,> {
+		let cmdline = "console=ttyAMA0 autoexec=off";
+		let regions = [
+			MemoryRegion::new(
+				0x4000_0000,
+				0x4000_0000,
+				256,
+				MemoryRegionKind::Usable,
+				0xf,
+			),
+			MemoryRegion::new(
+				0x4800_0000,
+				0xffff_0000_0000,
+				4,
+				MemoryRegionKind::RuntimeCode,
+				0x8000_0000_0000_000f,
+			),
+		];
+		let initrd = "initrd";
+		let modules = [BootModule::new(0x4900_0000, 512, CommandLine {
+			ptr: initrd.as_ptr(),
+			len: initrd.len(),
+		},),];
+
+		let out_of_resources = 1 << 63 | 9;
+		let exit = FirmwareService::ExitBootServices;
+		let ring = [
+			BootCall::new(exit, [0x1000, 7,], 0, 1_300,),
+			BootCall::new(
+				FirmwareService::AllocatePool,
+				[2, 0x10_0000,],
+				out_of_resources,
+				1_000,
+			),
+		];
+
+		let mut info = BootInfo::new(0x4400_0000 as *const u8,);
+		info.cmdline =
+			CommandLine { ptr: cmdline.as_ptr(), len: cmdline.len(), };
+		info.memory_map = MemoryRegions { ptr: regions.as_ptr(), len: 2, };
+		info.write_runtime_services(0xffff_0000_1000,);
+		info.runtime = RuntimeCaps {
+			supported: RuntimeCaps::GET_TIME | RuntimeCaps::RESET_SYSTEM,
+			flags:     RuntimeCaps::VIRTUAL_MODE,
+		};
+		info.modules = Modules { ptr: modules.as_ptr(), len: 1, };
+		info.firmware_calls =
+			FirmwareCalls { ptr: ring.as_ptr(), capacity: 2, count: 0, };
+		info.firmware_calls.write_count(3,);
+		info.milestones = BootMilestones::empty();
+		info.milestones.write_frequency(1_000_000,);
+		info.milestones.write_exit_boot_services(2_000,);
+		info.milestones.write_virtual_mode(2_150,);
+
+		let mut out = Bytes(vec![],);
+		// SAFETY: every pointer refers to a local which outlives the call
+		unsafe { info.serialize(&mut out,) };
+		out.0
+	}
+
+	#[test]
+	fn test_decode_serialized_from_log() {
+		let hex: String =
+			sample_dump().iter().map(|b| format!("{b:02x}"),).collect();
+		let (head, tail,) = hex.split_at(64,);
+		let log = format!(
+			"osh:1> bootinfo dump\r\n{BEGIN_MARKER}\r\n{head}\r\n{tail}\r\n\
+			 {END_MARKER}\r\n"
+		);
+
+		let dump = BootInfoDump::decode(log.as_bytes(),).unwrap();
+		assert_eq!(dump, BootInfoDump::decode(&sample_dump(),).unwrap());
+		assert_eq!(dump.device_tree, 0x4400_0000);
+		assert_eq!(dump.cmdline, "console=ttyAMA0 autoexec=off");
+		assert_eq!(dump.memory_map.len(), 2);
+		assert_eq!(dump.memory_map[1].virt_start, 0xffff_0000_0000);
+		assert_eq!(dump.memory_map[1].kind, 3);
+		assert_eq!(dump.runtime.unwrap().table, 0xffff_0000_1000);
+		assert_eq!(dump.framebuffer, None);
+		assert_eq!(dump.modules, [Module {
+			start:   0x4900_0000,
+			size:    512,
+			cmdline: "initrd".to_string(),
+		}]);
+
+		let report = dump.render();
+		assert!(report.contains("1024 KiB usable"));
+		assert!(report.contains("runtime code"));
+		assert!(report.contains("(virtual)"));
+		assert!(report.contains("supported get_time reset_system"));
+
+		let services: Vec<_,> =
+			dump.firmware.iter().map(|call| call.service,).collect();
+		assert_eq!(services, [2, 14]);
+		assert_eq!(dump.firmware[0].args, [2, 0x10_0000]);
+		assert!(report.contains("AllocatePool       0x2 0x100000  error 9\n"));
+		assert!(report.contains("+300          ExitBootServices"));
+
+		let milestones = dump.milestones.unwrap();
+		assert_eq!(milestones.virtual_mode, 2_150);
+		assert!(report.contains("  virtual_mode       +150 us\n"));
+		assert!(report.contains("  handoff            not reached\n"));
+	}
+
+	#[test]
+	fn test_reject_corrupted() {
+		let mut dump = sample_dump();
+		dump[12] ^= 1;
+		let err = BootInfoDump::parse(&dump,).unwrap_err();
+		assert!(err.to_string().contains("checksum"));
+
+		let cut = &sample_dump()[..30];
+		assert!(BootInfoDump::parse(cut,).is_err());
+	}
+}
@@ -0,0 +1,227 @@
+//! # Incremental Disk Image Assembly
+//!
+//! Copies build artifacts into a FAT disk image. Content hashes of the copied
+//! files are kept in a manifest next to the image, so that rebuilding only
+//! the kernel rewrites only the kernel, and rebuilding nothing leaves the
+//! image untouched.
+//!
+//! The image is created from scratch when it is missing, unreadable, has no
+//! manifest, or the set of files changes.
+//!
+//! ```rust,no_run
+//! use oso_dev_util::image::ImageFile;
+//! use oso_dev_util::image::assemble;
+//!
+//! let files = [ImageFile::new("EFI/BOOT/BOOTAA64.EFI", "oso_loader.efi",)];
+//! let status = assemble("disk.img".as_ref(), &files,).unwrap();
+//! println!("{status}");
+//! ```
+
+use crate::fat::FatImage;
+use anyhow::Context as _;
+use anyhow::Result as Rslt;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Size of newly created images
+pub const IMAGE_SIZE: u64 = 64 << 20;
+
+/// File to be placed in the image
+///
+/// # Fields
+///
+/// * `dest` - Path inside the image, separated by `/`
+/// * `src` - Path on the host
+#[derive(Clone, Debug, PartialEq, Eq,)]
+pub struct ImageFile {
+	pub dest: String,
+	pub src:  PathBuf,
+}
+
+impl ImageFile {
+	pub fn new(dest: impl Into<String,>, src: impl Into<PathBuf,>,) -> Self {
+		Self { dest: dest.into(), src: src.into(), }
+	}
+}
+
+/// What [`assemble`] did
+#[derive(Clone, Debug, PartialEq, Eq,)]
+pub enum ImageStatus {
+	/// Every file matched the manifest
+	UpToDate,
+	/// Only the listed files were rewritten
+	Patched(Vec<String,>,),
+	/// The image was created from scratch
+	Created,
+}
+
+impl Display for ImageStatus {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_,>,) -> std::fmt::Result {
+		match self {
+			Self::UpToDate => write!(f, "image up to date"),
+			Self::Patched(files,) => {
+				write!(f, "image patched: {}", files.join(", "))
+			},
+			Self::Created => write!(f, "image created"),
+		}
+	}
+}
+
+/// Brings the image at `img` up to date with `files`
+///
+/// # Errors
+///
+/// Returns an error if a source file can't be read or the image can't be
+/// written. The manifest is removed before the image is modified, so an
+/// interrupted run is followed by a full rebuild
+pub fn assemble(img: &Path, files: &[ImageFile],) -> Rslt<ImageStatus,> {
+	let contents = files
+		.iter()
+		.map(|f| {
+			std::fs::read(&f.src,)
+				.with_context(|| format!("failed to read {}", f.src.display()),)
+		},)
+		.collect::<Rslt<Vec<_,>,>>()?;
+	let hashes: BTreeMap<String, u64,> = files
+		.iter()
+		.zip(&contents,)
+		.map(|(f, data,)| (f.dest.clone(), fnv1a(data,),),)
+		.collect();
+
+	let manifest_path = manifest_path(img,);
+	let manifest = read_manifest(&manifest_path,);
+	let same_files = manifest
+		.as_ref()
+		.is_some_and(|m| m.keys().eq(hashes.keys(),),);
+	let opened = if same_files { FatImage::open(img,).ok() } else { None };
+
+	let (mut fat, status,) = match (opened, manifest,) {
+		(Some(fat,), Some(manifest,),) => {
+			let changed: Vec<String,> = hashes
+				.iter()
+				.filter(|(dest, hash,)| manifest.get(*dest,) != Some(hash,),)
+				.map(|(dest, _,)| dest.clone(),)
+				.collect();
+			if changed.is_empty() {
+				return Ok(ImageStatus::UpToDate,);
+			}
+			(fat, ImageStatus::Patched(changed,),)
+		},
+		_ => {
+			let fat = FatImage::create(img, IMAGE_SIZE,).with_context(|| {
+				format!("failed to create {}", img.display())
+			},)?;
+			(fat, ImageStatus::Created,)
+		},
+	};
+
+	let _ = std::fs::remove_file(&manifest_path,);
+	for (file, data,) in files.iter().zip(&contents,) {
+		let write = match &status {
+			ImageStatus::Patched(changed,) => changed.contains(&file.dest,),
+			_ => true,
+		};
+		if write {
+			fat.write_file(&file.dest, data,).with_context(|| {
+				format!("failed to write {} to {}", file.dest, img.display())
+			},)?;
+		}
+	}
+	fat.flush()?;
+
+	let manifest: String = hashes
+		.iter()
+		.map(|(dest, hash,)| format!("{hash:016x} {dest}\n"),)
+		.collect();
+	std::fs::write(&manifest_path, manifest,).with_context(|| {
+		format!("failed to write {}", manifest_path.display())
+	},)?;
+	Ok(status,)
+}
+
+/// Path of the manifest of the image at `img`
+pub fn manifest_path(img: &Path,) -> PathBuf {
+	let mut name = img.file_name().unwrap_or_default().to_os_string();
+	name.push(".manifest",);
+	img.with_file_name(name,)
+}
+
+/// Reads lines of `<hash> <dest>`. `None` if missing or malformed
+fn read_manifest(path: &Path,) -> Option<BTreeMap<String, u64,>,> {
+	std::fs::read_to_string(path,)
+		.ok()?
+		.lines()
+		.map(|line| {
+			let (hash, dest,) = line.split_once(' ',)?;
+			Some((dest.to_string(), u64::from_str_radix(hash, 16,).ok()?,),)
+		},)
+		.collect()
+}
+
+/// 64 bit FNV-1a. Detects changes of build artifacts, not tampering
+fn fnv1a(data: &[u8],) -> u64 {
+	data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+		(hash ^ *b as u64).wrapping_mul(0x0100_0000_01b3,)
+	},)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn setup(name: &str,) -> (PathBuf, Vec<ImageFile,>,) {
+		let dir = std::env::temp_dir().join(name,);
+		let _ = std::fs::remove_dir_all(&dir,);
+		std::fs::create_dir_all(&dir,).unwrap();
+		std::fs::write(dir.join("loader",), b"loader v1",).unwrap();
+		std::fs::write(dir.join("kernel",), b"kernel v1",).unwrap();
+		let files = vec![
+			ImageFile::new("EFI/BOOT/BOOTAA64.EFI", dir.join("loader",),),
+			ImageFile::new("oso_kernel.elf", dir.join("kernel",),),
+		];
+		(dir, files,)
+	}
+
+	#[test]
+	fn test_only_changed_files_are_patched() {
+		let (dir, files,) = setup("oso_image_patch",);
+		let img = dir.join("disk.img",);
+		assert_eq!(assemble(&img, &files).unwrap(), ImageStatus::Created);
+		assert_eq!(assemble(&img, &files).unwrap(), ImageStatus::UpToDate);
+
+		std::fs::write(dir.join("kernel",), b"kernel v2",).unwrap();
+		assert_eq!(
+			assemble(&img, &files).unwrap(),
+			ImageStatus::Patched(vec!["oso_kernel.elf".into()])
+		);
+		let mut fat = FatImage::open(&img,).unwrap();
+		assert_eq!(
+			fat.read_file("oso_kernel.elf").unwrap(),
+			Some(b"kernel v2".to_vec())
+		);
+		assert_eq!(
+			fat.read_file("EFI/BOOT/BOOTAA64.EFI").unwrap(),
+			Some(b"loader v1".to_vec())
+		);
+	}
+
+	#[test]
+	fn test_recreated_without_manifest() {
+		let (dir, files,) = setup("oso_image_recreate",);
+		let img = dir.join("disk.img",);
+		assemble(&img, &files,).unwrap();
+		std::fs::remove_file(manifest_path(&img,),).unwrap();
+		assert_eq!(assemble(&img, &files).unwrap(), ImageStatus::Created);
+
+		// a different set of files also starts over
+		assert_eq!(assemble(&img, &files[..1]).unwrap(), ImageStatus::Created);
+	}
+
+	#[test]
+	fn test_manifest_path() {
+		let path = manifest_path(Path::new("target/xtask/disk.img",),);
+		assert_eq!(path, Path::new("target/xtask/disk.img.manifest"));
+	}
+}
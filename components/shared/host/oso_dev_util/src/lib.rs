@@ -70,7 +70,12 @@
 
 use anyhow::Result as Rslt;
 
+pub mod audit;
+pub mod bench;
 pub mod cargo;
+pub mod cli;
+pub mod cpio;
+pub mod crash;
 #[cfg_attr(doc, aquamarine::aquamarine)]
 /// ```mermaid
 /// flowchart TD
@@ -80,7 +85,18 @@ pub mod cargo;
 /// C --> D
 /// ```
 pub mod decl_manage;
+pub mod dtb;
+pub mod elf;
+pub mod error_codes;
+pub mod fat;
 pub mod fs;
+pub mod handoff;
+pub mod image;
+pub mod scaffold;
+pub mod screenshot;
+pub mod symbol_map;
+pub mod trace;
+pub mod unsafe_audit;
 
 /// The path to the oso_dev_util crate manifest, set at compile time
 pub const OSO_DEV_UTIL_PATH: &str = std::env!("CARGO_MANIFEST_PATH");
@@ -199,11 +215,17 @@ mod tests {
 		use cargo::CompileOpt;
 		use cargo::Feature;
 		use cargo::Opts;
+		use cli::Verbosity;
 
 		let opts = Opts {
 			build_mode:    BuildMode::Debug,
 			feature_flags: Vec::<Feature,>::new(),
 			arch:          Arch::default(),
+			verbosity:     Verbosity::default(),
+			dry_run:       false,
+			jobs:          1,
+			keep_ansi:     false,
+			task:          cargo::Task::Run,
 		};
 
 		// Test trait methods
@@ -223,14 +245,22 @@ mod tests {
 		use cargo::Arch;
 		use cargo::BuildMode;
 		use cargo::Cli;
+		use cli::BuildArgs;
+		use cli::OutputArgs;
+		use cli::TargetArgs;
 
 		let cli = Cli {
-			build_mode:    Some(BuildMode::Release,),
-			feature_flags: None,
-			arch:          Some(Arch::Riscv64,),
+			target: TargetArgs { arch: Some(Arch::Riscv64,), },
+			build:  BuildArgs {
+				build_mode:    Some(BuildMode::Release,),
+				feature_flags: None,
+				jobs:          None,
+			},
+			output: OutputArgs::default(),
+			task:   None,
 		};
 
-		let opts = cli.to_opts();
+		let opts = cli.to_opts().unwrap();
 		assert!(opts.build_mode.is_release());
 		assert!(opts.feature_flags.is_empty());
 		assert!(opts.arch.is_riscv_64());
@@ -240,14 +270,22 @@ mod tests {
 	fn test_cli_defaults() {
 		// Test CLI with default values
 		use cargo::Cli;
+		use cli::BuildArgs;
+		use cli::OutputArgs;
+		use cli::TargetArgs;
 
 		let cli = Cli {
-			build_mode:    None,
-			feature_flags: None,
-			arch:          None,
+			target: TargetArgs { arch: None, },
+			build:  BuildArgs {
+				build_mode:    None,
+				feature_flags: None,
+				jobs:          None,
+			},
+			output: OutputArgs::default(),
+			task:   None,
 		};
 
-		let opts = cli.to_opts();
+		let opts = cli.to_opts().unwrap();
 		assert!(opts.build_mode.is_debug()); // Default should be Debug
 		assert!(opts.feature_flags.is_empty());
 		assert!(opts.arch.is_aarch_64()); // Default should be Aarch64
@@ -364,9 +402,10 @@ mod tests {
 		assert!(build_mode_values.contains(&BuildMode::Release));
 
 		let arch_values = Arch::value_variants();
-		assert_eq!(arch_values.len(), 2);
+		assert_eq!(arch_values.len(), 3);
 		assert!(arch_values.contains(&Arch::Aarch64));
 		assert!(arch_values.contains(&Arch::Riscv64));
+		assert!(arch_values.contains(&Arch::X86_64));
 	}
 
 	#[test]
@@ -477,6 +516,7 @@ mod tests {
 		use cargo::CompileOpt;
 		use cargo::Feature;
 		use cargo::Opts;
+		use cli::Verbosity;
 
 		// Test that all enums implement required traits
 		fn test_enum_traits<T,>(_value: T,)
@@ -497,6 +537,11 @@ mod tests {
 					build_mode,
 					feature_flags: Vec::<Feature,>::new(),
 					arch,
+					verbosity:     Verbosity::default(),
+					dry_run:       false,
+					jobs:          1,
+					keep_ansi:     false,
+					task:          cargo::Task::Run,
 				};
 
 				// Test CompileOpt trait methods
@@ -567,6 +612,7 @@ mod tests {
 		use cargo::BuildMode;
 		use cargo::Feature;
 		use cargo::Opts;
+		use cli::Verbosity;
 
 		// Test that we can create and drop many instances without issues
 		let mut opts_vec = Vec::new();
@@ -583,6 +629,11 @@ mod tests {
 				} else {
 					Arch::Riscv64
 				},
+				verbosity:     Verbosity::default(),
+				dry_run:       false,
+				jobs:          1,
+				keep_ansi:     false,
+				task:          cargo::Task::Run,
 			};
 			opts_vec.push(opts,);
 		}
@@ -624,12 +675,18 @@ mod tests {
 		use cargo::CompileOpt;
 		use cargo::Feature;
 		use cargo::Opts;
+		use cli::Verbosity;
 
 		// Example from CompileOpt documentation
 		let opts = Opts {
 			build_mode:    BuildMode::Debug,
 			feature_flags: Vec::<Feature,>::new(),
 			arch:          Arch::Aarch64,
+			verbosity:     Verbosity::default(),
+			dry_run:       false,
+			jobs:          1,
+			keep_ansi:     false,
+			task:          cargo::Task::Run,
 		};
 
 		let build_mode: String = opts.build_mode().into();
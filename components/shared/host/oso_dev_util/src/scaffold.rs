@@ -0,0 +1,345 @@
+//! # Crate Scaffolding
+//!
+//! Generates a new workspace crate with the boilerplate every crate of its
+//! kind carries, and registers it in the workspace manifest.
+//!
+//! | kind         | directory                   | boilerplate               |
+//! | ------------ | --------------------------- | ------------------------- |
+//! | `no_std`     | `components/shared/no_std/` | `#![no_std]`, `oso_error` |
+//! | `host`       | `components/shared/host/`   | `anyhow`, `Rslt` alias    |
+//! | `proc-macro` | `components/`               | `proc-macro = true`       |
+//!
+//! Generated crates are found by [`crate::decl_manage`] like any other crate,
+//! so they show up in the crate graph once they exist on disk.
+//!
+//! ```rust,no_run
+//! use oso_dev_util::scaffold::CrateKind;
+//! use oso_dev_util::scaffold::Scaffold;
+//! use oso_dev_util_helper::fs::project_root_path;
+//!
+//! let root = project_root_path().unwrap();
+//! let scaffold = Scaffold::new("oso_fs", CrateKind::NoStd,).unwrap();
+//! let path = scaffold.generate(&root,).unwrap();
+//! println!("created {}", path.display());
+//! ```
+
+use anyhow::Context as _;
+use anyhow::Result as Rslt;
+use anyhow::bail;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Section every manifest ends with
+const LINTS: &str = "\n[lints.clippy]\ntabs_in_doc_comments = \"allow\"\n";
+
+const NO_STD_MANIFEST: &str = r#"[package]
+name = "{name}"
+version = "0.1.0"
+edition = "2024"
+
+[dependencies]
+oso_error = { path = "../oso_error" }
+"#;
+
+const NO_STD_LIB: &str = r#"//! # {name}
+//!
+//! TODO: describe what this crate provides
+
+#![no_std]
+
+/// Result of this crate. See [`oso_error`]
+pub type Rslt<T = (), V = (),> = oso_error::Rslt<T, V,>;
+"#;
+
+const HOST_MANIFEST: &str = r#"[package]
+name = "{name}"
+version = "0.1.0"
+edition = "2024"
+
+[dependencies]
+anyhow = "*"
+colored = "*"
+
+[dev-dependencies]
+proptest = "*"
+"#;
+
+const HOST_LIB: &str = r#"//! # {name}
+//!
+//! TODO: describe what this crate provides
+
+pub use anyhow::Result as Rslt;
+"#;
+
+const PROC_MACRO_MANIFEST: &str = r#"[package]
+name = "{name}"
+version = "0.1.0"
+edition = "2024"
+
+[lib]
+proc-macro = true
+
+[dependencies]
+anyhow = "*"
+proc-macro2 = "*"
+quote = "*"
+syn = { version = "*", features = ["full", "extra-traits"] }
+"#;
+
+const PROC_MACRO_LIB: &str = r#"//! # {name}
+//!
+//! TODO: describe what macros this crate provides
+
+extern crate proc_macro;
+"#;
+
+const README: &str = "# {name}\n\nTODO: describe what this crate provides\n";
+
+/// Kind of crate generated by [`Scaffold`]
+#[derive(
+	Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum, strum_macros::Display,
+)]
+pub enum CrateKind {
+	/// runs on oso. no standard library
+	#[value(name = "no_std")]
+	#[strum(to_string = "no_std")]
+	NoStd,
+	/// runs on the development machine
+	#[strum(to_string = "host")]
+	Host,
+	/// procedural macros
+	#[strum(to_string = "proc-macro")]
+	ProcMacro,
+}
+
+impl CrateKind {
+	/// Directory which holds crates of this kind, relative to the project
+	/// root
+	pub fn dir(&self,) -> &'static str {
+		match self {
+			Self::NoStd => "components/shared/no_std",
+			Self::Host => "components/shared/host",
+			Self::ProcMacro => "components",
+		}
+	}
+
+	fn templates(&self,) -> [(&'static str, &'static str,); 2] {
+		match self {
+			Self::NoStd => {
+				[("Cargo.toml", NO_STD_MANIFEST,), ("src/lib.rs", NO_STD_LIB,),]
+			},
+			Self::Host => {
+				[("Cargo.toml", HOST_MANIFEST,), ("src/lib.rs", HOST_LIB,),]
+			},
+			Self::ProcMacro => [
+				("Cargo.toml", PROC_MACRO_MANIFEST,),
+				("src/lib.rs", PROC_MACRO_LIB,),
+			],
+		}
+	}
+}
+
+/// New crate to be generated
+///
+/// # Fields
+///
+/// * `name` - Package name, also used as directory name
+/// * `kind` - Which boilerplate the crate gets
+#[derive(Clone, Debug, PartialEq, Eq,)]
+pub struct Scaffold {
+	pub name: String,
+	pub kind: CrateKind,
+}
+
+impl Scaffold {
+	/// # Errors
+	///
+	/// Returns an error if `name` is not a snake case identifier
+	pub fn new(name: impl Into<String,>, kind: CrateKind,) -> Rslt<Self,> {
+		let name = name.into();
+		let valid = name.starts_with(|c: char| c.is_ascii_lowercase(),)
+			&& name.chars().all(|c| {
+				c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_'
+			},);
+		if !valid {
+			bail!("crate name must be snake case, got `{name}`");
+		}
+		Ok(Self { name, kind, },)
+	}
+
+	/// Directory of the crate relative to the project root
+	pub fn member(&self,) -> String {
+		format!("{}/{}", self.kind.dir(), self.name)
+	}
+
+	/// Files of the crate as `(path relative to crate, content)`
+	pub fn files(&self,) -> Vec<(&'static str, String,),> {
+		let mut files: Vec<_,> = self
+			.kind
+			.templates()
+			.into_iter()
+			.map(|(path, template,)| {
+				(path, template.replace("{name}", &self.name,),)
+			},)
+			.collect();
+		files[0].1.push_str(LINTS,);
+		files.push(("README.md", README.replace("{name}", &self.name,),),);
+		files
+	}
+
+	/// Writes the crate under `root` and adds it to workspace members
+	///
+	/// # Returns
+	///
+	/// Directory of the new crate
+	///
+	/// # Errors
+	///
+	/// Returns an error if the directory already exists
+	pub fn generate(&self, root: &Path,) -> Rslt<PathBuf,> {
+		let dir = root.join(self.member(),);
+		if dir.exists() {
+			bail!("{} already exists", dir.display());
+		}
+		for (path, content,) in self.files() {
+			let path = dir.join(path,);
+			let parent = path.parent().expect("file is in crate",);
+			std::fs::create_dir_all(parent,)?;
+			std::fs::write(&path, content,).with_context(|| {
+				format!("failed to write {}", path.display())
+			},)?;
+		}
+
+		let manifest = root.join("Cargo.toml",);
+		let toml = std::fs::read_to_string(&manifest,).with_context(|| {
+			format!("failed to read {}", manifest.display())
+		},)?;
+		let toml = insert_member(&toml, &self.member(), self.kind.dir(),)?;
+		std::fs::write(&manifest, toml,).with_context(|| {
+			format!("failed to write {}", manifest.display())
+		},)?;
+		Ok(dir,)
+	}
+}
+
+/// Adds `member` to `workspace.members` of `manifest`
+///
+/// `member` is placed after the last member directly under `group`, so crates
+/// of the same kind stay together. Without such member it is appended. The
+/// rest of `manifest` is kept as is.
+pub fn insert_member(
+	manifest: &str,
+	member: &str,
+	group: &str,
+) -> Rslt<String,> {
+	let table: toml::Table = manifest.parse()?;
+	let Some(members,) = table
+		.get("workspace",)
+		.and_then(|ws| ws.get("members",),)
+		.and_then(|m| m.as_array(),)
+	else {
+		bail!("workspace.members is missing");
+	};
+	let mut members: Vec<&str,> =
+		members.iter().filter_map(|m| m.as_str(),).collect();
+	if members.contains(&member,) {
+		return Ok(manifest.to_string(),);
+	}
+
+	let at = members
+		.iter()
+		.rposition(|m| Path::new(m,).parent() == Some(Path::new(group,),),)
+		.map_or(members.len(), |i| i + 1,);
+	members.insert(at, member,);
+
+	// replace the array in place to keep comments and formatting elsewhere
+	let Some(start,) = manifest
+		.find("members",)
+		.and_then(|key| manifest[key..].find('[',).map(|i| key + i,),)
+	else {
+		bail!("workspace.members is not an inline array");
+	};
+	let Some(len,) = manifest[start..].find(']',) else {
+		bail!("workspace.members is not closed");
+	};
+	let multiline = manifest[start..start + len].contains('\n',);
+	let quoted = members.iter().map(|m| format!("\"{m}\""),);
+	let array = if multiline {
+		let lines: String = quoted.map(|m| format!("\t{m},\n"),).collect();
+		format!("[\n{lines}]")
+	} else {
+		format!("[{}]", quoted.collect::<Vec<_,>>().join(", "))
+	};
+
+	let mut out = manifest.to_string();
+	out.replace_range(start..=start + len, &array,);
+	Ok(out,)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_member_is_grouped() {
+		let manifest = "[workspace]\nresolver = \"2\"\nmembers = [\"components/\
+		                shared/host/a\", \"xtask\"]\n";
+		let out = insert_member(
+			manifest,
+			"components/shared/host/b",
+			"components/shared/host",
+		)
+		.unwrap();
+		assert_eq!(
+			out,
+			"[workspace]\nresolver = \"2\"\nmembers = [\"components/shared/\
+			 host/a\", \"components/shared/host/b\", \"xtask\"]\n"
+		);
+	}
+
+	#[test]
+	fn test_multiline_members() {
+		let manifest =
+			"[workspace]\nmembers = [\n\t\"xtask\",\n]\n\n[profile]\n";
+		let out =
+			insert_member(manifest, "components/m", "components",).unwrap();
+		assert_eq!(
+			out,
+			"[workspace]\nmembers = [\n\t\"xtask\",\n\t\"components/m\",\n]\n\n\
+			 [profile]\n"
+		);
+		// inserting twice changes nothing
+		let again = insert_member(&out, "components/m", "components",);
+		assert_eq!(again.unwrap(), out);
+	}
+
+	#[test]
+	fn test_generate() {
+		let root = std::env::temp_dir().join("oso_scaffold_test",);
+		let _ = std::fs::remove_dir_all(&root,);
+		std::fs::create_dir_all(&root,).unwrap();
+		std::fs::write(root.join("Cargo.toml",), "[workspace]\nmembers = []\n",)
+			.unwrap();
+
+		let scaffold = Scaffold::new("oso_fs", CrateKind::NoStd,).unwrap();
+		let dir = scaffold.generate(&root,).unwrap();
+		let lib = std::fs::read_to_string(dir.join("src/lib.rs",),).unwrap();
+		assert!(lib.contains("#![no_std]"));
+		let manifest =
+			std::fs::read_to_string(dir.join("Cargo.toml",),).unwrap();
+		assert!(manifest.contains("name = \"oso_fs\""));
+		assert!(manifest.ends_with("tabs_in_doc_comments = \"allow\"\n"));
+		assert_eq!(
+			std::fs::read_to_string(root.join("Cargo.toml")).unwrap(),
+			"[workspace]\nmembers = [\"components/shared/no_std/oso_fs\"]\n"
+		);
+		assert!(scaffold.generate(&root).is_err());
+	}
+
+	#[test]
+	fn test_invalid_name() {
+		assert!(Scaffold::new("OsoFs", CrateKind::Host).is_err());
+		assert!(Scaffold::new("oso-fs", CrateKind::Host).is_err());
+		assert!(Scaffold::new("1oso", CrateKind::Host).is_err());
+	}
+}
@@ -0,0 +1,269 @@
+//! # Screen Capture Decoder
+//!
+//! Host side counterpart of the kernel's `base::graphic::capture` module.
+//! Decodes the QOI images written by the kernel shell command `screenshot`
+//! and compares them with golden images, so console rendering regressions
+//! fail a test run.
+//!
+//! A capture is read either as a raw QOI image starting with [`MAGIC`], or
+//! as hex lines between [`BEGIN_MARKER`] and [`END_MARKER`] anywhere in a
+//! text such as a serial log. When a log holds several captures, the last
+//! one is decoded.
+//!
+//! ```rust,no_run
+//! use oso_dev_util::screenshot::Image;
+//! use oso_dev_util::screenshot::extract;
+//!
+//! let capture = Image::parse(&extract(&std::fs::read("serial.log",)?,)?,)?;
+//! let golden = Image::parse(&std::fs::read("tests/console.qoi",)?,)?;
+//! let diff = capture.compare(&golden, 2,)?;
+//! assert!(diff.is_match(), "{diff}");
+//! # anyhow::Ok(())
+//! ```
+
+use crate::crash::find_hex;
+use anyhow::Result as Rslt;
+use anyhow::bail;
+use anyhow::ensure;
+use std::fmt;
+
+/// first bytes of every image
+pub const MAGIC: &[u8; 4] = b"qoif";
+/// line before a hex encoded capture
+pub const BEGIN_MARKER: &str = "-----BEGIN OSO SCREENSHOT-----";
+/// line after a hex encoded capture
+pub const END_MARKER: &str = "-----END OSO SCREENSHOT-----";
+/// bytes after the last pixel
+const END: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1,];
+const HEADER_LEN: usize = 14;
+
+/// Raw QOI image of a capture file, which is either a raw image or a text
+/// holding a hex encoded one
+pub fn extract(file: &[u8],) -> Rslt<Vec<u8,>,> {
+	if file.starts_with(MAGIC,) {
+		return Ok(file.to_vec(),);
+	}
+	find_hex(&String::from_utf8_lossy(file,), BEGIN_MARKER, END_MARKER,)
+}
+
+/// Decoded image
+///
+/// # Fields
+///
+/// * `pixels` - Red, green, blue and alpha bytes, row by row
+#[derive(Debug, Clone, PartialEq, Eq,)]
+pub struct Image {
+	pub width:  u32,
+	pub height: u32,
+	pub pixels: Vec<[u8; 4],>,
+}
+
+/// Pixels of a capture which differ from the golden image by more than the
+/// tolerance
+///
+/// # Fields
+///
+/// * `pixels` - Number of differing pixels
+/// * `largest` - Largest difference of a color channel over all pixels,
+///   including those within the tolerance
+/// * `bounds` - Smallest rectangle holding every differing pixel, as left,
+///   top, right and bottom, inclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct Diff {
+	pub pixels:  usize,
+	pub largest: u8,
+	pub bounds:  Option<[u32; 4],>,
+}
+
+impl Diff {
+	/// whether the capture matches the golden image
+	pub fn is_match(&self,) -> bool {
+		self.pixels == 0
+	}
+}
+
+impl fmt::Display for Diff {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		let Some([left, top, right, bottom,],) = self.bounds else {
+			let largest = self.largest;
+			return write!(f, "images match, largest difference {largest}");
+		};
+		write!(
+			f,
+			"{} pixels differ within ({left}, {top})..=({right}, {bottom}), \
+			 largest difference {}",
+			self.pixels, self.largest
+		)
+	}
+}
+
+impl Image {
+	/// decodes a raw QOI image
+	pub fn parse(bytes: &[u8],) -> Rslt<Self,> {
+		ensure!(bytes.starts_with(MAGIC,), "not a QOI image: bad magic");
+		ensure!(bytes.len() >= HEADER_LEN, "QOI image has no header");
+		let width = u32::from_be_bytes(bytes[4..8].try_into()?,);
+		let height = u32::from_be_bytes(bytes[8..12].try_into()?,);
+		let count = width as usize * height as usize;
+
+		let mut pixels = Vec::with_capacity(count,);
+		let mut index = [[0_u8; 4]; 64];
+		let mut pixel = [0, 0, 0, 0xff,];
+		let mut run = 0;
+		let mut pos = HEADER_LEN;
+		let mut read = || {
+			let byte = bytes.get(pos,).copied();
+			pos += 1;
+			byte.ok_or_else(|| anyhow::anyhow!("QOI image is cut"),)
+		};
+		while pixels.len() < count {
+			if run > 0 {
+				run -= 1;
+				pixels.push(pixel,);
+				continue;
+			}
+			let op = read()?;
+			match op {
+				0xfe => pixel = [read()?, read()?, read()?, pixel[3],],
+				0xff => pixel = [read()?, read()?, read()?, read()?,],
+				_ => match op >> 6 {
+					0 => pixel = index[op as usize],
+					1 => {
+						let delta =
+							|shift: u8| ((op >> shift) & 3).wrapping_sub(2,);
+						pixel[0] = pixel[0].wrapping_add(delta(4,),);
+						pixel[1] = pixel[1].wrapping_add(delta(2,),);
+						pixel[2] = pixel[2].wrapping_add(delta(0,),);
+					},
+					2 => {
+						let dg = (op & 0x3f).wrapping_sub(32,);
+						let second = read()?;
+						let dr = (second >> 4).wrapping_add(dg,);
+						let db = (second & 0xf).wrapping_add(dg,);
+						let (dr, db,) = (dr.wrapping_sub(8,), db.wrapping_sub(8,),);
+						pixel[0] = pixel[0].wrapping_add(dr,);
+						pixel[1] = pixel[1].wrapping_add(dg,);
+						pixel[2] = pixel[2].wrapping_add(db,);
+					},
+					_ => run = op & 0x3f,
+				},
+			}
+			index[hash(pixel,)] = pixel;
+			pixels.push(pixel,);
+		}
+		ensure!(
+			bytes[pos.min(bytes.len(),)..].starts_with(&END,),
+			"QOI image does not end after {count} pixels"
+		);
+		Ok(Self { width, height, pixels, },)
+	}
+
+	/// Differences of the color channels of `self` from `golden` larger
+	/// than `tolerance`. Alpha is not compared
+	///
+	/// # Errors
+	///
+	/// The images differ in size
+	pub fn compare(&self, golden: &Self, tolerance: u8,) -> Rslt<Diff,> {
+		if (self.width, self.height,) != (golden.width, golden.height,) {
+			bail!(
+				"capture is {}x{}, golden image {}x{}",
+				self.width,
+				self.height,
+				golden.width,
+				golden.height
+			);
+		}
+		let mut diff = Diff { pixels: 0, largest: 0, bounds: None, };
+		let pairs = self.pixels.iter().zip(&golden.pixels,);
+		for (i, (capture, golden,),) in pairs.enumerate() {
+			let largest =
+				(0..3).map(|c| capture[c].abs_diff(golden[c],),).max().unwrap();
+			diff.largest = diff.largest.max(largest,);
+			if largest <= tolerance {
+				continue;
+			}
+			diff.pixels += 1;
+			let (x, y,) = (i as u32 % self.width, i as u32 / self.width,);
+			let [left, top, right, bottom,] =
+				diff.bounds.get_or_insert([x, y, x, y,],);
+			*left = (*left).min(x,);
+			*top = (*top).min(y,);
+			*right = (*right).max(x,);
+			*bottom = (*bottom).max(y,);
+		}
+		Ok(diff,)
+	}
+}
+
+/// slot of `pixel` in the index of seen pixels
+fn hash([red, green, blue, alpha,]: [u8; 4],) -> usize {
+	let (red, green, blue, alpha,) =
+		(red as usize, green as usize, blue as usize, alpha as usize,);
+	(red * 3 + green * 5 + blue * 7 + alpha * 11) % 64
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// image of one row using every op, as encoded by the kernel
+	const IMAGE: [u8; 37] = [
+		b'q', b'o', b'i', b'f', 0, 0, 0, 7, 0, 0, 0, 1, 4, 0,
+		// diff, run of one, diff, index 49
+		0x79, 0xc0, 0x5b, 0x31,
+		// rgb, luma, rgba
+		0xfe, 20, 30, 40, 0xa3, 0xa9, 0xff, 25, 33, 44, 128,
+		0, 0, 0, 0, 0, 0, 0, 1,
+	];
+
+	fn image() -> Image {
+		Image::parse(&IMAGE,).unwrap()
+	}
+
+	#[test]
+	fn test_decode_every_op() {
+		let image = image();
+		assert_eq!((image.width, image.height,), (7, 1,));
+		assert_eq!(image.pixels, [
+			[1, 0, 255, 255,],
+			[1, 0, 255, 255,],
+			[0, 0, 0, 255,],
+			[1, 0, 255, 255,],
+			[20, 30, 40, 255,],
+			[25, 33, 44, 255,],
+			[25, 33, 44, 128,],
+		]);
+		assert!(Image::parse(&IMAGE[..30],).is_err());
+	}
+
+	#[test]
+	fn test_extract_from_serial_log() {
+		let hex: String = IMAGE.iter().map(|b| format!("{b:02x}"),).collect();
+		let (head, tail,) = hex.split_at(32,);
+		let log = format!(
+			"osh:1> screenshot\r\n{BEGIN_MARKER}\r\n{head}\r\n{tail}\r\n\
+			 {END_MARKER}\r\n"
+		);
+		let bytes = extract(log.as_bytes(),).unwrap();
+		assert_eq!(Image::parse(&bytes,).unwrap(), image());
+		assert_eq!(extract(&IMAGE,).unwrap(), IMAGE);
+	}
+
+	#[test]
+	fn test_compare_with_tolerance() {
+		let golden = image();
+		let mut capture = image();
+		capture.pixels[4][1] += 3;
+		capture.pixels[6][2] -= 1;
+
+		let diff = capture.compare(&golden, 2,).unwrap();
+		let bounds = Some([4, 0, 4, 0,],);
+		assert_eq!(diff, Diff { pixels: 1, largest: 3, bounds });
+		assert!(!diff.is_match());
+		assert!(capture.compare(&golden, 3,).unwrap().is_match());
+
+		capture.width = 1;
+		assert!(capture.compare(&golden, 0,).is_err());
+	}
+}
@@ -0,0 +1,244 @@
+//! # Symbol Map Writer
+//!
+//! Host side counterpart of the kernel's `base::symbols` module. Writes a
+//! table of the symbols of the linked kernel into the section reserved by
+//! `oso_proc_macro::symbol_map!`, so the kernel resolves addresses without
+//! the ELF symbol table. The section is patched in place, which keeps every
+//! address of the image as linked.
+//!
+//! ## Layout
+//!
+//! Integers are little endian. After the header of [`HEADER_SIZE`] bytes
+//! described in `oso_proc_macro_logic::symbol_map`:
+//!
+//! - `addrs: [u64; count + 1]`: Start of each symbol in ascending order,
+//!   followed by the end of the last one
+//! - `names: [u32; count + 1]`: Offset of each name in `text`, followed by
+//!   the length of `text`
+//! - `text`: Demangled names without their hashes, as UTF-8
+//!
+//! A symbol extends to the start of the next one. Where a symbol with a size
+//! ends before the next one, an entry with an empty name covers the gap, so
+//! addresses in padding are not resolved to the symbol before them.
+//!
+//! Both sides must agree on [`VERSION`].
+
+use crate::elf::ElfPatcher;
+use crate::elf::Symbol;
+use anyhow::Result as Rslt;
+use anyhow::bail;
+use anyhow::ensure;
+
+/// name of the section reserved by `symbol_map!`
+pub const SECTION: &str = ".oso_symbols";
+/// first bytes of the section
+pub const MAGIC: &[u8; 4] = b"OSYM";
+/// version of the layout written by [`encode`]
+pub const VERSION: u8 = 1;
+/// bytes of the header
+pub const HEADER_SIZE: usize = 16;
+/// longest name kept, in bytes. Longer names are cut
+pub const MAX_NAME: usize = 128;
+
+/// Writes the symbols of `elf` into its [`SECTION`]
+///
+/// Returns the number of entries written, or `None` if the image has no
+/// such section, e.g. as it was built without `symbol_map!`. Call it before
+/// [`ElfPatcher::strip`], which removes the symbols.
+pub fn embed(elf: &mut ElfPatcher,) -> Rslt<Option<usize,>,> {
+	let Some(section,) = elf.section(SECTION,) else {
+		return Ok(None,);
+	};
+	ensure!(
+		section.header.is_alloc() && section.data.starts_with(MAGIC,),
+		"{SECTION} is not reserved by symbol_map!"
+	);
+	let capacity = section.data.len();
+	let entries = entries(&elf.symbols()?,);
+	let count = entries.len();
+	elf.set_section_data(SECTION, encode(&entries, capacity,)?,)?;
+	Ok(Some(count,),)
+}
+
+/// Encodes `entries` as made by [`entries`] into a section of `capacity`
+/// bytes
+///
+/// # Errors
+///
+/// If the table does not fit, in which case the capacity passed to
+/// `symbol_map!` is to be raised
+pub fn encode(entries: &[(u64, String,)], capacity: usize,) -> Rslt<Vec<u8,>,> {
+	let end = entries.last().map_or(0, |(addr, _,)| *addr,);
+	let text_len: usize = entries.iter().map(|(_, name,)| name.len(),).sum();
+	let size = HEADER_SIZE + (entries.len() + 1) * 12 + text_len;
+	ensure!(
+		size <= capacity,
+		"symbol map of {size} bytes does not fit in {SECTION} of {capacity} \
+		 bytes. raise the capacity of symbol_map!"
+	);
+	// the last entry only marks the end of the symbols
+	let Some((_, symbols,),) = entries.split_last() else {
+		bail!("no symbols to map")
+	};
+
+	let mut map = Vec::with_capacity(capacity,);
+	map.extend_from_slice(MAGIC,);
+	map.extend_from_slice(&[VERSION, 0, 0, 0,],);
+	map.extend_from_slice(&(symbols.len() as u32).to_le_bytes(),);
+	map.extend_from_slice(&(capacity as u32).to_le_bytes(),);
+	for (addr, _,) in symbols {
+		map.extend_from_slice(&addr.to_le_bytes(),);
+	}
+	map.extend_from_slice(&end.to_le_bytes(),);
+	let mut offset = 0_u32;
+	for (_, name,) in symbols {
+		map.extend_from_slice(&offset.to_le_bytes(),);
+		offset += name.len() as u32;
+	}
+	map.extend_from_slice(&offset.to_le_bytes(),);
+	for (_, name,) in symbols {
+		map.extend_from_slice(name.as_bytes(),);
+	}
+	map.resize(capacity, 0,);
+	Ok(map,)
+}
+
+/// Entries of the map for `symbols` sorted by address: the start and name
+/// of each symbol, entries with empty names for gaps, and a last entry at
+/// the end of the last symbol
+///
+/// Of symbols at the same address, the first is kept.
+pub fn entries(symbols: &[Symbol],) -> Vec<(u64, String,),> {
+	let mut entries: Vec<(u64, String,),> = vec![];
+	let mut end = 0;
+	for symbol in symbols {
+		if entries.last().is_some_and(|(addr, _,)| *addr == symbol.addr,) {
+			end = end.max(symbol.addr + symbol.size,);
+			continue;
+		}
+		if end != 0 && end < symbol.addr {
+			entries.push((end, String::new(),),);
+		}
+		entries.push((symbol.addr, short_name(&symbol.name,),),);
+		end = symbol.addr + symbol.size.max(1,);
+	}
+	if !entries.is_empty() {
+		entries.push((end, String::new(),),);
+	}
+	entries
+}
+
+/// Finds the symbol containing `addr` in an encoded `map`, as the kernel
+/// does
+///
+/// Returns the name and the offset of `addr` into the symbol.
+pub fn resolve(map: &[u8], addr: u64,) -> Option<(&str, u64,),> {
+	let read = |at: usize, len: usize| map.get(at..at + len,);
+	if read(0, 4,)? != MAGIC || map[4] != VERSION {
+		return None;
+	}
+	let count = u32::from_le_bytes(read(8, 4,)?.try_into().ok()?,) as usize;
+	let addr_at = |i: usize| {
+		let bytes = read(HEADER_SIZE + i * 8, 8,)?;
+		Some(u64::from_le_bytes(bytes.try_into().ok()?,),)
+	};
+	let names = HEADER_SIZE + (count + 1) * 8;
+	let name_at = |i: usize| {
+		let bytes = read(names + i * 4, 4,)?;
+		Some(u32::from_le_bytes(bytes.try_into().ok()?,) as usize,)
+	};
+
+	if count == 0 || addr < addr_at(0,)? || addr >= addr_at(count,)? {
+		return None;
+	}
+	// first entry past `addr`, found among the entries before the end
+	let (mut low, mut high,) = (0, count,);
+	while low < high {
+		let mid = (low + high) / 2;
+		if addr_at(mid,)? <= addr { low = mid + 1 } else { high = mid }
+	}
+	let i = low - 1;
+	let text = names + (count + 1) * 4;
+	let name = read(text + name_at(i,)?, name_at(i + 1,)? - name_at(i,)?,)?;
+	let name = std::str::from_utf8(name,).ok()?;
+	if name.is_empty() {
+		return None;
+	}
+	Some((name, addr - addr_at(i,)?,),)
+}
+
+/// demangled `name` without its hash, cut to [`MAX_NAME`] bytes
+fn short_name(name: &str,) -> String {
+	let mut name = format!("{:#}", rustc_demangle::demangle(name,));
+	if name.len() > MAX_NAME {
+		let mut end = MAX_NAME;
+		while !name.is_char_boundary(end,) {
+			end -= 1;
+		}
+		name.truncate(end,);
+	}
+	name
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn symbol(name: &str, addr: u64, size: u64,) -> Symbol {
+		Symbol { name: name.to_string(), addr, size, }
+	}
+
+	fn sample() -> Vec<u8,> {
+		let symbols = [
+			symbol("_start", 0x1000, 0x10,),
+			symbol("_ZN10oso_kernel4init17h0123456789abcdefE", 0x1010, 0x20,),
+			symbol("kernel_main", 0x1040, 0,),
+			symbol("alias", 0x1040, 0x8,),
+		];
+		encode(&entries(&symbols,), 0x100,).unwrap()
+	}
+
+	#[test]
+	fn test_resolve_inside_symbols() {
+		let map = sample();
+		assert_eq!(resolve(&map, 0x1000), Some(("_start", 0,)));
+		assert_eq!(resolve(&map, 0x100f), Some(("_start", 0xf,)));
+		assert_eq!(resolve(&map, 0x1018), Some(("oso_kernel::init", 8,)));
+		assert_eq!(resolve(&map, 0x1047), Some(("kernel_main", 7,)));
+	}
+
+	#[test]
+	fn test_resolve_outside_symbols() {
+		let map = sample();
+		assert_eq!(resolve(&map, 0xfff), None);
+		// padding between `oso_kernel::init` and `kernel_main`
+		assert_eq!(resolve(&map, 0x1030), None);
+		assert_eq!(resolve(&map, 0x1048), None);
+	}
+
+	#[test]
+	fn test_encode_header() {
+		let map = sample();
+		assert_eq!(map.len(), 0x100);
+		assert_eq!(&map[..4], MAGIC);
+		assert_eq!(map[4], VERSION);
+		// 3 symbols and the gap after `oso_kernel::init`
+		assert_eq!(u32::from_le_bytes(map[8..12].try_into().unwrap()), 4);
+		assert_eq!(u32::from_le_bytes(map[12..16].try_into().unwrap()), 0x100);
+	}
+
+	#[test]
+	fn test_encode_too_small() {
+		let symbols = [symbol("_start", 0x1000, 0x10,)];
+		let e = encode(&entries(&symbols,), 32,).unwrap_err();
+		assert!(e.to_string().contains("raise the capacity"));
+	}
+
+	#[test]
+	fn test_short_name_is_cut() {
+		let name = "é".repeat(MAX_NAME,);
+		let short = short_name(&name,);
+		assert_eq!(short.len(), MAX_NAME);
+		assert!(name.starts_with(&short));
+	}
+}
@@ -0,0 +1,485 @@
+//! # Trace Export
+//!
+//! Host side counterpart of the kernel's `base::perf::trace` module. Decodes
+//! a dump of the trace rings and converts it into the JSON of Chrome's
+//! `about://tracing`, which Perfetto loads as well.
+//!
+//! A dump is read either as raw bytes starting with [`MAGIC`], or as hex
+//! lines between [`BEGIN_MARKER`] and [`END_MARKER`] anywhere in a text such
+//! as a serial log. When a log holds several dumps, the last one is decoded.
+//!
+//! In the timeline, each core is a thread of one process and time starts at
+//! the first event. The format is described in the kernel module. Both sides
+//! must agree on [`VERSION`].
+//!
+//! ## Heap Profiles
+//!
+//! A kernel built with the `alloc_trace` feature records its allocations as
+//! `heap` events, see its `base::perf::heap` module.
+//! [`TraceDump::to_folded_heap`] sums the bytes allocated at each call site
+//! into the folded stacks read by `inferno-flamegraph` and `flamegraph.pl`.
+
+use crate::crash::crc32;
+use crate::crash::find_hex;
+use crate::elf::Symbol;
+use crate::elf::symbolize;
+use anyhow::Result as Rslt;
+use anyhow::bail;
+use anyhow::ensure;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// first bytes of every dump
+pub const MAGIC: &[u8; 4] = b"OSOT";
+/// version of the format understood by [`TraceDump::parse`]
+pub const VERSION: u8 = 1;
+/// line before a hex encoded dump
+pub const BEGIN_MARKER: &str = "-----BEGIN OSO TRACE-----";
+/// line after a hex encoded dump
+pub const END_MARKER: &str = "-----END OSO TRACE-----";
+/// bytes of an event
+pub const RECORD_SIZE: usize = 24;
+
+const TAG_END: u8 = 0;
+const TAG_CLOCK: u8 = 1;
+const TAG_NAMES: u8 = 2;
+const TAG_EVENTS: u8 = 3;
+
+/// category of the tracepoints of heap profiles
+const HEAP: &str = "heap";
+/// bit of the argument of an `alloc` event where the call site starts
+const SITE_SHIFT: u32 = 40;
+
+/// what an event marks
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum Phase {
+	Instant,
+	Begin,
+	End,
+}
+
+impl Phase {
+	/// `ph` of the Trace Event Format
+	fn code(&self,) -> &'static str {
+		match self {
+			Self::Instant => "i",
+			Self::Begin => "B",
+			Self::End => "E",
+		}
+	}
+}
+
+/// event recorded at a tracepoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct Event {
+	/// ticks of the clock of the dump
+	pub timestamp: u64,
+	pub arg:       u64,
+	/// tracepoint, see [`TraceDump::names`]
+	pub id:        u16,
+	pub cpu:       u16,
+	pub phase:     Phase,
+}
+
+/// contents of a trace dump
+#[derive(Debug, Clone, PartialEq, Eq, Default,)]
+pub struct TraceDump {
+	/// of the timestamps in Hz, `0` if unknown
+	pub frequency: u64,
+	/// category and name of each tracepoint id
+	pub names:     BTreeMap<u16, (String, String,),>,
+	/// oldest first within each core
+	pub events:    Vec<Event,>,
+}
+
+impl TraceDump {
+	/// decodes a dump file, which is either a raw dump or a text holding a
+	/// hex encoded one
+	pub fn decode(file: &[u8],) -> Rslt<Self,> {
+		if file.starts_with(MAGIC,) {
+			return Self::parse(file,);
+		}
+		let text = String::from_utf8_lossy(file,);
+		Self::parse(&find_hex(&text, BEGIN_MARKER, END_MARKER,)?,)
+	}
+
+	/// parses a raw dump, checking its checksum
+	pub fn parse(bytes: &[u8],) -> Rslt<Self,> {
+		ensure!(bytes.starts_with(MAGIC,), "not a trace dump: bad magic");
+		ensure!(bytes.len() > MAGIC.len(), "trace dump has no version");
+		let version = bytes[MAGIC.len()];
+		ensure!(
+			version == VERSION,
+			"trace dump version {version} is not supported, expected {VERSION}"
+		);
+
+		let mut dump = Self::default();
+		let mut at = MAGIC.len() + 1;
+		loop {
+			let Some(&[tag, lo, hi,],) = bytes.get(at..at + 3,) else {
+				bail!("trace dump is cut at {at:#x} before its end record")
+			};
+			let len = u16::from_le_bytes([lo, hi,],) as usize;
+			let Some(payload,) = bytes.get(at + 3..at + 3 + len,) else {
+				bail!("record at {at:#x} exceeds the trace dump")
+			};
+
+			match tag {
+				TAG_END => {
+					ensure!(len == 4, "end record has length {len}");
+					let expected = u32::from_le_bytes(payload.try_into()?,);
+					let actual = crc32(&bytes[..at],);
+					ensure!(
+						expected == actual,
+						"trace dump checksum mismatch: {actual:#010x} != \
+						 {expected:#010x}"
+					);
+					return Ok(dump,);
+				},
+				TAG_CLOCK => {
+					ensure!(len == 8, "clock record has length {len}");
+					dump.frequency = u64::from_le_bytes(payload.try_into()?,);
+				},
+				TAG_NAMES => names(payload, &mut dump.names,)?,
+				TAG_EVENTS => {
+					ensure!(len.is_multiple_of(RECORD_SIZE,), "events are cut");
+					let events = payload.chunks_exact(RECORD_SIZE,).map(event,);
+					dump.events.extend(events,);
+				},
+				// records of later versions of the kernel
+				_ => (),
+			}
+			at += 3 + len;
+		}
+	}
+
+	/// JSON of the Trace Event Format
+	///
+	/// Timestamps are converted with `frequency`, or else the clock of the
+	/// dump. Without either, a tick counts as a nanosecond.
+	pub fn to_chrome_json(&self, frequency: Option<u64,>,) -> String {
+		let frequency = frequency.unwrap_or(self.frequency,);
+		let ticks_per_us = match frequency {
+			0 => 1000.0,
+			hz => hz as f64 / 1e6,
+		};
+		let mut events = self.events.clone();
+		events.sort_by_key(|event| event.timestamp,);
+		let start = events.first().map_or(0, |event| event.timestamp,);
+
+		let mut lines = vec![];
+		let mut cpus: Vec<u16,> =
+			events.iter().map(|event| event.cpu,).collect();
+		cpus.sort_unstable();
+		cpus.dedup();
+		for cpu in cpus {
+			lines.push(format!(
+				"{{\"name\": \"thread_name\", \"ph\": \"M\", \"pid\": 0, \
+				 \"tid\": {cpu}, \"args\": {{\"name\": \"core {cpu}\"}}}}"
+			),);
+		}
+		for event in &events {
+			let (category, name,) = match self.names.get(&event.id,) {
+				Some((category, name,),) => (quote(category,), quote(name,),),
+				None => {
+					let name = format!("tracepoint {}", event.id);
+					(quote("unknown",), quote(&name,),)
+				},
+			};
+			let ts = (event.timestamp - start) as f64 / ticks_per_us;
+			let mut line = format!(
+				"{{\"name\": {name}, \"cat\": {category}, \"ph\": \"{}\", \
+				 \"ts\": {ts:.3}, \"pid\": 0, \"tid\": {}",
+				event.phase.code(),
+				event.cpu
+			);
+			if event.phase == Phase::Instant {
+				line.push_str(", \"s\": \"t\"",);
+			}
+			let _ = write!(line, ", \"args\": {{\"arg\": {}}}}}", event.arg);
+			lines.push(line,);
+		}
+
+		let mut json = String::from("{\"traceEvents\": [\n",);
+		json.push_str(&lines.join(",\n",),);
+		json.push_str("\n], \"displayTimeUnit\": \"ns\"}\n",);
+		json
+	}
+
+	/// Folded stacks of the allocations in the `heap` events, one line of
+	/// `outer;...;inner bytes` per stack
+	///
+	/// Frames are resolved with `symbols`, sorted by address. Call sites
+	/// whose frames were overwritten in the rings, or never recorded as the
+	/// kernel knew too many, are named `site_<hash>`.
+	pub fn to_folded_heap(&self, symbols: &[Symbol],) -> String {
+		let mut frames: BTreeMap<u32, Vec<u64,>,> = BTreeMap::new();
+		let mut bytes: BTreeMap<u32, u64,> = BTreeMap::new();
+		// call site whose frames each core is recording
+		let mut recording: BTreeMap<u16, u32,> = BTreeMap::new();
+		for event in &self.events {
+			let name = match self.names.get(&event.id,) {
+				Some((category, name,),) if category == HEAP => name.as_str(),
+				_ => "",
+			};
+			match name {
+				"site" => {
+					let site = event.arg as u32;
+					frames.insert(site, vec![],);
+					recording.insert(event.cpu, site,);
+				},
+				"frame" => {
+					if let Some(site,) = recording.get(&event.cpu,) {
+						frames.entry(*site,).or_default().push(event.arg,);
+					}
+				},
+				_ => {
+					recording.remove(&event.cpu,);
+					if name == "alloc" {
+						let site = (event.arg >> SITE_SHIFT) as u32;
+						let size = event.arg as u32 as u64;
+						*bytes.entry(site,).or_default() += size;
+					}
+				},
+			}
+		}
+
+		let mut stacks: BTreeMap<String, u64,> = BTreeMap::new();
+		for (site, size,) in bytes {
+			let stack = match frames.get(&site,) {
+				Some(frames,) if !frames.is_empty() => frames
+					.iter()
+					.rev()
+					.map(|addr| frame_name(symbols, *addr,),)
+					.collect::<Vec<_,>>()
+					.join(";",),
+				_ => format!("site_{site:06x}"),
+			};
+			*stacks.entry(stack,).or_default() += size;
+		}
+		stacks
+			.into_iter()
+			.map(|(stack, size,)| format!("{stack} {size}\n"),)
+			.collect()
+	}
+}
+
+/// demangled symbol of a frame of a folded stack, without the `;` separating
+/// frames
+fn frame_name(symbols: &[Symbol], addr: u64,) -> String {
+	match symbolize(symbols, addr,) {
+		Some((symbol, _,),) => {
+			let name = rustc_demangle::demangle(&symbol.name,);
+			format!("{name:#}").replace(';', ",",)
+		},
+		None => format!("{addr:#x}"),
+	}
+}
+
+/// entries of a names record
+fn names(
+	mut payload: &[u8],
+	names: &mut BTreeMap<u16, (String, String,),>,
+) -> Rslt<(),> {
+	while let Some((id, rest,),) = payload.split_first_chunk::<2>() {
+		let (category, rest,) = text(rest,)?;
+		let (name, rest,) = text(rest,)?;
+		// a tracepoint registered twice keeps its first name
+		names.entry(u16::from_le_bytes(*id,),).or_insert((category, name,),);
+		payload = rest;
+	}
+	ensure!(payload.is_empty(), "names record is cut");
+	Ok((),)
+}
+
+/// text prefixed with its `u8` length, and the bytes after it
+fn text(payload: &[u8],) -> Rslt<(String, &[u8],),> {
+	let Some((&len, rest,),) = payload.split_first() else {
+		bail!("names record is cut")
+	};
+	let len = len as usize;
+	ensure!(rest.len() >= len, "names record is cut");
+	let text = String::from_utf8_lossy(&rest[..len],).into_owned();
+	Ok((text, &rest[len..],),)
+}
+
+fn event(record: &[u8],) -> Event {
+	let u64_at =
+		|i: usize| u64::from_le_bytes(record[i..i + 8].try_into().unwrap(),);
+	let u16_at = |i: usize| u16::from_le_bytes([record[i], record[i + 1],],);
+	let phase = match record[20] {
+		1 => Phase::Begin,
+		2 => Phase::End,
+		_ => Phase::Instant,
+	};
+	Event {
+		timestamp: u64_at(0,),
+		arg: u64_at(8,),
+		id: u16_at(16,),
+		cpu: u16_at(18,),
+		phase,
+	}
+}
+
+/// JSON string of `s`
+fn quote(s: &str,) -> String {
+	let mut quoted = String::from("\"",);
+	for c in s.chars() {
+		match c {
+			'"' => quoted.push_str("\\\"",),
+			'\\' => quoted.push_str("\\\\",),
+			c if c.is_control() => {
+				let _ = write!(quoted, "\\u{:04x}", c as u32);
+			},
+			c => quoted.push(c,),
+		}
+	}
+	quoted.push('"',);
+	quoted
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// tracepoint as `(id, category, name)`
+	type Point = (u16, &'static str, &'static str,);
+	/// event as `(timestamp, arg, id, cpu, phase)`
+	type Record = (u64, u64, u16, u16, u8,);
+
+	/// dump as the kernel writes it, with a 1 MHz clock, two tracepoints and
+	/// three events on two cores
+	fn sample_dump() -> Vec<u8,> {
+		let points = [(1, "sched", "switch",), (2, "boot", "fdt",),];
+		let records = [
+			(100, 0, 2, 0, 1,),
+			(150, 7, 1, 1, 0,),
+			(400, 0, 2, 0, 2,),
+		];
+		encode(&points, &records,)
+	}
+
+	/// dump of `points` and `records` with a 1 MHz clock
+	fn encode(points: &[Point], records: &[Record],) -> Vec<u8,> {
+		let mut out = MAGIC.to_vec();
+		out.push(VERSION,);
+		let mut record = |tag: u8, payload: &[u8]| {
+			out.push(tag,);
+			out.extend_from_slice(&(payload.len() as u16).to_le_bytes(),);
+			out.extend_from_slice(payload,);
+		};
+		record(TAG_CLOCK, &1_000_000u64.to_le_bytes(),);
+		let mut names = vec![];
+		for &(id, category, name,) in points {
+			names.extend_from_slice(&id.to_le_bytes(),);
+			for text in [category, name,] {
+				names.push(text.len() as u8,);
+				names.extend_from_slice(text.as_bytes(),);
+			}
+		}
+		record(TAG_NAMES, &names,);
+		let mut events = vec![];
+		for &(timestamp, arg, id, cpu, phase,) in records {
+			events.extend_from_slice(&timestamp.to_le_bytes(),);
+			events.extend_from_slice(&arg.to_le_bytes(),);
+			events.extend_from_slice(&id.to_le_bytes(),);
+			events.extend_from_slice(&cpu.to_le_bytes(),);
+			events.extend_from_slice(&[phase, 0, 0, 0,],);
+		}
+		record(TAG_EVENTS, &events,);
+
+		let crc = crc32(&out,);
+		out.push(TAG_END,);
+		out.extend_from_slice(&4u16.to_le_bytes(),);
+		out.extend_from_slice(&crc.to_le_bytes(),);
+		out
+	}
+
+	#[test]
+	fn test_decode_hex_from_log() {
+		let hex: String =
+			sample_dump().iter().map(|b| format!("{b:02x}"),).collect();
+		let (head, tail,) = hex.split_at(64,);
+		let log = format!(
+			"osh:3> trace dump\r\n{BEGIN_MARKER}\r\n{head}\r\n{tail}\r\n\
+			 {END_MARKER}\r\n"
+		);
+
+		let dump = TraceDump::decode(log.as_bytes(),).unwrap();
+		assert_eq!(dump, TraceDump::decode(&sample_dump(),).unwrap());
+		assert_eq!(dump.frequency, 1_000_000);
+		assert_eq!(dump.names[&1], ("sched".to_string(), "switch".to_string()));
+		assert_eq!(dump.events.len(), 3);
+		assert_eq!(dump.events[1], Event {
+			timestamp: 150,
+			arg:       7,
+			id:        1,
+			cpu:       1,
+			phase:     Phase::Instant,
+		});
+	}
+
+	#[test]
+	fn test_chrome_json() {
+		let dump = TraceDump::parse(&sample_dump(),).unwrap();
+		let json = dump.to_chrome_json(None,);
+		assert!(json.starts_with("{\"traceEvents\": [\n"));
+		assert!(json.contains("\"args\": {\"name\": \"core 1\"}"));
+		assert!(json.contains(
+			"{\"name\": \"fdt\", \"cat\": \"boot\", \"ph\": \"B\", \"ts\": \
+			 0.000, \"pid\": 0, \"tid\": 0, \"args\": {\"arg\": 0}}"
+		));
+		assert!(json.contains("\"ph\": \"E\", \"ts\": 300.000"));
+		assert!(json.contains(
+			"\"ph\": \"i\", \"ts\": 50.000, \"pid\": 0, \"tid\": 1, \
+			 \"s\": \"t\""
+		));
+
+		// 2 ticks per microsecond
+		let json = dump.to_chrome_json(Some(2_000_000,),);
+		assert!(json.contains("\"ph\": \"E\", \"ts\": 150.000"));
+		assert_eq!(quote("a\"b\\\n"), "\"a\\\"b\\\\\\u000a\"");
+	}
+
+	#[test]
+	fn test_folded_heap() {
+		let points = [
+			(1, "heap", "site",),
+			(2, "heap", "frame",),
+			(3, "heap", "alloc",),
+			(4, "sched", "switch",),
+		];
+		let alloc = |size: u64, site: u64| size | 3 << 32 | site << SITE_SHIFT;
+		let records = [
+			(10, 0x12, 1, 0, 0,),
+			(10, 0x1010, 2, 0, 0,),
+			(10, 0x2020, 2, 0, 0,),
+			(10, alloc(64, 0x12,), 3, 0, 0,),
+			(20, 0, 4, 0, 0,),
+			// not a frame of the site before the switch
+			(20, 0x1010, 2, 0, 0,),
+			(30, alloc(32, 0x12,), 3, 1, 0,),
+			(40, alloc(16, 0x34,), 3, 0, 0,),
+		];
+		let dump = TraceDump::parse(&encode(&points, &records,),).unwrap();
+		let symbols = [
+			Symbol { name: "inner".into(), addr: 0x1000, size: 0x100, },
+			Symbol { name: "outer".into(), addr: 0x2000, size: 0x100, },
+		];
+		let folded = dump.to_folded_heap(&symbols,);
+		assert_eq!(folded, "outer;inner 96\nsite_000034 16\n");
+		let folded = dump.to_folded_heap(&[],);
+		assert_eq!(folded, "0x2020;0x1010 96\nsite_000034 16\n");
+	}
+
+	#[test]
+	fn test_reject_corrupted() {
+		let mut dump = sample_dump();
+		dump[20] ^= 1;
+		let err = TraceDump::parse(&dump,).unwrap_err();
+		assert!(err.to_string().contains("checksum"));
+
+		let cut = &sample_dump()[..30];
+		assert!(TraceDump::parse(cut,).is_err());
+	}
+}
@@ -0,0 +1,465 @@
+//! # Unsafe Audit
+//!
+//! Lists every unsafe block, function, impl and trait of the workspace with
+//! the contract which says why it is sound, so the unsafe code of the loader
+//! and the kernel can be reviewed as one inventory. A [`Site`] takes its
+//! [`Contract`] from:
+//!
+//! - **Functions and traits**: `requires` of `#[safety(..)]`, or a `# Safety`
+//!   section of the documentation
+//! - **Blocks**: A `// SAFETY:` comment right above, or `reason` of the
+//!   `#[safety(..)]` of the enclosing function
+//! - **Impls**: `reason` of `#[safety(..)]`, or a `// SAFETY:` comment right
+//!   above
+//!
+//! Functions of `extern` blocks and code inside macro invocations are not
+//! listed.
+//!
+//! ```rust,no_run
+//! use oso_dev_util::unsafe_audit::audit_unsafe;
+//! use oso_dev_util_helper::fs::all_crates;
+//! use oso_dev_util_helper::fs::project_root_path;
+//!
+//! let root = project_root_path().unwrap();
+//! let audit = audit_unsafe(&root, &all_crates().unwrap(),).unwrap();
+//! print!("{}", audit.report());
+//! ```
+
+use anyhow::Result as Rslt;
+use std::fmt::Display;
+use std::path::Path;
+use std::path::PathBuf;
+use syn::spanned::Spanned;
+use syn::visit::Visit;
+
+/// Attribute carrying a contract
+const ATTR: &str = "safety";
+/// Marker of a contract in a comment
+const COMMENT: &str = "SAFETY:";
+
+/// What is unsafe
+#[derive(Clone, Copy, Debug, PartialEq, Eq,)]
+pub enum Kind {
+	Block,
+	Fn,
+	Impl,
+	Trait,
+}
+
+impl Display for Kind {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_,>,) -> std::fmt::Result {
+		let kind = match self {
+			Self::Block => "block",
+			Self::Fn => "fn",
+			Self::Impl => "impl",
+			Self::Trait => "trait",
+		};
+		write!(f, "{kind}")
+	}
+}
+
+/// Why a [`Site`] is sound
+#[derive(Clone, Debug, PartialEq, Eq,)]
+pub enum Contract {
+	/// `requires` of `#[safety(..)]`
+	Requires(String,),
+	/// `reason` of `#[safety(..)]`, of the site or its function
+	Reason(String,),
+	/// text of a `// SAFETY:` comment
+	Comment(String,),
+	/// `# Safety` section of the documentation
+	Doc,
+	Missing,
+}
+
+impl Display for Contract {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_,>,) -> std::fmt::Result {
+		match self {
+			Self::Requires(text,) => write!(f, "requires: {text}"),
+			Self::Reason(text,) => write!(f, "reason: {text}"),
+			Self::Comment(text,) => write!(f, "comment: {text}"),
+			Self::Doc => write!(f, "documented"),
+			Self::Missing => write!(f, "missing"),
+		}
+	}
+}
+
+/// Unsafe block, function, impl or trait
+///
+/// # Fields
+///
+/// * `kind` - What is unsafe
+/// * `file` - Source file, relative to the root
+/// * `line` - Line of the `unsafe` keyword, from 1
+/// * `within` - Name of the function or item holding the site
+/// * `contract` - Why the site is sound
+#[derive(Clone, Debug, PartialEq, Eq,)]
+pub struct Site {
+	pub kind:     Kind,
+	pub file:     PathBuf,
+	pub line:     usize,
+	pub within:   String,
+	pub contract: Contract,
+}
+
+/// Sites of the workspace, and files which could not be parsed
+#[derive(Clone, Debug, Default,)]
+pub struct Audit {
+	pub sites:    Vec<Site,>,
+	pub unparsed: Vec<(PathBuf, String,),>,
+}
+
+impl Audit {
+	/// Sites without a contract
+	pub fn missing(&self,) -> impl Iterator<Item = &Site,> {
+		self.sites.iter().filter(|site| site.contract == Contract::Missing,)
+	}
+
+	/// Inventory of the sites as markdown, a table per file
+	pub fn report(&self,) -> String {
+		let missing = self.missing().count();
+		let mut out = format!(
+			"# Unsafe Inventory\n\n{} unsafe sites, {missing} without a safety \
+			 contract\n",
+			self.sites.len()
+		);
+		let mut file = None;
+		for site in &self.sites {
+			if file != Some(&site.file,) {
+				file = Some(&site.file,);
+				out.push_str(&format!(
+					"\n## {}\n\n| Line | Kind | Within | Contract |\n| ---- | \
+					 ---- | ------ | -------- |\n",
+					site.file.display()
+				),);
+			}
+			let contract = match &site.contract {
+				Contract::Missing => "**missing**".to_string(),
+				contract => contract.to_string().replace('|', "\\|",),
+			};
+			out.push_str(&format!(
+				"| {} | {} | `{}` | {contract} |\n",
+				site.line, site.kind, site.within
+			),);
+		}
+		for (file, error,) in &self.unparsed {
+			let file = file.display();
+			out.push_str(&format!("\nnot parsed: {file}: {error}\n"),);
+		}
+		out
+	}
+}
+
+/// Audits the sources of `crates` located under `root`
+pub fn audit_unsafe(root: &Path, crates: &[PathBuf],) -> Rslt<Audit,> {
+	let mut audit = Audit::default();
+	for dir in crates {
+		audit_dir(root, &dir.join("src",), &mut audit,)?;
+	}
+	audit.sites.sort_by(|a, b| (&a.file, a.line,).cmp(&(&b.file, b.line,),),);
+	Ok(audit,)
+}
+
+fn audit_dir(root: &Path, dir: &Path, audit: &mut Audit,) -> Rslt<(),> {
+	let Ok(entries,) = dir.read_dir() else {
+		return Ok((),);
+	};
+	for entry in entries {
+		let path = entry?.path();
+		if path.is_dir() {
+			audit_dir(root, &path, audit,)?;
+			continue;
+		}
+		if path.extension().is_none_or(|ext| ext != "rs",) {
+			continue;
+		}
+		let source = std::fs::read_to_string(&path,)?;
+		let file = path.strip_prefix(root,).unwrap_or(&path,);
+		match audit_source(&source, file,) {
+			Ok(sites,) => audit.sites.extend(sites,),
+			Err(e,) => {
+				audit.unparsed.push((file.to_path_buf(), e.to_string(),),)
+			},
+		}
+	}
+	Ok((),)
+}
+
+/// Sites of the source of `file`
+pub fn audit_source(source: &str, file: &Path,) -> syn::Result<Vec<Site,>,> {
+	let syntax = syn::parse_file(source,)?;
+	let mut visitor = Visitor {
+		lines:  source.lines().collect(),
+		file,
+		within: vec![],
+		reason: vec![],
+		sites:  vec![],
+	};
+	visitor.visit_file(&syntax,);
+	Ok(visitor.sites,)
+}
+
+struct Visitor<'a,> {
+	lines:  Vec<&'a str,>,
+	file:   &'a Path,
+	/// names of the enclosing items, innermost last
+	within: Vec<String,>,
+	/// `reason` of the enclosing functions, innermost last
+	reason: Vec<Option<String,>,>,
+	sites:  Vec<Site,>,
+}
+
+impl Visitor<'_,> {
+	fn push(&mut self, kind: Kind, line: usize, contract: Contract,) {
+		self.sites.push(Site {
+			kind,
+			file: self.file.to_path_buf(),
+			line,
+			within: self.within.last().cloned().unwrap_or_default(),
+			contract,
+		},);
+	}
+
+	/// text of the `// SAFETY:` comment in the comment lines right above
+	/// `line`, skipping attributes
+	fn comment_above(&self, line: usize,) -> Option<String,> {
+		let mut comment = vec![];
+		for above in self.lines[..line.saturating_sub(1,)].iter().rev() {
+			let above = above.trim();
+			if above.starts_with("#[",) {
+				continue;
+			}
+			let Some(text,) = above.strip_prefix("//",) else {
+				break;
+			};
+			comment.push(text.trim(),);
+			if text.contains(COMMENT,) {
+				comment.reverse();
+				let text = comment.join(" ",);
+				let (_, text,) = text.split_once(COMMENT,)?;
+				return Some(text.trim().to_string(),);
+			}
+		}
+		None
+	}
+
+	fn visit_fn(
+		&mut self,
+		attrs: &[syn::Attribute],
+		sig: &syn::Signature,
+		visit: impl FnOnce(&mut Self,),
+	) {
+		let contract = contract_of(attrs,);
+		if let Some(unsafety,) = sig.unsafety {
+			let contract = match &contract {
+				Some(SafetyAttr { requires: Some(text,), .. },) => {
+					Contract::Requires(text.clone(),)
+				},
+				_ if has_safety_doc(attrs,) => Contract::Doc,
+				_ => Contract::Missing,
+			};
+			self.within.push(sig.ident.to_string(),);
+			self.push(Kind::Fn, unsafety.span.start().line, contract,);
+		} else {
+			self.within.push(sig.ident.to_string(),);
+		}
+		self.reason.push(contract.and_then(|c| c.reason,),);
+		visit(self,);
+		self.reason.pop();
+		self.within.pop();
+	}
+}
+
+impl<'ast,> Visit<'ast,> for Visitor<'_,> {
+	fn visit_item_fn(&mut self, item: &'ast syn::ItemFn,) {
+		self.visit_fn(&item.attrs, &item.sig, |v| {
+			syn::visit::visit_item_fn(v, item,)
+		},);
+	}
+
+	fn visit_impl_item_fn(&mut self, item: &'ast syn::ImplItemFn,) {
+		self.visit_fn(&item.attrs, &item.sig, |v| {
+			syn::visit::visit_impl_item_fn(v, item,)
+		},);
+	}
+
+	fn visit_trait_item_fn(&mut self, item: &'ast syn::TraitItemFn,) {
+		self.visit_fn(&item.attrs, &item.sig, |v| {
+			syn::visit::visit_trait_item_fn(v, item,)
+		},);
+	}
+
+	fn visit_item_impl(&mut self, item: &'ast syn::ItemImpl,) {
+		let ty = &item.self_ty;
+		let name = quote::ToTokens::to_token_stream(ty,).to_string();
+		if let Some(unsafety,) = item.unsafety {
+			let line = unsafety.span.start().line;
+			let reason = contract_of(&item.attrs,).and_then(|c| c.reason,);
+			let contract = match reason {
+				Some(reason,) => Contract::Reason(reason,),
+				None => match self.comment_above(line,) {
+					Some(comment,) => Contract::Comment(comment,),
+					None => Contract::Missing,
+				},
+			};
+			self.within.push(name,);
+			self.push(Kind::Impl, line, contract,);
+		} else {
+			self.within.push(name,);
+		}
+		syn::visit::visit_item_impl(self, item,);
+		self.within.pop();
+	}
+
+	fn visit_item_trait(&mut self, item: &'ast syn::ItemTrait,) {
+		self.within.push(item.ident.to_string(),);
+		if let Some(unsafety,) = item.unsafety {
+			let contract = match contract_of(&item.attrs,) {
+				Some(SafetyAttr { requires: Some(text,), .. },) => {
+					Contract::Requires(text,)
+				},
+				_ if has_safety_doc(&item.attrs,) => Contract::Doc,
+				_ => Contract::Missing,
+			};
+			self.push(Kind::Trait, unsafety.span.start().line, contract,);
+		}
+		syn::visit::visit_item_trait(self, item,);
+		self.within.pop();
+	}
+
+	fn visit_expr_unsafe(&mut self, expr: &'ast syn::ExprUnsafe,) {
+		let line = expr.unsafe_token.span().start().line;
+		let reason = self.reason.iter().rev().flatten().next().cloned();
+		let contract = match (self.comment_above(line,), reason,) {
+			(Some(comment,), _,) => Contract::Comment(comment,),
+			(None, Some(reason,),) => Contract::Reason(reason,),
+			(None, None,) => Contract::Missing,
+		};
+		self.push(Kind::Block, line, contract,);
+		syn::visit::visit_expr_unsafe(self, expr,);
+	}
+
+	// functions of extern blocks are declarations, not unsafe code
+	fn visit_item_foreign_mod(&mut self, _: &'ast syn::ItemForeignMod,) {}
+}
+
+/// arguments of `#[safety(..)]`
+#[derive(Default,)]
+struct SafetyAttr {
+	reason:   Option<String,>,
+	requires: Option<String,>,
+}
+
+/// arguments of the `#[safety(..)]` in `attrs`, also as
+/// `#[oso_proc_macro::safety(..)]`
+fn contract_of(attrs: &[syn::Attribute],) -> Option<SafetyAttr,> {
+	let attr = attrs.iter().find(|attr| {
+		attr.path().segments.last().is_some_and(|s| s.ident == ATTR,)
+	},)?;
+	let mut contract = SafetyAttr::default();
+	let _ = attr.parse_nested_meta(|meta| {
+		let value: syn::LitStr = meta.value()?.parse()?;
+		if meta.path.is_ident("reason",) {
+			contract.reason = Some(value.value(),);
+		} else if meta.path.is_ident("requires",) {
+			contract.requires = Some(value.value(),);
+		}
+		Ok((),)
+	},);
+	Some(contract,)
+}
+
+/// whether the documentation in `attrs` has a `# Safety` heading
+fn has_safety_doc(attrs: &[syn::Attribute],) -> bool {
+	attrs.iter().any(|attr| {
+		let syn::Meta::NameValue(meta,) = &attr.meta else {
+			return false;
+		};
+		let syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(doc,), .. },) =
+			&meta.value
+		else {
+			return false;
+		};
+		meta.path.is_ident("doc",) && doc.value().trim() == "# Safety"
+	},)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const SOURCE: &str = r#"
+/// Reads `ptr`
+///
+/// # Safety
+///
+/// `ptr` is valid
+pub unsafe fn read(ptr: *const u8) -> u8 {
+	// SAFETY: the caller keeps `ptr`
+	// valid
+	unsafe { *ptr }
+}
+
+#[safety(requires = "`ptr` is writable")]
+pub unsafe fn write(ptr: *mut u8) {
+	unsafe { *ptr = 0 }
+}
+
+pub unsafe fn undocumented() {}
+
+#[oso_proc_macro::safety(reason = "only one core runs")]
+fn install() {
+	unsafe { TABLE = 1 };
+}
+
+fn careless() {
+	let x = unsafe { TABLE };
+}
+
+// SAFETY: `Frame` only moves a pointer
+#[allow(dead_code)]
+unsafe impl Send for Frame {}
+
+unsafe impl Sync for Frame {}
+
+extern "C" {
+	fn external();
+}
+"#;
+
+	#[test]
+	fn test_audit_source() {
+		let sites = audit_source(SOURCE, Path::new("lib.rs",),).unwrap();
+		let sites: Vec<_,> = sites
+			.iter()
+			.map(|s| (s.line, s.kind, s.within.as_str(), s.contract.clone(),),)
+			.collect();
+		let comment = |text: &str| Contract::Comment(text.to_string(),);
+		let requires = Contract::Requires("`ptr` is writable".to_string(),);
+		let reason = Contract::Reason("only one core runs".to_string(),);
+		assert_eq!(sites, [
+			(7, Kind::Fn, "read", Contract::Doc,),
+			(10, Kind::Block, "read", comment("the caller keeps `ptr` valid"),),
+			(14, Kind::Fn, "write", requires,),
+			(15, Kind::Block, "write", Contract::Missing,),
+			(18, Kind::Fn, "undocumented", Contract::Missing,),
+			(22, Kind::Block, "install", reason,),
+			(26, Kind::Block, "careless", Contract::Missing,),
+			(31, Kind::Impl, "Frame", comment("`Frame` only moves a pointer"),),
+			(33, Kind::Impl, "Frame", Contract::Missing,),
+		]);
+	}
+
+	#[test]
+	fn test_report() {
+		let sites = audit_source(SOURCE, Path::new("lib.rs",),).unwrap();
+		let audit = Audit { sites, unparsed: vec![], };
+		assert_eq!(audit.missing().count(), 4);
+		let report = audit.report();
+		assert!(report.starts_with("# Unsafe Inventory\n\n9 unsafe sites, 4 "));
+		assert!(report.contains("\n## lib.rs\n"));
+		let row = "| 18 | fn | `undocumented` | **missing** |\n";
+		assert!(report.contains(row));
+		assert!(report.contains("| 7 | fn | `read` | documented |\n"));
+	}
+}
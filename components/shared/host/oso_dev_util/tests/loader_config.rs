@@ -0,0 +1,193 @@
+//! Compares the no_std configuration parser of `oso_no_std_shared` with the
+//! `toml` crate. Every source accepted by the former must be read identically
+//! by the latter, so host tools can edit `loader.cfg` with a std TOML crate.
+
+use oso_no_std_shared::parser::config::Config;
+use oso_no_std_shared::parser::config::Value;
+use proptest::prelude::*;
+
+/// Format example of the loader configuration module
+const LOADER_EXAMPLE: &str = r#"
+# seconds to wait before booting. 0 boots immediately
+timeout = 3
+
+[kernel]
+path = '\oso_kernel.elf'
+cmdline = "console=ttyAMA0"
+# boot even if the kernel does not accept this loader version
+check_version = true
+# cpio archive handed to the kernel, which mounts it at /
+initrd = '\initrd.cpio'
+
+[graphics]
+width = 1280
+height = 720
+
+[log]
+# quiet, info or debug
+level = "info"
+# level of the buffered log on the serial port. quiet disables it
+serial = "quiet"
+
+[panic]
+# halt or reboot after a panic. blink and exit halt in the loader
+policy = "reboot"
+
+# device tree overlays, applied in this order. keys only name them
+[overlays]
+uart1 = '\EFI\oso\overlays\uart1.dtbo'
+"#;
+/// Configuration `xtask test` writes to the disk image
+const TEST_CONFIG: &str = concat!(
+	"[kernel]\n",
+	"cmdline = \"test=exit panic=exit\"\n",
+	"initrd = '\\initrd.cpio'\n",
+);
+
+type Leaf = (Vec<String,>, toml::Value,);
+
+/// keys of `config` as dotted paths with their values, sorted
+fn leaves(config: &Config,) -> Vec<Leaf,> {
+	let mut leaves: Vec<_,> = config
+		.entries()
+		.map(|entry| {
+			let mut path: Vec<_,> = entry
+				.section
+				.split('.',)
+				.map(str::trim,)
+				.filter(|s| !s.is_empty(),)
+				.map(str::to_string,)
+				.collect();
+			path.push(entry.key.to_string(),);
+			let value = match entry.value {
+				Value::Str(s,) => toml::Value::String(s.to_string(),),
+				Value::Int(i,) => toml::Value::Integer(i,),
+				Value::Bool(b,) => toml::Value::Boolean(b,),
+			};
+			(path, value,)
+		},)
+		.collect();
+	leaves.sort_by(|a, b| a.0.cmp(&b.0,),);
+	leaves
+}
+
+/// non-table values of `table` as dotted paths, sorted
+fn toml_leaves(table: &toml::Table,) -> Vec<Leaf,> {
+	fn walk(table: &toml::Table, prefix: &[String], out: &mut Vec<Leaf,>,) {
+		for (key, value,) in table {
+			let mut path = prefix.to_vec();
+			path.push(key.clone(),);
+			match value {
+				toml::Value::Table(table,) => walk(table, &path, out,),
+				value => out.push((path, value.clone(),),),
+			}
+		}
+	}
+	let mut out = vec![];
+	walk(table, &[], &mut out,);
+	out.sort_by(|a, b| a.0.cmp(&b.0,),);
+	out
+}
+
+/// asserts that `toml` reads `src` as `Config` does if `Config` accepts it.
+/// returns whether `Config` accepts it
+fn compare(src: &str,) -> bool {
+	let table = toml::from_str::<toml::Table,>(src,);
+	let Ok(config,) = Config::parse(src,) else {
+		return false;
+	};
+	let table = table.unwrap_or_else(|e| panic!("toml rejects {src:?}: {e}"),);
+	assert_eq!(leaves(&config), toml_leaves(&table), "{src:?}");
+	true
+}
+
+#[test]
+fn test_shipped_configs_match_toml() {
+	assert!(compare(LOADER_EXAMPLE));
+	assert!(compare(TEST_CONFIG));
+}
+
+#[test]
+fn test_comments_and_whitespace_match_toml() {
+	let src = "# head\n\n  a = 1#tail\n[ b . c ] # table\n\td = 'x # y' \n";
+	assert!(compare(src));
+	assert!(compare("a = \"#\"\r\nb = true\r\n"));
+	assert!(compare(""));
+}
+
+#[test]
+fn test_values_match_toml() {
+	for value in [
+		"0", "-0", "+1", "1_000", "0xdead_BEEF", "0o17", "0b101", "true",
+		"false", "''", "\"\"", "'\\'", "\"'\"", "'\"'", "-9223372036854775808",
+	] {
+		assert!(compare(&format!("a = {value}")), "{value}");
+	}
+}
+
+#[test]
+fn test_unsupported_syntax_is_rejected() {
+	// valid TOML outside of the supported subset
+	for src in [
+		"\"a\" = 1",
+		"'a' = 1",
+		"a.b = 1",
+		"a = [1, 2]",
+		"a = { b = 1 }",
+		"[[a]]\nb = 1",
+		"a = 1.5",
+		"a = 1979-05-27",
+		"a = \"\\n\"",
+		"a = '''x'''",
+		"a = \"\"\"x\"\"\"",
+	] {
+		assert!(toml::from_str::<toml::Table>(src).is_ok(), "{src:?}");
+		assert!(!compare(src), "{src:?}");
+	}
+}
+
+#[test]
+fn test_invalid_input_is_rejected() {
+	for src in [
+		"a",
+		"a = ",
+		"a = 01",
+		"a = 1_",
+		"a = 0X1",
+		"a = 'x",
+		"a = 1 2",
+		"[a",
+		"[a.]",
+		"a = 1\na = 2",
+		"[a]\n[a]",
+		"a = 1\n[a]",
+		"a = 1\n[a.b]",
+		"[a]\nb = 1\n[a.b.c]",
+		"a = 9223372036854775808",
+	] {
+		assert!(toml::from_str::<toml::Table>(src).is_err(), "{src:?}");
+		assert!(!compare(src), "{src:?}");
+	}
+}
+
+/// line of a configuration built from a few names, so that sections and keys
+/// collide often
+fn line() -> impl Strategy<Value = String,> {
+	let name = prop::sample::select(vec!["a", "b", "c"],);
+	let section = prop::collection::vec(name.clone(), 1..3,)
+		.prop_map(|names| format!("[{}]", names.join(" . ")),);
+	let value = prop::sample::select(vec![
+		"1", "-0x1", "0b1_0", "01", "true", "'x'", "\"y\"", "1.0", "[]",
+	],);
+	let entry = (name, value,).prop_map(|(k, v,)| format!("{k} = {v}"),);
+	prop_oneof![section, entry, Just("# c".to_string())]
+}
+
+proptest! {
+	#[test]
+	fn test_generated_configs_match_toml(
+		lines in prop::collection::vec(line(), 0..8),
+	) {
+		compare(&lines.join("\n"));
+	}
+}
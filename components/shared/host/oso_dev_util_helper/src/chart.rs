@@ -0,0 +1,248 @@
+//! # Dependency Chart
+//!
+//! Renders dependencies between workspace crates as a mermaid flowchart or a
+//! DOT graph. Crates are colored by kind and edges to proc-macro crates are
+//! dashed.
+//!
+//! ```rust,no_run
+//! use oso_dev_util_helper::chart::DepChart;
+//!
+//! let chart = DepChart::of_workspace().unwrap();
+//! println!("{}", chart.mermaid());
+//! ```
+
+use crate::fs::CARGO_MANIFEST;
+use crate::fs::all_crates;
+use crate::fs::read_toml;
+use anyhow::Result as Rslt;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Dependency tables of a manifest shown in the chart
+const DEPENDENCY_TABLES: [&str; 2] = ["dependencies", "build-dependencies",];
+
+/// Kind of crate, which decides its color
+#[derive(Clone, Copy, Debug, PartialEq, Eq,)]
+pub enum ChartKind {
+	NoStd,
+	Host,
+	ProcMacro,
+}
+
+impl ChartKind {
+	/// Class name in mermaid output
+	fn class(&self,) -> &'static str {
+		match self {
+			Self::NoStd => "no_std",
+			Self::Host => "host",
+			Self::ProcMacro => "proc_macro",
+		}
+	}
+
+	fn color(&self,) -> &'static str {
+		match self {
+			Self::NoStd => "#f4a261",
+			Self::Host => "#8ecae6",
+			Self::ProcMacro => "#cdb4db",
+		}
+	}
+}
+
+/// Crate in [`DepChart`]
+///
+/// # Fields
+///
+/// * `name` - Package name
+/// * `kind` - Kind of the crate
+/// * `deps` - Indices of crates this crate depends on
+#[derive(Clone, Debug, PartialEq, Eq,)]
+pub struct ChartNode {
+	pub name: String,
+	pub kind: ChartKind,
+	pub deps: Vec<usize,>,
+}
+
+/// Dependencies between workspace crates
+#[derive(Clone, Debug, Default, PartialEq, Eq,)]
+pub struct DepChart {
+	nodes: Vec<ChartNode,>,
+}
+
+impl DepChart {
+	/// Chart of every crate found by [`all_crates`]
+	pub fn of_workspace() -> Rslt<Self,> {
+		Self::new(&all_crates()?,)
+	}
+
+	/// Reads manifests of `crates`. Directories with a virtual manifest are
+	/// skipped
+	pub fn new(crates: &[PathBuf],) -> Rslt<Self,> {
+		let mut manifests = vec![];
+		for dir in crates {
+			let Some(toml,) = read_toml(dir.join(CARGO_MANIFEST,),) else {
+				continue;
+			};
+			let toml = toml?;
+			let Some(name,) = toml
+				.get("package",)
+				.and_then(|pkg| pkg.get("name",),)
+				.and_then(|name| name.as_str(),)
+			else {
+				continue;
+			};
+
+			let kind = if is_proc_macro(&toml,) {
+				ChartKind::ProcMacro
+			} else if is_no_std(dir,) {
+				ChartKind::NoStd
+			} else {
+				ChartKind::Host
+			};
+			let deps = DEPENDENCY_TABLES
+				.iter()
+				.filter_map(|table| toml.get(*table,)?.as_table(),)
+				.flat_map(|table| table.keys().cloned(),)
+				.collect();
+			manifests.push((name.to_string(), kind, deps,),);
+		}
+		Ok(Self::from_manifests(manifests,),)
+	}
+
+	/// Builds chart from `(name, kind, dependency names)` of each crate.
+	/// Dependencies outside of the given crates are dropped
+	pub fn from_manifests(
+		manifests: Vec<(String, ChartKind, Vec<String,>,),>,
+	) -> Self {
+		let names: Vec<String,> =
+			manifests.iter().map(|(name, ..,)| name.clone(),).collect();
+		let nodes = manifests
+			.into_iter()
+			.map(|(name, kind, deps,)| {
+				let mut deps: Vec<usize,> = deps
+					.iter()
+					.filter_map(|dep| names.iter().position(|n| n == dep,),)
+					.collect();
+				deps.sort_unstable();
+				deps.dedup();
+				ChartNode { name, kind, deps, }
+			},)
+			.collect();
+		Self { nodes, }
+	}
+
+	pub fn nodes(&self,) -> &[ChartNode] {
+		&self.nodes
+	}
+
+	/// Renders the chart as a mermaid flowchart
+	pub fn mermaid(&self,) -> String {
+		let mut out = "flowchart TD\n".to_string();
+		for node in &self.nodes {
+			let class = node.kind.class();
+			out += &format!("\t{0}[{0}]:::{class}\n", node.name);
+		}
+		for (node, dep,) in self.edges() {
+			let arrow =
+				if dep.kind == ChartKind::ProcMacro { "-.->" } else { "-->" };
+			out += &format!("\t{} {arrow} {}\n", node.name, dep.name);
+		}
+		for kind in [ChartKind::NoStd, ChartKind::Host, ChartKind::ProcMacro,] {
+			out += &format!(
+				"\tclassDef {} fill:{}\n",
+				kind.class(),
+				kind.color()
+			);
+		}
+		out
+	}
+
+	/// Renders the chart as a graphviz DOT graph
+	pub fn dot(&self,) -> String {
+		let mut out = "digraph crates {\n\tnode [style=filled];\n".to_string();
+		for node in &self.nodes {
+			out += &format!(
+				"\t\"{}\" [fillcolor=\"{}\"];\n",
+				node.name,
+				node.kind.color()
+			);
+		}
+		for (node, dep,) in self.edges() {
+			let style = if dep.kind == ChartKind::ProcMacro {
+				" [style=dashed]"
+			} else {
+				""
+			};
+			out +=
+				&format!("\t\"{}\" -> \"{}\"{style};\n", node.name, dep.name);
+		}
+		out += "}\n";
+		out
+	}
+
+	fn edges(&self,) -> impl Iterator<Item = (&ChartNode, &ChartNode,),> {
+		self.nodes.iter().flat_map(move |node| {
+			node.deps.iter().map(move |dep| (node, &self.nodes[*dep],),)
+		},)
+	}
+}
+
+/// Whether `manifest` declares a proc-macro library
+pub fn is_proc_macro(manifest: &toml::Table,) -> bool {
+	manifest
+		.get("lib",)
+		.and_then(|lib| lib.get("proc-macro",),)
+		.and_then(|p| p.as_bool(),)
+		.unwrap_or_default()
+}
+
+/// Whether the crate root of the crate at `dir` has `#![no_std]`
+pub fn is_no_std(dir: &Path,) -> bool {
+	["src/lib.rs", "src/main.rs",].iter().any(|root| {
+		std::fs::read_to_string(dir.join(root,),)
+			.is_ok_and(|src| src.lines().any(|l| l.trim() == "#![no_std]",),)
+	},)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn chart() -> DepChart {
+		let crate_ = |name: &str, kind, deps: &[&str],| {
+			let deps = deps.iter().map(|d| d.to_string(),).collect();
+			(name.to_string(), kind, deps,)
+		};
+		DepChart::from_manifests(vec![
+			crate_("oso_kernel", ChartKind::NoStd, &[
+				"oso_error",
+				"oso_macro",
+				"log",
+			],),
+			crate_("oso_error", ChartKind::NoStd, &[],),
+			crate_("oso_macro", ChartKind::ProcMacro, &["syn",],),
+		],)
+	}
+
+	#[test]
+	fn test_mermaid() {
+		assert_eq!(
+			chart().mermaid(),
+			"flowchart TD\n\toso_kernel[oso_kernel]:::no_std\n\t\
+			 oso_error[oso_error]:::no_std\n\toso_macro[oso_macro]:::\
+			 proc_macro\n\toso_kernel --> oso_error\n\toso_kernel -.-> \
+			 oso_macro\n\tclassDef no_std fill:#f4a261\n\tclassDef host \
+			 fill:#8ecae6\n\tclassDef proc_macro fill:#cdb4db\n"
+		);
+	}
+
+	#[test]
+	fn test_dot() {
+		let dot = chart().dot();
+		assert!(dot.starts_with("digraph crates {\n"));
+		assert!(dot.contains("\t\"oso_kernel\" -> \"oso_error\";\n"));
+		assert!(
+			dot.contains("\t\"oso_kernel\" -> \"oso_macro\" [style=dashed];\n")
+		);
+		assert!(dot.contains("\t\"oso_macro\" [fillcolor=\"#cdb4db\"];\n"));
+	}
+}
@@ -1,6 +1,8 @@
 #![feature(exit_status_error)]
 #![feature(iterator_try_collect)]
 
+pub mod chart;
 pub mod cli;
 pub mod fs;
+pub mod specs;
 pub mod util;
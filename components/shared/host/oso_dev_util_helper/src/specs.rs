@@ -0,0 +1,121 @@
+//! # Vendored Specifications
+//!
+//! `status!` reads the UEFI specification from the web while expanding,
+//! which makes a build depend on the network and on whatever the server
+//! returns that day. A page vendored under [`SPECS_DIR`] by `cargo xtask
+//! vendor-specs` is read instead, so a build with the directory committed
+//! does not touch the network.
+//!
+//! Setting [`OFFLINE_ENV`] to `1` forbids network access of the macros: a
+//! macro which needs a page that is not vendored fails with a diagnostic
+//! instead of downloading it.
+//!
+//! Pages are stored under the host and path of their URL:
+//!
+//! ```text
+//! https://uefi.org/specs/UEFI/2.11/Apx_D_Status_Codes.html
+//! -> specs/uefi.org/specs/UEFI/2.11/Apx_D_Status_Codes.html
+//! ```
+
+use anyhow::Result as Rslt;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// directory of vendored pages, relative to the project root
+pub const SPECS_DIR: &str = "specs";
+/// environment variable which forbids network access of macros when `1`
+pub const OFFLINE_ENV: &str = "OSO_OFFLINE";
+
+/// whether [`OFFLINE_ENV`] is `1`
+pub fn is_offline() -> bool {
+	std::env::var(OFFLINE_ENV,).is_ok_and(|v| v == "1",)
+}
+
+/// path of the page at `url` under [`SPECS_DIR`] of `root`
+pub fn vendored_path(root: &Path, url: &str,) -> PathBuf {
+	let path = url.split_once("://",).map_or(url, |(_, path,)| path,);
+	root.join(SPECS_DIR,).join(path,)
+}
+
+/// URL of the status codes appendix of the UEFI specification `version`
+pub fn status_page_url(version: &str,) -> String {
+	format!("https://uefi.org/specs/UEFI/{version}/Apx_D_Status_Codes.html")
+}
+
+/// URLs of the pages read by the macros in the sources of `crates`, sorted
+/// and without duplicates
+pub fn crate_spec_pages(crates: &[PathBuf],) -> Rslt<Vec<String,>,> {
+	let mut pages = vec![];
+	for dir in crates {
+		collect_pages(&dir.join("src",), &mut pages,)?;
+	}
+	pages.sort();
+	pages.dedup();
+	Ok(pages,)
+}
+
+fn collect_pages(dir: &Path, pages: &mut Vec<String,>,) -> Rslt<(),> {
+	let Ok(entries,) = dir.read_dir() else {
+		return Ok((),);
+	};
+	for entry in entries {
+		let path = entry?.path();
+		if path.is_dir() {
+			collect_pages(&path, pages,)?;
+		} else if path.extension().is_some_and(|ext| ext == "rs",) {
+			pages.extend(spec_pages(&std::fs::read_to_string(&path,)?,),);
+		}
+	}
+	Ok((),)
+}
+
+/// URLs of the pages which the `status!` invocations of `source` read
+pub fn spec_pages(source: &str,) -> Vec<String,> {
+	source
+		.split("status!(",)
+		.skip(1,)
+		.filter_map(|args| {
+			let version = args.split([',', ')',],).next()?.trim();
+			let is_version = !version.is_empty()
+				&& version.chars().all(|c| c.is_ascii_digit() || c == '.',);
+			is_version.then(|| status_page_url(version,),)
+		},)
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_vendored_path() {
+		let url = status_page_url("2.11",);
+		let path = vendored_path(Path::new("/oso",), &url,);
+		let expected = "/oso/specs/uefi.org/specs/UEFI/2.11";
+		assert_eq!(path, Path::new(expected,).join("Apx_D_Status_Codes.html"));
+	}
+
+	#[test]
+	fn test_crate_spec_pages() -> Rslt<(),> {
+		let name = format!("oso_specs_{}", std::process::id());
+		let dir = std::env::temp_dir().join(name,);
+		std::fs::create_dir_all(dir.join("src/raw",),)?;
+		std::fs::write(dir.join("src/lib.rs",), "status!(2.11);",)?;
+		std::fs::write(dir.join("src/raw/types.rs",), "status!(2.11);",)?;
+		std::fs::write(dir.join("src/notes.md",), "status!(2.10);",)?;
+		let pages = crate_spec_pages(std::slice::from_ref(&dir,),);
+		std::fs::remove_dir_all(&dir,)?;
+		assert_eq!(pages?, vec![status_page_url("2.11",)]);
+		Ok((),)
+	}
+
+	#[test]
+	fn test_spec_pages() {
+		let source = "oso_proc_macro::status!(2.11);\nstatus!(2.10, pin = \
+		              \"sha256:00\");\n// status!(version)\n";
+		assert_eq!(spec_pages(source,), vec![
+			status_page_url("2.11",),
+			status_page_url("2.10",),
+		]);
+	}
+}
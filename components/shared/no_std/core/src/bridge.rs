@@ -13,6 +13,9 @@
 //! - CPU control functions (wait for interrupt, wait for event, no-operation)
 //! - Framebuffer configuration for graphics output
 //! - Device tree address handling
+//! - Boot information handoff from loader to kernel
+//! - Version handshake between loader and kernel
+//! - Panic policy shared by loader and kernel
 //!
 //! ## Usage
 //!
@@ -38,5 +41,8 @@
 //! wfi(); // This function never returns
 //! ```
 
+pub mod boot_info;
 pub mod device_tree;
 pub mod graphic;
+pub mod panic_policy;
+pub mod version;
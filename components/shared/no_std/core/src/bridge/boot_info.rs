@@ -0,0 +1,875 @@
+//! # Boot Information Module
+//!
+//! This module defines [`BootInfo`], the single structure the loader hands to
+//! the kernel entry point. It collects everything the kernel needs to know
+//! about the machine state at handoff time:
+//!
+//! - Address of the device tree blob
+//! - Kernel command line
+//! - Memory map after `ExitBootServices`, simplified into [`MemoryRegion`]s
+//! - Address of the UEFI runtime services table, and the services firmware
+//!   supports after boot in [`RuntimeCaps`]
+//! - CRC-32 of each kernel segment as the loader wrote it
+//! - Framebuffer the loader hands over, reported as
+//!   [`MemoryRegionKind::Framebuffer`] in the memory map
+//! - Boot modules, if the kernel was started by another boot protocol which
+//!   provides them
+//! - Boot services calls of the loader in [`FirmwareCalls`], if it was built
+//!   with its flight recorder
+//! - Counter values of the loader at [`Milestones`] between
+//!   `ExitBootServices` and the kernel entry
+//!
+//! ## ABI
+//!
+//! Every type in this module is `#[repr(C)]` so that the loader and the kernel
+//! agree on the layout even if they are built with different compiler
+//! settings. Slices are passed as pointer + length pairs ([`MemoryRegions`]).
+//!
+//! The size of each type and the offset of each field are checked at compile
+//! time by `#[derive(BridgeLayout)]`. The layout is that of 64-bit targets, so
+//! a target on which it differs fails to build instead of misreading the
+//! handoff.
+//!
+//! Fields wider than 32 bits are stored as little endian. Read and write them
+//! with their `read_<field>` and `write_<field>` accessors, which stay correct
+//! on big-endian targets and on targets which cannot access 64 bits at once.
+//!
+//! ## Lifetime
+//!
+//! The loader allocates `BootInfo` and the buffers it points to as loader
+//! data. These regions are reported as [`MemoryRegionKind::Loader`] and must
+//! not be reused by the kernel until it has finished reading boot information.
+//!
+//! ## Serialization
+//!
+//! [`BootInfo::serialize`] writes everything the kernel was handed in an
+//! encoding which does not depend on the layout, so the handoff of a machine
+//! can be attached to a bug report and decoded on the host by
+//! `cargo xtask bootinfo <file>`.
+//!
+//! The encoding starts with the magic [`SERIAL_MAGIC`] and the version byte
+//! [`SERIAL_VERSION`], followed by records of `tag: u8`, `len: u16` and `len`
+//! bytes of payload, like kernel crash dumps. Integers are little endian.
+//!
+//! | tag | record        | payload                                         |
+//! |-----|---------------|-------------------------------------------------|
+//! | 1   | abi           | [`BRIDGE_ABI`] as `u32`                         |
+//! | 2   | device tree   | address as `u64`                                |
+//! | 3   | command line  | UTF-8 text                                      |
+//! | 4   | memory region | fields of [`MemoryRegion`]                      |
+//! | 5   | runtime       | table address as `u64`, fields of [`RuntimeCaps`] |
+//! | 6   | segment       | fields of [`SegmentChecksum`]                   |
+//! | 7   | framebuffer   | fields of [`FrameBufConf`]                      |
+//! | 8   | module        | `start: u64`, `size: u64`, UTF-8 command line   |
+//! | 9   | firmware call | fields of [`FirmwareCall`]                      |
+//! | 10  | milestones    | fields of [`Milestones`]                        |
+//! | 0   | end           | CRC-32 (IEEE) of every byte before this record  |
+//!
+//! Fields are written in declaration order. Enums are written as `u32`,
+//! pointers and `usize` as `u64`.
+//! Memory regions, segments, modules and firmware calls are one record each,
+//! calls oldest first. Decoders skip
+//! tags they do not know, so records can be added without a new version.
+
+use super::device_tree::DeviceTreeAddress;
+use super::graphic::FrameBufConf;
+use super::version::BRIDGE_ABI;
+use crate::data::crc32;
+use crate::units;
+use oso_proc_macro::BridgeLayout;
+
+/// Information passed from the loader to the kernel entry point
+///
+/// # Fields
+///
+/// * `device_tree` - Pointer to the device tree blob
+/// * `cmdline` - Kernel command line configured in the loader configuration
+/// * `memory_map` - Memory regions as they were at handoff time
+/// * `runtime_services` - Address of the UEFI runtime services table. Virtual
+///   if `runtime` has [`RuntimeCaps::VIRTUAL_MODE`], otherwise physical as
+///   firmware stayed in physical mode, e.g. when `SetVirtualAddressMap`
+///   failed. `0` when runtime services are unavailable
+/// * `segments` - Checksums of the loadable segments of the kernel
+/// * `framebuffer` - Framebuffer in the final mode of the boot protocol. Null
+///   when there is none the kernel may draw on, e.g. without a display or
+///   with a `BltOnly` mode
+/// * `modules` - Files loaded next to the kernel, such as an initial ramdisk
+/// * `runtime` - Runtime services firmware supports after boot
+/// * `firmware_calls` - Boot services calls the loader made, empty unless
+///   its flight recorder ran
+/// * `milestones` - When the loader passed the steps after
+///   `ExitBootServices`
+#[repr(C)]
+#[derive(BridgeLayout, Debug, Clone, Copy,)]
+#[layout(size = 152)]
+pub struct BootInfo {
+	#[layout(offset = 0)]
+	pub device_tree:      DeviceTreeAddress,
+	#[layout(offset = 8)]
+	pub cmdline:          CommandLine,
+	#[layout(offset = 24)]
+	pub memory_map:       MemoryRegions,
+	#[layout(offset = 40)]
+	pub runtime_services: u64,
+	#[layout(offset = 48)]
+	pub segments:         SegmentChecksums,
+	#[layout(offset = 64)]
+	pub framebuffer:      *const FrameBufConf,
+	#[layout(offset = 72)]
+	pub modules:          Modules,
+	#[layout(offset = 88)]
+	pub runtime:          RuntimeCaps,
+	#[layout(offset = 96)]
+	pub firmware_calls:   FirmwareCalls,
+	#[layout(offset = 120)]
+	pub milestones:       Milestones,
+}
+
+impl BootInfo {
+	/// Creates boot information which only knows the device tree
+	///
+	/// Memory map and runtime services are filled in by the loader after
+	/// exiting boot services.
+	pub const fn new(device_tree: DeviceTreeAddress,) -> Self {
+		Self {
+			device_tree,
+			cmdline: CommandLine::empty(),
+			memory_map: MemoryRegions::empty(),
+			runtime_services: 0,
+			segments: SegmentChecksums::empty(),
+			framebuffer: core::ptr::null(),
+			modules: Modules::empty(),
+			runtime: RuntimeCaps::none(),
+			firmware_calls: FirmwareCalls::empty(),
+			milestones: Milestones::empty(),
+		}
+	}
+
+	/// Returns `true` if runtime services are callable, in virtual or
+	/// physical mode as `runtime` tells
+	pub const fn has_runtime_services(&self,) -> bool {
+		self.read_runtime_services() != 0
+	}
+
+	/// # Safety
+	///
+	/// `framebuffer` must be null or point to a configuration which stays valid
+	/// for `'a`
+	pub unsafe fn framebuffer<'a,>(&self,) -> Option<&'a FrameBufConf,> {
+		unsafe { self.framebuffer.as_ref() }
+	}
+
+	/// Writes the boot information into `out` in the encoding described in
+	/// the [module](self)
+	///
+	/// # Safety
+	///
+	/// Every pointer of `self` must be valid as its accessor requires
+	pub unsafe fn serialize(&self, out: &mut impl Write,) {
+		let mut s = Serializer { out, crc: !0, };
+		s.emit(&SERIAL_MAGIC,);
+		s.emit(&[SERIAL_VERSION,],);
+		s.record(SerialTag::Abi, &[&BRIDGE_ABI.to_le_bytes(),],);
+		let device_tree = self.device_tree as u64;
+		s.record(SerialTag::DeviceTree, &[&device_tree.to_le_bytes(),],);
+		s.text(SerialTag::CommandLine, &[], unsafe { self.cmdline.as_str() },);
+
+		for region in unsafe { self.memory_map.as_slice() } {
+			s.record(SerialTag::MemoryRegion, &[
+				&region.read_phys_start().to_le_bytes(),
+				&region.read_virt_start().to_le_bytes(),
+				&region.read_page_count().to_le_bytes(),
+				&(region.kind as u32).to_le_bytes(),
+				&region.read_attribute().to_le_bytes(),
+			],);
+		}
+		s.record(SerialTag::Runtime, &[
+			&self.read_runtime_services().to_le_bytes(),
+			&self.runtime.supported.to_le_bytes(),
+			&self.runtime.flags.to_le_bytes(),
+		],);
+		for segment in unsafe { self.segments.as_slice() } {
+			s.record(SerialTag::Segment, &[
+				&segment.read_start().to_le_bytes(),
+				&segment.read_size().to_le_bytes(),
+				&segment.flags.to_le_bytes(),
+				&segment.crc32.to_le_bytes(),
+			],);
+		}
+		if let Some(fb,) = unsafe { self.framebuffer() } {
+			s.record(SerialTag::Framebuffer, &[
+				&(fb.pixel_format as u32).to_le_bytes(),
+				&(fb.base as u64).to_le_bytes(),
+				&(fb.size as u64).to_le_bytes(),
+				&(fb.width as u64).to_le_bytes(),
+				&(fb.height as u64).to_le_bytes(),
+				&(fb.stride as u64).to_le_bytes(),
+			],);
+		}
+		for module in unsafe { self.modules.as_slice() } {
+			let (start, size,) = (module.read_start(), module.read_size(),);
+			let cmdline = unsafe { module.cmdline.as_str() };
+			s.text(
+				SerialTag::Module,
+				&[&start.to_le_bytes(), &size.to_le_bytes(),],
+				cmdline,
+			);
+		}
+		for call in unsafe { self.firmware_calls.iter() } {
+			s.record(SerialTag::FirmwareCall, &[
+				&call.read_timestamp().to_le_bytes(),
+				&call.read_status().to_le_bytes(),
+				&call.read_arg0().to_le_bytes(),
+				&call.read_arg1().to_le_bytes(),
+				&(call.service as u32).to_le_bytes(),
+			],);
+		}
+		let milestones = &self.milestones;
+		if milestones.read_exit_boot_services() != 0 {
+			s.record(SerialTag::Milestones, &[
+				&milestones.read_frequency().to_le_bytes(),
+				&milestones.read_exit_boot_services().to_le_bytes(),
+				&milestones.read_virtual_mode().to_le_bytes(),
+				&milestones.read_handoff().to_le_bytes(),
+			],);
+		}
+
+		let crc = !s.crc;
+		s.record(SerialTag::End, &[&crc.to_le_bytes(),],);
+	}
+}
+
+/// First bytes of a serialized [`BootInfo`]
+pub const SERIAL_MAGIC: [u8; 4] = *b"OSOB";
+/// Version of the encoding written by [`BootInfo::serialize`]
+pub const SERIAL_VERSION: u8 = 1;
+
+/// Record tags of a serialized [`BootInfo`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+#[repr(u8)]
+pub enum SerialTag {
+	End          = 0,
+	Abi          = 1,
+	DeviceTree   = 2,
+	CommandLine  = 3,
+	MemoryRegion = 4,
+	Runtime      = 5,
+	Segment      = 6,
+	Framebuffer  = 7,
+	Module       = 8,
+	FirmwareCall = 9,
+	Milestones   = 10,
+}
+
+/// Destination of a serialized [`BootInfo`]
+///
+/// Serializing runs on the crash path too, so errors are dropped by the
+/// destination.
+pub trait Write {
+	fn write(&mut self, bytes: &[u8],);
+}
+
+/// Writes records, keeping the checksum of every byte written
+struct Serializer<'a, W: Write,> {
+	out: &'a mut W,
+	crc: u32,
+}
+
+impl<W: Write,> Serializer<'_, W,> {
+	fn record(&mut self, tag: SerialTag, fields: &[&[u8]],) {
+		self.begin(tag, fields.iter().map(|field| field.len(),).sum(),);
+		fields.iter().for_each(|field| self.emit(field,),);
+	}
+
+	/// Record of `fields` followed by `text`, which is cut to fit
+	fn text(&mut self, tag: SerialTag, fields: &[&[u8]], text: &str,) {
+		let head: usize = fields.iter().map(|field| field.len(),).sum();
+		let mut len = text.len().min(u16::MAX as usize - head,);
+		while !text.is_char_boundary(len,) {
+			len -= 1;
+		}
+		self.begin(tag, head + len,);
+		fields.iter().for_each(|field| self.emit(field,),);
+		self.emit(&text.as_bytes()[..len],);
+	}
+
+	fn begin(&mut self, tag: SerialTag, len: usize,) {
+		self.emit(&[tag as u8,],);
+		self.emit(&(len as u16).to_le_bytes(),);
+	}
+
+	fn emit(&mut self, bytes: &[u8],) {
+		self.crc = crc32::update(self.crc, bytes,);
+		self.out.write(bytes,);
+	}
+}
+
+/// Runtime services firmware supports after boot
+///
+/// # Fields
+///
+/// * `supported` - `EFI_RT_SUPPORTED_*` bits of the services callable after
+///   `ExitBootServices`, from `EFI_RT_PROPERTIES_TABLE`. Firmware without the
+///   table supports every service
+/// * `flags` - How the services are called, e.g. [`Self::VIRTUAL_MODE`]
+#[repr(C)]
+#[derive(BridgeLayout, Debug, Clone, Copy, PartialEq, Eq,)]
+#[layout(size = 8)]
+pub struct RuntimeCaps {
+	#[layout(offset = 0)]
+	pub supported: u32,
+	#[layout(offset = 4)]
+	pub flags:     u32,
+}
+
+impl RuntimeCaps {
+	pub const GET_TIME: u32 = 0x0001;
+	pub const SET_TIME: u32 = 0x0002;
+	pub const GET_WAKEUP_TIME: u32 = 0x0004;
+	pub const SET_WAKEUP_TIME: u32 = 0x0008;
+	pub const GET_VARIABLE: u32 = 0x0010;
+	pub const GET_NEXT_VARIABLE_NAME: u32 = 0x0020;
+	pub const SET_VARIABLE: u32 = 0x0040;
+	pub const SET_VIRTUAL_ADDRESS_MAP: u32 = 0x0080;
+	pub const CONVERT_POINTER: u32 = 0x0100;
+	pub const GET_NEXT_HIGH_MONOTONIC_COUNT: u32 = 0x0200;
+	pub const RESET_SYSTEM: u32 = 0x0400;
+	pub const UPDATE_CAPSULE: u32 = 0x0800;
+	pub const QUERY_CAPSULE_CAPABILITIES: u32 = 0x1000;
+	pub const QUERY_VARIABLE_INFO: u32 = 0x2000;
+	/// Every service of UEFI 2.10
+	pub const ALL: u32 = 0x3fff;
+
+	/// Firmware switched into virtual mode, so the table and its services are
+	/// called at the virtual addresses of the memory map
+	pub const VIRTUAL_MODE: u32 = 0x1;
+
+	/// No runtime services
+	pub const fn none() -> Self {
+		Self { supported: 0, flags: 0, }
+	}
+
+	/// `true` if every service of `services` is supported
+	pub const fn supports(&self, services: u32,) -> bool {
+		self.supported & services == services
+	}
+
+	pub const fn is_virtual(&self,) -> bool {
+		self.flags & Self::VIRTUAL_MODE != 0
+	}
+}
+
+/// Pointer + length pair describing a UTF-8 string
+#[repr(C)]
+#[derive(BridgeLayout, Debug, Clone, Copy,)]
+#[layout(size = 16)]
+pub struct CommandLine {
+	#[layout(offset = 0)]
+	pub ptr: *const u8,
+	#[layout(offset = 8)]
+	pub len: usize,
+}
+
+impl CommandLine {
+	pub const fn empty() -> Self {
+		Self { ptr: core::ptr::null(), len: 0, }
+	}
+
+	/// # Safety
+	///
+	/// `ptr` must point to `len` bytes of valid UTF-8 which stay valid for `'a`
+	pub unsafe fn as_str<'a,>(&self,) -> &'a str {
+		if self.ptr.is_null() {
+			return "";
+		}
+		unsafe {
+			core::str::from_utf8_unchecked(core::slice::from_raw_parts(
+				self.ptr, self.len,
+			),)
+		}
+	}
+}
+
+/// Pointer + length pair describing an array of [`MemoryRegion`]
+///
+/// Regions do not cover the whole address space. Addresses outside of every
+/// region are not memory, and devices there are described by the device tree
+#[repr(C)]
+#[derive(BridgeLayout, Debug, Clone, Copy,)]
+#[layout(size = 16)]
+pub struct MemoryRegions {
+	#[layout(offset = 0)]
+	pub ptr: *const MemoryRegion,
+	#[layout(offset = 8)]
+	pub len: usize,
+}
+
+impl MemoryRegions {
+	pub const fn empty() -> Self {
+		Self { ptr: core::ptr::null(), len: 0, }
+	}
+
+	/// # Safety
+	///
+	/// `ptr` must point to `len` initialized regions which stay valid for `'a`
+	pub unsafe fn as_slice<'a,>(&self,) -> &'a [MemoryRegion] {
+		if self.ptr.is_null() {
+			return &[];
+		}
+		unsafe { core::slice::from_raw_parts(self.ptr, self.len,) }
+	}
+}
+
+/// Pointer + length pair describing an array of [`SegmentChecksum`]
+#[repr(C)]
+#[derive(BridgeLayout, Debug, Clone, Copy,)]
+#[layout(size = 16)]
+pub struct SegmentChecksums {
+	#[layout(offset = 0)]
+	pub ptr: *const SegmentChecksum,
+	#[layout(offset = 8)]
+	pub len: usize,
+}
+
+impl SegmentChecksums {
+	pub const fn empty() -> Self {
+		Self { ptr: core::ptr::null(), len: 0, }
+	}
+
+	/// # Safety
+	///
+	/// `ptr` must point to `len` initialized checksums which stay valid for
+	/// `'a`
+	pub unsafe fn as_slice<'a,>(&self,) -> &'a [SegmentChecksum] {
+		if self.ptr.is_null() {
+			return &[];
+		}
+		unsafe { core::slice::from_raw_parts(self.ptr, self.len,) }
+	}
+}
+
+/// CRC-32 of a loadable segment right after the loader copied it
+///
+/// # Fields
+///
+/// * `start` - Address of the segment
+/// * `size` - Size of the segment in memory, including zero filled bytes
+/// * `flags` - `p_flags` of the program header
+/// * `crc32` - [`crc32::checksum`] of the segment
+#[repr(C)]
+#[derive(BridgeLayout, Debug, Clone, Copy, PartialEq, Eq,)]
+#[layout(size = 24)]
+pub struct SegmentChecksum {
+	#[layout(offset = 0)]
+	pub start: u64,
+	#[layout(offset = 8)]
+	pub size:  u64,
+	#[layout(offset = 16)]
+	pub flags: u32,
+	#[layout(offset = 20)]
+	pub crc32: u32,
+}
+
+impl SegmentChecksum {
+	/// `p_flags` bit of writable segments
+	pub const WRITABLE: u32 = 0x2;
+	/// `p_flags` bit of executable segments
+	pub const EXECUTABLE: u32 = 0x1;
+
+	/// Checksum of `size` bytes at `start`
+	///
+	/// # Safety
+	///
+	/// The range must be readable
+	pub unsafe fn compute(start: u64, size: u64, flags: u32,) -> Self {
+		let crc32 = crc32::checksum(unsafe { segment(start, size,) },);
+		let mut checksum = Self { start: 0, size: 0, flags, crc32, };
+		checksum.write_start(start,);
+		checksum.write_size(size,);
+		checksum
+	}
+
+	/// Writable segments change once the kernel runs, so only the others can
+	/// be verified later
+	pub const fn is_verifiable(&self,) -> bool {
+		self.flags & Self::WRITABLE == 0
+	}
+
+	/// Current checksum of the segment
+	///
+	/// # Safety
+	///
+	/// The range of the segment must be readable
+	pub unsafe fn current(&self,) -> u32 {
+		let (start, size,) = (self.read_start(), self.read_size(),);
+		crc32::checksum(unsafe { segment(start, size,) },)
+	}
+}
+
+unsafe fn segment<'a,>(start: u64, size: u64,) -> &'a [u8] {
+	unsafe { core::slice::from_raw_parts(start as *const u8, size as usize,) }
+}
+
+/// Pointer + length pair describing an array of [`Module`]
+#[repr(C)]
+#[derive(BridgeLayout, Debug, Clone, Copy,)]
+#[layout(size = 16)]
+pub struct Modules {
+	#[layout(offset = 0)]
+	pub ptr: *const Module,
+	#[layout(offset = 8)]
+	pub len: usize,
+}
+
+impl Modules {
+	pub const fn empty() -> Self {
+		Self { ptr: core::ptr::null(), len: 0, }
+	}
+
+	/// # Safety
+	///
+	/// `ptr` must point to `len` initialized modules which stay valid for `'a`
+	pub unsafe fn as_slice<'a,>(&self,) -> &'a [Module] {
+		if self.ptr.is_null() {
+			return &[];
+		}
+		unsafe { core::slice::from_raw_parts(self.ptr, self.len,) }
+	}
+}
+
+/// File loaded into memory by the boot protocol
+///
+/// # Fields
+///
+/// * `start` - Address of the contents, as mapped at kernel entry
+/// * `size` - Size of the contents in bytes
+/// * `cmdline` - String attached to the module in the boot configuration
+#[repr(C)]
+#[derive(BridgeLayout, Debug, Clone, Copy,)]
+#[layout(size = 32)]
+pub struct Module {
+	#[layout(offset = 0)]
+	pub start:   u64,
+	#[layout(offset = 8)]
+	pub size:    u64,
+	#[layout(offset = 16)]
+	pub cmdline: CommandLine,
+}
+
+impl Module {
+	pub const fn new(start: u64, size: u64, cmdline: CommandLine,) -> Self {
+		let mut module = Self { start: 0, size: 0, cmdline, };
+		module.write_start(start,);
+		module.write_size(size,);
+		module
+	}
+
+	/// # Safety
+	///
+	/// The module must stay mapped for `'a`
+	pub unsafe fn contents<'a,>(&self,) -> &'a [u8] {
+		unsafe { segment(self.read_start(), self.read_size(),) }
+	}
+}
+
+/// Ring of the boot services calls the loader made, recorded by its flight
+/// recorder
+///
+/// Call `i` is stored at `i % capacity`, so once more than `capacity` calls
+/// were made only the latest `capacity` are kept.
+///
+/// # Fields
+///
+/// * `ptr` - Storage of `capacity` calls
+/// * `capacity` - Number of calls the ring holds
+/// * `count` - Number of calls recorded, including overwritten ones
+#[repr(C)]
+#[derive(BridgeLayout, Debug, Clone, Copy,)]
+#[layout(size = 24)]
+pub struct FirmwareCalls {
+	#[layout(offset = 0)]
+	pub ptr:      *const FirmwareCall,
+	#[layout(offset = 8)]
+	pub capacity: usize,
+	#[layout(offset = 16)]
+	pub count:    u64,
+}
+
+impl FirmwareCalls {
+	pub const fn empty() -> Self {
+		Self { ptr: core::ptr::null(), capacity: 0, count: 0, }
+	}
+
+	/// Number of calls which were overwritten by later ones
+	pub const fn dropped(&self,) -> u64 {
+		self.read_count().saturating_sub(self.capacity as u64,)
+	}
+
+	/// Calls kept in the ring, oldest first
+	///
+	/// # Safety
+	///
+	/// `ptr` must point to `capacity` calls, of which the first `count` are
+	/// initialized, which stay valid for `'a`
+	pub unsafe fn iter<'a,>(&self,) -> impl Iterator<Item = &'a FirmwareCall,> {
+		let ring: &[FirmwareCall] = if self.ptr.is_null() {
+			&[]
+		} else {
+			unsafe { core::slice::from_raw_parts(self.ptr, self.capacity,) }
+		};
+		let capacity = ring.len() as u64;
+		(self.dropped()..self.read_count())
+			.map(move |i| &ring[(i % capacity) as usize],)
+	}
+}
+
+/// Boot services call recorded by the flight recorder of the loader
+///
+/// # Fields
+///
+/// * `timestamp` - Counter of the CPU when the call returned, the generic
+///   timer on AArch64, `time` on RISC-V and the time stamp counter on x86_64
+/// * `status` - `EFI_STATUS` the call returned
+/// * `arg0`, `arg1` - Arguments and results of the call, as described by
+///   each [`FirmwareService`]
+/// * `service` - Service called
+#[repr(C)]
+#[derive(BridgeLayout, Debug, Clone, Copy, PartialEq, Eq,)]
+#[layout(size = 40)]
+pub struct FirmwareCall {
+	#[layout(offset = 0)]
+	pub timestamp: u64,
+	#[layout(offset = 8)]
+	pub status:    u64,
+	#[layout(offset = 16)]
+	pub arg0:      u64,
+	#[layout(offset = 24)]
+	pub arg1:      u64,
+	#[layout(offset = 32)]
+	pub service:   FirmwareService,
+}
+
+impl FirmwareCall {
+	pub const fn new(
+		service: FirmwareService,
+		args: [u64; 2],
+		status: u64,
+		timestamp: u64,
+	) -> Self {
+		let mut call =
+			Self { timestamp: 0, status: 0, arg0: 0, arg1: 0, service, };
+		call.write_timestamp(timestamp,);
+		call.write_status(status,);
+		call.write_arg0(args[0],);
+		call.write_arg1(args[1],);
+		call
+	}
+}
+
+/// Counter values of the loader after `ExitBootServices`, when it has no
+/// time services of firmware left
+///
+/// The counter is the one the kernel times its boot stages with, the
+/// physical count of the generic timer on AArch64, `time` on RISC-V and the
+/// time stamp counter on x86_64. `0` means the milestone was not reached.
+///
+/// # Fields
+///
+/// * `frequency` - Frequency of the counter in Hz, `0` if unknown
+/// * `exit_boot_services` - `ExitBootServices` returned
+/// * `virtual_mode` - `SetVirtualAddressMap` returned
+/// * `handoff` - Boot information is complete, right before the kernel is
+///   entered
+#[repr(C)]
+#[derive(BridgeLayout, Debug, Clone, Copy, PartialEq, Eq,)]
+#[layout(size = 32)]
+pub struct Milestones {
+	#[layout(offset = 0)]
+	pub frequency:          u64,
+	#[layout(offset = 8)]
+	pub exit_boot_services: u64,
+	#[layout(offset = 16)]
+	pub virtual_mode:       u64,
+	#[layout(offset = 24)]
+	pub handoff:            u64,
+}
+
+impl Milestones {
+	pub const fn empty() -> Self {
+		Self {
+			frequency:          0,
+			exit_boot_services: 0,
+			virtual_mode:       0,
+			handoff:            0,
+		}
+	}
+
+	/// Milestones by name, in the order the loader reaches them
+	pub const fn named(&self,) -> [(&'static str, u64,); 3] {
+		[
+			("exit_boot_services", self.read_exit_boot_services(),),
+			("virtual_mode", self.read_virtual_mode(),),
+			("handoff", self.read_handoff(),),
+		]
+	}
+
+	/// Microseconds from `ExitBootServices` to counter value `ticks`.
+	/// `None` if the frequency is unknown or the milestone was not reached
+	pub const fn micros_since_exit(&self, ticks: u64,) -> Option<u64,> {
+		let (frequency, exit,) =
+			(self.read_frequency(), self.read_exit_boot_services(),);
+		if frequency == 0 || exit == 0 || ticks == 0 {
+			return None;
+		}
+		let ticks = ticks.wrapping_sub(exit,) as u128;
+		Some((ticks * 1_000_000 / frequency as u128) as u64,)
+	}
+}
+
+/// Boot service of a [`FirmwareCall`], with what its arguments hold
+#[repr(u32)]
+#[derive(BridgeLayout, Debug, Clone, Copy, PartialEq, Eq,)]
+#[layout(size = 4)]
+pub enum FirmwareService {
+	/// Number of pages, and the address allocated
+	AllocatePages,
+	/// Size of the buffer passed and the size of the map, which is the size
+	/// needed when the buffer is too small
+	GetMemoryMap,
+	/// Memory type and size in bytes
+	AllocatePool,
+	/// Address of the buffer
+	FreePool,
+	/// Event type and task priority level
+	CreateEvent,
+	/// Timer type and trigger time in 100 nanoseconds
+	SetTimer,
+	/// Number of events, and the index of the signaled one
+	WaitForEvent,
+	/// Event
+	CloseEvent,
+	/// Handle and `time_low` of the protocol GUID
+	HandleProtocol,
+	/// Search type and number of handles found
+	LocateHandleBuffer,
+	/// Handle and `time_low` of the protocol GUID
+	OpenProtocol,
+	/// Handle and number of protocols on it
+	ProtocolsPerHandle,
+	/// Controller handle, and `1` if children were connected recursively
+	ConnectController,
+	/// Microseconds
+	Stall,
+	/// Image handle and map key
+	ExitBootServices,
+	/// Address and number of pages
+	FreePages,
+}
+
+/// A physically contiguous range of memory with uniform usage
+///
+/// # Fields
+///
+/// * `phys_start` - Physical start address. Always page aligned
+/// * `virt_start` - Virtual address assigned by the loader. Equal to
+///   `phys_start` for regions which have no dedicated mapping
+/// * `page_count` - Size of the region in 4KiB pages
+/// * `kind` - How the kernel may use the region
+/// * `attribute` - Raw UEFI memory attribute bits (cacheability, RO, XP, ...)
+#[repr(C)]
+#[derive(BridgeLayout, Debug, Clone, Copy, PartialEq, Eq,)]
+#[layout(size = 40)]
+pub struct MemoryRegion {
+	#[layout(offset = 0)]
+	pub phys_start: u64,
+	#[layout(offset = 8)]
+	pub virt_start: u64,
+	#[layout(offset = 16)]
+	pub page_count: u64,
+	#[layout(offset = 24)]
+	pub kind:       MemoryRegionKind,
+	#[layout(offset = 32)]
+	pub attribute:  u64,
+}
+
+impl MemoryRegion {
+	pub const PAGE_SIZE: u64 = units::PAGE_SIZE;
+
+	pub const fn new(
+		phys_start: u64,
+		virt_start: u64,
+		page_count: u64,
+		kind: MemoryRegionKind,
+		attribute: u64,
+	) -> Self {
+		let mut region = Self {
+			phys_start: 0,
+			virt_start: 0,
+			page_count: 0,
+			kind,
+			attribute: 0,
+		};
+		region.write_phys_start(phys_start,);
+		region.write_virt_start(virt_start,);
+		region.write_page_count(page_count,);
+		region.write_attribute(attribute,);
+		region
+	}
+
+	/// Size of the region in bytes
+	pub const fn size(&self,) -> u64 {
+		self.read_page_count() * Self::PAGE_SIZE
+	}
+
+	/// Physical end address (exclusive)
+	pub const fn phys_end(&self,) -> u64 {
+		self.read_phys_start() + self.size()
+	}
+
+	pub const fn contains(&self, phys_addr: u64,) -> bool {
+		self.read_phys_start() <= phys_addr && phys_addr < self.phys_end()
+	}
+}
+
+/// Usage of a [`MemoryRegion`] from the kernel's point of view
+#[repr(u32)]
+#[derive(BridgeLayout, Debug, Clone, Copy, PartialEq, Eq,)]
+#[layout(size = 4)]
+pub enum MemoryRegionKind {
+	/// Free memory
+	Usable,
+	/// Memory used by firmware during boot. Free after handoff
+	Reclaimable,
+	/// Kernel image and boot information. Must be preserved until the kernel
+	/// finished reading [`BootInfo`]
+	Loader,
+	/// Code of UEFI runtime services. Must stay mapped while runtime services
+	/// are in use
+	RuntimeCode,
+	/// Data of UEFI runtime services. Must stay mapped while runtime services
+	/// are in use
+	RuntimeData,
+	/// ACPI tables. Free after the tables are consumed
+	AcpiReclaim,
+	/// ACPI non volatile storage
+	AcpiNvs,
+	/// Memory mapped I/O
+	Mmio,
+	/// Anything the kernel must never touch
+	Reserved,
+	/// Framebuffer handed over by the boot loader. Firmware no longer draws
+	/// on it, and only the kernel owns it once claimed
+	Framebuffer,
+}
+
+impl MemoryRegionKind {
+	/// Returns `true` if the kernel may use the region as general purpose
+	/// memory right after boot
+	pub const fn is_usable(&self,) -> bool {
+		matches!(self, Self::Usable | Self::Reclaimable)
+	}
+
+	pub const fn is_runtime(&self,) -> bool {
+		matches!(self, Self::RuntimeCode | Self::RuntimeData)
+	}
+}
@@ -6,6 +6,33 @@
 //! Device Trees are commonly used in embedded systems and operating systems
 //! to provide a hardware description that the kernel can use to configure
 //! drivers and manage hardware resources.
+//!
+//! [`Fdt`] looks up nodes and properties of a flattened device tree blob
+//! without allocating.
+//!
+//! ## Decoding
+//!
+//! Readers which keep their own copy of a tree, such as the device tree
+//! overlays of the loader, decode it with the same helpers as [`Fdt`]:
+//!
+//! - [`be32`], [`be64`]: Big endian cells and header fields
+//! - [`c_str`]: NUL-terminated string, such as a path property
+//! - [`strings`]: Entries of a string list, such as `compatible`
+//! - [`name_matches`]: Node name lookup with an optional unit address
+//! - [`token`]: Tokens of the structure block
+//!
+//! Strings are decoded with [`utf8`], so invalid ones are skipped instead of
+//! being misread.
+
+/// Typed resources of device nodes
+pub mod resource;
+
+use crate::text::utf8;
+use core::fmt;
+
+/// Deepest node [`Fdt::write_path`] writes the path of. The root is at
+/// depth `0`
+pub const MAX_DEPTH: usize = 16;
 
 /// Represents a pointer to a Device Tree Blob (DTB) in memory.
 ///
@@ -33,3 +60,529 @@
 /// This is a raw pointer and should be used with care. The caller must ensure
 /// that the address points to a valid Device Tree Blob in memory.
 pub type DeviceTreeAddress = *const u8;
+
+/// Read-only view of a flattened device tree blob
+///
+/// Only walks the structure block, which is enough to look up nodes and
+/// their properties before the kernel has an allocator.
+#[derive(Clone, Copy,)]
+pub struct Fdt<'a,> {
+	structure: &'a [u8],
+	strings:   &'a [u8],
+}
+
+impl<'a,> Fdt<'a,> {
+	pub const MAGIC: u32 = 0xd00d_feed;
+
+	/// Reads the blob at `addr`. `None` if `addr` is null or the header is
+	/// invalid
+	///
+	/// # Safety
+	///
+	/// `addr` must be null or point to a blob of `totalsize` readable bytes
+	/// which stays valid for `'a`
+	pub unsafe fn from_addr(addr: DeviceTreeAddress,) -> Option<Self,> {
+		if addr.is_null() {
+			return None;
+		}
+		let header = unsafe { core::slice::from_raw_parts(addr, 8,) };
+		if be32(header, 0,)? != Self::MAGIC {
+			return None;
+		}
+		let total_size = be32(header, 4,)? as usize;
+		Self::new(unsafe { core::slice::from_raw_parts(addr, total_size,) },)
+	}
+
+	/// `None` if `blob` does not start with a valid header
+	pub fn new(blob: &'a [u8],) -> Option<Self,> {
+		if be32(blob, 0,)? != Self::MAGIC {
+			return None;
+		}
+		let structure = be32(blob, 8,)? as usize;
+		let strings = be32(blob, 12,)? as usize;
+		let strings_size = be32(blob, 32,)? as usize;
+		let structure_size = be32(blob, 36,)? as usize;
+		Some(Self {
+			structure: blob.get(structure..structure + structure_size,)?,
+			strings:   blob.get(strings..strings + strings_size,)?,
+		},)
+	}
+
+	/// Every node in depth first order, starting with the root
+	pub fn nodes(&self,) -> Nodes<'a,> {
+		Nodes { fdt: *self, cursor: 0, depth: 0, }
+	}
+
+	pub fn root(&self,) -> Option<Node<'a,>,> {
+		self.nodes().next()
+	}
+
+	/// Node at the absolute `path`, such as `/hypervisor` or `/cpus/cpu@0`
+	///
+	/// A path component without a unit address also matches a node with
+	/// one, so `/memory` finds `/memory@40000000`.
+	pub fn find(&self, path: &str,) -> Option<Node<'a,>,> {
+		let components = || path.split('/',).filter(|c| !c.is_empty(),);
+		let count = components().count();
+		// number of leading components matched by the current branch
+		let mut matched = 0;
+		for node in self.nodes() {
+			if node.depth == 0 {
+				if count == 0 {
+					return Some(node,);
+				}
+				continue;
+			}
+			// left the matched branch
+			matched = matched.min(node.depth - 1,);
+			if node.depth != matched + 1 {
+				continue;
+			}
+			if components().nth(matched,).is_some_and(|c| node.is_named(c,),) {
+				matched += 1;
+				if matched == count {
+					return Some(node,);
+				}
+			}
+		}
+		None
+	}
+
+	/// Node whose `phandle` property is `phandle`
+	pub fn by_phandle(&self, phandle: u32,) -> Option<Node<'a,>,> {
+		self.nodes().find(|node| node.phandle() == Some(phandle,),)
+	}
+
+	/// Writes the absolute path of `node`, such as `/cpus/cpu@0`
+	///
+	/// # Errors
+	///
+	/// If `node` is deeper than [`MAX_DEPTH`] or not a node of this tree
+	pub fn write_path(
+		&self,
+		node: &Node<'a,>,
+		out: &mut impl fmt::Write,
+	) -> fmt::Result {
+		if node.depth > MAX_DEPTH {
+			return Err(fmt::Error,);
+		}
+		let mut ancestors = [""; MAX_DEPTH + 1];
+		for n in self.nodes() {
+			if let Some(name,) = ancestors.get_mut(n.depth,) {
+				*name = n.name;
+			}
+			if n.body != node.body {
+				continue;
+			}
+			if n.depth == 0 {
+				return out.write_str("/",);
+			}
+			for name in &ancestors[1..=n.depth] {
+				write!(out, "/{name}")?;
+			}
+			return Ok((),);
+		}
+		Err(fmt::Error,)
+	}
+
+	fn string(&self, offset: usize,) -> Option<&'a str,> {
+		c_str(self.strings.get(offset..,)?,)
+	}
+}
+
+/// Iterator over the nodes of an [`Fdt`]
+pub struct Nodes<'a,> {
+	fdt:    Fdt<'a,>,
+	cursor: usize,
+	depth:  usize,
+}
+
+impl<'a,> Iterator for Nodes<'a,> {
+	type Item = Node<'a,>;
+
+	fn next(&mut self,) -> Option<Self::Item,> {
+		let structure = self.fdt.structure;
+		loop {
+			match be32(structure, self.cursor,)? {
+				token::BEGIN_NODE => {
+					let name = c_str(structure.get(self.cursor + 4..,)?,)?;
+					let body = align4(self.cursor + 4 + name.len() + 1,);
+					let node =
+						Node { fdt: self.fdt, name, depth: self.depth, body, };
+					self.cursor = body;
+					self.depth += 1;
+					return Some(node,);
+				},
+				token::END_NODE => {
+					self.depth = self.depth.checked_sub(1,)?;
+					self.cursor += 4;
+				},
+				token::PROP => {
+					let len = be32(structure, self.cursor + 4,)? as usize;
+					self.cursor = align4(self.cursor + 12 + len,);
+				},
+				token::NOP => self.cursor += 4,
+				_ => return None,
+			}
+		}
+	}
+}
+
+/// Node of an [`Fdt`]
+#[derive(Clone, Copy,)]
+pub struct Node<'a,> {
+	fdt:       Fdt<'a,>,
+	/// name including the unit address, empty for the root
+	pub name:  &'a str,
+	/// `0` for the root
+	pub depth: usize,
+	/// offset of the first token after the name
+	body:      usize,
+}
+
+impl<'a,> Node<'a,> {
+	/// `(name, value)` of each property of this node
+	pub fn properties(&self,) -> impl Iterator<Item = (&'a str, &'a [u8],),> {
+		let fdt = self.fdt;
+		let mut cursor = self.body;
+		core::iter::from_fn(move || {
+			loop {
+				match be32(fdt.structure, cursor,)? {
+					token::PROP => {
+						let len = be32(fdt.structure, cursor + 4,)? as usize;
+						let name = be32(fdt.structure, cursor + 8,)? as usize;
+						let value =
+							fdt.structure.get(cursor + 12..cursor + 12 + len,)?;
+						cursor = align4(cursor + 12 + len,);
+						return Some((fdt.string(name,)?, value,),);
+					},
+					token::NOP => cursor += 4,
+					// properties precede child nodes
+					_ => return None,
+				}
+			}
+		},)
+	}
+
+	/// See [`name_matches`]
+	pub fn is_named(&self, name: &str,) -> bool {
+		name_matches(self.name, name,)
+	}
+
+	pub fn property(&self, name: &str,) -> Option<&'a [u8],> {
+		self.properties().find(|(n, _,)| *n == name,).map(|(_, value,)| value,)
+	}
+
+	/// Entries of the `compatible` string list
+	pub fn compatible(&self,) -> impl Iterator<Item = &'a str,> {
+		strings(self.property("compatible",).unwrap_or_default(),)
+	}
+
+	pub fn is_compatible(&self, compatible: &str,) -> bool {
+		self.compatible().any(|c| c == compatible,)
+	}
+
+	/// Value of the `phandle` property, or of the older `linux,phandle`
+	pub fn phandle(&self,) -> Option<u32,> {
+		let value = self
+			.property("phandle",)
+			.or_else(|| self.property("linux,phandle",),)?;
+		be32(value, 0,)
+	}
+
+	/// This node followed by its descendants in depth first order
+	pub fn subtree(&self,) -> impl Iterator<Item = Node<'a,>,> + use<'a,> {
+		let depth = self.depth;
+		let descendants =
+			Nodes { fdt: self.fdt, cursor: self.body, depth: depth + 1, };
+		core::iter::once(*self,)
+			.chain(descendants.take_while(move |node| node.depth > depth,),)
+	}
+}
+
+/// Tokens of the structure block
+pub mod token {
+	pub const BEGIN_NODE: u32 = 1;
+	pub const END_NODE: u32 = 2;
+	pub const PROP: u32 = 3;
+	pub const NOP: u32 = 4;
+	pub const END: u32 = 9;
+}
+
+/// Big endian `u32` at `offset`, `None` past the end of `bytes`
+pub fn be32(bytes: &[u8], offset: usize,) -> Option<u32,> {
+	let b = bytes.get(offset..offset + 4,)?;
+	Some(u32::from_be_bytes([b[0], b[1], b[2], b[3],],),)
+}
+
+/// Big endian `u64` at `offset`, `None` past the end of `bytes`
+pub fn be64(bytes: &[u8], offset: usize,) -> Option<u64,> {
+	let b = bytes.get(offset..offset + 8,)?;
+	Some(u64::from_be_bytes(b.try_into().ok()?,),)
+}
+
+/// String up to the first NUL, which must exist. `None` if it is not UTF-8
+pub fn c_str(bytes: &[u8],) -> Option<&str,> {
+	let (string, _,) = utf8::split_once_byte(bytes, 0,)?;
+	utf8::from_bytes(string,).ok()
+}
+
+/// Non-empty entries of a string list property. Entries which are not
+/// UTF-8 are skipped
+pub fn strings(value: &[u8],) -> impl Iterator<Item = &str,> {
+	value
+		.split(|b| *b == 0,)
+		.filter(|s| !s.is_empty(),)
+		.filter_map(|s| utf8::from_bytes(s,).ok(),)
+}
+
+/// `true` if the node name `node` is `name`, or `node` without the unit
+/// address is `name` and `name` has none
+pub fn name_matches(node: &str, name: &str,) -> bool {
+	let base_name = node.split('@',).next();
+	node == name || !name.contains('@',) && base_name == Some(name,)
+}
+
+fn align4(offset: usize,) -> usize {
+	offset.next_multiple_of(4,)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	use crate::text::fixed::FixedString;
+
+	/// names of the properties a [`Blob`] may hold
+	const STRINGS: &[u8] = b"#address-cells\0#size-cells\0interrupt-parent\0\
+		phandle\0#interrupt-cells\0ranges\0reg\0interrupts\0clock-frequency\0\
+		compatible\0linux,phandle\0";
+
+	/// writes a structure block or a blob into a buffer
+	pub(crate) struct Blob {
+		pub(crate) bytes: [u8; 1024],
+		pub(crate) len:   usize,
+	}
+
+	impl Blob {
+		pub(crate) fn new() -> Self {
+			Self { bytes: [0; 1024], len: 0, }
+		}
+
+		pub(crate) fn words(&mut self, words: &[u32],) -> &mut Self {
+			for word in words {
+				self.bytes[self.len..self.len + 4]
+					.copy_from_slice(&word.to_be_bytes(),);
+				self.len += 4;
+			}
+			self
+		}
+
+		pub(crate) fn node(&mut self, name: &str,) -> &mut Self {
+			self.words(&[token::BEGIN_NODE,],);
+			self.bytes[self.len..self.len + name.len()]
+				.copy_from_slice(name.as_bytes(),);
+			self.len = align4(self.len + name.len() + 1,);
+			self
+		}
+
+		pub(crate) fn prop(&mut self, name: &str, cells: &[u32],) -> &mut Self {
+			self.header(name, cells.len() * 4,);
+			self.words(cells,)
+		}
+
+		/// property whose value is `value`, such as a string list
+		pub(crate) fn bytes(&mut self, name: &str, value: &[u8],) -> &mut Self {
+			self.header(name, value.len(),);
+			let len = value.len();
+			self.bytes[self.len..self.len + len].copy_from_slice(value,);
+			self.len = align4(self.len + len,);
+			self
+		}
+
+		pub(crate) fn end(&mut self,) -> &mut Self {
+			self.words(&[token::END_NODE,],)
+		}
+
+		/// blob with `self` as its structure block
+		pub(crate) fn finish(&self,) -> Self {
+			let mut blob = Self::new();
+			let structure = 56;
+			let strings = structure + self.len;
+			blob.words(&[
+				Fdt::MAGIC,
+				(strings + STRINGS.len()) as u32,
+				structure as u32,
+				strings as u32,
+				40,
+				17,
+				16,
+				0,
+				STRINGS.len() as u32,
+				self.len as u32,
+				0,
+				0,
+				0,
+				0,
+			],);
+			let end = strings + STRINGS.len();
+			blob.bytes[structure..strings]
+				.copy_from_slice(&self.bytes[..self.len],);
+			blob.bytes[strings..end].copy_from_slice(STRINGS,);
+			blob.len = end;
+			blob
+		}
+
+		fn header(&mut self, name: &str, len: usize,) {
+			let offset = STRINGS
+				.split(|b| *b == 0,)
+				.take_while(|n| *n != name.as_bytes(),)
+				.map(|n| n.len() + 1,)
+				.sum::<usize>();
+			self.words(&[token::PROP, len as u32, offset as u32,],);
+		}
+	}
+
+	/// `/` with two CPUs, memory and a hypervisor node
+	fn sample() -> Blob {
+		let mut s = Blob::new();
+		s.node("",).bytes("compatible", b"linux,dummy-virt\0",);
+		s.node("cpus",).prop("#address-cells", &[1,],);
+		s.node("cpu@0",).prop("reg", &[0,],).prop("phandle", &[8,],).end();
+		s.words(&[token::NOP,],);
+		s.node("cpu@1",)
+			.words(&[token::NOP,],)
+			.prop("reg", &[1,],)
+			.prop("linux,phandle", &[9,],)
+			.end();
+		s.end();
+		s.node("memory@40000000",)
+			.prop("reg", &[0x4000_0000, 0x800_0000,],)
+			.end();
+		s.node("hypervisor",)
+			.bytes("compatible", b"xen,xen-4.19\0xen,xen\0",)
+			.end();
+		s.end();
+		s.finish()
+	}
+
+	fn path(fdt: &Fdt, node: &Node,) -> FixedString<64,> {
+		let mut path = FixedString::new();
+		fdt.write_path(node, &mut path,).unwrap();
+		path
+	}
+
+	#[test]
+	fn test_new() {
+		let blob = sample();
+		assert!(Fdt::new(&blob.bytes[..blob.len],).is_some());
+		assert!(unsafe { Fdt::from_addr(core::ptr::null(),) }.is_none());
+		let fdt = unsafe { Fdt::from_addr(blob.bytes.as_ptr(),) }.unwrap();
+		assert_eq!(fdt.nodes().count(), 6);
+
+		// strings block cut off
+		assert!(Fdt::new(&blob.bytes[..blob.len - 1],).is_none());
+		assert!(Fdt::new(&blob.bytes[..8],).is_none());
+		let mut bad_magic = sample();
+		bad_magic.bytes[0] = 0;
+		assert!(Fdt::new(&bad_magic.bytes[..bad_magic.len],).is_none());
+	}
+
+	#[test]
+	fn test_find() {
+		let blob = sample();
+		let fdt = Fdt::new(&blob.bytes[..blob.len],).unwrap();
+		assert_eq!(fdt.find("/",).unwrap().depth, 0);
+		assert_eq!(fdt.find("/memory",).unwrap().name, "memory@40000000");
+		assert_eq!(fdt.find("/cpus/cpu",).unwrap().name, "cpu@0");
+		let cpu = fdt.find("/cpus/cpu@1",).unwrap();
+		assert_eq!((cpu.name, cpu.depth,), ("cpu@1", 2,));
+		assert!(fdt.find("/cpu@0",).is_none());
+		assert!(fdt.find("/cpus/cpu@2",).is_none());
+		assert!(fdt.find("/memory@0",).is_none());
+
+		assert_eq!(fdt.by_phandle(8,).unwrap().name, "cpu@0");
+		assert_eq!(fdt.by_phandle(9,).unwrap().name, "cpu@1");
+		assert!(fdt.by_phandle(1,).is_none());
+	}
+
+	#[test]
+	fn test_properties() {
+		let blob = sample();
+		let fdt = Fdt::new(&blob.bytes[..blob.len],).unwrap();
+		let cpu = fdt.find("/cpus/cpu@1",).unwrap();
+		let names = ["reg", "linux,phandle",];
+		assert!(cpu.properties().map(|(name, _,)| name,).eq(names,));
+		assert_eq!(cpu.property("reg",), Some(&[0, 0, 0, 1,][..],));
+		assert_eq!(cpu.property("phandle",), None);
+
+		let hypervisor = fdt.find("/hypervisor",).unwrap();
+		let compatible = ["xen,xen-4.19", "xen,xen",];
+		assert!(hypervisor.compatible().eq(compatible,));
+		assert!(hypervisor.is_compatible("xen,xen",));
+		assert!(!hypervisor.is_compatible("xen",));
+		// properties of the children are not the root's
+		let root = fdt.root().unwrap();
+		assert!(root.compatible().eq(["linux,dummy-virt",],));
+		assert_eq!(root.property("reg",), None);
+	}
+
+	#[test]
+	fn test_write_path_and_subtree() {
+		let blob = sample();
+		let fdt = Fdt::new(&blob.bytes[..blob.len],).unwrap();
+		let root = fdt.root().unwrap();
+		assert_eq!(path(&fdt, &root,).as_str(), "/");
+		let cpu = fdt.find("/cpus/cpu@1",).unwrap();
+		assert_eq!(path(&fdt, &cpu,).as_str(), "/cpus/cpu@1");
+
+		let cpus = fdt.find("/cpus",).unwrap();
+		let names = ["cpus", "cpu@0", "cpu@1",];
+		assert!(cpus.subtree().map(|node| node.name,).eq(names,));
+		assert_eq!(root.subtree().count(), 6);
+	}
+
+	#[test]
+	fn test_malformed_structure() {
+		// property longer than the structure block, then no end tokens
+		let mut s = Blob::new();
+		s.node("",).words(&[token::PROP, 64, 0,],);
+		let blob = s.finish();
+		let fdt = Fdt::new(&blob.bytes[..blob.len],).unwrap();
+		let root = fdt.root().unwrap();
+		assert_eq!(root.properties().count(), 0);
+		assert_eq!(fdt.nodes().count(), 1);
+
+		// unknown token and unbalanced end
+		let mut s = Blob::new();
+		s.node("",).end().end().node("orphan",);
+		let blob = s.finish();
+		let fdt = Fdt::new(&blob.bytes[..blob.len],).unwrap();
+		assert_eq!(fdt.nodes().count(), 1);
+		let mut s = Blob::new();
+		s.node("",).words(&[7,],).node("hidden",);
+		let blob = s.finish();
+		let fdt = Fdt::new(&blob.bytes[..blob.len],).unwrap();
+		assert!(fdt.find("/hidden",).is_none());
+	}
+
+	#[test]
+	fn test_decoding() {
+		let cells = [0, 0, 0, 1, 0, 0, 0, 2,];
+		assert_eq!(be32(&cells, 4,), Some(2));
+		assert_eq!(be32(&cells, 5,), None);
+		assert_eq!(be64(&cells, 0,), Some(0x1_0000_0002));
+		assert_eq!(be64(&cells, 1,), None);
+
+		assert_eq!(c_str(b"/soc\0rest",), Some("/soc"));
+		assert_eq!(c_str(b"/soc",), None);
+		// overlong `/`, which a lax decoder may read as a path separator
+		assert_eq!(c_str(b"\xc0\xafsoc\0",), None);
+		let list = b"arm,pl011\0\0\xed\xa0\x80\0arm,primecell\0";
+		assert!(strings(list,).eq(["arm,pl011", "arm,primecell",]));
+
+		assert!(name_matches("uart@9000000", "uart",));
+		assert!(name_matches("uart@9000000", "uart@9000000",));
+		assert!(!name_matches("uart@9000000", "uart@0",));
+		assert!(!name_matches("uart", "uart@9000000",));
+	}
+}
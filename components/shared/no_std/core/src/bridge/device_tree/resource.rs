@@ -0,0 +1,430 @@
+//! # Device Tree Resources
+//!
+//! Typed resources of a device node, so drivers do not decode cells of
+//! properties themselves:
+//!
+//! - [`Node::reg`]: [`MmioRegion`] of an entry of `reg`, translated to a CPU
+//!   address through the `ranges` of the buses above the node
+//! - [`Node::irq`]: [`IrqLine`] of an entry of `interrupts`, with the
+//!   controller named by the nearest `interrupt-parent`
+//! - [`Node::clock_frequency`]: `clock-frequency` in Hz
+//!
+//! Errors are [`DtResourceError`]s pointing at the node and the property,
+//! which [`Fdt::write_error`] writes as a message such as
+//! `/soc/serial@7e201000: reg: no entry 2`.
+//!
+//! `interrupts-extended` and interrupt nexus nodes with `interrupt-map` are
+//! not followed.
+//!
+//! ```rust,ignore
+//! let uart = fdt.find("/soc/serial@7e201000",)?;
+//! let regs = uart.reg(0,)?;
+//! let irq = uart.irq(0,)?.gic_intid();
+//! let clock = uart.clock_frequency()?;
+//! ```
+
+use super::Fdt;
+use super::MAX_DEPTH;
+use super::Node;
+use super::be32;
+use core::fmt;
+use oso_error::OsoError;
+use oso_error::Rslt;
+use oso_error::oso_err;
+use oso_error::parser::DtResourceError;
+
+/// `#address-cells` of a bus without the property
+pub const DEFAULT_ADDRESS_CELLS: usize = 2;
+/// `#size-cells` of a bus without the property
+pub const DEFAULT_SIZE_CELLS: usize = 1;
+/// Most cells of an interrupt specifier [`IrqLine`] holds
+pub const MAX_INTERRUPT_CELLS: usize = 4;
+
+/// Registers of a device, as the CPU addresses them
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct MmioRegion {
+	pub base: u64,
+	pub size: u64,
+}
+
+/// Interrupt of a device
+///
+/// # Fields
+///
+/// * `controller` - phandle of the interrupt controller, which defines what
+///   the cells of the specifier mean
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct IrqLine {
+	pub controller: u32,
+	cells:          [u32; MAX_INTERRUPT_CELLS],
+	len:            usize,
+}
+
+impl IrqLine {
+	/// Cells of the interrupt specifier
+	pub fn specifier(&self,) -> &[u32] {
+		&self.cells[..self.len]
+	}
+
+	/// Interrupt ID of a GIC specifier `<type number flags>`, counting SPIs
+	/// from 32 and PPIs from 16. `None` for other specifiers
+	pub fn gic_intid(&self,) -> Option<u32,> {
+		match self.specifier() {
+			[0, spi, _,] => spi.checked_add(32,),
+			[1, ppi, _,] if *ppi < 16 => Some(ppi + 16,),
+			_ => None,
+		}
+	}
+}
+
+impl<'a,> Node<'a,> {
+	/// Offset of the node in the structure block, by which errors point at
+	/// it
+	pub fn offset(&self,) -> usize {
+		self.body
+	}
+
+	/// Entry `index` of `reg`, with its address translated through the
+	/// `ranges` of every bus up to the root
+	///
+	/// # Errors
+	///
+	/// - [`DtResourceError::Missing`] if the node has no `reg`, or a bus
+	///   has no `ranges`
+	/// - [`DtResourceError::Malformed`] if the cells of `reg` or `ranges`
+	///   do not match `#address-cells` and `#size-cells`
+	/// - [`DtResourceError::OutOfRange`] if `reg` has no entry `index`
+	/// - [`DtResourceError::Untranslatable`] if no range of a bus holds the
+	///   address
+	pub fn reg(&self, index: usize,) -> Rslt<MmioRegion, DtResourceError,> {
+		let lineage = self.lineage()?;
+		let parent = self.depth.checked_sub(1,).and_then(|d| lineage[d],);
+		let (address_cells, size_cells,) = match parent {
+			Some(parent,) => (parent.address_cells()?, parent.size_cells()?,),
+			None => (DEFAULT_ADDRESS_CELLS, DEFAULT_SIZE_CELLS,),
+		};
+		let reg = self.require("reg",)?;
+		let [base, size,] =
+			self.entry("reg", reg, index, [address_cells, size_cells,],)?;
+
+		let mut base = base;
+		for level in (1..self.depth).rev() {
+			let (bus, above,) = (lineage[level], lineage[level - 1],);
+			let (Some(bus,), Some(above,),) = (bus, above,) else {
+				return Err(oso_err!(DtResourceError::Unknown),);
+			};
+			base = bus.translate(&above, base,)?;
+		}
+		Ok(MmioRegion { base, size, },)
+	}
+
+	/// Entry `index` of `interrupts`
+	///
+	/// # Errors
+	///
+	/// - [`DtResourceError::Missing`] if the node has no `interrupts`, no
+	///   node up to the root has `interrupt-parent`, or the controller has no
+	///   `#interrupt-cells`
+	/// - [`DtResourceError::Malformed`] if `interrupt-parent` refers to no
+	///   node, or the cells of `interrupts` do not match `#interrupt-cells`
+	/// - [`DtResourceError::OutOfRange`] if `interrupts` has no entry `index`
+	pub fn irq(&self, index: usize,) -> Rslt<IrqLine, DtResourceError,> {
+		let interrupts = self.require("interrupts",)?;
+		let lineage = self.lineage()?;
+		let holder = lineage[..=self.depth]
+			.iter()
+			.rev()
+			.flatten()
+			.find(|node| node.property("interrupt-parent",).is_some(),);
+		let Some(holder,) = holder else {
+			return Err(self.missing("interrupt-parent",),);
+		};
+		let controller = holder.cell("interrupt-parent",)?;
+		let Some(intc,) = self.fdt.by_phandle(controller,) else {
+			return Err(holder.malformed("interrupt-parent",),);
+		};
+		let len = intc.cell("#interrupt-cells",)? as usize;
+		if !(1..=MAX_INTERRUPT_CELLS).contains(&len,) {
+			return Err(intc.malformed("#interrupt-cells",),);
+		}
+
+		let stride = len * 4;
+		if interrupts.len() % stride != 0 {
+			return Err(self.malformed("interrupts",),);
+		}
+		let Some(entry,) = interrupts.chunks(stride,).nth(index,) else {
+			let (node, property,) = (self.body, "interrupts",);
+			return Err(oso_err!(DtResourceError::OutOfRange {
+				node,
+				property,
+				index
+			}),);
+		};
+		let mut cells = [0; MAX_INTERRUPT_CELLS];
+		for (i, cell,) in cells[..len].iter_mut().enumerate() {
+			*cell = be32(entry, i * 4,).unwrap_or_default();
+		}
+		Ok(IrqLine { controller, cells, len, },)
+	}
+
+	/// `clock-frequency` in Hz, of one or two cells
+	///
+	/// # Errors
+	///
+	/// - [`DtResourceError::Missing`] if the node has no `clock-frequency`
+	/// - [`DtResourceError::Malformed`] if it is neither one nor two cells
+	pub fn clock_frequency(&self,) -> Rslt<u64, DtResourceError,> {
+		let value = self.require("clock-frequency",)?;
+		match value.len() {
+			4 | 8 => Ok(cells(value,),),
+			_ => Err(self.malformed("clock-frequency",),),
+		}
+	}
+
+	/// Nodes from the root down to this one, by depth
+	fn lineage(
+		&self,
+	) -> Rslt<[Option<Node<'a,>,>; MAX_DEPTH + 1], DtResourceError,> {
+		let mut lineage = [None; MAX_DEPTH + 1];
+		for node in self.fdt.nodes() {
+			let Some(slot,) = lineage.get_mut(node.depth,) else {
+				continue;
+			};
+			*slot = Some(node,);
+			if node.body == self.body {
+				return Ok(lineage,);
+			}
+		}
+		Err(oso_err!(DtResourceError::Unknown),)
+	}
+
+	/// Address of the parent bus of `base`, translated through `ranges` of
+	/// this bus node, whose parent is `above`
+	fn translate(
+		&self,
+		above: &Node<'a,>,
+		base: u64,
+	) -> Rslt<u64, DtResourceError,> {
+		let ranges = self.require("ranges",)?;
+		if ranges.is_empty() {
+			return Ok(base,);
+		}
+		let child = self.address_cells()?;
+		let size = self.size_cells()?;
+		let parent = above.address_cells()?;
+		let count = ranges.len() / ((child + parent + size) * 4).max(1,);
+		for i in 0..count {
+			let [child_base, parent_base, len,] =
+				self.entry("ranges", ranges, i, [child, parent, size,],)?;
+			if base.wrapping_sub(child_base,) < len {
+				return Ok(parent_base + (base - child_base),);
+			}
+		}
+		let (node, property,) = (self.body, "ranges",);
+		Err(oso_err!(DtResourceError::Untranslatable { node, property }),)
+	}
+
+	/// Entry `index` of `value`, whose entries are numbers of `widths`
+	/// cells each
+	fn entry<const N: usize,>(
+		&self,
+		property: &'static str,
+		value: &[u8],
+		index: usize,
+		widths: [usize; N],
+	) -> Rslt<[u64; N], DtResourceError,> {
+		let stride: usize = widths.iter().sum::<usize>() * 4;
+		let too_wide = widths.iter().any(|w| *w > 2,);
+		if stride == 0 || !value.len().is_multiple_of(stride,) || too_wide {
+			return Err(self.malformed(property,),);
+		}
+		let Some(mut entry,) = value.chunks(stride,).nth(index,) else {
+			let node = self.body;
+			return Err(oso_err!(DtResourceError::OutOfRange {
+				node,
+				property,
+				index
+			}),);
+		};
+		let mut numbers = [0; N];
+		for (number, width,) in numbers.iter_mut().zip(widths,) {
+			let (head, rest,) = entry.split_at(width * 4,);
+			*number = cells(head,);
+			entry = rest;
+		}
+		Ok(numbers,)
+	}
+
+	fn address_cells(&self,) -> Rslt<usize, DtResourceError,> {
+		self.cells_or("#address-cells", DEFAULT_ADDRESS_CELLS,)
+	}
+
+	fn size_cells(&self,) -> Rslt<usize, DtResourceError,> {
+		self.cells_or("#size-cells", DEFAULT_SIZE_CELLS,)
+	}
+
+	/// Cell count of `property`, `default` without it
+	fn cells_or(
+		&self,
+		property: &'static str,
+		default: usize,
+	) -> Rslt<usize, DtResourceError,> {
+		match self.property(property,) {
+			Some(_,) => Ok(self.cell(property,)? as usize,),
+			None => Ok(default,),
+		}
+	}
+
+	/// Value of `property` of exactly one cell
+	fn cell(&self, property: &'static str,) -> Rslt<u32, DtResourceError,> {
+		let value = self.require(property,)?;
+		match value.len() {
+			4 => Ok(cells(value,) as u32,),
+			_ => Err(self.malformed(property,),),
+		}
+	}
+
+	fn require(
+		&self,
+		property: &'static str,
+	) -> Rslt<&'a [u8], DtResourceError,> {
+		self.property(property,).ok_or_else(|| self.missing(property,),)
+	}
+
+	fn missing(
+		&self,
+		property: &'static str,
+	) -> OsoError<DtResourceError,> {
+		let node = self.body;
+		oso_err!(DtResourceError::Missing { node, property })
+	}
+
+	fn malformed(
+		&self,
+		property: &'static str,
+	) -> OsoError<DtResourceError,> {
+		let node = self.body;
+		oso_err!(DtResourceError::Malformed { node, property })
+	}
+}
+
+impl<'a,> Fdt<'a,> {
+	/// Node at `offset` of the structure block, see [`Node::offset`]
+	pub fn node_at(&self, offset: usize,) -> Option<Node<'a,>,> {
+		self.nodes().find(|node| node.body == offset,)
+	}
+
+	/// Writes `error` with the path of the node it points at, such as
+	/// `/soc/serial@7e201000: reg: no entry 2`
+	pub fn write_error(
+		&self,
+		error: &DtResourceError,
+		out: &mut impl fmt::Write,
+	) -> fmt::Result {
+		let Some((offset, property,),) = error.source() else {
+			return write!(out, "{error:?}");
+		};
+		match self.node_at(offset,) {
+			Some(node,) => self.write_path(&node, out,)?,
+			None => write!(out, "node at {offset:#x}")?,
+		}
+		write!(out, ": {property}: ")?;
+		match error {
+			DtResourceError::Missing { .. } => write!(out, "missing"),
+			DtResourceError::Malformed { .. } => write!(out, "malformed"),
+			DtResourceError::OutOfRange { index, .. } => {
+				write!(out, "no entry {index}")
+			},
+			DtResourceError::Untranslatable { .. } => {
+				write!(out, "address outside of every range")
+			},
+			DtResourceError::Unknown => Ok((),),
+		}
+	}
+}
+
+/// big endian number of one or two cells
+fn cells(value: &[u8],) -> u64 {
+	value.chunks(4,).fold(0, |n, cell| {
+		n << 32 | be32(cell, 0,).unwrap_or_default() as u64
+	},)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::bridge::device_tree::tests::Blob;
+	use crate::text::fixed::FixedString;
+
+	/// `/` with a GIC and a UART behind `/soc`, whose registers the CPU
+	/// sees at `0xfe000000` instead of `0x7e000000`
+	fn sample() -> Blob {
+		let mut s = Blob::new();
+		s.node("",)
+			.prop("#address-cells", &[1,],)
+			.prop("#size-cells", &[1,],)
+			.prop("interrupt-parent", &[1,],);
+		s.node("intc@40041000",)
+			.prop("phandle", &[1,],)
+			.prop("#interrupt-cells", &[3,],)
+			.prop("reg", &[0x4004_1000, 0x1000,],)
+			.end();
+		s.node("soc",)
+			.prop("#address-cells", &[1,],)
+			.prop("#size-cells", &[1,],)
+			.prop("ranges", &[0x7e00_0000, 0xfe00_0000, 0x180_0000,],);
+		s.node("serial@7e201000",)
+			.prop("reg", &[0x7e20_1000, 0x200, 0x7f80_0000, 0x100,],)
+			.prop("interrupts", &[0, 121, 4, 1, 14, 4,],)
+			.prop("clock-frequency", &[48_000_000,],)
+			.end();
+		s.end().end();
+		s.finish()
+	}
+
+	fn describe(fdt: &Fdt, error: &DtResourceError,) -> FixedString<64,> {
+		let mut message = FixedString::new();
+		fdt.write_error(error, &mut message,).unwrap();
+		message
+	}
+
+	#[test]
+	fn test_reg_translated_through_ranges() {
+		let blob = sample();
+		let fdt = Fdt::new(&blob.bytes[..blob.len],).unwrap();
+		let uart = fdt.find("/soc/serial",).unwrap();
+		let regs = MmioRegion { base: 0xfe20_1000, size: 0x200, };
+		assert_eq!(uart.reg(0,).unwrap(), regs);
+		let intc = fdt.find("/intc",).unwrap();
+		let regs = MmioRegion { base: 0x4004_1000, size: 0x1000, };
+		assert_eq!(intc.reg(0,).unwrap(), regs);
+
+		let error = uart.reg(1,).unwrap_err().desc.unwrap();
+		let message = describe(&fdt, &error,);
+		let expected = "/soc: ranges: address outside of every range";
+		assert_eq!(message.as_str(), expected);
+		let error = uart.reg(2,).unwrap_err().desc.unwrap();
+		let message = describe(&fdt, &error,);
+		assert_eq!(message.as_str(), "/soc/serial@7e201000: reg: no entry 2");
+	}
+
+	#[test]
+	fn test_irq_and_clock() {
+		let blob = sample();
+		let fdt = Fdt::new(&blob.bytes[..blob.len],).unwrap();
+		let uart = fdt.find("/soc/serial",).unwrap();
+		let irq = uart.irq(0,).unwrap();
+		assert_eq!((irq.controller, irq.specifier(),), (1, &[0, 121, 4,][..],));
+		assert_eq!(irq.gic_intid(), Some(153,));
+		assert_eq!(uart.irq(1,).unwrap().gic_intid(), Some(30,));
+		assert_eq!(uart.clock_frequency().unwrap(), 48_000_000);
+
+		let soc = fdt.find("/soc",).unwrap();
+		let error = soc.clock_frequency().unwrap_err().desc.unwrap();
+		let node = soc.offset();
+		let property = "clock-frequency";
+		assert_eq!(error, DtResourceError::Missing { node, property });
+		let message = describe(&fdt, &error,);
+		assert_eq!(message.as_str(), "/soc: clock-frequency: missing");
+	}
+}
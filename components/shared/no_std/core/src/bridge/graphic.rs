@@ -85,6 +85,8 @@
 //! - Memory access must respect the stride to avoid buffer overruns
 //! - Concurrent access to framebuffer memory should be synchronized
 
+use oso_proc_macro::BridgeLayout;
+
 /// Represents the pixel format configuration for a framebuffer
 ///
 /// This enum defines the different pixel formats that can be used when
@@ -146,7 +148,8 @@
 /// - **BltOnly**: Optimized for bulk operations, limited individual pixel
 ///   access
 #[repr(C)]
-#[derive(Debug, PartialEq, Eq, Clone, Copy,)]
+#[derive(BridgeLayout, Debug, PartialEq, Eq, Clone, Copy,)]
+#[layout(size = 4)]
 pub enum PixelFormatConf {
 	/// Red, Green, Blue color format
 	///
@@ -329,20 +332,27 @@ impl PixelFormatConf {
 /// - Memory access respects the `stride` to avoid buffer overruns
 /// - Concurrent access to framebuffer memory is properly synchronized
 /// - The memory region remains valid for the lifetime of the configuration
-#[derive(Debug,)]
+#[derive(BridgeLayout, Debug,)]
 #[repr(C)]
+#[layout(size = 48)]
 pub struct FrameBufConf {
 	/// The pixel format used by the framebuffer
+	#[layout(offset = 0)]
 	pub pixel_format: PixelFormatConf,
 	/// Pointer to the start of the framebuffer memory
+	#[layout(offset = 8)]
 	pub base:         *mut u8,
 	/// Total size of the framebuffer in bytes
+	#[layout(offset = 16)]
 	pub size:         usize,
 	/// Width of the display in pixels
+	#[layout(offset = 24)]
 	pub width:        usize,
 	/// Height of the display in pixels
+	#[layout(offset = 32)]
 	pub height:       usize,
 	/// Number of bytes per row (may include padding)
+	#[layout(offset = 40)]
 	pub stride:       usize,
 }
 
@@ -0,0 +1,82 @@
+//! # Panic Policy
+//!
+//! What the loader and the kernel do after reporting a panic. A board left
+//! alone in a rack is better rebooted, while a board on the desk is better
+//! halted so the message stays on screen, and a board without a display or
+//! serial cable shows the panic by blinking its LED. A test run under QEMU
+//! ends QEMU with a failure status.
+//!
+//! The kernel reads the policy from `panic=<policy>` of its command line,
+//! the loader from `policy` in the `[panic]` section of its configuration.
+//! Both halt unless told otherwise.
+//!
+//! ```rust
+//! use oso_no_std_shared::bridge::panic_policy::PanicPolicy;
+//!
+//! assert_eq!(PanicPolicy::parse("reboot"), Some(PanicPolicy::Reboot));
+//! assert_eq!(PanicPolicy::default().as_str(), "halt");
+//! ```
+
+/// What to do after a panic is reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default,)]
+#[repr(u8)]
+pub enum PanicPolicy {
+	/// Waits for events forever, keeping the message on screen
+	#[default]
+	Halt   = 0,
+	/// Resets the system
+	Reboot = 1,
+	/// Spins blinking an LED, falling back to [`PanicPolicy::Halt`] where
+	/// there is no LED
+	Blink  = 2,
+	/// Exits QEMU with a failure status, so a test run ends at once. Not
+	/// supported by the loader, which halts instead
+	Exit   = 3,
+}
+
+impl PanicPolicy {
+	/// Every policy, in the order of their values
+	pub const ALL: [Self; 4] =
+		[Self::Halt, Self::Reboot, Self::Blink, Self::Exit,];
+
+	/// Policy named `name`, as written by [`PanicPolicy::as_str`]
+	pub fn parse(name: &str,) -> Option<Self,> {
+		Self::ALL.into_iter().find(|policy| policy.as_str() == name,)
+	}
+
+	/// Policy of the value `value`, as stored with `as u8`
+	pub const fn from_u8(value: u8,) -> Option<Self,> {
+		match value {
+			0 => Some(Self::Halt,),
+			1 => Some(Self::Reboot,),
+			2 => Some(Self::Blink,),
+			3 => Some(Self::Exit,),
+			_ => None,
+		}
+	}
+
+	pub const fn as_str(self,) -> &'static str {
+		match self {
+			Self::Halt => "halt",
+			Self::Reboot => "reboot",
+			Self::Blink => "blink",
+			Self::Exit => "exit",
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_round_trip() {
+		for policy in PanicPolicy::ALL {
+			assert_eq!(PanicPolicy::parse(policy.as_str(),), Some(policy));
+			assert_eq!(PanicPolicy::from_u8(policy as u8,), Some(policy));
+		}
+		assert_eq!(PanicPolicy::parse("Reboot",), None);
+		assert_eq!(PanicPolicy::parse("",), None);
+		assert_eq!(PanicPolicy::from_u8(4,), None);
+	}
+}
@@ -0,0 +1,375 @@
+//! # Version Handshake
+//!
+//! The kernel carries a [`VersionNote`] in the ELF note section
+//! [`NOTE_SECTION`]. Before jumping to the kernel, the loader finds it with
+//! [`find`] and asks [`KernelVersion::check`] whether the pair may boot, so a
+//! loader and a kernel of builds which disagree on the bridge are refused
+//! with a reason instead of misreading the handoff.
+//!
+//! ## Compatibility
+//!
+//! A pair is compatible when all of these hold:
+//!
+//! - Both are built against the same [`BRIDGE_ABI`]
+//! - The kernel version lies in the range the loader accepts
+//! - The loader version lies in the range the kernel declares in its note
+//!
+//! Versions are those of the crates, taken from `CARGO_PKG_VERSION` with
+//! [`Version::from_pkg`]. Ranges are usually [`VersionRange::caret`], which
+//! accepts the versions cargo treats as compatible with a `^` requirement.
+//!
+//! ```rust
+//! use oso_no_std_shared::bridge::version::BRIDGE_ABI;
+//! use oso_no_std_shared::bridge::version::Compatibility;
+//! use oso_no_std_shared::bridge::version::KernelVersion;
+//! use oso_no_std_shared::bridge::version::Version;
+//! use oso_no_std_shared::bridge::version::VersionNote;
+//! use oso_no_std_shared::bridge::version::VersionRange;
+//! use oso_no_std_shared::bridge::version::find;
+//!
+//! let note = VersionNote::new(KernelVersion {
+//! 	version: Version::from_pkg("0.3.1",),
+//! 	abi:     BRIDGE_ABI,
+//! 	loader:  VersionRange::caret(Version::new(0, 2, 0,),),
+//! },);
+//! let kernel = find(&note.to_bytes(),).unwrap();
+//! assert_eq!(kernel.version.to_string(), "0.3.1");
+//!
+//! let accepted = VersionRange::caret(Version::new(0, 3, 0,),);
+//! let loader = Version::new(0, 2, 4,);
+//! assert!(kernel.check(loader, accepted,).is_compatible());
+//! let loader = Version::new(0, 3, 0,);
+//! let refused = Compatibility::LoaderOutOfRange;
+//! assert_eq!(kernel.check(loader, accepted,), refused);
+//! ```
+
+use core::fmt;
+use oso_proc_macro::BridgeLayout;
+
+/// Name of the section the kernel places its [`VersionNote`] in
+pub const NOTE_SECTION: &str = ".note.oso.version";
+/// Owner name of the note, padded to 4 bytes
+pub const NOTE_NAME: [u8; 4] = *b"OSO\0";
+/// Type of the note among the notes of [`NOTE_NAME`]
+pub const NOTE_TYPE: u32 = 1;
+/// Revision of the types handed from the loader to the kernel. Bump it when
+/// a change to [`super::boot_info`] breaks the layout or the meaning of a
+/// field, and update the layout pinned to it in the tests
+pub const BRIDGE_ABI: u32 = 6;
+
+/// Semver-style version, without pre-release and build metadata
+#[repr(C)]
+#[derive(
+	BridgeLayout, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+#[layout(size = 12)]
+pub struct Version {
+	#[layout(offset = 0)]
+	pub major: u32,
+	#[layout(offset = 4)]
+	pub minor: u32,
+	#[layout(offset = 8)]
+	pub patch: u32,
+}
+
+impl Version {
+	pub const fn new(major: u32, minor: u32, patch: u32,) -> Self {
+		Self { major, minor, patch, }
+	}
+
+	/// Parses `major.minor.patch`, ignoring a `-pre` or `+build` suffix
+	pub const fn parse(s: &str,) -> Option<Self,> {
+		let bytes = s.as_bytes();
+		let mut parts = [0u32; 3];
+		let mut part = 0;
+		let mut digits = 0;
+		let mut i = 0;
+		while i < bytes.len() {
+			match bytes[i] {
+				b @ b'0'..=b'9' => {
+					let Some(n,) = parts[part].checked_mul(10,) else {
+						return None;
+					};
+					let Some(n,) = n.checked_add((b - b'0') as u32,) else {
+						return None;
+					};
+					parts[part] = n;
+					digits += 1;
+				},
+				b'.' if part < 2 && digits != 0 => {
+					part += 1;
+					digits = 0;
+				},
+				b'-' | b'+' => break,
+				_ => return None,
+			}
+			i += 1;
+		}
+		if part != 2 || digits == 0 {
+			return None;
+		}
+		Some(Self::new(parts[0], parts[1], parts[2],),)
+	}
+
+	/// Version of a crate, given `env!("CARGO_PKG_VERSION")`
+	///
+	/// # Panics
+	///
+	/// Panics if `pkg` is not a version. In constants, this fails the build
+	pub const fn from_pkg(pkg: &str,) -> Self {
+		match Self::parse(pkg,) {
+			Some(version,) => version,
+			None => panic!("package version is not major.minor.patch"),
+		}
+	}
+
+	/// First version which is not compatible with `self`: the next major
+	/// version, or the next minor version of `0.x` releases
+	pub const fn next_breaking(&self,) -> Self {
+		match (self.major, self.minor,) {
+			(0, 0,) => Self::new(0, 0, self.patch + 1,),
+			(0, minor,) => Self::new(0, minor + 1, 0,),
+			(major, _,) => Self::new(major + 1, 0, 0,),
+		}
+	}
+
+	fn from_bytes(bytes: &[u8],) -> Option<Self,> {
+		Some(Self::new(word(bytes, 0,)?, word(bytes, 4,)?, word(bytes, 8,)?,),)
+	}
+
+	fn write_bytes(&self, bytes: &mut [u8],) {
+		bytes[0..4].copy_from_slice(&self.major.to_le_bytes(),);
+		bytes[4..8].copy_from_slice(&self.minor.to_le_bytes(),);
+		bytes[8..12].copy_from_slice(&self.patch.to_le_bytes(),);
+	}
+}
+
+impl fmt::Display for Version {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+	}
+}
+
+/// Versions from `min` up to but excluding `max`
+#[repr(C)]
+#[derive(BridgeLayout, Debug, Clone, Copy, PartialEq, Eq,)]
+#[layout(size = 24)]
+pub struct VersionRange {
+	#[layout(offset = 0)]
+	pub min: Version,
+	#[layout(offset = 12)]
+	pub max: Version,
+}
+
+impl VersionRange {
+	pub const fn new(min: Version, max: Version,) -> Self {
+		Self { min, max, }
+	}
+
+	/// Versions compatible with `version` in the sense of cargo's `^version`
+	pub const fn caret(version: Version,) -> Self {
+		Self::new(version, version.next_breaking(),)
+	}
+
+	pub fn contains(&self, version: Version,) -> bool {
+		self.min <= version && version < self.max
+	}
+}
+
+impl fmt::Display for VersionRange {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		write!(f, ">={}, <{}", self.min, self.max)
+	}
+}
+
+/// Descriptor of the kernel's [`VersionNote`]
+///
+/// # Fields
+///
+/// * `version` - Version of the kernel
+/// * `abi` - [`BRIDGE_ABI`] the kernel was built against
+/// * `loader` - Loader versions the kernel accepts
+#[repr(C)]
+#[derive(BridgeLayout, Debug, Clone, Copy, PartialEq, Eq,)]
+#[layout(size = 40)]
+pub struct KernelVersion {
+	#[layout(offset = 0)]
+	pub version: Version,
+	#[layout(offset = 12)]
+	pub abi:     u32,
+	#[layout(offset = 16)]
+	pub loader:  VersionRange,
+}
+
+impl KernelVersion {
+	/// Size of the descriptor in the note
+	pub const SIZE: usize = 40;
+
+	/// Decides whether a loader of version `loader`, which accepts kernels in
+	/// `kernels`, may boot this kernel
+	pub fn check(
+		&self,
+		loader: Version,
+		kernels: VersionRange,
+	) -> Compatibility {
+		if self.abi != BRIDGE_ABI {
+			Compatibility::AbiMismatch
+		} else if !kernels.contains(self.version,) {
+			Compatibility::KernelOutOfRange
+		} else if !self.loader.contains(loader,) {
+			Compatibility::LoaderOutOfRange
+		} else {
+			Compatibility::Compatible
+		}
+	}
+
+	/// Reads the descriptor of a note. The note is little endian
+	pub fn from_bytes(bytes: &[u8],) -> Option<Self,> {
+		if bytes.len() != Self::SIZE {
+			return None;
+		}
+		Some(Self {
+			version: Version::from_bytes(&bytes[0..12],)?,
+			abi:     word(bytes, 12,)?,
+			loader:  VersionRange::new(
+				Version::from_bytes(&bytes[16..28],)?,
+				Version::from_bytes(&bytes[28..40],)?,
+			),
+		},)
+	}
+
+	pub fn to_bytes(&self,) -> [u8; Self::SIZE] {
+		let mut bytes = [0; Self::SIZE];
+		self.version.write_bytes(&mut bytes[0..12],);
+		bytes[12..16].copy_from_slice(&self.abi.to_le_bytes(),);
+		self.loader.min.write_bytes(&mut bytes[16..28],);
+		self.loader.max.write_bytes(&mut bytes[28..40],);
+		bytes
+	}
+}
+
+/// ELF note holding a [`KernelVersion`]
+///
+/// The kernel places one in [`NOTE_SECTION`]:
+///
+/// ```rust,ignore
+/// #[used]
+/// #[unsafe(link_section = ".note.oso.version")]
+/// static VERSION: VersionNote = VersionNote::new(KernelVersion { .. },);
+/// ```
+#[repr(C)]
+#[derive(BridgeLayout, Debug, Clone, Copy, PartialEq, Eq,)]
+#[layout(size = 56)]
+pub struct VersionNote {
+	#[layout(offset = 0)]
+	pub name_size: u32,
+	#[layout(offset = 4)]
+	pub desc_size: u32,
+	#[layout(offset = 8)]
+	pub ty:        u32,
+	#[layout(offset = 12)]
+	pub name:      [u8; 4],
+	#[layout(offset = 16)]
+	pub desc:      KernelVersion,
+}
+
+impl VersionNote {
+	pub const fn new(desc: KernelVersion,) -> Self {
+		Self {
+			name_size: NOTE_NAME.len() as u32,
+			desc_size: KernelVersion::SIZE as u32,
+			ty: NOTE_TYPE,
+			name: NOTE_NAME,
+			desc,
+		}
+	}
+
+	/// The note as it is stored in a little endian ELF file
+	pub fn to_bytes(&self,) -> [u8; 56] {
+		let mut bytes = [0; 56];
+		bytes[0..4].copy_from_slice(&self.name_size.to_le_bytes(),);
+		bytes[4..8].copy_from_slice(&self.desc_size.to_le_bytes(),);
+		bytes[8..12].copy_from_slice(&self.ty.to_le_bytes(),);
+		bytes[12..16].copy_from_slice(&self.name,);
+		bytes[16..].copy_from_slice(&self.desc.to_bytes(),);
+		bytes
+	}
+}
+
+/// Result of [`KernelVersion::check`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum Compatibility {
+	Compatible,
+	/// Loader and kernel are built against different [`BRIDGE_ABI`]s
+	AbiMismatch,
+	/// The loader does not accept the kernel version
+	KernelOutOfRange,
+	/// The kernel does not accept the loader version
+	LoaderOutOfRange,
+}
+
+impl Compatibility {
+	pub fn is_compatible(&self,) -> bool {
+		*self == Self::Compatible
+	}
+
+	/// Short description, e.g. for the boot log
+	pub fn as_str(&self,) -> &'static str {
+		match self {
+			Self::Compatible => "compatible",
+			Self::AbiMismatch => "bridge abi differs",
+			Self::KernelOutOfRange => "loader does not accept kernel version",
+			Self::LoaderOutOfRange => "kernel does not accept loader version",
+		}
+	}
+}
+
+/// Finds the [`KernelVersion`] among the notes of a `PT_NOTE` segment
+///
+/// Returns `None` if no note of [`NOTE_NAME`] and [`NOTE_TYPE`] is found, or
+/// the notes are truncated before it.
+pub fn find(notes: &[u8],) -> Option<KernelVersion,> {
+	let mut rest = notes;
+	while rest.len() >= 12 {
+		let name_size = word(rest, 0,)? as usize;
+		let desc_size = word(rest, 4,)? as usize;
+		let ty = word(rest, 8,)?;
+		let desc_start = 12 + name_size.next_multiple_of(4,);
+		let desc_end = desc_start.checked_add(desc_size,)?;
+		let name = rest.get(12..12 + name_size,)?;
+		let desc = rest.get(desc_start..desc_end,)?;
+		if name == NOTE_NAME && ty == NOTE_TYPE {
+			return KernelVersion::from_bytes(desc,);
+		}
+		rest = rest.get(desc_end.next_multiple_of(4,)..,).unwrap_or_default();
+	}
+	None
+}
+
+/// little endian `u32` at `offset` of `bytes`
+fn word(bytes: &[u8], offset: usize,) -> Option<u32,> {
+	let word = bytes.get(offset..offset + 4,)?;
+	Some(u32::from_le_bytes(word.try_into().ok()?,),)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::bridge::boot_info::BootInfo;
+	use core::mem::offset_of;
+
+	#[test]
+	fn test_layout_is_pinned_to_abi() {
+		// `runtime` made `runtime_services` possibly physical, and grew
+		// `BootInfo` from 88 to 96 bytes. 6 added `FirmwareService::FreePages`
+		let layout = (
+			size_of::<BootInfo,>(),
+			offset_of!(BootInfo, runtime),
+			offset_of!(BootInfo, firmware_calls),
+			offset_of!(BootInfo, milestones),
+		);
+		let message = "bump BRIDGE_ABI when the layout of BootInfo changes";
+		let pinned = (6, (152, 88, 96, 120,),);
+		assert_eq!((BRIDGE_ABI, layout,), pinned, "{message}");
+	}
+}
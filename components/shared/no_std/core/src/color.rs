@@ -0,0 +1,313 @@
+//! # Color Module
+//!
+//! This module provides [`Color`] and [`Style`], shared by every consumer of
+//! colors: the framebuffer, the consoles of the loader and the kernel, and
+//! the Markdown renderer.
+//!
+//! ## Parsing
+//!
+//! [`Color::parse`] accepts `#rrggbb`, `#rgb` and the names of the named
+//! constants such as `light_blue`, ignoring case. It is a `const fn`, so
+//! [`color!`](crate::color) validates a literal at compile time:
+//!
+//! ```rust
+//! use oso_no_std_shared::bridge::graphic::PixelFormatConf;
+//! use oso_no_std_shared::color;
+//! use oso_no_std_shared::color::Color;
+//! use oso_no_std_shared::color::Style;
+//! use oso_no_std_shared::text::console::ConsoleColor;
+//!
+//! const ACCENT: Color = color!("#fedcba");
+//! assert_eq!(ACCENT, Color::rgb(0xfe, 0xdc, 0xba,));
+//! assert_eq!(color!("#f80"), Color::rgb(0xff, 0x88, 0x00,));
+//! assert_eq!(Color::parse("Light_Blue"), Some(Color::LIGHT_BLUE));
+//! assert_eq!(Color::parse("#12345"), None);
+//!
+//! let bgr = ACCENT.to_pixel(PixelFormatConf::Bgr,);
+//! assert_eq!(bgr, Some([0xba, 0xdc, 0xfe, 0,]));
+//! assert_eq!(ACCENT.nearest_console(), ConsoleColor::White);
+//!
+//! let warning = Style::new(Color::BROWN, Color::BLACK,).bold();
+//! let (foreground, _,) = warning.console_colors();
+//! assert_eq!(foreground, ConsoleColor::Yellow);
+//! ```
+//!
+//! An invalid literal fails the build:
+//!
+//! ```rust,compile_fail
+//! let color = oso_no_std_shared::color!("#abcdeg");
+//! ```
+
+use crate::bridge::graphic::PixelFormatConf;
+use crate::text::console::ConsoleColor;
+use core::fmt;
+
+/// Color validated at compile time
+///
+/// ```rust,ignore
+/// fill_rectangle(&(0, 0,), &(100, 100,), &color!("#abcdef"),)?;
+/// ```
+#[macro_export]
+macro_rules! color {
+	($color:literal) => {
+		const {
+			match $crate::color::Color::parse($color,) {
+				Some(color,) => color,
+				None => panic!(concat!("invalid color `", $color, "`")),
+			}
+		}
+	};
+}
+
+/// 24 bit RGB color
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash,)]
+pub struct Color {
+	pub red:   u8,
+	pub green: u8,
+	pub blue:  u8,
+}
+
+impl Color {
+	pub const BLACK: Self = Self::rgb(0x00, 0x00, 0x00,);
+	pub const BLUE: Self = Self::rgb(0x00, 0x00, 0xaa,);
+	pub const BROWN: Self = Self::rgb(0xaa, 0x55, 0x00,);
+	pub const CYAN: Self = Self::rgb(0x00, 0xaa, 0xaa,);
+	pub const DARK_GRAY: Self = Self::rgb(0x55, 0x55, 0x55,);
+	pub const GREEN: Self = Self::rgb(0x00, 0xaa, 0x00,);
+	pub const LIGHT_BLUE: Self = Self::rgb(0x55, 0x55, 0xff,);
+	pub const LIGHT_CYAN: Self = Self::rgb(0x55, 0xff, 0xff,);
+	pub const LIGHT_GRAY: Self = Self::rgb(0xaa, 0xaa, 0xaa,);
+	pub const LIGHT_GREEN: Self = Self::rgb(0x55, 0xff, 0x55,);
+	pub const LIGHT_MAGENTA: Self = Self::rgb(0xff, 0x55, 0xff,);
+	pub const LIGHT_RED: Self = Self::rgb(0xff, 0x55, 0x55,);
+	pub const MAGENTA: Self = Self::rgb(0xaa, 0x00, 0xaa,);
+	pub const RED: Self = Self::rgb(0xaa, 0x00, 0x00,);
+	pub const WHITE: Self = Self::rgb(0xff, 0xff, 0xff,);
+	pub const YELLOW: Self = Self::rgb(0xff, 0xff, 0x55,);
+	/// Colors of [`ConsoleColor`], in its order
+	pub const PALETTE: [Self; 16] = [
+		Self::BLACK,
+		Self::BLUE,
+		Self::GREEN,
+		Self::CYAN,
+		Self::RED,
+		Self::MAGENTA,
+		Self::BROWN,
+		Self::LIGHT_GRAY,
+		Self::DARK_GRAY,
+		Self::LIGHT_BLUE,
+		Self::LIGHT_GREEN,
+		Self::LIGHT_CYAN,
+		Self::LIGHT_RED,
+		Self::LIGHT_MAGENTA,
+		Self::YELLOW,
+		Self::WHITE,
+	];
+	/// Names accepted by [`Self::from_name`], in the order of
+	/// [`Self::PALETTE`]
+	pub const NAMES: [&str; 16] = [
+		"black",
+		"blue",
+		"green",
+		"cyan",
+		"red",
+		"magenta",
+		"brown",
+		"light_gray",
+		"dark_gray",
+		"light_blue",
+		"light_green",
+		"light_cyan",
+		"light_red",
+		"light_magenta",
+		"yellow",
+		"white",
+	];
+
+	pub const fn rgb(red: u8, green: u8, blue: u8,) -> Self {
+		Self { red, green, blue, }
+	}
+
+	/// Parses `#rrggbb`, `#rgb` or a name of [`Self::NAMES`]
+	pub const fn parse(s: &str,) -> Option<Self,> {
+		let (red, green, blue,) = match s.as_bytes() {
+			[b'#', r1, r2, g1, g2, b1, b2,] => (
+				hex_pair(*r1, *r2,),
+				hex_pair(*g1, *g2,),
+				hex_pair(*b1, *b2,),
+			),
+			[b'#', r, g, b,] => {
+				(hex_pair(*r, *r,), hex_pair(*g, *g,), hex_pair(*b, *b,),)
+			},
+			[b'#', ..] => return None,
+			_ => return Self::from_name(s,),
+		};
+		match (red, green, blue,) {
+			(Some(r,), Some(g,), Some(b,),) => Some(Self::rgb(r, g, b,),),
+			_ => None,
+		}
+	}
+
+	/// Named constant called `name`, ignoring case
+	pub const fn from_name(name: &str,) -> Option<Self,> {
+		let mut i = 0;
+		while i < Self::NAMES.len() {
+			if eq_ignore_case(Self::NAMES[i].as_bytes(), name.as_bytes(),) {
+				return Some(Self::PALETTE[i],);
+			}
+			i += 1;
+		}
+		None
+	}
+
+	/// Bytes of a pixel in `format`, `None` for formats without fixed channel
+	/// positions. See [`Self::to_bitmask`] for [`PixelFormatConf::Bitmask`]
+	pub const fn to_pixel(&self, format: PixelFormatConf,) -> Option<[u8; 4],> {
+		let Self { red, green, blue, } = *self;
+		match format {
+			PixelFormatConf::Rgb => Some([red, green, blue, 0,],),
+			PixelFormatConf::Bgr => Some([blue, green, red, 0,],),
+			PixelFormatConf::Bitmask | PixelFormatConf::BltOnly => None,
+		}
+	}
+
+	/// 32 bit pixel with each channel scaled into its mask
+	pub const fn to_bitmask(
+		&self,
+		red_mask: u32,
+		green_mask: u32,
+		blue_mask: u32,
+	) -> u32 {
+		scale(self.red, red_mask,)
+			| scale(self.green, green_mask,)
+			| scale(self.blue, blue_mask,)
+	}
+
+	/// Console color closest to `self`
+	pub const fn nearest_console(&self,) -> ConsoleColor {
+		let mut nearest = 0;
+		let mut best = u32::MAX;
+		let mut i = 0;
+		while i < Self::PALETTE.len() {
+			let d = self.distance(&Self::PALETTE[i],);
+			if d < best {
+				best = d;
+				nearest = i;
+			}
+			i += 1;
+		}
+		ConsoleColor::ALL[nearest]
+	}
+
+	/// squared euclidean distance in RGB space
+	const fn distance(&self, other: &Self,) -> u32 {
+		let r = self.red.abs_diff(other.red,) as u32;
+		let g = self.green.abs_diff(other.green,) as u32;
+		let b = self.blue.abs_diff(other.blue,) as u32;
+		r * r + g * g + b * b
+	}
+}
+
+impl From<(u8, u8, u8,),> for Color {
+	fn from((red, green, blue,): (u8, u8, u8,),) -> Self {
+		Self::rgb(red, green, blue,)
+	}
+}
+
+impl From<ConsoleColor,> for Color {
+	fn from(color: ConsoleColor,) -> Self {
+		Self::PALETTE[color as usize]
+	}
+}
+
+/// Writes `#rrggbb`
+impl fmt::Display for Color {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		write!(f, "#{:02x}{:02x}{:02x}", self.red, self.green, self.blue)
+	}
+}
+
+/// Colors and weight of text
+///
+/// # Fields
+///
+/// * `foreground` - Color of the glyphs
+/// * `background` - Color behind the glyphs
+/// * `bold` - Consoles without bold fonts show the bright variant of the
+///   foreground instead
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash,)]
+pub struct Style {
+	pub foreground: Color,
+	pub background: Color,
+	pub bold:       bool,
+}
+
+impl Style {
+	/// Light gray on black, the style of firmware consoles after reset
+	pub const PLAIN: Self = Self::new(Color::LIGHT_GRAY, Color::BLACK,);
+
+	pub const fn new(foreground: Color, background: Color,) -> Self {
+		Self { foreground, background, bold: false, }
+	}
+
+	/// `self` in bold
+	pub const fn bold(self,) -> Self {
+		Self { bold: true, ..self }
+	}
+
+	/// Nearest console colors, with the bright foreground if bold
+	pub const fn console_colors(&self,) -> (ConsoleColor, ConsoleColor,) {
+		let mut foreground = self.foreground.nearest_console();
+		if self.bold {
+			foreground = foreground.bright();
+		}
+		(foreground, self.background.nearest_console(),)
+	}
+}
+
+impl Default for Style {
+	fn default() -> Self {
+		Self::PLAIN
+	}
+}
+
+/// value of two hex digits
+const fn hex_pair(high: u8, low: u8,) -> Option<u8,> {
+	match (hex_digit(high,), hex_digit(low,),) {
+		(Some(h,), Some(l,),) => Some(h << 4 | l,),
+		_ => None,
+	}
+}
+
+const fn hex_digit(c: u8,) -> Option<u8,> {
+	match c {
+		b'0'..=b'9' => Some(c - b'0',),
+		b'a'..=b'f' => Some(c - b'a' + 10,),
+		b'A'..=b'F' => Some(c - b'A' + 10,),
+		_ => None,
+	}
+}
+
+const fn eq_ignore_case(a: &[u8], b: &[u8],) -> bool {
+	if a.len() != b.len() {
+		return false;
+	}
+	let mut i = 0;
+	while i < a.len() {
+		if !a[i].eq_ignore_ascii_case(&b[i],) {
+			return false;
+		}
+		i += 1;
+	}
+	true
+}
+
+/// `value` scaled to the width of the contiguous `mask` and shifted into it
+const fn scale(value: u8, mask: u32,) -> u32 {
+	if mask == 0 {
+		return 0;
+	}
+	let shift = mask.trailing_zeros();
+	let max = (mask >> shift) as u64;
+	(((value as u64 * max + 127) / 255) as u32) << shift
+}
@@ -6,9 +6,13 @@
 //!
 //! ## Submodules
 //!
+//! - `crc32`: CRC-32 checksums of memory shared between loader and kernel
+//! - `qoi`: Encoder of the QOI image format, for screen captures
 //! - `tree`: Generic tree data structure with traversal and manipulation
 //!   capabilities
 
+pub mod crc32;
 pub mod list;
 pub mod node;
+pub mod qoi;
 pub mod tree;
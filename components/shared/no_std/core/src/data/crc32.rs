@@ -0,0 +1,35 @@
+//! CRC-32 (IEEE, reflected), as used by zip and ethernet
+//!
+//! Computed bitwise, as a table would cost 1 KiB for checksums which run once
+//! per boot.
+
+/// CRC-32 of `bytes`
+pub fn checksum(bytes: &[u8],) -> u32 {
+	!update(!0, bytes,)
+}
+
+/// Updates a CRC-32 with `bytes`. Start with `!0` and invert the result
+pub fn update(mut crc: u32, bytes: &[u8],) -> u32 {
+	for byte in bytes {
+		crc ^= *byte as u32;
+		for _ in 0..8 {
+			let mask = (crc & 1).wrapping_neg();
+			crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+		}
+	}
+	crc
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_checksum() {
+		assert_eq!(checksum(b"123456789"), 0xcbf4_3926);
+		assert_eq!(checksum(b""), 0);
+		// in parts
+		let crc = update(update(!0, b"1234",), b"56789",);
+		assert_eq!(!crc, 0xcbf4_3926);
+	}
+}
@@ -0,0 +1,204 @@
+//! Encoder of the Quite OK Image format (QOI), for screen captures
+//!
+//! [`Encoder`] takes pixels one at a time and passes the encoded bytes on as
+//! they are produced, so an image is encoded without a buffer of its size.
+//! Pixels are red, green, blue and alpha bytes. The format is described at
+//! <https://qoiformat.org/qoi-specification.pdf>.
+//!
+//! ```rust
+//! use oso_no_std_shared::data::qoi::Encoder;
+//! use oso_no_std_shared::data::qoi::Header;
+//!
+//! let mut bytes = [0_u8; 64];
+//! let mut len = 0;
+//! let mut encoder = Encoder::new(Header::rgb(2, 1,), |chunk: &[u8]| {
+//! 	bytes[len..len + chunk.len()].copy_from_slice(chunk,);
+//! 	len += chunk.len();
+//! },);
+//! encoder.push([0xff, 0, 0, 0xff,],);
+//! encoder.push([0xff, 0, 0, 0xff,],);
+//! encoder.finish();
+//! assert_eq!(&bytes[..4], b"qoif");
+//! ```
+
+/// First bytes of every image
+pub const MAGIC: [u8; 4] = *b"qoif";
+/// Bytes of the header
+pub const HEADER_LEN: usize = 14;
+/// Bytes after the last pixel
+pub const END: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1,];
+
+const OP_INDEX: u8 = 0x00;
+const OP_DIFF: u8 = 0x40;
+const OP_LUMA: u8 = 0x80;
+const OP_RUN: u8 = 0xc0;
+const OP_RGB: u8 = 0xfe;
+const OP_RGBA: u8 = 0xff;
+/// Longest run of one `OP_RUN`
+const MAX_RUN: u8 = 62;
+
+/// Size and channels of an image
+///
+/// # Fields
+///
+/// * `channels` - `3` if alpha is always opaque, `4` otherwise. Only
+///   informs decoders, as every pixel is encoded with its alpha
+/// * `colorspace` - `0` for sRGB with linear alpha, `1` for all linear
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct Header {
+	pub width:      u32,
+	pub height:     u32,
+	pub channels:   u8,
+	pub colorspace: u8,
+}
+
+impl Header {
+	/// Opaque sRGB image
+	pub const fn rgb(width: u32, height: u32,) -> Self {
+		Self { width, height, channels: 3, colorspace: 0, }
+	}
+
+	pub const fn to_bytes(&self,) -> [u8; HEADER_LEN] {
+		let [w0, w1, w2, w3,] = self.width.to_be_bytes();
+		let [h0, h1, h2, h3,] = self.height.to_be_bytes();
+		let [m0, m1, m2, m3,] = MAGIC;
+		let (channels, colorspace,) = (self.channels, self.colorspace,);
+		[m0, m1, m2, m3, w0, w1, w2, w3, h0, h1, h2, h3, channels, colorspace,]
+	}
+}
+
+/// Encodes pixels into `out` as they are pushed
+pub struct Encoder<W: FnMut(&[u8],),> {
+	out:   W,
+	/// pixels seen before, by [`hash`]
+	index: [[u8; 4]; 64],
+	prev:  [u8; 4],
+	/// pixels equal to `prev` not yet written
+	run:   u8,
+}
+
+impl<W: FnMut(&[u8],),> Encoder<W,> {
+	/// Writes `header` to `out`. The caller pushes `header.width *
+	/// header.height` pixels, row by row
+	pub fn new(header: Header, mut out: W,) -> Self {
+		out(&header.to_bytes(),);
+		Self { out, index: [[0; 4]; 64], prev: [0, 0, 0, 0xff,], run: 0, }
+	}
+
+	/// Encodes the next pixel
+	pub fn push(&mut self, pixel: [u8; 4],) {
+		if pixel == self.prev {
+			self.run += 1;
+			if self.run == MAX_RUN {
+				self.flush_run();
+			}
+			return;
+		}
+		self.flush_run();
+
+		let slot = hash(pixel,);
+		if self.index[slot] == pixel {
+			(self.out)(&[OP_INDEX | slot as u8],);
+		} else {
+			self.index[slot] = pixel;
+			let [red, green, blue, alpha,] = pixel;
+			let [prev_red, prev_green, prev_blue, prev_alpha,] = self.prev;
+			if alpha == prev_alpha {
+				let dr = red.wrapping_sub(prev_red,) as i8;
+				let dg = green.wrapping_sub(prev_green,) as i8;
+				let db = blue.wrapping_sub(prev_blue,) as i8;
+				let dr_dg = dr.wrapping_sub(dg,);
+				let db_dg = db.wrapping_sub(dg,);
+				let small = |d: i8| (-2..=1).contains(&d,);
+				let luma = |d: i8| (-8..=7).contains(&d,);
+				if small(dr,) && small(dg,) && small(db,) {
+					let diff = ((dr + 2) << 4 | (dg + 2) << 2 | (db + 2)) as u8;
+					(self.out)(&[OP_DIFF | diff],);
+				} else if (-32..=31).contains(&dg,)
+					&& luma(dr_dg,)
+					&& luma(db_dg,)
+				{
+					let second = ((dr_dg + 8) << 4 | (db_dg + 8)) as u8;
+					(self.out)(&[OP_LUMA | (dg + 32) as u8, second,],);
+				} else {
+					(self.out)(&[OP_RGB, red, green, blue,],);
+				}
+			} else {
+				(self.out)(&[OP_RGBA, red, green, blue, alpha,],);
+			}
+		}
+		self.prev = pixel;
+	}
+
+	/// Writes the pending run and [`END`], and returns `out`
+	pub fn finish(mut self,) -> W {
+		self.flush_run();
+		(self.out)(&END,);
+		self.out
+	}
+
+	fn flush_run(&mut self,) {
+		if self.run != 0 {
+			(self.out)(&[OP_RUN | (self.run - 1)],);
+			self.run = 0;
+		}
+	}
+}
+
+/// Slot of `pixel` in the index of seen pixels
+const fn hash([red, green, blue, alpha,]: [u8; 4],) -> usize {
+	let (red, green, blue, alpha,) =
+		(red as usize, green as usize, blue as usize, alpha as usize,);
+	(red * 3 + green * 5 + blue * 7 + alpha * 11) % 64
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// bytes of `pixels` encoded as an image of one row
+	fn encode(pixels: &[[u8; 4]],) -> ([u8; 256], usize,) {
+		let mut bytes = [0; 256];
+		let mut len = 0;
+		let width = pixels.len() as u32;
+		let header = Header { channels: 4, ..Header::rgb(width, 1,) };
+		let mut encoder = Encoder::new(header, |chunk: &[u8]| {
+			bytes[len..len + chunk.len()].copy_from_slice(chunk,);
+			len += chunk.len();
+		},);
+		for &pixel in pixels {
+			encoder.push(pixel,);
+		}
+		let _ = encoder.finish();
+		(bytes, len,)
+	}
+
+	#[test]
+	fn test_encode_every_op() {
+		let (bytes, len,) = encode(&[
+			[1, 0, 255, 255,],
+			[1, 0, 255, 255,],
+			[0, 0, 0, 255,],
+			[1, 0, 255, 255,],
+			[20, 30, 40, 255,],
+			[25, 33, 44, 255,],
+			[25, 33, 44, 128,],
+		],);
+		assert_eq!(&bytes[..len], [
+			b'q', b'o', b'i', b'f', 0, 0, 0, 7, 0, 0, 0, 1, 4, 0,
+			// diff, run of one, diff, index 49
+			0x79, 0xc0, 0x5b, 0x31,
+			// rgb, luma, rgba
+			0xfe, 20, 30, 40, 0xa3, 0xa9, 0xff, 25, 33, 44, 128,
+			0, 0, 0, 0, 0, 0, 0, 1,
+		]);
+	}
+
+	#[test]
+	fn test_encode_long_runs() {
+		// the first pixel equals the initial previous pixel
+		let (bytes, len,) = encode(&[[0, 0, 0, 255,]; 100],);
+		let ops = &bytes[HEADER_LEN..len - END.len()];
+		assert_eq!(ops, [OP_RUN | 61, OP_RUN | 37,]);
+	}
+}
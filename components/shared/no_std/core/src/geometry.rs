@@ -0,0 +1,468 @@
+//! # Geometry Module
+//!
+//! This module provides [`Point`], [`Size`] and [`Rect`] in pixels, for
+//! drawing primitives, the cursor layer and the tracking of damaged screen
+//! areas.
+//!
+//! ## Conventions
+//!
+//! - The origin is the top left corner, `y` grows downwards
+//! - A [`Rect`] contains its left and top edges but not its right and bottom
+//!   edges, so a rectangle of size `0` is empty and adjacent rectangles do
+//!   not overlap
+//! - Edges saturate at `usize::MAX` instead of overflowing
+//!
+//! ## Example
+//!
+//! ```rust
+//! use oso_no_std_shared::geometry::Point;
+//! use oso_no_std_shared::geometry::Rect;
+//! use oso_no_std_shared::geometry::Size;
+//!
+//! let screen = Size::new(640, 480,);
+//! let window = Rect::new(Point::new(600, 10,), Size::new(100, 20,),);
+//! let visible = window.clamp_to(screen,);
+//! assert_eq!(visible, Rect::new(Point::new(600, 10,), Size::new(40, 20,),));
+//!
+//! let spans = visible.spans();
+//! assert_eq!(spans.len(), 20);
+//! for span in spans {
+//! 	assert_eq!(span.len, 40);
+//! }
+//! ```
+
+use core::iter::FusedIterator;
+
+/// Position in pixels
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash,)]
+pub struct Point {
+	pub x: usize,
+	pub y: usize,
+}
+
+impl Point {
+	pub const ORIGIN: Self = Self::new(0, 0,);
+
+	pub const fn new(x: usize, y: usize,) -> Self {
+		Self { x, y, }
+	}
+
+	/// `self` moved by `(dx, dy)`, saturating at the bounds of `usize`
+	pub const fn offset(self, dx: isize, dy: isize,) -> Self {
+		let x = self.x.saturating_add_signed(dx,);
+		let y = self.y.saturating_add_signed(dy,);
+		Self::new(x, y,)
+	}
+
+	/// Nearest point inside `bounds`. The origin if `bounds` is empty
+	pub const fn clamp_to(self, bounds: Size,) -> Self {
+		Self::new(
+			min(self.x, bounds.width.saturating_sub(1,),),
+			min(self.y, bounds.height.saturating_sub(1,),),
+		)
+	}
+}
+
+impl From<(usize, usize,),> for Point {
+	fn from((x, y,): (usize, usize,),) -> Self {
+		Self::new(x, y,)
+	}
+}
+
+/// Extent in pixels
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash,)]
+pub struct Size {
+	pub width:  usize,
+	pub height: usize,
+}
+
+impl Size {
+	pub const fn new(width: usize, height: usize,) -> Self {
+		Self { width, height, }
+	}
+
+	/// Number of pixels, saturating at `usize::MAX`
+	pub const fn area(&self,) -> usize {
+		self.width.saturating_mul(self.height,)
+	}
+
+	pub const fn is_empty(&self,) -> bool {
+		self.width == 0 || self.height == 0
+	}
+}
+
+/// Axis-aligned rectangle
+///
+/// # Fields
+///
+/// * `origin` - Top left corner, inside the rectangle unless it is empty
+/// * `size` - Extent from `origin`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash,)]
+pub struct Rect {
+	pub origin: Point,
+	pub size:   Size,
+}
+
+impl Rect {
+	pub const fn new(origin: Point, size: Size,) -> Self {
+		Self { origin, size, }
+	}
+
+	/// Rectangle between two corners in any order. `a` is contained and `b`
+	/// is not, as the right and bottom edges of a rectangle
+	pub const fn from_corners(a: Point, b: Point,) -> Self {
+		let origin = Point::new(min(a.x, b.x,), min(a.y, b.y,),);
+		let size = Size::new(a.x.abs_diff(b.x,), a.y.abs_diff(b.y,),);
+		Self::new(origin, size,)
+	}
+
+	/// Rectangle covering the pixels from `left_top` to `right_bottom`, both
+	/// included. Empty if `right_bottom` is left of or above `left_top`
+	pub const fn from_inclusive(left_top: Point, right_bottom: Point,) -> Self {
+		let right = right_bottom.x.saturating_add(1,);
+		let bottom = right_bottom.y.saturating_add(1,);
+		let size = Size::new(
+			right.saturating_sub(left_top.x,),
+			bottom.saturating_sub(left_top.y,),
+		);
+		Self::new(left_top, size,)
+	}
+
+	/// `bounds` as a rectangle at the origin
+	pub const fn of_size(bounds: Size,) -> Self {
+		Self::new(Point::ORIGIN, bounds,)
+	}
+
+	pub const fn left(&self,) -> usize {
+		self.origin.x
+	}
+
+	pub const fn top(&self,) -> usize {
+		self.origin.y
+	}
+
+	/// First column right of the rectangle
+	pub const fn right(&self,) -> usize {
+		self.origin.x.saturating_add(self.size.width,)
+	}
+
+	/// First row below the rectangle
+	pub const fn bottom(&self,) -> usize {
+		self.origin.y.saturating_add(self.size.height,)
+	}
+
+	/// Last pixel inside the rectangle, `None` if it is empty
+	pub const fn right_bottom(&self,) -> Option<Point,> {
+		if self.is_empty() {
+			None
+		} else {
+			Some(Point::new(self.right() - 1, self.bottom() - 1,),)
+		}
+	}
+
+	/// Whether the rectangle has no pixels. Also true if its edges saturate
+	/// at its origin
+	pub const fn is_empty(&self,) -> bool {
+		self.right() == self.left() || self.bottom() == self.top()
+	}
+
+	pub const fn contains(&self, point: Point,) -> bool {
+		self.left() <= point.x
+			&& point.x < self.right()
+			&& self.top() <= point.y
+			&& point.y < self.bottom()
+	}
+
+	/// Whether every pixel of `other` is in `self`. An empty `other` is
+	/// contained by every rectangle
+	pub const fn contains_rect(&self, other: &Self,) -> bool {
+		other.is_empty()
+			|| (self.left() <= other.left()
+				&& other.right() <= self.right()
+				&& self.top() <= other.top()
+				&& other.bottom() <= self.bottom())
+	}
+
+	/// Pixels in both rectangles, `None` if they do not overlap
+	pub const fn intersection(&self, other: &Self,) -> Option<Self,> {
+		let left = max(self.left(), other.left(),);
+		let top = max(self.top(), other.top(),);
+		let right = min(self.right(), other.right(),);
+		let bottom = min(self.bottom(), other.bottom(),);
+		if left < right && top < bottom {
+			let size = Size::new(right - left, bottom - top,);
+			Some(Self::new(Point::new(left, top,), size,),)
+		} else {
+			None
+		}
+	}
+
+	/// Smallest rectangle containing both rectangles. Empty rectangles are
+	/// ignored, so a damaged area can start empty and grow by union
+	pub const fn union(&self, other: &Self,) -> Self {
+		if self.is_empty() {
+			return *other;
+		}
+		if other.is_empty() {
+			return *self;
+		}
+		let left_top = Point::new(
+			min(self.left(), other.left(),),
+			min(self.top(), other.top(),),
+		);
+		let right_bottom = Point::new(
+			max(self.right(), other.right(),),
+			max(self.bottom(), other.bottom(),),
+		);
+		Self::from_corners(left_top, right_bottom,)
+	}
+
+	/// Part of the rectangle on a screen of `bounds`. Empty at the origin if
+	/// nothing is on screen
+	pub const fn clamp_to(&self, bounds: Size,) -> Self {
+		match self.intersection(&Self::of_size(bounds,),) {
+			Some(rect,) => rect,
+			None => Self::new(Point::ORIGIN, Size::new(0, 0,),),
+		}
+	}
+
+	/// Rows of the rectangle from top to bottom
+	pub const fn spans(&self,) -> Spans {
+		let rows = if self.is_empty() { 0 } else { self.bottom() - self.top() };
+		Spans { rect: *self, row: 0, rows, }
+	}
+}
+
+/// Horizontal run of pixels
+///
+/// # Fields
+///
+/// * `start` - Leftmost pixel of the run
+/// * `len` - Number of pixels from `start` to the right
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct Span {
+	pub start: Point,
+	pub len:   usize,
+}
+
+/// Iterator over the rows of a [`Rect`], returned by [`Rect::spans`]
+#[derive(Debug, Clone,)]
+pub struct Spans {
+	rect: Rect,
+	row:  usize,
+	rows: usize,
+}
+
+impl Iterator for Spans {
+	type Item = Span;
+
+	fn next(&mut self,) -> Option<Self::Item,> {
+		if self.row == self.rows {
+			return None;
+		}
+		let start = Point::new(self.rect.left(), self.rect.top() + self.row,);
+		self.row += 1;
+		Some(Span { start, len: self.rect.right() - self.rect.left(), },)
+	}
+
+	fn size_hint(&self,) -> (usize, Option<usize,>,) {
+		let len = self.rows - self.row;
+		(len, Some(len,),)
+	}
+}
+
+impl DoubleEndedIterator for Spans {
+	fn next_back(&mut self,) -> Option<Self::Item,> {
+		if self.row == self.rows {
+			return None;
+		}
+		self.rows -= 1;
+		let start = Point::new(self.rect.left(), self.rect.top() + self.rows,);
+		Some(Span { start, len: self.rect.right() - self.rect.left(), },)
+	}
+}
+
+impl ExactSizeIterator for Spans {}
+
+impl FusedIterator for Spans {}
+
+const fn min(a: usize, b: usize,) -> usize {
+	if a < b { a } else { b }
+}
+
+const fn max(a: usize, b: usize,) -> usize {
+	if a < b { b } else { a }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn rect(x: usize, y: usize, width: usize, height: usize,) -> Rect {
+		Rect::new(Point::new(x, y,), Size::new(width, height,),)
+	}
+
+	#[test]
+	fn test_point_offset_saturates() {
+		let p = Point::new(5, 5,);
+		assert_eq!(p.offset(-2, 3,), Point::new(3, 8,));
+		assert_eq!(p.offset(-10, -10,), Point::ORIGIN);
+		let far = Point::new(usize::MAX - 1, 0,);
+		assert_eq!(far.offset(5, 0,), Point::new(usize::MAX, 0,));
+	}
+
+	#[test]
+	fn test_point_clamp_to() {
+		let screen = Size::new(640, 480,);
+		assert_eq!(Point::new(10, 20,).clamp_to(screen,), Point::new(10, 20,));
+		let corner = Point::new(639, 479,);
+		assert_eq!(Point::new(640, 480,).clamp_to(screen,), corner);
+		assert_eq!(Point::new(9999, 5,).clamp_to(screen,), Point::new(639, 5,));
+		assert_eq!(Point::new(3, 3,).clamp_to(Size::default(),), Point::ORIGIN);
+	}
+
+	#[test]
+	fn test_size_area_and_emptiness() {
+		assert_eq!(Size::new(3, 4,).area(), 12);
+		assert_eq!(Size::new(usize::MAX, 2,).area(), usize::MAX);
+		assert!(Size::new(0, 4,).is_empty());
+		assert!(Size::new(4, 0,).is_empty());
+		assert!(!Size::new(1, 1,).is_empty());
+	}
+
+	#[test]
+	fn test_rect_corners() {
+		let a = Point::new(10, 2,);
+		let b = Point::new(4, 8,);
+		assert_eq!(Rect::from_corners(a, b,), rect(4, 2, 6, 6,));
+		assert_eq!(Rect::from_corners(b, a,), rect(4, 2, 6, 6,));
+		assert!(Rect::from_corners(a, a,).is_empty());
+
+		let r = Rect::from_inclusive(Point::new(1, 1,), Point::new(3, 2,),);
+		assert_eq!(r, rect(1, 1, 3, 2,));
+		assert_eq!(r.right_bottom(), Some(Point::new(3, 2,)));
+		let single = Rect::from_inclusive(a, a,);
+		assert_eq!(single.size, Size::new(1, 1,));
+		assert!(Rect::from_inclusive(a, b,).is_empty());
+		assert_eq!(rect(1, 1, 0, 5,).right_bottom(), None);
+	}
+
+	#[test]
+	fn test_rect_edges_saturate() {
+		let r = rect(usize::MAX - 2, 0, 10, 1,);
+		assert_eq!(r.right(), usize::MAX);
+		assert!(!r.is_empty());
+		assert!(r.contains(Point::new(usize::MAX - 1, 0,)));
+		assert!(!r.contains(Point::new(usize::MAX, 0,)));
+		assert!(rect(usize::MAX, 0, 10, 1,).is_empty());
+	}
+
+	#[test]
+	fn test_rect_contains_excludes_right_and_bottom() {
+		let r = rect(2, 3, 4, 5,);
+		assert!(r.contains(Point::new(2, 3,)));
+		assert!(r.contains(Point::new(5, 7,)));
+		assert!(!r.contains(Point::new(6, 7,)));
+		assert!(!r.contains(Point::new(5, 8,)));
+		assert!(!r.contains(Point::new(1, 3,)));
+		assert!(!r.contains(Point::new(2, 2,)));
+		assert!(!rect(2, 3, 0, 0,).contains(Point::new(2, 3,)));
+	}
+
+	#[test]
+	fn test_rect_contains_rect() {
+		let outer = rect(0, 0, 10, 10,);
+		assert!(outer.contains_rect(&outer));
+		assert!(outer.contains_rect(&rect(2, 2, 8, 8,)));
+		assert!(!outer.contains_rect(&rect(2, 2, 9, 8,)));
+		assert!(!outer.contains_rect(&rect(11, 0, 1, 1,)));
+		assert!(outer.contains_rect(&rect(50, 50, 0, 0,)));
+		assert!(!rect(0, 0, 0, 0,).contains_rect(&rect(0, 0, 1, 1,)));
+	}
+
+	#[test]
+	fn test_rect_intersection() {
+		let a = rect(0, 0, 10, 10,);
+		let overlap = a.intersection(&rect(5, 5, 10, 10,),);
+		assert_eq!(overlap, Some(rect(5, 5, 5, 5,)));
+		assert_eq!(a.intersection(&rect(2, 3, 4, 5,)), Some(rect(2, 3, 4, 5,)));
+		assert_eq!(a.intersection(&a), Some(a));
+		// edges touch but no pixel is shared
+		assert_eq!(a.intersection(&rect(10, 0, 5, 5,)), None);
+		assert_eq!(a.intersection(&rect(0, 10, 5, 5,)), None);
+		assert_eq!(a.intersection(&rect(20, 20, 5, 5,)), None);
+		assert_eq!(a.intersection(&rect(3, 3, 0, 4,)), None);
+		// symmetric
+		let b = rect(7, 1, 9, 2,);
+		assert_eq!(a.intersection(&b), b.intersection(&a));
+	}
+
+	#[test]
+	fn test_rect_union() {
+		let a = rect(0, 0, 2, 2,);
+		let b = rect(5, 6, 1, 1,);
+		assert_eq!(a.union(&b), rect(0, 0, 6, 7,));
+		assert_eq!(b.union(&a), rect(0, 0, 6, 7,));
+		assert_eq!(a.union(&a), a);
+		let empty = rect(100, 100, 0, 0,);
+		assert_eq!(empty.union(&b), b);
+		assert_eq!(b.union(&empty), b);
+		assert!(empty.union(&rect(1, 1, 0, 3,)).is_empty());
+	}
+
+	#[test]
+	fn test_rect_clamp_to() {
+		let screen = Size::new(100, 50,);
+		assert_eq!(rect(10, 10, 5, 5,).clamp_to(screen,), rect(10, 10, 5, 5,));
+		let corner = rect(90, 40, 20, 20,).clamp_to(screen,);
+		assert_eq!(corner, rect(90, 40, 10, 10,));
+		let large = rect(0, 0, 1000, 1000,).clamp_to(screen,);
+		assert_eq!(large, Rect::of_size(screen,));
+		assert!(rect(100, 0, 5, 5,).clamp_to(screen,).is_empty());
+		assert_eq!(rect(200, 200, 5, 5,).clamp_to(screen,), Rect::default());
+		assert!(rect(0, 0, 5, 5,).clamp_to(Size::default(),).is_empty());
+	}
+
+	#[test]
+	fn test_rect_spans() {
+		let r = rect(3, 4, 5, 2,);
+		let mut spans = r.spans();
+		assert_eq!(spans.len(), 2);
+		let first = Span { start: Point::new(3, 4,), len: 5, };
+		assert_eq!(spans.next(), Some(first));
+		assert_eq!(spans.len(), 1);
+		let second = Span { start: Point::new(3, 5,), len: 5, };
+		assert_eq!(spans.next(), Some(second));
+		assert_eq!(spans.next(), None);
+		assert_eq!(spans.next(), None);
+
+		let rows: [usize; 2] = [5, 4,];
+		for (span, y,) in r.spans().rev().zip(rows,) {
+			assert_eq!(span.start.y, y);
+		}
+		let mut both = rect(0, 0, 1, 3,).spans();
+		assert_eq!(both.next().map(|s| s.start.y), Some(0));
+		assert_eq!(both.next_back().map(|s| s.start.y), Some(2));
+		assert_eq!(both.next().map(|s| s.start.y), Some(1));
+		assert_eq!(both.next_back(), None);
+	}
+
+	#[test]
+	fn test_rect_spans_of_empty_rect() {
+		assert_eq!(rect(0, 0, 0, 5,).spans().count(), 0);
+		assert_eq!(rect(0, 0, 5, 0,).spans().count(), 0);
+		assert_eq!(rect(usize::MAX, 0, 5, 5,).spans().count(), 0);
+	}
+
+	#[test]
+	fn test_rect_spans_cover_area() {
+		let r = rect(1, 2, 7, 3,);
+		let pixels: usize = r.spans().map(|s| s.len,).sum();
+		assert_eq!(pixels, r.size.area());
+		for span in r.spans() {
+			assert!(r.contains(span.start));
+			let last = Point::new(span.start.x + span.len - 1, span.start.y,);
+			assert!(r.contains(last));
+		}
+	}
+}
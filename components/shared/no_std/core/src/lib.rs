@@ -11,10 +11,18 @@
 //! ## Features
 //!
 //! - **Bridge Module**: Low-level hardware interfaces and CPU control functions
+//! - **Color Module**: Colors and text styles validated at compile time
 //! - **Data Module**: Generic data structures like trees for system data
 //!   management
+//! - **Geometry Module**: Points, sizes and rectangles with clipping math
+//! - **Mem Module**: Vectorized fills and row copies for framebuffers
 //! - **Parser Module**: Parsing utilities for binary data, HTML, and code
 //!   generation
+//! - **Path Module**: Paths with `/` and `\` separators in fixed size
+//!   buffers
+//! - **Shell Module**: Line editing for interactive shells
+//! - **Text Module**: Strict string decoding and heapless formatting
+//! - **Units Module**: Sizes in bytes and physical and virtual addresses
 //! - **CPU Control**: Platform-specific CPU power management functions
 //!
 //! ## Architecture
@@ -53,8 +61,15 @@
 
 // Public modules
 pub mod bridge;
+pub mod color;
 pub mod data;
+pub mod geometry;
+pub mod mem;
 pub mod parser;
+pub mod path;
+pub mod shell;
+pub mod text;
+pub mod units;
 
 use core::arch::asm;
 
@@ -0,0 +1,384 @@
+//! # Mem Module
+//!
+//! This module provides bulk fills and copies for framebuffers, where a
+//! rectangle is thousands of equal pixels and scrolling moves most of the
+//! screen.
+//!
+//! - AArch64: NEON stores and copies of 64 bytes per iteration
+//! - x86_64: `rep stosd` and `rep stosq` for fills, `rep movsb` for copies
+//! - Others: `slice::fill` and `memcpy`
+//!
+//! Runs shorter than [`SIMD_MIN`] bytes take the scalar path, as setting up
+//! the vector loop costs more than it saves. On x86_64, `rep movsb` is only
+//! fast with enhanced `rep movsb`, which is looked up with `cpuid` on the
+//! first copy. Other processors copy with `memcpy`. AArch64 targets without
+//! NEON, e.g. soft float ones, use the scalar path.
+//!
+//! Vector accesses only need the alignment of their elements, so the
+//! routines are usable on AArch64 with `+strict-align`.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use oso_no_std_shared::mem;
+//!
+//! // 4 rows of 8 pixels, 2 pixels of padding per row
+//! let mut screen = [0_u32; 40];
+//! mem::fill32(&mut screen[10..18], 0xff00_ff00,);
+//!
+//! // scroll up by a row
+//! let bytes = unsafe {
+//! 	core::slice::from_raw_parts_mut(screen.as_mut_ptr().cast(), 160,)
+//! };
+//! mem::copy_rows(bytes, 40, 32, 1, 0, 3,);
+//! assert!(screen[..8].iter().all(|&pixel| pixel == 0xff00_ff00));
+//! assert!(screen[10..18].iter().all(|&pixel| pixel == 0));
+//! ```
+
+/// Shortest run in bytes which is filled or copied with vector instructions
+pub const SIMD_MIN: usize = 64;
+
+/// Sets every element of `dst` to `value`
+///
+/// ```rust
+/// let mut span = [0_u32; 100];
+/// oso_no_std_shared::mem::fill32(&mut span, 0x00ff_8000,);
+/// assert!(span.iter().all(|&pixel| pixel == 0x00ff_8000));
+/// ```
+pub fn fill32(dst: &mut [u32], value: u32,) {
+	if size_of_val(dst,) < SIMD_MIN {
+		dst.fill(value,);
+		return;
+	}
+	// SAFETY: `dst` is valid for writes of its length
+	unsafe { arch::fill32(dst.as_mut_ptr(), dst.len(), value,) }
+}
+
+/// Sets every element of `dst` to `value`. Fills two pixels per element
+///
+/// ```rust
+/// let mut span = [0_u64; 100];
+/// oso_no_std_shared::mem::fill64(&mut span, u64::MAX,);
+/// assert!(span.iter().all(|&pixels| pixels == u64::MAX));
+/// ```
+pub fn fill64(dst: &mut [u64], value: u64,) {
+	if size_of_val(dst,) < SIMD_MIN {
+		dst.fill(value,);
+		return;
+	}
+	// SAFETY: `dst` is valid for writes of its length
+	unsafe { arch::fill64(dst.as_mut_ptr(), dst.len(), value,) }
+}
+
+/// Copies `rows` rows of `width` bytes from row `src` of `buf` to row `dst`
+///
+/// Rows start every `stride` bytes. The source and destination may overlap,
+/// as when scrolling, and bytes past `width` in each row are left alone.
+///
+/// # Panics
+///
+/// - If `width` is larger than `stride`
+/// - If the source or destination rows are not inside `buf`
+///
+/// ```rust
+/// use oso_no_std_shared::mem;
+///
+/// let mut buf = *b"aaaa.bbbb.cccc.";
+/// mem::copy_rows(&mut buf, 5, 4, 1, 0, 2,);
+/// assert_eq!(&buf, b"bbbb.cccc.cccc.");
+/// mem::copy_rows(&mut buf, 5, 4, 0, 1, 2,);
+/// assert_eq!(&buf, b"bbbb.bbbb.cccc.");
+/// ```
+pub fn copy_rows(
+	buf: &mut [u8],
+	stride: usize,
+	width: usize,
+	src: usize,
+	dst: usize,
+	rows: usize,
+) {
+	assert!(width <= stride, "row of {width} bytes in stride of {stride}");
+	if rows == 0 || width == 0 || src == dst {
+		return;
+	}
+	let end = |row: usize| (row + rows - 1) * stride + width;
+	assert!(end(src,) <= buf.len(), "source rows out of bounds");
+	assert!(end(dst,) <= buf.len(), "destination rows out of bounds");
+
+	let copy_row = |buf: &mut [u8], i: usize| {
+		let from = (src + i) * stride;
+		let to = (dst + i) * stride;
+		// rows are distinct and at most `stride` long, so a row does not
+		// overlap the one it is copied to
+		let (from, to,) = if from < to {
+			let (head, tail,) = buf.split_at_mut(to,);
+			(&head[from..from + width], &mut tail[..width],)
+		} else {
+			let (head, tail,) = buf.split_at_mut(from,);
+			(&tail[..width], &mut head[to..to + width],)
+		};
+		copy(from, to,);
+	};
+	// copy in the direction which reads each row before it is overwritten
+	if dst < src {
+		(0..rows).for_each(|i| copy_row(buf, i,),);
+	} else {
+		(0..rows).rev().for_each(|i| copy_row(buf, i,),);
+	}
+}
+
+/// copies `src` to `dst` of the same length
+fn copy(src: &[u8], dst: &mut [u8],) {
+	if dst.len() < SIMD_MIN {
+		dst.copy_from_slice(src,);
+		return;
+	}
+	// SAFETY: `src` and `dst` are distinct slices of `dst.len()` bytes
+	unsafe { arch::copy(src.as_ptr(), dst.as_mut_ptr(), dst.len(),) }
+}
+
+#[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+mod arch {
+	use core::arch::aarch64::vdupq_n_u32;
+	use core::arch::aarch64::vdupq_n_u64;
+	use core::arch::aarch64::vld1q_u8;
+	use core::arch::aarch64::vst1q_u8;
+	use core::arch::aarch64::vst1q_u32;
+	use core::arch::aarch64::vst1q_u64;
+
+	/// # Safety
+	///
+	/// `dst` is valid for writes of `len` elements
+	pub unsafe fn fill32(dst: *mut u32, len: usize, value: u32,) {
+		unsafe {
+			let v = vdupq_n_u32(value,);
+			let mut i = 0;
+			while i + 16 <= len {
+				let p = dst.add(i,);
+				vst1q_u32(p, v,);
+				vst1q_u32(p.add(4,), v,);
+				vst1q_u32(p.add(8,), v,);
+				vst1q_u32(p.add(12,), v,);
+				i += 16;
+			}
+			while i < len {
+				dst.add(i,).write(value,);
+				i += 1;
+			}
+		}
+	}
+
+	/// # Safety
+	///
+	/// `dst` is valid for writes of `len` elements
+	pub unsafe fn fill64(dst: *mut u64, len: usize, value: u64,) {
+		unsafe {
+			let v = vdupq_n_u64(value,);
+			let mut i = 0;
+			while i + 8 <= len {
+				let p = dst.add(i,);
+				vst1q_u64(p, v,);
+				vst1q_u64(p.add(2,), v,);
+				vst1q_u64(p.add(4,), v,);
+				vst1q_u64(p.add(6,), v,);
+				i += 8;
+			}
+			while i < len {
+				dst.add(i,).write(value,);
+				i += 1;
+			}
+		}
+	}
+
+	/// # Safety
+	///
+	/// `src` and `dst` are valid for `len` bytes and do not overlap
+	pub unsafe fn copy(src: *const u8, dst: *mut u8, len: usize,) {
+		unsafe {
+			let mut i = 0;
+			while i + 64 <= len {
+				let (s, d,) = (src.add(i,), dst.add(i,),);
+				let a = vld1q_u8(s,);
+				let b = vld1q_u8(s.add(16,),);
+				let c = vld1q_u8(s.add(32,),);
+				let e = vld1q_u8(s.add(48,),);
+				vst1q_u8(d, a,);
+				vst1q_u8(d.add(16,), b,);
+				vst1q_u8(d.add(32,), c,);
+				vst1q_u8(d.add(48,), e,);
+				i += 64;
+			}
+			core::ptr::copy_nonoverlapping(src.add(i,), dst.add(i,), len - i,);
+		}
+	}
+}
+
+#[cfg(target_arch = "x86_64")]
+mod arch {
+	use core::arch::asm;
+	use core::arch::x86_64::__cpuid_count;
+	use core::sync::atomic::AtomicU8;
+	use core::sync::atomic::Ordering;
+
+	/// whether the processor has enhanced `rep movsb`. 0 until looked up,
+	/// then 1 without and 2 with
+	static ERMS: AtomicU8 = AtomicU8::new(0,);
+
+	/// # Safety
+	///
+	/// `dst` is valid for writes of `len` elements
+	pub unsafe fn fill32(dst: *mut u32, len: usize, value: u32,) {
+		// SAFETY: the direction flag is clear as required by the ABI
+		unsafe {
+			asm!(
+				"rep stosd",
+				inout("rcx") len => _,
+				inout("rdi") dst => _,
+				in("eax") value,
+				options(nostack, preserves_flags),
+			);
+		}
+	}
+
+	/// # Safety
+	///
+	/// `dst` is valid for writes of `len` elements
+	pub unsafe fn fill64(dst: *mut u64, len: usize, value: u64,) {
+		// SAFETY: the direction flag is clear as required by the ABI
+		unsafe {
+			asm!(
+				"rep stosq",
+				inout("rcx") len => _,
+				inout("rdi") dst => _,
+				in("rax") value,
+				options(nostack, preserves_flags),
+			);
+		}
+	}
+
+	/// # Safety
+	///
+	/// `src` and `dst` are valid for `len` bytes and do not overlap
+	pub unsafe fn copy(src: *const u8, dst: *mut u8, len: usize,) {
+		if !erms() {
+			unsafe { core::ptr::copy_nonoverlapping(src, dst, len,) };
+			return;
+		}
+		// SAFETY: the direction flag is clear as required by the ABI
+		unsafe {
+			asm!(
+				"rep movsb",
+				inout("rcx") len => _,
+				inout("rsi") src => _,
+				inout("rdi") dst => _,
+				options(nostack, preserves_flags),
+			);
+		}
+	}
+
+	fn erms() -> bool {
+		match ERMS.load(Ordering::Relaxed,) {
+			0 => {
+				let ebx = __cpuid_count(7, 0,).ebx;
+				let erms = ebx & (1 << 9) != 0;
+				ERMS.store(1 + erms as u8, Ordering::Relaxed,);
+				erms
+			},
+			found => found == 2,
+		}
+	}
+}
+
+#[cfg(not(any(
+	all(target_arch = "aarch64", target_feature = "neon"),
+	target_arch = "x86_64"
+)))]
+mod arch {
+	/// # Safety
+	///
+	/// `dst` is valid for writes of `len` elements
+	pub unsafe fn fill32(dst: *mut u32, len: usize, value: u32,) {
+		unsafe { core::slice::from_raw_parts_mut(dst, len,) }.fill(value,);
+	}
+
+	/// # Safety
+	///
+	/// `dst` is valid for writes of `len` elements
+	pub unsafe fn fill64(dst: *mut u64, len: usize, value: u64,) {
+		unsafe { core::slice::from_raw_parts_mut(dst, len,) }.fill(value,);
+	}
+
+	/// # Safety
+	///
+	/// `src` and `dst` are valid for `len` bytes and do not overlap
+	pub unsafe fn copy(src: *const u8, dst: *mut u8, len: usize,) {
+		unsafe { core::ptr::copy_nonoverlapping(src, dst, len,) };
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const LEN: usize = 4096;
+
+	/// bytes which differ between neighbouring rows and offsets
+	fn pattern() -> [u8; LEN] {
+		core::array::from_fn(|i| (i * 7 + i / 251) as u8,)
+	}
+
+	/// [`copy_rows`] one byte at a time, reading from a copy of `buf`
+	fn reference(
+		buf: &mut [u8],
+		stride: usize,
+		width: usize,
+		src: usize,
+		dst: usize,
+		rows: usize,
+	) {
+		let old = pattern();
+		for row in 0..rows {
+			for x in 0..width {
+				buf[(dst + row) * stride + x] = old[(src + row) * stride + x];
+			}
+		}
+	}
+
+	#[test]
+	fn test_copy() {
+		let src = pattern();
+		for len in [SIMD_MIN, 65, 100, 127, 128, 200, 1000,] {
+			// misaligned by one byte on both sides
+			let mut dst = [0; LEN];
+			copy(&src[1..1 + len], &mut dst[3..3 + len],);
+			assert_eq!(dst[3..3 + len], src[1..1 + len], "{len} bytes");
+			assert!(dst[..3].iter().chain(&dst[3 + len..],).all(|&b| b == 0));
+
+			let mut dst = [0; LEN];
+			// SAFETY: both arrays hold `LEN` bytes
+			unsafe { arch::copy(src.as_ptr(), dst.as_mut_ptr(), len,) };
+			assert_eq!(dst[..len], src[..len], "{len} bytes");
+		}
+	}
+
+	#[test]
+	fn test_copy_rows() {
+		// scrolls up and down, by one row and by several
+		let scrolls = [(1, 0,), (0, 1,), (3, 0,), (0, 3,), (2, 7,),];
+		for width in [64, 100, 128, 200, 255, 256,] {
+			for stride in [width, width + 3,] {
+				for (src, dst,) in scrolls {
+					let rows = 8;
+					let mut buf = pattern();
+					let mut expected = pattern();
+					copy_rows(&mut buf, stride, width, src, dst, rows,);
+					reference(&mut expected, stride, width, src, dst, rows,);
+					assert!(
+						buf == expected,
+						"{width} of {stride} bytes from row {src} to {dst}"
+					);
+				}
+			}
+		}
+	}
+}
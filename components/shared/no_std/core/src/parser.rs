@@ -7,8 +7,10 @@
 //! ## Submodules
 //!
 //! - `binary`: Binary data parsing utilities
+//! - `config`: TOML subset parser for configuration files
 //! - `generator`: Parser generation framework and core traits
-//! - `html`: HTML parsing capabilities (currently empty)
+//! - `html`: HTML document tree and serializer
+//! - `markdown`: Markdown subset parser and renderer for help text
 //!
 //! ## Design Philosophy
 //!
@@ -18,5 +20,7 @@
 //! programming.
 
 pub mod binary;
+pub mod config;
 pub mod generator;
 pub mod html;
+pub mod markdown;
@@ -0,0 +1,513 @@
+//! # Configuration File Parsing Module
+//!
+//! This module parses a small subset of TOML without allocating. It is used by
+//! the loader for `\EFI\oso\loader.cfg`.
+//!
+//! ## Supported Syntax
+//!
+//! - Comments starting with `#`
+//! - Section headers `[name]`. Dotted names such as `[a.b]` are accepted as
+//!   a single name
+//! - Bare keys (`A-Za-z0-9_-`) followed by `=` and a value
+//! - Values:
+//!   - Basic strings `"..."` without escape sequences
+//!   - Literal strings `'...'`, which keep backslashes as is
+//!   - Integers in decimal, `0x`, `0o` or `0b` notation with optional `_`
+//!     separators
+//!   - Booleans `true` and `false`
+//!
+//! Anything outside of this subset is rejected rather than misread, so every
+//! file accepted here is read identically by a std TOML crate on the host.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use oso_no_std_shared::parser::config::Config;
+//! use oso_no_std_shared::parser::config::Value;
+//!
+//! let src = "
+//! timeout = 3
+//!
+//! [kernel]
+//! path = '\\oso_kernel.elf'
+//! ";
+//! let config = Config::parse(src,).unwrap();
+//! assert_eq!(config.get("", "timeout"), Some(Value::Int(3)));
+//! assert_eq!(
+//! 	config.get("kernel", "path"),
+//! 	Some(Value::Str("\\oso_kernel.elf"))
+//! );
+//! let path = config.get("kernel", "path",).and_then(|v| v.as_path(),);
+//! assert_eq!(path.and_then(|p| p.file_name()), Some("oso_kernel.elf"));
+//! ```
+
+use crate::path::PathN;
+use core::iter::Enumerate;
+use core::str::Lines;
+use oso_error::Rslt;
+use oso_error::oso_err;
+use oso_error::parser::ConfigError;
+
+/// Value of a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum Value<'a,> {
+	Str(&'a str,),
+	Int(i64,),
+	Bool(bool,),
+}
+
+impl<'a,> Value<'a,> {
+	pub fn as_str(&self,) -> Option<&'a str,> {
+		match self {
+			Self::Str(s,) => Some(s,),
+			_ => None,
+		}
+	}
+
+	/// String value as a path. Literal strings keep the backslashes of UEFI
+	/// paths
+	pub fn as_path(&self,) -> Option<&'a PathN,> {
+		self.as_str().map(PathN::new,)
+	}
+
+	pub fn as_int(&self,) -> Option<i64,> {
+		match self {
+			Self::Int(i,) => Some(*i,),
+			_ => None,
+		}
+	}
+
+	pub fn as_bool(&self,) -> Option<bool,> {
+		match self {
+			Self::Bool(b,) => Some(*b,),
+			_ => None,
+		}
+	}
+}
+
+/// `key = value` pair together with its location
+///
+/// # Fields
+///
+/// * `section` - Name of the enclosing section. Empty for keys before the
+///   first section header
+/// * `key` - Bare key
+/// * `value` - Parsed value
+/// * `line` - 1-based line number
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct Entry<'a,> {
+	pub section: &'a str,
+	pub key:     &'a str,
+	pub value:   Value<'a,>,
+	pub line:    usize,
+}
+
+/// Validated configuration source
+///
+/// [`Config::parse`] checks the whole source once, so lookups never fail.
+#[derive(Debug, Clone, Copy,)]
+pub struct Config<'a,> {
+	src: &'a str,
+}
+
+impl<'a,> Config<'a,> {
+	/// Validates syntax of `src` and rejects duplicated keys and sections
+	pub fn parse(src: &'a str,) -> Rslt<Self, ConfigError,> {
+		let items = Items::new(src,);
+		for (i, item,) in items.clone().enumerate() {
+			let item = item?;
+			let duplicate = items
+				.clone()
+				.take(i,)
+				.filter_map(Result::ok,)
+				.any(|prev| item.conflicts_with(&prev,),);
+			if duplicate {
+				return Err(oso_err!(item.duplicate_error()),);
+			}
+		}
+		Ok(Self { src, },)
+	}
+
+	/// Iterates over all entries in order of appearance
+	pub fn entries(&self,) -> impl Iterator<Item = Entry<'a,>,> + use<'a,> {
+		Items::new(self.src,).filter_map(|item| match item {
+			Ok(Item::Entry(entry,),) => Some(entry,),
+			_ => None,
+		},)
+	}
+
+	/// Iterates over entries of `section`. Pass `""` for keys before the first
+	/// section header
+	pub fn section(
+		&self,
+		section: &'a str,
+	) -> impl Iterator<Item = Entry<'a,>,> + use<'a,> {
+		self.entries().filter(move |entry| entry.section == section,)
+	}
+
+	/// Looks up the entry of `key` in `section`
+	pub fn entry(&self, section: &str, key: &str,) -> Option<Entry<'a,>,> {
+		self.entries()
+			.find(|entry| entry.section == section && entry.key == key,)
+	}
+
+	/// Looks up the value of `key` in `section`
+	pub fn get(&self, section: &str, key: &str,) -> Option<Value<'a,>,> {
+		self.entry(section, key,).map(|entry| entry.value,)
+	}
+}
+
+#[derive(Debug, Clone, Copy,)]
+enum Item<'a,> {
+	Section { name: &'a str, line: usize, },
+	Entry(Entry<'a,>,),
+}
+
+impl<'a,> Item<'a,> {
+	fn path(&self,) -> (&'a str, &'a str,) {
+		match self {
+			Self::Section { name, .. } => ("", name,),
+			Self::Entry(entry,) => (entry.section, entry.key,),
+		}
+	}
+
+	/// `true` if both items define the same dotted path, or if one of them is
+	/// a key and the other one would make it a table
+	fn conflicts_with(&self, other: &Self,) -> bool {
+		let (path, other_path,) = (self.path(), other.path(),);
+		let nested = starts_with(path, other_path,);
+		let contains = starts_with(other_path, path,);
+		match (nested, contains,) {
+			(true, true,) => true,
+			(true, false,) => matches!(other, Self::Entry(_,)),
+			(false, true,) => matches!(self, Self::Entry(_,)),
+			(false, false,) => false,
+		}
+	}
+
+	fn duplicate_error(&self,) -> ConfigError {
+		match self {
+			Self::Section { line, .. } => ConfigError::DuplicateSection(*line,),
+			Self::Entry(entry,) => ConfigError::DuplicateKey(entry.line,),
+		}
+	}
+}
+
+/// `true` if dotted path `section.key` starts with `prefix`. an empty section
+/// means no prefix, and the key of a section item is itself a dotted name
+fn starts_with(
+	(section, key,): (&str, &str,),
+	(prefix_section, prefix_key,): (&str, &str,),
+) -> bool {
+	fn parts<'a,>(
+		section: &'a str,
+		key: &'a str,
+	) -> impl Iterator<Item = &'a str,> {
+		[section, key,]
+			.into_iter()
+			.flat_map(|name| name.split('.',),)
+			.map(str::trim,)
+			.filter(|s| !s.is_empty(),)
+	}
+	let mut path = parts(section, key,);
+	parts(prefix_section, prefix_key,).all(|part| path.next() == Some(part,),)
+}
+
+#[derive(Debug, Clone,)]
+struct Items<'a,> {
+	lines:   Enumerate<Lines<'a,>,>,
+	section: &'a str,
+}
+
+impl<'a,> Items<'a,> {
+	fn new(src: &'a str,) -> Self {
+		Self { lines: src.lines().enumerate(), section: "", }
+	}
+}
+
+impl<'a,> Iterator for Items<'a,> {
+	type Item = Rslt<Item<'a,>, ConfigError,>;
+
+	fn next(&mut self,) -> Option<Self::Item,> {
+		for (i, line,) in self.lines.by_ref() {
+			let line_no = i + 1;
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#',) {
+				continue;
+			}
+
+			if line.starts_with('[',) {
+				let name = match section_header(line, line_no,) {
+					Ok(name,) => name,
+					Err(e,) => return Some(Err(oso_err!(e),),),
+				};
+				self.section = name;
+				return Some(Ok(Item::Section { name, line: line_no, },),);
+			}
+
+			let entry = key_value(line, line_no,).map(|(key, value,)| {
+				Item::Entry(Entry {
+					section: self.section,
+					key,
+					value,
+					line: line_no,
+				},)
+			},);
+			return Some(entry.map_err(|e| oso_err!(e),),);
+		}
+		None
+	}
+}
+
+fn section_header(line: &str, line_no: usize,) -> Result<&str, ConfigError,> {
+	let rest = &line[1..];
+	let Some(close,) = rest.find(']',) else {
+		return Err(ConfigError::InvalidSection(line_no,),);
+	};
+	let name = rest[..close].trim();
+	let valid = !name.is_empty()
+		&& !rest.starts_with('[',)
+		&& name.split('.',).all(|part| is_bare_key(part.trim(),),);
+	if !valid {
+		return Err(ConfigError::InvalidSection(line_no,),);
+	}
+	trailing(&rest[close + 1..], line_no,)?;
+	Ok(name,)
+}
+
+fn key_value(
+	line: &str,
+	line_no: usize,
+) -> Result<(&str, Value<'_,>,), ConfigError,> {
+	let Some((key, value,),) = line.split_once('=',) else {
+		return Err(ConfigError::MissingEquals(line_no,),);
+	};
+	let key = key.trim();
+	if !is_bare_key(key,) {
+		return Err(ConfigError::InvalidKey(line_no,),);
+	}
+
+	let value = value.trim_start();
+	let (value, rest,) = match value.as_bytes().first() {
+		Some(b'"',) => string(value, '"', line_no,)?,
+		Some(b'\'',) => string(value, '\'', line_no,)?,
+		_ => {
+			let end = value
+				.find(|c: char| c.is_whitespace() || c == '#',)
+				.unwrap_or(value.len(),);
+			(scalar(&value[..end], line_no,)?, &value[end..],)
+		},
+	};
+	trailing(rest, line_no,)?;
+	Ok((key, value,),)
+}
+
+/// parses a string quoted with `quote`. returns the value and the rest of the
+/// line
+fn string(
+	value: &str,
+	quote: char,
+	line_no: usize,
+) -> Result<(Value<'_,>, &str,), ConfigError,> {
+	let body = &value[1..];
+	let Some(close,) = body.find(quote,) else {
+		return Err(ConfigError::UnterminatedString(line_no,),);
+	};
+	let s = &body[..close];
+	// multi-line strings start with three quotes
+	if s.is_empty() && body[1..].starts_with(quote,) {
+		return Err(ConfigError::InvalidValue(line_no,),);
+	}
+	if quote == '"' && s.contains('\\',) {
+		return Err(ConfigError::UnsupportedEscape(line_no,),);
+	}
+	Ok((Value::Str(s,), &body[close + 1..],),)
+}
+
+fn scalar(
+	token: &str,
+	line_no: usize,
+) -> Result<Value<'static,>, ConfigError,> {
+	match token {
+		"true" => return Ok(Value::Bool(true,),),
+		"false" => return Ok(Value::Bool(false,),),
+		_ => {},
+	}
+	integer(token,)
+		.map(Value::Int,)
+		.ok_or(ConfigError::InvalidValue(line_no,),)
+}
+
+/// parses a TOML integer. returns `None` for anything TOML would reject or
+/// read as a different type
+fn integer(token: &str,) -> Option<i64,> {
+	let (negative, unsigned,) = match token.as_bytes().first()? {
+		b'-' => (true, &token[1..],),
+		b'+' => (false, &token[1..],),
+		_ => (false, token,),
+	};
+
+	let (radix, digits,) = match unsigned.get(..2,) {
+		Some("0x",) => (16, &unsigned[2..],),
+		Some("0o",) => (8, &unsigned[2..],),
+		Some("0b",) => (2, &unsigned[2..],),
+		_ => (10, unsigned,),
+	};
+	// prefixed integers can't have sign, decimal integers can't have leading
+	// zeros
+	if radix != 10 && unsigned.len() != token.len() {
+		return None;
+	}
+	if radix == 10 && digits.len() > 1 && digits.starts_with('0',) {
+		return None;
+	}
+	// underscores must be surrounded by digits
+	if digits.is_empty()
+		|| digits.starts_with('_',)
+		|| digits.ends_with('_',)
+		|| digits.contains("__",)
+	{
+		return None;
+	}
+
+	let mut acc: i64 = 0;
+	for c in digits.chars().filter(|c| *c != '_',) {
+		let d = c.to_digit(radix,)? as i64;
+		acc = acc.checked_mul(radix as i64,)?;
+		acc = if negative {
+			acc.checked_sub(d,)?
+		} else {
+			acc.checked_add(d,)?
+		};
+	}
+	Some(acc,)
+}
+
+fn trailing(rest: &str, line_no: usize,) -> Result<(), ConfigError,> {
+	let rest = rest.trim_start();
+	if rest.is_empty() || rest.starts_with('#',) {
+		Ok((),)
+	} else {
+		Err(ConfigError::TrailingCharacters(line_no,),)
+	}
+}
+
+fn is_bare_key(key: &str,) -> bool {
+	!key.is_empty()
+		&& key
+			.chars()
+			.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-',)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn error(src: &str,) -> Option<ConfigError,> {
+		Config::parse(src,).unwrap_err().desc
+	}
+
+	#[test]
+	fn test_sections_and_values() {
+		let src = "
+# comment
+timeout = 3 # seconds
+
+[kernel]
+path = '\\oso_kernel.elf'
+cmdline = \"console=ttyAMA0 # not a comment\"
+check_version = false
+
+[ graphics . mode ]
+width = 0x500
+";
+		let config = Config::parse(src,).unwrap();
+		assert_eq!(config.get("", "timeout"), Some(Value::Int(3)));
+		let path = config.get("kernel", "path",);
+		assert_eq!(path, Some(Value::Str("\\oso_kernel.elf")));
+		let cmdline = config.get("kernel", "cmdline",).unwrap();
+		assert_eq!(cmdline.as_str(), Some("console=ttyAMA0 # not a comment"));
+		let check = config.entry("kernel", "check_version",).unwrap();
+		assert_eq!((check.value.as_bool(), check.line), (Some(false), 8));
+		let width = config.get("graphics . mode", "width",);
+		assert_eq!(width, Some(Value::Int(1280)));
+		assert_eq!(config.get("", "path"), None);
+		assert_eq!(config.section("kernel").count(), 3);
+		assert_eq!(config.entries().count(), 5);
+	}
+
+	#[test]
+	fn test_integers() {
+		assert_eq!(integer("0"), Some(0));
+		assert_eq!(integer("-0"), Some(0));
+		assert_eq!(integer("+17"), Some(17));
+		assert_eq!(integer("1_000"), Some(1000));
+		assert_eq!(integer("0xdead_BEEF"), Some(0xdead_beef));
+		assert_eq!(integer("0o17"), Some(0o17));
+		assert_eq!(integer("0b101"), Some(5));
+		assert_eq!(integer("-9223372036854775808"), Some(i64::MIN));
+		assert_eq!(integer("9223372036854775808"), None);
+		for token in ["", "-", "01", "0x", "+0x1", "0X1", "1__0", "_1", "1_"] {
+			assert_eq!(integer(token), None, "{token}");
+		}
+		for token in ["1e3", "1.0", "inf", "nan", "0xg", "0b2", "1979-05-27"] {
+			assert_eq!(integer(token), None, "{token}");
+		}
+	}
+
+	#[test]
+	fn test_syntax_errors() {
+		let section = |line| Some(ConfigError::InvalidSection(line,),);
+		assert_eq!(error("[kernel"), section(1));
+		assert_eq!(error("\n[]"), section(2));
+		assert_eq!(error("[[bin]]"), section(1));
+		assert_eq!(error("[a.]"), section(1));
+		let key = Some(ConfigError::InvalidKey(1,),);
+		assert_eq!(error("\"a\" = 1"), key);
+		assert_eq!(error("a.b = 1"), key);
+		assert_eq!(error(" = 1"), key);
+		assert_eq!(error("timeout"), Some(ConfigError::MissingEquals(1)));
+		let value = Some(ConfigError::InvalidValue(1,),);
+		assert_eq!(error("a = [1, 2]"), value);
+		assert_eq!(error("a = 1.5"), value);
+		assert_eq!(error("a ="), value);
+		assert_eq!(error("a = '''x'''"), value);
+		let unterminated = Some(ConfigError::UnterminatedString(1,),);
+		assert_eq!(error("a = 'x"), unterminated);
+		let escape = Some(ConfigError::UnsupportedEscape(1,),);
+		assert_eq!(error("a = \"\\n\""), escape);
+		let trailing = Some(ConfigError::TrailingCharacters(1,),);
+		assert_eq!(error("[a] b"), trailing);
+		assert_eq!(error("a = 'x' y"), trailing);
+		assert_eq!(error("a = 1 2"), trailing);
+	}
+
+	#[test]
+	fn test_duplicates() {
+		assert_eq!(error("a = 1\na = 2"), Some(ConfigError::DuplicateKey(2)));
+		let section = |line| Some(ConfigError::DuplicateSection(line,),);
+		assert_eq!(error("[a]\nb = 1\n[c]\n[a]"), section(4));
+		assert_eq!(error("[a]\nb = 1\n[a.b]"), section(3));
+		let src = "[a.b]\n[a]\nb = 1";
+		assert_eq!(error(src), Some(ConfigError::DuplicateKey(3)));
+		// keys can't be extended into tables
+		assert_eq!(error("a = 1\n[a.b]"), section(2));
+		assert_eq!(error("[a]\nb = 1\n[a.b.c]"), section(3));
+
+		// super tables may follow their sub tables, keys may share names
+		let src = "[a.b]\nc = 1\n[a]\nd = 2\na = 3\n[b]\na = 4";
+		assert!(Config::parse(src).is_ok());
+	}
+
+	#[test]
+	fn test_value_conversions() {
+		let path = Value::Str("/EFI/oso/../boot.efi",);
+		let name = path.as_path().and_then(|p| p.file_name(),);
+		assert_eq!(name, Some("boot.efi"));
+		assert_eq!(path.as_int(), None);
+		assert_eq!(Value::Int(3).as_int(), Some(3));
+		assert_eq!(Value::Int(3).as_str(), None);
+		assert_eq!(Value::Bool(true).as_bool(), Some(true));
+		assert_eq!(Value::Bool(true).as_path(), None);
+	}
+}
@@ -1,50 +1,335 @@
-//! # HTML Parsing Module
+//! # HTML Module
 //!
-//! This module provides functionality for parsing HTML documents and fragments.
-//! It extends the core parser framework with HTML-specific operations optimized
-//! for system-level HTML processing needs.
+//! This module provides an HTML document tree and a serializer which writes
+//! the tree back to HTML. The tree borrows all of its parts, so documents can
+//! be built in `static`s or on the stack without allocating.
+//!
+//! ## Serialization
+//!
+//! [`HtmlDocument::write_to`] writes into any [`core::fmt::Write`].
+//! [`HtmlStyle`] decides the layout:
+//!
+//! - `indent`: Spaces per nesting level. `0` writes everything on one line
+//!   and keeps text as is, otherwise each node gets its own line and text is
+//!   trimmed
+//! - `quote`: How attribute values are quoted, see [`Quote`]
+//!
+//! Text and attribute values are escaped. Text inside `script` and `style`
+//! is written raw, and void elements such as `br` get no closing tag.
 //!
 //! ## Current Status
 //!
-//! This module is currently empty and represents a placeholder for future
-//! HTML parsing functionality. When implemented, it will provide:
-//!
-//! - HTML document structure parsing
-//! - Tag and attribute extraction
-//! - DOM tree construction
-//! - HTML validation and sanitization
-//!
-//! ## Planned Features
-//!
-//! - **HtmlParser**: Main parser for HTML documents
-//! - **HtmlContext**: Context for HTML parsing operations
-//! - **DomNode**: Representation of HTML DOM nodes
-//! - **HtmlValidator**: HTML validation utilities
-//!
-//! ## Design Goals
-//!
-//! The HTML parser will be designed to work in `no_std` environments while
-//! providing efficient parsing of HTML content for system-level applications
-//! such as documentation processing or configuration file parsing.
-
-// TODO: Implement HTML parsing functionality
-//
-// Example future structure:
-//
-// use crate::parser::generator::{Parser, Context};
-// use crate::data::tree::Tree;
-//
-// /// Context for HTML parsing operations
-// pub struct HtmlContext {
-//     // HTML-specific parsing context
-// }
-//
-// /// Parser for HTML documents
-// pub struct HtmlParser {
-//     // HTML parser implementation
-// }
-//
-// /// Representation of an HTML DOM node
-// pub struct DomNode {
-//     // DOM node data
-// }
+//! HTML parsing is not implemented yet. Once it is, the serializer lets the
+//! parser be tested by round trip.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use oso_no_std_shared::parser::html::Attr;
+//! use oso_no_std_shared::parser::html::Element;
+//! use oso_no_std_shared::parser::html::HtmlDocument;
+//! use oso_no_std_shared::parser::html::HtmlNode;
+//! use oso_no_std_shared::parser::html::HtmlStyle;
+//! use oso_no_std_shared::parser::html::Quote;
+//!
+//! let attrs = [Attr::new("class", "note",)];
+//! let text = [HtmlNode::Text("1 < 2",)];
+//! let body = [HtmlNode::Element(Element::new("p", &attrs, &text,),)];
+//! let html = [HtmlNode::Element(Element::new("body", &[], &body,),)];
+//! let doc = HtmlDocument::new(&html,);
+//!
+//! let mut out = String::new();
+//! doc.write_to(&mut out, HtmlStyle::default(),).unwrap();
+//! assert_eq!(
+//! 	out,
+//! 	"<!DOCTYPE html>\n<body>\n  <p class=\"note\">\n    1 &lt; 2\n  \
+//! 	 </p>\n</body>\n"
+//! );
+//!
+//! let style = HtmlStyle { indent: 0, quote: Quote::Minimal, };
+//! let mut out = String::new();
+//! doc.write_to(&mut out, style,).unwrap();
+//! assert_eq!(out, "<!DOCTYPE html><body><p class=note>1 &lt; 2</p></body>");
+//! ```
+
+use core::fmt;
+use core::fmt::Write;
+
+/// Elements which never have children nor a closing tag
+const VOID_ELEMENTS: [&str; 14] = [
+	"area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta",
+	"param", "source", "track", "wbr",
+];
+/// Elements whose text is not escaped
+const RAW_TEXT_ELEMENTS: [&str; 2] = ["script", "style",];
+
+/// Quoting of attribute values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default,)]
+pub enum Quote {
+	/// `name="value"`
+	#[default]
+	Double,
+	/// `name='value'`
+	Single,
+	/// `name=value` where the value allows it, double quotes otherwise
+	Minimal,
+}
+
+/// Layout of serialized HTML
+///
+/// # Fields
+///
+/// * `indent` - Spaces per nesting level. `0` writes a single line
+/// * `quote` - Quoting of attribute values
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct HtmlStyle {
+	pub indent: usize,
+	pub quote:  Quote,
+}
+
+impl HtmlStyle {
+	/// Single line without any whitespace added
+	pub const COMPACT: Self = Self { indent: 0, quote: Quote::Double, };
+}
+
+impl Default for HtmlStyle {
+	fn default() -> Self {
+		Self { indent: 2, quote: Quote::Double, }
+	}
+}
+
+/// Attribute of an element. `value` is `None` for boolean attributes such as
+/// `disabled`
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct Attr<'a,> {
+	pub name:  &'a str,
+	pub value: Option<&'a str,>,
+}
+
+impl<'a,> Attr<'a,> {
+	pub const fn new(name: &'a str, value: &'a str,) -> Self {
+		Self { name, value: Some(value,), }
+	}
+
+	/// Boolean attribute
+	pub const fn flag(name: &'a str,) -> Self {
+		Self { name, value: None, }
+	}
+}
+
+/// Element with its attributes and children
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct Element<'a,> {
+	pub tag:      &'a str,
+	pub attrs:    &'a [Attr<'a,>],
+	pub children: &'a [HtmlNode<'a,>],
+}
+
+impl<'a,> Element<'a,> {
+	pub const fn new(
+		tag: &'a str,
+		attrs: &'a [Attr<'a,>],
+		children: &'a [HtmlNode<'a,>],
+	) -> Self {
+		Self { tag, attrs, children, }
+	}
+
+	/// Whether the element is written without a closing tag
+	pub fn is_void(&self,) -> bool {
+		VOID_ELEMENTS.iter().any(|v| v.eq_ignore_ascii_case(self.tag,),)
+	}
+}
+
+/// Node of an HTML tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum HtmlNode<'a,> {
+	Element(Element<'a,>,),
+	/// Text content, unescaped
+	Text(&'a str,),
+	/// Content of `<!-- -->`
+	Comment(&'a str,),
+}
+
+/// HTML document
+///
+/// # Fields
+///
+/// * `doctype` - Whether `<!DOCTYPE html>` is written first
+/// * `nodes` - Top level nodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct HtmlDocument<'a,> {
+	pub doctype: bool,
+	pub nodes:   &'a [HtmlNode<'a,>],
+}
+
+impl<'a,> HtmlDocument<'a,> {
+	/// Document of `nodes` with a doctype
+	pub const fn new(nodes: &'a [HtmlNode<'a,>],) -> Self {
+		Self { doctype: true, nodes, }
+	}
+
+	/// Serializes the document into `w` laid out by `style`
+	pub fn write_to(
+		&self,
+		w: &mut impl Write,
+		style: HtmlStyle,
+	) -> fmt::Result {
+		let mut s = Serializer { w, style, started: false, };
+		if self.doctype {
+			s.open_line(0,)?;
+			s.w.write_str("<!DOCTYPE html>",)?;
+		}
+		for node in self.nodes {
+			s.node(node, 0, false,)?;
+		}
+		if style.indent > 0 && s.started {
+			s.w.write_char('\n',)?;
+		}
+		Ok((),)
+	}
+
+	/// [`Display`](fmt::Display) of the document laid out by `style`
+	pub fn styled(&self, style: HtmlStyle,) -> Styled<'_, 'a,> {
+		Styled { doc: self, style, }
+	}
+}
+
+impl fmt::Display for HtmlDocument<'_,> {
+	/// Writes the document in [`HtmlStyle::default`]
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		self.write_to(f, HtmlStyle::default(),)
+	}
+}
+
+/// Document paired with a style. See [`HtmlDocument::styled`]
+pub struct Styled<'d, 'a,> {
+	doc:   &'d HtmlDocument<'a,>,
+	style: HtmlStyle,
+}
+
+impl fmt::Display for Styled<'_, '_,> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		self.doc.write_to(f, self.style,)
+	}
+}
+
+struct Serializer<'w, W: Write,> {
+	w:       &'w mut W,
+	style:   HtmlStyle,
+	/// Whether anything has been written
+	started: bool,
+}
+
+impl<W: Write,> Serializer<'_, W,> {
+	/// Starts a line at nesting `depth` unless the style is single line
+	fn open_line(&mut self, depth: usize,) -> fmt::Result {
+		if self.style.indent > 0 {
+			if self.started {
+				self.w.write_char('\n',)?;
+			}
+			for _ in 0..depth * self.style.indent {
+				self.w.write_char(' ',)?;
+			}
+		}
+		self.started = true;
+		Ok((),)
+	}
+
+	fn node(
+		&mut self,
+		node: &HtmlNode,
+		depth: usize,
+		raw: bool,
+	) -> fmt::Result {
+		match node {
+			HtmlNode::Element(e,) => self.element(e, depth,),
+			HtmlNode::Text(text,) => {
+				let pretty = self.style.indent > 0;
+				let text = if pretty { text.trim() } else { text };
+				if text.is_empty() {
+					return Ok((),);
+				}
+				self.open_line(depth,)?;
+				if raw {
+					self.w.write_str(text,)
+				} else {
+					self.escaped(text, None,)
+				}
+			},
+			HtmlNode::Comment(comment,) => {
+				self.open_line(depth,)?;
+				write!(self.w, "<!--{comment}-->")
+			},
+		}
+	}
+
+	fn element(&mut self, e: &Element, depth: usize,) -> fmt::Result {
+		self.open_line(depth,)?;
+		write!(self.w, "<{}", e.tag)?;
+		for attr in e.attrs {
+			self.attr(attr,)?;
+		}
+		self.w.write_char('>',)?;
+		if e.is_void() {
+			return Ok((),);
+		}
+
+		if !e.children.is_empty() {
+			let raw = RAW_TEXT_ELEMENTS
+				.iter()
+				.any(|r| r.eq_ignore_ascii_case(e.tag,),);
+			for child in e.children {
+				self.node(child, depth + 1, raw,)?;
+			}
+			self.open_line(depth,)?;
+		}
+		write!(self.w, "</{}>", e.tag)
+	}
+
+	fn attr(&mut self, attr: &Attr,) -> fmt::Result {
+		write!(self.w, " {}", attr.name)?;
+		let Some(value,) = attr.value else {
+			return Ok((),);
+		};
+
+		let quote = match self.style.quote {
+			Quote::Double => Some('"',),
+			Quote::Single => Some('\'',),
+			Quote::Minimal if is_unquotable(value,) => None,
+			Quote::Minimal => Some('"',),
+		};
+		self.w.write_char('=',)?;
+		if let Some(q,) = quote {
+			self.w.write_char(q,)?;
+		}
+		self.escaped(value, quote,)?;
+		if let Some(q,) = quote {
+			self.w.write_char(q,)?;
+		}
+		Ok((),)
+	}
+
+	/// Writes `s` escaping `&`, `<`, `>` and `quote`
+	fn escaped(&mut self, s: &str, quote: Option<char,>,) -> fmt::Result {
+		for c in s.chars() {
+			match c {
+				'&' => self.w.write_str("&amp;",)?,
+				'<' => self.w.write_str("&lt;",)?,
+				'>' => self.w.write_str("&gt;",)?,
+				'"' if quote == Some('"',) => self.w.write_str("&quot;",)?,
+				'\'' if quote == Some('\'',) => self.w.write_str("&#39;",)?,
+				c => self.w.write_char(c,)?,
+			}
+		}
+		Ok((),)
+	}
+}
+
+/// Whether `value` can be written without quotes
+fn is_unquotable(value: &str,) -> bool {
+	!value.is_empty()
+		&& value.chars().all(|c| {
+			!c.is_ascii_whitespace()
+				&& !matches!(c, '"' | '\'' | '=' | '<' | '>' | '`')
+		},)
+}
@@ -0,0 +1,284 @@
+//! # Markdown-Lite Module
+//!
+//! This module parses a small subset of Markdown without allocating and
+//! renders it to any [`StyledWrite`], such as the kernel console. It lets
+//! help pages and menu descriptions be written as readable text instead of
+//! strings formatted by hand.
+//!
+//! ## Supported Syntax
+//!
+//! - Headings `#` to `######` followed by a space
+//! - Bullets `- ` or `* `, nested by two spaces of indentation per level
+//! - Code spans `` `code` ``
+//! - Bold `**bold**`
+//! - Blank lines separating paragraphs
+//!
+//! Anything else is plain text. Unterminated markers are written as is.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use oso_no_std_shared::parser::markdown::Block;
+//! use oso_no_std_shared::parser::markdown::Span;
+//! use oso_no_std_shared::parser::markdown::Unstyled;
+//! use oso_no_std_shared::parser::markdown::blocks;
+//! use oso_no_std_shared::parser::markdown::render;
+//! use oso_no_std_shared::parser::markdown::spans;
+//!
+//! let src = "# help\n\nrun `cmd` with **care**\n- first\n  - nested\n";
+//! let mut blocks = blocks(src,);
+//! assert_eq!(blocks.next(), Some(Block::Heading { level: 1, text: "help" }));
+//! assert_eq!(blocks.next(), Some(Block::Blank));
+//!
+//! let mut text = spans("run `cmd` with **care**",);
+//! assert_eq!(text.next(), Some(Span::Plain("run ")));
+//! assert_eq!(text.next(), Some(Span::Code("cmd")));
+//! assert_eq!(text.nth(1), Some(Span::Bold("care")));
+//!
+//! let mut out = String::new();
+//! render(src, &mut Unstyled(&mut out,),).unwrap();
+//! assert_eq!(
+//! 	out,
+//! 	"help\n====\n\nrun cmd with care\n  * first\n    * nested\n"
+//! );
+//! ```
+
+use crate::color::Color;
+use crate::color::Style;
+use crate::text::console::Console;
+use core::fmt;
+use core::str::Lines;
+
+/// Line level element
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum Block<'a,> {
+	/// `# text`. `level` is the number of `#`
+	Heading { level: u8, text: &'a str, },
+	/// `- text`. `depth` is 0 for top level bullets
+	Bullet { depth: usize, text: &'a str, },
+	/// Line of a paragraph, without surrounding whitespace
+	Line(&'a str,),
+	/// Empty line
+	Blank,
+}
+
+/// Inline element of a block's text
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum Span<'a,> {
+	Plain(&'a str,),
+	Code(&'a str,),
+	Bold(&'a str,),
+}
+
+/// Element of the rendered text, shown in the style of [`Element::style`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default,)]
+pub enum Element {
+	#[default]
+	Plain,
+	Heading(u8,),
+	Code,
+	Bold,
+	/// Bullet marker
+	Bullet,
+}
+
+impl Element {
+	/// Style of the element on a console: headings and bold text in bold,
+	/// code in cyan and bullet markers in dark gray
+	pub const fn style(&self,) -> Style {
+		match self {
+			Self::Plain => Style::PLAIN,
+			Self::Heading(_,) => Style::new(Color::WHITE, Color::BLACK,).bold(),
+			Self::Code => Style::new(Color::CYAN, Color::BLACK,),
+			Self::Bold => Style::PLAIN.bold(),
+			Self::Bullet => Style::new(Color::DARK_GRAY, Color::BLACK,),
+		}
+	}
+}
+
+/// Output of [`render`]
+///
+/// Every [`Console`] is a [`StyledWrite`].
+pub trait StyledWrite: fmt::Write {
+	/// Applies `style` to text written until the next call
+	fn set_style(&mut self, style: Style,) -> fmt::Result;
+}
+
+impl<C: Console + ?Sized,> StyledWrite for C {
+	fn set_style(&mut self, style: Style,) -> fmt::Result {
+		Console::set_style(self, style,)
+	}
+}
+
+/// Adapter rendering to a [`fmt::Write`] which has no styling
+pub struct Unstyled<W: fmt::Write,>(pub W,);
+
+impl<W: fmt::Write,> fmt::Write for Unstyled<W,> {
+	fn write_str(&mut self, s: &str,) -> fmt::Result {
+		self.0.write_str(s,)
+	}
+}
+
+impl<W: fmt::Write,> StyledWrite for Unstyled<W,> {
+	fn set_style(&mut self, _style: Style,) -> fmt::Result {
+		Ok((),)
+	}
+}
+
+/// Iterator over blocks of Markdown source
+#[derive(Debug, Clone,)]
+pub struct Blocks<'a,> {
+	lines: Lines<'a,>,
+}
+
+/// Splits `src` into blocks, one per line
+pub fn blocks(src: &str,) -> Blocks<'_,> {
+	Blocks { lines: src.lines(), }
+}
+
+impl<'a,> Iterator for Blocks<'a,> {
+	type Item = Block<'a,>;
+
+	fn next(&mut self,) -> Option<Self::Item,> {
+		let line = self.lines.next()?;
+		let trimmed = line.trim_start();
+		if trimmed.is_empty() {
+			return Some(Block::Blank,);
+		}
+
+		let hashes = trimmed.bytes().take_while(|b| *b == b'#',).count();
+		if (1..=6).contains(&hashes,)
+			&& let Some(text,) = trimmed[hashes..].strip_prefix(' ',)
+		{
+			let text = text.trim();
+			return Some(Block::Heading { level: hashes as u8, text, },);
+		}
+
+		let bullet = trimmed
+			.strip_prefix("- ",)
+			.or_else(|| trimmed.strip_prefix("* ",),);
+		if let Some(text,) = bullet {
+			let depth = (line.len() - trimmed.len()) / 2;
+			return Some(Block::Bullet { depth, text: text.trim(), },);
+		}
+
+		Some(Block::Line(line.trim(),),)
+	}
+}
+
+/// Iterator over inline elements of a block's text
+#[derive(Debug, Clone,)]
+pub struct Spans<'a,> {
+	rest: &'a str,
+}
+
+/// Splits `text` into plain text, code spans and bold text
+pub fn spans(text: &str,) -> Spans<'_,> {
+	Spans { rest: text, }
+}
+
+impl<'a,> Spans<'a,> {
+	/// Content between `marker` at the head of `rest` and the next `marker`
+	fn enclosed(&mut self, marker: &str,) -> Option<&'a str,> {
+		let body = self.rest.strip_prefix(marker,)?;
+		let end = body.find(marker,)?;
+		if end == 0 {
+			return None;
+		}
+		self.rest = &body[end + marker.len()..];
+		Some(&body[..end],)
+	}
+}
+
+impl<'a,> Iterator for Spans<'a,> {
+	type Item = Span<'a,>;
+
+	fn next(&mut self,) -> Option<Self::Item,> {
+		if self.rest.is_empty() {
+			return None;
+		}
+		if let Some(code,) = self.enclosed("`",) {
+			return Some(Span::Code(code,),);
+		}
+		if let Some(bold,) = self.enclosed("**",) {
+			return Some(Span::Bold(bold,),);
+		}
+
+		// plain text runs to the next marker which opens a span. a marker
+		// which does not is part of the text
+		let mut end = 1;
+		while end < self.rest.len() {
+			let mut probe = Spans { rest: &self.rest[end..], };
+			let opens = probe.enclosed("`",).is_some()
+				|| probe.enclosed("**",).is_some();
+			if opens {
+				break;
+			}
+			end += self.rest[end..].chars().next().map_or(1, char::len_utf8,);
+		}
+		let (plain, rest,) = self.rest.split_at(end,);
+		self.rest = rest;
+		Some(Span::Plain(plain,),)
+	}
+}
+
+/// Renders `src` to `out`
+///
+/// Headings of level 1 and 2 are underlined with `=` and `-`, and bullets
+/// are indented by two spaces per level and marked with `*`. Markers of code
+/// spans and bold text are dropped and their style is set instead.
+pub fn render(
+	src: &str,
+	out: &mut (impl StyledWrite + ?Sized),
+) -> fmt::Result {
+	for block in blocks(src,) {
+		match block {
+			Block::Heading { level, text, } => {
+				out.set_style(Element::Heading(level,).style(),)?;
+				out.write_str(text,)?;
+				out.set_style(Style::PLAIN,)?;
+				out.write_char('\n',)?;
+				let underline = match level {
+					1 => Some('=',),
+					2 => Some('-',),
+					_ => None,
+				};
+				if let Some(c,) = underline {
+					for _ in text.chars() {
+						out.write_char(c,)?;
+					}
+					out.write_char('\n',)?;
+				}
+			},
+			Block::Bullet { depth, text, } => {
+				for _ in 0..=depth {
+					out.write_str("  ",)?;
+				}
+				out.set_style(Element::Bullet.style(),)?;
+				out.write_str("* ",)?;
+				render_spans(text, out,)?;
+			},
+			Block::Line(text,) => render_spans(text, out,)?,
+			Block::Blank => out.write_char('\n',)?,
+		}
+	}
+	Ok((),)
+}
+
+/// Writes spans of `text` followed by a newline
+fn render_spans(
+	text: &str,
+	out: &mut (impl StyledWrite + ?Sized),
+) -> fmt::Result {
+	for span in spans(text,) {
+		let (element, text,) = match span {
+			Span::Plain(t,) => (Element::Plain, t,),
+			Span::Code(t,) => (Element::Code, t,),
+			Span::Bold(t,) => (Element::Bold, t,),
+		};
+		out.set_style(element.style(),)?;
+		out.write_str(text,)?;
+	}
+	out.set_style(Style::PLAIN,)?;
+	out.write_char('\n',)
+}
@@ -0,0 +1,604 @@
+//! # Path Module
+//!
+//! This module handles paths without a heap, for the loader on the EFI system
+//! partition, the kernel VFS and paths in configuration files.
+//!
+//! - [`PathN`]: Borrowed path, like `std::path::Path`
+//! - [`PathBufN`]: Path in an inline buffer of `N` bytes
+//!
+//! ## Separators
+//!
+//! Both `/` and `\` separate components, as UEFI uses backslashes and
+//! everything else slashes. [`PathN::normalize`] writes `/`, and
+//! [`PathBufN::set_separator`] switches a path to the other one.
+//!
+//! ## Normalization
+//!
+//! [`PathN::normalize`] drops empty and `.` components and resolves `..`
+//! against the preceding component. `..` at the root of an absolute path
+//! stays at the root, and leading `..` of a relative path are kept.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use oso_no_std_shared::path::PathBufN;
+//! use oso_no_std_shared::path::PathN;
+//! use oso_no_std_shared::path::Separator;
+//!
+//! let path = PathN::new("\\EFI\\.\\oso\\..\\BOOT\\bootaa64.efi",);
+//! let mut path = path.normalize::<64,>().unwrap();
+//! assert_eq!(path.as_str(), "/EFI/BOOT/bootaa64.efi");
+//! assert_eq!(path.file_stem(), Some("bootaa64"));
+//! assert_eq!(path.extension(), Some("efi"));
+//!
+//! path.set_separator(Separator::Backslash,);
+//! assert_eq!(path.as_str(), "\\EFI\\BOOT\\bootaa64.efi");
+//!
+//! let cfg = PathN::new("/boot",).join::<16,>("cfg",).unwrap();
+//! assert_eq!(cfg.as_str(), "/boot/cfg");
+//! assert!(PathBufN::<4,>::try_from("/boot",).is_err());
+//! ```
+
+use core::fmt;
+use core::ops::Deref;
+use oso_error::parser::PathError;
+
+/// Character written between components
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq,)]
+pub enum Separator {
+	#[default]
+	Slash,
+	/// used by UEFI
+	Backslash,
+}
+
+impl Separator {
+	pub const fn as_char(&self,) -> char {
+		match self {
+			Self::Slash => '/',
+			Self::Backslash => '\\',
+		}
+	}
+}
+
+/// Whether `c` separates components
+pub const fn is_separator(c: char,) -> bool {
+	c == '/' || c == '\\'
+}
+
+/// Part of a path
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum Component<'a,> {
+	/// leading separator of an absolute path
+	Root,
+	/// `.`
+	CurDir,
+	/// `..`
+	ParentDir,
+	Normal(&'a str,),
+}
+
+impl<'a,> Component<'a,> {
+	pub const fn as_str(&self,) -> &'a str {
+		match self {
+			Self::Root => "/",
+			Self::CurDir => ".",
+			Self::ParentDir => "..",
+			Self::Normal(name,) => name,
+		}
+	}
+}
+
+/// Iterator over the [`Component`]s of a path, without empty components
+#[derive(Debug, Clone,)]
+pub struct Components<'a,> {
+	rest:    &'a str,
+	/// whether [`Component::Root`] is still to be returned
+	at_root: bool,
+}
+
+impl<'a,> Iterator for Components<'a,> {
+	type Item = Component<'a,>;
+
+	fn next(&mut self,) -> Option<Self::Item,> {
+		if self.at_root {
+			self.at_root = false;
+			return Some(Component::Root,);
+		}
+		self.rest = self.rest.trim_start_matches(is_separator,);
+		if self.rest.is_empty() {
+			return None;
+		}
+		let end = self.rest.find(is_separator,).unwrap_or(self.rest.len(),);
+		let (name, rest,) = self.rest.split_at(end,);
+		self.rest = rest;
+		Some(match name {
+			"." => Component::CurDir,
+			".." => Component::ParentDir,
+			name => Component::Normal(name,),
+		},)
+	}
+}
+
+/// Borrowed path
+#[derive(PartialEq, Eq,)]
+#[repr(transparent)]
+pub struct PathN {
+	inner: str,
+}
+
+impl PathN {
+	pub fn new<S: AsRef<str,> + ?Sized,>(s: &S,) -> &Self {
+		// SAFETY: `PathN` is a transparent wrapper of `str`
+		unsafe { &*(s.as_ref() as *const str as *const Self) }
+	}
+
+	pub const fn as_str(&self,) -> &str {
+		&self.inner
+	}
+
+	/// Whether the path starts with a separator
+	pub fn is_absolute(&self,) -> bool {
+		self.inner.starts_with(is_separator,)
+	}
+
+	pub fn components(&self,) -> Components<'_,> {
+		Components { rest: &self.inner, at_root: self.is_absolute(), }
+	}
+
+	/// Last component if it is [`Component::Normal`]
+	pub fn file_name(&self,) -> Option<&str,> {
+		match self.components().last()? {
+			Component::Normal(name,) => Some(name,),
+			_ => None,
+		}
+	}
+
+	/// [`file_name`](Self::file_name) without its extension
+	pub fn file_stem(&self,) -> Option<&str,> {
+		let name = self.file_name()?;
+		Some(split_extension(name,).0,)
+	}
+
+	/// Part of the file name after its last `.`. A leading `.` as in
+	/// `.hidden` does not start an extension
+	pub fn extension(&self,) -> Option<&str,> {
+		split_extension(self.file_name()?,).1
+	}
+
+	/// Whether the extension is `ext`, ignoring ASCII case as FAT does
+	pub fn has_extension(&self, ext: &str,) -> bool {
+		self.extension().is_some_and(|e| e.eq_ignore_ascii_case(ext,),)
+	}
+
+	/// Path without its last component. `None` for the root and for paths
+	/// of a single relative component
+	pub fn parent(&self,) -> Option<&Self,> {
+		let trimmed = self.inner.trim_end_matches(is_separator,);
+		let end = trimmed.rfind(is_separator,)?;
+		let parent = trimmed[..end].trim_end_matches(is_separator,);
+		if parent.is_empty() {
+			// the separator at `end` is the root
+			Some(Self::new(&trimmed[..1],),)
+		} else {
+			Some(Self::new(parent,),)
+		}
+	}
+
+	/// Whether the components of `base` are the first ones of this path
+	pub fn starts_with(&self, base: impl AsRef<PathN,>,) -> bool {
+		self.strip_prefix(base,).is_some()
+	}
+
+	/// Rest of the path after the components of `base`
+	pub fn strip_prefix(&self, base: impl AsRef<PathN,>,) -> Option<&Self,> {
+		let mut rest = self.components();
+		for component in base.as_ref().components() {
+			if rest.next() != Some(component,) {
+				return None;
+			}
+		}
+		Some(Self::new(rest.rest.trim_start_matches(is_separator,),),)
+	}
+
+	/// `other` appended to this path, see [`PathBufN::push`]
+	pub fn join<const N: usize,>(
+		&self,
+		other: impl AsRef<PathN,>,
+	) -> Result<PathBufN<N,>, PathError,> {
+		let other = other.as_ref();
+		// an absolute `other` needs no room for this path
+		let base = if other.is_absolute() { "" } else { self.as_str() };
+		let mut path = PathBufN::try_from(base,)?;
+		path.push(other,)?;
+		Ok(path,)
+	}
+
+	/// Path without empty and `.` components and with `..` resolved,
+	/// separated by `/`. A relative path which resolves to nothing is `.`
+	pub fn normalize<const N: usize,>(
+		&self,
+	) -> Result<PathBufN<N,>, PathError,> {
+		let mut path = PathBufN::new();
+		// normal components in `path` which a `..` can remove
+		let mut depth = 0usize;
+		for component in self.components() {
+			match component {
+				Component::Root => path.push_str("/",)?,
+				Component::CurDir => {},
+				Component::ParentDir if depth > 0 => {
+					// a single relative component has no parent
+					if !path.pop() {
+						path.clear();
+					}
+					depth -= 1;
+				},
+				Component::ParentDir if path.is_absolute() => {},
+				Component::ParentDir => path.push_component("..",)?,
+				Component::Normal(name,) => {
+					path.push_component(name,)?;
+					depth += 1;
+				},
+			}
+		}
+		if path.is_empty() {
+			path.push_str(".",)?;
+		}
+		Ok(path,)
+	}
+}
+
+impl AsRef<PathN,> for PathN {
+	fn as_ref(&self,) -> &PathN {
+		self
+	}
+}
+
+impl AsRef<PathN,> for str {
+	fn as_ref(&self,) -> &PathN {
+		PathN::new(self,)
+	}
+}
+
+impl AsRef<str,> for PathN {
+	fn as_ref(&self,) -> &str {
+		&self.inner
+	}
+}
+
+impl fmt::Display for PathN {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		f.write_str(&self.inner,)
+	}
+}
+
+impl fmt::Debug for PathN {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		fmt::Debug::fmt(&self.inner, f,)
+	}
+}
+
+/// Path stored in an inline buffer of `N` bytes
+///
+/// Operations which would not fit fail with [`PathError::TooLong`] and leave
+/// the path unchanged.
+#[derive(Clone, Copy,)]
+pub struct PathBufN<const N: usize,> {
+	buf: [u8; N],
+	len: usize,
+}
+
+impl<const N: usize,> PathBufN<N,> {
+	pub const fn new() -> Self {
+		Self { buf: [0; N], len: 0, }
+	}
+
+	pub fn as_path(&self,) -> &PathN {
+		// SAFETY: only whole `str`s and ASCII separators are written
+		let s =
+			unsafe { core::str::from_utf8_unchecked(&self.buf[..self.len],) };
+		PathN::new(s,)
+	}
+
+	pub const fn capacity(&self,) -> usize {
+		N
+	}
+
+	pub const fn is_empty(&self,) -> bool {
+		self.len == 0
+	}
+
+	pub fn clear(&mut self,) {
+		self.len = 0;
+	}
+
+	/// Separator of the path: the first one it has, [`Separator::Slash`] if
+	/// it has none
+	pub fn separator(&self,) -> Separator {
+		let bytes = &self.buf[..self.len];
+		match bytes.iter().find(|b| is_separator(**b as char,),) {
+			Some(b'\\',) => Separator::Backslash,
+			_ => Separator::Slash,
+		}
+	}
+
+	/// Replaces every separator with `separator`
+	pub fn set_separator(&mut self, separator: Separator,) {
+		for byte in &mut self.buf[..self.len] {
+			if is_separator(*byte as char,) {
+				*byte = separator.as_char() as u8;
+			}
+		}
+	}
+
+	/// Appends `path` after a separator. An absolute `path` replaces this one
+	pub fn push(
+		&mut self,
+		path: impl AsRef<PathN,>,
+	) -> Result<(), PathError,> {
+		let path = path.as_ref();
+		if path.is_absolute() {
+			let mut new = Self::new();
+			new.push_str(path.as_str(),)?;
+			*self = new;
+			return Ok((),);
+		}
+		self.push_component(path.as_str(),)
+	}
+
+	/// Removes the last component. Returns `false` if there is no parent
+	pub fn pop(&mut self,) -> bool {
+		match self.as_path().parent() {
+			Some(parent,) => {
+				self.len = parent.as_str().len();
+				true
+			},
+			None => false,
+		}
+	}
+
+	/// Replaces the extension of the file name with `ext`, or removes it if
+	/// `ext` is empty. Returns `Ok(false)` if there is no file name
+	pub fn set_extension(&mut self, ext: &str,) -> Result<bool, PathError,> {
+		let Some(stem,) = self.file_stem() else {
+			return Ok(false,);
+		};
+		// the stem is a part of the buffer
+		let end =
+			stem.as_ptr() as usize - self.buf.as_ptr() as usize + stem.len();
+		let mut new = *self;
+		new.len = end;
+		if !ext.is_empty() {
+			new.push_str(".",)?;
+			new.push_str(ext,)?;
+		}
+		*self = new;
+		Ok(true,)
+	}
+
+	/// Appends `s` after a separator unless the path is empty or ends with one
+	fn push_component(&mut self, s: &str,) -> Result<(), PathError,> {
+		let mut new = *self;
+		if !new.is_empty() && !new.as_str().ends_with(is_separator,) {
+			let mut buf = [0; 1];
+			let separator = self.separator().as_char().encode_utf8(&mut buf,);
+			new.push_str(separator,)?;
+		}
+		new.push_str(s,)?;
+		*self = new;
+		Ok((),)
+	}
+
+	fn push_str(&mut self, s: &str,) -> Result<(), PathError,> {
+		if s.contains('\0',) {
+			return Err(PathError::Nul,);
+		}
+		let end = self.len + s.len();
+		if end > N {
+			return Err(PathError::TooLong { capacity: N, },);
+		}
+		self.buf[self.len..end].copy_from_slice(s.as_bytes(),);
+		self.len = end;
+		Ok((),)
+	}
+}
+
+impl<const N: usize,> Default for PathBufN<N,> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<const N: usize,> TryFrom<&str,> for PathBufN<N,> {
+	type Error = PathError;
+
+	fn try_from(value: &str,) -> Result<Self, Self::Error,> {
+		let mut path = Self::new();
+		path.push_str(value,)?;
+		Ok(path,)
+	}
+}
+
+impl<const N: usize,> TryFrom<&PathN,> for PathBufN<N,> {
+	type Error = PathError;
+
+	fn try_from(value: &PathN,) -> Result<Self, Self::Error,> {
+		Self::try_from(value.as_str(),)
+	}
+}
+
+impl<const N: usize,> Deref for PathBufN<N,> {
+	type Target = PathN;
+
+	fn deref(&self,) -> &Self::Target {
+		self.as_path()
+	}
+}
+
+impl<const N: usize,> AsRef<PathN,> for PathBufN<N,> {
+	fn as_ref(&self,) -> &PathN {
+		self.as_path()
+	}
+}
+
+impl<const N: usize,> PartialEq for PathBufN<N,> {
+	fn eq(&self, other: &Self,) -> bool {
+		self.as_path() == other.as_path()
+	}
+}
+
+impl<const N: usize,> Eq for PathBufN<N,> {}
+
+impl<const N: usize,> fmt::Display for PathBufN<N,> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		fmt::Display::fmt(self.as_path(), f,)
+	}
+}
+
+impl<const N: usize,> fmt::Debug for PathBufN<N,> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		fmt::Debug::fmt(self.as_path(), f,)
+	}
+}
+
+/// Splits `name` at its last `.` which is not the first character
+fn split_extension(name: &str,) -> (&str, Option<&str,>,) {
+	match name.rfind('.',) {
+		Some(0,) | None => (name, None,),
+		Some(dot,) => (&name[..dot], Some(&name[dot + 1..],),),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn normalize(path: &str,) -> PathBufN<32,> {
+		PathN::new(path,).normalize().unwrap()
+	}
+
+	#[test]
+	fn test_components() {
+		let path = PathN::new("\\EFI//./oso\\..\\",);
+		let components = [
+			Component::Root,
+			Component::Normal("EFI",),
+			Component::CurDir,
+			Component::Normal("oso",),
+			Component::ParentDir,
+		];
+		assert!(path.components().eq(components));
+		assert!(PathN::new("").components().eq([]));
+		assert!(PathN::new("//").components().eq([Component::Root]));
+	}
+
+	#[test]
+	fn test_normalize_parent_dir() {
+		assert_eq!(normalize("/..").as_str(), "/");
+		assert_eq!(normalize("/a/../..").as_str(), "/");
+		assert_eq!(normalize("/../a/b/..").as_str(), "/a");
+		assert_eq!(normalize("../a").as_str(), "../a");
+		assert_eq!(normalize("../..").as_str(), "../..");
+		assert_eq!(normalize("a/../..").as_str(), "..");
+		assert_eq!(normalize("../a/../b").as_str(), "../b");
+		assert_eq!(normalize("a/b/../..").as_str(), ".");
+	}
+
+	#[test]
+	fn test_normalize_empty_and_cur_dir() {
+		assert_eq!(normalize("").as_str(), ".");
+		assert_eq!(normalize(".").as_str(), ".");
+		assert_eq!(normalize("./a//b/.").as_str(), "a/b");
+		assert_eq!(normalize("//a/").as_str(), "/a");
+		assert_eq!(normalize("/./").as_str(), "/");
+	}
+
+	#[test]
+	fn test_mixed_separators() {
+		assert_eq!(normalize("a\\b/c").as_str(), "a/b/c");
+		assert_eq!(normalize("\\EFI/BOOT\\").as_str(), "/EFI/BOOT");
+
+		let path = PathN::new("/EFI\\BOOT",);
+		assert!(path.starts_with("\\EFI/"));
+		assert_eq!(path.strip_prefix("\\EFI").map(PathN::as_str), Some("BOOT"));
+		assert!(!path.starts_with("/EF"));
+		assert_eq!(path.parent().map(PathN::as_str), Some("/EFI"));
+
+		// pushed components follow the first separator of the path
+		let mut path = PathBufN::<32,>::try_from("\\EFI",).unwrap();
+		path.push("oso/loader.cfg",).unwrap();
+		assert_eq!(path.as_str(), "\\EFI\\oso/loader.cfg");
+		assert_eq!(path.separator(), Separator::Backslash);
+		path.set_separator(Separator::Slash,);
+		assert_eq!(path.as_str(), "/EFI/oso/loader.cfg");
+	}
+
+	#[test]
+	fn test_parent() {
+		let parent = |path| PathN::new(path,).parent().map(PathN::as_str,);
+		assert_eq!(parent("/a"), Some("/"));
+		assert_eq!(parent("\\a\\b\\"), Some("\\a"));
+		assert_eq!(parent("a//b"), Some("a"));
+		assert_eq!(parent("/"), None);
+		assert_eq!(parent("a"), None);
+		assert_eq!(parent(""), None);
+	}
+
+	#[test]
+	fn test_join_and_normalize_overflow() {
+		let too_long = |capacity| Some(PathError::TooLong { capacity, },);
+		let boot = PathN::new("/boot",);
+		assert_eq!(boot.join::<8>("cfg").err(), too_long(8));
+		assert_eq!(boot.join::<9>("cfg").unwrap().as_str(), "/boot/cfg");
+		// an absolute path replaces the base
+		assert_eq!(boot.join::<4>("/efi").unwrap().as_str(), "/efi");
+		assert_eq!(PathN::new("/abc").normalize::<3>().err(), too_long(3));
+		assert_eq!(PathN::new("a/..").normalize::<0>().err(), too_long(0));
+		let path = PathN::new("/a/../b",).normalize::<2,>().unwrap();
+		assert_eq!(path.as_str(), "/b");
+
+		let mut path = PathBufN::<8,>::try_from("/boot",).unwrap();
+		assert_eq!(path.push("efi").err(), too_long(8));
+		assert_eq!(path.set_extension("efi").err(), too_long(8));
+		assert_eq!(path.push("a\0"), Err(PathError::Nul));
+		assert_eq!(path.as_str(), "/boot");
+		assert_eq!(path.capacity(), 8);
+	}
+
+	#[test]
+	fn test_file_stem_and_extension() {
+		let split = |path| {
+			let path = PathN::new(path,);
+			(path.file_stem(), path.extension(),)
+		};
+		assert_eq!(split("/EFI/bootaa64.efi"), (Some("bootaa64"), Some("efi")));
+		let archive = (Some("archive.tar",), Some("gz",),);
+		assert_eq!(split("a/archive.tar.gz"), archive);
+		assert_eq!(split(".bashrc"), (Some(".bashrc"), None));
+		assert_eq!(split("/.config.toml"), (Some(".config"), Some("toml")));
+		assert_eq!(split("README"), (Some("README"), None));
+		assert_eq!(split("name."), (Some("name"), Some("")));
+		assert_eq!(split("dir/"), (Some("dir"), None));
+		assert_eq!(split("a/.."), (None, None));
+		assert_eq!(split("/"), (None, None));
+		assert!(PathN::new("BOOTAA64.EFI").has_extension("efi"));
+		assert!(!PathN::new(".efi").has_extension("efi"));
+	}
+
+	#[test]
+	fn test_set_extension() {
+		let mut path = PathBufN::<16,>::try_from("/EFI/boot.efi",).unwrap();
+		assert_eq!(path.set_extension("cfg"), Ok(true));
+		assert_eq!(path.as_str(), "/EFI/boot.cfg");
+		assert_eq!(path.set_extension(""), Ok(true));
+		assert_eq!(path.as_str(), "/EFI/boot");
+
+		let mut path = PathBufN::<16,>::try_from(".bashrc",).unwrap();
+		assert_eq!(path.set_extension("bak"), Ok(true));
+		assert_eq!(path.as_str(), ".bashrc.bak");
+
+		let mut path = PathBufN::<16,>::try_from("/",).unwrap();
+		assert_eq!(path.set_extension("efi"), Ok(false));
+		assert_eq!(path.as_str(), "/");
+	}
+}
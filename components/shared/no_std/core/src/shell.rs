@@ -0,0 +1,14 @@
+//! # Shell Module
+//!
+//! This module provides building blocks of interactive shells which are
+//! shared by the UART debug shell and terminals drawn on the framebuffer.
+//!
+//! ## Submodules
+//!
+//! - `line_editor`: Line editing with history and command name completion
+//! - `replay`: Recordings of input and timer ticks, replayed deterministically
+//! - `script`: Commands of shell scripts with comments and conditionals
+
+pub mod line_editor;
+pub mod replay;
+pub mod script;
@@ -0,0 +1,386 @@
+//! # Line Editor
+//!
+//! [`LineEditor`] turns key presses into a command line. It supports cursor
+//! movement, backspace and delete, history navigation and tab completion of
+//! command names. It does no I/O by itself, so the UART shell and a graphical
+//! terminal share it: the UART shell decodes bytes with [`KeyDecoder`] and
+//! redraws with [`LineEditor::redraw`], a graphical terminal reads
+//! [`LineEditor::line`] and [`LineEditor::cursor`] instead.
+//!
+//! Buffers are fixed size. `N` is the capacity of a line in bytes and `H`
+//! the number of history entries. Only printable ASCII is accepted.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use oso_no_std_shared::shell::line_editor::Action;
+//! use oso_no_std_shared::shell::line_editor::Key;
+//! use oso_no_std_shared::shell::line_editor::KeyDecoder;
+//! use oso_no_std_shared::shell::line_editor::LineEditor;
+//!
+//! let mut editor = LineEditor::<64, 8,>::new(&["help", "mem", "reboot",],);
+//! let mut decoder = KeyDecoder::new();
+//! // complete `mem`, type `x-v`, go back and erase `x`
+//! for b in b"me\t-v\x1b[D\x1b[Dx\x7f\r\n" {
+//! 	if let Some(key,) = decoder.feed(*b,)
+//! 		&& editor.feed(key,) == Action::Submit
+//! 	{
+//! 		assert_eq!(editor.line(), "mem -v");
+//! 	}
+//! }
+//!
+//! editor.feed(Key::Up,);
+//! assert_eq!(editor.line(), "mem -v");
+//! ```
+
+use core::fmt;
+
+/// Input of [`LineEditor`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum Key {
+	/// Printable ASCII character
+	Char(u8,),
+	Backspace,
+	Delete,
+	Left,
+	Right,
+	Home,
+	End,
+	Up,
+	Down,
+	Tab,
+	Enter,
+}
+
+/// Result of [`LineEditor::feed`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum Action {
+	/// Nothing changed
+	None,
+	/// Line or cursor changed and needs to be redrawn
+	Redraw,
+	/// Tab completion is ambiguous. Candidates are listed by
+	/// [`LineEditor::completions`]
+	Candidates,
+	/// Enter was pressed. The line stays readable until the next key
+	Submit,
+}
+
+/// Decodes terminal input bytes into [`Key`]s
+///
+/// Understands `DEL` and `BS` as backspace, `CR`, `LF` and `CR LF` as enter,
+/// and the ANSI escape sequences of arrow, home, end and delete keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default,)]
+pub struct KeyDecoder {
+	state: Escape,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default,)]
+enum Escape {
+	#[default]
+	Ground,
+	/// After `CR`, so that `LF` of `CR LF` is ignored
+	AfterCr,
+	/// After `ESC`
+	Esc,
+	/// After `ESC [`
+	Csi,
+	/// After `ESC [ <digit>`, waiting for `~`
+	Param(u8,),
+}
+
+impl KeyDecoder {
+	pub const fn new() -> Self {
+		Self { state: Escape::Ground, }
+	}
+
+	/// Feeds a byte. Returns a key once a whole key is read
+	pub fn feed(&mut self, byte: u8,) -> Option<Key,> {
+		let state = match self.state {
+			Escape::AfterCr if byte == b'\n' => {
+				self.state = Escape::Ground;
+				return None;
+			},
+			Escape::AfterCr => Escape::Ground,
+			state => state,
+		};
+		let (state, key,) = match (state, byte,) {
+			(Escape::Ground, 0x1b,) => (Escape::Esc, None,),
+			(Escape::Ground, b'\r',) => (Escape::AfterCr, Some(Key::Enter,),),
+			(Escape::Ground, b'\n',) => (Escape::Ground, Some(Key::Enter,),),
+			(Escape::Ground, 0x7f | 0x08,) => {
+				(Escape::Ground, Some(Key::Backspace,),)
+			},
+			(Escape::Ground, b'\t',) => (Escape::Ground, Some(Key::Tab,),),
+			(Escape::Ground, b' '..=b'~',) => {
+				(Escape::Ground, Some(Key::Char(byte,),),)
+			},
+			(Escape::Esc, b'[' | b'O',) => (Escape::Csi, None,),
+			(Escape::Csi, b'A',) => (Escape::Ground, Some(Key::Up,),),
+			(Escape::Csi, b'B',) => (Escape::Ground, Some(Key::Down,),),
+			(Escape::Csi, b'C',) => (Escape::Ground, Some(Key::Right,),),
+			(Escape::Csi, b'D',) => (Escape::Ground, Some(Key::Left,),),
+			(Escape::Csi, b'H',) => (Escape::Ground, Some(Key::Home,),),
+			(Escape::Csi, b'F',) => (Escape::Ground, Some(Key::End,),),
+			(Escape::Csi, b'0'..=b'9',) => (Escape::Param(byte,), None,),
+			(Escape::Param(p,), b'~',) => {
+				let key = match p {
+					b'1' | b'7' => Some(Key::Home,),
+					b'3' => Some(Key::Delete,),
+					b'4' | b'8' => Some(Key::End,),
+					_ => None,
+				};
+				(Escape::Ground, key,)
+			},
+			_ => (Escape::Ground, None,),
+		};
+		self.state = state;
+		key
+	}
+}
+
+/// Line editor with history and completion
+///
+/// See the [module documentation](self) for details.
+#[derive(Debug, Clone,)]
+pub struct LineEditor<'c, const N: usize, const H: usize,> {
+	buf:       [u8; N],
+	len:       usize,
+	cursor:    usize,
+	/// Whether the line was submitted and is cleared by the next key
+	submitted: bool,
+	history:   History<N, H,>,
+	/// Index of the history entry shown, counted from the newest
+	browsing:  Option<usize,>,
+	/// Line being edited before history navigation started
+	draft:     ([u8; N], usize,),
+	commands:  &'c [&'c str],
+}
+
+impl<'c, const N: usize, const H: usize,> LineEditor<'c, N, H,> {
+	/// Editor completing names of `commands`
+	pub const fn new(commands: &'c [&'c str],) -> Self {
+		Self {
+			buf: [0; N],
+			len: 0,
+			cursor: 0,
+			submitted: false,
+			history: History::new(),
+			browsing: None,
+			draft: ([0; N], 0,),
+			commands,
+		}
+	}
+
+	/// Current line
+	pub fn line(&self,) -> &str {
+		// only printable ASCII is ever inserted
+		core::str::from_utf8(&self.buf[..self.len],).unwrap_or_default()
+	}
+
+	/// Cursor position in bytes
+	pub fn cursor(&self,) -> usize {
+		self.cursor
+	}
+
+	/// Applies `key`
+	pub fn feed(&mut self, key: Key,) -> Action {
+		if self.submitted {
+			self.submitted = false;
+			self.set_line(&[],);
+		}
+
+		match key {
+			Key::Char(c,) => self.insert(c,),
+			Key::Backspace if self.cursor > 0 => {
+				self.cursor -= 1;
+				self.remove_at_cursor();
+				Action::Redraw
+			},
+			Key::Delete if self.cursor < self.len => {
+				self.remove_at_cursor();
+				Action::Redraw
+			},
+			Key::Left if self.cursor > 0 => {
+				self.cursor -= 1;
+				Action::Redraw
+			},
+			Key::Right if self.cursor < self.len => {
+				self.cursor += 1;
+				Action::Redraw
+			},
+			Key::Home => self.move_to(0,),
+			Key::End => self.move_to(self.len,),
+			Key::Up => self.browse(self.browsing.map_or(0, |i| i + 1,),),
+			Key::Down => match self.browsing {
+				Some(0,) => {
+					self.browsing = None;
+					let (draft, len,) = self.draft;
+					self.set_line(&draft[..len],);
+					Action::Redraw
+				},
+				Some(i,) => self.browse(i - 1,),
+				None => Action::None,
+			},
+			Key::Tab => self.complete(),
+			Key::Enter => {
+				self.history.push(&self.buf[..self.len],);
+				self.browsing = None;
+				self.submitted = true;
+				Action::Submit
+			},
+			_ => Action::None,
+		}
+	}
+
+	/// Commands whose name starts with the word before the cursor
+	pub fn completions(&self,) -> impl Iterator<Item = &'c str,> + '_ {
+		let word = self.word_before_cursor();
+		self.commands.iter().copied().filter(move |c| c.starts_with(word,),)
+	}
+
+	/// Redraws the line on an ANSI terminal after `prompt`
+	pub fn redraw(
+		&self,
+		out: &mut impl fmt::Write,
+		prompt: &str,
+	) -> fmt::Result {
+		write!(out, "\r\x1b[K{prompt}{}", self.line())?;
+		let back = self.len - self.cursor;
+		if back > 0 {
+			write!(out, "\x1b[{back}D")?;
+		}
+		Ok((),)
+	}
+
+	fn insert(&mut self, c: u8,) -> Action {
+		if self.len == N || !(b' '..=b'~').contains(&c,) {
+			return Action::None;
+		}
+		self.buf.copy_within(self.cursor..self.len, self.cursor + 1,);
+		self.buf[self.cursor] = c;
+		self.len += 1;
+		self.cursor += 1;
+		Action::Redraw
+	}
+
+	fn remove_at_cursor(&mut self,) {
+		self.buf.copy_within(self.cursor + 1..self.len, self.cursor,);
+		self.len -= 1;
+	}
+
+	fn move_to(&mut self, cursor: usize,) -> Action {
+		if cursor == self.cursor {
+			return Action::None;
+		}
+		self.cursor = cursor;
+		Action::Redraw
+	}
+
+	fn set_line(&mut self, line: &[u8],) {
+		let len = line.len().min(N,);
+		self.buf[..len].copy_from_slice(&line[..len],);
+		self.len = len;
+		self.cursor = len;
+	}
+
+	/// Shows the `index`th newest history entry
+	fn browse(&mut self, index: usize,) -> Action {
+		let Some(entry,) = self.history.nth_newest(index,) else {
+			return Action::None;
+		};
+		let mut line = [0; N];
+		let len = entry.len();
+		line[..len].copy_from_slice(entry,);
+		if self.browsing.is_none() {
+			self.draft.0 = self.buf;
+			self.draft.1 = self.len;
+		}
+		self.browsing = Some(index,);
+		self.set_line(&line[..len],);
+		Action::Redraw
+	}
+
+	/// Completes the command name under the cursor up to the longest common
+	/// prefix of the candidates
+	fn complete(&mut self,) -> Action {
+		let word_len = self.word_before_cursor().len();
+		if word_len != self.cursor {
+			// only the command name, which is the first word, is completed
+			return Action::None;
+		}
+
+		let mut candidates = self.completions();
+		let Some(first,) = candidates.next() else {
+			return Action::None;
+		};
+		let mut common = first.len();
+		let mut unique = true;
+		for other in candidates {
+			unique = false;
+			common = first
+				.bytes()
+				.zip(other.bytes(),)
+				.take(common,)
+				.take_while(|(a, b,)| a == b,)
+				.count();
+		}
+
+		let mut action = Action::None;
+		for c in first[word_len..common].bytes() {
+			action = self.insert(c,);
+		}
+		if unique && self.cursor == self.len {
+			action = self.insert(b' ',);
+		}
+		if unique || action == Action::Redraw {
+			action
+		} else {
+			Action::Candidates
+		}
+	}
+
+	fn word_before_cursor(&self,) -> &str {
+		let head = &self.line()[..self.cursor];
+		head.rsplit(' ',).next().unwrap_or_default()
+	}
+}
+
+/// Ring of submitted lines
+#[derive(Debug, Clone,)]
+struct History<const N: usize, const H: usize,> {
+	entries: [([u8; N], usize,); H],
+	/// Number of stored entries
+	count:   usize,
+	/// Slot written next
+	next:    usize,
+}
+
+impl<const N: usize, const H: usize,> History<N, H,> {
+	const fn new() -> Self {
+		Self { entries: [([0; N], 0,); H], count: 0, next: 0, }
+	}
+
+	/// Stores `line` unless it is blank or repeats the newest entry
+	fn push(&mut self, line: &[u8],) {
+		if H == 0 || line.iter().all(|b| *b == b' ',) {
+			return;
+		}
+		if self.nth_newest(0,) == Some(line,) {
+			return;
+		}
+		let slot = &mut self.entries[self.next];
+		slot.0[..line.len()].copy_from_slice(line,);
+		slot.1 = line.len();
+		self.next = (self.next + 1) % H;
+		self.count = (self.count + 1).min(H,);
+	}
+
+	fn nth_newest(&self, n: usize,) -> Option<&[u8],> {
+		if n >= self.count {
+			return None;
+		}
+		let (entry, len,) = &self.entries[(self.next + H - 1 - n) % H];
+		Some(&entry[..*len],)
+	}
+}
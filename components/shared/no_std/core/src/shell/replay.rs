@@ -0,0 +1,339 @@
+//! # Input Recordings
+//!
+//! A recording keeps the input bytes a shell read and the timer ticks in
+//! between, in the order they happened. The kernel records them while it
+//! runs under QEMU, and the simulator replays them one tick per frame, so a
+//! bug depending on when input arrives relative to the timer is reproduced
+//! on every run instead of once in a while.
+//!
+//! ## Format
+//!
+//! A recording starts with the magic `OSOR` and a version byte, followed by
+//! records of two bytes, a tag and a value:
+//!
+//! | tag | record | value                                                |
+//! |-----|--------|------------------------------------------------------|
+//! | 1   | ticks  | number of consecutive timer ticks, `1` to `255`      |
+//! | 2   | input  | byte read by the shell                               |
+//! | 0   | end    | none. CRC-32 (IEEE) of every byte before it follows  |
+//!
+//! The checksum is little endian. Recordings are written as hex lines
+//! between [`BEGIN_MARKER`] and [`END_MARKER`], like trace dumps, and
+//! [`find_hex`] reads them back from a serial log.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use oso_no_std_shared::shell::replay::Encoder;
+//! use oso_no_std_shared::shell::replay::Event;
+//! use oso_no_std_shared::shell::replay::Recording;
+//!
+//! let mut buf = [0; 32];
+//! let mut len = 0;
+//! let mut encoder = Encoder::new(|bytes: &[u8]| {
+//! 	buf[len..len + bytes.len()].copy_from_slice(bytes,);
+//! 	len += bytes.len();
+//! },);
+//! for event in [Event::Tick, Event::Tick, Event::Input(b'l',),] {
+//! 	encoder.push(event,);
+//! }
+//! encoder.finish();
+//!
+//! let recording = Recording::parse(&buf[..len],).unwrap();
+//! assert_eq!(recording.ticks(), 2);
+//! assert_eq!(recording.events().last(), Some(Event::Input(b'l')));
+//! ```
+
+use crate::data::crc32;
+use oso_error::Rslt;
+use oso_error::oso_err;
+use oso_error::parser::ReplayError;
+
+/// First bytes of every recording
+pub const MAGIC: [u8; 4] = *b"OSOR";
+/// Version of the format written by [`Encoder`]
+pub const VERSION: u8 = 1;
+/// Line before a hex encoded recording
+pub const BEGIN_MARKER: &str = "-----BEGIN OSO REPLAY-----";
+/// Line after a hex encoded recording
+pub const END_MARKER: &str = "-----END OSO REPLAY-----";
+
+const TAG_END: u8 = 0;
+const TAG_TICKS: u8 = 1;
+const TAG_INPUT: u8 = 2;
+/// magic and version
+const HEADER: usize = MAGIC.len() + 1;
+
+/// What happened next
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum Event {
+	/// The timer interrupt was taken
+	Tick,
+	/// The shell read a byte
+	Input(u8,),
+}
+
+/// Writes events as a recording, merging runs of ticks into one record
+pub struct Encoder<W: FnMut(&[u8],),> {
+	out:   W,
+	crc:   u32,
+	/// ticks not written yet
+	ticks: u8,
+}
+
+impl<W: FnMut(&[u8],),> Encoder<W,> {
+	/// Writes the header to `out`
+	pub fn new(out: W,) -> Self {
+		let mut this = Self { out, crc: !0, ticks: 0, };
+		this.emit(&MAGIC,);
+		this.emit(&[VERSION,],);
+		this
+	}
+
+	pub fn push(&mut self, event: Event,) {
+		match event {
+			Event::Tick => {
+				if self.ticks == u8::MAX {
+					self.flush_ticks();
+				}
+				self.ticks += 1;
+			},
+			Event::Input(byte,) => {
+				self.flush_ticks();
+				self.emit(&[TAG_INPUT, byte,],);
+			},
+		}
+	}
+
+	/// Writes the end record
+	pub fn finish(mut self,) {
+		self.flush_ticks();
+		let crc = !self.crc;
+		(self.out)(&[TAG_END,],);
+		(self.out)(&crc.to_le_bytes(),);
+	}
+
+	fn flush_ticks(&mut self,) {
+		if self.ticks != 0 {
+			self.emit(&[TAG_TICKS, self.ticks,],);
+			self.ticks = 0;
+		}
+	}
+
+	fn emit(&mut self, bytes: &[u8],) {
+		self.crc = crc32::update(self.crc, bytes,);
+		(self.out)(bytes,);
+	}
+}
+
+/// Recording whose header, records and checksum were checked
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct Recording<'a,> {
+	/// records before the end record
+	records: &'a [u8],
+}
+
+impl<'a,> Recording<'a,> {
+	/// Checks the recording at the start of `bytes`. Bytes after its end
+	/// record are ignored
+	///
+	/// # Errors
+	///
+	/// - [`ReplayError::BadMagic`] if `bytes` does not start with [`MAGIC`]
+	/// - [`ReplayError::UnsupportedVersion`] if it is not [`VERSION`]
+	/// - [`ReplayError::UnknownTag`] at the first record of an unknown tag
+	/// - [`ReplayError::Truncated`] if the end record or checksum is missing
+	/// - [`ReplayError::BadChecksum`] if the checksum does not match
+	pub fn parse(bytes: &'a [u8],) -> Rslt<Self, ReplayError,> {
+		if !bytes.starts_with(&MAGIC,) {
+			return Err(oso_err!(ReplayError::BadMagic),);
+		}
+		match bytes.get(MAGIC.len(),) {
+			None => return Err(oso_err!(ReplayError::Truncated),),
+			Some(&VERSION,) => {},
+			Some(&version,) => {
+				return Err(oso_err!(ReplayError::UnsupportedVersion(version)),);
+			},
+		}
+
+		let mut at = HEADER;
+		loop {
+			match bytes.get(at..at + 2,) {
+				Some(&[TAG_TICKS | TAG_INPUT, _,],) => at += 2,
+				Some(&[TAG_END, _,],) => break,
+				Some(_,) => return Err(oso_err!(ReplayError::UnknownTag(at)),),
+				None if bytes.get(at,) == Some(&TAG_END,) => break,
+				None => return Err(oso_err!(ReplayError::Truncated),),
+			}
+		}
+		let Some(crc,) = bytes.get(at + 1..at + 5,) else {
+			return Err(oso_err!(ReplayError::Truncated),);
+		};
+		let crc = u32::from_le_bytes([crc[0], crc[1], crc[2], crc[3],],);
+		if crc32::checksum(&bytes[..at],) != crc {
+			return Err(oso_err!(ReplayError::BadChecksum),);
+		}
+		Ok(Self { records: &bytes[HEADER..at], },)
+	}
+
+	pub fn events(&self,) -> Events<'a,> {
+		Events { records: self.records, ticks: 0, }
+	}
+
+	/// Timer ticks of the recording
+	pub fn ticks(&self,) -> u64 {
+		self.records
+			.chunks_exact(2,)
+			.filter(|record| record[0] == TAG_TICKS,)
+			.map(|record| record[1] as u64,)
+			.sum()
+	}
+}
+
+/// Events of a [`Recording`], in the order they happened
+#[derive(Debug, Clone,)]
+pub struct Events<'a,> {
+	records: &'a [u8],
+	/// ticks left of the current ticks record
+	ticks:   u8,
+}
+
+impl Iterator for Events<'_,> {
+	type Item = Event;
+
+	fn next(&mut self,) -> Option<Event,> {
+		loop {
+			if self.ticks != 0 {
+				self.ticks -= 1;
+				return Some(Event::Tick,);
+			}
+			let (&[tag, value,], rest,) = self.records.split_first_chunk()?;
+			self.records = rest;
+			match tag {
+				TAG_TICKS => self.ticks = value,
+				_ => return Some(Event::Input(value,),),
+			}
+		}
+	}
+}
+
+/// Decodes the last recording written as hex lines between [`BEGIN_MARKER`]
+/// and [`END_MARKER`] in `log`, such as a serial log, into `buf`. Returns
+/// the part of `buf` holding it
+///
+/// Whitespace between the digits, like the `\r` of a serial console, is
+/// ignored.
+///
+/// # Errors
+///
+/// - [`ReplayError::NotFound`] if `log` has no [`BEGIN_MARKER`]
+/// - [`ReplayError::Truncated`] if no [`END_MARKER`] follows it
+/// - [`ReplayError::InvalidHex`] if the digits between are not hex bytes
+/// - [`ReplayError::TooLarge`] if the recording does not fit `buf`
+pub fn find_hex<'b,>(
+	log: &str,
+	buf: &'b mut [u8],
+) -> Rslt<&'b [u8], ReplayError,> {
+	let Some(begin,) = log.rfind(BEGIN_MARKER,) else {
+		return Err(oso_err!(ReplayError::NotFound),);
+	};
+	let body = &log[begin + BEGIN_MARKER.len()..];
+	let Some(end,) = body.find(END_MARKER,) else {
+		return Err(oso_err!(ReplayError::Truncated),);
+	};
+
+	let mut digits = body[..end].bytes().filter(|b| !b.is_ascii_whitespace(),);
+	let mut len = 0;
+	while let Some(high,) = digits.next() {
+		let byte = match (nibble(high,), digits.next().and_then(nibble,),) {
+			(Some(high,), Some(low,),) => high << 4 | low,
+			_ => return Err(oso_err!(ReplayError::InvalidHex),),
+		};
+		if let Some(slot,) = buf.get_mut(len,) {
+			*slot = byte;
+		}
+		len += 1;
+	}
+	if len > buf.len() {
+		return Err(oso_err!(ReplayError::TooLarge { len }),);
+	}
+	Ok(&buf[..len],)
+}
+
+fn nibble(digit: u8,) -> Option<u8,> {
+	(digit as char).to_digit(16,).map(|n| n as u8,)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// recording of `events` in `buf`
+	fn encode<'b,>(events: &[Event], buf: &'b mut [u8],) -> &'b [u8] {
+		let mut len = 0;
+		let mut encoder = Encoder::new(|bytes: &[u8]| {
+			buf[len..len + bytes.len()].copy_from_slice(bytes,);
+			len += bytes.len();
+		},);
+		for event in events {
+			encoder.push(*event,);
+		}
+		encoder.finish();
+		&buf[..len]
+	}
+
+	#[test]
+	fn test_round_trip() {
+		let mut events = [Event::Tick; 300];
+		events[0] = Event::Input(b'v',);
+		events[299] = Event::Input(b'\r',);
+		let mut buf = [0; 64];
+		let bytes = encode(&events, &mut buf,);
+		// 298 ticks take two records
+		assert_eq!(bytes.len(), HEADER + 4 * 2 + 5);
+
+		let recording = Recording::parse(bytes,).unwrap();
+		assert_eq!(recording.ticks(), 298);
+		assert!(recording.events().eq(events));
+	}
+
+	#[test]
+	fn test_parse_errors() {
+		let mut buf = [0; 32];
+		let bytes = encode(&[Event::Tick, Event::Input(b'a',),], &mut buf,);
+		let error = |bytes: &[u8]| Recording::parse(bytes,).unwrap_err().desc;
+
+		assert_eq!(error(b"OSOT\x01"), Some(ReplayError::BadMagic));
+		let cut = &bytes[..bytes.len() - 1];
+		assert_eq!(error(cut), Some(ReplayError::Truncated));
+		let mut copy = [0; 32];
+		copy[..bytes.len()].copy_from_slice(bytes,);
+		copy[4] = 2;
+		let len = bytes.len();
+		let version = ReplayError::UnsupportedVersion(2,);
+		assert_eq!(error(&copy[..len]), Some(version));
+		copy[4] = VERSION;
+		copy[HEADER + 3] = b'b';
+		assert_eq!(error(&copy[..len]), Some(ReplayError::BadChecksum));
+		copy[HEADER] = 9;
+		assert_eq!(error(&copy[..len]), Some(ReplayError::UnknownTag(HEADER)));
+	}
+
+	#[test]
+	fn test_find_hex_in_log() {
+		let log = "osh:1> record dump\r\n-----BEGIN OSO REPLAY-----\r\n\
+		           4f534f52 01\r\n0102 0000\r\n-----END OSO REPLAY-----\r\n";
+		let mut buf = [0; 16];
+		let bytes = find_hex(log, &mut buf,).unwrap();
+		assert_eq!(bytes, [0x4f, 0x53, 0x4f, 0x52, 1, 1, 2, 0, 0]);
+
+		let mut small = [0; 4];
+		let error = find_hex(log, &mut small,).unwrap_err().desc;
+		assert_eq!(error, Some(ReplayError::TooLarge { len: 9 }));
+		let cut = &log[..log.len() - 28];
+		let error = find_hex(cut, &mut buf,).unwrap_err().desc;
+		assert_eq!(error, Some(ReplayError::Truncated));
+		let error = find_hex("boot ok", &mut buf,).unwrap_err().desc;
+		assert_eq!(error, Some(ReplayError::NotFound));
+	}
+}
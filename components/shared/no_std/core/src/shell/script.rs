@@ -0,0 +1,265 @@
+//! # Shell Scripts
+//!
+//! [`commands`] runs through a shell script, such as `autoexec.osh`, and
+//! yields the command lines a shell executes. Conditions are decided on the
+//! way by looking up variables, e.g. of the boot environment, so the shell
+//! only sees the commands of the taken branches.
+//!
+//! ## Syntax
+//!
+//! - One command per line, split into words by whitespace
+//! - `#` at the start of a line or after whitespace starts a comment
+//! - `if <condition>` opens a block which ends at `end`, with an optional
+//!   `else` in between. Blocks nest up to [`MAX_DEPTH`] levels
+//! - A condition is `name` (set), `!name` (not set), `name == value` or
+//!   `name != value`. An unset variable equals no value
+//!
+//! `if`, `else` and `end` are keywords only as the first word of a line, and
+//! `else` and `end` only alone. Conditions of skipped blocks are checked for
+//! syntax but not evaluated.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use oso_no_std_shared::shell::script::commands;
+//!
+//! let script = "\
+//! ## run the scheduler test under QEMU only
+//! env build
+//! if boot.hypervisor != none
+//! 	idle poll on   # no exit latency
+//! 	if cmdline.test
+//! 		tasks
+//! 	end
+//! else
+//! 	idle stats
+//! end
+//! ";
+//! let lookup = |name: &str| match name {
+//! 	"boot.hypervisor" => Some("qemu",),
+//! 	"cmdline.test" => Some("",),
+//! 	_ => None,
+//! };
+//!
+//! let mut run = commands(script, lookup,);
+//! let first = run.next().unwrap().unwrap();
+//! assert_eq!((first.line, first.text,), (2, "env build",));
+//! let poll = run.next().unwrap().unwrap();
+//! assert_eq!(poll.args().collect::<Vec<_,>>(), ["idle", "poll", "on",]);
+//! assert_eq!(run.next().unwrap().unwrap().text, "tasks");
+//! assert!(run.next().is_none());
+//! ```
+
+use core::iter::FusedIterator;
+use core::str::Lines;
+use core::str::SplitWhitespace;
+use oso_error::parser::ScriptError;
+
+/// Deepest nesting of `if` blocks
+pub const MAX_DEPTH: usize = 16;
+
+/// Command line of a script
+///
+/// # Fields
+///
+/// * `line` - 1-based line number in the script
+/// * `text` - Command without comment and surrounding whitespace
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct Command<'a,> {
+	pub line: usize,
+	pub text: &'a str,
+}
+
+impl<'a,> Command<'a,> {
+	/// Words of the command, starting with its name
+	pub fn args(&self,) -> SplitWhitespace<'a,> {
+		self.text.split_whitespace()
+	}
+}
+
+/// Commands of the taken branches of `src`
+///
+/// `lookup` returns the value of a variable, or `None` if it is not set.
+/// The iterator ends after the first error.
+pub fn commands<'a, 'v, F,>(src: &'a str, lookup: F,) -> Commands<'a, F,>
+where F: FnMut(&str,) -> Option<&'v str,> {
+	Commands {
+		lines: src.lines(),
+		line: 0,
+		lookup,
+		blocks: [Block::TOP; MAX_DEPTH],
+		depth: 0,
+		done: false,
+	}
+}
+
+/// Iterator returned by [`commands`]
+pub struct Commands<'a, F,> {
+	lines:  Lines<'a,>,
+	line:   usize,
+	lookup: F,
+	blocks: [Block; MAX_DEPTH],
+	depth:  usize,
+	done:   bool,
+}
+
+/// open `if` block
+#[derive(Clone, Copy,)]
+struct Block {
+	/// line of the `if`
+	line:      usize,
+	/// whether the enclosing block runs
+	enclosing: bool,
+	condition: bool,
+	in_else:   bool,
+}
+
+impl Block {
+	const TOP: Self =
+		Self { line: 0, enclosing: true, condition: true, in_else: false, };
+
+	fn runs(&self,) -> bool {
+		self.enclosing && self.condition != self.in_else
+	}
+}
+
+impl<'a, 'v, F,> Commands<'a, F,>
+where F: FnMut(&str,) -> Option<&'v str,>
+{
+	/// whether commands at the current depth run
+	fn runs(&self,) -> bool {
+		self.depth == 0 || self.blocks[self.depth - 1].runs()
+	}
+
+	/// handles a keyword line. `None` if `text` is a command
+	fn keyword(&mut self, text: &str,) -> Option<Result<(), ScriptError,>,> {
+		let line = self.line;
+		let mut words = text.split_whitespace();
+		let r = match words.next()? {
+			"if" => self.open(words,),
+			"else" if text == "else" => match self.depth.checked_sub(1,) {
+				Some(top,) if !self.blocks[top].in_else => {
+					self.blocks[top].in_else = true;
+					Ok((),)
+				},
+				_ => Err(ScriptError::UnexpectedElse(line,),),
+			},
+			"end" if text == "end" => match self.depth.checked_sub(1,) {
+				Some(top,) => {
+					self.depth = top;
+					Ok((),)
+				},
+				None => Err(ScriptError::UnexpectedEnd(line,),),
+			},
+			_ => return None,
+		};
+		Some(r,)
+	}
+
+	/// opens the block of `if` followed by `words`
+	fn open(&mut self, words: SplitWhitespace,) -> Result<(), ScriptError,> {
+		let line = self.line;
+		let enclosing = self.runs();
+		let condition = self.condition(words, enclosing,)?;
+		let Some(block,) = self.blocks.get_mut(self.depth,) else {
+			return Err(ScriptError::TooDeep(line,),);
+		};
+		*block = Block { line, enclosing, condition, in_else: false, };
+		self.depth += 1;
+		Ok((),)
+	}
+
+	/// parses the condition of `if`, and evaluates it if `evaluate`
+	fn condition(
+		&mut self,
+		mut words: SplitWhitespace,
+		evaluate: bool,
+	) -> Result<bool, ScriptError,> {
+		let invalid = ScriptError::InvalidCondition(self.line,);
+		let words = [words.next(), words.next(), words.next(), words.next(),];
+		let (name, test,): (&str, Test,) = match words {
+			[Some(name,), None, None, None,] => match name.strip_prefix('!',) {
+				Some(name,) => (name, Test::Unset,),
+				None => (name, Test::Set,),
+			},
+			[Some(name,), Some("=="), Some(value,), None,] => {
+				(name, Test::Equal(value,),)
+			},
+			[Some(name,), Some("!="), Some(value,), None,] => {
+				(name, Test::NotEqual(value,),)
+			},
+			_ => return Err(invalid,),
+		};
+		if name.is_empty() || name.starts_with('!',) {
+			return Err(invalid,);
+		}
+		if !evaluate {
+			return Ok(false,);
+		}
+		let value = (self.lookup)(name,);
+		Ok(match test {
+			Test::Set => value.is_some(),
+			Test::Unset => value.is_none(),
+			Test::Equal(expected,) => value == Some(expected,),
+			Test::NotEqual(expected,) => value != Some(expected,),
+		},)
+	}
+}
+
+#[derive(Clone, Copy,)]
+enum Test<'a,> {
+	Set,
+	Unset,
+	Equal(&'a str,),
+	NotEqual(&'a str,),
+}
+
+impl<'a, 'v, F,> Iterator for Commands<'a, F,>
+where F: FnMut(&str,) -> Option<&'v str,>
+{
+	type Item = Result<Command<'a,>, ScriptError,>;
+
+	fn next(&mut self,) -> Option<Self::Item,> {
+		if self.done {
+			return None;
+		}
+		while let Some(raw,) = self.lines.next() {
+			self.line += 1;
+			let text = strip_comment(raw,).trim();
+			if text.is_empty() {
+				continue;
+			}
+			match self.keyword(text,) {
+				Some(Ok((),),) => continue,
+				Some(Err(e,),) => {
+					self.done = true;
+					return Some(Err(e,),);
+				},
+				None if self.runs() => {
+					return Some(Ok(Command { line: self.line, text, },),);
+				},
+				None => continue,
+			}
+		}
+		self.done = true;
+		let top = self.depth.checked_sub(1,)?;
+		Some(Err(ScriptError::UnterminatedIf(self.blocks[top].line,),),)
+	}
+}
+
+impl<'a, 'v, F,> FusedIterator for Commands<'a, F,> where
+	F: FnMut(&str,) -> Option<&'v str,>
+{
+}
+
+/// `line` up to a `#` at its start or after whitespace
+fn strip_comment(line: &str,) -> &str {
+	let mut after_space = true;
+	for (i, c,) in line.char_indices() {
+		if c == '#' && after_space {
+			return &line[..i];
+		}
+		after_space = c.is_whitespace();
+	}
+	line
+}
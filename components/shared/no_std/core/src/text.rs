@@ -0,0 +1,17 @@
+//! # Text Module
+//!
+//! This module provides string handling without a heap: decoding of strings
+//! which arrive as raw bytes from firmware, disks and binaries, and
+//! formatting into fixed size buffers.
+//!
+//! ## Submodules
+//!
+//! - `console`: Console trait and the global console of `print!`
+//! - `fixed`: Formatting into fixed size buffers without a heap
+//! - `glyph`: Glyphs of the console font expanded into pixels and cached
+//! - `utf8`: Strict UTF-8 decoding and byte string helpers
+
+pub mod console;
+pub mod fixed;
+pub mod glyph;
+pub mod utf8;
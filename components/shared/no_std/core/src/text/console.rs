@@ -0,0 +1,260 @@
+//! # Console Module
+//!
+//! This module defines [`Console`], text output shared by the loader and the
+//! kernel, and the global console written by [`print!`](crate::print) and
+//! [`println!`](crate::println).
+//!
+//! - The loader implements [`Console`] for the UEFI text output protocol
+//! - The kernel implements it for the framebuffer text buffer and the early
+//!   console
+//!
+//! ## Global Console
+//!
+//! A console becomes global with [`install`]. Output written while the
+//! global console is busy, e.g. by a panic in the middle of a line, is
+//! dropped instead of deadlocking, as is output before a console is
+//! installed. Errors of the console are ignored, as there is nowhere to
+//! report them. [`uninstall`] removes the console once it is gone, e.g. when
+//! the loader exits boot services.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use core::fmt;
+//! use oso_no_std_shared::println;
+//! use oso_no_std_shared::text::console;
+//! use oso_no_std_shared::text::console::Console;
+//! use oso_no_std_shared::text::console::ConsoleColor;
+//! use oso_no_std_shared::text::fixed::FixedString;
+//!
+//! struct Log(FixedString<32,>,);
+//!
+//! impl fmt::Write for Log {
+//! 	fn write_str(&mut self, s: &str,) -> fmt::Result {
+//! 		self.0.write_str(s,)
+//! 	}
+//! }
+//!
+//! impl Console for Log {
+//! 	fn set_color(
+//! 		&mut self,
+//! 		_: ConsoleColor,
+//! 		_: ConsoleColor,
+//! 	) -> fmt::Result {
+//! 		Ok((),)
+//! 	}
+//!
+//! 	fn clear(&mut self,) -> fmt::Result {
+//! 		self.0 = FixedString::new();
+//! 		Ok((),)
+//! 	}
+//!
+//! 	fn cursor(&self,) -> (usize, usize,) {
+//! 		(self.0.len(), 0,)
+//! 	}
+//!
+//! 	fn set_cursor(&mut self, _: usize, _: usize,) -> fmt::Result {
+//! 		Err(fmt::Error,)
+//! 	}
+//! }
+//!
+//! static mut LOG: Log = Log(FixedString::new(),);
+//!
+//! console::install(unsafe { &mut *core::ptr::addr_of_mut!(LOG) },);
+//! println!("booted in {} ms", 42);
+//! let cursor = console::with(|console| console.cursor(),);
+//! assert_eq!(cursor, Some((16, 0,)));
+//! ```
+
+use crate::color::Style;
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::Ordering;
+
+static CONSOLE: Global = Global::new();
+
+/// Prints formatted text to the global console
+///
+/// ```rust,ignore
+/// print!("loading {name} ... ");
+/// ```
+#[macro_export]
+macro_rules! print {
+	($($arg:tt)*) => {
+		$crate::text::console::print(format_args!($($arg)*));
+	};
+}
+
+/// Prints formatted text to the global console with a newline
+///
+/// ```rust,ignore
+/// println!();
+/// println!("Value: {}, Address: 0x{:x}", 42, 0x1000);
+/// ```
+#[macro_export]
+macro_rules! println {
+	() => {
+		$crate::print!("\n");
+	};
+	($($arg:tt)*) => {
+		$crate::print!("{}\n", format_args!($($arg)*));
+	};
+}
+
+/// One of the 16 colors every text console can show
+///
+/// Numbered as the colors of UEFI text attributes and VGA text mode.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum ConsoleColor {
+	Black,
+	Blue,
+	Green,
+	Cyan,
+	Red,
+	Magenta,
+	Brown,
+	LightGray,
+	DarkGray,
+	LightBlue,
+	LightGreen,
+	LightCyan,
+	LightRed,
+	LightMagenta,
+	Yellow,
+	White,
+}
+
+impl ConsoleColor {
+	/// Every color, in the order of their numbers
+	pub const ALL: [Self; 16] = [
+		Self::Black,
+		Self::Blue,
+		Self::Green,
+		Self::Cyan,
+		Self::Red,
+		Self::Magenta,
+		Self::Brown,
+		Self::LightGray,
+		Self::DarkGray,
+		Self::LightBlue,
+		Self::LightGreen,
+		Self::LightCyan,
+		Self::LightRed,
+		Self::LightMagenta,
+		Self::Yellow,
+		Self::White,
+	];
+
+	/// Bright variant of the first 8 colors, shown for bold text. Bright
+	/// colors are returned as is
+	pub const fn bright(&self,) -> Self {
+		Self::ALL[*self as usize | 8]
+	}
+
+	/// Foreground parameter of the ANSI SGR sequence showing the color
+	pub const fn ansi_foreground(&self,) -> u8 {
+		/// ANSI numbers of the first 8 colors
+		const ANSI: [u8; 8] = [0, 4, 2, 6, 1, 5, 3, 7,];
+		let color = *self as usize;
+		let base = if color < 8 { 30 } else { 90 };
+		base + ANSI[color % 8]
+	}
+
+	/// Background parameter of the ANSI SGR sequence showing the color
+	pub const fn ansi_background(&self,) -> u8 {
+		self.ansi_foreground() + 10
+	}
+}
+
+/// Text output of the loader and the kernel
+pub trait Console: fmt::Write {
+	/// Colors of text written until the next call
+	fn set_color(
+		&mut self,
+		foreground: ConsoleColor,
+		background: ConsoleColor,
+	) -> fmt::Result;
+
+	/// Clears the screen and moves the cursor to the top left
+	fn clear(&mut self,) -> fmt::Result;
+
+	/// `(column, row)` of the cursor
+	fn cursor(&self,) -> (usize, usize,);
+
+	/// Moves the cursor to `column` of `row`
+	fn set_cursor(&mut self, column: usize, row: usize,) -> fmt::Result;
+
+	/// Style of text written until the next call. Shown with the nearest
+	/// console colors by default
+	fn set_style(&mut self, style: Style,) -> fmt::Result {
+		let (foreground, background,) = style.console_colors();
+		self.set_color(foreground, background,)
+	}
+}
+
+/// Makes `console` the global console
+///
+/// Waits while the previous console is written.
+pub fn install(console: &'static mut dyn Console,) {
+	while !CONSOLE.lock() {
+		core::hint::spin_loop();
+	}
+	// SAFETY: the lock is held
+	unsafe { *CONSOLE.console.get() = Some(console,) };
+	CONSOLE.unlock();
+}
+
+/// Removes the global console, after which printing does nothing
+///
+/// Waits while the console is written.
+pub fn uninstall() {
+	while !CONSOLE.lock() {
+		core::hint::spin_loop();
+	}
+	// SAFETY: the lock is held
+	unsafe { *CONSOLE.console.get() = None };
+	CONSOLE.unlock();
+}
+
+/// Calls `f` with the global console. `None` if no console is installed or
+/// it is busy
+pub fn with<R,>(f: impl FnOnce(&mut dyn Console,) -> R,) -> Option<R,> {
+	if !CONSOLE.lock() {
+		return None;
+	}
+	// SAFETY: the lock is held
+	let r = unsafe { &mut *CONSOLE.console.get() }.as_deref_mut().map(f,);
+	CONSOLE.unlock();
+	r
+}
+
+/// Writes `args` to the global console. Used by [`print!`](crate::print)
+pub fn print(args: fmt::Arguments,) {
+	let _ = with(|console| console.write_fmt(args,),);
+}
+
+/// installed console behind a try-lock
+struct Global {
+	busy:    AtomicBool,
+	console: UnsafeCell<Option<&'static mut dyn Console,>,>,
+}
+
+// SAFETY: the console is only accessed while `busy` is held
+unsafe impl Sync for Global {}
+
+impl Global {
+	const fn new() -> Self {
+		Self { busy: AtomicBool::new(false,), console: UnsafeCell::new(None,), }
+	}
+
+	/// `true` if the lock was taken
+	fn lock(&self,) -> bool {
+		!self.busy.swap(true, Ordering::Acquire,)
+	}
+
+	fn unlock(&self,) {
+		self.busy.store(false, Ordering::Release,);
+	}
+}
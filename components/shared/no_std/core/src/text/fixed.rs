@@ -0,0 +1,180 @@
+//! # Fixed Buffer Module
+//!
+//! This module formats into buffers on the stack, for code which needs a
+//! formatted string but has no heap, such as panic messages, log lines and
+//! names of UEFI variables.
+//!
+//! - [`FixedString`]: String with an inline buffer of `N` bytes implementing
+//!   [`core::fmt::Write`]
+//! - [`write_to_buf!`](crate::write_to_buf): Formats into a borrowed
+//!   `&mut [u8]`
+//!
+//! Output which does not fit is cut at a character boundary and reported as
+//! truncated rather than silently dropped.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use core::fmt::Write;
+//! use oso_no_std_shared::text::fixed::FixedString;
+//! use oso_no_std_shared::write_to_buf;
+//!
+//! let mut line = FixedString::<16,>::new();
+//! write!(line, "[{:>5}] ok", "INFO").unwrap();
+//! assert_eq!(line.as_str(), "[ INFO] ok");
+//!
+//! assert!(write!(line, " and more text").is_err());
+//! assert!(line.is_truncated());
+//! assert_eq!(line.as_str(), "[ INFO] ok and m");
+//!
+//! let mut buf = [0; 8];
+//! assert_eq!(write_to_buf!(&mut buf, "Boot{:04X}", 1), Ok("Boot0001"));
+//! assert_eq!(write_to_buf!(&mut buf, "Boot{:04X}!", 1), Err("Boot0001"));
+//! ```
+
+use core::fmt;
+use core::ops::Deref;
+
+/// Formats `args` into `buf`
+///
+/// Returns the written string, or `Err` with the part which fit if the
+/// output was truncated. See [`write_to_buf!`](crate::write_to_buf).
+pub fn write_to_buf<'a,>(
+	buf: &'a mut [u8],
+	args: fmt::Arguments,
+) -> Result<&'a str, &'a str,> {
+	let mut writer = BufWriter { buf, len: 0, truncated: false, };
+	// errors only come from truncation, which is reported below
+	let _ = fmt::write(&mut writer, args,);
+	let BufWriter { buf, len, truncated, } = writer;
+	// SAFETY: `push` copies whole characters only
+	let s = unsafe { core::str::from_utf8_unchecked(&buf[..len],) };
+	if truncated { Err(s,) } else { Ok(s,) }
+}
+
+/// Formats into a `&mut [u8]` like `format!`
+///
+/// Expands to [`text::fixed::write_to_buf`](crate::text::fixed::write_to_buf)
+/// and returns `Result<&str, &str>`, which is `Err` with the part which fit if
+/// the output was truncated.
+///
+/// ```rust
+/// use oso_no_std_shared::write_to_buf;
+///
+/// let mut buf = [0; 32];
+/// let msg = write_to_buf!(&mut buf, "core {} halted", 3).unwrap();
+/// assert_eq!(msg, "core 3 halted");
+/// ```
+#[macro_export]
+macro_rules! write_to_buf {
+	($buf:expr, $($arg:tt)*) => {
+		$crate::text::fixed::write_to_buf($buf, format_args!($($arg)*))
+	};
+}
+
+/// String stored in an inline buffer of `N` bytes
+///
+/// Writing past the capacity keeps the part which fits, marks the string as
+/// truncated and returns [`fmt::Error`].
+#[derive(Clone, Copy,)]
+pub struct FixedString<const N: usize,> {
+	buf:       [u8; N],
+	len:       usize,
+	truncated: bool,
+}
+
+impl<const N: usize,> FixedString<N,> {
+	pub const fn new() -> Self {
+		Self { buf: [0; N], len: 0, truncated: false, }
+	}
+
+	pub fn as_str(&self,) -> &str {
+		// SAFETY: `push` copies whole characters only
+		unsafe { core::str::from_utf8_unchecked(&self.buf[..self.len],) }
+	}
+
+	pub const fn capacity(&self,) -> usize {
+		N
+	}
+
+	/// Whether any write did not fit
+	pub const fn is_truncated(&self,) -> bool {
+		self.truncated
+	}
+
+	/// Empties the string and resets truncation
+	pub fn clear(&mut self,) {
+		self.len = 0;
+		self.truncated = false;
+	}
+}
+
+impl<const N: usize,> Default for FixedString<N,> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<const N: usize,> fmt::Write for FixedString<N,> {
+	fn write_str(&mut self, s: &str,) -> fmt::Result {
+		let fits = push(&mut self.buf, &mut self.len, s,);
+		self.truncated |= !fits;
+		if fits { Ok((),) } else { Err(fmt::Error,) }
+	}
+}
+
+impl<const N: usize,> Deref for FixedString<N,> {
+	type Target = str;
+
+	fn deref(&self,) -> &Self::Target {
+		self.as_str()
+	}
+}
+
+impl<const N: usize,> PartialEq for FixedString<N,> {
+	fn eq(&self, other: &Self,) -> bool {
+		self.as_str() == other.as_str()
+	}
+}
+
+impl<const N: usize,> Eq for FixedString<N,> {}
+
+impl<const N: usize,> fmt::Display for FixedString<N,> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		f.write_str(self.as_str(),)
+	}
+}
+
+impl<const N: usize,> fmt::Debug for FixedString<N,> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		fmt::Debug::fmt(self.as_str(), f,)
+	}
+}
+
+/// [`fmt::Write`] over a borrowed buffer
+struct BufWriter<'a,> {
+	buf:       &'a mut [u8],
+	len:       usize,
+	truncated: bool,
+}
+
+impl fmt::Write for BufWriter<'_,> {
+	fn write_str(&mut self, s: &str,) -> fmt::Result {
+		let fits = push(self.buf, &mut self.len, s,);
+		self.truncated |= !fits;
+		if fits { Ok((),) } else { Err(fmt::Error,) }
+	}
+}
+
+/// Appends as much of `s` as fits into `buf` after `len` bytes, cutting at a
+/// character boundary. Returns whether all of `s` fit
+fn push(buf: &mut [u8], len: &mut usize, s: &str,) -> bool {
+	let room = buf.len() - *len;
+	let mut n = s.len().min(room,);
+	while !s.is_char_boundary(n,) {
+		n -= 1;
+	}
+	buf[*len..*len + n].copy_from_slice(&s.as_bytes()[..n],);
+	*len += n;
+	n == s.len()
+}
@@ -0,0 +1,245 @@
+//! # Glyph Cache
+//!
+//! Drawing a character of the bitmap font tests each of the 128 bits of its
+//! glyph. The console draws the same few characters in the same colors over
+//! and over, so [`GlyphCache`] keeps glyphs expanded into pixels, keyed by
+//! character and colors, and the console copies their rows to the screen.
+//!
+//! An expanded glyph takes [`GLYPH_BYTES`] bytes. The cache holds as many
+//! as fit in its byte budget, at most its `N` slots, and replaces the least
+//! recently used glyph when it is full. A budget smaller than one glyph
+//! disables caching, and every glyph is expanded when it is drawn.
+//!
+//! The kernel console uses it as `base::graphic::glyph`. It lives here so the
+//! host can test and benchmark it.
+//!
+//! ```rust,ignore
+//! static mut GLYPHS: GlyphCache<64,> = GlyphCache::new(16 * 1024,);
+//! let key = GlyphKey { code: 'A' as u32, foreground: white, background: 0 };
+//! let pixels = GLYPHS.get(key, SINONOME.glyph(key.code,).unwrap_or(0,),);
+//! FRAME_BUFFER.blit(origin, GLYPH_WIDTH, pixels,);
+//! ```
+
+/// Width of a glyph in pixels
+pub const GLYPH_WIDTH: usize = 8;
+/// Height of a glyph in pixels
+pub const GLYPH_HEIGHT: usize = 16;
+/// Pixels of a glyph
+pub const GLYPH_PIXELS: usize = GLYPH_WIDTH * GLYPH_HEIGHT;
+/// Bytes an expanded glyph takes in the cache
+pub const GLYPH_BYTES: usize = GLYPH_PIXELS * 4;
+
+/// Glyph drawn in a pair of colors
+///
+/// # Fields
+///
+/// * `code` - Character of the glyph
+/// * `foreground` - Pixel value of the set bits of the glyph
+/// * `background` - Pixel value of the clear bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct GlyphKey {
+	pub code:       u32,
+	pub foreground: u32,
+	pub background: u32,
+}
+
+/// Lookups of a [`GlyphCache`] since it was created
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq,)]
+pub struct CacheStats {
+	pub hits:   u64,
+	pub misses: u64,
+	/// Glyphs in the cache
+	pub len:    usize,
+}
+
+/// Glyphs expanded into pixels, bounded by a byte budget
+pub struct GlyphCache<const N: usize,> {
+	slots:   [Slot; N],
+	len:     usize,
+	budget:  usize,
+	/// stamp of the latest lookup
+	clock:   u64,
+	/// expanded glyph while caching is disabled
+	scratch: [u32; GLYPH_PIXELS],
+	hits:    u64,
+	misses:  u64,
+}
+
+impl<const N: usize,> GlyphCache<N,> {
+	/// Cache of at most `budget` bytes of glyphs
+	pub const fn new(budget: usize,) -> Self {
+		Self {
+			slots: [Slot::EMPTY; N],
+			len: 0,
+			budget,
+			clock: 0,
+			scratch: [0; GLYPH_PIXELS],
+			hits: 0,
+			misses: 0,
+		}
+	}
+
+	/// Most glyphs the cache holds with its current budget
+	pub const fn capacity(&self,) -> usize {
+		let glyphs = self.budget / GLYPH_BYTES;
+		if glyphs < N { glyphs } else { N }
+	}
+
+	/// Changes the budget to `budget` bytes. Glyphs past the new capacity
+	/// are dropped
+	pub fn set_budget(&mut self, budget: usize,) {
+		self.budget = budget;
+		self.len = self.len.min(self.capacity(),);
+	}
+
+	/// Pixels of the glyph `bitmap` of `key.code` in the colors of `key`,
+	/// row by row
+	///
+	/// `bitmap` is only expanded if the cache misses.
+	pub fn get(
+		&mut self,
+		key: GlyphKey,
+		bitmap: u128,
+	) -> &[u32; GLYPH_PIXELS] {
+		let capacity = self.capacity();
+		self.clock += 1;
+		let live = &mut self.slots[..self.len];
+		if let Some(i,) = live.iter().position(|slot| slot.key == key,) {
+			self.hits += 1;
+			live[i].used = self.clock;
+			return &self.slots[i].pixels;
+		}
+
+		self.misses += 1;
+		let i = if self.len < capacity {
+			self.len += 1;
+			self.len - 1
+		} else if let Some(i,) = live
+			.iter()
+			.enumerate()
+			.min_by_key(|(_, slot,)| slot.used,)
+			.map(|(i, _,)| i,)
+		{
+			i
+		} else {
+			self.scratch = expand(bitmap, key.foreground, key.background,);
+			return &self.scratch;
+		};
+		let slot = &mut self.slots[i];
+		slot.key = key;
+		slot.used = self.clock;
+		slot.pixels = expand(bitmap, key.foreground, key.background,);
+		&slot.pixels
+	}
+
+	/// Drops every glyph, e.g. after the pixel format changed
+	pub fn clear(&mut self,) {
+		self.len = 0;
+	}
+
+	/// Hits and misses of [`Self::get`] so far
+	pub fn stats(&self,) -> CacheStats {
+		CacheStats { hits: self.hits, misses: self.misses, len: self.len, }
+	}
+}
+
+/// Pixels of the glyph `bitmap`: `foreground` where a bit is set and
+/// `background` elsewhere. Bit `x + y * GLYPH_WIDTH` is the pixel at
+/// `(x, y)`
+pub const fn expand(
+	bitmap: u128,
+	foreground: u32,
+	background: u32,
+) -> [u32; GLYPH_PIXELS] {
+	let mut pixels = [background; GLYPH_PIXELS];
+	let mut i = 0;
+	while i < GLYPH_PIXELS {
+		if bitmap & (1 << i) != 0 {
+			pixels[i] = foreground;
+		}
+		i += 1;
+	}
+	pixels
+}
+
+/// cached glyph
+struct Slot {
+	key:    GlyphKey,
+	/// clock of the latest lookup
+	used:   u64,
+	pixels: [u32; GLYPH_PIXELS],
+}
+
+impl Slot {
+	const EMPTY: Self = Self {
+		key:    GlyphKey { code: 0, foreground: 0, background: 0, },
+		used:   0,
+		pixels: [0; GLYPH_PIXELS],
+	};
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const WHITE: u32 = 0xffff_ffff;
+
+	fn key(code: char,) -> GlyphKey {
+		GlyphKey { code: code as u32, foreground: WHITE, background: 0, }
+	}
+
+	#[test]
+	fn test_expand() {
+		// top left and bottom right pixels
+		let bitmap = 1 | 1 << (GLYPH_PIXELS - 1);
+		let pixels = expand(bitmap, WHITE, 7,);
+		assert_eq!(pixels[0], WHITE);
+		assert_eq!(pixels[1], 7);
+		assert_eq!(pixels[GLYPH_WIDTH], 7);
+		assert_eq!(pixels[GLYPH_PIXELS - 1], WHITE);
+		assert_eq!(pixels.iter().filter(|p| **p == WHITE).count(), 2);
+	}
+
+	#[test]
+	fn test_hits_and_least_recently_used_eviction() {
+		let mut cache = GlyphCache::<4,>::new(2 * GLYPH_BYTES,);
+		assert_eq!(cache.capacity(), 2);
+		cache.get(key('a',), 1,);
+		cache.get(key('b',), 2,);
+		// `a` is used more recently than `b`
+		assert_eq!(cache.get(key('a'), 0)[0], WHITE);
+		cache.get(key('c',), 4,);
+		let stats = CacheStats { hits: 1, misses: 3, len: 2, };
+		assert_eq!(cache.stats(), stats);
+		// `b` was evicted, the bitmap passed now is expanded
+		assert_eq!(cache.get(key('b'), 0)[1], 0);
+		assert_eq!(cache.stats().misses, 4);
+
+		// colors are part of the key
+		let red = GlyphKey { foreground: 0xff_0000, ..key('b') };
+		assert_eq!(cache.get(red, 1)[0], 0xff_0000);
+	}
+
+	#[test]
+	fn test_budget() {
+		let mut cache = GlyphCache::<4,>::new(usize::MAX,);
+		assert_eq!(cache.capacity(), 4);
+		for code in ['a', 'b', 'c',] {
+			cache.get(key(code,), 1,);
+		}
+		cache.set_budget(GLYPH_BYTES,);
+		assert_eq!(cache.stats().len, 1);
+
+		// nothing is cached below one glyph, but glyphs are still drawn
+		cache.set_budget(GLYPH_BYTES - 1,);
+		assert_eq!(cache.capacity(), 0);
+		assert_eq!(cache.get(key('z'), 2)[1], WHITE);
+		assert_eq!(cache.get(key('z'), 2)[1], WHITE);
+		assert_eq!(cache.stats(), CacheStats { hits: 0, misses: 5, len: 0 });
+
+		cache.set_budget(usize::MAX,);
+		cache.get(key('a',), 1,);
+		cache.clear();
+		assert_eq!(cache.stats().len, 0);
+	}
+}
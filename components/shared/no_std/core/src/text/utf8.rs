@@ -0,0 +1,110 @@
+//! # UTF-8 Module
+//!
+//! This module decodes bytes into `&str` strictly. Invalid input is reported
+//! with the offset of the first invalid sequence instead of being replaced,
+//! so a corrupted string table or configuration file is noticed rather than
+//! misread.
+//!
+//! [`validate`] and [`from_bytes`] are `const`, so embedded data can be
+//! checked at compile time. [`TryFromBytes`] is the trait form used by
+//! parsers which are generic over their string type.
+//!
+//! ## Helpers
+//!
+//! - [`split_once_byte`]: Splits at the first delimiter, such as the `NUL` of
+//!   a C string
+//!
+//! Trimming is left to `<[u8]>::trim_ascii` of `core`.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use oso_no_std_shared::text::utf8::TryFromBytes;
+//! use oso_no_std_shared::text::utf8::from_bytes;
+//! use oso_no_std_shared::text::utf8::split_once_byte;
+//!
+//! let table = b"init\0\xe3\x81\x82\0";
+//! let (name, rest,) = split_once_byte(table, 0,).unwrap();
+//! assert_eq!(<&str>::try_from_bytes(name,), Ok("init"));
+//! assert_eq!(from_bytes(&rest[..3],), Ok("\u{3042}"));
+//!
+//! let err = from_bytes(b"ok\xe3\x81",).unwrap_err();
+//! assert_eq!((err.offset, err.len,), (2, None,));
+//! let err = from_bytes(b"ok\xc0\xaf",).unwrap_err();
+//! assert_eq!((err.offset, err.len,), (2, Some(1,),));
+//! ```
+
+use oso_error::parser::Utf8Error;
+
+/// Checks that `bytes` is valid UTF-8
+///
+/// Overlong encodings, surrogates and code points above `U+10FFFF` are
+/// rejected as by [`core::str::from_utf8`].
+pub const fn validate(bytes: &[u8],) -> Result<(), Utf8Error,> {
+	let mut i = 0;
+	while i < bytes.len() {
+		let lead = bytes[i];
+		let width = match lead {
+			0x00..=0x7f => 1,
+			0xc2..=0xdf => 2,
+			0xe0..=0xef => 3,
+			0xf0..=0xf4 => 4,
+			_ => return Err(Utf8Error { offset: i, len: Some(1,), },),
+		};
+		// the second byte is narrowed for leads which could otherwise encode
+		// overlongs, surrogates or code points out of range
+		let (second_min, second_max,) = match lead {
+			0xe0 => (0xa0, 0xbf,),
+			0xed => (0x80, 0x9f,),
+			0xf0 => (0x90, 0xbf,),
+			0xf4 => (0x80, 0x8f,),
+			_ => (0x80, 0xbf,),
+		};
+
+		let mut k = 1;
+		while k < width {
+			if i + k >= bytes.len() {
+				return Err(Utf8Error { offset: i, len: None, },);
+			}
+			let (min, max,) =
+				if k == 1 { (second_min, second_max,) } else { (0x80, 0xbf,) };
+			let b = bytes[i + k];
+			if b < min || b > max {
+				return Err(Utf8Error { offset: i, len: Some(k,), },);
+			}
+			k += 1;
+		}
+		i += width;
+	}
+	Ok((),)
+}
+
+/// Borrows `bytes` as `&str` after [`validate`]
+pub const fn from_bytes(bytes: &[u8],) -> Result<&str, Utf8Error,> {
+	match validate(bytes,) {
+		// SAFETY: validated above
+		Ok((),) => Ok(unsafe { core::str::from_utf8_unchecked(bytes,) },),
+		Err(e,) => Err(e,),
+	}
+}
+
+/// String types which can be decoded from bytes strictly
+pub trait TryFromBytes<'a,>: Sized {
+	fn try_from_bytes(bytes: &'a [u8],) -> Result<Self, Utf8Error,>;
+}
+
+impl<'a,> TryFromBytes<'a,> for &'a str {
+	fn try_from_bytes(bytes: &'a [u8],) -> Result<Self, Utf8Error,> {
+		from_bytes(bytes,)
+	}
+}
+
+/// Splits `bytes` at the first `delimiter`, which belongs to neither half.
+/// Returns `None` if `bytes` has no `delimiter`
+pub fn split_once_byte(
+	bytes: &[u8],
+	delimiter: u8,
+) -> Option<(&[u8], &[u8],),> {
+	let i = bytes.iter().position(|b| *b == delimiter,)?;
+	Some((&bytes[..i], &bytes[i + 1..],),)
+}
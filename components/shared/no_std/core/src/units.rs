@@ -0,0 +1,284 @@
+//! # Units Module
+//!
+//! This module provides [`ByteSize`] for sizes in bytes and the
+//! [`PhysAddr`] and [`VirtAddr`] newtypes for addresses, so page math is
+//! written once instead of as raw integer arithmetic at every memory map,
+//! allocator and page table.
+//!
+//! ## Conventions
+//!
+//! - Alignments are powers of two. Other alignments panic in debug builds
+//! - Rounding up and moving an address fail with `None` instead of wrapping
+//! - Sizes are printed in binary units, `1.5 MiB` with `{:.1}`. Digits past
+//!   the precision are cut, not rounded, so a size never looks larger than
+//!   it is
+//!
+//! ## Example
+//!
+//! ```rust
+//! use oso_no_std_shared::units::ByteSize;
+//! use oso_no_std_shared::units::PAGE_SIZE;
+//! use oso_no_std_shared::units::PhysAddr;
+//!
+//! let start = PhysAddr::new(0x4000_0123,);
+//! let end = start.checked_add(0x2000,).unwrap();
+//! let pages = PhysAddr::page_span(start, end,).unwrap();
+//! assert_eq!(pages.start, PhysAddr::new(0x4000_0000));
+//! assert_eq!(pages.end, PhysAddr::new(0x4000_3000));
+//!
+//! let size = ByteSize::new(pages.end - pages.start,);
+//! assert_eq!(size.pages(PAGE_SIZE,), 3);
+//! assert_eq!(format!("{size:.1}"), "12.0 KiB");
+//! ```
+
+use core::fmt;
+use core::ops::Range;
+use core::ops::Sub;
+
+/// Size of a page of the memory map and of the page tables
+pub const PAGE_SIZE: u64 = 4096;
+pub const KIB: u64 = 1 << 10;
+pub const MIB: u64 = 1 << 20;
+pub const GIB: u64 = 1 << 30;
+pub const TIB: u64 = 1 << 40;
+
+/// Size in bytes, printed in the largest binary unit it reaches
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash,)]
+pub struct ByteSize(pub u64,);
+
+impl ByteSize {
+	/// Units [`Display`](fmt::Display) chooses from, largest first
+	pub const UNITS: [(u64, &str,); 4] =
+		[(TIB, "TiB",), (GIB, "GiB",), (MIB, "MiB",), (KIB, "KiB",),];
+
+	pub const fn new(bytes: u64,) -> Self {
+		Self(bytes,)
+	}
+
+	/// Size of `count` pages of `page_size` bytes. `None` on overflow
+	pub const fn from_pages(count: u64, page_size: u64,) -> Option<Self,> {
+		match count.checked_mul(page_size,) {
+			Some(bytes,) => Some(Self(bytes,),),
+			None => None,
+		}
+	}
+
+	pub const fn bytes(&self,) -> u64 {
+		self.0
+	}
+
+	/// Pages of `page_size` bytes needed to hold the size, rounded up
+	pub const fn pages(&self, page_size: u64,) -> u64 {
+		self.0.div_ceil(page_size,)
+	}
+}
+
+impl From<u64,> for ByteSize {
+	fn from(bytes: u64,) -> Self {
+		Self(bytes,)
+	}
+}
+
+impl From<usize,> for ByteSize {
+	fn from(bytes: usize,) -> Self {
+		Self(bytes as u64,)
+	}
+}
+
+impl fmt::Display for ByteSize {
+	/// `512 B`, `4 KiB` or with a precision `1.50 MiB`. Precision is capped
+	/// at 3 digits
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		let unit = Self::UNITS.iter().find(|(unit, _,)| self.0 >= *unit,);
+		let Some(&(unit, name,),) = unit else {
+			return write!(f, "{} B", self.0);
+		};
+		let whole = self.0 / unit;
+		let precision = f.precision().unwrap_or(0,).min(3,);
+		if precision == 0 {
+			return write!(f, "{whole} {name}");
+		}
+		let scale = 10_u64.pow(precision as u32,);
+		// the remainder is below `unit`, at most 2^40, so this does not overflow
+		let fraction = self.0 % unit * scale / unit;
+		write!(f, "{whole}.{fraction:0precision$} {name}")
+	}
+}
+
+macro_rules! address {
+	($(#[$attr:meta])* $name:ident) => {
+		$(#[$attr])*
+		#[repr(transparent)]
+		#[derive(
+			Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash,
+		)]
+		pub struct $name(u64,);
+
+		impl $name {
+			pub const fn new(addr: u64,) -> Self {
+				Self(addr,)
+			}
+
+			pub const fn as_u64(&self,) -> u64 {
+				self.0
+			}
+
+			/// `true` if the address is a multiple of `align`
+			pub const fn is_aligned(&self, align: u64,) -> bool {
+				debug_assert!(align.is_power_of_two());
+				self.0 & (align - 1) == 0
+			}
+
+			/// Largest multiple of `align` at or below the address
+			pub const fn align_down(&self, align: u64,) -> Self {
+				debug_assert!(align.is_power_of_two());
+				Self(self.0 & !(align - 1),)
+			}
+
+			/// Smallest multiple of `align` at or above the address. `None`
+			/// if it is past the end of the address space
+			pub const fn align_up(&self, align: u64,) -> Option<Self,> {
+				debug_assert!(align.is_power_of_two());
+				match self.0.checked_add(align - 1,) {
+					Some(addr,) => Some(Self(addr & !(align - 1),),),
+					None => None,
+				}
+			}
+
+			/// Address `bytes` above. `None` on overflow
+			pub const fn checked_add(&self, bytes: u64,) -> Option<Self,> {
+				match self.0.checked_add(bytes,) {
+					Some(addr,) => Some(Self(addr,),),
+					None => None,
+				}
+			}
+
+			/// Address `bytes` below. `None` on underflow
+			pub const fn checked_sub(&self, bytes: u64,) -> Option<Self,> {
+				match self.0.checked_sub(bytes,) {
+					Some(addr,) => Some(Self(addr,),),
+					None => None,
+				}
+			}
+
+			/// Whole pages covering `start..end`, as page aligned bounds.
+			/// `None` if the last page ends past the address space
+			pub const fn page_span(
+				start: Self,
+				end: Self,
+			) -> Option<Range<Self,>,> {
+				match end.align_up(PAGE_SIZE,) {
+					Some(end,) => Some(start.align_down(PAGE_SIZE,)..end,),
+					None => None,
+				}
+			}
+		}
+
+		impl From<u64,> for $name {
+			fn from(addr: u64,) -> Self {
+				Self(addr,)
+			}
+		}
+
+		impl From<$name,> for u64 {
+			fn from(addr: $name,) -> Self {
+				addr.0
+			}
+		}
+
+		/// Bytes between two addresses
+		///
+		/// # Panics
+		///
+		/// If `rhs` is above `self`, in debug builds
+		impl Sub for $name {
+			type Output = u64;
+
+			fn sub(self, rhs: Self,) -> u64 {
+				self.0 - rhs.0
+			}
+		}
+
+		impl fmt::Display for $name {
+			fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+				write!(f, "{:#x}", self.0)
+			}
+		}
+
+		impl fmt::LowerHex for $name {
+			fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+				fmt::LowerHex::fmt(&self.0, f,)
+			}
+		}
+	};
+}
+
+address! {
+	/// Physical address
+	PhysAddr
+}
+
+address! {
+	/// Virtual address
+	VirtAddr
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::text::fixed::FixedString;
+	use core::fmt::Write;
+
+	fn show(args: fmt::Arguments,) -> FixedString<32,> {
+		let mut out = FixedString::new();
+		out.write_fmt(args,).unwrap();
+		out
+	}
+
+	#[test]
+	fn test_byte_size_display() {
+		assert_eq!(&*show(format_args!("{}", ByteSize(1023))), "1023 B");
+		assert_eq!(&*show(format_args!("{}", ByteSize(KIB))), "1 KiB");
+		let size = ByteSize(MIB + MIB / 2,);
+		assert_eq!(&*show(format_args!("{size}")), "1 MiB");
+		assert_eq!(&*show(format_args!("{size:.1}")), "1.5 MiB");
+		assert_eq!(&*show(format_args!("{size:.2}")), "1.50 MiB");
+		// cut, not rounded
+		let size = ByteSize(2 * GIB - 1,);
+		assert_eq!(&*show(format_args!("{size:.3}")), "1.999 GiB");
+		let size = ByteSize(u64::MAX,);
+		assert_eq!(&*show(format_args!("{size:.9}")), "16777215.999 TiB");
+	}
+
+	#[test]
+	fn test_byte_size_pages() {
+		assert_eq!(ByteSize(0).pages(PAGE_SIZE), 0);
+		assert_eq!(ByteSize(PAGE_SIZE).pages(PAGE_SIZE), 1);
+		assert_eq!(ByteSize(PAGE_SIZE + 1).pages(PAGE_SIZE), 2);
+		assert_eq!(ByteSize::from_pages(3, PAGE_SIZE), Some(ByteSize(12288)));
+		assert_eq!(ByteSize::from_pages(u64::MAX, 2), None);
+	}
+
+	#[test]
+	fn test_address_alignment() {
+		let addr = PhysAddr::new(0x1234,);
+		assert_eq!(addr.align_down(PAGE_SIZE), PhysAddr::new(0x1000));
+		assert_eq!(addr.align_up(PAGE_SIZE), Some(PhysAddr::new(0x2000)));
+		let aligned = VirtAddr::new(0x2000,);
+		assert!(aligned.is_aligned(PAGE_SIZE));
+		assert_eq!(aligned.align_up(PAGE_SIZE), Some(aligned));
+		assert_eq!(PhysAddr::new(u64::MAX).align_up(PAGE_SIZE), None);
+
+		let span = PhysAddr::page_span(addr, PhysAddr::new(0x2001,),).unwrap();
+		assert_eq!(span, PhysAddr::new(0x1000)..PhysAddr::new(0x3000));
+		assert_eq!(span.end - span.start, 2 * PAGE_SIZE);
+	}
+
+	#[test]
+	fn test_address_checked_arithmetic() {
+		let addr = PhysAddr::new(u64::MAX - 1,);
+		assert_eq!(addr.checked_add(1), Some(PhysAddr::new(u64::MAX)));
+		assert_eq!(addr.checked_add(2), None);
+		assert_eq!(PhysAddr::new(1).checked_sub(2), None);
+	}
+}
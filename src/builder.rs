@@ -6,14 +6,60 @@
 //! This module handles:
 //! - Building the OSO loader and kernel for the target architecture
 //! - Creating and formatting a disk image
-//! - Mounting the disk image and copying the built artifacts
+//! - Copying the built artifacts into the disk image, rewriting only files
+//!   which changed since the last build
 //! - Configuring and running QEMU with the appropriate firmware and disk image
+//! - Post-link processing of the kernel image (build info stamping, symbol
+//!   map, strip)
+//! - Running benchmarks and comparing them with a saved baseline
 //! - Cleanup of temporary files and unmounting disk images
 
+use anyhow::Context as _;
 use anyhow::Result as Rslt;
+use anyhow::anyhow;
+use anyhow::bail;
+use colored::Colorize;
+use oso_dev_util::audit::audit;
+use oso_dev_util::bench::CRITERION_HOME;
+use oso_dev_util::bench::LATEST;
+use oso_dev_util::bench::compare as compare_bench;
 use oso_dev_util::cargo::Assets;
+use oso_dev_util::cargo::DtCommand;
+use oso_dev_util::cargo::GraphFormat;
 use oso_dev_util::cargo::Opts;
+use oso_dev_util::cargo::Task;
+use oso_dev_util::cargo::parallel::run_parallel;
+use oso_dev_util::cargo::target::TargetSpec;
+use oso_dev_util::cpio;
+use oso_dev_util::crash::CrashDump;
+use oso_dev_util::decl_manage::crate_::CrateInfo;
+use oso_dev_util::decl_manage::crate_::OsoCrate;
+use oso_dev_util::decl_manage::graph::CrateGraph;
+use oso_dev_util::dtb::Dtb;
+use oso_dev_util::elf::ElfPatcher;
+use oso_dev_util::elf::Symbol;
+use oso_dev_util::error_codes;
+use oso_dev_util::error_codes::REGISTRY_MD;
+use oso_dev_util::error_codes::REGISTRY_RS;
 use oso_dev_util::fs::project_root;
+use oso_dev_util::handoff::BootInfoDump;
+use oso_dev_util::image::ImageFile;
+use oso_dev_util::image::assemble;
+use oso_dev_util::scaffold::CrateKind;
+use oso_dev_util::scaffold::Scaffold;
+use oso_dev_util::screenshot;
+use oso_dev_util::screenshot::Image;
+use oso_dev_util::symbol_map;
+use oso_dev_util::trace::TraceDump;
+use oso_dev_util::unsafe_audit::audit_unsafe;
+use oso_dev_util_helper::chart::DepChart;
+use oso_dev_util_helper::fs::all_crates;
+use oso_dev_util_helper::specs;
+use oso_dev_util_helper::specs::OFFLINE_ENV;
+use oso_dev_util_helper::specs::SPECS_DIR;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
 
 use crate::Xtask;
 
@@ -21,6 +67,28 @@ use crate::Xtask;
 const BOOT_DIR: &str = "efi/boot";
 /// mounting point path under target/
 const MOUNT_DIR: &str = "xtask/mnt";
+/// Disk image path under target/
+const DISK_IMG: &str = "xtask/disk.img";
+/// Post-linked kernel image under the workspace root
+const KERNEL_ELF: &str = "target/xtask/oso_kernel.elf";
+/// Initial ramdisk holding the boot script of `boot-test --autoexec` and
+/// `test`, under the workspace root
+const INITRD: &str = "target/xtask/initrd.cpio";
+/// Loader configuration of `boot-test --autoexec` and `test` under the
+/// workspace root
+const TEST_CONFIG: &str = "target/xtask/loader.cfg";
+/// Content of [`TEST_CONFIG`] for `boot-test --autoexec`, handing [`INITRD`]
+/// to the kernel
+const BOOT_TEST_CONFIG_SRC: &str = "[kernel]\ninitrd = '\\initrd.cpio'\n";
+/// Content of [`TEST_CONFIG`] for `test`. The kernel command line also makes
+/// the kernel exit QEMU with the result of the boot script, or after a panic
+const TEST_CONFIG_SRC: &str = concat!(
+	"[kernel]\n",
+	"cmdline = \"test=exit panic=exit\"\n",
+	"initrd = '\\initrd.cpio'\n",
+);
+/// Packages built by [`Xtask::build`]
+const PACKAGES: [&str; 2] = ["oso_loader", "oso_kernel",];
 
 impl Xtask {
 	/// Creates a new Builder instance with the specified options
@@ -79,4 +147,579 @@ impl Xtask {
 		let assets = Assets::new(opts.arch,)?;
 		Ok(Self { opts, ws, assets, },)
 	}
+
+	/// Subcommand given on the command line
+	pub fn task(&self,) -> &Task {
+		&self.opts.task
+	}
+
+	/// Generates crate `name` of `kind` and adds it to workspace members
+	pub fn new_crate(&self, name: &str, kind: CrateKind,) -> Rslt<(),> {
+		let scaffold = Scaffold::new(name, kind,)?;
+		if self.opts.dry_run {
+			for (path, _,) in scaffold.files() {
+				println!("create {}/{path}", scaffold.member());
+			}
+			return Ok((),);
+		}
+
+		let dir = scaffold.generate(&self.ws.path(),)?;
+		println!("created {kind} crate {name} at {}", dir.display());
+		Ok((),)
+	}
+
+	/// Checks naming conventions and crate placement of the workspace
+	///
+	/// # Errors
+	///
+	/// Returns an error if any violation is found
+	pub fn audit(&self,) -> Rslt<(),> {
+		let violations = audit(&self.ws.path(),)?;
+		for violation in &violations {
+			println!("{violation}");
+		}
+		if !violations.is_empty() {
+			bail!("{} convention violations", violations.len());
+		}
+		println!("no convention violations");
+		Ok((),)
+	}
+
+	/// Prints the dependency chart of workspace crates, or writes it to
+	/// `output`
+	pub fn graph(
+		&self,
+		format: GraphFormat,
+		output: Option<&Path,>,
+	) -> Rslt<(),> {
+		let chart = DepChart::of_workspace()?;
+		let chart = match format {
+			GraphFormat::Mermaid => chart.mermaid(),
+			GraphFormat::Dot => chart.dot(),
+		};
+		match output {
+			Some(path,) => {
+				std::fs::write(path, chart,)?;
+				println!("wrote {format} chart to {}", path.display());
+			},
+			None => print!("{chart}"),
+		}
+		Ok((),)
+	}
+
+	/// Runs the parser benchmarks of `oso_dev_util`
+	///
+	/// Results are saved as the baseline `save`, or as `latest`. If `compare`
+	/// is given, the results are compared with that saved baseline.
+	///
+	/// # Errors
+	///
+	/// Returns an error if any benchmark got slower than `compare` by more
+	/// than `threshold` percent
+	pub fn bench(
+		&self,
+		save: Option<&str,>,
+		compare: Option<&str,>,
+		threshold: u32,
+	) -> Rslt<(),> {
+		let home = self.ws.path().join(CRITERION_HOME,);
+		let run = save.unwrap_or(LATEST,);
+		let mut cmd = Command::new("cargo",);
+		cmd.current_dir(self.ws.path(),)
+			.env("CRITERION_HOME", &home,)
+			.args(["bench", "-p", "oso_dev_util",],)
+			.args(["--bench", "parsers", "--bench", "mem",],)
+			.args(["--", "--save-baseline", run,],);
+		self.opts.exec(&mut cmd,)?;
+
+		let Some(baseline,) = compare else {
+			return Ok((),);
+		};
+		if self.opts.dry_run {
+			println!("compare {run} with {baseline}");
+			return Ok((),);
+		}
+		let threshold = threshold as f64;
+		let mut regressions = 0;
+		for comparison in compare_bench(&home, baseline, run,)? {
+			if comparison.is_regression(threshold,) {
+				regressions += 1;
+				println!("{}", format!("regressed {comparison}").red());
+			} else {
+				println!("ok        {comparison}");
+			}
+		}
+		if regressions != 0 {
+			bail!("{regressions} benchmarks regressed more than {threshold}%");
+		}
+		Ok((),)
+	}
+
+	/// Prints the crash dump in `file` with its backtrace resolved
+	///
+	/// Symbols are read from `kernel`, or from the last built kernel image.
+	/// Release images are stripped, so their backtraces stay unresolved.
+	pub fn crash_decode(
+		&self,
+		file: &Path,
+		kernel: Option<&Path,>,
+	) -> Rslt<(),> {
+		let dump = CrashDump::decode(&std::fs::read(file,)?,)?;
+
+		let symbols = self.kernel_symbols(kernel,)?;
+		print!("{}", dump.render(&symbols,));
+		Ok((),)
+	}
+
+	/// Symbols of `kernel`, or of the last built kernel image. Empty if the
+	/// image does not exist
+	fn kernel_symbols(&self, kernel: Option<&Path,>,) -> Rslt<Vec<Symbol,>,> {
+		let default_kernel = self.ws.path().join(KERNEL_ELF,);
+		let kernel = kernel.unwrap_or(&default_kernel,);
+		if !kernel.exists() {
+			let kernel = kernel.display();
+			println!("{kernel} not found, addresses are not resolved");
+			return Ok(vec![],);
+		}
+		ElfPatcher::open(kernel,)?.symbols()
+	}
+
+	/// Converts the trace dump in `file` into a Chrome trace
+	///
+	/// The trace is written to `output`, or next to `file` with the
+	/// extension `json`. `frequency` overrides the clock of the dump, which
+	/// is unknown on x86_64.
+	pub fn trace_export(
+		&self,
+		file: &Path,
+		output: Option<&Path,>,
+		frequency: Option<u64,>,
+	) -> Rslt<(),> {
+		let dump = TraceDump::decode(&std::fs::read(file,)?,)?;
+		let output = match output {
+			Some(output,) => output.to_path_buf(),
+			None => file.with_extension("json",),
+		};
+		std::fs::write(&output, dump.to_chrome_json(frequency,),)?;
+		println!(
+			"{} events of {} tracepoints written to {}",
+			dump.events.len(),
+			dump.names.len(),
+			output.display()
+		);
+		Ok((),)
+	}
+
+	/// Folds the heap allocations of the trace dump in `file` into stacks
+	///
+	/// The stacks are written to `output`, or next to `file` with the
+	/// extension `folded`, for `inferno-flamegraph` or `flamegraph.pl`.
+	/// Frames are named with the symbols of `kernel`, or of the last built
+	/// kernel image.
+	pub fn heap_profile(
+		&self,
+		file: &Path,
+		output: Option<&Path,>,
+		kernel: Option<&Path,>,
+	) -> Rslt<(),> {
+		let dump = TraceDump::decode(&std::fs::read(file,)?,)?;
+
+		let symbols = self.kernel_symbols(kernel,)?;
+		let output = match output {
+			Some(output,) => output.to_path_buf(),
+			None => file.with_extension("folded",),
+		};
+		let folded = dump.to_folded_heap(&symbols,);
+		if folded.is_empty() {
+			println!("no heap events, was the kernel built with alloc_trace?");
+		}
+		std::fs::write(&output, &folded,)?;
+		let stacks = folded.lines().count();
+		println!("{stacks} stacks written to {}", output.display());
+		Ok((),)
+	}
+
+	/// Downloads the specification pages read by the macros of the workspace
+	/// into [`SPECS_DIR`]
+	///
+	/// Macros read the vendored pages instead of downloading them, so builds
+	/// with them committed work with `OSO_OFFLINE=1`.
+	pub fn vendor_specs(&self,) -> Rslt<(),> {
+		if specs::is_offline() {
+			bail!("{OFFLINE_ENV}=1 forbids downloading specifications");
+		}
+		let root = self.ws.path();
+		let pages = specs::crate_spec_pages(&all_crates()?,)?;
+		for url in &pages {
+			let path = specs::vendored_path(&root, url,);
+			std::fs::create_dir_all(path.parent().unwrap(),)?;
+			let mut cmd = Command::new("curl",);
+			cmd.args(["--fail", "--silent", "--show-error", "--location",],)
+				.arg("--output",)
+				.arg(&path,)
+				.arg(url,);
+			self.opts.exec(&mut cmd,)?;
+			println!("vendored {url}");
+		}
+		println!("{} pages vendored under {SPECS_DIR}", pages.len());
+		Ok((),)
+	}
+
+	/// Writes the registry of error codes to [`REGISTRY_MD`] and
+	/// [`REGISTRY_RS`]
+	///
+	/// With `check`, nothing is written and an outdated registry is an error
+	/// instead.
+	///
+	/// # Errors
+	///
+	/// Returns an error if variants of different enums share a code
+	pub fn error_codes(&self, check: bool,) -> Rslt<(),> {
+		let entries = self.error_code_entries()?;
+		let root = self.ws.path();
+		let files = [
+			(REGISTRY_MD, error_codes::markdown(&entries,),),
+			(REGISTRY_RS, error_codes::rust_table(&entries,),),
+		];
+		for (file, content,) in files {
+			let path = root.join(file,);
+			let current = std::fs::read_to_string(&path,).unwrap_or_default();
+			if current == content {
+				continue;
+			}
+			if check {
+				bail!("{file} is out of date. run `cargo xtask error-codes`");
+			}
+			std::fs::write(&path, content,)?;
+			println!("wrote {file}");
+		}
+		println!("{} error codes, no duplicates", entries.len());
+		Ok((),)
+	}
+
+	/// Error codes of the workspace
+	///
+	/// # Errors
+	///
+	/// Returns an error listing the codes which variants of different enums
+	/// share
+	fn error_code_entries(&self,) -> Rslt<Vec<error_codes::Entry,>,> {
+		let entries = error_codes::collect(&all_crates()?,)?;
+		let duplicates = error_codes::duplicates(&entries,);
+		for (code, names,) in &duplicates {
+			let message = format!("error code {code:#06x} is used by");
+			println!("{} {}", message.red(), names.join(", "));
+		}
+		if !duplicates.is_empty() {
+			bail!("{} error codes are used more than once", duplicates.len());
+		}
+		Ok(entries,)
+	}
+
+	/// Prints the unsafe code of the workspace which has no safety contract
+	/// and writes the inventory of all unsafe code to `output`
+	///
+	/// # Errors
+	///
+	/// With `strict`, returns an error if a site has no contract
+	pub fn audit_unsafe(
+		&self,
+		output: Option<&Path,>,
+		strict: bool,
+	) -> Rslt<(),> {
+		let audit = audit_unsafe(&self.ws.path(), &all_crates()?,)?;
+		for site in audit.missing() {
+			let at = format!("{}:{}", site.file.display(), site.line);
+			let within = &site.within;
+			println!("{} {at} {} in `{within}`", "missing".red(), site.kind);
+		}
+		for (file, error,) in &audit.unparsed {
+			println!("{} {}: {error}", "not parsed".yellow(), file.display());
+		}
+		if let Some(output,) = output {
+			std::fs::write(output, audit.report(),)?;
+			println!("wrote {}", output.display());
+		}
+		let missing = audit.missing().count();
+		let sites = audit.sites.len();
+		println!("{sites} unsafe sites, {missing} without a safety contract");
+		if strict && missing != 0 {
+			bail!("{missing} unsafe sites have no safety contract");
+		}
+		Ok((),)
+	}
+
+	/// Prints the device tree blob of a [`DtCommand`] like the kernel shell
+	/// command `dt`
+	pub fn dt(&self, command: &DtCommand,) -> Rslt<(),> {
+		let output = match command {
+			DtCommand::Print { file, path, } => {
+				let dtb = Dtb::parse(&std::fs::read(file,)?,)?;
+				dtb.print(path.as_deref().unwrap_or("/",),)?
+			},
+			DtCommand::Prop { file, path, name, } => {
+				Dtb::parse(&std::fs::read(file,)?,)?.prop(path, name,)?
+			},
+		};
+		print!("{output}");
+		Ok((),)
+	}
+
+	/// Prints the boot information dump in `file`
+	pub fn bootinfo(&self, file: &Path,) -> Rslt<(),> {
+		let dump = BootInfoDump::decode(&std::fs::read(file,)?,)?;
+		print!("{}", dump.render());
+		Ok((),)
+	}
+
+	/// Extracts the screen capture in `file` and compares it with `golden`
+	///
+	/// The capture is written to `output`, or next to `file` with the
+	/// extension `qoi`. With `update`, it is written to `golden` instead of
+	/// compared.
+	///
+	/// # Errors
+	///
+	/// The capture differs from `golden` by more than `tolerance` in a color
+	/// channel of a pixel
+	pub fn screenshot(
+		&self,
+		file: &Path,
+		output: Option<&Path,>,
+		golden: Option<&Path,>,
+		tolerance: u8,
+		update: bool,
+	) -> Rslt<(),> {
+		let bytes = screenshot::extract(&std::fs::read(file,)?,)?;
+		let capture = Image::parse(&bytes,)?;
+		let output = match output {
+			Some(output,) => output.to_path_buf(),
+			None => file.with_extension("qoi",),
+		};
+		if output != file {
+			std::fs::write(&output, &bytes,)?;
+		}
+		println!(
+			"{}x{} capture written to {}",
+			capture.width,
+			capture.height,
+			output.display()
+		);
+
+		let Some(golden,) = golden else {
+			return Ok((),);
+		};
+		if update {
+			std::fs::write(golden, &bytes,)?;
+			println!("golden image {} updated", golden.display());
+			return Ok((),);
+		}
+		let expected = Image::parse(&std::fs::read(golden,)?,)?;
+		let diff = capture.compare(&expected, tolerance,)?;
+		if !diff.is_match() {
+			bail!("capture differs from {}: {diff}", golden.display());
+		}
+		println!("capture matches {}: {diff}", golden.display());
+		Ok((),)
+	}
+
+	/// Runs the kernel console and shell in a window on the host, replaying
+	/// the recording in the serial log `replay` first
+	///
+	/// The simulator is built for the host from the workspace root, so the
+	/// target of the kernel crate is not picked up.
+	pub fn sim(
+		&self,
+		size: Option<&str,>,
+		replay: Option<&Path,>,
+	) -> Rslt<(),> {
+		let mut cmd = Command::new("cargo",);
+		cmd.current_dir(self.ws.path(),)
+			.args(["run", "-p", "oso_kernel", "--bin", "oso_sim",],)
+			.args(["--no-default-features", "--features", "sim,bgr",],);
+		if self.opts.build_mode.is_release() {
+			cmd.arg("--release",);
+		}
+		cmd.arg("--",);
+		if let Some(replay,) = replay {
+			cmd.arg("--replay",).arg(replay,);
+		}
+		cmd.args(size,);
+		self.opts.exec(&mut cmd,)
+	}
+
+	/// Builds the loader and the kernel
+	///
+	/// Crates which don't depend on each other are built at the same time, at
+	/// most `-j` of them at once. Output of each build is prefixed with the
+	/// package name. If a build fails, the others are stopped and a summary
+	/// is printed. Nothing is built while error codes are used twice, see
+	/// [`Xtask::error_codes`].
+	pub fn build(&self,) -> Rslt<(),> {
+		self.error_code_entries()?;
+		let crates = self.packages()?;
+		let graph = CrateGraph::new(&crates,)?;
+		let cmds = graph
+			.nodes()
+			.iter()
+			.map(|node| self.cargo_build(&node.name, &node.path,),)
+			.collect::<Rslt<Vec<_,>,>>()?;
+
+		if self.opts.dry_run {
+			return cmds
+				.into_iter()
+				.try_for_each(|mut cmd| self.opts.exec(&mut cmd,),);
+		}
+		run_parallel(&graph, cmds, self.opts.jobs,)?;
+		self.assemble_image()
+	}
+
+	/// Path of the disk image booted by QEMU
+	pub fn disk_img_path(&self,) -> Rslt<PathBuf,> {
+		Ok(self.ws.path().join("target",).join(DISK_IMG,),)
+	}
+
+	/// Copies the loader and the post-linked kernel into the disk image. The
+	/// boot script of `boot-test --autoexec` or `test` is packed into the
+	/// initial ramdisk [`INITRD`] as `autoexec.osh`, copied with a loader
+	/// configuration handing it to the kernel
+	///
+	/// Only files whose content changed since the last run are rewritten. If
+	/// nothing changed, the image is left untouched.
+	fn assemble_image(&self,) -> Rslt<(),> {
+		let spec = TargetSpec::kernel(self.opts.arch,);
+		let profile = self.opts.build_mode.profile_dir();
+		let crates = self.packages()?;
+		let loader = crates[0]
+			.path()
+			.join("target",)
+			.join(format!("{}-unknown-uefi", spec.rust_arch()),)
+			.join(profile,)
+			.join("oso_loader.efi",);
+		let built_kernel = crates[1]
+			.path()
+			.join("target",)
+			.join(spec.name(),)
+			.join(profile,)
+			.join("oso_kernel",);
+
+		// stamp a copy, so the image only changes when the kernel is rebuilt
+		let kernel = self.ws.path().join(KERNEL_ELF,);
+		if !self.opts.dry_run {
+			std::fs::copy(&built_kernel, &kernel,)?;
+		}
+		self.post_link(&kernel,)?;
+
+		let boot_file = match spec.rust_arch() {
+			"riscv64" => "BOOTRISCV64.EFI",
+			"x86_64" => "BOOTX64.EFI",
+			_ => "BOOTAA64.EFI",
+		};
+		let mut files = vec![
+			ImageFile::new(format!("{BOOT_DIR}/{boot_file}"), loader,),
+			ImageFile::new("oso_kernel.elf", kernel,),
+		];
+		let boot_script = match self.task() {
+			Task::BootTest { autoexec: Some(script,), .. } => {
+				Some((script, BOOT_TEST_CONFIG_SRC,),)
+			},
+			Task::Test { script, .. } => Some((script, TEST_CONFIG_SRC,),),
+			_ => None,
+		};
+		if let Some((script, config_src,),) = boot_script {
+			let initrd = self.ws.path().join(INITRD,);
+			let config = self.ws.path().join(TEST_CONFIG,);
+			if !self.opts.dry_run {
+				let script = std::fs::read(script,).with_context(|| {
+					format!("failed to read {}", script.display())
+				},)?;
+				let archive = cpio::archive(&[("autoexec.osh", &script,)],);
+				std::fs::write(&initrd, archive,)?;
+				std::fs::write(&config, config_src,)?;
+			}
+			files.push(ImageFile::new("initrd.cpio", initrd,),);
+			files.push(ImageFile::new("efi/oso/loader.cfg", config,),);
+		}
+		let img = self.disk_img_path()?;
+		if self.opts.dry_run {
+			for file in &files {
+				println!("copy {} to {}", file.src.display(), file.dest);
+			}
+			return Ok((),);
+		}
+		println!("{}", assemble(&img, &files,)?);
+		Ok((),)
+	}
+
+	/// Members of the workspace listed in [`PACKAGES`]
+	fn packages(&self,) -> Rslt<Vec<OsoCrate,>,> {
+		PACKAGES
+			.iter()
+			.map(|package| {
+				self.ws.find(package,).ok_or_else(|| {
+					anyhow!("package {package} is not in the workspace")
+				},)
+			},)
+			.collect()
+	}
+
+	/// `cargo build` of `package` located at `path`
+	fn cargo_build(&self, package: &str, path: &Path,) -> Rslt<Command,> {
+		let mut cmd = Command::new("cargo",);
+		cmd.current_dir(path,).arg("build",);
+		if self.opts.build_mode.is_release() {
+			cmd.arg("--release",);
+		}
+		cmd.args(self.opts.feature_args(),);
+
+		let spec = TargetSpec::kernel(self.opts.arch,);
+		if package == "oso_kernel" {
+			let spec_path = spec.install(&self.ws.path(),)?;
+			cmd.args(TargetSpec::cargo_args(&spec_path,),);
+		} else {
+			let target = format!("{}-unknown-uefi", spec.rust_arch());
+			cmd.args(["--target", &target,],);
+		}
+		Ok(cmd,)
+	}
+
+	/// Post-link step applied to the kernel image before it is copied into the
+	/// disk image
+	///
+	/// Checks that boot loaders find the Multiboot2 header when the kernel has
+	/// one, stamps build information into the `.oso_meta` note section, writes
+	/// the symbols into `.oso_symbols` when the kernel reserves it and, for
+	/// release builds, strips symbol tables and debug sections.
+	///
+	/// # Arguments
+	///
+	/// * `kernel` - Path to the linked kernel ELF. The file is rewritten in
+	///   place, unless `--dry-run` is given
+	pub fn post_link(&self, kernel: &Path,) -> Rslt<(),> {
+		if self.opts.dry_run {
+			println!("post link {}", kernel.display());
+			return Ok((),);
+		}
+
+		let mut elf = ElfPatcher::open(kernel,)?;
+		elf.check_multiboot2_header()?;
+
+		let version = env!("CARGO_PKG_VERSION");
+		elf.stamp_meta([
+			("version", version,),
+			("build_mode", self.opts.build_mode.as_ref(),),
+			("arch", self.opts.arch.as_ref(),),
+		],)?;
+
+		// before stripping, so release images resolve their addresses too
+		if let Some(count,) = symbol_map::embed(&mut elf,)? {
+			println!("symbol map: {count} entries");
+		}
+
+		if self.opts.build_mode.is_release() {
+			elf.strip();
+		}
+
+		elf.write_to(kernel,)
+	}
 }
@@ -9,8 +9,12 @@
 //! This crate provides a convenient way to:
 //! - Build the OSO loader (UEFI application) and kernel
 //! - Create and format a disk image
-//! - Mount the disk image and copy the built artifacts
+//! - Copy the built artifacts into the disk image. Unchanged artifacts are
+//!   not copied again, and "image up to date" is reported when nothing
+//!   changed
 //! - Configure and run QEMU with the appropriate firmware and disk image
+//! - Control the VM through QMP. Ctrl-C shuts QEMU down instead of leaving
+//!   it running
 //!
 //! ## Usage
 //!
@@ -22,40 +26,201 @@
 //!
 //! ### Options
 //!
-//! - `-r`, `--release`: Build in release mode (default is debug mode)
-//! - `-86`, `-x86_64`: Build for x86_64 architecture (default is aarch64)
-//! - `--debug`: Enable debug mode with GDB support (listens on port 12345)
+//! Options are shared with other host tools, see `oso_dev_util::cli`.
+//!
+//! - `-a`, `--arch`: Target architecture (default is aarch64)
+//! - `-b`, `--build-mode`: `debug` or `release` (default is debug)
+//! - `-f`, `--feature-flags`: Cargo features to enable, e.g. `-f rgb`. Pixel
+//!   formats `rgb`, `bgr`, `bitmask` and `bltonly` are mutually exclusive
+//! - `-j`, `--jobs`: Number of crates built at once (default is number of
+//!   cpus)
+//! - `-v`, `--verbose` / `-q`, `--quiet`: Amount of output
+//! - `--dry-run`: Print commands instead of executing them
+//! - `--keep-ansi`: Keep ANSI escape sequences in serial logs
+//!
+//! ### Subcommands
+//!
+//! - `run`: Run QEMU interactively (default)
+//! - `boot-test [--script <file>] [--autoexec <file>]`: Boot QEMU and check
+//!   serial output for milestones, e.g. `expect("loader image:", within =
+//!   10s)`. `--autoexec` packs a shell script into the initial ramdisk,
+//!   which the kernel runs at boot echoing each command as
+//!   `osh:<line>> <command>`
+//! - `test <script> [--timeout <seconds>]`: Boot QEMU with a shell script
+//!   as the boot script and exit with its result, which the kernel passes
+//!   through the exit status of QEMU: `0` if every command succeeded, `1` if
+//!   one failed or the script could not be read, `2` if the kernel
+//!   panicked, `3` if QEMU ran longer than the timeout (default 60s) and `4`
+//!   otherwise
+//! - `new-crate <name> --kind no_std|host|proc-macro`: Generate a workspace
+//!   crate with the boilerplate of its kind and add it to workspace members
+//! - `audit`: Check that module files and package names are snake case,
+//!   packages start with `oso_` and crates sit in the group of their kind.
+//!   Violations are printed with fix suggestions
+//! - `graph [--format mermaid|dot] [--output <file>]`: Print the dependency
+//!   chart of workspace crates. Crates are colored by kind and edges to
+//!   proc-macro crates are dashed
+//! - `bench [--save <name>] [--compare <baseline>] [--threshold <percent>]`:
+//!   Run parser benchmarks. Results are stored under `target/xtask/bench`.
+//!   With `--compare`, benchmarks slower than the baseline by more than the
+//!   threshold (default 5%) are reported and fail the run
+//! - `crash decode <file> [--kernel <elf>]`: Pretty-print a kernel crash
+//!   dump, raw or within a serial log, resolving the backtrace with symbols
+//!   of the last built kernel
+//! - `trace <file> [--output <json>] [--frequency <hz>]`: Convert a kernel
+//!   trace dump, raw or within a serial log, into a Chrome trace for
+//!   `about://tracing` or Perfetto. The dump is printed by the kernel shell
+//!   command `trace dump`
+//! - `heap <file> [--output <folded>] [--kernel <elf>]`: Fold the heap
+//!   allocations of a kernel trace dump into stacks weighted by bytes, for
+//!   `inferno-flamegraph` or `flamegraph.pl`. The kernel records them when
+//!   built with `-f alloc_trace` while tracing runs
+//! - `error-codes [--check]`: Write the registry of the codes given to
+//!   error variants by `#[oso_error_code(..)]`, as markdown and as
+//!   `oso_error::registry`. Fails if variants of different enums share a
+//!   code, as does every build. `--check` fails on an outdated registry
+//!   instead of writing it
+//! - `audit-unsafe [--output <file>] [--strict]`: List every unsafe block,
+//!   function, impl and trait with its safety contract, given by
+//!   `#[safety(..)]`, a `// SAFETY:` comment or a `# Safety` section. Sites
+//!   without one are printed, and `--output` writes the whole inventory as
+//!   markdown. `--strict` fails if a site has no contract
+//! - `vendor-specs`: Download the specification pages read by macros, such
+//!   as the UEFI status codes of `status!`, into `specs/`. Macros read them
+//!   instead of the web, and with `OSO_OFFLINE=1` fail rather than download
+//!   a page that is missing
+//! - `dt print <file> [path]`, `dt prop <file> <path> <name>`: Print a
+//!   device tree blob the way the kernel shell command `dt` does, so the
+//!   output can be diffed against a serial log
+//! - `bootinfo <file>`: Pretty-print the boot information the kernel was
+//!   handed, raw or within a serial log. The dump is printed by the kernel
+//!   shell command `bootinfo dump` and after a crash dump
+//! - `screenshot <file> [--output <qoi>] [--golden <qoi>] [--tolerance <n>]
+//!   [--update]`: Extract the QOI screen capture the kernel shell command
+//!   `screenshot` wrote, raw or within a serial log. With `--golden`, fail
+//!   if a color channel of a pixel differs from the golden image by more
+//!   than the tolerance (default 0). `--update` writes the capture as the
+//!   golden image instead
+//! - `sim [<width>x<height>] [--replay <log>]`: Run the kernel consoles and
+//!   debug shell in a window on the host instead of QEMU, see
+//!   `oso_kernel::sim`. Nothing is built for the target. Commands piped into
+//!   stdin are typed into the shell. `--replay` first replays the input and
+//!   timer ticks the kernel shell command `record dump` wrote to the log
+//!
+//! Serial output is logged to `target/xtask/logs/serial-<time>.log`.
 
 use anyhow::Result as Rslt;
 use colored::Colorize;
+use oso_dev_util::cargo::CrashCommand;
+use oso_dev_util::cargo::Task;
 use oso_dev_util_helper::cli::Run;
 use std::process::Command;
-use xtask::builder::Builder;
+use std::process::ExitCode;
+use std::time::Duration;
+use xtask::Xtask;
+use xtask::qemu::TestOutcome;
 
 /// Entry point for the xtask utility.
 ///
-/// Creates a new Builder instance, builds the OSO loader and kernel,
-/// and runs QEMU with the appropriate configuration.
-fn main() -> Rslt<(),> {
-	let builder = Builder::new()?;
+/// Builds the OSO loader and kernel, then runs QEMU interactively, checks
+/// boot milestones or runs a test depending on the subcommand. Exits with
+/// [`exit_code`].
+fn main() -> Rslt<ExitCode,> {
+	let xtask = Xtask::new()?;
 
-	let app = || {
-		builder.build()?;
-		builder.run()
+	let mut outcome = None;
+	let mut app = || {
+		match xtask.task() {
+			Task::NewCrate { name, kind, } => {
+				return xtask.new_crate(name, *kind,);
+			},
+			Task::Audit => return xtask.audit(),
+			Task::Graph { format, output, } => {
+				return xtask.graph(*format, output.as_deref(),);
+			},
+			Task::Bench { save, compare, threshold, } => {
+				let (save, compare,) = (save.as_deref(), compare.as_deref(),);
+				return xtask.bench(save, compare, *threshold,);
+			},
+			Task::Crash {
+				command: CrashCommand::Decode { file, kernel, },
+			} => {
+				return xtask.crash_decode(file, kernel.as_deref(),);
+			},
+			Task::Trace { file, output, frequency, } => {
+				return xtask.trace_export(file, output.as_deref(), *frequency,);
+			},
+			Task::Heap { file, output, kernel, } => {
+				let (output, kernel,) = (output.as_deref(), kernel.as_deref(),);
+				return xtask.heap_profile(file, output, kernel,);
+			},
+			Task::VendorSpecs => return xtask.vendor_specs(),
+			Task::ErrorCodes { check, } => return xtask.error_codes(*check,),
+			Task::AuditUnsafe { output, strict, } => {
+				return xtask.audit_unsafe(output.as_deref(), *strict,);
+			},
+			Task::Dt { command, } => return xtask.dt(command,),
+			Task::Bootinfo { file, } => return xtask.bootinfo(file,),
+			Task::Screenshot { file, output, golden, tolerance, update, } => {
+				return xtask.screenshot(
+					file,
+					output.as_deref(),
+					golden.as_deref(),
+					*tolerance,
+					*update,
+				);
+			},
+			Task::Sim { size, replay, } => {
+				return xtask.sim(size.as_deref(), replay.as_deref(),);
+			},
+			_ => {},
+		}
+		xtask.build()?;
+		match xtask.task() {
+			Task::BootTest { script, .. } => {
+				xtask.boot_test(script.as_deref(),)
+			},
+			Task::Test { timeout, .. } => {
+				outcome = Some(xtask.test(Duration::from_secs(*timeout,),)?,);
+				Ok((),)
+			},
+			_ => xtask.run(),
+		}
 	};
 
-	match app() {
-		Ok(_,) => println!("\n\nprogram run successfully\nexit"),
+	let failed = match app() {
+		Ok(_,) => {
+			println!("\n\nprogram run successfully\nexit");
+			false
+		},
 		Err(e,) => {
 			eprintln!(
 				"{} error msg:\n```rust\n{e:#?}\n```",
 				"program panicked".red().bold()
-			)
+			);
+			true
 		},
-	}
+	};
 
 	print_workspace()?;
-	Ok((),)
+	Ok(ExitCode::from(exit_code(xtask.task(), outcome, failed,),),)
+}
+
+/// Exit code of xtask after `task` ran, `1` if it `failed` and `0`
+/// otherwise. `test` exits with the code of its `outcome` instead
+///
+/// Scripts and CI rely on it, e.g. `error-codes --check`, `audit-unsafe
+/// --strict` and `bench --compare` fail the run through it.
+fn exit_code(task: &Task, outcome: Option<TestOutcome,>, failed: bool,) -> u8 {
+	match outcome {
+		Some(outcome,) => outcome.exit_code(),
+		// `test` failed before QEMU exited, e.g. in the build
+		None if matches!(task, Task::Test { .. }) => {
+			TestOutcome::Unknown(None,).exit_code()
+		},
+		None if failed => 1,
+		None => 0,
+	}
 }
 
 fn print_workspace() -> Rslt<(),> {
@@ -67,3 +232,35 @@ fn print_workspace() -> Rslt<(),> {
 		)
 		.run()
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::path::PathBuf;
+
+	#[test]
+	fn test_failed_tasks_exit_with_failure() {
+		for task in [
+			Task::ErrorCodes { check: true, },
+			Task::Audit,
+			Task::AuditUnsafe { output: None, strict: true, },
+			Task::Bench {
+				save:      None,
+				compare:   Some("main".into(),),
+				threshold: 5,
+			},
+		] {
+			assert_eq!(exit_code(&task, None, true), 1, "{task:?}");
+			assert_eq!(exit_code(&task, None, false), 0, "{task:?}");
+		}
+	}
+
+	#[test]
+	fn test_tests_exit_with_their_outcome() {
+		let test = Task::Test { script: PathBuf::from("t.osh",), timeout: 60, };
+		assert_eq!(exit_code(&test, Some(TestOutcome::Passed), false), 0);
+		assert_eq!(exit_code(&test, Some(TestOutcome::Panicked), false), 2);
+		// the build failed
+		assert_eq!(exit_code(&test, None, true), 4);
+	}
+}
@@ -6,13 +6,53 @@
 //! - Configuring QEMU command-line arguments based on the target architecture
 //! - Managing OVMF firmware files for UEFI boot
 //! - Setting up block devices and persistent flash memory
+//! - Controlling the running VM through [`qmp`], and shutting it down cleanly
+//!   on Ctrl-C
+//! - Reading the result of a test run from the exit status of QEMU, see
+//!   [`TestOutcome`]
 
+use anyhow::Context as _;
 use anyhow::Result as Rslt;
+use anyhow::bail;
+use colored::Colorize;
 use oso_dev_util::cargo::Arch;
+use oso_dev_util::cargo::Task;
+use oso_dev_util::cli::Verbosity;
+use oso_dev_util::decl_manage::crate_::CrateInfo;
+use std::os::unix::net::UnixStream;
 use std::path::Path;
+use std::fmt;
 use std::path::PathBuf;
+use std::process::Child;
+use std::process::Command;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
 
 use crate::Xtask;
+use crate::qemu::qmp::Qmp;
+use crate::qemu::serial::Expectation;
+use crate::qemu::serial::LOG_DIR;
+use crate::qemu::serial::SerialLog;
+
+pub mod qmp;
+pub mod serial;
+
+/// QMP socket path under target/
+const QMP_SOCKET: &str = "xtask/qmp.sock";
+/// Serial port socket path under target/
+const SERIAL_SOCKET: &str = "xtask/serial.sock";
+/// How often sockets of a starting QEMU are tried
+const RETRY_INTERVAL: Duration = Duration::from_millis(100,);
+/// How long QEMU may take to create the QMP socket or answer a command
+const QMP_TIMEOUT: Duration = Duration::from_secs(5,);
+/// How long QEMU may take to exit after `quit` before it is killed
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3,);
+/// How often the VM is checked for exit or Ctrl-C
+const POLL_INTERVAL: Duration = Duration::from_millis(100,);
 
 impl Xtask {
 	/// Gets the QEMU executable name based on the target architecture
@@ -54,12 +94,287 @@ impl Xtask {
 		let block_device = block_device(&self.disk_img_path()?,);
 		args.extend(block_device,);
 
+		// QEMU waits for the serial port to be connected before it starts the
+		// guest, so no output is lost
+		args.push("-serial".to_string(),);
+		args.push(format!(
+			"unix:{},server=on,wait=on",
+			self.target_dir().join(SERIAL_SOCKET,).display()
+		),);
+		args.push("-qmp".to_string(),);
+		args.push(format!(
+			"unix:{},server=on,wait=off",
+			self.qmp_socket_path().display()
+		),);
+
+		// the exit device of `test` on aarch64. riscv64 `virt` has sifive_test
+		if matches!(self.task(), Task::Test { .. })
+			&& self.arch() == Arch::Aarch64
+		{
+			args.push("-semihosting".to_string(),);
+		}
+
 		// setting the boot menu timeout to zero particularly speeds up the boot
 		args.push("-boot".to_string(),);
 		args.push("menu=on,splash-time=0".to_string(),);
 
 		Ok(args,)
 	}
+
+	/// Path of the QMP socket of the VM
+	pub fn qmp_socket_path(&self,) -> PathBuf {
+		self.target_dir().join(QMP_SOCKET,)
+	}
+
+	fn target_dir(&self,) -> PathBuf {
+		self.ws.path().join("target",)
+	}
+
+	/// Starts QEMU, connects to its QMP socket and starts logging its serial
+	/// port under [`LOG_DIR`]
+	pub fn launch(&self,) -> Rslt<Vm,> {
+		let serial_socket = self.target_dir().join(SERIAL_SOCKET,);
+		let qmp_socket = self.qmp_socket_path();
+		// sockets left by a killed QEMU would be connected to
+		let _ = std::fs::remove_file(&serial_socket,);
+		let _ = std::fs::remove_file(&qmp_socket,);
+
+		let child = Command::new(self.qemu(),)
+			.args(self.qemu_args()?,)
+			.spawn()
+			.with_context(|| format!("failed to start {}", self.qemu()),)?;
+		let mut vm = Vm { child, qmp: None, serial: None, };
+
+		let port = connect_socket(&serial_socket, QMP_TIMEOUT,)?;
+		vm.serial = Some(SerialLog::capture(
+			port,
+			&self.target_dir().join(LOG_DIR,),
+			self.opts.keep_ansi,
+			self.opts.verbosity > Verbosity::Quiet,
+		)?,);
+		vm.qmp = Some(Qmp::connect(&qmp_socket, QMP_TIMEOUT,)?,);
+		Ok(vm,)
+	}
+
+	/// Boots the VM and checks that serial output reaches each milestone
+	///
+	/// # Arguments
+	///
+	/// * `script` - Milestones in the language of [`serial`]. Built-in
+	///   milestones are checked if `None`
+	pub fn boot_test(&self, script: Option<&Path,>,) -> Rslt<(),> {
+		let script = match script {
+			Some(path,) => std::fs::read_to_string(path,).with_context(|| {
+				format!("failed to read {}", path.display())
+			},)?,
+			None => serial::BOOT_MILESTONES.to_string(),
+		};
+		let milestones = Expectation::parse_script(&script,)?;
+		if self.opts.dry_run {
+			println!("{} {}", self.qemu(), self.qemu_args()?.join(" "));
+			return Ok((),);
+		}
+
+		let mut vm = self.launch()?;
+		let start = Instant::now();
+		for milestone in &milestones {
+			vm.serial().expect(milestone,)?;
+			println!(
+				"{} {} ({:.1?})",
+				"reached".green().bold(),
+				milestone.pattern,
+				start.elapsed()
+			);
+		}
+		println!("serial log: {}", vm.serial().path().display());
+		vm.shutdown()
+	}
+
+	/// Boots the VM and waits until the kernel exits QEMU with the result of
+	/// the boot script
+	///
+	/// The kernel runs the script of `test` and exits through the exit
+	/// device of the architecture, see `oso_kernel::driver::qemu_exit`.
+	/// QEMU is shut down if it runs longer than `timeout`.
+	pub fn test(&self, timeout: Duration,) -> Rslt<TestOutcome,> {
+		if self.opts.dry_run {
+			println!("{} {}", self.qemu(), self.qemu_args()?.join(" "));
+			return Ok(TestOutcome::Passed,);
+		}
+
+		let mut vm = self.launch()?;
+		let start = Instant::now();
+		let outcome = loop {
+			if let Some(status,) = vm.child.try_wait()? {
+				break TestOutcome::from_code(status.code(),);
+			}
+			if start.elapsed() >= timeout {
+				vm.shutdown()?;
+				break TestOutcome::TimedOut;
+			}
+			thread::sleep(POLL_INTERVAL,);
+		};
+		println!("{outcome} ({:.1?})", start.elapsed());
+		println!("serial log: {}", vm.serial().path().display());
+		Ok(outcome,)
+	}
+
+	/// Runs the VM until it exits or Ctrl-C is pressed
+	///
+	/// On Ctrl-C, QEMU is asked to quit through QMP and killed if it doesn't
+	/// exit in time, so no QEMU process is left behind.
+	pub fn run(&self,) -> Rslt<(),> {
+		if self.opts.dry_run {
+			println!("{} {}", self.qemu(), self.qemu_args()?.join(" "));
+			return Ok((),);
+		}
+
+		let interrupted = Arc::new(AtomicBool::new(false,),);
+		let flag = interrupted.clone();
+		ctrlc::try_set_handler(move || flag.store(true, Ordering::Relaxed,),)
+			.context("failed to install Ctrl-C handler",)?;
+
+		let mut vm = self.launch()?;
+		loop {
+			if let Some(status,) = vm.child.try_wait()? {
+				if !status.success() {
+					bail!("QEMU exited with {status}");
+				}
+				return Ok((),);
+			}
+			if interrupted.load(Ordering::Relaxed,) {
+				return vm.shutdown();
+			}
+			thread::sleep(POLL_INTERVAL,);
+		}
+	}
+}
+
+/// Result of `test`, read from the exit status of QEMU
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum TestOutcome {
+	/// Every command of the script succeeded
+	Passed,
+	/// A command of the script failed
+	Failed,
+	/// The kernel panicked
+	Panicked,
+	/// QEMU did not exit in time
+	TimedOut,
+	/// QEMU exited with a status the kernel does not use, e.g. killed by a
+	/// signal
+	Unknown(Option<i32,>,),
+}
+
+impl TestOutcome {
+	/// Outcome of the exit status `code` of QEMU, which is the code the
+	/// kernel passed to `qemu_exit` on aarch64 and riscv64
+	pub fn from_code(code: Option<i32,>,) -> Self {
+		match code {
+			Some(0,) => Self::Passed,
+			Some(1,) => Self::Failed,
+			Some(2,) => Self::Panicked,
+			code => Self::Unknown(code,),
+		}
+	}
+
+	/// Exit code of xtask. The codes of the kernel are kept
+	pub fn exit_code(self,) -> u8 {
+		match self {
+			Self::Passed => 0,
+			Self::Failed => 1,
+			Self::Panicked => 2,
+			Self::TimedOut => 3,
+			Self::Unknown(_,) => 4,
+		}
+	}
+}
+
+impl fmt::Display for TestOutcome {
+	fn fmt(&self, f: &mut fmt::Formatter<'_,>,) -> fmt::Result {
+		match self {
+			Self::Passed => write!(f, "{}", "passed".green().bold()),
+			Self::Failed => write!(f, "{}", "failed".red().bold()),
+			Self::Panicked => write!(f, "{}", "kernel panicked".red().bold()),
+			Self::TimedOut => write!(f, "{}", "timed out".red().bold()),
+			Self::Unknown(Some(code,),) => {
+				write!(f, "{} {code}", "QEMU exited with".red().bold())
+			},
+			Self::Unknown(None,) => {
+				write!(f, "{}", "QEMU was killed".red().bold())
+			},
+		}
+	}
+}
+
+/// Running QEMU process. Killed when dropped
+pub struct Vm {
+	child:  Child,
+	qmp:    Option<Qmp,>,
+	serial: Option<SerialLog,>,
+}
+
+impl Vm {
+	/// QMP connection of the VM
+	pub fn qmp(&mut self,) -> &mut Qmp {
+		self.qmp.as_mut().expect("connected on launch",)
+	}
+
+	/// Serial output of the VM
+	pub fn serial(&mut self,) -> &mut SerialLog {
+		self.serial.as_mut().expect("connected on launch",)
+	}
+
+	/// Asks QEMU to quit, then kills it if it is still running after
+	/// [`SHUTDOWN_TIMEOUT`]
+	pub fn shutdown(&mut self,) -> Rslt<(),> {
+		// QEMU may already be exiting because it received Ctrl-C as well
+		if let Some(qmp,) = self.qmp.as_mut() {
+			let _ = qmp.quit();
+		}
+
+		let start = Instant::now();
+		while start.elapsed() < SHUTDOWN_TIMEOUT {
+			if self.child.try_wait()?.is_some() {
+				return Ok((),);
+			}
+			thread::sleep(POLL_INTERVAL,);
+		}
+		self.child.kill()?;
+		self.child.wait()?;
+		Ok((),)
+	}
+}
+
+impl Drop for Vm {
+	fn drop(&mut self,) {
+		if let Ok(None,) = self.child.try_wait() {
+			let _ = self.child.kill();
+			let _ = self.child.wait();
+		}
+	}
+}
+
+/// Connects to the unix socket at `path`, retrying until `timeout` elapses
+/// as QEMU creates it shortly after it starts
+pub(crate) fn connect_socket(
+	path: &Path,
+	timeout: Duration,
+) -> Rslt<UnixStream,> {
+	let start = Instant::now();
+	loop {
+		match UnixStream::connect(path,) {
+			Ok(stream,) => return Ok(stream,),
+			Err(_,) if start.elapsed() < timeout => {
+				thread::sleep(RETRY_INTERVAL,)
+			},
+			Err(e,) => {
+				return Err(e,).with_context(|| {
+					format!("failed to connect to {}", path.display())
+				},);
+			},
+		}
+	}
 }
 
 /// Manages OVMF firmware files for UEFI boot
@@ -104,21 +419,15 @@ fn basic_args(arch: Arch,) -> Vec<String,> {
 			// "ramfb".to_string(),
 		],
 		Arch::Riscv64 => todo!(),
-		// Architecture::X86_64 => {
-		// 	vec![
-		// 		"-machine".to_string(),
-		// 		"q35".to_string(),
-		// 		"-smp".to_string(),
-		// 		"4".to_string(),
-		// 		// allocate some memory
-		// 		// "-m".to_string(),
-		// 		// "256M".to_string(),
-		//
-		// 		// graphics device
-		// 		"-vga".to_string(),
-		// 		"std".to_string(),
-		// 	]
-		// },
+		Arch::X86_64 => vec![
+			"-machine".to_string(),
+			"q35".to_string(),
+			"-smp".to_string(),
+			"4".to_string(),
+			// graphics device
+			"-vga".to_string(),
+			"std".to_string(),
+		],
 	}
 }
 
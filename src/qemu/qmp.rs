@@ -0,0 +1,175 @@
+//! # QMP Client
+//!
+//! Controls a running QEMU through the QEMU Machine Protocol: JSON messages
+//! over a unix socket, enabled by `-qmp unix:<path>,server=on,wait=off`.
+//!
+//! Used to query the VM state, dump the framebuffer for visual regression
+//! tests, read guest memory, inject NMIs and resets, and shut QEMU down
+//! without leaving it running in the background.
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use xtask::qemu::qmp::Qmp;
+//!
+//! let timeout = Duration::from_secs(5,);
+//! let mut qmp = Qmp::connect("target/xtask/qmp.sock", timeout,)?;
+//! println!("{:?}", qmp.status()?);
+//! qmp.screenshot("boot.png".as_ref(),)?;
+//! qmp.quit()?;
+//! # anyhow::Ok(())
+//! ```
+
+use anyhow::Context as _;
+use anyhow::Result as Rslt;
+use anyhow::bail;
+use serde_json::Value;
+use serde_json::json;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::qemu::connect_socket;
+
+/// State reported by `query-status`
+///
+/// # Fields
+///
+/// * `running` - Whether vCPUs are executing
+/// * `status` - Run state, e.g. `running`, `paused` or `shutdown`
+#[derive(Clone, Debug, PartialEq, Eq,)]
+pub struct VmStatus {
+	pub running: bool,
+	pub status:  String,
+}
+
+/// Connection to the QMP socket of a QEMU instance
+pub struct Qmp {
+	reader: BufReader<UnixStream,>,
+	writer: UnixStream,
+	/// Asynchronous events received while waiting for command replies
+	events: Vec<Value,>,
+}
+
+impl Qmp {
+	/// Connects to the socket at `path` and negotiates capabilities
+	///
+	/// QEMU creates the socket shortly after it starts, so connecting is
+	/// retried until `timeout` elapses.
+	pub fn connect(path: impl AsRef<Path,>, timeout: Duration,) -> Rslt<Self,> {
+		let stream = connect_socket(path.as_ref(), timeout,)?;
+		stream.set_read_timeout(Some(timeout,),)?;
+
+		let mut qmp = Self {
+			reader: BufReader::new(stream.try_clone()?,),
+			writer: stream,
+			events: vec![],
+		};
+		let greeting = qmp.read_message()?;
+		if greeting.get("QMP",).is_none() {
+			bail!("unexpected greeting from QEMU: {greeting}");
+		}
+		qmp.execute("qmp_capabilities", None,)?;
+		Ok(qmp,)
+	}
+
+	/// Runs `command` and returns its `return` value
+	///
+	/// # Errors
+	///
+	/// Returns QEMU's error description if the command fails
+	pub fn execute(
+		&mut self,
+		command: &str,
+		arguments: Option<Value,>,
+	) -> Rslt<Value,> {
+		let mut request = json!({ "execute": command });
+		if let Some(arguments,) = arguments {
+			request["arguments"] = arguments;
+		}
+		writeln!(self.writer, "{request}")?;
+
+		loop {
+			let mut reply = self.read_message()?;
+			if reply.get("event",).is_some() {
+				self.events.push(reply,);
+				continue;
+			}
+			if let Some(error,) = reply.get("error",) {
+				bail!(
+					"{command} failed: {}",
+					error["desc"].as_str().unwrap_or("unknown error")
+				);
+			}
+			return match reply.get_mut("return",) {
+				Some(value,) => Ok(value.take(),),
+				None => bail!("unexpected reply to {command}: {reply}"),
+			};
+		}
+	}
+
+	pub fn status(&mut self,) -> Rslt<VmStatus,> {
+		let status = self.execute("query-status", None,)?;
+		Ok(VmStatus {
+			running: status["running"].as_bool().unwrap_or_default(),
+			status:  status["status"].as_str().unwrap_or_default().to_string(),
+		},)
+	}
+
+	/// Saves the framebuffer of the primary display to `path` as PNG
+	///
+	/// `path` is opened by QEMU, so it should be absolute.
+	pub fn screenshot(&mut self, path: &Path,) -> Rslt<(),> {
+		let args = json!({ "filename": path, "format": "png" });
+		self.execute("screendump", Some(args,),)?;
+		Ok((),)
+	}
+
+	/// Reads `len` bytes of guest physical memory at `addr`
+	///
+	/// QEMU writes the memory to a file which is read and removed afterwards.
+	pub fn read_memory(&mut self, addr: u64, len: usize,) -> Rslt<Vec<u8,>,> {
+		let dump = std::env::temp_dir()
+			.join(format!("oso_qmp_{}_{addr:x}.bin", std::process::id()),);
+		let args = json!({ "val": addr, "size": len, "filename": dump });
+		self.execute("pmemsave", Some(args,),)?;
+		let data = std::fs::read(&dump,)
+			.with_context(|| format!("failed to read {}", dump.display()),);
+		let _ = std::fs::remove_file(&dump,);
+		data
+	}
+
+	/// Injects a non-maskable interrupt into every vCPU
+	pub fn nmi(&mut self,) -> Rslt<(),> {
+		self.execute("inject-nmi", None,)?;
+		Ok((),)
+	}
+
+	/// Resets the VM as if the reset button was pressed
+	pub fn reset(&mut self,) -> Rslt<(),> {
+		self.execute("system_reset", None,)?;
+		Ok((),)
+	}
+
+	/// Terminates QEMU immediately
+	pub fn quit(&mut self,) -> Rslt<(),> {
+		self.execute("quit", None,)?;
+		Ok((),)
+	}
+
+	/// Takes events received so far, e.g. `SHUTDOWN` or `RESET`
+	pub fn take_events(&mut self,) -> Vec<Value,> {
+		std::mem::take(&mut self.events,)
+	}
+
+	fn read_message(&mut self,) -> Rslt<Value,> {
+		let mut line = String::new();
+		if self.reader.read_line(&mut line,)? == 0 {
+			bail!("QEMU closed the QMP connection");
+		}
+		serde_json::from_str(&line,)
+			.with_context(|| format!("invalid QMP message: {line}"),)
+	}
+}
@@ -0,0 +1,304 @@
+//! # Serial Console Capture
+//!
+//! Records the guest serial port to a timestamped log file under
+//! [`LOG_DIR`] and lets the boot test wait for milestones in the output.
+//!
+//! Milestones are written in a small assertion language, one per line:
+//!
+//! ```text
+//! # comments and blank lines are ignored
+//! expect("loader image:", within = 10s)
+//! expect("kernel: console up", within = 500ms)
+//! expect("idle")
+//! ```
+//!
+//! Each expectation must be met after the previous one. `within` accepts
+//! `ms`, `s` and `m` and defaults to [`DEFAULT_WITHIN`].
+
+use anyhow::Context as _;
+use anyhow::Result as Rslt;
+use anyhow::bail;
+use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+
+/// Log directory under target/
+pub const LOG_DIR: &str = "xtask/logs";
+/// Timeout of expectations without `within`
+pub const DEFAULT_WITHIN: Duration = Duration::from_secs(10,);
+/// Milestones checked by the boot test when no script is given
+pub const BOOT_MILESTONES: &str = r#"
+expect("loader image:", within = 30s)
+"#;
+
+/// Lines received so far and whether the guest closed the port
+#[derive(Default,)]
+struct Received {
+	lines:  Vec<String,>,
+	closed: bool,
+}
+
+/// Serial output being written to a log file
+pub struct SerialLog {
+	path:     PathBuf,
+	received: Arc<(Mutex<Received,>, Condvar,),>,
+	/// Index of the first line not yet matched by [`SerialLog::expect`]
+	cursor:   usize,
+}
+
+impl SerialLog {
+	/// Starts copying `port` to a new log file in `dir`
+	///
+	/// # Arguments
+	///
+	/// * `keep_ansi` - Write ANSI escape sequences to the log as received.
+	///   Matching always ignores them
+	/// * `echo` - Also print output to stdout
+	pub fn capture(
+		port: impl Read + Send + 'static,
+		dir: &Path,
+		keep_ansi: bool,
+		echo: bool,
+	) -> Rslt<Self,> {
+		std::fs::create_dir_all(dir,)
+			.with_context(|| format!("failed to create {}", dir.display()),)?;
+		let path = dir.join(format!("serial-{}.log", timestamp()),);
+		let mut file = File::create(&path,)
+			.with_context(|| format!("failed to create {}", path.display()),)?;
+
+		let received =
+			Arc::new((Mutex::new(Received::default(),), Condvar::new(),),);
+		let shared = received.clone();
+		thread::spawn(move || {
+			let mut reader = BufReader::new(port,);
+			let mut raw = vec![];
+			while reader.read_until(b'\n', &mut raw,).is_ok_and(|n| n > 0,) {
+				let line = String::from_utf8_lossy(&raw,).into_owned();
+				let plain = strip_ansi(&line,);
+				if echo {
+					print!("{line}");
+				}
+				let logged = if keep_ansi { &line } else { &plain };
+				let _ = file.write_all(logged.as_bytes(),);
+
+				let (lock, cvar,) = &*shared;
+				lock.lock().unwrap().lines.push(plain.trim_end().to_string(),);
+				cvar.notify_all();
+				raw.clear();
+			}
+			let (lock, cvar,) = &*shared;
+			lock.lock().unwrap().closed = true;
+			cvar.notify_all();
+		},);
+
+		Ok(Self { path, received, cursor: 0, },)
+	}
+
+	/// Path of the log file
+	pub fn path(&self,) -> &Path {
+		&self.path
+	}
+
+	/// Waits for a line containing `expected.pattern`, received after the
+	/// line matched by the previous call
+	///
+	/// # Returns
+	///
+	/// The matching line
+	///
+	/// # Errors
+	///
+	/// Returns an error if no line matches within `expected.within`, or the
+	/// guest closes the serial port first
+	pub fn expect(&mut self, expected: &Expectation,) -> Rslt<String,> {
+		let deadline = Instant::now() + expected.within;
+		let (lock, cvar,) = &*self.received;
+		let mut received = lock.lock().unwrap();
+		loop {
+			let found = received.lines[self.cursor..]
+				.iter()
+				.position(|line| line.contains(&expected.pattern,),);
+			if let Some(i,) = found {
+				self.cursor += i + 1;
+				return Ok(received.lines[self.cursor - 1].clone(),);
+			}
+			self.cursor = received.lines.len();
+
+			let now = Instant::now();
+			if received.closed {
+				bail!("serial port closed before `{}`", expected.pattern);
+			}
+			if now >= deadline {
+				bail!(
+					"`{}` not seen within {:?}, see {}",
+					expected.pattern,
+					expected.within,
+					self.path.display()
+				);
+			}
+			received = cvar.wait_timeout(received, deadline - now,).unwrap().0;
+		}
+	}
+}
+
+/// Milestone of serial output, e.g. `expect("console up", within = 10s)`
+#[derive(Clone, Debug, PartialEq, Eq,)]
+pub struct Expectation {
+	pub pattern: String,
+	pub within:  Duration,
+}
+
+impl Expectation {
+	/// Parses a milestone script. See the [module documentation](self)
+	pub fn parse_script(script: &str,) -> Rslt<Vec<Self,>,> {
+		script
+			.lines()
+			.enumerate()
+			.map(|(i, line,)| (i + 1, line.trim(),),)
+			.filter(|(_, line,)| !line.is_empty() && !line.starts_with('#',),)
+			.map(|(n, line,)| {
+				line.parse().with_context(|| format!("line {n}: `{line}`"),)
+			},)
+			.collect()
+	}
+}
+
+impl FromStr for Expectation {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str,) -> Result<Self, Self::Err,> {
+		let Some(args,) = s
+			.trim()
+			.strip_prefix("expect(",)
+			.and_then(|s| s.strip_suffix(')',),)
+		else {
+			bail!("expected `expect(\"...\", within = <duration>)`");
+		};
+		let (pattern, rest,) = parse_quoted(args.trim_start(),)?;
+
+		let rest = rest.trim();
+		let within = if rest.is_empty() {
+			DEFAULT_WITHIN
+		} else {
+			let Some(value,) = rest
+				.strip_prefix(',',)
+				.and_then(|r| r.trim().strip_prefix("within",),)
+				.and_then(|r| r.trim().strip_prefix('=',),)
+			else {
+				bail!("expected `, within = <duration>` after pattern");
+			};
+			parse_duration(value.trim(),)?
+		};
+		Ok(Self { pattern, within, },)
+	}
+}
+
+/// Splits `"..."` at the head of `s` into unescaped content and the rest
+fn parse_quoted(s: &str,) -> Rslt<(String, &str,),> {
+	let Some(body,) = s.strip_prefix('"',) else {
+		bail!("pattern must be a quoted string");
+	};
+	let mut content = String::new();
+	let mut chars = body.char_indices();
+	while let Some((i, c,),) = chars.next() {
+		match c {
+			'"' => return Ok((content, &body[i + 1..],),),
+			'\\' => match chars.next() {
+				Some((_, c,),) => content.push(c,),
+				None => break,
+			},
+			c => content.push(c,),
+		}
+	}
+	bail!("unterminated string")
+}
+
+/// Parses `500ms`, `10s` or `2m`
+fn parse_duration(s: &str,) -> Rslt<Duration,> {
+	let split = s.find(|c: char| !c.is_ascii_digit(),).unwrap_or(s.len(),);
+	let (value, unit,) = s.split_at(split,);
+	let value: u64 =
+		value.parse().with_context(|| format!("invalid duration `{s}`"),)?;
+	match unit {
+		"ms" => Ok(Duration::from_millis(value,),),
+		"s" => Ok(Duration::from_secs(value,),),
+		"m" => Ok(Duration::from_secs(value * 60,),),
+		_ => bail!("unknown unit of duration `{s}`. use ms, s or m"),
+	}
+}
+
+/// Removes ANSI escape sequences and carriage returns from `s`
+pub fn strip_ansi(s: &str,) -> String {
+	let mut out = String::with_capacity(s.len(),);
+	let mut chars = s.chars().peekable();
+	while let Some(c,) = chars.next() {
+		match c {
+			'\x1b' => match chars.next() {
+				// CSI: parameters then a final byte in @..~
+				Some('[',) => {
+					for c in chars.by_ref() {
+						if ('@'..='~').contains(&c,) {
+							break;
+						}
+					}
+				},
+				// OSC: terminated by BEL or ESC \
+				Some(']',) => {
+					while let Some(c,) = chars.next() {
+						if c == '\x07' {
+							break;
+						}
+						if c == '\x1b' && chars.peek() == Some(&'\\',) {
+							chars.next();
+							break;
+						}
+					}
+				},
+				// two character sequences
+				_ => {},
+			},
+			'\r' => {},
+			c => out.push(c,),
+		}
+	}
+	out
+}
+
+/// Current UTC time as `YYYYMMDD-HHMMSS`
+fn timestamp() -> String {
+	let secs = SystemTime::now()
+		.duration_since(SystemTime::UNIX_EPOCH,)
+		.unwrap_or_default()
+		.as_secs() as i64;
+	let (days, rem,) = (secs.div_euclid(86400,), secs.rem_euclid(86400,),);
+
+	// civil date from days since 1970-01-01
+	let z = days + 719468;
+	let era = z.div_euclid(146097,);
+	let doe = z.rem_euclid(146097,);
+	let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let day = doy - (153 * mp + 2) / 5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+	let year = yoe + era * 400 + (month <= 2) as i64;
+
+	format!(
+		"{year:04}{month:02}{day:02}-{:02}{:02}{:02}",
+		rem / 3600,
+		rem % 3600 / 60,
+		rem % 60
+	)
+}